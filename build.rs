@@ -0,0 +1,11 @@
+// Alias of the targets with an operating system, which provide the file system, the threads and
+// the clock that wasm32-unknown-unknown lacks
+fn main() {
+    println!("cargo::rerun-if-changed=build.rs");
+    println!("cargo::rustc-check-cfg=cfg(hosted)");
+    let arch = std::env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    let os = std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    if !(arch == "wasm32" && os == "unknown") {
+        println!("cargo::rustc-cfg=hosted");
+    }
+}
@@ -0,0 +1,7 @@
+use pyo3::prelude::*;
+
+/// Python module of the meshes, function spaces, operators, solvers and workflows of fe2o3
+#[pymodule]
+fn fe2o3(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    fe2o3_lib::python::register(module)
+}
@@ -17,6 +17,18 @@ use std::ops::{Deref, DerefMut};
 /// Please see documentation of DataView for layout details.
 pub struct DataHold<DataType: Clone, DimType: AsRef<[usize]>>(Vec<DataType>, DimType);
 
+impl<DataType: Clone, DimType: AsRef<[usize]>> DataHold<DataType, DimType> {
+    /// Take ownership of some data and interpret it with the given dimensions
+    pub fn new(data: Vec<DataType>, dimensions: DimType) -> Self {
+        let tot_comps: usize = dimensions.as_ref().iter().product();
+        assert!(
+            tot_comps == data.len(),
+            "Tried to build a DataHold with dimensions uncompatible with the data"
+        );
+        DataHold(data, dimensions)
+    }
+}
+
 // Make the DataHold behave like a &[DataType]
 impl<DataType: Clone, DimType: AsRef<[usize]>> Deref for DataHold<DataType, DimType> {
     type Target = [DataType];
@@ -224,9 +236,23 @@ mod tests {
         let mut hold: DataHold<i32, Vec<usize>> = DataHold(vec![], vec![]);
         hold.resize(vec![6, 3, 5], 0);
         assert_eq!(hold.len(), 6*3*5, "Did not resize data correctly");
-        let dims = vec![6,3,5];
+        let dims = [6,3,5];
         for (iv, it) in zip(hold.dimensions().iter(), dims.iter()) {
             assert_eq!(iv, it, "Did not set dimensions correctly during resize");
         }
     }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_data_hold_new() {
+        let hold = DataHold::new(vec![0, 1, 2, 3, 4, 5], [2, 3]);
+        assert_eq!(hold.multi_index([1, 0]), &3, "new did not keep the given layout");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    #[should_panic]
+    fn test_data_hold_bad_new() {
+        DataHold::new(vec![0, 1, 2, 3, 4, 5], [4, 2]);
+    }
 }
@@ -15,6 +15,18 @@ use super::data_traits::{DataContainer, DataMutator};
 /// Please see documentation of DataView for layout details.
 pub struct DataWrap<'a, DataType, DimType: AsRef<[usize]>>(&'a mut [DataType], DimType);
 
+impl<'a, DataType, DimType: AsRef<[usize]>> DataWrap<'a, DataType, DimType> {
+    /// Wrap some mutable data with the given dimensions
+    pub fn new(data: &'a mut [DataType], dimensions: DimType) -> Self {
+        let tot_comps: usize = dimensions.as_ref().iter().product();
+        assert!(
+            tot_comps == data.len(),
+            "Tried to build a DataWrap with dimensions uncompatible with the data"
+        );
+        DataWrap(data, dimensions)
+    }
+}
+
 // Make the DataWrap behave like a &[DataType]
 impl<'a, DataType, DimType: AsRef<[usize]>> Deref for DataWrap<'a, DataType, DimType> {
     type Target = [DataType];
@@ -81,4 +93,24 @@ mod tests {
             assert_eq!(*it, iv, "Changes in mutable iterator were unsuccessful");
         }
     }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_data_wrap_new() {
+        let mut base = vec![0, 1, 2, 3, 4, 5];
+        let wrap = DataWrap::new(&mut base, [3, 2]);
+        assert_eq!(
+            wrap.multi_index([2, 1]),
+            &5,
+            "new did not keep the given layout"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    #[should_panic]
+    fn test_data_wrap_bad_new() {
+        let mut base = vec![0, 1, 2, 3, 4, 5];
+        DataWrap::new(&mut base, [4, 2]);
+    }
 }
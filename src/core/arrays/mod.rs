@@ -5,3 +5,5 @@ pub mod data_view;
 pub mod data_wrap;
 
 pub mod data_hold;
+
+pub mod sparse_csr;
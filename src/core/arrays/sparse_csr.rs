@@ -0,0 +1,391 @@
+#[cfg(feature = "parallel")]
+use crate::parallel;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::clone::Clone;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Utility structure for storing sparse two dimensional data in the compressed sparse row format
+///
+/// The non zero entries of row `i` are the `values[row_offsets[i]..row_offsets[i + 1]]` and their
+/// column indices are stored at the same positions in `col_indices`. Column indices are kept sorted
+/// within each row so that entries can be found with a binary search.
+#[derive(Clone)]
+pub struct SparseCSR<DataType: Clone> {
+    n_cols: usize,
+    row_offsets: Vec<usize>,
+    col_indices: Vec<usize>,
+    values: Vec<DataType>,
+}
+
+impl<DataType: Clone> SparseCSR<DataType> {
+    /// Build a matrix from its raw CSR arrays
+    pub fn new(
+        n_cols: usize,
+        row_offsets: Vec<usize>,
+        col_indices: Vec<usize>,
+        values: Vec<DataType>,
+    ) -> Self {
+        assert!(
+            !row_offsets.is_empty() && row_offsets[0] == 0,
+            "Row offsets of a SparseCSR should start with a 0"
+        );
+        assert!(
+            row_offsets.windows(2).all(|w| w[0] <= w[1]),
+            "Row offsets of a SparseCSR should be non decreasing"
+        );
+        assert!(
+            *row_offsets.last().unwrap() == col_indices.len() && col_indices.len() == values.len(),
+            "Sizes of the column indices and values do not match the row offsets"
+        );
+        for row in row_offsets.windows(2) {
+            let cols = &col_indices[row[0]..row[1]];
+            assert!(
+                cols.windows(2).all(|w| w[0] < w[1]),
+                "Column indices of a SparseCSR row should be strictly increasing"
+            );
+            assert!(
+                cols.iter().all(|col| *col < n_cols),
+                "Column index out of the bounds of the SparseCSR"
+            );
+        }
+        SparseCSR {
+            n_cols,
+            row_offsets,
+            col_indices,
+            values,
+        }
+    }
+
+    /// Number of rows in the matrix
+    pub fn n_rows(&self) -> usize {
+        self.row_offsets.len() - 1
+    }
+
+    /// Number of columns in the matrix
+    pub fn n_cols(&self) -> usize {
+        self.n_cols
+    }
+
+    /// Number of stored entries
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Offsets of each row in the column indices and values arrays
+    pub fn row_offsets(&self) -> &[usize] {
+        &self.row_offsets
+    }
+
+    /// Column indices of the stored entries
+    pub fn col_indices(&self) -> &[usize] {
+        &self.col_indices
+    }
+
+    /// Stored entries
+    pub fn values(&self) -> &[DataType] {
+        &self.values
+    }
+
+    /// Mutable access to the stored entries
+    pub fn values_mut(&mut self) -> &mut [DataType] {
+        &mut self.values
+    }
+
+    /// Get the column indices and values of a row
+    pub fn row(&self, row: usize) -> (&[usize], &[DataType]) {
+        let (start, end) = (self.row_offsets[row], self.row_offsets[row + 1]);
+        (&self.col_indices[start..end], &self.values[start..end])
+    }
+
+    /// Position of entry (row, col) in the values array if it is stored
+    pub fn position(&self, row: usize, col: usize) -> Option<usize> {
+        let start = self.row_offsets[row];
+        let end = self.row_offsets[row + 1];
+        self.col_indices[start..end]
+            .binary_search(&col)
+            .ok()
+            .map(|pos| start + pos)
+    }
+
+    /// Get a stored entry
+    pub fn get(&self, row: usize, col: usize) -> Option<&DataType> {
+        self.position(row, col).map(|pos| &self.values[pos])
+    }
+
+    /// Get a mutable reference to a stored entry
+    pub fn get_mut(&mut self, row: usize, col: usize) -> Option<&mut DataType> {
+        self.position(row, col).map(|pos| &mut self.values[pos])
+    }
+
+    /// Set all the stored entries to the same value keeping the structure untouched
+    pub fn fill(&mut self, value: DataType) {
+        for val in self.values.iter_mut() {
+            *val = value.clone();
+        }
+    }
+
+    /// Transposed matrix
+    pub fn transpose(&self) -> SparseCSR<DataType> {
+        let mut row_offsets = vec![0; self.n_cols + 1];
+        for col in self.col_indices.iter() {
+            row_offsets[col + 1] += 1;
+        }
+        for i in 0..self.n_cols {
+            row_offsets[i + 1] += row_offsets[i];
+        }
+        // Rows are visited in order so the transposed columns come out sorted
+        let mut next = row_offsets.clone();
+        let mut positions = vec![0; self.nnz()];
+        let mut col_indices = vec![0; self.nnz()];
+        for row in 0..self.n_rows() {
+            for k in self.row_offsets[row]..self.row_offsets[row + 1] {
+                let col = self.col_indices[k];
+                positions[next[col]] = k;
+                col_indices[next[col]] = row;
+                next[col] += 1;
+            }
+        }
+        let values = positions.iter().map(|k| self.values[*k].clone()).collect();
+        SparseCSR {
+            n_cols: self.n_rows(),
+            row_offsets,
+            col_indices,
+            values,
+        }
+    }
+}
+
+impl SparseCSR<f64> {
+    /// Compute the matrix vector product y = A x
+    ///
+    /// With the parallel feature, the rows of large matrices are partitioned between the threads
+    /// of the parallel configuration.
+    pub fn apply(&self, x: &[f64], y: &mut [f64]) {
+        assert!(
+            x.len() == self.n_cols && y.len() == self.n_rows(),
+            "Vectors do not match the size of the matrix"
+        );
+        let row_product = |row: usize| -> f64 {
+            let (cols, vals) = self.row(row);
+            cols.iter().zip(vals.iter()).map(|(c, v)| v * x[*c]).sum()
+        };
+        #[cfg(feature = "parallel")]
+        if let Some(chunk) = parallel::row_chunk(y.len()) {
+            parallel::install(|| {
+                y.par_iter_mut()
+                    .enumerate()
+                    .with_min_len(chunk)
+                    .for_each(|(row, y_row)| *y_row = row_product(row))
+            });
+            return;
+        }
+        for (row, y_row) in y.iter_mut().enumerate() {
+            *y_row = row_product(row);
+        }
+    }
+
+    /// Compute the sparse matrix sum A + scale B
+    pub fn add(&self, scale: f64, other: &SparseCSR<f64>) -> SparseCSR<f64> {
+        assert!(
+            self.n_rows() == other.n_rows() && self.n_cols == other.n_cols,
+            "Matrix sizes do not match for the sum"
+        );
+        let mut row_offsets = vec![0];
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+        for row in 0..self.n_rows() {
+            // Merge of the two sorted rows
+            let (cols, vals) = self.row(row);
+            let (other_cols, other_vals) = other.row(row);
+            let (mut i, mut j) = (0, 0);
+            while i < cols.len() || j < other_cols.len() {
+                if j == other_cols.len() || (i < cols.len() && cols[i] < other_cols[j]) {
+                    col_indices.push(cols[i]);
+                    values.push(vals[i]);
+                    i += 1;
+                } else if i == cols.len() || other_cols[j] < cols[i] {
+                    col_indices.push(other_cols[j]);
+                    values.push(scale * other_vals[j]);
+                    j += 1;
+                } else {
+                    col_indices.push(cols[i]);
+                    values.push(vals[i] + scale * other_vals[j]);
+                    i += 1;
+                    j += 1;
+                }
+            }
+            row_offsets.push(col_indices.len());
+        }
+        SparseCSR {
+            n_cols: self.n_cols,
+            row_offsets,
+            col_indices,
+            values,
+        }
+    }
+
+    /// Compute the sparse matrix product A B
+    pub fn multiply(&self, other: &SparseCSR<f64>) -> SparseCSR<f64> {
+        assert_eq!(
+            self.n_cols,
+            other.n_rows(),
+            "Matrix sizes do not match for the product"
+        );
+        let mut row_offsets = vec![0];
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+        // Dense accumulator over the columns of the result with the cols touched by the row
+        let mut accumulator = vec![0.0; other.n_cols];
+        let mut touched = vec![false; other.n_cols];
+        let mut row_cols = Vec::new();
+        for row in 0..self.n_rows() {
+            let (cols, vals) = self.row(row);
+            for (k, a) in cols.iter().zip(vals) {
+                let (other_cols, other_vals) = other.row(*k);
+                for (j, b) in other_cols.iter().zip(other_vals) {
+                    if !touched[*j] {
+                        touched[*j] = true;
+                        row_cols.push(*j);
+                    }
+                    accumulator[*j] += a * b;
+                }
+            }
+            row_cols.sort_unstable();
+            for j in row_cols.drain(..) {
+                col_indices.push(j);
+                values.push(accumulator[j]);
+                accumulator[j] = 0.0;
+                touched[j] = false;
+            }
+            row_offsets.push(col_indices.len());
+        }
+        SparseCSR {
+            n_cols: other.n_cols,
+            row_offsets,
+            col_indices,
+            values,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_matrix() -> SparseCSR<i32> {
+        // | 1 0 2 |
+        // | 0 3 0 |
+        // | 4 0 5 |
+        SparseCSR::new(
+            3,
+            vec![0, 2, 3, 5],
+            vec![0, 2, 1, 0, 2],
+            vec![1, 2, 3, 4, 5],
+        )
+    }
+
+    #[test]
+    fn test_sparse_csr_sizes() {
+        let csr = build_matrix();
+        assert_eq!(csr.n_rows(), 3, "Wrong number of rows");
+        assert_eq!(csr.n_cols(), 3, "Wrong number of columns");
+        assert_eq!(csr.nnz(), 5, "Wrong number of non zeros");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_sparse_csr_access() {
+        let csr = build_matrix();
+        assert_eq!(csr.get(0, 2), Some(&2), "Could not access (0, 2)");
+        assert_eq!(csr.get(2, 0), Some(&4), "Could not access (2, 0)");
+        assert_eq!(csr.get(1, 0), None, "(1, 0) should not be stored");
+        let (cols, vals) = csr.row(2);
+        assert_eq!(cols, &[0, 2], "Wrong columns for row 2");
+        assert_eq!(vals, &[4, 5], "Wrong values for row 2");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_sparse_csr_write() {
+        let mut csr = build_matrix();
+        *csr.get_mut(1, 1).unwrap() += 4;
+        assert_eq!(csr.get(1, 1), Some(&7), "Change in (1, 1) was unsuccessful");
+        csr.fill(0);
+        assert!(
+            csr.values().iter().all(|v| *v == 0),
+            "Fill was unsuccessful"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_sparse_csr_apply() {
+        let csr = SparseCSR::new(
+            3,
+            vec![0, 2, 3, 5],
+            vec![0, 2, 1, 0, 2],
+            vec![1.0, 2.0, 3.0, 4.0, 5.0],
+        );
+        let mut y = vec![0.0; 3];
+        csr.apply(&[1.0, 2.0, 3.0], &mut y);
+        assert_eq!(y, vec![7.0, 6.0, 19.0], "Wrong matrix vector product");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_sparse_csr_algebra() {
+        let csr = SparseCSR::new(
+            3,
+            vec![0, 2, 3, 5],
+            vec![0, 2, 1, 0, 2],
+            vec![1.0, 2.0, 3.0, 4.0, 5.0],
+        );
+        let transposed = csr.transpose();
+        assert_eq!(transposed.col_indices(), &[0, 2, 1, 0, 2], "Wrong columns");
+        assert_eq!(
+            transposed.values(),
+            &[1.0, 4.0, 3.0, 2.0, 5.0],
+            "Wrong transposed values"
+        );
+        let product = csr.multiply(&transposed);
+        // | 5  0 14 |
+        // | 0  9  0 |
+        // | 14 0 41 |
+        assert_eq!(product.row_offsets(), &[0, 2, 3, 5], "Wrong row offsets");
+        assert_eq!(product.col_indices(), &[0, 2, 1, 0, 2], "Wrong columns");
+        assert_eq!(
+            product.values(),
+            &[5.0, 14.0, 9.0, 14.0, 41.0],
+            "Wrong product values"
+        );
+        let sum = csr.add(-1.0, &transposed);
+        assert_eq!(sum.col_indices(), &[0, 2, 1, 0, 2], "Wrong sum columns");
+        assert_eq!(
+            sum.values(),
+            &[0.0, -2.0, 0.0, 2.0, 0.0],
+            "Wrong sum values"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    #[should_panic]
+    fn test_sparse_csr_unsorted_columns() {
+        SparseCSR::new(3, vec![0, 2], vec![2, 0], vec![1, 2]);
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    #[should_panic]
+    fn test_sparse_csr_column_out_of_bounds() {
+        SparseCSR::new(2, vec![0, 1], vec![2], vec![1]);
+    }
+}
@@ -0,0 +1,983 @@
+use std::io::{Error, ErrorKind, Result};
+use std::ops::{Add, Mul, Neg, Sub};
+
+//--------------------------------------------------------------------------------------------------
+// # Enums
+//--------------------------------------------------------------------------------------------------
+
+// Token of the source of an expression
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Operator(char),
+}
+
+// Function of one argument
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Unary {
+    Negate,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Exp,
+    Ln,
+    Log10,
+    Sqrt,
+    Abs,
+    Sign,
+    Floor,
+    Ceil,
+}
+
+// Function of two arguments
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Binary {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    Atan2,
+    Min,
+    Max,
+}
+
+// Node of the syntax tree of an expression
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Constant(f64),
+    Coordinate(usize),
+    Time,
+    Unary(Unary, Box<Node>),
+    Binary(Binary, Box<Node>, Box<Node>),
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Scalar expression of the physical coordinates x, y, z and the time t such as
+/// "sin(pi*x)*exp(-t)"
+///
+/// The source is parsed once into a syntax tree whose constant parts are folded, so that
+/// evaluating it at every quadrature or nodal point only walks the tree. Expressions combine
+/// numbers, the constants pi and e, named constants given when parsing, the operators + - * / ^
+/// (^ binding tightest and to the right), parentheses and the functions sin, cos, tan, asin, acos,
+/// atan, sinh, cosh, tanh, exp, ln (or log), log10, sqrt, abs, sign, floor, ceil, atan2, min, max
+/// and pow. Coordinates beyond the dimension of the point are 0. With the serde feature an
+/// expression deserializes from its source or from a number.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+// Recursive descent parser of a list of tokens
+struct Parser<'s> {
+    tokens: Vec<Token>,
+    position: usize,
+    constants: &'s [(&'s str, f64)],
+}
+
+impl Expression {
+    /// Parse the source of an expression
+    pub fn parse(source: &str) -> Result<Self> {
+        Expression::parse_with_constants(source, &[])
+    }
+
+    /// Parse the source of an expression which may use named constants, such as material
+    /// parameters, on top of pi and e
+    pub fn parse_with_constants(source: &str, constants: &[(&str, f64)]) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            constants,
+        };
+        let root = parser.sum()?;
+        if let Some(token) = parser.tokens.get(parser.position) {
+            return Err(invalid(format!("Unexpected {:?} in {}", token, source)));
+        }
+        Ok(Expression {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    /// Expression of a constant value
+    pub fn constant(value: f64) -> Self {
+        Expression {
+            source: value.to_string(),
+            root: Node::Constant(value),
+        }
+    }
+
+    /// Source of the expression
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether the expression depends neither on the coordinates nor on the time
+    pub fn is_constant(&self) -> bool {
+        matches!(self.root, Node::Constant(_))
+    }
+
+    /// Whether the expression depends on the time
+    pub fn is_transient(&self) -> bool {
+        self.root.depends_on_time()
+    }
+
+    /// Value at the physical coordinates of a point and a time
+    pub fn eval(&self, x: &[f64], t: f64) -> f64 {
+        self.root.eval(x, t)
+    }
+
+    /// Symbolic derivative along a coordinate (0 for x, 1 for y and 2 for z)
+    ///
+    /// The derivatives of sign, floor and ceil vanish and abs, min and max are differentiated
+    /// away from their kinks. The source of the derivative is rendered from its simplified tree.
+    pub fn derivative(&self, coordinate: usize) -> Expression {
+        Expression::from_node(self.root.derivative(&Node::Coordinate(coordinate)))
+    }
+
+    /// Symbolic derivative with respect to the time
+    pub fn time_derivative(&self) -> Expression {
+        Expression::from_node(self.root.derivative(&Node::Time))
+    }
+
+    /// Symbolic Laplacian, the sum of the second derivatives along the first dim coordinates
+    pub fn laplacian(&self, dim: usize) -> Expression {
+        (0..dim)
+            .map(|k| self.derivative(k).derivative(k))
+            .fold(Expression::constant(0.0), |sum, d| sum + d)
+    }
+
+    // Expression of a syntax tree
+    fn from_node(root: Node) -> Expression {
+        Expression {
+            source: root.render(),
+            root,
+        }
+    }
+}
+
+impl Add for Expression {
+    type Output = Expression;
+
+    fn add(self, other: Expression) -> Expression {
+        Expression::from_node(Node::add(self.root, other.root))
+    }
+}
+
+impl Sub for Expression {
+    type Output = Expression;
+
+    fn sub(self, other: Expression) -> Expression {
+        Expression::from_node(Node::subtract(self.root, other.root))
+    }
+}
+
+impl Mul for Expression {
+    type Output = Expression;
+
+    fn mul(self, other: Expression) -> Expression {
+        Expression::from_node(Node::multiply(self.root, other.root))
+    }
+}
+
+impl Mul<Expression> for f64 {
+    type Output = Expression;
+
+    fn mul(self, other: Expression) -> Expression {
+        Expression::constant(self) * other
+    }
+}
+
+impl Neg for Expression {
+    type Output = Expression;
+
+    fn neg(self) -> Expression {
+        Expression::from_node(Node::unary(Unary::Negate, self.root))
+    }
+}
+
+impl From<f64> for Expression {
+    fn from(value: f64) -> Self {
+        Expression::constant(value)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Expression {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Source {
+            Number(f64),
+            Text(String),
+        }
+        match Source::deserialize(deserializer)? {
+            Source::Number(value) => Ok(Expression::constant(value)),
+            Source::Text(source) => Expression::parse(&source).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl Node {
+    // Value at a point and a time
+    fn eval(&self, x: &[f64], t: f64) -> f64 {
+        match self {
+            Node::Constant(value) => *value,
+            Node::Coordinate(k) => x.get(*k).copied().unwrap_or(0.0),
+            Node::Time => t,
+            Node::Unary(function, argument) => function.apply(argument.eval(x, t)),
+            Node::Binary(function, left, right) => {
+                function.apply(left.eval(x, t), right.eval(x, t))
+            }
+        }
+    }
+
+    // Whether the node depends on the time
+    fn depends_on_time(&self) -> bool {
+        match self {
+            Node::Constant(_) | Node::Coordinate(_) => false,
+            Node::Time => true,
+            Node::Unary(_, argument) => argument.depends_on_time(),
+            Node::Binary(_, left, right) => left.depends_on_time() || right.depends_on_time(),
+        }
+    }
+
+    // Derivative with respect to a coordinate or the time node
+    fn derivative(&self, variable: &Node) -> Node {
+        match self {
+            Node::Constant(_) => Node::Constant(0.0),
+            Node::Coordinate(_) | Node::Time => {
+                Node::Constant(if self == variable { 1.0 } else { 0.0 })
+            }
+            Node::Unary(function, argument) => {
+                let a = (**argument).clone();
+                let da = argument.derivative(variable);
+                let outer = match function {
+                    Unary::Negate => return Node::unary(Unary::Negate, da),
+                    Unary::Sign | Unary::Floor | Unary::Ceil => return Node::Constant(0.0),
+                    Unary::Sin => Node::unary(Unary::Cos, a),
+                    Unary::Cos => Node::unary(Unary::Negate, Node::unary(Unary::Sin, a)),
+                    Unary::Tan => Node::divide(
+                        Node::Constant(1.0),
+                        Node::multiply(
+                            Node::unary(Unary::Cos, a.clone()),
+                            Node::unary(Unary::Cos, a),
+                        ),
+                    ),
+                    Unary::Asin | Unary::Acos => {
+                        let root = Node::unary(
+                            Unary::Sqrt,
+                            Node::subtract(Node::Constant(1.0), Node::multiply(a.clone(), a)),
+                        );
+                        let sign = if *function == Unary::Asin { 1.0 } else { -1.0 };
+                        Node::divide(Node::Constant(sign), root)
+                    }
+                    Unary::Atan => Node::divide(
+                        Node::Constant(1.0),
+                        Node::add(Node::Constant(1.0), Node::multiply(a.clone(), a)),
+                    ),
+                    Unary::Sinh => Node::unary(Unary::Cosh, a),
+                    Unary::Cosh => Node::unary(Unary::Sinh, a),
+                    Unary::Tanh => {
+                        let tanh = Node::unary(Unary::Tanh, a);
+                        Node::subtract(Node::Constant(1.0), Node::multiply(tanh.clone(), tanh))
+                    }
+                    Unary::Exp => self.clone(),
+                    Unary::Ln => Node::divide(Node::Constant(1.0), a),
+                    Unary::Log10 => Node::divide(
+                        Node::Constant(1.0),
+                        Node::multiply(a, Node::Constant(std::f64::consts::LN_10)),
+                    ),
+                    Unary::Sqrt => Node::divide(Node::Constant(0.5), Node::unary(Unary::Sqrt, a)),
+                    Unary::Abs => Node::unary(Unary::Sign, a),
+                };
+                Node::multiply(outer, da)
+            }
+            Node::Binary(function, left, right) => {
+                let (a, b) = ((**left).clone(), (**right).clone());
+                let (da, db) = (left.derivative(variable), right.derivative(variable));
+                match function {
+                    Binary::Add => Node::add(da, db),
+                    Binary::Subtract => Node::subtract(da, db),
+                    Binary::Multiply => Node::add(Node::multiply(da, b), Node::multiply(a, db)),
+                    Binary::Divide => Node::divide(
+                        Node::subtract(Node::multiply(da, b.clone()), Node::multiply(a, db)),
+                        Node::multiply(b.clone(), b),
+                    ),
+                    Binary::Power => {
+                        // Constant exponents avoid the logarithm of negative bases
+                        let constant = matches!(b, Node::Constant(_));
+                        if constant {
+                            let exponent = Node::subtract(b.clone(), Node::Constant(1.0));
+                            Node::multiply(
+                                Node::multiply(b, Node::binary(Binary::Power, a, exponent)),
+                                da,
+                            )
+                        } else {
+                            Node::multiply(
+                                self.clone(),
+                                Node::add(
+                                    Node::multiply(db, Node::unary(Unary::Ln, a.clone())),
+                                    Node::divide(Node::multiply(b, da), a),
+                                ),
+                            )
+                        }
+                    }
+                    Binary::Atan2 => Node::divide(
+                        Node::subtract(
+                            Node::multiply(b.clone(), da),
+                            Node::multiply(a.clone(), db),
+                        ),
+                        Node::add(Node::multiply(a.clone(), a), Node::multiply(b.clone(), b)),
+                    ),
+                    Binary::Min | Binary::Max => {
+                        // Mean of the derivatives plus or minus half their difference
+                        let sign = Node::unary(Unary::Sign, Node::subtract(a, b));
+                        let half = Node::multiply(
+                            Node::Constant(0.5),
+                            Node::multiply(sign, Node::subtract(da.clone(), db.clone())),
+                        );
+                        let mean = Node::multiply(Node::Constant(0.5), Node::add(da, db));
+                        if *function == Binary::Max {
+                            Node::add(mean, half)
+                        } else {
+                            Node::subtract(mean, half)
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Source of the node, parenthesized so that it parses back to the same tree
+    fn render(&self) -> String {
+        match self {
+            Node::Constant(value) if *value < 0.0 => format!("({})", value),
+            Node::Constant(value) => format!("{}", value),
+            Node::Coordinate(k) => ["x", "y", "z"]
+                .get(*k)
+                .map_or_else(|| "0".to_string(), |name| name.to_string()),
+            Node::Time => "t".to_string(),
+            Node::Unary(Unary::Negate, argument) => format!("(-{})", argument.render()),
+            Node::Unary(function, argument) => {
+                format!("{}({})", function.name(), argument.render())
+            }
+            Node::Binary(function, left, right) => {
+                let (a, b) = (left.render(), right.render());
+                match function {
+                    Binary::Add => format!("({} + {})", a, b),
+                    Binary::Subtract => format!("({} - {})", a, b),
+                    Binary::Multiply => format!("({} * {})", a, b),
+                    Binary::Divide => format!("({} / {})", a, b),
+                    Binary::Power => format!("({}^{})", a, b),
+                    Binary::Atan2 => format!("atan2({}, {})", a, b),
+                    Binary::Min => format!("min({}, {})", a, b),
+                    Binary::Max => format!("max({}, {})", a, b),
+                }
+            }
+        }
+    }
+
+    // Sum simplified when a term vanishes
+    fn add(a: Node, b: Node) -> Node {
+        match (a, b) {
+            (Node::Constant(0.0), b) => b,
+            (a, Node::Constant(0.0)) => a,
+            (a, b) => Node::binary(Binary::Add, a, b),
+        }
+    }
+
+    // Difference simplified when a term vanishes
+    fn subtract(a: Node, b: Node) -> Node {
+        match (a, b) {
+            (a, Node::Constant(0.0)) => a,
+            (Node::Constant(0.0), b) => Node::unary(Unary::Negate, b),
+            (a, b) => Node::binary(Binary::Subtract, a, b),
+        }
+    }
+
+    // Product simplified when a factor vanishes or is one
+    fn multiply(a: Node, b: Node) -> Node {
+        match (a, b) {
+            (Node::Constant(0.0), _) | (_, Node::Constant(0.0)) => Node::Constant(0.0),
+            (Node::Constant(1.0), b) => b,
+            (a, Node::Constant(1.0)) => a,
+            (a, b) => Node::binary(Binary::Multiply, a, b),
+        }
+    }
+
+    // Quotient simplified when the numerator vanishes or the denominator is one
+    fn divide(a: Node, b: Node) -> Node {
+        match (a, b) {
+            (Node::Constant(0.0), _) => Node::Constant(0.0),
+            (a, Node::Constant(1.0)) => a,
+            (a, b) => Node::binary(Binary::Divide, a, b),
+        }
+    }
+
+    // Node of a function of one argument, folded if the argument is constant
+    fn unary(function: Unary, argument: Node) -> Node {
+        match argument {
+            Node::Constant(value) => Node::Constant(function.apply(value)),
+            argument => Node::Unary(function, Box::new(argument)),
+        }
+    }
+
+    // Node of a function of two arguments, folded if both are constant
+    fn binary(function: Binary, left: Node, right: Node) -> Node {
+        match (left, right) {
+            (Node::Constant(a), Node::Constant(b)) => Node::Constant(function.apply(a, b)),
+            (left, right) => Node::Binary(function, Box::new(left), Box::new(right)),
+        }
+    }
+}
+
+impl Unary {
+    // Function of a name
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "sin" => Unary::Sin,
+            "cos" => Unary::Cos,
+            "tan" => Unary::Tan,
+            "asin" => Unary::Asin,
+            "acos" => Unary::Acos,
+            "atan" => Unary::Atan,
+            "sinh" => Unary::Sinh,
+            "cosh" => Unary::Cosh,
+            "tanh" => Unary::Tanh,
+            "exp" => Unary::Exp,
+            "ln" | "log" => Unary::Ln,
+            "log10" => Unary::Log10,
+            "sqrt" => Unary::Sqrt,
+            "abs" => Unary::Abs,
+            "sign" => Unary::Sign,
+            "floor" => Unary::Floor,
+            "ceil" => Unary::Ceil,
+            _ => return None,
+        })
+    }
+
+    // Name of the function, none for the negation
+    fn name(self) -> &'static str {
+        match self {
+            Unary::Negate => "",
+            Unary::Sin => "sin",
+            Unary::Cos => "cos",
+            Unary::Tan => "tan",
+            Unary::Asin => "asin",
+            Unary::Acos => "acos",
+            Unary::Atan => "atan",
+            Unary::Sinh => "sinh",
+            Unary::Cosh => "cosh",
+            Unary::Tanh => "tanh",
+            Unary::Exp => "exp",
+            Unary::Ln => "ln",
+            Unary::Log10 => "log10",
+            Unary::Sqrt => "sqrt",
+            Unary::Abs => "abs",
+            Unary::Sign => "sign",
+            Unary::Floor => "floor",
+            Unary::Ceil => "ceil",
+        }
+    }
+
+    // Value of the function
+    fn apply(self, a: f64) -> f64 {
+        match self {
+            Unary::Negate => -a,
+            Unary::Sin => a.sin(),
+            Unary::Cos => a.cos(),
+            Unary::Tan => a.tan(),
+            Unary::Asin => a.asin(),
+            Unary::Acos => a.acos(),
+            Unary::Atan => a.atan(),
+            Unary::Sinh => a.sinh(),
+            Unary::Cosh => a.cosh(),
+            Unary::Tanh => a.tanh(),
+            Unary::Exp => a.exp(),
+            Unary::Ln => a.ln(),
+            Unary::Log10 => a.log10(),
+            Unary::Sqrt => a.sqrt(),
+            Unary::Abs => a.abs(),
+            Unary::Sign => {
+                if a == 0.0 {
+                    0.0
+                } else {
+                    a.signum()
+                }
+            }
+            Unary::Floor => a.floor(),
+            Unary::Ceil => a.ceil(),
+        }
+    }
+}
+
+impl Binary {
+    // Function of a name
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "atan2" => Binary::Atan2,
+            "min" => Binary::Min,
+            "max" => Binary::Max,
+            "pow" => Binary::Power,
+            _ => return None,
+        })
+    }
+
+    // Value of the function
+    fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            Binary::Add => a + b,
+            Binary::Subtract => a - b,
+            Binary::Multiply => a * b,
+            Binary::Divide => a / b,
+            Binary::Power => a.powf(b),
+            Binary::Atan2 => a.atan2(b),
+            Binary::Min => a.min(b),
+            Binary::Max => a.max(b),
+        }
+    }
+}
+
+impl<'s> Parser<'s> {
+    // Terms separated by + and -
+    fn sum(&mut self) -> Result<Node> {
+        let mut node = self.product()?;
+        while let Some(operator) = self.operator(&['+', '-']) {
+            let function = if operator == '+' {
+                Binary::Add
+            } else {
+                Binary::Subtract
+            };
+            node = Node::binary(function, node, self.product()?);
+        }
+        Ok(node)
+    }
+
+    // Factors separated by * and /
+    fn product(&mut self) -> Result<Node> {
+        let mut node = self.factor()?;
+        while let Some(operator) = self.operator(&['*', '/']) {
+            let function = if operator == '*' {
+                Binary::Multiply
+            } else {
+                Binary::Divide
+            };
+            node = Node::binary(function, node, self.factor()?);
+        }
+        Ok(node)
+    }
+
+    // Signed power
+    fn factor(&mut self) -> Result<Node> {
+        match self.operator(&['+', '-']) {
+            Some('-') => Ok(Node::unary(Unary::Negate, self.factor()?)),
+            Some(_) => self.factor(),
+            None => self.power(),
+        }
+    }
+
+    // Atom raised to a signed power, right associative
+    fn power(&mut self) -> Result<Node> {
+        let base = self.atom()?;
+        if self.operator(&['^']).is_some() {
+            Ok(Node::binary(Binary::Power, base, self.factor()?))
+        } else {
+            Ok(base)
+        }
+    }
+
+    // Number, variable, constant, function call or parenthesized expression
+    fn atom(&mut self) -> Result<Node> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        match token {
+            Some(Token::Number(value)) => Ok(Node::Constant(value)),
+            Some(Token::Operator('(')) => {
+                let node = self.sum()?;
+                self.expect(')')?;
+                Ok(node)
+            }
+            Some(Token::Name(name)) => {
+                if self.operator(&['(']).is_some() {
+                    self.call(&name)
+                } else {
+                    self.variable(&name)
+                }
+            }
+            Some(token) => Err(invalid(format!("Unexpected {:?}", token))),
+            None => Err(invalid("Unexpected end of expression".to_string())),
+        }
+    }
+
+    // Arguments of a function whose opening parenthesis was read
+    fn call(&mut self, name: &str) -> Result<Node> {
+        let first = self.sum()?;
+        if let Some(function) = Unary::from_name(name) {
+            self.expect(')')?;
+            Ok(Node::unary(function, first))
+        } else if let Some(function) = Binary::from_name(name) {
+            self.expect(',')?;
+            let second = self.sum()?;
+            self.expect(')')?;
+            Ok(Node::binary(function, first, second))
+        } else {
+            Err(invalid(format!("Unknown function {}", name)))
+        }
+    }
+
+    // Coordinate, time or constant of a name
+    fn variable(&self, name: &str) -> Result<Node> {
+        if let Some((_, value)) = self.constants.iter().find(|(n, _)| *n == name) {
+            return Ok(Node::Constant(*value));
+        }
+        match name {
+            "x" => Ok(Node::Coordinate(0)),
+            "y" => Ok(Node::Coordinate(1)),
+            "z" => Ok(Node::Coordinate(2)),
+            "t" => Ok(Node::Time),
+            "pi" => Ok(Node::Constant(std::f64::consts::PI)),
+            "e" => Ok(Node::Constant(std::f64::consts::E)),
+            _ => Err(invalid(format!("Unknown variable {}", name))),
+        }
+    }
+
+    // Consume the next token if it is one of the operators
+    fn operator(&mut self, operators: &[char]) -> Option<char> {
+        match self.tokens.get(self.position) {
+            Some(Token::Operator(c)) if operators.contains(c) => {
+                self.position += 1;
+                Some(*c)
+            }
+            _ => None,
+        }
+    }
+
+    // Consume an operator which has to come next
+    fn expect(&mut self, operator: char) -> Result<()> {
+        match self.operator(&[operator]) {
+            Some(_) => Ok(()),
+            None => Err(invalid(format!("Expected {}", operator))),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Tokens of the source of an expression
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut k = 0;
+    while k < chars.len() {
+        let c = chars[k];
+        if c.is_whitespace() {
+            k += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = k;
+            while k < chars.len() && (chars[k].is_ascii_digit() || chars[k] == '.') {
+                k += 1;
+            }
+            // Exponent, whose sign is not an operator
+            if k < chars.len() && (chars[k] == 'e' || chars[k] == 'E') {
+                let mut end = k + 1;
+                if end < chars.len() && (chars[end] == '+' || chars[end] == '-') {
+                    end += 1;
+                }
+                if end < chars.len() && chars[end].is_ascii_digit() {
+                    k = end;
+                    while k < chars.len() && chars[k].is_ascii_digit() {
+                        k += 1;
+                    }
+                }
+            }
+            let number: String = chars[start..k].iter().collect();
+            let value = number
+                .parse()
+                .map_err(|_| invalid(format!("Invalid number {} in {}", number, source)))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = k;
+            while k < chars.len() && (chars[k].is_alphanumeric() || chars[k] == '_') {
+                k += 1;
+            }
+            tokens.push(Token::Name(chars[start..k].iter().collect()));
+        } else if "+-*/^(),".contains(c) {
+            tokens.push(Token::Operator(c));
+            k += 1;
+        } else {
+            return Err(invalid(format!("Unexpected character {} in {}", c, source)));
+        }
+    }
+    Ok(tokens)
+}
+
+// Error of an invalid expression
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_expression() {
+        let e = Expression::parse("sin(pi*x)*exp(-t)").unwrap();
+        assert!(e.is_transient() && !e.is_constant(), "Wrong dependencies");
+        let value = e.eval(&[0.25, 3.0], 0.5);
+        assert!(
+            (value - (0.25 * PI).sin() * (-0.5f64).exp()).abs() < 1e-15,
+            "Wrong value {}",
+            value
+        );
+        let cases = [
+            ("1 + 2 * 3", 7.0),
+            ("(1 + 2) * 3", 9.0),
+            ("2^3^2", 512.0),
+            ("-2^2", -4.0),
+            ("2^-1", 0.5),
+            ("1.5e-1 + .5E1", 5.15),
+            ("8 / 4 / 2 - 1 - 1", -1.0),
+            ("max(x, y) + min(1, 2) + pow(2, 3)", 11.0),
+            ("atan2(1, 1) * 4 / pi + sqrt(abs(-4)) + ln(e)", 4.0),
+            ("x + y + z", 3.0),
+        ];
+        for (source, expected) in cases {
+            let value = Expression::parse(source).unwrap().eval(&[1.0, 2.0], 0.0);
+            assert!(
+                (value - expected).abs() < 1e-14,
+                "{} gives {} instead of {}",
+                source,
+                value,
+                expected
+            );
+        }
+        let folded = Expression::parse("2 * cos(pi) + 1").unwrap();
+        assert!(folded.is_constant(), "Constants not folded");
+        assert_eq!(folded.eval(&[], 0.0), -1.0, "Wrong folded value");
+        let named = Expression::parse_with_constants("k * x", &[("k", 4.0)]).unwrap();
+        assert_eq!(named.eval(&[0.5], 0.0), 2.0, "Wrong named constant");
+        for source in ["1 +", "sin(x", "foo(x)", "w", "2 $ 3", "(1))", "atan2(1)"] {
+            let error = Expression::parse(source).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidInput, "{} accepted", source);
+        }
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_derivative() {
+        let (x, y, t) = (0.3, 0.7, 0.2);
+        let cases: [(&str, usize, f64); 6] = [
+            ("sin(pi*x)*y^2", 0, PI * (PI * x).cos() * y * y),
+            ("sin(pi*x)*y^2", 1, 2.0 * (PI * x).sin() * y),
+            (
+                "exp(x*y)/(1 + x)",
+                0,
+                (x * y).exp() * (y * (1.0 + x) - 1.0) / (1.0 + x).powi(2),
+            ),
+            (
+                "sqrt(x) + ln(y) + atan(x*y)",
+                1,
+                1.0 / y + x / (1.0 + x * x * y * y),
+            ),
+            ("x^y + max(x, y)", 0, y * x.powf(y - 1.0)),
+            (
+                "tanh(x) - cosh(2*y) * abs(x - 1)",
+                0,
+                1.0 - x.tanh().powi(2) + (2.0 * y).cosh(),
+            ),
+        ];
+        for (source, coordinate, expected) in cases {
+            let derivative = Expression::parse(source).unwrap().derivative(coordinate);
+            let value = derivative.eval(&[x, y], t);
+            assert!(
+                (value - expected).abs() < 1e-13,
+                "Derivative of {} gives {} instead of {}",
+                source,
+                value,
+                expected
+            );
+            // The rendered source parses back to the derivative
+            let reparsed = Expression::parse(derivative.source()).unwrap();
+            assert!(
+                (reparsed.eval(&[x, y], t) - value).abs() < 1e-13,
+                "Wrong source {}",
+                derivative.source()
+            );
+        }
+        let u = Expression::parse("exp(-t) * x^2 * y").unwrap();
+        let heat = u.time_derivative() - u.laplacian(2);
+        let expected = -(-t).exp() * x * x * y - 2.0 * (-t).exp() * y;
+        assert!(
+            (heat.eval(&[x, y], t) - expected).abs() < 1e-14,
+            "Wrong heat operator {}",
+            heat.source()
+        );
+        assert!(
+            Expression::parse("3*x + 2")
+                .unwrap()
+                .derivative(1)
+                .is_constant(),
+            "Constant derivatives should fold"
+        );
+        assert_eq!(
+            (2.0 * -Expression::parse("y").unwrap()).eval(&[0.0, 1.5], 0.0),
+            -3.0
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_expression_functions() {
+        let (x, y): (f64, f64) = (0.4, 0.7);
+        let cases: [(&str, f64); 23] = [
+            ("sin(x)", x.sin()),
+            ("cos(x)", x.cos()),
+            ("tan(x)", x.tan()),
+            ("asin(x)", x.asin()),
+            ("acos(x)", x.acos()),
+            ("atan(x)", x.atan()),
+            ("sinh(x)", x.sinh()),
+            ("cosh(x)", x.cosh()),
+            ("tanh(x)", x.tanh()),
+            ("exp(x)", x.exp()),
+            ("ln(x)", x.ln()),
+            ("log(x)", x.ln()),
+            ("log10(x)", x.log10()),
+            ("sqrt(x)", x.sqrt()),
+            ("abs(-x)", x),
+            ("sign(-x)", -1.0),
+            ("floor(x + 2)", 2.0),
+            ("ceil(x + 2)", 3.0),
+            ("atan2(y, x)", y.atan2(x)),
+            ("min(x, y)", x),
+            ("max(x, y)", y),
+            ("pow(y, x)", y.powf(x)),
+            ("x / y - x * y", x / y - x * y),
+        ];
+        for (source, expected) in cases {
+            let e = Expression::parse(source).unwrap();
+            let value = e.eval(&[x, y], 0.0);
+            assert!(
+                (value - expected).abs() < 1e-15,
+                "{} gives {} instead of {}",
+                source,
+                value,
+                expected
+            );
+            // Derivatives match centered differences along both coordinates
+            let h = 1e-6;
+            for coordinate in 0..2 {
+                let (mut forward, mut backward) = ([x, y], [x, y]);
+                forward[coordinate] += h;
+                backward[coordinate] -= h;
+                let difference = (e.eval(&forward, 0.0) - e.eval(&backward, 0.0)) / (2.0 * h);
+                let derivative = e.derivative(coordinate).eval(&[x, y], 0.0);
+                assert!(
+                    (derivative - difference).abs() < 1e-8,
+                    "Derivative of {} along {} gives {} instead of {}",
+                    source,
+                    coordinate,
+                    derivative,
+                    difference
+                );
+            }
+            assert!(
+                !e.is_transient() && e.time_derivative().eval(&[x, y], 0.0) == 0.0,
+                "{} should not depend on the time",
+                source
+            );
+        }
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_expression_errors() {
+        let cases = [
+            ("", "Unexpected end of expression"),
+            ("1 2", "Unexpected Number(2.0) in 1 2"),
+            ("1.2.3", "Invalid number 1.2.3 in 1.2.3"),
+            ("x # y", "Unexpected character # in x # y"),
+            ("*x", "Unexpected Operator('*')"),
+            ("sin()", "Unexpected Operator(')')"),
+            ("min(1, 2", "Expected )"),
+            ("sin(1, 2)", "Expected )"),
+            ("pow(2 3)", "Expected ,"),
+            ("cot(x)", "Unknown function cot"),
+            ("k * x", "Unknown variable k"),
+        ];
+        for (source, message) in cases {
+            let error = Expression::parse(source).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidInput, "{} accepted", source);
+            assert_eq!(error.to_string(), message, "Wrong error for {}", source);
+        }
+        // Named constants take precedence over the predefined names
+        let shadowed = Expression::parse_with_constants("e * x", &[("e", 2.0), ("k", 3.0)]);
+        assert_eq!(
+            shadowed.unwrap().eval(&[1.5], 0.0),
+            3.0,
+            "Named constant should shadow e"
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_expression_operators() {
+        let constant = Expression::constant(2.5);
+        assert!(
+            constant.is_constant() && !constant.is_transient(),
+            "Wrong dependencies"
+        );
+        assert_eq!(constant.source(), "2.5", "Wrong constant source");
+        assert_eq!(Expression::from(2.5), constant, "Wrong conversion");
+        let x = Expression::parse("x").unwrap();
+        let t = Expression::parse("t").unwrap();
+        assert_eq!(x.source(), "x", "Source should be kept");
+        let combined = (x.clone() + t.clone()) * (x.clone() - 1.0.into()) - -t.clone();
+        let value = combined.eval(&[3.0], 2.0);
+        assert_eq!(value, 12.0, "Wrong combined value");
+        let reparsed = Expression::parse(combined.source()).unwrap();
+        assert_eq!(
+            reparsed.eval(&[3.0], 2.0),
+            value,
+            "Wrong source {}",
+            combined.source()
+        );
+        assert!(combined.is_transient(), "Time dependence lost");
+        assert_eq!(
+            combined.time_derivative().eval(&[3.0], 2.0),
+            3.0,
+            "Wrong time derivative"
+        );
+        // Coordinates beyond the dimension of the point vanish
+        let z = Expression::parse("x + y + z + 1").unwrap();
+        assert_eq!(z.eval(&[2.0], 0.0), 3.0, "Missing coordinates should be 0");
+        assert_eq!(
+            z.laplacian(3).eval(&[2.0], 0.0),
+            0.0,
+            "Wrong Laplacian of a linear expression"
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use crate::core::timers::{self, Stopwatch};
+use std::io::{IsTerminal, Write};
+use std::time::Duration;
+
+// Logging macros of the library forwarding to the log facade with the log feature and checking
+// their arguments without emitting anything otherwise
+
+#[cfg(feature = "log")]
+macro_rules! info {
+    ($($arg:tt)*) => { ::log::info!($($arg)*) };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! info {
+    ($($arg:tt)*) => {{
+        let _ = ::std::format_args!($($arg)*);
+    }};
+}
+
+#[cfg(feature = "log")]
+macro_rules! debug {
+    ($($arg:tt)*) => { ::log::debug!($($arg)*) };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! debug {
+    ($($arg:tt)*) => {{
+        let _ = ::std::format_args!($($arg)*);
+    }};
+}
+
+#[cfg(feature = "log")]
+macro_rules! trace {
+    ($($arg:tt)*) => { ::log::trace!($($arg)*) };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! trace {
+    ($($arg:tt)*) => {{
+        let _ = ::std::format_args!($($arg)*);
+    }};
+}
+
+#[cfg(feature = "log")]
+macro_rules! warning {
+    ($($arg:tt)*) => { ::log::warn!($($arg)*) };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! warning {
+    ($($arg:tt)*) => {{
+        let _ = ::std::format_args!($($arg)*);
+    }};
+}
+
+pub(crate) use {debug, info, trace, warning};
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Named phase of a run, logged at the debug level when entered and with its duration when the
+/// span is dropped
+///
+/// The library opens spans around its assembly, factorization, solve and I/O phases, which are
+/// also timed in the Timers registry of the thread. Messages go through the log facade with the
+/// log feature, any logger (env_logger, tracing's log bridge...) then showing them, and nowhere
+/// otherwise.
+#[derive(Debug)]
+pub struct Span {
+    path: Vec<String>,
+    start: Stopwatch,
+}
+
+/// Terminal progress bar of a loop with a known number of steps
+///
+/// The bar is redrawn on the standard error when the percentage changes and only if it is a
+/// terminal, so that redirected outputs and tests stay clean. It is finished with a new line when
+/// dropped.
+#[derive(Debug)]
+pub struct ProgressBar {
+    label: String,
+    total: usize,
+    done: usize,
+    drawn: Option<usize>,
+    visible: bool,
+    start: Stopwatch,
+}
+
+impl Span {
+    /// Enter a phase
+    pub fn enter(name: &str) -> Self {
+        debug!("{} started", name);
+        Span {
+            path: timers::enter(name),
+            start: Stopwatch::start(),
+        }
+    }
+
+    /// Name of the phase
+    pub fn name(&self) -> &str {
+        self.path.last().unwrap()
+    }
+
+    /// Time elapsed since the phase was entered
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let elapsed = self.elapsed();
+        debug!("{} done in {:.3?}", self.name(), elapsed);
+        timers::exit(&self.path, elapsed);
+    }
+}
+
+impl ProgressBar {
+    /// Bar of a loop of total steps shown before the label
+    pub fn new(label: &str, total: usize) -> Self {
+        ProgressBar {
+            label: label.to_string(),
+            total,
+            done: 0,
+            drawn: None,
+            visible: std::io::stderr().is_terminal(),
+            start: Stopwatch::start(),
+        }
+    }
+
+    /// Bar which is never drawn, for loops whose progress was not asked for
+    pub fn hidden(label: &str, total: usize) -> Self {
+        let mut bar = ProgressBar::new(label, total);
+        bar.visible = false;
+        bar
+    }
+
+    /// Count one more step done
+    pub fn inc(&mut self) {
+        self.set(self.done + 1);
+    }
+
+    /// Set the number of steps done
+    pub fn set(&mut self, done: usize) {
+        self.done = done.min(self.total);
+        let percent = self.percent();
+        if self.visible && self.drawn != Some(percent) {
+            self.drawn = Some(percent);
+            let mut stderr = std::io::stderr();
+            let _ = write!(stderr, "\r{}", self.render());
+            let _ = stderr.flush();
+        }
+    }
+
+    /// Number of steps done
+    pub fn done(&self) -> usize {
+        self.done
+    }
+
+    /// Line of the bar: label, 30 cells filled by the fraction done, counts and elapsed time
+    pub fn render(&self) -> String {
+        let width = 30;
+        let filled = match self.total {
+            0 => width,
+            total => self.done * width / total,
+        };
+        format!(
+            "{} [{}{}] {}/{} {:.1?}",
+            self.label,
+            "#".repeat(filled),
+            " ".repeat(width - filled),
+            self.done,
+            self.total,
+            self.start.elapsed()
+        )
+    }
+
+    // Percentage of the steps done
+    fn percent(&self) -> usize {
+        match self.total {
+            0 => 100,
+            total => 100 * self.done / total,
+        }
+    }
+}
+
+impl Drop for ProgressBar {
+    fn drop(&mut self) {
+        if self.visible && self.drawn.is_some() {
+            let _ = writeln!(std::io::stderr());
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logging() {
+        let span = Span::enter("assembly");
+        info!("{} cells", 12);
+        trace!("iteration {} residual {:e}", 1, 1e-3);
+        assert_eq!(span.name(), "assembly", "Wrong span name");
+        assert!(
+            span.elapsed() < Duration::from_secs(1),
+            "Wrong elapsed time"
+        );
+        let mut bar = ProgressBar::hidden("steps", 4);
+        bar.inc();
+        bar.set(10);
+        assert_eq!(bar.done(), 4, "Progress should stop at the total");
+        let line = bar.render();
+        assert!(
+            line.starts_with(&format!("steps [{}] 4/4 ", "#".repeat(30))),
+            "Wrong bar {}",
+            line
+        );
+        bar.set(2);
+        assert!(
+            bar.render()
+                .starts_with(&format!("steps [{}{}] 2/4", "#".repeat(15), " ".repeat(15))),
+            "Wrong half bar"
+        );
+    }
+}
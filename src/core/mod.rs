@@ -3,3 +3,15 @@ pub mod types;
 
 /// Minimal data arrays structures
 pub mod arrays;
+
+/// Parsed expressions of the coordinates and time
+pub mod expression;
+
+/// Logging of the phases of a run and terminal progress bars
+pub mod logging;
+
+/// Hierarchical timers of the phases of a run
+pub mod timers;
+
+/// Spatial search structures over point clouds
+pub mod search;
@@ -0,0 +1,246 @@
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// k-d tree over a cloud of points for nearest neighbor and radius queries
+///
+/// The points are held as flat coordinates of a given dimension. The tree is implicit in a
+/// permutation of the points: the median of every range splits it along the axis of its largest
+/// extent, the lower half coming before it and the upper half after it. Queries descend into the
+/// half containing the query point first and only visit the other half when the splitting plane is
+/// closer than the current candidates. Point location in the cells of a mesh is done by the
+/// CellTree, the bounding volume hierarchy of the cells.
+#[derive(Clone, Debug)]
+pub struct KdTree {
+    dim: usize,
+    points: Vec<f64>,
+    indices: Vec<usize>,
+    axes: Vec<usize>,
+}
+
+impl KdTree {
+    /// Build the tree over points given by their flat coordinates of dimension dim
+    pub fn new(dim: usize, points: &[f64]) -> Self {
+        assert!(dim > 0, "Points of a KdTree need at least one coordinate");
+        assert!(
+            points.len().is_multiple_of(dim),
+            "Point coordinates do not match the dimension"
+        );
+        let n_points = points.len() / dim;
+        let mut tree = KdTree {
+            dim,
+            points: points.to_vec(),
+            indices: (0..n_points).collect(),
+            axes: vec![0; n_points],
+        };
+        tree.build(0, n_points);
+        tree
+    }
+
+    // Split the points in the range start..end at their median along the axis of largest extent
+    fn build(&mut self, start: usize, end: usize) {
+        if end - start < 2 {
+            return;
+        }
+        let dim = self.dim;
+        let axis = (0..dim)
+            .map(|d| {
+                let (min, max) = self.indices[start..end].iter().fold(
+                    (f64::INFINITY, f64::NEG_INFINITY),
+                    |(min, max), p| {
+                        let x = self.points[p * dim + d];
+                        (min.min(x), max.max(x))
+                    },
+                );
+                max - min
+            })
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(d, _)| d)
+            .unwrap();
+        let middle = (start + end) / 2;
+        let points = &self.points;
+        self.indices[start..end].select_nth_unstable_by(middle - start, |a, b| {
+            points[a * dim + axis].total_cmp(&points[b * dim + axis])
+        });
+        self.axes[middle] = axis;
+        self.build(start, middle);
+        self.build(middle + 1, end);
+    }
+
+    /// Dimension of the points
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Number of points
+    pub fn n_points(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Coordinates of a point
+    pub fn point(&self, index: usize) -> &[f64] {
+        &self.points[index * self.dim..(index + 1) * self.dim]
+    }
+
+    /// Index of the point closest to a query point and its distance, none for an empty tree
+    pub fn nearest(&self, point: &[f64]) -> Option<(usize, f64)> {
+        self.k_nearest(point, 1).first().copied()
+    }
+
+    /// Indices of the k points closest to a query point and their distances, closest first
+    pub fn k_nearest(&self, point: &[f64], k: usize) -> Vec<(usize, f64)> {
+        assert_eq!(point.len(), self.dim, "Query point has the wrong dimension");
+        let mut best = Vec::with_capacity(k + 1);
+        if k > 0 {
+            self.search_nearest(0, self.n_points(), point, k, &mut best);
+        }
+        best.into_iter()
+            .map(|(distance, index): (f64, usize)| (index, distance.sqrt()))
+            .collect()
+    }
+
+    // Update the k closest points, as sorted squared distances and indices, with the range
+    // start..end
+    fn search_nearest(
+        &self,
+        start: usize,
+        end: usize,
+        point: &[f64],
+        k: usize,
+        best: &mut Vec<(f64, usize)>,
+    ) {
+        if start >= end {
+            return;
+        }
+        let middle = (start + end) / 2;
+        let index = self.indices[middle];
+        let distance = self.squared_distance(index, point);
+        if best.len() < k || distance < best[best.len() - 1].0 {
+            let position = best.partition_point(|(d, _)| *d <= distance);
+            best.insert(position, (distance, index));
+            best.truncate(k);
+        }
+        let axis = self.axes[middle];
+        let offset = point[axis] - self.points[index * self.dim + axis];
+        let (near, far) = if offset < 0.0 {
+            ((start, middle), (middle + 1, end))
+        } else {
+            ((middle + 1, end), (start, middle))
+        };
+        self.search_nearest(near.0, near.1, point, k, best);
+        if best.len() < k || offset * offset < best[best.len() - 1].0 {
+            self.search_nearest(far.0, far.1, point, k, best);
+        }
+    }
+
+    /// Indices of the points within a distance of a query point, in increasing order
+    pub fn within(&self, point: &[f64], radius: f64) -> Vec<usize> {
+        assert_eq!(point.len(), self.dim, "Query point has the wrong dimension");
+        let mut found = Vec::new();
+        if radius >= 0.0 {
+            self.search_within(0, self.n_points(), point, radius * radius, &mut found);
+        }
+        found.sort_unstable();
+        found
+    }
+
+    // Collect the points of the range start..end within a squared distance of the query point
+    fn search_within(
+        &self,
+        start: usize,
+        end: usize,
+        point: &[f64],
+        squared_radius: f64,
+        found: &mut Vec<usize>,
+    ) {
+        if start >= end {
+            return;
+        }
+        let middle = (start + end) / 2;
+        let index = self.indices[middle];
+        if self.squared_distance(index, point) <= squared_radius {
+            found.push(index);
+        }
+        let axis = self.axes[middle];
+        let offset = point[axis] - self.points[index * self.dim + axis];
+        if offset <= 0.0 || offset * offset <= squared_radius {
+            self.search_within(start, middle, point, squared_radius, found);
+        }
+        if offset >= 0.0 || offset * offset <= squared_radius {
+            self.search_within(middle + 1, end, point, squared_radius, found);
+        }
+    }
+
+    // Squared distance between a point of the tree and a query point
+    fn squared_distance(&self, index: usize, point: &[f64]) -> f64 {
+        self.point(index)
+            .iter()
+            .zip(point)
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pseudo-random coordinates in [0, 1) of a linear congruential generator
+    fn random_coordinates(n: usize, seed: u64) -> Vec<f64> {
+        let mut state = seed;
+        (0..n)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 11) as f64 / (1u64 << 53) as f64
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_kd_tree_queries() {
+        let points = random_coordinates(3 * 500, 7);
+        let tree = KdTree::new(3, &points);
+        assert_eq!(tree.n_points(), 500, "Wrong number of points");
+        let distance = |p: usize, q: &[f64]| -> f64 {
+            tree.point(p)
+                .iter()
+                .zip(q)
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum::<f64>()
+                .sqrt()
+        };
+        for query in random_coordinates(3 * 20, 11).chunks(3) {
+            let mut brute: Vec<(usize, f64)> = (0..500).map(|p| (p, distance(p, query))).collect();
+            brute.sort_by(|a, b| a.1.total_cmp(&b.1));
+            let (nearest, d) = tree.nearest(query).unwrap();
+            assert_eq!(nearest, brute[0].0, "Wrong nearest point");
+            assert!((d - brute[0].1).abs() < 1e-14, "Wrong nearest distance");
+            let k_nearest: Vec<usize> = tree.k_nearest(query, 5).iter().map(|n| n.0).collect();
+            let expected: Vec<usize> = brute[..5].iter().map(|n| n.0).collect();
+            assert_eq!(k_nearest, expected, "Wrong k nearest points");
+            let mut within: Vec<usize> = brute
+                .iter()
+                .filter(|(_, d)| *d <= 0.2)
+                .map(|(p, _)| *p)
+                .collect();
+            within.sort_unstable();
+            assert_eq!(tree.within(query, 0.2), within, "Wrong points in the ball");
+        }
+        assert!(
+            KdTree::new(2, &[]).nearest(&[0.0, 0.0]).is_none(),
+            "Empty tree has no nearest point"
+        );
+        assert_eq!(
+            tree.k_nearest(&[0.5; 3], 600).len(),
+            500,
+            "At most all the points are the nearest"
+        );
+    }
+}
@@ -0,0 +1,373 @@
+use std::cell::RefCell;
+use std::fmt::Write;
+#[cfg(hosted)]
+use std::path::Path;
+use std::time::Duration;
+#[cfg(hosted)]
+use std::time::Instant;
+
+thread_local! {
+    // Names of the spans currently open on the thread, outermost first
+    static OPEN: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    // Registries recording the spans of the thread with the number of open spans when they were
+    // pushed, the first one living as long as the thread
+    static REGISTRIES: RefCell<Vec<(usize, Timers)>> = RefCell::new(vec![(0, Timers::new())]);
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Accumulated wall time and number of calls of a named phase nested in its parent phases
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timer {
+    path: Vec<String>,
+    count: usize,
+    total: Duration,
+}
+
+/// Registry of hierarchical timers of the phases of a run
+///
+/// Every thread records the logging spans it opens, such as the assembly, factorization, solve and
+/// I/O phases of the library, in a registry of its own: a span opened while another one is open
+/// is timed as its child. Timers are listed in the order their phases were first entered and
+/// collect() gives the timers of a single workflow while it runs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Timers {
+    timers: Vec<Timer>,
+}
+
+/// Wall clock measuring the time elapsed since it was started
+///
+/// Targets without a clock like wasm32-unknown-unknown, where Instant::now panics, read zero
+/// durations so that the timed phases still run in browsers.
+#[derive(Clone, Copy, Debug)]
+pub struct Stopwatch {
+    #[cfg(hosted)]
+    start: Instant,
+}
+
+// Guard of a registry pushed by Timers::collect, popping it even if the workflow panics
+struct Collection;
+
+impl Timer {
+    /// Name of the phase
+    pub fn name(&self) -> &str {
+        self.path.last().unwrap()
+    }
+
+    /// Names of the parent phases then of the phase
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    /// Number of parent phases
+    pub fn depth(&self) -> usize {
+        self.path.len() - 1
+    }
+
+    /// Number of times the phase ended
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Wall time spent in the phase
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// Mean wall time of a call, zero if the phase never ended
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => self.total.div_f64(count as f64),
+        }
+    }
+}
+
+impl Timers {
+    /// Empty registry
+    pub fn new() -> Self {
+        Timers { timers: Vec::new() }
+    }
+
+    /// Timers recorded so far by the current thread
+    pub fn current() -> Self {
+        REGISTRIES.with(|registries| registries.borrow()[0].1.clone())
+    }
+
+    /// Clear the timers of the current thread
+    pub fn reset() {
+        REGISTRIES.with(|registries| registries.borrow_mut()[0].1 = Timers::new());
+    }
+
+    /// Run a workflow on the current thread and return its result with the timers of the spans it
+    /// opened, whose paths start at the phases the workflow entered
+    pub fn collect<T, Workflow: FnOnce() -> T>(workflow: Workflow) -> (T, Timers) {
+        let depth = OPEN.with(|open| open.borrow().len());
+        REGISTRIES.with(|registries| registries.borrow_mut().push((depth, Timers::new())));
+        let collection = Collection;
+        let result = workflow();
+        drop(collection);
+        let timers = REGISTRIES.with(|registries| registries.borrow_mut().pop().unwrap().1);
+        (result, timers)
+    }
+
+    /// Add the wall time of a call of a phase, registering it and its parents if needed
+    pub fn record(&mut self, path: &[&str], elapsed: Duration) {
+        let timer = self.entry(path);
+        timer.count += 1;
+        timer.total += elapsed;
+    }
+
+    /// Timers in the order their phases were first entered, parents before their children
+    pub fn timers(&self) -> &[Timer] {
+        &self.timers
+    }
+
+    /// Timer of a phase given by its path
+    pub fn get(&self, path: &[&str]) -> Option<&Timer> {
+        self.timers.iter().find(|timer| timer.path == path)
+    }
+
+    /// Wall time spent in the outermost phases
+    pub fn total(&self) -> Duration {
+        self.timers
+            .iter()
+            .filter(|timer| timer.depth() == 0)
+            .map(|timer| timer.total)
+            .sum()
+    }
+
+    /// Table of the phases indented by depth with their calls, total and mean times in seconds and
+    /// share of the total time
+    pub fn summary(&self) -> String {
+        let names: Vec<String> = self
+            .timers
+            .iter()
+            .map(|timer| format!("{}{}", "  ".repeat(timer.depth()), timer.name()))
+            .collect();
+        let width = names.iter().map(|n| n.chars().count()).fold(5, usize::max);
+        let total = self.total().as_secs_f64();
+        let mut summary = format!(
+            "{:<width$} {:>8} {:>12} {:>12} {:>7}\n",
+            "Phase", "Calls", "Total [s]", "Mean [s]", "Share"
+        );
+        for (timer, name) in self.timers.iter().zip(&names) {
+            let share = if total > 0.0 {
+                100.0 * timer.total.as_secs_f64() / total
+            } else {
+                0.0
+            };
+            writeln!(
+                summary,
+                "{:<width$} {:>8} {:>12.6} {:>12.6} {:>6.1}%",
+                name,
+                timer.count,
+                timer.total.as_secs_f64(),
+                timer.mean().as_secs_f64(),
+                share
+            )
+            .unwrap();
+        }
+        summary
+    }
+
+    /// JSON array with one object per timer giving its path, calls and total time in seconds
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("[\n");
+        for (k, timer) in self.timers.iter().enumerate() {
+            let path: Vec<String> = timer.path.iter().map(|n| json_string(n)).collect();
+            let separator = if k + 1 < self.timers.len() { "," } else { "" };
+            writeln!(
+                json,
+                "  {{\"path\": [{}], \"count\": {}, \"seconds\": {}}}{}",
+                path.join(", "),
+                timer.count,
+                timer.total.as_secs_f64(),
+                separator
+            )
+            .unwrap();
+        }
+        json.push_str("]\n");
+        json
+    }
+
+    /// Write the timers as JSON if the path has the json extension and as the summary otherwise
+    #[cfg(hosted)]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let contents = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => self.to_json(),
+            _ => self.summary(),
+        };
+        std::fs::write(path, contents)
+    }
+
+    // Timer of a path, registered with its missing parents
+    fn entry(&mut self, path: &[&str]) -> &mut Timer {
+        assert!(!path.is_empty(), "Timers need a name");
+        for depth in 1..=path.len() {
+            if self.get(&path[..depth]).is_none() {
+                // Children go after the last timer of their parent's subtree
+                let parent = &path[..depth - 1];
+                let position = self
+                    .timers
+                    .iter()
+                    .rposition(|timer| {
+                        timer.path.len() >= parent.len()
+                            && timer.path.iter().zip(parent).all(|(n, p)| n == p)
+                    })
+                    .map_or(self.timers.len(), |k| k + 1);
+                let timer = Timer {
+                    path: path[..depth].iter().map(|n| n.to_string()).collect(),
+                    count: 0,
+                    total: Duration::ZERO,
+                };
+                self.timers.insert(position, timer);
+            }
+        }
+        self.timers
+            .iter_mut()
+            .find(|timer| timer.path == path)
+            .unwrap()
+    }
+}
+
+impl Stopwatch {
+    /// Start measuring
+    pub fn start() -> Self {
+        Stopwatch {
+            #[cfg(hosted)]
+            start: Instant::now(),
+        }
+    }
+
+    /// Time elapsed since the start
+    #[cfg(hosted)]
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Time elapsed since the start
+    #[cfg(not(hosted))]
+    pub fn elapsed(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+impl Drop for Collection {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            REGISTRIES.with(|registries| registries.borrow_mut().pop());
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Open a span on the current thread and return its path
+pub(crate) fn enter(name: &str) -> Vec<String> {
+    let path = OPEN.with(|open| {
+        let mut open = open.borrow_mut();
+        open.push(name.to_string());
+        open.clone()
+    });
+    for_each_registry(&path, |timers, path| {
+        timers.entry(path);
+    });
+    path
+}
+
+// Close the span of a path on the current thread after it lasted for some time
+pub(crate) fn exit(path: &[String], elapsed: Duration) {
+    OPEN.with(|open| open.borrow_mut().truncate(path.len() - 1));
+    for_each_registry(path, |timers, path| timers.record(path, elapsed));
+}
+
+// Apply an update to the registries of the current thread with the path relative to each of them,
+// skipping the ones pushed while the span was already open
+fn for_each_registry<Update: Fn(&mut Timers, &[&str])>(path: &[String], update: Update) {
+    let path: Vec<&str> = path.iter().map(|n| n.as_str()).collect();
+    REGISTRIES.with(|registries| {
+        for (depth, timers) in registries.borrow_mut().iter_mut() {
+            if *depth < path.len() {
+                update(timers, &path[*depth..]);
+            }
+        }
+    });
+}
+
+// Quoted JSON string with its quotes, backslashes and control characters escaped
+fn json_string(value: &str) -> String {
+    let mut json = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::logging::Span;
+
+    #[test]
+    fn test_timers() {
+        let (value, timers) = Timers::collect(|| {
+            let _run = Span::enter("run");
+            for _ in 0..3 {
+                let _assembly = Span::enter("assembly");
+            }
+            let _solve = Span::enter("solve");
+            std::thread::sleep(Duration::from_millis(2));
+            42
+        });
+        assert_eq!(value, 42, "Wrong result of the workflow");
+        let paths: Vec<&[String]> = timers.timers().iter().map(|t| t.path()).collect();
+        assert_eq!(
+            paths,
+            [vec!["run"], vec!["run", "assembly"], vec!["run", "solve"]],
+            "Wrong hierarchy"
+        );
+        assert_eq!(
+            timers.get(&["run", "assembly"]).unwrap().count(),
+            3,
+            "Wrong number of calls"
+        );
+        let run = timers.get(&["run"]).unwrap();
+        let solve = timers.get(&["run", "solve"]).unwrap();
+        assert!(run.total() >= solve.total(), "Parents last longer");
+        assert!(solve.total() >= Duration::from_millis(2), "Wrong wall time");
+        assert_eq!(timers.total(), run.total(), "Wrong total");
+        let summary = timers.summary();
+        let lines: Vec<&str> = summary.lines().collect();
+        assert!(lines[0].starts_with("Phase "), "Wrong header");
+        assert!(lines[2].starts_with("  assembly "), "Children are indented");
+        assert!(lines[1].ends_with("100.0%"), "Wrong share {}", lines[1]);
+        assert!(
+            Timers::current().get(&["run", "solve"]).is_some(),
+            "The thread keeps the timers"
+        );
+        let mut timers = Timers::new();
+        timers.record(&["io", "write \"a\""], Duration::from_millis(500));
+        assert_eq!(
+            timers.to_json(),
+            "[\n  {\"path\": [\"io\"], \"count\": 0, \"seconds\": 0},\n  \
+             {\"path\": [\"io\", \"write \\\"a\\\"\"], \"count\": 1, \"seconds\": 0.5}\n]\n",
+            "Wrong JSON"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use infinitable::Infinitable;
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
 
 // Setting values by default for commonly used types
 type _Fe2O3Int = i32;
@@ -7,6 +8,724 @@ type _Fe2O3Float = f64;
 // Type that can take infinity values for spaces that might be infinite
 type _Fe2O3SizeType = Infinitable<usize>;
 
+/// Forward mode dual number carrying a value and its derivatives with respect to a set of
+/// variables
+///
+/// Arithmetic on duals applies the chain rule so that evaluating a function on variables seeded
+/// with Dual::variable gives its exact gradient along with its value. Constants have no stored
+/// derivatives and take the number of derivatives of the duals they are combined with.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Dual {
+    value: f64,
+    derivatives: Vec<f64>,
+}
+
+impl Dual {
+    /// Constant with zero derivatives
+    pub fn constant(value: f64) -> Self {
+        Dual {
+            value,
+            derivatives: Vec::new(),
+        }
+    }
+
+    /// Variable of the given index among n variables, its derivative being 1 with respect to
+    /// itself and 0 with respect to the others
+    pub fn variable(value: f64, index: usize, n_variables: usize) -> Self {
+        assert!(index < n_variables, "Variable index out of range");
+        let mut derivatives = vec![0.0; n_variables];
+        derivatives[index] = 1.0;
+        Dual { value, derivatives }
+    }
+
+    /// Value of the number
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Derivatives with respect to the variables, empty for constants
+    pub fn derivatives(&self) -> &[f64] {
+        &self.derivatives
+    }
+
+    /// Derivative with respect to a variable
+    pub fn derivative(&self, index: usize) -> f64 {
+        self.derivatives.get(index).copied().unwrap_or(0.0)
+    }
+
+    /// Sine
+    pub fn sin(&self) -> Self {
+        self.chain(self.value.sin(), self.value.cos())
+    }
+
+    /// Cosine
+    pub fn cos(&self) -> Self {
+        self.chain(self.value.cos(), -self.value.sin())
+    }
+
+    /// Exponential
+    pub fn exp(&self) -> Self {
+        let exp = self.value.exp();
+        self.chain(exp, exp)
+    }
+
+    /// Natural logarithm
+    pub fn ln(&self) -> Self {
+        self.chain(self.value.ln(), 1.0 / self.value)
+    }
+
+    /// Square root
+    pub fn sqrt(&self) -> Self {
+        let sqrt = self.value.sqrt();
+        self.chain(sqrt, 0.5 / sqrt)
+    }
+
+    /// Integer power
+    pub fn powi(&self, n: i32) -> Self {
+        self.chain(self.value.powi(n), n as f64 * self.value.powi(n - 1))
+    }
+
+    /// Real power
+    pub fn powf(&self, n: f64) -> Self {
+        self.chain(self.value.powf(n), n * self.value.powf(n - 1.0))
+    }
+
+    /// Absolute value, whose derivative at 0 is taken as 0
+    pub fn abs(&self) -> Self {
+        self.chain(
+            self.value.abs(),
+            self.value.signum() * (self.value != 0.0) as u8 as f64,
+        )
+    }
+
+    /// Hyperbolic tangent
+    pub fn tanh(&self) -> Self {
+        let tanh = self.value.tanh();
+        self.chain(tanh, 1.0 - tanh * tanh)
+    }
+
+    // Composition with a function of the given value and derivative at the value of self
+    fn chain(&self, value: f64, derivative: f64) -> Self {
+        Dual {
+            value,
+            derivatives: self.derivatives.iter().map(|d| derivative * d).collect(),
+        }
+    }
+
+    // Linear combination a self + b other of the derivatives with the given value
+    fn combine(&self, a: f64, other: &Dual, b: f64, value: f64) -> Self {
+        let n = self.derivatives.len().max(other.derivatives.len());
+        Dual {
+            value,
+            derivatives: (0..n)
+                .map(|i| a * self.derivative(i) + b * other.derivative(i))
+                .collect(),
+        }
+    }
+}
+
+impl From<f64> for Dual {
+    fn from(value: f64) -> Self {
+        Dual::constant(value)
+    }
+}
+
+impl Add<&Dual> for &Dual {
+    type Output = Dual;
+    fn add(self, other: &Dual) -> Dual {
+        self.combine(1.0, other, 1.0, self.value + other.value)
+    }
+}
+
+impl Sub<&Dual> for &Dual {
+    type Output = Dual;
+    fn sub(self, other: &Dual) -> Dual {
+        self.combine(1.0, other, -1.0, self.value - other.value)
+    }
+}
+
+impl Mul<&Dual> for &Dual {
+    type Output = Dual;
+    fn mul(self, other: &Dual) -> Dual {
+        self.combine(other.value, other, self.value, self.value * other.value)
+    }
+}
+
+impl Div<&Dual> for &Dual {
+    type Output = Dual;
+    fn div(self, other: &Dual) -> Dual {
+        let inverse = 1.0 / other.value;
+        let value = self.value * inverse;
+        self.combine(inverse, other, -value * inverse, value)
+    }
+}
+
+impl Add<f64> for &Dual {
+    type Output = Dual;
+    fn add(self, other: f64) -> Dual {
+        self.chain(self.value + other, 1.0)
+    }
+}
+
+impl Sub<f64> for &Dual {
+    type Output = Dual;
+    fn sub(self, other: f64) -> Dual {
+        self.chain(self.value - other, 1.0)
+    }
+}
+
+impl Mul<f64> for &Dual {
+    type Output = Dual;
+    fn mul(self, other: f64) -> Dual {
+        self.chain(self.value * other, other)
+    }
+}
+
+impl Div<f64> for &Dual {
+    type Output = Dual;
+    fn div(self, other: f64) -> Dual {
+        self.chain(self.value / other, 1.0 / other)
+    }
+}
+
+impl Add<&Dual> for f64 {
+    type Output = Dual;
+    fn add(self, other: &Dual) -> Dual {
+        other + self
+    }
+}
+
+impl Sub<&Dual> for f64 {
+    type Output = Dual;
+    fn sub(self, other: &Dual) -> Dual {
+        other.chain(self - other.value, -1.0)
+    }
+}
+
+impl Mul<&Dual> for f64 {
+    type Output = Dual;
+    fn mul(self, other: &Dual) -> Dual {
+        other * self
+    }
+}
+
+impl Div<&Dual> for f64 {
+    type Output = Dual;
+    fn div(self, other: &Dual) -> Dual {
+        other.chain(self / other.value, -self / (other.value * other.value))
+    }
+}
+
+impl Neg for &Dual {
+    type Output = Dual;
+    fn neg(self) -> Dual {
+        self.chain(-self.value, -1.0)
+    }
+}
+
+impl Neg for Dual {
+    type Output = Dual;
+    fn neg(self) -> Dual {
+        -&self
+    }
+}
+
+// Operators on owned duals forwarding to the ones on references
+macro_rules! forward_dual_operator {
+    ($trait:ident, $method:ident) => {
+        impl $trait<Dual> for Dual {
+            type Output = Dual;
+            fn $method(self, other: Dual) -> Dual {
+                (&self).$method(&other)
+            }
+        }
+
+        impl $trait<&Dual> for Dual {
+            type Output = Dual;
+            fn $method(self, other: &Dual) -> Dual {
+                (&self).$method(other)
+            }
+        }
+
+        impl $trait<Dual> for &Dual {
+            type Output = Dual;
+            fn $method(self, other: Dual) -> Dual {
+                self.$method(&other)
+            }
+        }
+
+        impl $trait<f64> for Dual {
+            type Output = Dual;
+            fn $method(self, other: f64) -> Dual {
+                (&self).$method(other)
+            }
+        }
+
+        impl $trait<Dual> for f64 {
+            type Output = Dual;
+            fn $method(self, other: Dual) -> Dual {
+                self.$method(&other)
+            }
+        }
+    };
+}
+
+forward_dual_operator!(Add, add);
+forward_dual_operator!(Sub, sub);
+forward_dual_operator!(Mul, mul);
+forward_dual_operator!(Div, div);
+
+impl AddAssign<&Dual> for Dual {
+    fn add_assign(&mut self, other: &Dual) {
+        *self = &*self + other;
+    }
+}
+
+impl AddAssign<Dual> for Dual {
+    fn add_assign(&mut self, other: Dual) {
+        *self = &*self + &other;
+    }
+}
+
+impl AddAssign<f64> for Dual {
+    fn add_assign(&mut self, other: f64) {
+        self.value += other;
+    }
+}
+
+impl SubAssign<&Dual> for Dual {
+    fn sub_assign(&mut self, other: &Dual) {
+        *self = &*self - other;
+    }
+}
+
+impl SubAssign<Dual> for Dual {
+    fn sub_assign(&mut self, other: Dual) {
+        *self = &*self - &other;
+    }
+}
+
+impl SubAssign<f64> for Dual {
+    fn sub_assign(&mut self, other: f64) {
+        self.value -= other;
+    }
+}
+
+impl MulAssign<f64> for Dual {
+    fn mul_assign(&mut self, other: f64) {
+        self.value *= other;
+        self.derivatives.iter_mut().for_each(|d| *d *= other);
+    }
+}
+
+/// Physical dimension as the exponents of the SI base quantities length, mass, time, temperature
+/// and electric current
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Dimension {
+    exponents: [i8; 5],
+}
+
+/// Value in SI units with its physical dimension, checked when combined or converted to a number
+///
+/// Quantities parse from a number followed by units, such as "210 GPa", "1.5e-3 m^2/s" or
+/// "45 W/(m*K)", with the SI base and derived units N, Pa, J, W, Hz, V, Ω (or Ohm), S, C, T and
+/// H, the grams, minutes, hours, liters and bars, all of them taking the SI prefixes from p to
+/// T (u for micro). Units are multiplied by * or spaces, divided by / (which applies to the next
+/// factor only, so the denominator of several units is parenthesized) and raised to integer powers
+/// by ^. Temperatures are in kelvins, or differences of degrees Celsius. A bare number is
+/// dimensionless.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quantity {
+    value: f64,
+    dimension: Dimension,
+}
+
+impl Dimension {
+    /// No dimension
+    pub const DIMENSIONLESS: Dimension = Dimension::new(0, 0, 0, 0, 0);
+    /// Length, in m
+    pub const LENGTH: Dimension = Dimension::new(1, 0, 0, 0, 0);
+    /// Mass, in kg
+    pub const MASS: Dimension = Dimension::new(0, 1, 0, 0, 0);
+    /// Time, in s
+    pub const TIME: Dimension = Dimension::new(0, 0, 1, 0, 0);
+    /// Temperature, in K
+    pub const TEMPERATURE: Dimension = Dimension::new(0, 0, 0, 1, 0);
+    /// Electric current, in A
+    pub const CURRENT: Dimension = Dimension::new(0, 0, 0, 0, 1);
+    /// Velocity, in m/s
+    pub const VELOCITY: Dimension = Dimension::new(1, 0, -1, 0, 0);
+    /// Force, in N
+    pub const FORCE: Dimension = Dimension::new(1, 1, -2, 0, 0);
+    /// Pressure and stress, in Pa
+    pub const PRESSURE: Dimension = Dimension::new(-1, 1, -2, 0, 0);
+    /// Energy, in J
+    pub const ENERGY: Dimension = Dimension::new(2, 1, -2, 0, 0);
+    /// Power, in W
+    pub const POWER: Dimension = Dimension::new(2, 1, -3, 0, 0);
+    /// Mass density, in kg/m^3
+    pub const DENSITY: Dimension = Dimension::new(-3, 1, 0, 0, 0);
+    /// Kinematic viscosity and diffusivity, in m^2/s
+    pub const DIFFUSIVITY: Dimension = Dimension::new(2, 0, -1, 0, 0);
+    /// Rate, such as a reaction coefficient or a frequency, in 1/s
+    pub const RATE: Dimension = Dimension::new(0, 0, -1, 0, 0);
+    /// Thermal conductivity, in W/(m*K)
+    pub const THERMAL_CONDUCTIVITY: Dimension = Dimension::new(1, 1, -3, -1, 0);
+    /// Volumetric heat capacity, in J/(m^3*K)
+    pub const VOLUMETRIC_HEAT_CAPACITY: Dimension = Dimension::new(-1, 1, -2, -1, 0);
+
+    /// Dimension of the exponents of length, mass, time, temperature and current
+    pub const fn new(length: i8, mass: i8, time: i8, temperature: i8, current: i8) -> Self {
+        Dimension {
+            exponents: [length, mass, time, temperature, current],
+        }
+    }
+
+    /// Exponents of length, mass, time, temperature and current
+    pub fn exponents(&self) -> [i8; 5] {
+        self.exponents
+    }
+
+    /// Whether the dimension is the one of pure numbers
+    pub fn is_dimensionless(&self) -> bool {
+        *self == Dimension::DIMENSIONLESS
+    }
+
+    /// Dimension raised to an integer power
+    ///
+    /// Panics if an exponent overflows, see checked_powi.
+    pub fn powi(&self, power: i8) -> Self {
+        self.checked_powi(power)
+            .expect("Exponent of the dimension overflows")
+    }
+
+    /// Dimension raised to an integer power, None if an exponent overflows
+    pub fn checked_powi(&self, power: i8) -> Option<Self> {
+        let mut exponents = self.exponents;
+        for e in exponents.iter_mut() {
+            *e = e.checked_mul(power)?;
+        }
+        Some(Dimension { exponents })
+    }
+
+    /// Dimension of a product, None if an exponent overflows
+    pub fn checked_mul(&self, other: Dimension) -> Option<Self> {
+        let mut exponents = self.exponents;
+        for (a, b) in exponents.iter_mut().zip(other.exponents) {
+            *a = a.checked_add(b)?;
+        }
+        Some(Dimension { exponents })
+    }
+
+    /// Dimension of a quotient, None if an exponent overflows
+    pub fn checked_div(&self, other: Dimension) -> Option<Self> {
+        self.checked_mul(other.checked_powi(-1)?)
+    }
+}
+
+impl std::fmt::Display for Dimension {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_dimensionless() {
+            return write!(f, "1");
+        }
+        let units: Vec<String> = ["m", "kg", "s", "K", "A"]
+            .iter()
+            .zip(self.exponents)
+            .filter(|(_, e)| *e != 0)
+            .map(|(unit, e)| match e {
+                1 => unit.to_string(),
+                _ => format!("{}^{}", unit, e),
+            })
+            .collect();
+        write!(f, "{}", units.join(" "))
+    }
+}
+
+impl Mul for Dimension {
+    type Output = Dimension;
+    fn mul(self, other: Dimension) -> Dimension {
+        self.checked_mul(other)
+            .expect("Exponent of the dimension overflows")
+    }
+}
+
+impl Div for Dimension {
+    type Output = Dimension;
+    fn div(self, other: Dimension) -> Dimension {
+        self.checked_div(other)
+            .expect("Exponent of the dimension overflows")
+    }
+}
+
+impl Quantity {
+    /// Quantity of a value in SI units and a dimension
+    pub fn new(value: f64, dimension: Dimension) -> Self {
+        Quantity { value, dimension }
+    }
+
+    /// Quantity of a number followed by units
+    pub fn parse(source: &str) -> std::io::Result<Self> {
+        let invalid =
+            |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+        let source = source.trim();
+        // Longest start of the source that is a number
+        let split = (1..=source.len())
+            .rev()
+            .filter(|i| source.is_char_boundary(*i))
+            .find(|i| source[..*i].trim().parse::<f64>().is_ok())
+            .unwrap_or(0);
+        let value: f64 = source[..split]
+            .trim()
+            .parse()
+            .map_err(|_| invalid(format!("No number at the start of {:?}", source)))?;
+        let units = source[split..].trim();
+        if units.is_empty() {
+            return Ok(Quantity::new(value, Dimension::DIMENSIONLESS));
+        }
+        let mut parser = UnitParser {
+            chars: units.chars().collect(),
+            position: 0,
+        };
+        let (scale, dimension) = parser
+            .product()
+            .filter(|_| parser.position == parser.chars.len())
+            .ok_or_else(|| invalid(format!("Invalid units {:?}", units)))?;
+        Ok(Quantity::new(value * scale, dimension))
+    }
+
+    /// Value in SI units
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Physical dimension
+    pub fn dimension(&self) -> Dimension {
+        self.dimension
+    }
+
+    /// Value in SI units if the quantity has the expected dimension
+    pub fn value_in(&self, expected: Dimension) -> std::io::Result<f64> {
+        if self.dimension == expected {
+            Ok(self.value)
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Expected a quantity in {} but got {} {}",
+                    expected, self.value, self.dimension
+                ),
+            ))
+        }
+    }
+}
+
+impl std::fmt::Display for Quantity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.dimension.is_dimensionless() {
+            write!(f, "{}", self.value)
+        } else {
+            write!(f, "{} {}", self.value, self.dimension)
+        }
+    }
+}
+
+impl Add for Quantity {
+    type Output = Quantity;
+    fn add(self, other: Quantity) -> Quantity {
+        assert_eq!(
+            self.dimension, other.dimension,
+            "Added quantities of different dimensions"
+        );
+        Quantity::new(self.value + other.value, self.dimension)
+    }
+}
+
+impl Sub for Quantity {
+    type Output = Quantity;
+    fn sub(self, other: Quantity) -> Quantity {
+        assert_eq!(
+            self.dimension, other.dimension,
+            "Subtracted quantities of different dimensions"
+        );
+        Quantity::new(self.value - other.value, self.dimension)
+    }
+}
+
+impl Mul for Quantity {
+    type Output = Quantity;
+    fn mul(self, other: Quantity) -> Quantity {
+        Quantity::new(self.value * other.value, self.dimension * other.dimension)
+    }
+}
+
+impl Div for Quantity {
+    type Output = Quantity;
+    fn div(self, other: Quantity) -> Quantity {
+        Quantity::new(self.value / other.value, self.dimension / other.dimension)
+    }
+}
+
+impl Mul<f64> for Quantity {
+    type Output = Quantity;
+    fn mul(self, other: f64) -> Quantity {
+        Quantity::new(self.value * other, self.dimension)
+    }
+}
+
+impl Neg for Quantity {
+    type Output = Quantity;
+    fn neg(self) -> Quantity {
+        Quantity::new(-self.value, self.dimension)
+    }
+}
+
+// Recursive descent parser of products and quotients of units giving their SI scale and dimension
+struct UnitParser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl UnitParser {
+    // Factors multiplied by * or spaces, or divided by /
+    fn product(&mut self) -> Option<(f64, Dimension)> {
+        let (mut scale, mut dimension) = self.factor()?;
+        loop {
+            let spaced = self.skip_spaces();
+            let divide = match self.chars.get(self.position) {
+                Some('/') => true,
+                Some('*') => false,
+                Some(c) if spaced && *c != ')' => {
+                    // Multiplication by a space, the factor starts at the position
+                    self.position -= 1;
+                    false
+                }
+                _ => return Some((scale, dimension)),
+            };
+            self.position += 1;
+            self.skip_spaces();
+            let (s, d) = self.factor()?;
+            // Overflowing exponents make the units invalid
+            if divide {
+                scale /= s;
+                dimension = dimension.checked_div(d)?;
+            } else {
+                scale *= s;
+                dimension = dimension.checked_mul(d)?;
+            }
+        }
+    }
+
+    // Unit or parenthesized product, raised to an optional integer power
+    fn factor(&mut self) -> Option<(f64, Dimension)> {
+        let (scale, dimension) = if self.chars.get(self.position) == Some(&'(') {
+            self.position += 1;
+            self.skip_spaces();
+            let inner = self.product()?;
+            self.skip_spaces();
+            (self.chars.get(self.position) == Some(&')')).then_some(())?;
+            self.position += 1;
+            inner
+        } else if self.chars.get(self.position) == Some(&'1') {
+            // Numerator of a quotient of units such as 1/s
+            self.position += 1;
+            (1.0, Dimension::DIMENSIONLESS)
+        } else {
+            let start = self.position;
+            while self
+                .chars
+                .get(self.position)
+                .is_some_and(|c| c.is_alphabetic())
+            {
+                self.position += 1;
+            }
+            let symbol: String = self.chars[start..self.position].iter().collect();
+            unit(&symbol)?
+        };
+        if self.chars.get(self.position) != Some(&'^') {
+            return Some((scale, dimension));
+        }
+        self.position += 1;
+        let start = self.position;
+        if self.chars.get(self.position) == Some(&'-') {
+            self.position += 1;
+        }
+        while self
+            .chars
+            .get(self.position)
+            .is_some_and(|c| c.is_ascii_digit())
+        {
+            self.position += 1;
+        }
+        let power: i8 = self.chars[start..self.position]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()?;
+        Some((scale.powi(power as i32), dimension.checked_powi(power)?))
+    }
+
+    // Skip the spaces at the position, returning whether there were any
+    fn skip_spaces(&mut self) -> bool {
+        let start = self.position;
+        while self.chars.get(self.position) == Some(&' ') {
+            self.position += 1;
+        }
+        self.position > start
+    }
+}
+
+// SI scale and dimension of a unit symbol, possibly prefixed
+fn unit(symbol: &str) -> Option<(f64, Dimension)> {
+    const UNITS: [(&str, f64, Dimension); 22] = [
+        ("m", 1.0, Dimension::LENGTH),
+        ("g", 1e-3, Dimension::MASS),
+        ("s", 1.0, Dimension::TIME),
+        ("K", 1.0, Dimension::TEMPERATURE),
+        ("degC", 1.0, Dimension::TEMPERATURE),
+        ("A", 1.0, Dimension::CURRENT),
+        ("N", 1.0, Dimension::FORCE),
+        ("Pa", 1.0, Dimension::PRESSURE),
+        ("J", 1.0, Dimension::ENERGY),
+        ("W", 1.0, Dimension::POWER),
+        ("Hz", 1.0, Dimension::RATE),
+        ("V", 1.0, Dimension::new(2, 1, -3, 0, -1)),
+        ("Ω", 1.0, Dimension::new(2, 1, -3, 0, -2)),
+        ("Ohm", 1.0, Dimension::new(2, 1, -3, 0, -2)),
+        ("S", 1.0, Dimension::new(-2, -1, 3, 0, 2)),
+        ("C", 1.0, Dimension::new(0, 0, 1, 0, 1)),
+        ("T", 1.0, Dimension::new(0, 1, -2, 0, -1)),
+        ("H", 1.0, Dimension::new(2, 1, -2, 0, -2)),
+        ("min", 60.0, Dimension::TIME),
+        ("h", 3600.0, Dimension::TIME),
+        ("L", 1e-3, Dimension::new(3, 0, 0, 0, 0)),
+        ("bar", 1e5, Dimension::PRESSURE),
+    ];
+    const PREFIXES: [(char, f64); 11] = [
+        ('p', 1e-12),
+        ('n', 1e-9),
+        ('u', 1e-6),
+        ('µ', 1e-6),
+        ('m', 1e-3),
+        ('c', 1e-2),
+        ('d', 1e-1),
+        ('k', 1e3),
+        ('M', 1e6),
+        ('G', 1e9),
+        ('T', 1e12),
+    ];
+    let find = |symbol: &str| {
+        UNITS
+            .iter()
+            .find(|(name, _, _)| *name == symbol)
+            .map(|(_, scale, dimension)| (*scale, *dimension))
+    };
+    find(symbol).or_else(|| {
+        let prefix = symbol.chars().next()?;
+        let (_, factor) = PREFIXES.iter().find(|(p, _)| *p == prefix)?;
+        find(&symbol[prefix.len_utf8()..]).map(|(scale, dimension)| (factor * scale, dimension))
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -17,4 +736,109 @@ mod tests {
         let zero: _Fe2O3SizeType = _Fe2O3SizeType::Finite(0);
         assert!(infty != zero, "Why would zero be equal to infinity?");
     }
+
+    #[test]
+    fn test_dual() {
+        // f(x, y) = x^2 sin(y) / (1 + exp(x y)) - sqrt(y) + 3
+        let (x0, y0) = (0.7, 1.3);
+        let f = |x: &Dual, y: &Dual| x.powi(2) * y.sin() / (1.0 + (x * y).exp()) - y.sqrt() + 3.0;
+        let value = f(&Dual::variable(x0, 0, 2), &Dual::variable(y0, 1, 2));
+        let exact = |x: f64, y: f64| x * x * y.sin() / (1.0 + (x * y).exp()) - y.sqrt() + 3.0;
+        assert!((value.value() - exact(x0, y0)).abs() < 1e-15, "Wrong value");
+        let h = 1e-6;
+        let dx = (exact(x0 + h, y0) - exact(x0 - h, y0)) / (2.0 * h);
+        let dy = (exact(x0, y0 + h) - exact(x0, y0 - h)) / (2.0 * h);
+        assert!(
+            (value.derivative(0) - dx).abs() < 1e-8,
+            "Wrong x derivative"
+        );
+        assert!(
+            (value.derivative(1) - dy).abs() < 1e-8,
+            "Wrong y derivative"
+        );
+        // Constants combine with variables of any size
+        let mut sum = Dual::constant(2.0);
+        sum += Dual::variable(1.0, 2, 3) * 4.0;
+        sum *= 0.5;
+        assert_eq!(sum.value(), 3.0, "Wrong accumulated value");
+        assert_eq!(
+            sum.derivatives(),
+            &[0.0, 0.0, 2.0],
+            "Wrong accumulated derivatives"
+        );
+        assert_eq!(
+            Dual::constant(1.0).derivative(5),
+            0.0,
+            "Constants have no derivative"
+        );
+    }
+
+    #[test]
+    fn test_quantity() {
+        let cases = [
+            ("210 GPa", 210e9, Dimension::PRESSURE),
+            ("1.5e-3 m^2/s", 1.5e-3, Dimension::DIFFUSIVITY),
+            ("45 W/(m*K)", 45.0, Dimension::THERMAL_CONDUCTIVITY),
+            ("7.8 g/cm^3", 7800.0, Dimension::DENSITY),
+            ("2 kN m", 2e3, Dimension::ENERGY),
+            ("30 min", 1800.0, Dimension::TIME),
+            ("0.5 1/s", 0.5, Dimension::RATE),
+            ("-4 mm", -4e-3, Dimension::LENGTH),
+            ("0.3", 0.3, Dimension::DIMENSIONLESS),
+        ];
+        for (source, value, dimension) in cases {
+            let quantity = Quantity::parse(source).unwrap();
+            assert!(
+                (quantity.value_in(dimension).unwrap() - value).abs() <= 1e-12 * value.abs(),
+                "Wrong value of {}: {}",
+                source,
+                quantity
+            );
+        }
+        let stress = Quantity::parse("3 MPa").unwrap();
+        assert!(
+            stress.value_in(Dimension::FORCE).is_err(),
+            "A stress is not a force"
+        );
+        for invalid in [
+            "m",
+            "3 furlongs",
+            "2 m^",
+            "1 (m/s",
+            "1 m^100 m^100",
+            "1 (m^64)^2",
+            "1 s/s^-128",
+        ] {
+            assert!(Quantity::parse(invalid).is_err(), "Parsed {:?}", invalid);
+        }
+        assert_eq!(
+            Dimension::LENGTH.checked_powi(127),
+            Some(Dimension::new(127, 0, 0, 0, 0)),
+            "Wrong power of the dimension"
+        );
+        assert!(
+            Dimension::LENGTH
+                .powi(127)
+                .checked_mul(Dimension::LENGTH)
+                .is_none(),
+            "Overflowing exponents should be detected"
+        );
+        let area = Quantity::parse("2 m").unwrap() * Quantity::parse("50 cm").unwrap();
+        let force = stress * area;
+        assert_eq!(
+            force.dimension(),
+            Dimension::FORCE,
+            "Wrong product dimension"
+        );
+        assert_eq!(
+            (force - Quantity::new(1e6, Dimension::FORCE)).value(),
+            2e6,
+            "Wrong difference"
+        );
+        assert_eq!(
+            Dimension::THERMAL_CONDUCTIVITY.to_string(),
+            "m kg s^-3 K^-1",
+            "Wrong display"
+        );
+    }
 }
@@ -0,0 +1,997 @@
+use super::cell_values::{CellValues, CoordinateSystem};
+use super::constraints::AffineConstraints;
+use super::dof_map::DofMap;
+use super::facet_values::FacetValues;
+use super::facets::Facets;
+use super::mesh::Mesh;
+use crate::core::arrays::data_wrap::DataWrap;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::core::logging::{ProgressBar, Span};
+use crate::core::types::Dual;
+use crate::spaces::lagrange::LagrangeElement;
+use crate::spaces::quadrature::QuadratureRule;
+use std::sync::OnceLock;
+use std::thread;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Structure looping over the cells of a mesh to assemble global matrices and vectors
+///
+/// For every cell, the Assembler computes the CellValues of the element and hands them to a user
+/// provided kernel together with a zeroed local matrix (or vector) wrapped in a DataWrap. The local
+/// contributions are then added to the global storage following the DofMap. The global matrix
+/// has to be allocated beforehand with a SparsityPattern built from the same DofMap.
+///
+/// Facet integrals use a separate rule on the reference facet which defaults to one exact for
+/// twice the order of the element. The facets of the mesh are only computed the first time a facet
+/// loop is run.
+///
+/// The parallel cell loops split the cells between threads (as many as the parallel configuration
+/// of the process when the loop runs by default) which accumulate in their own buffers summed once
+/// every thread is done.
+///
+/// Every loop runs in a logging Span and the sequential cell loops can show a progress bar.
+///
+/// Integrals are cartesian by default, axisymmetric coordinates are set with set_coordinate_system.
+pub struct Assembler<'a> {
+    mesh: &'a Mesh,
+    element: &'a LagrangeElement,
+    dof_map: &'a DofMap,
+    quadrature: QuadratureRule,
+    facet_quadrature: QuadratureRule,
+    facets: OnceLock<Facets>,
+    coordinate_system: CoordinateSystem,
+    n_threads: Option<usize>,
+    progress: bool,
+}
+
+impl<'a> Assembler<'a> {
+    /// Build an assembler for an element numbered by dof_map on the mesh
+    pub fn new(
+        mesh: &'a Mesh,
+        element: &'a LagrangeElement,
+        dof_map: &'a DofMap,
+        quadrature: QuadratureRule,
+    ) -> Self {
+        assert!(
+            dof_map.n_cells() == mesh.n_cells(),
+            "DofMap and Mesh do not have the same number of cells"
+        );
+        assert!(
+            dof_map.dofs_per_cell() == element.n_dofs(),
+            "DofMap does not match the number of shape functions of the element"
+        );
+        let facet_quadrature =
+            QuadratureRule::simplex(element.dim().saturating_sub(1), 2 * element.order());
+        Assembler {
+            mesh,
+            element,
+            dof_map,
+            quadrature,
+            facet_quadrature,
+            facets: OnceLock::new(),
+            coordinate_system: CoordinateSystem::Cartesian,
+            n_threads: None,
+            progress: false,
+        }
+    }
+
+    /// Change the quadrature rule used on the reference facet
+    pub fn set_facet_quadrature(&mut self, facet_quadrature: QuadratureRule) {
+        assert!(
+            facet_quadrature.dim() + 1 == self.element.dim(),
+            "Facet quadrature should be one dimension lower than the element"
+        );
+        self.facet_quadrature = facet_quadrature;
+    }
+
+    /// Change the coordinate system the integrals are taken in
+    pub fn set_coordinate_system(&mut self, coordinate_system: CoordinateSystem) {
+        assert!(
+            coordinate_system == CoordinateSystem::Cartesian || self.mesh.geometric_dim() == 2,
+            "Axisymmetric coordinates need a mesh of the (r, z) half plane"
+        );
+        self.coordinate_system = coordinate_system;
+    }
+
+    /// Coordinate system the integrals are taken in
+    pub fn coordinate_system(&self) -> CoordinateSystem {
+        self.coordinate_system
+    }
+
+    /// Change the number of threads used by the parallel loops
+    pub fn set_n_threads(&mut self, n_threads: usize) {
+        assert!(n_threads > 0, "Assembly needs at least one thread");
+        self.n_threads = Some(n_threads);
+    }
+
+    /// Number of threads used by the parallel loops, the one of the parallel configuration of the
+    /// process unless it was changed
+    pub fn n_threads(&self) -> usize {
+        self.n_threads.unwrap_or_else(crate::parallel::num_threads)
+    }
+
+    /// Show a terminal progress bar during the sequential cell loops
+    pub fn set_progress(&mut self, progress: bool) {
+        self.progress = progress;
+    }
+
+    /// Mesh the assembler loops over
+    pub fn mesh(&self) -> &Mesh {
+        self.mesh
+    }
+
+    /// Element the shape functions come from
+    pub fn element(&self) -> &LagrangeElement {
+        self.element
+    }
+
+    /// Numbering of the global dofs
+    pub fn dof_map(&self) -> &DofMap {
+        self.dof_map
+    }
+
+    /// Quadrature rule used on every cell
+    pub fn quadrature(&self) -> &QuadratureRule {
+        &self.quadrature
+    }
+
+    /// Quadrature rule used on every facet
+    pub fn facet_quadrature(&self) -> &QuadratureRule {
+        &self.facet_quadrature
+    }
+
+    /// Facets of the mesh
+    pub fn facets(&self) -> &Facets {
+        self.facets.get_or_init(|| Facets::new(self.mesh))
+    }
+
+    /// Add the contributions of the kernel on every cell to a global matrix
+    ///
+    /// The kernel receives the CellValues of the cell and a (dofs per cell, dofs per cell) local
+    /// matrix to fill.
+    pub fn assemble_matrix<Kernel>(&self, matrix: &mut SparseCSR<f64>, mut kernel: Kernel)
+    where
+        Kernel: FnMut(&CellValues, &mut DataWrap<f64, [usize; 2]>),
+    {
+        let n = self.element.n_dofs();
+        assert!(
+            matrix.n_rows() == self.dof_map.n_dofs() && matrix.n_cols() == self.dof_map.n_dofs(),
+            "Global matrix does not match the number of dofs"
+        );
+        let (_span, mut progress) = self.cell_loop("matrix assembly");
+        let mut values = self.cell_values();
+        let mut local = vec![0.0; n * n];
+        for cell in 0..self.mesh.n_cells() {
+            progress.inc();
+            values.reinit(self.mesh, cell);
+            local.iter_mut().for_each(|v| *v = 0.0);
+            kernel(&values, &mut DataWrap::new(&mut local, [n, n]));
+            add_local_matrix(matrix, self.dof_map.cell_dofs(cell), &local);
+        }
+    }
+
+    /// Add the contributions of the kernel on every cell to a global vector
+    ///
+    /// The kernel receives the CellValues of the cell and a local vector of size dofs per cell to
+    /// fill.
+    pub fn assemble_vector<Kernel>(&self, vector: &mut [f64], mut kernel: Kernel)
+    where
+        Kernel: FnMut(&CellValues, &mut DataWrap<f64, [usize; 1]>),
+    {
+        let n = self.element.n_dofs();
+        assert!(
+            vector.len() == self.dof_map.n_dofs(),
+            "Global vector does not match the number of dofs"
+        );
+        let (_span, mut progress) = self.cell_loop("vector assembly");
+        let mut values = self.cell_values();
+        let mut local = vec![0.0; n];
+        for cell in 0..self.mesh.n_cells() {
+            progress.inc();
+            values.reinit(self.mesh, cell);
+            local.iter_mut().for_each(|v| *v = 0.0);
+            kernel(&values, &mut DataWrap::new(&mut local, [n]));
+            for (i, dof) in self.dof_map.cell_dofs(cell).iter().enumerate() {
+                vector[*dof] += local[i];
+            }
+        }
+    }
+
+    /// Add the contributions of the kernel on every cell to a global matrix using several threads
+    ///
+    /// Same as assemble_matrix but the kernel is shared between the threads so it cannot mutate its
+    /// environment. Every thread holds a copy of the stored entries of the matrix.
+    pub fn par_assemble_matrix<Kernel>(&self, matrix: &mut SparseCSR<f64>, kernel: Kernel)
+    where
+        Kernel: Fn(&CellValues, &mut DataWrap<f64, [usize; 2]>) + Sync,
+    {
+        let n = self.element.n_dofs();
+        assert!(
+            matrix.n_rows() == self.dof_map.n_dofs() && matrix.n_cols() == self.dof_map.n_dofs(),
+            "Global matrix does not match the number of dofs"
+        );
+        let structure = &*matrix;
+        let buffers = self.par_cells(structure.nnz(), |values, buffer| {
+            let mut local = vec![0.0; n * n];
+            kernel(values, &mut DataWrap::new(&mut local, [n, n]));
+            let dofs = self.dof_map.cell_dofs(values.cell());
+            for (i, row) in dofs.iter().enumerate() {
+                for (j, col) in dofs.iter().enumerate() {
+                    let position = structure
+                        .position(*row, *col)
+                        .expect("Global matrix is missing an entry of the assembled pattern");
+                    buffer[position] += local[i * n + j];
+                }
+            }
+        });
+        for buffer in buffers {
+            for (v, b) in matrix.values_mut().iter_mut().zip(buffer.iter()) {
+                *v += b;
+            }
+        }
+    }
+
+    /// Add the contributions of the kernel on every cell to a global vector using several threads
+    ///
+    /// Same as assemble_vector but the kernel is shared between the threads so it cannot mutate its
+    /// environment.
+    pub fn par_assemble_vector<Kernel>(&self, vector: &mut [f64], kernel: Kernel)
+    where
+        Kernel: Fn(&CellValues, &mut DataWrap<f64, [usize; 1]>) + Sync,
+    {
+        let n = self.element.n_dofs();
+        assert!(
+            vector.len() == self.dof_map.n_dofs(),
+            "Global vector does not match the number of dofs"
+        );
+        let buffers = self.par_cells(vector.len(), |values, buffer| {
+            let mut local = vec![0.0; n];
+            kernel(values, &mut DataWrap::new(&mut local, [n]));
+            for (i, dof) in self.dof_map.cell_dofs(values.cell()).iter().enumerate() {
+                buffer[*dof] += local[i];
+            }
+        });
+        for buffer in buffers {
+            for (v, b) in vector.iter_mut().zip(buffer.iter()) {
+                *v += b;
+            }
+        }
+    }
+
+    // Values of the shape functions on the cells in the coordinate system of the assembler
+    fn cell_values(&self) -> CellValues {
+        let mut values = CellValues::new(self.element, &self.quadrature);
+        values.set_coordinate_system(self.coordinate_system);
+        values
+    }
+
+    // Traces of the shape functions on the facets in the coordinate system of the assembler
+    fn facet_values(&self) -> FacetValues<'a> {
+        let mut values = FacetValues::new(self.element, &self.facet_quadrature);
+        values.set_coordinate_system(self.coordinate_system);
+        values
+    }
+
+    // Span and progress bar of a sequential cell loop
+    fn cell_loop(&self, name: &str) -> (Span, ProgressBar) {
+        let n_cells = self.mesh.n_cells();
+        let progress = if self.progress {
+            ProgressBar::new(name, n_cells)
+        } else {
+            ProgressBar::hidden(name, n_cells)
+        };
+        (Span::enter(name), progress)
+    }
+
+    // Split the cells in contiguous chunks handed to the threads with a zeroed buffer of the given
+    // size each and return the buffers, a single thread running on the calling one (the only
+    // thread on targets like wasm32-unknown-unknown)
+    fn par_cells<Accumulate>(&self, size: usize, accumulate: Accumulate) -> Vec<Vec<f64>>
+    where
+        Accumulate: Fn(&CellValues, &mut [f64]) + Sync,
+    {
+        let _span = Span::enter("parallel assembly");
+        let n_cells = self.mesh.n_cells();
+        let n_threads = self.n_threads();
+        let chunk = n_cells.div_ceil(n_threads).max(1);
+        let run = |start: usize| {
+            let mut values = self.cell_values();
+            let mut buffer = vec![0.0; size];
+            for cell in start..(start + chunk).min(n_cells) {
+                values.reinit(self.mesh, cell);
+                accumulate(&values, &mut buffer);
+            }
+            buffer
+        };
+        if n_threads == 1 {
+            return vec![run(0)];
+        }
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..n_cells)
+                .step_by(chunk)
+                .map(|start| {
+                    let run = &run;
+                    scope.spawn(move || run(start))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("An assembly thread panicked"))
+                .collect()
+        })
+    }
+
+    /// Add the contributions of the kernel on every cell to a global matrix and vector at once
+    /// while condensing the constraints
+    ///
+    /// The kernel receives the CellValues of the cell, a local matrix and a local vector to fill.
+    /// The global matrix has to be allocated with SparsityPattern::from_dofmap_and_constraints.
+    /// Once the system is solved, AffineConstraints::distribute recovers the constrained values.
+    pub fn assemble_system<Kernel>(
+        &self,
+        matrix: &mut SparseCSR<f64>,
+        vector: &mut [f64],
+        constraints: &AffineConstraints,
+        mut kernel: Kernel,
+    ) where
+        Kernel: FnMut(&CellValues, &mut DataWrap<f64, [usize; 2]>, &mut DataWrap<f64, [usize; 1]>),
+    {
+        let n = self.element.n_dofs();
+        assert!(
+            constraints.n_dofs() == self.dof_map.n_dofs(),
+            "Constraints do not match the number of dofs"
+        );
+        let (_span, mut progress) = self.cell_loop("system assembly");
+        let mut values = self.cell_values();
+        let mut local_matrix = vec![0.0; n * n];
+        let mut local_vector = vec![0.0; n];
+        for cell in 0..self.mesh.n_cells() {
+            progress.inc();
+            values.reinit(self.mesh, cell);
+            local_matrix.iter_mut().for_each(|v| *v = 0.0);
+            local_vector.iter_mut().for_each(|v| *v = 0.0);
+            kernel(
+                &values,
+                &mut DataWrap::new(&mut local_matrix, [n, n]),
+                &mut DataWrap::new(&mut local_vector, [n]),
+            );
+            constraints.distribute_local_to_global(
+                self.dof_map.cell_dofs(cell),
+                Some(&local_matrix),
+                Some(&local_vector),
+                Some(&mut *matrix),
+                Some(&mut *vector),
+            );
+        }
+    }
+
+    /// Add the contributions of a nonlinear residual kernel on every cell to a global residual
+    /// vector and its consistent tangent matrix, differentiated automatically
+    ///
+    /// The kernel receives the CellValues of the cell, the values of the solution on the dofs of
+    /// the cell as dual numbers seeded with their local index, and a local residual of size dofs
+    /// per cell to fill. The derivatives of the local residual give the local tangent matrix, so
+    /// only the residual has to be written.
+    pub fn assemble_residual_and_tangent<Kernel>(
+        &self,
+        solution: &[f64],
+        residual: &mut [f64],
+        tangent: &mut SparseCSR<f64>,
+        mut kernel: Kernel,
+    ) where
+        Kernel: FnMut(&CellValues, &[Dual], &mut [Dual]),
+    {
+        let n = self.element.n_dofs();
+        let n_dofs = self.dof_map.n_dofs();
+        assert!(
+            solution.len() == n_dofs && residual.len() == n_dofs,
+            "Global vectors do not match the number of dofs"
+        );
+        assert!(
+            tangent.n_rows() == n_dofs && tangent.n_cols() == n_dofs,
+            "Global matrix does not match the number of dofs"
+        );
+        let (_span, mut progress) = self.cell_loop("residual and tangent assembly");
+        let mut values = self.cell_values();
+        let mut local_tangent = vec![0.0; n * n];
+        for cell in 0..self.mesh.n_cells() {
+            progress.inc();
+            values.reinit(self.mesh, cell);
+            let dofs = self.dof_map.cell_dofs(cell);
+            let local_solution: Vec<Dual> = dofs
+                .iter()
+                .enumerate()
+                .map(|(i, dof)| Dual::variable(solution[*dof], i, n))
+                .collect();
+            let mut local_residual = vec![Dual::constant(0.0); n];
+            kernel(&values, &local_solution, &mut local_residual);
+            for (i, (dof, r)) in dofs.iter().zip(&local_residual).enumerate() {
+                residual[*dof] += r.value();
+                (0..n).for_each(|j| local_tangent[i * n + j] = r.derivative(j));
+            }
+            add_local_matrix(tangent, dofs, &local_tangent);
+        }
+    }
+
+    /// Add the contributions of the kernel on the boundary facets carrying the tag to a global
+    /// matrix
+    ///
+    /// The kernel receives the FacetValues of the facet seen from its cell and a (dofs per cell,
+    /// dofs per cell) local matrix to fill.
+    pub fn assemble_exterior_facets<Kernel>(
+        &self,
+        tag: usize,
+        matrix: &mut SparseCSR<f64>,
+        mut kernel: Kernel,
+    ) where
+        Kernel: FnMut(&FacetValues, &mut DataWrap<f64, [usize; 2]>),
+    {
+        let n = self.element.n_dofs();
+        let facets = self.facets();
+        let mut values = self.facet_values();
+        let mut local = vec![0.0; n * n];
+        for facet in facets.boundary_facets() {
+            let vertices = facets.facet_vertices(facet);
+            if self.mesh.facet_tag(vertices) != Some(tag) {
+                continue;
+            }
+            let cell = facets.facet_cells(facet)[0].0;
+            values.reinit(self.mesh, vertices, cell);
+            local.iter_mut().for_each(|v| *v = 0.0);
+            kernel(&values, &mut DataWrap::new(&mut local, [n, n]));
+            add_local_matrix(matrix, self.dof_map.cell_dofs(cell), &local);
+        }
+    }
+
+    /// Add the contributions of the kernel on the boundary facets carrying the tag to a global
+    /// vector
+    ///
+    /// The kernel receives the FacetValues of the facet seen from its cell and a local vector of
+    /// size dofs per cell to fill.
+    pub fn assemble_exterior_facets_vector<Kernel>(
+        &self,
+        tag: usize,
+        vector: &mut [f64],
+        mut kernel: Kernel,
+    ) where
+        Kernel: FnMut(&FacetValues, &mut DataWrap<f64, [usize; 1]>),
+    {
+        let n = self.element.n_dofs();
+        let facets = self.facets();
+        let mut values = self.facet_values();
+        let mut local = vec![0.0; n];
+        for facet in facets.boundary_facets() {
+            let vertices = facets.facet_vertices(facet);
+            if self.mesh.facet_tag(vertices) != Some(tag) {
+                continue;
+            }
+            let cell = facets.facet_cells(facet)[0].0;
+            values.reinit(self.mesh, vertices, cell);
+            local.iter_mut().for_each(|v| *v = 0.0);
+            kernel(&values, &mut DataWrap::new(&mut local, [n]));
+            for (i, dof) in self.dof_map.cell_dofs(cell).iter().enumerate() {
+                vector[*dof] += local[i];
+            }
+        }
+    }
+
+    /// Add the contributions of the kernel on the interior facets to a global matrix
+    ///
+    /// The kernel receives the FacetValues of the facet seen from both of its cells and a (2 dofs
+    /// per cell, 2 dofs per cell) local matrix to fill where the dofs of the first cell come first.
+    /// The quadrature points of both FacetValues match. The global matrix has to be allocated with
+    /// SparsityPattern::from_dofmap_and_facets.
+    pub fn assemble_interior_facets<Kernel>(&self, matrix: &mut SparseCSR<f64>, mut kernel: Kernel)
+    where
+        Kernel: FnMut(&FacetValues, &FacetValues, &mut DataWrap<f64, [usize; 2]>),
+    {
+        let n = self.element.n_dofs();
+        let facets = self.facets();
+        let mut first = self.facet_values();
+        let mut second = self.facet_values();
+        let mut local = vec![0.0; 4 * n * n];
+        let mut dofs = Vec::with_capacity(2 * n);
+        for facet in facets.interior_facets() {
+            let vertices = facets.facet_vertices(facet);
+            let cells = facets.facet_cells(facet);
+            first.reinit(self.mesh, vertices, cells[0].0);
+            second.reinit(self.mesh, vertices, cells[1].0);
+            local.iter_mut().for_each(|v| *v = 0.0);
+            kernel(
+                &first,
+                &second,
+                &mut DataWrap::new(&mut local, [2 * n, 2 * n]),
+            );
+            dofs.clear();
+            dofs.extend_from_slice(self.dof_map.cell_dofs(cells[0].0));
+            dofs.extend_from_slice(self.dof_map.cell_dofs(cells[1].0));
+            add_local_matrix(matrix, &dofs, &local);
+        }
+    }
+
+    /// Add the contributions of the kernel on the interior facets to a global vector
+    ///
+    /// The kernel receives the FacetValues of the facet seen from both of its cells and a local
+    /// vector of size 2 dofs per cell to fill where the dofs of the first cell come first.
+    pub fn assemble_interior_facets_vector<Kernel>(&self, vector: &mut [f64], mut kernel: Kernel)
+    where
+        Kernel: FnMut(&FacetValues, &FacetValues, &mut DataWrap<f64, [usize; 1]>),
+    {
+        let n = self.element.n_dofs();
+        let facets = self.facets();
+        let mut first = self.facet_values();
+        let mut second = self.facet_values();
+        let mut local = vec![0.0; 2 * n];
+        for facet in facets.interior_facets() {
+            let vertices = facets.facet_vertices(facet);
+            let cells = facets.facet_cells(facet);
+            first.reinit(self.mesh, vertices, cells[0].0);
+            second.reinit(self.mesh, vertices, cells[1].0);
+            local.iter_mut().for_each(|v| *v = 0.0);
+            kernel(&first, &second, &mut DataWrap::new(&mut local, [2 * n]));
+            let dofs = self
+                .dof_map
+                .cell_dofs(cells[0].0)
+                .iter()
+                .chain(self.dof_map.cell_dofs(cells[1].0).iter());
+            for (i, dof) in dofs.enumerate() {
+                vector[*dof] += local[i];
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Add a dense local matrix to the rows and columns of the global matrix given by dofs
+pub(crate) fn add_local_matrix(matrix: &mut SparseCSR<f64>, dofs: &[usize], local: &[f64]) {
+    let n = dofs.len();
+    for (i, row) in dofs.iter().enumerate() {
+        for (j, col) in dofs.iter().enumerate() {
+            *matrix
+                .get_mut(*row, *col)
+                .expect("Global matrix is missing an entry of the assembled pattern") +=
+                local[i * n + j];
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::data_hold::DataHold;
+    use crate::core::arrays::data_traits::DataMutator;
+    use crate::discretizations::cell_mapping::CellMapping;
+    use crate::discretizations::sparsity::SparsityPattern;
+    use crate::discretizations::test_meshes::unit_square;
+
+    fn mass_kernel(values: &CellValues, local: &mut DataWrap<f64, [usize; 2]>) {
+        for q in 0..values.n_points() {
+            for i in 0..values.n_dofs() {
+                for j in 0..values.n_dofs() {
+                    *local.multi_index_mut([i, j]) +=
+                        values.shape_value(q, i) * values.shape_value(q, j) * values.weight(q);
+                }
+            }
+        }
+    }
+
+    fn stiffness_kernel(values: &CellValues, local: &mut DataWrap<f64, [usize; 2]>) {
+        for q in 0..values.n_points() {
+            for i in 0..values.n_dofs() {
+                for j in 0..values.n_dofs() {
+                    let dot: f64 = values
+                        .shape_gradient(q, i)
+                        .iter()
+                        .zip(values.shape_gradient(q, j).iter())
+                        .map(|(a, b)| a * b)
+                        .sum();
+                    *local.multi_index_mut([i, j]) += dot * values.weight(q);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_assemble_mass_matrix() {
+        let mesh = unit_square();
+        for order in 1..4 {
+            let element = LagrangeElement::new(2, order);
+            let dof_map = DofMap::lagrange(&mesh, &element);
+            let assembler = Assembler::new(
+                &mesh,
+                &element,
+                &dof_map,
+                QuadratureRule::simplex(2, 2 * order),
+            );
+            let mut matrix = SparsityPattern::from_dofmap(&dof_map).to_csr(0.0);
+            assembler.assemble_matrix(&mut matrix, mass_kernel);
+            let total: f64 = matrix.values().iter().sum();
+            assert!(
+                (total - 1.0).abs() < 1e-13,
+                "Mass matrix entries should sum to the area for order {}",
+                order
+            );
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_par_assemble_matrix() {
+        // Unit square cut in 4 x 4 squares split in two triangles
+        let mut vertices = Vec::new();
+        for j in 0..5 {
+            for i in 0..5 {
+                vertices.extend_from_slice(&[i as f64 / 4.0, j as f64 / 4.0]);
+            }
+        }
+        let mut cells = Vec::new();
+        for j in 0..4 {
+            for i in 0..4 {
+                let v = 5 * j + i;
+                cells.extend_from_slice(&[v, v + 1, v + 5, v + 6, v + 5, v + 1]);
+            }
+        }
+        let mesh = Mesh::new(
+            DataHold::new(vertices, [25, 2]),
+            DataHold::new(cells, [32, 3]),
+        );
+        let element = LagrangeElement::new(2, 2);
+        let dof_map = DofMap::lagrange(&mesh, &element);
+        let mut assembler =
+            Assembler::new(&mesh, &element, &dof_map, QuadratureRule::simplex(2, 4));
+        let pattern = SparsityPattern::from_dofmap(&dof_map);
+        let mut serial = pattern.to_csr(0.0);
+        assembler.assemble_matrix(&mut serial, stiffness_kernel);
+        let mut serial_vector = vec![0.0; dof_map.n_dofs()];
+        assembler.assemble_vector(&mut serial_vector, |values, local| {
+            for q in 0..values.n_points() {
+                for i in 0..values.n_dofs() {
+                    local[i] += values.point(q)[0] * values.shape_value(q, i) * values.weight(q);
+                }
+            }
+        });
+        for n_threads in [1, 3, 50] {
+            assembler.set_n_threads(n_threads);
+            let mut parallel = pattern.to_csr(0.0);
+            assembler.par_assemble_matrix(&mut parallel, stiffness_kernel);
+            for (a, b) in serial.values().iter().zip(parallel.values().iter()) {
+                assert!(
+                    (a - b).abs() < 1e-13,
+                    "Parallel matrix differs with {} threads",
+                    n_threads
+                );
+            }
+            let mut parallel_vector = vec![0.0; dof_map.n_dofs()];
+            assembler.par_assemble_vector(&mut parallel_vector, |values, local| {
+                for q in 0..values.n_points() {
+                    for i in 0..values.n_dofs() {
+                        local[i] +=
+                            values.point(q)[0] * values.shape_value(q, i) * values.weight(q);
+                    }
+                }
+            });
+            for (a, b) in serial_vector.iter().zip(parallel_vector.iter()) {
+                assert!(
+                    (a - b).abs() < 1e-14,
+                    "Parallel vector differs with {} threads",
+                    n_threads
+                );
+            }
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_assemble_stiffness_matrix() {
+        let mesh = unit_square();
+        let element = LagrangeElement::new(2, 1);
+        let dof_map = DofMap::lagrange(&mesh, &element);
+        let assembler = Assembler::new(&mesh, &element, &dof_map, QuadratureRule::simplex(2, 0));
+        let mut matrix = SparsityPattern::from_dofmap(&dof_map).to_csr(0.0);
+        assembler.assemble_matrix(&mut matrix, stiffness_kernel);
+        assert!(
+            (matrix.get(0, 0).unwrap() - 1.0).abs() < 1e-14,
+            "Wrong diagonal entry for vertex 0"
+        );
+        assert!(
+            (matrix.get(1, 1).unwrap() - 1.0).abs() < 1e-14,
+            "Wrong diagonal entry for vertex 1"
+        );
+        assert!(
+            (matrix.get(1, 2).unwrap()).abs() < 1e-14,
+            "Wrong entry on the diagonal edge"
+        );
+        for row in 0..matrix.n_rows() {
+            let sum: f64 = matrix.row(row).1.iter().sum();
+            assert!(sum.abs() < 1e-14, "Stiffness rows should sum to zero");
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_assemble_vector() {
+        let mesh = unit_square();
+        let element = LagrangeElement::new(2, 2);
+        let dof_map = DofMap::lagrange(&mesh, &element);
+        let assembler = Assembler::new(&mesh, &element, &dof_map, QuadratureRule::simplex(2, 3));
+        let mut vector = vec![0.0; dof_map.n_dofs()];
+        // Right hand side of f = x
+        assembler.assemble_vector(&mut vector, |values, local| {
+            for q in 0..values.n_points() {
+                for i in 0..values.n_dofs() {
+                    *local.multi_index_mut([i]) +=
+                        values.point(q)[0] * values.shape_value(q, i) * values.weight(q);
+                }
+            }
+        });
+        let total: f64 = vector.iter().sum();
+        assert!((total - 0.5).abs() < 1e-14, "Integral of x should be 0.5");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_assemble_system_with_constraints() {
+        // -u'' = 1 on [0, 1] with u(0) = 1 and u(1) = 2 has the quadratic solution
+        // u = 1 + x + x (1 - x) / 2 which P2 elements reproduce exactly
+        let mesh = Mesh::new(
+            DataHold::new(vec![0.0, 0.5, 1.0], [3, 1]),
+            DataHold::new(vec![0, 1, 1, 2], [2, 2]),
+        );
+        let element = LagrangeElement::new(1, 2);
+        let dof_map = DofMap::lagrange(&mesh, &element);
+        let assembler = Assembler::new(&mesh, &element, &dof_map, QuadratureRule::simplex(1, 4));
+        let mut constraints = AffineConstraints::new(dof_map.n_dofs());
+        constraints.add_dirichlet(0, 1.0);
+        constraints.add_dirichlet(2, 2.0);
+        constraints.close();
+        let mut matrix =
+            SparsityPattern::from_dofmap_and_constraints(&dof_map, &constraints).to_csr(0.0);
+        let mut rhs = vec![0.0; dof_map.n_dofs()];
+        assembler.assemble_system(&mut matrix, &mut rhs, &constraints, |values, a, f| {
+            stiffness_kernel(values, a);
+            for q in 0..values.n_points() {
+                for i in 0..values.n_dofs() {
+                    *f.multi_index_mut([i]) += values.shape_value(q, i) * values.weight(q);
+                }
+            }
+        });
+        // Dense gaussian elimination of the condensed system
+        let n = dof_map.n_dofs();
+        let mut dense = vec![0.0; n * n];
+        for row in 0..n {
+            let (cols, vals) = matrix.row(row);
+            for (col, val) in cols.iter().zip(vals.iter()) {
+                dense[row * n + col] = *val;
+            }
+        }
+        for k in 0..n {
+            for i in (k + 1)..n {
+                let factor = dense[i * n + k] / dense[k * n + k];
+                for j in k..n {
+                    dense[i * n + j] -= factor * dense[k * n + j];
+                }
+                rhs[i] -= factor * rhs[k];
+            }
+        }
+        let mut solution = vec![0.0; n];
+        for k in (0..n).rev() {
+            let sum: f64 = ((k + 1)..n).map(|j| dense[k * n + j] * solution[j]).sum();
+            solution[k] = (rhs[k] - sum) / dense[k * n + k];
+        }
+        constraints.distribute(&mut solution);
+        for cell in 0..mesh.n_cells() {
+            let mapping = CellMapping::new(&mesh, cell);
+            for node in 0..element.n_dofs() {
+                let mut x = [0.0];
+                mapping.map_point(&element.node_coordinates(node), &mut x);
+                let exact = 1.0 + x[0] + x[0] * (1.0 - x[0]) / 2.0;
+                assert!(
+                    (solution[dof_map.cell_dofs(cell)[node]] - exact).abs() < 1e-12,
+                    "Wrong solution at x = {}",
+                    x[0]
+                );
+            }
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_assemble_exterior_facets() {
+        let mut mesh = unit_square();
+        mesh.tag_boundary(1, |x| x[0] == 1.0 || x[1] == 1.0);
+        let element = LagrangeElement::new(2, 2);
+        let dof_map = DofMap::lagrange(&mesh, &element);
+        let assembler = Assembler::new(&mesh, &element, &dof_map, QuadratureRule::simplex(2, 4));
+        let mut matrix = SparsityPattern::from_dofmap(&dof_map).to_csr(0.0);
+        assembler.assemble_exterior_facets(1, &mut matrix, |values, local| {
+            for q in 0..values.n_points() {
+                for i in 0..values.n_dofs() {
+                    for j in 0..values.n_dofs() {
+                        *local.multi_index_mut([i, j]) +=
+                            values.shape_value(q, i) * values.shape_value(q, j) * values.weight(q);
+                    }
+                }
+            }
+        });
+        let total: f64 = matrix.values().iter().sum();
+        assert!(
+            (total - 2.0).abs() < 1e-13,
+            "Tagged boundary length should be 2"
+        );
+        // Flux of the position vector through the tagged sides is the tagged length
+        let mut vector = vec![0.0; dof_map.n_dofs()];
+        assembler.assemble_exterior_facets_vector(1, &mut vector, |values, local| {
+            for q in 0..values.n_points() {
+                let flux: f64 = values
+                    .point(q)
+                    .iter()
+                    .zip(values.normal().iter())
+                    .map(|(x, n)| x * n)
+                    .sum();
+                for i in 0..values.n_dofs() {
+                    *local.multi_index_mut([i]) +=
+                        flux * values.shape_value(q, i) * values.weight(q);
+                }
+            }
+        });
+        let total: f64 = vector.iter().sum();
+        assert!(
+            (total - 2.0).abs() < 1e-13,
+            "Wrong flux through the tagged sides"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_assemble_surface() {
+        // Unit square tilted in the plane z = x + y of 3 dimensions, of area sqrt(3)
+        let mut mesh = Mesh::new(
+            DataHold::new(
+                vec![0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 2.0],
+                [4, 3],
+            ),
+            DataHold::new(vec![0, 1, 2, 3, 2, 1], [2, 3]),
+        );
+        mesh.tag_boundary(1, |_| true);
+        let element = LagrangeElement::new(2, 1);
+        let dof_map = DofMap::lagrange(&mesh, &element);
+        let assembler = Assembler::new(&mesh, &element, &dof_map, QuadratureRule::simplex(2, 2));
+        let pattern = SparsityPattern::from_dofmap(&dof_map);
+        let mut mass = pattern.to_csr(0.0);
+        assembler.assemble_matrix(&mut mass, mass_kernel);
+        let area: f64 = mass.values().iter().sum();
+        assert!((area - 3.0_f64.sqrt()).abs() < 1e-14, "Wrong surface area");
+        // Tangential gradient of x is (2, -1, 1) / 3 of squared norm 2 / 3
+        let mut stiffness = pattern.to_csr(0.0);
+        assembler.assemble_matrix(&mut stiffness, stiffness_kernel);
+        let x = [0.0, 1.0, 0.0, 1.0];
+        let mut product = [0.0; 4];
+        stiffness.apply(&x, &mut product);
+        let energy: f64 = product.iter().zip(x.iter()).map(|(a, b)| a * b).sum();
+        assert!(
+            (energy - 2.0 / 3.0 * 3.0_f64.sqrt()).abs() < 1e-14,
+            "Wrong tangential stiffness"
+        );
+        // Conormals lie in the surface so that the flux of the position is its surface divergence 2
+        // integrated over the area
+        let mut vector = vec![0.0; dof_map.n_dofs()];
+        assembler.assemble_exterior_facets_vector(1, &mut vector, |values, local| {
+            for q in 0..values.n_points() {
+                let flux: f64 = values
+                    .point(q)
+                    .iter()
+                    .zip(values.normal().iter())
+                    .map(|(x, n)| x * n)
+                    .sum();
+                for i in 0..values.n_dofs() {
+                    *local.multi_index_mut([i]) +=
+                        flux * values.shape_value(q, i) * values.weight(q);
+                }
+            }
+        });
+        let total: f64 = vector.iter().sum();
+        assert!(
+            (total - 2.0 * 3.0_f64.sqrt()).abs() < 1e-13,
+            "Wrong flux through the surface boundary"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_assemble_interior_facets() {
+        let mesh = unit_square();
+        let element = LagrangeElement::new(2, 1);
+        let dof_map = DofMap::lagrange(&mesh, &element);
+        let assembler = Assembler::new(&mesh, &element, &dof_map, QuadratureRule::simplex(2, 2));
+        let mut matrix =
+            SparsityPattern::from_dofmap_and_facets(&dof_map, assembler.facets()).to_csr(0.0);
+        // Jump penalty: continuous functions have no jumps
+        assembler.assemble_interior_facets(&mut matrix, |first, second, local| {
+            let n = first.n_dofs();
+            for q in 0..first.n_points() {
+                let jumps: Vec<f64> = (0..n)
+                    .map(|i| first.shape_value(q, i))
+                    .chain((0..n).map(|i| -second.shape_value(q, i)))
+                    .collect();
+                for i in 0..2 * n {
+                    for j in 0..2 * n {
+                        *local.multi_index_mut([i, j]) += jumps[i] * jumps[j] * first.weight(q);
+                    }
+                }
+            }
+        });
+        assert!(
+            matrix.values().iter().all(|v| v.abs() < 1e-14),
+            "Continuous shape functions should not jump"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_assemble_residual_and_tangent() {
+        // Nonlinear diffusion residual ∫ (1 + u^2) ∇u · ∇v + u^3 v on P2 elements
+        let mesh = unit_square();
+        let element = LagrangeElement::new(2, 2);
+        let dof_map = DofMap::lagrange(&mesh, &element);
+        let assembler = Assembler::new(&mesh, &element, &dof_map, QuadratureRule::simplex(2, 8));
+        let kernel = |values: &CellValues, u: &[Dual], local: &mut [Dual]| {
+            for q in 0..values.n_points() {
+                let mut u_q = Dual::constant(0.0);
+                let mut gradient = vec![Dual::constant(0.0); values.dim()];
+                for (j, u) in u.iter().enumerate() {
+                    u_q += u * values.shape_value(q, j);
+                    for (g, s) in gradient.iter_mut().zip(values.shape_gradient(q, j)) {
+                        *g += u * *s;
+                    }
+                }
+                let diffusivity = 1.0 + u_q.powi(2);
+                for (i, r) in local.iter_mut().enumerate() {
+                    let mut integrand = u_q.powi(3) * values.shape_value(q, i);
+                    for (g, s) in gradient.iter().zip(values.shape_gradient(q, i)) {
+                        integrand += &diffusivity * g * *s;
+                    }
+                    *r += integrand * values.weight(q);
+                }
+            }
+        };
+        let n = dof_map.n_dofs();
+        let solution: Vec<f64> = (0..n).map(|i| (i as f64 * 0.7).sin()).collect();
+        let pattern = SparsityPattern::from_dofmap(&dof_map);
+        let mut tangent = pattern.to_csr(0.0);
+        let mut residual = vec![0.0; n];
+        assembler.assemble_residual_and_tangent(&solution, &mut residual, &mut tangent, kernel);
+        // Centered finite differences of the residual column by column
+        let h = 1e-6;
+        for j in 0..n {
+            let mut columns = [vec![0.0; n], vec![0.0; n]];
+            for (column, sign) in columns.iter_mut().zip([1.0, -1.0]) {
+                let mut shifted = solution.clone();
+                shifted[j] += sign * h;
+                let mut ignored = pattern.to_csr(0.0);
+                assembler.assemble_residual_and_tangent(&shifted, column, &mut ignored, kernel);
+            }
+            for (i, (plus, minus)) in columns[0].iter().zip(&columns[1]).enumerate() {
+                let difference = (plus - minus) / (2.0 * h);
+                let entry = tangent.get(i, j).copied().unwrap_or(0.0);
+                assert!(
+                    (entry - difference).abs() < 1e-7,
+                    "Tangent entry ({}, {}) is {} instead of {}",
+                    i,
+                    j,
+                    entry,
+                    difference
+                );
+            }
+        }
+    }
+}
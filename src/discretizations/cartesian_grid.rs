@@ -0,0 +1,225 @@
+use super::mesh::Mesh;
+use crate::core::arrays::data_hold::DataHold;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Structure describing a box split into identical axis aligned cells
+///
+/// Cells are numbered lexicographically with the first direction running fastest. The grid is
+/// never stored explicitly: it is the natural support of tensor product elements applied with
+/// sum factorization where every cell shares the same diagonal jacobian.
+pub struct CartesianGrid {
+    lower: Vec<f64>,
+    upper: Vec<f64>,
+    cells_per_dim: Vec<usize>,
+}
+
+impl CartesianGrid {
+    /// Build the grid of the box [lower, upper] with cells_per_dim cells along every direction
+    pub fn new(lower: Vec<f64>, upper: Vec<f64>, cells_per_dim: Vec<usize>) -> Self {
+        assert!(
+            lower.len() == upper.len() && lower.len() == cells_per_dim.len(),
+            "Bounds and number of cells of the grid should have the same dimension"
+        );
+        assert!(
+            lower.iter().zip(upper.iter()).all(|(l, u)| l < u),
+            "Lower bounds of the grid should be smaller than the upper ones"
+        );
+        assert!(
+            cells_per_dim.iter().all(|n| *n > 0),
+            "A grid needs at least one cell along every direction"
+        );
+        CartesianGrid {
+            lower,
+            upper,
+            cells_per_dim,
+        }
+    }
+
+    /// Dimension of the grid
+    pub fn dim(&self) -> usize {
+        self.lower.len()
+    }
+
+    /// Lower corner of the box
+    pub fn lower(&self) -> &[f64] {
+        &self.lower
+    }
+
+    /// Number of cells along every direction
+    pub fn cells_per_dim(&self) -> &[usize] {
+        &self.cells_per_dim
+    }
+
+    /// Total number of cells
+    pub fn n_cells(&self) -> usize {
+        self.cells_per_dim.iter().product()
+    }
+
+    /// Size of the cells along a direction
+    pub fn cell_size(&self, direction: usize) -> f64 {
+        (self.upper[direction] - self.lower[direction]) / self.cells_per_dim[direction] as f64
+    }
+
+    /// Position of a cell along every direction
+    pub fn cell_multi_index(&self, cell: usize) -> Vec<usize> {
+        let mut rest = cell;
+        self.cells_per_dim
+            .iter()
+            .map(|n| {
+                let index = rest % n;
+                rest /= n;
+                index
+            })
+            .collect()
+    }
+
+    /// Coordinates of the nodes along a direction, the last one being exactly the upper bound so
+    /// that the boundary can be tagged by equality
+    pub fn nodes(&self, direction: usize) -> Vec<f64> {
+        let n = self.cells_per_dim[direction];
+        (0..=n)
+            .map(|index| {
+                if index == n {
+                    self.upper[direction]
+                } else {
+                    self.lower[direction] + index as f64 * self.cell_size(direction)
+                }
+            })
+            .collect()
+    }
+
+    /// Simplicial mesh of the grid splitting every cell into the dim! simplices of Kuhn, which
+    /// is conforming across cells
+    ///
+    /// The boundary facets on the lower side of direction k are tagged 2 k + 1 and the ones on its
+    /// upper side 2 k + 2.
+    pub fn simplex_mesh(&self) -> Mesh {
+        let nodes: Vec<Vec<f64>> = (0..self.dim()).map(|k| self.nodes(k)).collect();
+        kuhn_mesh(&nodes)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Simplicial mesh of the tensor product of the nodes along every direction, splitting every cell
+// into the simplices of Kuhn and tagging the sides of the box as in CartesianGrid::simplex_mesh
+pub(crate) fn kuhn_mesh(nodes: &[Vec<f64>]) -> Mesh {
+    let dim = nodes.len();
+    let points: Vec<usize> = nodes.iter().map(|n| n.len()).collect();
+    let n_vertices: usize = points.iter().product();
+    let mut coordinates = Vec::with_capacity(n_vertices * dim);
+    for vertex in 0..n_vertices {
+        let mut rest = vertex;
+        for (k, n_points) in points.iter().enumerate() {
+            coordinates.push(nodes[k][rest % n_points]);
+            rest /= n_points;
+        }
+    }
+    let strides: Vec<usize> = (0..dim).map(|k| points[..k].iter().product()).collect();
+    let permutations = permutations(dim);
+    let n_cells: usize = points.iter().map(|n| n - 1).product();
+    let mut cells = Vec::with_capacity(n_cells * permutations.len() * (dim + 1));
+    for cell in 0..n_cells {
+        let mut rest = cell;
+        let mut corner = 0;
+        for (n_points, stride) in points.iter().zip(&strides) {
+            corner += (rest % (n_points - 1)) * stride;
+            rest /= n_points - 1;
+        }
+        for permutation in &permutations {
+            let mut vertex = corner;
+            cells.push(vertex);
+            for k in permutation {
+                vertex += strides[*k];
+                cells.push(vertex);
+            }
+        }
+    }
+    let n_simplices = cells.len() / (dim + 1);
+    let mut mesh = Mesh::new(
+        DataHold::new(coordinates, [n_vertices, dim]),
+        DataHold::new(cells, [n_simplices, dim + 1]),
+    );
+    for (k, nodes) in nodes.iter().enumerate() {
+        let (lower, upper) = (nodes[0], nodes[nodes.len() - 1]);
+        mesh.tag_boundary(2 * k + 1, |x| x[k] == lower);
+        mesh.tag_boundary(2 * k + 2, |x| x[k] == upper);
+    }
+    mesh
+}
+
+// All the orderings of 0..n
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![Vec::new()];
+    }
+    let mut all = Vec::new();
+    for shorter in permutations(n - 1) {
+        for position in 0..n {
+            let mut permutation = shorter.clone();
+            permutation.insert(position, n - 1);
+            all.push(permutation);
+        }
+    }
+    all
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::facets::Facets;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::spaces::quadrature::QuadratureRule;
+
+    #[test]
+    fn test_cartesian_grid() {
+        let grid = CartesianGrid::new(vec![0.0, -1.0], vec![3.0, 1.0], vec![3, 4]);
+        assert_eq!(grid.n_cells(), 12, "Wrong number of cells");
+        assert_eq!(grid.cell_multi_index(7), vec![1, 2], "Wrong cell ordering");
+        assert!((grid.cell_size(1) - 0.5).abs() < 1e-15, "Wrong cell size");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_cartesian_grid_simplex_mesh() {
+        let grid = CartesianGrid::new(vec![0.0, 0.0, 0.0], vec![1.0, 2.0, 0.3], vec![2, 3, 1]);
+        let mesh = grid.simplex_mesh();
+        assert_eq!(mesh.n_vertices(), 24, "Wrong number of vertices");
+        assert_eq!(mesh.n_cells(), 36, "Wrong number of tetrahedra");
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(3, 1));
+        let volume: f64 = {
+            let mut integrals = vec![0.0; space.n_dofs()];
+            space
+                .assembler(QuadratureRule::simplex(3, 1))
+                .assemble_vector(&mut integrals, |values, local| {
+                    for q in 0..values.n_points() {
+                        for i in 0..values.n_dofs() {
+                            local[i] += values.shape_value(q, i) * values.weight(q);
+                        }
+                    }
+                });
+            integrals.iter().sum()
+        };
+        assert!(
+            (volume - 0.6).abs() < 1e-14,
+            "Simplices should fill the box"
+        );
+        // Two triangles on every face cell of the upper side in y
+        let upper_y = mesh.facet_tags().filter(|(_, tag)| *tag == 4).count();
+        assert_eq!(upper_y, 4, "Wrong number of tagged facets");
+        assert_eq!(
+            Facets::new(&mesh).boundary_facets().len(),
+            2 * (6 + 2 + 3) * 2
+        );
+    }
+}
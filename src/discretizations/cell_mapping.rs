@@ -0,0 +1,379 @@
+use super::mesh::Mesh;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Affine mapping from the reference simplex to a physical cell of a mesh
+///
+/// The mapping is x = x0 + J xi where x0 is the first vertex of the cell and the columns of the
+/// jacobian J are the edges going from the first vertex to the others. The jacobian is stored row
+/// first (J[i][j] = dx_i / dxi_j) and has as many rows as the geometric dimension and as many
+/// columns as the topological dimension.
+///
+/// Cells of a lower dimension than the space (curves in 2 or 3 dimensions, surfaces in 3
+/// dimensions) use the pseudo inverse (J^T J)^-1 J^T in place of the inverse and the square root of
+/// the Gram determinant det(J^T J) as the (positive) determinant. Physical gradients are then the
+/// tangential gradients along the cell and physical points are projected onto the plane of the cell
+/// by the inverse mapping.
+pub struct CellMapping {
+    dim: usize,
+    topological_dim: usize,
+    origin: Vec<f64>,
+    jacobian: Vec<f64>,
+    inverse: Vec<f64>,
+    determinant: f64,
+}
+
+impl CellMapping {
+    /// Build the mapping of a cell of the mesh
+    pub fn new(mesh: &Mesh, cell: usize) -> Self {
+        let (dim, topological_dim) = (mesh.geometric_dim(), mesh.topological_dim());
+        assert!(
+            topological_dim >= 1 && topological_dim <= dim && dim <= 3,
+            "CellMapping is only implemented for cells of dimension 1, 2 or 3 in at most 3 dimensions"
+        );
+        let mut mapping = CellMapping {
+            dim,
+            topological_dim,
+            origin: vec![0.0; dim],
+            jacobian: vec![0.0; dim * topological_dim],
+            inverse: vec![0.0; topological_dim * dim],
+            determinant: 0.0,
+        };
+        mapping.reinit(mesh, cell);
+        mapping
+    }
+
+    /// Recompute the mapping for another cell of the mesh
+    pub fn reinit(&mut self, mesh: &Mesh, cell: usize) {
+        let (dim, tdim) = (self.dim, self.topological_dim);
+        let vertices = mesh.cell(cell);
+        self.origin.copy_from_slice(mesh.vertex(vertices[0]));
+        for j in 0..tdim {
+            let vertex = mesh.vertex(vertices[j + 1]);
+            for (i, (x, x0)) in vertex.iter().zip(self.origin.iter()).enumerate() {
+                self.jacobian[i * tdim + j] = x - x0;
+            }
+        }
+        if tdim == dim {
+            self.determinant = invert(dim, &self.jacobian, &mut self.inverse);
+        } else {
+            // Pseudo inverse from the inverse of the metric tensor J^T J
+            let mut metric = vec![0.0; tdim * tdim];
+            for a in 0..tdim {
+                for b in 0..tdim {
+                    metric[a * tdim + b] = (0..dim)
+                        .map(|i| self.jacobian[i * tdim + a] * self.jacobian[i * tdim + b])
+                        .sum();
+                }
+            }
+            let mut metric_inverse = vec![0.0; tdim * tdim];
+            let gram = invert(tdim, &metric, &mut metric_inverse);
+            self.determinant = gram.max(0.0).sqrt();
+            for a in 0..tdim {
+                for i in 0..dim {
+                    self.inverse[a * dim + i] = (0..tdim)
+                        .map(|b| metric_inverse[a * tdim + b] * self.jacobian[i * tdim + b])
+                        .sum();
+                }
+            }
+        }
+        assert!(
+            self.determinant != 0.0,
+            "Tried to map a degenerate cell {}",
+            cell
+        );
+    }
+
+    /// Dimension of the physical space
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Dimension of the reference cell
+    pub fn topological_dim(&self) -> usize {
+        self.topological_dim
+    }
+
+    /// Signed determinant of the jacobian, or the measure ratio sqrt(det(J^T J)) for cells of a
+    /// lower dimension than the space
+    pub fn determinant(&self) -> f64 {
+        self.determinant
+    }
+
+    /// Jacobian of the mapping of size (geometric dimension, topological dimension) stored row first
+    pub fn jacobian(&self) -> &[f64] {
+        &self.jacobian
+    }
+
+    /// Inverse (or pseudo inverse) of the jacobian of size (topological dimension, geometric
+    /// dimension) stored row first
+    pub fn inverse_jacobian(&self) -> &[f64] {
+        &self.inverse
+    }
+
+    /// Map a reference point to the physical cell
+    pub fn map_point(&self, reference: &[f64], physical: &mut [f64]) {
+        let tdim = self.topological_dim;
+        for (i, x) in physical.iter_mut().enumerate().take(self.dim) {
+            *x = self.origin[i]
+                + (0..tdim)
+                    .map(|j| self.jacobian[i * tdim + j] * reference[j])
+                    .sum::<f64>();
+        }
+    }
+
+    /// Map a physical point back to the reference cell
+    pub fn inverse_map_point(&self, physical: &[f64], reference: &mut [f64]) {
+        let dim = self.dim;
+        for (i, xi) in reference.iter_mut().enumerate().take(self.topological_dim) {
+            *xi = (0..dim)
+                .map(|j| self.inverse[i * dim + j] * (physical[j] - self.origin[j]))
+                .sum();
+        }
+    }
+
+    /// Transform a gradient with respect to the reference coordinates into a physical gradient
+    ///
+    /// The physical gradient is J^-T times the reference gradient (the tangential gradient for
+    /// cells of a lower dimension than the space).
+    pub fn map_gradient(&self, reference: &[f64], physical: &mut [f64]) {
+        let dim = self.dim;
+        for (i, g) in physical.iter_mut().enumerate().take(dim) {
+            *g = (0..self.topological_dim)
+                .map(|j| self.inverse[j * dim + i] * reference[j])
+                .sum();
+        }
+    }
+
+    /// Contravariant Piola transformation of a reference vector field value (H(div) elements)
+    ///
+    /// The physical value is J v / |det J| which preserves the normal fluxes through the facets.
+    pub fn contravariant_piola(&self, reference: &[f64], physical: &mut [f64]) {
+        let tdim = self.topological_dim;
+        let scale = 1.0 / self.determinant.abs();
+        for (i, v) in physical.iter_mut().enumerate().take(self.dim) {
+            *v = scale
+                * (0..tdim)
+                    .map(|j| self.jacobian[i * tdim + j] * reference[j])
+                    .sum::<f64>();
+        }
+    }
+
+    /// Covariant Piola transformation of a reference vector field value (H(curl) elements)
+    ///
+    /// The physical value is J^-T v which preserves the tangential moments along the edges.
+    pub fn covariant_piola(&self, reference: &[f64], physical: &mut [f64]) {
+        self.map_gradient(reference, physical);
+    }
+
+    /// Unit normal of a cell of codimension one (a curve in 2 dimensions or a surface in 3)
+    ///
+    /// The normal of a curve is its tangent turned clockwise, the one of a surface is the cross
+    /// product of its first two edges, so that counterclockwise loops and outward oriented surface
+    /// meshes of closed domains get outward normals.
+    pub fn normal(&self, normal: &mut [f64]) {
+        assert!(
+            self.topological_dim + 1 == self.dim,
+            "Normals are only defined for cells of codimension one"
+        );
+        let j = &self.jacobian;
+        match self.dim {
+            2 => normal[..2].copy_from_slice(&[j[1], -j[0]]),
+            _ => normal[..3].copy_from_slice(&[
+                j[2] * j[5] - j[4] * j[3],
+                j[4] * j[1] - j[0] * j[5],
+                j[0] * j[3] - j[2] * j[1],
+            ]),
+        }
+        let norm = normal.iter().map(|n| n * n).sum::<f64>().sqrt();
+        normal.iter_mut().for_each(|n| *n /= norm);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Invert a small row first matrix returning its determinant
+pub(crate) fn invert(dim: usize, m: &[f64], inv: &mut [f64]) -> f64 {
+    match dim {
+        1 => {
+            inv[0] = 1.0 / m[0];
+            m[0]
+        }
+        2 => {
+            let det = m[0] * m[3] - m[1] * m[2];
+            inv[0] = m[3] / det;
+            inv[1] = -m[1] / det;
+            inv[2] = -m[2] / det;
+            inv[3] = m[0] / det;
+            det
+        }
+        3 => {
+            let cof = [
+                m[4] * m[8] - m[5] * m[7],
+                m[5] * m[6] - m[3] * m[8],
+                m[3] * m[7] - m[4] * m[6],
+                m[2] * m[7] - m[1] * m[8],
+                m[0] * m[8] - m[2] * m[6],
+                m[1] * m[6] - m[0] * m[7],
+                m[1] * m[5] - m[2] * m[4],
+                m[2] * m[3] - m[0] * m[5],
+                m[0] * m[4] - m[1] * m[3],
+            ];
+            let det = m[0] * cof[0] + m[1] * cof[1] + m[2] * cof[2];
+            // The inverse is the transposed cofactor matrix over the determinant
+            for i in 0..3 {
+                for j in 0..3 {
+                    inv[i * 3 + j] = cof[j * 3 + i] / det;
+                }
+            }
+            det
+        }
+        _ => panic!("Can only invert matrices of dimension 1, 2 or 3"),
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::data_hold::DataHold;
+
+    fn build_tetrahedron() -> Mesh {
+        Mesh::new(
+            DataHold::new(
+                vec![1.0, 0.0, 0.0, 3.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.5, 4.0],
+                [4, 3],
+            ),
+            DataHold::new(vec![0, 1, 2, 3], [1, 4]),
+        )
+    }
+
+    #[test]
+    fn test_cell_mapping_determinant() {
+        let mapping = CellMapping::new(&build_tetrahedron(), 0);
+        assert!(
+            (mapping.determinant() - 8.0).abs() < 1e-14,
+            "Wrong determinant for the tetrahedron"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_cell_mapping_points() {
+        let mapping = CellMapping::new(&build_tetrahedron(), 0);
+        let mut physical = [0.0; 3];
+        mapping.map_point(&[0.0, 0.0, 1.0], &mut physical);
+        assert_eq!(
+            physical,
+            [1.0, 0.5, 4.0],
+            "Last vertex was not mapped correctly"
+        );
+        let mut reference = [0.0; 3];
+        mapping.map_point(&[0.2, 0.3, 0.1], &mut physical);
+        mapping.inverse_map_point(&physical, &mut reference);
+        for (r, e) in reference.iter().zip([0.2, 0.3, 0.1].iter()) {
+            assert!((r - e).abs() < 1e-14, "Inverse mapping is not consistent");
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_cell_mapping_gradient() {
+        // The gradient of the reference coordinate xi_0 is the first row of the inverse jacobian
+        let mapping = CellMapping::new(&build_tetrahedron(), 0);
+        let mut physical = [0.0; 3];
+        mapping.map_gradient(&[1.0, 0.0, 0.0], &mut physical);
+        let inverse = mapping.inverse_jacobian();
+        for i in 0..3 {
+            assert!(
+                (physical[i] - inverse[i]).abs() < 1e-14,
+                "Wrong physical gradient"
+            );
+        }
+        let mut identity = [0.0; 9];
+        let jacobian = mapping.jacobian();
+        for i in 0..3 {
+            for j in 0..3 {
+                identity[i * 3 + j] = (0..3)
+                    .map(|k| jacobian[i * 3 + k] * inverse[k * 3 + j])
+                    .sum();
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!(
+                    (identity[i * 3 + j] - expected).abs() < 1e-14,
+                    "Inverse jacobian is wrong"
+                );
+            }
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_cell_mapping_piola() {
+        // Contravariant and covariant transformations are dual up to the determinant
+        let mapping = CellMapping::new(&build_tetrahedron(), 0);
+        let (v, w) = ([0.3, -1.0, 2.0], [1.5, 0.5, -0.2]);
+        let mut pv = [0.0; 3];
+        let mut pw = [0.0; 3];
+        mapping.contravariant_piola(&v, &mut pv);
+        mapping.covariant_piola(&w, &mut pw);
+        let dot =
+            |a: &[f64], b: &[f64]| -> f64 { a.iter().zip(b.iter()).map(|(x, y)| x * y).sum() };
+        assert!(
+            (dot(&pv, &pw) - dot(&v, &w) / 8.0).abs() < 1e-14,
+            "Piola transformations are not dual"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_cell_mapping_surface() {
+        // Triangle of the plane z = x + y in 3 dimensions
+        let mesh = Mesh::new(
+            DataHold::new(vec![0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 2.0, 2.0], [3, 3]),
+            DataHold::new(vec![0, 1, 2], [1, 3]),
+        );
+        let mapping = CellMapping::new(&mesh, 0);
+        assert_eq!(mapping.topological_dim(), 2, "Wrong topological dimension");
+        assert!(
+            (mapping.determinant() - 2.0 * 3.0_f64.sqrt()).abs() < 1e-14,
+            "Determinant should be twice the area"
+        );
+        let mut normal = [0.0; 3];
+        mapping.normal(&mut normal);
+        let expected = [-1.0, -1.0, 1.0].map(|n: f64| n / 3.0_f64.sqrt());
+        for (n, e) in normal.iter().zip(expected.iter()) {
+            assert!((n - e).abs() < 1e-14, "Wrong surface normal");
+        }
+        // Points off the surface are projected along the normal
+        let mut reference = [0.0; 2];
+        mapping.inverse_map_point(&[0.25, 0.5, 0.75 + 0.3], &mut reference);
+        let mut physical = [0.0; 3];
+        mapping.map_point(&reference, &mut physical);
+        let offset: Vec<f64> = physical
+            .iter()
+            .zip([0.25, 0.5, 1.05].iter())
+            .map(|(a, b)| b - a)
+            .collect();
+        let along: f64 = offset.iter().zip(normal.iter()).map(|(a, b)| a * b).sum();
+        assert!(
+            (along * along - offset.iter().map(|o| o * o).sum::<f64>()).abs() < 1e-14,
+            "Inverse mapping should project orthogonally"
+        );
+        // Tangential gradient of x is e_x minus its normal component
+        let mut gradient = [0.0; 3];
+        mapping.map_gradient(&[1.0, 0.0], &mut gradient);
+        for (g, e) in gradient
+            .iter()
+            .zip([2.0 / 3.0, -1.0 / 3.0, 1.0 / 3.0].iter())
+        {
+            assert!((g - e).abs() < 1e-14, "Wrong tangential gradient");
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use super::cell_mapping::CellMapping;
+use super::mesh::Mesh;
+
+// Maximum number of cells in a leaf of the tree
+const LEAF_SIZE: usize = 4;
+
+// Tolerance on the barycentric coordinates of a point inside a cell
+const TOLERANCE: f64 = 1e-10;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Bounding volume hierarchy of axis aligned boxes around the cells of a mesh
+///
+/// The tree is built by recursively splitting the cells at the median of their centroids along
+/// the longest side of the box. Point location descends into the boxes containing the point and
+/// checks the barycentric coordinates of the point in the candidate cells.
+pub struct CellTree {
+    dim: usize,
+    cells: Vec<usize>,
+    nodes: Vec<TreeNode>,
+}
+
+// Node of the tree holding its box as (lower corner, upper corner) and either its two children or
+// the range of its cells
+struct TreeNode {
+    bounds: Vec<f64>,
+    children: Option<(usize, usize)>,
+    range: (usize, usize),
+}
+
+impl CellTree {
+    /// Build the tree around the cells of a mesh
+    pub fn new(mesh: &Mesh) -> Self {
+        let dim = mesh.geometric_dim();
+        let n_cells = mesh.n_cells();
+        let mut boxes = Vec::with_capacity(2 * dim * n_cells);
+        let mut centroids = Vec::with_capacity(dim * n_cells);
+        for cell in 0..n_cells {
+            let vertices = mesh.cell(cell);
+            let mut bounds = vec![f64::INFINITY; dim];
+            bounds.extend(vec![f64::NEG_INFINITY; dim]);
+            for v in vertices {
+                for (d, x) in mesh.vertex(*v).iter().enumerate() {
+                    bounds[d] = bounds[d].min(*x);
+                    bounds[dim + d] = bounds[dim + d].max(*x);
+                }
+            }
+            for d in 0..dim {
+                let sum: f64 = vertices.iter().map(|v| mesh.vertex(*v)[d]).sum();
+                centroids.push(sum / vertices.len() as f64);
+            }
+            boxes.extend(bounds);
+        }
+        let mut tree = CellTree {
+            dim,
+            cells: (0..n_cells).collect(),
+            nodes: Vec::new(),
+        };
+        if n_cells > 0 {
+            tree.build(&boxes, &centroids, 0, n_cells);
+        }
+        tree
+    }
+
+    // Build the node holding the cells in the range start..end and return its index
+    fn build(&mut self, boxes: &[f64], centroids: &[f64], start: usize, end: usize) -> usize {
+        let dim = self.dim;
+        let mut bounds = vec![f64::INFINITY; dim];
+        bounds.extend(vec![f64::NEG_INFINITY; dim]);
+        for cell in &self.cells[start..end] {
+            let cell_box = &boxes[2 * dim * cell..2 * dim * (cell + 1)];
+            for d in 0..dim {
+                bounds[d] = bounds[d].min(cell_box[d]);
+                bounds[dim + d] = bounds[dim + d].max(cell_box[dim + d]);
+            }
+        }
+        let index = self.nodes.len();
+        self.nodes.push(TreeNode {
+            bounds,
+            children: None,
+            range: (start, end),
+        });
+        if end - start > LEAF_SIZE {
+            let bounds = &self.nodes[index].bounds;
+            let axis = (0..dim)
+                .max_by(|a, b| {
+                    (bounds[dim + *a] - bounds[*a]).total_cmp(&(bounds[dim + *b] - bounds[*b]))
+                })
+                .unwrap();
+            let middle = (start + end) / 2;
+            self.cells[start..end].select_nth_unstable_by(middle - start, |a, b| {
+                centroids[a * dim + axis].total_cmp(&centroids[b * dim + axis])
+            });
+            let left = self.build(boxes, centroids, start, middle);
+            let right = self.build(boxes, centroids, middle, end);
+            self.nodes[index].children = Some((left, right));
+        }
+        index
+    }
+
+    /// Cells whose bounding box contains the point
+    pub fn candidates(&self, point: &[f64]) -> Vec<usize> {
+        let dim = self.dim;
+        let mut found = Vec::new();
+        if self.nodes.is_empty() {
+            return found;
+        }
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let inside = (0..dim).all(|d| {
+                let tolerance = TOLERANCE * (1.0 + node.bounds[dim + d] - node.bounds[d]);
+                point[d] >= node.bounds[d] - tolerance
+                    && point[d] <= node.bounds[dim + d] + tolerance
+            });
+            if !inside {
+                continue;
+            }
+            match node.children {
+                Some((left, right)) => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+                None => found.extend_from_slice(&self.cells[node.range.0..node.range.1]),
+            }
+        }
+        found
+    }
+
+    /// Cells whose bounding box intersects the box [lower, upper]
+    pub fn overlapping(&self, lower: &[f64], upper: &[f64]) -> Vec<usize> {
+        let dim = self.dim;
+        let mut found = Vec::new();
+        if self.nodes.is_empty() {
+            return found;
+        }
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let intersects = (0..dim).all(|d| {
+                let tolerance = TOLERANCE * (1.0 + node.bounds[dim + d] - node.bounds[d]);
+                upper[d] >= node.bounds[d] - tolerance
+                    && lower[d] <= node.bounds[dim + d] + tolerance
+            });
+            if !intersects {
+                continue;
+            }
+            match node.children {
+                Some((left, right)) => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+                None => found.extend_from_slice(&self.cells[node.range.0..node.range.1]),
+            }
+        }
+        found
+    }
+
+    /// Find a cell of the mesh containing the point and the reference coordinates of the point in it
+    ///
+    /// The mesh has to be the one the tree was built on. On curves and surfaces the point has to lie
+    /// on a cell and not only project onto it.
+    pub fn locate(&self, mesh: &Mesh, point: &[f64]) -> Option<(usize, Vec<f64>)> {
+        let manifold = mesh.topological_dim() < self.dim;
+        let mut reference = vec![0.0; mesh.topological_dim()];
+        let mut projected = vec![0.0; self.dim];
+        let mut mapping: Option<CellMapping> = None;
+        for cell in self.candidates(point) {
+            match mapping.as_mut() {
+                Some(mapping) => mapping.reinit(mesh, cell),
+                None => mapping = Some(CellMapping::new(mesh, cell)),
+            }
+            let mapping = mapping.as_ref().unwrap();
+            mapping.inverse_map_point(point, &mut reference);
+            let sum: f64 = reference.iter().sum();
+            if reference.iter().any(|xi| *xi < -TOLERANCE) || sum > 1.0 + TOLERANCE {
+                continue;
+            }
+            if manifold {
+                mapping.map_point(&reference, &mut projected);
+                let distance = projected
+                    .iter()
+                    .zip(point.iter())
+                    .map(|(a, b)| (a - b) * (a - b))
+                    .sum::<f64>()
+                    .sqrt();
+                let scale = point.iter().map(|x| x * x).sum::<f64>().sqrt();
+                if distance > TOLERANCE * (1.0 + scale) {
+                    continue;
+                }
+            }
+            return Some((cell, reference));
+        }
+        None
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::test_meshes::square_grid;
+
+    #[test]
+    fn test_cell_tree_locate() {
+        let mesh = square_grid(8);
+        let tree = CellTree::new(&mesh);
+        let point = [0.3, 0.66];
+        let (cell, reference) = tree.locate(&mesh, &point).expect("Point was not located");
+        let mut mapped = [0.0; 2];
+        CellMapping::new(&mesh, cell).map_point(&reference, &mut mapped);
+        assert!(
+            (mapped[0] - point[0]).abs() < 1e-14 && (mapped[1] - point[1]).abs() < 1e-14,
+            "Reference coordinates do not map back to the point"
+        );
+        // Lower left triangle of the square (2, 5)
+        assert_eq!(cell, 2 * (5 * 8 + 2), "Wrong cell");
+        assert!(
+            tree.candidates(&point).len() < mesh.n_cells(),
+            "Tree should prune cells"
+        );
+        assert!(
+            tree.locate(&mesh, &[1.5, 0.5]).is_none(),
+            "Point outside of the mesh was located"
+        );
+        assert!(
+            tree.locate(&mesh, &[1.0, 1.0]).is_some(),
+            "Corner of the mesh was not located"
+        );
+        let overlapping = tree.overlapping(&[0.3, 0.3], &[0.4, 0.4]);
+        assert!(
+            !overlapping.contains(&cell) && overlapping.contains(&(2 * (2 * 8 + 2))),
+            "Wrong cells overlapping a box"
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use super::cell_mapping::CellMapping;
+use super::mesh::Mesh;
+use crate::spaces::lagrange::LagrangeElement;
+use crate::spaces::quadrature::QuadratureRule;
+use std::f64::consts::PI;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Coordinate system of the physical coordinates of a 2-D mesh
+///
+/// Axisymmetric meshes are the meridian half plane (r, z) with r >= 0 of a body of revolution:
+/// integration weights carry the 2 pi r of the volume element so that integrals are taken over the
+/// whole body (and facet integrals over surfaces of revolution). Scalar operators need nothing else,
+/// the divergence and strains of vector fields get the hoop terms u_r / r added by the workflows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoordinateSystem {
+    /// Plain cartesian coordinates
+    Cartesian,
+    /// Radial and axial coordinates (r, z) of a body of revolution
+    Axisymmetric,
+}
+
+/// Structure holding the values of the shape functions of an element at the quadrature points of a
+/// physical cell
+///
+/// The reference values are computed once at construction and the physical quantities (gradients,
+/// integration weights and points) are updated for every cell by reinit. Physical points and
+/// gradients have the geometric dimension of the mesh, which exceeds the dimension of the element on
+/// curves and surfaces where the gradients are tangential.
+pub struct CellValues {
+    dim: usize,
+    geometric_dim: usize,
+    coordinate_system: CoordinateSystem,
+    n_dofs: usize,
+    cell: usize,
+    reference_points: Vec<f64>,
+    reference_weights: Vec<f64>,
+    reference_gradients: Vec<f64>,
+    values: Vec<f64>,
+    gradients: Vec<f64>,
+    weights: Vec<f64>,
+    points: Vec<f64>,
+    mapping: Option<CellMapping>,
+}
+
+impl CellValues {
+    /// Evaluate the shape functions of an element at the points of a quadrature rule
+    pub fn new(element: &LagrangeElement, quadrature: &QuadratureRule) -> Self {
+        let dim = element.dim();
+        assert!(
+            quadrature.dim() == dim,
+            "Quadrature and element dimensions do not match"
+        );
+        let n_dofs = element.n_dofs();
+        let n_points = quadrature.n_points();
+        let mut reference_points = Vec::with_capacity(n_points * dim);
+        let mut values = vec![0.0; n_points * n_dofs];
+        let mut reference_gradients = vec![0.0; n_points * n_dofs * dim];
+        for q in 0..n_points {
+            let point = quadrature.point(q);
+            reference_points.extend_from_slice(point);
+            element.values(point, &mut values[q * n_dofs..(q + 1) * n_dofs]);
+            element.gradients(
+                point,
+                &mut reference_gradients[q * n_dofs * dim..(q + 1) * n_dofs * dim],
+            );
+        }
+        CellValues {
+            dim,
+            geometric_dim: dim,
+            coordinate_system: CoordinateSystem::Cartesian,
+            n_dofs,
+            cell: 0,
+            reference_points,
+            reference_weights: quadrature.weights().to_vec(),
+            gradients: reference_gradients.clone(),
+            reference_gradients,
+            values,
+            weights: quadrature.weights().to_vec(),
+            points: vec![0.0; n_points * dim],
+            mapping: None,
+        }
+    }
+
+    /// Change the coordinate system the integration weights are computed in
+    pub fn set_coordinate_system(&mut self, coordinate_system: CoordinateSystem) {
+        self.coordinate_system = coordinate_system;
+    }
+
+    /// Update the physical quantities for a cell of the mesh
+    pub fn reinit(&mut self, mesh: &Mesh, cell: usize) {
+        if mesh.geometric_dim() != self.geometric_dim {
+            self.geometric_dim = mesh.geometric_dim();
+            self.gradients = vec![0.0; self.n_points() * self.n_dofs * self.geometric_dim];
+            self.points = vec![0.0; self.n_points() * self.geometric_dim];
+            self.mapping = None;
+        }
+        match self.mapping.as_mut() {
+            Some(mapping) => mapping.reinit(mesh, cell),
+            None => self.mapping = Some(CellMapping::new(mesh, cell)),
+        }
+        let mapping = self.mapping.as_ref().unwrap();
+        let (dim, geometric_dim) = (self.dim, self.geometric_dim);
+        let det = mapping.determinant().abs();
+        for q in 0..self.n_points() {
+            self.weights[q] = self.reference_weights[q] * det;
+            mapping.map_point(
+                &self.reference_points[q * dim..(q + 1) * dim],
+                &mut self.points[q * geometric_dim..(q + 1) * geometric_dim],
+            );
+        }
+        if self.coordinate_system == CoordinateSystem::Axisymmetric {
+            axisymmetric_weights(geometric_dim, &self.points, &mut self.weights);
+        }
+        for (reference, physical) in self
+            .reference_gradients
+            .chunks(dim)
+            .zip(self.gradients.chunks_mut(geometric_dim))
+        {
+            mapping.map_gradient(reference, physical);
+        }
+        self.cell = cell;
+    }
+
+    /// Cell the values were last computed on
+    pub fn cell(&self) -> usize {
+        self.cell
+    }
+
+    /// Mapping of the cell the values were last computed on
+    pub fn mapping(&self) -> &CellMapping {
+        self.mapping
+            .as_ref()
+            .expect("CellValues were never initialized on a cell")
+    }
+
+    /// Dimension of the cell
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Dimension of the physical points and gradients
+    pub fn geometric_dim(&self) -> usize {
+        self.geometric_dim
+    }
+
+    /// Coordinate system of the integration weights
+    pub fn coordinate_system(&self) -> CoordinateSystem {
+        self.coordinate_system
+    }
+
+    /// Number of quadrature points
+    pub fn n_points(&self) -> usize {
+        self.reference_weights.len()
+    }
+
+    /// Number of shape functions
+    pub fn n_dofs(&self) -> usize {
+        self.n_dofs
+    }
+
+    /// Value of shape function i at quadrature point q
+    pub fn shape_value(&self, q: usize, i: usize) -> f64 {
+        self.values[q * self.n_dofs + i]
+    }
+
+    /// Physical gradient of shape function i at quadrature point q
+    pub fn shape_gradient(&self, q: usize, i: usize) -> &[f64] {
+        let start = (q * self.n_dofs + i) * self.geometric_dim;
+        &self.gradients[start..start + self.geometric_dim]
+    }
+
+    /// Integration weight at quadrature point q including the jacobian determinant (and 2 pi r in
+    /// axisymmetric coordinates)
+    pub fn weight(&self, q: usize) -> f64 {
+        self.weights[q]
+    }
+
+    /// Physical coordinates of quadrature point q
+    pub fn point(&self, q: usize) -> &[f64] {
+        &self.points[q * self.geometric_dim..(q + 1) * self.geometric_dim]
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Multiply the weights by the circumference 2 pi r of the circles the points sweep
+pub(crate) fn axisymmetric_weights(geometric_dim: usize, points: &[f64], weights: &mut [f64]) {
+    assert!(
+        geometric_dim == 2,
+        "Axisymmetric coordinates need a mesh of the (r, z) half plane"
+    );
+    for (weight, point) in weights.iter_mut().zip(points.chunks(2)) {
+        *weight *= 2.0 * PI * point[0];
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::data_hold::DataHold;
+
+    #[test]
+    fn test_cell_values_reinit() {
+        let mesh = Mesh::new(
+            DataHold::new(vec![0.0, 0.0, 2.0, 0.0, 0.0, 2.0], [3, 2]),
+            DataHold::new(vec![0, 1, 2], [1, 3]),
+        );
+        let element = LagrangeElement::new(2, 1);
+        let mut values = CellValues::new(&element, &QuadratureRule::simplex(2, 2));
+        values.reinit(&mesh, 0);
+        let area: f64 = (0..values.n_points()).map(|q| values.weight(q)).sum();
+        assert!((area - 2.0).abs() < 1e-14, "Wrong cell area");
+        // Shape function 1 is x / 2
+        for q in 0..values.n_points() {
+            assert!(
+                (values.shape_value(q, 1) - values.point(q)[0] / 2.0).abs() < 1e-14,
+                "Shape value does not match the physical point"
+            );
+            let grad = values.shape_gradient(q, 1);
+            assert!(
+                (grad[0] - 0.5).abs() < 1e-14 && grad[1].abs() < 1e-14,
+                "Wrong physical gradient"
+            );
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_cell_values_axisymmetric() {
+        // Triangle of the (r, z) half plane sweeping a cone of radius and height 2
+        let mesh = Mesh::new(
+            DataHold::new(vec![0.0, 0.0, 2.0, 0.0, 0.0, 2.0], [3, 2]),
+            DataHold::new(vec![0, 1, 2], [1, 3]),
+        );
+        let element = LagrangeElement::new(2, 1);
+        let mut values = CellValues::new(&element, &QuadratureRule::simplex(2, 2));
+        values.set_coordinate_system(CoordinateSystem::Axisymmetric);
+        values.reinit(&mesh, 0);
+        let volume: f64 = (0..values.n_points()).map(|q| values.weight(q)).sum();
+        assert!(
+            (volume - 8.0 * PI / 3.0).abs() < 1e-13,
+            "Wrong volume of the cone"
+        );
+    }
+}
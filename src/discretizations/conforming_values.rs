@@ -0,0 +1,473 @@
+use super::cell_mapping::CellMapping;
+use super::facets::Facets;
+use super::mesh::Mesh;
+use crate::spaces::nedelec::NedelecElement;
+use crate::spaces::quadrature::QuadratureRule;
+use crate::spaces::raviart_thomas::RaviartThomasElement;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Structure holding the values and divergences of Raviart-Thomas shape functions at the quadrature
+/// points of a physical cell
+///
+/// Shape functions are mapped with the contravariant Piola transformation and multiplied by the
+/// orientation of their facet: a facet is oriented by the outward normal of the first cell
+/// touching it in Facets so that the normal component of the global shape functions is continuous.
+pub struct HDivValues {
+    dim: usize,
+    n_dofs: usize,
+    cell: usize,
+    reference_points: Vec<f64>,
+    reference_weights: Vec<f64>,
+    reference_values: Vec<f64>,
+    reference_divergences: Vec<f64>,
+    values: Vec<f64>,
+    divergences: Vec<f64>,
+    weights: Vec<f64>,
+    points: Vec<f64>,
+    signs: Vec<f64>,
+    mapping: Option<CellMapping>,
+}
+
+/// Structure holding the values and curls of Nédélec shape functions at the quadrature points of a
+/// physical cell
+///
+/// Shape functions are mapped with the covariant Piola transformation and multiplied by the
+/// orientation of their edge: an edge is oriented from its lower to its higher global vertex so
+/// that the tangential component of the global shape functions is continuous.
+pub struct HCurlValues {
+    dim: usize,
+    curl_dim: usize,
+    edges: Vec<(usize, usize)>,
+    cell: usize,
+    reference_points: Vec<f64>,
+    reference_weights: Vec<f64>,
+    reference_values: Vec<f64>,
+    reference_curls: Vec<f64>,
+    values: Vec<f64>,
+    curls: Vec<f64>,
+    weights: Vec<f64>,
+    points: Vec<f64>,
+    mapping: Option<CellMapping>,
+}
+
+impl HDivValues {
+    /// Evaluate the shape functions of an element at the points of a quadrature rule
+    pub fn new(element: &RaviartThomasElement, quadrature: &QuadratureRule) -> Self {
+        let dim = element.dim();
+        assert!(
+            quadrature.dim() == dim,
+            "Quadrature and element dimensions do not match"
+        );
+        let n_dofs = element.n_dofs();
+        let n_points = quadrature.n_points();
+        let (reference_points, reference_values) =
+            reference_values(quadrature, n_dofs * dim, |p, v| element.values(p, v));
+        let mut reference_divergences = vec![0.0; n_dofs];
+        element.divergences(&mut reference_divergences);
+        HDivValues {
+            dim,
+            n_dofs,
+            cell: 0,
+            reference_points,
+            reference_weights: quadrature.weights().to_vec(),
+            values: reference_values.clone(),
+            reference_values,
+            divergences: reference_divergences.clone(),
+            reference_divergences,
+            weights: quadrature.weights().to_vec(),
+            points: vec![0.0; n_points * dim],
+            signs: vec![1.0; n_dofs],
+            mapping: None,
+        }
+    }
+
+    /// Update the physical quantities for a cell of the mesh
+    pub fn reinit(&mut self, mesh: &Mesh, facets: &Facets, cell: usize) {
+        let dim = self.dim;
+        let vertices = mesh.cell(cell);
+        for (local, sign) in self.signs.iter_mut().enumerate() {
+            let facet: Vec<usize> = vertices
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != local)
+                .map(|(_, v)| *v)
+                .collect();
+            let facet = facets
+                .facet_index(&facet)
+                .expect("Facets do not belong to the mesh");
+            *sign = if facets.facet_cells(facet)[0].0 == cell {
+                1.0
+            } else {
+                -1.0
+            };
+        }
+        let mapping = reinit_mapping(&mut self.mapping, mesh, cell);
+        let det = mapping.determinant().abs();
+        update_points(
+            mapping,
+            &self.reference_points,
+            &self.reference_weights,
+            &mut self.points,
+            &mut self.weights,
+        );
+        for (q_values, q_reference) in self
+            .values
+            .chunks_mut(self.n_dofs * dim)
+            .zip(self.reference_values.chunks(self.n_dofs * dim))
+        {
+            for ((value, reference), sign) in q_values
+                .chunks_mut(dim)
+                .zip(q_reference.chunks(dim))
+                .zip(self.signs.iter())
+            {
+                mapping.contravariant_piola(reference, value);
+                value.iter_mut().for_each(|v| *v *= sign);
+            }
+        }
+        for ((divergence, reference), sign) in self
+            .divergences
+            .iter_mut()
+            .zip(self.reference_divergences.iter())
+            .zip(self.signs.iter())
+        {
+            *divergence = sign * reference / det;
+        }
+        self.cell = cell;
+    }
+
+    /// Cell the values were last computed on
+    pub fn cell(&self) -> usize {
+        self.cell
+    }
+
+    /// Dimension of the cell
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Number of quadrature points
+    pub fn n_points(&self) -> usize {
+        self.reference_weights.len()
+    }
+
+    /// Number of shape functions
+    pub fn n_dofs(&self) -> usize {
+        self.n_dofs
+    }
+
+    /// Orientations of the facets of the cell
+    pub fn signs(&self) -> &[f64] {
+        &self.signs
+    }
+
+    /// Value of shape function i at quadrature point q
+    pub fn shape_value(&self, q: usize, i: usize) -> &[f64] {
+        let start = (q * self.n_dofs + i) * self.dim;
+        &self.values[start..start + self.dim]
+    }
+
+    /// Divergence of shape function i (constant on the cell)
+    pub fn shape_divergence(&self, i: usize) -> f64 {
+        self.divergences[i]
+    }
+
+    /// Integration weight at quadrature point q including the jacobian determinant
+    pub fn weight(&self, q: usize) -> f64 {
+        self.weights[q]
+    }
+
+    /// Physical coordinates of quadrature point q
+    pub fn point(&self, q: usize) -> &[f64] {
+        &self.points[q * self.dim..(q + 1) * self.dim]
+    }
+}
+
+impl HCurlValues {
+    /// Evaluate the shape functions of an element at the points of a quadrature rule
+    pub fn new(element: &NedelecElement, quadrature: &QuadratureRule) -> Self {
+        let dim = element.dim();
+        assert!(
+            quadrature.dim() == dim,
+            "Quadrature and element dimensions do not match"
+        );
+        let n_dofs = element.n_dofs();
+        let n_points = quadrature.n_points();
+        let (reference_points, reference_values) =
+            reference_values(quadrature, n_dofs * dim, |p, v| element.values(p, v));
+        let mut reference_curls = vec![0.0; n_dofs * element.curl_dim()];
+        element.curls(&mut reference_curls);
+        HCurlValues {
+            dim,
+            curl_dim: element.curl_dim(),
+            edges: element.edges().to_vec(),
+            cell: 0,
+            reference_points,
+            reference_weights: quadrature.weights().to_vec(),
+            values: reference_values.clone(),
+            reference_values,
+            curls: reference_curls.clone(),
+            reference_curls,
+            weights: quadrature.weights().to_vec(),
+            points: vec![0.0; n_points * dim],
+            mapping: None,
+        }
+    }
+
+    /// Update the physical quantities for a cell of the mesh
+    pub fn reinit(&mut self, mesh: &Mesh, cell: usize) {
+        let dim = self.dim;
+        let n_dofs = self.edges.len();
+        let vertices = mesh.cell(cell);
+        let signs: Vec<f64> = self
+            .edges
+            .iter()
+            .map(|(a, b)| {
+                if vertices[*a] < vertices[*b] {
+                    1.0
+                } else {
+                    -1.0
+                }
+            })
+            .collect();
+        let mapping = reinit_mapping(&mut self.mapping, mesh, cell);
+        update_points(
+            mapping,
+            &self.reference_points,
+            &self.reference_weights,
+            &mut self.points,
+            &mut self.weights,
+        );
+        for (q_values, q_reference) in self
+            .values
+            .chunks_mut(n_dofs * dim)
+            .zip(self.reference_values.chunks(n_dofs * dim))
+        {
+            for ((value, reference), sign) in q_values
+                .chunks_mut(dim)
+                .zip(q_reference.chunks(dim))
+                .zip(signs.iter())
+            {
+                mapping.covariant_piola(reference, value);
+                value.iter_mut().for_each(|v| *v *= sign);
+            }
+        }
+        // The curl transforms as a scalar density in 2D and contravariantly in 3D
+        let det = mapping.determinant();
+        for ((curl, reference), sign) in self
+            .curls
+            .chunks_mut(self.curl_dim)
+            .zip(self.reference_curls.chunks(self.curl_dim))
+            .zip(signs.iter())
+        {
+            if self.curl_dim == 1 {
+                curl[0] = sign * reference[0] / det;
+            } else {
+                mapping.contravariant_piola(reference, curl);
+                let scale = sign * det.abs() / det;
+                curl.iter_mut().for_each(|c| *c *= scale);
+            }
+        }
+        self.cell = cell;
+    }
+
+    /// Cell the values were last computed on
+    pub fn cell(&self) -> usize {
+        self.cell
+    }
+
+    /// Dimension of the cell
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Number of components of the curl (1 in 2 dimensions and 3 in 3 dimensions)
+    pub fn curl_dim(&self) -> usize {
+        self.curl_dim
+    }
+
+    /// Number of quadrature points
+    pub fn n_points(&self) -> usize {
+        self.reference_weights.len()
+    }
+
+    /// Number of shape functions
+    pub fn n_dofs(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// Value of shape function i at quadrature point q
+    pub fn shape_value(&self, q: usize, i: usize) -> &[f64] {
+        let start = (q * self.n_dofs() + i) * self.dim;
+        &self.values[start..start + self.dim]
+    }
+
+    /// Curl of shape function i (constant on the cell)
+    pub fn shape_curl(&self, i: usize) -> &[f64] {
+        &self.curls[i * self.curl_dim..(i + 1) * self.curl_dim]
+    }
+
+    /// Integration weight at quadrature point q including the jacobian determinant
+    pub fn weight(&self, q: usize) -> f64 {
+        self.weights[q]
+    }
+
+    /// Physical coordinates of quadrature point q
+    pub fn point(&self, q: usize) -> &[f64] {
+        &self.points[q * self.dim..(q + 1) * self.dim]
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Gather the points of a rule and evaluate values of size stride at each of them
+fn reference_values<Evaluate>(
+    quadrature: &QuadratureRule,
+    stride: usize,
+    evaluate: Evaluate,
+) -> (Vec<f64>, Vec<f64>)
+where
+    Evaluate: Fn(&[f64], &mut [f64]),
+{
+    let n_points = quadrature.n_points();
+    let mut points = Vec::with_capacity(n_points * quadrature.dim());
+    let mut values = vec![0.0; n_points * stride];
+    for q in 0..n_points {
+        points.extend_from_slice(quadrature.point(q));
+        evaluate(
+            quadrature.point(q),
+            &mut values[q * stride..(q + 1) * stride],
+        );
+    }
+    (points, values)
+}
+
+fn reinit_mapping<'m>(
+    mapping: &'m mut Option<CellMapping>,
+    mesh: &Mesh,
+    cell: usize,
+) -> &'m CellMapping {
+    match mapping.as_mut() {
+        Some(mapping) => mapping.reinit(mesh, cell),
+        None => *mapping = Some(CellMapping::new(mesh, cell)),
+    }
+    mapping.as_ref().unwrap()
+}
+
+fn update_points(
+    mapping: &CellMapping,
+    reference_points: &[f64],
+    reference_weights: &[f64],
+    points: &mut [f64],
+    weights: &mut [f64],
+) {
+    let dim = mapping.dim();
+    let det = mapping.determinant().abs();
+    for (q, weight) in weights.iter_mut().enumerate() {
+        *weight = reference_weights[q] * det;
+        mapping.map_point(
+            &reference_points[q * dim..(q + 1) * dim],
+            &mut points[q * dim..(q + 1) * dim],
+        );
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::data_hold::DataHold;
+    use crate::discretizations::dof_map::DofMap;
+    use crate::discretizations::test_meshes::unit_square;
+
+    // Both cells see the midpoint of the diagonal at the reference point (0.5, 0.5)
+    fn midpoint_rule() -> QuadratureRule {
+        QuadratureRule::new(DataHold::new(vec![0.5, 0.5], [1, 2]), vec![1.0])
+    }
+
+    // Combination of the shape functions of a cell with global coefficients
+    fn combine(values: &[&[f64]], dofs: &[usize], coefficients: &[f64]) -> [f64; 2] {
+        let mut result = [0.0; 2];
+        for (value, dof) in values.iter().zip(dofs.iter()) {
+            result[0] += coefficients[*dof] * value[0];
+            result[1] += coefficients[*dof] * value[1];
+        }
+        result
+    }
+
+    #[test]
+    fn test_hdiv_values_normal_continuity() {
+        let mesh = unit_square();
+        let facets = Facets::new(&mesh);
+        let element = RaviartThomasElement::new(2);
+        let dof_map = DofMap::facets(&mesh, &facets);
+        let coefficients = [1.0, -2.0, 3.0, 0.5, 4.0];
+        let mut values = HDivValues::new(&element, &midpoint_rule());
+        let mut sides = Vec::new();
+        for cell in 0..2 {
+            values.reinit(&mesh, &facets, cell);
+            let shapes: Vec<&[f64]> = (0..3).map(|i| values.shape_value(0, i)).collect();
+            sides.push(combine(&shapes, dof_map.cell_dofs(cell), &coefficients));
+        }
+        let jump = (sides[0][0] - sides[1][0]) + (sides[0][1] - sides[1][1]);
+        assert!(jump.abs() < 1e-14, "Normal component should be continuous");
+        // Divergence theorem: the outward fluxes of the shape functions are their orientations
+        let mut values = HDivValues::new(&element, &QuadratureRule::simplex(2, 1));
+        values.reinit(&mesh, &facets, 1);
+        for i in 0..3 {
+            let flux: f64 = (0..values.n_points())
+                .map(|q| values.shape_divergence(i) * values.weight(q))
+                .sum();
+            assert!(
+                (flux - values.signs()[i]).abs() < 1e-14,
+                "Wrong flux of shape function {}",
+                i
+            );
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_hcurl_values_constant_field() {
+        let mesh = unit_square();
+        let element = NedelecElement::new(2);
+        let dof_map = DofMap::edges(&mesh, element.edges());
+        // Degrees of freedom of the constant field c are c . (x_q - x_p) for edges p < q
+        let c = [2.0, -1.0];
+        let mut coefficients = vec![0.0; dof_map.n_dofs()];
+        for cell in 0..mesh.n_cells() {
+            let vertices = mesh.cell(cell);
+            for ((a, b), dof) in element.edges().iter().zip(dof_map.cell_dofs(cell)) {
+                let (p, q) = (
+                    vertices[*a].min(vertices[*b]),
+                    vertices[*a].max(vertices[*b]),
+                );
+                coefficients[*dof] = (0..2)
+                    .map(|k| c[k] * (mesh.vertex(q)[k] - mesh.vertex(p)[k]))
+                    .sum();
+            }
+        }
+        let mut values = HCurlValues::new(&element, &midpoint_rule());
+        for cell in 0..2 {
+            values.reinit(&mesh, cell);
+            let shapes: Vec<&[f64]> = (0..3).map(|i| values.shape_value(0, i)).collect();
+            let field = combine(&shapes, dof_map.cell_dofs(cell), &coefficients);
+            assert!(
+                (field[0] - c[0]).abs() < 1e-14 && (field[1] - c[1]).abs() < 1e-14,
+                "Constant field was not reproduced on cell {}",
+                cell
+            );
+            let curl: f64 = (0..3)
+                .map(|i| coefficients[dof_map.cell_dofs(cell)[i]] * values.shape_curl(i)[0])
+                .sum();
+            assert!(curl.abs() < 1e-14, "Constant field should have no curl");
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use crate::core::arrays::sparse_csr::SparseCSR;
+
+// Entries (j, c_ij) and inhomogeneity b_i of a constraint
+type Constraint = (Vec<(usize, f64)>, f64);
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Structure holding linear relations between degrees of freedom
+///
+/// Every constrained dof i satisfies u_i = sum_j c_ij u_j + b_i. Hanging nodes, periodicity, multi
+/// point constraints and Dirichlet conditions (no c_ij) all fit this form. Constraints are applied
+/// while scattering local contributions (see distribute_local_to_global): the rows and columns of
+/// constrained dofs are condensed onto the dofs they depend on and the constrained rows are
+/// replaced by a diagonal entry. Once the condensed system is solved, distribute recovers the
+/// values of the constrained dofs.
+///
+/// Constraints may reference other constrained dofs until close is called, which resolves the
+/// chains so that every constraint only depends on unconstrained dofs.
+pub struct AffineConstraints {
+    constraints: Vec<Option<Constraint>>,
+    closed: bool,
+}
+
+impl AffineConstraints {
+    /// Build an empty set of constraints on n_dofs dofs
+    pub fn new(n_dofs: usize) -> Self {
+        AffineConstraints {
+            constraints: vec![None; n_dofs],
+            closed: true,
+        }
+    }
+
+    /// Total number of dofs
+    pub fn n_dofs(&self) -> usize {
+        self.constraints.len()
+    }
+
+    /// Number of constrained dofs
+    pub fn n_constraints(&self) -> usize {
+        self.constraints.iter().filter(|c| c.is_some()).count()
+    }
+
+    /// Add (or replace) the constraint u_dof = sum_j entries_j.1 u_(entries_j.0) + inhomogeneity
+    pub fn add_constraint(&mut self, dof: usize, entries: &[(usize, f64)], inhomogeneity: f64) {
+        assert!(
+            entries.iter().all(|(j, _)| *j != dof && *j < self.n_dofs()),
+            "A constraint cannot depend on its own dof or on dofs out of bounds"
+        );
+        self.constraints[dof] = Some((entries.to_vec(), inhomogeneity));
+        self.closed = false;
+    }
+
+    /// Constrain a dof to a fixed value
+    pub fn add_dirichlet(&mut self, dof: usize, value: f64) {
+        self.add_constraint(dof, &[], value);
+    }
+
+    /// Whether a dof is constrained
+    pub fn is_constrained(&self, dof: usize) -> bool {
+        self.constraints[dof].is_some()
+    }
+
+    /// Entries and inhomogeneity of the constraint on a dof
+    pub fn constraint(&self, dof: usize) -> Option<(&[(usize, f64)], f64)> {
+        self.constraints[dof]
+            .as_ref()
+            .map(|(entries, b)| (entries.as_slice(), *b))
+    }
+
+    /// Whether all the constraints only depend on unconstrained dofs
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Resolve chains of constraints so that all constraints depend on unconstrained dofs only
+    ///
+    /// Panics if the constraints are cyclic.
+    pub fn close(&mut self) {
+        let n_dofs = self.n_dofs();
+        for dof in 0..n_dofs {
+            let mut iterations = 0;
+            while let Some((entries, inhomogeneity)) = self.constraints[dof].clone() {
+                if entries.iter().all(|(j, _)| !self.is_constrained(*j)) {
+                    break;
+                }
+                iterations += 1;
+                assert!(
+                    iterations <= n_dofs,
+                    "Constraints on dof {} are cyclic",
+                    dof
+                );
+                let mut resolved: Vec<(usize, f64)> = Vec::with_capacity(entries.len());
+                let mut b = inhomogeneity;
+                for (j, c) in entries {
+                    match &self.constraints[j] {
+                        Some((sub_entries, sub_b)) => {
+                            assert!(j != dof, "Constraints on dof {} are cyclic", dof);
+                            resolved.extend(sub_entries.iter().map(|(k, s)| (*k, c * s)));
+                            b += c * sub_b;
+                        }
+                        None => resolved.push((j, c)),
+                    }
+                }
+                assert!(
+                    resolved.iter().all(|(j, _)| *j != dof),
+                    "Constraints on dof {} are cyclic",
+                    dof
+                );
+                // Merge the entries depending on the same dof
+                resolved.sort_by_key(|(j, _)| *j);
+                let mut merged: Vec<(usize, f64)> = Vec::with_capacity(resolved.len());
+                for (j, c) in resolved {
+                    match merged.last_mut() {
+                        Some((last, value)) if *last == j => *value += c,
+                        _ => merged.push((j, c)),
+                    }
+                }
+                self.constraints[dof] = Some((merged, b));
+            }
+        }
+        self.closed = true;
+    }
+
+    /// Condense a local system onto a global one
+    ///
+    /// local_matrix is a dense (dofs, dofs) row first matrix and local_vector a vector of size dofs
+    /// (either may be omitted). Constrained rows receive the absolute value of their local diagonal
+    /// entry (or 1 if it vanishes) and no right hand side so that the global system stays
+    /// invertible. The global matrix needs the pattern of
+    /// SparsityPattern::from_dofmap_and_constraints.
+    pub fn distribute_local_to_global(
+        &self,
+        dofs: &[usize],
+        local_matrix: Option<&[f64]>,
+        local_vector: Option<&[f64]>,
+        matrix: Option<&mut SparseCSR<f64>>,
+        vector: Option<&mut [f64]>,
+    ) {
+        assert!(
+            self.closed,
+            "Constraints need to be closed before being applied"
+        );
+        let n = dofs.len();
+        let expand = |dof: usize| -> Vec<(usize, f64)> {
+            match &self.constraints[dof] {
+                Some((entries, _)) => entries.clone(),
+                None => vec![(dof, 1.0)],
+            }
+        };
+        let expanded: Vec<Vec<(usize, f64)>> = dofs.iter().map(|d| expand(*d)).collect();
+        if let (Some(local), Some(global)) = (local_matrix, matrix) {
+            for i in 0..n {
+                for j in 0..n {
+                    let value = local[i * n + j];
+                    for (r, cr) in expanded[i].iter() {
+                        for (s, cs) in expanded[j].iter() {
+                            *global
+                                .get_mut(*r, *s)
+                                .expect("Global matrix is missing a condensed entry") +=
+                                cr * cs * value;
+                        }
+                    }
+                }
+                if self.is_constrained(dofs[i]) {
+                    let diagonal = local[i * n + i].abs();
+                    *global
+                        .get_mut(dofs[i], dofs[i])
+                        .expect("Global matrix is missing a diagonal entry") +=
+                        if diagonal != 0.0 { diagonal } else { 1.0 };
+                }
+            }
+        }
+        if let Some(global) = vector {
+            for i in 0..n {
+                let mut value = local_vector.map_or(0.0, |v| v[i]);
+                // Move the inhomogeneities to the right hand side
+                if let Some(local) = local_matrix {
+                    for (j, dof) in dofs.iter().enumerate() {
+                        if let Some((_, b)) = &self.constraints[*dof] {
+                            value -= local[i * n + j] * b;
+                        }
+                    }
+                }
+                for (r, cr) in expanded[i].iter() {
+                    global[*r] += cr * value;
+                }
+            }
+        }
+    }
+
+    /// Set the values of the constrained dofs of a solution from the unconstrained ones
+    pub fn distribute(&self, solution: &mut [f64]) {
+        assert!(
+            self.closed,
+            "Constraints need to be closed before being applied"
+        );
+        for (dof, constraint) in self.constraints.iter().enumerate() {
+            if let Some((entries, b)) = constraint {
+                solution[dof] = b + entries.iter().map(|(j, c)| c * solution[*j]).sum::<f64>();
+            }
+        }
+    }
+
+    /// Set the values of the constrained dofs of a vector without the inhomogeneities, as for
+    /// increments or adjoint solutions
+    pub fn distribute_homogeneous(&self, vector: &mut [f64]) {
+        assert!(
+            self.closed,
+            "Constraints need to be closed before being applied"
+        );
+        for (dof, constraint) in self.constraints.iter().enumerate() {
+            if let Some((entries, _)) = constraint {
+                vector[dof] = entries.iter().map(|(j, c)| c * vector[*j]).sum::<f64>();
+            }
+        }
+    }
+
+    /// Apply the transpose of the constraints to a vector (for instance the derivative of a
+    /// functional): the values of the constrained dofs are moved onto the dofs they depend on and
+    /// zeroed
+    pub fn condense(&self, vector: &mut [f64]) {
+        assert!(
+            self.closed,
+            "Constraints need to be closed before being applied"
+        );
+        for (dof, constraint) in self.constraints.iter().enumerate() {
+            if let Some((entries, _)) = constraint {
+                let value = std::mem::take(&mut vector[dof]);
+                for (j, c) in entries.iter() {
+                    vector[*j] += c * value;
+                }
+            }
+        }
+    }
+
+    /// Zero the values of the constrained dofs (for instance in a residual)
+    pub fn set_zero(&self, vector: &mut [f64]) {
+        for (dof, constraint) in self.constraints.iter().enumerate() {
+            if constraint.is_some() {
+                vector[dof] = 0.0;
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constraints_close() {
+        let mut constraints = AffineConstraints::new(5);
+        constraints.add_constraint(2, &[(1, 0.5), (3, 0.5)], 0.0);
+        constraints.add_constraint(1, &[(0, 1.0)], 1.0);
+        constraints.add_constraint(3, &[(4, 1.0), (0, 1.0)], 0.0);
+        assert!(!constraints.is_closed(), "Constraints should not be closed");
+        constraints.close();
+        let (entries, b) = constraints.constraint(2).unwrap();
+        assert_eq!(entries, &[(0, 1.0), (4, 0.5)], "Chains were not resolved");
+        assert_eq!(b, 0.5, "Inhomogeneities were not propagated");
+        let mut solution = vec![2.0, 0.0, 0.0, 0.0, 4.0];
+        constraints.distribute(&mut solution);
+        assert_eq!(
+            solution,
+            vec![2.0, 3.0, 4.5, 6.0, 4.0],
+            "Wrong distribution"
+        );
+        let mut increment = vec![2.0, 0.0, 0.0, 0.0, 4.0];
+        constraints.distribute_homogeneous(&mut increment);
+        assert_eq!(increment, vec![2.0, 2.0, 4.0, 6.0, 4.0]);
+        let mut derivative = vec![0.0, 1.0, 1.0, 1.0, 0.0];
+        constraints.condense(&mut derivative);
+        assert_eq!(
+            derivative,
+            vec![3.0, 0.0, 0.0, 0.0, 1.5],
+            "Wrong condensation"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    #[should_panic]
+    fn test_constraints_cycle() {
+        let mut constraints = AffineConstraints::new(3);
+        constraints.add_constraint(0, &[(1, 1.0)], 0.0);
+        constraints.add_constraint(1, &[(0, 1.0)], 0.0);
+        constraints.close();
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_constraints_condensation() {
+        // Local matrix [[1, -1], [-1, 1]] on dofs (0, 1) with u_1 = 2 u_0 + 1
+        let mut constraints = AffineConstraints::new(2);
+        constraints.add_constraint(1, &[(0, 2.0)], 1.0);
+        constraints.close();
+        let mut matrix = SparseCSR::new(2, vec![0, 2, 4], vec![0, 1, 0, 1], vec![0.0; 4]);
+        let mut vector = vec![0.0; 2];
+        constraints.distribute_local_to_global(
+            &[0, 1],
+            Some(&[1.0, -1.0, -1.0, 1.0]),
+            Some(&[1.0, 1.0]),
+            Some(&mut matrix),
+            Some(&mut vector),
+        );
+        // Condensed energy (1 - 2)^2 u_0^2 = u_0^2
+        assert_eq!(matrix.get(0, 0), Some(&1.0), "Wrong condensed entry");
+        assert_eq!(
+            matrix.get(0, 1),
+            Some(&0.0),
+            "Constrained column should vanish"
+        );
+        assert_eq!(matrix.get(1, 1), Some(&1.0), "Wrong constrained diagonal");
+        // Condensed right hand side (1 + 1) + 2 * (1 - 1) once the inhomogeneity is moved
+        assert_eq!(vector[0], 2.0, "Wrong condensed right hand side");
+        assert_eq!(vector[1], 0.0, "Constrained right hand side should vanish");
+    }
+}
@@ -0,0 +1,387 @@
+use super::assembler::{add_local_matrix, Assembler};
+use super::cell_values::CellValues;
+use super::dof_map::DofMap;
+use super::mesh::Mesh;
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::data_wrap::DataWrap;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::solvers::dense::determinant;
+use crate::spaces::quadrature::QuadratureRule;
+
+// Vertices of a sub-simplex in the reference coordinates of a cell with their level set values
+type SubSimplex = Vec<(Vec<f64>, f64)>;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Position of a cell with respect to an implicit geometry
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellLocation {
+    /// Cell inside the geometry
+    Inside,
+    /// Cell outside of the geometry
+    Outside,
+    /// Cell crossed by the boundary of the geometry
+    Cut,
+}
+
+/// Cells of a background mesh classified against the implicit geometry {x : phi(x) < 0} of a level
+/// set phi, for fictitious domain discretizations without body-fitted meshes
+///
+/// The level set is interpolated linearly on every cell from its vertex values. The cut cells are
+/// integrated on their inside part only, split into sub-simplices carrying a copy of the rule of
+/// the assembler, and the cells outside are left out. The functions of the space are kept on all
+/// the cells touching the geometry (the active cells), the dofs of the outside cells only being
+/// inactive and to be constrained to zero. As cut cells may keep arbitrarily small parts, the
+/// ghost penalty on their facets extends the coercivity of the inside cells to the whole active
+/// part and bounds the conditioning of the system independently of the cuts.
+pub struct CutCells {
+    vertex_values: Vec<f64>,
+    locations: Vec<CellLocation>,
+}
+
+impl CutCells {
+    /// Classify the cells of a mesh against the geometry of a level set evaluated at the vertices
+    pub fn new<LevelSet>(mesh: &Mesh, level_set: LevelSet) -> Self
+    where
+        LevelSet: Fn(&[f64]) -> f64,
+    {
+        let values = (0..mesh.n_vertices())
+            .map(|v| level_set(mesh.vertex(v)))
+            .collect();
+        CutCells::from_vertex_values(mesh, values)
+    }
+
+    /// Classify the cells of a mesh against the geometry of the vertex values of a level set
+    pub fn from_vertex_values(mesh: &Mesh, vertex_values: Vec<f64>) -> Self {
+        assert_eq!(
+            vertex_values.len(),
+            mesh.n_vertices(),
+            "Level set values do not match the vertices of the mesh"
+        );
+        let locations = (0..mesh.n_cells())
+            .map(|cell| {
+                let values = mesh.cell(cell).iter().map(|v| vertex_values[*v]);
+                if values.clone().all(|phi| phi <= 0.0) {
+                    CellLocation::Inside
+                } else if values.clone().all(|phi| phi >= 0.0) {
+                    CellLocation::Outside
+                } else {
+                    CellLocation::Cut
+                }
+            })
+            .collect();
+        CutCells {
+            vertex_values,
+            locations,
+        }
+    }
+
+    /// Values of the level set at the vertices
+    pub fn vertex_values(&self) -> &[f64] {
+        &self.vertex_values
+    }
+
+    /// Position of a cell
+    pub fn location(&self, cell: usize) -> CellLocation {
+        self.locations[cell]
+    }
+
+    /// Whether a cell is inside or cut
+    pub fn is_active(&self, cell: usize) -> bool {
+        self.locations[cell] != CellLocation::Outside
+    }
+
+    /// Cells with a given position
+    pub fn cells(&self, location: CellLocation) -> Vec<usize> {
+        (0..self.locations.len())
+            .filter(|cell| self.locations[*cell] == location)
+            .collect()
+    }
+
+    /// Dofs which only belong to outside cells
+    pub fn inactive_dofs(&self, dof_map: &DofMap) -> Vec<usize> {
+        let mut active = vec![false; dof_map.n_dofs()];
+        for cell in (0..dof_map.n_cells()).filter(|cell| self.is_active(*cell)) {
+            for dof in dof_map.cell_dofs(cell) {
+                active[*dof] = true;
+            }
+        }
+        (0..dof_map.n_dofs()).filter(|dof| !active[*dof]).collect()
+    }
+
+    /// Rule integrating over the inside part of a cell, in its reference coordinates, made of the
+    /// given reference rule mapped onto every inside sub-simplex (no points for outside cells)
+    pub fn quadrature(&self, mesh: &Mesh, cell: usize, rule: &QuadratureRule) -> QuadratureRule {
+        let dim = mesh.topological_dim();
+        let mut simplex: SubSimplex = Vec::with_capacity(dim + 1);
+        for (k, v) in mesh.cell(cell).iter().enumerate() {
+            let mut reference = vec![0.0; dim];
+            if k > 0 {
+                reference[k - 1] = 1.0;
+            }
+            simplex.push((reference, self.vertex_values[*v]));
+        }
+        let mut inside = Vec::new();
+        split(simplex, &mut inside);
+        let mut points = Vec::with_capacity(inside.len() * rule.n_points() * dim);
+        let mut weights = Vec::with_capacity(inside.len() * rule.n_points());
+        let mut jacobian = DataHold::new(vec![0.0; dim * dim], [dim, dim]);
+        for vertices in &inside {
+            let origin = &vertices[0];
+            for i in 0..dim {
+                for j in 0..dim {
+                    jacobian[i * dim + j] = vertices[j + 1][i] - origin[i];
+                }
+            }
+            let det = if dim == 0 {
+                1.0
+            } else {
+                determinant(&jacobian).abs()
+            };
+            for q in 0..rule.n_points() {
+                let xi = rule.point(q);
+                points.extend((0..dim).map(|i| {
+                    origin[i] + (0..dim).map(|j| jacobian[i * dim + j] * xi[j]).sum::<f64>()
+                }));
+                weights.push(rule.weights()[q] * det);
+            }
+        }
+        let n_points = weights.len();
+        QuadratureRule::new(DataHold::new(points, [n_points, dim]), weights)
+    }
+
+    /// Add the contributions of the kernel on the inside part of every active cell to a global
+    /// matrix, as Assembler::assemble_matrix
+    pub fn assemble_matrix<Kernel>(
+        &self,
+        assembler: &Assembler,
+        matrix: &mut SparseCSR<f64>,
+        mut kernel: Kernel,
+    ) where
+        Kernel: FnMut(&CellValues, &mut DataWrap<f64, [usize; 2]>),
+    {
+        let n = assembler.element().n_dofs();
+        let mut local = vec![0.0; n * n];
+        self.cell_loop(assembler, |values| {
+            local.iter_mut().for_each(|v| *v = 0.0);
+            kernel(values, &mut DataWrap::new(&mut local, [n, n]));
+            add_local_matrix(matrix, assembler.dof_map().cell_dofs(values.cell()), &local);
+        });
+    }
+
+    /// Add the contributions of the kernel on the inside part of every active cell to a global
+    /// vector, as Assembler::assemble_vector
+    pub fn assemble_vector<Kernel>(
+        &self,
+        assembler: &Assembler,
+        vector: &mut [f64],
+        mut kernel: Kernel,
+    ) where
+        Kernel: FnMut(&CellValues, &mut DataWrap<f64, [usize; 1]>),
+    {
+        let n = assembler.element().n_dofs();
+        let mut local = vec![0.0; n];
+        self.cell_loop(assembler, |values| {
+            local.iter_mut().for_each(|v| *v = 0.0);
+            kernel(values, &mut DataWrap::new(&mut local, [n]));
+            for (i, dof) in assembler
+                .dof_map()
+                .cell_dofs(values.cell())
+                .iter()
+                .enumerate()
+            {
+                vector[*dof] += local[i];
+            }
+        });
+    }
+
+    // Call the action with the CellValues of the inside part of every active cell
+    fn cell_loop<Action>(&self, assembler: &Assembler, mut action: Action)
+    where
+        Action: FnMut(&CellValues),
+    {
+        let mesh = assembler.mesh();
+        let mut inside_values = CellValues::new(assembler.element(), assembler.quadrature());
+        inside_values.set_coordinate_system(assembler.coordinate_system());
+        for cell in 0..mesh.n_cells() {
+            match self.locations[cell] {
+                CellLocation::Outside => {}
+                CellLocation::Inside => {
+                    inside_values.reinit(mesh, cell);
+                    action(&inside_values);
+                }
+                CellLocation::Cut => {
+                    let rule = self.quadrature(mesh, cell, assembler.quadrature());
+                    let mut values = CellValues::new(assembler.element(), &rule);
+                    values.set_coordinate_system(assembler.coordinate_system());
+                    values.reinit(mesh, cell);
+                    action(&values);
+                }
+            }
+        }
+    }
+
+    /// Add the ghost penalty penalty h int [grad(u) . n] [grad(v) . n] on the interior facets of
+    /// the cut cells between two active cells to a global matrix allocated for interior facets
+    ///
+    /// It vanishes on the functions which are polynomials across the facets, so that it does not
+    /// change the consistency of linear elements (higher orders would also need the jumps of the
+    /// higher derivatives). The penalty is scaled like the operator, for instance by the
+    /// diffusivity, and values of 0.1 to 1 are usual.
+    pub fn ghost_penalty(&self, assembler: &Assembler, matrix: &mut SparseCSR<f64>, penalty: f64) {
+        let n = assembler.element().n_dofs();
+        let mut normal_derivatives = vec![0.0; 2 * n];
+        assembler.assemble_interior_facets(matrix, |first, second, local| {
+            let (a, b) = (first.cell(), second.cell());
+            let penalized = self.is_active(a)
+                && self.is_active(b)
+                && (self.locations[a] == CellLocation::Cut
+                    || self.locations[b] == CellLocation::Cut);
+            if !penalized {
+                return;
+            }
+            let h = match first.dim() {
+                1 | 2 => first.facet_measure(),
+                _ => first.facet_measure().sqrt(),
+            };
+            let normal = first.normal();
+            for q in 0..first.n_points() {
+                for i in 0..n {
+                    let (ga, gb) = (first.shape_gradient(q, i), second.shape_gradient(q, i));
+                    normal_derivatives[i] = ga.iter().zip(normal).map(|(g, n)| g * n).sum();
+                    normal_derivatives[n + i] =
+                        -gb.iter().zip(normal).map(|(g, n)| g * n).sum::<f64>();
+                }
+                let factor = penalty * h * first.weight(q);
+                for i in 0..2 * n {
+                    for j in 0..2 * n {
+                        local[i * 2 * n + j] +=
+                            factor * normal_derivatives[i] * normal_derivatives[j];
+                    }
+                }
+            }
+        });
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Split a simplex along the zero level of the linear interpolant of its vertex values, pushing the
+// vertices of its inside parts. Every split cuts an edge with values of opposite signs at its zero,
+// which removes the edge from both halves until no edge crosses the level set.
+fn split(simplex: SubSimplex, inside: &mut Vec<Vec<Vec<f64>>>) {
+    let n = simplex.len();
+    let crossing = (0..n)
+        .flat_map(|a| (a + 1..n).map(move |b| (a, b)))
+        .find(|(a, b)| simplex[*a].1 * simplex[*b].1 < 0.0);
+    match crossing {
+        Some((a, b)) => {
+            let ((xa, pa), (xb, pb)) = (&simplex[a], &simplex[b]);
+            let t = pa / (pa - pb);
+            let point: Vec<f64> = xa.iter().zip(xb).map(|(x, y)| x + t * (y - x)).collect();
+            let mut lower = simplex.clone();
+            lower[b] = (point.clone(), 0.0);
+            let mut upper = simplex;
+            upper[a] = (point, 0.0);
+            split(lower, inside);
+            split(upper, inside);
+        }
+        None => {
+            if simplex.iter().map(|(_, phi)| phi).sum::<f64>() < 0.0 {
+                inside.push(simplex.into_iter().map(|(x, _)| x).collect());
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::function::Function;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::discretizations::sparsity::SparsityPattern;
+    use crate::spaces::lagrange::LagrangeElement;
+
+    #[test]
+    fn test_cut_cells_quadrature() {
+        // Pentagon {x + y < 1.2} of area 0.68 cut from the unit square and tetrahedron {x + y + z <
+        // 0.5} of volume 1 / 48 from the unit cube, both exact for linear level sets
+        let cases = [
+            (vec![8, 8], 1.2, 0.68, QuadratureRule::simplex(2, 2)),
+            (
+                vec![3, 3, 3],
+                0.5,
+                1.0 / 48.0,
+                QuadratureRule::simplex(3, 2),
+            ),
+        ];
+        for (cells, offset, expected, rule) in cases {
+            let dim = cells.len();
+            let mesh = CartesianGrid::new(vec![0.0; dim], vec![1.0; dim], cells).simplex_mesh();
+            let cut = CutCells::new(&mesh, |x| x.iter().sum::<f64>() - offset);
+            assert!(
+                !cut.cells(CellLocation::Cut).is_empty(),
+                "Level set should cut cells"
+            );
+            let space = FunctionSpace::new(&mesh, LagrangeElement::new(dim, 1));
+            let assembler = space.assembler(rule);
+            let mut integrals = vec![0.0; space.n_dofs()];
+            cut.assemble_vector(&assembler, &mut integrals, |values, local| {
+                for q in 0..values.n_points() {
+                    for i in 0..values.n_dofs() {
+                        local[i] += values.shape_value(q, i) * values.weight(q);
+                    }
+                }
+            });
+            let measure: f64 = integrals.iter().sum();
+            assert!(
+                (measure - expected).abs() < 1e-13,
+                "Wrong measure {} of the cut domain",
+                measure
+            );
+            let inactive = cut.inactive_dofs(space.dof_map());
+            assert!(
+                inactive
+                    .iter()
+                    .all(|v| mesh.vertex(*v).iter().sum::<f64>() > offset),
+                "Dofs of the geometry were deactivated"
+            );
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_ghost_penalty() {
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![6, 6]).simplex_mesh();
+        let cut = CutCells::new(&mesh, |x| {
+            ((x[0] - 0.5).powi(2) + (x[1] - 0.5).powi(2)).sqrt() - 0.33
+        });
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let assembler = space.assembler(QuadratureRule::simplex(2, 2));
+        let pattern = SparsityPattern::from_dofmap_and_facets(space.dof_map(), assembler.facets());
+        let mut matrix = pattern.to_csr(0.0);
+        cut.ghost_penalty(&assembler, &mut matrix, 1.0);
+        let energy = |u: &Function| -> f64 {
+            let mut product = vec![0.0; space.n_dofs()];
+            matrix.apply(u.values(), &mut product);
+            product.iter().zip(u.values()).map(|(a, b)| a * b).sum()
+        };
+        let mut u = Function::new(&space);
+        u.interpolate(|x| 2.0 * x[0] - x[1]);
+        assert!(
+            energy(&u).abs() < 1e-12,
+            "Linear functions are not penalized"
+        );
+        u.interpolate(|x| x[0] * x[0]);
+        assert!(energy(&u) > 1e-3, "Kinks should be penalized");
+    }
+}
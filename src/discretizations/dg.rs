@@ -0,0 +1,166 @@
+use super::forms::{
+    avg, coefficient, constant, dot, ds, ds_interior, dx, facet_size, grad, jump, normal, test,
+    trial, vector_coefficient, Form,
+};
+use super::nitsche::Nitsche;
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Symmetric interior penalty (SIPG) form of -div(k grad u) on a discontinuous space
+///
+/// Cell terms k grad(u) . grad(v) are complemented on the interior facets by the consistency term
+/// -avg(k grad(u)) . n jump(v), the symmetry term -jump(u) avg(k grad(v)) . n and the penalty term
+/// penalty k / h jump(u) jump(v). The form is coercive for penalties large enough, a common choice
+/// is a few times the square of the order of the element.
+pub fn sipg(diffusivity: f64, penalty: f64) -> Form {
+    diffusivity * dot(grad(trial()), grad(test())) * dx()
+        + (penalty * diffusivity * jump(trial()) * jump(test()) / facet_size()
+            - diffusivity * dot(avg(grad(trial())), normal()) * jump(test())
+            - diffusivity * jump(trial()) * dot(avg(grad(test())), normal()))
+            * ds_interior()
+}
+
+/// Nitsche terms of the SIPG form weakly imposing Dirichlet conditions on the boundary facets
+/// carrying a tag
+pub fn sipg_boundary(tag: usize, diffusivity: f64, penalty: f64) -> Form {
+    sipg_nitsche(tag, diffusivity, penalty).bilinear()
+}
+
+/// Right hand side matching sipg_boundary for the Dirichlet value g on the facets carrying a tag
+pub fn sipg_boundary_rhs<Value>(tag: usize, diffusivity: f64, penalty: f64, g: Value) -> Form
+where
+    Value: Fn(&[f64]) -> f64 + Send + Sync + 'static,
+{
+    sipg_nitsche(tag, diffusivity, penalty).linear(g)
+}
+
+// Symmetric Nitsche terms shared by sipg_boundary and its right hand side
+fn sipg_nitsche(tag: usize, diffusivity: f64, penalty: f64) -> Nitsche {
+    Nitsche::new(tag)
+        .with_constant_diffusivity(diffusivity)
+        .with_penalty(penalty)
+}
+
+/// Lax-Friedrichs form of the advection operator div(b u) on a discontinuous space
+///
+/// The cell terms -u b . grad(v) are coupled through the numerical flux
+/// avg(u) b . n + max_speed / 2 jump(u) tested with jump(v) on the interior facets. The maximal
+/// speed should bound |b . n| for the scheme to be stable, it recovers the upwind flux when equal
+/// to it.
+pub fn lax_friedrichs<Velocity>(velocity: Velocity, max_speed: f64) -> Form
+where
+    Velocity: Fn(&[f64], &mut [f64]) + Send + Sync + 'static,
+{
+    let b = vector_coefficient(velocity);
+    -(trial() * dot(b.clone(), grad(test()))) * dx()
+        + (avg(trial()) * dot(b, normal()) + 0.5 * max_speed * jump(trial()))
+            * jump(test())
+            * ds_interior()
+}
+
+/// Boundary part of the Lax-Friedrichs flux with an exterior state on the facets carrying a tag
+///
+/// The flux (b . n (u + g) + max_speed (u - g)) / 2 is split between this bilinear form holding the
+/// terms in u and lax_friedrichs_boundary_rhs holding the ones in the exterior state g.
+pub fn lax_friedrichs_boundary<Velocity>(tag: usize, velocity: Velocity, max_speed: f64) -> Form
+where
+    Velocity: Fn(&[f64], &mut [f64]) + Send + Sync + 'static,
+{
+    0.5 * (dot(vector_coefficient(velocity), normal()) + constant(max_speed))
+        * trial()
+        * test()
+        * ds(tag)
+}
+
+/// Right hand side matching lax_friedrichs_boundary for the exterior state g
+pub fn lax_friedrichs_boundary_rhs<Velocity, Value>(
+    tag: usize,
+    velocity: Velocity,
+    max_speed: f64,
+    g: Value,
+) -> Form
+where
+    Velocity: Fn(&[f64], &mut [f64]) + Send + Sync + 'static,
+    Value: Fn(&[f64]) -> f64 + Send + Sync + 'static,
+{
+    0.5 * (constant(max_speed) - dot(vector_coefficient(velocity), normal()))
+        * coefficient(g)
+        * test()
+        * ds(tag)
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::function::Function;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::discretizations::mesh::Mesh;
+    use crate::discretizations::sparsity::SparsityPattern;
+    use crate::discretizations::test_meshes::square_grid;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::spaces::quadrature::QuadratureRule;
+
+    // Unit square cut in 2 x 2 squares split in two triangles with every boundary facet tagged 1
+    fn build_mesh() -> Mesh {
+        let mut mesh = square_grid(2);
+        mesh.tag_boundary(1, |_| true);
+        mesh
+    }
+
+    // Residual A u - b of a form and its right hand side on the interpolant of an exact solution
+    fn residual<Exact>(bilinear: &Form, linear: &Form, exact: Exact) -> f64
+    where
+        Exact: Fn(&[f64]) -> f64,
+    {
+        let mesh = build_mesh();
+        let space = FunctionSpace::discontinuous(&mesh, LagrangeElement::new(2, 1));
+        let assembler = space.assembler(QuadratureRule::simplex(2, 2));
+        let pattern = SparsityPattern::from_dofmap_and_facets(space.dof_map(), assembler.facets());
+        let mut matrix = pattern.to_csr(0.0);
+        bilinear.assemble_matrix(&assembler, &mut matrix);
+        let mut rhs = vec![0.0; space.n_dofs()];
+        linear.assemble_vector(&assembler, &mut rhs);
+        let mut u = Function::new(&space);
+        u.interpolate(exact);
+        let mut product = vec![0.0; space.n_dofs()];
+        matrix.apply(u.values(), &mut product);
+        product
+            .iter()
+            .zip(rhs.iter())
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn test_sipg_consistency() {
+        // Linear solutions of the Laplace equation satisfy the discrete equations exactly
+        let exact = |x: &[f64]| x[0] + 2.0 * x[1];
+        let bilinear = sipg(2.0, 10.0) + sipg_boundary(1, 2.0, 10.0);
+        let linear = sipg_boundary_rhs(1, 2.0, 10.0, exact);
+        assert!(
+            residual(&bilinear, &linear, exact) < 1e-12,
+            "SIPG is not consistent"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_lax_friedrichs_consistency() {
+        // div(b u) = 3 for b = (1, 1) and u = x + 2 y
+        let exact = |x: &[f64]| x[0] + 2.0 * x[1];
+        let velocity = |_: &[f64], b: &mut [f64]| b.copy_from_slice(&[1.0, 1.0]);
+        let bilinear = lax_friedrichs(velocity, 2.0) + lax_friedrichs_boundary(1, velocity, 2.0);
+        let linear =
+            constant(3.0) * test() * dx() + lax_friedrichs_boundary_rhs(1, velocity, 2.0, exact);
+        assert!(
+            residual(&bilinear, &linear, exact) < 1e-12,
+            "Lax-Friedrichs flux is not consistent"
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::data_traits::DataContainer;
+use crate::discretizations::facets::Facets;
+use crate::discretizations::mesh::Mesh;
+use crate::spaces::lagrange::LagrangeElement;
+use std::collections::HashMap;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Structure mapping the local degrees of freedom of every cell to their global numbering
+///
+/// The cell dofs are held as a two dimensional array of size (number of cells, dofs per cell) where
+/// each row holds the global indices of the degrees of freedom of a cell in the local ordering of
+/// the element.
+pub struct DofMap {
+    cell_dofs: DataHold<usize, [usize; 2]>,
+    n_dofs: usize,
+}
+
+impl DofMap {
+    /// Build a DofMap from a cell to global dofs table and the total number of global dofs
+    pub fn new(cell_dofs: DataHold<usize, [usize; 2]>, n_dofs: usize) -> Self {
+        assert!(
+            cell_dofs.iter().all(|dof| *dof < n_dofs),
+            "Tried to build a DofMap with dofs out of the global numbering bounds"
+        );
+        DofMap { cell_dofs, n_dofs }
+    }
+
+    /// Number the dofs of a continuous Lagrange element on a mesh
+    ///
+    /// Vertex dofs take the index of their mesh vertex, the other dofs are numbered after them in
+    /// the order they are met. A node of a cell is identified globally by the mesh vertices of the
+    /// sub-entity supporting it together with its barycentric weights so that neighbouring cells
+    /// agree on shared nodes whatever their local vertex ordering.
+    pub fn lagrange(mesh: &Mesh, element: &LagrangeElement) -> Self {
+        assert!(
+            element.dim() == mesh.topological_dim(),
+            "Element and mesh dimensions do not match"
+        );
+        let n_cells = mesh.n_cells();
+        let dofs_per_cell = element.n_dofs();
+        let mut n_dofs = mesh.n_vertices();
+        let mut numbering: HashMap<Vec<(usize, usize)>, usize> = HashMap::new();
+        let mut cell_dofs = Vec::with_capacity(n_cells * dofs_per_cell);
+        for cell in 0..n_cells {
+            let vertices = mesh.cell(cell);
+            for node in 0..dofs_per_cell {
+                let multi_index = element.node_multi_index(node);
+                let mut key: Vec<(usize, usize)> = vertices
+                    .iter()
+                    .zip(multi_index.iter())
+                    .filter(|(_, a)| **a > 0)
+                    .map(|(v, a)| (*v, *a))
+                    .collect();
+                if key.len() == 1 {
+                    cell_dofs.push(key[0].0);
+                    continue;
+                }
+                key.sort_unstable();
+                let dof = *numbering.entry(key).or_insert_with(|| {
+                    n_dofs += 1;
+                    n_dofs - 1
+                });
+                cell_dofs.push(dof);
+            }
+        }
+        DofMap::new(DataHold::new(cell_dofs, [n_cells, dofs_per_cell]), n_dofs)
+    }
+
+    /// Number the dofs of a discontinuous (broken) element on a mesh
+    ///
+    /// Every cell owns its dofs which are numbered contiguously cell after cell: local dof i of cell
+    /// k is the global dof k * dofs per cell + i.
+    pub fn discontinuous(mesh: &Mesh, element: &LagrangeElement) -> Self {
+        assert!(
+            element.dim() == mesh.topological_dim(),
+            "Element and mesh dimensions do not match"
+        );
+        let n_cells = mesh.n_cells();
+        let dofs_per_cell = element.n_dofs();
+        DofMap::new(
+            DataHold::new(
+                (0..n_cells * dofs_per_cell).collect(),
+                [n_cells, dofs_per_cell],
+            ),
+            n_cells * dofs_per_cell,
+        )
+    }
+
+    /// Number one dof per facet of the mesh (for H(div) elements)
+    ///
+    /// Local dof i of a cell is the facet opposite to its local vertex i and the global dof is the
+    /// index of the facet in Facets.
+    pub fn facets(mesh: &Mesh, facets: &Facets) -> Self {
+        let n_cells = mesh.n_cells();
+        let dofs_per_cell = mesh.vertices_per_cell();
+        let mut cell_dofs = Vec::with_capacity(n_cells * dofs_per_cell);
+        for cell in 0..n_cells {
+            let vertices = mesh.cell(cell);
+            for local in 0..dofs_per_cell {
+                let facet: Vec<usize> = vertices
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != local)
+                    .map(|(_, v)| *v)
+                    .collect();
+                cell_dofs.push(
+                    facets
+                        .facet_index(&facet)
+                        .expect("Facets do not belong to the mesh"),
+                );
+            }
+        }
+        DofMap::new(
+            DataHold::new(cell_dofs, [n_cells, dofs_per_cell]),
+            facets.n_facets(),
+        )
+    }
+
+    /// Number one dof per edge of the mesh (for H(curl) elements)
+    ///
+    /// Local dof e of a cell is the edge joining the local vertices edges[e]. Global dofs are
+    /// numbered in the order the edges are met.
+    pub fn edges(mesh: &Mesh, edges: &[(usize, usize)]) -> Self {
+        let n_cells = mesh.n_cells();
+        let dofs_per_cell = edges.len();
+        let mut numbering: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut cell_dofs = Vec::with_capacity(n_cells * dofs_per_cell);
+        for cell in 0..n_cells {
+            let vertices = mesh.cell(cell);
+            for (a, b) in edges {
+                let (va, vb) = (vertices[*a], vertices[*b]);
+                let n_edges = numbering.len();
+                cell_dofs.push(*numbering.entry((va.min(vb), va.max(vb))).or_insert(n_edges));
+            }
+        }
+        let n_dofs = numbering.len();
+        DofMap::new(DataHold::new(cell_dofs, [n_cells, dofs_per_cell]), n_dofs)
+    }
+
+    /// Total number of global degrees of freedom
+    pub fn n_dofs(&self) -> usize {
+        self.n_dofs
+    }
+
+    /// Number of cells in the map
+    pub fn n_cells(&self) -> usize {
+        self.cell_dofs.dimensions()[0]
+    }
+
+    /// Number of degrees of freedom on each cell
+    pub fn dofs_per_cell(&self) -> usize {
+        self.cell_dofs.dimensions()[1]
+    }
+
+    /// Global indices of the degrees of freedom of a cell
+    pub fn cell_dofs(&self, cell: usize) -> &[usize] {
+        let n = self.dofs_per_cell();
+        &self.cell_dofs[cell * n..(cell + 1) * n]
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::test_meshes::unit_square;
+
+    #[test]
+    fn test_dof_map_access() {
+        let dof_map = DofMap::new(DataHold::new(vec![0, 1, 2, 1, 3, 2], [2, 3]), 4);
+        assert_eq!(dof_map.n_dofs(), 4, "Wrong number of dofs");
+        assert_eq!(dof_map.n_cells(), 2, "Wrong number of cells");
+        assert_eq!(dof_map.dofs_per_cell(), 3, "Wrong number of dofs per cell");
+        assert_eq!(dof_map.cell_dofs(1), &[1, 3, 2], "Wrong dofs for cell 1");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    #[should_panic]
+    fn test_dof_map_out_of_bounds() {
+        DofMap::new(DataHold::new(vec![0, 1, 2, 1, 3, 2], [2, 3]), 3);
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_dof_map_lagrange() {
+        // Two triangles sharing the edge (1, 2) with opposite orientations
+        let mesh = unit_square();
+        let dof_map = DofMap::lagrange(&mesh, &LagrangeElement::new(2, 1));
+        assert_eq!(dof_map.n_dofs(), 4, "P1 dofs should be the vertices");
+        assert_eq!(
+            dof_map.cell_dofs(1),
+            &[3, 2, 1],
+            "P1 dofs should follow the cells"
+        );
+        let element = LagrangeElement::new(2, 3);
+        let dof_map = DofMap::lagrange(&mesh, &element);
+        // 4 vertices, 5 edges with 2 dofs and 2 interior dofs
+        assert_eq!(dof_map.n_dofs(), 4 + 5 * 2 + 2, "Wrong number of P3 dofs");
+        let shared = |cell: usize| -> Vec<usize> {
+            let mut dofs: Vec<usize> = (0..element.n_dofs())
+                .filter(|node| {
+                    let a = element.node_multi_index(*node);
+                    let local = mesh.cell(cell);
+                    (0..3).all(|i| a[i] == 0 || local[i] == 1 || local[i] == 2)
+                })
+                .map(|node| dof_map.cell_dofs(cell)[node])
+                .collect();
+            dofs.sort_unstable();
+            dofs
+        };
+        assert_eq!(
+            shared(0),
+            shared(1),
+            "Cells do not agree on the shared edge"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_dof_map_facets_and_edges() {
+        let mesh = unit_square();
+        let dof_map = DofMap::facets(&mesh, &Facets::new(&mesh));
+        assert_eq!(dof_map.n_dofs(), 5, "Wrong number of facet dofs");
+        assert_eq!(
+            dof_map.cell_dofs(0)[0],
+            dof_map.cell_dofs(1)[0],
+            "Cells do not agree on the shared facet"
+        );
+        let dof_map = DofMap::edges(&mesh, &[(0, 1), (0, 2), (1, 2)]);
+        assert_eq!(dof_map.n_dofs(), 5, "Wrong number of edge dofs");
+        assert_eq!(
+            dof_map.cell_dofs(0)[2],
+            dof_map.cell_dofs(1)[2],
+            "Cells do not agree on the shared edge"
+        );
+    }
+}
@@ -0,0 +1,299 @@
+use super::cell_values::CellValues;
+use super::facet_values::FacetValues;
+use super::facets::Facets;
+use super::function::Function;
+use crate::core::arrays::data_hold::DataHold;
+use crate::spaces::quadrature::QuadratureRule;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+type Source = dyn Fn(&[f64]) -> f64;
+
+/// Residual based a posteriori error estimator for -div(k grad u) = f with a constant k
+///
+/// The indicator of a cell K is the square root of
+/// h_K^2 |f + k lap(u_h)|_K^2 + 1/2 sum_F h_F |jump(k grad(u_h) . n)|_F^2 over its interior facets,
+/// to which h_F |g - k grad(u_h) . n|_F^2 is added on the facets with a Neumann flux g. h_K is the
+/// diameter of the cell and h_F the size of the facet. Up to constants, the indicators bound the
+/// energy norm of the error from above and below (locally).
+pub struct ErrorEstimator {
+    diffusivity: f64,
+    source: Box<Source>,
+    neumann: Vec<(usize, Box<Source>)>,
+}
+
+impl ErrorEstimator {
+    /// Estimator for the Poisson problem with a diffusivity and a source term
+    pub fn new<Function>(diffusivity: f64, source: Function) -> Self
+    where
+        Function: Fn(&[f64]) -> f64 + 'static,
+    {
+        ErrorEstimator {
+            diffusivity,
+            source: Box::new(source),
+            neumann: Vec::new(),
+        }
+    }
+
+    /// Add the residual of the Neumann condition k grad(u) . n = g on the facets carrying a tag
+    pub fn with_neumann<Function>(mut self, tag: usize, flux: Function) -> Self
+    where
+        Function: Fn(&[f64]) -> f64 + 'static,
+    {
+        self.neumann.push((tag, Box::new(flux)));
+        self
+    }
+
+    /// Compute the indicators of every cell for a discrete solution
+    pub fn estimate(&self, solution: &Function) -> DataHold<f64, [usize; 1]> {
+        let space = solution.space();
+        assert!(
+            space.n_components() == 1,
+            "Error estimation is only implemented for scalar problems"
+        );
+        let mesh = space.mesh();
+        let element = space.element();
+        let dim = element.dim();
+        let order = element.order();
+        let values = solution.values();
+        let k = self.diffusivity;
+        let mut squared = vec![0.0; mesh.n_cells()];
+        // Element residuals
+        let quadrature = QuadratureRule::simplex(dim, 2 * order + 2);
+        let mut cell_values = CellValues::new(element, &quadrature);
+        let n = element.n_dofs();
+        let mut reference_hessians = vec![0.0; n * dim * dim];
+        let mut laplacians = vec![0.0; n];
+        for (cell, eta) in squared.iter_mut().enumerate() {
+            cell_values.reinit(mesh, cell);
+            let inverse = cell_values.mapping().inverse_jacobian();
+            let dofs = space.dof_map().cell_dofs(cell);
+            let h = diameter(mesh.cell(cell).iter().map(|v| mesh.vertex(*v)));
+            for q in 0..cell_values.n_points() {
+                element.hessians(quadrature.point(q), &mut reference_hessians);
+                // Trace of J^-T H J^-1 for affine cells
+                for (laplacian, hessian) in laplacians
+                    .iter_mut()
+                    .zip(reference_hessians.chunks(dim * dim))
+                {
+                    *laplacian = 0.0;
+                    for a in 0..dim {
+                        for c in 0..dim {
+                            for d in 0..dim {
+                                *laplacian += inverse[c * dim + a]
+                                    * hessian[c * dim + d]
+                                    * inverse[d * dim + a];
+                            }
+                        }
+                    }
+                }
+                let lap_u: f64 = dofs
+                    .iter()
+                    .zip(laplacians.iter())
+                    .map(|(dof, l)| values[*dof] * l)
+                    .sum();
+                let residual = (self.source)(cell_values.point(q)) + k * lap_u;
+                *eta += h * h * residual * residual * cell_values.weight(q);
+            }
+        }
+        // Facet residuals
+        let facets = Facets::new(mesh);
+        let facet_quadrature = QuadratureRule::simplex(dim - 1, 2 * order);
+        let mut first = FacetValues::new(element, &facet_quadrature);
+        let mut second = FacetValues::new(element, &facet_quadrature);
+        let normal_flux = |values_on_side: &FacetValues, q: usize| -> f64 {
+            let dofs = space.dof_map().cell_dofs(values_on_side.cell());
+            let normal = values_on_side.normal();
+            dofs.iter()
+                .enumerate()
+                .map(|(i, dof)| {
+                    let grad = values_on_side.shape_gradient(q, i);
+                    values[*dof]
+                        * grad
+                            .iter()
+                            .zip(normal.iter())
+                            .map(|(g, n)| g * n)
+                            .sum::<f64>()
+                })
+                .sum::<f64>()
+                * k
+        };
+        for facet in 0..facets.n_facets() {
+            let vertices = facets.facet_vertices(facet);
+            let cells = facets.facet_cells(facet);
+            let h = diameter(vertices.iter().map(|v| mesh.vertex(*v)));
+            if cells.len() == 2 {
+                first.reinit(mesh, vertices, cells[0].0);
+                second.reinit(mesh, vertices, cells[1].0);
+                // Outward normals are opposite so the jump is the sum of the outward fluxes
+                let jump: f64 = (0..first.n_points())
+                    .map(|q| {
+                        let j = normal_flux(&first, q) + normal_flux(&second, q);
+                        j * j * first.weight(q)
+                    })
+                    .sum();
+                squared[cells[0].0] += 0.5 * h * jump;
+                squared[cells[1].0] += 0.5 * h * jump;
+            } else {
+                let tag = mesh.facet_tag(vertices);
+                if let Some((_, flux)) = self.neumann.iter().find(|(t, _)| Some(*t) == tag) {
+                    first.reinit(mesh, vertices, cells[0].0);
+                    let residual: f64 = (0..first.n_points())
+                        .map(|q| {
+                            let r = flux(first.point(q)) - normal_flux(&first, q);
+                            r * r * first.weight(q)
+                        })
+                        .sum();
+                    squared[cells[0].0] += h * residual;
+                }
+            }
+        }
+        let n_cells = squared.len();
+        DataHold::new(squared.into_iter().map(f64::sqrt).collect(), [n_cells])
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Global estimate (square root of the sum of the squared indicators)
+pub fn global_estimate(indicators: &[f64]) -> f64 {
+    indicators.iter().map(|eta| eta * eta).sum::<f64>().sqrt()
+}
+
+/// Dörfler (bulk) marking: smallest set of cells whose squared indicators sum to at least theta
+/// times the total
+///
+/// The marked cells are returned sorted by decreasing indicator.
+pub fn mark_dorfler(indicators: &[f64], theta: f64) -> Vec<usize> {
+    assert!(
+        (0.0..=1.0).contains(&theta),
+        "Dörfler parameter should lie in [0, 1]"
+    );
+    let total: f64 = indicators.iter().map(|eta| eta * eta).sum();
+    let mut marked = Vec::new();
+    let mut sum = 0.0;
+    for cell in sorted_cells(indicators) {
+        if sum >= theta * total {
+            break;
+        }
+        sum += indicators[cell] * indicators[cell];
+        marked.push(cell);
+    }
+    marked
+}
+
+/// Fixed fraction marking: the given fraction of the cells with the largest indicators
+///
+/// The marked cells are returned sorted by decreasing indicator.
+pub fn mark_fixed_fraction(indicators: &[f64], fraction: f64) -> Vec<usize> {
+    assert!(
+        (0.0..=1.0).contains(&fraction),
+        "Marked fraction should lie in [0, 1]"
+    );
+    let n_marked = (fraction * indicators.len() as f64).ceil() as usize;
+    sorted_cells(indicators)
+        .into_iter()
+        .take(n_marked)
+        .collect()
+}
+
+// Cells sorted by decreasing indicator
+fn sorted_cells(indicators: &[f64]) -> Vec<usize> {
+    let mut cells: Vec<usize> = (0..indicators.len()).collect();
+    cells.sort_by(|a, b| indicators[*b].total_cmp(&indicators[*a]));
+    cells
+}
+
+// Largest distance between two of the points
+fn diameter<'p, Points>(points: Points) -> f64
+where
+    Points: Iterator<Item = &'p [f64]> + Clone,
+{
+    let mut h: f64 = 0.0;
+    for a in points.clone() {
+        for b in points.clone() {
+            let d: f64 = a.iter().zip(b.iter()).map(|(x, y)| (x - y) * (x - y)).sum();
+            h = h.max(d.sqrt());
+        }
+    }
+    h
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::discretizations::test_meshes::square_grid;
+    use crate::spaces::lagrange::LagrangeElement;
+
+    #[test]
+    fn test_estimator_exact_solution() {
+        // Quadratics solve -lap(u) = -2 and are reproduced by P2 elements
+        let mut mesh = square_grid(2);
+        mesh.tag_boundary(1, |x| x[0] == 1.0);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        let mut u = Function::new(&space);
+        u.interpolate(|x| x[0] * x[0]);
+        let estimator = ErrorEstimator::new(1.0, |_| -2.0).with_neumann(1, |_| 2.0);
+        let indicators = estimator.estimate(&u);
+        assert!(
+            global_estimate(&indicators) < 1e-12,
+            "Exact solutions should have no error"
+        );
+        // A wrong Neumann flux is detected
+        let estimator = ErrorEstimator::new(1.0, |_| -2.0).with_neumann(1, |_| 1.0);
+        assert!(
+            global_estimate(&estimator.estimate(&u)) > 0.1,
+            "Wrong Neumann flux should be seen"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_estimator_convergence() {
+        // The estimate of the P1 interpolant of x^2 decreases linearly with the mesh size
+        let estimate = |n: usize| -> f64 {
+            let mesh = square_grid(n);
+            let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+            let mut u = Function::new(&space);
+            u.interpolate(|x| x[0] * x[0]);
+            global_estimate(&ErrorEstimator::new(1.0, |_| -2.0).estimate(&u))
+        };
+        let ratio = estimate(4) / estimate(8);
+        assert!(
+            (ratio - 2.0).abs() < 0.2,
+            "Estimate should be first order, got a ratio of {}",
+            ratio
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_marking_strategies() {
+        let indicators = [1.0, 3.0, 0.5, 2.0, 0.1];
+        // Squares sum to 14.26 and the two largest give 13
+        assert_eq!(
+            mark_dorfler(&indicators, 0.9),
+            vec![1, 3],
+            "Wrong Dörfler set"
+        );
+        assert_eq!(
+            mark_dorfler(&indicators, 0.95),
+            vec![1, 3, 0],
+            "Wrong Dörfler set"
+        );
+        assert_eq!(
+            mark_fixed_fraction(&indicators, 0.5),
+            vec![1, 3, 0],
+            "Wrong fixed fraction"
+        );
+    }
+}
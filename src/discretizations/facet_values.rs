@@ -0,0 +1,299 @@
+use super::cell_mapping::CellMapping;
+use super::cell_values::{axisymmetric_weights, CoordinateSystem};
+use super::mesh::Mesh;
+use crate::spaces::lagrange::LagrangeElement;
+use crate::spaces::quadrature::QuadratureRule;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Structure holding the traces of the shape functions of a cell on one of its facets
+///
+/// The quadrature rule lives on the reference facet and is laid out along the sorted vertices of
+/// the facet. Two cells sharing a facet therefore see the same physical quadrature points in the
+/// same order which is what interior facet integrals need. The weights include the measure of the
+/// physical facet and the normal is the unit normal pointing out of the cell, which lies in the
+/// plane of the cell (the conormal) on curves and surfaces.
+pub struct FacetValues<'a> {
+    element: &'a LagrangeElement,
+    dim: usize,
+    geometric_dim: usize,
+    coordinate_system: CoordinateSystem,
+    cell: usize,
+    local_facet: usize,
+    reference_points: Vec<f64>,
+    reference_weights: Vec<f64>,
+    values: Vec<f64>,
+    gradients: Vec<f64>,
+    weights: Vec<f64>,
+    points: Vec<f64>,
+    normal: Vec<f64>,
+    measure: f64,
+    mapping: Option<CellMapping>,
+}
+
+impl<'a> FacetValues<'a> {
+    /// Prepare the evaluation of the element on facets with a rule on the reference facet
+    pub fn new(element: &'a LagrangeElement, quadrature: &QuadratureRule) -> Self {
+        let dim = element.dim();
+        assert!(
+            quadrature.dim() + 1 == dim,
+            "Facet quadrature should be one dimension lower than the element"
+        );
+        let n_points = quadrature.n_points();
+        let n_dofs = element.n_dofs();
+        let mut reference_points = Vec::with_capacity(n_points * (dim - 1));
+        for q in 0..n_points {
+            reference_points.extend_from_slice(quadrature.point(q));
+        }
+        FacetValues {
+            element,
+            dim,
+            geometric_dim: dim,
+            coordinate_system: CoordinateSystem::Cartesian,
+            cell: 0,
+            local_facet: 0,
+            reference_points,
+            reference_weights: quadrature.weights().to_vec(),
+            values: vec![0.0; n_points * n_dofs],
+            gradients: vec![0.0; n_points * n_dofs * dim],
+            weights: vec![0.0; n_points],
+            points: vec![0.0; n_points * dim],
+            normal: vec![0.0; dim],
+            measure: 0.0,
+            mapping: None,
+        }
+    }
+
+    /// Change the coordinate system the integration weights are computed in
+    pub fn set_coordinate_system(&mut self, coordinate_system: CoordinateSystem) {
+        self.coordinate_system = coordinate_system;
+    }
+
+    /// Update the values for the facet with the given sorted vertices seen from one of its cells
+    pub fn reinit(&mut self, mesh: &Mesh, facet_vertices: &[usize], cell: usize) {
+        if mesh.geometric_dim() != self.geometric_dim {
+            self.geometric_dim = mesh.geometric_dim();
+            self.gradients = vec![0.0; self.values.len() * self.geometric_dim];
+            self.points = vec![0.0; self.reference_weights.len() * self.geometric_dim];
+            self.normal = vec![0.0; self.geometric_dim];
+            self.mapping = None;
+        }
+        let (dim, geometric_dim) = (self.dim, self.geometric_dim);
+        let n_dofs = self.element.n_dofs();
+        let cell_vertices = mesh.cell(cell);
+        self.local_facet = cell_vertices
+            .iter()
+            .position(|v| !facet_vertices.contains(v))
+            .expect("Facet does not belong to the cell");
+        self.cell = cell;
+        match self.mapping.as_mut() {
+            Some(mapping) => mapping.reinit(mesh, cell),
+            None => self.mapping = Some(CellMapping::new(mesh, cell)),
+        }
+        let mapping = self.mapping.as_ref().unwrap();
+        let scale = facet_scale(mesh, facet_vertices);
+        // The reference facet is the simplex of dimension dim - 1 of measure 1 / (dim - 1)!
+        self.measure = scale / (1..dim).product::<usize>() as f64;
+        // Position of every facet vertex among the local vertices of the cell
+        let local: Vec<usize> = facet_vertices
+            .iter()
+            .map(|v| cell_vertices.iter().position(|c| c == v).unwrap())
+            .collect();
+        let mut bary = vec![0.0; dim + 1];
+        let mut reference_gradients = vec![0.0; n_dofs * dim];
+        for q in 0..self.reference_weights.len() {
+            let facet_point = &self.reference_points[q * (dim - 1)..(q + 1) * (dim - 1)];
+            bary.iter_mut().for_each(|l| *l = 0.0);
+            bary[local[0]] = 1.0 - facet_point.iter().sum::<f64>();
+            for (k, y) in facet_point.iter().enumerate() {
+                bary[local[k + 1]] = *y;
+            }
+            let reference = &bary[1..];
+            self.element
+                .values(reference, &mut self.values[q * n_dofs..(q + 1) * n_dofs]);
+            self.element.gradients(reference, &mut reference_gradients);
+            for (i, grad) in reference_gradients.chunks(dim).enumerate() {
+                let start = (q * n_dofs + i) * geometric_dim;
+                mapping.map_gradient(grad, &mut self.gradients[start..start + geometric_dim]);
+            }
+            mapping.map_point(
+                reference,
+                &mut self.points[q * geometric_dim..(q + 1) * geometric_dim],
+            );
+            self.weights[q] = self.reference_weights[q] * scale;
+        }
+        if self.coordinate_system == CoordinateSystem::Axisymmetric {
+            axisymmetric_weights(geometric_dim, &self.points, &mut self.weights);
+        }
+        // Outward reference normal of the local facet pushed to the physical cell
+        let mut reference_normal = vec![0.0; dim];
+        if self.local_facet == 0 {
+            reference_normal.iter_mut().for_each(|n| *n = 1.0);
+        } else {
+            reference_normal[self.local_facet - 1] = -1.0;
+        }
+        mapping.map_gradient(&reference_normal, &mut self.normal);
+        let norm = self.normal.iter().map(|n| n * n).sum::<f64>().sqrt();
+        self.normal.iter_mut().for_each(|n| *n /= norm);
+    }
+
+    /// Cell the facet is seen from
+    pub fn cell(&self) -> usize {
+        self.cell
+    }
+
+    /// Local index of the facet in the cell
+    pub fn local_facet(&self) -> usize {
+        self.local_facet
+    }
+
+    /// Dimension of the cell
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Dimension of the physical points, gradients and normal
+    pub fn geometric_dim(&self) -> usize {
+        self.geometric_dim
+    }
+
+    /// Number of quadrature points on the facet
+    pub fn n_points(&self) -> usize {
+        self.reference_weights.len()
+    }
+
+    /// Number of shape functions of the cell
+    pub fn n_dofs(&self) -> usize {
+        self.element.n_dofs()
+    }
+
+    /// Value of shape function i at quadrature point q
+    pub fn shape_value(&self, q: usize, i: usize) -> f64 {
+        self.values[q * self.n_dofs() + i]
+    }
+
+    /// Physical gradient of shape function i at quadrature point q
+    pub fn shape_gradient(&self, q: usize, i: usize) -> &[f64] {
+        let start = (q * self.n_dofs() + i) * self.geometric_dim;
+        &self.gradients[start..start + self.geometric_dim]
+    }
+
+    /// Integration weight at quadrature point q including the facet measure (and 2 pi r in
+    /// axisymmetric coordinates)
+    pub fn weight(&self, q: usize) -> f64 {
+        self.weights[q]
+    }
+
+    /// Physical coordinates of quadrature point q
+    pub fn point(&self, q: usize) -> &[f64] {
+        &self.points[q * self.geometric_dim..(q + 1) * self.geometric_dim]
+    }
+
+    /// Unit normal to the facet pointing out of the cell
+    pub fn normal(&self) -> &[f64] {
+        &self.normal
+    }
+
+    /// Measure (length or area) of the physical facet
+    pub fn facet_measure(&self) -> f64 {
+        self.measure
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Ratio between the measure of a physical facet and the one of the reference facet
+fn facet_scale(mesh: &Mesh, facet_vertices: &[usize]) -> f64 {
+    let origin = mesh.vertex(facet_vertices[0]);
+    let edges: Vec<Vec<f64>> = facet_vertices[1..]
+        .iter()
+        .map(|v| {
+            mesh.vertex(*v)
+                .iter()
+                .zip(origin.iter())
+                .map(|(x, x0)| x - x0)
+                .collect()
+        })
+        .collect();
+    let dot = |a: &[f64], b: &[f64]| -> f64 { a.iter().zip(b.iter()).map(|(x, y)| x * y).sum() };
+    // Square root of the Gram determinant of the edges
+    match edges.len() {
+        0 => 1.0,
+        1 => dot(&edges[0], &edges[0]).sqrt(),
+        2 => {
+            let g00 = dot(&edges[0], &edges[0]);
+            let g01 = dot(&edges[0], &edges[1]);
+            let g11 = dot(&edges[1], &edges[1]);
+            (g00 * g11 - g01 * g01).sqrt()
+        }
+        _ => panic!("Facets are only implemented up to dimension 2"),
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::test_meshes::unit_square;
+
+    #[test]
+    fn test_facet_values_boundary() {
+        let mesh = unit_square();
+        let element = LagrangeElement::new(2, 1);
+        let mut values = FacetValues::new(&element, &QuadratureRule::simplex(1, 2));
+        // Right side of the square seen from cell 1
+        values.reinit(&mesh, &[1, 3], 1);
+        assert_eq!(values.local_facet(), 1, "Wrong local facet");
+        let normal = values.normal();
+        assert!(
+            (normal[0] - 1.0).abs() < 1e-14 && normal[1].abs() < 1e-14,
+            "Wrong outward normal"
+        );
+        let length: f64 = (0..values.n_points()).map(|q| values.weight(q)).sum();
+        assert!((length - 1.0).abs() < 1e-14, "Wrong facet length");
+        assert!(
+            (values.facet_measure() - 1.0).abs() < 1e-14,
+            "Wrong facet measure"
+        );
+        for q in 0..values.n_points() {
+            assert!(
+                (values.point(q)[0] - 1.0).abs() < 1e-14,
+                "Quadrature point is not on the facet"
+            );
+            // The shape function of vertex 2 vanishes on the facet
+            assert!(values.shape_value(q, 1).abs() < 1e-14, "Wrong trace");
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_facet_values_interior_matching() {
+        let mesh = unit_square();
+        let element = LagrangeElement::new(2, 2);
+        let quadrature = QuadratureRule::simplex(1, 4);
+        let mut first = FacetValues::new(&element, &quadrature);
+        let mut second = FacetValues::new(&element, &quadrature);
+        first.reinit(&mesh, &[1, 2], 0);
+        second.reinit(&mesh, &[1, 2], 1);
+        let length: f64 = (0..first.n_points()).map(|q| first.weight(q)).sum();
+        assert!(
+            (length - 2.0_f64.sqrt()).abs() < 1e-14,
+            "Wrong interior facet length"
+        );
+        for q in 0..first.n_points() {
+            for (a, b) in first.point(q).iter().zip(second.point(q).iter()) {
+                assert!((a - b).abs() < 1e-14, "Quadrature points do not match");
+            }
+        }
+        for (a, b) in first.normal().iter().zip(second.normal().iter()) {
+            assert!((a + b).abs() < 1e-14, "Normals should be opposite");
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use super::mesh::Mesh;
+use std::collections::HashMap;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Structure holding the facets of a mesh and their connectivity to the cells
+///
+/// A facet is identified by the sorted list of its vertices. The local facet i of a cell is the one
+/// opposite to its local vertex i. Facets touching a single cell are on the boundary of the mesh,
+/// the others are interior facets shared by exactly two cells.
+pub struct Facets {
+    n_vertices: usize,
+    vertices: Vec<usize>,
+    cells: Vec<Vec<(usize, usize)>>,
+    indices: HashMap<Vec<usize>, usize>,
+}
+
+impl Facets {
+    /// Compute the facets of a mesh
+    pub fn new(mesh: &Mesh) -> Self {
+        let n_vertices = mesh.vertices_per_cell() - 1;
+        let mut vertices = Vec::new();
+        let mut cells: Vec<Vec<(usize, usize)>> = Vec::new();
+        let mut indices = HashMap::new();
+        for cell in 0..mesh.n_cells() {
+            let cell_vertices = mesh.cell(cell);
+            for local in 0..cell_vertices.len() {
+                let mut key: Vec<usize> = cell_vertices
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != local)
+                    .map(|(_, v)| *v)
+                    .collect();
+                key.sort_unstable();
+                let facet = *indices.entry(key.clone()).or_insert_with(|| {
+                    vertices.extend_from_slice(&key);
+                    cells.push(Vec::with_capacity(2));
+                    cells.len() - 1
+                });
+                cells[facet].push((cell, local));
+                assert!(
+                    cells[facet].len() <= 2,
+                    "Facet {:?} is shared by more than two cells",
+                    key
+                );
+            }
+        }
+        Facets {
+            n_vertices,
+            vertices,
+            cells,
+            indices,
+        }
+    }
+
+    /// Number of facets
+    pub fn n_facets(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Sorted vertices of a facet
+    pub fn facet_vertices(&self, facet: usize) -> &[usize] {
+        &self.vertices[facet * self.n_vertices..(facet + 1) * self.n_vertices]
+    }
+
+    /// Cells touching a facet with the local index of the facet in each of them
+    pub fn facet_cells(&self, facet: usize) -> &[(usize, usize)] {
+        &self.cells[facet]
+    }
+
+    /// Index of the facet made of the given vertices if it exists
+    pub fn facet_index(&self, vertices: &[usize]) -> Option<usize> {
+        let mut key = vertices.to_vec();
+        key.sort_unstable();
+        self.indices.get(&key).copied()
+    }
+
+    /// Whether a facet is on the boundary of the mesh
+    pub fn is_boundary(&self, facet: usize) -> bool {
+        self.cells[facet].len() == 1
+    }
+
+    /// Indices of the boundary facets
+    pub fn boundary_facets(&self) -> Vec<usize> {
+        (0..self.n_facets())
+            .filter(|f| self.is_boundary(*f))
+            .collect()
+    }
+
+    /// Indices of the interior facets
+    pub fn interior_facets(&self) -> Vec<usize> {
+        (0..self.n_facets())
+            .filter(|f| !self.is_boundary(*f))
+            .collect()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::test_meshes::unit_square;
+
+    #[test]
+    fn test_facets_square() {
+        let mesh = unit_square();
+        let facets = Facets::new(&mesh);
+        assert_eq!(facets.n_facets(), 5, "Wrong number of facets");
+        assert_eq!(
+            facets.boundary_facets().len(),
+            4,
+            "Wrong number of boundary facets"
+        );
+        let interior = facets.interior_facets();
+        assert_eq!(interior.len(), 1, "Wrong number of interior facets");
+        assert_eq!(
+            facets.facet_vertices(interior[0]),
+            &[1, 2],
+            "Wrong interior facet"
+        );
+        assert_eq!(
+            facets.facet_cells(interior[0]),
+            &[(0, 0), (1, 0)],
+            "Wrong cells around the interior facet"
+        );
+        assert_eq!(
+            facets.facet_index(&[2, 1]),
+            Some(interior[0]),
+            "Could not find the interior facet"
+        );
+        assert_eq!(facets.facet_index(&[0, 3]), None, "(0, 3) is not a facet");
+    }
+}
@@ -0,0 +1,907 @@
+use super::assembler::Assembler;
+use super::cell_values::CellValues;
+use super::facet_values::FacetValues;
+use crate::core::arrays::data_wrap::DataWrap;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::sync::Arc;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Symbolic expression appearing in the integrand of a variational form
+///
+/// Expressions are built from the trial and test functions, their gradients, constants, user
+/// coefficients, the position, the facet normal and size and combined with +, -, * (scalar
+/// products), / (by scalars without arguments) and dot. On interior facets the trial and test
+/// functions are multivalued and have to appear inside jump or avg. The shape (scalar or vector)
+/// and the arguments of an expression are checked while it is built so that ill formed integrands
+/// are caught before any assembly.
+#[derive(Clone)]
+pub struct Expr {
+    node: Arc<Node>,
+    shape: Shape,
+    trial: usize,
+    test: usize,
+}
+
+/// Domain an integral is taken over
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Measure {
+    /// Every cell of the mesh
+    Cells,
+    /// The boundary facets carrying a tag
+    ExteriorFacets(usize),
+    /// The facets shared by two cells
+    InteriorFacets,
+}
+
+/// Sum of integrals of expressions over measures
+///
+/// A form with both the trial and test functions in every integrand is bilinear and assembles to a
+/// matrix, one with only the test function is linear and assembles to a vector. Forms with interior
+/// facet integrals couple neighbouring cells and their matrices have to be allocated with
+/// SparsityPattern::from_dofmap_and_facets.
+#[derive(Clone)]
+pub struct Form {
+    integrals: Vec<(Measure, Expr)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Shape {
+    Scalar,
+    Vector,
+}
+
+type ScalarFunction = dyn Fn(&[f64]) -> f64 + Send + Sync;
+type VectorFunction = dyn Fn(&[f64], &mut [f64]) + Send + Sync;
+
+enum Node {
+    Trial,
+    Test,
+    TrialGradient,
+    TestGradient,
+    Constant(f64),
+    Coefficient(Box<ScalarFunction>),
+    VectorCoefficient(Box<VectorFunction>),
+    Position,
+    Normal,
+    FacetSize,
+    Sum(Expr, Expr),
+    Product(Expr, Expr),
+    Quotient(Expr, Expr),
+    Dot(Expr, Expr),
+    Negation(Expr),
+    Jump(Expr),
+    Average(Expr),
+}
+
+// Value of an expression at a point, vectors are padded with zeros up to dimension 3
+#[derive(Clone, Copy)]
+enum Value {
+    Scalar(f64),
+    Vector([f64; 3]),
+}
+
+// Where to evaluate an expression: quadrature point, indices of the trial and test functions and
+// side of the interior facet the arguments are restricted to
+struct Context<'v, Values> {
+    values: &'v Values,
+    q: usize,
+    trial: usize,
+    test: usize,
+    side: Option<usize>,
+}
+
+// Traces of the shape functions on an interior facet seen from both of its cells, the dofs of the
+// first cell come first
+struct InteriorValues<'v, 'e> {
+    first: &'v FacetValues<'e>,
+    second: &'v FacetValues<'e>,
+}
+
+impl<'v, Values> Context<'v, Values> {
+    fn restrict(&self, side: usize) -> Self {
+        Context {
+            values: self.values,
+            q: self.q,
+            trial: self.trial,
+            test: self.test,
+            side: Some(side),
+        }
+    }
+}
+
+impl Expr {
+    fn new(node: Node, shape: Shape, trial: usize, test: usize) -> Self {
+        Expr {
+            node: Arc::new(node),
+            shape,
+            trial,
+            test,
+        }
+    }
+
+    /// Whether the expression is scalar valued
+    pub fn is_scalar(&self) -> bool {
+        self.shape == Shape::Scalar
+    }
+
+    /// Whether the expression depends on the trial function
+    pub fn has_trial(&self) -> bool {
+        self.trial > 0
+    }
+
+    /// Whether the expression depends on the test function
+    pub fn has_test(&self) -> bool {
+        self.test > 0
+    }
+
+    fn evaluate<Values: PointValues>(&self, context: &Context<Values>) -> Value {
+        let values = context.values;
+        let vector = |slice: &[f64]| {
+            let mut v = [0.0; 3];
+            v[..slice.len()].copy_from_slice(slice);
+            Value::Vector(v)
+        };
+        let (q, side) = (context.q, context.side);
+        match self.node.as_ref() {
+            Node::Trial => Value::Scalar(values.shape_value(q, context.trial, side)),
+            Node::Test => Value::Scalar(values.shape_value(q, context.test, side)),
+            Node::TrialGradient => vector(values.shape_gradient(q, context.trial, side)),
+            Node::TestGradient => vector(values.shape_gradient(q, context.test, side)),
+            Node::Constant(c) => Value::Scalar(*c),
+            Node::Coefficient(f) => Value::Scalar(f(values.point(context.q))),
+            Node::VectorCoefficient(f) => {
+                let mut v = [0.0; 3];
+                f(values.point(context.q), &mut v[..values.dim()]);
+                Value::Vector(v)
+            }
+            Node::Position => vector(values.point(context.q)),
+            Node::Normal => vector(
+                values
+                    .normal()
+                    .expect("Normals are only available on facet integrals"),
+            ),
+            Node::FacetSize => Value::Scalar(
+                values
+                    .facet_size()
+                    .expect("Facet sizes are only available on facet integrals"),
+            ),
+            Node::Sum(a, b) => match (a.evaluate(context), b.evaluate(context)) {
+                (Value::Scalar(x), Value::Scalar(y)) => Value::Scalar(x + y),
+                (Value::Vector(x), Value::Vector(y)) => {
+                    Value::Vector([x[0] + y[0], x[1] + y[1], x[2] + y[2]])
+                }
+                _ => unreachable!(),
+            },
+            Node::Product(a, b) => match (a.evaluate(context), b.evaluate(context)) {
+                (Value::Scalar(x), Value::Scalar(y)) => Value::Scalar(x * y),
+                (Value::Scalar(s), Value::Vector(v)) | (Value::Vector(v), Value::Scalar(s)) => {
+                    Value::Vector([s * v[0], s * v[1], s * v[2]])
+                }
+                _ => unreachable!(),
+            },
+            Node::Quotient(a, b) => match (a.evaluate(context), b.evaluate(context)) {
+                (Value::Scalar(x), Value::Scalar(y)) => Value::Scalar(x / y),
+                (Value::Vector(v), Value::Scalar(s)) => {
+                    Value::Vector([v[0] / s, v[1] / s, v[2] / s])
+                }
+                _ => unreachable!(),
+            },
+            Node::Dot(a, b) => match (a.evaluate(context), b.evaluate(context)) {
+                (Value::Vector(x), Value::Vector(y)) => {
+                    Value::Scalar(x[0] * y[0] + x[1] * y[1] + x[2] * y[2])
+                }
+                _ => unreachable!(),
+            },
+            Node::Negation(a) => match a.evaluate(context) {
+                Value::Scalar(x) => Value::Scalar(-x),
+                Value::Vector(v) => Value::Vector([-v[0], -v[1], -v[2]]),
+            },
+            Node::Jump(a) => combine(
+                a.evaluate(&context.restrict(0)),
+                a.evaluate(&context.restrict(1)),
+                1.0,
+                -1.0,
+            ),
+            Node::Average(a) => combine(
+                a.evaluate(&context.restrict(0)),
+                a.evaluate(&context.restrict(1)),
+                0.5,
+                0.5,
+            ),
+        }
+    }
+
+    fn evaluate_scalar<Values: PointValues>(&self, context: &Context<Values>) -> f64 {
+        match self.evaluate(context) {
+            Value::Scalar(x) => x,
+            Value::Vector(_) => unreachable!(),
+        }
+    }
+
+    // Check that jump and avg only appear on interior facets where they wrap every argument
+    fn check_restrictions(&self, interior: bool, restricted: bool) {
+        match self.node.as_ref() {
+            Node::Trial | Node::Test | Node::TrialGradient | Node::TestGradient => assert!(
+                !interior || restricted,
+                "Arguments should appear inside jump or avg on interior facets"
+            ),
+            Node::Jump(a) | Node::Average(a) => {
+                assert!(
+                    interior,
+                    "jump and avg are only available on interior facet integrals"
+                );
+                a.check_restrictions(interior, true);
+            }
+            Node::Sum(a, b) | Node::Product(a, b) | Node::Quotient(a, b) | Node::Dot(a, b) => {
+                a.check_restrictions(interior, restricted);
+                b.check_restrictions(interior, restricted);
+            }
+            Node::Negation(a) => a.check_restrictions(interior, restricted),
+            _ => (),
+        }
+    }
+}
+
+impl Form {
+    /// Number of arguments of the form (2 for bilinear forms and 1 for linear forms)
+    pub fn rank(&self) -> usize {
+        match self.integrals.first() {
+            Some((_, expr)) if expr.has_trial() => 2,
+            _ => 1,
+        }
+    }
+
+    /// Integrals making up the form
+    pub fn integrals(&self) -> &[(Measure, Expr)] {
+        &self.integrals
+    }
+
+    /// Compile the cell integrals of a bilinear form to a kernel for Assembler::assemble_matrix
+    pub fn cell_matrix_kernel(&self) -> impl Fn(&CellValues, &mut DataWrap<f64, [usize; 2]>) + '_ {
+        assert!(self.rank() == 2, "Only bilinear forms assemble to matrices");
+        move |values, local| {
+            for (measure, expr) in self.integrals.iter() {
+                if *measure == Measure::Cells {
+                    integrate_matrix(expr, values, local);
+                }
+            }
+        }
+    }
+
+    /// Compile the cell integrals of a linear form to a kernel for Assembler::assemble_vector
+    pub fn cell_vector_kernel(&self) -> impl Fn(&CellValues, &mut DataWrap<f64, [usize; 1]>) + '_ {
+        assert!(self.rank() == 1, "Only linear forms assemble to vectors");
+        move |values, local| {
+            for (measure, expr) in self.integrals.iter() {
+                if *measure == Measure::Cells {
+                    integrate_vector(expr, values, local);
+                }
+            }
+        }
+    }
+
+    /// Assemble a bilinear form to a global matrix
+    ///
+    /// The matrix needs the pattern of SparsityPattern::from_dofmap_and_facets when the form has
+    /// interior facet integrals.
+    pub fn assemble_matrix(&self, assembler: &Assembler, matrix: &mut SparseCSR<f64>) {
+        assembler.assemble_matrix(matrix, self.cell_matrix_kernel());
+        for tag in self.facet_tags() {
+            assembler.assemble_exterior_facets(tag, matrix, |values, local| {
+                for (measure, expr) in self.integrals.iter() {
+                    if *measure == Measure::ExteriorFacets(tag) {
+                        integrate_matrix(expr, values, local);
+                    }
+                }
+            });
+        }
+        if self.has_interior_facets() {
+            assembler.assemble_interior_facets(matrix, |first, second, local| {
+                let values = InteriorValues { first, second };
+                for (measure, expr) in self.integrals.iter() {
+                    if *measure == Measure::InteriorFacets {
+                        integrate_matrix(expr, &values, local);
+                    }
+                }
+            });
+        }
+    }
+
+    /// Assemble a linear form to a global vector
+    pub fn assemble_vector(&self, assembler: &Assembler, vector: &mut [f64]) {
+        assembler.assemble_vector(vector, self.cell_vector_kernel());
+        for tag in self.facet_tags() {
+            assembler.assemble_exterior_facets_vector(tag, vector, |values, local| {
+                for (measure, expr) in self.integrals.iter() {
+                    if *measure == Measure::ExteriorFacets(tag) {
+                        integrate_vector(expr, values, local);
+                    }
+                }
+            });
+        }
+        if self.has_interior_facets() {
+            assembler.assemble_interior_facets_vector(vector, |first, second, local| {
+                let values = InteriorValues { first, second };
+                for (measure, expr) in self.integrals.iter() {
+                    if *measure == Measure::InteriorFacets {
+                        integrate_vector(expr, &values, local);
+                    }
+                }
+            });
+        }
+    }
+
+    fn has_interior_facets(&self) -> bool {
+        self.integrals
+            .iter()
+            .any(|(measure, _)| *measure == Measure::InteriorFacets)
+    }
+
+    // Distinct tags of the facet integrals
+    fn facet_tags(&self) -> Vec<usize> {
+        let mut tags: Vec<usize> = self
+            .integrals
+            .iter()
+            .filter_map(|(measure, _)| match measure {
+                Measure::ExteriorFacets(tag) => Some(*tag),
+                Measure::Cells | Measure::InteriorFacets => None,
+            })
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+        tags
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Traits
+//--------------------------------------------------------------------------------------------------
+
+// Common interface of the shape function values on cells and facets, the side restricting the
+// arguments is only used on interior facets
+trait PointValues {
+    fn dim(&self) -> usize;
+    fn n_points(&self) -> usize;
+    fn n_dofs(&self) -> usize;
+    fn shape_value(&self, q: usize, i: usize, side: Option<usize>) -> f64;
+    fn shape_gradient(&self, q: usize, i: usize, side: Option<usize>) -> &[f64];
+    fn weight(&self, q: usize) -> f64;
+    fn point(&self, q: usize) -> &[f64];
+    fn normal(&self) -> Option<&[f64]>;
+    fn facet_size(&self) -> Option<f64>;
+}
+
+impl PointValues for CellValues {
+    fn dim(&self) -> usize {
+        CellValues::geometric_dim(self)
+    }
+    fn n_points(&self) -> usize {
+        CellValues::n_points(self)
+    }
+    fn n_dofs(&self) -> usize {
+        CellValues::n_dofs(self)
+    }
+    fn shape_value(&self, q: usize, i: usize, _: Option<usize>) -> f64 {
+        CellValues::shape_value(self, q, i)
+    }
+    fn shape_gradient(&self, q: usize, i: usize, _: Option<usize>) -> &[f64] {
+        CellValues::shape_gradient(self, q, i)
+    }
+    fn weight(&self, q: usize) -> f64 {
+        CellValues::weight(self, q)
+    }
+    fn point(&self, q: usize) -> &[f64] {
+        CellValues::point(self, q)
+    }
+    fn normal(&self) -> Option<&[f64]> {
+        None
+    }
+    fn facet_size(&self) -> Option<f64> {
+        None
+    }
+}
+
+impl PointValues for FacetValues<'_> {
+    fn dim(&self) -> usize {
+        FacetValues::geometric_dim(self)
+    }
+    fn n_points(&self) -> usize {
+        FacetValues::n_points(self)
+    }
+    fn n_dofs(&self) -> usize {
+        FacetValues::n_dofs(self)
+    }
+    fn shape_value(&self, q: usize, i: usize, _: Option<usize>) -> f64 {
+        FacetValues::shape_value(self, q, i)
+    }
+    fn shape_gradient(&self, q: usize, i: usize, _: Option<usize>) -> &[f64] {
+        FacetValues::shape_gradient(self, q, i)
+    }
+    fn weight(&self, q: usize) -> f64 {
+        FacetValues::weight(self, q)
+    }
+    fn point(&self, q: usize) -> &[f64] {
+        FacetValues::point(self, q)
+    }
+    fn normal(&self) -> Option<&[f64]> {
+        Some(FacetValues::normal(self))
+    }
+    fn facet_size(&self) -> Option<f64> {
+        Some(size_from_measure(self))
+    }
+}
+
+// Shape functions of the other side vanish and the normal is the one pointing out of the first cell
+impl PointValues for InteriorValues<'_, '_> {
+    fn dim(&self) -> usize {
+        self.first.geometric_dim()
+    }
+    fn n_points(&self) -> usize {
+        self.first.n_points()
+    }
+    fn n_dofs(&self) -> usize {
+        2 * self.first.n_dofs()
+    }
+    fn shape_value(&self, q: usize, i: usize, side: Option<usize>) -> f64 {
+        let n = self.first.n_dofs();
+        match (side, i < n) {
+            (Some(0), true) => self.first.shape_value(q, i),
+            (Some(1), false) => self.second.shape_value(q, i - n),
+            (Some(_), _) => 0.0,
+            (None, _) => unreachable!(),
+        }
+    }
+    fn shape_gradient(&self, q: usize, i: usize, side: Option<usize>) -> &[f64] {
+        const ZERO: [f64; 3] = [0.0; 3];
+        let n = self.first.n_dofs();
+        match (side, i < n) {
+            (Some(0), true) => self.first.shape_gradient(q, i),
+            (Some(1), false) => self.second.shape_gradient(q, i - n),
+            (Some(_), _) => &ZERO[..self.dim()],
+            (None, _) => unreachable!(),
+        }
+    }
+    fn weight(&self, q: usize) -> f64 {
+        self.first.weight(q)
+    }
+    fn point(&self, q: usize) -> &[f64] {
+        self.first.point(q)
+    }
+    fn normal(&self) -> Option<&[f64]> {
+        Some(self.first.normal())
+    }
+    fn facet_size(&self) -> Option<f64> {
+        Some(size_from_measure(self.first))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Operators
+//--------------------------------------------------------------------------------------------------
+
+impl Add for Expr {
+    type Output = Expr;
+    fn add(self, other: Expr) -> Expr {
+        assert!(
+            self.shape == other.shape,
+            "Cannot add scalar and vector expressions"
+        );
+        assert!(
+            self.trial == other.trial && self.test == other.test,
+            "Terms of a sum should depend on the same arguments"
+        );
+        let (shape, trial, test) = (self.shape, self.trial, self.test);
+        Expr::new(Node::Sum(self, other), shape, trial, test)
+    }
+}
+
+impl Sub for Expr {
+    type Output = Expr;
+    fn sub(self, other: Expr) -> Expr {
+        self + (-other)
+    }
+}
+
+impl Neg for Expr {
+    type Output = Expr;
+    fn neg(self) -> Expr {
+        let (shape, trial, test) = (self.shape, self.trial, self.test);
+        Expr::new(Node::Negation(self), shape, trial, test)
+    }
+}
+
+impl Mul for Expr {
+    type Output = Expr;
+    fn mul(self, other: Expr) -> Expr {
+        assert!(
+            self.is_scalar() || other.is_scalar(),
+            "Use dot to multiply two vector expressions"
+        );
+        let shape = if self.is_scalar() {
+            other.shape
+        } else {
+            self.shape
+        };
+        let (trial, test) = (self.trial + other.trial, self.test + other.test);
+        assert!(
+            trial <= 1 && test <= 1,
+            "Forms should be linear in the trial and test functions"
+        );
+        Expr::new(Node::Product(self, other), shape, trial, test)
+    }
+}
+
+impl Mul<Expr> for f64 {
+    type Output = Expr;
+    fn mul(self, other: Expr) -> Expr {
+        constant(self) * other
+    }
+}
+
+impl Mul<f64> for Expr {
+    type Output = Expr;
+    fn mul(self, other: f64) -> Expr {
+        self * constant(other)
+    }
+}
+
+impl Div for Expr {
+    type Output = Expr;
+    fn div(self, other: Expr) -> Expr {
+        assert!(
+            other.is_scalar() && !other.has_trial() && !other.has_test(),
+            "Only divisions by scalars without arguments are allowed"
+        );
+        let (shape, trial, test) = (self.shape, self.trial, self.test);
+        Expr::new(Node::Quotient(self, other), shape, trial, test)
+    }
+}
+
+impl Div<f64> for Expr {
+    type Output = Expr;
+    fn div(self, other: f64) -> Expr {
+        self / constant(other)
+    }
+}
+
+impl Mul<Measure> for Expr {
+    type Output = Form;
+    fn mul(self, measure: Measure) -> Form {
+        assert!(self.is_scalar(), "Integrands should be scalar");
+        assert!(
+            self.has_test(),
+            "Integrands should depend on the test function"
+        );
+        self.check_restrictions(measure == Measure::InteriorFacets, false);
+        Form {
+            integrals: vec![(measure, self)],
+        }
+    }
+}
+
+impl Add for Form {
+    type Output = Form;
+    fn add(mut self, other: Form) -> Form {
+        assert!(
+            self.rank() == other.rank(),
+            "Cannot add bilinear and linear forms"
+        );
+        self.integrals.extend(other.integrals);
+        self
+    }
+}
+
+impl Sub for Form {
+    type Output = Form;
+    fn sub(mut self, other: Form) -> Form {
+        assert!(
+            self.rank() == other.rank(),
+            "Cannot subtract bilinear and linear forms"
+        );
+        self.integrals.extend(
+            other
+                .integrals
+                .into_iter()
+                .map(|(measure, expr)| (measure, -expr)),
+        );
+        self
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Trial function (the unknown) of a bilinear form
+pub fn trial() -> Expr {
+    Expr::new(Node::Trial, Shape::Scalar, 1, 0)
+}
+
+/// Test function of a bilinear or linear form
+pub fn test() -> Expr {
+    Expr::new(Node::Test, Shape::Scalar, 0, 1)
+}
+
+/// Gradient of the trial or test function
+pub fn grad(expr: Expr) -> Expr {
+    match expr.node.as_ref() {
+        Node::Trial => Expr::new(Node::TrialGradient, Shape::Vector, 1, 0),
+        Node::Test => Expr::new(Node::TestGradient, Shape::Vector, 0, 1),
+        _ => panic!("Gradients can only be taken of the trial and test functions"),
+    }
+}
+
+/// Scalar product of two vector expressions
+pub fn dot(a: Expr, b: Expr) -> Expr {
+    assert!(
+        !a.is_scalar() && !b.is_scalar(),
+        "dot needs two vector expressions"
+    );
+    let (trial, test) = (a.trial + b.trial, a.test + b.test);
+    assert!(
+        trial <= 1 && test <= 1,
+        "Forms should be linear in the trial and test functions"
+    );
+    Expr::new(Node::Dot(a, b), Shape::Scalar, trial, test)
+}
+
+/// Constant scalar
+pub fn constant(value: f64) -> Expr {
+    Expr::new(Node::Constant(value), Shape::Scalar, 0, 0)
+}
+
+/// Scalar function of the physical coordinates
+pub fn coefficient<Function>(function: Function) -> Expr
+where
+    Function: Fn(&[f64]) -> f64 + Send + Sync + 'static,
+{
+    Expr::new(Node::Coefficient(Box::new(function)), Shape::Scalar, 0, 0)
+}
+
+/// Vector function of the physical coordinates filling a slice of the dimension of the mesh
+pub fn vector_coefficient<Function>(function: Function) -> Expr
+where
+    Function: Fn(&[f64], &mut [f64]) + Send + Sync + 'static,
+{
+    Expr::new(
+        Node::VectorCoefficient(Box::new(function)),
+        Shape::Vector,
+        0,
+        0,
+    )
+}
+
+/// Physical coordinates
+pub fn position() -> Expr {
+    Expr::new(Node::Position, Shape::Vector, 0, 0)
+}
+
+/// Unit outward normal (only in facet integrals)
+pub fn normal() -> Expr {
+    Expr::new(Node::Normal, Shape::Vector, 0, 0)
+}
+
+/// Size of the facet (its length in 2 dimensions and the square root of its area in 3 dimensions,
+/// only in facet integrals)
+pub fn facet_size() -> Expr {
+    Expr::new(Node::FacetSize, Shape::Scalar, 0, 0)
+}
+
+/// Difference between the values seen from the first and the second cell of an interior facet
+///
+/// Combined with normal(), which is the normal pointing out of the first cell on interior facets,
+/// jump(u) * normal() is the usual vector jump u+ n+ + u- n-.
+pub fn jump(expr: Expr) -> Expr {
+    let (shape, trial, test) = (expr.shape, expr.trial, expr.test);
+    Expr::new(Node::Jump(expr), shape, trial, test)
+}
+
+/// Mean of the values seen from both cells of an interior facet
+pub fn avg(expr: Expr) -> Expr {
+    let (shape, trial, test) = (expr.shape, expr.trial, expr.test);
+    Expr::new(Node::Average(expr), shape, trial, test)
+}
+
+/// Integration over the cells of the mesh
+pub fn dx() -> Measure {
+    Measure::Cells
+}
+
+/// Integration over the boundary facets carrying a tag
+pub fn ds(tag: usize) -> Measure {
+    Measure::ExteriorFacets(tag)
+}
+
+/// Integration over the interior facets
+pub fn ds_interior() -> Measure {
+    Measure::InteriorFacets
+}
+
+// Linear combination of two values of the same shape
+fn combine(x: Value, y: Value, a: f64, b: f64) -> Value {
+    match (x, y) {
+        (Value::Scalar(x), Value::Scalar(y)) => Value::Scalar(a * x + b * y),
+        (Value::Vector(x), Value::Vector(y)) => Value::Vector([
+            a * x[0] + b * y[0],
+            a * x[1] + b * y[1],
+            a * x[2] + b * y[2],
+        ]),
+        _ => unreachable!(),
+    }
+}
+
+// Characteristic length of a facet from its measure
+fn size_from_measure(values: &FacetValues) -> f64 {
+    match values.dim() {
+        1 => 1.0,
+        2 => values.facet_measure(),
+        _ => values.facet_measure().sqrt(),
+    }
+}
+
+// Add the integral of a bilinear integrand to a local matrix
+fn integrate_matrix<Values: PointValues>(
+    expr: &Expr,
+    values: &Values,
+    local: &mut DataWrap<f64, [usize; 2]>,
+) {
+    let n = values.n_dofs();
+    for q in 0..values.n_points() {
+        let weight = values.weight(q);
+        for test in 0..n {
+            for trial in 0..n {
+                let context = Context {
+                    values,
+                    q,
+                    trial,
+                    test,
+                    side: None,
+                };
+                local[test * n + trial] += expr.evaluate_scalar(&context) * weight;
+            }
+        }
+    }
+}
+
+// Add the integral of a linear integrand to a local vector
+fn integrate_vector<Values: PointValues>(
+    expr: &Expr,
+    values: &Values,
+    local: &mut DataWrap<f64, [usize; 1]>,
+) {
+    for q in 0..values.n_points() {
+        let weight = values.weight(q);
+        for test in 0..values.n_dofs() {
+            let context = Context {
+                values,
+                q,
+                trial: 0,
+                test,
+                side: None,
+            };
+            local[test] += expr.evaluate_scalar(&context) * weight;
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::dof_map::DofMap;
+    use crate::discretizations::sparsity::SparsityPattern;
+    use crate::discretizations::test_meshes::unit_square;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::spaces::quadrature::QuadratureRule;
+
+    #[test]
+    fn test_forms_bilinear() {
+        let mut mesh = unit_square();
+        mesh.tag_boundary(1, |x| x[0] == 1.0 || x[1] == 1.0);
+        let element = LagrangeElement::new(2, 2);
+        let dof_map = DofMap::lagrange(&mesh, &element);
+        let assembler = Assembler::new(&mesh, &element, &dof_map, QuadratureRule::simplex(2, 4));
+        let pattern = SparsityPattern::from_dofmap(&dof_map);
+        let mass = trial() * test() * dx();
+        assert_eq!(mass.rank(), 2, "Mass form should be bilinear");
+        let mut matrix = pattern.to_csr(0.0);
+        mass.assemble_matrix(&assembler, &mut matrix);
+        let total: f64 = matrix.values().iter().sum();
+        assert!((total - 1.0).abs() < 1e-13, "Mass should sum to the area");
+        // Stiffness rows sum to zero and the boundary term adds the tagged length
+        let form = dot(grad(trial()), grad(test())) * dx() + 2.0 * trial() * test() * ds(1);
+        let mut matrix = pattern.to_csr(0.0);
+        form.assemble_matrix(&assembler, &mut matrix);
+        let total: f64 = matrix.values().iter().sum();
+        assert!(
+            (total - 4.0).abs() < 1e-13,
+            "Wrong total of the stiffness and boundary terms"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_forms_linear() {
+        let mut mesh = unit_square();
+        mesh.tag_boundary(1, |x| x[0] == 1.0 || x[1] == 1.0);
+        let element = LagrangeElement::new(2, 1);
+        let dof_map = DofMap::lagrange(&mesh, &element);
+        let assembler = Assembler::new(&mesh, &element, &dof_map, QuadratureRule::simplex(2, 2));
+        // Integral of x over the square and flux of the position through the tagged sides
+        let form = coefficient(|x| x[0]) * test() * dx()
+            + dot(position(), normal()) * test() * ds(1)
+            - constant(1.0) * test() * dx();
+        assert_eq!(form.rank(), 1, "Form should be linear");
+        let mut vector = vec![0.0; dof_map.n_dofs()];
+        form.assemble_vector(&assembler, &mut vector);
+        let total: f64 = vector.iter().sum();
+        assert!(
+            (total - 1.5).abs() < 1e-13,
+            "Wrong total of the linear form"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_forms_interior_facets() {
+        let mesh = unit_square();
+        let element = LagrangeElement::new(2, 1);
+        let dof_map = DofMap::discontinuous(&mesh, &element);
+        let assembler = Assembler::new(&mesh, &element, &dof_map, QuadratureRule::simplex(2, 2));
+        let pattern = SparsityPattern::from_dofmap_and_facets(&dof_map, assembler.facets());
+        let form = jump(trial()) * jump(test()) * ds_interior();
+        let mut matrix = pattern.to_csr(0.0);
+        form.assemble_matrix(&assembler, &mut matrix);
+        let quadratic = |u: &[f64]| -> f64 {
+            let mut product = vec![0.0; u.len()];
+            matrix.apply(u, &mut product);
+            product.iter().zip(u.iter()).map(|(a, b)| a * b).sum()
+        };
+        assert!(
+            quadratic(&[1.0; 6]).abs() < 1e-14,
+            "Constants should have no jump"
+        );
+        // Indicator of the first cell jumps by one along the diagonal
+        assert!(
+            (quadratic(&[1.0, 1.0, 1.0, 0.0, 0.0, 0.0]) - 2.0_f64.sqrt()).abs() < 1e-14,
+            "Wrong jump of the indicator"
+        );
+        // Flux of the position through the diagonal: x . n = 1 / sqrt(2) on it
+        let form = avg(test()) * dot(position(), normal()) / facet_size() * ds_interior();
+        let mut vector = vec![0.0; dof_map.n_dofs()];
+        form.assemble_vector(&assembler, &mut vector);
+        let total: f64 = vector.iter().sum();
+        assert!(
+            (total - 1.0 / 2.0_f64.sqrt()).abs() < 1e-14,
+            "Wrong interior linear form"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    #[should_panic]
+    fn test_forms_unrestricted_interior() {
+        let _ = trial() * jump(test()) * ds_interior();
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    #[should_panic]
+    fn test_forms_non_linear() {
+        let _ = trial() * trial() * test() * dx();
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    #[should_panic]
+    fn test_forms_mismatched_sum() {
+        let _ = (trial() * test() + test()) * dx();
+    }
+}
@@ -0,0 +1,356 @@
+use super::cell_mapping::CellMapping;
+use super::function_space::FunctionSpace;
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::data_traits::DataContainer;
+use std::ops::{AddAssign, MulAssign, SubAssign};
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Structure pairing a vector of dof values with the FunctionSpace it discretizes
+///
+/// The values follow the global numbering of the space (components interleaved). Arithmetic is
+/// only defined between functions of the same space.
+#[derive(Clone)]
+pub struct Function<'a> {
+    space: &'a FunctionSpace<'a>,
+    values: Vec<f64>,
+}
+
+impl<'a> Function<'a> {
+    /// Build the zero function of a space
+    pub fn new(space: &'a FunctionSpace<'a>) -> Self {
+        Function {
+            space,
+            values: vec![0.0; space.n_dofs()],
+        }
+    }
+
+    /// Build a function of a space from its dof values
+    pub fn from_values(space: &'a FunctionSpace<'a>, values: Vec<f64>) -> Self {
+        assert!(
+            values.len() == space.n_dofs(),
+            "Values do not match the number of dofs of the space"
+        );
+        Function { space, values }
+    }
+
+    /// Space of the function
+    pub fn space(&self) -> &'a FunctionSpace<'a> {
+        self.space
+    }
+
+    /// Dof values
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// Mutable dof values
+    pub fn values_mut(&mut self) -> &mut [f64] {
+        &mut self.values
+    }
+
+    /// Give back the dof values
+    pub fn into_values(self) -> Vec<f64> {
+        self.values
+    }
+
+    /// Set the function to the nodal interpolant of a scalar expression of the physical coordinates
+    pub fn interpolate<Expression>(&mut self, expression: Expression)
+    where
+        Expression: Fn(&[f64]) -> f64,
+    {
+        assert!(
+            self.space.n_components() == 1,
+            "Use interpolate_vector for spaces with several components"
+        );
+        self.interpolate_vector(|x, value| value[0] = expression(x));
+    }
+
+    /// Set the function to the nodal interpolant of an expression filling the components at the
+    /// given physical coordinates
+    pub fn interpolate_vector<Expression>(&mut self, expression: Expression)
+    where
+        Expression: Fn(&[f64], &mut [f64]),
+    {
+        let space = self.space;
+        let mesh = space.mesh();
+        let element = space.element();
+        let n_components = space.n_components();
+        let mut x = vec![0.0; mesh.geometric_dim()];
+        let mut value = vec![0.0; n_components];
+        if mesh.n_cells() == 0 {
+            return;
+        }
+        let mut mapping = CellMapping::new(mesh, 0);
+        for cell in 0..mesh.n_cells() {
+            mapping.reinit(mesh, cell);
+            for (node, dof) in space.dof_map().cell_dofs(cell).iter().enumerate() {
+                mapping.map_point(&element.node_coordinates(node), &mut x);
+                expression(&x, &mut value);
+                for (c, v) in value.iter().enumerate() {
+                    self.values[space.dof(*dof, c)] = *v;
+                }
+            }
+        }
+    }
+
+    /// Dof values of one component following the scalar numbering of the space
+    pub fn component(&self, component: usize) -> Vec<f64> {
+        let n_components = self.space.n_components();
+        assert!(component < n_components, "Component out of bounds");
+        self.values
+            .iter()
+            .skip(component)
+            .step_by(n_components)
+            .copied()
+            .collect()
+    }
+
+    /// Set the dof values of one component from values following the scalar numbering
+    pub fn set_component(&mut self, component: usize, values: &[f64]) {
+        let n_components = self.space.n_components();
+        assert!(component < n_components, "Component out of bounds");
+        assert!(
+            values.len() * n_components == self.values.len(),
+            "Values do not match the number of scalar dofs of the space"
+        );
+        for (dof, value) in values.iter().enumerate() {
+            self.values[dof * n_components + component] = *value;
+        }
+    }
+
+    /// Add a times other to the function
+    pub fn axpy(&mut self, a: f64, other: &Function) {
+        self.check_space(other);
+        for (v, o) in self.values.iter_mut().zip(other.values.iter()) {
+            *v += a * o;
+        }
+    }
+
+    /// Values of the function at the vertices of the mesh as a (vertices, components) array
+    ///
+    /// For discontinuous spaces a vertex takes the value of the last cell touching it.
+    pub fn vertex_values(&self) -> DataHold<f64, [usize; 2]> {
+        let space = self.space;
+        let mesh = space.mesh();
+        let n_components = space.n_components();
+        let mut values = vec![0.0; mesh.n_vertices() * n_components];
+        // Vertex nodes come first in the local ordering of the element
+        for cell in 0..mesh.n_cells() {
+            let dofs = space.dof_map().cell_dofs(cell);
+            for (vertex, dof) in mesh.cell(cell).iter().zip(dofs.iter()) {
+                for c in 0..n_components {
+                    values[vertex * n_components + c] = self.values[space.dof(*dof, c)];
+                }
+            }
+        }
+        DataHold::new(values, [mesh.n_vertices(), n_components])
+    }
+
+    /// Evaluate the function in a cell at reference coordinates filling the components
+    pub fn eval_in_cell(&self, cell: usize, reference: &[f64], value: &mut [f64]) {
+        let space = self.space;
+        let mut shape_values = vec![0.0; space.element().n_dofs()];
+        space.element().values(reference, &mut shape_values);
+        value.iter_mut().for_each(|v| *v = 0.0);
+        for (phi, dof) in shape_values
+            .iter()
+            .zip(space.dof_map().cell_dofs(cell).iter())
+        {
+            for (c, v) in value.iter_mut().enumerate() {
+                *v += phi * self.values[space.dof(*dof, c)];
+            }
+        }
+    }
+
+    /// Evaluate the physical gradient of the function in a cell at reference coordinates
+    ///
+    /// The gradient is written as a flat array of size (components, geometric dimension), it is the
+    /// tangential gradient on curves and surfaces.
+    pub fn gradient_in_cell(&self, cell: usize, reference: &[f64], gradient: &mut [f64]) {
+        let space = self.space;
+        let (dim, geometric_dim) = (space.element().dim(), space.mesh().geometric_dim());
+        let n_dofs = space.element().n_dofs();
+        assert!(
+            gradient.len() == space.n_components() * geometric_dim,
+            "Gradient buffer does not match the components and dimension of the space"
+        );
+        let mapping = CellMapping::new(space.mesh(), cell);
+        let mut reference_gradients = vec![0.0; n_dofs * dim];
+        space
+            .element()
+            .gradients(reference, &mut reference_gradients);
+        let mut physical = vec![0.0; geometric_dim];
+        gradient.iter_mut().for_each(|g| *g = 0.0);
+        for (grad, dof) in reference_gradients
+            .chunks(dim)
+            .zip(space.dof_map().cell_dofs(cell).iter())
+        {
+            mapping.map_gradient(grad, &mut physical);
+            for (c, row) in gradient.chunks_mut(geometric_dim).enumerate() {
+                let value = self.values[space.dof(*dof, c)];
+                for (g, p) in row.iter_mut().zip(physical.iter()) {
+                    *g += value * p;
+                }
+            }
+        }
+    }
+
+    /// Evaluate the function at a physical point if it lies in the mesh
+    pub fn eval(&self, point: &[f64]) -> Option<Vec<f64>> {
+        let (cell, reference) = self.space.mesh().locate(point)?;
+        let mut value = vec![0.0; self.space.n_components()];
+        self.eval_in_cell(cell, &reference, &mut value);
+        Some(value)
+    }
+
+    /// Evaluate the function at (points, geometric dimension) physical points
+    ///
+    /// Points lying outside of the mesh get no value.
+    pub fn eval_at(&self, points: &DataHold<f64, [usize; 2]>) -> Vec<Option<Vec<f64>>> {
+        let dim = self.space.mesh().geometric_dim();
+        assert!(
+            points.dimensions()[1] == dim,
+            "Points do not match the dimension of the mesh"
+        );
+        points.chunks(dim).map(|point| self.eval(point)).collect()
+    }
+
+    fn check_space(&self, other: &Function) {
+        assert!(
+            std::ptr::eq(self.space, other.space),
+            "Functions do not belong to the same space"
+        );
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Operators
+//--------------------------------------------------------------------------------------------------
+
+impl AddAssign<&Function<'_>> for Function<'_> {
+    fn add_assign(&mut self, other: &Function) {
+        self.axpy(1.0, other);
+    }
+}
+
+impl SubAssign<&Function<'_>> for Function<'_> {
+    fn sub_assign(&mut self, other: &Function) {
+        self.axpy(-1.0, other);
+    }
+}
+
+impl MulAssign<f64> for Function<'_> {
+    fn mul_assign(&mut self, a: f64) {
+        self.values.iter_mut().for_each(|v| *v *= a);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::test_meshes::{empty_mesh, unit_square};
+    use crate::spaces::lagrange::LagrangeElement;
+
+    #[test]
+    fn test_function_interpolate() {
+        let mesh = unit_square();
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        let mut u = Function::new(&space);
+        u.interpolate(|x| x[0] * x[1]);
+        let vertex_values = u.vertex_values();
+        assert_eq!(
+            vertex_values.dimensions(),
+            &[4, 1],
+            "Wrong vertex values shape"
+        );
+        assert_eq!(
+            vertex_values.as_ref(),
+            &[0.0, 0.0, 0.0, 1.0],
+            "Wrong vertex values"
+        );
+        // Midpoint of the diagonal edge
+        assert!(
+            u.values().iter().any(|v| (v - 0.25).abs() < 1e-14),
+            "Missing edge node value"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_function_arithmetic_and_components() {
+        let mesh = unit_square();
+        let space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let mut u = Function::new(&space);
+        u.interpolate_vector(|x, value| {
+            value[0] = x[0];
+            value[1] = 1.0;
+        });
+        let mut v = u.clone();
+        v *= 2.0;
+        v -= &u;
+        v += &u;
+        assert_eq!(
+            v.component(0),
+            vec![0.0, 2.0, 0.0, 2.0],
+            "Wrong first component"
+        );
+        assert_eq!(v.component(1), vec![2.0; 4], "Wrong second component");
+        v.set_component(1, &[0.0; 4]);
+        assert_eq!(
+            v.vertex_values().as_ref(),
+            &[0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 2.0, 0.0],
+            "Wrong vertex values"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    #[should_panic]
+    fn test_function_different_spaces() {
+        let mesh = unit_square();
+        let first = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let second = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let mut u = Function::new(&first);
+        u += &Function::new(&second);
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_function_eval_at() {
+        let mesh = unit_square();
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        let mut u = Function::new(&space);
+        u.interpolate(|x| x[0] * x[0] - x[1]);
+        let points = DataHold::new(vec![0.2, 0.3, 0.9, 0.6, 2.0, 0.5], [3, 2]);
+        let values = u.eval_at(&points);
+        for (point, value) in points.chunks(2).zip(values.iter()).take(2) {
+            let exact = point[0] * point[0] - point[1];
+            assert!(
+                (value.as_ref().unwrap()[0] - exact).abs() < 1e-14,
+                "Wrong value at {:?}",
+                point
+            );
+        }
+        assert!(values[2].is_none(), "Point outside of the mesh has a value");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_function_empty_mesh() {
+        let mesh = empty_mesh();
+        let space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let mut u = Function::new(&space);
+        u.interpolate_vector(|x, u| u.copy_from_slice(x));
+        assert!(
+            u.values().is_empty(),
+            "Functions of empty meshes have no values"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use super::assembler::Assembler;
+use super::cell_mapping::CellMapping;
+use super::dof_map::DofMap;
+use super::facets::Facets;
+use super::mesh::Mesh;
+use crate::core::arrays::data_hold::DataHold;
+use crate::spaces::lagrange::LagrangeElement;
+use crate::spaces::quadrature::QuadratureRule;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Structure tying a Lagrange element and its numbering to a mesh
+///
+/// Every scalar dof of the DofMap carries n_components values which are interleaved in the global
+/// numbering: component c of scalar dof s is the global dof s * n_components + c. Discontinuous
+/// spaces number the dofs cell by cell (see DofMap::discontinuous) so that functions are broken
+/// polynomials coupled only through interior facet integrals.
+pub struct FunctionSpace<'a> {
+    mesh: &'a Mesh,
+    element: LagrangeElement,
+    dof_map: DofMap,
+    n_components: usize,
+    discontinuous: bool,
+}
+
+impl<'a> FunctionSpace<'a> {
+    /// Build the space of continuous scalar functions of an element on a mesh
+    pub fn new(mesh: &'a Mesh, element: LagrangeElement) -> Self {
+        FunctionSpace::vector(mesh, element, 1)
+    }
+
+    /// Build the space of continuous functions with n_components components of an element on a
+    /// mesh
+    pub fn vector(mesh: &'a Mesh, element: LagrangeElement, n_components: usize) -> Self {
+        let dof_map = DofMap::lagrange(mesh, &element);
+        FunctionSpace::with_dof_map(mesh, element, dof_map, n_components, false)
+    }
+
+    /// Build the space of discontinuous scalar functions of an element on a mesh
+    pub fn discontinuous(mesh: &'a Mesh, element: LagrangeElement) -> Self {
+        FunctionSpace::discontinuous_vector(mesh, element, 1)
+    }
+
+    /// Build the space of discontinuous functions with n_components components of an element on a
+    /// mesh
+    pub fn discontinuous_vector(
+        mesh: &'a Mesh,
+        element: LagrangeElement,
+        n_components: usize,
+    ) -> Self {
+        let dof_map = DofMap::discontinuous(mesh, &element);
+        FunctionSpace::with_dof_map(mesh, element, dof_map, n_components, true)
+    }
+
+    fn with_dof_map(
+        mesh: &'a Mesh,
+        element: LagrangeElement,
+        dof_map: DofMap,
+        n_components: usize,
+        discontinuous: bool,
+    ) -> Self {
+        assert!(n_components > 0, "A space needs at least one component");
+        FunctionSpace {
+            mesh,
+            element,
+            dof_map,
+            n_components,
+            discontinuous,
+        }
+    }
+
+    /// Mesh the space lives on
+    pub fn mesh(&self) -> &'a Mesh {
+        self.mesh
+    }
+
+    /// Element of the space
+    pub fn element(&self) -> &LagrangeElement {
+        &self.element
+    }
+
+    /// Numbering of the scalar dofs
+    pub fn dof_map(&self) -> &DofMap {
+        &self.dof_map
+    }
+
+    /// Number of components of the functions of the space
+    pub fn n_components(&self) -> usize {
+        self.n_components
+    }
+
+    /// Whether the dofs are owned by the cells (broken polynomials)
+    pub fn is_discontinuous(&self) -> bool {
+        self.discontinuous
+    }
+
+    /// Total number of dofs (all components included)
+    pub fn n_dofs(&self) -> usize {
+        self.dof_map.n_dofs() * self.n_components
+    }
+
+    /// Global dof of a component of a scalar dof
+    pub fn dof(&self, scalar_dof: usize, component: usize) -> usize {
+        scalar_dof * self.n_components + component
+    }
+
+    /// Physical coordinates of the node of every scalar dof, of size (number of scalar dofs,
+    /// geometric dimension)
+    pub fn dof_coordinates(&self) -> DataHold<f64, [usize; 2]> {
+        let dim = self.mesh.geometric_dim();
+        let mut coordinates = vec![0.0; self.dof_map.n_dofs() * dim];
+        if self.mesh.n_cells() == 0 {
+            return DataHold::new(coordinates, [0, dim]);
+        }
+        let mut mapping = CellMapping::new(self.mesh, 0);
+        for cell in 0..self.mesh.n_cells() {
+            mapping.reinit(self.mesh, cell);
+            for (node, dof) in self.dof_map.cell_dofs(cell).iter().enumerate() {
+                mapping.map_point(
+                    &self.element.node_coordinates(node),
+                    &mut coordinates[dof * dim..(dof + 1) * dim],
+                );
+            }
+        }
+        DataHold::new(coordinates, [self.dof_map.n_dofs(), dim])
+    }
+
+    /// Sorted scalar dofs whose nodes lie on the boundary facets carrying the tag
+    pub fn tagged_dofs(&self, tag: usize) -> Vec<usize> {
+        let facets = Facets::new(self.mesh);
+        let mut dofs = Vec::new();
+        for facet in facets.boundary_facets() {
+            if self.mesh.facet_tag(facets.facet_vertices(facet)) != Some(tag) {
+                continue;
+            }
+            // Nodes of the local facet i have a zero barycentric coordinate i
+            let (cell, local) = facets.facet_cells(facet)[0];
+            for (node, dof) in self.dof_map.cell_dofs(cell).iter().enumerate() {
+                if self.element.node_multi_index(node)[local] == 0 {
+                    dofs.push(*dof);
+                }
+            }
+        }
+        dofs.sort_unstable();
+        dofs.dedup();
+        dofs
+    }
+
+    /// Build an assembler on the space with a cell quadrature rule
+    pub fn assembler(&self, quadrature: QuadratureRule) -> Assembler<'_> {
+        assert!(
+            self.n_components == 1,
+            "Assembly is only implemented for scalar spaces"
+        );
+        Assembler::new(self.mesh, &self.element, &self.dof_map, quadrature)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::test_meshes::{empty_mesh, unit_square};
+
+    #[test]
+    fn test_function_space_vector() {
+        let mesh = unit_square();
+        let space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        assert_eq!(space.dof_map().n_dofs(), 9, "Wrong number of scalar dofs");
+        assert_eq!(space.n_dofs(), 18, "Wrong number of dofs");
+        assert_eq!(space.dof(4, 1), 9, "Wrong interleaving of the components");
+        let empty = empty_mesh();
+        let empty_space = FunctionSpace::vector(&empty, LagrangeElement::new(2, 2), 2);
+        assert!(
+            empty_space.dof_coordinates().is_empty(),
+            "Empty meshes have no dof coordinates"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_function_space_discontinuous() {
+        let mesh = unit_square();
+        let space = FunctionSpace::discontinuous(&mesh, LagrangeElement::new(2, 1));
+        assert!(space.is_discontinuous(), "Space should be discontinuous");
+        assert_eq!(space.n_dofs(), 6, "Shared vertices should not share dofs");
+        assert_eq!(
+            space.dof_map().cell_dofs(1),
+            &[3, 4, 5],
+            "Wrong cell numbering"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_function_space_tagged_dofs() {
+        let mut mesh = unit_square();
+        mesh.tag_boundary(1, |x| x[0] == 0.0);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        let coordinates = space.dof_coordinates();
+        let dofs = space.tagged_dofs(1);
+        assert_eq!(dofs.len(), 3, "The left side carries 3 nodes");
+        for dof in &dofs {
+            assert_eq!(coordinates[dof * 2], 0.0, "Node off the tagged side");
+        }
+        assert!(space.tagged_dofs(2).is_empty(), "No facet carries tag 2");
+    }
+}
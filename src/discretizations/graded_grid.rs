@@ -0,0 +1,187 @@
+use super::cartesian_grid::{kuhn_mesh, CartesianGrid};
+use super::mesh::Mesh;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Structured grid of a box whose nodes are spaced independently along every direction
+///
+/// The grid starts uniform and the spacing along a direction is replaced by a geometric grading,
+/// the cells growing by a constant ratio from the lower to the upper side, or by a boundary layer
+/// toward a side of the box, the cells growing from a first cell height by a growth ratio until
+/// they reach the uniform size of the rest of the direction. The number of cells along every
+/// direction is kept: the growth is raised when the cells cannot otherwise reach the opposite side
+/// and a first height above the uniform size gives uniform cells. Sides are given by the tags of
+/// CartesianGrid::simplex_mesh, 2 k + 1 for the lower side of direction k and 2 k + 2 for its
+/// upper side.
+#[derive(Clone, Debug)]
+pub struct GradedGrid {
+    nodes: Vec<Vec<f64>>,
+}
+
+impl GradedGrid {
+    /// Uniform grid of the box [lower, upper] with cells_per_dim cells along every direction
+    pub fn new(lower: Vec<f64>, upper: Vec<f64>, cells_per_dim: Vec<usize>) -> Self {
+        let grid = CartesianGrid::new(lower, upper, cells_per_dim);
+        GradedGrid {
+            nodes: (0..grid.dim()).map(|k| grid.nodes(k)).collect(),
+        }
+    }
+
+    /// Grade the cells along a direction geometrically, every cell being ratio times larger than
+    /// the previous one
+    pub fn with_geometric_grading(mut self, direction: usize, ratio: f64) -> Self {
+        assert!(direction < self.dim(), "Grading direction out of bounds");
+        assert!(ratio > 0.0, "Grading ratio should be positive");
+        let sizes: Vec<f64> = (0..self.n_cells(direction))
+            .map(|i| ratio.powi(i as i32))
+            .collect();
+        self.set_sizes(direction, &sizes);
+        self
+    }
+
+    /// Stretch the cells along a direction into a boundary layer toward the side carrying a tag,
+    /// the cells growing away from it from the first height by the growth ratio
+    pub fn with_boundary_layer(mut self, tag: usize, first_height: f64, growth: f64) -> Self {
+        assert!(
+            tag >= 1 && tag <= 2 * self.dim(),
+            "Boundary layer tag is not a side of the box"
+        );
+        assert!(first_height > 0.0, "First cell height should be positive");
+        assert!(growth >= 1.0, "Boundary layer cells should not shrink");
+        let direction = (tag - 1) / 2;
+        let nodes = &self.nodes[direction];
+        let length = nodes[nodes.len() - 1] - nodes[0];
+        let n = self.n_cells(direction);
+        let mut sizes = Vec::with_capacity(n);
+        let mut covered = 0.0;
+        let mut height = first_height;
+        for i in 0..n {
+            let uniform = (length - covered) / (n - i) as f64;
+            if height >= uniform {
+                sizes.resize(n, uniform);
+                covered = length;
+                break;
+            }
+            sizes.push(height);
+            covered += height;
+            height *= growth;
+        }
+        if covered < length {
+            // Raise the growth until the layer fills the direction
+            let filled = |growth: f64| (0..n).map(|i| growth.powi(i as i32)).sum::<f64>();
+            let target = length / first_height;
+            let (mut low, mut high) = (growth, 2.0 * growth);
+            while filled(high) < target {
+                high *= 2.0;
+            }
+            for _ in 0..100 {
+                let middle = 0.5 * (low + high);
+                if filled(middle) < target {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+            sizes = (0..n).map(|i| first_height * high.powi(i as i32)).collect();
+        }
+        if tag.is_multiple_of(2) {
+            sizes.reverse();
+        }
+        self.set_sizes(direction, &sizes);
+        self
+    }
+
+    // Space the nodes along a direction proportionally to the sizes of its cells
+    fn set_sizes(&mut self, direction: usize, sizes: &[f64]) {
+        let nodes = &mut self.nodes[direction];
+        let (lower, upper) = (nodes[0], nodes[nodes.len() - 1]);
+        let scale = (upper - lower) / sizes.iter().sum::<f64>();
+        let mut position = lower;
+        for (node, size) in nodes[1..].iter_mut().zip(sizes) {
+            position += size * scale;
+            *node = position;
+        }
+        // Exact upper bound so that the boundary can be tagged by equality
+        nodes[sizes.len()] = upper;
+    }
+
+    /// Dimension of the grid
+    pub fn dim(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Number of cells along a direction
+    pub fn n_cells(&self, direction: usize) -> usize {
+        self.nodes[direction].len() - 1
+    }
+
+    /// Coordinates of the nodes along a direction
+    pub fn nodes(&self, direction: usize) -> &[f64] {
+        &self.nodes[direction]
+    }
+
+    /// Simplicial mesh of the grid split and tagged as by CartesianGrid::simplex_mesh
+    pub fn simplex_mesh(&self) -> Mesh {
+        kuhn_mesh(&self.nodes)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graded_grid() {
+        let grid = GradedGrid::new(vec![0.0, 0.0], vec![2.0, 1.0], vec![4, 10])
+            .with_geometric_grading(0, 2.0)
+            .with_boundary_layer(4, 0.01, 1.5);
+        // Cells of size 2 / 15 (1, 2, 4, 8) along x
+        for (node, expected) in grid.nodes(0).iter().zip([0.0, 1.0, 3.0, 7.0, 15.0]) {
+            assert!(
+                (node - 2.0 * expected / 15.0).abs() < 1e-14,
+                "Wrong geometric grading"
+            );
+        }
+        let y = grid.nodes(1);
+        assert_eq!(y[10], 1.0, "Upper bound should be exact");
+        assert!(
+            (y[10] - y[9] - 0.01).abs() < 1e-12,
+            "Wrong first cell height"
+        );
+        assert!(
+            (y[9] - y[8] - 0.015).abs() < 1e-12,
+            "Wrong growth of the boundary layer"
+        );
+        let sizes: Vec<f64> = y.windows(2).map(|w| w[1] - w[0]).collect();
+        assert!(
+            sizes.windows(2).all(|s| s[0] >= s[1] - 1e-12),
+            "Cells should shrink toward the wall"
+        );
+        // Cells away from the layer share the remaining height uniformly
+        assert!(
+            (sizes[0] - sizes[1]).abs() < 1e-14,
+            "Core cells should be uniform"
+        );
+        let mesh = grid.simplex_mesh();
+        assert_eq!(mesh.n_cells(), 2 * 40, "Wrong number of triangles");
+        let upper_y = mesh.facet_tags().filter(|(_, tag)| *tag == 4).count();
+        assert_eq!(upper_y, 4, "Wall was not tagged");
+        // Five cells growing by 1.2 from 0.01 only cover 0.0744, the growth is raised to about 2.9
+        let nodes = GradedGrid::new(vec![0.0], vec![1.0], vec![5])
+            .with_boundary_layer(1, 0.01, 1.2)
+            .nodes(0)
+            .to_vec();
+        assert!((nodes[1] - 0.01).abs() < 1e-12, "First height was not kept");
+        let growth = (nodes[2] - nodes[1]) / nodes[1];
+        assert!(
+            growth > 2.0 && ((nodes[5] - nodes[4]) / (nodes[4] - nodes[3]) - growth).abs() < 1e-8,
+            "Growth was not raised uniformly"
+        );
+    }
+}
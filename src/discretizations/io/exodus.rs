@@ -0,0 +1,696 @@
+use super::netcdf::{text, text_array, NcValues, NetCdf};
+use crate::core::arrays::data_hold::DataHold;
+#[cfg(hosted)]
+use crate::core::logging::Span;
+use crate::discretizations::facets::Facets;
+use crate::discretizations::function::Function;
+use crate::discretizations::mesh::Mesh;
+use std::collections::BTreeMap;
+#[cfg(hosted)]
+use std::fs::File;
+#[cfg(hosted)]
+use std::io::{BufReader, BufWriter};
+use std::io::{Error, ErrorKind, Read, Result, Write};
+#[cfg(hosted)]
+use std::path::Path;
+
+// Length of the names stored in the file (including the null terminator)
+const NAME_LENGTH: usize = 33;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Time steps and nodal variables of an Exodus II file
+///
+/// Nodal values are given at the vertices of the mesh read from the same file.
+pub struct ExodusResults {
+    times: Vec<f64>,
+    names: Vec<String>,
+    values: Vec<Vec<f64>>,
+}
+
+impl ExodusResults {
+    /// Times of the steps
+    pub fn times(&self) -> &[f64] {
+        &self.times
+    }
+
+    /// Names of the nodal variables
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Values of a nodal variable at the vertices for a time step
+    pub fn nodal_values(&self, name: &str, step: usize) -> Option<&[f64]> {
+        let variable = self.names.iter().position(|n| n == name)?;
+        let n_nodes = self.values[variable].len() / self.times.len().max(1);
+        self.values[variable].get(step * n_nodes..(step + 1) * n_nodes)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Read a mesh and its nodal results from an Exodus II file
+///
+/// Only files in the classic NetCDF formats with linear simplicial element blocks (BAR2, TRI3 and
+/// TETRA4) are supported. The blocks are concatenated in the order of the file. The sides of a side
+/// set become facets tagged with the id of the set and the nodes of a node set vertices tagged with
+/// its id.
+pub fn read_exodus<Reader: Read>(reader: &mut Reader) -> Result<(Mesh, ExodusResults)> {
+    let dataset = NetCdf::read(reader)?;
+    let dim = dataset
+        .dimension("num_dim")
+        .ok_or_else(|| invalid("Missing number of dimensions"))?;
+    let n_nodes = dataset.dimension("num_nodes").unwrap_or(0);
+    // Coordinates are either split by direction or stored as (num_dim, num_nodes)
+    let mut vertices = vec![0.0; n_nodes * dim];
+    for (d, name) in ["coordx", "coordy", "coordz"].iter().take(dim).enumerate() {
+        let coordinates = match dataset.variable(name) {
+            Some(variable) => floats(variable.values())?,
+            None => {
+                let coord = floats(
+                    dataset
+                        .variable("coord")
+                        .ok_or_else(|| invalid("Missing coordinates"))?
+                        .values(),
+                )?;
+                coord[d * n_nodes..(d + 1) * n_nodes].to_vec()
+            }
+        };
+        for (v, x) in coordinates.iter().enumerate() {
+            vertices[v * dim + d] = *x;
+        }
+    }
+    let n_blocks = dataset.dimension("num_el_blk").unwrap_or(0);
+    let mut cells = Vec::new();
+    let mut nodes_per_cell = None;
+    for block in 1..=n_blocks {
+        let Some(connect) = dataset.variable(&format!("connect{}", block)) else {
+            continue;
+        };
+        let n = dataset
+            .dimension(&format!("num_nod_per_el{}", block))
+            .ok_or_else(|| invalid("Missing number of nodes per element"))?;
+        let elem_type = connect
+            .attribute("elem_type")
+            .and_then(|t| t.to_text())
+            .unwrap_or_default()
+            .to_uppercase();
+        let simplex = match n {
+            2 => ["BAR", "BEAM", "TRUSS", "EDGE"]
+                .iter()
+                .any(|p| elem_type.starts_with(p)),
+            3 => elem_type.starts_with("TRI"),
+            4 => elem_type.starts_with("TET"),
+            _ => false,
+        };
+        if !simplex || nodes_per_cell.is_some_and(|m| m != n) {
+            return Err(invalid(
+                "Only blocks of a single type of linear simplices are supported",
+            ));
+        }
+        nodes_per_cell = Some(n);
+        for node in integers(connect.values())? {
+            cells.push(node_index(node, n_nodes)?);
+        }
+    }
+    let n = nodes_per_cell.ok_or_else(|| invalid("File has no element block"))?;
+    let n_cells = cells.len() / n;
+    let mut mesh = Mesh::new(
+        DataHold::new(vertices, [n_nodes, dim]),
+        DataHold::new(cells, [n_cells, n]),
+    );
+    let sides = exodus_sides(n);
+    for set in 1..=dataset.dimension("num_side_sets").unwrap_or(0) {
+        let tag = set_id(&dataset, "ss_prop1", set)?;
+        let (Some(elements), Some(local_sides)) = (
+            dataset.variable(&format!("elem_ss{}", set)),
+            dataset.variable(&format!("side_ss{}", set)),
+        ) else {
+            continue;
+        };
+        for (element, side) in integers(elements.values())?
+            .iter()
+            .zip(integers(local_sides.values())?.iter())
+        {
+            let cell = node_index(*element, n_cells)?;
+            let side = node_index(*side, sides.len())?;
+            let vertices: Vec<usize> = sides[side].iter().map(|i| mesh.cell(cell)[*i]).collect();
+            mesh.tag_facet(&vertices, tag);
+        }
+    }
+    for set in 1..=dataset.dimension("num_node_sets").unwrap_or(0) {
+        let tag = set_id(&dataset, "ns_prop1", set)?;
+        if let Some(nodes) = dataset.variable(&format!("node_ns{}", set)) {
+            for node in integers(nodes.values())? {
+                mesh.tag_vertex(node_index(node, n_nodes)?, tag);
+            }
+        }
+    }
+    let times = match dataset.variable("time_whole") {
+        Some(variable) => floats(variable.values())?,
+        None => Vec::new(),
+    };
+    let names: Vec<String> = match dataset.variable("name_nod_var") {
+        Some(variable) => match variable.values() {
+            NcValues::Char(characters) => {
+                let length = dataset.dimension("len_name").unwrap_or(NAME_LENGTH);
+                characters.chunks(length).map(text).collect()
+            }
+            _ => return Err(invalid("Variable names should be characters")),
+        },
+        None => Vec::new(),
+    };
+    let mut values = Vec::with_capacity(names.len());
+    for v in 1..=names.len() {
+        match dataset.variable(&format!("vals_nod_var{}", v)) {
+            Some(variable) => values.push(floats(variable.values())?),
+            None => {
+                // Older files store all the variables in a (time_step, num_nod_var, num_nodes)
+                // array
+                let all = floats(
+                    dataset
+                        .variable("vals_nod_var")
+                        .ok_or_else(|| invalid("Missing nodal variable values"))?
+                        .values(),
+                )?;
+                let n_variables = names.len();
+                values.push(
+                    (0..times.len())
+                        .flat_map(|t| {
+                            let start = (t * n_variables + v - 1) * n_nodes;
+                            all[start..start + n_nodes].to_vec()
+                        })
+                        .collect(),
+                );
+            }
+        }
+    }
+    Ok((
+        mesh,
+        ExodusResults {
+            times,
+            names,
+            values,
+        },
+    ))
+}
+
+/// Read a mesh and its nodal results from an Exodus II file on disk
+#[cfg(hosted)]
+pub fn load_exodus<P: AsRef<Path>>(path: P) -> Result<(Mesh, ExodusResults)> {
+    let _span = Span::enter("Exodus input");
+    read_exodus(&mut BufReader::new(File::open(path)?))
+}
+
+/// Write a mesh and named functions on it in the Exodus II format
+///
+/// The mesh is written as a single element block, its tagged facets as one side set per tag and
+/// its tagged vertices as one node set per tag. Functions are written at a single time step 0 as
+/// nodal variables at the vertices with the suffixes _x, _y and _z for the components of vector
+/// functions.
+pub fn write_exodus<Writer: Write>(
+    writer: &mut Writer,
+    mesh: &Mesh,
+    functions: &[(&str, &Function)],
+) -> Result<()> {
+    let n = mesh.vertices_per_cell();
+    let elem_type = match n {
+        2 => "BAR2",
+        3 => "TRI3",
+        4 => "TETRA4",
+        _ => panic!("Cells with {} vertices cannot be written to Exodus", n),
+    };
+    let dim = mesh.geometric_dim();
+    let mut dataset = NetCdf::new();
+    dataset.add_attribute("api_version", NcValues::Float(vec![8.03]));
+    dataset.add_attribute("version", NcValues::Float(vec![8.03]));
+    dataset.add_attribute("floating_point_word_size", NcValues::Int(vec![8]));
+    dataset.add_attribute("file_size", NcValues::Int(vec![1]));
+    dataset.add_attribute(
+        "maximum_name_length",
+        NcValues::Int(vec![NAME_LENGTH as i32 - 1]),
+    );
+    dataset.add_attribute("title", NcValues::Char(b"Fe2O3 output".to_vec()));
+    let len_name = dataset.add_dimension("len_name", NAME_LENGTH);
+    let time_step = dataset.add_record_dimension("time_step", usize::from(!functions.is_empty()));
+    let num_dim = dataset.add_dimension("num_dim", dim);
+    let num_nodes = dataset.add_dimension("num_nodes", mesh.n_vertices());
+    dataset.add_dimension("num_elem", mesh.n_cells());
+    let num_el_blk = dataset.add_dimension("num_el_blk", 1);
+    let num_el_in_blk = dataset.add_dimension("num_el_in_blk1", mesh.n_cells());
+    let num_nod_per_el = dataset.add_dimension("num_nod_per_el1", n);
+    // Coordinates and connectivity
+    for (d, name) in ["coordx", "coordy", "coordz"].iter().take(dim).enumerate() {
+        let coordinates = (0..mesh.n_vertices()).map(|v| mesh.vertex(v)[d]).collect();
+        dataset.add_variable(name, &[num_nodes], NcValues::Double(coordinates));
+    }
+    let coordinate_names: Vec<String> = ["x", "y", "z"][..dim]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    dataset.add_variable(
+        "coor_names",
+        &[num_dim, len_name],
+        text_array(&coordinate_names, NAME_LENGTH),
+    );
+    dataset.add_variable("eb_status", &[num_el_blk], NcValues::Int(vec![1]));
+    dataset
+        .add_variable("eb_prop1", &[num_el_blk], NcValues::Int(vec![1]))
+        .add_attribute("name", NcValues::Char(b"ID".to_vec()));
+    let connectivity = mesh.cells().iter().map(|v| *v as i32 + 1).collect();
+    dataset
+        .add_variable(
+            "connect1",
+            &[num_el_in_blk, num_nod_per_el],
+            NcValues::Int(connectivity),
+        )
+        .add_attribute("elem_type", NcValues::Char(elem_type.as_bytes().to_vec()));
+    // Side sets from the tagged facets
+    let mut side_sets: BTreeMap<usize, Vec<(i32, i32)>> = BTreeMap::new();
+    if mesh.facet_tags().next().is_some() {
+        let facets = Facets::new(mesh);
+        let sides = exodus_sides(n);
+        for (vertices, tag) in mesh.facet_tags() {
+            let facet = facets
+                .facet_index(vertices)
+                .expect("Tagged facet does not belong to the mesh");
+            let (cell, local) = facets.facet_cells(facet)[0];
+            // Local facet i is opposite to the local vertex i
+            let side = sides.iter().position(|s| !s.contains(&local)).unwrap();
+            side_sets
+                .entry(tag)
+                .or_default()
+                .push((cell as i32 + 1, side as i32 + 1));
+        }
+    }
+    if !side_sets.is_empty() {
+        let num_side_sets = dataset.add_dimension("num_side_sets", side_sets.len());
+        let ids = side_sets.keys().map(|t| *t as i32).collect();
+        dataset.add_variable(
+            "ss_status",
+            &[num_side_sets],
+            NcValues::Int(vec![1; side_sets.len()]),
+        );
+        dataset
+            .add_variable("ss_prop1", &[num_side_sets], NcValues::Int(ids))
+            .add_attribute("name", NcValues::Char(b"ID".to_vec()));
+        for (s, sides) in side_sets.values_mut().enumerate() {
+            sides.sort_unstable();
+            let size = dataset.add_dimension(&format!("num_side_ss{}", s + 1), sides.len());
+            let elements = sides.iter().map(|(e, _)| *e).collect();
+            let local = sides.iter().map(|(_, l)| *l).collect();
+            dataset.add_variable(
+                &format!("elem_ss{}", s + 1),
+                &[size],
+                NcValues::Int(elements),
+            );
+            dataset.add_variable(&format!("side_ss{}", s + 1), &[size], NcValues::Int(local));
+        }
+    }
+    // Node sets from the tagged vertices
+    let mut node_sets: BTreeMap<usize, Vec<i32>> = BTreeMap::new();
+    for (vertex, tag) in mesh.vertex_tags() {
+        node_sets.entry(tag).or_default().push(vertex as i32 + 1);
+    }
+    if !node_sets.is_empty() {
+        let num_node_sets = dataset.add_dimension("num_node_sets", node_sets.len());
+        let ids = node_sets.keys().map(|t| *t as i32).collect();
+        dataset.add_variable(
+            "ns_status",
+            &[num_node_sets],
+            NcValues::Int(vec![1; node_sets.len()]),
+        );
+        dataset
+            .add_variable("ns_prop1", &[num_node_sets], NcValues::Int(ids))
+            .add_attribute("name", NcValues::Char(b"ID".to_vec()));
+        for (s, nodes) in node_sets.values_mut().enumerate() {
+            nodes.sort_unstable();
+            let size = dataset.add_dimension(&format!("num_nod_ns{}", s + 1), nodes.len());
+            dataset.add_variable(
+                &format!("node_ns{}", s + 1),
+                &[size],
+                NcValues::Int(nodes.clone()),
+            );
+        }
+    }
+    // Nodal variables at the single time step
+    if !functions.is_empty() {
+        dataset.add_variable("time_whole", &[time_step], NcValues::Double(vec![0.0]));
+        let mut names = Vec::new();
+        let mut values = Vec::new();
+        for (name, function) in functions {
+            assert!(
+                std::ptr::eq(function.space().mesh(), mesh),
+                "Function {} does not live on the written mesh",
+                name
+            );
+            let n_components = function.space().n_components();
+            let vertex_values = function.vertex_values();
+            for c in 0..n_components {
+                names.push(match n_components {
+                    1 => name.to_string(),
+                    2 | 3 => format!("{}_{}", name, ["x", "y", "z"][c]),
+                    _ => format!("{}_{}", name, c),
+                });
+                values.push(
+                    vertex_values
+                        .chunks(n_components)
+                        .map(|v| v[c])
+                        .collect::<Vec<f64>>(),
+                );
+            }
+        }
+        let num_nod_var = dataset.add_dimension("num_nod_var", names.len());
+        dataset.add_variable(
+            "name_nod_var",
+            &[num_nod_var, len_name],
+            text_array(&names, NAME_LENGTH),
+        );
+        for (v, values) in values.into_iter().enumerate() {
+            dataset.add_variable(
+                &format!("vals_nod_var{}", v + 1),
+                &[time_step, num_nodes],
+                NcValues::Double(values),
+            );
+        }
+    }
+    dataset.write(writer)
+}
+
+/// Write a mesh and named functions on it to an Exodus II file
+#[cfg(hosted)]
+pub fn save_exodus<P: AsRef<Path>>(
+    path: P,
+    mesh: &Mesh,
+    functions: &[(&str, &Function)],
+) -> Result<()> {
+    let _span = Span::enter("Exodus output");
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_exodus(&mut writer, mesh, functions)?;
+    writer.flush()
+}
+
+// Local vertices of the sides of the Exodus simplices in the order of their side numbers
+fn exodus_sides(vertices_per_cell: usize) -> Vec<Vec<usize>> {
+    match vertices_per_cell {
+        2 => vec![vec![0], vec![1]],
+        3 => vec![vec![0, 1], vec![1, 2], vec![2, 0]],
+        _ => vec![vec![0, 1, 3], vec![1, 2, 3], vec![0, 3, 2], vec![0, 2, 1]],
+    }
+}
+
+// Id of a set from the property array, the position of the set otherwise
+fn set_id(dataset: &NetCdf, property: &str, set: usize) -> Result<usize> {
+    match dataset.variable(property) {
+        Some(ids) => {
+            let id = *integers(ids.values())?
+                .get(set - 1)
+                .ok_or_else(|| invalid("Missing set id"))?;
+            usize::try_from(id).map_err(|_| invalid("Negative set id"))
+        }
+        None => Ok(set),
+    }
+}
+
+// Zero based index of a one based entry checked against the number of entities
+fn node_index(one_based: i64, n: usize) -> Result<usize> {
+    if one_based >= 1 && (one_based as usize) <= n {
+        Ok(one_based as usize - 1)
+    } else {
+        Err(invalid("Index out of bounds"))
+    }
+}
+
+fn integers(values: &NcValues) -> Result<Vec<i64>> {
+    values
+        .to_integers()
+        .ok_or_else(|| invalid("Expected integer values"))
+}
+
+fn floats(values: &NcValues) -> Result<Vec<f64>> {
+    values
+        .to_floats()
+        .ok_or_else(|| invalid("Expected floating point values"))
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::discretizations::test_meshes::unit_square;
+    use crate::spaces::lagrange::LagrangeElement;
+
+    // Unit square of the vertices (0, 0), (1, 0), (0, 1) and (1, 1) in an element block of the
+    // triangle 1 2 4 and a second block of the given element type and connectivity, with the side
+    // 1 of the first triangle in a side set of id 3, the vertex 4 in a node set of the given id and
+    // a nodal variable t = step + vertex over three time steps
+    fn two_block_square(elem_type: &str, connectivity: Vec<i32>, node_set: i32) -> NetCdf {
+        let mut dataset = NetCdf::new();
+        let len_name = dataset.add_dimension("len_name", NAME_LENGTH);
+        let time_step = dataset.add_record_dimension("time_step", 3);
+        dataset.add_dimension("num_dim", 2);
+        let num_nodes = dataset.add_dimension("num_nodes", 4);
+        dataset.add_dimension("num_el_blk", 2);
+        dataset.add_variable(
+            "coordx",
+            &[num_nodes],
+            NcValues::Double(vec![0.0, 1.0, 0.0, 1.0]),
+        );
+        dataset.add_variable(
+            "coordy",
+            &[num_nodes],
+            NcValues::Float(vec![0.0, 0.0, 1.0, 1.0]),
+        );
+        let n = if elem_type.starts_with("BAR") { 2 } else { 3 };
+        for (block, (elem_type, connectivity)) in
+            [("TRI3", vec![1, 2, 4]), (elem_type, connectivity)]
+                .into_iter()
+                .enumerate()
+        {
+            let n = if block == 0 { 3 } else { n };
+            let cells = dataset.add_dimension(
+                &format!("num_el_in_blk{}", block + 1),
+                connectivity.len() / n,
+            );
+            let nodes = dataset.add_dimension(&format!("num_nod_per_el{}", block + 1), n);
+            dataset
+                .add_variable(
+                    &format!("connect{}", block + 1),
+                    &[cells, nodes],
+                    NcValues::Int(connectivity),
+                )
+                .add_attribute("elem_type", NcValues::Char(elem_type.as_bytes().to_vec()));
+        }
+        let num_side_sets = dataset.add_dimension("num_side_sets", 1);
+        dataset.add_variable("ss_prop1", &[num_side_sets], NcValues::Int(vec![3]));
+        let sides = dataset.add_dimension("num_side_ss1", 1);
+        dataset.add_variable("elem_ss1", &[sides], NcValues::Int(vec![1]));
+        dataset.add_variable("side_ss1", &[sides], NcValues::Short(vec![1]));
+        let num_node_sets = dataset.add_dimension("num_node_sets", 1);
+        dataset.add_variable("ns_prop1", &[num_node_sets], NcValues::Int(vec![node_set]));
+        let nodes = dataset.add_dimension("num_nod_ns1", 1);
+        dataset.add_variable("node_ns1", &[nodes], NcValues::Int(vec![4]));
+        dataset.add_variable(
+            "time_whole",
+            &[time_step],
+            NcValues::Double(vec![0.0, 0.5, 1.0]),
+        );
+        let num_nod_var = dataset.add_dimension("num_nod_var", 1);
+        dataset.add_variable(
+            "name_nod_var",
+            &[num_nod_var, len_name],
+            text_array(&["t".to_string()], NAME_LENGTH),
+        );
+        dataset.add_variable(
+            "vals_nod_var1",
+            &[time_step, num_nodes],
+            NcValues::Double(
+                (0..3)
+                    .flat_map(|s| (0..4).map(move |v| (s + v) as f64))
+                    .collect(),
+            ),
+        );
+        dataset
+    }
+
+    #[test]
+    fn test_exodus_round_trip() {
+        let mut mesh = unit_square();
+        mesh.tag_boundary(4, |x| x[0] == 1.0);
+        mesh.tag_facet(&[0, 1], 2);
+        mesh.tag_vertex(0, 7);
+        let scalar_space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let vector_space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let mut u = Function::new(&scalar_space);
+        u.interpolate(|x| x[0] + 2.0 * x[1]);
+        let mut w = Function::new(&vector_space);
+        w.interpolate_vector(|x, value| value.copy_from_slice(x));
+        let mut bytes = Vec::new();
+        write_exodus(&mut bytes, &mesh, &[("u", &u), ("w", &w)]).unwrap();
+        let (read, results) = read_exodus(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read.n_vertices(), 4, "Wrong number of vertices");
+        assert_eq!(read.cell(1), &[3, 2, 1], "Wrong connectivity");
+        assert_eq!(read.vertex(3), &[1.0, 1.0], "Wrong coordinates");
+        assert_eq!(read.facet_tag(&[1, 3]), Some(4), "Side set was not read");
+        assert_eq!(read.facet_tag(&[1, 0]), Some(2), "Side set was not read");
+        assert_eq!(read.facet_tag(&[0, 2]), None, "Untagged facet");
+        assert_eq!(read.vertex_tag(0), Some(7), "Node set was not read");
+        assert_eq!(results.times(), &[0.0], "Wrong time steps");
+        assert_eq!(
+            results.names(),
+            &["u", "w_x", "w_y"],
+            "Wrong variable names"
+        );
+        assert_eq!(
+            results.nodal_values("u", 0).unwrap(),
+            &[0.0, 1.0, 2.0, 3.0],
+            "Wrong nodal values"
+        );
+        assert_eq!(
+            results.nodal_values("w_y", 0).unwrap(),
+            &[0.0, 0.0, 1.0, 1.0],
+            "Wrong nodal values"
+        );
+        assert!(results.nodal_values("v", 0).is_none(), "Unknown variable");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_read_exodus_fixture() {
+        let bytes = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/plate.exo"));
+        let (mesh, results) = read_exodus(&mut bytes.as_slice()).unwrap();
+        assert_eq!(mesh.n_vertices(), 6, "Wrong number of vertices");
+        assert_eq!(mesh.n_cells(), 5, "Wrong number of cells");
+        assert_eq!(mesh.vertex(4), &[1.0, 0.5], "Wrong coordinates");
+        assert_eq!(mesh.cell(2), &[1, 2, 4], "Wrong connectivity");
+        assert_eq!(mesh.facet_tag(&[0, 5]), Some(3), "Side set was not read");
+        assert_eq!(mesh.facet_tag(&[5, 1]), Some(3), "Side set was not read");
+        assert_eq!(mesh.facet_tag(&[1, 2]), None, "Untagged facet");
+        assert_eq!(mesh.vertex_tag(2), Some(5), "Node set was not read");
+        assert_eq!(results.times(), &[0.0, 0.5], "Wrong time steps");
+        assert_eq!(results.names(), &["temperature"], "Wrong variable names");
+        assert_eq!(
+            results.nodal_values("temperature", 1).unwrap(),
+            &[0.0, 4.0, 5.0, 1.0, 2.5, 2.0],
+            "Wrong nodal values"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_read_exodus_netcdf4() {
+        let error = read_exodus(&mut b"\x89HDF\r\n\x1a\n\0\0\0\0".as_slice())
+            .err()
+            .expect("NetCDF-4 files should be rejected");
+        assert_eq!(error.kind(), ErrorKind::InvalidData, "Wrong error kind");
+        assert!(
+            error.to_string().contains("NetCDF-4") && error.to_string().contains("nccopy"),
+            "The error should name the format and how to convert it: {}",
+            error
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_read_exodus_blocks() {
+        let mut bytes = Vec::new();
+        two_block_square("tri", vec![4, 3, 1], 5)
+            .write(&mut bytes)
+            .unwrap();
+        let (mesh, results) = read_exodus(&mut bytes.as_slice()).unwrap();
+        assert_eq!(mesh.n_cells(), 2, "Blocks should be concatenated");
+        assert_eq!(
+            mesh.cell(0),
+            &[0, 1, 3],
+            "Wrong connectivity of the first block"
+        );
+        assert_eq!(
+            mesh.cell(1),
+            &[3, 2, 0],
+            "Wrong connectivity of the second block"
+        );
+        assert_eq!(mesh.vertex(2), &[0.0, 1.0], "Wrong coordinates");
+        assert_eq!(mesh.facet_tag(&[0, 1]), Some(3), "Side set was not read");
+        assert_eq!(mesh.vertex_tag(3), Some(5), "Node set was not read");
+        assert_eq!(results.times(), &[0.0, 0.5, 1.0], "Wrong time steps");
+        for step in 0..3 {
+            let expected: Vec<f64> = (0..4).map(|v| (step + v) as f64).collect();
+            assert_eq!(
+                results.nodal_values("t", step).unwrap(),
+                expected.as_slice(),
+                "Wrong nodal values of step {}",
+                step
+            );
+        }
+        assert!(results.nodal_values("t", 3).is_none(), "Unknown time step");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_read_exodus_invalid() {
+        let mut no_dimension = NetCdf::new();
+        no_dimension.add_dimension("num_nodes", 1);
+        let mut no_coordinates = NetCdf::new();
+        no_coordinates.add_dimension("num_dim", 1);
+        let mut no_block = NetCdf::new();
+        no_block.add_dimension("num_dim", 1);
+        let num_nodes = no_block.add_dimension("num_nodes", 1);
+        no_block.add_variable("coordx", &[num_nodes], NcValues::Double(vec![0.0]));
+        let cases = [
+            ("a file without dimension", no_dimension, "dimensions"),
+            ("a file without coordinates", no_coordinates, "coordinates"),
+            ("a file without element block", no_block, "no element block"),
+            (
+                "blocks of triangles and segments",
+                two_block_square("BAR2", vec![1, 2], 5),
+                "single type",
+            ),
+            (
+                "a block of quadrangles",
+                two_block_square("QUAD4", vec![1, 2, 4], 5),
+                "single type",
+            ),
+            (
+                "a node out of the mesh",
+                two_block_square("TRI3", vec![4, 3, 5], 5),
+                "out of bounds",
+            ),
+            (
+                "a negative node set id",
+                two_block_square("TRI3", vec![4, 3, 1], -1),
+                "Negative set id",
+            ),
+        ];
+        for (case, dataset, message) in cases {
+            let mut bytes = Vec::new();
+            dataset.write(&mut bytes).unwrap();
+            let error = read_exodus(&mut bytes.as_slice())
+                .err()
+                .unwrap_or_else(|| panic!("Read {}", case));
+            assert_eq!(
+                error.kind(),
+                ErrorKind::InvalidData,
+                "Wrong error kind for {}",
+                case
+            );
+            assert!(
+                error.to_string().contains(message),
+                "Wrong error for {}: {}",
+                case,
+                error
+            );
+        }
+    }
+}
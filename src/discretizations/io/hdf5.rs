@@ -0,0 +1,1163 @@
+use std::io::{Error, ErrorKind, Read, Result};
+
+const SIGNATURE: &[u8] = b"\x89HDF\r\n\x1a\n";
+
+// Types of the object header messages
+const DATASPACE: u16 = 0x0001;
+const LINK_INFO: u16 = 0x0002;
+const DATATYPE: u16 = 0x0003;
+const LINK: u16 = 0x0006;
+const LAYOUT: u16 = 0x0008;
+const FILTERS: u16 = 0x000B;
+const ATTRIBUTE: u16 = 0x000C;
+const CONTINUATION: u16 = 0x0010;
+const SYMBOL_TABLE: u16 = 0x0011;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Values of an HDF5 dataset or attribute converted to a few common types
+#[derive(Clone, Debug, PartialEq)]
+pub enum H5Values {
+    /// Fixed point numbers of any size
+    Integers(Vec<i64>),
+    /// Single or double precision floating point numbers
+    Floats(Vec<f64>),
+    /// Fixed length strings without their padding
+    Strings(Vec<String>),
+}
+
+/// Read only HDF5 file held in memory
+///
+/// Only the subset of the format written by default by the HDF5 library for simple files is
+/// supported: groups stored with symbol tables or compact links, datasets of fixed point, floating
+/// point or fixed length string values with a compact, contiguous or unfiltered chunked layout and
+/// attributes stored in the object headers. Checksums are not verified.
+///
+/// The following features make reading fail with an error naming them:
+/// - superblocks of versions above 3,
+/// - filtered (e.g. compressed or shuffled) chunked datasets,
+/// - chunked datasets indexed by the structures of version 4 layout messages, which files written
+///   with the latest library format (`H5F_LIBVER_LATEST`) use instead of B-trees,
+/// - virtual datasets,
+/// - other datatypes than fixed point, floating point and fixed length strings (compound,
+///   variable length, enumerated, array...),
+/// - dense link storage of groups with many children and shared messages.
+///
+/// Such files can usually be rewritten in the supported subset with
+/// `h5repack -f NONE -l CONTI input output`.
+///
+/// Objects are found by their absolute path such as "/group/dataset".
+pub struct Hdf5 {
+    bytes: Vec<u8>,
+    base: usize,
+    offset_size: usize,
+    length_size: usize,
+    root: usize,
+}
+
+// Message of an object header
+struct Message<'a> {
+    kind: u16,
+    flags: u8,
+    data: &'a [u8],
+}
+
+// Element type of a dataset or attribute
+struct Datatype {
+    class: u8,
+    size: usize,
+    big_endian: bool,
+    signed: bool,
+}
+
+// Storage of the raw data of a dataset
+enum Layout<'a> {
+    Compact(&'a [u8]),
+    Contiguous(Option<usize>),
+    Chunked(Option<usize>, Vec<usize>),
+}
+
+impl H5Values {
+    /// Number of values
+    pub fn len(&self) -> usize {
+        match self {
+            H5Values::Integers(v) => v.len(),
+            H5Values::Floats(v) => v.len(),
+            H5Values::Strings(v) => v.len(),
+        }
+    }
+
+    /// Whether there are no values
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Values as integers (None for floating point numbers and strings)
+    pub fn to_integers(&self) -> Option<Vec<i64>> {
+        match self {
+            H5Values::Integers(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
+    /// Values converted to floating point numbers (None for strings)
+    pub fn to_floats(&self) -> Option<Vec<f64>> {
+        match self {
+            H5Values::Integers(v) => Some(v.iter().map(|x| *x as f64).collect()),
+            H5Values::Floats(v) => Some(v.clone()),
+            H5Values::Strings(_) => None,
+        }
+    }
+
+    /// Bytes of the values for text stored either as strings or as 8 bit integers
+    pub fn to_bytes(&self) -> Option<Vec<u8>> {
+        match self {
+            H5Values::Integers(v) => Some(v.iter().map(|x| *x as u8).collect()),
+            H5Values::Floats(_) => None,
+            H5Values::Strings(v) => Some(v.iter().flat_map(|s| s.bytes()).collect()),
+        }
+    }
+}
+
+impl Hdf5 {
+    /// Read a whole HDF5 file
+    pub fn read<Reader: Read>(reader: &mut Reader) -> Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        // The superblock is at the start of the file or at a power of two from 512 bytes on
+        let mut start = 0;
+        while bytes.get(start..start + 8) != Some(SIGNATURE) {
+            start = if start == 0 { 512 } else { 2 * start };
+            if start + 8 > bytes.len() {
+                return Err(invalid("Not an HDF5 file"));
+            }
+        }
+        let version = *bytes
+            .get(start + 8)
+            .ok_or_else(|| invalid("Truncated superblock"))?;
+        if version > 3 {
+            return Err(invalid(&format!(
+                "HDF5 superblock version {} is not supported (only versions 0 to 3 are)",
+                version
+            )));
+        }
+        let mut cursor = Cursor {
+            bytes: &bytes,
+            position: start + if version < 2 { 13 } else { 9 },
+            base: 0,
+            offset_size: 8,
+            length_size: 8,
+        };
+        cursor.offset_size = cursor.take(1)?[0] as usize;
+        cursor.length_size = cursor.take(1)?[0] as usize;
+        if ![2, 4, 8].contains(&cursor.offset_size) || ![2, 4, 8].contains(&cursor.length_size) {
+            return Err(invalid("Unsupported HDF5 offset or length size"));
+        }
+        match version {
+            // Reserved byte, group K values, consistency flags and the indexed storage K
+            0 => cursor.take(9)?,
+            1 => cursor.take(13)?,
+            // Consistency flags of versions 2 and 3
+            _ => cursor.take(1)?,
+        };
+        cursor.base = cursor.uint(cursor.offset_size)? as usize;
+        if version < 2 {
+            // Free space, end of file and driver addresses then the root symbol table entry
+            cursor.take(4 * cursor.offset_size)?;
+        } else {
+            // Superblock extension and end of file addresses
+            cursor.take(2 * cursor.offset_size)?;
+        }
+        let root = cursor
+            .offset()?
+            .ok_or_else(|| invalid("Missing root group"))?;
+        let (base, offset_size, length_size) =
+            (cursor.base, cursor.offset_size, cursor.length_size);
+        let file = Hdf5 {
+            bytes,
+            base,
+            offset_size,
+            length_size,
+            root,
+        };
+        Ok(file)
+    }
+
+    /// Whether an object exists at the path
+    pub fn exists(&self, path: &str) -> bool {
+        self.object(path).is_ok()
+    }
+
+    /// Names of the objects linked from the group at the path
+    pub fn children(&self, path: &str) -> Result<Vec<String>> {
+        Ok(self
+            .links(self.object(path)?)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect())
+    }
+
+    /// Values of the dataset at the path flattened with the last dimension running fastest
+    pub fn dataset(&self, path: &str) -> Result<H5Values> {
+        let messages = self.messages(self.object(path)?)?;
+        let find = |kind: u16| {
+            messages
+                .iter()
+                .find(|m| m.kind == kind)
+                .ok_or_else(|| invalid("Object is not a dataset"))
+        };
+        let dimensions = self.dataspace(find(DATASPACE)?)?;
+        let datatype = Datatype::parse(find(DATATYPE)?)?;
+        let n: usize = dimensions.iter().product();
+        let size = n * datatype.size;
+        let raw = match self.layout(find(LAYOUT)?)? {
+            Layout::Compact(data) => data
+                .get(..size)
+                .ok_or_else(|| invalid("Truncated compact dataset"))?
+                .to_vec(),
+            Layout::Contiguous(Some(address)) => self.cursor(address)?.take(size)?.to_vec(),
+            Layout::Contiguous(None) | Layout::Chunked(None, _) => vec![0; size],
+            Layout::Chunked(Some(address), chunk) => {
+                if messages.iter().any(|m| m.kind == FILTERS) {
+                    return Err(invalid(
+                        "Filtered (e.g. compressed) HDF5 datasets are not supported",
+                    ));
+                }
+                let mut raw = vec![0; size];
+                self.read_chunks(address, &dimensions, &chunk, datatype.size, &mut raw)?;
+                raw
+            }
+        };
+        datatype.decode(&raw)
+    }
+
+    /// Values of an attribute of the object at the path if it has one of that name
+    pub fn attribute(&self, path: &str, name: &str) -> Result<Option<H5Values>> {
+        for message in self.messages(self.object(path)?)? {
+            if message.kind != ATTRIBUTE {
+                continue;
+            }
+            let data = message.data;
+            let version = data.first().copied().unwrap_or(0);
+            let field = |i: usize| -> Result<usize> {
+                data.get(i..i + 2)
+                    .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+                    .ok_or_else(|| invalid("Truncated attribute"))
+            };
+            let (name_size, type_size, space_size) = (field(2)?, field(4)?, field(6)?);
+            // Version 1 pads every field to 8 bytes and version 3 adds the name encoding
+            let pad = |n: usize| if version == 1 { n.div_ceil(8) * 8 } else { n };
+            let mut position = if version == 3 { 9 } else { 8 };
+            let attribute_name = data
+                .get(position..position + name_size)
+                .map(|b| {
+                    String::from_utf8_lossy(b)
+                        .trim_end_matches('\0')
+                        .to_string()
+                })
+                .ok_or_else(|| invalid("Truncated attribute"))?;
+            position += pad(name_size);
+            if attribute_name != name {
+                continue;
+            }
+            let datatype_data = data
+                .get(position..position + type_size)
+                .ok_or_else(|| invalid("Truncated attribute"))?;
+            let datatype = Datatype::parse(&Message {
+                kind: DATATYPE,
+                flags: 0,
+                data: datatype_data,
+            })?;
+            position += pad(type_size);
+            let dimensions = self.dataspace(&Message {
+                kind: DATASPACE,
+                flags: 0,
+                data: data
+                    .get(position..position + space_size)
+                    .ok_or_else(|| invalid("Truncated attribute"))?,
+            })?;
+            position += pad(space_size);
+            let size = dimensions.iter().product::<usize>() * datatype.size;
+            let raw = data
+                .get(position..position + size)
+                .ok_or_else(|| invalid("Truncated attribute"))?;
+            return datatype.decode(raw).map(Some);
+        }
+        Ok(None)
+    }
+
+    // Address of the object header at a path
+    fn object(&self, path: &str) -> Result<usize> {
+        let mut address = self.root;
+        for name in path.split('/').filter(|n| !n.is_empty()) {
+            address = self
+                .links(address)?
+                .into_iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, a)| a)
+                .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("No object {}", path)))?;
+        }
+        Ok(address)
+    }
+
+    // Names and object header addresses of the links of a group
+    fn links(&self, address: usize) -> Result<Vec<(String, usize)>> {
+        let mut links = Vec::new();
+        for message in self.messages(address)? {
+            let mut cursor = self.cursor_on(message.data);
+            match message.kind {
+                SYMBOL_TABLE => {
+                    let tree = cursor.offset()?;
+                    let heap = cursor
+                        .offset()?
+                        .ok_or_else(|| invalid("Missing local heap"))?;
+                    let mut heap = self.cursor(heap)?;
+                    if heap.take(4)? != b"HEAP" {
+                        return Err(invalid("Corrupted local heap"));
+                    }
+                    heap.take(4 + 2 * self.length_size)?;
+                    let names = heap.offset()?.ok_or_else(|| invalid("Missing heap data"))?;
+                    if let Some(tree) = tree {
+                        self.read_symbols(tree, names, &mut links)?;
+                    }
+                }
+                LINK => {
+                    cursor.take(1)?;
+                    let flags = cursor.take(1)?[0];
+                    let kind = if flags & 0x08 != 0 {
+                        cursor.take(1)?[0]
+                    } else {
+                        0
+                    };
+                    if flags & 0x04 != 0 {
+                        cursor.take(8)?;
+                    }
+                    if flags & 0x10 != 0 {
+                        cursor.take(1)?;
+                    }
+                    let length = cursor.uint(1 << (flags & 0x03))? as usize;
+                    let name = String::from_utf8_lossy(cursor.take(length)?).into_owned();
+                    // Only hard links point to objects of the file
+                    if kind == 0 {
+                        if let Some(target) = cursor.offset()? {
+                            links.push((name, target));
+                        }
+                    }
+                }
+                LINK_INFO => {
+                    cursor.take(1)?;
+                    if cursor.take(1)?[0] & 0x01 != 0 {
+                        cursor.take(8)?;
+                    }
+                    if cursor.offset()?.is_some() {
+                        return Err(invalid("Dense HDF5 link storage is not supported"));
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(links)
+    }
+
+    // Walk a group B-tree down to the symbol table nodes
+    fn read_symbols(
+        &self,
+        address: usize,
+        names: usize,
+        links: &mut Vec<(String, usize)>,
+    ) -> Result<()> {
+        let mut cursor = self.cursor(address)?;
+        if cursor.take(4)? != b"TREE" || cursor.take(1)?[0] != 0 {
+            return Err(invalid("Corrupted group B-tree"));
+        }
+        let level = cursor.take(1)?[0];
+        let entries = cursor.uint(2)? as usize;
+        cursor.take(2 * self.offset_size)?;
+        for _ in 0..entries {
+            cursor.take(self.length_size)?;
+            let child = cursor
+                .offset()?
+                .ok_or_else(|| invalid("Missing B-tree child"))?;
+            if level > 0 {
+                self.read_symbols(child, names, links)?;
+                continue;
+            }
+            let mut node = self.cursor(child)?;
+            if node.take(4)? != b"SNOD" {
+                return Err(invalid("Corrupted symbol table node"));
+            }
+            node.take(2)?;
+            for _ in 0..node.uint(2)? {
+                let name = node.uint(self.offset_size)? as usize;
+                let target = node
+                    .offset()?
+                    .ok_or_else(|| invalid("Missing object header"))?;
+                node.take(24)?;
+                let bytes = &self.bytes[(names + name).min(self.bytes.len())..];
+                let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+                links.push((String::from_utf8_lossy(&bytes[..end]).into_owned(), target));
+            }
+        }
+        Ok(())
+    }
+
+    // Messages of an object header in both versions including its continuation blocks
+    fn messages(&self, address: usize) -> Result<Vec<Message<'_>>> {
+        let mut messages = Vec::new();
+        let version2 = self.cursor(address)?.take(4)? == b"OHDR";
+        let mut blocks = Vec::new();
+        let mut flags = 0;
+        if version2 {
+            let mut cursor = self.cursor(address + 4)?;
+            cursor.take(1)?;
+            flags = cursor.take(1)?[0];
+            if flags & 0x20 != 0 {
+                cursor.take(16)?;
+            }
+            if flags & 0x10 != 0 {
+                cursor.take(4)?;
+            }
+            let size = cursor.uint(1 << (flags & 0x03))? as usize;
+            blocks.push((cursor.position, size));
+        } else {
+            let mut cursor = self.cursor(address)?;
+            if cursor.take(1)?[0] != 1 {
+                return Err(invalid("Unsupported HDF5 object header version"));
+            }
+            cursor.take(7)?;
+            let size = cursor.uint(4)? as usize;
+            blocks.push((address + 16, size));
+        }
+        while let Some((start, size)) = blocks.pop() {
+            let mut cursor = self.cursor(start)?;
+            let header = if version2 {
+                4 + 2 * ((flags >> 2) & 1) as usize
+            } else {
+                8
+            };
+            while cursor.position + header <= start + size {
+                let (kind, size, message_flags) = if version2 {
+                    let kind = cursor.take(1)?[0] as u16;
+                    let size = cursor.uint(2)? as usize;
+                    let message_flags = cursor.take(1)?[0];
+                    cursor.take(header - 4)?;
+                    (kind, size, message_flags)
+                } else {
+                    let kind = cursor.uint(2)? as u16;
+                    let size = cursor.uint(2)? as usize;
+                    let message_flags = cursor.take(1)?[0];
+                    cursor.take(3)?;
+                    (kind, size, message_flags)
+                };
+                let data = cursor.take(size)?;
+                if kind == CONTINUATION {
+                    let mut continuation = self.cursor_on(data);
+                    let offset = continuation
+                        .offset()?
+                        .ok_or_else(|| invalid("Missing continuation block"))?;
+                    let length = continuation.length()?;
+                    // Version 2 blocks start with a signature and end with a checksum
+                    blocks.push(if version2 {
+                        (offset + 4, length.saturating_sub(8))
+                    } else {
+                        (offset, length)
+                    });
+                } else {
+                    messages.push(Message {
+                        kind,
+                        flags: message_flags,
+                        data,
+                    });
+                }
+            }
+        }
+        Ok(messages)
+    }
+
+    // Dimensions of a dataspace (empty for scalars)
+    fn dataspace(&self, message: &Message) -> Result<Vec<usize>> {
+        if message.flags & 0x02 != 0 {
+            return Err(invalid("Shared HDF5 messages are not supported"));
+        }
+        let mut cursor = self.cursor_on(message.data);
+        let version = cursor.take(1)?[0];
+        let rank = cursor.take(1)?[0] as usize;
+        cursor.take(1)?;
+        if version == 1 {
+            cursor.take(5)?;
+        } else if cursor.take(1)?[0] == 2 {
+            // Null dataspace
+            return Ok(vec![0]);
+        }
+        (0..rank).map(|_| cursor.length()).collect()
+    }
+
+    fn layout<'a>(&self, message: &Message<'a>) -> Result<Layout<'a>> {
+        let mut cursor = self.cursor_on(message.data);
+        let version = cursor.take(1)?[0];
+        if version < 3 {
+            let rank = cursor.take(1)?[0] as usize;
+            let class = cursor.take(1)?[0];
+            cursor.take(5)?;
+            let address = if class != 0 { cursor.offset()? } else { None };
+            let dimensions: Vec<usize> = (0..rank)
+                .map(|_| cursor.uint(4).map(|d| d as usize))
+                .collect::<Result<_>>()?;
+            return Ok(match class {
+                0 => {
+                    let size = cursor.uint(4)? as usize;
+                    Layout::Compact(cursor.take(size)?)
+                }
+                1 => Layout::Contiguous(address),
+                _ => Layout::Chunked(address, dimensions),
+            });
+        }
+        match cursor.take(1)?[0] {
+            0 => {
+                let size = cursor.uint(2)? as usize;
+                Ok(Layout::Compact(cursor.take(size)?))
+            }
+            1 => Ok(Layout::Contiguous(cursor.offset()?)),
+            2 if version == 3 => {
+                let rank = cursor.take(1)?[0] as usize;
+                let address = cursor.offset()?;
+                let dimensions = (0..rank)
+                    .map(|_| cursor.uint(4).map(|d| d as usize))
+                    .collect::<Result<_>>()?;
+                Ok(Layout::Chunked(address, dimensions))
+            }
+            2 => Err(invalid(
+                "Chunked HDF5 datasets of version 4 layout messages (written with the latest \
+                 library format) are not supported",
+            )),
+            3 => Err(invalid("Virtual HDF5 datasets are not supported")),
+            _ => Err(invalid("Unknown HDF5 data layout")),
+        }
+    }
+
+    // Copy the chunks indexed by a B-tree into the flat raw data of the dataset
+    fn read_chunks(
+        &self,
+        address: usize,
+        dimensions: &[usize],
+        chunk: &[usize],
+        element_size: usize,
+        raw: &mut [u8],
+    ) -> Result<()> {
+        let mut cursor = self.cursor(address)?;
+        if cursor.take(4)? != b"TREE" || cursor.take(1)?[0] != 1 {
+            return Err(invalid("Corrupted chunk B-tree"));
+        }
+        let level = cursor.take(1)?[0];
+        let entries = cursor.uint(2)? as usize;
+        cursor.take(2 * self.offset_size)?;
+        // The chunk dimensions hold the element size last
+        let rank = dimensions.len();
+        let chunk = &chunk[..rank];
+        let chunk_size = chunk.iter().product::<usize>() * element_size;
+        for _ in 0..entries {
+            cursor.take(8)?;
+            let offsets: Vec<usize> = (0..=rank)
+                .map(|_| cursor.uint(8).map(|o| o as usize))
+                .collect::<Result<_>>()?;
+            let child = cursor
+                .offset()?
+                .ok_or_else(|| invalid("Missing B-tree child"))?;
+            if level > 0 {
+                self.read_chunks(child, dimensions, chunk, element_size, raw)?;
+                continue;
+            }
+            let data = self.cursor(child)?.take(chunk_size)?;
+            // Odometer over the elements of the chunk clipped to the dataset
+            let mut local = vec![0; rank];
+            for element in data.chunks_exact(element_size) {
+                let mut index = 0;
+                let mut inside = true;
+                for d in 0..rank {
+                    let global = offsets[d] + local[d];
+                    inside &= global < dimensions[d];
+                    index = index * dimensions[d] + global;
+                }
+                if inside {
+                    raw[index * element_size..(index + 1) * element_size].copy_from_slice(element);
+                }
+                for d in (0..rank).rev() {
+                    local[d] += 1;
+                    if local[d] < chunk[d] {
+                        break;
+                    }
+                    local[d] = 0;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn cursor(&self, address: usize) -> Result<Cursor<'_>> {
+        if address > self.bytes.len() {
+            return Err(invalid("Address outside of the HDF5 file"));
+        }
+        Ok(Cursor {
+            bytes: &self.bytes,
+            position: address,
+            base: self.base,
+            offset_size: self.offset_size,
+            length_size: self.length_size,
+        })
+    }
+
+    fn cursor_on<'a>(&self, bytes: &'a [u8]) -> Cursor<'a> {
+        Cursor {
+            bytes,
+            position: 0,
+            base: self.base,
+            offset_size: self.offset_size,
+            length_size: self.length_size,
+        }
+    }
+}
+
+impl Datatype {
+    fn parse(message: &Message) -> Result<Self> {
+        if message.flags & 0x02 != 0 {
+            return Err(invalid("Shared HDF5 datatypes are not supported"));
+        }
+        let data = message.data;
+        if data.len() < 8 {
+            return Err(invalid("Truncated datatype"));
+        }
+        let class = data[0] & 0x0F;
+        let size = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        let supported = match class {
+            0 => [1, 2, 4, 8].contains(&size),
+            1 => [4, 8].contains(&size) && data[1] & 0x40 == 0,
+            3 => true,
+            _ => false,
+        };
+        if !supported {
+            let name = match class {
+                0 | 1 => "numeric of this size or byte order",
+                2 => "time",
+                4 => "bitfield",
+                5 => "opaque",
+                6 => "compound",
+                7 => "reference",
+                8 => "enumerated",
+                9 => "variable length",
+                10 => "array",
+                _ => "unknown",
+            };
+            return Err(invalid(&format!(
+                "HDF5 {} datatypes are not supported",
+                name
+            )));
+        }
+        Ok(Datatype {
+            class,
+            size,
+            big_endian: data[1] & 0x01 != 0,
+            signed: data[1] & 0x08 != 0,
+        })
+    }
+
+    fn decode(&self, raw: &[u8]) -> Result<H5Values> {
+        let elements = raw.chunks_exact(self.size);
+        let unsigned = |bytes: &[u8]| {
+            let mut value = 0u64;
+            for b in 0..self.size {
+                let byte = if self.big_endian {
+                    bytes[b]
+                } else {
+                    bytes[self.size - 1 - b]
+                };
+                value = (value << 8) | byte as u64;
+            }
+            value
+        };
+        Ok(match self.class {
+            0 => H5Values::Integers(
+                elements
+                    .map(|e| {
+                        let value = unsigned(e);
+                        let shift = 64 - 8 * self.size as u32;
+                        if self.signed {
+                            ((value << shift) as i64) >> shift
+                        } else {
+                            value as i64
+                        }
+                    })
+                    .collect(),
+            ),
+            1 => H5Values::Floats(
+                elements
+                    .map(|e| {
+                        let bits = unsigned(e);
+                        if self.size == 4 {
+                            f32::from_bits(bits as u32) as f64
+                        } else {
+                            f64::from_bits(bits)
+                        }
+                    })
+                    .collect(),
+            ),
+            _ => H5Values::Strings(
+                elements
+                    .map(|e| {
+                        let end = e.iter().position(|b| *b == 0).unwrap_or(e.len());
+                        String::from_utf8_lossy(&e[..end]).trim_end().to_string()
+                    })
+                    .collect(),
+            ),
+        })
+    }
+}
+
+// Little endian reading position in the bytes of a file
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    base: usize,
+    offset_size: usize,
+    length_size: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let slice = self
+            .bytes
+            .get(self.position..self.position + n)
+            .ok_or_else(|| invalid("Unexpected end of the HDF5 file"))?;
+        self.position += n;
+        Ok(slice)
+    }
+
+    fn uint(&mut self, n: usize) -> Result<u64> {
+        Ok(self
+            .take(n)?
+            .iter()
+            .rev()
+            .fold(0, |value, b| (value << 8) | *b as u64))
+    }
+
+    // Absolute address in the file (None for the undefined address)
+    fn offset(&mut self) -> Result<Option<usize>> {
+        let n = self.offset_size;
+        let value = self.uint(n)?;
+        if value == u64::MAX >> (64 - 8 * n) {
+            Ok(None)
+        } else {
+            Ok(Some(self.base + value as usize))
+        }
+    }
+
+    fn length(&mut self) -> Result<usize> {
+        let n = self.length_size;
+        Ok(self.uint(n)? as usize)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Object of a file built for testing
+    pub(crate) enum Node {
+        Group(Vec<(String, Node)>, Vec<(String, H5Values)>),
+        Dataset(H5Values, Option<usize>, Vec<(String, H5Values)>),
+    }
+
+    /// Bytes of an HDF5 file with a version 0 superblock
+    ///
+    /// Groups are written with either symbol tables and version 1 object headers or compact links
+    /// and version 2 object headers. Datasets of more than one chunk are chunked.
+    pub(crate) fn build(root: &Node, compact_links: bool) -> Vec<u8> {
+        let mut bytes = vec![0; 96];
+        let root = write_node(root, compact_links, &mut bytes);
+        bytes[..8].copy_from_slice(SIGNATURE);
+        bytes[13] = 8;
+        bytes[14] = 8;
+        bytes[16] = 4;
+        bytes[18] = 16;
+        bytes[24..32].copy_from_slice(&0u64.to_le_bytes());
+        bytes[32..40].copy_from_slice(&u64::MAX.to_le_bytes());
+        let end = bytes.len() as u64;
+        bytes[40..48].copy_from_slice(&end.to_le_bytes());
+        bytes[48..56].copy_from_slice(&u64::MAX.to_le_bytes());
+        bytes[64..72].copy_from_slice(&(root as u64).to_le_bytes());
+        bytes
+    }
+
+    fn write_node(node: &Node, compact_links: bool, bytes: &mut Vec<u8>) -> usize {
+        match node {
+            Node::Group(children, attributes) => {
+                let addresses: Vec<u64> = children
+                    .iter()
+                    .map(|(_, child)| write_node(child, compact_links, bytes) as u64)
+                    .collect();
+                let mut messages = Vec::new();
+                if compact_links {
+                    let mut info = vec![0, 0];
+                    info.extend(u64::MAX.to_le_bytes());
+                    info.extend(u64::MAX.to_le_bytes());
+                    messages.push((LINK_INFO, info));
+                    for ((name, _), address) in children.iter().zip(&addresses) {
+                        let mut link = vec![1, 0, name.len() as u8];
+                        link.extend(name.as_bytes());
+                        link.extend(address.to_le_bytes());
+                        messages.push((LINK, link));
+                    }
+                } else {
+                    // Local heap holding the names after an empty one
+                    let mut names = vec![0; 8];
+                    let mut offsets = Vec::new();
+                    for (name, _) in children {
+                        offsets.push(names.len() as u64);
+                        names.extend(name.as_bytes());
+                        names.resize((names.len() + 1).div_ceil(8) * 8, 0);
+                    }
+                    let data = bytes.len() as u64;
+                    bytes.extend(&names);
+                    let heap = bytes.len() as u64;
+                    bytes.extend(b"HEAP\0\0\0\0");
+                    bytes.extend((names.len() as u64).to_le_bytes());
+                    bytes.extend(u64::MAX.to_le_bytes());
+                    bytes.extend(data.to_le_bytes());
+                    let symbols = bytes.len() as u64;
+                    bytes.extend(b"SNOD\x01\0");
+                    bytes.extend((children.len() as u16).to_le_bytes());
+                    for (offset, address) in offsets.iter().zip(&addresses) {
+                        bytes.extend(offset.to_le_bytes());
+                        bytes.extend(address.to_le_bytes());
+                        bytes.extend([0; 24]);
+                    }
+                    let tree = bytes.len() as u64;
+                    bytes.extend(b"TREE\0\0\x01\0");
+                    bytes.extend(u64::MAX.to_le_bytes());
+                    bytes.extend(u64::MAX.to_le_bytes());
+                    bytes.extend(0u64.to_le_bytes());
+                    bytes.extend(symbols.to_le_bytes());
+                    bytes.extend(offsets.last().copied().unwrap_or(0).to_le_bytes());
+                    let mut table = tree.to_le_bytes().to_vec();
+                    table.extend(heap.to_le_bytes());
+                    messages.push((SYMBOL_TABLE, table));
+                }
+                for (name, values) in attributes {
+                    messages.push((ATTRIBUTE, attribute(name, values, compact_links)));
+                }
+                write_header(&messages, compact_links, bytes)
+            }
+            Node::Dataset(values, chunk, attributes) => {
+                let (datatype, raw) = encode(values);
+                let mut messages = vec![
+                    (DATASPACE, dataspace(values.len(), false)),
+                    (DATATYPE, datatype),
+                ];
+                let element_size = raw.len() / values.len().max(1);
+                let mut layout = vec![3];
+                match chunk {
+                    Some(chunk) => {
+                        // Chunks padded to their full size indexed by a single B-tree leaf
+                        let n_chunks = values.len().div_ceil(*chunk);
+                        let mut chunks = Vec::new();
+                        for c in 0..n_chunks {
+                            chunks.push(bytes.len() as u64);
+                            let start = c * chunk * element_size;
+                            let end = ((c + 1) * chunk * element_size).min(raw.len());
+                            bytes.extend(&raw[start..end]);
+                            bytes.resize(bytes.len() + start + chunk * element_size - end, 0);
+                        }
+                        let tree = bytes.len() as u64;
+                        bytes.extend(b"TREE\x01\0");
+                        bytes.extend((n_chunks as u16).to_le_bytes());
+                        bytes.extend(u64::MAX.to_le_bytes());
+                        bytes.extend(u64::MAX.to_le_bytes());
+                        for (c, address) in chunks.iter().enumerate() {
+                            bytes.extend(((chunk * element_size) as u32).to_le_bytes());
+                            bytes.extend(0u32.to_le_bytes());
+                            bytes.extend(((c * chunk) as u64).to_le_bytes());
+                            bytes.extend(0u64.to_le_bytes());
+                            bytes.extend(address.to_le_bytes());
+                        }
+                        bytes.extend([0; 24]);
+                        layout.extend([2, 2]);
+                        layout.extend(tree.to_le_bytes());
+                        layout.extend((*chunk as u32).to_le_bytes());
+                        layout.extend((element_size as u32).to_le_bytes());
+                    }
+                    None => {
+                        let address = bytes.len() as u64;
+                        bytes.extend(&raw);
+                        layout.push(1);
+                        layout.extend(address.to_le_bytes());
+                        layout.extend((raw.len() as u64).to_le_bytes());
+                    }
+                }
+                messages.push((LAYOUT, layout));
+                for (name, values) in attributes {
+                    messages.push((ATTRIBUTE, attribute(name, values, false)));
+                }
+                write_header(&messages, false, bytes)
+            }
+        }
+    }
+
+    // Object header of either version with a continuation block for the second half of messages
+    fn write_header(messages: &[(u16, Vec<u8>)], version2: bool, bytes: &mut Vec<u8>) -> usize {
+        let half = messages.len() / 2;
+        let encode_messages = |messages: &[(u16, Vec<u8>)]| {
+            let mut block = Vec::new();
+            for (kind, data) in messages {
+                if version2 {
+                    block.push(*kind as u8);
+                    block.extend((data.len() as u16).to_le_bytes());
+                    block.push(0);
+                    block.extend(data);
+                } else {
+                    let size = data.len().div_ceil(8) * 8;
+                    block.extend(kind.to_le_bytes());
+                    block.extend((size as u16).to_le_bytes());
+                    block.extend([0; 4]);
+                    block.extend(data);
+                    block.resize(block.len() + size - data.len(), 0);
+                }
+            }
+            block
+        };
+        let mut continuation = encode_messages(&messages[half..]);
+        let continuation_address = bytes.len() as u64;
+        if version2 {
+            continuation.splice(0..0, *b"OCHK");
+            continuation.extend([0; 4]);
+        }
+        bytes.extend(&continuation);
+        let mut pointer = continuation_address.to_le_bytes().to_vec();
+        pointer.extend((continuation.len() as u64).to_le_bytes());
+        let mut first = messages[..half].to_vec();
+        first.push((CONTINUATION, pointer));
+        let block = encode_messages(&first);
+        let address = bytes.len();
+        if version2 {
+            bytes.extend(b"OHDR\x02\x02");
+            bytes.extend((block.len() as u32).to_le_bytes());
+            bytes.extend(&block);
+            bytes.extend([0; 4]);
+        } else {
+            bytes.extend([1, 0]);
+            bytes.extend((first.len() as u16).to_le_bytes());
+            bytes.extend(1u32.to_le_bytes());
+            bytes.extend((block.len() as u32).to_le_bytes());
+            bytes.extend([0; 4]);
+            bytes.extend(&block);
+        }
+        address
+    }
+
+    // Datatype message and little endian raw data of values
+    fn encode(values: &H5Values) -> (Vec<u8>, Vec<u8>) {
+        match values {
+            H5Values::Integers(v) => {
+                let mut datatype = vec![0x10, 0x08, 0, 0];
+                datatype.extend(4u32.to_le_bytes());
+                datatype.extend([0, 0, 32, 0]);
+                let raw = v.iter().flat_map(|x| (*x as i32).to_le_bytes()).collect();
+                (datatype, raw)
+            }
+            H5Values::Floats(v) => {
+                let mut datatype = vec![0x11, 0x20, 63, 0];
+                datatype.extend(8u32.to_le_bytes());
+                datatype.extend([0, 0, 64, 0, 52, 11, 0, 52]);
+                datatype.extend(1023u32.to_le_bytes());
+                let raw = v.iter().flat_map(|x| x.to_le_bytes()).collect();
+                (datatype, raw)
+            }
+            H5Values::Strings(v) => {
+                let size = v.iter().map(|s| s.len()).max().unwrap_or(0) + 1;
+                let mut datatype = vec![0x13, 0, 0, 0];
+                datatype.extend((size as u32).to_le_bytes());
+                let mut raw = Vec::new();
+                for s in v {
+                    raw.extend(s.as_bytes());
+                    raw.resize(raw.len() + size - s.len(), 0);
+                }
+                (datatype, raw)
+            }
+        }
+    }
+
+    // One dimensional dataspace message (version 2 for the compact variant)
+    fn dataspace(n: usize, version2: bool) -> Vec<u8> {
+        let mut space = if version2 {
+            vec![2, 1, 0, 1]
+        } else {
+            vec![1, 1, 0, 0, 0, 0, 0, 0]
+        };
+        space.extend((n as u64).to_le_bytes());
+        space
+    }
+
+    // Attribute message of version 3 or of the padded version 1
+    fn attribute(name: &str, values: &H5Values, version3: bool) -> Vec<u8> {
+        let (datatype, raw) = encode(values);
+        let space = dataspace(values.len(), version3);
+        let mut name = name.as_bytes().to_vec();
+        name.push(0);
+        let mut message = vec![if version3 { 3 } else { 1 }, 0];
+        message.extend((name.len() as u16).to_le_bytes());
+        message.extend((datatype.len() as u16).to_le_bytes());
+        message.extend((space.len() as u16).to_le_bytes());
+        if version3 {
+            message.push(0);
+        }
+        for field in [name, datatype, space] {
+            let size = if version3 {
+                field.len()
+            } else {
+                field.len().div_ceil(8) * 8
+            };
+            message.resize(message.len() + size, 0);
+            let start = message.len() - size;
+            message[start..start + field.len()].copy_from_slice(&field);
+        }
+        message.extend(raw);
+        message
+    }
+
+    #[test]
+    fn test_hdf5_read() {
+        let tree = Node::Group(
+            vec![
+                (
+                    "numbers".to_string(),
+                    Node::Group(
+                        vec![
+                            (
+                                "x".to_string(),
+                                Node::Dataset(
+                                    H5Values::Floats(vec![0.5, -1.0, 2.0]),
+                                    None,
+                                    vec![("NBR".to_string(), H5Values::Integers(vec![3]))],
+                                ),
+                            ),
+                            (
+                                "ids".to_string(),
+                                Node::Dataset(
+                                    H5Values::Integers((-3..4).collect()),
+                                    Some(3),
+                                    vec![],
+                                ),
+                            ),
+                        ],
+                        vec![("NUM".to_string(), H5Values::Integers(vec![-7]))],
+                    ),
+                ),
+                (
+                    "names".to_string(),
+                    Node::Dataset(
+                        H5Values::Strings(vec!["left".to_string(), "top".to_string()]),
+                        None,
+                        vec![],
+                    ),
+                ),
+            ],
+            vec![],
+        );
+        for compact_links in [false, true] {
+            let bytes = build(&tree, compact_links);
+            let file = Hdf5::read(&mut bytes.as_slice()).unwrap();
+            assert_eq!(
+                file.children("/").unwrap(),
+                vec!["numbers", "names"],
+                "Wrong root group"
+            );
+            assert_eq!(
+                file.children("/numbers").unwrap(),
+                vec!["x", "ids"],
+                "Wrong group"
+            );
+            assert!(file.exists("/numbers/x"), "Dataset should exist");
+            assert!(!file.exists("/numbers/y"), "Dataset should not exist");
+            assert_eq!(
+                file.dataset("/numbers/x").unwrap(),
+                H5Values::Floats(vec![0.5, -1.0, 2.0]),
+                "Wrong contiguous dataset"
+            );
+            assert_eq!(
+                file.dataset("/numbers/ids").unwrap(),
+                H5Values::Integers((-3..4).collect()),
+                "Wrong chunked dataset"
+            );
+            assert_eq!(
+                file.dataset("names").unwrap().to_bytes(),
+                Some(b"lefttop".to_vec()),
+                "Wrong strings"
+            );
+            assert_eq!(
+                file.attribute("/numbers/x", "NBR").unwrap(),
+                Some(H5Values::Integers(vec![3])),
+                "Wrong dataset attribute"
+            );
+            assert_eq!(
+                file.attribute("/numbers", "NUM").unwrap(),
+                Some(H5Values::Integers(vec![-7])),
+                "Wrong group attribute"
+            );
+            assert_eq!(
+                file.attribute("/numbers", "NBR").unwrap(),
+                None,
+                "Unknown attribute"
+            );
+        }
+        assert!(
+            Hdf5::read(&mut b"CDF\x01".as_slice()).is_err(),
+            "Other formats should be rejected"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_hdf5_unsupported() {
+        let tree = Node::Group(
+            vec![(
+                "x".to_string(),
+                Node::Dataset(H5Values::Floats(vec![0.5]), None, vec![]),
+            )],
+            vec![],
+        );
+        let mut bytes = build(&tree, false);
+        bytes[8] = 4;
+        let error = Hdf5::read(&mut bytes.as_slice()).err().unwrap();
+        assert!(
+            error.to_string().contains("superblock version 4"),
+            "The error should name the superblock version: {}",
+            error
+        );
+        bytes[8] = 0;
+        // Turn the floating point datatype into a compound one
+        let datatype = bytes
+            .windows(4)
+            .position(|w| w == [0x11, 0x20, 63, 0])
+            .unwrap();
+        bytes[datatype] = 0x16;
+        let file = Hdf5::read(&mut bytes.as_slice()).unwrap();
+        let error = file.dataset("/x").err().unwrap();
+        assert!(
+            error.to_string().contains("compound datatypes"),
+            "The error should name the datatype class: {}",
+            error
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use super::hdf5::{H5Values, Hdf5};
+use crate::core::arrays::data_hold::DataHold;
+#[cfg(hosted)]
+use crate::core::logging::Span;
+use crate::discretizations::mesh::Mesh;
+use std::collections::{BTreeMap, HashMap};
+#[cfg(hosted)]
+use std::fs::File;
+#[cfg(hosted)]
+use std::io::BufReader;
+use std::io::{Error, ErrorKind, Read, Result};
+#[cfg(hosted)]
+use std::path::Path;
+
+// Length of the group names of the families
+const GROUP_NAME_LENGTH: usize = 80;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Groups of a MED mesh
+///
+/// MED attaches a family number to every entity and every family belongs to some groups. Facet and
+/// vertex families are kept as tags of the Mesh (the absolute value of the family number) so a
+/// group is given by the tags of its families. A cell can belong to several groups so the cells of a
+/// group are listed explicitly.
+pub struct MedGroups {
+    tags: BTreeMap<String, Vec<usize>>,
+    cells: BTreeMap<String, Vec<usize>>,
+}
+
+impl MedGroups {
+    /// Names of the groups
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .tags
+            .keys()
+            .chain(self.cells.keys())
+            .map(|n| n.as_str())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Facet and vertex tags of the families of a group
+    pub fn tags(&self, group: &str) -> &[usize] {
+        self.tags.get(group).map_or(&[], |t| t.as_slice())
+    }
+
+    /// Cells of a group
+    pub fn cells(&self, group: &str) -> &[usize] {
+        self.cells.get(group).map_or(&[], |c| c.as_slice())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Read the first unstructured mesh of a MED file (as written by Salome) and its groups
+///
+/// The cells are the linear simplices (SE2, TR3 or TE4) of the dimension of the mesh and any other
+/// cell of that dimension is an error. Linear simplices of the dimension below become tagged facets
+/// and nodes of non zero families tagged vertices. The first computation step is read when the
+/// mesh has several. Files are read with the HDF5 subset of Hdf5, whose documentation lists the
+/// unsupported features (compressed datasets, newer superblocks...) and how to convert such files.
+pub fn read_med<Reader: Read>(reader: &mut Reader) -> Result<(Mesh, MedGroups)> {
+    let file = Hdf5::read(reader)?;
+    let name = file
+        .children("/ENS_MAA")?
+        .into_iter()
+        .next()
+        .ok_or_else(|| invalid("MED file has no mesh"))?;
+    let mesh_path = format!("/ENS_MAA/{}", name);
+    let dim = dimension_attribute(&file, &mesh_path, "ESP")
+        .or_else(|_| dimension_attribute(&file, &mesh_path, "DIM"))?;
+    // MED 3 and later store the mesh by computation step
+    let mut data_path = mesh_path.clone();
+    if !file.exists(&format!("{}/NOE", mesh_path)) {
+        let mut steps = file.children(&mesh_path)?;
+        steps.sort_unstable();
+        let step = steps
+            .into_iter()
+            .find(|s| file.exists(&format!("{}/{}/NOE", mesh_path, s)))
+            .ok_or_else(|| invalid("MED mesh has no nodes"))?;
+        data_path = format!("{}/{}", mesh_path, step);
+    }
+    // Coordinates are stored without interlacing: all the first coordinates first
+    let coordinates = floats(file.dataset(&format!("{}/NOE/COO", data_path))?)?;
+    let n_nodes = coordinates.len() / dim;
+    let mut vertices = vec![0.0; n_nodes * dim];
+    for (i, x) in coordinates.iter().enumerate() {
+        vertices[(i % n_nodes) * dim + i / n_nodes] = *x;
+    }
+    let node_families = families(&file, &format!("{}/NOE", data_path), n_nodes)?;
+    // Nodal connectivities of the linear simplices by dimension
+    let mut simplices: HashMap<usize, (Vec<usize>, Vec<i64>)> = HashMap::new();
+    let mut other_types = Vec::new();
+    let elements_path = format!("{}/MAI", data_path);
+    let types = if file.exists(&elements_path) {
+        file.children(&elements_path)?
+    } else {
+        Vec::new()
+    };
+    for element_type in types {
+        let type_path = format!("{}/{}", elements_path, element_type);
+        let simplex_dim = match element_type.as_str() {
+            "SE2" => 1,
+            "TR3" => 2,
+            "TE4" => 3,
+            _ => {
+                other_types.push(element_type);
+                continue;
+            }
+        };
+        let n = simplex_dim + 1;
+        let connectivity = integers(file.dataset(&format!("{}/NOD", type_path))?)?;
+        let n_elements = connectivity.len() / n;
+        let mut elements = vec![0; n_elements * n];
+        for (i, node) in connectivity.iter().enumerate() {
+            if *node < 1 || *node as usize > n_nodes {
+                return Err(invalid("MED element references a non existing node"));
+            }
+            elements[(i % n_elements) * n + i / n_elements] = *node as usize - 1;
+        }
+        simplices.insert(
+            simplex_dim,
+            (elements, families(&file, &type_path, n_elements)?),
+        );
+    }
+    let mesh_dim = match dimension_attribute(&file, &mesh_path, "DIM") {
+        Ok(mesh_dim) => mesh_dim,
+        Err(_) => *simplices
+            .keys()
+            .max()
+            .ok_or_else(|| invalid("MED mesh has no simplices"))?,
+    };
+    let type_dimension = |element_type: &str| match element_type.get(..2) {
+        Some("PO") => 0,
+        Some("SE") => 1,
+        Some("TR") | Some("QU") => 2,
+        _ => 3,
+    };
+    if other_types.iter().any(|t| type_dimension(t) == mesh_dim) {
+        return Err(invalid(
+            "Only linear simplicial cells are supported in MED meshes",
+        ));
+    }
+    let (cells, cell_families) = simplices
+        .remove(&mesh_dim)
+        .ok_or_else(|| invalid("MED mesh has no cells"))?;
+    let n_cells = cell_families.len();
+    let mut mesh = Mesh::new(
+        DataHold::new(vertices, [n_nodes, dim]),
+        DataHold::new(cells, [n_cells, mesh_dim + 1]),
+    );
+    if let Some((facets, facet_families)) = simplices.get(&(mesh_dim - 1)) {
+        for (facet, family) in facets.chunks(mesh_dim).zip(facet_families) {
+            if *family != 0 {
+                mesh.tag_facet(facet, family.unsigned_abs() as usize);
+            }
+        }
+    }
+    for (vertex, family) in node_families.iter().enumerate() {
+        if *family != 0 {
+            mesh.tag_vertex(vertex, family.unsigned_abs() as usize);
+        }
+    }
+    // Groups of the families of elements (negative numbers) and nodes (positive numbers)
+    let mut family_groups: HashMap<i64, Vec<String>> = HashMap::new();
+    for kind in ["ELEME", "NOEUD"] {
+        let path = format!("/FAS/{}/{}", name, kind);
+        if !file.exists(&path) {
+            continue;
+        }
+        for family in file.children(&path)? {
+            let family_path = format!("{}/{}", path, family);
+            let number = integer_attribute(&file, &family_path, "NUM")?;
+            let names_path = format!("{}/GRO/NOM", family_path);
+            if !file.exists(&names_path) {
+                continue;
+            }
+            let names = file
+                .dataset(&names_path)?
+                .to_bytes()
+                .ok_or_else(|| invalid("MED group names should be text"))?;
+            family_groups.insert(number, names.chunks(GROUP_NAME_LENGTH).map(text).collect());
+        }
+    }
+    let mut groups = MedGroups {
+        tags: BTreeMap::new(),
+        cells: BTreeMap::new(),
+    };
+    for (number, names) in family_groups.iter() {
+        for name in names {
+            let tags = groups.tags.entry(name.clone()).or_default();
+            tags.push(number.unsigned_abs() as usize);
+            tags.sort_unstable();
+        }
+    }
+    for (cell, family) in cell_families.iter().enumerate() {
+        for name in family_groups.get(family).into_iter().flatten() {
+            groups.cells.entry(name.clone()).or_default().push(cell);
+        }
+    }
+    Ok((mesh, groups))
+}
+
+/// Read the first mesh of a MED file on disk and its groups
+#[cfg(hosted)]
+pub fn load_med<P: AsRef<Path>>(path: P) -> Result<(Mesh, MedGroups)> {
+    let _span = Span::enter("MED input");
+    read_med(&mut BufReader::new(File::open(path)?))
+}
+
+// Family numbers of the entities of a group (zero when the file has none)
+fn families(file: &Hdf5, path: &str, n: usize) -> Result<Vec<i64>> {
+    let path = format!("{}/FAM", path);
+    if !file.exists(&path) {
+        return Ok(vec![0; n]);
+    }
+    let families = integers(file.dataset(&path)?)?;
+    if families.len() != n {
+        return Err(invalid("Wrong number of MED family numbers"));
+    }
+    Ok(families)
+}
+
+fn integer_attribute(file: &Hdf5, path: &str, name: &str) -> Result<i64> {
+    file.attribute(path, name)?
+        .and_then(|v| v.to_integers())
+        .and_then(|v| v.first().copied())
+        .ok_or_else(|| invalid("Missing MED attribute"))
+}
+
+fn dimension_attribute(file: &Hdf5, path: &str, name: &str) -> Result<usize> {
+    match usize::try_from(integer_attribute(file, path, name)?) {
+        Ok(dim) if dim > 0 => Ok(dim),
+        _ => Err(invalid("Wrong MED dimension")),
+    }
+}
+
+fn integers(values: H5Values) -> Result<Vec<i64>> {
+    values
+        .to_integers()
+        .ok_or_else(|| invalid("Expected integer values"))
+}
+
+fn floats(values: H5Values) -> Result<Vec<f64>> {
+    values
+        .to_floats()
+        .ok_or_else(|| invalid("Expected floating point values"))
+}
+
+// Characters of a fixed length name up to the first null byte
+fn text(characters: &[u8]) -> String {
+    let end = characters
+        .iter()
+        .position(|c| *c == 0)
+        .unwrap_or(characters.len());
+    String::from_utf8_lossy(&characters[..end])
+        .trim_end()
+        .to_string()
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::io::hdf5::tests::{build, Node};
+
+    fn dataset(name: &str, values: H5Values) -> (String, Node) {
+        (name.to_string(), Node::Dataset(values, None, vec![]))
+    }
+
+    fn group(
+        name: &str,
+        children: Vec<(String, Node)>,
+        attributes: &[(&str, i64)],
+    ) -> (String, Node) {
+        let attributes = attributes
+            .iter()
+            .map(|(n, v)| (n.to_string(), H5Values::Integers(vec![*v])))
+            .collect();
+        (name.to_string(), Node::Group(children, attributes))
+    }
+
+    fn family(name: &str, number: i64, groups: &[&str]) -> (String, Node) {
+        let mut names = Vec::new();
+        for group in groups {
+            let mut characters = group.as_bytes().to_vec();
+            characters.resize(GROUP_NAME_LENGTH, 0);
+            names.extend(characters.into_iter().map(|c| c as i64));
+        }
+        let names = group("GRO", vec![dataset("NOM", H5Values::Integers(names))], &[]);
+        group(name, vec![names], &[("NUM", number)])
+    }
+
+    #[test]
+    fn test_read_med() {
+        // Unit square split in two triangles with two boundary segments and a corner node
+        let nodes = group(
+            "NOE",
+            vec![
+                dataset(
+                    "COO",
+                    H5Values::Floats(vec![0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0]),
+                ),
+                dataset("FAM", H5Values::Integers(vec![1, 0, 0, 0])),
+            ],
+            &[],
+        );
+        let triangles = group(
+            "TR3",
+            vec![
+                dataset("NOD", H5Values::Integers(vec![1, 4, 2, 3, 3, 2])),
+                dataset("FAM", H5Values::Integers(vec![-3, 0])),
+            ],
+            &[],
+        );
+        let segments = group(
+            "SE2",
+            vec![
+                dataset("NOD", H5Values::Integers(vec![1, 2, 2, 4])),
+                dataset("FAM", H5Values::Integers(vec![-1, -2])),
+            ],
+            &[],
+        );
+        let step = group(
+            "-0000000000000000001-0000000000000000001",
+            vec![nodes, group("MAI", vec![triangles, segments], &[])],
+            &[("NDT", -1)],
+        );
+        let meshes = group(
+            "ENS_MAA",
+            vec![group("square", vec![step], &[("ESP", 2), ("DIM", 2)])],
+            &[],
+        );
+        let families = group(
+            "FAS",
+            vec![group(
+                "square",
+                vec![
+                    group("FAMILLE_ZERO", vec![], &[("NUM", 0)]),
+                    group(
+                        "ELEME",
+                        vec![
+                            family("FAM_-1_bottom", -1, &["bottom", "walls"]),
+                            family("FAM_-2_right", -2, &["walls"]),
+                            family("FAM_-3_inner", -3, &["inner"]),
+                        ],
+                        &[],
+                    ),
+                    group("NOEUD", vec![family("FAM_1_corner", 1, &["corner"])], &[]),
+                ],
+                &[],
+            )],
+            &[],
+        );
+        let bytes = build(&Node::Group(vec![meshes, families], vec![]), true);
+        let (mesh, groups) = read_med(&mut bytes.as_slice()).unwrap();
+        assert_eq!(mesh.n_vertices(), 4, "Wrong number of vertices");
+        assert_eq!(mesh.vertex(3), &[1.0, 1.0], "Wrong coordinates");
+        assert_eq!(mesh.n_cells(), 2, "Wrong number of cells");
+        assert_eq!(mesh.cell(0), &[0, 1, 2], "Wrong connectivity");
+        assert_eq!(mesh.cell(1), &[3, 2, 1], "Wrong connectivity");
+        assert_eq!(mesh.facet_tag(&[0, 1]), Some(1), "Wrong facet tag");
+        assert_eq!(mesh.facet_tag(&[1, 3]), Some(2), "Wrong facet tag");
+        assert_eq!(mesh.vertex_tag(0), Some(1), "Wrong vertex tag");
+        assert_eq!(mesh.vertex_tag(1), None, "Vertex should not be tagged");
+        assert_eq!(
+            groups.names(),
+            vec!["bottom", "corner", "inner", "walls"],
+            "Wrong group names"
+        );
+        assert_eq!(groups.tags("walls"), &[1, 2], "Wrong group tags");
+        assert_eq!(groups.cells("inner"), &[0], "Wrong group cells");
+        assert!(groups.cells("walls").is_empty(), "Group has no cells");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_read_med_fixture() {
+        let bytes = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/plate.med"));
+        let (mesh, groups) = read_med(&mut bytes.as_slice()).unwrap();
+        assert_eq!(mesh.n_vertices(), 6, "Wrong number of vertices");
+        assert_eq!(mesh.vertex(4), &[1.0, 0.5], "Wrong coordinates");
+        assert_eq!(mesh.n_cells(), 5, "Wrong number of cells");
+        assert_eq!(mesh.cell(1), &[5, 1, 4], "Wrong connectivity");
+        assert_eq!(mesh.facet_tag(&[0, 5]), Some(1), "Wrong facet tag");
+        assert_eq!(mesh.facet_tag(&[5, 1]), Some(1), "Wrong facet tag");
+        assert_eq!(mesh.facet_tag(&[1, 2]), Some(2), "Wrong facet tag");
+        assert_eq!(mesh.facet_tag(&[2, 3]), None, "Facet should not be tagged");
+        assert_eq!(mesh.vertex_tag(2), Some(1), "Wrong vertex tag");
+        assert_eq!(
+            groups.names(),
+            vec!["bottom", "corner", "lower_left", "walls"],
+            "Wrong group names"
+        );
+        assert_eq!(groups.tags("walls"), &[1, 2], "Wrong group tags");
+        assert_eq!(groups.cells("lower_left"), &[0, 1], "Wrong group cells");
+    }
+}
@@ -0,0 +1,17 @@
+/// Legacy VTK output of meshes and functions
+pub mod vtk;
+
+/// Reading and writing of datasets in the classic NetCDF format
+pub mod netcdf;
+
+/// Exodus II meshes and nodal results
+pub mod exodus;
+
+/// Read only access to HDF5 files
+pub mod hdf5;
+
+/// Reading of MED meshes (the format of Salome)
+pub mod med;
+
+/// In-memory buffers of 2-D meshes and functions for interactive rendering
+pub mod render;
@@ -0,0 +1,811 @@
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+// Tags of the lists of the header
+const ABSENT: u32 = 0;
+const NC_DIMENSION: u32 = 10;
+const NC_VARIABLE: u32 = 11;
+const NC_ATTRIBUTE: u32 = 12;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Typed values of a NetCDF variable or attribute
+#[derive(Clone, Debug, PartialEq)]
+pub enum NcValues {
+    /// Signed 8 bit integers
+    Byte(Vec<i8>),
+    /// Characters (bytes of text)
+    Char(Vec<u8>),
+    /// Signed 16 bit integers
+    Short(Vec<i16>),
+    /// Signed 32 bit integers
+    Int(Vec<i32>),
+    /// Single precision floating point numbers
+    Float(Vec<f32>),
+    /// Double precision floating point numbers
+    Double(Vec<f64>),
+}
+
+/// Variable of a NetCDF dataset
+///
+/// The values are stored flat with the last dimension running fastest. Record variables have the
+/// record dimension first and hold the values of every record one after the other.
+pub struct NcVariable {
+    name: String,
+    dimensions: Vec<usize>,
+    attributes: Vec<(String, NcValues)>,
+    values: NcValues,
+}
+
+/// In memory NetCDF dataset in the classic format
+///
+/// Datasets are read from the classic and 64-bit offset formats (versions 1 and 2) and written in
+/// the 64-bit offset format. The HDF5 based NetCDF-4 format is not supported. At most one
+/// dimension is the unlimited record dimension whose length is the number of records.
+#[derive(Default)]
+pub struct NetCdf {
+    dimensions: Vec<(String, usize)>,
+    record_dimension: Option<usize>,
+    attributes: Vec<(String, NcValues)>,
+    variables: Vec<NcVariable>,
+}
+
+impl NcValues {
+    /// Number of values
+    pub fn len(&self) -> usize {
+        match self {
+            NcValues::Byte(v) => v.len(),
+            NcValues::Char(v) => v.len(),
+            NcValues::Short(v) => v.len(),
+            NcValues::Int(v) => v.len(),
+            NcValues::Float(v) => v.len(),
+            NcValues::Double(v) => v.len(),
+        }
+    }
+
+    /// Whether there are no values
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Values converted to integers (None for characters and floating point values)
+    pub fn to_integers(&self) -> Option<Vec<i64>> {
+        match self {
+            NcValues::Byte(v) => Some(v.iter().map(|x| *x as i64).collect()),
+            NcValues::Short(v) => Some(v.iter().map(|x| *x as i64).collect()),
+            NcValues::Int(v) => Some(v.iter().map(|x| *x as i64).collect()),
+            _ => None,
+        }
+    }
+
+    /// Values converted to floating point numbers (None for characters)
+    pub fn to_floats(&self) -> Option<Vec<f64>> {
+        match self {
+            NcValues::Float(v) => Some(v.iter().map(|x| *x as f64).collect()),
+            NcValues::Double(v) => Some(v.clone()),
+            NcValues::Char(_) => None,
+            _ => self
+                .to_integers()
+                .map(|v| v.iter().map(|x| *x as f64).collect()),
+        }
+    }
+
+    /// Characters up to the first null byte as a string (None for numbers)
+    pub fn to_text(&self) -> Option<String> {
+        match self {
+            NcValues::Char(v) => Some(text(v)),
+            _ => None,
+        }
+    }
+
+    fn type_code(&self) -> u32 {
+        match self {
+            NcValues::Byte(_) => 1,
+            NcValues::Char(_) => 2,
+            NcValues::Short(_) => 3,
+            NcValues::Int(_) => 4,
+            NcValues::Float(_) => 5,
+            NcValues::Double(_) => 6,
+        }
+    }
+
+    // Big endian bytes of the values in the range
+    fn encode(&self, start: usize, end: usize, bytes: &mut Vec<u8>) {
+        match self {
+            NcValues::Byte(v) => bytes.extend(v[start..end].iter().map(|x| *x as u8)),
+            NcValues::Char(v) => bytes.extend_from_slice(&v[start..end]),
+            NcValues::Short(v) => v[start..end]
+                .iter()
+                .for_each(|x| bytes.extend_from_slice(&x.to_be_bytes())),
+            NcValues::Int(v) => v[start..end]
+                .iter()
+                .for_each(|x| bytes.extend_from_slice(&x.to_be_bytes())),
+            NcValues::Float(v) => v[start..end]
+                .iter()
+                .for_each(|x| bytes.extend_from_slice(&x.to_be_bytes())),
+            NcValues::Double(v) => v[start..end]
+                .iter()
+                .for_each(|x| bytes.extend_from_slice(&x.to_be_bytes())),
+        }
+    }
+
+    // Values of a type decoded from big endian bytes
+    fn decode(type_code: u32, bytes: &[u8]) -> Result<Self> {
+        let size = type_size(type_code).ok_or_else(|| invalid("Unknown NetCDF type"))?;
+        let chunks = bytes.chunks_exact(size);
+        Ok(match type_code {
+            1 => NcValues::Byte(bytes.iter().map(|b| *b as i8).collect()),
+            2 => NcValues::Char(bytes.to_vec()),
+            3 => NcValues::Short(chunks.map(|c| i16::from_be_bytes([c[0], c[1]])).collect()),
+            4 => NcValues::Int(
+                chunks
+                    .map(|c| i32::from_be_bytes(c.try_into().unwrap()))
+                    .collect(),
+            ),
+            5 => NcValues::Float(
+                chunks
+                    .map(|c| f32::from_be_bytes(c.try_into().unwrap()))
+                    .collect(),
+            ),
+            _ => NcValues::Double(
+                chunks
+                    .map(|c| f64::from_be_bytes(c.try_into().unwrap()))
+                    .collect(),
+            ),
+        })
+    }
+}
+
+impl NcVariable {
+    /// Name of the variable
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Indices of the dimensions of the variable in the dataset
+    pub fn dimensions(&self) -> &[usize] {
+        &self.dimensions
+    }
+
+    /// Attribute of the variable
+    pub fn attribute(&self, name: &str) -> Option<&NcValues> {
+        find(&self.attributes, name)
+    }
+
+    /// Attach an attribute to the variable
+    pub fn add_attribute(&mut self, name: &str, values: NcValues) -> &mut Self {
+        self.attributes.push((name.to_string(), values));
+        self
+    }
+
+    /// Values of the variable
+    pub fn values(&self) -> &NcValues {
+        &self.values
+    }
+}
+
+impl NetCdf {
+    /// Empty dataset
+    pub fn new() -> Self {
+        NetCdf::default()
+    }
+
+    /// Add a fixed size dimension and return its index
+    pub fn add_dimension(&mut self, name: &str, length: usize) -> usize {
+        assert!(
+            length > 0,
+            "Only the record dimension can have a zero length"
+        );
+        self.dimensions.push((name.to_string(), length));
+        self.dimensions.len() - 1
+    }
+
+    /// Add the record dimension with a number of records and return its index
+    pub fn add_record_dimension(&mut self, name: &str, n_records: usize) -> usize {
+        assert!(
+            self.record_dimension.is_none(),
+            "A NetCDF dataset has at most one record dimension"
+        );
+        self.dimensions.push((name.to_string(), n_records));
+        self.record_dimension = Some(self.dimensions.len() - 1);
+        self.dimensions.len() - 1
+    }
+
+    /// Add a global attribute
+    pub fn add_attribute(&mut self, name: &str, values: NcValues) {
+        self.attributes.push((name.to_string(), values));
+    }
+
+    /// Add a variable on dimensions given by their indices
+    ///
+    /// The record dimension can only come first.
+    pub fn add_variable(
+        &mut self,
+        name: &str,
+        dimensions: &[usize],
+        values: NcValues,
+    ) -> &mut NcVariable {
+        assert!(
+            dimensions
+                .iter()
+                .skip(1)
+                .all(|d| Some(*d) != self.record_dimension),
+            "Record dimension of variable {} should be its first",
+            name
+        );
+        let size: usize = dimensions.iter().map(|d| self.dimensions[*d].1).product();
+        assert!(
+            values.len() == size,
+            "Values of variable {} do not match its dimensions",
+            name
+        );
+        self.variables.push(NcVariable {
+            name: name.to_string(),
+            dimensions: dimensions.to_vec(),
+            attributes: Vec::new(),
+            values,
+        });
+        self.variables.last_mut().unwrap()
+    }
+
+    /// Length of a dimension given by its name
+    pub fn dimension(&self, name: &str) -> Option<usize> {
+        self.dimensions
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, length)| *length)
+    }
+
+    /// Global attribute
+    pub fn attribute(&self, name: &str) -> Option<&NcValues> {
+        find(&self.attributes, name)
+    }
+
+    /// Variable given by its name
+    pub fn variable(&self, name: &str) -> Option<&NcVariable> {
+        self.variables.iter().find(|v| v.name == name)
+    }
+
+    /// Variables of the dataset
+    pub fn variables(&self) -> &[NcVariable] {
+        &self.variables
+    }
+
+    /// Read a dataset in the classic or the 64-bit offset format
+    pub fn read<Reader: Read>(reader: &mut Reader) -> Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let mut cursor = Cursor {
+            bytes: &bytes,
+            position: 0,
+        };
+        let magic = cursor.take(4)?;
+        if &magic[..3] != b"CDF" {
+            return Err(invalid(if &magic[1..4] == b"HDF" {
+                "NetCDF-4 (HDF5) files are not supported, convert them to the 64-bit offset format \
+                 first (e.g. `nccopy -k nc6 input output`)"
+            } else {
+                "Not a NetCDF file"
+            }));
+        }
+        let offset_size = match magic[3] {
+            1 => 4,
+            2 => 8,
+            _ => return Err(invalid("Unsupported NetCDF format version")),
+        };
+        let n_records = cursor.read_u32()? as usize;
+        let mut dataset = NetCdf::new();
+        for _ in 0..cursor.read_list(NC_DIMENSION)? {
+            let name = cursor.read_name()?;
+            let length = cursor.read_u32()? as usize;
+            if length == 0 {
+                dataset.add_record_dimension(&name, n_records);
+            } else {
+                dataset.add_dimension(&name, length);
+            }
+        }
+        dataset.attributes = cursor.read_attributes()?;
+        // Headers of the variables with their type, begin and size of a record
+        let mut headers = Vec::new();
+        for _ in 0..cursor.read_list(NC_VARIABLE)? {
+            let name = cursor.read_name()?;
+            let n_dimensions = cursor.read_u32()? as usize;
+            let mut dimensions = Vec::with_capacity(n_dimensions);
+            for _ in 0..n_dimensions {
+                let dimension = cursor.read_u32()? as usize;
+                if dimension >= dataset.dimensions.len() {
+                    return Err(invalid("Variable on an unknown dimension"));
+                }
+                dimensions.push(dimension);
+            }
+            let attributes = cursor.read_attributes()?;
+            let type_code = cursor.read_u32()?;
+            type_size(type_code).ok_or_else(|| invalid("Unknown NetCDF type"))?;
+            let vsize = cursor.read_u32()? as usize;
+            let begin = if offset_size == 4 {
+                cursor.read_u32()? as usize
+            } else {
+                cursor.read_u64()? as usize
+            };
+            headers.push((name, dimensions, attributes, type_code, vsize, begin));
+        }
+        let is_record = |dimensions: &[usize]| {
+            !dimensions.is_empty() && Some(dimensions[0]) == dataset.record_dimension
+        };
+        let n_record_variables = headers.iter().filter(|h| is_record(&h.1)).count();
+        // Records hold the padded slices of every record variable (unpadded if there is only one)
+        let record_size: usize = headers
+            .iter()
+            .filter(|h| is_record(&h.1))
+            .map(|h| {
+                if n_record_variables == 1 {
+                    dataset.slice_size(&h.1, h.3)
+                } else {
+                    h.4
+                }
+            })
+            .sum();
+        let mut variables = Vec::with_capacity(headers.len());
+        for (name, dimensions, attributes, type_code, _, begin) in headers {
+            let slice = dataset.slice_size(&dimensions, type_code);
+            let data = if is_record(&dimensions) {
+                let mut data = Vec::with_capacity(slice * n_records);
+                for record in 0..n_records {
+                    let start = begin + record * record_size;
+                    data.extend_from_slice(cursor.at(start, slice)?);
+                }
+                data
+            } else {
+                cursor.at(begin, slice)?.to_vec()
+            };
+            variables.push(NcVariable {
+                name,
+                dimensions,
+                attributes,
+                values: NcValues::decode(type_code, &data)?,
+            });
+        }
+        dataset.variables = variables;
+        Ok(dataset)
+    }
+
+    /// Write the dataset in the 64-bit offset format
+    pub fn write<Writer: Write>(&self, writer: &mut Writer) -> Result<()> {
+        let is_record = |v: &NcVariable| {
+            !v.dimensions.is_empty() && Some(v.dimensions[0]) == self.record_dimension
+        };
+        let n_records = self.record_dimension.map_or(0, |d| self.dimensions[d].1);
+        let n_record_variables = self.variables.iter().filter(|v| is_record(v)).count();
+        let slice_sizes: Vec<usize> = self
+            .variables
+            .iter()
+            .map(|v| self.slice_size(&v.dimensions, v.values.type_code()))
+            .collect();
+        let vsizes: Vec<usize> = slice_sizes.iter().map(|s| padded(*s)).collect();
+        // The header size does not depend on the offsets
+        let header_size = self.header(&vsizes, &vec![0; self.variables.len()]).len();
+        let mut begins = vec![0; self.variables.len()];
+        let mut offset = header_size;
+        for (v, variable) in self.variables.iter().enumerate() {
+            if !is_record(variable) {
+                begins[v] = offset;
+                offset += vsizes[v];
+            }
+        }
+        let mut record_size = 0;
+        for (v, variable) in self.variables.iter().enumerate() {
+            if is_record(variable) {
+                begins[v] = offset + record_size;
+                record_size += if n_record_variables == 1 {
+                    slice_sizes[v]
+                } else {
+                    vsizes[v]
+                };
+            }
+        }
+        let mut bytes = self.header(&vsizes, &begins);
+        for variable in self.variables.iter().filter(|v| !is_record(v)) {
+            variable.values.encode(0, variable.values.len(), &mut bytes);
+            pad(&mut bytes);
+        }
+        for record in 0..n_records {
+            for variable in self.variables.iter().filter(|v| is_record(v)) {
+                let n = variable.values.len() / n_records;
+                variable
+                    .values
+                    .encode(record * n, (record + 1) * n, &mut bytes);
+                if n_record_variables > 1 {
+                    pad(&mut bytes);
+                }
+            }
+        }
+        writer.write_all(&bytes)
+    }
+
+    // Header of the dataset with the given variable sizes and offsets
+    fn header(&self, vsizes: &[usize], begins: &[usize]) -> Vec<u8> {
+        let mut bytes = b"CDF\x02".to_vec();
+        let n_records = self.record_dimension.map_or(0, |d| self.dimensions[d].1);
+        push_u32(&mut bytes, n_records);
+        push_list_header(&mut bytes, NC_DIMENSION, self.dimensions.len());
+        for (d, (name, length)) in self.dimensions.iter().enumerate() {
+            push_name(&mut bytes, name);
+            let length = if Some(d) == self.record_dimension {
+                0
+            } else {
+                *length
+            };
+            push_u32(&mut bytes, length);
+        }
+        push_attributes(&mut bytes, &self.attributes);
+        push_list_header(&mut bytes, NC_VARIABLE, self.variables.len());
+        for (v, variable) in self.variables.iter().enumerate() {
+            push_name(&mut bytes, &variable.name);
+            push_u32(&mut bytes, variable.dimensions.len());
+            for d in variable.dimensions.iter() {
+                push_u32(&mut bytes, *d);
+            }
+            push_attributes(&mut bytes, &variable.attributes);
+            bytes.extend_from_slice(&variable.values.type_code().to_be_bytes());
+            push_u32(&mut bytes, vsizes[v]);
+            bytes.extend_from_slice(&(begins[v] as u64).to_be_bytes());
+        }
+        bytes
+    }
+
+    // Size in bytes of the values of a variable (of a single record for record variables)
+    fn slice_size(&self, dimensions: &[usize], type_code: u32) -> usize {
+        let size: usize = dimensions
+            .iter()
+            .filter(|d| Some(**d) != self.record_dimension)
+            .map(|d| self.dimensions[*d].1)
+            .product();
+        size * type_size(type_code).unwrap()
+    }
+}
+
+// Reading position in the bytes of a file
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let slice = self.at(self.position, n)?;
+        self.position += n;
+        Ok(slice)
+    }
+
+    fn at(&self, start: usize, n: usize) -> Result<&'a [u8]> {
+        self.bytes
+            .get(start..start + n)
+            .ok_or_else(|| invalid("Unexpected end of the NetCDF file"))
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn read_name(&mut self) -> Result<String> {
+        let n = self.read_u32()? as usize;
+        let name = String::from_utf8_lossy(self.take(n)?).into_owned();
+        self.take(padded(n) - n)?;
+        Ok(name)
+    }
+
+    // Number of elements of a list which is either absent or has the given tag
+    fn read_list(&mut self, tag: u32) -> Result<usize> {
+        let found = self.read_u32()?;
+        let n = self.read_u32()? as usize;
+        if found == tag || (found == ABSENT && n == 0) {
+            Ok(n)
+        } else {
+            Err(invalid("Malformed NetCDF header"))
+        }
+    }
+
+    fn read_attributes(&mut self) -> Result<Vec<(String, NcValues)>> {
+        let n = self.read_list(NC_ATTRIBUTE)?;
+        let mut attributes = Vec::with_capacity(n);
+        for _ in 0..n {
+            let name = self.read_name()?;
+            let type_code = self.read_u32()?;
+            let size = type_size(type_code).ok_or_else(|| invalid("Unknown NetCDF type"))?;
+            let n_values = self.read_u32()? as usize;
+            let bytes = self.take(n_values * size)?;
+            self.take(padded(n_values * size) - n_values * size)?;
+            attributes.push((name, NcValues::decode(type_code, bytes)?));
+        }
+        Ok(attributes)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Characters of a fixed length string up to the first null byte
+pub fn text(characters: &[u8]) -> String {
+    let end = characters
+        .iter()
+        .position(|c| *c == 0)
+        .unwrap_or(characters.len());
+    String::from_utf8_lossy(&characters[..end])
+        .trim_end()
+        .to_string()
+}
+
+/// Strings written in a (number of strings, length) character array padded with null bytes
+pub fn text_array(strings: &[String], length: usize) -> NcValues {
+    let mut characters = vec![0; strings.len() * length];
+    for (s, string) in strings.iter().enumerate() {
+        let n = string.len().min(length - 1);
+        characters[s * length..s * length + n].copy_from_slice(&string.as_bytes()[..n]);
+    }
+    NcValues::Char(characters)
+}
+
+fn find<'a>(attributes: &'a [(String, NcValues)], name: &str) -> Option<&'a NcValues> {
+    attributes.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+}
+
+fn type_size(type_code: u32) -> Option<usize> {
+    match type_code {
+        1 | 2 => Some(1),
+        3 => Some(2),
+        4 | 5 => Some(4),
+        6 => Some(8),
+        _ => None,
+    }
+}
+
+fn padded(n: usize) -> usize {
+    n.div_ceil(4) * 4
+}
+
+fn pad(bytes: &mut Vec<u8>) {
+    bytes.resize(padded(bytes.len()), 0);
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn push_u32(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend_from_slice(&(value as u32).to_be_bytes());
+}
+
+fn push_name(bytes: &mut Vec<u8>, name: &str) {
+    push_u32(bytes, name.len());
+    bytes.extend_from_slice(name.as_bytes());
+    pad(bytes);
+}
+
+fn push_list_header(bytes: &mut Vec<u8>, tag: u32, n: usize) {
+    push_u32(
+        bytes,
+        if n == 0 {
+            ABSENT as usize
+        } else {
+            tag as usize
+        },
+    );
+    push_u32(bytes, n);
+}
+
+fn push_attributes(bytes: &mut Vec<u8>, attributes: &[(String, NcValues)]) {
+    push_list_header(bytes, NC_ATTRIBUTE, attributes.len());
+    for (name, values) in attributes {
+        push_name(bytes, name);
+        bytes.extend_from_slice(&values.type_code().to_be_bytes());
+        push_u32(bytes, values.len());
+        values.encode(0, values.len(), bytes);
+        pad(bytes);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_netcdf_round_trip() {
+        let mut dataset = NetCdf::new();
+        let time = dataset.add_record_dimension("time", 2);
+        let nodes = dataset.add_dimension("nodes", 3);
+        let length = dataset.add_dimension("length", 5);
+        dataset.add_attribute("title", NcValues::Char(b"test".to_vec()));
+        dataset
+            .add_variable("ids", &[nodes], NcValues::Short(vec![1, 2, 3]))
+            .add_attribute("name", NcValues::Char(b"ID".to_vec()));
+        dataset.add_variable("names", &[length], NcValues::Char(b"abc\0\0".to_vec()));
+        dataset.add_variable("times", &[time], NcValues::Double(vec![0.0, 0.5]));
+        dataset.add_variable(
+            "values",
+            &[time, nodes],
+            NcValues::Float(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]),
+        );
+        let mut bytes = Vec::new();
+        dataset.write(&mut bytes).unwrap();
+        assert_eq!(&bytes[..4], b"CDF\x02", "Wrong magic number");
+        assert_eq!(bytes.len() % 4, 0, "Data should be padded");
+        let read = NetCdf::read(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read.dimension("time"), Some(2), "Wrong number of records");
+        assert_eq!(read.dimension("nodes"), Some(3), "Wrong dimension");
+        assert_eq!(
+            read.attribute("title").unwrap().to_text(),
+            Some("test".to_string()),
+            "Wrong global attribute"
+        );
+        let ids = read.variable("ids").unwrap();
+        assert_eq!(
+            ids.values(),
+            &NcValues::Short(vec![1, 2, 3]),
+            "Wrong values"
+        );
+        assert_eq!(
+            ids.attribute("name").unwrap().to_text(),
+            Some("ID".to_string()),
+            "Wrong variable attribute"
+        );
+        assert_eq!(
+            read.variable("names").unwrap().values().to_text(),
+            Some("abc".to_string()),
+            "Wrong characters"
+        );
+        assert_eq!(
+            read.variable("values").unwrap().values(),
+            &NcValues::Float(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]),
+            "Wrong record values"
+        );
+        assert_eq!(
+            read.variable("times").unwrap().values().to_floats(),
+            Some(vec![0.0, 0.5]),
+            "Wrong record values"
+        );
+        assert!(
+            NetCdf::read(&mut b"\x89HDF\r\n".as_slice()).is_err(),
+            "NetCDF-4 files should be rejected"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_netcdf_records() {
+        // Record variables of every type over three records, padded in the records when there are
+        // several of them and packed when there is only one
+        let all_types = [
+            NcValues::Byte(vec![-1, 2, -3, 4, -5, 6]),
+            NcValues::Char(b"abcdef".to_vec()),
+            NcValues::Short(vec![-7, 8, -9, 10, -11, 12]),
+            NcValues::Int(vec![1 << 20, -2, 3, 4, 5, 6]),
+            NcValues::Float(vec![0.5, -1.5, 2.5, 3.5, 4.5, 5.5]),
+            NcValues::Double(vec![1e-300, -2.0, 3.0, 4.0, 5.0, 1e300]),
+        ];
+        for n_variables in [all_types.len(), 1] {
+            let mut dataset = NetCdf::new();
+            let steps = dataset.add_record_dimension("time_step", 3);
+            let pair = dataset.add_dimension("pair", 2);
+            let odd = dataset.add_dimension("odd", 3);
+            dataset.add_variable("fixed", &[odd], NcValues::Byte(vec![1, 2, 3]));
+            for (v, values) in all_types.iter().take(n_variables).enumerate() {
+                dataset
+                    .add_variable(&format!("record{}", v), &[steps, pair], values.clone())
+                    .add_attribute("units", NcValues::Char(b"s".to_vec()))
+                    .add_attribute("range", NcValues::Double(vec![0.0, 1.0]));
+            }
+            let mut bytes = Vec::new();
+            dataset.write(&mut bytes).unwrap();
+            let read = NetCdf::read(&mut bytes.as_slice()).unwrap();
+            assert_eq!(
+                read.dimension("time_step"),
+                Some(3),
+                "Wrong number of records"
+            );
+            assert_eq!(
+                read.variables().len(),
+                n_variables + 1,
+                "Wrong number of variables"
+            );
+            assert_eq!(
+                read.variable("fixed").unwrap().values(),
+                &NcValues::Byte(vec![1, 2, 3]),
+                "Wrong fixed size values"
+            );
+            for (v, values) in all_types.iter().take(n_variables).enumerate() {
+                let variable = read.variable(&format!("record{}", v)).unwrap();
+                assert_eq!(variable.dimensions(), &[steps, pair], "Wrong dimensions");
+                assert_eq!(variable.values(), values, "Wrong record values");
+                assert_eq!(
+                    variable.attribute("range"),
+                    Some(&NcValues::Double(vec![0.0, 1.0])),
+                    "Wrong variable attribute"
+                );
+            }
+        }
+        let integers = NcValues::Short(vec![-1, 2]);
+        assert_eq!(integers.to_integers(), Some(vec![-1, 2]), "Wrong integers");
+        assert_eq!(integers.to_floats(), Some(vec![-1.0, 2.0]), "Wrong floats");
+        assert_eq!(
+            NcValues::Float(vec![1.0]).to_integers(),
+            None,
+            "Floats are not integers"
+        );
+        assert_eq!(
+            NcValues::Char(b"x".to_vec()).to_floats(),
+            None,
+            "Text is not numbers"
+        );
+        assert_eq!(
+            NcValues::Int(vec![1]).to_text(),
+            None,
+            "Numbers are not text"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_netcdf_malformed() {
+        // Variable v of two integers on the dimension n, its header ending at byte 84
+        let mut dataset = NetCdf::new();
+        let n = dataset.add_dimension("n", 2);
+        dataset.add_variable("v", &[n], NcValues::Int(vec![1, 2]));
+        let mut bytes = Vec::new();
+        dataset.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 92, "Unexpected layout of the file");
+        let patched = |offset: usize, value: u32| {
+            let mut patched = bytes.clone();
+            patched[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+            patched
+        };
+        let cases = [
+            ("an empty file", Vec::new(), "end"),
+            ("a text file", b"CSV,1,2\n".to_vec(), "Not a NetCDF file"),
+            (
+                "a version 5 file",
+                [b"CDF\x05", &bytes[4..]].concat(),
+                "version",
+            ),
+            ("a truncated header", bytes[..30].to_vec(), "end"),
+            ("truncated values", bytes[..88].to_vec(), "end"),
+            (
+                "an attribute list tag for the dimensions",
+                patched(8, NC_ATTRIBUTE),
+                "Malformed",
+            ),
+            (
+                "a variable on the dimension 5",
+                patched(56, 5),
+                "unknown dimension",
+            ),
+            ("the type 9", patched(68, 9), "Unknown NetCDF type"),
+        ];
+        for (case, bytes, message) in cases {
+            let error = NetCdf::read(&mut bytes.as_slice())
+                .err()
+                .unwrap_or_else(|| panic!("Read {}", case));
+            assert_eq!(
+                error.kind(),
+                ErrorKind::InvalidData,
+                "Wrong error kind for {}",
+                case
+            );
+            assert!(
+                error.to_string().contains(message),
+                "Wrong error for {}: {}",
+                case,
+                error
+            );
+        }
+    }
+}
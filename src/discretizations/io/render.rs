@@ -0,0 +1,195 @@
+use crate::core::arrays::data_traits::DataContainer;
+use crate::discretizations::function::Function;
+use crate::discretizations::mesh::Mesh;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// In-memory vertex and index buffers of a triangle mesh and of functions on it for interactive
+/// rendering, for instance by WebGL in a browser demo built for wasm32-unknown-unknown
+///
+/// Positions hold the x, y coordinates of the vertices, triangles the three vertices of every cell
+/// and edges the two vertices of every edge of the mesh (for wireframes), as the flat f32 and u32
+/// arrays taken by vertex buffers and drawElements. Fields hold the vertex values of functions (the
+/// euclidean norm for several components, the vertex values only for high order functions as in
+/// the VTK output) with their range to map them to colors. Fields can be replaced between frames
+/// without rebuilding the mesh buffers.
+pub struct RenderBuffers {
+    positions: Vec<f32>,
+    triangles: Vec<u32>,
+    edges: Vec<u32>,
+    fields: Vec<(String, Vec<f32>, [f32; 2])>,
+}
+
+impl RenderBuffers {
+    /// Buffers of a 2-D triangle mesh without fields
+    pub fn new(mesh: &Mesh) -> Self {
+        assert!(
+            mesh.geometric_dim() == 2 && mesh.vertices_per_cell() == 3,
+            "Render buffers need a 2-D triangle mesh"
+        );
+        assert!(
+            mesh.n_vertices() <= u32::MAX as usize,
+            "Too many vertices for 32 bit indices"
+        );
+        let positions = mesh.vertices().iter().map(|x| *x as f32).collect();
+        let triangles = mesh.cells().iter().map(|v| *v as u32).collect();
+        let mut pairs: Vec<[u32; 2]> = Vec::with_capacity(3 * mesh.n_cells());
+        for cell in 0..mesh.n_cells() {
+            let vertices = mesh.cell(cell);
+            for k in 0..3 {
+                let (a, b) = (vertices[k] as u32, vertices[(k + 1) % 3] as u32);
+                pairs.push([a.min(b), a.max(b)]);
+            }
+        }
+        pairs.sort_unstable();
+        pairs.dedup();
+        RenderBuffers {
+            positions,
+            triangles,
+            edges: pairs.into_iter().flatten().collect(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Add a named function on the mesh
+    pub fn with_function(mut self, name: &str, function: &Function) -> Self {
+        self.set_function(name, function);
+        self
+    }
+
+    /// Add a named function on the mesh or replace the values of the field with this name
+    pub fn set_function(&mut self, name: &str, function: &Function) {
+        let vertex_values = function.vertex_values();
+        let [n_vertices, n_components] = *vertex_values.dimensions();
+        assert!(
+            2 * n_vertices == self.positions.len(),
+            "Function {} does not live on the rendered mesh",
+            name
+        );
+        let values: Vec<f32> = vertex_values
+            .chunks(n_components)
+            .map(|v| match v {
+                [value] => *value as f32,
+                _ => v.iter().map(|c| c * c).sum::<f64>().sqrt() as f32,
+            })
+            .collect();
+        let range = values
+            .iter()
+            .fold([f32::INFINITY, f32::NEG_INFINITY], |[min, max], v| {
+                [min.min(*v), max.max(*v)]
+            });
+        match self.fields.iter_mut().find(|(n, _, _)| n == name) {
+            Some(field) => *field = (name.to_string(), values, range),
+            None => self.fields.push((name.to_string(), values, range)),
+        }
+    }
+
+    /// Number of vertices
+    pub fn n_vertices(&self) -> usize {
+        self.positions.len() / 2
+    }
+
+    /// Coordinates x, y of the vertices
+    pub fn positions(&self) -> &[f32] {
+        &self.positions
+    }
+
+    /// Vertices of the triangles
+    pub fn triangles(&self) -> &[u32] {
+        &self.triangles
+    }
+
+    /// Vertices of the edges, every edge appearing once
+    pub fn edges(&self) -> &[u32] {
+        &self.edges
+    }
+
+    /// Names of the fields in the order they were added
+    pub fn field_names(&self) -> Vec<&str> {
+        self.fields
+            .iter()
+            .map(|(name, _, _)| name.as_str())
+            .collect()
+    }
+
+    /// Vertex values of a field
+    pub fn field(&self, name: &str) -> Option<&[f32]> {
+        self.fields
+            .iter()
+            .find(|(n, _, _)| n == name)
+            .map(|(_, values, _)| values.as_slice())
+    }
+
+    /// Minimum and maximum values of a field
+    pub fn range(&self, name: &str) -> Option<[f32; 2]> {
+        self.fields
+            .iter()
+            .find(|(n, _, _)| n == name)
+            .map(|(_, _, range)| *range)
+    }
+
+    /// Field values scaled to [0, 1] over its range (0 for constant fields), as texture coordinates
+    /// of a color map
+    pub fn normalized(&self, name: &str) -> Option<Vec<f32>> {
+        let [min, max] = self.range(name)?;
+        let scale = if max > min { 1.0 / (max - min) } else { 0.0 };
+        self.field(name)
+            .map(|values| values.iter().map(|v| (v - min) * scale).collect())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::discretizations::test_meshes::unit_square;
+    use crate::spaces::lagrange::LagrangeElement;
+
+    #[test]
+    fn test_render_buffers() {
+        let mesh = unit_square();
+        let scalar_space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let vector_space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let mut u = Function::new(&scalar_space);
+        u.interpolate(|x| x[0] + x[1]);
+        let mut w = Function::new(&vector_space);
+        w.interpolate_vector(|x, value| value.copy_from_slice(&[3.0 * x[0], 4.0 * x[0]]));
+        let mut buffers = RenderBuffers::new(&mesh)
+            .with_function("u", &u)
+            .with_function("w", &w);
+        assert_eq!(buffers.n_vertices(), 4, "Wrong number of vertices");
+        assert_eq!(buffers.positions()[6..], [1.0, 1.0], "Wrong position");
+        assert_eq!(buffers.triangles(), &[0, 1, 2, 3, 2, 1], "Wrong triangles");
+        assert_eq!(
+            buffers.edges(),
+            &[0, 1, 0, 2, 1, 2, 1, 3, 2, 3],
+            "Edges should be listed once"
+        );
+        assert_eq!(buffers.field_names(), vec!["u", "w"], "Wrong fields");
+        assert_eq!(
+            buffers.field("w").unwrap(),
+            &[0.0, 5.0, 0.0, 5.0],
+            "Wrong norms"
+        );
+        assert_eq!(buffers.range("u"), Some([0.0, 2.0]), "Wrong range");
+        assert_eq!(
+            buffers.normalized("u").unwrap(),
+            vec![0.0, 0.5, 0.5, 1.0],
+            "Wrong normalized values"
+        );
+        u.values_mut().iter_mut().for_each(|v| *v = -*v);
+        buffers.set_function("u", &u);
+        assert_eq!(buffers.field_names().len(), 2, "Fields should be replaced");
+        assert_eq!(
+            buffers.range("u"),
+            Some([-2.0, 0.0]),
+            "Field was not updated"
+        );
+    }
+}
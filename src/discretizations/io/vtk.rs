@@ -0,0 +1,453 @@
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::data_traits::DataContainer;
+#[cfg(hosted)]
+use crate::core::logging::Span;
+use crate::discretizations::cell_mapping::CellMapping;
+use crate::discretizations::function::Function;
+use crate::discretizations::mesh::Mesh;
+use std::collections::HashMap;
+#[cfg(hosted)]
+use std::fs::File;
+#[cfg(hosted)]
+use std::io::BufWriter;
+use std::io::{Result, Write};
+#[cfg(hosted)]
+use std::path::{Path, PathBuf};
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Time series of legacy VTK files indexed by a ParaView .series file
+///
+/// Every written step goes to the file prefix_<step>.vtk and the file prefix.vtk.series, listing
+/// the steps written so far with their times, is rewritten so that ParaView opens the whole series
+/// as one time dependent dataset even if the simulation stops early.
+#[cfg(hosted)]
+pub struct VtkSeries {
+    prefix: PathBuf,
+    times: Vec<f64>,
+}
+
+#[cfg(hosted)]
+impl VtkSeries {
+    /// Series of files starting with the prefix path, whose directory has to exist
+    pub fn new<P: AsRef<Path>>(prefix: P) -> Self {
+        VtkSeries {
+            prefix: prefix.as_ref().to_path_buf(),
+            times: Vec::new(),
+        }
+    }
+
+    /// Times of the steps written so far
+    pub fn times(&self) -> &[f64] {
+        &self.times
+    }
+
+    /// Write a mesh and named functions on it as the step at the given time
+    pub fn write(&mut self, time: f64, mesh: &Mesh, functions: &[(&str, &Function)]) -> Result<()> {
+        save_vtk(self.step_path(self.times.len()), mesh, functions)?;
+        self.times.push(time);
+        let mut name = self.prefix.file_name().unwrap_or_default().to_os_string();
+        name.push(".vtk.series");
+        let mut writer = BufWriter::new(File::create(self.prefix.with_file_name(name))?);
+        writeln!(writer, "{{")?;
+        writeln!(writer, "  \"file-series-version\" : \"1.0\",")?;
+        writeln!(writer, "  \"files\" : [")?;
+        for (step, time) in self.times.iter().enumerate() {
+            let path = self.step_path(step);
+            let file = path.file_name().unwrap().to_string_lossy();
+            let separator = if step + 1 < self.times.len() { "," } else { "" };
+            writeln!(
+                writer,
+                "    {{ \"name\" : \"{}\", \"time\" : {:e} }}{}",
+                file, time, separator
+            )?;
+        }
+        writeln!(writer, "  ]")?;
+        writeln!(writer, "}}")?;
+        writer.flush()
+    }
+
+    // File of a step
+    pub(crate) fn step_path(&self, step: usize) -> PathBuf {
+        let mut name = self.prefix.file_name().unwrap_or_default().to_os_string();
+        name.push(format!("_{:05}.vtk", step));
+        self.prefix.with_file_name(name)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Write a mesh and named functions on it in the ASCII legacy VTK format
+///
+/// Functions are written as point data at the vertices of the mesh: scalars for one component and
+/// vectors (padded to three components) for two or three. Only the vertex values of high order
+/// functions are kept, use write_vtk_subdivided to see them inside the cells.
+pub fn write_vtk<Writer: Write>(
+    writer: &mut Writer,
+    mesh: &Mesh,
+    functions: &[(&str, &Function)],
+) -> Result<()> {
+    let mut points = Vec::with_capacity(mesh.n_vertices() * mesh.geometric_dim());
+    for v in 0..mesh.n_vertices() {
+        points.extend_from_slice(mesh.vertex(v));
+    }
+    write_grid(
+        writer,
+        mesh.geometric_dim(),
+        &points,
+        mesh.vertices_per_cell(),
+        mesh.cells(),
+    )?;
+    if functions.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "POINT_DATA {}", mesh.n_vertices())?;
+    for (name, function) in functions {
+        check_mesh(name, function, mesh);
+        let values = function.vertex_values();
+        write_point_data(writer, name, function.space().n_components(), &values)?;
+    }
+    Ok(())
+}
+
+/// Write a mesh and named functions on it in the ASCII legacy VTK format with every cell split in
+/// subdivisions^dim sub-simplices
+///
+/// The sub-simplices come from the lattice of spacing 1 / subdivisions in every cell (points of
+/// neighbouring cells are duplicated) so that functions of order up to subdivisions are shown
+/// through their exact values at the nodes of the lattice.
+pub fn write_vtk_subdivided<Writer: Write>(
+    writer: &mut Writer,
+    mesh: &Mesh,
+    functions: &[(&str, &Function)],
+    subdivisions: usize,
+) -> Result<()> {
+    assert!(subdivisions > 0, "Cells need at least one subdivision");
+    let dim = mesh.topological_dim();
+    let geometric_dim = mesh.geometric_dim();
+    let (lattice, simplices) = subdivide(dim, subdivisions);
+    let n_lattice = lattice.len();
+    let n_points = mesh.n_cells() * n_lattice;
+    let references: Vec<Vec<f64>> = lattice
+        .iter()
+        .map(|node| {
+            node.iter()
+                .map(|i| *i as f64 / subdivisions as f64)
+                .collect()
+        })
+        .collect();
+    let mut points = vec![0.0; n_points * geometric_dim];
+    let mut sub_cells = Vec::with_capacity(mesh.n_cells() * simplices.len() * (dim + 1));
+    for cell in 0..mesh.n_cells() {
+        let mapping = CellMapping::new(mesh, cell);
+        for (node, reference) in references.iter().enumerate() {
+            let offset = (cell * n_lattice + node) * geometric_dim;
+            mapping.map_point(reference, &mut points[offset..offset + geometric_dim]);
+        }
+        for simplex in simplices.iter() {
+            sub_cells.extend(simplex.iter().map(|node| cell * n_lattice + node));
+        }
+    }
+    let n_sub_cells = sub_cells.len() / (dim + 1);
+    write_grid(
+        writer,
+        geometric_dim,
+        &points,
+        dim + 1,
+        &DataHold::new(sub_cells, [n_sub_cells, dim + 1]),
+    )?;
+    if functions.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "POINT_DATA {}", n_points)?;
+    for (name, function) in functions {
+        check_mesh(name, function, mesh);
+        let n_components = function.space().n_components();
+        let mut values = vec![0.0; n_points * n_components];
+        for cell in 0..mesh.n_cells() {
+            for (node, reference) in references.iter().enumerate() {
+                let offset = (cell * n_lattice + node) * n_components;
+                function.eval_in_cell(cell, reference, &mut values[offset..offset + n_components]);
+            }
+        }
+        write_point_data(writer, name, n_components, &values)?;
+    }
+    Ok(())
+}
+
+/// Write a mesh and named functions on it to a legacy VTK file
+#[cfg(hosted)]
+pub fn save_vtk<P: AsRef<Path>>(
+    path: P,
+    mesh: &Mesh,
+    functions: &[(&str, &Function)],
+) -> Result<()> {
+    let _span = Span::enter("VTK output");
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_vtk(&mut writer, mesh, functions)?;
+    writer.flush()
+}
+
+/// Write a mesh with subdivided cells and named functions on it to a legacy VTK file
+#[cfg(hosted)]
+pub fn save_vtk_subdivided<P: AsRef<Path>>(
+    path: P,
+    mesh: &Mesh,
+    functions: &[(&str, &Function)],
+    subdivisions: usize,
+) -> Result<()> {
+    let _span = Span::enter("VTK output");
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_vtk_subdivided(&mut writer, mesh, functions, subdivisions)?;
+    writer.flush()
+}
+
+// Write the header, the points and the simplicial cells of an unstructured grid
+fn write_grid<Writer: Write>(
+    writer: &mut Writer,
+    dim: usize,
+    points: &[f64],
+    vertices_per_cell: usize,
+    cells: &DataHold<usize, [usize; 2]>,
+) -> Result<()> {
+    let cell_type = match vertices_per_cell {
+        1 => 1,
+        2 => 3,
+        3 => 5,
+        4 => 10,
+        _ => panic!(
+            "Cells with {} vertices are not simplices",
+            vertices_per_cell
+        ),
+    };
+    let n_cells = cells.dimensions()[0];
+    writeln!(writer, "# vtk DataFile Version 3.0")?;
+    writeln!(writer, "Fe2O3 output")?;
+    writeln!(writer, "ASCII")?;
+    writeln!(writer, "DATASET UNSTRUCTURED_GRID")?;
+    writeln!(writer, "POINTS {} double", points.len() / dim)?;
+    for point in points.chunks(dim) {
+        write_padded(writer, point)?;
+    }
+    writeln!(
+        writer,
+        "CELLS {} {}",
+        n_cells,
+        n_cells * (vertices_per_cell + 1)
+    )?;
+    for cell in cells.chunks(vertices_per_cell) {
+        write!(writer, "{}", vertices_per_cell)?;
+        for v in cell {
+            write!(writer, " {}", v)?;
+        }
+        writeln!(writer)?;
+    }
+    writeln!(writer, "CELL_TYPES {}", n_cells)?;
+    for _ in 0..n_cells {
+        writeln!(writer, "{}", cell_type)?;
+    }
+    Ok(())
+}
+
+// Write the values of a function at the points as scalars or padded vectors
+fn write_point_data<Writer: Write>(
+    writer: &mut Writer,
+    name: &str,
+    n_components: usize,
+    values: &[f64],
+) -> Result<()> {
+    match n_components {
+        1 => {
+            writeln!(writer, "SCALARS {} double 1", name)?;
+            writeln!(writer, "LOOKUP_TABLE default")?;
+            for v in values.iter() {
+                writeln!(writer, "{}", v)?;
+            }
+        }
+        2 | 3 => {
+            writeln!(writer, "VECTORS {} double", name)?;
+            for v in values.chunks(n_components) {
+                write_padded(writer, v)?;
+            }
+        }
+        _ => panic!("Functions with more than 3 components cannot be written to VTK"),
+    }
+    Ok(())
+}
+
+fn check_mesh(name: &str, function: &Function, mesh: &Mesh) {
+    assert!(
+        std::ptr::eq(function.space().mesh(), mesh),
+        "Function {} does not live on the written mesh",
+        name
+    );
+}
+
+// Lattice points of spacing 1 / n of the reference simplex and the n^dim sub-simplices they form
+//
+// The simplex is mapped to n >= y_1 >= ... >= y_dim >= 0 with y_k = x_k + ... + x_dim where it is
+// the union of the Kuhn simplices of the unit cubes of the grid: from a corner of a cube, add the
+// unit vectors along a permutation of the directions.
+fn subdivide(dim: usize, n: usize) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+    let to_lattice = |y: &[usize]| -> Vec<usize> {
+        (0..dim)
+            .map(|k| y[k] - if k + 1 < dim { y[k + 1] } else { 0 })
+            .collect()
+    };
+    let inside = |y: &[usize]| y.windows(2).all(|w| w[0] >= w[1]) && y.iter().all(|v| *v <= n);
+    let mut lattice = Vec::new();
+    let mut index = HashMap::new();
+    let mut simplices = Vec::new();
+    let permutations = permutations(dim);
+    for corner in 0..n.pow(dim as u32) {
+        let y: Vec<usize> = (0..dim).map(|k| (corner / n.pow(k as u32)) % n).collect();
+        for permutation in permutations.iter() {
+            let mut vertex = y.clone();
+            let mut vertices = vec![vertex.clone()];
+            for k in permutation.iter() {
+                vertex[*k] += 1;
+                vertices.push(vertex.clone());
+            }
+            if !vertices.iter().all(|v| inside(v)) {
+                continue;
+            }
+            let simplex = vertices
+                .iter()
+                .map(|v| {
+                    *index.entry(v.clone()).or_insert_with(|| {
+                        lattice.push(to_lattice(v));
+                        lattice.len() - 1
+                    })
+                })
+                .collect();
+            simplices.push(simplex);
+        }
+    }
+    (lattice, simplices)
+}
+
+// All the orderings of 0..n
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![vec![]];
+    }
+    let mut result = Vec::new();
+    for permutation in permutations(n - 1) {
+        for position in 0..n {
+            let mut extended = permutation.clone();
+            extended.insert(position, n - 1);
+            result.push(extended);
+        }
+    }
+    result
+}
+
+// Write a line of three coordinates padding the missing ones with zeros
+fn write_padded<Writer: Write>(writer: &mut Writer, values: &[f64]) -> Result<()> {
+    let mut padded = [0.0; 3];
+    padded[..values.len()].copy_from_slice(values);
+    writeln!(writer, "{} {} {}", padded[0], padded[1], padded[2])
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::discretizations::test_meshes::unit_square;
+    use crate::spaces::lagrange::LagrangeElement;
+
+    #[test]
+    fn test_write_vtk() {
+        let mesh = unit_square();
+        let scalar_space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        let vector_space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let mut u = Function::new(&scalar_space);
+        u.interpolate(|x| x[0] + x[1]);
+        let mut w = Function::new(&vector_space);
+        w.interpolate_vector(|x, value| value.copy_from_slice(x));
+        let mut buffer = Vec::new();
+        write_vtk(&mut buffer, &mesh, &[("u", &u), ("w", &w)]).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[4], "POINTS 4 double", "Wrong points header");
+        assert_eq!(lines[8], "1 1 0", "Wrong padded point");
+        assert_eq!(lines[9], "CELLS 2 8", "Wrong cells header");
+        assert_eq!(lines[11], "3 3 2 1", "Wrong cell connectivity");
+        assert_eq!(lines[13], "5", "Wrong cell type");
+        assert_eq!(lines[15], "POINT_DATA 4", "Wrong point data header");
+        assert_eq!(lines[16], "SCALARS u double 1", "Wrong scalar header");
+        assert_eq!(lines[21], "2", "Wrong scalar value");
+        assert_eq!(lines[22], "VECTORS w double", "Wrong vector header");
+        assert_eq!(lines[24], "1 0 0", "Wrong vector value");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_write_vtk_subdivided() {
+        let mesh = Mesh::new(
+            DataHold::new(vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0], [3, 2]),
+            DataHold::new(vec![0, 1, 2], [1, 3]),
+        );
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        let mut u = Function::new(&space);
+        u.interpolate(|x| x[0] * x[0]);
+        let mut buffer = Vec::new();
+        write_vtk_subdivided(&mut buffer, &mesh, &[("u", &u)], 2).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[4], "POINTS 6 double", "Wrong points header");
+        assert_eq!(lines[11], "CELLS 4 16", "Wrong cells header");
+        assert_eq!(lines[21], "POINT_DATA 6", "Wrong point data header");
+        // Values at the midpoints of the edges are the ones hidden by the vertex output
+        let points: Vec<&str> = lines[5..11].to_vec();
+        let values: Vec<f64> = lines[24..30].iter().map(|v| v.parse().unwrap()).collect();
+        for (point, value) in points.iter().zip(values.iter()) {
+            let x: f64 = point.split(' ').next().unwrap().parse().unwrap();
+            assert!((value - x * x).abs() < 1e-14, "Wrong subdivided value");
+        }
+        assert!(points.contains(&"0.5 0 0"), "Missing edge midpoint");
+        // Kuhn subdivision of the tetrahedron
+        let (lattice, simplices) = subdivide(3, 3);
+        assert_eq!(lattice.len(), 20, "Wrong number of lattice points");
+        assert_eq!(simplices.len(), 27, "Wrong number of sub-simplices");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_vtk_series() {
+        let mesh = Mesh::new(
+            DataHold::new(vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0], [3, 2]),
+            DataHold::new(vec![0, 1, 2], [1, 3]),
+        );
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let mut u = Function::new(&space);
+        let directory = std::env::temp_dir().join("fe2o3_test_vtk_series");
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut series = VtkSeries::new(directory.join("heat"));
+        for time in [0.0, 0.5] {
+            u.interpolate(|x| time + x[0]);
+            series.write(time, &mesh, &[("u", &u)]).unwrap();
+        }
+        assert_eq!(series.times(), &[0.0, 0.5], "Wrong times of the series");
+        let index = std::fs::read_to_string(directory.join("heat.vtk.series")).unwrap();
+        assert!(
+            index.contains("\"name\" : \"heat_00001.vtk\", \"time\" : 5e-1 }"),
+            "Wrong series index {}",
+            index
+        );
+        let last = std::fs::read_to_string(directory.join("heat_00001.vtk")).unwrap();
+        assert!(
+            last.lines().any(|l| l == "1.5"),
+            "Wrong values of the last step"
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
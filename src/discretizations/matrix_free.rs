@@ -0,0 +1,350 @@
+use super::cartesian_grid::CartesianGrid;
+use crate::core::arrays::data_hold::DataHold;
+use crate::solvers::linear_operator::LinearOperator;
+use crate::spaces::quadrature::QuadratureRule;
+use crate::spaces::tensor_product::TensorProductElement;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Operator u -> mass M u + stiffness K u of a continuous tensor product element on a
+/// CartesianGrid applied without assembling any matrix
+///
+/// The action is computed cell by cell with sum factorization: values and gradients at the
+/// order + 1 Gauss-Legendre points per direction are obtained by contracting the cell values with
+/// the one dimensional basis one direction at a time, which costs O(order^(d + 1)) per cell
+/// instead of the O(order^(2 d)) of a local matrix. Global dofs are the nodes of the grid numbered
+/// lexicographically with the first direction running fastest.
+pub struct MatrixFreeOperator<'a> {
+    grid: &'a CartesianGrid,
+    element: TensorProductElement,
+    mass: f64,
+    stiffness: f64,
+    nodes_per_dim: Vec<usize>,
+    values: Vec<f64>,
+    derivatives: Vec<f64>,
+    weights: Vec<f64>,
+}
+
+impl<'a> MatrixFreeOperator<'a> {
+    /// Build the operator mass M + stiffness K of an element on a grid
+    pub fn new(
+        grid: &'a CartesianGrid,
+        element: TensorProductElement,
+        mass: f64,
+        stiffness: f64,
+    ) -> Self {
+        assert!(
+            element.dim() == grid.dim(),
+            "Element and grid dimensions do not match"
+        );
+        let n = element.n_dofs_1d();
+        let quadrature = QuadratureRule::gauss_legendre(n);
+        // One dimensional basis at the quadrature points stored as (points, shape functions)
+        let mut values = vec![0.0; n * n];
+        let mut derivatives = vec![0.0; n * n];
+        for q in 0..n {
+            let x = quadrature.point(q)[0];
+            element.values_1d(x, &mut values[q * n..(q + 1) * n]);
+            element.derivatives_1d(x, &mut derivatives[q * n..(q + 1) * n]);
+        }
+        let nodes_per_dim = grid
+            .cells_per_dim()
+            .iter()
+            .map(|c| c * element.order() + 1)
+            .collect();
+        MatrixFreeOperator {
+            grid,
+            weights: quadrature.weights().to_vec(),
+            element,
+            mass,
+            stiffness,
+            nodes_per_dim,
+            values,
+            derivatives,
+        }
+    }
+
+    /// Mass operator of an element on a grid
+    pub fn mass(grid: &'a CartesianGrid, element: TensorProductElement) -> Self {
+        MatrixFreeOperator::new(grid, element, 1.0, 0.0)
+    }
+
+    /// Stiffness (Laplace) operator of an element on a grid
+    pub fn stiffness(grid: &'a CartesianGrid, element: TensorProductElement) -> Self {
+        MatrixFreeOperator::new(grid, element, 0.0, 1.0)
+    }
+
+    /// Grid the operator lives on
+    pub fn grid(&self) -> &CartesianGrid {
+        self.grid
+    }
+
+    /// Element of the operator
+    pub fn element(&self) -> &TensorProductElement {
+        &self.element
+    }
+
+    /// Number of global dofs
+    pub fn n_dofs(&self) -> usize {
+        self.nodes_per_dim.iter().product()
+    }
+
+    /// Physical coordinates of the global dofs as a (dofs, dimension) array
+    pub fn dof_coordinates(&self) -> DataHold<f64, [usize; 2]> {
+        let dim = self.grid.dim();
+        let order = self.element.order() as f64;
+        let mut coordinates = Vec::with_capacity(self.n_dofs() * dim);
+        for dof in 0..self.n_dofs() {
+            let mut rest = dof;
+            for (d, n) in self.nodes_per_dim.iter().enumerate() {
+                let index = rest % n;
+                rest /= n;
+                coordinates
+                    .push(self.grid.lower()[d] + index as f64 * self.grid.cell_size(d) / order);
+            }
+        }
+        DataHold::new(coordinates, [self.n_dofs(), dim])
+    }
+
+    /// Global dofs of a cell in the local ordering of the element
+    pub fn cell_dofs(&self, cell: usize) -> Vec<usize> {
+        let order = self.element.order();
+        let position = self.grid.cell_multi_index(cell);
+        (0..self.element.n_dofs())
+            .map(|node| {
+                let mut stride = 1;
+                let mut dof = 0;
+                for (d, i) in self.element.node_multi_index(node).iter().enumerate() {
+                    dof += (position[d] * order + i) * stride;
+                    stride *= self.nodes_per_dim[d];
+                }
+                dof
+            })
+            .collect()
+    }
+
+    /// Diagonal of the operator (for instance for Jacobi preconditioning)
+    ///
+    /// The local matrices are Kronecker products of one dimensional matrices so that their
+    /// diagonals are products of one dimensional diagonals.
+    pub fn diagonal(&self) -> Vec<f64> {
+        let n = self.element.n_dofs_1d();
+        let dim = self.grid.dim();
+        let diagonal_1d = |basis: &[f64]| -> Vec<f64> {
+            (0..n)
+                .map(|i| {
+                    (0..n)
+                        .map(|q| self.weights[q] * basis[q * n + i] * basis[q * n + i])
+                        .sum()
+                })
+                .collect()
+        };
+        let mass_1d = diagonal_1d(&self.values);
+        let stiffness_1d = diagonal_1d(&self.derivatives);
+        let sizes: Vec<f64> = (0..dim).map(|d| self.grid.cell_size(d)).collect();
+        let local: Vec<f64> = (0..self.element.n_dofs())
+            .map(|node| {
+                let index = self.element.node_multi_index(node);
+                let mass: f64 = (0..dim).map(|d| mass_1d[index[d]] * sizes[d]).product();
+                let stiffness: f64 = (0..dim)
+                    .map(|k| {
+                        mass / (mass_1d[index[k]] * sizes[k]) * stiffness_1d[index[k]] / sizes[k]
+                    })
+                    .sum();
+                self.mass * mass + self.stiffness * stiffness
+            })
+            .collect();
+        let mut diagonal = vec![0.0; self.n_dofs()];
+        for cell in 0..self.grid.n_cells() {
+            for (dof, value) in self.cell_dofs(cell).iter().zip(local.iter()) {
+                diagonal[*dof] += value;
+            }
+        }
+        diagonal
+    }
+
+    // Action of the operator on the values of one cell
+    fn apply_cell(&self, input: &[f64], output: &mut [f64], scratch: &mut [Vec<f64>; 2]) {
+        let dim = self.grid.dim();
+        let n = self.element.n_dofs_1d();
+        let sizes: Vec<f64> = (0..dim).map(|d| self.grid.cell_size(d)).collect();
+        let volume: f64 = sizes.iter().product();
+        let [work, at_points] = scratch;
+        output.iter_mut().for_each(|v| *v = 0.0);
+        // Each term contracts with the basis (values or derivatives in direction k) to the points,
+        // scales by the quadrature weights and contracts back with the transposed basis
+        let terms = std::iter::once((None, self.mass))
+            .chain((0..dim).map(|k| (Some(k), self.stiffness / (sizes[k] * sizes[k]))));
+        for (derivative, factor) in terms {
+            if factor == 0.0 {
+                continue;
+            }
+            let basis = |d: usize| {
+                if Some(d) == derivative {
+                    &self.derivatives
+                } else {
+                    &self.values
+                }
+            };
+            at_points.copy_from_slice(input);
+            for d in 0..dim {
+                contract(basis(d), n, d, false, at_points, work);
+                std::mem::swap(at_points, work);
+            }
+            for (point, value) in at_points.iter_mut().enumerate() {
+                let weight: f64 = (0..dim)
+                    .map(|d| self.weights[(point / n.pow(d as u32)) % n])
+                    .product();
+                *value *= factor * weight * volume;
+            }
+            for d in 0..dim {
+                contract(basis(d), n, d, true, at_points, work);
+                std::mem::swap(at_points, work);
+            }
+            for (o, r) in output.iter_mut().zip(at_points.iter()) {
+                *o += r;
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Traits
+//--------------------------------------------------------------------------------------------------
+
+impl LinearOperator for MatrixFreeOperator<'_> {
+    fn n_rows(&self) -> usize {
+        self.n_dofs()
+    }
+
+    fn n_cols(&self) -> usize {
+        self.n_dofs()
+    }
+
+    fn apply(&self, x: &[f64], y: &mut [f64]) {
+        assert!(
+            x.len() == self.n_dofs() && y.len() == self.n_dofs(),
+            "Vectors do not match the number of dofs of the operator"
+        );
+        let n_local = self.element.n_dofs();
+        let mut local_x = vec![0.0; n_local];
+        let mut local_y = vec![0.0; n_local];
+        let mut scratch = [vec![0.0; n_local], vec![0.0; n_local]];
+        y.iter_mut().for_each(|v| *v = 0.0);
+        for cell in 0..self.grid.n_cells() {
+            let dofs = self.cell_dofs(cell);
+            for (local, dof) in local_x.iter_mut().zip(dofs.iter()) {
+                *local = x[*dof];
+            }
+            self.apply_cell(&local_x, &mut local_y, &mut scratch);
+            for (local, dof) in local_y.iter().zip(dofs.iter()) {
+                y[*dof] += local;
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Contract a tensor of n^d entries with the (n, n) matrix (or its transpose) along a direction
+fn contract(
+    matrix: &[f64],
+    n: usize,
+    direction: usize,
+    transpose: bool,
+    input: &[f64],
+    output: &mut [f64],
+) {
+    let stride = n.pow(direction as u32);
+    let outer = input.len() / (stride * n);
+    for o in 0..outer {
+        for r in 0..n {
+            for s in 0..stride {
+                output[(o * n + r) * stride + s] = (0..n)
+                    .map(|c| {
+                        let a = if transpose {
+                            matrix[c * n + r]
+                        } else {
+                            matrix[r * n + c]
+                        };
+                        a * input[(o * n + c) * stride + s]
+                    })
+                    .sum();
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dot(a: &[f64], b: &[f64]) -> f64 {
+        a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn test_matrix_free_mass() {
+        let grid = CartesianGrid::new(vec![0.0; 3], vec![2.0, 1.0, 1.0], vec![2, 1, 2]);
+        let operator = MatrixFreeOperator::mass(&grid, TensorProductElement::new(3, 3));
+        assert_eq!(operator.n_dofs(), 7 * 4 * 7, "Wrong number of dofs");
+        let ones = vec![1.0; operator.n_dofs()];
+        let mut product = vec![0.0; operator.n_dofs()];
+        operator.apply(&ones, &mut product);
+        assert!(
+            (product.iter().sum::<f64>() - 2.0).abs() < 1e-12,
+            "Mass should sum to the volume"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_matrix_free_stiffness() {
+        let grid = CartesianGrid::new(vec![0.0, 0.0], vec![2.0, 1.0], vec![3, 2]);
+        let operator = MatrixFreeOperator::stiffness(&grid, TensorProductElement::new(2, 3));
+        let coordinates = operator.dof_coordinates();
+        let u: Vec<f64> = (0..operator.n_dofs())
+            .map(|i| coordinates[2 * i] + 2.0 * coordinates[2 * i + 1])
+            .collect();
+        let mut product = vec![0.0; operator.n_dofs()];
+        operator.apply(&vec![1.0; operator.n_dofs()], &mut product);
+        assert!(
+            product.iter().all(|v| v.abs() < 1e-12),
+            "Constants should be in the kernel of the stiffness"
+        );
+        // u K u is the integral of |grad u|^2 = 5 over the area 2
+        operator.apply(&u, &mut product);
+        assert!(
+            (dot(&u, &product) - 10.0).abs() < 1e-11,
+            "Wrong energy of the linear function"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_matrix_free_diagonal() {
+        let grid = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 2.0], vec![2, 2]);
+        let operator = MatrixFreeOperator::new(&grid, TensorProductElement::new(2, 2), 3.0, 0.5);
+        let diagonal = operator.diagonal();
+        let mut unit = vec![0.0; operator.n_dofs()];
+        let mut product = vec![0.0; operator.n_dofs()];
+        for i in 0..operator.n_dofs() {
+            unit[i] = 1.0;
+            operator.apply(&unit, &mut product);
+            unit[i] = 0.0;
+            assert!(
+                (product[i] - diagonal[i]).abs() < 1e-12,
+                "Wrong diagonal entry {}",
+                i
+            );
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::data_traits::DataContainer;
+use crate::core::search::KdTree;
+use crate::discretizations::cell_tree::CellTree;
+use crate::discretizations::facets::Facets;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Structure holding a simplicial mesh
+///
+/// The vertices are held as a two dimensional array of size (number of vertices, geometric
+/// dimension) and the cells as a two dimensional array of size (number of cells, topological
+/// dimension + 1) holding the vertex indices of every cell. The local ordering of the vertices of a
+/// cell follows the one of the reference simplex.
+///
+/// Facets can be tagged with integers (for instance to select the boundary conditions applied on
+/// them). Tags are stored by the sorted vertices of the facet. Vertices can be tagged in the same
+/// way (for instance from the node sets of a mesh file) and cells by their index (for instance to
+/// select the material of a region).
+///
+/// The CellTree used to locate points and the KdTree of the vertices used to find the nearest ones
+/// are only built the first time they are needed.
+pub struct Mesh {
+    vertices: DataHold<f64, [usize; 2]>,
+    cells: DataHold<usize, [usize; 2]>,
+    facet_tags: HashMap<Vec<usize>, usize>,
+    vertex_tags: HashMap<usize, usize>,
+    cell_tags: HashMap<usize, usize>,
+    cell_tree: OnceLock<CellTree>,
+    vertex_tree: OnceLock<KdTree>,
+}
+
+impl Mesh {
+    /// Build a mesh from its vertex coordinates and cell connectivity
+    pub fn new(vertices: DataHold<f64, [usize; 2]>, cells: DataHold<usize, [usize; 2]>) -> Self {
+        let n_vertices = vertices.dimensions()[0];
+        assert!(
+            cells.iter().all(|v| *v < n_vertices),
+            "Tried to build a Mesh with cells referencing non existing vertices"
+        );
+        assert!(
+            cells.dimensions()[1] >= 1 && cells.dimensions()[1] <= vertices.dimensions()[1] + 1,
+            "Tried to build a Mesh with cells of a dimension higher than the space"
+        );
+        Mesh {
+            vertices,
+            cells,
+            facet_tags: HashMap::new(),
+            vertex_tags: HashMap::new(),
+            cell_tags: HashMap::new(),
+            cell_tree: OnceLock::new(),
+            vertex_tree: OnceLock::new(),
+        }
+    }
+
+    /// Dimension of the space the vertices live in
+    pub fn geometric_dim(&self) -> usize {
+        self.vertices.dimensions()[1]
+    }
+
+    /// Dimension of the cells
+    pub fn topological_dim(&self) -> usize {
+        self.cells.dimensions()[1] - 1
+    }
+
+    /// Number of vertices
+    pub fn n_vertices(&self) -> usize {
+        self.vertices.dimensions()[0]
+    }
+
+    /// Number of cells
+    pub fn n_cells(&self) -> usize {
+        self.cells.dimensions()[0]
+    }
+
+    /// Number of vertices per cell
+    pub fn vertices_per_cell(&self) -> usize {
+        self.cells.dimensions()[1]
+    }
+
+    /// Coordinates of a vertex
+    pub fn vertex(&self, vertex: usize) -> &[f64] {
+        let dim = self.geometric_dim();
+        &self.vertices[vertex * dim..(vertex + 1) * dim]
+    }
+
+    /// Vertex indices of a cell
+    pub fn cell(&self, cell: usize) -> &[usize] {
+        let n = self.vertices_per_cell();
+        &self.cells[cell * n..(cell + 1) * n]
+    }
+
+    /// All the vertex coordinates
+    pub fn vertices(&self) -> &DataHold<f64, [usize; 2]> {
+        &self.vertices
+    }
+
+    /// All the cell connectivities
+    pub fn cells(&self) -> &DataHold<usize, [usize; 2]> {
+        &self.cells
+    }
+
+    /// Bounding volume hierarchy of the cells
+    pub fn cell_tree(&self) -> &CellTree {
+        self.cell_tree.get_or_init(|| CellTree::new(self))
+    }
+
+    /// Find a cell containing the point and the reference coordinates of the point in it
+    pub fn locate(&self, point: &[f64]) -> Option<(usize, Vec<f64>)> {
+        self.cell_tree().locate(self, point)
+    }
+
+    /// k-d tree of the vertices
+    pub fn vertex_tree(&self) -> &KdTree {
+        self.vertex_tree
+            .get_or_init(|| KdTree::new(self.geometric_dim(), &self.vertices))
+    }
+
+    /// Vertex closest to a point and its distance
+    pub fn nearest_vertex(&self, point: &[f64]) -> Option<(usize, f64)> {
+        self.vertex_tree().nearest(point)
+    }
+
+    /// Tag the facet made of the given vertices
+    pub fn tag_facet(&mut self, vertices: &[usize], tag: usize) {
+        let mut key = vertices.to_vec();
+        key.sort_unstable();
+        self.facet_tags.insert(key, tag);
+    }
+
+    /// Tag of the facet made of the given vertices if it has one
+    pub fn facet_tag(&self, vertices: &[usize]) -> Option<usize> {
+        let mut key = vertices.to_vec();
+        key.sort_unstable();
+        self.facet_tags.get(&key).copied()
+    }
+
+    /// Tagged facets as their sorted vertices and tag
+    pub fn facet_tags(&self) -> impl Iterator<Item = (&[usize], usize)> {
+        self.facet_tags.iter().map(|(k, t)| (k.as_slice(), *t))
+    }
+
+    /// Tag a vertex
+    pub fn tag_vertex(&mut self, vertex: usize, tag: usize) {
+        self.vertex_tags.insert(vertex, tag);
+    }
+
+    /// Tag of a vertex if it has one
+    pub fn vertex_tag(&self, vertex: usize) -> Option<usize> {
+        self.vertex_tags.get(&vertex).copied()
+    }
+
+    /// Tagged vertices and their tag
+    pub fn vertex_tags(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.vertex_tags.iter().map(|(v, t)| (*v, *t))
+    }
+
+    /// Tag a cell
+    pub fn tag_cell(&mut self, cell: usize, tag: usize) {
+        assert!(cell < self.n_cells(), "Cell is not in the mesh");
+        self.cell_tags.insert(cell, tag);
+    }
+
+    /// Tag of a cell if it has one
+    pub fn cell_tag(&self, cell: usize) -> Option<usize> {
+        self.cell_tags.get(&cell).copied()
+    }
+
+    /// Tagged cells and their tag
+    pub fn cell_tags(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.cell_tags.iter().map(|(c, t)| (*c, *t))
+    }
+
+    /// Tag all the cells whose centroid satisfies the predicate
+    pub fn tag_cells<Predicate>(&mut self, tag: usize, predicate: Predicate)
+    where
+        Predicate: Fn(&[f64]) -> bool,
+    {
+        let dim = self.geometric_dim();
+        let mut centroid = vec![0.0; dim];
+        for cell in 0..self.n_cells() {
+            centroid.iter_mut().for_each(|c| *c = 0.0);
+            for v in self.cell(cell) {
+                centroid
+                    .iter_mut()
+                    .zip(self.vertex(*v))
+                    .for_each(|(c, x)| *c += x);
+            }
+            let n = self.vertices_per_cell() as f64;
+            centroid.iter_mut().for_each(|c| *c /= n);
+            if predicate(&centroid) {
+                self.cell_tags.insert(cell, tag);
+            }
+        }
+    }
+
+    /// Tag all the boundary facets whose vertices all satisfy the predicate
+    pub fn tag_boundary<Predicate>(&mut self, tag: usize, predicate: Predicate)
+    where
+        Predicate: Fn(&[f64]) -> bool,
+    {
+        let facets = Facets::new(self);
+        for facet in facets.boundary_facets() {
+            let vertices = facets.facet_vertices(facet);
+            if vertices.iter().all(|v| predicate(self.vertex(*v))) {
+                self.facet_tags.insert(vertices.to_vec(), tag);
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::test_meshes::unit_square_cells;
+
+    #[test]
+    fn test_mesh_access() {
+        let mesh = unit_square_cells([0, 1, 2, 1, 3, 2]);
+        assert_eq!(mesh.geometric_dim(), 2, "Wrong geometric dimension");
+        assert_eq!(mesh.topological_dim(), 2, "Wrong topological dimension");
+        assert_eq!(mesh.n_vertices(), 4, "Wrong number of vertices");
+        assert_eq!(mesh.n_cells(), 2, "Wrong number of cells");
+        assert_eq!(
+            mesh.vertex(3),
+            &[1.0, 1.0],
+            "Wrong coordinates for vertex 3"
+        );
+        assert_eq!(mesh.cell(1), &[1, 3, 2], "Wrong vertices for cell 1");
+        assert_eq!(
+            mesh.nearest_vertex(&[0.9, 0.7]).map(|(v, _)| v),
+            Some(3),
+            "Wrong nearest vertex"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_mesh_tag_boundary() {
+        let mut mesh = unit_square_cells([0, 1, 2, 1, 3, 2]);
+        mesh.tag_boundary(7, |x| x[0] == 0.0);
+        assert_eq!(mesh.facet_tag(&[2, 0]), Some(7), "Left side was not tagged");
+        assert_eq!(
+            mesh.facet_tag(&[0, 1]),
+            None,
+            "Bottom side should not be tagged"
+        );
+        mesh.tag_facet(&[1, 0], 3);
+        assert_eq!(
+            mesh.facet_tag(&[0, 1]),
+            Some(3),
+            "Bottom side was not tagged"
+        );
+        mesh.tag_vertex(3, 2);
+        assert_eq!(mesh.vertex_tag(3), Some(2), "Vertex was not tagged");
+        assert_eq!(mesh.vertex_tag(0), None, "Vertex should not be tagged");
+        mesh.tag_cells(4, |x| x[0] + x[1] > 1.0);
+        assert_eq!(mesh.cell_tag(1), Some(4), "Upper cell was not tagged");
+        assert_eq!(mesh.cell_tag(0), None, "Lower cell should not be tagged");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    #[should_panic]
+    fn test_mesh_bad_connectivity() {
+        Mesh::new(
+            DataHold::new(vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0], [3, 2]),
+            DataHold::new(vec![0, 1, 3], [1, 3]),
+        );
+    }
+}
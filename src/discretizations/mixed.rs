@@ -0,0 +1,333 @@
+use super::assembler::add_local_matrix;
+use super::cell_values::{CellValues, CoordinateSystem};
+use super::constraints::AffineConstraints;
+use super::dof_map::DofMap;
+use super::function::Function;
+use super::function_space::FunctionSpace;
+use super::mesh::Mesh;
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::data_wrap::DataWrap;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::spaces::quadrature::QuadratureRule;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Structure composing several function spaces (the fields) on the same mesh into one system
+///
+/// The global dofs are numbered by blocks: all the dofs of field 0 come first (in the numbering of
+/// its FunctionSpace), then the ones of field 1 and so on. On a cell, the local dofs are also
+/// ordered by field and, inside a field, node by node with the components interleaved (see
+/// local_index). A single vector valued FunctionSpace is the mixed space with one field.
+pub struct MixedSpace<'a> {
+    spaces: Vec<&'a FunctionSpace<'a>>,
+    offsets: Vec<usize>,
+    local_offsets: Vec<usize>,
+    dof_map: DofMap,
+}
+
+/// Structure looping over the cells of a mesh to assemble the global systems of a MixedSpace
+///
+/// Kernels receive the CellValues of every field (in the order of the fields) and local matrices
+/// or vectors following the local ordering of the MixedSpace.
+pub struct MixedAssembler<'a> {
+    space: &'a MixedSpace<'a>,
+    quadrature: QuadratureRule,
+    coordinate_system: CoordinateSystem,
+}
+
+impl<'a> MixedSpace<'a> {
+    /// Compose function spaces living on the same mesh
+    pub fn new(spaces: Vec<&'a FunctionSpace<'a>>) -> Self {
+        assert!(!spaces.is_empty(), "A mixed space needs at least one field");
+        let mesh = spaces[0].mesh();
+        assert!(
+            spaces.iter().all(|s| std::ptr::eq(s.mesh(), mesh)),
+            "Fields of a mixed space should live on the same mesh"
+        );
+        let mut offsets = vec![0];
+        let mut local_offsets = vec![0];
+        for space in spaces.iter() {
+            offsets.push(offsets.last().unwrap() + space.n_dofs());
+            local_offsets.push(
+                local_offsets.last().unwrap() + space.element().n_dofs() * space.n_components(),
+            );
+        }
+        let dofs_per_cell = *local_offsets.last().unwrap();
+        let mut cell_dofs = Vec::with_capacity(mesh.n_cells() * dofs_per_cell);
+        for cell in 0..mesh.n_cells() {
+            for (space, offset) in spaces.iter().zip(offsets.iter()) {
+                for dof in space.dof_map().cell_dofs(cell) {
+                    for c in 0..space.n_components() {
+                        cell_dofs.push(offset + space.dof(*dof, c));
+                    }
+                }
+            }
+        }
+        let dof_map = DofMap::new(
+            DataHold::new(cell_dofs, [mesh.n_cells(), dofs_per_cell]),
+            *offsets.last().unwrap(),
+        );
+        MixedSpace {
+            spaces,
+            offsets,
+            local_offsets,
+            dof_map,
+        }
+    }
+
+    /// Mesh the fields live on
+    pub fn mesh(&self) -> &'a Mesh {
+        self.spaces[0].mesh()
+    }
+
+    /// Number of fields
+    pub fn n_fields(&self) -> usize {
+        self.spaces.len()
+    }
+
+    /// Space of a field
+    pub fn sub_space(&self, field: usize) -> &'a FunctionSpace<'a> {
+        self.spaces[field]
+    }
+
+    /// Total number of dofs
+    pub fn n_dofs(&self) -> usize {
+        *self.offsets.last().unwrap()
+    }
+
+    /// Range of the global dofs of a field
+    pub fn block_range(&self, field: usize) -> std::ops::Range<usize> {
+        self.offsets[field]..self.offsets[field + 1]
+    }
+
+    /// Numbering of all the dofs of the fields on every cell
+    pub fn dof_map(&self) -> &DofMap {
+        &self.dof_map
+    }
+
+    /// Index in the local matrices and vectors of a component of a node of a field
+    pub fn local_index(&self, field: usize, node: usize, component: usize) -> usize {
+        self.local_offsets[field] + node * self.spaces[field].n_components() + component
+    }
+
+    /// Split global values into one Function per field
+    pub fn split(&self, values: &[f64]) -> Vec<Function<'a>> {
+        assert!(
+            values.len() == self.n_dofs(),
+            "Values do not match the number of dofs of the mixed space"
+        );
+        self.spaces
+            .iter()
+            .enumerate()
+            .map(|(field, space)| {
+                Function::from_values(space, values[self.block_range(field)].to_vec())
+            })
+            .collect()
+    }
+
+    /// Gather one Function per field into global values
+    pub fn join(&self, functions: &[&Function]) -> Vec<f64> {
+        assert!(
+            functions.len() == self.n_fields()
+                && functions
+                    .iter()
+                    .zip(self.spaces.iter())
+                    .all(|(f, s)| std::ptr::eq(f.space(), *s)),
+            "Functions do not match the fields of the mixed space"
+        );
+        functions
+            .iter()
+            .flat_map(|f| f.values().iter().copied())
+            .collect()
+    }
+}
+
+impl<'a> MixedAssembler<'a> {
+    /// Build an assembler for a mixed space with a cell quadrature rule used by every field
+    pub fn new(space: &'a MixedSpace<'a>, quadrature: QuadratureRule) -> Self {
+        MixedAssembler {
+            space,
+            quadrature,
+            coordinate_system: CoordinateSystem::Cartesian,
+        }
+    }
+
+    /// Change the coordinate system the integrals are taken in
+    pub fn set_coordinate_system(&mut self, coordinate_system: CoordinateSystem) {
+        assert!(
+            coordinate_system == CoordinateSystem::Cartesian
+                || self.space.mesh().geometric_dim() == 2,
+            "Axisymmetric coordinates need a mesh of the (r, z) half plane"
+        );
+        self.coordinate_system = coordinate_system;
+    }
+
+    /// Mixed space the assembler works on
+    pub fn space(&self) -> &MixedSpace<'a> {
+        self.space
+    }
+
+    /// Add the contributions of the kernel on every cell to a global matrix
+    ///
+    /// The global matrix has to be allocated with a SparsityPattern built from the DofMap of the
+    /// mixed space.
+    pub fn assemble_matrix<Kernel>(&self, matrix: &mut SparseCSR<f64>, mut kernel: Kernel)
+    where
+        Kernel: FnMut(&[CellValues], &mut DataWrap<f64, [usize; 2]>),
+    {
+        self.assemble(|values, dofs, local_matrix, _| {
+            kernel(values, local_matrix);
+            add_local_matrix(matrix, dofs, local_matrix);
+        });
+    }
+
+    /// Add the contributions of the kernel on every cell to a global vector
+    pub fn assemble_vector<Kernel>(&self, vector: &mut [f64], mut kernel: Kernel)
+    where
+        Kernel: FnMut(&[CellValues], &mut DataWrap<f64, [usize; 1]>),
+    {
+        self.assemble(|values, dofs, _, local_vector| {
+            kernel(values, local_vector);
+            for (i, dof) in dofs.iter().enumerate() {
+                vector[*dof] += local_vector[i];
+            }
+        });
+    }
+
+    /// Add the contributions of the kernel on every cell to a global matrix and vector at once
+    /// while condensing the constraints
+    ///
+    /// The global matrix has to be allocated with SparsityPattern::from_dofmap_and_constraints
+    /// applied to the DofMap of the mixed space.
+    pub fn assemble_system<Kernel>(
+        &self,
+        matrix: &mut SparseCSR<f64>,
+        vector: &mut [f64],
+        constraints: &AffineConstraints,
+        mut kernel: Kernel,
+    ) where
+        Kernel:
+            FnMut(&[CellValues], &mut DataWrap<f64, [usize; 2]>, &mut DataWrap<f64, [usize; 1]>),
+    {
+        assert!(
+            constraints.n_dofs() == self.space.n_dofs(),
+            "Constraints do not match the number of dofs"
+        );
+        self.assemble(|values, dofs, local_matrix, local_vector| {
+            kernel(values, local_matrix, local_vector);
+            constraints.distribute_local_to_global(
+                dofs,
+                Some(local_matrix),
+                Some(local_vector),
+                Some(&mut *matrix),
+                Some(&mut *vector),
+            );
+        });
+    }
+
+    // Loop over the cells handing zeroed local storage to the scatter closure
+    fn assemble<Scatter>(&self, mut scatter: Scatter)
+    where
+        Scatter: FnMut(
+            &[CellValues],
+            &[usize],
+            &mut DataWrap<f64, [usize; 2]>,
+            &mut DataWrap<f64, [usize; 1]>,
+        ),
+    {
+        let space = self.space;
+        let mesh = space.mesh();
+        let n = space.dof_map().dofs_per_cell();
+        let mut values: Vec<CellValues> = space
+            .spaces
+            .iter()
+            .map(|s| {
+                let mut values = CellValues::new(s.element(), &self.quadrature);
+                values.set_coordinate_system(self.coordinate_system);
+                values
+            })
+            .collect();
+        let mut local_matrix = vec![0.0; n * n];
+        let mut local_vector = vec![0.0; n];
+        for cell in 0..mesh.n_cells() {
+            values.iter_mut().for_each(|v| v.reinit(mesh, cell));
+            local_matrix.iter_mut().for_each(|v| *v = 0.0);
+            local_vector.iter_mut().for_each(|v| *v = 0.0);
+            scatter(
+                &values,
+                space.dof_map().cell_dofs(cell),
+                &mut DataWrap::new(&mut local_matrix, [n, n]),
+                &mut DataWrap::new(&mut local_vector, [n]),
+            );
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::sparsity::SparsityPattern;
+    use crate::discretizations::test_meshes::unit_square;
+    use crate::spaces::lagrange::LagrangeElement;
+
+    #[test]
+    fn test_mixed_space_numbering() {
+        let mesh = unit_square();
+        let velocity = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        let pressure = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let space = MixedSpace::new(vec![&velocity, &pressure]);
+        assert_eq!(space.n_dofs(), 22, "Wrong number of dofs");
+        assert_eq!(space.block_range(1), 18..22, "Wrong pressure block");
+        assert_eq!(space.dof_map().dofs_per_cell(), 15, "Wrong dofs per cell");
+        assert_eq!(space.local_index(1, 2, 0), 14, "Wrong local index");
+        // First pressure node of cell 1 is vertex 3
+        assert_eq!(space.dof_map().cell_dofs(1)[12], 21, "Wrong block offset");
+        let values: Vec<f64> = (0..22).map(|i| i as f64).collect();
+        let fields = space.split(&values);
+        assert_eq!(fields[1].values(), &[18.0, 19.0, 20.0, 21.0], "Wrong split");
+        assert_eq!(space.join(&[&fields[0], &fields[1]]), values, "Wrong join");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_mixed_assembler_divergence() {
+        let mesh = unit_square();
+        let velocity = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        let pressure = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let space = MixedSpace::new(vec![&velocity, &pressure]);
+        let assembler = MixedAssembler::new(&space, QuadratureRule::simplex(2, 3));
+        let mut matrix = SparsityPattern::from_dofmap(space.dof_map()).to_csr(0.0);
+        // Block of int q div(v)
+        assembler.assemble_matrix(&mut matrix, |values, local| {
+            let n = space.dof_map().dofs_per_cell();
+            let (v, p) = (&values[0], &values[1]);
+            for q in 0..v.n_points() {
+                for i in 0..p.n_dofs() {
+                    let row = space.local_index(1, i, 0);
+                    for j in 0..v.n_dofs() {
+                        for c in 0..2 {
+                            let col = space.local_index(0, j, c);
+                            local[row * n + col] +=
+                                p.shape_value(q, i) * v.shape_gradient(q, j)[c] * v.weight(q);
+                        }
+                    }
+                }
+            }
+        });
+        // div(x, y) = 2 so that the sum of int q div(v) over the pressure basis is twice the area
+        let mut u = Function::new(&velocity);
+        u.interpolate_vector(|x, value| value.copy_from_slice(x));
+        let p = Function::new(&pressure);
+        let values = space.join(&[&u, &p]);
+        let mut product = vec![0.0; space.n_dofs()];
+        matrix.apply(&values, &mut product);
+        let total: f64 = product[space.block_range(1)].iter().sum();
+        assert!((total - 2.0).abs() < 1e-13, "Wrong divergence block");
+    }
+}
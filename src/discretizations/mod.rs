@@ -1 +1,99 @@
+/// Simplicial meshes
+pub mod mesh;
 
+/// Meshes shared by the tests of the library
+#[cfg(test)]
+pub(crate) mod test_meshes;
+
+/// Mapping between mesh cells and global degrees of freedom
+pub mod dof_map;
+
+/// Symbolic structure of the global sparse matrices
+pub mod sparsity;
+
+/// Mapping between reference and physical cells
+pub mod cell_mapping;
+
+/// Shape function values on physical cells
+pub mod cell_values;
+
+/// Loops over the mesh assembling global matrices and vectors
+pub mod assembler;
+
+/// Facet topology of meshes
+pub mod facets;
+
+/// Shape function traces on facets
+pub mod facet_values;
+
+/// Linear constraints between degrees of freedom
+pub mod constraints;
+
+/// Lagrange multiplier enforcement of constraints by augmented saddle point systems
+pub mod multipliers;
+
+/// Symbolic variational forms compiled to assembly kernels
+pub mod forms;
+
+/// Ready made mass, stiffness and advection matrices
+pub mod operators;
+
+/// Spaces of finite element functions on a mesh
+pub mod function_space;
+
+/// Finite element functions pairing dof values with their space
+pub mod function;
+
+/// Input and output of meshes and functions
+pub mod io;
+
+/// Bounding volume hierarchy locating points in the cells of a mesh
+pub mod cell_tree;
+
+/// L2 projection and interpolation onto function spaces
+pub mod projection;
+
+/// Vector valued and mixed function spaces
+pub mod mixed;
+
+/// Piola mapped values of H(div) and H(curl) conforming elements on physical cells
+pub mod conforming_values;
+
+/// Interior penalty and Lax-Friedrichs forms for discontinuous Galerkin discretizations
+pub mod dg;
+
+/// Nitsche weak imposition of Dirichlet conditions on boundary facets
+pub mod nitsche;
+
+/// Fictitious domain discretizations on cut cells of level set geometries
+pub mod cut_cell;
+
+/// Axis aligned structured grids of boxes
+pub mod cartesian_grid;
+
+/// Structured grids of boxes graded geometrically or stretched into boundary layers
+pub mod graded_grid;
+
+/// Matrix-free application of operators of tensor product elements by sum factorization
+pub mod matrix_free;
+
+/// Recovery of continuous gradients and fluxes from discrete solutions
+pub mod recovery;
+
+/// State variables stored at the quadrature points of the cells with commit and rollback
+pub mod quadrature_field;
+
+/// Residual based a posteriori error estimation and marking strategies for adaptivity
+pub mod estimator;
+
+/// Interpolation and conservative projection of functions between non-matching meshes
+pub mod transfer;
+
+/// Isogeometric discretizations on NURBS patches
+pub mod nurbs;
+
+/// Partitioning of meshes into subdomains with ghost layers for domain decomposition
+pub mod partition;
+
+/// Uniform refinement of meshes with parent maps and prolongations for geometric multigrid
+pub mod refinement;
@@ -0,0 +1,265 @@
+use super::function_space::FunctionSpace;
+use super::operators::{lumped_mass_matrix, Lumping};
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::spaces::quadrature::QuadratureRule;
+
+// Entries (j, b_kj) and value g_k of a constraint
+type Constraint = (Vec<(usize, f64)>, f64);
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Linear constraints sum_j b_kj u_j = g_k enforced weakly by Lagrange multipliers
+///
+/// Unlike AffineConstraints, which eliminates constrained dofs, every constraint adds a multiplier
+/// unknown and a row to the system: the augmented saddle point system [[A, B^T], [B, 0]] [u, l] =
+/// [f, g] is built from the assembled one by augment, the multipliers being numbered after the
+/// dofs. This enforces integral conditions such as a vanishing mean pressure and ties between the
+/// dofs of interfaces, the multipliers being the reactions which enforce them (for instance the
+/// interface fluxes). The augmented system is indefinite and needs a solver with pivoting such as
+/// the sparse LU or a saddle point solver.
+pub struct LagrangeMultipliers {
+    n_dofs: usize,
+    constraints: Vec<Constraint>,
+}
+
+impl LagrangeMultipliers {
+    /// Build an empty set of constraints on n_dofs dofs
+    pub fn new(n_dofs: usize) -> Self {
+        LagrangeMultipliers {
+            n_dofs,
+            constraints: Vec::new(),
+        }
+    }
+
+    /// Number of dofs
+    pub fn n_dofs(&self) -> usize {
+        self.n_dofs
+    }
+
+    /// Number of multipliers, one per constraint
+    pub fn n_multipliers(&self) -> usize {
+        self.constraints.len()
+    }
+
+    /// Add the constraint sum_j entries_j.1 u_(entries_j.0) = value and return its multiplier
+    pub fn add_constraint(&mut self, entries: &[(usize, f64)], value: f64) -> usize {
+        assert!(
+            entries.iter().all(|(j, _)| *j < self.n_dofs),
+            "Constraint depends on dofs out of bounds"
+        );
+        // Merge the entries of the same dof
+        let mut sorted = entries.to_vec();
+        sorted.sort_by_key(|(j, _)| *j);
+        let mut merged: Vec<(usize, f64)> = Vec::with_capacity(sorted.len());
+        for (j, b) in sorted {
+            match merged.last_mut() {
+                Some((last, value)) if *last == j => *value += b,
+                _ => merged.push((j, b)),
+            }
+        }
+        self.constraints.push((merged, value));
+        self.constraints.len() - 1
+    }
+
+    /// Constrain the value of a dof
+    pub fn add_dof_value(&mut self, dof: usize, value: f64) -> usize {
+        self.add_constraint(&[(dof, 1.0)], value)
+    }
+
+    /// Constrain the value of a component of the functions of a space at a physical point
+    pub fn add_point_value(
+        &mut self,
+        space: &FunctionSpace,
+        point: &[f64],
+        component: usize,
+        value: f64,
+    ) -> usize {
+        let (cell, reference) = space
+            .mesh()
+            .locate(point)
+            .expect("Constrained point lies outside of the mesh");
+        let mut shape_values = vec![0.0; space.element().n_dofs()];
+        space.element().values(&reference, &mut shape_values);
+        let entries: Vec<(usize, f64)> = shape_values
+            .iter()
+            .zip(space.dof_map().cell_dofs(cell))
+            .map(|(phi, dof)| (space.dof(*dof, component), *phi))
+            .collect();
+        self.add_constraint(&entries, value)
+    }
+
+    /// Constrain the integral of a component of the functions of a space over the mesh, a zero
+    /// value giving the mean-zero condition of pressures or pure Neumann problems
+    pub fn add_integral(&mut self, space: &FunctionSpace, component: usize, value: f64) -> usize {
+        let element = space.element();
+        let assembler = space.assembler(QuadratureRule::simplex(element.dim(), element.order()));
+        let integrals = lumped_mass_matrix(&assembler, Lumping::RowSum);
+        let entries: Vec<(usize, f64)> = integrals
+            .values()
+            .iter()
+            .enumerate()
+            .map(|(s, integral)| (space.dof(s, component), *integral))
+            .collect();
+        self.add_constraint(&entries, value)
+    }
+
+    /// Tie the dofs of two lists pairwise, u_(first_i) = u_(second_i), for instance across an
+    /// interface between non-conforming parts
+    pub fn add_tie(&mut self, first: &[usize], second: &[usize]) {
+        assert_eq!(first.len(), second.len(), "Tied dofs should come in pairs");
+        for (a, b) in first.iter().zip(second) {
+            self.add_constraint(&[(*a, 1.0), (*b, -1.0)], 0.0);
+        }
+    }
+
+    /// Entries and value of the constraint of a multiplier
+    pub fn constraint(&self, multiplier: usize) -> (&[(usize, f64)], f64) {
+        let (entries, value) = &self.constraints[multiplier];
+        (entries, *value)
+    }
+
+    /// Constraint matrix B of size (multipliers, dofs)
+    pub fn constraint_matrix(&self) -> SparseCSR<f64> {
+        let mut row_offsets = vec![0];
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+        for (entries, _) in &self.constraints {
+            for (j, b) in entries {
+                col_indices.push(*j);
+                values.push(*b);
+            }
+            row_offsets.push(col_indices.len());
+        }
+        SparseCSR::new(self.n_dofs, row_offsets, col_indices, values)
+    }
+
+    /// Augmented saddle point matrix [[A, B^T], [B, 0]] and right hand side [f, g] of an assembled
+    /// system
+    pub fn augment(&self, matrix: &SparseCSR<f64>, rhs: &[f64]) -> (SparseCSR<f64>, Vec<f64>) {
+        assert!(
+            matrix.n_rows() == self.n_dofs && matrix.n_cols() == self.n_dofs,
+            "Matrix does not match the dofs of the constraints"
+        );
+        assert_eq!(
+            rhs.len(),
+            self.n_dofs,
+            "Right hand side does not match the dofs of the constraints"
+        );
+        let n = self.n_dofs;
+        let constraint_matrix = self.constraint_matrix();
+        let transpose = constraint_matrix.transpose();
+        let mut row_offsets = vec![0];
+        let mut col_indices = Vec::with_capacity(matrix.nnz() + 2 * constraint_matrix.nnz());
+        let mut values = Vec::with_capacity(col_indices.capacity());
+        for row in 0..n {
+            let (cols, row_values) = matrix.row(row);
+            col_indices.extend_from_slice(cols);
+            values.extend_from_slice(row_values);
+            let (multipliers, coefficients) = transpose.row(row);
+            col_indices.extend(multipliers.iter().map(|k| n + k));
+            values.extend_from_slice(coefficients);
+            row_offsets.push(col_indices.len());
+        }
+        for k in 0..self.n_multipliers() {
+            let (cols, row_values) = constraint_matrix.row(k);
+            col_indices.extend_from_slice(cols);
+            values.extend_from_slice(row_values);
+            row_offsets.push(col_indices.len());
+        }
+        let augmented = SparseCSR::new(n + self.n_multipliers(), row_offsets, col_indices, values);
+        let mut augmented_rhs = rhs.to_vec();
+        augmented_rhs.extend(self.constraints.iter().map(|(_, value)| *value));
+        (augmented, augmented_rhs)
+    }
+
+    /// Dof values and multipliers of a solution of the augmented system
+    pub fn split<'s>(&self, solution: &'s [f64]) -> (&'s [f64], &'s [f64]) {
+        assert_eq!(
+            solution.len(),
+            self.n_dofs + self.n_multipliers(),
+            "Solution does not match the augmented system"
+        );
+        solution.split_at(self.n_dofs)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::function::Function;
+    use crate::discretizations::operators::stiffness_matrix;
+    use crate::solvers::sparse_direct::SparseLU;
+    use crate::spaces::lagrange::LagrangeElement;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_multipliers_tie() {
+        // Minimize |u|^2 / 2 - b . u with u_0 = u_2 and u_3 = 5
+        let identity = SparseCSR::new(4, (0..5).collect(), (0..4).collect(), vec![1.0; 4]);
+        let mut multipliers = LagrangeMultipliers::new(4);
+        multipliers.add_tie(&[0], &[2]);
+        let fixed = multipliers.add_dof_value(3, 5.0);
+        assert_eq!(fixed, 1, "Multipliers are numbered in order");
+        let merged = multipliers.add_constraint(&[(1, 1.0), (1, 1.0)], 0.0);
+        assert_eq!(
+            multipliers.constraint(merged).0,
+            &[(1, 2.0)],
+            "Entries were not merged"
+        );
+        let (matrix, rhs) = multipliers.augment(&identity, &[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(matrix.n_rows(), 7, "Wrong size of the augmented system");
+        let mut solution = vec![0.0; 7];
+        SparseLU::new(&matrix).unwrap().solve(&rhs, &mut solution);
+        let (u, l) = multipliers.split(&solution);
+        let expected = [2.0, 0.0, 2.0, 5.0];
+        for (u, e) in u.iter().zip(&expected) {
+            assert!((u - e).abs() < 1e-12, "Wrong constrained solution");
+        }
+        assert!((l[0] + 1.0).abs() < 1e-12, "Wrong tie reaction");
+        assert!((l[1] + 1.0).abs() < 1e-12, "Wrong value reaction");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_multipliers_mean_zero() {
+        // Pure Neumann problem -lap(u) = 2 pi^2 u of u = cos(pi x) cos(pi y), which has a zero mean
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![16, 16]).simplex_mesh();
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        let exact = |x: &[f64]| (PI * x[0]).cos() * (PI * x[1]).cos();
+        let assembler = space.assembler(QuadratureRule::simplex(2, 4));
+        let stiffness = stiffness_matrix(&assembler, |_| 1.0);
+        let mut rhs = vec![0.0; space.n_dofs()];
+        assembler.assemble_vector(&mut rhs, |values, local| {
+            for q in 0..values.n_points() {
+                let f = 2.0 * PI * PI * exact(values.point(q)) * values.weight(q);
+                for i in 0..values.n_dofs() {
+                    local[i] += f * values.shape_value(q, i);
+                }
+            }
+        });
+        let mut multipliers = LagrangeMultipliers::new(space.n_dofs());
+        multipliers.add_integral(&space, 0, 0.0);
+        let (matrix, augmented_rhs) = multipliers.augment(&stiffness, &rhs);
+        let mut solution = vec![0.0; matrix.n_rows()];
+        SparseLU::new(&matrix)
+            .expect("Augmented system should be invertible")
+            .solve(&augmented_rhs, &mut solution);
+        let (u, l) = multipliers.split(&solution);
+        assert!(l[0].abs() < 1e-8, "Compatible loads need no reaction");
+        let mut interpolant = Function::new(&space);
+        interpolant.interpolate(exact);
+        let error = u
+            .iter()
+            .zip(interpolant.values())
+            .map(|(u, e)| (u - e).abs())
+            .fold(0.0, f64::max);
+        assert!(error < 1e-3, "Wrong mean-zero solution, error {}", error);
+    }
+}
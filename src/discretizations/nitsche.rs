@@ -0,0 +1,219 @@
+use super::forms::{
+    coefficient, constant, dot, ds, facet_size, grad, normal, test, trial, Expr, Form,
+};
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Sign of the symmetry term of the Nitsche method
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NitscheVariant {
+    /// Symmetric terms, adjoint consistent but only coercive for penalties large enough
+    Symmetric,
+    /// Antisymmetric symmetry term, coercive for any non negative penalty (even without penalty)
+    NonSymmetric,
+    /// No symmetry term
+    Incomplete,
+}
+
+/// Nitsche weak imposition of the Dirichlet condition u = g of -div(k grad u) on the boundary
+/// facets carrying a tag
+///
+/// Instead of constraining the boundary dofs, the bilinear form is complemented on the facets by
+/// the consistency term -k grad(u) . n v, the symmetry term -s k grad(v) . n u and the penalty term
+/// penalty k / h u v, the right hand side by the matching terms in g. The exact solution satisfies
+/// the discrete equations so that the boundary conditions hold as accurately as the equation, for
+/// boundaries not matching the dofs (unfitted or curved boundaries) and contact. The symmetric
+/// variant needs penalties above a few times the square of the order of the element.
+#[derive(Clone)]
+pub struct Nitsche {
+    tag: usize,
+    diffusivity: Expr,
+    penalty: f64,
+    variant: NitscheVariant,
+}
+
+impl Nitsche {
+    /// Symmetric Nitsche terms on the facets carrying a tag for a unit diffusivity and a penalty of
+    /// 10
+    pub fn new(tag: usize) -> Self {
+        Nitsche {
+            tag,
+            diffusivity: constant(1.0),
+            penalty: 10.0,
+            variant: NitscheVariant::Symmetric,
+        }
+    }
+
+    /// Set a diffusivity varying with the physical coordinates
+    pub fn with_diffusivity<Coefficient>(mut self, diffusivity: Coefficient) -> Self
+    where
+        Coefficient: Fn(&[f64]) -> f64 + Send + Sync + 'static,
+    {
+        self.diffusivity = coefficient(diffusivity);
+        self
+    }
+
+    /// Set a constant diffusivity
+    pub fn with_constant_diffusivity(mut self, diffusivity: f64) -> Self {
+        self.diffusivity = constant(diffusivity);
+        self
+    }
+
+    /// Set the penalty, scaled by k / h on every facet
+    pub fn with_penalty(mut self, penalty: f64) -> Self {
+        assert!(penalty >= 0.0, "Nitsche penalty should not be negative");
+        self.penalty = penalty;
+        self
+    }
+
+    /// Set the variant of the symmetry term
+    pub fn with_variant(mut self, variant: NitscheVariant) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    /// Tag of the facets
+    pub fn tag(&self) -> usize {
+        self.tag
+    }
+
+    /// Bilinear facet terms to add to the form of the operator
+    pub fn bilinear(&self) -> Form {
+        let k = self.diffusivity.clone();
+        let mut integrand = self.penalty * k.clone() * trial() * test() / facet_size()
+            - k.clone() * dot(grad(trial()), normal()) * test();
+        if let Some(sign) = self.symmetry_sign() {
+            integrand = integrand - sign * k * trial() * dot(grad(test()), normal());
+        }
+        integrand * ds(self.tag)
+    }
+
+    /// Linear facet terms for the Dirichlet value g to add to the right hand side
+    pub fn linear<Value>(&self, g: Value) -> Form
+    where
+        Value: Fn(&[f64]) -> f64 + Send + Sync + 'static,
+    {
+        let (k, g) = (self.diffusivity.clone(), coefficient(g));
+        let mut integrand = self.penalty * k.clone() * g.clone() * test() / facet_size();
+        if let Some(sign) = self.symmetry_sign() {
+            integrand = integrand - sign * k * g * dot(grad(test()), normal());
+        }
+        integrand * ds(self.tag)
+    }
+
+    // Factor of the symmetry term, none when it is left out
+    fn symmetry_sign(&self) -> Option<f64> {
+        match self.variant {
+            NitscheVariant::Symmetric => Some(1.0),
+            NitscheVariant::NonSymmetric => Some(-1.0),
+            NitscheVariant::Incomplete => None,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::sparse_csr::SparseCSR;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::forms::dx;
+    use crate::discretizations::function::Function;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::discretizations::sparsity::SparsityPattern;
+    use crate::solvers::sparse_direct::SparseLU;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::spaces::quadrature::QuadratureRule;
+
+    #[test]
+    fn test_nitsche_quadratic() {
+        // Quadratic elements reproduce u = x^2 + x y of -div(2 grad u) = -4 without strong
+        // boundary conditions for every variant, the non symmetric one even without penalty
+        let mut mesh =
+            CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![3, 3]).simplex_mesh();
+        mesh.tag_boundary(1, |_| true);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        let assembler = space.assembler(QuadratureRule::simplex(2, 4));
+        let exact = |x: &[f64]| x[0] * x[0] + x[0] * x[1];
+        let mut u = Function::new(&space);
+        u.interpolate(exact);
+        let variants = [
+            (NitscheVariant::Symmetric, 20.0),
+            (NitscheVariant::NonSymmetric, 0.0),
+            (NitscheVariant::Incomplete, 20.0),
+        ];
+        for (variant, penalty) in variants {
+            let nitsche = Nitsche::new(1)
+                .with_constant_diffusivity(2.0)
+                .with_penalty(penalty)
+                .with_variant(variant);
+            let bilinear = 2.0 * dot(grad(trial()), grad(test())) * dx() + nitsche.bilinear();
+            let linear = constant(-4.0) * test() * dx() + nitsche.linear(exact);
+            let mut matrix = SparsityPattern::from_dofmap(space.dof_map()).to_csr(0.0);
+            bilinear.assemble_matrix(&assembler, &mut matrix);
+            let mut rhs = vec![0.0; space.n_dofs()];
+            linear.assemble_vector(&assembler, &mut rhs);
+            let mut solution = vec![0.0; space.n_dofs()];
+            SparseLU::new(&matrix)
+                .expect("Nitsche system should be invertible")
+                .solve(&rhs, &mut solution);
+            let error = solution
+                .iter()
+                .zip(u.values())
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f64::max);
+            assert!(error < 1e-10, "{:?} Nitsche error {}", variant, error);
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_nitsche_symmetry() {
+        let mut mesh =
+            CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![2, 2]).simplex_mesh();
+        mesh.tag_boundary(1, |x| x[0] == 0.0);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let assembler = space.assembler(QuadratureRule::simplex(2, 2));
+        let pattern = SparsityPattern::from_dofmap(space.dof_map());
+        let assemble = |variant| {
+            let nitsche = Nitsche::new(1)
+                .with_diffusivity(|x| 1.0 + x[1])
+                .with_variant(variant);
+            let mut matrix = pattern.to_csr(0.0);
+            nitsche.bilinear().assemble_matrix(&assembler, &mut matrix);
+            matrix
+        };
+        let symmetric = assemble(NitscheVariant::Symmetric);
+        let transpose = symmetric.transpose();
+        assert!(
+            symmetric
+                .values()
+                .iter()
+                .zip(transpose.values())
+                .all(|(a, b)| (a - b).abs() < 1e-14),
+            "Symmetric variant should give a symmetric matrix"
+        );
+        // On the left side u = 1 + x has u = 1 and grad(u) . n = -1, the symmetry terms cancel in
+        // the energy of the non symmetric variant which reduces to int 10 k / h for h = 1 / 2
+        let mut u = Function::new(&space);
+        u.interpolate(|x| 1.0 + x[0]);
+        let energy = |matrix: &SparseCSR<f64>| -> f64 {
+            let mut product = vec![0.0; space.n_dofs()];
+            matrix.apply(u.values(), &mut product);
+            product.iter().zip(u.values()).map(|(a, b)| a * b).sum()
+        };
+        assert!(
+            (energy(&assemble(NitscheVariant::NonSymmetric)) - 30.0).abs() < 1e-12,
+            "Wrong non symmetric energy"
+        );
+        assert!(
+            (energy(&symmetric) - 33.0).abs() < 1e-12,
+            "Wrong symmetric energy"
+        );
+    }
+}
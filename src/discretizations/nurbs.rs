@@ -0,0 +1,574 @@
+use super::assembler::add_local_matrix;
+use super::cell_mapping::invert;
+use super::dof_map::DofMap;
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::data_traits::DataContainer;
+use crate::core::arrays::data_wrap::DataWrap;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::spaces::bspline::{bernstein, KnotVector};
+use crate::spaces::quadrature::QuadratureRule;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Single patch isogeometric discretization with a tensor product NURBS basis
+///
+/// The basis functions are R_a = w_a N_a / sum_b w_b N_b where N_a are products of the B-splines of
+/// every parametric direction. The same basis describes the geometry, x = sum_a R_a P_a with the
+/// control points P_a, and the unknowns. Basis functions and control points are numbered
+/// lexicographically with the first direction running fastest, as are the elements (products of
+/// the non empty knot spans). The DofMap of the patch gives the sparsity of the global matrices.
+pub struct NurbsPatch {
+    knots: Vec<KnotVector>,
+    control_points: DataHold<f64, [usize; 2]>,
+    weights: Vec<f64>,
+    extraction: Vec<Vec<Vec<f64>>>,
+    dof_map: DofMap,
+}
+
+/// Structure holding the values of the NURBS basis at the quadrature points of an element of a
+/// patch
+///
+/// The Bernstein polynomials are evaluated once on the reference element [0, 1]^d and every reinit
+/// recovers the B-splines of the element by Bézier extraction before applying the weights and the
+/// geometric mapping.
+pub struct PatchValues {
+    dim: usize,
+    n_dofs: usize,
+    element: usize,
+    n_points_1d: usize,
+    reference_weights: Vec<f64>,
+    bernstein_values: Vec<Vec<f64>>,
+    bernstein_derivatives: Vec<Vec<f64>>,
+    values: Vec<f64>,
+    gradients: Vec<f64>,
+    weights: Vec<f64>,
+    points: Vec<f64>,
+}
+
+impl NurbsPatch {
+    /// Build a patch from the knot vectors of every direction, the control points and their
+    /// weights
+    pub fn new(
+        knots: Vec<KnotVector>,
+        control_points: DataHold<f64, [usize; 2]>,
+        weights: Vec<f64>,
+    ) -> Self {
+        let dim = knots.len();
+        let n_basis: usize = knots.iter().map(|k| k.n_basis()).product();
+        assert!(
+            (1..=3).contains(&dim),
+            "Patches should have 1, 2 or 3 parametric directions"
+        );
+        assert!(
+            control_points.dimensions() == &[n_basis, dim],
+            "Control points do not match the basis and the dimension of the patch"
+        );
+        assert!(
+            weights.len() == n_basis && weights.iter().all(|w| *w > 0.0),
+            "Every control point needs a positive weight"
+        );
+        let extraction = knots.iter().map(|k| k.bezier_extraction()).collect();
+        let mut patch = NurbsPatch {
+            knots,
+            control_points,
+            weights,
+            extraction,
+            dof_map: DofMap::new(DataHold::new(vec![], [0, 0]), 0),
+        };
+        let n_elements = patch.n_elements();
+        let dofs_per_element: usize = patch.knots.iter().map(|k| k.degree() + 1).product();
+        let mut cell_dofs = Vec::with_capacity(n_elements * dofs_per_element);
+        for element in 0..n_elements {
+            cell_dofs.extend(patch.element_basis(element));
+        }
+        patch.dof_map = DofMap::new(
+            DataHold::new(cell_dofs, [n_elements, dofs_per_element]),
+            n_basis,
+        );
+        patch
+    }
+
+    /// Build a polynomial B-spline patch (all weights equal to one)
+    pub fn bspline(knots: Vec<KnotVector>, control_points: DataHold<f64, [usize; 2]>) -> Self {
+        let n_basis = control_points.dimensions()[0];
+        NurbsPatch::new(knots, control_points, vec![1.0; n_basis])
+    }
+
+    /// Number of parametric (and physical) directions
+    pub fn dim(&self) -> usize {
+        self.knots.len()
+    }
+
+    /// Knot vector of a parametric direction
+    pub fn knots(&self, direction: usize) -> &KnotVector {
+        &self.knots[direction]
+    }
+
+    /// Number of basis functions
+    pub fn n_basis(&self) -> usize {
+        self.dof_map.n_dofs()
+    }
+
+    /// Number of elements
+    pub fn n_elements(&self) -> usize {
+        self.knots.iter().map(|k| k.n_elements()).product()
+    }
+
+    /// Coordinates of a control point
+    pub fn control_point(&self, basis: usize) -> &[f64] {
+        let dim = self.dim();
+        &self.control_points[basis * dim..(basis + 1) * dim]
+    }
+
+    /// Weight of a control point
+    pub fn weight(&self, basis: usize) -> f64 {
+        self.weights[basis]
+    }
+
+    /// Numbering of the basis functions on the elements
+    pub fn dof_map(&self) -> &DofMap {
+        &self.dof_map
+    }
+
+    /// Position of an element along every direction
+    pub fn element_multi_index(&self, element: usize) -> Vec<usize> {
+        let mut rest = element;
+        self.knots
+            .iter()
+            .map(|k| {
+                let index = rest % k.n_elements();
+                rest /= k.n_elements();
+                index
+            })
+            .collect()
+    }
+
+    /// Map a parametric point of the patch to the physical space
+    pub fn map_point(&self, parametric: &[f64], physical: &mut [f64]) {
+        let dim = self.dim();
+        let mut factors = Vec::with_capacity(dim);
+        let mut first = Vec::with_capacity(dim);
+        for (knots, x) in self.knots.iter().zip(parametric.iter()) {
+            let mut values = vec![0.0; knots.degree() + 1];
+            let span = knots.basis(*x, &mut values, &mut vec![0.0; knots.degree() + 1]);
+            first.push(span - knots.degree());
+            factors.push(values);
+        }
+        physical.iter_mut().for_each(|x| *x = 0.0);
+        let mut total = 0.0;
+        for local in 0..factors.iter().map(|f| f.len()).product() {
+            let (value, basis) = self.tensor_basis(local, &factors, &first);
+            let weighted = value * self.weights[basis];
+            total += weighted;
+            for (x, p) in physical.iter_mut().zip(self.control_point(basis).iter()) {
+                *x += weighted * p;
+            }
+        }
+        physical.iter_mut().for_each(|x| *x /= total);
+    }
+
+    /// Add the contributions of the kernel on every element to a global matrix
+    ///
+    /// The kernel receives the PatchValues of the element built with a tensor product of the one
+    /// dimensional rule and a (dofs per element, dofs per element) local matrix to fill.
+    pub fn assemble_matrix<Kernel>(
+        &self,
+        quadrature: &QuadratureRule,
+        matrix: &mut SparseCSR<f64>,
+        mut kernel: Kernel,
+    ) where
+        Kernel: FnMut(&PatchValues, &mut DataWrap<f64, [usize; 2]>),
+    {
+        assert!(
+            matrix.n_rows() == self.n_basis() && matrix.n_cols() == self.n_basis(),
+            "Global matrix does not match the number of basis functions"
+        );
+        let mut values = PatchValues::new(self, quadrature);
+        let n = values.n_dofs();
+        let mut local = vec![0.0; n * n];
+        for element in 0..self.n_elements() {
+            values.reinit(self, element);
+            local.iter_mut().for_each(|v| *v = 0.0);
+            kernel(&values, &mut DataWrap::new(&mut local, [n, n]));
+            add_local_matrix(matrix, self.dof_map.cell_dofs(element), &local);
+        }
+    }
+
+    /// Add the contributions of the kernel on every element to a global vector
+    pub fn assemble_vector<Kernel>(
+        &self,
+        quadrature: &QuadratureRule,
+        vector: &mut [f64],
+        mut kernel: Kernel,
+    ) where
+        Kernel: FnMut(&PatchValues, &mut DataWrap<f64, [usize; 1]>),
+    {
+        assert!(
+            vector.len() == self.n_basis(),
+            "Global vector does not match the number of basis functions"
+        );
+        let mut values = PatchValues::new(self, quadrature);
+        let n = values.n_dofs();
+        let mut local = vec![0.0; n];
+        for element in 0..self.n_elements() {
+            values.reinit(self, element);
+            local.iter_mut().for_each(|v| *v = 0.0);
+            kernel(&values, &mut DataWrap::new(&mut local, [n]));
+            for (dof, v) in self.dof_map.cell_dofs(element).iter().zip(local.iter()) {
+                vector[*dof] += v;
+            }
+        }
+    }
+
+    // Global basis functions not vanishing on an element in the local lexicographic order
+    fn element_basis(&self, element: usize) -> Vec<usize> {
+        let multi_index = self.element_multi_index(element);
+        let first: Vec<usize> = self
+            .knots
+            .iter()
+            .zip(multi_index.iter())
+            .map(|(k, e)| k.element_span(*e) - k.degree())
+            .collect();
+        let n_local: usize = self.knots.iter().map(|k| k.degree() + 1).product();
+        (0..n_local)
+            .map(|local| {
+                let mut rest = local;
+                let mut stride = 1;
+                let mut basis = 0;
+                for (k, f) in self.knots.iter().zip(first.iter()) {
+                    basis += (f + rest % (k.degree() + 1)) * stride;
+                    rest /= k.degree() + 1;
+                    stride *= k.n_basis();
+                }
+                basis
+            })
+            .collect()
+    }
+
+    // Value of the local tensor product function and its global index from the one dimensional
+    // factors
+    fn tensor_basis(&self, local: usize, factors: &[Vec<f64>], first: &[usize]) -> (f64, usize) {
+        let mut rest = local;
+        let mut stride = 1;
+        let mut basis = 0;
+        let mut value = 1.0;
+        for ((k, factor), f) in self.knots.iter().zip(factors.iter()).zip(first.iter()) {
+            let a = rest % factor.len();
+            rest /= factor.len();
+            value *= factor[a];
+            basis += (f + a) * stride;
+            stride *= k.n_basis();
+        }
+        (value, basis)
+    }
+}
+
+impl PatchValues {
+    /// Evaluate the Bernstein polynomials of a patch at the points of a one dimensional rule
+    pub fn new(patch: &NurbsPatch, quadrature: &QuadratureRule) -> Self {
+        assert!(
+            quadrature.dim() == 1,
+            "Patch values are built from a one dimensional rule"
+        );
+        let dim = patch.dim();
+        let n_points_1d = quadrature.n_points();
+        let n_points = n_points_1d.pow(dim as u32);
+        let n_dofs = patch.dof_map.dofs_per_cell();
+        let mut bernstein_values = Vec::with_capacity(dim);
+        let mut bernstein_derivatives = Vec::with_capacity(dim);
+        for knots in patch.knots.iter() {
+            let n = knots.degree() + 1;
+            let mut values = vec![0.0; n_points_1d * n];
+            let mut derivatives = vec![0.0; n_points_1d * n];
+            for q in 0..n_points_1d {
+                bernstein(
+                    knots.degree(),
+                    quadrature.point(q)[0],
+                    &mut values[q * n..(q + 1) * n],
+                    &mut derivatives[q * n..(q + 1) * n],
+                );
+            }
+            bernstein_values.push(values);
+            bernstein_derivatives.push(derivatives);
+        }
+        let reference_weights = (0..n_points)
+            .map(|q| {
+                (0..dim)
+                    .map(|d| quadrature.weights()[(q / n_points_1d.pow(d as u32)) % n_points_1d])
+                    .product()
+            })
+            .collect();
+        PatchValues {
+            dim,
+            n_dofs,
+            element: 0,
+            n_points_1d,
+            reference_weights,
+            bernstein_values,
+            bernstein_derivatives,
+            values: vec![0.0; n_points * n_dofs],
+            gradients: vec![0.0; n_points * n_dofs * dim],
+            weights: vec![0.0; n_points],
+            points: vec![0.0; n_points * dim],
+        }
+    }
+
+    /// Update the basis values and the physical quantities for an element of the patch
+    pub fn reinit(&mut self, patch: &NurbsPatch, element: usize) {
+        let dim = self.dim;
+        let n_dofs = self.n_dofs;
+        self.element = element;
+        let multi_index = patch.element_multi_index(element);
+        let basis = patch.element_basis(element);
+        let mut scale = 1.0;
+        for (knots, e) in patch.knots.iter().zip(multi_index.iter()) {
+            let (a, b) = knots.element_bounds(*e);
+            scale *= b - a;
+        }
+        let mut splines = vec![0.0; n_dofs];
+        let mut spline_gradients = vec![0.0; n_dofs * dim];
+        let mut jacobian = vec![0.0; dim * dim];
+        let mut inverse = vec![0.0; dim * dim];
+        for q in 0..self.n_points() {
+            // One dimensional B-splines and derivatives with respect to the parameter
+            let mut factors = Vec::with_capacity(dim);
+            let mut derivatives = Vec::with_capacity(dim);
+            for (d, (knots, e)) in patch.knots.iter().zip(multi_index.iter()).enumerate() {
+                let n = knots.degree() + 1;
+                let (a, b) = knots.element_bounds(*e);
+                let operator = &patch.extraction[d][*e];
+                let q_d = (q / self.n_points_1d.pow(d as u32)) % self.n_points_1d;
+                let values = &self.bernstein_values[d][q_d * n..(q_d + 1) * n];
+                let slopes = &self.bernstein_derivatives[d][q_d * n..(q_d + 1) * n];
+                let extract = |bernsteins: &[f64], i: usize| -> f64 {
+                    (0..n).map(|j| operator[i * n + j] * bernsteins[j]).sum()
+                };
+                factors.push((0..n).map(|i| extract(values, i)).collect::<Vec<f64>>());
+                derivatives.push(
+                    (0..n)
+                        .map(|i| extract(slopes, i) / (b - a))
+                        .collect::<Vec<f64>>(),
+                );
+            }
+            // Weighted tensor products
+            let mut total = 0.0;
+            let mut total_gradient = vec![0.0; dim];
+            for local in 0..n_dofs {
+                let weight = patch.weights[basis[local]];
+                let mut rest = local;
+                let mut indices = Vec::with_capacity(dim);
+                for factor in factors.iter() {
+                    indices.push(rest % factor.len());
+                    rest /= factor.len();
+                }
+                let value: f64 = (0..dim).map(|d| factors[d][indices[d]]).product();
+                splines[local] = weight * value;
+                total += weight * value;
+                for k in 0..dim {
+                    let derivative: f64 = (0..dim)
+                        .map(|d| {
+                            if d == k {
+                                derivatives[d][indices[d]]
+                            } else {
+                                factors[d][indices[d]]
+                            }
+                        })
+                        .product();
+                    spline_gradients[local * dim + k] = weight * derivative;
+                    total_gradient[k] += weight * derivative;
+                }
+            }
+            // Rational functions, geometry and jacobian
+            let point = &mut self.points[q * dim..(q + 1) * dim];
+            point.iter_mut().for_each(|x| *x = 0.0);
+            jacobian.iter_mut().for_each(|j| *j = 0.0);
+            for local in 0..n_dofs {
+                let value = splines[local] / total;
+                self.values[q * n_dofs + local] = value;
+                let control_point = patch.control_point(basis[local]);
+                for k in 0..dim {
+                    let derivative =
+                        (spline_gradients[local * dim + k] - value * total_gradient[k]) / total;
+                    spline_gradients[local * dim + k] = derivative;
+                    for i in 0..dim {
+                        jacobian[i * dim + k] += derivative * control_point[i];
+                    }
+                }
+                for (x, p) in point.iter_mut().zip(control_point.iter()) {
+                    *x += value * p;
+                }
+            }
+            let det = invert(dim, &jacobian, &mut inverse);
+            self.weights[q] = self.reference_weights[q] * scale * det.abs();
+            // Physical gradients are J^-T times the parametric ones
+            for local in 0..n_dofs {
+                let offset = (q * n_dofs + local) * dim;
+                for i in 0..dim {
+                    self.gradients[offset + i] = (0..dim)
+                        .map(|k| inverse[k * dim + i] * spline_gradients[local * dim + k])
+                        .sum();
+                }
+            }
+        }
+    }
+
+    /// Index of the element the values were last computed on
+    pub fn element(&self) -> usize {
+        self.element
+    }
+
+    /// Dimension of the patch
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Number of quadrature points
+    pub fn n_points(&self) -> usize {
+        self.weights.len()
+    }
+
+    /// Number of basis functions not vanishing on an element
+    pub fn n_dofs(&self) -> usize {
+        self.n_dofs
+    }
+
+    /// Value of basis function i at quadrature point q
+    pub fn shape_value(&self, q: usize, i: usize) -> f64 {
+        self.values[q * self.n_dofs + i]
+    }
+
+    /// Physical gradient of basis function i at quadrature point q
+    pub fn shape_gradient(&self, q: usize, i: usize) -> &[f64] {
+        let offset = (q * self.n_dofs + i) * self.dim;
+        &self.gradients[offset..offset + self.dim]
+    }
+
+    /// Integration weight of quadrature point q including the jacobian determinant
+    pub fn weight(&self, q: usize) -> f64 {
+        self.weights[q]
+    }
+
+    /// Physical coordinates of quadrature point q
+    pub fn point(&self, q: usize) -> &[f64] {
+        &self.points[q * self.dim..(q + 1) * self.dim]
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::data_traits::DataMutator;
+    use crate::discretizations::sparsity::SparsityPattern;
+
+    fn mass_kernel(values: &PatchValues, local: &mut DataWrap<f64, [usize; 2]>) {
+        for q in 0..values.n_points() {
+            for i in 0..values.n_dofs() {
+                for j in 0..values.n_dofs() {
+                    *local.multi_index_mut([i, j]) +=
+                        values.shape_value(q, i) * values.shape_value(q, j) * values.weight(q);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_nurbs_quarter_annulus() {
+        // Exact quarter circles of radius 1 and 2 (quadratic along the angle, linear along the
+        // radius)
+        let angular = KnotVector::new(2, vec![0.0, 0.0, 0.0, 1.0, 1.0, 1.0]);
+        let radial = KnotVector::new(1, vec![0.0, 0.0, 1.0, 1.0]);
+        let mut points = Vec::new();
+        let mut weights = Vec::new();
+        for r in [1.0, 2.0] {
+            points.extend_from_slice(&[r, 0.0, r, r, 0.0, r]);
+            weights.extend_from_slice(&[1.0, 0.5f64.sqrt(), 1.0]);
+        }
+        let patch = NurbsPatch::new(
+            vec![angular, radial],
+            DataHold::new(points, [6, 2]),
+            weights,
+        );
+        assert_eq!(patch.n_elements(), 1, "Wrong number of elements");
+        let mut x = [0.0; 2];
+        patch.map_point(&[0.3, 0.0], &mut x);
+        assert!(
+            ((x[0] * x[0] + x[1] * x[1]).sqrt() - 1.0).abs() < 1e-14,
+            "Inner boundary should be a circle"
+        );
+        // Rational basis sums to one so the sum of the mass matrix is the area
+        let mut mass = SparsityPattern::from_dofmap(patch.dof_map()).to_csr(0.0);
+        patch.assemble_matrix(&QuadratureRule::gauss_legendre(8), &mut mass, mass_kernel);
+        let area: f64 = mass.values().iter().sum();
+        assert!(
+            (area - 0.75 * std::f64::consts::PI).abs() < 1e-10,
+            "Wrong area {} of the quarter annulus",
+            area
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_bspline_stiffness() {
+        // Control points at the Greville abscissae reproduce the identity map
+        let knots = KnotVector::open_uniform(2, 3);
+        let greville: Vec<f64> = (0..knots.n_basis())
+            .map(|i| 0.5 * (knots.knots()[i + 1] + knots.knots()[i + 2]))
+            .collect();
+        let mut points = Vec::new();
+        for y in greville.iter() {
+            for x in greville.iter() {
+                points.extend_from_slice(&[*x, *y]);
+            }
+        }
+        let n = greville.len() * greville.len();
+        let patch = NurbsPatch::bspline(
+            vec![KnotVector::open_uniform(2, 3), knots],
+            DataHold::new(points, [n, 2]),
+        );
+        let mut stiffness = SparsityPattern::from_dofmap(patch.dof_map()).to_csr(0.0);
+        patch.assemble_matrix(
+            &QuadratureRule::gauss_legendre(3),
+            &mut stiffness,
+            |values, local| {
+                for q in 0..values.n_points() {
+                    for i in 0..values.n_dofs() {
+                        for j in 0..values.n_dofs() {
+                            let grad_i = values.shape_gradient(q, i);
+                            let grad_j = values.shape_gradient(q, j);
+                            *local.multi_index_mut([i, j]) +=
+                                (grad_i[0] * grad_j[0] + grad_i[1] * grad_j[1]) * values.weight(q);
+                        }
+                    }
+                }
+            },
+        );
+        // The field u = x has coefficients given by the control points
+        let u: Vec<f64> = (0..n).map(|a| patch.control_point(a)[0]).collect();
+        let mut ku = vec![0.0; n];
+        stiffness.apply(&u, &mut ku);
+        let energy: f64 = u.iter().zip(ku.iter()).map(|(u, k)| u * k).sum();
+        assert!((energy - 1.0).abs() < 1e-12, "Wrong energy {}", energy);
+        let mut rhs = vec![0.0; n];
+        patch.assemble_vector(
+            &QuadratureRule::gauss_legendre(3),
+            &mut rhs,
+            |values, local| {
+                for q in 0..values.n_points() {
+                    for i in 0..values.n_dofs() {
+                        local[i] += values.shape_value(q, i) * values.weight(q);
+                    }
+                }
+            },
+        );
+        assert!(
+            (rhs.iter().sum::<f64>() - 1.0).abs() < 1e-12,
+            "Basis should integrate to the area of the square"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use super::assembler::Assembler;
+use super::sparsity::SparsityPattern;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::solvers::linear_operator::Diagonal;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Schemes lumping the mass matrix into a diagonal one of the same total mass
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lumping {
+    /// Sums of the rows of the consistent mass matrix, which vanish or become negative at the
+    /// vertices of quadratic simplices
+    RowSum,
+    /// Diagonal of the consistent mass matrix scaled on every cell to keep the mass of the cell
+    /// (Hinton, Rock and Zienkiewicz), which stays positive for every order
+    Hrz,
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Assemble the mass matrix M_ij = int phi_j phi_i
+pub fn mass_matrix(assembler: &Assembler) -> SparseCSR<f64> {
+    let mut matrix = SparsityPattern::from_dofmap(assembler.dof_map()).to_csr(0.0);
+    assembler.assemble_matrix(&mut matrix, |values, local| {
+        let n = values.n_dofs();
+        for q in 0..values.n_points() {
+            for i in 0..n {
+                let vi = values.shape_value(q, i) * values.weight(q);
+                for j in 0..n {
+                    local[i * n + j] += vi * values.shape_value(q, j);
+                }
+            }
+        }
+    });
+    matrix
+}
+
+/// Assemble a lumped mass matrix, whose inverse is applied entrywise by explicit time integrators
+pub fn lumped_mass_matrix(assembler: &Assembler, lumping: Lumping) -> Diagonal {
+    let mut diagonal = vec![0.0; assembler.dof_map().n_dofs()];
+    assembler.assemble_vector(&mut diagonal, |values, local| {
+        let n = values.n_dofs();
+        match lumping {
+            // Shape functions sum to one so that the row sums are the integrals of the shapes
+            Lumping::RowSum => {
+                for q in 0..values.n_points() {
+                    for i in 0..n {
+                        local[i] += values.shape_value(q, i) * values.weight(q);
+                    }
+                }
+            }
+            Lumping::Hrz => {
+                let mut cell_mass = 0.0;
+                for q in 0..values.n_points() {
+                    cell_mass += values.weight(q);
+                    for i in 0..n {
+                        local[i] += values.shape_value(q, i).powi(2) * values.weight(q);
+                    }
+                }
+                let scale = cell_mass / local.iter().sum::<f64>();
+                local.iter_mut().for_each(|m| *m *= scale);
+            }
+        }
+    });
+    Diagonal::new(diagonal)
+}
+
+/// Assemble the stiffness matrix K_ij = int k grad(phi_j) . grad(phi_i) for a scalar coefficient k
+/// of the physical coordinates
+pub fn stiffness_matrix<Coefficient>(
+    assembler: &Assembler,
+    coefficient: Coefficient,
+) -> SparseCSR<f64>
+where
+    Coefficient: Fn(&[f64]) -> f64,
+{
+    let mut matrix = SparsityPattern::from_dofmap(assembler.dof_map()).to_csr(0.0);
+    assembler.assemble_matrix(&mut matrix, |values, local| {
+        let n = values.n_dofs();
+        for q in 0..values.n_points() {
+            let scale = coefficient(values.point(q)) * values.weight(q);
+            for i in 0..n {
+                let gi = values.shape_gradient(q, i);
+                for j in 0..n {
+                    let dot: f64 = gi
+                        .iter()
+                        .zip(values.shape_gradient(q, j).iter())
+                        .map(|(a, b)| a * b)
+                        .sum();
+                    local[i * n + j] += scale * dot;
+                }
+            }
+        }
+    });
+    matrix
+}
+
+/// Assemble the advection matrix C_ij = int (b . grad(phi_j)) phi_i for a velocity field b
+///
+/// The velocity fills a slice of the dimension of the mesh at the given physical coordinates.
+pub fn advection_matrix<Velocity>(assembler: &Assembler, velocity: Velocity) -> SparseCSR<f64>
+where
+    Velocity: Fn(&[f64], &mut [f64]),
+{
+    let mut matrix = SparsityPattern::from_dofmap(assembler.dof_map()).to_csr(0.0);
+    let mut b = vec![0.0; assembler.element().dim()];
+    assembler.assemble_matrix(&mut matrix, |values, local| {
+        let n = values.n_dofs();
+        for q in 0..values.n_points() {
+            velocity(values.point(q), &mut b);
+            for j in 0..n {
+                let flux: f64 = b
+                    .iter()
+                    .zip(values.shape_gradient(q, j).iter())
+                    .map(|(a, g)| a * g)
+                    .sum::<f64>()
+                    * values.weight(q);
+                for i in 0..n {
+                    local[i * n + j] += flux * values.shape_value(q, i);
+                }
+            }
+        }
+    });
+    matrix
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::dof_map::DofMap;
+    use crate::discretizations::test_meshes::unit_square;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::spaces::quadrature::QuadratureRule;
+
+    // Product of a matrix with a vector
+    fn apply(matrix: &SparseCSR<f64>, vector: &[f64]) -> Vec<f64> {
+        (0..matrix.n_rows())
+            .map(|row| {
+                let (cols, vals) = matrix.row(row);
+                cols.iter()
+                    .zip(vals.iter())
+                    .map(|(c, v)| v * vector[*c])
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_operators_mass_and_stiffness() {
+        let mesh = unit_square();
+        let element = LagrangeElement::new(2, 1);
+        let dof_map = DofMap::lagrange(&mesh, &element);
+        let assembler = Assembler::new(&mesh, &element, &dof_map, QuadratureRule::simplex(2, 2));
+        let mass = mass_matrix(&assembler);
+        let total: f64 = mass.values().iter().sum();
+        assert!((total - 1.0).abs() < 1e-14, "Mass should sum to the area");
+        let stiffness = stiffness_matrix(&assembler, |_| 2.0);
+        assert!(
+            (stiffness.get(0, 0).unwrap() - 2.0).abs() < 1e-14,
+            "Wrong scaled diagonal entry"
+        );
+        for row in 0..stiffness.n_rows() {
+            let sum: f64 = stiffness.row(row).1.iter().sum();
+            assert!(sum.abs() < 1e-14, "Stiffness rows should sum to zero");
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_operators_lumped_mass() {
+        let mesh = unit_square();
+        let element = LagrangeElement::new(2, 2);
+        let dof_map = DofMap::lagrange(&mesh, &element);
+        let assembler = Assembler::new(&mesh, &element, &dof_map, QuadratureRule::simplex(2, 4));
+        let consistent = apply(&mass_matrix(&assembler), &vec![1.0; dof_map.n_dofs()]);
+        let row_sum = lumped_mass_matrix(&assembler, Lumping::RowSum);
+        for (lumped, sum) in row_sum.values().iter().zip(&consistent) {
+            assert!((lumped - sum).abs() < 1e-14, "Wrong row sum");
+        }
+        // Row sums vanish at the vertices of quadratic triangles
+        assert!(
+            row_sum.values()[0].abs() < 1e-14,
+            "Vertex row sum should vanish"
+        );
+        let hrz = lumped_mass_matrix(&assembler, Lumping::Hrz);
+        let total: f64 = hrz.values().iter().sum();
+        assert!((total - 1.0).abs() < 1e-14, "HRZ should keep the area");
+        assert!(
+            hrz.values().iter().all(|m| *m > 0.0),
+            "HRZ masses should be positive"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_operators_advection() {
+        let mesh = unit_square();
+        let element = LagrangeElement::new(2, 1);
+        let dof_map = DofMap::lagrange(&mesh, &element);
+        let assembler = Assembler::new(&mesh, &element, &dof_map, QuadratureRule::simplex(2, 2));
+        let advection = advection_matrix(&assembler, |_, b| {
+            b[0] = 1.0;
+            b[1] = 2.0;
+        });
+        // Constants are not transported and u = x + y has b . grad(u) = 3
+        let constant = apply(&advection, &[1.0; 4]);
+        assert!(
+            constant.iter().all(|v| v.abs() < 1e-14),
+            "Constants should not be advected"
+        );
+        let linear: Vec<f64> = (0..mesh.n_vertices())
+            .map(|v| mesh.vertex(v).iter().sum())
+            .collect();
+        let total: f64 = apply(&advection, &linear).iter().sum();
+        assert!((total - 3.0).abs() < 1e-14, "Wrong advection of x + y");
+    }
+}
@@ -0,0 +1,654 @@
+use super::facets::Facets;
+use super::mesh::Mesh;
+use crate::core::arrays::data_hold::DataHold;
+use std::collections::{HashMap, VecDeque};
+#[cfg(feature = "metis")]
+use std::io::{Error, Result};
+
+// Bits of the quantized coordinates interleaved in the Morton keys
+const MORTON_BITS: u32 = 63;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Method splitting the cells of a mesh into parts of nearly equal sizes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PartitionMethod {
+    /// Recursive bisection of the cell centroids at the median along the longest side of their box
+    CoordinateBisection,
+    /// Contiguous chunks of the cells ordered along the Morton curve through their centroids
+    SpaceFillingCurve,
+    /// Recursive bisection of the dual graph (cells sharing a facet) at the median of the breadth
+    /// first level sets grown from a pseudo peripheral cell
+    GraphBisection,
+}
+
+/// Assignment of the cells of a mesh to a number of parts
+///
+/// Partitions are computed by the native methods, by METIS with the metis feature, or given
+/// directly by the part of every cell when they come from another partitioner.
+pub struct Partition {
+    n_parts: usize,
+    parts: Vec<usize>,
+}
+
+/// Part of a mesh extracted with layers of ghost cells around it
+///
+/// The local mesh holds the owned cells first followed by the ghost cells layer by layer, ghost
+/// cells being the cells sharing a vertex with the previous layer. Local vertices are numbered in
+/// the order they appear in the local cells. Every vertex is owned by the lowest part among the
+/// parts of the cells touching it. The facet and vertex tags of the mesh are kept.
+pub struct Subdomain {
+    part: usize,
+    mesh: Mesh,
+    cells: Vec<usize>,
+    vertices: Vec<usize>,
+    n_owned_cells: usize,
+    cell_owners: Vec<usize>,
+    vertex_owners: Vec<usize>,
+}
+
+impl Partition {
+    /// Split the cells of a mesh into parts
+    pub fn new(mesh: &Mesh, n_parts: usize, method: PartitionMethod) -> Self {
+        assert!(n_parts > 0, "Tried to partition a mesh in no part");
+        let centroids = centroids(mesh);
+        let dim = mesh.geometric_dim();
+        let mut parts = vec![0; mesh.n_cells()];
+        let mut cells: Vec<usize> = (0..mesh.n_cells()).collect();
+        match method {
+            PartitionMethod::CoordinateBisection => {
+                bisect(&mut cells, 0, n_parts, &mut parts, &mut |cells, split| {
+                    order_by_coordinates(cells, split, dim, &centroids)
+                })
+            }
+            PartitionMethod::SpaceFillingCurve => {
+                let keys = morton_keys(dim, &centroids);
+                cells.sort_by_key(|c| keys[*c]);
+                let n_cells = cells.len();
+                for (i, cell) in cells.iter().enumerate() {
+                    parts[*cell] = i * n_parts / n_cells;
+                }
+            }
+            PartitionMethod::GraphBisection => {
+                let graph = dual_graph(mesh);
+                let mut inside = vec![false; mesh.n_cells()];
+                bisect(&mut cells, 0, n_parts, &mut parts, &mut |cells, _| {
+                    order_by_levels(cells, &graph, &mut inside)
+                })
+            }
+        }
+        Partition { n_parts, parts }
+    }
+
+    /// Split the cells of a mesh into parts by the multilevel k-way partitioning of METIS on its
+    /// dual graph (cells sharing a facet), called with its default options
+    ///
+    /// METIS has to be built with 32 bit indices. An error is returned if the mesh is too large
+    /// for them or if METIS fails.
+    #[cfg(feature = "metis")]
+    pub fn metis(mesh: &Mesh, n_parts: usize) -> Result<Self> {
+        assert!(n_parts > 0, "Tried to partition a mesh in no part");
+        // METIS does not handle a single part
+        if n_parts == 1 {
+            return Ok(Partition {
+                n_parts,
+                parts: vec![0; mesh.n_cells()],
+            });
+        }
+        let index = |i: usize| {
+            ffi::idx_t::try_from(i)
+                .map_err(|_| Error::other("Mesh too large for the 32 bit indices of METIS"))
+        };
+        let graph = dual_graph(mesh);
+        let mut offsets = vec![0];
+        let mut adjacency = Vec::new();
+        for neighbours in &graph {
+            for neighbour in neighbours {
+                adjacency.push(index(*neighbour)?);
+            }
+            offsets.push(index(adjacency.len())?);
+        }
+        let mut n_vertices = index(graph.len())?;
+        let mut n_constraints = 1;
+        let mut n_metis_parts = index(n_parts)?;
+        let mut edge_cut = 0;
+        let mut parts = vec![0; graph.len()];
+        let null = std::ptr::null_mut();
+        let code = unsafe {
+            ffi::METIS_PartGraphKway(
+                &mut n_vertices,
+                &mut n_constraints,
+                offsets.as_mut_ptr(),
+                adjacency.as_mut_ptr(),
+                null,
+                null,
+                null,
+                &mut n_metis_parts,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                null,
+                &mut edge_cut,
+                parts.as_mut_ptr(),
+            )
+        };
+        if code != ffi::METIS_OK {
+            return Err(Error::other(format!(
+                "METIS partitioning failed with error code {}",
+                code
+            )));
+        }
+        Ok(Partition {
+            n_parts,
+            parts: parts.into_iter().map(|p| p as usize).collect(),
+        })
+    }
+
+    /// Partition given by the part of every cell
+    pub fn from_parts(n_parts: usize, parts: Vec<usize>) -> Self {
+        assert!(
+            parts.iter().all(|p| *p < n_parts),
+            "Tried to build a Partition with cells in non existing parts"
+        );
+        Partition { n_parts, parts }
+    }
+
+    /// Number of parts
+    pub fn n_parts(&self) -> usize {
+        self.n_parts
+    }
+
+    /// Part of a cell
+    pub fn part(&self, cell: usize) -> usize {
+        self.parts[cell]
+    }
+
+    /// Parts of all the cells
+    pub fn parts(&self) -> &[usize] {
+        &self.parts
+    }
+
+    /// Cells of a part
+    pub fn cells(&self, part: usize) -> Vec<usize> {
+        (0..self.parts.len())
+            .filter(|c| self.parts[*c] == part)
+            .collect()
+    }
+
+    /// Number of interior facets between cells of different parts
+    pub fn edge_cut(&self, mesh: &Mesh) -> usize {
+        let facets = Facets::new(mesh);
+        (0..facets.n_facets())
+            .filter(|f| match facets.facet_cells(*f) {
+                [(a, _), (b, _)] => self.parts[*a] != self.parts[*b],
+                _ => false,
+            })
+            .count()
+    }
+
+    /// Extract a part of the mesh with a number of layers of ghost cells
+    pub fn subdomain(&self, mesh: &Mesh, part: usize, ghost_layers: usize) -> Subdomain {
+        assert_eq!(
+            self.parts.len(),
+            mesh.n_cells(),
+            "Partition does not match the mesh"
+        );
+        let mut vertex_cells = vec![Vec::new(); mesh.n_vertices()];
+        for cell in 0..mesh.n_cells() {
+            for vertex in mesh.cell(cell) {
+                vertex_cells[*vertex].push(cell);
+            }
+        }
+        let mut included = vec![false; mesh.n_cells()];
+        let mut cells = self.cells(part);
+        cells.iter().for_each(|c| included[*c] = true);
+        let n_owned_cells = cells.len();
+        let mut layer_start = 0;
+        for _ in 0..ghost_layers {
+            let layer_end = cells.len();
+            for c in layer_start..layer_end {
+                for vertex in mesh.cell(cells[c]) {
+                    for neighbour in &vertex_cells[*vertex] {
+                        if !included[*neighbour] {
+                            included[*neighbour] = true;
+                            cells.push(*neighbour);
+                        }
+                    }
+                }
+            }
+            layer_start = layer_end;
+        }
+        // Local numbering of the vertices
+        let mut local_vertices = HashMap::new();
+        let mut vertices = Vec::new();
+        let mut connectivity = Vec::with_capacity(cells.len() * mesh.vertices_per_cell());
+        for cell in &cells {
+            for vertex in mesh.cell(*cell) {
+                let local = *local_vertices.entry(*vertex).or_insert_with(|| {
+                    vertices.push(*vertex);
+                    vertices.len() - 1
+                });
+                connectivity.push(local);
+            }
+        }
+        let dim = mesh.geometric_dim();
+        let coordinates = vertices
+            .iter()
+            .flat_map(|v| mesh.vertex(*v).to_vec())
+            .collect();
+        let mut local_mesh = Mesh::new(
+            DataHold::new(coordinates, [vertices.len(), dim]),
+            DataHold::new(connectivity, [cells.len(), mesh.vertices_per_cell()]),
+        );
+        for (facet, tag) in mesh.facet_tags() {
+            let local: Option<Vec<usize>> = facet
+                .iter()
+                .map(|v| local_vertices.get(v).copied())
+                .collect();
+            if let Some(local) = local {
+                local_mesh.tag_facet(&local, tag);
+            }
+        }
+        for (vertex, tag) in mesh.vertex_tags() {
+            if let Some(local) = local_vertices.get(&vertex) {
+                local_mesh.tag_vertex(*local, tag);
+            }
+        }
+        for (local, cell) in cells.iter().enumerate() {
+            if let Some(tag) = mesh.cell_tag(*cell) {
+                local_mesh.tag_cell(local, tag);
+            }
+        }
+        let cell_owners = cells.iter().map(|c| self.parts[*c]).collect();
+        let vertex_owners = vertices
+            .iter()
+            .map(|v| {
+                vertex_cells[*v]
+                    .iter()
+                    .map(|c| self.parts[*c])
+                    .min()
+                    .unwrap()
+            })
+            .collect();
+        Subdomain {
+            part,
+            mesh: local_mesh,
+            cells,
+            vertices,
+            n_owned_cells,
+            cell_owners,
+            vertex_owners,
+        }
+    }
+}
+
+impl Subdomain {
+    /// Part the subdomain was extracted for
+    pub fn part(&self) -> usize {
+        self.part
+    }
+
+    /// Local mesh of the owned and ghost cells
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    /// Number of owned cells (the first cells of the local mesh)
+    pub fn n_owned_cells(&self) -> usize {
+        self.n_owned_cells
+    }
+
+    /// Whether a local cell is a ghost cell
+    pub fn is_ghost(&self, cell: usize) -> bool {
+        cell >= self.n_owned_cells
+    }
+
+    /// Cell of the global mesh of a local cell
+    pub fn global_cell(&self, cell: usize) -> usize {
+        self.cells[cell]
+    }
+
+    /// Vertex of the global mesh of a local vertex
+    pub fn global_vertex(&self, vertex: usize) -> usize {
+        self.vertices[vertex]
+    }
+
+    /// Part owning a local cell
+    pub fn cell_owner(&self, cell: usize) -> usize {
+        self.cell_owners[cell]
+    }
+
+    /// Part owning a local vertex
+    pub fn vertex_owner(&self, vertex: usize) -> usize {
+        self.vertex_owners[vertex]
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Recursively split the cells between a range of parts in proportion to the number of parts on
+// each side using an ordering putting the first split cells on the first side
+fn bisect<Order>(
+    cells: &mut [usize],
+    first_part: usize,
+    n_parts: usize,
+    parts: &mut [usize],
+    order: &mut Order,
+) where
+    Order: FnMut(&mut [usize], usize),
+{
+    if n_parts == 1 || cells.is_empty() {
+        cells.iter().for_each(|c| parts[*c] = first_part);
+        return;
+    }
+    let left_parts = n_parts / 2;
+    let split = cells.len() * left_parts / n_parts;
+    order(cells, split);
+    let (left, right) = cells.split_at_mut(split);
+    bisect(left, first_part, left_parts, parts, order);
+    bisect(
+        right,
+        first_part + left_parts,
+        n_parts - left_parts,
+        parts,
+        order,
+    );
+}
+
+// Put the cells with the split smallest centroid coordinates along the longest side of their
+// bounding box first
+fn order_by_coordinates(cells: &mut [usize], split: usize, dim: usize, centroids: &[f64]) {
+    let mut lower = vec![f64::INFINITY; dim];
+    let mut upper = vec![f64::NEG_INFINITY; dim];
+    for cell in cells.iter() {
+        for d in 0..dim {
+            lower[d] = lower[d].min(centroids[cell * dim + d]);
+            upper[d] = upper[d].max(centroids[cell * dim + d]);
+        }
+    }
+    let axis = (0..dim)
+        .max_by(|a, b| (upper[*a] - lower[*a]).total_cmp(&(upper[*b] - lower[*b])))
+        .unwrap();
+    if split < cells.len() {
+        cells.select_nth_unstable_by(split, |a, b| {
+            centroids[a * dim + axis].total_cmp(&centroids[b * dim + axis])
+        });
+    }
+}
+
+// Order the cells by breadth first search in the dual graph restricted to them starting from a
+// pseudo peripheral cell (the last cell reached from the first one)
+fn order_by_levels(cells: &mut [usize], graph: &[Vec<usize>], inside: &mut [bool]) {
+    cells.iter().for_each(|c| inside[*c] = true);
+    let first = breadth_first(cells, cells[0], graph, inside);
+    let peripheral = *first.last().unwrap();
+    cells.iter().for_each(|c| inside[*c] = true);
+    let order = breadth_first(cells, peripheral, graph, inside);
+    cells.copy_from_slice(&order);
+}
+
+// Breadth first ordering of the marked cells from a start cell, restarting in the next marked
+// cell for disconnected components and clearing the marks
+fn breadth_first(
+    cells: &[usize],
+    start: usize,
+    graph: &[Vec<usize>],
+    inside: &mut [bool],
+) -> Vec<usize> {
+    let mut order = Vec::with_capacity(cells.len());
+    let mut queue = VecDeque::new();
+    let mut next = 0;
+    inside[start] = false;
+    queue.push_back(start);
+    while order.len() < cells.len() {
+        let cell = match queue.pop_front() {
+            Some(cell) => cell,
+            None => {
+                while !inside[cells[next]] {
+                    next += 1;
+                }
+                inside[cells[next]] = false;
+                cells[next]
+            }
+        };
+        order.push(cell);
+        for neighbour in &graph[cell] {
+            if inside[*neighbour] {
+                inside[*neighbour] = false;
+                queue.push_back(*neighbour);
+            }
+        }
+    }
+    order
+}
+
+// Cells sharing an interior facet with every cell
+fn dual_graph(mesh: &Mesh) -> Vec<Vec<usize>> {
+    let facets = Facets::new(mesh);
+    let mut graph = vec![Vec::new(); mesh.n_cells()];
+    for facet in facets.interior_facets() {
+        if let [(a, _), (b, _)] = facets.facet_cells(facet) {
+            graph[*a].push(*b);
+            graph[*b].push(*a);
+        }
+    }
+    graph
+}
+
+// Centroids of the cells stored as (number of cells, geometric dimension)
+fn centroids(mesh: &Mesh) -> Vec<f64> {
+    let dim = mesh.geometric_dim();
+    let mut centroids = vec![0.0; mesh.n_cells() * dim];
+    for (cell, centroid) in centroids.chunks_mut(dim).enumerate() {
+        let vertices = mesh.cell(cell);
+        for vertex in vertices {
+            for (c, x) in centroid.iter_mut().zip(mesh.vertex(*vertex)) {
+                *c += x / vertices.len() as f64;
+            }
+        }
+    }
+    centroids
+}
+
+// Morton keys of the centroids quantized on a regular grid of their bounding box
+fn morton_keys(dim: usize, centroids: &[f64]) -> Vec<u64> {
+    let bits = MORTON_BITS / dim as u32;
+    let mut lower = vec![f64::INFINITY; dim];
+    let mut upper = vec![f64::NEG_INFINITY; dim];
+    for centroid in centroids.chunks(dim) {
+        for d in 0..dim {
+            lower[d] = lower[d].min(centroid[d]);
+            upper[d] = upper[d].max(centroid[d]);
+        }
+    }
+    let scale = ((1u64 << bits) - 1) as f64;
+    centroids
+        .chunks(dim)
+        .map(|centroid| {
+            let quantized: Vec<u64> = (0..dim)
+                .map(|d| {
+                    let width = upper[d] - lower[d];
+                    if width > 0.0 {
+                        ((centroid[d] - lower[d]) / width * scale) as u64
+                    } else {
+                        0
+                    }
+                })
+                .collect();
+            let mut key = 0;
+            for bit in (0..bits).rev() {
+                for q in &quantized {
+                    key = (key << 1) | ((q >> bit) & 1);
+                }
+            }
+            key
+        })
+        .collect()
+}
+
+// Declarations of the C interface of METIS 5 built with 32 bit indices and reals
+#[cfg(feature = "metis")]
+#[allow(non_camel_case_types, non_snake_case)]
+mod ffi {
+    pub type idx_t = i32;
+    pub type real_t = f32;
+
+    pub const METIS_OK: i32 = 1;
+
+    #[link(name = "metis")]
+    extern "C" {
+        pub fn METIS_PartGraphKway(
+            nvtxs: *mut idx_t,
+            ncon: *mut idx_t,
+            xadj: *mut idx_t,
+            adjncy: *mut idx_t,
+            vwgt: *mut idx_t,
+            vsize: *mut idx_t,
+            adjwgt: *mut idx_t,
+            nparts: *mut idx_t,
+            tpwgts: *mut real_t,
+            ubvec: *mut real_t,
+            options: *mut idx_t,
+            edgecut: *mut idx_t,
+            part: *mut idx_t,
+        ) -> i32;
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::test_meshes::square_grid;
+
+    #[test]
+    fn test_partition_balance() {
+        let mesh = square_grid(8);
+        for method in [
+            PartitionMethod::CoordinateBisection,
+            PartitionMethod::SpaceFillingCurve,
+            PartitionMethod::GraphBisection,
+        ] {
+            let partition = Partition::new(&mesh, 3, method);
+            let sizes: Vec<usize> = (0..3).map(|p| partition.cells(p).len()).collect();
+            assert_eq!(sizes.iter().sum::<usize>(), 128, "Cells were lost");
+            assert!(
+                sizes.iter().all(|s| *s == 42 || *s == 43),
+                "Unbalanced partition {:?} with {:?}",
+                sizes,
+                method
+            );
+            // Parts should be compact: far fewer cut facets than a random assignment
+            assert!(
+                partition.edge_cut(&mesh) <= 40,
+                "Too many cut facets with {:?}",
+                method
+            );
+        }
+        let halves = Partition::new(&mesh, 2, PartitionMethod::CoordinateBisection);
+        assert_eq!(
+            halves.edge_cut(&mesh),
+            8,
+            "Halves should be cut along a line"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[cfg(feature = "metis")]
+    #[test]
+    fn test_metis_partition() {
+        let mesh = square_grid(8);
+        let partition = Partition::metis(&mesh, 3).unwrap();
+        assert_eq!(partition.n_parts(), 3, "Wrong number of parts");
+        let sizes: Vec<usize> = (0..3).map(|p| partition.cells(p).len()).collect();
+        assert_eq!(sizes.iter().sum::<usize>(), 128, "Cells were lost");
+        // METIS allows a 3 % imbalance by default
+        assert!(
+            sizes.iter().all(|s| (40..=44).contains(s)),
+            "Unbalanced partition {:?}",
+            sizes
+        );
+        assert!(partition.edge_cut(&mesh) <= 40, "Too many cut facets");
+        assert!(
+            Partition::metis(&mesh, 1)
+                .unwrap()
+                .parts()
+                .iter()
+                .all(|p| *p == 0),
+            "A single part should hold every cell"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_subdomain_ghosts() {
+        let mut mesh = square_grid(4);
+        mesh.tag_boundary(1, |x| x[0] == 0.0);
+        mesh.tag_vertex(0, 5);
+        // Left and right halves of the square
+        let parts = (0..mesh.n_cells())
+            .map(|c| usize::from((c / 2) % 4 >= 2))
+            .collect();
+        let partition = Partition::from_parts(2, parts);
+        let subdomain = partition.subdomain(&mesh, 0, 1);
+        assert_eq!(subdomain.part(), 0, "Wrong part");
+        assert_eq!(subdomain.n_owned_cells(), 16, "Wrong number of owned cells");
+        // Cells of the right half touching the vertices of the line x = 0.5
+        assert_eq!(
+            subdomain.mesh().n_cells(),
+            24,
+            "Wrong number of ghost cells"
+        );
+        for cell in 0..subdomain.mesh().n_cells() {
+            let global = subdomain.global_cell(cell);
+            assert_eq!(
+                subdomain.cell_owner(cell),
+                partition.part(global),
+                "Wrong cell owner"
+            );
+            assert_eq!(
+                subdomain.is_ghost(cell),
+                partition.part(global) != 0,
+                "Wrong ghost cell"
+            );
+            for (local, vertex) in subdomain.mesh().cell(cell).iter().zip(mesh.cell(global)) {
+                assert_eq!(
+                    subdomain.global_vertex(*local),
+                    *vertex,
+                    "Wrong local vertices"
+                );
+            }
+        }
+        for vertex in 0..subdomain.mesh().n_vertices() {
+            let x = subdomain.mesh().vertex(vertex)[0];
+            let expected = usize::from(x > 0.5);
+            assert_eq!(
+                subdomain.vertex_owner(vertex),
+                expected,
+                "Wrong vertex owner"
+            );
+        }
+        assert_eq!(
+            subdomain.mesh().facet_tags().count(),
+            4,
+            "Facet tags were not kept"
+        );
+        assert_eq!(
+            subdomain.mesh().vertex_tag(0),
+            Some(5),
+            "Vertex tag was not kept"
+        );
+        let wide = partition.subdomain(&mesh, 1, 4);
+        assert_eq!(
+            wide.mesh().n_cells(),
+            32,
+            "Ghost layers should cover the mesh"
+        );
+    }
+}
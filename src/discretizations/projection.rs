@@ -0,0 +1,246 @@
+use super::assembler::Assembler;
+use super::cell_mapping::CellMapping;
+use super::cell_values::CellValues;
+use super::function::Function;
+use super::function_space::FunctionSpace;
+use super::operators::mass_matrix;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::solvers::krylov::{ConjugateGradient, Jacobi};
+use crate::spaces::quadrature::QuadratureRule;
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// L2 projection of an expression filling the components at the given physical coordinates onto a
+/// space
+///
+/// Every component solves the mass matrix system M u_c = (f_c, phi) assembled with a rule exact
+/// for twice the order of the element plus two.
+pub fn project<'a, Expression>(
+    target: &'a FunctionSpace<'a>,
+    expression: Expression,
+) -> Function<'a>
+where
+    Expression: Fn(&[f64], &mut [f64]),
+{
+    let degree = 2 * target.element().order() + 2;
+    let mut value = vec![0.0; target.n_components()];
+    project_with(target, degree, |values, q, c| {
+        expression(values.point(q), &mut value);
+        value[c]
+    })
+}
+
+/// L2 projection of a function onto another space on the same mesh
+///
+/// The spaces may have different orders but need the same number of components.
+pub fn project_function<'a>(source: &Function, target: &'a FunctionSpace<'a>) -> Function<'a> {
+    let source_space = source.space();
+    check_compatible(source_space, target);
+    assert!(
+        std::ptr::eq(source_space.mesh(), target.mesh()),
+        "L2 projection is only implemented between spaces on the same mesh"
+    );
+    let degree = source_space.element().order() + target.element().order();
+    let dim = target.mesh().geometric_dim();
+    let mut reference = vec![0.0; dim];
+    let mut value = vec![0.0; target.n_components()];
+    project_with(target, degree, |values, q, c| {
+        values
+            .mapping()
+            .inverse_map_point(values.point(q), &mut reference);
+        source.eval_in_cell(values.cell(), &reference, &mut value);
+        value[c]
+    })
+}
+
+/// Nodal interpolation of a function onto another space
+///
+/// When both spaces share the mesh the source is evaluated cell by cell, otherwise every node of
+/// the target is located in the mesh of the source (the target mesh has to be covered by it).
+pub fn interpolate<'a>(source: &Function, target: &'a FunctionSpace<'a>) -> Function<'a> {
+    let source_space = source.space();
+    check_compatible(source_space, target);
+    let mesh = target.mesh();
+    let element = target.element();
+    let same_mesh = std::ptr::eq(source_space.mesh(), mesh);
+    let mut result = Function::new(target);
+    let mut x = vec![0.0; mesh.geometric_dim()];
+    let mut value = vec![0.0; target.n_components()];
+    if mesh.n_cells() == 0 {
+        return result;
+    }
+    let mut mapping = CellMapping::new(mesh, 0);
+    for cell in 0..mesh.n_cells() {
+        mapping.reinit(mesh, cell);
+        for (node, dof) in target.dof_map().cell_dofs(cell).iter().enumerate() {
+            let reference = element.node_coordinates(node);
+            if same_mesh {
+                source.eval_in_cell(cell, &reference, &mut value);
+            } else {
+                mapping.map_point(&reference, &mut x);
+                value = source
+                    .eval(&x)
+                    .expect("Target node is outside of the source mesh");
+            }
+            for (c, v) in value.iter().enumerate() {
+                result.values_mut()[target.dof(*dof, c)] = *v;
+            }
+        }
+    }
+    result
+}
+
+// Solve the mass matrix systems of every component with the right hand sides (f_c, phi) where f_c
+// is given at the quadrature points by the integrand
+fn project_with<'a, Integrand>(
+    target: &'a FunctionSpace<'a>,
+    degree: usize,
+    mut integrand: Integrand,
+) -> Function<'a>
+where
+    Integrand: FnMut(&CellValues, usize, usize) -> f64,
+{
+    let dim = target.mesh().topological_dim();
+    let n_scalar = target.dof_map().n_dofs();
+    let assembler = Assembler::new(
+        target.mesh(),
+        target.element(),
+        target.dof_map(),
+        QuadratureRule::simplex(dim, degree),
+    );
+    let mass = mass_matrix(&assembler);
+    let mut result = Function::new(target);
+    let mut rhs = vec![0.0; n_scalar];
+    let mut solution = vec![0.0; n_scalar];
+    for c in 0..target.n_components() {
+        rhs.iter_mut().for_each(|v| *v = 0.0);
+        assembler.assemble_vector(&mut rhs, |values, local| {
+            for q in 0..values.n_points() {
+                let f = integrand(values, q, c) * values.weight(q);
+                for i in 0..values.n_dofs() {
+                    local[i] += f * values.shape_value(q, i);
+                }
+            }
+        });
+        conjugate_gradient(&mass, &rhs, &mut solution);
+        result.set_component(c, &solution);
+    }
+    result
+}
+
+fn check_compatible(source: &FunctionSpace, target: &FunctionSpace) {
+    assert!(
+        source.n_components() == target.n_components(),
+        "Spaces do not have the same number of components"
+    );
+}
+
+// Jacobi preconditioned conjugate gradient for the symmetric positive definite mass matrix
+pub(crate) fn conjugate_gradient(matrix: &SparseCSR<f64>, rhs: &[f64], x: &mut [f64]) {
+    x.iter_mut().for_each(|v| *v = 0.0);
+    ConjugateGradient::new()
+        .with_relative_tolerance(1e-14)
+        .with_max_iterations((10 * rhs.len()).max(100))
+        .solve_preconditioned(matrix, &Jacobi::new(matrix), rhs, x);
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::data_hold::DataHold;
+    use crate::discretizations::mesh::Mesh;
+    use crate::discretizations::test_meshes::{empty_mesh, unit_square};
+    use crate::spaces::lagrange::LagrangeElement;
+
+    #[test]
+    fn test_project_reproduces_space() {
+        let mesh = unit_square();
+        let space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        // Quadratics belong to the P2 space so the projection is exact
+        let u = project(&space, |x, value| {
+            value[0] = x[0] * x[1];
+            value[1] = 1.0 - x[0] * x[0];
+        });
+        for point in [[0.1, 0.2], [0.7, 0.8], [0.5, 0.5]] {
+            let value = u.eval(&point).unwrap();
+            assert!(
+                (value[0] - point[0] * point[1]).abs() < 1e-12
+                    && (value[1] - 1.0 + point[0] * point[0]).abs() < 1e-12,
+                "Projection is not exact at {:?}",
+                point
+            );
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_project_preserves_mean() {
+        let mesh = unit_square();
+        let fine = FunctionSpace::new(&mesh, LagrangeElement::new(2, 3));
+        let coarse = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let mut u = Function::new(&fine);
+        u.interpolate(|x| x[0] * x[0] * x[1]);
+        let p = project_function(&u, &coarse);
+        // Constants are in the target space so the integral is preserved
+        let integral = |f: &Function| -> f64 {
+            let space = f.space();
+            let assembler = Assembler::new(
+                space.mesh(),
+                space.element(),
+                space.dof_map(),
+                QuadratureRule::simplex(2, 2 * space.element().order()),
+            );
+            let mass = mass_matrix(&assembler);
+            let mut product = vec![0.0; space.n_dofs()];
+            mass.apply(f.values(), &mut product);
+            product.iter().sum()
+        };
+        assert!(
+            (integral(&p) - integral(&u)).abs() < 1e-12,
+            "Projection should preserve the integral"
+        );
+        assert!(
+            (integral(&u) - 1.0 / 6.0).abs() < 1e-12,
+            "Wrong integral of x^2 y"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_interpolate_between_spaces() {
+        let mesh = unit_square();
+        let linear = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let cubic = FunctionSpace::new(&mesh, LagrangeElement::new(2, 3));
+        let mut u = Function::new(&linear);
+        u.interpolate(|x| 2.0 * x[0] - x[1]);
+        let v = interpolate(&u, &cubic);
+        assert!(
+            (v.eval(&[0.3, 0.4]).unwrap()[0] - 0.2).abs() < 1e-14,
+            "Linear function was not reproduced"
+        );
+        // Interpolation onto a shifted mesh covered by the source
+        let shifted = Mesh::new(
+            DataHold::new(vec![0.1, 0.1, 0.6, 0.1, 0.1, 0.6], [3, 2]),
+            DataHold::new(vec![0, 1, 2], [1, 3]),
+        );
+        let target = FunctionSpace::new(&shifted, LagrangeElement::new(2, 2));
+        let w = interpolate(&u, &target);
+        assert!(
+            (w.eval(&[0.2, 0.3]).unwrap()[0] - 0.1).abs() < 1e-14,
+            "Linear function was not reproduced on the shifted mesh"
+        );
+        let empty = empty_mesh();
+        let empty_space = FunctionSpace::new(&empty, LagrangeElement::new(2, 1));
+        let nothing = interpolate(&u, &empty_space);
+        assert!(
+            nothing.values().is_empty(),
+            "Interpolation onto an empty mesh has no values"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use crate::discretizations::mesh::Mesh;
+use crate::spaces::quadrature::QuadratureRule;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Values of some components at every quadrature point of every cell of a mesh, such as the
+/// plastic strain, damage or history variables of path dependent materials
+///
+/// The values are laid out cell by cell, point by point in the order of the quadrature rule. The
+/// committed values of the last converged step are kept apart from the trial values of the current
+/// step: nonlinear iterations read the committed values and overwrite the trial ones, commit()
+/// accepts them once the step converged and rollback() discards them to restart a step, for
+/// instance with a smaller time step.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuadratureField {
+    n_cells: usize,
+    n_points: usize,
+    n_components: usize,
+    weights: Vec<f64>,
+    committed: Vec<f64>,
+    trial: Vec<f64>,
+}
+
+impl QuadratureField {
+    /// Field of zeros with components at the points of a quadrature rule on every cell of a mesh
+    pub fn new(mesh: &Mesh, quadrature: &QuadratureRule, n_components: usize) -> Self {
+        let n = mesh.n_cells() * quadrature.n_points() * n_components;
+        QuadratureField {
+            n_cells: mesh.n_cells(),
+            n_points: quadrature.n_points(),
+            n_components,
+            weights: quadrature.weights().to_vec(),
+            committed: vec![0.0; n],
+            trial: vec![0.0; n],
+        }
+    }
+
+    /// Number of cells
+    pub fn n_cells(&self) -> usize {
+        self.n_cells
+    }
+
+    /// Number of quadrature points per cell
+    pub fn n_points(&self) -> usize {
+        self.n_points
+    }
+
+    /// Number of components per point
+    pub fn n_components(&self) -> usize {
+        self.n_components
+    }
+
+    /// Set the committed and trial values of every point
+    pub fn fill_with<Initial>(&mut self, mut initial: Initial)
+    where
+        Initial: FnMut(&mut [f64]),
+    {
+        if self.n_components > 0 {
+            self.committed
+                .chunks_mut(self.n_components)
+                .for_each(&mut initial);
+            self.trial.copy_from_slice(&self.committed);
+        }
+    }
+
+    /// Committed values of a point of a cell
+    pub fn committed(&self, cell: usize, q: usize) -> &[f64] {
+        let start = self.offset(cell, q);
+        &self.committed[start..start + self.n_components]
+    }
+
+    /// Trial values of a point of a cell
+    pub fn trial(&self, cell: usize, q: usize) -> &[f64] {
+        let start = self.offset(cell, q);
+        &self.trial[start..start + self.n_components]
+    }
+
+    /// Committed values and trial values to update of a point of a cell
+    pub fn point_mut(&mut self, cell: usize, q: usize) -> (&[f64], &mut [f64]) {
+        let start = self.offset(cell, q);
+        let end = start + self.n_components;
+        (&self.committed[start..end], &mut self.trial[start..end])
+    }
+
+    /// Committed values and trial values to update of all the points of a cell, point by point
+    pub fn cell_mut(&mut self, cell: usize) -> (&[f64], &mut [f64]) {
+        let start = self.offset(cell, 0);
+        let end = start + self.n_points * self.n_components;
+        (&self.committed[start..end], &mut self.trial[start..end])
+    }
+
+    /// Accept the trial values of the current step
+    pub fn commit(&mut self) {
+        self.committed.copy_from_slice(&self.trial);
+    }
+
+    /// Discard the trial values, resetting them to the committed ones
+    pub fn rollback(&mut self) {
+        self.trial.copy_from_slice(&self.committed);
+    }
+
+    /// Committed values of all points, cell by cell then point by point
+    pub fn values(&self) -> &[f64] {
+        &self.committed
+    }
+
+    /// Mean of a committed component over every cell weighted by the quadrature rule, for output
+    /// as cell data
+    pub fn cell_means(&self, component: usize) -> Vec<f64> {
+        assert!(component < self.n_components, "Component out of bounds");
+        let total: f64 = self.weights.iter().sum();
+        (0..self.n_cells)
+            .map(|cell| {
+                self.weights
+                    .iter()
+                    .enumerate()
+                    .map(|(q, w)| w * self.committed(cell, q)[component])
+                    .sum::<f64>()
+                    / total
+            })
+            .collect()
+    }
+
+    // Start of the values of a point of a cell
+    fn offset(&self, cell: usize, q: usize) -> usize {
+        assert!(
+            cell < self.n_cells && q < self.n_points,
+            "Quadrature point out of bounds"
+        );
+        (cell * self.n_points + q) * self.n_components
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+
+    #[test]
+    fn test_quadrature_field() {
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![2, 2]).simplex_mesh();
+        let quadrature = QuadratureRule::simplex(2, 2);
+        let mut field = QuadratureField::new(&mesh, &quadrature, 2);
+        assert_eq!(field.n_cells(), mesh.n_cells(), "Wrong number of cells");
+        assert_eq!(
+            field.n_points(),
+            quadrature.n_points(),
+            "Wrong number of points"
+        );
+        field.fill_with(|values| values.copy_from_slice(&[1.0, 0.0]));
+        // Iterations of a step accumulating into the trial values from the committed ones
+        for iteration in 0..3 {
+            let (committed, trial) = field.point_mut(1, 0);
+            trial[1] = committed[1] + iteration as f64;
+        }
+        assert_eq!(field.trial(1, 0), [1.0, 2.0], "Wrong trial values");
+        assert_eq!(field.committed(1, 0), [1.0, 0.0], "Trial values leaked");
+        field.rollback();
+        assert_eq!(field.trial(1, 0), [1.0, 0.0], "Rollback failed");
+        let (committed, trial) = field.cell_mut(2);
+        for (t, c) in trial.iter_mut().zip(committed) {
+            *t = 2.0 * c + 1.0;
+        }
+        field.commit();
+        assert_eq!(field.committed(2, 1), [3.0, 1.0], "Commit failed");
+        let means = field.cell_means(0);
+        assert!((means[2] - 3.0).abs() < 1e-14, "Wrong cell mean");
+        assert!((means[0] - 1.0).abs() < 1e-14, "Wrong cell mean");
+        assert_eq!(
+            field.values().len(),
+            mesh.n_cells() * quadrature.n_points() * 2
+        );
+    }
+}
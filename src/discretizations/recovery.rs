@@ -0,0 +1,274 @@
+use super::cell_mapping::CellMapping;
+use super::function::Function;
+use super::function_space::FunctionSpace;
+use crate::spaces::quadrature::QuadratureRule;
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Continuous gradient of a function obtained by averaging the cell gradients at the nodes of a
+/// target space
+///
+/// The target lives on the same mesh and has dimension times as many components as the source:
+/// component c * dim + k of the target is the derivative along k of component c of the source. The
+/// gradients of the cells touching a node are weighted by the cell volumes.
+pub fn recover_gradient<'a>(source: &Function, target: &'a FunctionSpace<'a>) -> Function<'a> {
+    recover_with(source, target, |_| 1.0)
+}
+
+/// Continuous flux -k grad(u) of a function for a conductivity k given at the physical coordinates
+///
+/// The flux is recovered on the nodes of the target as in recover_gradient.
+pub fn recover_flux<'a, Conductivity>(
+    source: &Function,
+    target: &'a FunctionSpace<'a>,
+    conductivity: Conductivity,
+) -> Function<'a>
+where
+    Conductivity: Fn(&[f64]) -> f64,
+{
+    recover_with(source, target, |x| -conductivity(x))
+}
+
+/// Zienkiewicz-Zhu superconvergent patch recovery of the gradient onto a linear target space
+///
+/// For every vertex, each gradient component sampled at the superconvergent points of the cells
+/// around it (centroids for linear elements, quadrature points of degree 2 (order - 1) otherwise)
+/// is fitted in the least squares sense by a linear polynomial which is evaluated at the vertex.
+/// Patches with too few points for the fit (typically at corners) fall back to the mean of the
+/// samples.
+pub fn patch_recovery<'a>(source: &Function, target: &'a FunctionSpace<'a>) -> Function<'a> {
+    let space = source.space();
+    let mesh = target.mesh();
+    check_target(space, target);
+    assert!(
+        target.element().order() == 1 && !target.is_discontinuous(),
+        "Patch recovery targets continuous linear spaces"
+    );
+    let dim = mesh.topological_dim();
+    let n_values = space.n_components() * dim;
+    let order = space.element().order();
+    let sampling: Vec<Vec<f64>> = if order == 1 {
+        vec![vec![1.0 / (dim + 1) as f64; dim]]
+    } else {
+        let rule = QuadratureRule::simplex(dim, 2 * (order - 1));
+        (0..rule.n_points())
+            .map(|q| rule.point(q).to_vec())
+            .collect()
+    };
+    // Physical sampling points of every cell followed by the gradients sampled there
+    let mut samples: Vec<Vec<(Vec<f64>, Vec<f64>)>> = Vec::with_capacity(mesh.n_cells());
+    let mut patches = vec![Vec::new(); mesh.n_vertices()];
+    for cell in 0..mesh.n_cells() {
+        let mapping = CellMapping::new(mesh, cell);
+        let cell_samples = sampling
+            .iter()
+            .map(|reference| {
+                let mut x = vec![0.0; dim];
+                mapping.map_point(reference, &mut x);
+                let mut gradient = vec![0.0; n_values];
+                source.gradient_in_cell(cell, reference, &mut gradient);
+                (x, gradient)
+            })
+            .collect();
+        samples.push(cell_samples);
+        for vertex in mesh.cell(cell) {
+            patches[*vertex].push(cell);
+        }
+    }
+    let mut result = Function::new(target);
+    for (vertex, patch) in patches.iter().enumerate() {
+        let center = mesh.vertex(vertex);
+        let points: Vec<&(Vec<f64>, Vec<f64>)> = patch
+            .iter()
+            .flat_map(|cell| samples[*cell].iter())
+            .collect();
+        let scale = points
+            .iter()
+            .map(|(x, _)| distance(x, center))
+            .fold(0.0, f64::max);
+        // Normal equations of the fit in the basis 1, (x - center) / scale
+        let basis = |x: &[f64]| -> Vec<f64> {
+            std::iter::once(1.0)
+                .chain(x.iter().zip(center.iter()).map(|(x, c)| (x - c) / scale))
+                .collect()
+        };
+        let mut normal = vec![0.0; (dim + 1) * (dim + 1)];
+        for (x, _) in points.iter() {
+            let p = basis(x);
+            for i in 0..=dim {
+                for j in 0..=dim {
+                    normal[i * (dim + 1) + j] += p[i] * p[j];
+                }
+            }
+        }
+        // Vertex dofs of continuous spaces take the index of their vertex
+        let dof = vertex;
+        for k in 0..n_values {
+            let mut rhs = vec![0.0; dim + 1];
+            for (x, gradient) in points.iter() {
+                for (r, p) in rhs.iter_mut().zip(basis(x).iter()) {
+                    *r += p * gradient[k];
+                }
+            }
+            // The value of the fit at the vertex is the constant coefficient
+            let value = match solve_small(&normal, &rhs) {
+                Some(coefficients) => coefficients[0],
+                None => rhs[0] / points.len() as f64,
+            };
+            result.values_mut()[target.dof(dof, k)] = value;
+        }
+    }
+    result
+}
+
+// Average the scaled cell gradients at the nodes of the target
+fn recover_with<'a, Scale>(
+    source: &Function,
+    target: &'a FunctionSpace<'a>,
+    scale: Scale,
+) -> Function<'a>
+where
+    Scale: Fn(&[f64]) -> f64,
+{
+    let space = source.space();
+    let mesh = target.mesh();
+    check_target(space, target);
+    let dim = mesh.topological_dim();
+    let n_values = space.n_components() * dim;
+    let element = target.element();
+    let mut result = Function::new(target);
+    let mut weights = vec![0.0; target.dof_map().n_dofs()];
+    let mut gradient = vec![0.0; n_values];
+    let mut x = vec![0.0; dim];
+    for cell in 0..mesh.n_cells() {
+        let mapping = CellMapping::new(mesh, cell);
+        let volume = mapping.determinant().abs();
+        for (node, dof) in target.dof_map().cell_dofs(cell).iter().enumerate() {
+            let reference = element.node_coordinates(node);
+            source.gradient_in_cell(cell, &reference, &mut gradient);
+            mapping.map_point(&reference, &mut x);
+            let factor = scale(&x) * volume;
+            for (k, g) in gradient.iter().enumerate() {
+                result.values_mut()[target.dof(*dof, k)] += factor * g;
+            }
+            weights[*dof] += volume;
+        }
+    }
+    for (dof, weight) in weights.iter().enumerate() {
+        for k in 0..n_values {
+            result.values_mut()[target.dof(dof, k)] /= weight;
+        }
+    }
+    result
+}
+
+fn check_target(source: &FunctionSpace, target: &FunctionSpace) {
+    assert!(
+        std::ptr::eq(source.mesh(), target.mesh()),
+        "Recovery is only implemented between spaces on the same mesh"
+    );
+    assert!(
+        target.n_components() == source.n_components() * source.mesh().topological_dim(),
+        "Target should have dimension times the components of the source"
+    );
+}
+
+fn distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f64>()
+        .sqrt()
+}
+
+// Gaussian elimination with partial pivoting on a small dense system, None if it is singular
+fn solve_small(matrix: &[f64], rhs: &[f64]) -> Option<Vec<f64>> {
+    let n = rhs.len();
+    let mut a = matrix.to_vec();
+    let mut b = rhs.to_vec();
+    let norm = a.iter().fold(0.0, |m: f64, v| m.max(v.abs()));
+    for k in 0..n {
+        let pivot = (k..n)
+            .max_by(|i, j| a[i * n + k].abs().total_cmp(&a[j * n + k].abs()))
+            .unwrap();
+        if a[pivot * n + k].abs() <= 1e-10 * norm {
+            return None;
+        }
+        for j in 0..n {
+            a.swap(k * n + j, pivot * n + j);
+        }
+        b.swap(k, pivot);
+        for i in (k + 1)..n {
+            let factor = a[i * n + k] / a[k * n + k];
+            for j in k..n {
+                a[i * n + j] -= factor * a[k * n + j];
+            }
+            b[i] -= factor * b[k];
+        }
+    }
+    for k in (0..n).rev() {
+        let sum: f64 = ((k + 1)..n).map(|j| a[k * n + j] * b[j]).sum();
+        b[k] = (b[k] - sum) / a[k * n + k];
+    }
+    Some(b)
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::test_meshes::square_grid;
+    use crate::spaces::lagrange::LagrangeElement;
+
+    #[test]
+    fn test_recover_gradient_and_flux() {
+        let mesh = square_grid(4);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        let target = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        let mut u = Function::new(&space);
+        u.interpolate(|x| x[0] + 2.0 * x[1]);
+        let gradient = recover_gradient(&u, &target);
+        let flux = recover_flux(&u, &target, |_| 2.0);
+        for (g, q) in gradient.values().chunks(2).zip(flux.values().chunks(2)) {
+            assert!(
+                (g[0] - 1.0).abs() < 1e-12 && (g[1] - 2.0).abs() < 1e-12,
+                "Gradient of a linear function should be recovered exactly"
+            );
+            assert!(
+                (q[0] + 2.0).abs() < 1e-12 && (q[1] + 4.0).abs() < 1e-12,
+                "Wrong recovered flux"
+            );
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_patch_recovery() {
+        let mesh = square_grid(4);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let target = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let mut u = Function::new(&space);
+        u.interpolate(|x| 3.0 * x[0] - x[1]);
+        let gradient = patch_recovery(&u, &target);
+        for g in gradient.values().chunks(2) {
+            assert!(
+                (g[0] - 3.0).abs() < 1e-12 && (g[1] + 1.0).abs() < 1e-12,
+                "Gradient of a linear function should be recovered exactly"
+            );
+        }
+        // The cell gradients of x^2 are only first order accurate but the recovery is exact at the
+        // interior vertices of the uniform mesh
+        u.interpolate(|x| x[0] * x[0]);
+        let gradient = patch_recovery(&u, &target);
+        let recovered = gradient.eval(&[0.5, 0.5]).unwrap();
+        assert!(
+            (recovered[0] - 1.0).abs() < 1e-12 && recovered[1].abs() < 1e-12,
+            "Patch recovery should be exact for quadratics at interior vertices"
+        );
+    }
+}
@@ -0,0 +1,538 @@
+use super::mesh::Mesh;
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use std::collections::HashMap;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Uniform or local refinement of a simplicial mesh with the maps from the refined entities to
+/// their parents
+///
+/// Uniform refinement splits every edge at its midpoint: segments give 2 children, triangles 4 and
+/// tetrahedra the 8 of Bey's red refinement. Local refinement bisects cells through the midpoints
+/// of their longest edges. In both cases the vertices of the coarse mesh keep their index and the
+/// midpoints are numbered after them. Facet tags are passed on to the child facets and vertex tags
+/// to the coarse vertices.
+pub struct Refinement {
+    mesh: Mesh,
+    vertex_parents: Vec<[usize; 2]>,
+    cell_parents: Vec<usize>,
+    n_coarse_vertices: usize,
+}
+
+impl Refinement {
+    /// Refine every cell of a mesh
+    pub fn uniform(coarse: &Mesh) -> Self {
+        let dim = coarse.geometric_dim();
+        let n_coarse_vertices = coarse.n_vertices();
+        let mut vertex_parents: Vec<[usize; 2]> = (0..n_coarse_vertices).map(|v| [v, v]).collect();
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut midpoint = |a: usize, b: usize| {
+            let key = (a.min(b), a.max(b));
+            *midpoints.entry(key).or_insert_with(|| {
+                vertex_parents.push([key.0, key.1]);
+                vertex_parents.len() - 1
+            })
+        };
+        let mut cells = Vec::new();
+        let mut cell_parents = Vec::new();
+        for cell in 0..coarse.n_cells() {
+            for child in children(coarse.cell(cell), &mut midpoint) {
+                cells.extend(child);
+                cell_parents.push(cell);
+            }
+        }
+        let mut facet_tags = Vec::new();
+        for (facet, tag) in coarse.facet_tags() {
+            for child in children(facet, &mut midpoint) {
+                facet_tags.push((child, tag));
+            }
+        }
+        let mut vertices = Vec::with_capacity(vertex_parents.len() * dim);
+        for [a, b] in vertex_parents.iter() {
+            let (a, b) = (coarse.vertex(*a), coarse.vertex(*b));
+            vertices.extend(a.iter().zip(b).map(|(a, b)| 0.5 * (a + b)));
+        }
+        let n_cells = cell_parents.len();
+        let mut mesh = Mesh::new(
+            DataHold::new(vertices, [vertex_parents.len(), dim]),
+            DataHold::new(cells, [n_cells, coarse.vertices_per_cell()]),
+        );
+        for (facet, tag) in facet_tags {
+            mesh.tag_facet(&facet, tag);
+        }
+        for (vertex, tag) in coarse.vertex_tags() {
+            mesh.tag_vertex(vertex, tag);
+        }
+        for (cell, parent) in cell_parents.iter().enumerate() {
+            if let Some(tag) = coarse.cell_tag(*parent) {
+                mesh.tag_cell(cell, tag);
+            }
+        }
+        Refinement {
+            mesh,
+            vertex_parents,
+            cell_parents,
+            n_coarse_vertices,
+        }
+    }
+
+    /// Refine the marked cells of a mesh by longest edge bisection
+    ///
+    /// Every marked cell is bisected at least once through the midpoint of its longest edge (ties
+    /// broken by the vertex indices), all the cells sharing the edge being bisected with it. Before
+    /// an edge is split, the neighbours whose longest edge it is not are bisected first (Rivara's
+    /// longest edge propagation) so that the mesh stays conforming and the shapes of the cells do
+    /// not degenerate.
+    pub fn bisect(coarse: &Mesh, marked: &[usize]) -> Self {
+        let dim = coarse.geometric_dim();
+        let mut bisection = Bisection {
+            dim,
+            vertices: (0..coarse.n_vertices())
+                .flat_map(|v| coarse.vertex(v).iter().copied())
+                .collect(),
+            vertex_parents: (0..coarse.n_vertices()).map(|v| [v, v]).collect(),
+            cells: (0..coarse.n_cells())
+                .map(|c| coarse.cell(c).to_vec())
+                .collect(),
+            cell_parents: (0..coarse.n_cells()).collect(),
+            edge_cells: HashMap::new(),
+            facet_tags: coarse
+                .facet_tags()
+                .map(|(facet, tag)| (facet.to_vec(), tag))
+                .collect(),
+            split: vec![false; coarse.n_cells()],
+        };
+        for cell in 0..coarse.n_cells() {
+            bisection.attach(cell);
+        }
+        for cell in marked {
+            assert!(*cell < coarse.n_cells(), "Marked cell is not in the mesh");
+            if !bisection.split[*cell] {
+                let edge = bisection.longest_edge(*cell);
+                bisection.split_edge(edge);
+            }
+        }
+        let n_cells = bisection.cells.len();
+        let n_vertices = bisection.vertex_parents.len();
+        let mut mesh = Mesh::new(
+            DataHold::new(bisection.vertices, [n_vertices, dim]),
+            DataHold::new(
+                bisection.cells.concat(),
+                [n_cells, coarse.vertices_per_cell()],
+            ),
+        );
+        for (facet, tag) in bisection.facet_tags {
+            mesh.tag_facet(&facet, tag);
+        }
+        for (vertex, tag) in coarse.vertex_tags() {
+            mesh.tag_vertex(vertex, tag);
+        }
+        for (cell, parent) in bisection.cell_parents.iter().enumerate() {
+            if let Some(tag) = coarse.cell_tag(*parent) {
+                mesh.tag_cell(cell, tag);
+            }
+        }
+        Refinement {
+            mesh,
+            vertex_parents: bisection.vertex_parents,
+            cell_parents: bisection.cell_parents,
+            n_coarse_vertices: coarse.n_vertices(),
+        }
+    }
+
+    /// Refined mesh
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    /// Take the refined mesh out of the refinement
+    pub fn into_mesh(self) -> Mesh {
+        self.mesh
+    }
+
+    /// Vertices a refined vertex lies in the middle of (twice the same for coarse vertices)
+    ///
+    /// The parents are coarse vertices for uniform refinements and may be earlier midpoints for
+    /// local ones.
+    pub fn vertex_parents(&self, vertex: usize) -> [usize; 2] {
+        self.vertex_parents[vertex]
+    }
+
+    /// Coarse cell a refined cell was cut from
+    pub fn cell_parent(&self, cell: usize) -> usize {
+        self.cell_parents[cell]
+    }
+
+    /// Interpolation of the vertex values of the coarse mesh onto the refined mesh
+    ///
+    /// This is the prolongation between the continuous linear Lagrange spaces of the two meshes
+    /// whose dofs are numbered by vertex.
+    pub fn prolongation(&self) -> SparseCSR<f64> {
+        // Coarse weights of every vertex, the parents of a midpoint coming before it
+        let mut weights: Vec<Vec<(usize, f64)>> = Vec::with_capacity(self.vertex_parents.len());
+        for [a, b] in self.vertex_parents.iter() {
+            if a == b {
+                weights.push(vec![(*a, 1.0)]);
+            } else {
+                let mut merged: Vec<(usize, f64)> = Vec::new();
+                for (vertex, weight) in weights[*a].iter().chain(weights[*b].iter()) {
+                    match merged.iter_mut().find(|(v, _)| v == vertex) {
+                        Some((_, w)) => *w += 0.5 * weight,
+                        None => merged.push((*vertex, 0.5 * weight)),
+                    }
+                }
+                merged.sort_unstable_by_key(|(v, _)| *v);
+                weights.push(merged);
+            }
+        }
+        let mut row_offsets = vec![0];
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+        for row in weights {
+            for (vertex, weight) in row {
+                col_indices.push(vertex);
+                values.push(weight);
+            }
+            row_offsets.push(col_indices.len());
+        }
+        SparseCSR::new(self.n_coarse_vertices, row_offsets, col_indices, values)
+    }
+}
+
+// Mesh being refined by longest edge bisection
+struct Bisection {
+    dim: usize,
+    vertices: Vec<f64>,
+    vertex_parents: Vec<[usize; 2]>,
+    cells: Vec<Vec<usize>>,
+    cell_parents: Vec<usize>,
+    edge_cells: HashMap<(usize, usize), Vec<usize>>,
+    facet_tags: HashMap<Vec<usize>, usize>,
+    split: Vec<bool>,
+}
+
+impl Bisection {
+    // Register a cell with its edges
+    fn attach(&mut self, cell: usize) {
+        for edge in edges(&self.cells[cell]) {
+            self.edge_cells.entry(edge).or_default().push(cell);
+        }
+    }
+
+    // Remove a cell from its edges
+    fn detach(&mut self, cell: usize) {
+        for edge in edges(&self.cells[cell]) {
+            let cells = self.edge_cells.get_mut(&edge).unwrap();
+            cells.retain(|c| *c != cell);
+            if cells.is_empty() {
+                self.edge_cells.remove(&edge);
+            }
+        }
+    }
+
+    // Squared length of an edge
+    fn length(&self, (a, b): (usize, usize)) -> f64 {
+        let dim = self.dim;
+        (0..dim)
+            .map(|k| self.vertices[a * dim + k] - self.vertices[b * dim + k])
+            .map(|d| d * d)
+            .sum()
+    }
+
+    // Longest edge of a cell, ties broken by the vertex indices for all cells to agree
+    fn longest_edge(&self, cell: usize) -> (usize, usize) {
+        edges(&self.cells[cell])
+            .into_iter()
+            .max_by(|e, f| self.length(*e).total_cmp(&self.length(*f)).then(e.cmp(f)))
+            .unwrap()
+    }
+
+    // Bisect all the cells sharing an edge once it is their longest one
+    fn split_edge(&mut self, edge: (usize, usize)) {
+        loop {
+            let patch = self.edge_cells[&edge].clone();
+            // Longer edges of the neighbours are split first, which terminates as they grow
+            match patch
+                .iter()
+                .map(|c| self.longest_edge(*c))
+                .find(|longest| *longest != edge)
+            {
+                Some(longest) => self.split_edge(longest),
+                None => break,
+            }
+        }
+        let (a, b) = edge;
+        let midpoint = self.vertex_parents.len();
+        self.vertex_parents.push([a, b]);
+        for k in 0..self.dim {
+            let x = 0.5 * (self.vertices[a * self.dim + k] + self.vertices[b * self.dim + k]);
+            self.vertices.push(x);
+        }
+        for cell in self.edge_cells[&edge].clone() {
+            self.detach(cell);
+            let replace = |from: usize| -> Vec<usize> {
+                self.cells[cell]
+                    .iter()
+                    .map(|v| if *v == from { midpoint } else { *v })
+                    .collect()
+            };
+            let (first, second) = (replace(b), replace(a));
+            // Tagged facets of the cell holding the edge are split with it
+            for other in self.cells[cell].iter().filter(|v| **v != a && **v != b) {
+                let mut facet: Vec<usize> = self.cells[cell]
+                    .iter()
+                    .copied()
+                    .filter(|v| v != other)
+                    .collect();
+                facet.sort_unstable();
+                if let Some(tag) = self.facet_tags.remove(&facet) {
+                    for from in [a, b] {
+                        let mut child: Vec<usize> = facet
+                            .iter()
+                            .map(|v| if *v == from { midpoint } else { *v })
+                            .collect();
+                        child.sort_unstable();
+                        self.facet_tags.insert(child, tag);
+                    }
+                }
+            }
+            self.cells[cell] = first;
+            self.cells.push(second);
+            self.cell_parents.push(self.cell_parents[cell]);
+            if cell < self.split.len() {
+                self.split[cell] = true;
+            }
+            self.attach(cell);
+            self.attach(self.cells.len() - 1);
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Children of a simplex given by its vertices, the orientation of the parent is kept for segments
+// and triangles
+fn children<Midpoint>(vertices: &[usize], midpoint: &mut Midpoint) -> Vec<Vec<usize>>
+where
+    Midpoint: FnMut(usize, usize) -> usize,
+{
+    match *vertices {
+        [v] => vec![vec![v]],
+        [v0, v1] => {
+            let m = midpoint(v0, v1);
+            vec![vec![v0, m], vec![m, v1]]
+        }
+        [v0, v1, v2] => {
+            let (m01, m12, m02) = (midpoint(v0, v1), midpoint(v1, v2), midpoint(v0, v2));
+            vec![
+                vec![v0, m01, m02],
+                vec![m01, v1, m12],
+                vec![m02, m12, v2],
+                vec![m12, m02, m01],
+            ]
+        }
+        [v0, v1, v2, v3] => {
+            let (m01, m02, m03) = (midpoint(v0, v1), midpoint(v0, v2), midpoint(v0, v3));
+            let (m12, m13, m23) = (midpoint(v1, v2), midpoint(v1, v3), midpoint(v2, v3));
+            vec![
+                vec![v0, m01, m02, m03],
+                vec![m01, v1, m12, m13],
+                vec![m02, m12, v2, m23],
+                vec![m03, m13, m23, v3],
+                vec![m01, m02, m03, m13],
+                vec![m01, m02, m12, m13],
+                vec![m02, m03, m13, m23],
+                vec![m02, m12, m13, m23],
+            ]
+        }
+        _ => panic!("Refinement is only implemented for simplices up to tetrahedra"),
+    }
+}
+
+// Edges of a simplex as sorted vertex pairs
+fn edges(vertices: &[usize]) -> Vec<(usize, usize)> {
+    let mut edges = Vec::new();
+    for (k, a) in vertices.iter().enumerate() {
+        for b in &vertices[k + 1..] {
+            edges.push((*a.min(b), *a.max(b)));
+        }
+    }
+    edges
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::test_meshes::unit_square_cells;
+
+    #[test]
+    fn test_uniform_refinement() {
+        let mut mesh = unit_square_cells([0, 1, 2, 1, 3, 2]);
+        mesh.tag_boundary(7, |x| x[0] == 0.0);
+        let first = Refinement::uniform(&mesh);
+        let second = Refinement::uniform(first.mesh());
+        let refined = second.mesh();
+        assert_eq!(refined.n_cells(), 32, "Wrong number of cells");
+        assert_eq!(refined.n_vertices(), 25, "Wrong number of vertices");
+        assert_eq!(second.cell_parent(5), 1, "Wrong parent cell");
+        let parents = first.vertex_parents(4);
+        assert_eq!(parents, [0, 1], "Wrong parents of the first midpoint");
+        assert_eq!(first.mesh().vertex(4), &[0.5, 0.0], "Wrong midpoint");
+        let left_facets = refined.facet_tags().filter(|(_, t)| *t == 7).count();
+        assert_eq!(left_facets, 4, "Tags were not passed to the child facets");
+        // Linear functions are interpolated exactly
+        let linear = |x: &[f64]| 1.0 + 2.0 * x[0] - x[1];
+        let coarse_values: Vec<f64> = (0..first.mesh().n_vertices())
+            .map(|v| linear(first.mesh().vertex(v)))
+            .collect();
+        let mut fine_values = vec![0.0; refined.n_vertices()];
+        second
+            .prolongation()
+            .apply(&coarse_values, &mut fine_values);
+        for (v, value) in fine_values.iter().enumerate() {
+            assert!(
+                (value - linear(refined.vertex(v))).abs() < 1e-14,
+                "Wrong prolongation"
+            );
+        }
+        // Bey's refinement keeps the volume of a tetrahedron
+        let tetrahedron = Mesh::new(
+            DataHold::new(
+                vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+                [4, 3],
+            ),
+            DataHold::new(vec![0, 1, 2, 3], [1, 4]),
+        );
+        let refined = Refinement::uniform(&tetrahedron).into_mesh();
+        assert_eq!(refined.n_vertices(), 10, "Wrong number of vertices");
+        let volumes: Vec<f64> = (0..refined.n_cells())
+            .map(|c| {
+                let v: Vec<&[f64]> = refined.cell(c).iter().map(|v| refined.vertex(*v)).collect();
+                let e: Vec<Vec<f64>> = (1..4)
+                    .map(|i| (0..3).map(|k| v[i][k] - v[0][k]).collect())
+                    .collect();
+                let det = e[0][0] * (e[1][1] * e[2][2] - e[1][2] * e[2][1])
+                    - e[0][1] * (e[1][0] * e[2][2] - e[1][2] * e[2][0])
+                    + e[0][2] * (e[1][0] * e[2][1] - e[1][1] * e[2][0]);
+                det.abs() / 6.0
+            })
+            .collect();
+        assert!(
+            volumes.iter().all(|v| (v - 1.0 / 48.0).abs() < 1e-14),
+            "Children should all have the same volume"
+        );
+        let volume: f64 = volumes.iter().sum();
+        assert!((volume - 1.0 / 6.0).abs() < 1e-14, "Volume was not kept");
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_local_refinement() {
+        use crate::discretizations::cartesian_grid::CartesianGrid;
+        for dim in [2, 3] {
+            let grid = CartesianGrid::new(vec![0.0; dim], vec![1.0; dim], vec![2; dim]);
+            let mut mesh = grid.simplex_mesh();
+            // Repeatedly refine the cells touching the origin
+            for _ in 0..4 {
+                let marked: Vec<usize> = (0..mesh.n_cells())
+                    .filter(|c| {
+                        mesh.cell(*c)
+                            .iter()
+                            .any(|v| mesh.vertex(*v).iter().all(|x| *x == 0.0))
+                    })
+                    .collect();
+                let refinement = Refinement::bisect(&mesh, &marked);
+                assert!(
+                    refinement.mesh().n_cells() > mesh.n_cells() + marked.len() - 1,
+                    "Marked cells were not bisected"
+                );
+                mesh = refinement.into_mesh();
+            }
+            // Conforming meshes only have facets with a single cell on the boundary
+            let mut facets: HashMap<Vec<usize>, usize> = HashMap::new();
+            let mut volume = 0.0;
+            for c in 0..mesh.n_cells() {
+                let cell = mesh.cell(c);
+                for skip in 0..cell.len() {
+                    let mut facet: Vec<usize> = cell.to_vec();
+                    facet.remove(skip);
+                    facet.sort_unstable();
+                    *facets.entry(facet).or_default() += 1;
+                }
+                let origin = mesh.vertex(cell[0]);
+                let edges: Vec<Vec<f64>> = cell[1..]
+                    .iter()
+                    .map(|v| {
+                        mesh.vertex(*v)
+                            .iter()
+                            .zip(origin)
+                            .map(|(x, o)| x - o)
+                            .collect()
+                    })
+                    .collect();
+                volume += match dim {
+                    2 => (edges[0][0] * edges[1][1] - edges[0][1] * edges[1][0]).abs() / 2.0,
+                    _ => {
+                        let e = &edges;
+                        (e[0][0] * (e[1][1] * e[2][2] - e[1][2] * e[2][1])
+                            - e[0][1] * (e[1][0] * e[2][2] - e[1][2] * e[2][0])
+                            + e[0][2] * (e[1][0] * e[2][1] - e[1][1] * e[2][0]))
+                            .abs()
+                            / 6.0
+                    }
+                };
+            }
+            assert!(
+                (volume - 1.0).abs() < 1e-12,
+                "Volume was not kept in {}D",
+                dim
+            );
+            for (facet, count) in &facets {
+                let on_boundary = (0..dim).any(|k| {
+                    facet.iter().all(|v| mesh.vertex(*v)[k] == 0.0)
+                        || facet.iter().all(|v| mesh.vertex(*v)[k] == 1.0)
+                });
+                assert!(
+                    *count == 2 || (*count == 1 && on_boundary),
+                    "Hanging facet {:?} in {}D",
+                    facet,
+                    dim
+                );
+                let tagged = mesh.facet_tag(facet).is_some();
+                assert_eq!(
+                    tagged, on_boundary,
+                    "Boundary tags were not passed on in {}D",
+                    dim
+                );
+            }
+        }
+        // Linear functions are interpolated exactly through the chains of midpoints
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![2, 2]).simplex_mesh();
+        let first = Refinement::bisect(&mesh, &[0]);
+        let second = Refinement::bisect(first.mesh(), &[0, 1]);
+        let linear = |x: &[f64]| 1.0 + 2.0 * x[0] - x[1];
+        let coarse_values: Vec<f64> = (0..first.mesh().n_vertices())
+            .map(|v| linear(first.mesh().vertex(v)))
+            .collect();
+        let refined = second.mesh();
+        let mut fine_values = vec![0.0; refined.n_vertices()];
+        second
+            .prolongation()
+            .apply(&coarse_values, &mut fine_values);
+        for (v, value) in fine_values.iter().enumerate() {
+            assert!(
+                (value - linear(refined.vertex(v))).abs() < 1e-14,
+                "Wrong prolongation"
+            );
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use super::constraints::AffineConstraints;
+use super::dof_map::DofMap;
+use super::facets::Facets;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use std::clone::Clone;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Symbolic structure of a sparse matrix in the compressed sparse row format
+///
+/// A SparsityPattern only holds the positions of the non zero entries. It is meant to be computed
+/// once for a given discretization and then used to allocate as many matrices as needed (see
+/// SparseCSR) so that numerical assembly never has to reallocate.
+pub struct SparsityPattern {
+    n_cols: usize,
+    row_offsets: Vec<usize>,
+    col_indices: Vec<usize>,
+}
+
+impl SparsityPattern {
+    /// Compute the pattern of the global matrix coupling all the dofs sharing a cell
+    pub fn from_dofmap(dof_map: &DofMap) -> Self {
+        SparsityPattern::from_groups(dof_map.n_dofs(), dof_map.n_cells(), |cell, dofs| {
+            dofs.extend_from_slice(dof_map.cell_dofs(cell))
+        })
+    }
+
+    /// Compute the pattern of the global matrix also coupling the dofs of cells sharing a facet
+    ///
+    /// This is the pattern needed by interior facet integrals (see
+    /// Assembler::assemble_interior_facets).
+    pub fn from_dofmap_and_facets(dof_map: &DofMap, facets: &Facets) -> Self {
+        let interior = facets.interior_facets();
+        let n_cells = dof_map.n_cells();
+        SparsityPattern::from_groups(dof_map.n_dofs(), n_cells + interior.len(), |group, dofs| {
+            if group < n_cells {
+                dofs.extend_from_slice(dof_map.cell_dofs(group));
+            } else {
+                for (cell, _) in facets.facet_cells(interior[group - n_cells]) {
+                    dofs.extend_from_slice(dof_map.cell_dofs(*cell));
+                }
+            }
+        })
+    }
+
+    /// Compute the pattern of the global matrix once the constraints are condensed
+    ///
+    /// The dofs of a cell are coupled with the dofs their constraints depend on (see
+    /// AffineConstraints::distribute_local_to_global).
+    pub fn from_dofmap_and_constraints(dof_map: &DofMap, constraints: &AffineConstraints) -> Self {
+        SparsityPattern::from_groups(dof_map.n_dofs(), dof_map.n_cells(), |cell, dofs| {
+            for dof in dof_map.cell_dofs(cell) {
+                dofs.push(*dof);
+                if let Some((entries, _)) = constraints.constraint(*dof) {
+                    dofs.extend(entries.iter().map(|(j, _)| *j));
+                }
+            }
+        })
+    }
+
+    // Build the pattern where all the dofs of each group are coupled together
+    fn from_groups<Fill>(n_dofs: usize, n_groups: usize, fill: Fill) -> Self
+    where
+        Fill: Fn(usize, &mut Vec<usize>),
+    {
+        let mut group_dofs = Vec::new();
+        // Invert the groups to get the groups touching every dof
+        let mut dof_group_offsets = vec![0; n_dofs + 1];
+        for group in 0..n_groups {
+            group_dofs.clear();
+            fill(group, &mut group_dofs);
+            for dof in group_dofs.iter() {
+                dof_group_offsets[dof + 1] += 1;
+            }
+        }
+        for dof in 0..n_dofs {
+            dof_group_offsets[dof + 1] += dof_group_offsets[dof];
+        }
+        let mut dof_groups = vec![0; dof_group_offsets[n_dofs]];
+        let mut position = dof_group_offsets.clone();
+        for group in 0..n_groups {
+            group_dofs.clear();
+            fill(group, &mut group_dofs);
+            for dof in group_dofs.iter() {
+                dof_groups[position[*dof]] = group;
+                position[*dof] += 1;
+            }
+        }
+        // Gather the coupled dofs row by row using a marker to avoid duplicates
+        let mut marker = vec![usize::MAX; n_dofs];
+        let mut row_offsets = Vec::with_capacity(n_dofs + 1);
+        let mut col_indices = Vec::new();
+        row_offsets.push(0);
+        for row in 0..n_dofs {
+            let row_start = col_indices.len();
+            for group in &dof_groups[dof_group_offsets[row]..dof_group_offsets[row + 1]] {
+                group_dofs.clear();
+                fill(*group, &mut group_dofs);
+                for col in group_dofs.iter() {
+                    if marker[*col] != row {
+                        marker[*col] = row;
+                        col_indices.push(*col);
+                    }
+                }
+            }
+            col_indices[row_start..].sort_unstable();
+            row_offsets.push(col_indices.len());
+        }
+        SparsityPattern {
+            n_cols: n_dofs,
+            row_offsets,
+            col_indices,
+        }
+    }
+
+    /// Number of rows in the pattern
+    pub fn n_rows(&self) -> usize {
+        self.row_offsets.len() - 1
+    }
+
+    /// Number of columns in the pattern
+    pub fn n_cols(&self) -> usize {
+        self.n_cols
+    }
+
+    /// Number of non zero entries in the pattern
+    pub fn nnz(&self) -> usize {
+        self.col_indices.len()
+    }
+
+    /// Offsets of each row in the column indices array
+    pub fn row_offsets(&self) -> &[usize] {
+        &self.row_offsets
+    }
+
+    /// Column indices of the non zero entries
+    pub fn col_indices(&self) -> &[usize] {
+        &self.col_indices
+    }
+
+    /// Allocate a matrix following the pattern with all entries set to value
+    pub fn to_csr<DataType: Clone>(&self, value: DataType) -> SparseCSR<DataType> {
+        SparseCSR::new(
+            self.n_cols,
+            self.row_offsets.clone(),
+            self.col_indices.clone(),
+            vec![value; self.nnz()],
+        )
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::data_hold::DataHold;
+    use crate::discretizations::test_meshes::unit_square_cells;
+
+    // Two triangles sharing the edge (1, 2)
+    fn build_dof_map() -> DofMap {
+        DofMap::new(DataHold::new(vec![0, 1, 2, 1, 3, 2], [2, 3]), 4)
+    }
+
+    #[test]
+    fn test_sparsity_pattern_from_dofmap() {
+        let pattern = SparsityPattern::from_dofmap(&build_dof_map());
+        assert_eq!(pattern.n_rows(), 4, "Wrong number of rows");
+        assert_eq!(pattern.n_cols(), 4, "Wrong number of columns");
+        assert_eq!(pattern.nnz(), 14, "Wrong number of non zeros");
+        assert_eq!(
+            pattern.row_offsets(),
+            &[0, 3, 7, 11, 14],
+            "Wrong row offsets"
+        );
+        assert_eq!(
+            pattern.col_indices(),
+            &[0, 1, 2, 0, 1, 2, 3, 0, 1, 2, 3, 1, 2, 3],
+            "Wrong column indices"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_sparsity_pattern_to_csr() {
+        let pattern = SparsityPattern::from_dofmap(&build_dof_map());
+        let csr = pattern.to_csr(0.0);
+        assert_eq!(
+            csr.nnz(),
+            pattern.nnz(),
+            "Matrix does not follow the pattern"
+        );
+        assert_eq!(csr.get(0, 3), None, "Dofs 0 and 3 should not be coupled");
+        assert_eq!(csr.get(3, 1), Some(&0.0), "Dofs 3 and 1 should be coupled");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_sparsity_pattern_from_dofmap_and_facets() {
+        // Discontinuous numbering of two triangles sharing the edge (1, 2)
+        let mesh = unit_square_cells([0, 1, 2, 1, 3, 2]);
+        let dof_map = DofMap::new(DataHold::new(vec![0, 1, 2, 3, 4, 5], [2, 3]), 6);
+        let pattern = SparsityPattern::from_dofmap(&dof_map);
+        assert_eq!(pattern.nnz(), 18, "Cells should not be coupled");
+        let pattern = SparsityPattern::from_dofmap_and_facets(&dof_map, &Facets::new(&mesh));
+        assert_eq!(pattern.nnz(), 36, "Neighbouring cells should be coupled");
+    }
+}
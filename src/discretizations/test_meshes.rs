@@ -0,0 +1,57 @@
+use super::cartesian_grid::CartesianGrid;
+use super::mesh::Mesh;
+use crate::core::arrays::data_hold::DataHold;
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Unit square split in two triangles along the diagonal from (1, 0) to (0, 1)
+pub(crate) fn unit_square() -> Mesh {
+    unit_square_cells([0, 1, 2, 3, 2, 1])
+}
+
+/// Unit square with the vertices (0, 0), (1, 0), (0, 1) and (1, 1) split in two triangles given by
+/// their vertices
+pub(crate) fn unit_square_cells(cells: [usize; 6]) -> Mesh {
+    Mesh::new(
+        DataHold::new(vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0], [4, 2]),
+        DataHold::new(cells.to_vec(), [2, 3]),
+    )
+}
+
+/// Unit square split in n x n squares of two triangles each, the vertices being numbered along x
+/// first
+pub(crate) fn square_grid(n: usize) -> Mesh {
+    let mut vertices = Vec::new();
+    for j in 0..=n {
+        for i in 0..=n {
+            vertices.extend_from_slice(&[i as f64 / n as f64, j as f64 / n as f64]);
+        }
+    }
+    let mut cells = Vec::new();
+    for j in 0..n {
+        for i in 0..n {
+            let v = (n + 1) * j + i;
+            cells.extend_from_slice(&[v, v + 1, v + n + 1, v + n + 2, v + n + 1, v + 1]);
+        }
+    }
+    Mesh::new(
+        DataHold::new(vertices, [(n + 1) * (n + 1), 2]),
+        DataHold::new(cells, [2 * n * n, 3]),
+    )
+}
+
+/// Triangle mesh of the plane without any vertex or cell
+pub(crate) fn empty_mesh() -> Mesh {
+    Mesh::new(
+        DataHold::new(Vec::new(), [0, 2]),
+        DataHold::new(Vec::new(), [0, 3]),
+    )
+}
+
+/// Unit square of a cartesian grid of n x n squares split in two triangles each, its sides being
+/// tagged as by CartesianGrid::simplex_mesh
+pub(crate) fn unit_square_grid(n: usize) -> Mesh {
+    CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![n, n]).simplex_mesh()
+}
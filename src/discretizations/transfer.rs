@@ -0,0 +1,304 @@
+use super::assembler::Assembler;
+use super::cell_mapping::CellMapping;
+use super::function::Function;
+use super::function_space::FunctionSpace;
+use super::mesh::Mesh;
+use super::operators::mass_matrix;
+use super::projection::{conjugate_gradient, interpolate};
+use crate::spaces::quadrature::QuadratureRule;
+
+// Relative area under which pieces of the supermesh are dropped
+const TOLERANCE: f64 = 1e-12;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Way of transferring a function onto a space of a non-matching mesh
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransferMethod {
+    /// Evaluate the source at the nodes of the target
+    Interpolation,
+    /// L2 projection integrated exactly on the supermesh of the two meshes
+    ///
+    /// The integral of the function over the common domain is preserved.
+    ConservativeProjection,
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Transfer a function onto a space built on another mesh
+///
+/// Interpolation needs the nodes of the target to be covered by the source mesh. Conservative
+/// projection integrates the source against the target shape functions on the intersections of
+/// the cells of both meshes and is only implemented for triangle meshes.
+pub fn transfer<'a>(
+    source: &Function,
+    target: &'a FunctionSpace<'a>,
+    method: TransferMethod,
+) -> Function<'a> {
+    match method {
+        TransferMethod::Interpolation => interpolate(source, target),
+        TransferMethod::ConservativeProjection => conservative_projection(source, target),
+    }
+}
+
+// Solve the mass matrix systems of the target with right hand sides integrated on the supermesh
+fn conservative_projection<'a>(source: &Function, target: &'a FunctionSpace<'a>) -> Function<'a> {
+    let source_space = source.space();
+    let source_mesh = source_space.mesh();
+    let mesh = target.mesh();
+    assert!(
+        source_space.n_components() == target.n_components(),
+        "Spaces do not have the same number of components"
+    );
+    assert!(
+        mesh.topological_dim() == 2
+            && mesh.geometric_dim() == 2
+            && source_mesh.topological_dim() == 2
+            && source_mesh.geometric_dim() == 2,
+        "Conservative transfer is only implemented between triangle meshes of the plane"
+    );
+    // Nothing is integrated without cells on either side
+    if mesh.n_cells() == 0 || source_mesh.n_cells() == 0 {
+        return Function::new(target);
+    }
+    let element = target.element();
+    let n_components = target.n_components();
+    let n_scalar = target.dof_map().n_dofs();
+    let quadrature = QuadratureRule::simplex(2, source_space.element().order() + element.order());
+    let mut rhs = vec![vec![0.0; n_scalar]; n_components];
+    let mut shape = vec![0.0; element.n_dofs()];
+    let mut value = vec![0.0; n_components];
+    let mut x = [0.0; 2];
+    let mut reference = [0.0; 2];
+    let mut source_reference = [0.0; 2];
+    let mut mapping = CellMapping::new(mesh, 0);
+    let mut source_mapping = CellMapping::new(source_mesh, 0);
+    for cell in 0..mesh.n_cells() {
+        mapping.reinit(mesh, cell);
+        let triangle = counterclockwise(mesh, cell);
+        let lower = [0, 1].map(|d| triangle.iter().fold(f64::INFINITY, |m, p| m.min(p[d])));
+        let upper = [0, 1].map(|d| triangle.iter().fold(f64::NEG_INFINITY, |m, p| m.max(p[d])));
+        let minimum_area = TOLERANCE * mapping.determinant().abs();
+        let dofs = target.dof_map().cell_dofs(cell);
+        for source_cell in source_mesh.cell_tree().overlapping(&lower, &upper) {
+            let polygon = clip(&counterclockwise(source_mesh, source_cell), &triangle);
+            if polygon.len() < 3 {
+                continue;
+            }
+            source_mapping.reinit(source_mesh, source_cell);
+            // Fan triangulation of the convex intersection
+            for k in 1..(polygon.len() - 1) {
+                let (p0, p1, p2) = (polygon[0], polygon[k], polygon[k + 1]);
+                let det = cross(&p0, &p1, &p2);
+                if det <= minimum_area {
+                    continue;
+                }
+                for q in 0..quadrature.n_points() {
+                    let xi = quadrature.point(q);
+                    for d in 0..2 {
+                        x[d] = p0[d] + xi[0] * (p1[d] - p0[d]) + xi[1] * (p2[d] - p0[d]);
+                    }
+                    let weight = quadrature.weights()[q] * det;
+                    source_mapping.inverse_map_point(&x, &mut source_reference);
+                    source.eval_in_cell(source_cell, &source_reference, &mut value);
+                    mapping.inverse_map_point(&x, &mut reference);
+                    element.values(&reference, &mut shape);
+                    for (c, v) in value.iter().enumerate() {
+                        for (dof, phi) in dofs.iter().zip(shape.iter()) {
+                            rhs[c][*dof] += weight * v * phi;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let assembler = Assembler::new(
+        mesh,
+        element,
+        target.dof_map(),
+        QuadratureRule::simplex(2, 2 * element.order()),
+    );
+    let mass = mass_matrix(&assembler);
+    let mut result = Function::new(target);
+    let mut solution = vec![0.0; n_scalar];
+    for (c, b) in rhs.iter().enumerate() {
+        conjugate_gradient(&mass, b, &mut solution);
+        result.set_component(c, &solution);
+    }
+    result
+}
+
+// Vertices of a triangle of the mesh in counterclockwise order
+fn counterclockwise(mesh: &Mesh, cell: usize) -> [[f64; 2]; 3] {
+    let vertices = mesh.cell(cell);
+    let mut triangle = [0, 1, 2].map(|i| {
+        let x = mesh.vertex(vertices[i]);
+        [x[0], x[1]]
+    });
+    if cross(&triangle[0], &triangle[1], &triangle[2]) < 0.0 {
+        triangle.swap(1, 2);
+    }
+    triangle
+}
+
+// Twice the signed area of the triangle (a, b, c)
+fn cross(a: &[f64; 2], b: &[f64; 2], c: &[f64; 2]) -> f64 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+// Sutherland-Hodgman clipping of a convex polygon by a counterclockwise triangle
+fn clip(subject: &[[f64; 2]], triangle: &[[f64; 2]; 3]) -> Vec<[f64; 2]> {
+    let mut polygon = subject.to_vec();
+    for e in 0..3 {
+        let (a, b) = (triangle[e], triangle[(e + 1) % 3]);
+        let side = |p: &[f64; 2]| cross(&a, &b, p);
+        let input = std::mem::take(&mut polygon);
+        for (i, p) in input.iter().enumerate() {
+            let next = &input[(i + 1) % input.len()];
+            let (s, t) = (side(p), side(next));
+            if s >= 0.0 {
+                polygon.push(*p);
+            }
+            if (s >= 0.0) != (t >= 0.0) {
+                let lambda = s / (s - t);
+                polygon.push([
+                    p[0] + lambda * (next[0] - p[0]),
+                    p[1] + lambda * (next[1] - p[1]),
+                ]);
+            }
+        }
+        if polygon.is_empty() {
+            break;
+        }
+    }
+    polygon
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::data_hold::DataHold;
+    use crate::discretizations::test_meshes::{empty_mesh, square_grid};
+    use crate::spaces::lagrange::LagrangeElement;
+
+    // Unit square cut in n x n squares split in two triangles along either diagonal
+    fn build_mesh(n: usize, flip: bool) -> Mesh {
+        let grid = square_grid(n);
+        if !flip {
+            return grid;
+        }
+        let mut cells = Vec::new();
+        for j in 0..n {
+            for i in 0..n {
+                let v = (n + 1) * j + i;
+                cells.extend_from_slice(&[v, v + 1, v + n + 2, v, v + n + 2, v + n + 1]);
+            }
+        }
+        Mesh::new(
+            DataHold::new(grid.vertices().to_vec(), [(n + 1) * (n + 1), 2]),
+            DataHold::new(cells, [2 * n * n, 3]),
+        )
+    }
+
+    // Integral of a linear function from its vertex values
+    fn integral(u: &Function) -> f64 {
+        let mesh = u.space().mesh();
+        (0..mesh.n_cells())
+            .map(|cell| {
+                let area = 0.5 * CellMapping::new(mesh, cell).determinant().abs();
+                let sum: f64 = mesh.cell(cell).iter().map(|v| u.values()[*v]).sum();
+                area * sum / 3.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_clip_triangles() {
+        let triangle = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+        let shifted = [[0.5, 0.0], [1.5, 0.0], [0.5, 1.0]];
+        let polygon = clip(&shifted, &triangle);
+        let area: f64 = (1..(polygon.len() - 1))
+            .map(|k| 0.5 * cross(&polygon[0], &polygon[k], &polygon[k + 1]))
+            .sum();
+        assert!((area - 0.125).abs() < 1e-15, "Wrong intersection area");
+        let far = [[2.0, 2.0], [3.0, 2.0], [2.0, 3.0]];
+        assert!(
+            clip(&far, &triangle).is_empty(),
+            "Triangles do not intersect"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_transfer_conservative() {
+        let source_mesh = build_mesh(5, false);
+        let target_mesh = build_mesh(3, true);
+        let source_space = FunctionSpace::new(&source_mesh, LagrangeElement::new(2, 1));
+        let target_space = FunctionSpace::new(&target_mesh, LagrangeElement::new(2, 1));
+        let mut u = Function::new(&source_space);
+        u.interpolate(|x| (3.0 * x[0]).sin() * x[1] + x[1] * x[1]);
+        let transferred = transfer(&u, &target_space, TransferMethod::ConservativeProjection);
+        assert!(
+            (integral(&transferred) - integral(&u)).abs() < 1e-12,
+            "Conservative transfer should preserve the integral"
+        );
+        // Linear functions belong to both spaces and are transferred exactly
+        u.interpolate(|x| 1.0 + 2.0 * x[0] - x[1]);
+        let transferred = transfer(&u, &target_space, TransferMethod::ConservativeProjection);
+        for (v, x) in transferred
+            .values()
+            .iter()
+            .zip((0..target_mesh.n_vertices()).map(|v| target_mesh.vertex(v)))
+        {
+            assert!(
+                (v - 1.0 - 2.0 * x[0] + x[1]).abs() < 1e-12,
+                "Linear function should be transferred exactly"
+            );
+        }
+        let empty = empty_mesh();
+        let empty_space = FunctionSpace::new(&empty, LagrangeElement::new(2, 1));
+        let method = TransferMethod::ConservativeProjection;
+        assert!(
+            transfer(&u, &empty_space, method).values().is_empty(),
+            "Transfer onto an empty mesh has no values"
+        );
+        let nothing = Function::new(&empty_space);
+        assert!(
+            transfer(&nothing, &target_space, method)
+                .values()
+                .iter()
+                .all(|v| *v == 0.0),
+            "Transfer from an empty mesh should vanish"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_transfer_interpolation() {
+        let source_mesh = build_mesh(4, false);
+        let target_mesh = build_mesh(3, true);
+        let source_space = FunctionSpace::new(&source_mesh, LagrangeElement::new(2, 2));
+        let target_space = FunctionSpace::new(&target_mesh, LagrangeElement::new(2, 1));
+        let mut u = Function::new(&source_space);
+        u.interpolate(|x| x[0] * x[1]);
+        let transferred = transfer(&u, &target_space, TransferMethod::Interpolation);
+        for (v, x) in transferred
+            .values()
+            .iter()
+            .zip((0..target_mesh.n_vertices()).map(|v| target_mesh.vertex(v)))
+        {
+            assert!(
+                (v - x[0] * x[1]).abs() < 1e-12,
+                "Quadratic source should be interpolated exactly at the vertices"
+            );
+        }
+    }
+}
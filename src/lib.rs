@@ -19,3 +19,10 @@ pub mod solvers;
 
 /// Module implementing recurring data pipelines while using the library
 pub mod workflows;
+
+/// Module configuring the threads used by the library
+pub mod parallel;
+
+/// Module exposing meshes, spaces, operators, solvers and workflows to Python
+#[cfg(feature = "python")]
+pub mod python;
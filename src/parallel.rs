@@ -0,0 +1,374 @@
+use crate::core::logging::warning;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::RwLock;
+#[cfg(feature = "parallel")]
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// Configuration of the process, read from the environment on first use
+static CONFIG: RwLock<Option<ParallelConfig>> = RwLock::new(None);
+
+// Thread pool of the configured number of threads and pinning, built on first use
+#[cfg(feature = "parallel")]
+static POOL: Mutex<Option<(usize, Pinning, Arc<rayon::ThreadPool>)>> = Mutex::new(None);
+
+//--------------------------------------------------------------------------------------------------
+// # Enums
+//--------------------------------------------------------------------------------------------------
+
+/// Placement of the worker threads of the thread pool on the cores
+///
+/// With the parallel feature the threads of the pool are pinned on their cores when they start,
+/// on Linux only: elsewhere, or if the system refuses, they are left to the scheduler.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Pinning {
+    /// Threads left to the scheduler
+    #[default]
+    None,
+    /// Threads on consecutive cores
+    Compact,
+    /// Threads spread evenly over the cores
+    Spread,
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Thread usage of the library, shared by the process
+///
+/// The number of threads sizes the assembly loops and, with the parallel feature, the thread pool
+/// of the sparse matrix vector products and Krylov vector kernels, which are only split between
+/// threads above a vector length or a number of rows and hand at least a chunk of entries to each
+/// thread. By default the number of threads is the available parallelism (the global rayon pool
+/// with the parallel feature), vectors are split from 8192 entries, matrices from 4096 rows and
+/// chunks hold 1024 entries.
+///
+/// The configuration is read from the FE2O3_NUM_THREADS, FE2O3_MIN_LENGTH, FE2O3_MIN_ROWS,
+/// FE2O3_CHUNK_SIZE and FE2O3_PINNING (none, compact or spread) environment variables on first
+/// use, the defaults being kept if a variable is invalid (with a warning through the log facade
+/// with the log feature, from_env giving the error), and can be replaced at any time by
+/// set_config. A single thread runs everything serially.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParallelConfig {
+    num_threads: Option<usize>,
+    min_length: usize,
+    min_rows: usize,
+    chunk_size: usize,
+    pinning: Pinning,
+}
+
+impl ParallelConfig {
+    /// Configuration with the default parameters
+    pub fn new() -> Self {
+        ParallelConfig {
+            num_threads: None,
+            min_length: 8192,
+            min_rows: 4096,
+            chunk_size: 1024,
+            pinning: Pinning::None,
+        }
+    }
+
+    /// Default configuration overridden by the environment variables which are set, an error
+    /// naming the variable and its value if one of them is invalid
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Set the number of threads
+    pub fn with_num_threads(mut self, num_threads: usize) -> Self {
+        assert!(num_threads > 0, "Parallel code needs at least one thread");
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    /// Set the vector length from which the Krylov kernels are split between threads
+    pub fn with_min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Set the number of rows from which the matrix vector products are split between threads
+    pub fn with_min_rows(mut self, min_rows: usize) -> Self {
+        self.min_rows = min_rows;
+        self
+    }
+
+    /// Set the least number of entries or rows handed to a thread
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "Chunks should hold at least one entry");
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Set the placement hint of the threads
+    pub fn with_pinning(mut self, pinning: Pinning) -> Self {
+        self.pinning = pinning;
+        self
+    }
+
+    /// Number of threads, the available parallelism if it is not set
+    pub fn num_threads(&self) -> usize {
+        self.num_threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+
+    /// Vector length from which the Krylov kernels are split between threads
+    pub fn min_length(&self) -> usize {
+        self.min_length
+    }
+
+    /// Number of rows from which the matrix vector products are split between threads
+    pub fn min_rows(&self) -> usize {
+        self.min_rows
+    }
+
+    /// Least number of entries or rows handed to a thread
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Placement hint of the threads
+    pub fn pinning(&self) -> Pinning {
+        self.pinning
+    }
+
+    // Default configuration overridden by the variables found by a lookup
+    fn from_vars<Lookup>(lookup: Lookup) -> Result<Self>
+    where
+        Lookup: Fn(&str) -> Option<String>,
+    {
+        let invalid = |name: &str, expected: &str, value: &str| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} should be {}, not {:?}", name, expected, value),
+            )
+        };
+        let count = |name: &str, least: usize| {
+            lookup(name)
+                .map(|value| match value.trim().parse::<usize>() {
+                    Ok(count) if count >= least => Ok(count),
+                    _ if least > 0 => Err(invalid(name, "a positive count", &value)),
+                    _ => Err(invalid(name, "a count", &value)),
+                })
+                .transpose()
+        };
+        let mut config = Self::new();
+        if let Some(num_threads) = count("FE2O3_NUM_THREADS", 1)? {
+            config = config.with_num_threads(num_threads);
+        }
+        if let Some(min_length) = count("FE2O3_MIN_LENGTH", 0)? {
+            config = config.with_min_length(min_length);
+        }
+        if let Some(min_rows) = count("FE2O3_MIN_ROWS", 0)? {
+            config = config.with_min_rows(min_rows);
+        }
+        if let Some(chunk_size) = count("FE2O3_CHUNK_SIZE", 1)? {
+            config = config.with_chunk_size(chunk_size);
+        }
+        if let Some(pinning) = lookup("FE2O3_PINNING") {
+            config = config.with_pinning(match pinning.trim().to_lowercase().as_str() {
+                "none" => Pinning::None,
+                "compact" => Pinning::Compact,
+                "spread" => Pinning::Spread,
+                _ => {
+                    return Err(invalid(
+                        "FE2O3_PINNING",
+                        "none, compact or spread",
+                        &pinning,
+                    ))
+                }
+            });
+        }
+        Ok(config)
+    }
+}
+
+impl Default for ParallelConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pinning {
+    /// Core hinted for a thread of a pool of n_threads on n_cores, None if left to the scheduler
+    pub fn core(&self, thread: usize, n_threads: usize, n_cores: usize) -> Option<usize> {
+        assert!(
+            thread < n_threads && n_cores > 0,
+            "Thread out of the pool or no cores"
+        );
+        match self {
+            Pinning::None => None,
+            Pinning::Compact => Some(thread % n_cores),
+            Pinning::Spread => Some(thread * n_cores.max(n_threads) / n_threads % n_cores),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Configuration of the process
+pub fn config() -> ParallelConfig {
+    if let Some(config) = *CONFIG.read().unwrap() {
+        return config;
+    }
+    *CONFIG.write().unwrap().get_or_insert_with(|| {
+        ParallelConfig::from_env().unwrap_or_else(|error| {
+            warning!("{}, keeping the default parallel configuration", error);
+            ParallelConfig::new()
+        })
+    })
+}
+
+/// Replace the configuration of the process, which applies to the next parallel loops
+pub fn set_config(config: ParallelConfig) {
+    *CONFIG.write().unwrap() = Some(config);
+}
+
+/// Number of threads of the configuration of the process
+pub fn num_threads() -> usize {
+    config().num_threads()
+}
+
+// Chunk size if a vector of a length is split between threads
+#[cfg(feature = "parallel")]
+pub(crate) fn vector_chunk(length: usize) -> Option<usize> {
+    let config = config();
+    (config.num_threads != Some(1) && length >= config.min_length).then_some(config.chunk_size)
+}
+
+// Chunk size if a matrix of a number of rows is split between threads
+#[cfg(feature = "parallel")]
+pub(crate) fn row_chunk(n_rows: usize) -> Option<usize> {
+    let config = config();
+    (config.num_threads != Some(1) && n_rows >= config.min_rows).then_some(config.chunk_size)
+}
+
+// Run an operation in the thread pool of the configuration, the global rayon pool if neither the
+// number of threads nor the pinning is set
+#[cfg(feature = "parallel")]
+pub(crate) fn install<Operation, R>(operation: Operation) -> R
+where
+    Operation: FnOnce() -> R + Send,
+    R: Send,
+{
+    let config = config();
+    if config.num_threads.is_none() && config.pinning == Pinning::None {
+        return operation();
+    }
+    let (num_threads, pinning) = (config.num_threads(), config.pinning);
+    let pool = {
+        let mut pool = POOL.lock().unwrap();
+        if !matches!(&*pool, Some((n, p, _)) if *n == num_threads && *p == pinning) {
+            let n_cores = thread::available_parallelism().map_or(1, |n| n.get());
+            let built = rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .thread_name(|i| format!("fe2o3-{}", i))
+                .start_handler(move |i| {
+                    if let Some(core) = pinning.core(i, num_threads, n_cores) {
+                        pin_thread(core);
+                    }
+                })
+                .build()
+                .expect("Failed to build the thread pool");
+            *pool = Some((num_threads, pinning, Arc::new(built)));
+        }
+        pool.as_ref().unwrap().2.clone()
+    };
+    pool.install(operation)
+}
+
+// Pin the calling thread on a core through the affinity mask of Linux, the thread being left to
+// the scheduler if the core is out of the mask or the call fails
+#[cfg(all(feature = "parallel", target_os = "linux"))]
+fn pin_thread(core: usize) {
+    extern "C" {
+        fn sched_setaffinity(pid: i32, size: usize, mask: *const u64) -> i32;
+    }
+    // Mask of the 1024 cores of cpu_set_t
+    let mut mask = [0u64; 16];
+    if core >= 64 * mask.len() {
+        return;
+    }
+    mask[core / 64] = 1 << (core % 64);
+    // Pid 0 is the calling thread
+    if unsafe { sched_setaffinity(0, std::mem::size_of_val(&mask), mask.as_ptr()) } != 0 {
+        warning!("Failed to pin a thread on core {}", core);
+    }
+}
+
+// Threads are left to the scheduler without an affinity API
+#[cfg(all(feature = "parallel", not(target_os = "linux")))]
+fn pin_thread(_core: usize) {}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Configuration of variables given by name and value
+    fn from_vars(vars: &[(&str, &str)]) -> Result<ParallelConfig> {
+        ParallelConfig::from_vars(|name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn test_parallel_config() {
+        let from_env = from_vars(&[
+            ("FE2O3_NUM_THREADS", "3"),
+            ("FE2O3_CHUNK_SIZE", " 256"),
+            ("FE2O3_PINNING", "Spread"),
+        ])
+        .unwrap();
+        assert_eq!(
+            from_env,
+            ParallelConfig::new()
+                .with_num_threads(3)
+                .with_chunk_size(256)
+                .with_pinning(Pinning::Spread),
+            "Wrong configuration from the environment"
+        );
+        assert_eq!(
+            from_env.min_length(),
+            8192,
+            "Unset variables should keep defaults"
+        );
+        assert_eq!(
+            from_vars(&[("FE2O3_MIN_ROWS", "0")]).unwrap().min_rows(),
+            0,
+            "Matrices may always be split"
+        );
+        let invalid = [
+            ("FE2O3_NUM_THREADS", "0"),
+            ("FE2O3_NUM_THREADS", "many"),
+            ("FE2O3_MIN_LENGTH", "-1"),
+            ("FE2O3_CHUNK_SIZE", "0"),
+            ("FE2O3_PINNING", "scatter"),
+        ];
+        for (name, value) in invalid {
+            let error = from_vars(&[(name, value)]).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidInput, "Wrong error kind");
+            assert!(
+                error.to_string().starts_with(name)
+                    && error.to_string().contains(&format!("{:?}", value)),
+                "Wrong error for {}={}: {}",
+                name,
+                value,
+                error
+            );
+        }
+        let cores: Vec<Option<usize>> = (0..4).map(|t| Pinning::Spread.core(t, 4, 8)).collect();
+        assert_eq!(cores, [Some(0), Some(2), Some(4), Some(6)], "Wrong spread");
+        assert_eq!(Pinning::Compact.core(5, 6, 4), Some(1), "Wrong compact");
+        assert_eq!(Pinning::None.core(0, 1, 4), None, "Unpinned threads");
+    }
+}
@@ -0,0 +1,737 @@
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::data_traits::DataContainer;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::core::expression::Expression;
+use crate::discretizations::cartesian_grid::CartesianGrid;
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+use crate::discretizations::io::exodus::{load_exodus, save_exodus};
+use crate::discretizations::io::med::load_med;
+use crate::discretizations::io::vtk::save_vtk;
+use crate::discretizations::mesh::Mesh;
+use crate::discretizations::operators::{mass_matrix, stiffness_matrix};
+use crate::discretizations::refinement::Refinement;
+use crate::solvers::config::{MethodConfig, SolverConfig};
+use crate::spaces::lagrange::LagrangeElement;
+use crate::spaces::quadrature::QuadratureRule;
+use crate::workflows::advection_diffusion::{AdvectionDiffusion, Stabilization};
+use crate::workflows::heat::HeatEquation;
+use pyo3::exceptions::{PyBufferError, PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::ffi;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::HashMap;
+use std::ffi::{c_int, c_void, CString};
+use std::rc::Rc;
+
+//--------------------------------------------------------------------------------------------------
+// # Enums
+//--------------------------------------------------------------------------------------------------
+
+// Values viewed by an array, either owned or shared with the mesh or matrix they belong to
+enum ArrayData {
+    Floats(Vec<f64>),
+    Coordinates(DataHold<f64, [usize; 2]>),
+    Vertices(Rc<Mesh>),
+    Cells(Rc<Mesh>),
+    MatrixValues(Rc<SparseCSR<f64>>),
+    ColumnIndices(Rc<SparseCSR<f64>>),
+    RowOffsets(Rc<SparseCSR<f64>>),
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Read only one or two dimensional array exposed through the buffer protocol, so that
+/// numpy.asarray views its memory without copying
+///
+/// The vertices and cells of a mesh and the arrays of a sparse matrix are not copied either: the
+/// array views the memory of the mesh or matrix, which it keeps alive, and computed values are
+/// moved into the array.
+#[pyclass(name = "Array", module = "fe2o3", unsendable)]
+pub struct PyArray {
+    data: ArrayData,
+    ndim: usize,
+    shape: [ffi::Py_ssize_t; 2],
+    strides: [ffi::Py_ssize_t; 2],
+}
+
+/// Simplicial mesh with its tags
+#[pyclass(name = "Mesh", module = "fe2o3", unsendable)]
+pub struct PyMesh {
+    mesh: Rc<Mesh>,
+}
+
+/// Space of continuous Lagrange elements of an order with some components on a mesh
+#[pyclass(name = "FunctionSpace", module = "fe2o3", unsendable)]
+pub struct PyFunctionSpace {
+    mesh: Rc<Mesh>,
+    order: usize,
+    n_components: usize,
+}
+
+/// Sparse matrix in the compressed sparse row format
+#[pyclass(name = "SparseMatrix", module = "fe2o3", unsendable)]
+pub struct PySparseMatrix {
+    matrix: Rc<SparseCSR<f64>>,
+}
+
+impl ArrayData {
+    // Pointer, number of bytes, size and format of the items
+    fn raw(&self) -> (*const c_void, usize, usize, &'static str) {
+        let floats = |values: &[f64]| {
+            (
+                values.as_ptr() as *const c_void,
+                std::mem::size_of_val(values),
+                std::mem::size_of::<f64>(),
+                "d",
+            )
+        };
+        let indices = |values: &[usize]| {
+            (
+                values.as_ptr() as *const c_void,
+                std::mem::size_of_val(values),
+                std::mem::size_of::<usize>(),
+                if std::mem::size_of::<usize>() == 8 {
+                    "Q"
+                } else {
+                    "I"
+                },
+            )
+        };
+        match self {
+            ArrayData::Floats(values) => floats(values),
+            ArrayData::Coordinates(values) => floats(values),
+            ArrayData::Vertices(mesh) => floats(mesh.vertices()),
+            ArrayData::Cells(mesh) => indices(mesh.cells()),
+            ArrayData::MatrixValues(matrix) => floats(matrix.values()),
+            ArrayData::ColumnIndices(matrix) => indices(matrix.col_indices()),
+            ArrayData::RowOffsets(matrix) => indices(matrix.row_offsets()),
+        }
+    }
+}
+
+impl PyArray {
+    // Row first array of one or two dimensions viewing the data
+    fn new(data: ArrayData, shape: &[usize]) -> Self {
+        let itemsize = data.raw().2 as ffi::Py_ssize_t;
+        let (ndim, shape, strides) = match *shape {
+            [n] => (1, [n as ffi::Py_ssize_t, 1], [itemsize, 0]),
+            [rows, cols] => (
+                2,
+                [rows as ffi::Py_ssize_t, cols as ffi::Py_ssize_t],
+                [cols as ffi::Py_ssize_t * itemsize, itemsize],
+            ),
+            _ => unreachable!("Arrays have one or two dimensions"),
+        };
+        PyArray {
+            data,
+            ndim,
+            shape,
+            strides,
+        }
+    }
+
+    // Vector taking the values without copying them
+    fn vector(values: Vec<f64>) -> Self {
+        let n = values.len();
+        PyArray::new(ArrayData::Floats(values), &[n])
+    }
+}
+
+#[pymethods]
+impl PyArray {
+    /// Sizes of the dimensions
+    #[getter]
+    fn shape(&self) -> Vec<usize> {
+        self.shape[..self.ndim]
+            .iter()
+            .map(|s| *s as usize)
+            .collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.shape[0] as usize
+    }
+
+    unsafe fn __getbuffer__(
+        slf: Bound<'_, Self>,
+        view: *mut ffi::Py_buffer,
+        flags: c_int,
+    ) -> PyResult<()> {
+        if view.is_null() {
+            return Err(PyBufferError::new_err("View is null"));
+        }
+        if (flags & ffi::PyBUF_WRITABLE) == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("Arrays are read only"));
+        }
+        let array = slf.borrow();
+        let (buffer, len, itemsize, format) = array.data.raw();
+        (*view).buf = buffer as *mut c_void;
+        (*view).len = len as ffi::Py_ssize_t;
+        (*view).readonly = 1;
+        (*view).itemsize = itemsize as ffi::Py_ssize_t;
+        (*view).format = if (flags & ffi::PyBUF_FORMAT) == ffi::PyBUF_FORMAT {
+            CString::new(format).unwrap().into_raw()
+        } else {
+            std::ptr::null_mut()
+        };
+        (*view).ndim = array.ndim as c_int;
+        // The shape and strides live in the array, which the view keeps alive
+        (*view).shape = if (flags & ffi::PyBUF_ND) == ffi::PyBUF_ND {
+            array.shape.as_ptr() as *mut ffi::Py_ssize_t
+        } else {
+            std::ptr::null_mut()
+        };
+        (*view).strides = if (flags & ffi::PyBUF_STRIDES) == ffi::PyBUF_STRIDES {
+            array.strides.as_ptr() as *mut ffi::Py_ssize_t
+        } else {
+            std::ptr::null_mut()
+        };
+        (*view).suboffsets = std::ptr::null_mut();
+        (*view).internal = std::ptr::null_mut();
+        drop(array);
+        (*view).obj = slf.into_any().into_ptr();
+        Ok(())
+    }
+
+    unsafe fn __releasebuffer__(&self, view: *mut ffi::Py_buffer) {
+        if !(*view).format.is_null() {
+            drop(CString::from_raw((*view).format));
+        }
+    }
+}
+
+#[pymethods]
+impl PyMesh {
+    /// Box split into simplices whose lower and upper sides along direction k are tagged 2 k + 1
+    /// and 2 k + 2
+    #[staticmethod]
+    #[pyo3(name = "box")]
+    fn cartesian_box(lower: Vec<f64>, upper: Vec<f64>, cells: Vec<usize>) -> PyResult<Self> {
+        if lower.len() != upper.len() || lower.len() != cells.len() {
+            return Err(PyValueError::new_err(
+                "Corners and cells should have the same dimension",
+            ));
+        }
+        let mesh = CartesianGrid::new(lower, upper, cells).simplex_mesh();
+        Ok(PyMesh {
+            mesh: Rc::new(mesh),
+        })
+    }
+
+    /// Read an Exodus II (.exo, .e) or MED (.med) file
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Self> {
+        let mesh = if path.ends_with(".med") {
+            load_med(path)?.0
+        } else if path.ends_with(".exo") || path.ends_with(".e") {
+            load_exodus(path)?.0
+        } else {
+            return Err(PyValueError::new_err(format!(
+                "Unknown mesh format of {}",
+                path
+            )));
+        };
+        Ok(PyMesh {
+            mesh: Rc::new(mesh),
+        })
+    }
+
+    /// Write the mesh to an Exodus II file
+    fn save_exodus(&self, path: &str) -> PyResult<()> {
+        Ok(save_exodus(path, &self.mesh, &[])?)
+    }
+
+    /// Mesh with every cell split uniformly
+    fn refine(&self) -> Self {
+        PyMesh {
+            mesh: Rc::new(Refinement::uniform(&self.mesh).into_mesh()),
+        }
+    }
+
+    /// Dimension of the coordinates
+    #[getter]
+    fn dim(&self) -> usize {
+        self.mesh.geometric_dim()
+    }
+
+    /// Number of vertices
+    #[getter]
+    fn n_vertices(&self) -> usize {
+        self.mesh.n_vertices()
+    }
+
+    /// Number of cells
+    #[getter]
+    fn n_cells(&self) -> usize {
+        self.mesh.n_cells()
+    }
+
+    /// Coordinates of the vertices as a (vertices, dimension) array viewing the mesh
+    fn vertices(&self) -> PyArray {
+        let dimensions = *self.mesh.vertices().dimensions();
+        PyArray::new(ArrayData::Vertices(self.mesh.clone()), &dimensions)
+    }
+
+    /// Vertices of the cells as a (cells, vertices per cell) array viewing the mesh
+    fn cells(&self) -> PyArray {
+        let dimensions = *self.mesh.cells().dimensions();
+        PyArray::new(ArrayData::Cells(self.mesh.clone()), &dimensions)
+    }
+}
+
+impl PyFunctionSpace {
+    // Space on the mesh, whose dof numbering only depends on the mesh and the element
+    fn space(&self) -> FunctionSpace<'_> {
+        let element = LagrangeElement::new(self.mesh.geometric_dim(), self.order);
+        FunctionSpace::vector(&self.mesh, element, self.n_components)
+    }
+
+    // Function of the space with values given by Python
+    fn function<'s>(
+        &self,
+        space: &'s FunctionSpace<'s>,
+        values: Vec<f64>,
+    ) -> PyResult<Function<'s>> {
+        if values.len() != space.n_dofs() {
+            return Err(PyValueError::new_err(
+                "Values do not match the dofs of the space",
+            ));
+        }
+        Ok(Function::from_values(space, values))
+    }
+
+    // Quadrature rule integrating products of shape functions exactly
+    fn quadrature(&self) -> QuadratureRule {
+        QuadratureRule::simplex(self.mesh.geometric_dim(), 2 * self.order)
+    }
+}
+
+#[pymethods]
+impl PyFunctionSpace {
+    #[new]
+    #[pyo3(signature = (mesh, order = 1, n_components = 1))]
+    fn new(mesh: PyRef<'_, PyMesh>, order: usize, n_components: usize) -> PyResult<Self> {
+        if order == 0 || n_components == 0 {
+            return Err(PyValueError::new_err(
+                "Spaces need a positive order and number of components",
+            ));
+        }
+        Ok(PyFunctionSpace {
+            mesh: mesh.mesh.clone(),
+            order,
+            n_components,
+        })
+    }
+
+    /// Number of dofs
+    #[getter]
+    fn n_dofs(&self) -> usize {
+        self.space().n_dofs()
+    }
+
+    /// Coordinates of the scalar dofs as a (dofs, dimension) array
+    fn dof_coordinates(&self) -> PyArray {
+        let coordinates = self.space().dof_coordinates();
+        let dimensions = *coordinates.dimensions();
+        PyArray::new(ArrayData::Coordinates(coordinates), &dimensions)
+    }
+
+    /// Scalar dofs on the facets carrying a tag
+    fn tagged_dofs(&self, tag: usize) -> Vec<usize> {
+        self.space().tagged_dofs(tag)
+    }
+
+    /// Values of an expression of the coordinates at the dofs of a scalar space
+    fn interpolate(&self, expression: &Bound<'_, PyAny>) -> PyResult<PyArray> {
+        let expression = to_expression(expression)?;
+        let space = self.space();
+        let mut function = Function::new(&space);
+        function.interpolate(|x| expression.eval(x, 0.0));
+        Ok(PyArray::vector(function.into_values()))
+    }
+
+    /// Mass matrix
+    fn mass_matrix(&self) -> PySparseMatrix {
+        let space = self.space();
+        let matrix = mass_matrix(&space.assembler(self.quadrature()));
+        PySparseMatrix {
+            matrix: Rc::new(matrix),
+        }
+    }
+
+    /// Stiffness matrix of a coefficient, a number or an expression of the coordinates
+    #[pyo3(signature = (coefficient = None))]
+    fn stiffness_matrix(&self, coefficient: Option<&Bound<'_, PyAny>>) -> PyResult<PySparseMatrix> {
+        let coefficient = match coefficient {
+            Some(c) => to_expression(c)?,
+            None => Expression::constant(1.0),
+        };
+        let space = self.space();
+        let assembler = space.assembler(self.quadrature());
+        let matrix = stiffness_matrix(&assembler, |x| coefficient.eval(x, 0.0));
+        Ok(PySparseMatrix {
+            matrix: Rc::new(matrix),
+        })
+    }
+
+    /// Write fields given by name and dof values to a VTK file
+    fn save_vtk(&self, path: &str, fields: HashMap<String, Vec<f64>>) -> PyResult<()> {
+        let space = self.space();
+        let functions = fields
+            .into_iter()
+            .map(|(name, values)| Ok((name, self.function(&space, values)?)))
+            .collect::<PyResult<Vec<(String, Function)>>>()?;
+        let named: Vec<(&str, &Function)> =
+            functions.iter().map(|(n, f)| (n.as_str(), f)).collect();
+        Ok(save_vtk(path, &self.mesh, &named)?)
+    }
+}
+
+#[pymethods]
+impl PySparseMatrix {
+    /// Numbers of rows and columns
+    #[getter]
+    fn shape(&self) -> (usize, usize) {
+        (self.matrix.n_rows(), self.matrix.n_cols())
+    }
+
+    /// Number of stored entries
+    #[getter]
+    fn nnz(&self) -> usize {
+        self.matrix.nnz()
+    }
+
+    /// Values, column indices and row offsets viewing the matrix, as taken by
+    /// scipy.sparse.csr_matrix((data, indices, indptr))
+    fn csr(&self) -> (PyArray, PyArray, PyArray) {
+        let nnz = self.matrix.nnz();
+        (
+            PyArray::new(ArrayData::MatrixValues(self.matrix.clone()), &[nnz]),
+            PyArray::new(ArrayData::ColumnIndices(self.matrix.clone()), &[nnz]),
+            PyArray::new(
+                ArrayData::RowOffsets(self.matrix.clone()),
+                &[self.matrix.n_rows() + 1],
+            ),
+        )
+    }
+
+    /// Product with a vector
+    fn apply(&self, x: Vec<f64>) -> PyResult<PyArray> {
+        if x.len() != self.matrix.n_cols() {
+            return Err(PyValueError::new_err("Vector does not match the matrix"));
+        }
+        let mut y = vec![0.0; self.matrix.n_rows()];
+        self.matrix.apply(&x, &mut y);
+        Ok(PyArray::vector(y))
+    }
+
+    /// Solve the system for a right hand side by "sparse_lu", "sparse_cholesky", "cg" or "gmres"
+    #[pyo3(signature = (rhs, method = "sparse_lu"))]
+    fn solve(&self, rhs: Vec<f64>, method: &str) -> PyResult<PyArray> {
+        if rhs.len() != self.matrix.n_rows() {
+            return Err(PyValueError::new_err(
+                "Right hand side does not match the matrix",
+            ));
+        }
+        let method = match method {
+            "sparse_lu" => MethodConfig::SparseLU,
+            "sparse_cholesky" => MethodConfig::SparseCholesky,
+            "cg" => MethodConfig::ConjugateGradient,
+            "gmres" => MethodConfig::Gmres { restart: 30 },
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "Unknown solver method {}",
+                    method
+                )))
+            }
+        };
+        let solver = SolverConfig::new(method)
+            .build(&self.matrix)
+            .ok_or_else(|| PyRuntimeError::new_err("Matrix is singular"))?;
+        let mut x = vec![0.0; rhs.len()];
+        if !solver.solve(&rhs, &mut x).converged() {
+            return Err(PyRuntimeError::new_err("Solve did not converge"));
+        }
+        Ok(PyArray::vector(x))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Expression of a Python number or of the source of an expression
+fn to_expression(value: &Bound<'_, PyAny>) -> PyResult<Expression> {
+    if let Ok(source) = value.extract::<String>() {
+        Ok(Expression::parse(&source)?)
+    } else {
+        Ok(Expression::constant(value.extract::<f64>()?))
+    }
+}
+
+// Expressions of the values of a Python dictionary of tags
+fn to_tagged(values: HashMap<usize, Bound<'_, PyAny>>) -> PyResult<Vec<(usize, Expression)>> {
+    values
+        .into_iter()
+        .map(|(tag, value)| Ok((tag, to_expression(&value)?)))
+        .collect()
+}
+
+/// Solve -k Δu = f on a scalar space with the values of u imposed on tagged boundaries, the
+/// source and the values being numbers or expressions of the coordinates
+#[pyfunction]
+#[pyo3(signature = (space, source = None, dirichlet = HashMap::new(), diffusivity = 1.0))]
+fn solve_poisson(
+    space: PyRef<'_, PyFunctionSpace>,
+    source: Option<&Bound<'_, PyAny>>,
+    dirichlet: HashMap<usize, Bound<'_, PyAny>>,
+    diffusivity: f64,
+) -> PyResult<PyArray> {
+    let source = source.map(to_expression).transpose()?;
+    let dirichlet = to_tagged(dirichlet)?;
+    let function_space = space.space();
+    let mut problem = AdvectionDiffusion::new(&function_space)
+        .with_stabilization(Stabilization::Galerkin)
+        .with_diffusivity(diffusivity);
+    if let Some(source) = &source {
+        problem = problem.with_source(|x| source.eval(x, 0.0));
+    }
+    for (tag, value) in &dirichlet {
+        problem = problem.with_dirichlet(*tag, |x| value.eval(x, 0.0));
+    }
+    Ok(PyArray::vector(problem.solve().into_values()))
+}
+
+// Optional parameters of solve_heat, given by keyword
+struct HeatOptions<'py> {
+    source: Option<Bound<'py, PyAny>>,
+    dirichlet: HashMap<usize, Bound<'py, PyAny>>,
+    conductivity: f64,
+    capacity: f64,
+}
+
+impl<'py> HeatOptions<'py> {
+    // Options of the keyword arguments, the missing ones taking their default values
+    fn extract(kwargs: Option<&Bound<'py, PyDict>>) -> PyResult<Self> {
+        let mut options = HeatOptions {
+            source: None,
+            dirichlet: HashMap::new(),
+            conductivity: 1.0,
+            capacity: 1.0,
+        };
+        let Some(kwargs) = kwargs else {
+            return Ok(options);
+        };
+        for (key, value) in kwargs.iter() {
+            match key.extract::<String>()?.as_str() {
+                "source" => options.source = (!value.is_none()).then_some(value),
+                "dirichlet" => options.dirichlet = value.extract()?,
+                "conductivity" => options.conductivity = value.extract()?,
+                "capacity" => options.capacity = value.extract()?,
+                key => {
+                    return Err(PyTypeError::new_err(format!(
+                        "solve_heat got an unexpected keyword argument {}",
+                        key
+                    )))
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Integrate the heat equation c du/dt - k Δu = f from an initial temperature until an end time,
+/// returning the times and the temperatures of every step as a (steps + 1, dofs) array. The
+/// source, dirichlet, conductivity and capacity are given by keyword.
+#[pyfunction]
+#[pyo3(signature = (space, initial, end, time_step, **options))]
+fn solve_heat(
+    space: PyRef<'_, PyFunctionSpace>,
+    initial: &Bound<'_, PyAny>,
+    end: f64,
+    time_step: f64,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<(Vec<f64>, PyArray)> {
+    let HeatOptions {
+        source,
+        dirichlet,
+        conductivity,
+        capacity,
+    } = HeatOptions::extract(options)?;
+    if time_step <= 0.0 || end <= 0.0 {
+        return Err(PyValueError::new_err(
+            "Times and time steps should be positive",
+        ));
+    }
+    let initial = to_expression(initial)?;
+    let source = source.as_ref().map(to_expression).transpose()?;
+    let dirichlet = to_tagged(dirichlet)?;
+    let function_space = space.space();
+    let mut problem = HeatEquation::new(&function_space)
+        .with_conductivity(conductivity)
+        .with_capacity(capacity);
+    if let Some(source) = &source {
+        problem = problem.with_source(|t, x| source.eval(x, t));
+    }
+    for (tag, value) in &dirichlet {
+        problem = problem.with_dirichlet(*tag, |t, x| value.eval(x, t));
+    }
+    let mut u = Function::new(&function_space);
+    u.interpolate(|x| initial.eval(x, 0.0));
+    let mut times = Vec::new();
+    let mut values = Vec::new();
+    problem.solve(&mut u, 0.0, end, time_step, |t, u| {
+        times.push(t);
+        values.extend_from_slice(u.values());
+    });
+    let dimensions = [times.len(), function_space.n_dofs()];
+    Ok((times, PyArray::new(ArrayData::Floats(values), &dimensions)))
+}
+
+/// Add the classes and functions of the library to a Python module, which the extension module
+/// of the python directory (built by maturin) names fe2o3
+pub fn register(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyArray>()?;
+    module.add_class::<PyMesh>()?;
+    module.add_class::<PyFunctionSpace>()?;
+    module.add_class::<PySparseMatrix>()?;
+    module.add_function(wrap_pyfunction!(solve_poisson, module)?)?;
+    module.add_function(wrap_pyfunction!(solve_heat, module)?)?;
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyString;
+
+    // Nested lists of the items of an array read through the buffer protocol
+    fn to_list<'py, T: FromPyObject<'py>>(array: &Bound<'py, PyArray>) -> PyResult<T> {
+        let memoryview = array.py().import_bound("builtins")?.getattr("memoryview")?;
+        memoryview
+            .call1((array,))?
+            .call_method0("tolist")?
+            .extract()
+    }
+
+    #[test]
+    fn test_python_mesh_arrays() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| -> PyResult<()> {
+            let mesh = PyMesh::cartesian_box(vec![0.0, 0.0], vec![1.0, 1.0], vec![2, 2])?;
+            let vertices = Bound::new(py, mesh.vertices())?;
+            assert!(
+                std::ptr::eq(
+                    vertices.borrow().data.raw().0,
+                    mesh.mesh.vertices().as_ptr() as *const c_void
+                ),
+                "Vertices should view the mesh"
+            );
+            let rows: Vec<Vec<f64>> = to_list(&vertices)?;
+            assert_eq!(rows.len(), mesh.n_vertices(), "Wrong number of rows");
+            assert_eq!(
+                rows.concat(),
+                &mesh.mesh.vertices()[..],
+                "Vertices changed through the buffer"
+            );
+            let cells = Bound::new(py, mesh.cells())?;
+            let rows: Vec<Vec<usize>> = to_list(&cells)?;
+            assert_eq!(rows.len(), mesh.n_cells(), "Wrong number of rows");
+            assert_eq!(
+                rows.concat(),
+                &mesh.mesh.cells()[..],
+                "Cells changed through the buffer"
+            );
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_python_function_arrays() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| -> PyResult<()> {
+            let mesh = PyMesh::cartesian_box(vec![0.0, 0.0], vec![1.0, 1.0], vec![2, 2])?;
+            let mesh = Bound::new(py, mesh)?;
+            let space = PyFunctionSpace::new(mesh.borrow(), 2, 1)?;
+            let coordinates: Vec<Vec<f64>> = to_list(&Bound::new(py, space.dof_coordinates())?)?;
+            assert_eq!(coordinates.len(), space.n_dofs(), "Wrong number of dofs");
+            let expression = PyString::new_bound(py, "x + 2 * y");
+            let values: Vec<f64> =
+                to_list(&Bound::new(py, space.interpolate(expression.as_any())?)?)?;
+            for (value, x) in values.iter().zip(&coordinates) {
+                assert!(
+                    (value - x[0] - 2.0 * x[1]).abs() < 1e-12,
+                    "Wrong interpolated value"
+                );
+            }
+            let mass = space.mass_matrix();
+            let ones = vec![1.0; space.n_dofs()];
+            let product: Vec<f64> = to_list(&Bound::new(py, mass.apply(ones)?)?)?;
+            assert!(
+                (product.iter().sum::<f64>() - 1.0).abs() < 1e-12,
+                "The mass matrix should integrate to the area"
+            );
+            let (values, indices, offsets) = mass.csr();
+            let values: Vec<f64> = to_list(&Bound::new(py, values)?)?;
+            let indices: Vec<usize> = to_list(&Bound::new(py, indices)?)?;
+            let offsets: Vec<usize> = to_list(&Bound::new(py, offsets)?)?;
+            assert_eq!(values, mass.matrix.values(), "Wrong matrix values");
+            assert_eq!(indices, mass.matrix.col_indices(), "Wrong column indices");
+            assert_eq!(offsets, mass.matrix.row_offsets(), "Wrong row offsets");
+            assert!(
+                mass.apply(vec![1.0]).is_err(),
+                "Vectors should match the matrix"
+            );
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_python_solve_heat() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| -> PyResult<()> {
+            let module = PyModule::new_bound(py, "fe2o3")?;
+            register(&module)?;
+            let mesh = PyMesh::cartesian_box(vec![0.0, 0.0], vec![1.0, 1.0], vec![2, 2])?;
+            let space = Bound::new(
+                py,
+                PyFunctionSpace::new(Bound::new(py, mesh)?.borrow(), 1, 1)?,
+            )?;
+            let solve_heat = module.getattr("solve_heat")?;
+            let options = PyDict::new_bound(py);
+            options.set_item("capacity", 2.0)?;
+            let (times, temperatures): (Vec<f64>, Bound<'_, PyArray>) = solve_heat
+                .call((&space, 1.0, 0.5, 0.25), Some(&options))?
+                .extract()?;
+            assert_eq!(times.len(), 3, "Wrong number of steps");
+            let temperatures: Vec<Vec<f64>> = to_list(&temperatures)?;
+            for temperature in temperatures.concat() {
+                assert!(
+                    (temperature - 1.0).abs() < 1e-10,
+                    "Constant temperatures should stay constant"
+                );
+            }
+            options.set_item("conductance", 1.0)?;
+            let error = solve_heat
+                .call((&space, 1.0, 0.5, 0.25), Some(&options))
+                .unwrap_err();
+            assert!(
+                error.is_instance_of::<PyTypeError>(py),
+                "Unknown keywords should be rejected"
+            );
+            Ok(())
+        })
+        .unwrap();
+    }
+}
@@ -0,0 +1,374 @@
+use crate::core::arrays::sparse_csr::SparseCSR;
+
+// Maximum number of power iterations of the 1-norm estimator
+const NORM_ITERATIONS: usize = 5;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Estimates of the extreme eigenvalues of a symmetric positive definite operator
+///
+/// The estimates of the conjugate gradient are the extreme eigenvalues of its Lanczos tridiagonal
+/// matrix, which lie inside the spectrum of the (preconditioned) operator and approach its ends
+/// from within as the iterations go on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpectrumEstimate {
+    smallest: f64,
+    largest: f64,
+}
+
+impl SpectrumEstimate {
+    /// Estimate of the smallest eigenvalue
+    pub fn smallest(&self) -> f64 {
+        self.smallest
+    }
+
+    /// Estimate of the largest eigenvalue
+    pub fn largest(&self) -> f64 {
+        self.largest
+    }
+
+    /// Estimate of the spectral condition number, a lower bound of the true one
+    pub fn condition_number(&self) -> f64 {
+        self.largest / self.smallest
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Estimate of the 1-norm of the inverse of a square matrix of the given size from solves with
+/// the matrix and its transpose (Hager's algorithm with Higham's modifications)
+///
+/// The estimate is a lower bound of the true norm which is exact in most cases and rarely off by
+/// more than a factor 3, for a handful of solves.
+pub fn inverse_norm1_estimate<Solve, SolveTranspose>(
+    n: usize,
+    solve: Solve,
+    solve_transpose: SolveTranspose,
+) -> f64
+where
+    Solve: Fn(&[f64], &mut [f64]),
+    SolveTranspose: Fn(&[f64], &mut [f64]),
+{
+    if n == 0 {
+        return 0.0;
+    }
+    let norm1 = |v: &[f64]| v.iter().map(|v| v.abs()).sum::<f64>();
+    let mut x = vec![1.0 / n as f64; n];
+    let mut y = vec![0.0; n];
+    let mut z = vec![0.0; n];
+    let mut estimate: f64 = 0.0;
+    let mut previous = None;
+    for iteration in 0..NORM_ITERATIONS {
+        solve(&x, &mut y);
+        estimate = estimate.max(norm1(&y));
+        let signs: Vec<f64> = y
+            .iter()
+            .map(|y| if *y >= 0.0 { 1.0 } else { -1.0 })
+            .collect();
+        solve_transpose(&signs, &mut z);
+        let j = (0..n)
+            .max_by(|i, j| z[*i].abs().total_cmp(&z[*j].abs()))
+            .unwrap();
+        let gradient: f64 = z.iter().zip(&x).map(|(z, x)| z * x).sum();
+        if iteration > 0 && (z[j].abs() <= gradient || previous == Some(j)) {
+            break;
+        }
+        previous = Some(j);
+        x.iter_mut().for_each(|x| *x = 0.0);
+        x[j] = 1.0;
+    }
+    // Alternating vector catching the matrices on which the power iterations are fooled
+    let denominator = (n.max(2) - 1) as f64;
+    for (i, x) in x.iter_mut().enumerate() {
+        let sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+        *x = sign * (1.0 + i as f64 / denominator);
+    }
+    solve(&x, &mut y);
+    estimate.max(2.0 * norm1(&y) / (3.0 * n as f64))
+}
+
+/// 1-norm (largest column sum of absolute values) of a sparse matrix
+pub fn norm1(matrix: &SparseCSR<f64>) -> f64 {
+    let mut sums = vec![0.0; matrix.n_cols()];
+    for (col, value) in matrix.col_indices().iter().zip(matrix.values()) {
+        sums[*col] += value.abs();
+    }
+    sums.into_iter().fold(0.0, f64::max)
+}
+
+// Smallest and largest eigenvalues of a symmetric tridiagonal matrix by Sturm sequence bisection
+pub(crate) fn tridiagonal_extreme_eigenvalues(
+    diagonal: &[f64],
+    off_diagonal: &[f64],
+) -> (f64, f64) {
+    let n = diagonal.len();
+    // Gershgorin bounds of the spectrum
+    let (mut low, mut high) = (f64::INFINITY, f64::NEG_INFINITY);
+    for (i, d) in diagonal.iter().enumerate() {
+        let radius = if i > 0 {
+            off_diagonal[i - 1].abs()
+        } else {
+            0.0
+        } + off_diagonal.get(i).map_or(0.0, |b| b.abs());
+        low = low.min(d - radius);
+        high = high.max(d + radius);
+    }
+    // Number of eigenvalues below a value
+    let count = |value: f64| {
+        let mut pivot = 1.0;
+        let mut below = 0;
+        for (i, d) in diagonal.iter().enumerate() {
+            let coupling = if i > 0 {
+                off_diagonal[i - 1].powi(2) / pivot
+            } else {
+                0.0
+            };
+            pivot = d - value - coupling;
+            if pivot == 0.0 {
+                pivot = -f64::EPSILON * (high.abs() + low.abs());
+            }
+            if pivot < 0.0 {
+                below += 1;
+            }
+        }
+        below
+    };
+    let bisect = |rank: usize| {
+        let (mut a, mut b) = (low, high);
+        while b - a > f64::EPSILON * (a.abs() + b.abs()) {
+            let middle = 0.5 * (a + b);
+            if middle <= a || middle >= b {
+                break;
+            }
+            if count(middle) > rank {
+                b = middle;
+            } else {
+                a = middle;
+            }
+        }
+        0.5 * (a + b)
+    };
+    (bisect(0), bisect(n - 1))
+}
+
+// Spectrum estimate from the step lengths and direction updates of the conjugate gradient
+//
+// The Lanczos matrix has the diagonal 1 / α_k + β_(k-1) / α_(k-1) and the off diagonal
+// √β_k / α_k.
+pub(crate) fn conjugate_gradient_spectrum(
+    alphas: &[f64],
+    betas: &[f64],
+) -> Option<SpectrumEstimate> {
+    if alphas.is_empty() {
+        return None;
+    }
+    let diagonal: Vec<f64> = alphas
+        .iter()
+        .enumerate()
+        .map(|(k, alpha)| {
+            1.0 / alpha
+                + if k > 0 {
+                    betas[k - 1] / alphas[k - 1]
+                } else {
+                    0.0
+                }
+        })
+        .collect();
+    let off_diagonal: Vec<f64> = (1..alphas.len())
+        .map(|k| betas[k - 1].sqrt() / alphas[k - 1])
+        .collect();
+    let (smallest, largest) = tridiagonal_extreme_eigenvalues(&diagonal, &off_diagonal);
+    Some(SpectrumEstimate { smallest, largest })
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::data_hold::DataHold;
+    use crate::solvers::dense::{Cholesky, LU};
+    use crate::solvers::krylov::{ConjugateGradient, Jacobi};
+    use crate::solvers::sparse_direct::{SparseCholesky, SparseLU};
+
+    #[test]
+    fn test_condition_estimates() {
+        // 1D laplacian scaled by a varying factor to make it badly conditioned for Jacobi to fix
+        let n: usize = 30;
+        let mut offsets = vec![0];
+        let (mut cols, mut values) = (Vec::new(), Vec::new());
+        for i in 0..n {
+            for j in i.saturating_sub(1)..(i + 2).min(n) {
+                let scale = ((1 + i) * (1 + j)) as f64;
+                cols.push(j);
+                values.push(if i == j { 2.0 } else { -1.0 } * scale);
+            }
+            offsets.push(cols.len());
+        }
+        let matrix = SparseCSR::new(n, offsets, cols, values);
+        let mut x = vec![0.0; n];
+        let convergence =
+            ConjugateGradient::new()
+                .with_spectrum_estimate()
+                .solve(&matrix, &vec![1.0; n], &mut x);
+        let plain = convergence.spectrum_estimate().unwrap();
+        // Dense eigenvalues to compare with
+        let mut dense = DataHold::new(vec![0.0; n * n], [n, n]);
+        for i in 0..n {
+            let (cols, values) = matrix.row(i);
+            for (j, v) in cols.iter().zip(values) {
+                dense[i * n + j] = *v;
+            }
+        }
+        let (diagonal, off_diagonal): (Vec<f64>, Vec<f64>) = (0..n)
+            .map(|i| {
+                (
+                    dense[i * n + i],
+                    if i + 1 < n { dense[i * n + i + 1] } else { 0.0 },
+                )
+            })
+            .unzip();
+        let (smallest, largest) =
+            tridiagonal_extreme_eigenvalues(&diagonal, &off_diagonal[..n - 1]);
+        assert!(
+            (plain.smallest() - smallest).abs() < 1e-6 * smallest,
+            "Wrong smallest eigenvalue {} instead of {}",
+            plain.smallest(),
+            smallest
+        );
+        assert!(
+            (plain.largest() - largest).abs() < 1e-6 * largest,
+            "Wrong largest eigenvalue {} instead of {}",
+            plain.largest(),
+            largest
+        );
+        let mut x = vec![0.0; n];
+        let preconditioned = ConjugateGradient::new()
+            .with_spectrum_estimate()
+            .solve_preconditioned(&matrix, &Jacobi::new(&matrix), &vec![1.0; n], &mut x)
+            .spectrum_estimate()
+            .unwrap();
+        assert!(
+            preconditioned.condition_number() < 0.1 * plain.condition_number(),
+            "Jacobi should fix the scaling"
+        );
+        // Factorizations against the exact 1-norm condition number
+        let inverse = LU::new(&dense).unwrap().inverse();
+        let column_norm = |a: &DataHold<f64, [usize; 2]>| {
+            (0..n)
+                .map(|j| (0..n).map(|i| a[i * n + j].abs()).sum::<f64>())
+                .fold(0.0, f64::max)
+        };
+        let exact = column_norm(&dense) * column_norm(&inverse);
+        assert!(
+            (norm1(&matrix) - column_norm(&dense)).abs() < 1e-12,
+            "Wrong 1-norm"
+        );
+        let estimates = [
+            LU::new(&dense).unwrap().condition_estimate(),
+            Cholesky::new(&dense).unwrap().condition_estimate(),
+            SparseLU::new(&matrix).unwrap().condition_estimate(),
+            SparseCholesky::new(&matrix).unwrap().condition_estimate(),
+        ];
+        for estimate in estimates {
+            assert!(
+                estimate <= exact * (1.0 + 1e-10) && estimate >= exact / 3.0,
+                "Bad condition estimate {} instead of {}",
+                estimate,
+                exact
+            );
+        }
+    }
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_condition_estimates_nonsymmetric() {
+        // Unit upper triangular matrix with -1 above the diagonal, whose inverse has the entries
+        // 2^(j - i - 1) above the diagonal and the 1-norm 2^(n - 1)
+        let n: usize = 10;
+        let mut dense = DataHold::new(vec![0.0; n * n], [n, n]);
+        let mut offsets = vec![0];
+        let (mut cols, mut values) = (Vec::new(), Vec::new());
+        for i in 0..n {
+            for j in i..n {
+                let value = if i == j { 1.0 } else { -1.0 };
+                dense[i * n + j] = value;
+                cols.push(j);
+                values.push(value);
+            }
+            offsets.push(cols.len());
+        }
+        let matrix = SparseCSR::new(n, offsets, cols, values);
+        let exact = n as f64 * 2.0_f64.powi(n as i32 - 1);
+        assert_eq!(norm1(&matrix), n as f64, "Wrong 1-norm");
+        let estimates = [
+            LU::new(&dense).unwrap().condition_estimate(),
+            SparseLU::new(&matrix).unwrap().condition_estimate(),
+        ];
+        for estimate in estimates {
+            assert!(
+                estimate <= exact * (1.0 + 1e-10) && estimate >= exact / 3.0,
+                "Bad condition estimate {} instead of {}",
+                estimate,
+                exact
+            );
+        }
+        // Diagonal matrices are estimated exactly
+        let diagonal = [1.0, 0.5, 4.0];
+        let solve = |b: &[f64], x: &mut [f64]| {
+            x.iter_mut()
+                .zip(b.iter().zip(&diagonal))
+                .for_each(|(x, (b, d))| *x = b / d);
+        };
+        assert_eq!(
+            inverse_norm1_estimate(3, solve, solve),
+            2.0,
+            "Wrong inverse norm of a diagonal matrix"
+        );
+        assert_eq!(
+            inverse_norm1_estimate(0, solve, solve),
+            0.0,
+            "Empty matrices have a zero norm"
+        );
+        // Spectrum estimates are only computed on request and from at least one iteration
+        let laplacian = SparseCSR::new(
+            3,
+            vec![0, 2, 5, 7],
+            vec![0, 1, 0, 1, 2, 1, 2],
+            vec![2.0, -1.0, -1.0, 2.0, -1.0, -1.0, 2.0],
+        );
+        let mut x = vec![0.0; 3];
+        assert!(
+            ConjugateGradient::new()
+                .solve(&laplacian, &[1.0, 0.0, 1.0], &mut x)
+                .spectrum_estimate()
+                .is_none(),
+            "Spectrum estimated without request"
+        );
+        x.iter_mut().for_each(|x| *x = 0.0);
+        assert!(
+            ConjugateGradient::new()
+                .with_spectrum_estimate()
+                .solve(&laplacian, &[0.0; 3], &mut x)
+                .spectrum_estimate()
+                .is_none(),
+            "No iteration gives no estimate"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    #[should_panic]
+    fn test_condition_solve_transpose_size() {
+        let dense = DataHold::new(vec![2.0, 1.0, 1.0, 3.0], [2, 2]);
+        let mut x = vec![0.0; 3];
+        LU::new(&dense)
+            .unwrap()
+            .solve_transpose(&[1.0, 1.0, 1.0], &mut x);
+    }
+}
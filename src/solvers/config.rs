@@ -0,0 +1,477 @@
+use super::krylov::{norm, ConjugateGradient, Convergence, Gmres, Jacobi};
+use super::linear_operator::Preconditioner;
+use super::multigrid::{CycleType, Multigrid, SmoothedAggregation};
+use super::relaxation::{Relaxation, RelaxationMethod};
+use super::sparse_direct::{SparseCholesky, SparseLU};
+use super::stopping_criterion::{StopReason, StoppingCriterion};
+use crate::core::arrays::sparse_csr::SparseCSR;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Parameters of a smoothed aggregation multigrid in a configuration
+///
+/// By default one V-cycle with one symmetric Gauss-Seidel sweep is applied and the setup uses
+/// the defaults of SmoothedAggregation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(default))]
+pub struct MultigridConfig {
+    cycles: usize,
+    cycle_type: CycleType,
+    smoother: RelaxationMethod,
+    sweeps: usize,
+    strength_threshold: f64,
+    coarse_size: usize,
+    max_levels: usize,
+}
+
+/// Method solving the system
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum MethodConfig {
+    /// Conjugate gradient for symmetric positive definite systems
+    ConjugateGradient,
+    /// Restarted GMRES with the given restart length
+    Gmres {
+        /// Number of iterations between restarts
+        restart: usize,
+    },
+    /// Standalone relaxation sweeps
+    Relaxation(RelaxationMethod),
+    /// Standalone multigrid cycles, the number of cycles of the configuration being ignored
+    Multigrid(MultigridConfig),
+    /// Sparse LU factorization
+    #[cfg_attr(feature = "serde", serde(rename = "sparse_lu"))]
+    SparseLU,
+    /// Sparse Cholesky factorization for symmetric positive definite systems
+    SparseCholesky,
+}
+
+/// Preconditioner of the Krylov methods
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum PreconditionerConfig {
+    /// Inverse of the diagonal
+    Jacobi,
+    /// Sweeps of a relaxation method from a zero guess
+    Relaxation {
+        /// Relaxation method of the sweeps
+        method: RelaxationMethod,
+        /// Number of sweeps
+        sweeps: usize,
+    },
+    /// Cycles of a multigrid from a zero guess
+    Multigrid(MultigridConfig),
+    /// Sparse LU factorization, usually of an approximation of the system
+    #[cfg_attr(feature = "serde", serde(rename = "sparse_lu"))]
+    SparseLU,
+    /// Sparse Cholesky factorization
+    SparseCholesky,
+}
+
+/// Description of a solver selected at runtime, built either with the builder methods or (with
+/// the serde feature) deserialized from a configuration file
+///
+/// For instance a conjugate gradient preconditioned by two V-cycles of algebraic multigrid reads
+/// in JSON {"method": "conjugate_gradient", "preconditioner": {"multigrid": {"cycles": 2}}},
+/// missing fields taking their default values. By default the method is the conjugate gradient
+/// without preconditioner, the relative tolerance 1e-10, the absolute tolerance 0 and at most
+/// 1000 iterations are done.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(default))]
+pub struct SolverConfig {
+    method: MethodConfig,
+    preconditioner: Option<PreconditionerConfig>,
+    relative_tolerance: f64,
+    absolute_tolerance: f64,
+    max_iterations: usize,
+}
+
+/// Solver of a matrix built from a configuration
+pub struct ConfiguredSolver<'a> {
+    matrix: &'a SparseCSR<f64>,
+    method: Method<'a>,
+    preconditioner: Option<Box<dyn Preconditioner + 'a>>,
+    stopping: StoppingCriterion,
+}
+
+// Solver of the configured method
+enum Method<'a> {
+    ConjugateGradient(ConjugateGradient),
+    Gmres(Gmres),
+    Relaxation(Relaxation<'a>),
+    Multigrid(Multigrid),
+    Direct(Box<dyn Preconditioner + 'a>),
+}
+
+// Multigrid preconditioner applying several cycles
+struct MultigridCycles {
+    multigrid: Multigrid,
+    cycles: usize,
+}
+
+impl Default for MultigridConfig {
+    fn default() -> Self {
+        MultigridConfig {
+            cycles: 1,
+            cycle_type: CycleType::V,
+            smoother: RelaxationMethod::SymmetricSor(1.0),
+            sweeps: 1,
+            strength_threshold: 0.08,
+            coarse_size: 50,
+            max_levels: 10,
+        }
+    }
+}
+
+impl MultigridConfig {
+    /// Configuration with the default parameters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of cycles of every application as a preconditioner
+    pub fn with_cycles(mut self, cycles: usize) -> Self {
+        assert!(cycles > 0, "Multigrid needs at least one cycle");
+        self.cycles = cycles;
+        self
+    }
+
+    /// Set the recursion pattern of the cycles
+    pub fn with_cycle_type(mut self, cycle_type: CycleType) -> Self {
+        self.cycle_type = cycle_type;
+        self
+    }
+
+    /// Set the relaxation method and number of sweeps of the smoother
+    pub fn with_smoother(mut self, smoother: RelaxationMethod, sweeps: usize) -> Self {
+        self.smoother = smoother;
+        self.sweeps = sweeps;
+        self
+    }
+
+    /// Set the strength threshold, coarse size and maximum number of levels of the setup
+    pub fn with_setup(
+        mut self,
+        strength_threshold: f64,
+        coarse_size: usize,
+        max_levels: usize,
+    ) -> Self {
+        self.strength_threshold = strength_threshold;
+        self.coarse_size = coarse_size;
+        self.max_levels = max_levels;
+        self
+    }
+
+    // Multigrid hierarchy of a matrix
+    fn build(&self, matrix: &SparseCSR<f64>) -> Multigrid {
+        SmoothedAggregation::new()
+            .with_strength_threshold(self.strength_threshold)
+            .with_coarse_size(self.coarse_size)
+            .with_max_levels(self.max_levels)
+            .build(matrix)
+            .with_cycle_type(self.cycle_type)
+            .with_smoother(self.smoother)
+            .with_sweeps(self.sweeps)
+    }
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        SolverConfig {
+            method: MethodConfig::ConjugateGradient,
+            preconditioner: None,
+            relative_tolerance: 1e-10,
+            absolute_tolerance: 0.0,
+            max_iterations: 1000,
+        }
+    }
+}
+
+impl SolverConfig {
+    /// Configuration of the given method with the default tolerances and no preconditioner
+    pub fn new(method: MethodConfig) -> Self {
+        SolverConfig {
+            method,
+            ..Self::default()
+        }
+    }
+
+    /// Set the preconditioner, only taken by the Krylov methods
+    pub fn with_preconditioner(mut self, preconditioner: PreconditionerConfig) -> Self {
+        self.preconditioner = Some(preconditioner);
+        self
+    }
+
+    /// Set the tolerance on the residual relative to the right hand side
+    pub fn with_relative_tolerance(mut self, tolerance: f64) -> Self {
+        self.relative_tolerance = tolerance;
+        self
+    }
+
+    /// Set the tolerance on the norm of the residual
+    pub fn with_absolute_tolerance(mut self, tolerance: f64) -> Self {
+        self.absolute_tolerance = tolerance;
+        self
+    }
+
+    /// Set the maximum number of iterations of the iterative methods
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Method of the configuration
+    pub fn method(&self) -> MethodConfig {
+        self.method
+    }
+
+    /// Preconditioner of the configuration if any
+    pub fn preconditioner(&self) -> Option<PreconditionerConfig> {
+        self.preconditioner
+    }
+
+    /// Build the configured solver of a square matrix, None if one of the factorizations fails
+    pub fn build<'a>(&self, matrix: &'a SparseCSR<f64>) -> Option<ConfiguredSolver<'a>> {
+        let stopping = StoppingCriterion::new(
+            self.relative_tolerance,
+            self.absolute_tolerance,
+            self.max_iterations,
+        );
+        let method = match self.method {
+            MethodConfig::ConjugateGradient => Method::ConjugateGradient(
+                ConjugateGradient::new().with_stopping_criterion(stopping),
+            ),
+            MethodConfig::Gmres { restart } => Method::Gmres(
+                Gmres::new()
+                    .with_restart(restart)
+                    .with_stopping_criterion(stopping),
+            ),
+            MethodConfig::Relaxation(relaxation) => Method::Relaxation(
+                Relaxation::new(matrix, relaxation).with_stopping_criterion(stopping),
+            ),
+            MethodConfig::Multigrid(multigrid) => {
+                Method::Multigrid(multigrid.build(matrix).with_stopping_criterion(stopping))
+            }
+            MethodConfig::SparseLU => Method::Direct(Box::new(SparseLU::new(matrix)?)),
+            MethodConfig::SparseCholesky => Method::Direct(Box::new(SparseCholesky::new(matrix)?)),
+        };
+        let preconditioner: Option<Box<dyn Preconditioner + 'a>> = match self.preconditioner {
+            None => None,
+            Some(preconditioner) => {
+                assert!(
+                    matches!(method, Method::ConjugateGradient(_) | Method::Gmres(_)),
+                    "Only the Krylov methods take a preconditioner"
+                );
+                Some(match preconditioner {
+                    PreconditionerConfig::Jacobi => Box::new(Jacobi::new(matrix)),
+                    PreconditionerConfig::Relaxation { method, sweeps } => {
+                        Box::new(Relaxation::new(matrix, method).with_sweeps(sweeps))
+                    }
+                    PreconditionerConfig::Multigrid(multigrid) => Box::new(MultigridCycles {
+                        multigrid: multigrid.build(matrix),
+                        cycles: multigrid.cycles,
+                    }),
+                    PreconditionerConfig::SparseLU => Box::new(SparseLU::new(matrix)?),
+                    PreconditionerConfig::SparseCholesky => Box::new(SparseCholesky::new(matrix)?),
+                })
+            }
+        };
+        Some(ConfiguredSolver {
+            matrix,
+            method,
+            preconditioner,
+            stopping,
+        })
+    }
+}
+
+impl ConfiguredSolver<'_> {
+    /// Solve A x = b starting from the values in x
+    ///
+    /// Direct methods count as a single iteration whose residual is checked against the
+    /// tolerances, a failed check being reported as a breakdown.
+    pub fn solve(&self, rhs: &[f64], x: &mut [f64]) -> Convergence {
+        let matrix = self.matrix;
+        match (&self.method, &self.preconditioner) {
+            (Method::ConjugateGradient(solver), Some(preconditioner)) => {
+                solver.solve_preconditioned(matrix, &preconditioner.as_ref(), rhs, x)
+            }
+            (Method::ConjugateGradient(solver), None) => solver.solve(matrix, rhs, x),
+            (Method::Gmres(solver), Some(preconditioner)) => {
+                solver.solve_preconditioned(matrix, &preconditioner.as_ref(), rhs, x)
+            }
+            (Method::Gmres(solver), None) => solver.solve(matrix, rhs, x),
+            (Method::Relaxation(solver), _) => solver.solve(rhs, x),
+            (Method::Multigrid(solver), _) => solver.solve(rhs, x),
+            (Method::Direct(factorization), _) => {
+                factorization.apply(rhs, x);
+                let mut residual = vec![0.0; rhs.len()];
+                matrix.apply(x, &mut residual);
+                residual.iter_mut().zip(rhs).for_each(|(r, b)| *r = b - *r);
+                let residual_norm = norm(&residual);
+                let reason = self
+                    .stopping
+                    .start(norm(rhs))
+                    .check(0, residual_norm, None)
+                    .unwrap_or(StopReason::Breakdown);
+                Convergence {
+                    reason,
+                    iterations: 1,
+                    residual_norm,
+                    spectrum: None,
+                }
+            }
+        }
+    }
+}
+
+impl Preconditioner for MultigridCycles {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        z.iter_mut().for_each(|z| *z = 0.0);
+        for _ in 0..self.cycles {
+            self.multigrid.cycle(r, z);
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solver_config() {
+        // 2D laplacian on a square grid
+        let side: usize = 16;
+        let n = side * side;
+        let mut offsets = vec![0];
+        let (mut cols, mut values) = (Vec::new(), Vec::new());
+        for i in 0..n {
+            let (x, y) = (i % side, i / side);
+            let mut row = vec![(i, 4.0)];
+            if x > 0 {
+                row.push((i - 1, -1.0));
+            }
+            if x + 1 < side {
+                row.push((i + 1, -1.0));
+            }
+            if y > 0 {
+                row.push((i - side, -1.0));
+            }
+            if y + 1 < side {
+                row.push((i + side, -1.0));
+            }
+            row.sort_by_key(|(j, _)| *j);
+            for (j, a) in row {
+                cols.push(j);
+                values.push(a);
+            }
+            offsets.push(cols.len());
+        }
+        let matrix = SparseCSR::new(n, offsets, cols, values);
+        let rhs = vec![1.0; n];
+        let solve = |config: SolverConfig| {
+            let mut x = vec![0.0; n];
+            let convergence = config.build(&matrix).unwrap().solve(&rhs, &mut x);
+            assert!(convergence.converged(), "{:?} did not converge", config);
+            convergence.iterations()
+        };
+        let plain = solve(SolverConfig::default().with_relative_tolerance(1e-8));
+        // CG + AMG(2 V-cycles, Gauss-Seidel smoother)
+        let amg = MultigridConfig::new()
+            .with_cycles(2)
+            .with_smoother(RelaxationMethod::SymmetricSor(1.0), 1)
+            .with_setup(0.08, 10, 10);
+        let preconditioned = solve(
+            SolverConfig::new(MethodConfig::ConjugateGradient)
+                .with_preconditioner(PreconditionerConfig::Multigrid(amg))
+                .with_relative_tolerance(1e-8),
+        );
+        assert!(
+            preconditioned < plain / 2,
+            "Multigrid should speed up the conjugate gradient"
+        );
+        let configs = [
+            SolverConfig::new(MethodConfig::Gmres { restart: 20 }).with_preconditioner(
+                PreconditionerConfig::Relaxation {
+                    method: RelaxationMethod::SymmetricSor(1.0),
+                    sweeps: 2,
+                },
+            ),
+            SolverConfig::new(MethodConfig::Multigrid(amg)).with_relative_tolerance(1e-8),
+            SolverConfig::new(MethodConfig::SparseCholesky),
+            SolverConfig::new(MethodConfig::ConjugateGradient)
+                .with_preconditioner(PreconditionerConfig::SparseLU),
+        ];
+        for config in configs {
+            solve(config);
+        }
+    }
+    //--------------------------------------------------------------------------------------------------
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_solver_config_deserialize() {
+        let config: SolverConfig = serde_json::from_str(
+            r#"{"method": "conjugate_gradient", "preconditioner": {"multigrid": {"cycles": 2}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            SolverConfig::new(MethodConfig::ConjugateGradient).with_preconditioner(
+                PreconditionerConfig::Multigrid(MultigridConfig::new().with_cycles(2))
+            ),
+            "Missing fields should take their default values"
+        );
+        let config: SolverConfig = serde_json::from_str(
+            r#"{
+                "method": {"gmres": {"restart": 20}},
+                "preconditioner": {"relaxation": {"method": {"symmetric_sor": 1.2}, "sweeps": 2}},
+                "relative_tolerance": 1e-6,
+                "max_iterations": 50
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            SolverConfig::new(MethodConfig::Gmres { restart: 20 })
+                .with_preconditioner(PreconditionerConfig::Relaxation {
+                    method: RelaxationMethod::SymmetricSor(1.2),
+                    sweeps: 2,
+                })
+                .with_relative_tolerance(1e-6)
+                .with_max_iterations(50),
+            "Wrong GMRES configuration"
+        );
+        let config: SolverConfig = serde_json::from_str(r#"{"method": "sparse_lu"}"#).unwrap();
+        assert_eq!(
+            config.method(),
+            MethodConfig::SparseLU,
+            "Wrong direct method"
+        );
+        for invalid in [
+            r#"{"method": "lu"}"#,
+            r#"{"method": {"gmres": {}}}"#,
+            r#"{"preconditioner": "multigrid"}"#,
+            r#"{"max_iterations": -1}"#,
+        ] {
+            assert!(
+                serde_json::from_str::<SolverConfig>(invalid).is_err(),
+                "Deserialized {}",
+                invalid
+            );
+        }
+    }
+}
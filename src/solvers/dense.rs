@@ -0,0 +1,466 @@
+use super::conditioning::inverse_norm1_estimate;
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::data_traits::{DataContainer, DataMutator};
+
+// Pivots below this fraction of the largest entry of the matrix are considered zero
+const SINGULAR_TOLERANCE: f64 = 1e-13;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// LU factorization with partial pivoting P A = L U of a square matrix
+///
+/// L (with a unit diagonal) and U are stored together in a single (n, n) array and the row
+/// permutation P as the original row of every row.
+pub struct LU {
+    factors: DataHold<f64, [usize; 2]>,
+    permutation: Vec<usize>,
+    sign: f64,
+    // 1-norm of the factorized matrix for the condition estimate
+    norm: f64,
+}
+
+/// Cholesky factorization A = L L^T of a symmetric positive definite matrix
+///
+/// Only the lower triangle of the matrix is read and L is stored in the lower triangle of an
+/// (n, n) array.
+pub struct Cholesky {
+    factor: DataHold<f64, [usize; 2]>,
+    norm: f64,
+}
+
+/// Eigendecomposition A = V diag(λ) V^T of a symmetric matrix by cyclic Jacobi rotations
+///
+/// Eigenvalues are sorted decreasingly and the orthonormal eigenvectors are the columns of the
+/// (n, n) array V. Sweeps over all the off diagonal entries are repeated until they are negligible
+/// against the diagonal, each costing O(n³).
+pub struct SymmetricEigen {
+    values: Vec<f64>,
+    vectors: DataHold<f64, [usize; 2]>,
+}
+
+impl LU {
+    /// Factorize a square matrix, None if it is singular
+    pub fn new(matrix: &DataHold<f64, [usize; 2]>) -> Option<Self> {
+        let n = square_size(matrix);
+        let mut factors = DataHold::new(matrix.to_vec(), [n, n]);
+        let mut permutation: Vec<usize> = (0..n).collect();
+        let mut sign = 1.0;
+        let tolerance = SINGULAR_TOLERANCE * max_entry(matrix);
+        for k in 0..n {
+            let pivot = (k..n)
+                .max_by(|i, j| {
+                    factors
+                        .multi_index([*i, k])
+                        .abs()
+                        .total_cmp(&factors.multi_index([*j, k]).abs())
+                })
+                .unwrap();
+            if factors.multi_index([pivot, k]).abs() <= tolerance {
+                return None;
+            }
+            if pivot != k {
+                for j in 0..n {
+                    factors.swap(k * n + j, pivot * n + j);
+                }
+                permutation.swap(k, pivot);
+                sign = -sign;
+            }
+            let diagonal = *factors.multi_index([k, k]);
+            for i in (k + 1)..n {
+                let factor = *factors.multi_index([i, k]) / diagonal;
+                *factors.multi_index_mut([i, k]) = factor;
+                for j in (k + 1)..n {
+                    let update = factor * factors.multi_index([k, j]);
+                    *factors.multi_index_mut([i, j]) -= update;
+                }
+            }
+        }
+        Some(LU {
+            factors,
+            permutation,
+            sign,
+            norm: norm1(n, |i, j| *matrix.multi_index([i, j])),
+        })
+    }
+
+    /// Size of the factorized matrix
+    pub fn size(&self) -> usize {
+        self.permutation.len()
+    }
+
+    /// Combined factors with L strictly below the diagonal and U on and above it
+    pub fn factors(&self) -> &DataHold<f64, [usize; 2]> {
+        &self.factors
+    }
+
+    /// Original row of every row of the factors
+    pub fn permutation(&self) -> &[usize] {
+        &self.permutation
+    }
+
+    /// Solve A x = b
+    pub fn solve(&self, rhs: &[f64], x: &mut [f64]) {
+        let n = self.size();
+        assert!(
+            rhs.len() == n && x.len() == n,
+            "Vectors do not match the size of the matrix"
+        );
+        for (i, row) in self.permutation.iter().enumerate() {
+            x[i] = rhs[*row];
+        }
+        for i in 0..n {
+            let sum: f64 = (0..i)
+                .map(|j| self.factors.multi_index([i, j]) * x[j])
+                .sum();
+            x[i] -= sum;
+        }
+        for i in (0..n).rev() {
+            let sum: f64 = ((i + 1)..n)
+                .map(|j| self.factors.multi_index([i, j]) * x[j])
+                .sum();
+            x[i] = (x[i] - sum) / self.factors.multi_index([i, i]);
+        }
+    }
+
+    /// Solve A^T x = b
+    pub fn solve_transpose(&self, rhs: &[f64], x: &mut [f64]) {
+        let n = self.size();
+        assert!(
+            rhs.len() == n && x.len() == n,
+            "Vectors do not match the size of the matrix"
+        );
+        let mut y = rhs.to_vec();
+        for i in 0..n {
+            let sum: f64 = (0..i)
+                .map(|j| self.factors.multi_index([j, i]) * y[j])
+                .sum();
+            y[i] = (y[i] - sum) / self.factors.multi_index([i, i]);
+        }
+        for i in (0..n).rev() {
+            let sum: f64 = ((i + 1)..n)
+                .map(|j| self.factors.multi_index([j, i]) * y[j])
+                .sum();
+            y[i] -= sum;
+        }
+        for (i, row) in self.permutation.iter().enumerate() {
+            x[*row] = y[i];
+        }
+    }
+
+    /// Estimate of the 1-norm condition number of the matrix from a few solves
+    pub fn condition_estimate(&self) -> f64 {
+        let inverse_norm = inverse_norm1_estimate(
+            self.size(),
+            |b, x| self.solve(b, x),
+            |b, x| self.solve_transpose(b, x),
+        );
+        self.norm * inverse_norm
+    }
+
+    /// Determinant of the matrix
+    pub fn determinant(&self) -> f64 {
+        (0..self.size())
+            .map(|i| self.factors.multi_index([i, i]))
+            .product::<f64>()
+            * self.sign
+    }
+
+    /// Inverse of the matrix
+    pub fn inverse(&self) -> DataHold<f64, [usize; 2]> {
+        let n = self.size();
+        let mut inverse = DataHold::new(vec![0.0; n * n], [n, n]);
+        let mut column = vec![0.0; n];
+        let mut unit = vec![0.0; n];
+        for j in 0..n {
+            unit[j] = 1.0;
+            self.solve(&unit, &mut column);
+            unit[j] = 0.0;
+            for (i, value) in column.iter().enumerate() {
+                *inverse.multi_index_mut([i, j]) = *value;
+            }
+        }
+        inverse
+    }
+}
+
+impl Cholesky {
+    /// Factorize a symmetric matrix, None if it is not positive definite
+    pub fn new(matrix: &DataHold<f64, [usize; 2]>) -> Option<Self> {
+        let n = square_size(matrix);
+        let mut factor: DataHold<f64, [usize; 2]> = DataHold::new(vec![0.0; n * n], [n, n]);
+        let tolerance = SINGULAR_TOLERANCE * max_entry(matrix);
+        for j in 0..n {
+            let sum: f64 = (0..j).map(|k| factor.multi_index([j, k]).powi(2)).sum();
+            let pivot = matrix.multi_index([j, j]) - sum;
+            if pivot <= tolerance {
+                return None;
+            }
+            let diagonal = pivot.sqrt();
+            *factor.multi_index_mut([j, j]) = diagonal;
+            for i in (j + 1)..n {
+                let sum: f64 = (0..j)
+                    .map(|k| factor.multi_index([i, k]) * factor.multi_index([j, k]))
+                    .sum();
+                *factor.multi_index_mut([i, j]) = (matrix.multi_index([i, j]) - sum) / diagonal;
+            }
+        }
+        Some(Cholesky {
+            factor,
+            norm: norm1(n, |i, j| *matrix.multi_index([i.max(j), i.min(j)])),
+        })
+    }
+
+    /// Size of the factorized matrix
+    pub fn size(&self) -> usize {
+        self.factor.dimensions()[0]
+    }
+
+    /// Lower triangular factor L
+    pub fn factor(&self) -> &DataHold<f64, [usize; 2]> {
+        &self.factor
+    }
+
+    /// Solve A x = b
+    pub fn solve(&self, rhs: &[f64], x: &mut [f64]) {
+        let n = self.size();
+        assert!(
+            rhs.len() == n && x.len() == n,
+            "Vectors do not match the size of the matrix"
+        );
+        for i in 0..n {
+            let sum: f64 = (0..i).map(|j| self.factor.multi_index([i, j]) * x[j]).sum();
+            x[i] = (rhs[i] - sum) / self.factor.multi_index([i, i]);
+        }
+        for i in (0..n).rev() {
+            let sum: f64 = ((i + 1)..n)
+                .map(|j| self.factor.multi_index([j, i]) * x[j])
+                .sum();
+            x[i] = (x[i] - sum) / self.factor.multi_index([i, i]);
+        }
+    }
+
+    /// Estimate of the 1-norm condition number of the matrix from a few solves
+    pub fn condition_estimate(&self) -> f64 {
+        let solve = |b: &[f64], x: &mut [f64]| self.solve(b, x);
+        self.norm * inverse_norm1_estimate(self.size(), solve, solve)
+    }
+
+    /// Determinant of the matrix
+    pub fn determinant(&self) -> f64 {
+        (0..self.size())
+            .map(|i| self.factor.multi_index([i, i]).powi(2))
+            .product()
+    }
+}
+
+impl SymmetricEigen {
+    /// Decompose a symmetric matrix, of which only the lower triangle is read
+    pub fn new(matrix: &DataHold<f64, [usize; 2]>) -> Self {
+        let n = square_size(matrix);
+        let mut a: DataHold<f64, [usize; 2]> = DataHold::new(vec![0.0; n * n], [n, n]);
+        let mut v: DataHold<f64, [usize; 2]> = DataHold::new(vec![0.0; n * n], [n, n]);
+        for i in 0..n {
+            for j in 0..n {
+                *a.multi_index_mut([i, j]) = *matrix.multi_index([i.max(j), i.min(j)]);
+            }
+            *v.multi_index_mut([i, i]) = 1.0;
+        }
+        for _ in 0..100 {
+            let off: f64 = (0..n)
+                .flat_map(|i| (0..i).map(move |j| (i, j)))
+                .map(|(i, j)| a.multi_index([i, j]).powi(2))
+                .sum();
+            let diagonal: f64 = (0..n).map(|i| a.multi_index([i, i]).powi(2)).sum();
+            if off <= 1e-30 * diagonal || off == 0.0 {
+                break;
+            }
+            for p in 0..n {
+                for q in (p + 1)..n {
+                    let apq = *a.multi_index([p, q]);
+                    if apq == 0.0 {
+                        continue;
+                    }
+                    // Rotation zeroing the entry (p, q)
+                    let theta = (a.multi_index([q, q]) - a.multi_index([p, p])) / (2.0 * apq);
+                    let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                    let c = 1.0 / (t * t + 1.0).sqrt();
+                    let s = t * c;
+                    for k in 0..n {
+                        let (akp, akq) = (*a.multi_index([k, p]), *a.multi_index([k, q]));
+                        *a.multi_index_mut([k, p]) = c * akp - s * akq;
+                        *a.multi_index_mut([k, q]) = s * akp + c * akq;
+                    }
+                    for k in 0..n {
+                        let (apk, aqk) = (*a.multi_index([p, k]), *a.multi_index([q, k]));
+                        *a.multi_index_mut([p, k]) = c * apk - s * aqk;
+                        *a.multi_index_mut([q, k]) = s * apk + c * aqk;
+                    }
+                    for k in 0..n {
+                        let (vkp, vkq) = (*v.multi_index([k, p]), *v.multi_index([k, q]));
+                        *v.multi_index_mut([k, p]) = c * vkp - s * vkq;
+                        *v.multi_index_mut([k, q]) = s * vkp + c * vkq;
+                    }
+                }
+            }
+        }
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|i, j| a.multi_index([*j, *j]).total_cmp(a.multi_index([*i, *i])));
+        let mut vectors: DataHold<f64, [usize; 2]> = DataHold::new(vec![0.0; n * n], [n, n]);
+        for (column, k) in order.iter().enumerate() {
+            for i in 0..n {
+                *vectors.multi_index_mut([i, column]) = *v.multi_index([i, *k]);
+            }
+        }
+        SymmetricEigen {
+            values: order.iter().map(|k| *a.multi_index([*k, *k])).collect(),
+            vectors,
+        }
+    }
+
+    /// Eigenvalues sorted decreasingly
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// Orthonormal eigenvectors as the columns of an (n, n) array
+    pub fn vectors(&self) -> &DataHold<f64, [usize; 2]> {
+        &self.vectors
+    }
+
+    /// Eigenvector of an eigenvalue
+    pub fn vector(&self, k: usize) -> Vec<f64> {
+        let n = self.values.len();
+        (0..n).map(|i| *self.vectors.multi_index([i, k])).collect()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Determinant of a square matrix (zero if it is singular)
+pub fn determinant(matrix: &DataHold<f64, [usize; 2]>) -> f64 {
+    LU::new(matrix).map_or(0.0, |lu| lu.determinant())
+}
+
+fn square_size(matrix: &DataHold<f64, [usize; 2]>) -> usize {
+    let [n, m] = *matrix.dimensions();
+    assert_eq!(n, m, "Tried to factorize a non square matrix");
+    n
+}
+
+// 1-norm of a square matrix given by its entries
+fn norm1<Entry: Fn(usize, usize) -> f64>(n: usize, entry: Entry) -> f64 {
+    (0..n)
+        .map(|j| (0..n).map(|i| entry(i, j).abs()).sum::<f64>())
+        .fold(0.0, f64::max)
+}
+
+fn max_entry(matrix: &[f64]) -> f64 {
+    matrix.iter().fold(0.0, |m: f64, v| m.max(v.abs()))
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lu() {
+        // Needs pivoting since the first diagonal entry vanishes
+        let matrix = DataHold::new(vec![0.0, 2.0, 1.0, 1.0, 1.0, 0.0, 3.0, 0.0, 1.0], [3, 3]);
+        let lu = LU::new(&matrix).unwrap();
+        assert!(
+            (lu.determinant() + 5.0).abs() < 1e-14,
+            "Wrong determinant {}",
+            lu.determinant()
+        );
+        let mut x = vec![0.0; 3];
+        lu.solve(&[3.0, 2.0, 4.0], &mut x);
+        for (x, expected) in x.iter().zip([1.0, 1.0, 1.0]) {
+            assert!((x - expected).abs() < 1e-14, "Wrong solution");
+        }
+        lu.solve_transpose(&[4.0, 3.0, 2.0], &mut x);
+        for (x, expected) in x.iter().zip([1.0, 1.0, 1.0]) {
+            assert!((x - expected).abs() < 1e-14, "Wrong transposed solution");
+        }
+        let inverse = lu.inverse();
+        for i in 0..3 {
+            for j in 0..3 {
+                let product: f64 = (0..3)
+                    .map(|k| matrix.multi_index([i, k]) * inverse.multi_index([k, j]))
+                    .sum();
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((product - expected).abs() < 1e-14, "Wrong inverse");
+            }
+        }
+        let singular = DataHold::new(vec![1.0, 2.0, 2.0, 4.0], [2, 2]);
+        assert!(LU::new(&singular).is_none(), "Matrix should be singular");
+        assert_eq!(determinant(&singular), 0.0, "Singular determinant");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_cholesky() {
+        let matrix = DataHold::new(vec![4.0, 2.0, 0.0, 2.0, 5.0, 1.0, 0.0, 1.0, 3.0], [3, 3]);
+        let cholesky = Cholesky::new(&matrix).unwrap();
+        assert!(
+            (cholesky.determinant() - determinant(&matrix)).abs() < 1e-12,
+            "Wrong determinant"
+        );
+        assert!(
+            (cholesky.determinant() - 44.0).abs() < 1e-12,
+            "Wrong determinant"
+        );
+        let mut x = vec![0.0; 3];
+        cholesky.solve(&[6.0, 8.0, 4.0], &mut x);
+        for (x, expected) in x.iter().zip([1.0, 1.0, 1.0]) {
+            assert!((x - expected).abs() < 1e-14, "Wrong solution");
+        }
+        let indefinite = DataHold::new(vec![1.0, 2.0, 2.0, 1.0], [2, 2]);
+        assert!(
+            Cholesky::new(&indefinite).is_none(),
+            "Matrix is not positive definite"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_symmetric_eigen() {
+        // Second difference matrix of eigenvalues 2 - 2 cos(kπ / 4)
+        let matrix = DataHold::new(
+            vec![2.0, -1.0, 0.0, -1.0, 2.0, -1.0, 0.0, -1.0, 2.0],
+            [3, 3],
+        );
+        let eigen = SymmetricEigen::new(&matrix);
+        let expected = [2.0 + 2.0f64.sqrt(), 2.0, 2.0 - 2.0f64.sqrt()];
+        for (value, expected) in eigen.values().iter().zip(expected) {
+            assert!(
+                (value - expected).abs() < 1e-12,
+                "Wrong eigenvalue {}",
+                value
+            );
+        }
+        for k in 0..3 {
+            let v = eigen.vector(k);
+            for i in 0..3 {
+                let av: f64 = (0..3).map(|j| matrix.multi_index([i, j]) * v[j]).sum();
+                assert!(
+                    (av - eigen.values()[k] * v[i]).abs() < 1e-12,
+                    "Wrong eigenvector {}",
+                    k
+                );
+            }
+            let norm: f64 = v.iter().map(|x| x * x).sum();
+            assert!(
+                (norm - 1.0).abs() < 1e-12,
+                "Eigenvectors should be normalized"
+            );
+        }
+    }
+}
@@ -0,0 +1,865 @@
+use super::krylov::{ConjugateGradient, Convergence, Gmres, Identity};
+use super::linear_operator::{LinearOperator, Preconditioner};
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::discretizations::partition::Subdomain;
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+//--------------------------------------------------------------------------------------------------
+// # Traits
+//--------------------------------------------------------------------------------------------------
+
+/// Collective communication between the processes of a distributed computation
+///
+/// Every process has to call the same collective operations in the same order.
+pub trait Communicator {
+    /// Rank of the calling process
+    fn rank(&self) -> usize;
+
+    /// Number of processes
+    fn size(&self) -> usize;
+
+    /// Sum of the values given by all the processes, the same on every process
+    fn all_reduce_sum(&self, value: f64) -> f64;
+
+    /// Send the i-th list of indices to the process of rank i and return the lists received from
+    /// every process in the order of their ranks
+    fn exchange_indices(&self, outgoing: Vec<Vec<usize>>) -> Vec<Vec<usize>>;
+
+    /// Send the i-th list of values to the process of rank i and return the lists received from
+    /// every process in the order of their ranks
+    fn exchange_values(&self, outgoing: Vec<Vec<f64>>) -> Vec<Vec<f64>>;
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Communicator between threads of the same process, every thread holding the communicator of
+/// its rank
+///
+/// It runs distributed computations without MPI, for instance to test them.
+pub struct ThreadCommunicator {
+    rank: usize,
+    senders: Vec<Sender<Message>>,
+    receivers: Vec<Receiver<Message>>,
+}
+
+/// Communicator over MPI processes
+#[cfg(feature = "mpi")]
+pub struct MpiCommunicator {
+    communicator: mpi::topology::SimpleCommunicator,
+}
+
+/// Distribution of the entries of a vector between processes
+///
+/// Every process holds a local vector whose entries are either owned by the process or ghosts,
+/// which are copies of entries owned by other processes. Local entries are identified by their
+/// global index and the communication pattern refreshing the ghosts is set up at construction.
+pub struct IndexMap {
+    global_indices: Vec<usize>,
+    owned: Vec<bool>,
+    // Local indices of the owned entries sent to every rank, and of the ghosts received from it
+    sends: Vec<Vec<usize>>,
+    receives: Vec<Vec<usize>>,
+}
+
+/// Local part of a distributed vector with its ghost entries
+pub struct DistributedVector<'a> {
+    map: &'a IndexMap,
+    values: Vec<f64>,
+}
+
+/// Row distributed sparse matrix whose local matrix holds the rows of the owned entries with the
+/// columns of the local (owned and ghost) entries
+///
+/// As a linear operator the matrix acts on local vectors: the ghosts of the input are refreshed
+/// before the product and the ghosts of the output are set to zero. The rows of the ghost entries
+/// in the local matrix are ignored.
+pub struct DistributedMatrix<'a, Comm: Communicator> {
+    communicator: &'a Comm,
+    map: &'a IndexMap,
+    matrix: SparseCSR<f64>,
+}
+
+// Point to point message of the thread communicator
+enum Message {
+    Value(f64),
+    Indices(Vec<usize>),
+    Values(Vec<f64>),
+}
+
+// Preconditioner applying another one to the local vector and zeroing the ghosts of the result
+struct OwnedPart<'a, Precond> {
+    preconditioner: &'a Precond,
+    owned: &'a [bool],
+}
+
+impl ThreadCommunicator {
+    /// Communicators of all the ranks of a group of threads
+    pub fn group(size: usize) -> Vec<ThreadCommunicator> {
+        // One channel per ordered pair of ranks which keeps the messages of every pair in order
+        let mut senders: Vec<Vec<Sender<Message>>> = (0..size).map(|_| Vec::new()).collect();
+        let mut receivers: Vec<Vec<Receiver<Message>>> = (0..size).map(|_| Vec::new()).collect();
+        for from in senders.iter_mut() {
+            for to in receivers.iter_mut() {
+                let (sender, receiver) = channel();
+                from.push(sender);
+                to.push(receiver);
+            }
+        }
+        senders
+            .into_iter()
+            .zip(receivers)
+            .enumerate()
+            .map(|(rank, (senders, receivers))| ThreadCommunicator {
+                rank,
+                senders,
+                receivers,
+            })
+            .collect()
+    }
+
+    // Send one message to every rank and receive one from every rank
+    fn all_to_all(&self, outgoing: Vec<Message>) -> Vec<Message> {
+        assert_eq!(
+            outgoing.len(),
+            self.size(),
+            "One message per rank is needed"
+        );
+        for (sender, message) in self.senders.iter().zip(outgoing) {
+            sender.send(message).expect("Rank of the group hung up");
+        }
+        self.receivers
+            .iter()
+            .map(|receiver| receiver.recv().expect("Rank of the group hung up"))
+            .collect()
+    }
+}
+
+impl Communicator for ThreadCommunicator {
+    fn rank(&self) -> usize {
+        self.rank
+    }
+
+    fn size(&self) -> usize {
+        self.senders.len()
+    }
+
+    fn all_reduce_sum(&self, value: f64) -> f64 {
+        // Summing in the order of the ranks gives the same result on every rank
+        let outgoing = (0..self.size()).map(|_| Message::Value(value)).collect();
+        self.all_to_all(outgoing)
+            .into_iter()
+            .map(|message| match message {
+                Message::Value(value) => value,
+                _ => panic!("Ranks called different collective operations"),
+            })
+            .sum()
+    }
+
+    fn exchange_indices(&self, outgoing: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+        let outgoing = outgoing.into_iter().map(Message::Indices).collect();
+        self.all_to_all(outgoing)
+            .into_iter()
+            .map(|message| match message {
+                Message::Indices(indices) => indices,
+                _ => panic!("Ranks called different collective operations"),
+            })
+            .collect()
+    }
+
+    fn exchange_values(&self, outgoing: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+        let outgoing = outgoing.into_iter().map(Message::Values).collect();
+        self.all_to_all(outgoing)
+            .into_iter()
+            .map(|message| match message {
+                Message::Values(values) => values,
+                _ => panic!("Ranks called different collective operations"),
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "mpi")]
+impl MpiCommunicator {
+    /// Communicator over the processes of an MPI communicator, usually the world of the universe
+    /// returned by mpi::initialize
+    pub fn new(communicator: mpi::topology::SimpleCommunicator) -> Self {
+        MpiCommunicator { communicator }
+    }
+
+    // Variable size all to all exchange of the flattened lists
+    fn all_to_all<T>(&self, outgoing: Vec<Vec<T>>) -> Vec<Vec<T>>
+    where
+        T: mpi::datatype::Equivalence + Clone + Default,
+    {
+        use mpi::datatype::{Partition, PartitionMut};
+        use mpi::traits::*;
+        let displacements = |counts: &[i32]| -> Vec<i32> {
+            counts
+                .iter()
+                .scan(0, |offset, count| {
+                    let displacement = *offset;
+                    *offset += count;
+                    Some(displacement)
+                })
+                .collect()
+        };
+        let send_counts: Vec<i32> = outgoing.iter().map(|list| list.len() as i32).collect();
+        let mut receive_counts = vec![0_i32; self.size()];
+        self.communicator
+            .all_to_all_into(&send_counts[..], &mut receive_counts[..]);
+        let send_buffer: Vec<T> = outgoing.concat();
+        let send_displacements = displacements(&send_counts);
+        let receive_displacements = displacements(&receive_counts);
+        let total = receive_counts.iter().sum::<i32>() as usize;
+        let mut receive_buffer = vec![T::default(); total];
+        {
+            let send = Partition::new(&send_buffer[..], &send_counts[..], &send_displacements[..]);
+            let mut receive = PartitionMut::new(
+                &mut receive_buffer[..],
+                &receive_counts[..],
+                &receive_displacements[..],
+            );
+            self.communicator
+                .all_to_all_varcount_into(&send, &mut receive);
+        }
+        receive_counts
+            .iter()
+            .zip(&receive_displacements)
+            .map(|(count, start)| {
+                receive_buffer[*start as usize..(*start + *count) as usize].to_vec()
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "mpi")]
+impl Communicator for MpiCommunicator {
+    fn rank(&self) -> usize {
+        use mpi::traits::*;
+        self.communicator.rank() as usize
+    }
+
+    fn size(&self) -> usize {
+        use mpi::traits::*;
+        self.communicator.size() as usize
+    }
+
+    fn all_reduce_sum(&self, value: f64) -> f64 {
+        use mpi::collective::SystemOperation;
+        use mpi::traits::*;
+        let mut sum = 0.0;
+        self.communicator
+            .all_reduce_into(&value, &mut sum, SystemOperation::sum());
+        sum
+    }
+
+    fn exchange_indices(&self, outgoing: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+        let outgoing = outgoing
+            .into_iter()
+            .map(|list| list.into_iter().map(|i| i as u64).collect())
+            .collect();
+        self.all_to_all::<u64>(outgoing)
+            .into_iter()
+            .map(|list| list.into_iter().map(|i| i as usize).collect())
+            .collect()
+    }
+
+    fn exchange_values(&self, outgoing: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+        self.all_to_all(outgoing)
+    }
+}
+
+impl IndexMap {
+    /// Distribution of the local entries of the given global indices and owner ranks
+    ///
+    /// This is a collective operation. An entry is owned by the calling process when its owner is
+    /// the rank of the process, and every ghost must be owned by the process given as its owner.
+    pub fn new<Comm: Communicator>(
+        communicator: &Comm,
+        global_indices: Vec<usize>,
+        owners: &[usize],
+    ) -> Self {
+        assert_eq!(
+            global_indices.len(),
+            owners.len(),
+            "Every local entry needs an owner"
+        );
+        let rank = communicator.rank();
+        let size = communicator.size();
+        let owned: Vec<bool> = owners.iter().map(|owner| *owner == rank).collect();
+        let mut receives = vec![Vec::new(); size];
+        let mut requests = vec![Vec::new(); size];
+        for (local, owner) in owners.iter().enumerate() {
+            if *owner != rank {
+                assert!(*owner < size, "Owner is not a rank of the communicator");
+                receives[*owner].push(local);
+                requests[*owner].push(global_indices[local]);
+            }
+        }
+        // Owners learn which of their entries every other process holds as ghosts
+        let owned_locals: HashMap<usize, usize> = global_indices
+            .iter()
+            .zip(&owned)
+            .enumerate()
+            .filter(|(_, (_, owned))| **owned)
+            .map(|(local, (global, _))| (*global, local))
+            .collect();
+        let sends = communicator
+            .exchange_indices(requests)
+            .into_iter()
+            .map(|requested| {
+                requested
+                    .iter()
+                    .map(|global| {
+                        *owned_locals
+                            .get(global)
+                            .expect("Ghost entry is not owned by the given owner")
+                    })
+                    .collect()
+            })
+            .collect();
+        IndexMap {
+            global_indices,
+            owned,
+            sends,
+            receives,
+        }
+    }
+
+    /// Distribution of the vertex values of a subdomain, local entries being numbered by local
+    /// vertex, which are the dofs of continuous linear Lagrange spaces
+    pub fn from_subdomain<Comm: Communicator>(communicator: &Comm, subdomain: &Subdomain) -> Self {
+        assert_eq!(
+            subdomain.part(),
+            communicator.rank(),
+            "Subdomain does not belong to the calling process"
+        );
+        let n_vertices = subdomain.mesh().n_vertices();
+        let global_indices = (0..n_vertices)
+            .map(|v| subdomain.global_vertex(v))
+            .collect();
+        let owners: Vec<usize> = (0..n_vertices).map(|v| subdomain.vertex_owner(v)).collect();
+        Self::new(communicator, global_indices, &owners)
+    }
+
+    /// Number of local entries
+    pub fn n_local(&self) -> usize {
+        self.global_indices.len()
+    }
+
+    /// Number of local entries owned by the process
+    pub fn n_owned(&self) -> usize {
+        self.owned.iter().filter(|owned| **owned).count()
+    }
+
+    /// Global index of a local entry
+    pub fn global_index(&self, local: usize) -> usize {
+        self.global_indices[local]
+    }
+
+    /// Whether a local entry is owned by the process
+    pub fn is_owned(&self, local: usize) -> bool {
+        self.owned[local]
+    }
+
+    /// Copy the values of the owned entries into the ghosts of the other processes, which is a
+    /// collective operation
+    pub fn update_ghosts<Comm: Communicator>(&self, communicator: &Comm, values: &mut [f64]) {
+        assert_eq!(
+            values.len(),
+            self.n_local(),
+            "Vector does not match the index map"
+        );
+        let outgoing = self
+            .sends
+            .iter()
+            .map(|locals| locals.iter().map(|local| values[*local]).collect())
+            .collect();
+        let incoming = communicator.exchange_values(outgoing);
+        for (locals, received) in self.receives.iter().zip(incoming) {
+            for (local, value) in locals.iter().zip(received) {
+                values[*local] = value;
+            }
+        }
+    }
+
+    /// Inner product of two distributed vectors over their owned entries, which is a collective
+    /// operation
+    pub fn dot<Comm: Communicator>(&self, communicator: &Comm, a: &[f64], b: &[f64]) -> f64 {
+        let local: f64 = a
+            .iter()
+            .zip(b)
+            .zip(&self.owned)
+            .filter(|(_, owned)| **owned)
+            .map(|((a, b), _)| a * b)
+            .sum();
+        communicator.all_reduce_sum(local)
+    }
+}
+
+impl<'a> DistributedVector<'a> {
+    /// Zero vector
+    pub fn new(map: &'a IndexMap) -> Self {
+        DistributedVector {
+            map,
+            values: vec![0.0; map.n_local()],
+        }
+    }
+
+    /// Vector of the given local values
+    pub fn from_values(map: &'a IndexMap, values: Vec<f64>) -> Self {
+        assert_eq!(
+            values.len(),
+            map.n_local(),
+            "Values do not match the index map"
+        );
+        DistributedVector { map, values }
+    }
+
+    /// Index map of the vector
+    pub fn map(&self) -> &IndexMap {
+        self.map
+    }
+
+    /// Local values of the owned and ghost entries
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// Mutable local values of the owned and ghost entries
+    pub fn values_mut(&mut self) -> &mut [f64] {
+        &mut self.values
+    }
+
+    /// Take the local values out of the vector
+    pub fn into_values(self) -> Vec<f64> {
+        self.values
+    }
+
+    /// Refresh the ghost entries from their owners
+    pub fn update_ghosts<Comm: Communicator>(&mut self, communicator: &Comm) {
+        self.map.update_ghosts(communicator, &mut self.values);
+    }
+
+    /// Inner product with another vector of the same map
+    pub fn dot<Comm: Communicator>(&self, communicator: &Comm, other: &DistributedVector) -> f64 {
+        self.map.dot(communicator, &self.values, &other.values)
+    }
+
+    /// Euclidean norm of the distributed vector
+    pub fn norm<Comm: Communicator>(&self, communicator: &Comm) -> f64 {
+        self.dot(communicator, self).sqrt()
+    }
+}
+
+impl<'a, Comm: Communicator> DistributedMatrix<'a, Comm> {
+    /// Distributed matrix of the local matrix assembled on the local entries of the map
+    pub fn new(communicator: &'a Comm, map: &'a IndexMap, matrix: SparseCSR<f64>) -> Self {
+        assert!(
+            matrix.n_rows() == map.n_local() && matrix.n_cols() == map.n_local(),
+            "Local matrix does not match the index map"
+        );
+        DistributedMatrix {
+            communicator,
+            map,
+            matrix,
+        }
+    }
+
+    /// Local matrix
+    pub fn local_matrix(&self) -> &SparseCSR<f64> {
+        &self.matrix
+    }
+
+    /// Index map of the rows and columns
+    pub fn map(&self) -> &IndexMap {
+        self.map
+    }
+}
+
+impl<Comm: Communicator> LinearOperator for DistributedMatrix<'_, Comm> {
+    fn n_rows(&self) -> usize {
+        self.map.n_local()
+    }
+
+    fn n_cols(&self) -> usize {
+        self.map.n_local()
+    }
+
+    fn apply(&self, x: &[f64], y: &mut [f64]) {
+        let mut ghosted = x.to_vec();
+        self.map.update_ghosts(self.communicator, &mut ghosted);
+        for (row, y) in y.iter_mut().enumerate() {
+            *y = if self.map.owned[row] {
+                let (cols, values) = self.matrix.row(row);
+                cols.iter().zip(values).map(|(c, v)| v * ghosted[*c]).sum()
+            } else {
+                0.0
+            };
+        }
+    }
+}
+
+impl<Precond: Preconditioner> Preconditioner for OwnedPart<'_, Precond> {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        self.preconditioner.apply(r, z);
+        z.iter_mut()
+            .zip(self.owned)
+            .filter(|(_, owned)| !**owned)
+            .for_each(|(z, _)| *z = 0.0);
+    }
+}
+
+impl ConjugateGradient {
+    /// Solve a distributed system A x = b starting from the values in x, which is a collective
+    /// operation
+    ///
+    /// The ghosts of x are refreshed when returning.
+    pub fn solve_distributed<Comm: Communicator>(
+        &self,
+        matrix: &DistributedMatrix<Comm>,
+        rhs: &DistributedVector,
+        x: &mut DistributedVector,
+    ) -> Convergence {
+        self.solve_distributed_preconditioned(matrix, &Identity, rhs, x)
+    }
+
+    /// Solve a distributed system with a preconditioner applied to the local vectors, the ghosts
+    /// of its result being ignored
+    pub fn solve_distributed_preconditioned<Comm, Precond>(
+        &self,
+        matrix: &DistributedMatrix<Comm>,
+        preconditioner: &Precond,
+        rhs: &DistributedVector,
+        x: &mut DistributedVector,
+    ) -> Convergence
+    where
+        Comm: Communicator,
+        Precond: Preconditioner,
+    {
+        let (map, communicator) = (matrix.map, matrix.communicator);
+        let rhs = owned_values(map, rhs);
+        let preconditioner = OwnedPart {
+            preconditioner,
+            owned: &map.owned,
+        };
+        let convergence =
+            self.solve_with_inner_product(matrix, &preconditioner, &rhs, &mut x.values, |a, b| {
+                map.dot(communicator, a, b)
+            });
+        x.update_ghosts(communicator);
+        convergence
+    }
+}
+
+impl Gmres {
+    /// Solve a distributed system A x = b starting from the values in x, which is a collective
+    /// operation
+    ///
+    /// The ghosts of x are refreshed when returning.
+    pub fn solve_distributed<Comm: Communicator>(
+        &self,
+        matrix: &DistributedMatrix<Comm>,
+        rhs: &DistributedVector,
+        x: &mut DistributedVector,
+    ) -> Convergence {
+        self.solve_distributed_preconditioned(matrix, &Identity, rhs, x)
+    }
+
+    /// Solve a distributed system with a preconditioner applied to the local vectors, the ghosts
+    /// of its result being ignored
+    pub fn solve_distributed_preconditioned<Comm, Precond>(
+        &self,
+        matrix: &DistributedMatrix<Comm>,
+        preconditioner: &Precond,
+        rhs: &DistributedVector,
+        x: &mut DistributedVector,
+    ) -> Convergence
+    where
+        Comm: Communicator,
+        Precond: Preconditioner,
+    {
+        let (map, communicator) = (matrix.map, matrix.communicator);
+        let rhs = owned_values(map, rhs);
+        let preconditioner = OwnedPart {
+            preconditioner,
+            owned: &map.owned,
+        };
+        let convergence =
+            self.solve_with_inner_product(matrix, &preconditioner, &rhs, &mut x.values, |a, b| {
+                map.dot(communicator, a, b)
+            });
+        x.update_ghosts(communicator);
+        convergence
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Local values of a distributed vector with zero ghosts
+fn owned_values(map: &IndexMap, vector: &DistributedVector) -> Vec<f64> {
+    vector
+        .values
+        .iter()
+        .zip(&map.owned)
+        .map(|(v, owned)| if *owned { *v } else { 0.0 })
+        .collect()
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::mesh::Mesh;
+    use crate::discretizations::partition::{Partition, PartitionMethod};
+    use crate::discretizations::test_meshes::square_grid;
+    use crate::solvers::krylov::Jacobi;
+    use std::thread;
+
+    // Graph laplacian of the cell edges with a shift, assembled cell by cell like a finite
+    // element matrix
+    fn assemble(mesh: &Mesh) -> SparseCSR<f64> {
+        let n = mesh.n_vertices();
+        let mut rows = vec![Vec::new(); n];
+        for cell in 0..mesh.n_cells() {
+            let vertices = mesh.cell(cell);
+            for a in vertices {
+                rows[*a].push((*a, 0.1));
+                for b in vertices.iter().filter(|b| *b != a) {
+                    rows[*a].push((*a, 1.0));
+                    rows[*a].push((*b, -1.0));
+                }
+            }
+        }
+        let mut offsets = vec![0];
+        let (mut cols, mut values) = (Vec::new(), Vec::new());
+        for mut row in rows {
+            row.sort_by_key(|(col, _)| *col);
+            for (col, value) in row {
+                if cols.len() > offsets[offsets.len() - 1] && cols[cols.len() - 1] == col {
+                    let last = values.len() - 1;
+                    values[last] += value;
+                } else {
+                    cols.push(col);
+                    values.push(value);
+                }
+            }
+            offsets.push(cols.len());
+        }
+        SparseCSR::new(n, offsets, cols, values)
+    }
+
+    // Run a closure on every rank of a group of threads and return the results in rank order
+    fn on_ranks<Run, R>(size: usize, run: Run) -> Vec<R>
+    where
+        Run: Fn(&ThreadCommunicator) -> R + Sync,
+        R: Send,
+    {
+        thread::scope(|scope| {
+            let handles: Vec<_> = ThreadCommunicator::group(size)
+                .into_iter()
+                .map(|communicator| {
+                    let run = &run;
+                    scope.spawn(move || run(&communicator))
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        })
+    }
+
+    #[test]
+    fn test_distributed_solvers() {
+        let mesh = square_grid(8);
+        let rhs_value = |v: usize| (v as f64).sin();
+        let matrix = assemble(&mesh);
+        let rhs: Vec<f64> = (0..mesh.n_vertices()).map(rhs_value).collect();
+        let mut expected = vec![0.0; mesh.n_vertices()];
+        ConjugateGradient::new()
+            .with_relative_tolerance(1e-12)
+            .solve(&matrix, &rhs, &mut expected);
+        let n_parts = 3;
+        let partition = Partition::new(&mesh, n_parts, PartitionMethod::GraphBisection);
+        let subdomains: Vec<Subdomain> = (0..n_parts)
+            .map(|part| partition.subdomain(&mesh, part, 1))
+            .collect();
+        let handles: Vec<_> = ThreadCommunicator::group(n_parts)
+            .into_iter()
+            .zip(subdomains)
+            .map(|(communicator, subdomain)| {
+                thread::spawn(move || {
+                    let map = IndexMap::from_subdomain(&communicator, &subdomain);
+                    let local_matrix = assemble(subdomain.mesh());
+                    let matrix = DistributedMatrix::new(&communicator, &map, local_matrix);
+                    let rhs_values = (0..map.n_local())
+                        .map(|v| rhs_value(map.global_index(v)))
+                        .collect();
+                    let rhs = DistributedVector::from_values(&map, rhs_values);
+                    let total = communicator.all_reduce_sum(map.n_owned() as f64);
+                    let mut solutions = Vec::new();
+                    for gmres in [false, true] {
+                        let mut x = DistributedVector::new(&map);
+                        let convergence = if gmres {
+                            Gmres::new()
+                                .with_relative_tolerance(1e-12)
+                                .solve_distributed(&matrix, &rhs, &mut x)
+                        } else {
+                            ConjugateGradient::new()
+                                .with_relative_tolerance(1e-12)
+                                .solve_distributed(&matrix, &rhs, &mut x)
+                        };
+                        assert!(convergence.converged(), "Distributed solve failed");
+                        solutions.push(x.into_values());
+                    }
+                    let globals: Vec<usize> =
+                        (0..map.n_local()).map(|v| map.global_index(v)).collect();
+                    (total, globals, solutions)
+                })
+            })
+            .collect();
+        for handle in handles {
+            let (total, globals, solutions) = handle.join().unwrap();
+            assert_eq!(total, 81.0, "Every vertex should have a single owner");
+            for solution in solutions {
+                // Ghosts are refreshed too
+                for (global, value) in globals.iter().zip(solution) {
+                    assert!(
+                        (value - expected[*global]).abs() < 1e-8,
+                        "Wrong distributed solution"
+                    );
+                }
+            }
+        }
+    }
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_distributed_ghosts() {
+        // Rank r owns the entries 3 r to 3 r + 2 and holds the neighbouring entries of a ring as
+        // ghosts, rank 0 holding one of rank 2 too
+        let results = on_ranks(4, |communicator| {
+            let rank = communicator.rank();
+            let mut globals: Vec<usize> = (3 * rank..3 * rank + 3).collect();
+            globals.extend([3 * ((rank + 1) % 4), 3 * ((rank + 3) % 4) + 2]);
+            if rank == 0 {
+                globals.push(7);
+            }
+            let owners: Vec<usize> = globals.iter().map(|global| global / 3).collect();
+            let map = IndexMap::new(communicator, globals, &owners);
+            let values = (0..map.n_local())
+                .map(|v| match map.is_owned(v) {
+                    true => 10.0 * map.global_index(v) as f64,
+                    false => f64::NAN,
+                })
+                .collect();
+            let mut vector = DistributedVector::from_values(&map, values);
+            vector.update_ghosts(communicator);
+            let entries: Vec<(usize, f64)> = (0..map.n_local())
+                .map(|v| (map.global_index(v), vector.values()[v]))
+                .collect();
+            (map.n_owned(), entries, vector.norm(communicator))
+        });
+        for (rank, (n_owned, entries, norm)) in results.into_iter().enumerate() {
+            assert_eq!(n_owned, 3, "Wrong number of owned entries");
+            assert_eq!(
+                entries.len(),
+                if rank == 0 { 6 } else { 5 },
+                "Wrong number of local entries"
+            );
+            for (global, value) in entries {
+                assert_eq!(
+                    value,
+                    10.0 * global as f64,
+                    "Ghost {} not refreshed on rank {}",
+                    global,
+                    rank
+                );
+            }
+            // Sum of the squares of 10 g for g from 0 to 11, ghosts excluded
+            assert!(
+                (norm - 10.0 * 506.0_f64.sqrt()).abs() < 1e-12,
+                "Wrong distributed norm"
+            );
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_distributed_matrix() {
+        let mesh = square_grid(6);
+        let value = |v: usize| 1.0 + (v as f64).cos();
+        let matrix = assemble(&mesh);
+        let x: Vec<f64> = (0..mesh.n_vertices()).map(value).collect();
+        let mut product = vec![0.0; mesh.n_vertices()];
+        matrix.apply(&x, &mut product);
+        let mut expected = vec![0.0; mesh.n_vertices()];
+        ConjugateGradient::new()
+            .with_relative_tolerance(1e-12)
+            .solve(&matrix, &x, &mut expected);
+        let n_parts = 4;
+        let partition = Partition::new(&mesh, n_parts, PartitionMethod::GraphBisection);
+        let subdomains: Vec<Subdomain> = (0..n_parts)
+            .map(|part| partition.subdomain(&mesh, part, 1))
+            .collect();
+        let results = on_ranks(n_parts, |communicator| {
+            let subdomain = &subdomains[communicator.rank()];
+            let map = IndexMap::from_subdomain(communicator, subdomain);
+            let distributed =
+                DistributedMatrix::new(communicator, &map, assemble(subdomain.mesh()));
+            // Ghosts of the input are refreshed by the product
+            let local_x: Vec<f64> = (0..map.n_local())
+                .map(|v| match map.is_owned(v) {
+                    true => value(map.global_index(v)),
+                    false => f64::NAN,
+                })
+                .collect();
+            let mut local_product = vec![1.0; map.n_local()];
+            distributed.apply(&local_x, &mut local_product);
+            let rhs = DistributedVector::from_values(&map, local_x);
+            let mut solution = DistributedVector::new(&map);
+            let jacobi = Jacobi::new(distributed.local_matrix());
+            let convergence = Gmres::new()
+                .with_restart(5)
+                .with_relative_tolerance(1e-12)
+                .solve_distributed_preconditioned(&distributed, &jacobi, &rhs, &mut solution);
+            let solution = solution.into_values();
+            let entries: Vec<(usize, bool, f64, f64)> = (0..map.n_local())
+                .map(|v| {
+                    let global = map.global_index(v);
+                    (global, map.is_owned(v), local_product[v], solution[v])
+                })
+                .collect();
+            (convergence.converged(), entries)
+        });
+        for (converged, entries) in results {
+            assert!(converged, "Preconditioned distributed GMRES failed");
+            for (global, owned, local_product, solution) in entries {
+                let expected_product = if owned { product[global] } else { 0.0 };
+                assert!(
+                    (local_product - expected_product).abs() < 1e-12,
+                    "Wrong distributed product of entry {}",
+                    global
+                );
+                assert!(
+                    (solution - expected[global]).abs() < 1e-8,
+                    "Wrong distributed solution of entry {}",
+                    global
+                );
+            }
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    #[should_panic]
+    fn test_distributed_unknown_owner() {
+        let communicators = ThreadCommunicator::group(1);
+        IndexMap::new(&communicators[0], vec![0, 1], &[0, 1]);
+    }
+}
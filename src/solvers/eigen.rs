@@ -0,0 +1,731 @@
+use super::dense::LU;
+use super::krylov::{dot, norm};
+use super::sparse_direct::SparseLU;
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::data_traits::DataMutator;
+use crate::core::arrays::sparse_csr::SparseCSR;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Real eigenvalues and eigenvectors sorted by increasing distance to the shift
+pub struct EigenPairs {
+    values: Vec<f64>,
+    vectors: Vec<Vec<f64>>,
+    converged: bool,
+}
+
+/// Complex eigenvalues as (real, imaginary) parts and eigenvectors as (real, imaginary) vectors
+/// sorted by increasing distance to the shift
+pub struct ComplexEigenPairs {
+    values: Vec<(f64, f64)>,
+    vectors: Vec<(Vec<f64>, Vec<f64>)>,
+    converged: bool,
+}
+
+/// Shift-invert Lanczos solver for symmetric generalized eigenproblems K x = λ M x
+///
+/// The Lanczos process with full reorthogonalization is run on (K - σ M)^-1 M in the M inner
+/// product, (K - σ M) being factorized once by the sparse LU. The eigenvalues closest to the shift
+/// σ (0 by default, giving the smallest ones of a positive definite problem) are found first and
+/// the eigenvectors are M orthonormal. The Krylov space grows up to its maximum dimension (twice
+/// the number of wanted eigenpairs plus 20 by default) until the Ritz residuals fall below the
+/// tolerance (1e-10 by default) relative to the Ritz values.
+pub struct Lanczos {
+    n_eigenpairs: usize,
+    shift: f64,
+    krylov_dimension: Option<usize>,
+    tolerance: f64,
+}
+
+/// Shift-invert Arnoldi solver for general generalized eigenproblems K x = λ M x
+///
+/// The Arnoldi process with modified Gram-Schmidt is run on (K - σ M)^-1 M and the eigenvalues of
+/// the Hessenberg matrix are found by the Francis double shift QR algorithm, the Ritz vectors
+/// following from inverse iteration. Parameters have the same meaning and defaults as for Lanczos
+/// and the eigenvectors have a unit euclidean norm.
+pub struct Arnoldi {
+    n_eigenpairs: usize,
+    shift: f64,
+    krylov_dimension: Option<usize>,
+    tolerance: f64,
+}
+
+impl EigenPairs {
+    /// Number of eigenpairs
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Whether there are no eigenpairs
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Eigenvalues
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// Eigenvector of an eigenpair
+    pub fn vector(&self, pair: usize) -> &[f64] {
+        &self.vectors[pair]
+    }
+
+    /// Whether all the wanted eigenpairs met the tolerance
+    pub fn converged(&self) -> bool {
+        self.converged
+    }
+}
+
+impl ComplexEigenPairs {
+    /// Number of eigenpairs
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Whether there are no eigenpairs
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Eigenvalues as (real, imaginary) parts
+    pub fn values(&self) -> &[(f64, f64)] {
+        &self.values
+    }
+
+    /// Real and imaginary parts of the eigenvector of an eigenpair
+    pub fn vector(&self, pair: usize) -> (&[f64], &[f64]) {
+        let (real, imaginary) = &self.vectors[pair];
+        (real, imaginary)
+    }
+
+    /// Whether all the wanted eigenpairs met the tolerance
+    pub fn converged(&self) -> bool {
+        self.converged
+    }
+}
+
+impl Lanczos {
+    /// Solver for the given number of eigenpairs
+    pub fn new(n_eigenpairs: usize) -> Self {
+        Lanczos {
+            n_eigenpairs,
+            shift: 0.0,
+            krylov_dimension: None,
+            tolerance: 1e-10,
+        }
+    }
+
+    /// Set the shift the wanted eigenvalues are closest to
+    pub fn with_shift(mut self, shift: f64) -> Self {
+        self.shift = shift;
+        self
+    }
+
+    /// Set the maximum dimension of the Krylov space
+    pub fn with_krylov_dimension(mut self, dimension: usize) -> Self {
+        self.krylov_dimension = Some(dimension);
+        self
+    }
+
+    /// Set the tolerance on the Ritz residuals relative to the Ritz values
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Solve K x = λ M x, the mass matrix being the identity when it is not given
+    pub fn solve(&self, stiffness: &SparseCSR<f64>, mass: Option<&SparseCSR<f64>>) -> EigenPairs {
+        let n = stiffness.n_rows();
+        let (lu, mass) = shift_invert(stiffness, mass, self.shift);
+        let max_dimension = krylov_dimension(self.krylov_dimension, self.n_eigenpairs, n);
+        let mut basis: Vec<Vec<f64>> = Vec::new();
+        let mut mass_basis: Vec<Vec<f64>> = Vec::new();
+        let mut alphas: Vec<f64> = Vec::new();
+        let mut betas: Vec<f64> = Vec::new();
+        let mut q = start_vector(n);
+        let mut mq = vec![0.0; n];
+        mass.apply(&q, &mut mq);
+        let scale = dot(&q, &mq).sqrt();
+        q.iter_mut().for_each(|v| *v /= scale);
+        mq.iter_mut().for_each(|v| *v /= scale);
+        let mut w = vec![0.0; n];
+        let mut mw = vec![0.0; n];
+        let mut ritz = (Vec::new(), Vec::new());
+        let mut converged = false;
+        while basis.len() < max_dimension {
+            lu.solve(&mq, &mut w);
+            basis.push(q.clone());
+            mass_basis.push(mq.clone());
+            // Full reorthogonalization done twice keeps the basis M orthonormal
+            alphas.push(dot(&mq, &w));
+            for _ in 0..2 {
+                for (v, mv) in basis.iter().zip(&mass_basis) {
+                    let projection = dot(mv, &w);
+                    w.iter_mut().zip(v).for_each(|(w, v)| *w -= projection * v);
+                }
+            }
+            mass.apply(&w, &mut mw);
+            let beta = dot(&w, &mw).max(0.0).sqrt();
+            let m = alphas.len();
+            let mut tridiagonal = vec![0.0; m * m];
+            for i in 0..m {
+                tridiagonal[i * m + i] = alphas[i];
+                if i + 1 < m {
+                    tridiagonal[i * m + i + 1] = betas[i];
+                    tridiagonal[(i + 1) * m + i] = betas[i];
+                }
+            }
+            let (values, vectors) = symmetric_eigen(m, tridiagonal);
+            let mut order: Vec<usize> = (0..m).collect();
+            order.sort_by(|i, j| values[*j].abs().total_cmp(&values[*i].abs()));
+            order.truncate(self.n_eigenpairs);
+            let breakdown = beta <= 1e-14 * alphas.iter().fold(0.0, |a: f64, b| a.max(b.abs()));
+            converged = order.len() == self.n_eigenpairs.min(n)
+                && order.iter().all(|i| {
+                    (beta * vectors[(m - 1) * m + i]).abs() <= self.tolerance * values[*i].abs()
+                });
+            ritz = (
+                order.iter().map(|i| values[*i]).collect(),
+                order
+                    .iter()
+                    .map(|i| (0..m).map(|k| vectors[k * m + i]).collect::<Vec<f64>>())
+                    .collect(),
+            );
+            if converged || breakdown {
+                converged = converged || breakdown;
+                break;
+            }
+            betas.push(beta);
+            q.iter_mut().zip(&w).for_each(|(q, w)| *q = w / beta);
+            mq.iter_mut().zip(&mw).for_each(|(q, w)| *q = w / beta);
+        }
+        let (thetas, coefficients) = ritz;
+        EigenPairs {
+            values: thetas
+                .iter()
+                .map(|theta| self.shift + 1.0 / theta)
+                .collect(),
+            vectors: coefficients.iter().map(|y| combine(&basis, y)).collect(),
+            converged,
+        }
+    }
+}
+
+impl Arnoldi {
+    /// Solver for the given number of eigenpairs
+    pub fn new(n_eigenpairs: usize) -> Self {
+        Arnoldi {
+            n_eigenpairs,
+            shift: 0.0,
+            krylov_dimension: None,
+            tolerance: 1e-10,
+        }
+    }
+
+    /// Set the shift the wanted eigenvalues are closest to
+    pub fn with_shift(mut self, shift: f64) -> Self {
+        self.shift = shift;
+        self
+    }
+
+    /// Set the maximum dimension of the Krylov space
+    pub fn with_krylov_dimension(mut self, dimension: usize) -> Self {
+        self.krylov_dimension = Some(dimension);
+        self
+    }
+
+    /// Set the tolerance on the Ritz residuals relative to the Ritz values
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Solve K x = λ M x, the mass matrix being the identity when it is not given
+    pub fn solve(
+        &self,
+        stiffness: &SparseCSR<f64>,
+        mass: Option<&SparseCSR<f64>>,
+    ) -> ComplexEigenPairs {
+        let n = stiffness.n_rows();
+        let (lu, mass) = shift_invert(stiffness, mass, self.shift);
+        let max_dimension = krylov_dimension(self.krylov_dimension, self.n_eigenpairs, n);
+        let mut q = start_vector(n);
+        let scale = norm(&q);
+        q.iter_mut().for_each(|v| *v /= scale);
+        let mut basis = vec![q];
+        // Columns of the Hessenberg matrix
+        let mut columns: Vec<Vec<f64>> = Vec::new();
+        let mut mq = vec![0.0; n];
+        let mut w = vec![0.0; n];
+        let mut ritz = (Vec::new(), Vec::new());
+        let mut converged = false;
+        while columns.len() < max_dimension {
+            mass.apply(basis.last().unwrap(), &mut mq);
+            lu.solve(&mq, &mut w);
+            let mut column = Vec::with_capacity(basis.len() + 1);
+            for v in basis.iter() {
+                let projection = dot(v, &w);
+                w.iter_mut().zip(v).for_each(|(w, v)| *w -= projection * v);
+                column.push(projection);
+            }
+            let h = norm(&w);
+            column.push(h);
+            columns.push(column);
+            let m = columns.len();
+            let mut hessenberg = vec![vec![0.0; m]; m];
+            for (j, column) in columns.iter().enumerate() {
+                for (i, value) in column.iter().enumerate().take(m) {
+                    hessenberg[i][j] = *value;
+                }
+            }
+            let mut order: Vec<(f64, f64)> = hessenberg_eigenvalues(hessenberg.clone());
+            order.sort_by(|a, b| a.0.hypot(a.1).total_cmp(&b.0.hypot(b.1)).reverse());
+            order.truncate(self.n_eigenpairs);
+            let vectors: Vec<(Vec<f64>, Vec<f64>)> = order
+                .iter()
+                .map(|mu| hessenberg_eigenvector(&hessenberg, *mu))
+                .collect();
+            let breakdown = h <= 1e-14 * columns[0][0].abs().max(f64::MIN_POSITIVE);
+            converged = order.len() == self.n_eigenpairs.min(n)
+                && order.iter().zip(&vectors).all(|(mu, (real, imaginary))| {
+                    h * real[m - 1].hypot(imaginary[m - 1]) <= self.tolerance * mu.0.hypot(mu.1)
+                });
+            ritz = (order, vectors);
+            if converged || breakdown {
+                converged = converged || breakdown;
+                break;
+            }
+            basis.push(w.iter().map(|w| w / h).collect());
+        }
+        let (mus, coefficients) = ritz;
+        ComplexEigenPairs {
+            values: mus
+                .iter()
+                .map(|(a, b)| {
+                    let modulus = a * a + b * b;
+                    (self.shift + a / modulus, -b / modulus)
+                })
+                .collect(),
+            vectors: coefficients
+                .iter()
+                .map(|(real, imaginary)| {
+                    let (mut real, mut imaginary) =
+                        (combine(&basis, real), combine(&basis, imaginary));
+                    let scale = norm(&real).hypot(norm(&imaginary));
+                    real.iter_mut().for_each(|v| *v /= scale);
+                    imaginary.iter_mut().for_each(|v| *v /= scale);
+                    (real, imaginary)
+                })
+                .collect(),
+            converged,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Factorization of K - σ M and the mass matrix (the identity if none is given)
+fn shift_invert(
+    stiffness: &SparseCSR<f64>,
+    mass: Option<&SparseCSR<f64>>,
+    shift: f64,
+) -> (SparseLU, SparseCSR<f64>) {
+    let n = stiffness.n_rows();
+    let mass = mass
+        .cloned()
+        .unwrap_or_else(|| SparseCSR::new(n, (0..=n).collect(), (0..n).collect(), vec![1.0; n]));
+    let lu = SparseLU::new(&stiffness.add(-shift, &mass))
+        .expect("Shifted matrix is singular, the shift is an eigenvalue");
+    (lu, mass)
+}
+
+fn krylov_dimension(dimension: Option<usize>, n_eigenpairs: usize, n: usize) -> usize {
+    dimension.unwrap_or(2 * n_eigenpairs + 20).min(n).max(1)
+}
+
+// Deterministic start vector with no particular structure
+fn start_vector(n: usize) -> Vec<f64> {
+    (0..n)
+        .map(|i| 0.5 + ((i as f64 + 1.0) * 0.618_033_988_749_895).fract())
+        .collect()
+}
+
+fn combine(basis: &[Vec<f64>], coefficients: &[f64]) -> Vec<f64> {
+    let mut vector = vec![0.0; basis[0].len()];
+    for (v, c) in basis.iter().zip(coefficients) {
+        vector.iter_mut().zip(v).for_each(|(x, v)| *x += c * v);
+    }
+    vector
+}
+
+// Eigenvalues and row first eigenvectors (as columns) of a small symmetric matrix by cyclic Jacobi
+pub(crate) fn symmetric_eigen(n: usize, mut a: Vec<f64>) -> (Vec<f64>, Vec<f64>) {
+    let mut v = vec![0.0; n * n];
+    for i in 0..n {
+        v[i * n + i] = 1.0;
+    }
+    let scale: f64 = a.iter().map(|x| x * x).sum();
+    for _ in 0..100 {
+        let off: f64 = (0..n)
+            .flat_map(|p| ((p + 1)..n).map(move |q| (p, q)))
+            .map(|(p, q)| a[p * n + q].powi(2))
+            .sum();
+        if off <= 1e-32 * scale {
+            break;
+        }
+        for p in 0..n {
+            for q in (p + 1)..n {
+                let apq = a[p * n + q];
+                if apq == 0.0 {
+                    continue;
+                }
+                let theta = (a[q * n + q] - a[p * n + p]) / (2.0 * apq);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for k in 0..n {
+                    let (akp, akq) = (a[k * n + p], a[k * n + q]);
+                    a[k * n + p] = c * akp - s * akq;
+                    a[k * n + q] = s * akp + c * akq;
+                }
+                for k in 0..n {
+                    let (apk, aqk) = (a[p * n + k], a[q * n + k]);
+                    a[p * n + k] = c * apk - s * aqk;
+                    a[q * n + k] = s * apk + c * aqk;
+                }
+                for k in 0..n {
+                    let (vkp, vkq) = (v[k * n + p], v[k * n + q]);
+                    v[k * n + p] = c * vkp - s * vkq;
+                    v[k * n + q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+    ((0..n).map(|i| a[i * n + i]).collect(), v)
+}
+
+// Eigenvalues of an upper Hessenberg matrix by the Francis double shift QR algorithm
+fn hessenberg_eigenvalues(mut a: Vec<Vec<f64>>) -> Vec<(f64, f64)> {
+    let n = a.len() as isize;
+    let at = |i: isize| i as usize;
+    let mut values = vec![(0.0, 0.0); n as usize];
+    let mut norm = 0.0;
+    for i in 0..n {
+        for j in (i - 1).max(0)..n {
+            norm += a[at(i)][at(j)].abs();
+        }
+    }
+    let mut nn = n - 1;
+    let mut t = 0.0;
+    while nn >= 0 {
+        let mut iterations = 0;
+        loop {
+            // Look for a single small subdiagonal element
+            let mut l = nn;
+            while l > 0 {
+                let mut s = a[at(l - 1)][at(l - 1)].abs() + a[at(l)][at(l)].abs();
+                if s == 0.0 {
+                    s = norm;
+                }
+                if a[at(l)][at(l - 1)].abs() + s == s {
+                    a[at(l)][at(l - 1)] = 0.0;
+                    break;
+                }
+                l -= 1;
+            }
+            let mut x = a[at(nn)][at(nn)];
+            if l == nn {
+                values[at(nn)] = (x + t, 0.0);
+                nn -= 1;
+            } else {
+                let mut y = a[at(nn - 1)][at(nn - 1)];
+                let mut w = a[at(nn)][at(nn - 1)] * a[at(nn - 1)][at(nn)];
+                if l == nn - 1 {
+                    let p = 0.5 * (y - x);
+                    let q = p * p + w;
+                    let z = q.abs().sqrt();
+                    x += t;
+                    if q >= 0.0 {
+                        let z = p + z.copysign(p);
+                        let second = if z != 0.0 { x - w / z } else { x + z };
+                        values[at(nn - 1)] = (x + z, 0.0);
+                        values[at(nn)] = (second, 0.0);
+                    } else {
+                        values[at(nn - 1)] = (x + p, z);
+                        values[at(nn)] = (x + p, -z);
+                    }
+                    nn -= 2;
+                } else {
+                    assert!(iterations < 60, "QR algorithm did not converge");
+                    if iterations == 10 || iterations == 20 {
+                        // Exceptional shift
+                        t += x;
+                        for i in 0..=nn {
+                            a[at(i)][at(i)] -= x;
+                        }
+                        let s = a[at(nn)][at(nn - 1)].abs() + a[at(nn - 1)][at(nn - 2)].abs();
+                        x = 0.75 * s;
+                        y = x;
+                        w = -0.4375 * s * s;
+                    }
+                    iterations += 1;
+                    let (mut p, mut q, mut r) = (0.0, 0.0, 0.0);
+                    let mut m = nn - 2;
+                    while m >= l {
+                        let z = a[at(m)][at(m)];
+                        let rr = x - z;
+                        let ss = y - z;
+                        p = (rr * ss - w) / a[at(m + 1)][at(m)] + a[at(m)][at(m + 1)];
+                        q = a[at(m + 1)][at(m + 1)] - z - rr - ss;
+                        r = a[at(m + 2)][at(m + 1)];
+                        let s = p.abs() + q.abs() + r.abs();
+                        p /= s;
+                        q /= s;
+                        r /= s;
+                        if m == l {
+                            break;
+                        }
+                        let u = a[at(m)][at(m - 1)].abs() * (q.abs() + r.abs());
+                        let v = p.abs()
+                            * (a[at(m - 1)][at(m - 1)].abs()
+                                + z.abs()
+                                + a[at(m + 1)][at(m + 1)].abs());
+                        if u + v == v {
+                            break;
+                        }
+                        m -= 1;
+                    }
+                    for i in (m + 2)..=nn {
+                        a[at(i)][at(i - 2)] = 0.0;
+                        if i != m + 2 {
+                            a[at(i)][at(i - 3)] = 0.0;
+                        }
+                    }
+                    let mut k = m;
+                    while k < nn {
+                        if k != m {
+                            p = a[at(k)][at(k - 1)];
+                            q = a[at(k + 1)][at(k - 1)];
+                            r = if k + 1 != nn {
+                                a[at(k + 2)][at(k - 1)]
+                            } else {
+                                0.0
+                            };
+                            x = p.abs() + q.abs() + r.abs();
+                            if x != 0.0 {
+                                p /= x;
+                                q /= x;
+                                r /= x;
+                            }
+                        }
+                        let s = (p * p + q * q + r * r).sqrt().copysign(p);
+                        if s != 0.0 {
+                            if k == m {
+                                if l != m {
+                                    a[at(k)][at(k - 1)] = -a[at(k)][at(k - 1)];
+                                }
+                            } else {
+                                a[at(k)][at(k - 1)] = -s * x;
+                            }
+                            p += s;
+                            x = p / s;
+                            y = q / s;
+                            let z = r / s;
+                            q /= p;
+                            r /= p;
+                            for j in k..=nn {
+                                let mut p = a[at(k)][at(j)] + q * a[at(k + 1)][at(j)];
+                                if k + 1 != nn {
+                                    p += r * a[at(k + 2)][at(j)];
+                                    a[at(k + 2)][at(j)] -= p * z;
+                                }
+                                a[at(k + 1)][at(j)] -= p * y;
+                                a[at(k)][at(j)] -= p * x;
+                            }
+                            for i in l..=nn.min(k + 3) {
+                                let mut p = x * a[at(i)][at(k)] + y * a[at(i)][at(k + 1)];
+                                if k + 1 != nn {
+                                    p += z * a[at(i)][at(k + 2)];
+                                    a[at(i)][at(k + 2)] -= p * r;
+                                }
+                                a[at(i)][at(k + 1)] -= p * q;
+                                a[at(i)][at(k)] -= p;
+                            }
+                        }
+                        k += 1;
+                    }
+                }
+            }
+            if l + 1 >= nn {
+                break;
+            }
+        }
+    }
+    values
+}
+
+// Eigenvector of a small matrix by two steps of inverse iteration with a slightly perturbed complex
+// shift, solved as a real system of twice the size
+fn hessenberg_eigenvector(matrix: &[Vec<f64>], value: (f64, f64)) -> (Vec<f64>, Vec<f64>) {
+    let m = matrix.len();
+    let scale = matrix
+        .iter()
+        .flatten()
+        .fold(0.0, |s: f64, v| s.max(v.abs()))
+        .max(f64::MIN_POSITIVE);
+    let (a, b) = (value.0 + 1e-10 * scale, value.1);
+    let mut system = DataHold::new(vec![0.0; 4 * m * m], [2 * m, 2 * m]);
+    for (i, row) in matrix.iter().enumerate() {
+        for (j, value) in row.iter().enumerate() {
+            let entry = value - if i == j { a } else { 0.0 };
+            *system.multi_index_mut([i, j]) = entry;
+            *system.multi_index_mut([m + i, m + j]) = entry;
+        }
+        *system.multi_index_mut([i, m + i]) = b;
+        *system.multi_index_mut([m + i, i]) = -b;
+    }
+    let mut y = vec![1.0; 2 * m];
+    if let Some(lu) = LU::new(&system) {
+        let mut next = vec![0.0; 2 * m];
+        for _ in 0..2 {
+            lu.solve(&y, &mut next);
+            let scale = norm(&next);
+            y.iter_mut().zip(&next).for_each(|(y, n)| *y = n / scale);
+        }
+    }
+    let imaginary = y.split_off(m);
+    (y, imaginary)
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tridiagonal matrix with constant diagonals
+    fn build_tridiagonal(n: usize, lower: f64, diagonal: f64, upper: f64) -> SparseCSR<f64> {
+        let mut row_offsets = vec![0];
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+        for i in 0..n {
+            for j in i.saturating_sub(1)..(i + 2).min(n) {
+                col_indices.push(j);
+                values.push(match j as isize - i as isize {
+                    -1 => lower,
+                    0 => diagonal,
+                    _ => upper,
+                });
+            }
+            row_offsets.push(col_indices.len());
+        }
+        SparseCSR::new(n, row_offsets, col_indices, values)
+    }
+
+    #[test]
+    fn test_lanczos() {
+        // Linear finite elements of -u'' = λ u on (0, 1) with Dirichlet conditions
+        let n = 60;
+        let h = 1.0 / (n + 1) as f64;
+        let stiffness = build_tridiagonal(n, -1.0 / h, 2.0 / h, -1.0 / h);
+        let mass = build_tridiagonal(n, h / 6.0, 4.0 * h / 6.0, h / 6.0);
+        let exact = |k: usize| {
+            let c = (k as f64 * std::f64::consts::PI * h).cos();
+            6.0 * (1.0 - c) / (h * h * (2.0 + c))
+        };
+        let pairs = Lanczos::new(4).solve(&stiffness, Some(&mass));
+        assert!(pairs.converged(), "Lanczos did not converge");
+        assert_eq!(pairs.len(), 4, "Wrong number of eigenpairs");
+        let mut kx = vec![0.0; n];
+        let mut mx = vec![0.0; n];
+        for (k, value) in pairs.values().iter().enumerate() {
+            assert!(
+                (value - exact(k + 1)).abs() < 1e-8 * exact(k + 1),
+                "Wrong eigenvalue {} for mode {}",
+                value,
+                k + 1
+            );
+            stiffness.apply(pairs.vector(k), &mut kx);
+            mass.apply(pairs.vector(k), &mut mx);
+            assert!(
+                (dot(pairs.vector(k), &mx) - 1.0).abs() < 1e-10,
+                "Not M normalized"
+            );
+            for (kx, mx) in kx.iter().zip(&mx) {
+                assert!((kx - value * mx).abs() < 1e-6 * value, "Wrong eigenvector");
+            }
+        }
+        // The shift selects the eigenvalue closest to it
+        let shifted = Lanczos::new(1)
+            .with_shift(exact(10) + 1.0)
+            .solve(&stiffness, Some(&mass));
+        assert!(
+            (shifted.values()[0] - exact(10)).abs() < 1e-8 * exact(10),
+            "Wrong shifted eigenvalue"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_arnoldi() {
+        // Non symmetric tridiagonal matrix whose eigenvalues are 4 + 2 sqrt(2) cos(kπ / (n + 1))
+        let n = 40;
+        let matrix = build_tridiagonal(n, -2.0, 4.0, -1.0);
+        let pairs = Arnoldi::new(3).solve(&matrix, None);
+        assert!(pairs.converged(), "Arnoldi did not converge");
+        let mut ax = vec![0.0; n];
+        for (k, (real, imaginary)) in pairs.values().iter().enumerate() {
+            let angle = (n - k) as f64 * std::f64::consts::PI / (n + 1) as f64;
+            let exact = 4.0 + 2.0 * 2.0_f64.sqrt() * angle.cos();
+            assert!((real - exact).abs() < 1e-8, "Wrong eigenvalue {}", real);
+            assert!(imaginary.abs() < 1e-8, "Eigenvalue should be real");
+            let (vector, _) = pairs.vector(k);
+            matrix.apply(vector, &mut ax);
+            for (ax, x) in ax.iter().zip(vector) {
+                assert!((ax - real * x).abs() < 1e-6, "Wrong eigenvector");
+            }
+        }
+        // Rotation blocks have the complex eigenvalues d ± i / 2
+        let blocks = 5;
+        let mut row_offsets = vec![0];
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+        for block in 0..blocks {
+            let d = (block + 1) as f64;
+            for entries in [[d, 0.5], [-0.5, d]] {
+                col_indices.extend([2 * block, 2 * block + 1]);
+                values.extend(entries);
+                row_offsets.push(col_indices.len());
+            }
+        }
+        let rotations = SparseCSR::new(2 * blocks, row_offsets, col_indices, values);
+        let pairs = Arnoldi::new(2).solve(&rotations, None);
+        assert!(pairs.converged(), "Arnoldi did not converge");
+        for (k, (real, imaginary)) in pairs.values().iter().enumerate() {
+            assert!((real - 1.0).abs() < 1e-8, "Wrong real part {}", real);
+            assert!((imaginary.abs() - 0.5).abs() < 1e-8, "Wrong imaginary part");
+            // A (u + i v) = (a + i b) (u + i v)
+            let (u, v) = pairs.vector(k);
+            let (mut au, mut av) = (vec![0.0; 2 * blocks], vec![0.0; 2 * blocks]);
+            rotations.apply(u, &mut au);
+            rotations.apply(v, &mut av);
+            for i in 0..2 * blocks {
+                assert!(
+                    (au[i] - real * u[i] + imaginary * v[i]).abs() < 1e-6,
+                    "Wrong real part of the eigenvector"
+                );
+                assert!(
+                    (av[i] - real * v[i] - imaginary * u[i]).abs() < 1e-6,
+                    "Wrong imaginary part of the eigenvector"
+                );
+            }
+        }
+    }
+}
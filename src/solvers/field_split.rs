@@ -0,0 +1,311 @@
+use super::krylov::{Convergence, Gmres, Identity};
+use super::linear_operator::{LinearOperator, Preconditioner};
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Block factorization of [[A, B], [C, D]] applied by the field split as a preconditioner, S
+/// being the Schur complement D - C A^-1 B
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchurFactorization {
+    /// Inverse of diag(A, S)
+    Diagonal,
+    /// Inverse of [[A, 0], [C, S]]
+    Lower,
+    /// Inverse of [[A, B], [0, S]]
+    Upper,
+    /// Exact inverse [[I, -A^-1 B], [0, I]] diag(A^-1, S^-1) [[I, 0], [-C A^-1, I]]
+    Full,
+}
+
+/// Schur complement S = D - C A^-1 B of the first block of a 2x2 block operator applied with an
+/// approximate inverse of A
+pub struct SchurComplement<'a> {
+    b: &'a dyn LinearOperator,
+    c: &'a dyn LinearOperator,
+    d: Option<&'a dyn LinearOperator>,
+    a_solver: &'a dyn Preconditioner,
+}
+
+/// Field split solver of the 2x2 block systems [[A, B], [C, D]] [u, p] = [f, g] through the Schur
+/// complement S = D - C A^-1 B
+///
+/// The A block is inverted by a user chosen solver (a direct factorization, multigrid, or any
+/// other preconditioner) and the Schur complement, which is never assembled, either by GMRES
+/// preconditioned by a user approximation of its inverse (such as the inverse of a pressure mass
+/// matrix for Stokes) when solving, or by that approximation alone when the field split is itself
+/// used as a preconditioner of the whole system. Vectors are the concatenation of the two fields.
+/// A missing D block is zero, as for saddle point problems. By default the factorization is the
+/// full one, the Schur approximation the identity, and the Schur complement is solved by GMRES
+/// with its default parameters.
+pub struct FieldSplit<'a> {
+    a: &'a dyn LinearOperator,
+    schur: SchurComplement<'a>,
+    schur_preconditioner: Option<&'a dyn Preconditioner>,
+    schur_solver: Gmres,
+    factorization: SchurFactorization,
+}
+
+impl<'a> SchurComplement<'a> {
+    /// Schur complement of the blocks B, C and D (zero if None) with the inverse of A applied by
+    /// a solver
+    pub fn new(
+        b: &'a dyn LinearOperator,
+        c: &'a dyn LinearOperator,
+        d: Option<&'a dyn LinearOperator>,
+        a_solver: &'a dyn Preconditioner,
+    ) -> Self {
+        assert_eq!(
+            b.n_rows(),
+            c.n_cols(),
+            "Off diagonal blocks have incompatible sizes"
+        );
+        assert!(
+            d.is_none_or(|d| d.n_rows() == c.n_rows() && d.n_cols() == b.n_cols()),
+            "Second diagonal block does not match the off diagonal ones"
+        );
+        SchurComplement { b, c, d, a_solver }
+    }
+}
+
+impl LinearOperator for SchurComplement<'_> {
+    fn n_rows(&self) -> usize {
+        self.c.n_rows()
+    }
+
+    fn n_cols(&self) -> usize {
+        self.b.n_cols()
+    }
+
+    fn apply(&self, x: &[f64], y: &mut [f64]) {
+        let n = self.b.n_rows();
+        let mut bx = vec![0.0; n];
+        let mut inverse = vec![0.0; n];
+        self.b.apply(x, &mut bx);
+        self.a_solver.apply(&bx, &mut inverse);
+        self.c.apply(&inverse, y);
+        match self.d {
+            Some(d) => {
+                let mut dx = vec![0.0; y.len()];
+                d.apply(x, &mut dx);
+                y.iter_mut().zip(&dx).for_each(|(y, d)| *y = d - *y);
+            }
+            None => y.iter_mut().for_each(|y| *y = -*y),
+        }
+    }
+}
+
+impl<'a> FieldSplit<'a> {
+    /// Field split of the blocks A, B, C and D (zero if None) with the solver of A
+    pub fn new(
+        a: &'a dyn LinearOperator,
+        b: &'a dyn LinearOperator,
+        c: &'a dyn LinearOperator,
+        d: Option<&'a dyn LinearOperator>,
+        a_solver: &'a dyn Preconditioner,
+    ) -> Self {
+        assert!(
+            a.n_rows() == a.n_cols() && a.n_rows() == b.n_rows() && a.n_cols() == c.n_cols(),
+            "First diagonal block does not match the off diagonal ones"
+        );
+        FieldSplit {
+            a,
+            schur: SchurComplement::new(b, c, d, a_solver),
+            schur_preconditioner: None,
+            schur_solver: Gmres::new(),
+            factorization: SchurFactorization::Full,
+        }
+    }
+
+    /// Set the approximation of the inverse of the Schur complement
+    pub fn with_schur_preconditioner(mut self, preconditioner: &'a dyn Preconditioner) -> Self {
+        self.schur_preconditioner = Some(preconditioner);
+        self
+    }
+
+    /// Set the GMRES solver of the Schur complement used by solve
+    pub fn with_schur_solver(mut self, solver: Gmres) -> Self {
+        self.schur_solver = solver;
+        self
+    }
+
+    /// Set the block factorization applied as a preconditioner
+    pub fn with_factorization(mut self, factorization: SchurFactorization) -> Self {
+        self.factorization = factorization;
+        self
+    }
+
+    /// Size of the first field
+    pub fn first_size(&self) -> usize {
+        self.a.n_rows()
+    }
+
+    /// Size of the second field
+    pub fn second_size(&self) -> usize {
+        self.schur.n_rows()
+    }
+
+    /// Schur complement of the system
+    pub fn schur_complement(&self) -> &SchurComplement<'a> {
+        &self.schur
+    }
+
+    /// Solve the block system by the full factorization, the Schur complement being solved by
+    /// GMRES starting from the second field of x, and return the convergence of that solve
+    ///
+    /// The solution is as accurate as the solver of A is.
+    pub fn solve(&self, rhs: &[f64], x: &mut [f64]) -> Convergence {
+        let n = self.first_size();
+        assert!(
+            rhs.len() == n + self.second_size() && x.len() == rhs.len(),
+            "Vectors do not match the size of the block system"
+        );
+        let (f, g) = rhs.split_at(n);
+        let (u, p) = x.split_at_mut(n);
+        // S p = g - C A^-1 f
+        self.schur.a_solver.apply(f, u);
+        let mut schur_rhs = vec![0.0; g.len()];
+        self.schur.c.apply(u, &mut schur_rhs);
+        schur_rhs.iter_mut().zip(g).for_each(|(r, g)| *r = g - *r);
+        let convergence = match self.schur_preconditioner {
+            Some(preconditioner) => {
+                self.schur_solver
+                    .solve_preconditioned(&self.schur, &preconditioner, &schur_rhs, p)
+            }
+            None => self.schur_solver.solve(&self.schur, &schur_rhs, p),
+        };
+        self.back_substitute(f, p, u);
+        convergence
+    }
+
+    // Solve A u = f - B p
+    fn back_substitute(&self, f: &[f64], p: &[f64], u: &mut [f64]) {
+        let mut first_rhs = vec![0.0; f.len()];
+        self.schur.b.apply(p, &mut first_rhs);
+        first_rhs.iter_mut().zip(f).for_each(|(r, f)| *r = f - *r);
+        self.schur.a_solver.apply(&first_rhs, u);
+    }
+
+    // Apply the approximate inverse of the Schur complement
+    fn apply_schur_inverse(&self, r: &[f64], z: &mut [f64]) {
+        match self.schur_preconditioner {
+            Some(preconditioner) => preconditioner.apply(r, z),
+            None => Identity.apply(r, z),
+        }
+    }
+}
+
+impl Preconditioner for FieldSplit<'_> {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        let n = self.first_size();
+        let (f, g) = r.split_at(n);
+        let (u, p) = z.split_at_mut(n);
+        let mut second_rhs = vec![0.0; g.len()];
+        match self.factorization {
+            SchurFactorization::Diagonal => {
+                self.schur.a_solver.apply(f, u);
+                self.apply_schur_inverse(g, p);
+            }
+            SchurFactorization::Upper => {
+                self.apply_schur_inverse(g, p);
+                self.back_substitute(f, p, u);
+            }
+            SchurFactorization::Lower | SchurFactorization::Full => {
+                self.schur.a_solver.apply(f, u);
+                self.schur.c.apply(u, &mut second_rhs);
+                second_rhs.iter_mut().zip(g).for_each(|(r, g)| *r = g - *r);
+                self.apply_schur_inverse(&second_rhs, p);
+                if self.factorization == SchurFactorization::Full {
+                    self.back_substitute(f, p, u);
+                }
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::sparse_csr::SparseCSR;
+    use crate::solvers::linear_operator::BlockOperator;
+    use crate::solvers::sparse_direct::SparseLU;
+
+    #[test]
+    fn test_field_split() {
+        // Saddle point system with a 1D laplacian and a discrete divergence of neighbour differences
+        let (n, m): (usize, usize) = (8, 4);
+        let mut offsets = vec![0];
+        let (mut cols, mut values) = (Vec::new(), Vec::new());
+        for i in 0..n {
+            for j in i.saturating_sub(1)..(i + 2).min(n) {
+                cols.push(j);
+                values.push(if i == j { 2.0 } else { -1.0 });
+            }
+            offsets.push(cols.len());
+        }
+        let a = SparseCSR::new(n, offsets, cols, values);
+        let divergence = SparseCSR::new(
+            n,
+            (0..=m).map(|i| 2 * i).collect(),
+            (0..m).flat_map(|i| [2 * i, 2 * i + 1]).collect(),
+            (0..m).flat_map(|_| [1.0, -1.0]).collect(),
+        );
+        let gradient = divergence.transpose();
+        let a_solver = SparseLU::new(&a).unwrap();
+        let system = BlockOperator::new(&[n, m], &[n, m])
+            .with_block(0, 0, &a)
+            .with_block(0, 1, &gradient)
+            .with_block(1, 0, &divergence);
+        let expected: Vec<f64> = (0..n + m).map(|i| (i as f64).sin()).collect();
+        let mut rhs = vec![0.0; n + m];
+        system.apply(&expected, &mut rhs);
+        let split = FieldSplit::new(&a, &gradient, &divergence, None, &a_solver);
+        let mut x = vec![0.0; n + m];
+        let convergence = split.solve(&rhs, &mut x);
+        assert!(convergence.converged(), "Schur complement solve failed");
+        for (x, e) in x.iter().zip(&expected) {
+            assert!((x - e).abs() < 1e-8, "Wrong solution of the block system");
+        }
+        // Exact Schur complement inverse from its assembled columns
+        let mut schur_values = vec![0.0; m * m];
+        let mut column = vec![0.0; m];
+        for j in 0..m {
+            let mut unit = vec![0.0; m];
+            unit[j] = 1.0;
+            split.schur_complement().apply(&unit, &mut column);
+            (0..m).for_each(|i| schur_values[i * m + j] = column[i]);
+        }
+        let schur = SparseCSR::new(
+            m,
+            (0..=m).map(|i| i * m).collect(),
+            (0..m * m).map(|k| k % m).collect(),
+            schur_values,
+        );
+        let schur_solver = SparseLU::new(&schur).unwrap();
+        // Known bounds on the preconditioned GMRES iterations with exact blocks
+        for (factorization, bound) in [
+            (SchurFactorization::Diagonal, 3),
+            (SchurFactorization::Lower, 2),
+            (SchurFactorization::Upper, 2),
+            (SchurFactorization::Full, 1),
+        ] {
+            let preconditioner = FieldSplit::new(&a, &gradient, &divergence, None, &a_solver)
+                .with_schur_preconditioner(&schur_solver)
+                .with_factorization(factorization);
+            let mut x = vec![0.0; n + m];
+            let convergence =
+                Gmres::new().solve_preconditioned(&system, &preconditioner, &rhs, &mut x);
+            assert!(
+                convergence.converged() && convergence.iterations() <= bound,
+                "{:?} factorization needs {} iterations",
+                factorization,
+                convergence.iterations()
+            );
+        }
+    }
+}
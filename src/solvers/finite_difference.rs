@@ -0,0 +1,381 @@
+use super::krylov::{distance, norm, Convergence, Gmres, Identity};
+use super::linear_operator::{LinearOperator, Preconditioner};
+use super::monitor::{Monitor, Recorder};
+use super::nonlinear::{line_search, NonlinearProblem};
+use super::stopping_criterion::{StopReason, StoppingCriterion};
+use crate::core::arrays::sparse_csr::SparseCSR;
+use std::rc::Rc;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Finite difference approximation of a sparse jacobian by perturbing groups of columns at once
+///
+/// The columns of the sparsity pattern are colored greedily so that two columns sharing a row
+/// never have the same color. The columns of one color are then perturbed together and the
+/// jacobian costs one residual evaluation per color, which is a small number (3 for tridiagonal
+/// patterns) independent of the size for meshes. Each column is perturbed by the square root of
+/// the machine precision relative to the magnitude of its unknown, giving about half the digits
+/// of the exact jacobian.
+pub struct FiniteDifferenceJacobian {
+    pattern: SparseCSR<f64>,
+    colors: Vec<usize>,
+    n_colors: usize,
+}
+
+/// Nonlinear problem defined by its residual alone, the jacobian being approximated by colored
+/// finite differences on a sparsity pattern
+pub struct FiniteDifferenceProblem<Residual> {
+    residual: Residual,
+    jacobian: FiniteDifferenceJacobian,
+}
+
+/// Jacobian-free Newton-Krylov solver of F(x) = 0 needing only the residual
+///
+/// The Newton systems J s = -F are solved inexactly by GMRES whose products with the jacobian are
+/// the directional finite differences (F(x + ε v) - F(x)) / ε, followed by a backtracking line
+/// search. An optional preconditioner, for instance the factorization of an approximate or
+/// lagged jacobian, is applied inside GMRES. The stopping criterion and its defaults are the ones
+/// of Newton and the GMRES solves stop at 1e-4 relative to the residual by default.
+pub struct JacobianFreeNewton {
+    stopping: StoppingCriterion,
+    gmres: Gmres,
+    monitor: Option<Rc<dyn Monitor>>,
+}
+
+// Jacobian at x applied by directional finite differences of the residual
+struct DirectionalDerivative<'a, Residual> {
+    residual: &'a Residual,
+    x: &'a [f64],
+    r: &'a [f64],
+}
+
+impl FiniteDifferenceJacobian {
+    /// Coloring of the columns of a square sparsity pattern, whose values are ignored
+    pub fn new(pattern: &SparseCSR<f64>) -> Self {
+        let n = pattern.n_rows();
+        assert_eq!(n, pattern.n_cols(), "Jacobian pattern should be square");
+        let columns = pattern.transpose();
+        let mut colors = vec![usize::MAX; n];
+        let mut marks = vec![usize::MAX; n];
+        let mut n_colors = 0;
+        for j in 0..n {
+            // Mark the colors of the columns sharing a row with j
+            for i in columns.row(j).0 {
+                for k in pattern.row(*i).0 {
+                    if colors[*k] != usize::MAX {
+                        marks[colors[*k]] = j;
+                    }
+                }
+            }
+            colors[j] = (0..n).find(|c| marks[*c] != j).unwrap();
+            n_colors = n_colors.max(colors[j] + 1);
+        }
+        FiniteDifferenceJacobian {
+            pattern: pattern.clone(),
+            colors,
+            n_colors,
+        }
+    }
+
+    /// Number of colors, which is the number of residual evaluations of a jacobian
+    pub fn n_colors(&self) -> usize {
+        self.n_colors
+    }
+
+    /// Color of every column
+    pub fn colors(&self) -> &[usize] {
+        &self.colors
+    }
+
+    /// Approximate jacobian of the residual at x
+    pub fn evaluate<Residual>(&self, residual: Residual, x: &[f64]) -> SparseCSR<f64>
+    where
+        Residual: Fn(&[f64], &mut [f64]),
+    {
+        let n = self.colors.len();
+        assert_eq!(x.len(), n, "Vector does not match the size of the jacobian");
+        let steps: Vec<f64> = x
+            .iter()
+            .map(|x| f64::EPSILON.sqrt() * x.abs().max(1.0))
+            .collect();
+        let mut r = vec![0.0; n];
+        residual(x, &mut r);
+        let mut perturbed = x.to_vec();
+        let mut perturbed_r = vec![0.0; n];
+        let mut jacobian = self.pattern.clone();
+        for color in 0..self.n_colors {
+            for j in (0..n).filter(|j| self.colors[*j] == color) {
+                perturbed[j] = x[j] + steps[j];
+            }
+            residual(&perturbed, &mut perturbed_r);
+            for i in 0..n {
+                let start = jacobian.row_offsets()[i];
+                let end = jacobian.row_offsets()[i + 1];
+                for position in start..end {
+                    let j = jacobian.col_indices()[position];
+                    if self.colors[j] == color {
+                        jacobian.values_mut()[position] = (perturbed_r[i] - r[i]) / steps[j];
+                    }
+                }
+            }
+            perturbed.copy_from_slice(x);
+        }
+        jacobian
+    }
+}
+
+impl<Residual> FiniteDifferenceProblem<Residual>
+where
+    Residual: Fn(&[f64], &mut [f64]),
+{
+    /// Problem computing r = F(x) with the residual closure, the jacobian having the sparsity
+    /// pattern given
+    pub fn new(pattern: &SparseCSR<f64>, residual: Residual) -> Self {
+        FiniteDifferenceProblem {
+            residual,
+            jacobian: FiniteDifferenceJacobian::new(pattern),
+        }
+    }
+
+    /// Finite difference approximation of the jacobian
+    pub fn finite_difference_jacobian(&self) -> &FiniteDifferenceJacobian {
+        &self.jacobian
+    }
+}
+
+impl<Residual> NonlinearProblem for FiniteDifferenceProblem<Residual>
+where
+    Residual: Fn(&[f64], &mut [f64]),
+{
+    fn size(&self) -> usize {
+        self.jacobian.colors.len()
+    }
+
+    fn residual(&self, x: &[f64], r: &mut [f64]) {
+        (self.residual)(x, r);
+    }
+
+    fn jacobian(&self, x: &[f64]) -> SparseCSR<f64> {
+        self.jacobian.evaluate(&self.residual, x)
+    }
+}
+
+impl Default for JacobianFreeNewton {
+    fn default() -> Self {
+        JacobianFreeNewton {
+            stopping: StoppingCriterion::new(1e-10, 1e-14, 50),
+            gmres: Gmres::new().with_relative_tolerance(1e-4),
+            monitor: None,
+        }
+    }
+}
+
+impl JacobianFreeNewton {
+    /// Solver with the default parameters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the tolerance on the residual relative to the initial one
+    pub fn with_relative_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_relative_tolerance(tolerance);
+        self
+    }
+
+    /// Set the tolerance on the norm of the residual
+    pub fn with_absolute_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_absolute_tolerance(tolerance);
+        self
+    }
+
+    /// Set the maximum number of Newton iterations
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.stopping = self.stopping.with_max_iterations(max_iterations);
+        self
+    }
+
+    /// Replace the whole stopping criterion, the reference norm being the one of the initial
+    /// residual
+    pub fn with_stopping_criterion(mut self, stopping: StoppingCriterion) -> Self {
+        self.stopping = stopping;
+        self
+    }
+
+    /// Set the GMRES solver of the Newton systems, whose relative tolerance is the forcing term
+    /// of the inexact Newton iterations
+    pub fn with_gmres(mut self, gmres: Gmres) -> Self {
+        self.gmres = gmres;
+        self
+    }
+
+    /// Set a monitor observing the residual norm of every Newton iteration
+    pub fn with_monitor<M: Monitor + 'static>(mut self, monitor: M) -> Self {
+        self.monitor = Some(Rc::new(monitor));
+        self
+    }
+
+    /// Solve F(x) = 0 starting from the values in x, the residual closure computing r = F(x)
+    pub fn solve<Residual>(&self, residual: Residual, x: &mut [f64]) -> Convergence
+    where
+        Residual: Fn(&[f64], &mut [f64]),
+    {
+        self.solve_preconditioned(residual, &Identity, x)
+    }
+
+    /// Solve F(x) = 0 starting from the values in x with a preconditioner of the jacobian
+    pub fn solve_preconditioned<Residual, Precond>(
+        &self,
+        residual: Residual,
+        preconditioner: &Precond,
+        x: &mut [f64],
+    ) -> Convergence
+    where
+        Residual: Fn(&[f64], &mut [f64]),
+        Precond: Preconditioner,
+    {
+        let n = x.len();
+        let mut r = vec![0.0; n];
+        residual(x, &mut r);
+        let mut residual_norm = norm(&r);
+        let mut test = self.stopping.start(residual_norm);
+        let mut rhs = vec![0.0; n];
+        let mut step = vec![0.0; n];
+        let mut trial = vec![0.0; n];
+        let mut trial_r = vec![0.0; n];
+        let mut previous = x.to_vec();
+        let mut iterations = 0;
+        let mut update = None;
+        let recorder = Recorder::start(&self.monitor);
+        let reason = loop {
+            recorder.record(iterations, residual_norm);
+            if let Some(reason) = test.check(iterations, residual_norm, update) {
+                break reason;
+            }
+            iterations += 1;
+            rhs.iter_mut().zip(&r).for_each(|(b, r)| *b = -r);
+            step.iter_mut().for_each(|s| *s = 0.0);
+            let jacobian = DirectionalDerivative {
+                residual: &residual,
+                x,
+                r: &r,
+            };
+            self.gmres
+                .solve_preconditioned(&jacobian, preconditioner, &rhs, &mut step);
+            previous.copy_from_slice(x);
+            if !line_search(&residual, x, &mut r, &step, &mut trial, &mut trial_r) {
+                break StopReason::Breakdown;
+            }
+            residual_norm = norm(&r);
+            update = Some((distance(x, &previous), norm(x)));
+        };
+        Convergence {
+            reason,
+            iterations,
+            residual_norm,
+            spectrum: None,
+        }
+    }
+}
+
+impl<Residual> LinearOperator for DirectionalDerivative<'_, Residual>
+where
+    Residual: Fn(&[f64], &mut [f64]),
+{
+    fn n_rows(&self) -> usize {
+        self.x.len()
+    }
+
+    fn n_cols(&self) -> usize {
+        self.x.len()
+    }
+
+    fn apply(&self, v: &[f64], y: &mut [f64]) {
+        let length = norm(v);
+        if length == 0.0 {
+            y.iter_mut().for_each(|y| *y = 0.0);
+            return;
+        }
+        let epsilon = f64::EPSILON.sqrt() * (1.0 + norm(self.x)) / length;
+        let perturbed: Vec<f64> = self.x.iter().zip(v).map(|(x, v)| x + epsilon * v).collect();
+        (self.residual)(&perturbed, y);
+        y.iter_mut()
+            .zip(self.r)
+            .for_each(|(y, r)| *y = (*y - r) / epsilon);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solvers::nonlinear::Newton;
+    use crate::solvers::sparse_direct::SparseLU;
+
+    #[test]
+    fn test_finite_difference_newton() {
+        // Discrete Bratu problem -u'' = exp(u) on (0, 1) with homogeneous Dirichlet conditions
+        let n: usize = 20;
+        let h2 = 1.0 / ((n + 1) * (n + 1)) as f64;
+        let bratu = |u: &[f64], r: &mut [f64]| {
+            for i in 0..n {
+                let left = if i > 0 { u[i - 1] } else { 0.0 };
+                let right = if i + 1 < n { u[i + 1] } else { 0.0 };
+                r[i] = 2.0 * u[i] - left - right - h2 * u[i].exp();
+            }
+        };
+        let mut offsets = vec![0];
+        let mut cols = Vec::new();
+        for i in 0..n {
+            cols.extend(i.saturating_sub(1)..(i + 2).min(n));
+            offsets.push(cols.len());
+        }
+        let n_entries = cols.len();
+        let pattern = SparseCSR::new(n, offsets, cols, vec![0.0; n_entries]);
+        let problem = FiniteDifferenceProblem::new(&pattern, bratu);
+        assert_eq!(
+            problem.finite_difference_jacobian().n_colors(),
+            3,
+            "Tridiagonal patterns need 3 colors"
+        );
+        let x0: Vec<f64> = (0..n).map(|i| 0.1 * (i as f64).sin()).collect();
+        let jacobian = problem.jacobian(&x0);
+        for (i, x) in x0.iter().enumerate() {
+            for j in i.saturating_sub(1)..(i + 2).min(n) {
+                let exact = if i == j { 2.0 - h2 * x.exp() } else { -1.0 };
+                assert!(
+                    (jacobian.get(i, j).unwrap() - exact).abs() < 1e-6,
+                    "Wrong jacobian entry ({}, {})",
+                    i,
+                    j
+                );
+            }
+        }
+        let mut expected = vec![0.0; n];
+        let newton = Newton::new().solve(&problem, &mut expected);
+        assert!(
+            newton.converged(),
+            "Finite difference Newton did not converge"
+        );
+        // Jacobian-free solves without and with a lagged jacobian preconditioner
+        let lagged = SparseLU::new(&problem.jacobian(&vec![0.0; n])).unwrap();
+        let mut x = vec![0.0; n];
+        let plain = JacobianFreeNewton::new().solve(bratu, &mut x);
+        let mut y = vec![0.0; n];
+        let preconditioned = JacobianFreeNewton::new().solve_preconditioned(bratu, &lagged, &mut y);
+        for (convergence, x) in [(plain, &x), (preconditioned, &y)] {
+            assert!(convergence.converged(), "JFNK did not converge");
+            assert!(
+                convergence.iterations() <= 8,
+                "JFNK needs too many iterations"
+            );
+            for (x, e) in x.iter().zip(&expected) {
+                assert!((x - e).abs() < 1e-8, "Wrong JFNK solution");
+            }
+        }
+    }
+}
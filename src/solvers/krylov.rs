@@ -0,0 +1,832 @@
+use super::conditioning::{conjugate_gradient_spectrum, SpectrumEstimate};
+use super::linear_operator::{LinearOperator, Preconditioner};
+use super::monitor::{Monitor, Recorder};
+use super::stopping_criterion::{StopReason, StoppingCriterion};
+use crate::core::arrays::sparse_csr::SparseCSR;
+#[cfg(feature = "parallel")]
+use crate::parallel;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::rc::Rc;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Preconditioner by the inverse of the diagonal of a matrix
+pub struct Jacobi {
+    inverse_diagonal: Vec<f64>,
+}
+
+/// Preconditioner by the incomplete LU factorization of a matrix without fill in, ILU(0)
+///
+/// The unit lower and upper factors keep the sparsity pattern of the matrix, the updates of the
+/// elimination falling outside of it being dropped. The factorization is exact for banded
+/// matrices without gaps in their bands such as tridiagonal ones.
+pub struct IncompleteLU {
+    factors: SparseCSR<f64>,
+    diagonal: Vec<usize>,
+}
+
+/// Outcome of an iterative solve
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Convergence {
+    pub(crate) reason: StopReason,
+    pub(crate) iterations: usize,
+    pub(crate) residual_norm: f64,
+    pub(crate) spectrum: Option<SpectrumEstimate>,
+}
+
+/// Conjugate gradient solver for symmetric positive definite operators
+///
+/// Iterations stop when the euclidean norm of the residual falls below the largest of the absolute
+/// tolerance and the relative tolerance times the norm of the right hand side. By default the
+/// relative tolerance is 1e-10, the absolute tolerance 0 and at most 1000 iterations are done.
+pub struct ConjugateGradient {
+    stopping: StoppingCriterion,
+    monitor: Option<Rc<dyn Monitor>>,
+    estimate_spectrum: bool,
+}
+
+/// Side on which GMRES applies its preconditioner
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PreconditionerSide {
+    /// Solve P^-1 A x = P^-1 b, the stopping criterion then measures the preconditioned residual
+    Left,
+    /// Solve A P^-1 y = b with x = P^-1 y, the stopping criterion measures the true residual
+    Right,
+}
+
+/// Restarted GMRES(m) solver for general square operators
+///
+/// The Krylov basis is orthogonalized by modified Gram-Schmidt and the least squares problem is
+/// solved by Givens rotations, the solution being updated every restart length iterations. The
+/// stopping criterion is the one of ConjugateGradient. By default the restart length is 30, the
+/// relative tolerance 1e-10, the absolute tolerance 0, at most 1000 iterations are done and the
+/// preconditioner is applied on the right.
+#[derive(Clone)]
+pub struct Gmres {
+    restart: usize,
+    stopping: StoppingCriterion,
+    side: PreconditionerSide,
+    monitor: Option<Rc<dyn Monitor>>,
+}
+
+// Preconditioner doing nothing for the unpreconditioned solves
+pub(crate) struct Identity;
+
+impl Jacobi {
+    /// Build the preconditioner from the diagonal of a matrix
+    pub fn new(matrix: &SparseCSR<f64>) -> Self {
+        let inverse_diagonal = (0..matrix.n_rows())
+            .map(|i| {
+                let diagonal = matrix.get(i, i).copied().unwrap_or(0.0);
+                assert!(
+                    diagonal != 0.0,
+                    "Jacobi preconditioner needs a non zero diagonal"
+                );
+                1.0 / diagonal
+            })
+            .collect();
+        Jacobi { inverse_diagonal }
+    }
+}
+
+impl Preconditioner for Jacobi {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        for ((z, r), d) in z.iter_mut().zip(r).zip(&self.inverse_diagonal) {
+            *z = r * d;
+        }
+    }
+}
+
+impl IncompleteLU {
+    /// Factorize a square matrix, which fails when a diagonal entry is missing or a pivot vanishes
+    pub fn new(matrix: &SparseCSR<f64>) -> Option<Self> {
+        assert_eq!(
+            matrix.n_rows(),
+            matrix.n_cols(),
+            "Incomplete LU needs a square matrix"
+        );
+        let n = matrix.n_rows();
+        let mut factors = matrix.clone();
+        let diagonal = (0..n)
+            .map(|i| factors.position(i, i))
+            .collect::<Option<Vec<usize>>>()?;
+        let offsets = factors.row_offsets().to_vec();
+        for i in 0..n {
+            for p in offsets[i]..diagonal[i] {
+                let k = factors.col_indices()[p];
+                let pivot = factors.values()[diagonal[k]];
+                if pivot == 0.0 {
+                    return None;
+                }
+                let l = factors.values()[p] / pivot;
+                factors.values_mut()[p] = l;
+                // Update the entries of row i right of column k which are in the pattern
+                for q in (p + 1)..offsets[i + 1] {
+                    let j = factors.col_indices()[q];
+                    if let Some(kj) = factors.position(k, j) {
+                        let update = l * factors.values()[kj];
+                        factors.values_mut()[q] -= update;
+                    }
+                }
+            }
+            if factors.values()[diagonal[i]] == 0.0 {
+                return None;
+            }
+        }
+        Some(IncompleteLU { factors, diagonal })
+    }
+}
+
+impl Preconditioner for IncompleteLU {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        let n = self.diagonal.len();
+        let offsets = self.factors.row_offsets();
+        let cols = self.factors.col_indices();
+        let values = self.factors.values();
+        for i in 0..n {
+            let lower: f64 = (offsets[i]..self.diagonal[i])
+                .map(|p| values[p] * z[cols[p]])
+                .sum();
+            z[i] = r[i] - lower;
+        }
+        for i in (0..n).rev() {
+            let upper: f64 = ((self.diagonal[i] + 1)..offsets[i + 1])
+                .map(|p| values[p] * z[cols[p]])
+                .sum();
+            z[i] = (z[i] - upper) / values[self.diagonal[i]];
+        }
+    }
+}
+
+impl Preconditioner for Identity {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        z.copy_from_slice(r);
+    }
+}
+
+impl Convergence {
+    /// Whether the stopping criterion was met
+    pub fn converged(&self) -> bool {
+        self.reason.converged()
+    }
+
+    /// Test of the stopping criterion which stopped the solve
+    pub fn stop_reason(&self) -> StopReason {
+        self.reason
+    }
+
+    /// Number of iterations done
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// Euclidean norm of the final residual
+    pub fn residual_norm(&self) -> f64 {
+        self.residual_norm
+    }
+
+    /// Extreme eigenvalues of the (preconditioned) operator estimated by the solver if it was
+    /// asked to
+    pub fn spectrum_estimate(&self) -> Option<SpectrumEstimate> {
+        self.spectrum
+    }
+}
+
+impl Default for ConjugateGradient {
+    fn default() -> Self {
+        ConjugateGradient {
+            stopping: StoppingCriterion::new(1e-10, 0.0, 1000),
+            monitor: None,
+            estimate_spectrum: false,
+        }
+    }
+}
+
+impl ConjugateGradient {
+    /// Solver with the default tolerances
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the tolerance on the residual relative to the right hand side
+    pub fn with_relative_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_relative_tolerance(tolerance);
+        self
+    }
+
+    /// Set the tolerance on the norm of the residual
+    pub fn with_absolute_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_absolute_tolerance(tolerance);
+        self
+    }
+
+    /// Set the maximum number of iterations
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.stopping = self.stopping.with_max_iterations(max_iterations);
+        self
+    }
+
+    /// Replace the whole stopping criterion, the reference norm being the one of the right hand
+    /// side
+    pub fn with_stopping_criterion(mut self, stopping: StoppingCriterion) -> Self {
+        self.stopping = stopping;
+        self
+    }
+
+    /// Set a monitor observing the residual norm of every iteration
+    pub fn with_monitor<M: Monitor + 'static>(mut self, monitor: M) -> Self {
+        self.monitor = Some(Rc::new(monitor));
+        self
+    }
+
+    /// Estimate the extreme eigenvalues of the preconditioned operator from the coefficients of
+    /// the iterations, which tells a badly conditioned operator from a poor preconditioner
+    pub fn with_spectrum_estimate(mut self) -> Self {
+        self.estimate_spectrum = true;
+        self
+    }
+
+    /// Solve A x = b starting from the values in x
+    pub fn solve<Operator>(&self, operator: &Operator, rhs: &[f64], x: &mut [f64]) -> Convergence
+    where
+        Operator: LinearOperator,
+    {
+        self.solve_preconditioned(operator, &Identity, rhs, x)
+    }
+
+    /// Solve A x = b starting from the values in x with a symmetric positive definite preconditioner
+    pub fn solve_preconditioned<Operator, Precond>(
+        &self,
+        operator: &Operator,
+        preconditioner: &Precond,
+        rhs: &[f64],
+        x: &mut [f64],
+    ) -> Convergence
+    where
+        Operator: LinearOperator,
+        Precond: Preconditioner,
+    {
+        self.solve_with_inner_product(operator, preconditioner, rhs, x, dot)
+    }
+
+    // Solve with the given inner product, which distributed solves compute across processes
+    pub(crate) fn solve_with_inner_product<Operator, Precond, Inner>(
+        &self,
+        operator: &Operator,
+        preconditioner: &Precond,
+        rhs: &[f64],
+        x: &mut [f64],
+        inner: Inner,
+    ) -> Convergence
+    where
+        Operator: LinearOperator,
+        Precond: Preconditioner,
+        Inner: Fn(&[f64], &[f64]) -> f64,
+    {
+        let norm = |v: &[f64]| inner(v, v).sqrt();
+        let n = operator.n_rows();
+        assert!(
+            operator.n_cols() == n && rhs.len() == n && x.len() == n,
+            "Vectors do not match the size of the operator"
+        );
+        let mut test = self.stopping.start(norm(rhs));
+        let mut r = vec![0.0; n];
+        operator.apply(x, &mut r);
+        r.iter_mut().zip(rhs).for_each(|(r, b)| *r = b - *r);
+        let mut z = vec![0.0; n];
+        preconditioner.apply(&r, &mut z);
+        let mut p = z.clone();
+        let mut ap = vec![0.0; n];
+        let mut rz = inner(&r, &z);
+        let mut residual_norm = norm(&r);
+        let mut iterations = 0;
+        let mut update = None;
+        let (mut alphas, mut betas) = (Vec::new(), Vec::new());
+        let recorder = Recorder::start(&self.monitor);
+        let reason = loop {
+            recorder.record(iterations, residual_norm);
+            if let Some(reason) = test.check(iterations, residual_norm, update) {
+                break reason;
+            }
+            operator.apply(&p, &mut ap);
+            let curvature = inner(&p, &ap);
+            // Breakdown for operators which are not positive definite
+            if curvature <= 0.0 {
+                break StopReason::Breakdown;
+            }
+            let alpha = rz / curvature;
+            axpy(alpha, &p, x);
+            axpy(-alpha, &ap, &mut r);
+            update = Some((alpha.abs() * norm(&p), norm(x)));
+            iterations += 1;
+            residual_norm = norm(&r);
+            preconditioner.apply(&r, &mut z);
+            let rz_next = inner(&r, &z);
+            let beta = rz_next / rz;
+            if self.estimate_spectrum {
+                alphas.push(alpha);
+                betas.push(beta);
+            }
+            rz = rz_next;
+            for (p, z) in p.iter_mut().zip(&z) {
+                *p = z + beta * *p;
+            }
+        };
+        Convergence {
+            reason,
+            iterations,
+            residual_norm,
+            spectrum: conjugate_gradient_spectrum(&alphas, &betas),
+        }
+    }
+}
+
+impl Default for Gmres {
+    fn default() -> Self {
+        Gmres {
+            restart: 30,
+            stopping: StoppingCriterion::new(1e-10, 0.0, 1000),
+            side: PreconditionerSide::Right,
+            monitor: None,
+        }
+    }
+}
+
+impl Gmres {
+    /// Solver with the default parameters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of iterations between restarts
+    pub fn with_restart(mut self, restart: usize) -> Self {
+        assert!(restart > 0, "GMRES needs a non zero restart length");
+        self.restart = restart;
+        self
+    }
+
+    /// Set the tolerance on the residual relative to the right hand side
+    pub fn with_relative_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_relative_tolerance(tolerance);
+        self
+    }
+
+    /// Set the tolerance on the norm of the residual
+    pub fn with_absolute_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_absolute_tolerance(tolerance);
+        self
+    }
+
+    /// Set the maximum number of iterations over all the restarts
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.stopping = self.stopping.with_max_iterations(max_iterations);
+        self
+    }
+
+    /// Replace the whole stopping criterion, the reference norm being the one of the right hand
+    /// side
+    pub fn with_stopping_criterion(mut self, stopping: StoppingCriterion) -> Self {
+        self.stopping = stopping;
+        self
+    }
+
+    /// Set the side on which the preconditioner is applied
+    pub fn with_side(mut self, side: PreconditionerSide) -> Self {
+        self.side = side;
+        self
+    }
+
+    /// Set a monitor observing the residual norm of every iteration
+    pub fn with_monitor<M: Monitor + 'static>(mut self, monitor: M) -> Self {
+        self.monitor = Some(Rc::new(monitor));
+        self
+    }
+
+    /// Solve A x = b starting from the values in x
+    pub fn solve<Operator>(&self, operator: &Operator, rhs: &[f64], x: &mut [f64]) -> Convergence
+    where
+        Operator: LinearOperator,
+    {
+        self.solve_preconditioned(operator, &Identity, rhs, x)
+    }
+
+    /// Solve A x = b starting from the values in x with a preconditioner
+    pub fn solve_preconditioned<Operator, Precond>(
+        &self,
+        operator: &Operator,
+        preconditioner: &Precond,
+        rhs: &[f64],
+        x: &mut [f64],
+    ) -> Convergence
+    where
+        Operator: LinearOperator,
+        Precond: Preconditioner,
+    {
+        self.solve_with_inner_product(operator, preconditioner, rhs, x, dot)
+    }
+
+    // Solve with the given inner product, which distributed solves compute across processes
+    pub(crate) fn solve_with_inner_product<Operator, Precond, Inner>(
+        &self,
+        operator: &Operator,
+        preconditioner: &Precond,
+        rhs: &[f64],
+        x: &mut [f64],
+        inner: Inner,
+    ) -> Convergence
+    where
+        Operator: LinearOperator,
+        Precond: Preconditioner,
+        Inner: Fn(&[f64], &[f64]) -> f64,
+    {
+        let norm = |v: &[f64]| inner(v, v).sqrt();
+        let n = operator.n_rows();
+        assert!(
+            operator.n_cols() == n && rhs.len() == n && x.len() == n,
+            "Vectors do not match the size of the operator"
+        );
+        let left = self.side == PreconditionerSide::Left;
+        let mut work = vec![0.0; n];
+        let rhs_norm = if left {
+            preconditioner.apply(rhs, &mut work);
+            norm(&work)
+        } else {
+            norm(rhs)
+        };
+        let mut test = self.stopping.start(rhs_norm);
+        let m = self.restart.min(n.max(1));
+        let mut basis = vec![vec![0.0; n]; m + 1];
+        let mut hessenberg = vec![vec![0.0; m + 1]; m];
+        let mut rotations = vec![(1.0, 0.0); m];
+        let mut g = vec![0.0; m + 1];
+        let mut w = vec![0.0; n];
+        let mut iterations = 0;
+        let mut residual_norm;
+        let mut update = None;
+        let recorder = Recorder::start(&self.monitor);
+        let reason = loop {
+            // Residual of the current solution starting the Krylov basis
+            operator.apply(x, &mut work);
+            work.iter_mut().zip(rhs).for_each(|(r, b)| *r = b - *r);
+            if left {
+                preconditioner.apply(&work, &mut basis[0]);
+            } else {
+                basis[0].copy_from_slice(&work);
+            }
+            residual_norm = norm(&basis[0]);
+            if iterations == 0 {
+                recorder.record(iterations, residual_norm);
+            }
+            // The true residual replaces the estimate of the last iteration
+            if let Some(reason) = test.check(iterations, residual_norm, update) {
+                break reason;
+            }
+            basis[0].iter_mut().for_each(|v| *v /= residual_norm);
+            g.iter_mut().for_each(|v| *v = 0.0);
+            g[0] = residual_norm;
+            let mut k = 0;
+            while k < m {
+                if left {
+                    operator.apply(&basis[k], &mut work);
+                    preconditioner.apply(&work, &mut w);
+                } else {
+                    preconditioner.apply(&basis[k], &mut work);
+                    operator.apply(&work, &mut w);
+                }
+                let column = &mut hessenberg[k];
+                for (i, vector) in basis.iter().enumerate().take(k + 1) {
+                    column[i] = inner(&w, vector);
+                    axpy(-column[i], vector, &mut w);
+                }
+                column[k + 1] = norm(&w);
+                let breakdown = column[k + 1] <= f64::EPSILON * residual_norm;
+                if !breakdown {
+                    let scale = column[k + 1];
+                    basis[k + 1]
+                        .iter_mut()
+                        .zip(&w)
+                        .for_each(|(v, w)| *v = w / scale);
+                }
+                // Previous rotations then the one eliminating the subdiagonal entry
+                for (i, (c, s)) in rotations.iter().enumerate().take(k) {
+                    let (a, b) = (column[i], column[i + 1]);
+                    column[i] = c * a + s * b;
+                    column[i + 1] = c * b - s * a;
+                }
+                let radius = column[k].hypot(column[k + 1]);
+                let (c, s) = if radius == 0.0 {
+                    (1.0, 0.0)
+                } else {
+                    (column[k] / radius, column[k + 1] / radius)
+                };
+                rotations[k] = (c, s);
+                column[k] = radius;
+                column[k + 1] = 0.0;
+                g[k + 1] = -s * g[k];
+                g[k] *= c;
+                iterations += 1;
+                k += 1;
+                recorder.record(iterations, g[k].abs());
+                if test.check(iterations, g[k].abs(), None).is_some() || breakdown {
+                    break;
+                }
+            }
+            // Back substitution of the triangular least squares system and solution update
+            let mut y = g[..k].to_vec();
+            for i in (0..k).rev() {
+                let sum: f64 = ((i + 1)..k).map(|j| hessenberg[j][i] * y[j]).sum();
+                y[i] = (y[i] - sum) / hessenberg[i][i];
+            }
+            work.iter_mut().for_each(|v| *v = 0.0);
+            for (vector, y) in basis.iter().zip(&y) {
+                axpy(*y, vector, &mut work);
+            }
+            if left {
+                axpy(1.0, &work, x);
+                update = Some((norm(&work), norm(x)));
+            } else {
+                preconditioner.apply(&work, &mut w);
+                axpy(1.0, &w, x);
+                update = Some((norm(&w), norm(x)));
+            }
+        };
+        Convergence {
+            reason,
+            iterations,
+            residual_norm,
+            spectrum: None,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+pub(crate) fn dot(a: &[f64], b: &[f64]) -> f64 {
+    #[cfg(feature = "parallel")]
+    if let Some(chunk) = parallel::vector_chunk(a.len()) {
+        return parallel::install(|| {
+            a.par_iter()
+                .zip(b)
+                .with_min_len(chunk)
+                .map(|(x, y)| x * y)
+                .sum()
+        });
+    }
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+pub(crate) fn norm(a: &[f64]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+pub(crate) fn distance(a: &[f64], b: &[f64]) -> f64 {
+    #[cfg(feature = "parallel")]
+    if let Some(chunk) = parallel::vector_chunk(a.len()) {
+        return parallel::install(|| {
+            a.par_iter()
+                .zip(b)
+                .with_min_len(chunk)
+                .map(|(x, y)| (x - y) * (x - y))
+                .sum::<f64>()
+                .sqrt()
+        });
+    }
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f64>()
+        .sqrt()
+}
+
+// Compute y = a x + y
+pub(crate) fn axpy(a: f64, x: &[f64], y: &mut [f64]) {
+    #[cfg(feature = "parallel")]
+    if let Some(chunk) = parallel::vector_chunk(y.len()) {
+        parallel::install(|| {
+            y.par_iter_mut()
+                .zip(x)
+                .with_min_len(chunk)
+                .for_each(|(y, x)| *y += a * x)
+        });
+        return;
+    }
+    y.iter_mut().zip(x).for_each(|(y, x)| *y += a * x);
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tridiagonal matrix of the 1D Laplacian with a varying diagonal
+    fn build_matrix(n: usize) -> SparseCSR<f64> {
+        let mut row_offsets = vec![0];
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+        for i in 0..n {
+            for j in i.saturating_sub(1)..(i + 2).min(n) {
+                col_indices.push(j);
+                values.push(if i == j { 2.0 + i as f64 } else { -1.0 });
+            }
+            row_offsets.push(col_indices.len());
+        }
+        SparseCSR::new(n, row_offsets, col_indices, values)
+    }
+
+    // Diagonal operator applied without a matrix
+    struct Scaling(Vec<f64>);
+
+    impl LinearOperator for Scaling {
+        fn n_rows(&self) -> usize {
+            self.0.len()
+        }
+
+        fn n_cols(&self) -> usize {
+            self.0.len()
+        }
+
+        fn apply(&self, x: &[f64], y: &mut [f64]) {
+            for ((y, x), s) in y.iter_mut().zip(x).zip(&self.0) {
+                *y = s * x;
+            }
+        }
+    }
+
+    #[test]
+    fn test_conjugate_gradient() {
+        let n = 30;
+        let matrix = build_matrix(n);
+        let expected: Vec<f64> = (0..n).map(|i| (i as f64).sin()).collect();
+        let mut rhs = vec![0.0; n];
+        matrix.apply(&expected, &mut rhs);
+        let solver = ConjugateGradient::new().with_relative_tolerance(1e-12);
+        let mut x = vec![0.0; n];
+        let plain = solver.solve(&matrix, &rhs, &mut x);
+        assert!(plain.converged(), "Conjugate gradient did not converge");
+        assert!(plain.iterations() <= n, "Too many iterations");
+        for (x, e) in x.iter().zip(&expected) {
+            assert!((x - e).abs() < 1e-9, "Wrong solution");
+        }
+        let mut x = vec![0.0; n];
+        let preconditioned =
+            solver.solve_preconditioned(&matrix, &Jacobi::new(&matrix), &rhs, &mut x);
+        assert!(
+            preconditioned.converged(),
+            "Preconditioned solve did not converge"
+        );
+        assert!(
+            preconditioned.iterations() < plain.iterations(),
+            "Jacobi should help on a varying diagonal"
+        );
+        // Starting from the solution needs no iteration
+        let converged = solver.solve(&matrix, &rhs, &mut x);
+        assert_eq!(converged.iterations(), 0, "Solution was already converged");
+        let mut x = vec![0.0; n];
+        let stopped = ConjugateGradient::new()
+            .with_max_iterations(2)
+            .solve(&matrix, &rhs, &mut x);
+        assert!(!stopped.converged(), "Two iterations should not be enough");
+        assert_eq!(stopped.iterations(), 2, "Wrong number of iterations");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_conjugate_gradient_matrix_free() {
+        let operator = Scaling(vec![1.0, 2.0, 4.0]);
+        let mut x = vec![0.0; 3];
+        let result = ConjugateGradient::new()
+            .with_absolute_tolerance(1e-12)
+            .solve(&operator, &[1.0, 1.0, 1.0], &mut x);
+        assert!(result.converged(), "Conjugate gradient did not converge");
+        assert!(result.residual_norm() <= 1e-12, "Wrong residual norm");
+        assert!(result.iterations() <= 3, "Too many iterations");
+        for (x, expected) in x.iter().zip([1.0, 0.5, 0.25]) {
+            assert!((x - expected).abs() < 1e-12, "Wrong solution");
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_incomplete_lu() {
+        // ILU(0) of a tridiagonal matrix is its exact LU factorization
+        let n = 20;
+        let matrix = build_matrix(n);
+        let ilu = IncompleteLU::new(&matrix).unwrap();
+        let expected: Vec<f64> = (0..n).map(|i| (i as f64 * 0.3).sin()).collect();
+        let mut rhs = vec![0.0; n];
+        matrix.apply(&expected, &mut rhs);
+        let mut x = vec![0.0; n];
+        ilu.apply(&rhs, &mut x);
+        for (x, e) in x.iter().zip(&expected) {
+            assert!((x - e).abs() < 1e-12, "ILU(0) should be exact");
+        }
+        let zero_pivot =
+            SparseCSR::new(2, vec![0, 2, 4], vec![0, 1, 0, 1], vec![1.0, 1.0, 1.0, 1.0]);
+        assert!(
+            IncompleteLU::new(&zero_pivot).is_none(),
+            "Vanishing pivots should fail"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_gmres() {
+        // Upwinded advection diffusion with a varying diagonal is not symmetric
+        let n: usize = 40;
+        let mut row_offsets = vec![0];
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+        for i in 0..n {
+            for j in i.saturating_sub(1)..(i + 2).min(n) {
+                col_indices.push(j);
+                values.push(match j as isize - i as isize {
+                    0 => 3.0 + i as f64,
+                    -1 => -2.0,
+                    _ => -0.5,
+                });
+            }
+            row_offsets.push(col_indices.len());
+        }
+        let matrix = SparseCSR::new(n, row_offsets, col_indices, values);
+        let expected: Vec<f64> = (0..n).map(|i| (i as f64).cos()).collect();
+        let mut rhs = vec![0.0; n];
+        matrix.apply(&expected, &mut rhs);
+        let check = |x: &[f64]| {
+            for (x, e) in x.iter().zip(&expected) {
+                assert!((x - e).abs() < 1e-8, "Wrong solution");
+            }
+        };
+        let mut x = vec![0.0; n];
+        let full = Gmres::new()
+            .with_restart(n)
+            .with_relative_tolerance(1e-12)
+            .solve(&matrix, &rhs, &mut x);
+        assert!(full.converged(), "Full GMRES did not converge");
+        assert!(full.iterations() <= n, "Too many iterations");
+        check(&x);
+        let mut x = vec![0.0; n];
+        let restarted = Gmres::new()
+            .with_restart(5)
+            .with_relative_tolerance(1e-12)
+            .solve(&matrix, &rhs, &mut x);
+        assert!(restarted.converged(), "Restarted GMRES did not converge");
+        assert!(
+            restarted.iterations() >= full.iterations(),
+            "Restarts should not speed up"
+        );
+        check(&x);
+        let jacobi = Jacobi::new(&matrix);
+        for side in [PreconditionerSide::Left, PreconditionerSide::Right] {
+            let mut x = vec![0.0; n];
+            let result = Gmres::new()
+                .with_restart(10)
+                .with_relative_tolerance(1e-12)
+                .with_side(side)
+                .solve_preconditioned(&matrix, &jacobi, &rhs, &mut x);
+            assert!(result.converged(), "Preconditioned GMRES did not converge");
+            assert!(
+                result.iterations() <= restarted.iterations(),
+                "Jacobi should help on a varying diagonal"
+            );
+            check(&x);
+        }
+        let mut x = vec![0.0; n];
+        let stopped = Gmres::new()
+            .with_max_iterations(3)
+            .solve(&matrix, &rhs, &mut x);
+        assert!(
+            !stopped.converged(),
+            "Three iterations should not be enough"
+        );
+        assert_eq!(stopped.iterations(), 3, "Wrong number of iterations");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_large_kernels() {
+        // Long enough to be split between threads with the parallel feature
+        let n: usize = 20000;
+        let a: Vec<f64> = (0..n).map(|i| i as f64).collect();
+        let mut b = vec![1.0; n];
+        assert_eq!(dot(&a, &b), (n * (n - 1) / 2) as f64, "Wrong dot product");
+        axpy(-1.0, &a, &mut b);
+        assert_eq!(b[n - 1], 2.0 - n as f64, "Wrong axpy");
+        assert_eq!(distance(&b, &b), 0.0, "Wrong distance");
+        let matrix = build_matrix(n);
+        let mut y = vec![0.0; n];
+        matrix.apply(&a, &mut y);
+        for (row, y) in y.iter().enumerate() {
+            let (cols, values) = matrix.row(row);
+            let expected: f64 = cols.iter().zip(values).map(|(c, v)| v * a[*c]).sum();
+            assert_eq!(*y, expected, "Wrong product of row {}", row);
+        }
+    }
+}
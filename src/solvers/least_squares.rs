@@ -0,0 +1,320 @@
+use super::krylov::{axpy, dot, norm, Convergence};
+use super::linear_operator::LinearOperator;
+use super::monitor::{Monitor, Recorder};
+use super::stopping_criterion::StoppingCriterion;
+use std::rc::Rc;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Conjugate gradient on the normal equations A^T A x = A^T b (CGLS) minimizing ||b - A x|| for
+/// rectangular or rank deficient operators
+///
+/// The normal equations are never formed, every iteration applying the operator and its
+/// transpose once. Iterations stop when the norm of the normal equations residual A^T (b - A x)
+/// falls below the largest of the absolute tolerance and the relative tolerance times its initial
+/// norm, which is also the residual norm of the results. Started from zero on a rank deficient
+/// operator the iterates converge to the solution of minimum norm. By default the relative
+/// tolerance is 1e-10, the absolute tolerance 0 and at most 1000 iterations are done.
+pub struct Cgls {
+    stopping: StoppingCriterion,
+    monitor: Option<Rc<dyn Monitor>>,
+}
+
+/// LSQR solver of Paige and Saunders minimizing ||b - A x|| for rectangular or rank deficient
+/// operators
+///
+/// Mathematically equivalent to CGLS but built on the Golub-Kahan bidiagonalization of the
+/// operator, which is more robust for badly conditioned ones. The stopping criterion and its
+/// defaults are the ones of Cgls.
+pub struct Lsqr {
+    stopping: StoppingCriterion,
+    monitor: Option<Rc<dyn Monitor>>,
+}
+
+impl Default for Cgls {
+    fn default() -> Self {
+        Cgls {
+            stopping: StoppingCriterion::new(1e-10, 0.0, 1000),
+            monitor: None,
+        }
+    }
+}
+
+impl Cgls {
+    /// Solver with the default tolerances
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the tolerance on the normal equations residual relative to its initial norm
+    pub fn with_relative_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_relative_tolerance(tolerance);
+        self
+    }
+
+    /// Set the tolerance on the norm of the normal equations residual
+    pub fn with_absolute_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_absolute_tolerance(tolerance);
+        self
+    }
+
+    /// Set the maximum number of iterations
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.stopping = self.stopping.with_max_iterations(max_iterations);
+        self
+    }
+
+    /// Replace the whole stopping criterion, the reference norm being the initial one of the
+    /// normal equations residual
+    pub fn with_stopping_criterion(mut self, stopping: StoppingCriterion) -> Self {
+        self.stopping = stopping;
+        self
+    }
+
+    /// Set a monitor observing the normal equations residual norm of every iteration
+    pub fn with_monitor<M: Monitor + 'static>(mut self, monitor: M) -> Self {
+        self.monitor = Some(Rc::new(monitor));
+        self
+    }
+
+    /// Minimize ||b - A x|| starting from the values in x, the operator having a transpose
+    pub fn solve<Operator>(&self, operator: &Operator, rhs: &[f64], x: &mut [f64]) -> Convergence
+    where
+        Operator: LinearOperator,
+    {
+        check_sizes(operator, rhs, x);
+        let mut r = vec![0.0; rhs.len()];
+        operator.apply(x, &mut r);
+        r.iter_mut().zip(rhs).for_each(|(r, b)| *r = b - *r);
+        let mut s = vec![0.0; x.len()];
+        operator.apply_transpose(&r, &mut s);
+        let mut p = s.clone();
+        let mut q = vec![0.0; rhs.len()];
+        let mut gamma = dot(&s, &s);
+        let mut residual_norm = gamma.sqrt();
+        let mut test = self.stopping.start(residual_norm);
+        let mut iterations = 0;
+        let mut update = None;
+        let recorder = Recorder::start(&self.monitor);
+        let reason = loop {
+            recorder.record(iterations, residual_norm);
+            if let Some(reason) = test.check(iterations, residual_norm, update) {
+                break reason;
+            }
+            operator.apply(&p, &mut q);
+            let alpha = gamma / dot(&q, &q);
+            axpy(alpha, &p, x);
+            axpy(-alpha, &q, &mut r);
+            update = Some((alpha.abs() * norm(&p), norm(x)));
+            operator.apply_transpose(&r, &mut s);
+            let gamma_next = dot(&s, &s);
+            let beta = gamma_next / gamma;
+            gamma = gamma_next;
+            for (p, s) in p.iter_mut().zip(&s) {
+                *p = s + beta * *p;
+            }
+            iterations += 1;
+            residual_norm = gamma.sqrt();
+        };
+        Convergence {
+            reason,
+            iterations,
+            residual_norm,
+            spectrum: None,
+        }
+    }
+}
+
+impl Default for Lsqr {
+    fn default() -> Self {
+        Lsqr {
+            stopping: StoppingCriterion::new(1e-10, 0.0, 1000),
+            monitor: None,
+        }
+    }
+}
+
+impl Lsqr {
+    /// Solver with the default tolerances
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the tolerance on the normal equations residual relative to its initial norm
+    pub fn with_relative_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_relative_tolerance(tolerance);
+        self
+    }
+
+    /// Set the tolerance on the norm of the normal equations residual
+    pub fn with_absolute_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_absolute_tolerance(tolerance);
+        self
+    }
+
+    /// Set the maximum number of iterations
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.stopping = self.stopping.with_max_iterations(max_iterations);
+        self
+    }
+
+    /// Replace the whole stopping criterion, the reference norm being the initial one of the
+    /// normal equations residual
+    pub fn with_stopping_criterion(mut self, stopping: StoppingCriterion) -> Self {
+        self.stopping = stopping;
+        self
+    }
+
+    /// Set a monitor observing the normal equations residual norm of every iteration
+    pub fn with_monitor<M: Monitor + 'static>(mut self, monitor: M) -> Self {
+        self.monitor = Some(Rc::new(monitor));
+        self
+    }
+
+    /// Minimize ||b - A x|| starting from the values in x, the operator having a transpose
+    pub fn solve<Operator>(&self, operator: &Operator, rhs: &[f64], x: &mut [f64]) -> Convergence
+    where
+        Operator: LinearOperator,
+    {
+        check_sizes(operator, rhs, x);
+        // Bidiagonalization started from the initial residual
+        let mut u = vec![0.0; rhs.len()];
+        operator.apply(x, &mut u);
+        u.iter_mut().zip(rhs).for_each(|(u, b)| *u = b - *u);
+        let mut beta = normalize(&mut u);
+        let mut v = vec![0.0; x.len()];
+        operator.apply_transpose(&u, &mut v);
+        let mut alpha = normalize(&mut v);
+        let mut w = v.clone();
+        let mut av = vec![0.0; u.len()];
+        let mut atu = vec![0.0; v.len()];
+        let (mut phi_bar, mut rho_bar) = (beta, alpha);
+        let mut residual_norm = alpha * beta;
+        let mut test = self.stopping.start(residual_norm);
+        let mut iterations = 0;
+        let mut update = None;
+        let recorder = Recorder::start(&self.monitor);
+        let reason = loop {
+            recorder.record(iterations, residual_norm);
+            if let Some(reason) = test.check(iterations, residual_norm, update) {
+                break reason;
+            }
+            // Next vectors of the bidiagonalization
+            operator.apply(&v, &mut av);
+            u.iter_mut().zip(&av).for_each(|(u, a)| *u = a - alpha * *u);
+            beta = normalize(&mut u);
+            operator.apply_transpose(&u, &mut atu);
+            v.iter_mut().zip(&atu).for_each(|(v, a)| *v = a - beta * *v);
+            alpha = normalize(&mut v);
+            // Rotation eliminating the subdiagonal of the bidiagonal matrix
+            let rho = rho_bar.hypot(beta);
+            let (c, s) = (rho_bar / rho, beta / rho);
+            let theta = s * alpha;
+            rho_bar = -c * alpha;
+            let phi = c * phi_bar;
+            phi_bar *= s;
+            axpy(phi / rho, &w, x);
+            update = Some(((phi / rho).abs() * norm(&w), norm(x)));
+            for (w, v) in w.iter_mut().zip(&v) {
+                *w = v - theta / rho * *w;
+            }
+            iterations += 1;
+            residual_norm = phi_bar * alpha * c.abs();
+        };
+        Convergence {
+            reason,
+            iterations,
+            residual_norm,
+            spectrum: None,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+fn check_sizes<Operator: LinearOperator>(operator: &Operator, rhs: &[f64], x: &[f64]) {
+    assert!(
+        operator.has_transpose(),
+        "Least squares solvers need the transpose of the operator"
+    );
+    assert!(
+        rhs.len() == operator.n_rows() && x.len() == operator.n_cols(),
+        "Vectors do not match the size of the operator"
+    );
+}
+
+// Scale a vector to a unit norm unless it vanishes and return its norm
+fn normalize(v: &mut [f64]) -> f64 {
+    let length = norm(v);
+    if length > 0.0 {
+        v.iter_mut().for_each(|v| *v /= length);
+    }
+    length
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::sparse_csr::SparseCSR;
+
+    #[test]
+    fn test_least_squares() {
+        // Line fit y = a + b t through points off the line
+        let times = [0.0, 1.0, 2.0, 3.0, 4.0];
+        let points = [1.0, 2.9, 5.2, 7.1, 8.8];
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+        for t in times {
+            col_indices.extend([0, 1]);
+            values.extend([1.0, t]);
+        }
+        let fit = SparseCSR::new(2, (0..=10).step_by(2).collect(), col_indices, values);
+        // Normal equations [5 10; 10 30] x = [25, 69.8]
+        let expected = [1.04, 1.98];
+        // Two identical columns make the operator rank deficient, the minimum norm solution
+        // splitting the coefficient between them
+        let deficient = SparseCSR::new(
+            3,
+            vec![0, 3, 6, 9],
+            vec![0, 1, 2, 0, 1, 2, 0, 1, 2],
+            vec![1.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 2.0],
+        );
+        // Columns (1, 1, 1) twice and (0, 1, 2) fitting (1, 2, 4)
+        let minimum_norm = [5.0 / 12.0, 5.0 / 12.0, 1.5];
+        for name in ["CGLS", "LSQR"] {
+            let solve = |a: &SparseCSR<f64>, b: &[f64], x: &mut [f64]| match name {
+                "CGLS" => Cgls::new().solve(a, b, x),
+                _ => Lsqr::new().solve(a, b, x),
+            };
+            let mut x = vec![0.0; 2];
+            let convergence = solve(&fit, &points, &mut x);
+            assert!(convergence.converged(), "{} did not converge", name);
+            assert!(
+                convergence.iterations() <= 3,
+                "{} needs too many iterations",
+                name
+            );
+            for (x, e) in x.iter().zip(expected) {
+                assert!((x - e).abs() < 1e-10, "Wrong {} fit {}", name, x);
+            }
+            let mut x = vec![0.0; 3];
+            let convergence = solve(&deficient, &[1.0, 2.0, 4.0], &mut x);
+            assert!(convergence.converged(), "{} did not converge", name);
+            for (x, e) in x.iter().zip(minimum_norm) {
+                assert!(
+                    (x - e).abs() < 1e-10,
+                    "Not the {} minimum norm solution",
+                    name
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use crate::core::arrays::sparse_csr::SparseCSR;
+
+//--------------------------------------------------------------------------------------------------
+// # Traits
+//--------------------------------------------------------------------------------------------------
+
+/// Linear map acting on vectors which is all iterative solvers need from a system
+///
+/// Assembled matrices and matrix-free operators both implement it so that they can be handed to
+/// the same solvers.
+pub trait LinearOperator {
+    /// Dimension of the output vectors
+    fn n_rows(&self) -> usize;
+
+    /// Dimension of the input vectors
+    fn n_cols(&self) -> usize;
+
+    /// Compute y = A x
+    fn apply(&self, x: &[f64], y: &mut [f64]);
+
+    /// Whether the operator can apply its transpose
+    fn has_transpose(&self) -> bool {
+        false
+    }
+
+    /// Compute y = A^T x, only available if has_transpose is true
+    fn apply_transpose(&self, _x: &[f64], _y: &mut [f64]) {
+        panic!("Transpose is not available for this operator")
+    }
+}
+
+/// Approximate inverse of an operator applied to the residual at every iteration of a solver
+pub trait Preconditioner {
+    /// Compute z = P^-1 r
+    fn apply(&self, r: &[f64], z: &mut [f64]);
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Product A B of two operators applied right to left
+pub struct Composition<'a, Left: ?Sized, Right: ?Sized> {
+    left: &'a Left,
+    right: &'a Right,
+}
+
+/// Operator A^T applying the transpose of another
+pub struct Transposed<'a, Operator: ?Sized> {
+    operator: &'a Operator,
+}
+
+/// Diagonal matrix stored by its diagonal, such as a lumped mass matrix, whose inverse is applied
+/// entrywise
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagonal {
+    values: Vec<f64>,
+}
+
+/// Operator made of blocks of other operators, missing blocks being zero
+///
+/// The vectors are the concatenation of the block vectors in the order of the block rows for the
+/// outputs and of the block columns for the inputs.
+pub struct BlockOperator<'a> {
+    row_offsets: Vec<usize>,
+    col_offsets: Vec<usize>,
+    blocks: Vec<Option<&'a dyn LinearOperator>>,
+}
+
+impl<T: LinearOperator + ?Sized> LinearOperator for &T {
+    fn n_rows(&self) -> usize {
+        (**self).n_rows()
+    }
+
+    fn n_cols(&self) -> usize {
+        (**self).n_cols()
+    }
+
+    fn apply(&self, x: &[f64], y: &mut [f64]) {
+        (**self).apply(x, y)
+    }
+
+    fn has_transpose(&self) -> bool {
+        (**self).has_transpose()
+    }
+
+    fn apply_transpose(&self, x: &[f64], y: &mut [f64]) {
+        (**self).apply_transpose(x, y)
+    }
+}
+
+impl<T: Preconditioner + ?Sized> Preconditioner for &T {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        (**self).apply(r, z)
+    }
+}
+
+impl LinearOperator for SparseCSR<f64> {
+    fn n_rows(&self) -> usize {
+        SparseCSR::n_rows(self)
+    }
+
+    fn n_cols(&self) -> usize {
+        SparseCSR::n_cols(self)
+    }
+
+    fn apply(&self, x: &[f64], y: &mut [f64]) {
+        SparseCSR::apply(self, x, y)
+    }
+
+    fn has_transpose(&self) -> bool {
+        true
+    }
+
+    fn apply_transpose(&self, x: &[f64], y: &mut [f64]) {
+        y.iter_mut().for_each(|y| *y = 0.0);
+        for (row, x) in x.iter().enumerate() {
+            let (cols, values) = self.row(row);
+            for (col, value) in cols.iter().zip(values) {
+                y[*col] += value * x;
+            }
+        }
+    }
+}
+
+impl Diagonal {
+    /// Matrix of the given diagonal
+    pub fn new(values: Vec<f64>) -> Self {
+        Diagonal { values }
+    }
+
+    /// Number of rows and columns
+    pub fn size(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Diagonal entries
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// Mutable diagonal entries, for instance to set the ones of constrained dofs
+    pub fn values_mut(&mut self) -> &mut [f64] {
+        &mut self.values
+    }
+
+    /// Solve D x = b
+    pub fn solve(&self, rhs: &[f64], x: &mut [f64]) {
+        assert!(
+            self.values.iter().all(|d| *d != 0.0),
+            "Diagonal matrix is singular"
+        );
+        for ((x, b), d) in x.iter_mut().zip(rhs).zip(&self.values) {
+            *x = b / d;
+        }
+    }
+
+    /// Inverse matrix
+    pub fn inverse(&self) -> Diagonal {
+        let mut inverse = vec![0.0; self.size()];
+        self.solve(&vec![1.0; self.size()], &mut inverse);
+        Diagonal::new(inverse)
+    }
+
+    /// Matrix in the compressed sparse row format, for the solvers which need one
+    pub fn to_csr(&self) -> SparseCSR<f64> {
+        let n = self.size();
+        SparseCSR::new(n, (0..=n).collect(), (0..n).collect(), self.values.clone())
+    }
+}
+
+impl LinearOperator for Diagonal {
+    fn n_rows(&self) -> usize {
+        self.size()
+    }
+
+    fn n_cols(&self) -> usize {
+        self.size()
+    }
+
+    fn apply(&self, x: &[f64], y: &mut [f64]) {
+        for ((y, x), d) in y.iter_mut().zip(x).zip(&self.values) {
+            *y = d * x;
+        }
+    }
+
+    fn has_transpose(&self) -> bool {
+        true
+    }
+
+    fn apply_transpose(&self, x: &[f64], y: &mut [f64]) {
+        LinearOperator::apply(self, x, y)
+    }
+}
+
+impl<'a, Left, Right> Composition<'a, Left, Right>
+where
+    Left: LinearOperator + ?Sized,
+    Right: LinearOperator + ?Sized,
+{
+    /// Product of the left and right operators
+    pub fn new(left: &'a Left, right: &'a Right) -> Self {
+        assert_eq!(
+            left.n_cols(),
+            right.n_rows(),
+            "Operators of the composition do not have compatible sizes"
+        );
+        Composition { left, right }
+    }
+}
+
+impl<Left, Right> LinearOperator for Composition<'_, Left, Right>
+where
+    Left: LinearOperator + ?Sized,
+    Right: LinearOperator + ?Sized,
+{
+    fn n_rows(&self) -> usize {
+        self.left.n_rows()
+    }
+
+    fn n_cols(&self) -> usize {
+        self.right.n_cols()
+    }
+
+    fn apply(&self, x: &[f64], y: &mut [f64]) {
+        let mut intermediate = vec![0.0; self.right.n_rows()];
+        self.right.apply(x, &mut intermediate);
+        self.left.apply(&intermediate, y);
+    }
+
+    fn has_transpose(&self) -> bool {
+        self.left.has_transpose() && self.right.has_transpose()
+    }
+
+    fn apply_transpose(&self, x: &[f64], y: &mut [f64]) {
+        let mut intermediate = vec![0.0; self.left.n_cols()];
+        self.left.apply_transpose(x, &mut intermediate);
+        self.right.apply_transpose(&intermediate, y);
+    }
+}
+
+impl<'a, Operator: LinearOperator + ?Sized> Transposed<'a, Operator> {
+    /// Transpose of an operator which has one
+    pub fn new(operator: &'a Operator) -> Self {
+        assert!(
+            operator.has_transpose(),
+            "Operator does not have a transpose"
+        );
+        Transposed { operator }
+    }
+}
+
+impl<Operator: LinearOperator + ?Sized> LinearOperator for Transposed<'_, Operator> {
+    fn n_rows(&self) -> usize {
+        self.operator.n_cols()
+    }
+
+    fn n_cols(&self) -> usize {
+        self.operator.n_rows()
+    }
+
+    fn apply(&self, x: &[f64], y: &mut [f64]) {
+        self.operator.apply_transpose(x, y)
+    }
+
+    fn has_transpose(&self) -> bool {
+        true
+    }
+
+    fn apply_transpose(&self, x: &[f64], y: &mut [f64]) {
+        self.operator.apply(x, y)
+    }
+}
+
+impl<'a> BlockOperator<'a> {
+    /// Zero operator with blocks of the given row and column sizes
+    pub fn new(row_sizes: &[usize], col_sizes: &[usize]) -> Self {
+        let offsets = |sizes: &[usize]| {
+            let mut offsets = vec![0];
+            sizes
+                .iter()
+                .for_each(|size| offsets.push(offsets[offsets.len() - 1] + size));
+            offsets
+        };
+        BlockOperator {
+            row_offsets: offsets(row_sizes),
+            col_offsets: offsets(col_sizes),
+            blocks: vec![None; row_sizes.len() * col_sizes.len()],
+        }
+    }
+
+    /// Set the block of the given block row and column
+    pub fn with_block(mut self, row: usize, col: usize, block: &'a dyn LinearOperator) -> Self {
+        let n_block_cols = self.col_offsets.len() - 1;
+        assert!(
+            block.n_rows() == self.row_offsets[row + 1] - self.row_offsets[row]
+                && block.n_cols() == self.col_offsets[col + 1] - self.col_offsets[col],
+            "Block does not have the size of its position"
+        );
+        self.blocks[row * n_block_cols + col] = Some(block);
+        self
+    }
+
+    // Blocks with their block row and column
+    fn blocks(&self) -> impl Iterator<Item = (usize, usize, &'a dyn LinearOperator)> + '_ {
+        let n_block_cols = self.col_offsets.len() - 1;
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, block)| {
+                block.map(|block| (index / n_block_cols, index % n_block_cols, block))
+            })
+    }
+}
+
+impl LinearOperator for BlockOperator<'_> {
+    fn n_rows(&self) -> usize {
+        self.row_offsets[self.row_offsets.len() - 1]
+    }
+
+    fn n_cols(&self) -> usize {
+        self.col_offsets[self.col_offsets.len() - 1]
+    }
+
+    fn apply(&self, x: &[f64], y: &mut [f64]) {
+        y.iter_mut().for_each(|y| *y = 0.0);
+        for (row, col, block) in self.blocks() {
+            let rows = self.row_offsets[row]..self.row_offsets[row + 1];
+            let mut product = vec![0.0; rows.len()];
+            block.apply(
+                &x[self.col_offsets[col]..self.col_offsets[col + 1]],
+                &mut product,
+            );
+            y[rows].iter_mut().zip(&product).for_each(|(y, p)| *y += p);
+        }
+    }
+
+    fn has_transpose(&self) -> bool {
+        self.blocks().all(|(_, _, block)| block.has_transpose())
+    }
+
+    fn apply_transpose(&self, x: &[f64], y: &mut [f64]) {
+        y.iter_mut().for_each(|y| *y = 0.0);
+        for (row, col, block) in self.blocks() {
+            let cols = self.col_offsets[col]..self.col_offsets[col + 1];
+            let mut product = vec![0.0; cols.len()];
+            block.apply_transpose(
+                &x[self.row_offsets[row]..self.row_offsets[row + 1]],
+                &mut product,
+            );
+            y[cols].iter_mut().zip(&product).for_each(|(y, p)| *y += p);
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::data_hold::DataHold;
+    use crate::discretizations::dof_map::DofMap;
+    use crate::discretizations::sparsity::SparsityPattern;
+
+    // Generic use of the trait as a solver would
+    fn residual_norm<Operator: LinearOperator>(operator: &Operator, x: &[f64], b: &[f64]) -> f64 {
+        let mut y = vec![0.0; operator.n_rows()];
+        operator.apply(x, &mut y);
+        y.iter()
+            .zip(b.iter())
+            .map(|(y, b)| (y - b) * (y - b))
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    #[test]
+    fn test_linear_operator_sparse_csr() {
+        let dof_map = DofMap::new(DataHold::new(vec![0, 1, 1, 2], [2, 2]), 3);
+        let mut matrix = SparsityPattern::from_dofmap(&dof_map).to_csr(0.0);
+        for i in 0..3 {
+            *matrix.get_mut(i, i).unwrap() = 2.0;
+        }
+        *matrix.get_mut(0, 1).unwrap() = -1.0;
+        assert_eq!(LinearOperator::n_rows(&matrix), 3, "Wrong number of rows");
+        assert!(
+            residual_norm(&matrix, &[1.0, 1.0, 1.0], &[1.0, 2.0, 2.0]) < 1e-15,
+            "Wrong product through the trait"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_composed_operators() {
+        // A = [[1, 2], [0, 3]]
+        let a = SparseCSR::new(2, vec![0, 2, 3], vec![0, 1, 1], vec![1.0, 2.0, 3.0]);
+        let mut y = vec![0.0; 2];
+        a.apply_transpose(&[1.0, 1.0], &mut y);
+        assert_eq!(y, vec![1.0, 5.0], "Wrong transpose product");
+        let transposed = Transposed::new(&a);
+        Composition::new(&a, &transposed).apply(&[1.0, 1.0], &mut y);
+        assert_eq!(y, vec![11.0, 15.0], "Wrong product of A A^T");
+        // [[A, A^T], [0, A]] of size 4 from dynamic blocks
+        let block = BlockOperator::new(&[2, 2], &[2, 2])
+            .with_block(0, 0, &a)
+            .with_block(0, 1, &transposed)
+            .with_block(1, 1, &a);
+        assert!(block.has_transpose(), "Blocks all have a transpose");
+        let mut z = vec![0.0; 4];
+        block.apply(&[1.0, 0.0, 0.0, 1.0], &mut z);
+        assert_eq!(z, vec![1.0, 3.0, 2.0, 3.0], "Wrong block product");
+        block.apply_transpose(&[1.0, 0.0, 0.0, 1.0], &mut z);
+        assert_eq!(z, vec![1.0, 2.0, 1.0, 3.0], "Wrong block transpose product");
+        let diagonal = Diagonal::new(vec![2.0, 4.0]);
+        Composition::new(&diagonal, &a).apply(&[1.0, 1.0], &mut y);
+        assert_eq!(y, vec![6.0, 12.0], "Wrong diagonal scaling");
+        diagonal.solve(&[1.0, 1.0], &mut y);
+        assert_eq!(y, diagonal.inverse().values(), "Wrong diagonal inverse");
+        assert_eq!(
+            diagonal.to_csr().get(1, 1),
+            Some(&4.0),
+            "Wrong diagonal matrix"
+        );
+        assert!(
+            residual_norm(&(&block as &dyn LinearOperator), &[0.0; 4], &[0.0; 4]) == 0.0,
+            "Trait objects should be usable as operators"
+        );
+    }
+}
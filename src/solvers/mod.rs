@@ -1 +1,59 @@
+/// Linear operator and preconditioner abstractions with composed and block operators
+pub mod linear_operator;
 
+/// Stopping criteria shared by the iterative solvers
+pub mod stopping_criterion;
+
+/// Monitoring of the iterations of the solvers
+pub mod monitor;
+
+/// Condition number and extreme eigenvalue estimates
+pub mod conditioning;
+
+/// Dense LU and Cholesky factorizations
+pub mod dense;
+
+/// Krylov subspace iterative solvers and preconditioners
+pub mod krylov;
+
+/// CGLS and LSQR solvers of least squares problems
+pub mod least_squares;
+
+/// Jacobi, Gauss-Seidel and SOR relaxation solvers and smoothers
+pub mod relaxation;
+
+/// Smoothed aggregation algebraic multigrid
+pub mod multigrid;
+
+/// Overlapping additive Schwarz preconditioners with an optional coarse correction
+pub mod schwarz;
+
+/// Schur complement field split solver of 2x2 block systems
+pub mod field_split;
+
+/// Distributed vectors, matrices and Krylov solves over MPI processes or threads
+#[cfg(feature = "distributed")]
+pub mod distributed;
+
+/// KSP and SNES solves through PETSc
+#[cfg(feature = "petsc")]
+pub mod petsc;
+
+/// Sparse direct LU and Cholesky factorizations
+pub mod sparse_direct;
+
+/// Shift-invert Lanczos and Arnoldi eigenvalue solvers
+pub mod eigen;
+
+/// Newton and Picard solvers for systems of nonlinear equations
+pub mod nonlinear;
+
+/// Colored finite difference jacobians and jacobian-free Newton-Krylov
+pub mod finite_difference;
+
+/// Implicit theta method, BDF, IMEX, structural dynamics and explicit central difference time
+/// integrators
+pub mod time_integration;
+
+/// Runtime solver selection from builders or declarative configurations
+pub mod config;
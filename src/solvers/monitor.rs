@@ -0,0 +1,326 @@
+use crate::core::logging::trace;
+use crate::core::timers::Stopwatch;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+//--------------------------------------------------------------------------------------------------
+// # Traits
+//--------------------------------------------------------------------------------------------------
+
+/// Observer of the iterations of a solver
+///
+/// Closures taking an iteration record are monitors.
+pub trait Monitor {
+    /// Called with the initial state as iteration 0 and then after every iteration
+    fn observe(&self, record: &IterationRecord);
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// State of a solver after one of its iterations
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IterationRecord {
+    iteration: usize,
+    residual_norm: f64,
+    elapsed: Duration,
+}
+
+/// Monitor recording every iteration of the solves it observes
+///
+/// Clones share the same records so that one can be given to a solver and the other queried after
+/// the solve. Records of successive solves follow each other, every solve starting with an
+/// iteration 0.
+#[derive(Clone, Debug, Default)]
+pub struct ResidualHistory {
+    records: Rc<RefCell<Vec<IterationRecord>>>,
+}
+
+// Timer reporting the iterations of one solve to an optional monitor
+pub(crate) struct Recorder<'a> {
+    monitor: Option<&'a dyn Monitor>,
+    start: Stopwatch,
+}
+
+impl IterationRecord {
+    /// Number of iterations done, 0 for the initial state
+    pub fn iteration(&self) -> usize {
+        self.iteration
+    }
+
+    /// Norm of the residual the solver measures convergence with
+    pub fn residual_norm(&self) -> f64 {
+        self.residual_norm
+    }
+
+    /// Time elapsed since the start of the solve
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+impl ResidualHistory {
+    /// Empty history
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of records
+    pub fn len(&self) -> usize {
+        self.records.borrow().len()
+    }
+
+    /// Whether nothing was recorded
+    pub fn is_empty(&self) -> bool {
+        self.records.borrow().is_empty()
+    }
+
+    /// Copy of the records in the order of the iterations
+    pub fn records(&self) -> Vec<IterationRecord> {
+        self.records.borrow().clone()
+    }
+
+    /// Residual norms in the order of the iterations
+    pub fn residual_norms(&self) -> Vec<f64> {
+        self.records
+            .borrow()
+            .iter()
+            .map(|record| record.residual_norm)
+            .collect()
+    }
+
+    /// Forget the records
+    pub fn clear(&self) {
+        self.records.borrow_mut().clear();
+    }
+}
+
+impl Monitor for ResidualHistory {
+    fn observe(&self, record: &IterationRecord) {
+        self.records.borrow_mut().push(*record);
+    }
+}
+
+impl<F: Fn(&IterationRecord)> Monitor for F {
+    fn observe(&self, record: &IterationRecord) {
+        self(record)
+    }
+}
+
+impl<'a> Recorder<'a> {
+    // Start the timer of a solve
+    pub(crate) fn start(monitor: &'a Option<Rc<dyn Monitor>>) -> Self {
+        Recorder {
+            monitor: monitor.as_deref(),
+            start: Stopwatch::start(),
+        }
+    }
+
+    // Report an iteration to the log and to the monitor if there is one
+    pub(crate) fn record(&self, iteration: usize, residual_norm: f64) {
+        trace!("iteration {} residual {:e}", iteration, residual_norm);
+        if let Some(monitor) = self.monitor {
+            monitor.observe(&IterationRecord {
+                iteration,
+                residual_norm,
+                elapsed: self.start.elapsed(),
+            });
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::sparse_csr::SparseCSR;
+    use crate::solvers::krylov::{ConjugateGradient, Convergence, Gmres};
+    use crate::solvers::multigrid::Multigrid;
+    use crate::solvers::nonlinear::{FixedPointProblem, Newton, NonlinearProblem, Picard};
+    use crate::solvers::relaxation::{Relaxation, RelaxationMethod};
+    use std::cell::Cell;
+
+    // x^2 = 2
+    struct Square;
+
+    impl NonlinearProblem for Square {
+        fn size(&self) -> usize {
+            1
+        }
+
+        fn residual(&self, x: &[f64], r: &mut [f64]) {
+            r[0] = x[0] * x[0] - 2.0;
+        }
+
+        fn jacobian(&self, x: &[f64]) -> SparseCSR<f64> {
+            SparseCSR::new(1, vec![0, 1], vec![0], vec![2.0 * x[0]])
+        }
+    }
+
+    // x = cos(x)
+    struct Cosine;
+
+    impl FixedPointProblem for Cosine {
+        fn size(&self) -> usize {
+            1
+        }
+
+        fn update(&self, x: &[f64], next: &mut [f64]) {
+            next[0] = x[0].cos();
+        }
+    }
+
+    // 1D laplacian of the given size
+    fn laplacian(n: usize) -> SparseCSR<f64> {
+        let mut offsets = vec![0];
+        let (mut cols, mut values) = (Vec::new(), Vec::new());
+        for i in 0..n {
+            for j in i.saturating_sub(1)..(i + 2).min(n) {
+                cols.push(j);
+                values.push(if i == j { 2.0 } else { -1.0 });
+            }
+            offsets.push(cols.len());
+        }
+        SparseCSR::new(n, offsets, cols, values)
+    }
+
+    // Residual norms recorded by a new history during a solve, with the convergence of the solve
+    fn recorded<F: FnOnce(ResidualHistory) -> Convergence>(solve: F) -> (Vec<f64>, Convergence) {
+        let history = ResidualHistory::new();
+        let convergence = solve(history.clone());
+        (history.residual_norms(), convergence)
+    }
+
+    #[test]
+    fn test_residual_history() {
+        let n: usize = 20;
+        let matrix = laplacian(n);
+        let history = ResidualHistory::new();
+        let mut x = vec![0.0; n];
+        let convergence = ConjugateGradient::new()
+            .with_monitor(history.clone())
+            .solve(&matrix, &vec![1.0; n], &mut x);
+        let records = history.records();
+        assert_eq!(
+            records.len(),
+            convergence.iterations() + 1,
+            "Every iteration should be recorded"
+        );
+        assert!(
+            records.iter().enumerate().all(|(i, r)| r.iteration() == i),
+            "Wrong iteration numbers"
+        );
+        assert!(
+            records.windows(2).all(|r| r[0].elapsed() <= r[1].elapsed()),
+            "Elapsed times should increase"
+        );
+        assert_eq!(
+            history.residual_norms().last().copied(),
+            Some(convergence.residual_norm()),
+            "Last record should be the final residual"
+        );
+        // Closures are monitors and records of successive solves accumulate
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        history.clear();
+        let gmres =
+            Gmres::new().with_monitor(move |_: &IterationRecord| counter.set(counter.get() + 1));
+        x.iter_mut().for_each(|x| *x = 0.0);
+        let convergence = gmres.solve(&matrix, &vec![1.0; n], &mut x);
+        assert_eq!(
+            calls.get(),
+            convergence.iterations() + 1,
+            "Closure was not called"
+        );
+        let newton = Newton::new().with_monitor(history.clone());
+        for _ in 0..2 {
+            let convergence = newton.solve(&Square, &mut [1.0]);
+            assert!(convergence.converged(), "Newton did not converge");
+        }
+        let starts = history
+            .records()
+            .iter()
+            .filter(|r| r.iteration() == 0)
+            .count();
+        assert_eq!(starts, 2, "Both solves should have been recorded");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_residual_history_solvers() {
+        let n: usize = 20;
+        let matrix = laplacian(n);
+        let rhs = vec![1.0; n];
+        let solves: Vec<(Vec<f64>, Convergence)> = vec![
+            recorded(|history| {
+                Relaxation::new(&matrix, RelaxationMethod::GaussSeidel)
+                    .with_monitor(history)
+                    .solve(&rhs, &mut vec![0.0; n])
+            }),
+            recorded(|history| {
+                Multigrid::new(&matrix)
+                    .with_monitor(history)
+                    .solve(&rhs, &mut vec![0.0; n])
+            }),
+            recorded(|history| {
+                Picard::new()
+                    .with_monitor(history)
+                    .solve(&Cosine, &mut [0.0])
+            }),
+        ];
+        for (norms, convergence) in solves {
+            assert!(convergence.converged(), "Solver did not converge");
+            assert_eq!(
+                norms.len(),
+                convergence.iterations() + 1,
+                "Every iteration should be recorded"
+            );
+            assert_eq!(
+                norms.last().copied(),
+                Some(convergence.residual_norm()),
+                "Last record should be the final residual"
+            );
+        }
+        // Unconverged solves are recorded up to their last iteration
+        let (norms, convergence) = recorded(|history| {
+            ConjugateGradient::new()
+                .with_max_iterations(3)
+                .with_monitor(history)
+                .solve(&matrix, &rhs, &mut vec![0.0; n])
+        });
+        assert!(!convergence.converged(), "Solve should not converge");
+        assert_eq!(norms.len(), 4, "Wrong number of records");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_residual_history_clear() {
+        let history = ResidualHistory::new();
+        assert!(history.is_empty(), "New history should be empty");
+        let shared = history.clone();
+        Newton::new()
+            .with_monitor(shared.clone())
+            .solve(&Square, &mut [1.0]);
+        assert!(!history.is_empty(), "Clones should share their records");
+        assert_eq!(
+            history.len(),
+            shared.len(),
+            "Clones should share their records"
+        );
+        assert_eq!(
+            history.records()[0].residual_norm(),
+            1.0,
+            "Initial record should be the initial residual"
+        );
+        shared.clear();
+        assert!(
+            history.is_empty(),
+            "Clearing a clone should clear the history"
+        );
+    }
+}
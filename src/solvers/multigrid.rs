@@ -0,0 +1,595 @@
+use super::dense::LU;
+use super::krylov::{distance, norm, Convergence};
+use super::linear_operator::Preconditioner;
+use super::monitor::{Monitor, Recorder};
+use super::relaxation::{Relaxation, RelaxationMethod};
+use super::stopping_criterion::StoppingCriterion;
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::data_traits::DataMutator;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use std::rc::Rc;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Setup parameters of the smoothed aggregation algebraic multigrid
+///
+/// Unknowns i and j are strongly coupled when |a_ij| >= threshold sqrt(|a_ii a_jj|) and aggregates
+/// are grown from strongly coupled neighbourhoods. The tentative piecewise constant prolongation is
+/// then smoothed by one damped Jacobi step. Coarsening stops when a level has at most the coarse
+/// size unknowns, when the maximum number of levels is reached or when aggregation stalls. By
+/// default the threshold is 0.08, the coarse size 50 and at most 10 levels are built.
+pub struct SmoothedAggregation {
+    strength_threshold: f64,
+    coarse_size: usize,
+    max_levels: usize,
+}
+
+/// Recursion pattern of the multigrid cycles
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum CycleType {
+    /// One coarse correction per level
+    V,
+    /// Two coarse corrections per level
+    W,
+}
+
+/// Multigrid hierarchy applying cycles as a solver or a preconditioner
+///
+/// The hierarchy is either built algebraically by smoothed aggregation or geometrically from the
+/// prolongations between nested meshes (see the refinement module of the discretizations), the
+/// coarse matrices being the Galerkin products R A P with R the transposed prolongation. Every
+/// level is smoothed before and after the coarse correction (symmetric Gauss-Seidel with a single
+/// sweep by default, which keeps the cycles symmetric) and the coarsest level is solved with a
+/// dense LU factorization. V-cycles are used by default. The standalone solver uses the stopping
+/// criterion of the Krylov solvers with a relative tolerance of 1e-10, an absolute tolerance of 0
+/// and at most 100 cycles.
+pub struct Multigrid {
+    matrices: Vec<SparseCSR<f64>>,
+    prolongations: Vec<SparseCSR<f64>>,
+    restrictions: Vec<SparseCSR<f64>>,
+    coarse_solver: Option<LU>,
+    cycle_type: CycleType,
+    smoother: RelaxationMethod,
+    sweeps: usize,
+    stopping: StoppingCriterion,
+    monitor: Option<Rc<dyn Monitor>>,
+}
+
+impl Default for SmoothedAggregation {
+    fn default() -> Self {
+        SmoothedAggregation {
+            strength_threshold: 0.08,
+            coarse_size: 50,
+            max_levels: 10,
+        }
+    }
+}
+
+impl SmoothedAggregation {
+    /// Setup with the default parameters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the threshold above which couplings are strong
+    pub fn with_strength_threshold(mut self, threshold: f64) -> Self {
+        self.strength_threshold = threshold;
+        self
+    }
+
+    /// Set the number of unknowns below which the coarsening stops
+    pub fn with_coarse_size(mut self, coarse_size: usize) -> Self {
+        self.coarse_size = coarse_size;
+        self
+    }
+
+    /// Set the maximum number of levels of the hierarchy
+    pub fn with_max_levels(mut self, max_levels: usize) -> Self {
+        assert!(max_levels > 0, "Multigrid needs at least one level");
+        self.max_levels = max_levels;
+        self
+    }
+
+    /// Build the multigrid hierarchy of a square matrix
+    pub fn build(&self, matrix: &SparseCSR<f64>) -> Multigrid {
+        assert_eq!(
+            matrix.n_rows(),
+            matrix.n_cols(),
+            "Multigrid needs a square matrix"
+        );
+        let mut matrices = vec![matrix.clone()];
+        let mut prolongations = Vec::new();
+        while matrices.len() < self.max_levels {
+            let fine = matrices.last().unwrap();
+            if fine.n_rows() <= self.coarse_size {
+                break;
+            }
+            let (aggregates, n_aggregates) = aggregate(fine, self.strength_threshold);
+            if n_aggregates == fine.n_rows() || n_aggregates == 0 {
+                break;
+            }
+            let prolongation = smoothed_prolongation(fine, &aggregates, n_aggregates);
+            matrices.push(galerkin(fine, &prolongation));
+            prolongations.push(prolongation);
+        }
+        Multigrid::from_hierarchy(matrices, prolongations)
+    }
+}
+
+impl Multigrid {
+    /// Hierarchy of a matrix with the default smoothed aggregation parameters
+    pub fn new(matrix: &SparseCSR<f64>) -> Self {
+        SmoothedAggregation::new().build(matrix)
+    }
+
+    /// Geometric hierarchy of a matrix from the prolongations of the coarser levels
+    ///
+    /// The first prolongation maps the level just below the matrix onto it, the next ones go on
+    /// towards the coarsest level.
+    pub fn from_prolongations(matrix: &SparseCSR<f64>, prolongations: Vec<SparseCSR<f64>>) -> Self {
+        assert_eq!(
+            matrix.n_rows(),
+            matrix.n_cols(),
+            "Multigrid needs a square matrix"
+        );
+        let mut matrices = vec![matrix.clone()];
+        for prolongation in prolongations.iter() {
+            let fine = matrices.last().unwrap();
+            assert_eq!(
+                prolongation.n_rows(),
+                fine.n_rows(),
+                "Prolongation does not match the size of the finer level"
+            );
+            matrices.push(galerkin(fine, prolongation));
+        }
+        Multigrid::from_hierarchy(matrices, prolongations)
+    }
+
+    fn from_hierarchy(matrices: Vec<SparseCSR<f64>>, prolongations: Vec<SparseCSR<f64>>) -> Self {
+        let restrictions = prolongations.iter().map(|p| p.transpose()).collect();
+        let coarse_solver = LU::new(&dense(matrices.last().unwrap()));
+        Multigrid {
+            matrices,
+            prolongations,
+            restrictions,
+            coarse_solver,
+            cycle_type: CycleType::V,
+            smoother: RelaxationMethod::SymmetricSor(1.0),
+            sweeps: 1,
+            stopping: StoppingCriterion::new(1e-10, 0.0, 100),
+            monitor: None,
+        }
+    }
+
+    /// Set the recursion pattern of the cycles
+    pub fn with_cycle_type(mut self, cycle_type: CycleType) -> Self {
+        self.cycle_type = cycle_type;
+        self
+    }
+
+    /// Set the relaxation method of the smoother on every level
+    pub fn with_smoother(mut self, smoother: RelaxationMethod) -> Self {
+        self.smoother = smoother;
+        self
+    }
+
+    /// Set the number of smoothing sweeps before and after the coarse correction
+    pub fn with_sweeps(mut self, sweeps: usize) -> Self {
+        self.sweeps = sweeps;
+        self
+    }
+
+    /// Set the tolerance on the residual relative to the right hand side
+    pub fn with_relative_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_relative_tolerance(tolerance);
+        self
+    }
+
+    /// Set the tolerance on the norm of the residual
+    pub fn with_absolute_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_absolute_tolerance(tolerance);
+        self
+    }
+
+    /// Set the maximum number of cycles of the standalone solver
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.stopping = self.stopping.with_max_iterations(max_iterations);
+        self
+    }
+
+    /// Replace the whole stopping criterion, the reference norm being the one of the right hand
+    /// side
+    pub fn with_stopping_criterion(mut self, stopping: StoppingCriterion) -> Self {
+        self.stopping = stopping;
+        self
+    }
+
+    /// Set a monitor observing the residual norm of every iteration
+    pub fn with_monitor<M: Monitor + 'static>(mut self, monitor: M) -> Self {
+        self.monitor = Some(Rc::new(monitor));
+        self
+    }
+
+    /// Number of levels including the finest one
+    pub fn n_levels(&self) -> usize {
+        self.matrices.len()
+    }
+
+    /// Matrix of a level, 0 being the finest
+    pub fn matrix(&self, level: usize) -> &SparseCSR<f64> {
+        &self.matrices[level]
+    }
+
+    /// Prolongation from a level to the finer one above it
+    pub fn prolongation(&self, level: usize) -> &SparseCSR<f64> {
+        assert!(level > 0, "The finest level has no prolongation");
+        &self.prolongations[level - 1]
+    }
+
+    /// Apply one cycle to x for the system A x = b
+    pub fn cycle(&self, rhs: &[f64], x: &mut [f64]) {
+        let n = self.matrices[0].n_rows();
+        assert!(
+            rhs.len() == n && x.len() == n,
+            "Vectors do not match the size of the matrix"
+        );
+        self.cycle_level(0, rhs, x);
+    }
+
+    /// Solve A x = b by repeated cycles starting from the values in x
+    pub fn solve(&self, rhs: &[f64], x: &mut [f64]) -> Convergence {
+        let matrix = &self.matrices[0];
+        let mut test = self.stopping.start(norm(rhs));
+        let mut residual = vec![0.0; rhs.len()];
+        let residual_norm = |x: &[f64], residual: &mut [f64]| {
+            matrix.apply(x, residual);
+            residual.iter_mut().zip(rhs).for_each(|(r, b)| *r = b - *r);
+            norm(residual)
+        };
+        let mut current = residual_norm(x, &mut residual);
+        let mut iterations = 0;
+        let mut previous = x.to_vec();
+        let mut update = None;
+        let recorder = Recorder::start(&self.monitor);
+        let reason = loop {
+            recorder.record(iterations, current);
+            if let Some(reason) = test.check(iterations, current, update) {
+                break reason;
+            }
+            previous.copy_from_slice(x);
+            self.cycle(rhs, x);
+            iterations += 1;
+            current = residual_norm(x, &mut residual);
+            update = Some((distance(x, &previous), norm(x)));
+        };
+        Convergence {
+            reason,
+            iterations,
+            residual_norm: current,
+            spectrum: None,
+        }
+    }
+
+    fn cycle_level(&self, level: usize, rhs: &[f64], x: &mut [f64]) {
+        let matrix = &self.matrices[level];
+        if level + 1 == self.matrices.len() {
+            match &self.coarse_solver {
+                Some(lu) => lu.solve(rhs, x),
+                // Singular coarse matrices (e.g. pure Neumann problems) are only relaxed
+                None => Relaxation::new(matrix, RelaxationMethod::SymmetricSor(1.0))
+                    .with_sweeps(20)
+                    .smooth(rhs, x),
+            }
+            return;
+        }
+        // Smoothers are cheap to build compared to a sweep so they are not stored
+        let smoother = Relaxation::new(matrix, self.smoother).with_sweeps(self.sweeps);
+        smoother.smooth(rhs, x);
+        let mut residual = vec![0.0; rhs.len()];
+        matrix.apply(x, &mut residual);
+        residual.iter_mut().zip(rhs).for_each(|(r, b)| *r = b - *r);
+        let restriction = &self.restrictions[level];
+        let mut coarse_rhs = vec![0.0; restriction.n_rows()];
+        restriction.apply(&residual, &mut coarse_rhs);
+        let mut correction = vec![0.0; coarse_rhs.len()];
+        self.cycle_level(level + 1, &coarse_rhs, &mut correction);
+        if self.cycle_type == CycleType::W && level + 2 < self.matrices.len() {
+            self.cycle_level(level + 1, &coarse_rhs, &mut correction);
+        }
+        self.prolongations[level].apply(&correction, &mut residual);
+        x.iter_mut().zip(&residual).for_each(|(x, c)| *x += c);
+        smoother.smooth(rhs, x);
+    }
+}
+
+impl Preconditioner for Multigrid {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        z.iter_mut().for_each(|z| *z = 0.0);
+        self.cycle(r, z);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Aggregate of every unknown and number of aggregates from the strong couplings of the matrix
+fn aggregate(matrix: &SparseCSR<f64>, threshold: f64) -> (Vec<usize>, usize) {
+    let n = matrix.n_rows();
+    let diagonal: Vec<f64> = (0..n)
+        .map(|i| matrix.get(i, i).copied().unwrap_or(0.0).abs())
+        .collect();
+    let strong: Vec<Vec<usize>> = (0..n)
+        .map(|i| {
+            let (cols, vals) = matrix.row(i);
+            cols.iter()
+                .zip(vals)
+                .filter(|(j, a)| {
+                    **j != i && a.abs() >= threshold * (diagonal[i] * diagonal[**j]).sqrt()
+                })
+                .map(|(j, _)| *j)
+                .collect()
+        })
+        .collect();
+    let mut aggregates = vec![usize::MAX; n];
+    let mut n_aggregates = 0;
+    // Whole neighbourhoods which are still free become aggregates
+    for i in 0..n {
+        if aggregates[i] == usize::MAX && strong[i].iter().all(|j| aggregates[*j] == usize::MAX) {
+            aggregates[i] = n_aggregates;
+            for j in strong[i].iter() {
+                aggregates[*j] = n_aggregates;
+            }
+            n_aggregates += 1;
+        }
+    }
+    // Left over unknowns join an aggregate they are strongly coupled to
+    let first_pass = aggregates.clone();
+    for i in 0..n {
+        if aggregates[i] == usize::MAX {
+            if let Some(j) = strong[i].iter().find(|j| first_pass[**j] != usize::MAX) {
+                aggregates[i] = first_pass[*j];
+            }
+        }
+    }
+    // Remaining unknowns form new aggregates with their free neighbours
+    for i in 0..n {
+        if aggregates[i] == usize::MAX {
+            aggregates[i] = n_aggregates;
+            for j in strong[i].iter() {
+                if aggregates[*j] == usize::MAX {
+                    aggregates[*j] = n_aggregates;
+                }
+            }
+            n_aggregates += 1;
+        }
+    }
+    (aggregates, n_aggregates)
+}
+
+// Tentative piecewise constant prolongation smoothed by damped Jacobi (I - w D^-1 A) T
+fn smoothed_prolongation(
+    matrix: &SparseCSR<f64>,
+    aggregates: &[usize],
+    n_aggregates: usize,
+) -> SparseCSR<f64> {
+    let n = matrix.n_rows();
+    let tentative = SparseCSR::new(
+        n_aggregates,
+        (0..=n).collect(),
+        aggregates.to_vec(),
+        vec![1.0; n],
+    );
+    let mut scaled = matrix.clone();
+    // Gershgorin bound on the spectral radius of D^-1 A for the damping factor 4 / (3 rho)
+    let mut spectral_radius: f64 = 0.0;
+    for i in 0..n {
+        let diagonal = matrix.get(i, i).copied().unwrap_or(0.0);
+        assert!(diagonal != 0.0, "Multigrid needs a non zero diagonal");
+        let range = matrix.row_offsets()[i]..matrix.row_offsets()[i + 1];
+        let row = &mut scaled.values_mut()[range];
+        row.iter_mut().for_each(|a| *a /= diagonal);
+        spectral_radius = spectral_radius.max(row.iter().map(|a| a.abs()).sum());
+    }
+    let damping = 4.0 / (3.0 * spectral_radius);
+    let mut prolongation = scaled.multiply(&tentative);
+    prolongation
+        .values_mut()
+        .iter_mut()
+        .for_each(|p| *p *= -damping);
+    for (i, aggregate) in aggregates.iter().enumerate() {
+        *prolongation.get_mut(i, *aggregate).unwrap() += 1.0;
+    }
+    prolongation
+}
+
+// Coarse matrix R A P with R the transposed prolongation
+fn galerkin(fine: &SparseCSR<f64>, prolongation: &SparseCSR<f64>) -> SparseCSR<f64> {
+    prolongation
+        .transpose()
+        .multiply(&fine.multiply(prolongation))
+}
+
+fn dense(matrix: &SparseCSR<f64>) -> DataHold<f64, [usize; 2]> {
+    let n = matrix.n_rows();
+    let mut dense = DataHold::new(vec![0.0; n * n], [n, n]);
+    for i in 0..n {
+        let (cols, vals) = matrix.row(i);
+        for (j, a) in cols.iter().zip(vals) {
+            *dense.multi_index_mut([i, *j]) = *a;
+        }
+    }
+    dense
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solvers::krylov::ConjugateGradient;
+
+    // Five point Laplacian on a square grid with Dirichlet conditions
+    fn build_laplacian(side: usize) -> SparseCSR<f64> {
+        let mut row_offsets = vec![0];
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+        for i in 0..side * side {
+            let (x, y) = (i % side, i / side);
+            let mut row = vec![(i, 4.0)];
+            if y > 0 {
+                row.push((i - side, -1.0));
+            }
+            if x > 0 {
+                row.push((i - 1, -1.0));
+            }
+            if x + 1 < side {
+                row.push((i + 1, -1.0));
+            }
+            if y + 1 < side {
+                row.push((i + side, -1.0));
+            }
+            row.sort_by_key(|(j, _)| *j);
+            for (j, a) in row {
+                col_indices.push(j);
+                values.push(a);
+            }
+            row_offsets.push(col_indices.len());
+        }
+        SparseCSR::new(side * side, row_offsets, col_indices, values)
+    }
+
+    #[test]
+    fn test_smoothed_aggregation() {
+        let side = 32;
+        let n = side * side;
+        let matrix = build_laplacian(side);
+        let multigrid = Multigrid::new(&matrix);
+        assert!(
+            multigrid.n_levels() > 2,
+            "Hierarchy should have several levels"
+        );
+        for level in 1..multigrid.n_levels() {
+            let (fine, coarse) = (multigrid.matrix(level - 1), multigrid.matrix(level));
+            assert!(
+                coarse.n_rows() < fine.n_rows() / 3,
+                "Coarsening is too slow"
+            );
+            assert_eq!(
+                multigrid.prolongation(level).n_cols(),
+                coarse.n_rows(),
+                "Prolongation does not match the coarse level"
+            );
+        }
+        assert!(
+            multigrid.matrix(multigrid.n_levels() - 1).n_rows() <= 50,
+            "Coarsest level is too large"
+        );
+        let expected: Vec<f64> = (0..n).map(|i| ((i * i) % 11) as f64).collect();
+        let mut rhs = vec![0.0; n];
+        matrix.apply(&expected, &mut rhs);
+        let mut x = vec![0.0; n];
+        let standalone = multigrid.solve(&rhs, &mut x);
+        assert!(standalone.converged(), "Multigrid did not converge");
+        assert!(standalone.iterations() < 40, "Too many V-cycles");
+        for (x, e) in x.iter().zip(&expected) {
+            assert!((x - e).abs() < 1e-7, "Wrong solution");
+        }
+        let solver = ConjugateGradient::new().with_relative_tolerance(1e-10);
+        let mut x = vec![0.0; n];
+        let plain = solver.solve(&matrix, &rhs, &mut x);
+        let mut x = vec![0.0; n];
+        let preconditioned = solver.solve_preconditioned(&matrix, &multigrid, &rhs, &mut x);
+        assert!(
+            preconditioned.converged(),
+            "Preconditioned CG did not converge"
+        );
+        assert!(
+            3 * preconditioned.iterations() < plain.iterations(),
+            "Multigrid should cut the iterations of CG"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_geometric_multigrid() {
+        // Nested grids of the 1D Laplacian with linear interpolation between them
+        let levels = 6;
+        let size = |level: usize| -> usize { (1 << (levels - level)) - 1 };
+        let prolongations: Vec<SparseCSR<f64>> = (0..levels - 1)
+            .map(|level| {
+                let (n_fine, n_coarse) = (size(level), size(level + 1));
+                let mut row_offsets = vec![0];
+                let mut col_indices = Vec::new();
+                let mut values = Vec::new();
+                for i in 0..n_fine {
+                    if i % 2 == 1 {
+                        col_indices.push(i / 2);
+                        values.push(1.0);
+                    } else {
+                        for j in [(i / 2).checked_sub(1), Some(i / 2)].into_iter().flatten() {
+                            if j < n_coarse {
+                                col_indices.push(j);
+                                values.push(0.5);
+                            }
+                        }
+                    }
+                    row_offsets.push(col_indices.len());
+                }
+                SparseCSR::new(n_coarse, row_offsets, col_indices, values)
+            })
+            .collect();
+        let n = size(0);
+        let mut row_offsets = vec![0];
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+        for i in 0..n {
+            for j in i.saturating_sub(1)..(i + 2).min(n) {
+                col_indices.push(j);
+                values.push(if i == j { 2.0 } else { -1.0 });
+            }
+            row_offsets.push(col_indices.len());
+        }
+        let matrix = SparseCSR::new(n, row_offsets, col_indices, values);
+        let expected: Vec<f64> = (0..n).map(|i| ((i * i) % 5) as f64).collect();
+        let mut rhs = vec![0.0; n];
+        matrix.apply(&expected, &mut rhs);
+        let mut iterations = Vec::new();
+        for cycle_type in [CycleType::V, CycleType::W] {
+            let multigrid = Multigrid::from_prolongations(&matrix, prolongations.clone())
+                .with_cycle_type(cycle_type)
+                .with_sweeps(2);
+            assert_eq!(multigrid.n_levels(), levels, "Wrong number of levels");
+            assert_eq!(
+                multigrid.matrix(levels - 1).n_rows(),
+                1,
+                "Wrong coarsest size"
+            );
+            let mut x = vec![0.0; n];
+            let result = multigrid.solve(&rhs, &mut x);
+            assert!(
+                result.converged(),
+                "{:?}-cycles did not converge",
+                cycle_type
+            );
+            for (x, e) in x.iter().zip(&expected) {
+                assert!((x - e).abs() < 1e-7, "Wrong solution");
+            }
+            iterations.push(result.iterations());
+        }
+        assert!(iterations[0] < 15, "Too many V-cycles");
+        assert!(
+            iterations[1] <= iterations[0],
+            "W-cycles should not need more iterations"
+        );
+    }
+}
@@ -0,0 +1,666 @@
+use super::krylov::{distance, dot, norm, Convergence};
+use super::monitor::{Monitor, Recorder};
+use super::sparse_direct::SparseLU;
+use super::stopping_criterion::{StopReason, StoppingCriterion};
+use crate::core::arrays::sparse_csr::SparseCSR;
+use std::rc::Rc;
+
+//--------------------------------------------------------------------------------------------------
+// # Traits
+//--------------------------------------------------------------------------------------------------
+
+/// System of nonlinear equations F(x) = 0 with a sparse jacobian
+pub trait NonlinearProblem {
+    /// Number of unknowns and equations
+    fn size(&self) -> usize;
+
+    /// Compute r = F(x)
+    fn residual(&self, x: &[f64], r: &mut [f64]);
+
+    /// Jacobian matrix of F at x
+    fn jacobian(&self, x: &[f64]) -> SparseCSR<f64>;
+}
+
+/// Fixed point problem x = G(x), for instance the solution of a linear system whose coefficients
+/// are frozen at the previous iterate
+pub trait FixedPointProblem {
+    /// Number of unknowns
+    fn size(&self) -> usize;
+
+    /// Compute next = G(x)
+    fn update(&self, x: &[f64], next: &mut [f64]);
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Strategy making Newton converge from initial guesses far from the solution
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Globalization {
+    /// Full Newton steps
+    None,
+    /// Backtracking of the Newton step until the Armijo condition holds on 1/2 |F|^2
+    LineSearch,
+    /// Dogleg steps between the steepest descent and Newton directions in a trust region of the
+    /// given initial radius
+    TrustRegion(f64),
+    /// Pseudo-transient continuation following dx/dt = -F(x) to its steady state by full steps of
+    /// (I / δ + J) s = -F, the pseudo time step δ starting from the given value and scaled by the
+    /// decrease of the residual norm at every iteration (switched evolution relaxation) so that
+    /// Newton is recovered close to the solution
+    PseudoTransient(f64),
+}
+
+/// Newton solver with the jacobian systems solved by the sparse LU
+///
+/// Iterations stop when the euclidean norm of the residual falls below the largest of the absolute
+/// tolerance and the relative tolerance times the norm of the initial residual. By default the
+/// relative tolerance is 1e-10, the absolute tolerance 1e-14, at most 50 iterations are done and
+/// backtracking line search is used. Iterations also stop without convergence when the jacobian
+/// is singular or when no acceptable step can be found.
+pub struct Newton {
+    stopping: StoppingCriterion,
+    globalization: Globalization,
+    monitor: Option<Rc<dyn Monitor>>,
+}
+
+/// Picard (fixed point) iteration x <- x + ω (G(x) - x) with under-relaxation and optional Aitken
+/// acceleration
+///
+/// Iterations stop when the euclidean norm of the update G(x) - x falls below the largest of the
+/// absolute tolerance and the relative tolerance times the norm of the initial update, which is
+/// the residual norm reported. By default the relative tolerance is 1e-10, the absolute tolerance
+/// 1e-14, at most 100 iterations are done, the relaxation factor is 1 and Aitken acceleration is
+/// not used.
+pub struct Picard {
+    stopping: StoppingCriterion,
+    relaxation: f64,
+    aitken: bool,
+    monitor: Option<Rc<dyn Monitor>>,
+}
+
+// Point tried by a globalization and its residual
+struct Trial {
+    x: Vec<f64>,
+    r: Vec<f64>,
+}
+
+impl Default for Newton {
+    fn default() -> Self {
+        Newton {
+            stopping: StoppingCriterion::new(1e-10, 1e-14, 50),
+            globalization: Globalization::LineSearch,
+            monitor: None,
+        }
+    }
+}
+
+impl Newton {
+    /// Solver with the default parameters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the tolerance on the residual relative to the initial one
+    pub fn with_relative_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_relative_tolerance(tolerance);
+        self
+    }
+
+    /// Set the tolerance on the norm of the residual
+    pub fn with_absolute_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_absolute_tolerance(tolerance);
+        self
+    }
+
+    /// Set the maximum number of iterations
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.stopping = self.stopping.with_max_iterations(max_iterations);
+        self
+    }
+
+    /// Replace the whole stopping criterion, the reference norm being the one of the initial
+    /// residual
+    pub fn with_stopping_criterion(mut self, stopping: StoppingCriterion) -> Self {
+        self.stopping = stopping;
+        self
+    }
+
+    /// Set the globalization strategy, the trust region radius and the initial pseudo time step
+    /// having to be positive and finite
+    pub fn with_globalization(mut self, globalization: Globalization) -> Self {
+        if let Globalization::TrustRegion(value) | Globalization::PseudoTransient(value) =
+            globalization
+        {
+            assert!(
+                value > 0.0 && value.is_finite(),
+                "{:?} needs a positive and finite parameter",
+                globalization
+            );
+        }
+        self.globalization = globalization;
+        self
+    }
+
+    /// Set a monitor observing the residual norm of every iteration
+    pub fn with_monitor<M: Monitor + 'static>(mut self, monitor: M) -> Self {
+        self.monitor = Some(Rc::new(monitor));
+        self
+    }
+
+    /// Solve F(x) = 0 starting from the values in x
+    pub fn solve<Problem>(&self, problem: &Problem, x: &mut [f64]) -> Convergence
+    where
+        Problem: NonlinearProblem,
+    {
+        let n = problem.size();
+        assert_eq!(x.len(), n, "Vector does not match the size of the problem");
+        let mut r = vec![0.0; n];
+        problem.residual(x, &mut r);
+        let mut residual_norm = norm(&r);
+        let mut test = self.stopping.start(residual_norm);
+        let (mut radius, mut pseudo_step) = match self.globalization {
+            Globalization::TrustRegion(radius) => (radius, 0.0),
+            Globalization::PseudoTransient(pseudo_step) => (0.0, pseudo_step),
+            _ => (0.0, 0.0),
+        };
+        let mut step = vec![0.0; n];
+        let mut trial = Trial {
+            x: vec![0.0; n],
+            r: vec![0.0; n],
+        };
+        let mut previous = x.to_vec();
+        let mut iterations = 0;
+        let mut update = None;
+        let recorder = Recorder::start(&self.monitor);
+        let reason = loop {
+            recorder.record(iterations, residual_norm);
+            if let Some(reason) = test.check(iterations, residual_norm, update) {
+                break reason;
+            }
+            iterations += 1;
+            let jacobian = problem.jacobian(x);
+            let shifted;
+            let system = if let Globalization::PseudoTransient(_) = self.globalization {
+                shifted = jacobian.add(1.0 / pseudo_step, &identity(n));
+                &shifted
+            } else {
+                &jacobian
+            };
+            let Some(lu) = SparseLU::new(system) else {
+                break StopReason::Breakdown;
+            };
+            previous.copy_from_slice(x);
+            lu.solve(&r, &mut step);
+            step.iter_mut().for_each(|s| *s = -*s);
+            let accepted = match self.globalization {
+                Globalization::None => {
+                    x.iter_mut().zip(&step).for_each(|(x, s)| *x += s);
+                    problem.residual(x, &mut r);
+                    true
+                }
+                Globalization::LineSearch => line_search(
+                    |x: &[f64], r: &mut [f64]| problem.residual(x, r),
+                    x,
+                    &mut r,
+                    &step,
+                    &mut trial.x,
+                    &mut trial.r,
+                ),
+                Globalization::TrustRegion(_) => {
+                    let (accepted, next_radius) =
+                        dogleg(problem, &jacobian, x, &mut r, &mut step, &mut trial, radius);
+                    radius = next_radius;
+                    // Rejected steps shrink the region and are retried from the same point
+                    accepted || radius > f64::EPSILON * norm(x).max(1.0)
+                }
+                Globalization::PseudoTransient(_) => {
+                    x.iter_mut().zip(&step).for_each(|(x, s)| *x += s);
+                    problem.residual(x, &mut r);
+                    let next_norm = norm(&r);
+                    if next_norm > 0.0 {
+                        pseudo_step *= residual_norm / next_norm;
+                    }
+                    next_norm.is_finite()
+                }
+            };
+            if !accepted {
+                break StopReason::Breakdown;
+            }
+            residual_norm = norm(&r);
+            // Rejected trust region steps leave x unchanged
+            let change = distance(x, &previous);
+            update = (change > 0.0).then(|| (change, norm(x)));
+        };
+        Convergence {
+            reason,
+            iterations,
+            residual_norm,
+            spectrum: None,
+        }
+    }
+}
+
+impl Default for Picard {
+    fn default() -> Self {
+        Picard {
+            stopping: StoppingCriterion::new(1e-10, 1e-14, 100),
+            relaxation: 1.0,
+            aitken: false,
+            monitor: None,
+        }
+    }
+}
+
+impl Picard {
+    /// Solver with the default parameters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the tolerance on the update relative to the initial one
+    pub fn with_relative_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_relative_tolerance(tolerance);
+        self
+    }
+
+    /// Set the tolerance on the norm of the update
+    pub fn with_absolute_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_absolute_tolerance(tolerance);
+        self
+    }
+
+    /// Set the maximum number of iterations
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.stopping = self.stopping.with_max_iterations(max_iterations);
+        self
+    }
+
+    /// Replace the whole stopping criterion, the reference norm being the one of the initial
+    /// update
+    pub fn with_stopping_criterion(mut self, stopping: StoppingCriterion) -> Self {
+        self.stopping = stopping;
+        self
+    }
+
+    /// Set the relaxation factor (of the first iteration only with Aitken acceleration)
+    pub fn with_relaxation(mut self, relaxation: f64) -> Self {
+        self.relaxation = relaxation;
+        self
+    }
+
+    /// Set whether the relaxation factor is updated by Aitken's delta squared process
+    pub fn with_aitken(mut self, aitken: bool) -> Self {
+        self.aitken = aitken;
+        self
+    }
+
+    /// Set a monitor observing the update norm of every iteration
+    pub fn with_monitor<M: Monitor + 'static>(mut self, monitor: M) -> Self {
+        self.monitor = Some(Rc::new(monitor));
+        self
+    }
+
+    /// Solve x = G(x) starting from the values in x
+    pub fn solve<Problem>(&self, problem: &Problem, x: &mut [f64]) -> Convergence
+    where
+        Problem: FixedPointProblem,
+    {
+        let n = problem.size();
+        assert_eq!(x.len(), n, "Vector does not match the size of the problem");
+        let mut update = vec![0.0; n];
+        let mut previous_update = vec![0.0; n];
+        let compute_update = |x: &[f64], update: &mut [f64]| {
+            problem.update(x, update);
+            update.iter_mut().zip(x).for_each(|(u, x)| *u -= x);
+            norm(update)
+        };
+        let mut update_norm = compute_update(x, &mut update);
+        let mut test = self.stopping.start(update_norm);
+        let mut relaxation = self.relaxation;
+        let mut iterations = 0;
+        let mut change = None;
+        let recorder = Recorder::start(&self.monitor);
+        let reason = loop {
+            recorder.record(iterations, update_norm);
+            if let Some(reason) = test.check(iterations, update_norm, change) {
+                break reason;
+            }
+            if self.aitken && iterations > 0 {
+                // Relaxation minimizing the linearized update along the last two ones
+                let difference: Vec<f64> = update
+                    .iter()
+                    .zip(&previous_update)
+                    .map(|(u, p)| u - p)
+                    .collect();
+                let squared = dot(&difference, &difference);
+                if squared > 0.0 {
+                    relaxation *= -dot(&previous_update, &difference) / squared;
+                }
+            }
+            x.iter_mut()
+                .zip(&update)
+                .for_each(|(x, u)| *x += relaxation * u);
+            change = Some((relaxation.abs() * update_norm, norm(x)));
+            iterations += 1;
+            std::mem::swap(&mut update, &mut previous_update);
+            update_norm = compute_update(x, &mut update);
+        };
+        Convergence {
+            reason,
+            iterations,
+            residual_norm: update_norm,
+            spectrum: None,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Identity matrix of the given size
+fn identity(n: usize) -> SparseCSR<f64> {
+    SparseCSR::new(n, (0..=n).collect(), (0..n).collect(), vec![1.0; n])
+}
+
+// Halve the step until 1/2 |F|^2 decreases enough, update x and its residual r if one is found
+pub(crate) fn line_search<Residual>(
+    residual: Residual,
+    x: &mut [f64],
+    r: &mut [f64],
+    step: &[f64],
+    trial: &mut [f64],
+    trial_r: &mut [f64],
+) -> bool
+where
+    Residual: Fn(&[f64], &mut [f64]),
+{
+    // Armijo constant, the slope of 1/2 |F|^2 along the Newton step being -|F|^2
+    const SUFFICIENT_DECREASE: f64 = 1e-4;
+    let merit = 0.5 * dot(r, r);
+    let mut length = 1.0;
+    for _ in 0..30 {
+        trial
+            .iter_mut()
+            .zip(x.iter().zip(step))
+            .for_each(|(t, (x, s))| *t = x + length * s);
+        residual(trial, trial_r);
+        if 0.5 * dot(trial_r, trial_r) <= (1.0 - 2.0 * SUFFICIENT_DECREASE * length) * merit {
+            x.copy_from_slice(trial);
+            r.copy_from_slice(trial_r);
+            return true;
+        }
+        length *= 0.5;
+    }
+    false
+}
+
+// Dogleg step in the trust region, update x and its residual r if the step is accepted and return
+// whether it was along with the next radius; step holds the Newton step on entry
+fn dogleg<Problem>(
+    problem: &Problem,
+    jacobian: &SparseCSR<f64>,
+    x: &mut [f64],
+    r: &mut [f64],
+    step: &mut [f64],
+    trial: &mut Trial,
+    radius: f64,
+) -> (bool, f64)
+where
+    Problem: NonlinearProblem,
+{
+    let n = x.len();
+    let newton_length = norm(step);
+    if newton_length > radius {
+        // Steepest descent direction -J^T F and its minimizer along the linear model
+        let mut gradient = vec![0.0; n];
+        jacobian.transpose().apply(r, &mut gradient);
+        let mut jg = vec![0.0; n];
+        jacobian.apply(&gradient, &mut jg);
+        let gradient_norm = norm(&gradient);
+        let cauchy = dot(&gradient, &gradient) / dot(&jg, &jg);
+        if cauchy * gradient_norm >= radius {
+            let scale = radius / gradient_norm;
+            step.iter_mut()
+                .zip(&gradient)
+                .for_each(|(s, g)| *s = -scale * g);
+        } else {
+            // Point on the segment from the Cauchy point to the Newton point at the radius
+            let cauchy_point: Vec<f64> = gradient.iter().map(|g| -cauchy * g).collect();
+            let difference: Vec<f64> = step.iter().zip(&cauchy_point).map(|(s, c)| s - c).collect();
+            let a = dot(&difference, &difference);
+            let b = 2.0 * dot(&cauchy_point, &difference);
+            let c = dot(&cauchy_point, &cauchy_point) - radius * radius;
+            let t = (-b + (b * b - 4.0 * a * c).max(0.0).sqrt()) / (2.0 * a);
+            step.iter_mut()
+                .zip(cauchy_point.iter().zip(&difference))
+                .for_each(|(s, (c, d))| *s = c + t * d);
+        }
+    }
+    let step_length = norm(step);
+    let mut predicted = vec![0.0; n];
+    jacobian.apply(step, &mut predicted);
+    predicted
+        .iter_mut()
+        .zip(r.iter())
+        .for_each(|(p, r)| *p += r);
+    trial
+        .x
+        .iter_mut()
+        .zip(x.iter().zip(step.iter()))
+        .for_each(|(t, (x, s))| *t = x + s);
+    problem.residual(&trial.x, &mut trial.r);
+    let merit = 0.5 * dot(r, r);
+    let actual_decrease = merit - 0.5 * dot(&trial.r, &trial.r);
+    let predicted_decrease = merit - 0.5 * dot(&predicted, &predicted);
+    let ratio = if predicted_decrease > 0.0 {
+        actual_decrease / predicted_decrease
+    } else {
+        -1.0
+    };
+    let next_radius = if ratio < 0.25 {
+        0.25 * step_length
+    } else if ratio > 0.75 && step_length >= 0.99 * radius {
+        2.0 * radius
+    } else {
+        radius
+    };
+    if ratio > 1e-4 {
+        x.copy_from_slice(&trial.x);
+        r.copy_from_slice(&trial.r);
+        (true, next_radius)
+    } else {
+        (false, next_radius)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solvers::monitor::ResidualHistory;
+
+    // Equations atan(x) = 0 and y = x, full Newton steps overshoot atan further and further from
+    // initial guesses above 1.39
+    struct Arctangent;
+
+    impl NonlinearProblem for Arctangent {
+        fn size(&self) -> usize {
+            2
+        }
+
+        fn residual(&self, x: &[f64], r: &mut [f64]) {
+            r[0] = x[0].atan();
+            r[1] = x[1] - x[0];
+        }
+
+        fn jacobian(&self, x: &[f64]) -> SparseCSR<f64> {
+            let values = vec![1.0 / (1.0 + x[0] * x[0]), -1.0, 1.0];
+            SparseCSR::new(2, vec![0, 1, 3], vec![0, 0, 1], values)
+        }
+    }
+
+    // Equation x^3 - 2 x + 2 = 0 whose residual has a local minimum at x = sqrt(2 / 3) on the way to
+    // the root -1.7693 from x = 1
+    struct Cubic;
+
+    impl NonlinearProblem for Cubic {
+        fn size(&self) -> usize {
+            1
+        }
+
+        fn residual(&self, x: &[f64], r: &mut [f64]) {
+            r[0] = x[0].powi(3) - 2.0 * x[0] + 2.0;
+        }
+
+        fn jacobian(&self, x: &[f64]) -> SparseCSR<f64> {
+            SparseCSR::new(1, vec![0, 1], vec![0], vec![3.0 * x[0] * x[0] - 2.0])
+        }
+    }
+
+    #[test]
+    fn test_newton_globalization() {
+        let mut x = vec![0.5, 3.0];
+        let local = Newton::new()
+            .with_globalization(Globalization::None)
+            .solve(&Arctangent, &mut x);
+        assert!(
+            local.converged(),
+            "Newton should converge close to the root"
+        );
+        let mut x = vec![3.0, 3.0];
+        let bare = Newton::new()
+            .with_globalization(Globalization::None)
+            .solve(&Arctangent, &mut x);
+        assert!(!bare.converged(), "Bare Newton should diverge");
+        for globalization in [
+            Globalization::LineSearch,
+            Globalization::TrustRegion(1.0),
+            Globalization::PseudoTransient(0.5),
+        ] {
+            let mut x = vec![3.0, 3.0];
+            let result = Newton::new()
+                .with_globalization(globalization)
+                .solve(&Arctangent, &mut x);
+            assert!(result.converged(), "{:?} did not converge", globalization);
+            assert!(x[0].abs() < 1e-10, "Wrong first unknown {}", x[0]);
+            assert!(x[1].abs() < 1e-10, "Wrong second unknown {}", x[1]);
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_newton_pseudo_transient() {
+        let mut x = vec![1.0];
+        let line_search = Newton::new().solve(&Cubic, &mut x);
+        assert!(
+            !line_search.converged(),
+            "Line search should stall at the local minimum of the residual"
+        );
+        let history = ResidualHistory::new();
+        let mut x = vec![1.0];
+        let result = Newton::new()
+            .with_max_iterations(200)
+            .with_globalization(Globalization::PseudoTransient(0.1))
+            .with_monitor(history.clone())
+            .solve(&Cubic, &mut x);
+        assert!(result.converged(), "Pseudo-transient continuation failed");
+        assert!((x[0] + 1.769292354).abs() < 1e-8, "Wrong root {}", x[0]);
+        // Pseudo time steps growing with the decrease of the residual recover the quadratic
+        // convergence of Newton while small fixed ones would only reduce it linearly
+        let norms = history.residual_norms();
+        assert!(
+            norms.len() > 10,
+            "The path to the root should take many small steps"
+        );
+        for pair in norms.windows(2).filter(|pair| pair[0] < 0.1) {
+            assert!(
+                pair[1] < 10.0 * pair[0] * pair[0],
+                "Convergence should be quadratic close to the root: {:?}",
+                norms
+            );
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    #[should_panic]
+    fn test_newton_pseudo_transient_step() {
+        let _ = Newton::new().with_globalization(Globalization::PseudoTransient(0.0));
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_picard() {
+        // Map x -> 3 - 2 x whose fixed point 1 repels plain iterations
+        struct Repelling;
+
+        impl FixedPointProblem for Repelling {
+            fn size(&self) -> usize {
+                2
+            }
+
+            fn update(&self, x: &[f64], next: &mut [f64]) {
+                next.iter_mut().zip(x).for_each(|(n, x)| *n = 3.0 - 2.0 * x);
+            }
+        }
+
+        // Map x -> cos(x) converging slowly
+        struct Cosine;
+
+        impl FixedPointProblem for Cosine {
+            fn size(&self) -> usize {
+                3
+            }
+
+            fn update(&self, x: &[f64], next: &mut [f64]) {
+                next.iter_mut().zip(x).for_each(|(n, x)| *n = x.cos());
+            }
+        }
+
+        let mut x = vec![0.0, 2.0];
+        let plain = Picard::new().solve(&Repelling, &mut x);
+        assert!(!plain.converged(), "Plain iterations should diverge");
+        let mut x = vec![0.0, 2.0];
+        let relaxed = Picard::new().with_relaxation(0.3).solve(&Repelling, &mut x);
+        assert!(
+            relaxed.converged(),
+            "Under-relaxed iterations did not converge"
+        );
+        assert!(
+            x.iter().all(|x| (x - 1.0).abs() < 1e-9),
+            "Wrong fixed point"
+        );
+        let mut x = vec![0.0, 2.0];
+        let accelerated = Picard::new().with_aitken(true).solve(&Repelling, &mut x);
+        assert!(
+            accelerated.converged(),
+            "Aitken iterations did not converge"
+        );
+        assert!(
+            accelerated.iterations() <= 3,
+            "Aitken should solve linear maps"
+        );
+        let mut x = vec![0.0, 0.5, 1.0];
+        let slow = Picard::new().solve(&Cosine, &mut x);
+        let mut y = vec![0.0, 0.5, 1.0];
+        let fast = Picard::new().with_aitken(true).solve(&Cosine, &mut y);
+        assert!(
+            slow.converged() && fast.converged(),
+            "Cosine did not converge"
+        );
+        assert!(
+            2 * fast.iterations() < slow.iterations(),
+            "Aitken should accelerate the iterations"
+        );
+        for (x, y) in x.iter().zip(&y) {
+            assert!((x - y).abs() < 1e-9, "Fixed points do not match");
+            assert!((x - x.cos()).abs() < 1e-9, "Wrong fixed point");
+        }
+    }
+}
@@ -0,0 +1,806 @@
+use super::krylov::{norm, Convergence};
+use super::nonlinear::NonlinearProblem;
+use super::stopping_criterion::{StopReason, StoppingCriterion};
+use crate::core::arrays::sparse_csr::SparseCSR;
+use mpi::raw::AsRaw;
+use mpi::topology::SimpleCommunicator;
+use std::ffi::{c_void, CString};
+use std::io::{Error, Result};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::OnceLock;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Linear solver handing sparse systems to a PETSc KSP of the given Krylov and preconditioner
+/// types (for instance "gmres" and "ilu", or "preonly" and "lu" for a direct solve)
+///
+/// The systems are solved sequentially on the calling process, PETSc being initialized on first
+/// use. Options of the PETSc options database (such as -ksp_monitor) are applied after the ones
+/// of the solver. PETSc must be built with 32 bit indices and real double precision scalars.
+/// Failing PETSc calls make the solve return an error with the PETSc error code.
+pub struct PetscKsp {
+    ksp_type: CString,
+    pc_type: CString,
+    stopping: StoppingCriterion,
+}
+
+/// Nonlinear solver handing problems to a PETSc SNES of the given type (for instance "newtonls"
+/// or "newtontr") whose linear systems are solved by a KSP
+///
+/// The relative tolerance is the one on the residual relative to the initial residual and the
+/// solution tolerance the one on the step relative to the solution. A panic of the residual or
+/// the jacobian of the problem aborts the solve, which returns an error with the panic message.
+pub struct PetscSnes {
+    snes_type: CString,
+    ksp: PetscKsp,
+    stopping: StoppingCriterion,
+}
+
+// Matrix of PETSc in the AIJ (compressed sparse row) format
+struct Matrix {
+    mat: ffi::Mat,
+    // Arrays of the matrix when PETSc borrows them instead of copying
+    _arrays: Option<(Vec<ffi::PetscInt>, Vec<ffi::PetscInt>, Vec<f64>)>,
+}
+
+// Vector of PETSc borrowing a slice
+struct Vector {
+    vec: ffi::Vec,
+}
+
+// Context of the SNES callbacks
+struct SnesContext<'a, Problem> {
+    problem: &'a Problem,
+    residual: Vec<f64>,
+    // Message of the first error or panic of a callback
+    failure: Option<String>,
+}
+
+static INITIALIZE: OnceLock<ffi::PetscErrorCode> = OnceLock::new();
+
+impl PetscKsp {
+    /// Solver of the given PETSc Krylov and preconditioner types with the tolerances of the
+    /// native Krylov solvers
+    pub fn new(ksp_type: &str, pc_type: &str) -> Self {
+        PetscKsp {
+            ksp_type: CString::new(ksp_type).expect("KSP type is not a valid C string"),
+            pc_type: CString::new(pc_type).expect("PC type is not a valid C string"),
+            stopping: StoppingCriterion::new(1e-10, 0.0, 1000),
+        }
+    }
+
+    /// Set the tolerances and maximum number of iterations, the solution and stagnation tests
+    /// being ignored by PETSc
+    pub fn with_stopping_criterion(mut self, stopping: StoppingCriterion) -> Self {
+        self.stopping = stopping;
+        self
+    }
+
+    /// Solve A x = b starting from the values in x
+    ///
+    /// Solvers doing a fixed number of iterations (such as "preonly") are judged by the residual
+    /// against the tolerances, like the native direct solvers.
+    pub fn solve(
+        &self,
+        matrix: &SparseCSR<f64>,
+        rhs: &[f64],
+        x: &mut [f64],
+    ) -> Result<Convergence> {
+        let n = matrix.n_rows();
+        assert!(
+            matrix.n_cols() == n && rhs.len() == n && x.len() == n,
+            "Vectors do not match the size of the matrix"
+        );
+        initialize()?;
+        let petsc_matrix = Matrix::borrowed(matrix)?;
+        let b = Vector::new(rhs.as_ptr() as *mut f64, n)?;
+        let solution = Vector::new(x.as_mut_ptr(), n)?;
+        let mut ksp = std::ptr::null_mut();
+        let (mut convergence, reason) = unsafe {
+            check(ffi::KSPCreate(comm_self(), &mut ksp))?;
+            let outcome = self
+                .configure(ksp)
+                .and_then(|_| {
+                    check(ffi::KSPSetOperators(
+                        ksp,
+                        petsc_matrix.mat,
+                        petsc_matrix.mat,
+                    ))
+                })
+                .and_then(|_| check(ffi::KSPSetInitialGuessNonzero(ksp, ffi::PETSC_TRUE)))
+                .and_then(|_| check(ffi::KSPSolve(ksp, b.vec, solution.vec)))
+                .and_then(|_| ksp_convergence(ksp));
+            let destroyed = check(ffi::KSPDestroy(&mut ksp));
+            let outcome = outcome?;
+            destroyed?;
+            outcome
+        };
+        drop(solution);
+        if reason.is_none() {
+            let mut residual = vec![0.0; n];
+            matrix.apply(x, &mut residual);
+            residual.iter_mut().zip(rhs).for_each(|(r, b)| *r = b - *r);
+            convergence.residual_norm = norm(&residual);
+            convergence.reason = self
+                .stopping
+                .start(norm(rhs))
+                .check(0, convergence.residual_norm, None)
+                .unwrap_or(StopReason::Breakdown);
+        }
+        Ok(convergence)
+    }
+
+    // Set the types and tolerances of a KSP
+    unsafe fn configure(&self, ksp: ffi::KSP) -> Result<()> {
+        let mut pc = std::ptr::null_mut();
+        check(ffi::KSPSetType(ksp, self.ksp_type.as_ptr()))?;
+        check(ffi::KSPGetPC(ksp, &mut pc))?;
+        check(ffi::PCSetType(pc, self.pc_type.as_ptr()))?;
+        check(ffi::KSPSetTolerances(
+            ksp,
+            self.stopping.relative_tolerance(),
+            self.stopping.absolute_tolerance(),
+            ffi::PETSC_DEFAULT,
+            self.stopping.max_iterations() as ffi::PetscInt,
+        ))?;
+        check(ffi::KSPSetFromOptions(ksp))
+    }
+}
+
+impl PetscSnes {
+    /// Solver of the given SNES type with the tolerances of the native Newton solver and linear
+    /// systems solved by the given KSP
+    pub fn new(snes_type: &str, ksp: PetscKsp) -> Self {
+        PetscSnes {
+            snes_type: CString::new(snes_type).expect("SNES type is not a valid C string"),
+            ksp,
+            stopping: StoppingCriterion::new(1e-10, 1e-14, 50),
+        }
+    }
+
+    /// Set the tolerances and maximum number of iterations, the stagnation test being ignored by
+    /// PETSc
+    pub fn with_stopping_criterion(mut self, stopping: StoppingCriterion) -> Self {
+        self.stopping = stopping;
+        self
+    }
+
+    /// Solve F(x) = 0 starting from the values in x
+    ///
+    /// The sparsity of the jacobian at the initial guess is the one of all the jacobians.
+    pub fn solve<Problem>(&self, problem: &Problem, x: &mut [f64]) -> Result<Convergence>
+    where
+        Problem: NonlinearProblem,
+    {
+        let n = problem.size();
+        assert_eq!(x.len(), n, "Vector does not match the size of the problem");
+        initialize()?;
+        let jacobian = Matrix::preallocated(&problem.jacobian(x))?;
+        let mut context = SnesContext {
+            problem,
+            residual: vec![0.0; n],
+            failure: None,
+        };
+        let residual = Vector::new(context.residual.as_mut_ptr(), n)?;
+        let solution = Vector::new(x.as_mut_ptr(), n)?;
+        let context_pointer = &mut context as *mut SnesContext<Problem> as *mut c_void;
+        let mut snes = std::ptr::null_mut();
+        let outcome = unsafe {
+            check(ffi::SNESCreate(comm_self(), &mut snes))?;
+            let outcome =
+                self.run::<Problem>(snes, &residual, &solution, &jacobian, context_pointer);
+            let destroyed = check(ffi::SNESDestroy(&mut snes));
+            outcome.and_then(|convergence| destroyed.map(|_| convergence))
+        };
+        // The message of a failing callback explains the error code of PETSc
+        match context.failure.take() {
+            Some(message) => Err(Error::other(message)),
+            None => outcome,
+        }
+    }
+
+    // Configure a SNES with the callbacks of a problem and solve
+    unsafe fn run<Problem: NonlinearProblem>(
+        &self,
+        snes: ffi::SNES,
+        residual: &Vector,
+        solution: &Vector,
+        jacobian: &Matrix,
+        context: *mut c_void,
+    ) -> Result<Convergence> {
+        check(ffi::SNESSetType(snes, self.snes_type.as_ptr()))?;
+        check(ffi::SNESSetFunction(
+            snes,
+            residual.vec,
+            snes_residual::<Problem>,
+            context,
+        ))?;
+        check(ffi::SNESSetJacobian(
+            snes,
+            jacobian.mat,
+            jacobian.mat,
+            snes_jacobian::<Problem>,
+            context,
+        ))?;
+        check(ffi::SNESSetTolerances(
+            snes,
+            self.stopping.absolute_tolerance(),
+            self.stopping.relative_tolerance(),
+            self.stopping.solution_tolerance(),
+            self.stopping.max_iterations() as ffi::PetscInt,
+            ffi::PETSC_DEFAULT as ffi::PetscInt,
+        ))?;
+        let mut ksp = std::ptr::null_mut();
+        check(ffi::SNESGetKSP(snes, &mut ksp))?;
+        self.ksp.configure(ksp)?;
+        check(ffi::SNESSetFromOptions(snes))?;
+        check(ffi::SNESSolve(snes, std::ptr::null_mut(), solution.vec))?;
+        let mut iterations = 0;
+        let mut residual_norm = 0.0;
+        let mut reason = 0;
+        check(ffi::SNESGetIterationNumber(snes, &mut iterations))?;
+        check(ffi::SNESGetFunctionNorm(snes, &mut residual_norm))?;
+        check(ffi::SNESGetConvergedReason(snes, &mut reason))?;
+        Ok(Convergence {
+            reason: snes_stop_reason(reason),
+            iterations: iterations as usize,
+            residual_norm,
+            spectrum: None,
+        })
+    }
+}
+
+impl Matrix {
+    // Matrix sharing the arrays of a copy of the entries
+    fn borrowed(matrix: &SparseCSR<f64>) -> Result<Self> {
+        let to_petsc = |indices: &[usize]| -> Vec<ffi::PetscInt> {
+            indices.iter().map(|i| *i as ffi::PetscInt).collect()
+        };
+        let mut row_offsets = to_petsc(matrix.row_offsets());
+        let mut col_indices = to_petsc(matrix.col_indices());
+        let mut values = matrix.values().to_vec();
+        let mut mat = std::ptr::null_mut();
+        unsafe {
+            check(ffi::MatCreateSeqAIJWithArrays(
+                comm_self(),
+                matrix.n_rows() as ffi::PetscInt,
+                matrix.n_cols() as ffi::PetscInt,
+                row_offsets.as_mut_ptr(),
+                col_indices.as_mut_ptr(),
+                values.as_mut_ptr(),
+                &mut mat,
+            ))?;
+        }
+        Ok(Matrix {
+            mat,
+            _arrays: Some((row_offsets, col_indices, values)),
+        })
+    }
+
+    // Matrix owning its arrays, preallocated with the sparsity of a matrix and holding its values
+    fn preallocated(matrix: &SparseCSR<f64>) -> Result<Self> {
+        let row_sizes: Vec<ffi::PetscInt> = matrix
+            .row_offsets()
+            .windows(2)
+            .map(|offsets| (offsets[1] - offsets[0]) as ffi::PetscInt)
+            .collect();
+        let mut mat = std::ptr::null_mut();
+        unsafe {
+            check(ffi::MatCreateSeqAIJ(
+                comm_self(),
+                matrix.n_rows() as ffi::PetscInt,
+                matrix.n_cols() as ffi::PetscInt,
+                0,
+                row_sizes.as_ptr(),
+                &mut mat,
+            ))?;
+            let preallocated = Matrix { mat, _arrays: None };
+            set_values(mat, matrix)?;
+            Ok(preallocated)
+        }
+    }
+}
+
+impl Drop for Matrix {
+    fn drop(&mut self) {
+        // Errors cannot be reported from a destructor
+        unsafe {
+            ffi::MatDestroy(&mut self.mat);
+        }
+    }
+}
+
+impl Vector {
+    // Vector using the given array, which must outlive it
+    fn new(values: *mut f64, n: usize) -> Result<Self> {
+        let mut vec = std::ptr::null_mut();
+        unsafe {
+            check(ffi::VecCreateSeqWithArray(
+                comm_self(),
+                1,
+                n as ffi::PetscInt,
+                values,
+                &mut vec,
+            ))?;
+        }
+        Ok(Vector { vec })
+    }
+}
+
+impl Drop for Vector {
+    fn drop(&mut self) {
+        // Errors cannot be reported from a destructor
+        unsafe {
+            ffi::VecDestroy(&mut self.vec);
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Initialize PETSc (and MPI if needed) once per process
+fn initialize() -> Result<()> {
+    check(*INITIALIZE.get_or_init(|| unsafe {
+        let mut initialized = ffi::PETSC_FALSE;
+        let code = ffi::PetscInitialized(&mut initialized);
+        if code != 0 || initialized == ffi::PETSC_TRUE {
+            return code;
+        }
+        ffi::PetscInitializeNoArguments()
+    }))
+}
+
+// Communicator of the calling process only
+fn comm_self() -> mpi::ffi::MPI_Comm {
+    SimpleCommunicator::self_comm().as_raw()
+}
+
+// Turn the error codes of PETSc into errors
+fn check(code: ffi::PetscErrorCode) -> Result<()> {
+    if code == 0 {
+        Ok(())
+    } else {
+        Err(Error::other(format!(
+            "PETSc call failed with error code {}",
+            code
+        )))
+    }
+}
+
+// Stop reason of a KSPConvergedReason, None for a fixed number of iterations (KSP_CONVERGED_ITS)
+// which does not test the residual
+fn ksp_stop_reason(reason: i32) -> Option<StopReason> {
+    Some(match reason {
+        // KSP_CONVERGED_RTOL_NORMAL and KSP_CONVERGED_RTOL
+        1 | 2 => StopReason::RelativeTolerance,
+        // KSP_CONVERGED_ATOL and KSP_CONVERGED_ATOL_NORMAL
+        3 | 9 => StopReason::AbsoluteTolerance,
+        4 => return None,
+        // KSP_DIVERGED_ITS
+        -3 => StopReason::MaxIterations,
+        // Other successes such as KSP_CONVERGED_HAPPY_BREAKDOWN
+        r if r > 0 => StopReason::RelativeTolerance,
+        _ => StopReason::Breakdown,
+    })
+}
+
+// Stop reason of a SNESConvergedReason
+fn snes_stop_reason(reason: i32) -> StopReason {
+    match reason {
+        // SNES_CONVERGED_FNORM_ABS, SNES_CONVERGED_FNORM_RELATIVE and
+        // SNES_CONVERGED_SNORM_RELATIVE
+        2 => StopReason::AbsoluteTolerance,
+        3 => StopReason::RelativeTolerance,
+        4 => StopReason::SolutionChange,
+        // SNES_DIVERGED_MAX_IT
+        -5 => StopReason::MaxIterations,
+        // Other successes such as SNES_CONVERGED_ITS of "ksponly"
+        r if r > 0 => StopReason::RelativeTolerance,
+        _ => StopReason::Breakdown,
+    }
+}
+
+// Outcome of a KSP solve and its stop reason if the KSP tested the residual
+unsafe fn ksp_convergence(ksp: ffi::KSP) -> Result<(Convergence, Option<StopReason>)> {
+    let mut iterations = 0;
+    let mut residual_norm = 0.0;
+    let mut reason = 0;
+    check(ffi::KSPGetIterationNumber(ksp, &mut iterations))?;
+    check(ffi::KSPGetResidualNorm(ksp, &mut residual_norm))?;
+    check(ffi::KSPGetConvergedReason(ksp, &mut reason))?;
+    let stop_reason = ksp_stop_reason(reason);
+    Ok((
+        Convergence {
+            reason: stop_reason.unwrap_or(StopReason::Breakdown),
+            iterations: iterations as usize,
+            residual_norm,
+            spectrum: None,
+        },
+        stop_reason,
+    ))
+}
+
+// Replace the values of a matrix preallocated with the sparsity of another
+unsafe fn set_values(mat: ffi::Mat, matrix: &SparseCSR<f64>) -> Result<()> {
+    check(ffi::MatZeroEntries(mat))?;
+    for row in 0..matrix.n_rows() {
+        let (cols, values) = matrix.row(row);
+        let row = row as ffi::PetscInt;
+        let cols: Vec<ffi::PetscInt> = cols.iter().map(|c| *c as ffi::PetscInt).collect();
+        check(ffi::MatSetValues(
+            mat,
+            1,
+            &row,
+            cols.len() as ffi::PetscInt,
+            cols.as_ptr(),
+            values.as_ptr(),
+            ffi::INSERT_VALUES,
+        ))?;
+    }
+    check(ffi::MatAssemblyBegin(mat, ffi::MAT_FINAL_ASSEMBLY))?;
+    check(ffi::MatAssemblyEnd(mat, ffi::MAT_FINAL_ASSEMBLY))
+}
+
+// Run the body of a SNES callback without unwinding into PETSc: errors and panics are kept in the
+// context and become the error code of a user error
+unsafe fn guard<Problem>(
+    context: *mut c_void,
+    body: impl FnOnce(&SnesContext<Problem>) -> Result<()>,
+) -> ffi::PetscErrorCode {
+    let context = &mut *(context as *mut SnesContext<Problem>);
+    let message = match panic::catch_unwind(AssertUnwindSafe(|| body(context))) {
+        Ok(Ok(())) => return 0,
+        Ok(Err(error)) => error.to_string(),
+        Err(payload) => match payload.downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => payload
+                .downcast_ref::<String>()
+                .cloned()
+                .unwrap_or_else(|| "Panic in a SNES callback".to_string()),
+        },
+    };
+    context.failure.get_or_insert(message);
+    ffi::PETSC_ERR_USER
+}
+
+// Residual callback of the SNES
+unsafe extern "C" fn snes_residual<Problem: NonlinearProblem>(
+    _snes: ffi::SNES,
+    x: ffi::Vec,
+    r: ffi::Vec,
+    context: *mut c_void,
+) -> ffi::PetscErrorCode {
+    guard(context, |context: &SnesContext<Problem>| {
+        let n = context.residual.len();
+        let mut x_values = std::ptr::null();
+        let mut r_values = std::ptr::null_mut();
+        check(ffi::VecGetArrayRead(x, &mut x_values))?;
+        check(ffi::VecGetArray(r, &mut r_values))?;
+        context.problem.residual(
+            std::slice::from_raw_parts(x_values, n),
+            std::slice::from_raw_parts_mut(r_values, n),
+        );
+        check(ffi::VecRestoreArray(r, &mut r_values))?;
+        check(ffi::VecRestoreArrayRead(x, &mut x_values))
+    })
+}
+
+// Jacobian callback of the SNES
+unsafe extern "C" fn snes_jacobian<Problem: NonlinearProblem>(
+    _snes: ffi::SNES,
+    x: ffi::Vec,
+    jacobian: ffi::Mat,
+    _preconditioner: ffi::Mat,
+    context: *mut c_void,
+) -> ffi::PetscErrorCode {
+    guard(context, |context: &SnesContext<Problem>| {
+        let n = context.residual.len();
+        let mut x_values = std::ptr::null();
+        check(ffi::VecGetArrayRead(x, &mut x_values))?;
+        let matrix = context
+            .problem
+            .jacobian(std::slice::from_raw_parts(x_values, n));
+        check(ffi::VecRestoreArrayRead(x, &mut x_values))?;
+        set_values(jacobian, &matrix)
+    })
+}
+
+// Declarations of the C interface of PETSc used by the wrappers
+#[allow(non_camel_case_types, non_snake_case, clippy::upper_case_acronyms)]
+mod ffi {
+    use mpi::ffi::MPI_Comm;
+    use std::ffi::{c_char, c_void};
+
+    pub type PetscErrorCode = i32;
+    pub type PetscInt = i32;
+    pub type PetscReal = f64;
+    pub type PetscScalar = f64;
+    pub type PetscBool = i32;
+    pub type InsertMode = i32;
+    pub type MatAssemblyType = i32;
+
+    pub const PETSC_FALSE: PetscBool = 0;
+    pub const PETSC_TRUE: PetscBool = 1;
+    pub const PETSC_DEFAULT: PetscReal = -2.0;
+    pub const INSERT_VALUES: InsertMode = 1;
+    pub const MAT_FINAL_ASSEMBLY: MatAssemblyType = 0;
+    pub const PETSC_ERR_USER: PetscErrorCode = 83;
+
+    #[repr(C)]
+    pub struct _p_Mat {
+        _private: [u8; 0],
+    }
+    #[repr(C)]
+    pub struct _p_Vec {
+        _private: [u8; 0],
+    }
+    #[repr(C)]
+    pub struct _p_KSP {
+        _private: [u8; 0],
+    }
+    #[repr(C)]
+    pub struct _p_PC {
+        _private: [u8; 0],
+    }
+    #[repr(C)]
+    pub struct _p_SNES {
+        _private: [u8; 0],
+    }
+
+    pub type Mat = *mut _p_Mat;
+    pub type Vec = *mut _p_Vec;
+    pub type KSP = *mut _p_KSP;
+    pub type PC = *mut _p_PC;
+    pub type SNES = *mut _p_SNES;
+
+    pub type SnesFunction = unsafe extern "C" fn(SNES, Vec, Vec, *mut c_void) -> PetscErrorCode;
+    pub type SnesJacobian =
+        unsafe extern "C" fn(SNES, Vec, Mat, Mat, *mut c_void) -> PetscErrorCode;
+
+    #[link(name = "petsc")]
+    extern "C" {
+        pub fn PetscInitializeNoArguments() -> PetscErrorCode;
+        pub fn PetscInitialized(initialized: *mut PetscBool) -> PetscErrorCode;
+
+        pub fn MatCreateSeqAIJWithArrays(
+            comm: MPI_Comm,
+            m: PetscInt,
+            n: PetscInt,
+            i: *mut PetscInt,
+            j: *mut PetscInt,
+            a: *mut PetscScalar,
+            mat: *mut Mat,
+        ) -> PetscErrorCode;
+        pub fn MatCreateSeqAIJ(
+            comm: MPI_Comm,
+            m: PetscInt,
+            n: PetscInt,
+            nz: PetscInt,
+            nnz: *const PetscInt,
+            mat: *mut Mat,
+        ) -> PetscErrorCode;
+        pub fn MatSetValues(
+            mat: Mat,
+            m: PetscInt,
+            rows: *const PetscInt,
+            n: PetscInt,
+            cols: *const PetscInt,
+            values: *const PetscScalar,
+            mode: InsertMode,
+        ) -> PetscErrorCode;
+        pub fn MatZeroEntries(mat: Mat) -> PetscErrorCode;
+        pub fn MatAssemblyBegin(mat: Mat, kind: MatAssemblyType) -> PetscErrorCode;
+        pub fn MatAssemblyEnd(mat: Mat, kind: MatAssemblyType) -> PetscErrorCode;
+        pub fn MatDestroy(mat: *mut Mat) -> PetscErrorCode;
+
+        pub fn VecCreateSeqWithArray(
+            comm: MPI_Comm,
+            block_size: PetscInt,
+            n: PetscInt,
+            array: *mut PetscScalar,
+            vec: *mut Vec,
+        ) -> PetscErrorCode;
+        pub fn VecGetArray(vec: Vec, array: *mut *mut PetscScalar) -> PetscErrorCode;
+        pub fn VecRestoreArray(vec: Vec, array: *mut *mut PetscScalar) -> PetscErrorCode;
+        pub fn VecGetArrayRead(vec: Vec, array: *mut *const PetscScalar) -> PetscErrorCode;
+        pub fn VecRestoreArrayRead(vec: Vec, array: *mut *const PetscScalar) -> PetscErrorCode;
+        pub fn VecDestroy(vec: *mut Vec) -> PetscErrorCode;
+
+        pub fn KSPCreate(comm: MPI_Comm, ksp: *mut KSP) -> PetscErrorCode;
+        pub fn KSPSetType(ksp: KSP, kind: *const c_char) -> PetscErrorCode;
+        pub fn KSPGetPC(ksp: KSP, pc: *mut PC) -> PetscErrorCode;
+        pub fn PCSetType(pc: PC, kind: *const c_char) -> PetscErrorCode;
+        pub fn KSPSetTolerances(
+            ksp: KSP,
+            rtol: PetscReal,
+            abstol: PetscReal,
+            dtol: PetscReal,
+            maxits: PetscInt,
+        ) -> PetscErrorCode;
+        pub fn KSPSetFromOptions(ksp: KSP) -> PetscErrorCode;
+        pub fn KSPSetOperators(ksp: KSP, amat: Mat, pmat: Mat) -> PetscErrorCode;
+        pub fn KSPSetInitialGuessNonzero(ksp: KSP, nonzero: PetscBool) -> PetscErrorCode;
+        pub fn KSPSolve(ksp: KSP, b: Vec, x: Vec) -> PetscErrorCode;
+        pub fn KSPGetIterationNumber(ksp: KSP, iterations: *mut PetscInt) -> PetscErrorCode;
+        pub fn KSPGetResidualNorm(ksp: KSP, norm: *mut PetscReal) -> PetscErrorCode;
+        pub fn KSPGetConvergedReason(ksp: KSP, reason: *mut i32) -> PetscErrorCode;
+        pub fn KSPDestroy(ksp: *mut KSP) -> PetscErrorCode;
+
+        pub fn SNESCreate(comm: MPI_Comm, snes: *mut SNES) -> PetscErrorCode;
+        pub fn SNESSetType(snes: SNES, kind: *const c_char) -> PetscErrorCode;
+        pub fn SNESSetFunction(
+            snes: SNES,
+            r: Vec,
+            function: SnesFunction,
+            context: *mut c_void,
+        ) -> PetscErrorCode;
+        pub fn SNESSetJacobian(
+            snes: SNES,
+            amat: Mat,
+            pmat: Mat,
+            jacobian: SnesJacobian,
+            context: *mut c_void,
+        ) -> PetscErrorCode;
+        pub fn SNESSetTolerances(
+            snes: SNES,
+            abstol: PetscReal,
+            rtol: PetscReal,
+            stol: PetscReal,
+            maxit: PetscInt,
+            maxf: PetscInt,
+        ) -> PetscErrorCode;
+        pub fn SNESGetKSP(snes: SNES, ksp: *mut KSP) -> PetscErrorCode;
+        pub fn SNESSetFromOptions(snes: SNES) -> PetscErrorCode;
+        pub fn SNESSolve(snes: SNES, b: Vec, x: Vec) -> PetscErrorCode;
+        pub fn SNESGetIterationNumber(snes: SNES, iterations: *mut PetscInt) -> PetscErrorCode;
+        pub fn SNESGetFunctionNorm(snes: SNES, norm: *mut PetscReal) -> PetscErrorCode;
+        pub fn SNESGetConvergedReason(snes: SNES, reason: *mut i32) -> PetscErrorCode;
+        pub fn SNESDestroy(snes: *mut SNES) -> PetscErrorCode;
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // x^3 = 8 and y = x
+    struct Cubic;
+
+    impl NonlinearProblem for Cubic {
+        fn size(&self) -> usize {
+            2
+        }
+
+        fn residual(&self, x: &[f64], r: &mut [f64]) {
+            r[0] = x[0].powi(3) - 8.0;
+            r[1] = x[1] - x[0];
+        }
+
+        fn jacobian(&self, x: &[f64]) -> SparseCSR<f64> {
+            SparseCSR::new(
+                2,
+                vec![0, 1, 3],
+                vec![0, 0, 1],
+                vec![3.0 * x[0] * x[0], -1.0, 1.0],
+            )
+        }
+    }
+
+    // Problem whose residual panics away from the origin
+    struct Failing;
+
+    impl NonlinearProblem for Failing {
+        fn size(&self) -> usize {
+            1
+        }
+
+        fn residual(&self, x: &[f64], r: &mut [f64]) {
+            assert!(x[0] == 0.0, "Residual outside of its domain");
+            r[0] = 0.0;
+        }
+
+        fn jacobian(&self, _x: &[f64]) -> SparseCSR<f64> {
+            SparseCSR::new(1, vec![0, 1], vec![0], vec![1.0])
+        }
+    }
+
+    #[test]
+    fn test_petsc_solvers() {
+        let matrix = SparseCSR::new(
+            3,
+            vec![0, 2, 5, 7],
+            vec![0, 1, 0, 1, 2, 1, 2],
+            vec![2.0, -1.0, -1.0, 2.0, -1.0, -1.0, 2.0],
+        );
+        for (ksp_type, pc_type) in [("cg", "jacobi"), ("preonly", "lu")] {
+            let mut x = vec![0.0; 3];
+            let convergence = PetscKsp::new(ksp_type, pc_type)
+                .solve(&matrix, &[1.0, 0.0, 1.0], &mut x)
+                .unwrap();
+            assert!(convergence.converged(), "KSP {} did not converge", ksp_type);
+            assert!(
+                x.iter().all(|x| (x - 1.0).abs() < 1e-8),
+                "Wrong solution of KSP {}",
+                ksp_type
+            );
+        }
+        let mut x = vec![1.0, 1.0];
+        let convergence = PetscSnes::new("newtonls", PetscKsp::new("preonly", "lu"))
+            .solve(&Cubic, &mut x)
+            .unwrap();
+        assert!(convergence.converged(), "SNES did not converge");
+        assert!(
+            (x[0] - 2.0).abs() < 1e-8 && (x[1] - 2.0).abs() < 1e-8,
+            "Wrong SNES solution"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_petsc_failures() {
+        let mut x = vec![1.0];
+        let error = PetscSnes::new("newtonls", PetscKsp::new("preonly", "lu"))
+            .solve(&Failing, &mut x)
+            .expect_err("A panicking residual should fail the solve");
+        assert_eq!(
+            error.to_string(),
+            "Residual outside of its domain",
+            "The error should carry the panic message"
+        );
+        assert!(check(0).is_ok(), "Zero is the success code");
+        assert!(
+            check(ffi::PETSC_ERR_USER)
+                .unwrap_err()
+                .to_string()
+                .contains("83"),
+            "The error should carry the PETSc error code"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_petsc_stop_reasons() {
+        let ksp_reasons = [
+            (1, Some(StopReason::RelativeTolerance)),
+            (2, Some(StopReason::RelativeTolerance)),
+            (3, Some(StopReason::AbsoluteTolerance)),
+            (9, Some(StopReason::AbsoluteTolerance)),
+            (4, None),
+            (7, Some(StopReason::RelativeTolerance)),
+            (-3, Some(StopReason::MaxIterations)),
+            (-5, Some(StopReason::Breakdown)),
+        ];
+        for (reason, expected) in ksp_reasons {
+            assert_eq!(
+                ksp_stop_reason(reason),
+                expected,
+                "Wrong stop reason of KSPConvergedReason {}",
+                reason
+            );
+        }
+        let snes_reasons = [
+            (2, StopReason::AbsoluteTolerance),
+            (3, StopReason::RelativeTolerance),
+            (4, StopReason::SolutionChange),
+            (5, StopReason::RelativeTolerance),
+            (-5, StopReason::MaxIterations),
+            (-6, StopReason::Breakdown),
+        ];
+        for (reason, expected) in snes_reasons {
+            assert_eq!(
+                snes_stop_reason(reason),
+                expected,
+                "Wrong stop reason of SNESConvergedReason {}",
+                reason
+            );
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use super::krylov::{distance, norm, Convergence};
+use super::linear_operator::Preconditioner;
+use super::monitor::{Monitor, Recorder};
+use super::stopping_criterion::StoppingCriterion;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use std::rc::Rc;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Classical relaxation methods updating the unknowns from the rows of the matrix
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum RelaxationMethod {
+    /// Weighted Jacobi, every unknown is updated from the previous iterate
+    Jacobi(f64),
+    /// Forward Gauss-Seidel, unknowns are updated in order from the latest values
+    GaussSeidel,
+    /// Successive over relaxation, Gauss-Seidel with a relaxation factor in (0, 2)
+    Sor(f64),
+    /// Symmetric successive over relaxation, a forward then a backward SOR sweep
+    SymmetricSor(f64),
+}
+
+/// Relaxation of a sparse square matrix usable as a solver, a smoother or a preconditioner
+///
+/// As a standalone solver sweeps are repeated until the euclidean norm of the residual falls below
+/// the largest of the absolute tolerance and the relative tolerance times the norm of the right
+/// hand side. As a smoother or a preconditioner a fixed number of sweeps (1 by default) is done,
+/// starting from a zero guess for the latter. Only the Jacobi and symmetric SOR methods give
+/// symmetric preconditioners suited to the conjugate gradient.
+pub struct Relaxation<'a> {
+    matrix: &'a SparseCSR<f64>,
+    inverse_diagonal: Vec<f64>,
+    method: RelaxationMethod,
+    sweeps: usize,
+    stopping: StoppingCriterion,
+    monitor: Option<Rc<dyn Monitor>>,
+}
+
+impl<'a> Relaxation<'a> {
+    /// Relaxation of a matrix with non zero diagonal entries
+    pub fn new(matrix: &'a SparseCSR<f64>, method: RelaxationMethod) -> Self {
+        assert_eq!(
+            matrix.n_rows(),
+            matrix.n_cols(),
+            "Relaxation needs a square matrix"
+        );
+        let inverse_diagonal = (0..matrix.n_rows())
+            .map(|i| {
+                let diagonal = matrix.get(i, i).copied().unwrap_or(0.0);
+                assert!(diagonal != 0.0, "Relaxation needs a non zero diagonal");
+                1.0 / diagonal
+            })
+            .collect();
+        Relaxation {
+            matrix,
+            inverse_diagonal,
+            method,
+            sweeps: 1,
+            stopping: StoppingCriterion::new(1e-10, 0.0, 10000),
+            monitor: None,
+        }
+    }
+
+    /// Set the number of sweeps done when smoothing or preconditioning
+    pub fn with_sweeps(mut self, sweeps: usize) -> Self {
+        self.sweeps = sweeps;
+        self
+    }
+
+    /// Set the tolerance on the residual relative to the right hand side
+    pub fn with_relative_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_relative_tolerance(tolerance);
+        self
+    }
+
+    /// Set the tolerance on the norm of the residual
+    pub fn with_absolute_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_absolute_tolerance(tolerance);
+        self
+    }
+
+    /// Set the maximum number of sweeps of the standalone solver
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.stopping = self.stopping.with_max_iterations(max_iterations);
+        self
+    }
+
+    /// Replace the whole stopping criterion, the reference norm being the one of the right hand
+    /// side
+    pub fn with_stopping_criterion(mut self, stopping: StoppingCriterion) -> Self {
+        self.stopping = stopping;
+        self
+    }
+
+    /// Set a monitor observing the residual norm of every iteration
+    pub fn with_monitor<M: Monitor + 'static>(mut self, monitor: M) -> Self {
+        self.monitor = Some(Rc::new(monitor));
+        self
+    }
+
+    /// Method used for the sweeps
+    pub fn method(&self) -> RelaxationMethod {
+        self.method
+    }
+
+    /// Apply the smoothing sweeps to x for the system A x = b
+    pub fn smooth(&self, rhs: &[f64], x: &mut [f64]) {
+        self.check_sizes(rhs, x);
+        let mut work = vec![0.0; rhs.len()];
+        for _ in 0..self.sweeps {
+            self.sweep(rhs, x, &mut work);
+        }
+    }
+
+    /// Solve A x = b by repeated sweeps starting from the values in x
+    pub fn solve(&self, rhs: &[f64], x: &mut [f64]) -> Convergence {
+        self.check_sizes(rhs, x);
+        let mut test = self.stopping.start(norm(rhs));
+        let mut work = vec![0.0; rhs.len()];
+        let mut residual_norm = self.residual(rhs, x, &mut work);
+        let mut iterations = 0;
+        let mut previous = x.to_vec();
+        let mut update = None;
+        let recorder = Recorder::start(&self.monitor);
+        let reason = loop {
+            recorder.record(iterations, residual_norm);
+            if let Some(reason) = test.check(iterations, residual_norm, update) {
+                break reason;
+            }
+            previous.copy_from_slice(x);
+            self.sweep(rhs, x, &mut work);
+            iterations += 1;
+            residual_norm = self.residual(rhs, x, &mut work);
+            update = Some((distance(x, &previous), norm(x)));
+        };
+        Convergence {
+            reason,
+            iterations,
+            residual_norm,
+            spectrum: None,
+        }
+    }
+
+    fn check_sizes(&self, rhs: &[f64], x: &[f64]) {
+        let n = self.inverse_diagonal.len();
+        assert!(
+            rhs.len() == n && x.len() == n,
+            "Vectors do not match the size of the matrix"
+        );
+    }
+
+    // Store b - A x in the work vector and return its norm
+    fn residual(&self, rhs: &[f64], x: &[f64], work: &mut [f64]) -> f64 {
+        self.matrix.apply(x, work);
+        work.iter_mut().zip(rhs).for_each(|(r, b)| *r = b - *r);
+        norm(work)
+    }
+
+    // One sweep of the method, the work vector is only used by Jacobi
+    fn sweep(&self, rhs: &[f64], x: &mut [f64], work: &mut [f64]) {
+        match self.method {
+            RelaxationMethod::Jacobi(weight) => {
+                self.residual(rhs, x, work);
+                for ((x, r), d) in x.iter_mut().zip(work.iter()).zip(&self.inverse_diagonal) {
+                    *x += weight * r * d;
+                }
+            }
+            RelaxationMethod::GaussSeidel => self.update_rows(0..rhs.len(), 1.0, rhs, x),
+            RelaxationMethod::Sor(omega) => self.update_rows(0..rhs.len(), omega, rhs, x),
+            RelaxationMethod::SymmetricSor(omega) => {
+                self.update_rows(0..rhs.len(), omega, rhs, x);
+                self.update_rows((0..rhs.len()).rev(), omega, rhs, x);
+            }
+        }
+    }
+
+    // Relax the rows in the given order using the latest values of the unknowns
+    fn update_rows<Rows>(&self, rows: Rows, omega: f64, rhs: &[f64], x: &mut [f64])
+    where
+        Rows: Iterator<Item = usize>,
+    {
+        for i in rows {
+            let (cols, vals) = self.matrix.row(i);
+            let product: f64 = cols.iter().zip(vals).map(|(j, a)| a * x[*j]).sum();
+            x[i] += omega * (rhs[i] - product) * self.inverse_diagonal[i];
+        }
+    }
+}
+
+impl Preconditioner for Relaxation<'_> {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        z.iter_mut().for_each(|z| *z = 0.0);
+        self.smooth(r, z);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solvers::krylov::ConjugateGradient;
+
+    // Tridiagonal matrix of the 1D Laplacian
+    fn build_laplacian(n: usize) -> SparseCSR<f64> {
+        let mut row_offsets = vec![0];
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+        for i in 0..n {
+            for j in i.saturating_sub(1)..(i + 2).min(n) {
+                col_indices.push(j);
+                values.push(if i == j { 2.0 } else { -1.0 });
+            }
+            row_offsets.push(col_indices.len());
+        }
+        SparseCSR::new(n, row_offsets, col_indices, values)
+    }
+
+    #[test]
+    fn test_relaxation_solvers() {
+        let n = 10;
+        let matrix = build_laplacian(n);
+        let expected: Vec<f64> = (0..n).map(|i| (i as f64 * 0.3).sin()).collect();
+        let mut rhs = vec![0.0; n];
+        matrix.apply(&expected, &mut rhs);
+        let omega = 2.0 / (1.0 + (std::f64::consts::PI / (n + 1) as f64).sin());
+        let mut iterations = Vec::new();
+        for method in [
+            RelaxationMethod::Jacobi(1.0),
+            RelaxationMethod::GaussSeidel,
+            RelaxationMethod::Sor(omega),
+            RelaxationMethod::SymmetricSor(1.5),
+        ] {
+            let mut x = vec![0.0; n];
+            let result = Relaxation::new(&matrix, method)
+                .with_relative_tolerance(1e-10)
+                .solve(&rhs, &mut x);
+            assert!(result.converged(), "{:?} did not converge", method);
+            for (x, e) in x.iter().zip(&expected) {
+                assert!((x - e).abs() < 1e-8, "Wrong solution with {:?}", method);
+            }
+            iterations.push(result.iterations());
+        }
+        assert!(
+            iterations[1] < iterations[0],
+            "Gauss-Seidel should beat Jacobi"
+        );
+        assert!(
+            iterations[2] < iterations[1],
+            "Optimal SOR should beat Gauss-Seidel"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_relaxation_smoother() {
+        let n = 50;
+        let matrix = build_laplacian(n);
+        // Oscillatory error is damped much faster than smooth error
+        let oscillatory: Vec<f64> = (0..n)
+            .map(|i| if i % 2 == 0 { 1.0 } else { -1.0 })
+            .collect();
+        let smooth: Vec<f64> = (0..n)
+            .map(|i| (std::f64::consts::PI * (i + 1) as f64 / (n + 1) as f64).sin())
+            .collect();
+        let smoother = Relaxation::new(&matrix, RelaxationMethod::Jacobi(2.0 / 3.0)).with_sweeps(3);
+        let rhs = vec![0.0; n];
+        let mut damped_oscillatory = oscillatory.clone();
+        smoother.smooth(&rhs, &mut damped_oscillatory);
+        let mut damped_smooth = smooth.clone();
+        smoother.smooth(&rhs, &mut damped_smooth);
+        assert!(
+            norm(&damped_oscillatory) < 0.1 * norm(&oscillatory),
+            "Oscillatory error not damped"
+        );
+        assert!(
+            norm(&damped_smooth) > 0.9 * norm(&smooth),
+            "Smooth error should be damped slowly"
+        );
+        // Symmetric SOR preconditioning of the conjugate gradient
+        let target: Vec<f64> = (0..n).map(|i| ((i * i) % 7) as f64).collect();
+        let mut rhs = vec![0.0; n];
+        matrix.apply(&target, &mut rhs);
+        let solver = ConjugateGradient::new().with_relative_tolerance(1e-12);
+        let mut x = vec![0.0; n];
+        let plain = solver.solve(&matrix, &rhs, &mut x);
+        let mut x = vec![0.0; n];
+        let ssor = Relaxation::new(&matrix, RelaxationMethod::SymmetricSor(1.5));
+        let preconditioned = solver.solve_preconditioned(&matrix, &ssor, &rhs, &mut x);
+        assert!(
+            preconditioned.converged(),
+            "Preconditioned CG did not converge"
+        );
+        assert!(
+            preconditioned.iterations() < plain.iterations(),
+            "Symmetric SOR should reduce the iterations"
+        );
+    }
+}
@@ -0,0 +1,402 @@
+use super::dense::LU;
+use super::krylov::IncompleteLU;
+use super::linear_operator::Preconditioner;
+use super::sparse_direct::SparseLU;
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::core::logging::{debug, Span};
+use crate::discretizations::function_space::FunctionSpace;
+use crate::discretizations::partition::Partition;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Solver of the subdomain problems of a Schwarz preconditioner
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LocalSolver {
+    /// Sparse LU factorization of the subdomain matrices
+    SparseLU,
+    /// Incomplete LU factorization without fill in of the subdomain matrices
+    IncompleteLU,
+}
+
+/// Builder of overlapping additive Schwarz preconditioners
+///
+/// Unknowns are first split into disjoint parts, for instance the dofs owned by the parts of a
+/// mesh partition (a dof belonging to the lowest part among the parts of the cells touching it).
+/// Every part is then grown by layers of the neighbours in the matrix graph into an overlapping
+/// subdomain. By default subdomains overlap by one layer, are solved by a sparse LU and there is
+/// no coarse correction.
+pub struct Schwarz {
+    overlap: usize,
+    local_solver: LocalSolver,
+    coarse_correction: bool,
+}
+
+/// Two level additive Schwarz preconditioner P^-1 = R_0^T A_0^-1 R_0 + sum_i R_i^T A_i^-1 R_i
+///
+/// R_i restricts to the unknowns of subdomain i and A_i = R_i A R_i^T is the subdomain matrix.
+/// The optional coarse space is spanned by the indicators of the disjoint parts (of every
+/// component for vector spaces), the Nicolaides coarse space, whose Galerkin matrix A_0 is
+/// factorized densely. It carries the global information between distant subdomains so that the
+/// iteration counts stop growing with their number. The preconditioner is symmetric when the
+/// matrix is and the local solves are exact.
+pub struct AdditiveSchwarz {
+    size: usize,
+    subdomains: Vec<Vec<usize>>,
+    solvers: Vec<Box<dyn Preconditioner>>,
+    coarse: Option<(Vec<usize>, LU)>,
+}
+
+impl Default for Schwarz {
+    fn default() -> Self {
+        Schwarz {
+            overlap: 1,
+            local_solver: LocalSolver::SparseLU,
+            coarse_correction: false,
+        }
+    }
+}
+
+impl Schwarz {
+    /// Builder with the default parameters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of layers of neighbours added around every part
+    pub fn with_overlap(mut self, overlap: usize) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Set the solver of the subdomain problems
+    pub fn with_local_solver(mut self, local_solver: LocalSolver) -> Self {
+        self.local_solver = local_solver;
+        self
+    }
+
+    /// Add the coarse correction of the Nicolaides coarse space
+    pub fn with_coarse_correction(mut self) -> Self {
+        self.coarse_correction = true;
+        self
+    }
+
+    /// Preconditioner of a square matrix from disjoint parts covering its unknowns, which fails if
+    /// a subdomain or the coarse matrix cannot be factorized
+    pub fn build(&self, matrix: &SparseCSR<f64>, parts: &[Vec<usize>]) -> Option<AdditiveSchwarz> {
+        let mut owners = vec![usize::MAX; matrix.n_rows()];
+        for (part, dofs) in parts.iter().enumerate() {
+            for dof in dofs {
+                assert!(
+                    owners[*dof] == usize::MAX,
+                    "Parts of a Schwarz preconditioner should be disjoint"
+                );
+                owners[*dof] = part;
+            }
+        }
+        assert!(
+            owners.iter().all(|o| *o != usize::MAX),
+            "Parts of a Schwarz preconditioner should cover the unknowns"
+        );
+        let coarse = self.coarse_correction.then_some((owners, parts.len()));
+        self.assemble(matrix, parts, coarse)
+    }
+
+    /// Preconditioner of the matrix of a space on the parts of a partition of its mesh
+    pub fn build_from_partition(
+        &self,
+        matrix: &SparseCSR<f64>,
+        space: &FunctionSpace,
+        partition: &Partition,
+    ) -> Option<AdditiveSchwarz> {
+        let mesh = space.mesh();
+        assert_eq!(
+            matrix.n_rows(),
+            space.n_dofs(),
+            "Matrix does not match the space"
+        );
+        assert_eq!(
+            partition.parts().len(),
+            mesh.n_cells(),
+            "Partition does not match the mesh"
+        );
+        let n_components = space.n_components();
+        let n_scalar_dofs = space.n_dofs() / n_components;
+        let mut owners = vec![usize::MAX; n_scalar_dofs];
+        for cell in 0..mesh.n_cells() {
+            let part = partition.part(cell);
+            for dof in space.dof_map().cell_dofs(cell) {
+                owners[*dof] = owners[*dof].min(part);
+            }
+        }
+        let mut parts = vec![Vec::new(); partition.n_parts()];
+        let mut groups = vec![0; space.n_dofs()];
+        for (scalar, owner) in owners.iter().enumerate() {
+            for c in 0..n_components {
+                let dof = space.dof(scalar, c);
+                parts[*owner].push(dof);
+                groups[dof] = owner * n_components + c;
+            }
+        }
+        let coarse = self
+            .coarse_correction
+            .then_some((groups, partition.n_parts() * n_components));
+        self.assemble(matrix, &parts, coarse)
+    }
+
+    // Grow the parts into subdomains, factorize their matrices and the coarse matrix of the
+    // indicators of the groups of the unknowns
+    fn assemble(
+        &self,
+        matrix: &SparseCSR<f64>,
+        parts: &[Vec<usize>],
+        coarse: Option<(Vec<usize>, usize)>,
+    ) -> Option<AdditiveSchwarz> {
+        assert_eq!(
+            matrix.n_rows(),
+            matrix.n_cols(),
+            "Schwarz preconditioners need a square matrix"
+        );
+        let _span = Span::enter("Schwarz setup");
+        let size = matrix.n_rows();
+        let mut local = vec![usize::MAX; size];
+        let mut subdomains = Vec::with_capacity(parts.len());
+        let mut solvers: Vec<Box<dyn Preconditioner>> = Vec::with_capacity(parts.len());
+        for part in parts.iter().filter(|p| !p.is_empty()) {
+            let mut dofs = part.clone();
+            dofs.iter().for_each(|d| local[*d] = 0);
+            let mut layer_start = 0;
+            for _ in 0..self.overlap {
+                let layer_end = dofs.len();
+                for k in layer_start..layer_end {
+                    for j in matrix.row(dofs[k]).0 {
+                        if local[*j] == usize::MAX {
+                            local[*j] = 0;
+                            dofs.push(*j);
+                        }
+                    }
+                }
+                layer_start = layer_end;
+            }
+            dofs.sort_unstable();
+            dofs.iter().enumerate().for_each(|(k, d)| local[*d] = k);
+            let submatrix = restrict(matrix, &dofs, &local);
+            dofs.iter().for_each(|d| local[*d] = usize::MAX);
+            let solver: Box<dyn Preconditioner> = match self.local_solver {
+                LocalSolver::SparseLU => Box::new(SparseLU::new(&submatrix)?),
+                LocalSolver::IncompleteLU => Box::new(IncompleteLU::new(&submatrix)?),
+            };
+            subdomains.push(dofs);
+            solvers.push(solver);
+        }
+        let coarse = match coarse {
+            Some((mut groups, n_groups)) => {
+                // Number the groups having unknowns, empty parts adding no coarse vector
+                let mut numbering = vec![usize::MAX; n_groups];
+                let mut n_groups = 0;
+                for group in groups.iter_mut() {
+                    if numbering[*group] == usize::MAX {
+                        numbering[*group] = n_groups;
+                        n_groups += 1;
+                    }
+                    *group = numbering[*group];
+                }
+                let mut coarse_matrix = vec![0.0; n_groups * n_groups];
+                for (i, group) in groups.iter().enumerate() {
+                    let (cols, values) = matrix.row(i);
+                    for (j, value) in cols.iter().zip(values) {
+                        coarse_matrix[group * n_groups + groups[*j]] += value;
+                    }
+                }
+                let lu = LU::new(&DataHold::new(coarse_matrix, [n_groups, n_groups]))?;
+                Some((groups, lu))
+            }
+            None => None,
+        };
+        debug!(
+            "Schwarz preconditioner of {} subdomains of at most {} unknowns",
+            subdomains.len(),
+            subdomains.iter().map(|s| s.len()).max().unwrap_or(0)
+        );
+        Some(AdditiveSchwarz {
+            size,
+            subdomains,
+            solvers,
+            coarse,
+        })
+    }
+}
+
+impl AdditiveSchwarz {
+    /// Number of subdomains
+    pub fn n_subdomains(&self) -> usize {
+        self.subdomains.len()
+    }
+
+    /// Sorted unknowns of a subdomain
+    pub fn subdomain(&self, subdomain: usize) -> &[usize] {
+        &self.subdomains[subdomain]
+    }
+
+    /// Dimension of the coarse space (0 without coarse correction)
+    pub fn coarse_size(&self) -> usize {
+        self.coarse.as_ref().map_or(0, |(_, lu)| lu.size())
+    }
+}
+
+impl Preconditioner for AdditiveSchwarz {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        assert!(
+            r.len() == self.size && z.len() == self.size,
+            "Vectors do not match the Schwarz preconditioner"
+        );
+        z.fill(0.0);
+        for (dofs, solver) in self.subdomains.iter().zip(&self.solvers) {
+            let local_r: Vec<f64> = dofs.iter().map(|d| r[*d]).collect();
+            let mut local_z = vec![0.0; dofs.len()];
+            solver.apply(&local_r, &mut local_z);
+            for (d, value) in dofs.iter().zip(&local_z) {
+                z[*d] += value;
+            }
+        }
+        if let Some((groups, lu)) = &self.coarse {
+            let mut coarse_r = vec![0.0; lu.size()];
+            for (group, value) in groups.iter().zip(r) {
+                coarse_r[*group] += value;
+            }
+            let mut coarse_z = vec![0.0; lu.size()];
+            lu.solve(&coarse_r, &mut coarse_z);
+            for (z, group) in z.iter_mut().zip(groups) {
+                *z += coarse_z[*group];
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Submatrix of the sorted unknowns, local holding their positions and usize::MAX elsewhere
+fn restrict(matrix: &SparseCSR<f64>, dofs: &[usize], local: &[usize]) -> SparseCSR<f64> {
+    let mut row_offsets = vec![0];
+    let mut col_indices = Vec::new();
+    let mut values = Vec::new();
+    for dof in dofs {
+        let (cols, row_values) = matrix.row(*dof);
+        for (j, value) in cols.iter().zip(row_values) {
+            if local[*j] != usize::MAX {
+                col_indices.push(local[*j]);
+                values.push(*value);
+            }
+        }
+        row_offsets.push(col_indices.len());
+    }
+    SparseCSR::new(dofs.len(), row_offsets, col_indices, values)
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::operators::{mass_matrix, stiffness_matrix};
+    use crate::discretizations::partition::PartitionMethod;
+    use crate::solvers::krylov::{ConjugateGradient, Gmres};
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::spaces::quadrature::QuadratureRule;
+
+    #[test]
+    fn test_schwarz_overlap() {
+        // Tridiagonal matrix split in two halves growing by one neighbour per layer
+        let n: usize = 10;
+        let mut row_offsets = vec![0];
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+        for i in 0..n {
+            for j in i.saturating_sub(1)..(i + 2).min(n) {
+                col_indices.push(j);
+                values.push(if i == j { 2.0 } else { -1.0 });
+            }
+            row_offsets.push(col_indices.len());
+        }
+        let matrix = SparseCSR::new(n, row_offsets, col_indices, values);
+        let parts = vec![(0..5).collect(), (5..10).collect()];
+        let schwarz = Schwarz::new()
+            .with_overlap(2)
+            .build(&matrix, &parts)
+            .unwrap();
+        assert_eq!(schwarz.n_subdomains(), 2, "Wrong number of subdomains");
+        assert_eq!(
+            schwarz.subdomain(0),
+            &[0, 1, 2, 3, 4, 5, 6],
+            "Wrong overlap"
+        );
+        assert_eq!(
+            schwarz.subdomain(1),
+            &[3, 4, 5, 6, 7, 8, 9],
+            "Wrong overlap"
+        );
+        assert_eq!(schwarz.coarse_size(), 0, "No coarse space was asked for");
+        // Without overlap the exact local solves of the one-part splitting invert the matrix
+        let whole = vec![(0..n).collect()];
+        let exact = Schwarz::new()
+            .with_overlap(0)
+            .build(&matrix, &whole)
+            .unwrap();
+        let rhs: Vec<f64> = (0..n).map(|i| i as f64).collect();
+        let mut x = vec![0.0; n];
+        exact.apply(&rhs, &mut x);
+        let mut residual = vec![0.0; n];
+        matrix.apply(&x, &mut residual);
+        for (r, b) in residual.iter().zip(&rhs) {
+            assert!((r - b).abs() < 1e-10, "One subdomain should solve exactly");
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_schwarz_partition() {
+        // Reaction diffusion on a square split in 16 parts
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![24, 24]).simplex_mesh();
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let assembler = space.assembler(QuadratureRule::simplex(2, 2));
+        let matrix = stiffness_matrix(&assembler, |_| 1.0).add(1e-3, &mass_matrix(&assembler));
+        let partition = Partition::new(&mesh, 16, PartitionMethod::GraphBisection);
+        let rhs: Vec<f64> = (0..space.n_dofs()).map(|i| (i as f64).sin()).collect();
+        let cg = ConjugateGradient::new().with_relative_tolerance(1e-8);
+        let solve = |schwarz: &Schwarz| {
+            let preconditioner = schwarz
+                .build_from_partition(&matrix, &space, &partition)
+                .unwrap();
+            let mut x = vec![0.0; space.n_dofs()];
+            let convergence = cg.solve_preconditioned(&matrix, &preconditioner, &rhs, &mut x);
+            assert!(convergence.converged(), "Schwarz preconditioned CG failed");
+            convergence.iterations()
+        };
+        let one_level = solve(&Schwarz::new());
+        let two_level = solve(&Schwarz::new().with_coarse_correction());
+        assert!(
+            two_level < one_level,
+            "Coarse correction should reduce the iterations ({} against {})",
+            two_level,
+            one_level
+        );
+        let preconditioner = Schwarz::new()
+            .with_local_solver(LocalSolver::IncompleteLU)
+            .with_coarse_correction()
+            .build_from_partition(&matrix, &space, &partition)
+            .unwrap();
+        assert_eq!(preconditioner.coarse_size(), 16, "Wrong coarse space");
+        let mut x = vec![0.0; space.n_dofs()];
+        let convergence = Gmres::new()
+            .with_relative_tolerance(1e-8)
+            .solve_preconditioned(&matrix, &preconditioner, &rhs, &mut x);
+        assert!(convergence.converged(), "ILU subdomain solves failed");
+    }
+}
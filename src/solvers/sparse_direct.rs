@@ -0,0 +1,540 @@
+use super::conditioning::{inverse_norm1_estimate, norm1};
+use super::linear_operator::Preconditioner;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::core::logging::{debug, Span};
+use std::collections::VecDeque;
+
+// Pivots below this fraction of the largest entry of the matrix are considered zero
+const SINGULAR_TOLERANCE: f64 = 1e-13;
+
+// The diagonal is kept as the LU pivot as long as it is above this fraction of the largest
+// candidate which limits the fill created by pivoting
+const PIVOT_THRESHOLD: f64 = 0.1;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Sparse Cholesky factorization P A P^T = L L^T of a symmetric positive definite matrix
+///
+/// The unknowns are first reordered by reverse Cuthill-McKee to limit the fill and the rows of L
+/// are then computed one after the other by sparse triangular solves (up-looking algorithm). The
+/// matrix is assumed to be symmetric and only its rows are read.
+pub struct SparseCholesky {
+    permutation: Vec<usize>,
+    // Columns of L with their sorted (row, value) entries, the diagonal coming first
+    columns: Vec<Vec<(usize, f64)>>,
+    // 1-norm of the factorized matrix for the condition estimate
+    norm: f64,
+}
+
+/// Sparse LU factorization with threshold partial pivoting of a square matrix
+///
+/// The columns are reordered by reverse Cuthill-McKee of the symmetrized pattern and factorized
+/// left to right by sparse triangular solves (Gilbert-Peierls algorithm). The pivot of every
+/// column is the diagonal entry unless another one is ten times larger.
+pub struct SparseLU {
+    column_order: Vec<usize>,
+    // Original row pivoted at every step
+    pivot_rows: Vec<usize>,
+    // Columns of the unit L in the original rows without the diagonal
+    lower: Vec<Vec<(usize, f64)>>,
+    // Columns of U in the step indices with the diagonal last
+    upper: Vec<Vec<(usize, f64)>>,
+    norm: f64,
+}
+
+impl SparseCholesky {
+    /// Factorize a symmetric matrix, None if it is not positive definite
+    pub fn new(matrix: &SparseCSR<f64>) -> Option<Self> {
+        let _span = Span::enter("Cholesky factorization");
+        let n = square_size(matrix);
+        debug!(
+            "Cholesky factorization of {} rows and {} entries",
+            n,
+            matrix.nnz()
+        );
+        let permutation = reverse_cuthill_mckee(matrix);
+        let mut inverse = vec![0; n];
+        for (new, old) in permutation.iter().enumerate() {
+            inverse[*old] = new;
+        }
+        let tolerance = SINGULAR_TOLERANCE * max_entry(matrix);
+        let mut columns: Vec<Vec<(usize, f64)>> = vec![Vec::new(); n];
+        let mut x = vec![0.0; n];
+        let mut marks = vec![false; n];
+        for k in 0..n {
+            // Column k of the upper triangle of the permuted matrix, which is row k by symmetry
+            let (cols, vals) = matrix.row(permutation[k]);
+            let mut diagonal = 0.0;
+            let mut starts = Vec::new();
+            for (col, value) in cols.iter().zip(vals) {
+                let i = inverse[*col];
+                if i < k {
+                    x[i] = *value;
+                    starts.push(i);
+                } else if i == k {
+                    diagonal = *value;
+                }
+            }
+            // Solve L[..k, ..k] y = a in the topological order of the reach, y being row k of L
+            let order = reach(&starts, &mut marks, |j| {
+                columns[j][1..].iter().map(|(r, _)| *r).collect()
+            });
+            for j in order.iter() {
+                let y = x[*j] / columns[*j][0].1;
+                x[*j] = y;
+                for (r, l) in columns[*j][1..].iter() {
+                    x[*r] -= l * y;
+                }
+                diagonal -= y * y;
+            }
+            if diagonal <= tolerance {
+                return None;
+            }
+            columns[k].push((k, diagonal.sqrt()));
+            let mut row = order;
+            row.sort_unstable();
+            for j in row {
+                columns[j].push((k, x[j]));
+                x[j] = 0.0;
+            }
+        }
+        Some(SparseCholesky {
+            permutation,
+            columns,
+            norm: norm1(matrix),
+        })
+    }
+
+    /// Size of the factorized matrix
+    pub fn size(&self) -> usize {
+        self.permutation.len()
+    }
+
+    /// Number of non zero entries of L
+    pub fn nnz(&self) -> usize {
+        self.columns.iter().map(|c| c.len()).sum()
+    }
+
+    /// Original index of every reordered unknown
+    pub fn permutation(&self) -> &[usize] {
+        &self.permutation
+    }
+
+    /// Solve A x = b
+    pub fn solve(&self, rhs: &[f64], x: &mut [f64]) {
+        let n = self.size();
+        assert!(
+            rhs.len() == n && x.len() == n,
+            "Vectors do not match the size of the matrix"
+        );
+        let mut y: Vec<f64> = self.permutation.iter().map(|i| rhs[*i]).collect();
+        for (j, column) in self.columns.iter().enumerate() {
+            y[j] /= column[0].1;
+            for (r, l) in column[1..].iter() {
+                y[*r] -= l * y[j];
+            }
+        }
+        for (j, column) in self.columns.iter().enumerate().rev() {
+            let sum: f64 = column[1..].iter().map(|(r, l)| l * y[*r]).sum();
+            y[j] = (y[j] - sum) / column[0].1;
+        }
+        for (new, old) in self.permutation.iter().enumerate() {
+            x[*old] = y[new];
+        }
+    }
+
+    /// Estimate of the 1-norm condition number of the matrix from a few solves
+    pub fn condition_estimate(&self) -> f64 {
+        let solve = |b: &[f64], x: &mut [f64]| self.solve(b, x);
+        self.norm * inverse_norm1_estimate(self.size(), solve, solve)
+    }
+}
+
+impl SparseLU {
+    /// Factorize a square matrix, None if it is singular
+    pub fn new(matrix: &SparseCSR<f64>) -> Option<Self> {
+        let _span = Span::enter("LU factorization");
+        let n = square_size(matrix);
+        debug!(
+            "LU factorization of {} rows and {} entries",
+            n,
+            matrix.nnz()
+        );
+        let column_order = reverse_cuthill_mckee(matrix);
+        let by_columns = matrix.transpose();
+        let tolerance = SINGULAR_TOLERANCE * max_entry(matrix);
+        let mut step_of_row: Vec<Option<usize>> = vec![None; n];
+        let mut pivot_rows = Vec::with_capacity(n);
+        let mut lower: Vec<Vec<(usize, f64)>> = Vec::with_capacity(n);
+        let mut upper: Vec<Vec<(usize, f64)>> = Vec::with_capacity(n);
+        let mut x = vec![0.0; n];
+        let mut touched = vec![false; n];
+        let mut marks = vec![false; n];
+        for (k, column) in column_order.iter().copied().enumerate() {
+            let (rows, vals) = by_columns.row(column);
+            let mut pattern = Vec::new();
+            let mut starts = Vec::new();
+            for (row, value) in rows.iter().zip(vals) {
+                x[*row] = *value;
+                touched[*row] = true;
+                pattern.push(*row);
+                if let Some(j) = step_of_row[*row] {
+                    starts.push(j);
+                }
+            }
+            // Solve with the columns of L already computed in the topological order of the reach
+            let order = reach(&starts, &mut marks, |j| {
+                lower[j]
+                    .iter()
+                    .filter_map(|(r, _)| step_of_row[*r])
+                    .collect()
+            });
+            let mut u_column = Vec::with_capacity(order.len() + 1);
+            for j in order.iter() {
+                let u = x[pivot_rows[*j]];
+                u_column.push((*j, u));
+                for (r, l) in lower[*j].iter() {
+                    if !touched[*r] {
+                        touched[*r] = true;
+                        pattern.push(*r);
+                    }
+                    x[*r] -= l * u;
+                }
+            }
+            // Threshold pivoting among the rows not pivoted yet
+            let candidates: Vec<usize> = pattern
+                .iter()
+                .copied()
+                .filter(|r| step_of_row[*r].is_none())
+                .collect();
+            let largest = candidates.iter().fold(0.0, |m: f64, r| m.max(x[*r].abs()));
+            if largest <= tolerance {
+                return None;
+            }
+            let pivot = if touched[column]
+                && step_of_row[column].is_none()
+                && x[column].abs() >= PIVOT_THRESHOLD * largest
+            {
+                column
+            } else {
+                *candidates.iter().find(|r| x[**r].abs() == largest).unwrap()
+            };
+            let diagonal = x[pivot];
+            u_column.push((k, diagonal));
+            let mut l_column: Vec<(usize, f64)> = candidates
+                .iter()
+                .filter(|r| **r != pivot && x[**r] != 0.0)
+                .map(|r| (*r, x[*r] / diagonal))
+                .collect();
+            l_column.sort_unstable_by_key(|(r, _)| *r);
+            step_of_row[pivot] = Some(k);
+            pivot_rows.push(pivot);
+            lower.push(l_column);
+            upper.push(u_column);
+            for r in pattern {
+                x[r] = 0.0;
+                touched[r] = false;
+            }
+        }
+        Some(SparseLU {
+            column_order,
+            pivot_rows,
+            lower,
+            upper,
+            norm: norm1(matrix),
+        })
+    }
+
+    /// Size of the factorized matrix
+    pub fn size(&self) -> usize {
+        self.column_order.len()
+    }
+
+    /// Number of non zero entries of L and U (without the unit diagonal of L)
+    pub fn nnz(&self) -> usize {
+        self.lower
+            .iter()
+            .chain(self.upper.iter())
+            .map(|c| c.len())
+            .sum()
+    }
+
+    /// Solve A x = b
+    pub fn solve(&self, rhs: &[f64], x: &mut [f64]) {
+        let n = self.size();
+        assert!(
+            rhs.len() == n && x.len() == n,
+            "Vectors do not match the size of the matrix"
+        );
+        let mut z = rhs.to_vec();
+        let mut y = vec![0.0; n];
+        for (j, column) in self.lower.iter().enumerate() {
+            y[j] = z[self.pivot_rows[j]];
+            for (r, l) in column.iter() {
+                z[*r] -= l * y[j];
+            }
+        }
+        for (k, column) in self.upper.iter().enumerate().rev() {
+            let (last, others) = column.split_last().unwrap();
+            y[k] /= last.1;
+            for (j, u) in others.iter() {
+                y[*j] -= u * y[k];
+            }
+            x[self.column_order[k]] = y[k];
+        }
+    }
+
+    /// Solve A^T x = b
+    pub fn solve_transpose(&self, rhs: &[f64], x: &mut [f64]) {
+        let n = self.size();
+        assert!(
+            rhs.len() == n && x.len() == n,
+            "Vectors do not match the size of the matrix"
+        );
+        // U^T w = b in the column order then L^T x = w, the rows of L being pivoted later
+        let mut w: Vec<f64> = self.column_order.iter().map(|c| rhs[*c]).collect();
+        for (k, column) in self.upper.iter().enumerate() {
+            let (last, others) = column.split_last().unwrap();
+            let sum: f64 = others.iter().map(|(j, u)| u * w[*j]).sum();
+            w[k] = (w[k] - sum) / last.1;
+        }
+        for (j, column) in self.lower.iter().enumerate().rev() {
+            let sum: f64 = column.iter().map(|(r, l)| l * x[*r]).sum();
+            x[self.pivot_rows[j]] = w[j] - sum;
+        }
+    }
+
+    /// Estimate of the 1-norm condition number of the matrix from a few solves
+    pub fn condition_estimate(&self) -> f64 {
+        let inverse_norm = inverse_norm1_estimate(
+            self.size(),
+            |b, x| self.solve(b, x),
+            |b, x| self.solve_transpose(b, x),
+        );
+        self.norm * inverse_norm
+    }
+}
+
+impl Preconditioner for SparseCholesky {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        self.solve(r, z);
+    }
+}
+
+impl Preconditioner for SparseLU {
+    fn apply(&self, r: &[f64], z: &mut [f64]) {
+        self.solve(r, z);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Reverse Cuthill-McKee ordering of the symmetrized pattern of a square matrix
+///
+/// The returned permutation gives the original index of every reordered unknown. Every connected
+/// component is started from a vertex of minimum degree.
+pub fn reverse_cuthill_mckee(matrix: &SparseCSR<f64>) -> Vec<usize> {
+    let n = square_size(matrix);
+    let transposed = matrix.transpose();
+    let neighbours: Vec<Vec<usize>> = (0..n)
+        .map(|i| {
+            let mut row: Vec<usize> = matrix
+                .row(i)
+                .0
+                .iter()
+                .chain(transposed.row(i).0)
+                .copied()
+                .filter(|j| *j != i)
+                .collect();
+            row.sort_unstable();
+            row.dedup();
+            row
+        })
+        .collect();
+    let mut by_degree: Vec<usize> = (0..n).collect();
+    by_degree.sort_by_key(|i| neighbours[*i].len());
+    let mut visited = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    for start in by_degree {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut queue = VecDeque::from([start]);
+        while let Some(i) = queue.pop_front() {
+            order.push(i);
+            let mut next: Vec<usize> = neighbours[i]
+                .iter()
+                .copied()
+                .filter(|j| !visited[*j])
+                .collect();
+            next.sort_by_key(|j| neighbours[*j].len());
+            for j in next {
+                visited[j] = true;
+                queue.push_back(j);
+            }
+        }
+    }
+    order.reverse();
+    order
+}
+
+// Nodes reachable from the starts in the graph given by the children in topological order
+// (reverse post order of a depth first search), the marks are left cleared
+fn reach<Children>(starts: &[usize], marks: &mut [bool], children: Children) -> Vec<usize>
+where
+    Children: Fn(usize) -> Vec<usize>,
+{
+    let mut post_order = Vec::new();
+    let mut stack: Vec<(usize, Vec<usize>)> = Vec::new();
+    for start in starts {
+        if marks[*start] {
+            continue;
+        }
+        marks[*start] = true;
+        stack.push((*start, children(*start)));
+        while let Some((node, pending)) = stack.last_mut() {
+            match pending.pop() {
+                Some(child) if !marks[child] => {
+                    marks[child] = true;
+                    let grand_children = children(child);
+                    stack.push((child, grand_children));
+                }
+                Some(_) => {}
+                None => {
+                    post_order.push(*node);
+                    stack.pop();
+                }
+            }
+        }
+    }
+    for node in post_order.iter() {
+        marks[*node] = false;
+    }
+    post_order.reverse();
+    post_order
+}
+
+fn square_size(matrix: &SparseCSR<f64>) -> usize {
+    assert_eq!(
+        matrix.n_rows(),
+        matrix.n_cols(),
+        "Tried to factorize a non square matrix"
+    );
+    matrix.n_rows()
+}
+
+fn max_entry(matrix: &SparseCSR<f64>) -> f64 {
+    matrix.values().iter().fold(0.0, |m: f64, v| m.max(v.abs()))
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Five point stencil on a square grid with a first order term of the given strength
+    fn build_matrix(side: usize, advection: f64) -> SparseCSR<f64> {
+        let mut row_offsets = vec![0];
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+        for i in 0..side * side {
+            let (x, y) = (i % side, i / side);
+            let mut row = vec![(i, 4.0 + advection)];
+            if y > 0 {
+                row.push((i - side, -1.0));
+            }
+            if x > 0 {
+                row.push((i - 1, -1.0 - advection));
+            }
+            if x + 1 < side {
+                row.push((i + 1, -1.0));
+            }
+            if y + 1 < side {
+                row.push((i + side, -1.0));
+            }
+            row.sort_by_key(|(j, _)| *j);
+            for (j, a) in row {
+                col_indices.push(j);
+                values.push(a);
+            }
+            row_offsets.push(col_indices.len());
+        }
+        SparseCSR::new(side * side, row_offsets, col_indices, values)
+    }
+
+    fn check_solution(matrix: &SparseCSR<f64>, x: &[f64], rhs: &[f64]) {
+        let mut product = vec![0.0; rhs.len()];
+        matrix.apply(x, &mut product);
+        for (p, b) in product.iter().zip(rhs) {
+            assert!((p - b).abs() < 1e-10, "Wrong solution");
+        }
+    }
+
+    #[test]
+    fn test_sparse_cholesky() {
+        let side = 20;
+        let n = side * side;
+        let matrix = build_matrix(side, 0.0);
+        let cholesky = SparseCholesky::new(&matrix).unwrap();
+        assert!(cholesky.nnz() < n * side * 2, "Fill is not bounded");
+        let mut sorted = cholesky.permutation().to_vec();
+        sorted.sort_unstable();
+        assert!(sorted.iter().copied().eq(0..n), "Not a permutation");
+        let rhs: Vec<f64> = (0..n).map(|i| ((i * i) % 7) as f64).collect();
+        let mut x = vec![0.0; n];
+        cholesky.solve(&rhs, &mut x);
+        check_solution(&matrix, &x, &rhs);
+        let indefinite =
+            SparseCSR::new(2, vec![0, 2, 4], vec![0, 1, 0, 1], vec![1.0, 2.0, 2.0, 1.0]);
+        assert!(
+            SparseCholesky::new(&indefinite).is_none(),
+            "Matrix is not positive definite"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_sparse_lu() {
+        let side = 15;
+        let n = side * side;
+        let matrix = build_matrix(side, 2.0);
+        let lu = SparseLU::new(&matrix).unwrap();
+        assert!(lu.nnz() < n * side * 4, "Fill is not bounded");
+        let rhs: Vec<f64> = (0..n).map(|i| (i as f64).sin()).collect();
+        let mut x = vec![0.0; n];
+        lu.solve(&rhs, &mut x);
+        check_solution(&matrix, &x, &rhs);
+        lu.solve_transpose(&rhs, &mut x);
+        check_solution(&matrix.transpose(), &x, &rhs);
+        // Needs pivoting since the first diagonal entry vanishes
+        let matrix = SparseCSR::new(
+            3,
+            vec![0, 2, 4, 6],
+            vec![1, 2, 0, 1, 0, 2],
+            vec![2.0, 1.0, 1.0, 1.0, 3.0, 1.0],
+        );
+        let lu = SparseLU::new(&matrix).unwrap();
+        let mut x = vec![0.0; 3];
+        lu.solve(&[3.0, 2.0, 4.0], &mut x);
+        for (x, expected) in x.iter().zip([1.0, 1.0, 1.0]) {
+            assert!((x - expected).abs() < 1e-14, "Wrong solution with pivoting");
+        }
+        lu.solve_transpose(&[4.0, 3.0, 2.0], &mut x);
+        for (x, expected) in x.iter().zip([1.0, 1.0, 1.0]) {
+            assert!((x - expected).abs() < 1e-14, "Wrong transposed solution");
+        }
+        let singular = SparseCSR::new(2, vec![0, 2, 4], vec![0, 1, 0, 1], vec![1.0, 2.0, 2.0, 4.0]);
+        assert!(
+            SparseLU::new(&singular).is_none(),
+            "Matrix should be singular"
+        );
+    }
+}
@@ -0,0 +1,371 @@
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Reason an iterative solve stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// Residual norm below the absolute tolerance
+    AbsoluteTolerance,
+    /// Residual norm below the relative tolerance times the reference norm
+    RelativeTolerance,
+    /// Norm of the last update of the solution below the solution tolerance times the norm of the
+    /// solution
+    SolutionChange,
+    /// Residual norm not reduced enough over the stagnation window
+    Stagnation,
+    /// Maximum number of iterations reached
+    MaxIterations,
+    /// Solver unable to go on, for instance on a singular jacobian or an operator which is not
+    /// positive definite for the conjugate gradient
+    Breakdown,
+}
+
+/// Tests deciding when an iterative solver stops
+///
+/// A solve converges when the residual norm falls below the largest of the absolute tolerance and
+/// the relative tolerance times a reference norm chosen by the solver (the right hand side for
+/// linear solvers, the initial residual for nonlinear ones), or when the update of the solution
+/// falls below the solution tolerance relative to the solution. It fails when the residual norm
+/// is not reduced by the stagnation factor over the stagnation window or after the maximum number
+/// of iterations. The solution and stagnation tests are disabled by default.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StoppingCriterion {
+    relative_tolerance: f64,
+    absolute_tolerance: f64,
+    solution_tolerance: f64,
+    stagnation: Option<(usize, f64)>,
+    max_iterations: usize,
+}
+
+// Stopping criterion evaluated along one solve
+pub(crate) struct StoppingTest<'a> {
+    criterion: &'a StoppingCriterion,
+    tolerance: f64,
+    residual_norms: Vec<f64>,
+}
+
+impl StopReason {
+    /// Whether the solve stopped on one of the convergence tests
+    pub fn converged(&self) -> bool {
+        matches!(
+            self,
+            StopReason::AbsoluteTolerance
+                | StopReason::RelativeTolerance
+                | StopReason::SolutionChange
+        )
+    }
+}
+
+impl StoppingCriterion {
+    /// Criterion with the given residual tolerances and maximum number of iterations
+    pub fn new(relative_tolerance: f64, absolute_tolerance: f64, max_iterations: usize) -> Self {
+        StoppingCriterion {
+            relative_tolerance,
+            absolute_tolerance,
+            solution_tolerance: 0.0,
+            stagnation: None,
+            max_iterations,
+        }
+    }
+
+    /// Set the tolerance on the residual relative to the reference norm
+    pub fn with_relative_tolerance(mut self, tolerance: f64) -> Self {
+        self.relative_tolerance = tolerance;
+        self
+    }
+
+    /// Set the tolerance on the norm of the residual
+    pub fn with_absolute_tolerance(mut self, tolerance: f64) -> Self {
+        self.absolute_tolerance = tolerance;
+        self
+    }
+
+    /// Set the tolerance on the norm of the update relative to the norm of the solution
+    pub fn with_solution_tolerance(mut self, tolerance: f64) -> Self {
+        self.solution_tolerance = tolerance;
+        self
+    }
+
+    /// Stop when the residual norm is not multiplied by at most the given factor over a window of
+    /// iterations
+    pub fn with_stagnation(mut self, window: usize, factor: f64) -> Self {
+        assert!(window > 0, "Stagnation window should not be empty");
+        self.stagnation = Some((window, factor));
+        self
+    }
+
+    /// Set the maximum number of iterations
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Tolerance on the residual relative to the reference norm
+    pub fn relative_tolerance(&self) -> f64 {
+        self.relative_tolerance
+    }
+
+    /// Tolerance on the norm of the residual
+    pub fn absolute_tolerance(&self) -> f64 {
+        self.absolute_tolerance
+    }
+
+    /// Tolerance on the update relative to the solution, 0 when the test is disabled
+    pub fn solution_tolerance(&self) -> f64 {
+        self.solution_tolerance
+    }
+
+    /// Maximum number of iterations
+    pub fn max_iterations(&self) -> usize {
+        self.max_iterations
+    }
+
+    // Start evaluating the criterion for a solve of the given reference norm
+    pub(crate) fn start(&self, reference_norm: f64) -> StoppingTest<'_> {
+        StoppingTest {
+            criterion: self,
+            tolerance: self
+                .absolute_tolerance
+                .max(self.relative_tolerance * reference_norm),
+            residual_norms: Vec::new(),
+        }
+    }
+}
+
+impl StoppingTest<'_> {
+    // Reason to stop after the given iteration if any, the update and solution norms being given
+    // when the solution changed. Calling it again for the same iteration replaces the residual.
+    pub(crate) fn check(
+        &mut self,
+        iteration: usize,
+        residual_norm: f64,
+        update: Option<(f64, f64)>,
+    ) -> Option<StopReason> {
+        let criterion = self.criterion;
+        self.residual_norms.truncate(iteration);
+        self.residual_norms.push(residual_norm);
+        if residual_norm <= self.tolerance {
+            return Some(if residual_norm <= criterion.absolute_tolerance {
+                StopReason::AbsoluteTolerance
+            } else {
+                StopReason::RelativeTolerance
+            });
+        }
+        if let Some((update_norm, solution_norm)) = update {
+            if criterion.solution_tolerance > 0.0
+                && update_norm <= criterion.solution_tolerance * solution_norm
+            {
+                return Some(StopReason::SolutionChange);
+            }
+        }
+        if let Some((window, factor)) = criterion.stagnation {
+            let n = self.residual_norms.len();
+            if n > window && residual_norm > factor * self.residual_norms[n - 1 - window] {
+                return Some(StopReason::Stagnation);
+            }
+        }
+        if iteration >= criterion.max_iterations {
+            return Some(StopReason::MaxIterations);
+        }
+        None
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::sparse_csr::SparseCSR;
+    use crate::solvers::krylov::{ConjugateGradient, Gmres};
+    use crate::solvers::multigrid::Multigrid;
+    use crate::solvers::nonlinear::{Newton, NonlinearProblem};
+    use crate::solvers::relaxation::{Relaxation, RelaxationMethod};
+
+    // Scalar equation x^2 - 2 = 0
+    struct Square;
+
+    impl NonlinearProblem for Square {
+        fn size(&self) -> usize {
+            1
+        }
+
+        fn residual(&self, x: &[f64], r: &mut [f64]) {
+            r[0] = x[0] * x[0] - 2.0;
+        }
+
+        fn jacobian(&self, x: &[f64]) -> SparseCSR<f64> {
+            SparseCSR::new(1, vec![0, 1], vec![0], vec![2.0 * x[0]])
+        }
+    }
+
+    // 1D laplacian of n unknowns
+    fn laplacian(n: usize) -> SparseCSR<f64> {
+        let mut offsets = vec![0];
+        let (mut cols, mut values) = (Vec::new(), Vec::new());
+        for i in 0..n {
+            for j in i.saturating_sub(1)..(i + 2).min(n) {
+                cols.push(j);
+                values.push(if i == j { 2.0 } else { -1.0 });
+            }
+            offsets.push(cols.len());
+        }
+        SparseCSR::new(n, offsets, cols, values)
+    }
+
+    #[test]
+    fn test_stopping_criterion() {
+        let criterion = StoppingCriterion::new(1e-3, 1e-8, 10).with_stagnation(2, 0.5);
+        let mut test = criterion.start(1.0);
+        assert_eq!(test.check(0, 1.0, None), None, "Should go on");
+        assert_eq!(test.check(1, 0.4, None), None, "Should go on");
+        assert_eq!(
+            test.check(2, 0.55, None),
+            Some(StopReason::Stagnation),
+            "Residual was not halved in two iterations"
+        );
+        assert_eq!(
+            test.check(2, 1e-4, None),
+            Some(StopReason::RelativeTolerance),
+            "Iteration should have been replaced"
+        );
+        let criterion = criterion.with_solution_tolerance(1e-6);
+        let mut test = criterion.start(1.0);
+        assert_eq!(
+            test.check(1, 1.0, Some((1e-7, 1.0))),
+            Some(StopReason::SolutionChange),
+            "Solution did not change"
+        );
+        assert_eq!(
+            test.check(10, 1.0, None),
+            Some(StopReason::MaxIterations),
+            "Too many iterations"
+        );
+        // Shared by the solvers
+        let matrix = SparseCSR::new(
+            3,
+            vec![0, 2, 5, 7],
+            vec![0, 1, 0, 1, 2, 1, 2],
+            vec![2.0, -1.0, -1.0, 2.0, -1.0, -1.0, 2.0],
+        );
+        let mut x = vec![0.0; 3];
+        let convergence = ConjugateGradient::new()
+            .with_stopping_criterion(StoppingCriterion::new(0.0, 1e-12, 100))
+            .solve(&matrix, &[1.0, 0.0, 1.0], &mut x);
+        assert_eq!(
+            convergence.stop_reason(),
+            StopReason::AbsoluteTolerance,
+            "Conjugate gradient should converge"
+        );
+        let mut x = vec![0.0; 3];
+        let convergence = Relaxation::new(&matrix, RelaxationMethod::Jacobi(1.0))
+            .with_stopping_criterion(
+                StoppingCriterion::new(1e-12, 0.0, 1000).with_stagnation(5, 1e-3),
+            )
+            .solve(&[1.0, 0.0, 1.0], &mut x);
+        assert_eq!(
+            convergence.stop_reason(),
+            StopReason::Stagnation,
+            "Jacobi does not reduce the residual that fast"
+        );
+        assert!(!convergence.converged(), "Stagnation is a failure");
+    }
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_stopping_criterion_parameters() {
+        let criterion = StoppingCriterion::new(1e-6, 1e-9, 50)
+            .with_relative_tolerance(1e-4)
+            .with_absolute_tolerance(1e-7)
+            .with_solution_tolerance(1e-5)
+            .with_max_iterations(20);
+        assert_eq!(
+            criterion.relative_tolerance(),
+            1e-4,
+            "Wrong relative tolerance"
+        );
+        assert_eq!(
+            criterion.absolute_tolerance(),
+            1e-7,
+            "Wrong absolute tolerance"
+        );
+        assert_eq!(
+            criterion.solution_tolerance(),
+            1e-5,
+            "Wrong solution tolerance"
+        );
+        assert_eq!(
+            criterion.max_iterations(),
+            20,
+            "Wrong maximum of iterations"
+        );
+        let mut test = criterion.start(1e-4);
+        assert_eq!(
+            test.check(1, 5e-8, None),
+            Some(StopReason::AbsoluteTolerance),
+            "The absolute tolerance exceeds the relative one"
+        );
+        let reasons = [
+            (StopReason::AbsoluteTolerance, true),
+            (StopReason::RelativeTolerance, true),
+            (StopReason::SolutionChange, true),
+            (StopReason::Stagnation, false),
+            (StopReason::MaxIterations, false),
+            (StopReason::Breakdown, false),
+        ];
+        for (reason, converged) in reasons {
+            assert_eq!(
+                reason.converged(),
+                converged,
+                "Wrong outcome of {:?}",
+                reason
+            );
+        }
+        // Newton stops on the change of the solution when the residuals cannot vanish
+        let mut x = vec![1.0];
+        let convergence = Newton::new()
+            .with_stopping_criterion(
+                StoppingCriterion::new(0.0, 0.0, 50).with_solution_tolerance(1e-10),
+            )
+            .solve(&Square, &mut x);
+        assert_eq!(
+            convergence.stop_reason(),
+            StopReason::SolutionChange,
+            "Newton should stop on the solution change"
+        );
+        assert!(
+            (x[0] - 2.0_f64.sqrt()).abs() < 1e-12,
+            "Wrong Newton solution"
+        );
+        let matrix = laplacian(64);
+        let rhs = vec![1.0; 64];
+        let limited = StoppingCriterion::new(1e-14, 0.0, 2);
+        let mut x = vec![0.0; 64];
+        let convergence = Multigrid::new(&matrix)
+            .with_stopping_criterion(limited)
+            .solve(&rhs, &mut x);
+        assert_eq!(
+            (convergence.stop_reason(), convergence.iterations()),
+            (StopReason::MaxIterations, 2),
+            "Multigrid should stop after two cycles"
+        );
+        let mut x = vec![0.0; 64];
+        let convergence = Gmres::new()
+            .with_stopping_criterion(limited)
+            .solve(&matrix, &rhs, &mut x);
+        assert_eq!(
+            (convergence.stop_reason(), convergence.iterations()),
+            (StopReason::MaxIterations, 2),
+            "GMRES should stop after two iterations"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    #[should_panic]
+    fn test_stopping_criterion_empty_window() {
+        StoppingCriterion::new(1e-6, 0.0, 10).with_stagnation(0, 0.5);
+    }
+}
@@ -0,0 +1,1199 @@
+use super::linear_operator::Diagonal;
+use super::nonlinear::{Newton, NonlinearProblem};
+use super::sparse_direct::SparseLU;
+use crate::core::arrays::sparse_csr::SparseCSR;
+
+//--------------------------------------------------------------------------------------------------
+// # Traits
+//--------------------------------------------------------------------------------------------------
+
+/// Semi-discrete system of ordinary differential equations M du/dt = f(t, u)
+pub trait TransientProblem {
+    /// Number of unknowns
+    fn size(&self) -> usize;
+
+    /// Mass matrix M, which is only asked for once per integration
+    fn mass(&self) -> SparseCSR<f64>;
+
+    /// Compute the right hand side f(t, u)
+    fn rhs(&self, time: f64, u: &[f64], f: &mut [f64]);
+
+    /// Jacobian matrix of f with respect to u
+    fn jacobian(&self, time: f64, u: &[f64]) -> SparseCSR<f64>;
+}
+
+/// System M du/dt = g(t, u) + h(t, u) whose right hand side is split into a stiff part g treated
+/// implicitly and a nonstiff part h treated explicitly
+pub trait SplitProblem {
+    /// Number of unknowns
+    fn size(&self) -> usize;
+
+    /// Mass matrix M, which is only asked for once per integration
+    fn mass(&self) -> SparseCSR<f64>;
+
+    /// Compute the stiff part g(t, u) of the right hand side
+    fn implicit_rhs(&self, time: f64, u: &[f64], g: &mut [f64]);
+
+    /// Jacobian matrix of g with respect to u
+    fn implicit_jacobian(&self, time: f64, u: &[f64]) -> SparseCSR<f64>;
+
+    /// Compute the nonstiff part h(t, u) of the right hand side
+    fn explicit_rhs(&self, time: f64, u: &[f64], h: &mut [f64]);
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Implicit time discretization schemes
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum TimeScheme {
+    /// First order implicit (backward) Euler, the theta method with theta = 1
+    ImplicitEuler,
+    /// Second order Crank-Nicolson, the theta method with theta = 1/2
+    CrankNicolson,
+    /// Theta method M (u_n+1 - u_n) / dt = theta f(t_n+1, u_n+1) + (1 - theta) f(t_n, u_n)
+    Theta(f64),
+    /// Backward differentiation formula of the given order between 1 and 3
+    Bdf(usize),
+}
+
+/// Integrator of transient problems solving every step with Newton
+///
+/// Without step control, the time step is reduced if needed so that a whole number of steps ends
+/// on the final time.
+/// Multi-step BDF schemes of order k start with k - 1 Crank-Nicolson steps which keeps their
+/// order.
+pub struct TimeIntegrator {
+    scheme: TimeScheme,
+    time_step: f64,
+    newton: Newton,
+    step_control: Option<StepControl>,
+}
+
+/// Time step adaptivity from the local error estimated by step doubling
+///
+/// Every step is computed once with the full step and once with two half steps, the difference
+/// giving the local error of the scheme. A step whose error norm exceeds the tolerance, or whose
+/// Newton solve fails, is rejected and retried with a smaller step.
+///
+/// Step doubling stands in for embedded estimates: the integrators are implicit theta schemes
+/// without an embedded Runge-Kutta pair, and a BDF local truncation estimate would need the
+/// variable step BDF coefficients, so the adaptive schemes are restricted to the one step ones.
+/// The estimate costs three Newton solves per step instead of one but is accurate for any theta,
+/// Richardson extrapolation scaling the difference by 2^p - 1 with p the order of the scheme.
+#[derive(Clone, Copy, Debug)]
+pub struct StepControl {
+    relative_tolerance: f64,
+    absolute_tolerance: f64,
+    min_step: f64,
+    max_step: f64,
+    safety: f64,
+}
+
+/// Schemes for second order systems M a + C v + K u = f(t)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DynamicsScheme {
+    /// Newmark family of the given beta and gamma, (1/4, 1/2) being the unconditionally stable
+    /// and energy conserving average acceleration
+    Newmark(f64, f64),
+    /// Hilber-Hughes-Taylor method whose parameter in [0, 1/3] controls the numerical damping
+    Hht(f64),
+    /// Generalized-alpha method of Chung and Hulbert with the given spectral radius at infinite
+    /// frequency in [0, 1], lower values damping the high frequencies more
+    GeneralizedAlpha(f64),
+}
+
+/// Linear second order system M a + C v + K u = f(t) of structural dynamics
+pub struct SecondOrderSystem<'a> {
+    mass: &'a SparseCSR<f64>,
+    damping: Option<&'a SparseCSR<f64>>,
+    stiffness: &'a SparseCSR<f64>,
+}
+
+/// Displacement, velocity and acceleration of a second order system
+pub struct DynamicState {
+    displacement: Vec<f64>,
+    velocity: Vec<f64>,
+    acceleration: Vec<f64>,
+}
+
+/// Fixed step integrator of linear second order systems
+///
+/// The time step is reduced if needed so that a whole number of steps ends on the final time.
+/// The effective matrix of the scheme is factorized once by the sparse LU and the initial
+/// acceleration follows from the equation at the start time.
+pub struct DynamicsIntegrator {
+    scheme: DynamicsScheme,
+    time_step: f64,
+}
+
+/// Linear second order system M a + C v + K u = f(t) with lumped (diagonal) mass and damping
+/// matrices, as integrated by explicit schemes without solving any system
+pub struct LumpedSystem<'a> {
+    mass: &'a Diagonal,
+    damping: Option<&'a Diagonal>,
+    stiffness: &'a SparseCSR<f64>,
+}
+
+/// Explicit central difference integrator of lumped second order systems
+///
+/// Steps follow the velocity Verlet form v_n+1/2 = v_n + dt/2 a_n, u_n+1 = u_n + dt v_n+1/2 and
+/// (M + dt/2 C) v_n+1 = M v_n+1/2 + dt/2 (f_n+1 - K u_n+1), the diagonal damping keeping the
+/// scheme second order and free of solves. It is only stable for time steps below the critical
+/// one 2 / omega_max of the highest natural frequency (see LumpedSystem::critical_time_step). The
+/// time step is reduced if needed so that a whole number of steps ends on the final time.
+pub struct CentralDifference {
+    time_step: f64,
+}
+
+/// Implicit-explicit Runge-Kutta schemes of Ascher, Ruuth and Spiteri, which are all stiffly
+/// accurate and L-stable for the implicit part
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImexScheme {
+    /// First order forward-backward Euler
+    Euler,
+    /// Second order scheme with two implicit stages, ARS(2,2,2)
+    Ars222,
+    /// Third order scheme with four implicit stages, ARS(4,4,3)
+    Ars443,
+}
+
+/// Fixed step integrator of split problems with implicit-explicit Runge-Kutta schemes
+///
+/// Every implicit stage is solved with Newton. The time step is reduced if needed so that a whole
+/// number of steps ends on the final time.
+pub struct ImexIntegrator {
+    scheme: ImexScheme,
+    time_step: f64,
+    newton: Newton,
+}
+
+// Stiff part of a split problem seen as a transient problem for the implicit stages
+struct ImplicitPart<'a, Problem>(&'a Problem);
+
+// Theta method on a problem of the adaptive integration
+struct ThetaMethod<'a, Problem> {
+    problem: &'a Problem,
+    mass: &'a SparseCSR<f64>,
+    theta: f64,
+}
+
+// Nonlinear system a M u + history - c f(t, u) = 0 of one implicit step
+struct StepProblem<'a, Problem> {
+    problem: &'a Problem,
+    mass: &'a SparseCSR<f64>,
+    time: f64,
+    mass_factor: f64,
+    rhs_factor: f64,
+    history: Vec<f64>,
+}
+
+impl TimeIntegrator {
+    /// Integrator with the given scheme and time step
+    pub fn new(scheme: TimeScheme, time_step: f64) -> Self {
+        assert!(time_step > 0.0, "Time step should be positive");
+        if let TimeScheme::Bdf(order) = scheme {
+            assert!(
+                (1..=3).contains(&order),
+                "BDF schemes are implemented from order 1 to 3"
+            );
+        }
+        TimeIntegrator {
+            scheme,
+            time_step,
+            newton: Newton::new(),
+            step_control: None,
+        }
+    }
+
+    /// Adapt the time step, the one given at construction being the initial step
+    pub fn with_step_control(mut self, step_control: StepControl) -> Self {
+        assert!(
+            !matches!(self.scheme, TimeScheme::Bdf(order) if order > 1),
+            "Adaptive time steps are only implemented for one step schemes"
+        );
+        self.step_control = Some(step_control);
+        self
+    }
+
+    /// Set the Newton solver used for every step
+    pub fn with_newton(mut self, newton: Newton) -> Self {
+        self.newton = newton;
+        self
+    }
+
+    /// Integrate from the start to the end time, u holding the initial condition and then the
+    /// solution of every step, which is also passed to the observer
+    ///
+    /// The number of steps is returned, or the time of the step at which Newton failed to
+    /// converge. u then holds the solution of the last successful step.
+    pub fn integrate<Problem, Observer>(
+        &self,
+        problem: &Problem,
+        start: f64,
+        end: f64,
+        u: &mut [f64],
+        mut observer: Observer,
+    ) -> Result<usize, f64>
+    where
+        Problem: TransientProblem,
+        Observer: FnMut(f64, &[f64]),
+    {
+        let n = problem.size();
+        assert_eq!(u.len(), n, "Vector does not match the size of the problem");
+        if let Some(control) = &self.step_control {
+            return self.integrate_adaptive(problem, control, start, end, u, observer);
+        }
+        let n_steps = ((end - start) / self.time_step - 1e-10).ceil().max(1.0) as usize;
+        let dt = (end - start) / n_steps as f64;
+        let mass = problem.mass();
+        // Previous solutions, the latest first
+        let mut history: Vec<Vec<f64>> = vec![u.to_vec()];
+        let mut f = vec![0.0; n];
+        let mut mu = vec![0.0; n];
+        for step in 0..n_steps {
+            let time = start + step as f64 * dt;
+            let scheme = match self.scheme {
+                TimeScheme::Bdf(order) if step + 1 < order => TimeScheme::CrankNicolson,
+                scheme => scheme,
+            };
+            let (mass_factor, rhs_factor, step_history) = match scheme {
+                TimeScheme::Bdf(order) => {
+                    let coefficients: &[f64] = match order {
+                        1 => &[1.0, -1.0],
+                        2 => &[1.5, -2.0, 0.5],
+                        _ => &[11.0 / 6.0, -3.0, 1.5, -1.0 / 3.0],
+                    };
+                    let mut combination = vec![0.0; n];
+                    for (previous, a) in history.iter().zip(&coefficients[1..]) {
+                        combination
+                            .iter_mut()
+                            .zip(previous)
+                            .for_each(|(c, p)| *c += a * p);
+                    }
+                    mass.apply(&combination, &mut mu);
+                    (coefficients[0], dt, mu.clone())
+                }
+                _ => {
+                    let theta = match scheme {
+                        TimeScheme::ImplicitEuler => 1.0,
+                        TimeScheme::CrankNicolson => 0.5,
+                        TimeScheme::Theta(theta) => theta,
+                        TimeScheme::Bdf(_) => unreachable!(),
+                    };
+                    problem.rhs(time, &history[0], &mut f);
+                    mass.apply(&history[0], &mut mu);
+                    let step_history = mu
+                        .iter()
+                        .zip(&f)
+                        .map(|(m, f)| -m - dt * (1.0 - theta) * f)
+                        .collect();
+                    (1.0, dt * theta, step_history)
+                }
+            };
+            let step_problem = StepProblem {
+                problem,
+                mass: &mass,
+                time: time + dt,
+                mass_factor,
+                rhs_factor,
+                history: step_history,
+            };
+            let mut next = history[0].clone();
+            if !self.newton.solve(&step_problem, &mut next).converged() {
+                return Err(time + dt);
+            }
+            u.copy_from_slice(&next);
+            observer(time + dt, u);
+            history.insert(0, next);
+            history.truncate(3);
+        }
+        Ok(n_steps)
+    }
+
+    // Adaptive counterpart of integrate for the one step schemes
+    fn integrate_adaptive<Problem, Observer>(
+        &self,
+        problem: &Problem,
+        control: &StepControl,
+        start: f64,
+        end: f64,
+        u: &mut [f64],
+        mut observer: Observer,
+    ) -> Result<usize, f64>
+    where
+        Problem: TransientProblem,
+        Observer: FnMut(f64, &[f64]),
+    {
+        let theta = match self.scheme {
+            TimeScheme::ImplicitEuler | TimeScheme::Bdf(_) => 1.0,
+            TimeScheme::CrankNicolson => 0.5,
+            TimeScheme::Theta(theta) => theta,
+        };
+        let order = if theta == 0.5 { 2 } else { 1 };
+        let mass = problem.mass();
+        let method = ThetaMethod {
+            problem,
+            mass: &mass,
+            theta,
+        };
+        let mut time = start;
+        let mut dt = self.time_step.clamp(control.min_step, control.max_step);
+        let mut n_steps = 0;
+        let mut coarse = u.to_vec();
+        let mut middle = u.to_vec();
+        let mut fine = u.to_vec();
+        while time < end && end - time > 1e-12 * (end - start) {
+            dt = dt.min(end - time);
+            let accepted = self.theta_step(&method, time, dt, u, &mut coarse)
+                && self.theta_step(&method, time, 0.5 * dt, u, &mut middle)
+                && self.theta_step(&method, time + 0.5 * dt, 0.5 * dt, &middle, &mut fine);
+            // Weighted root mean square of the local error of the fine solution
+            let error = if accepted {
+                let scale = (2.0_f64.powi(order) - 1.0) * (u.len() as f64).sqrt();
+                coarse
+                    .iter()
+                    .zip(&fine)
+                    .map(|(c, f)| {
+                        let weight =
+                            control.absolute_tolerance + control.relative_tolerance * f.abs();
+                        ((f - c) / (scale * weight)).powi(2)
+                    })
+                    .sum::<f64>()
+                    .sqrt()
+            } else {
+                f64::INFINITY
+            };
+            if error <= 1.0 {
+                time += dt;
+                n_steps += 1;
+                u.copy_from_slice(&fine);
+                observer(time, u);
+            } else if dt <= control.min_step {
+                return Err(time + dt);
+            }
+            let factor = if error.is_finite() {
+                control.safety * error.max(1e-10).powf(-1.0 / (order + 1) as f64)
+            } else {
+                0.25
+            };
+            dt = (dt * factor.clamp(0.2, 5.0)).clamp(control.min_step, control.max_step);
+        }
+        Ok(n_steps)
+    }
+
+    // Solve one step of the theta method from u at the given time into next
+    fn theta_step<Problem: TransientProblem>(
+        &self,
+        method: &ThetaMethod<Problem>,
+        time: f64,
+        dt: f64,
+        u: &[f64],
+        next: &mut [f64],
+    ) -> bool {
+        let ThetaMethod {
+            problem,
+            mass,
+            theta,
+        } = *method;
+        let mut f = vec![0.0; u.len()];
+        let mut mu = vec![0.0; u.len()];
+        problem.rhs(time, u, &mut f);
+        mass.apply(u, &mut mu);
+        let step_problem = StepProblem {
+            problem,
+            mass,
+            time: time + dt,
+            mass_factor: 1.0,
+            rhs_factor: dt * theta,
+            history: mu
+                .iter()
+                .zip(&f)
+                .map(|(m, f)| -m - dt * (1.0 - theta) * f)
+                .collect(),
+        };
+        next.copy_from_slice(u);
+        self.newton.solve(&step_problem, next).converged()
+    }
+}
+
+impl StepControl {
+    /// Control keeping the local error below the given relative tolerance
+    pub fn new(relative_tolerance: f64) -> Self {
+        StepControl {
+            relative_tolerance,
+            absolute_tolerance: relative_tolerance,
+            min_step: 1e-12,
+            max_step: f64::INFINITY,
+            safety: 0.9,
+        }
+    }
+
+    /// Set the absolute tolerance on the local error, equal to the relative one by default
+    pub fn with_absolute_tolerance(mut self, tolerance: f64) -> Self {
+        self.absolute_tolerance = tolerance;
+        self
+    }
+
+    /// Set the smallest and largest allowed time steps
+    pub fn with_step_bounds(mut self, min_step: f64, max_step: f64) -> Self {
+        assert!(
+            0.0 < min_step && min_step <= max_step,
+            "Step bounds should be positive and ordered"
+        );
+        self.min_step = min_step;
+        self.max_step = max_step;
+        self
+    }
+
+    /// Set the safety factor applied to the optimal step, 0.9 by default
+    pub fn with_safety(mut self, safety: f64) -> Self {
+        self.safety = safety;
+        self
+    }
+}
+
+impl<'a> SecondOrderSystem<'a> {
+    /// Undamped system of the given mass and stiffness matrices
+    pub fn new(mass: &'a SparseCSR<f64>, stiffness: &'a SparseCSR<f64>) -> Self {
+        assert!(
+            mass.n_rows() == stiffness.n_rows() && mass.n_cols() == stiffness.n_cols(),
+            "Mass and stiffness matrices do not have the same size"
+        );
+        SecondOrderSystem {
+            mass,
+            damping: None,
+            stiffness,
+        }
+    }
+
+    /// Set the damping matrix
+    pub fn with_damping(mut self, damping: &'a SparseCSR<f64>) -> Self {
+        assert!(
+            damping.n_rows() == self.mass.n_rows() && damping.n_cols() == self.mass.n_cols(),
+            "Damping matrix does not have the size of the mass matrix"
+        );
+        self.damping = Some(damping);
+        self
+    }
+
+    /// Number of unknowns
+    pub fn size(&self) -> usize {
+        self.mass.n_rows()
+    }
+
+    // Compute f - C v - K u
+    fn internal_balance(&self, load: &[f64], u: &[f64], v: &[f64], out: &mut [f64]) {
+        let mut product = vec![0.0; out.len()];
+        self.stiffness.apply(u, out);
+        if let Some(damping) = self.damping {
+            damping.apply(v, &mut product);
+            out.iter_mut().zip(&product).for_each(|(o, p)| *o += p);
+        }
+        out.iter_mut().zip(load).for_each(|(o, f)| *o = f - *o);
+    }
+}
+
+impl DynamicState {
+    /// State at rest with the given displacement and velocity, the acceleration being computed
+    /// when integrating
+    pub fn new(displacement: Vec<f64>, velocity: Vec<f64>) -> Self {
+        assert_eq!(
+            displacement.len(),
+            velocity.len(),
+            "Displacement and velocity do not have the same size"
+        );
+        let acceleration = vec![0.0; displacement.len()];
+        DynamicState {
+            displacement,
+            velocity,
+            acceleration,
+        }
+    }
+
+    /// Displacement u
+    pub fn displacement(&self) -> &[f64] {
+        &self.displacement
+    }
+
+    /// Velocity v
+    pub fn velocity(&self) -> &[f64] {
+        &self.velocity
+    }
+
+    /// Acceleration a
+    pub fn acceleration(&self) -> &[f64] {
+        &self.acceleration
+    }
+}
+
+impl DynamicsIntegrator {
+    /// Integrator with the given scheme and time step
+    pub fn new(scheme: DynamicsScheme, time_step: f64) -> Self {
+        assert!(time_step > 0.0, "Time step should be positive");
+        match scheme {
+            DynamicsScheme::Hht(alpha) => assert!(
+                (0.0..=1.0 / 3.0).contains(&alpha),
+                "HHT parameter should be in [0, 1/3]"
+            ),
+            DynamicsScheme::GeneralizedAlpha(radius) => assert!(
+                (0.0..=1.0).contains(&radius),
+                "Spectral radius should be in [0, 1]"
+            ),
+            DynamicsScheme::Newmark(..) => {}
+        }
+        DynamicsIntegrator { scheme, time_step }
+    }
+
+    // Parameters (alpha_m, alpha_f, beta, gamma) of the generalized-alpha form of the scheme
+    fn parameters(&self) -> (f64, f64, f64, f64) {
+        match self.scheme {
+            DynamicsScheme::Newmark(beta, gamma) => (0.0, 0.0, beta, gamma),
+            DynamicsScheme::Hht(alpha) => (0.0, alpha, 0.25 * (1.0 + alpha).powi(2), 0.5 + alpha),
+            DynamicsScheme::GeneralizedAlpha(radius) => {
+                let alpha_m = (2.0 * radius - 1.0) / (radius + 1.0);
+                let alpha_f = radius / (radius + 1.0);
+                let gamma = 0.5 - alpha_m + alpha_f;
+                (
+                    alpha_m,
+                    alpha_f,
+                    0.25 * (1.0 - alpha_m + alpha_f).powi(2),
+                    gamma,
+                )
+            }
+        }
+    }
+
+    /// Integrate from the start to the end time, the load being computed by load(t, f), and
+    /// return the number of steps
+    ///
+    /// The state holds the initial displacement and velocity and is updated at every step before
+    /// being passed to the observer.
+    pub fn integrate<Load, Observer>(
+        &self,
+        system: &SecondOrderSystem,
+        load: Load,
+        start: f64,
+        end: f64,
+        state: &mut DynamicState,
+        mut observer: Observer,
+    ) -> usize
+    where
+        Load: Fn(f64, &mut [f64]),
+        Observer: FnMut(f64, &DynamicState),
+    {
+        let n = system.size();
+        assert_eq!(
+            state.displacement.len(),
+            n,
+            "State does not match the size of the system"
+        );
+        let n_steps = ((end - start) / self.time_step - 1e-10).ceil().max(1.0) as usize;
+        let dt = (end - start) / n_steps as f64;
+        let (alpha_m, alpha_f, beta, gamma) = self.parameters();
+        let mut f = vec![0.0; n];
+        let mut balance = vec![0.0; n];
+        load(start, &mut f);
+        system.internal_balance(&f, &state.displacement, &state.velocity, &mut balance);
+        SparseLU::new(system.mass)
+            .expect("Mass matrix is singular")
+            .solve(&balance, &mut state.acceleration);
+        // (1 - alpha_m) M + (1 - alpha_f) (gamma dt C + beta dt^2 K)
+        let mut effective = system.mass.clone();
+        effective
+            .values_mut()
+            .iter_mut()
+            .for_each(|m| *m *= 1.0 - alpha_m);
+        let effective = effective.add((1.0 - alpha_f) * beta * dt * dt, system.stiffness);
+        let effective = match system.damping {
+            Some(damping) => effective.add((1.0 - alpha_f) * gamma * dt, damping),
+            None => effective,
+        };
+        let lu = SparseLU::new(&effective).expect("Effective matrix of the scheme is singular");
+        let mut u_predicted = vec![0.0; n];
+        let mut v_predicted = vec![0.0; n];
+        let mut ma = vec![0.0; n];
+        let mut next_acceleration = vec![0.0; n];
+        for step in 0..n_steps {
+            let time = start + step as f64 * dt;
+            let DynamicState {
+                displacement: u,
+                velocity: v,
+                acceleration: a,
+            } = &mut *state;
+            // Parts of the generalized midpoint quantities known from the current state
+            for i in 0..n {
+                let u_newmark = u[i] + dt * v[i] + dt * dt * (0.5 - beta) * a[i];
+                let v_newmark = v[i] + dt * (1.0 - gamma) * a[i];
+                u_predicted[i] = (1.0 - alpha_f) * u_newmark + alpha_f * u[i];
+                v_predicted[i] = (1.0 - alpha_f) * v_newmark + alpha_f * v[i];
+            }
+            load(time + (1.0 - alpha_f) * dt, &mut f);
+            system.internal_balance(&f, &u_predicted, &v_predicted, &mut balance);
+            system.mass.apply(a, &mut ma);
+            balance
+                .iter_mut()
+                .zip(&ma)
+                .for_each(|(b, ma)| *b -= alpha_m * ma);
+            lu.solve(&balance, &mut next_acceleration);
+            for i in 0..n {
+                let (a_old, a_new) = (a[i], next_acceleration[i]);
+                u[i] += dt * v[i] + dt * dt * ((0.5 - beta) * a_old + beta * a_new);
+                v[i] += dt * ((1.0 - gamma) * a_old + gamma * a_new);
+                a[i] = a_new;
+            }
+            observer(time + dt, state);
+        }
+        n_steps
+    }
+}
+
+impl<'a> LumpedSystem<'a> {
+    /// Undamped system of the given lumped mass and stiffness matrices
+    pub fn new(mass: &'a Diagonal, stiffness: &'a SparseCSR<f64>) -> Self {
+        assert!(
+            mass.size() == stiffness.n_rows() && mass.size() == stiffness.n_cols(),
+            "Mass and stiffness matrices do not have the same size"
+        );
+        assert!(
+            mass.values().iter().all(|m| *m > 0.0),
+            "Lumped masses should be positive"
+        );
+        LumpedSystem {
+            mass,
+            damping: None,
+            stiffness,
+        }
+    }
+
+    /// Set the lumped damping matrix, for instance a multiple of the mass
+    pub fn with_damping(mut self, damping: &'a Diagonal) -> Self {
+        assert_eq!(
+            damping.size(),
+            self.mass.size(),
+            "Damping matrix does not have the size of the mass matrix"
+        );
+        self.damping = Some(damping);
+        self
+    }
+
+    /// Number of unknowns
+    pub fn size(&self) -> usize {
+        self.mass.size()
+    }
+
+    /// Time step 2 / omega below the critical time step of the central difference scheme, the
+    /// highest natural frequency being bounded by omega^2 <= max_i sum_j |K_ij| / M_ii
+    pub fn critical_time_step(&self) -> f64 {
+        let bound = (0..self.size())
+            .map(|i| {
+                let row: f64 = self.stiffness.row(i).1.iter().map(|k| k.abs()).sum();
+                row / self.mass.values()[i]
+            })
+            .fold(0.0, f64::max);
+        2.0 / bound.sqrt()
+    }
+}
+
+impl CentralDifference {
+    /// Integrator with the given time step
+    pub fn new(time_step: f64) -> Self {
+        assert!(time_step > 0.0, "Time step should be positive");
+        CentralDifference { time_step }
+    }
+
+    /// Integrate from the start to the end time, the load being computed by load(t, f), and
+    /// return the number of steps
+    ///
+    /// The state holds the initial displacement and velocity and is updated at every step before
+    /// being passed to the observer.
+    pub fn integrate<Load, Observer>(
+        &self,
+        system: &LumpedSystem,
+        load: Load,
+        start: f64,
+        end: f64,
+        state: &mut DynamicState,
+        mut observer: Observer,
+    ) -> usize
+    where
+        Load: Fn(f64, &mut [f64]),
+        Observer: FnMut(f64, &DynamicState),
+    {
+        let n = system.size();
+        assert_eq!(
+            state.displacement.len(),
+            n,
+            "State does not match the size of the system"
+        );
+        let n_steps = ((end - start) / self.time_step - 1e-10).ceil().max(1.0) as usize;
+        let dt = (end - start) / n_steps as f64;
+        let mass = system.mass.values();
+        let damping = |i: usize| system.damping.map_or(0.0, |c| c.values()[i]);
+        let mut balance = vec![0.0; n];
+        // Compute f - K u
+        let elastic_balance = |time: f64, u: &[f64], balance: &mut [f64]| {
+            let mut ku = vec![0.0; n];
+            load(time, balance);
+            system.stiffness.apply(u, &mut ku);
+            balance.iter_mut().zip(&ku).for_each(|(b, ku)| *b -= ku);
+        };
+        elastic_balance(start, &state.displacement, &mut balance);
+        for i in 0..n {
+            state.acceleration[i] = (balance[i] - damping(i) * state.velocity[i]) / mass[i];
+        }
+        for step in 0..n_steps {
+            let time = start + (step + 1) as f64 * dt;
+            let DynamicState {
+                displacement: u,
+                velocity: v,
+                acceleration: a,
+            } = &mut *state;
+            for i in 0..n {
+                v[i] += 0.5 * dt * a[i];
+                u[i] += dt * v[i];
+            }
+            elastic_balance(time, u, &mut balance);
+            for i in 0..n {
+                let c = damping(i);
+                v[i] = (mass[i] * v[i] + 0.5 * dt * balance[i]) / (mass[i] + 0.5 * dt * c);
+                a[i] = (balance[i] - c * v[i]) / mass[i];
+            }
+            observer(time, state);
+        }
+        n_steps
+    }
+}
+
+impl ImexScheme {
+    // Butcher tableaux (c, implicit A, explicit A) with the explicit first stage included
+    fn tableaux(&self) -> (Vec<f64>, Vec<Vec<f64>>, Vec<Vec<f64>>) {
+        match self {
+            ImexScheme::Euler => (
+                vec![0.0, 1.0],
+                vec![vec![], vec![0.0, 1.0]],
+                vec![vec![], vec![1.0]],
+            ),
+            ImexScheme::Ars222 => {
+                let gamma = 1.0 - 0.5_f64.sqrt();
+                let delta = 1.0 - 0.5 / gamma;
+                (
+                    vec![0.0, gamma, 1.0],
+                    vec![vec![], vec![0.0, gamma], vec![0.0, 1.0 - gamma, gamma]],
+                    vec![vec![], vec![gamma], vec![delta, 1.0 - delta]],
+                )
+            }
+            ImexScheme::Ars443 => (
+                vec![0.0, 0.5, 2.0 / 3.0, 0.5, 1.0],
+                vec![
+                    vec![],
+                    vec![0.0, 0.5],
+                    vec![0.0, 1.0 / 6.0, 0.5],
+                    vec![0.0, -0.5, 0.5, 0.5],
+                    vec![0.0, 1.5, -1.5, 0.5, 0.5],
+                ],
+                vec![
+                    vec![],
+                    vec![0.5],
+                    vec![11.0 / 18.0, 1.0 / 18.0],
+                    vec![5.0 / 6.0, -5.0 / 6.0, 0.5],
+                    vec![0.25, 1.75, 0.75, -1.75],
+                ],
+            ),
+        }
+    }
+}
+
+impl ImexIntegrator {
+    /// Integrator with the given scheme and time step
+    pub fn new(scheme: ImexScheme, time_step: f64) -> Self {
+        assert!(time_step > 0.0, "Time step should be positive");
+        ImexIntegrator {
+            scheme,
+            time_step,
+            newton: Newton::new(),
+        }
+    }
+
+    /// Set the Newton solver used for every implicit stage
+    pub fn with_newton(mut self, newton: Newton) -> Self {
+        self.newton = newton;
+        self
+    }
+
+    /// Integrate from the start to the end time, u holding the initial condition and then the
+    /// solution of every step, which is also passed to the observer
+    ///
+    /// The number of steps is returned, or the time of the step at which Newton failed to
+    /// converge. u then holds the solution of the last successful step.
+    pub fn integrate<Problem, Observer>(
+        &self,
+        problem: &Problem,
+        start: f64,
+        end: f64,
+        u: &mut [f64],
+        mut observer: Observer,
+    ) -> Result<usize, f64>
+    where
+        Problem: SplitProblem,
+        Observer: FnMut(f64, &[f64]),
+    {
+        let n = problem.size();
+        assert_eq!(u.len(), n, "Vector does not match the size of the problem");
+        let n_steps = ((end - start) / self.time_step - 1e-10).ceil().max(1.0) as usize;
+        let dt = (end - start) / n_steps as f64;
+        let mass = problem.mass();
+        let implicit = ImplicitPart(problem);
+        let (c, implicit_a, explicit_a) = self.scheme.tableaux();
+        let mut mu = vec![0.0; n];
+        for step in 0..n_steps {
+            let time = start + step as f64 * dt;
+            mass.apply(u, &mut mu);
+            // Implicit and explicit right hand sides of the computed stages
+            let mut g: Vec<Vec<f64>> = Vec::with_capacity(c.len());
+            let mut h: Vec<Vec<f64>> = Vec::with_capacity(c.len());
+            let mut stage = u.to_vec();
+            for (i, c) in c.iter().enumerate() {
+                if i > 0 {
+                    let mut history: Vec<f64> = mu.iter().map(|m| -m).collect();
+                    for (j, (g, h)) in g.iter().zip(&h).enumerate() {
+                        let (a, a_hat) = (implicit_a[i][j], explicit_a[i][j]);
+                        for ((r, g), h) in history.iter_mut().zip(g).zip(h) {
+                            *r -= dt * (a * g + a_hat * h);
+                        }
+                    }
+                    let step_problem = StepProblem {
+                        problem: &implicit,
+                        mass: &mass,
+                        time: time + c * dt,
+                        mass_factor: 1.0,
+                        rhs_factor: dt * implicit_a[i][i],
+                        history,
+                    };
+                    if !self.newton.solve(&step_problem, &mut stage).converged() {
+                        return Err(time + dt);
+                    }
+                }
+                if i + 1 < implicit_a.len() {
+                    let (mut g_stage, mut h_stage) = (vec![0.0; n], vec![0.0; n]);
+                    problem.implicit_rhs(time + c * dt, &stage, &mut g_stage);
+                    problem.explicit_rhs(time + c * dt, &stage, &mut h_stage);
+                    g.push(g_stage);
+                    h.push(h_stage);
+                }
+            }
+            // Stiffly accurate schemes end on their last stage
+            u.copy_from_slice(&stage);
+            observer(time + dt, u);
+        }
+        Ok(n_steps)
+    }
+}
+
+impl<Problem> TransientProblem for ImplicitPart<'_, Problem>
+where
+    Problem: SplitProblem,
+{
+    fn size(&self) -> usize {
+        self.0.size()
+    }
+
+    fn mass(&self) -> SparseCSR<f64> {
+        self.0.mass()
+    }
+
+    fn rhs(&self, time: f64, u: &[f64], f: &mut [f64]) {
+        self.0.implicit_rhs(time, u, f);
+    }
+
+    fn jacobian(&self, time: f64, u: &[f64]) -> SparseCSR<f64> {
+        self.0.implicit_jacobian(time, u)
+    }
+}
+
+impl<Problem> NonlinearProblem for StepProblem<'_, Problem>
+where
+    Problem: TransientProblem,
+{
+    fn size(&self) -> usize {
+        self.history.len()
+    }
+
+    fn residual(&self, x: &[f64], r: &mut [f64]) {
+        let mut f = vec![0.0; x.len()];
+        self.problem.rhs(self.time, x, &mut f);
+        self.mass.apply(x, r);
+        for ((r, h), f) in r.iter_mut().zip(&self.history).zip(&f) {
+            *r = self.mass_factor * *r + h - self.rhs_factor * f;
+        }
+    }
+
+    fn jacobian(&self, x: &[f64]) -> SparseCSR<f64> {
+        let mut jacobian = self.problem.jacobian(self.time, x);
+        jacobian
+            .values_mut()
+            .iter_mut()
+            .for_each(|v| *v *= -self.rhs_factor);
+        jacobian.add(self.mass_factor, self.mass)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Riccati equation m u' = -m u^2 whose solution from u(0) = 1 is 1 / (1 + t)
+    struct Riccati(f64);
+
+    impl TransientProblem for Riccati {
+        fn size(&self) -> usize {
+            1
+        }
+
+        fn mass(&self) -> SparseCSR<f64> {
+            SparseCSR::new(1, vec![0, 1], vec![0], vec![self.0])
+        }
+
+        fn rhs(&self, _time: f64, u: &[f64], f: &mut [f64]) {
+            f[0] = -self.0 * u[0] * u[0];
+        }
+
+        fn jacobian(&self, _time: f64, u: &[f64]) -> SparseCSR<f64> {
+            SparseCSR::new(1, vec![0, 1], vec![0], vec![-2.0 * self.0 * u[0]])
+        }
+    }
+
+    #[test]
+    fn test_time_integration_orders() {
+        let error = |scheme: TimeScheme, time_step: f64| {
+            let mut u = vec![1.0];
+            let mut observed = 0;
+            let steps = TimeIntegrator::new(scheme, time_step)
+                .integrate(&Riccati(2.0), 0.0, 1.0, &mut u, |_, _| observed += 1)
+                .unwrap();
+            assert_eq!(steps, observed, "Observer was not called at every step");
+            (u[0] - 0.5).abs()
+        };
+        for (scheme, order) in [
+            (TimeScheme::ImplicitEuler, 1),
+            (TimeScheme::CrankNicolson, 2),
+            (TimeScheme::Theta(0.75), 1),
+            (TimeScheme::Bdf(1), 1),
+            (TimeScheme::Bdf(2), 2),
+            (TimeScheme::Bdf(3), 3),
+        ] {
+            let ratio = error(scheme, 0.05) / error(scheme, 0.025);
+            let expected = 2.0_f64.powi(order);
+            assert!(
+                (ratio / expected - 1.0).abs() < 0.2,
+                "{:?} should be of order {} but the error ratio is {}",
+                scheme,
+                order,
+                ratio
+            );
+        }
+        // The time step is shortened to end on the final time
+        let mut u = vec![1.0];
+        let mut last = 0.0;
+        let steps = TimeIntegrator::new(TimeScheme::Bdf(2), 0.3)
+            .integrate(&Riccati(1.0), 0.0, 1.0, &mut u, |t, _| last = t)
+            .unwrap();
+        assert_eq!(steps, 4, "Wrong number of steps");
+        assert!((last - 1.0).abs() < 1e-14, "Integration did not end at 1");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_adaptive_time_steps() {
+        let mut steps = Vec::new();
+        let mut previous = 0.0;
+        let mut u = vec![1.0];
+        let n_steps = TimeIntegrator::new(TimeScheme::CrankNicolson, 1e-3)
+            .with_step_control(StepControl::new(1e-6))
+            .integrate(&Riccati(1.0), 0.0, 20.0, &mut u, |t, _| {
+                steps.push(t - previous);
+                previous = t;
+            })
+            .unwrap();
+        assert_eq!(
+            n_steps,
+            steps.len(),
+            "Observer was not called at every step"
+        );
+        assert!(
+            (previous - 20.0).abs() < 1e-12,
+            "Integration did not end at 20"
+        );
+        assert!(
+            (u[0] - 1.0 / 21.0).abs() < 1e-4,
+            "Wrong adaptive solution {}",
+            u[0]
+        );
+        assert!(
+            steps[steps.len() - 2] > 20.0 * steps[0],
+            "Steps should grow as the solution flattens"
+        );
+        // Steps cannot be made small enough for the solution blowing up in finite time
+        let mut u = vec![-1.0];
+        let result = TimeIntegrator::new(TimeScheme::ImplicitEuler, 0.1)
+            .with_step_control(StepControl::new(1e-4).with_step_bounds(1e-3, 1.0))
+            .integrate(&Riccati(1.0), 0.0, 2.0, &mut u, |_, _| {});
+        assert!(result.is_err(), "Adaptive integration should have failed");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_structural_dynamics() {
+        // Decoupled slow damped mode and fast undamped mode
+        let (slow, fast, ratio) = (2.0 * std::f64::consts::PI, 2000.0, 0.05);
+        let diagonal = |values: Vec<f64>| SparseCSR::new(2, vec![0, 1, 2], vec![0, 1], values);
+        let mass = diagonal(vec![1.0, 1.0]);
+        let stiffness = diagonal(vec![slow * slow, fast * fast]);
+        let damping = diagonal(vec![2.0 * ratio * slow, 0.0]);
+        let system = SecondOrderSystem::new(&mass, &stiffness).with_damping(&damping);
+        let damped = slow * (1.0 - ratio * ratio).sqrt();
+        let exact = (-ratio * slow).exp() * (damped.cos() + ratio * slow / damped * damped.sin());
+        let final_state = |scheme: DynamicsScheme, time_step: f64| {
+            let mut state = DynamicState::new(vec![1.0, 1.0], vec![0.0, 0.0]);
+            DynamicsIntegrator::new(scheme, time_step).integrate(
+                &system,
+                |_, f| f.iter_mut().for_each(|f| *f = 0.0),
+                0.0,
+                1.0,
+                &mut state,
+                |_, _| {},
+            );
+            state
+        };
+        let average = DynamicsScheme::Newmark(0.25, 0.5);
+        for scheme in [
+            average,
+            DynamicsScheme::Hht(0.1),
+            DynamicsScheme::GeneralizedAlpha(0.5),
+        ] {
+            let coarse = (final_state(scheme, 0.01).displacement()[0] - exact).abs();
+            let fine = (final_state(scheme, 0.005).displacement()[0] - exact).abs();
+            assert!(
+                coarse / fine > 3.5,
+                "{:?} should be second order, the error ratio is {}",
+                scheme,
+                coarse / fine
+            );
+        }
+        // Energy of the fast mode is kept by average acceleration and damped by generalized-alpha
+        let energy = |state: &DynamicState| {
+            0.5 * (state.velocity()[1].powi(2) + (fast * state.displacement()[1]).powi(2))
+        };
+        let initial = 0.5 * fast * fast;
+        let kept = energy(&final_state(average, 0.01));
+        assert!(
+            (kept / initial - 1.0).abs() < 1e-10,
+            "Energy was not conserved"
+        );
+        let damped = energy(&final_state(DynamicsScheme::GeneralizedAlpha(0.0), 0.01));
+        assert!(damped < 1e-10 * initial, "High frequencies were not damped");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_central_difference() {
+        // Decoupled slow damped mode and undamped mode of frequency 40
+        let (slow, fast, ratio) = (2.0 * std::f64::consts::PI, 40.0, 0.05);
+        let mass = Diagonal::new(vec![1.0, 1.0]);
+        let stiffness =
+            SparseCSR::new(2, vec![0, 1, 2], vec![0, 1], vec![slow * slow, fast * fast]);
+        let damping = Diagonal::new(vec![2.0 * ratio * slow, 0.0]);
+        let system = LumpedSystem::new(&mass, &stiffness).with_damping(&damping);
+        assert!(
+            (system.critical_time_step() - 2.0 / fast).abs() < 1e-14,
+            "Wrong critical time step"
+        );
+        let damped = slow * (1.0 - ratio * ratio).sqrt();
+        let exact = (-ratio * slow).exp() * (damped.cos() + ratio * slow / damped * damped.sin());
+        let final_state = |time_step: f64| {
+            let mut state = DynamicState::new(vec![1.0, 1.0], vec![0.0, 0.0]);
+            CentralDifference::new(time_step).integrate(
+                &system,
+                |_, f| f.iter_mut().for_each(|f| *f = 0.0),
+                0.0,
+                1.0,
+                &mut state,
+                |_, _| {},
+            );
+            state
+        };
+        let coarse = (final_state(0.01).displacement()[0] - exact).abs();
+        let fine = (final_state(0.005).displacement()[0] - exact).abs();
+        assert!(
+            coarse / fine > 3.5,
+            "Central difference should be second order, the error ratio is {}",
+            coarse / fine
+        );
+        // Above the critical time step the fast mode blows up
+        let unstable = final_state(0.06).displacement()[1].abs();
+        assert!(unstable > 1e3, "Central difference should be unstable");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_imex_orders() {
+        // u' = -5 u + cos(t) u, the decay being implicit, of solution exp(-5 t + sin(t))
+        struct Split;
+
+        impl SplitProblem for Split {
+            fn size(&self) -> usize {
+                1
+            }
+
+            fn mass(&self) -> SparseCSR<f64> {
+                SparseCSR::new(1, vec![0, 1], vec![0], vec![1.0])
+            }
+
+            fn implicit_rhs(&self, _time: f64, u: &[f64], g: &mut [f64]) {
+                g[0] = -5.0 * u[0];
+            }
+
+            fn implicit_jacobian(&self, _time: f64, _u: &[f64]) -> SparseCSR<f64> {
+                SparseCSR::new(1, vec![0, 1], vec![0], vec![-5.0])
+            }
+
+            fn explicit_rhs(&self, time: f64, u: &[f64], h: &mut [f64]) {
+                h[0] = time.cos() * u[0];
+            }
+        }
+
+        let exact = (-5.0 + 1.0_f64.sin()).exp();
+        let error = |scheme: ImexScheme, time_step: f64| {
+            let mut u = vec![1.0];
+            ImexIntegrator::new(scheme, time_step)
+                .integrate(&Split, 0.0, 1.0, &mut u, |_, _| {})
+                .unwrap();
+            (u[0] - exact).abs()
+        };
+        for (scheme, order) in [
+            (ImexScheme::Euler, 1),
+            (ImexScheme::Ars222, 2),
+            (ImexScheme::Ars443, 3),
+        ] {
+            let ratio = error(scheme, 0.02) / error(scheme, 0.01);
+            let expected = 2.0_f64.powi(order);
+            assert!(
+                (ratio / expected - 1.0).abs() < 0.2,
+                "{:?} should be of order {} but the error ratio is {}",
+                scheme,
+                order,
+                ratio
+            );
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Non decreasing sequence of knots defining a one dimensional B-spline basis of some degree
+///
+/// The basis has (number of knots - degree - 1) functions, each supported on degree + 1 knot
+/// spans. The elements of the basis are the knot spans of non zero length and on every element
+/// exactly degree + 1 functions do not vanish.
+pub struct KnotVector {
+    degree: usize,
+    knots: Vec<f64>,
+    spans: Vec<usize>,
+}
+
+impl KnotVector {
+    /// Build the basis of the given degree on a knot vector
+    pub fn new(degree: usize, knots: Vec<f64>) -> Self {
+        assert!(
+            knots.len() >= 2 * (degree + 1),
+            "Knot vector is too short for the degree"
+        );
+        assert!(
+            knots.windows(2).all(|w| w[0] <= w[1]),
+            "Knots should be non decreasing"
+        );
+        let spans: Vec<usize> = (degree..(knots.len() - degree - 1))
+            .filter(|i| knots[*i] < knots[i + 1])
+            .collect();
+        assert!(!spans.is_empty(), "Knot vector has no non empty span");
+        KnotVector {
+            degree,
+            knots,
+            spans,
+        }
+    }
+
+    /// Open knot vector on [0, 1] with uniformly spaced simple interior knots
+    pub fn open_uniform(degree: usize, n_elements: usize) -> Self {
+        let mut knots = vec![0.0; degree + 1];
+        knots.extend((1..n_elements).map(|i| i as f64 / n_elements as f64));
+        knots.extend(vec![1.0; degree + 1]);
+        KnotVector::new(degree, knots)
+    }
+
+    /// Polynomial degree of the basis
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    /// Knots of the basis
+    pub fn knots(&self) -> &[f64] {
+        &self.knots
+    }
+
+    /// Number of basis functions
+    pub fn n_basis(&self) -> usize {
+        self.knots.len() - self.degree - 1
+    }
+
+    /// Number of non empty knot spans
+    pub fn n_elements(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Index of the knot starting an element: the functions span - degree..=span live on it
+    pub fn element_span(&self, element: usize) -> usize {
+        self.spans[element]
+    }
+
+    /// Parametric bounds of an element
+    pub fn element_bounds(&self, element: usize) -> (f64, f64) {
+        let span = self.spans[element];
+        (self.knots[span], self.knots[span + 1])
+    }
+
+    /// Index of the knot starting the span containing x (the last span for the end of the basis)
+    pub fn find_span(&self, x: f64) -> usize {
+        let last = *self.spans.last().unwrap();
+        if x >= self.knots[last] {
+            return last;
+        }
+        *self
+            .spans
+            .iter()
+            .find(|span| x < self.knots[**span + 1])
+            .unwrap()
+    }
+
+    /// Evaluate the non vanishing basis functions and their derivatives at x by Cox-de Boor
+    ///
+    /// The buffers hold degree + 1 values for the functions span - degree..=span and the span is
+    /// returned.
+    pub fn basis(&self, x: f64, values: &mut [f64], derivatives: &mut [f64]) -> usize {
+        let p = self.degree;
+        assert!(
+            values.len() == p + 1 && derivatives.len() == p + 1,
+            "Buffers do not match the number of non vanishing functions"
+        );
+        let span = self.find_span(x);
+        let lower = self.cox_de_boor(span, x, p.saturating_sub(1));
+        values.copy_from_slice(&self.cox_de_boor(span, x, p));
+        derivatives.iter_mut().for_each(|d| *d = 0.0);
+        if p == 0 {
+            return span;
+        }
+        // N'_{i,p} = p N_{i,p-1} / (u_{i+p} - u_i) - p N_{i+1,p-1} / (u_{i+p+1} - u_{i+1})
+        let u = &self.knots;
+        for (a, derivative) in derivatives.iter_mut().enumerate() {
+            let i = span + a - p;
+            if a > 0 && u[i + p] > u[i] {
+                *derivative += p as f64 * lower[a - 1] / (u[i + p] - u[i]);
+            }
+            if a < p && u[i + p + 1] > u[i + 1] {
+                *derivative -= p as f64 * lower[a] / (u[i + p + 1] - u[i + 1]);
+            }
+        }
+        span
+    }
+
+    /// Bézier extraction operators of every element
+    ///
+    /// The (degree + 1, degree + 1) row first operator C of an element expresses its non vanishing
+    /// B-splines in the Bernstein polynomials of the element mapped to [0, 1]: N_a = sum_b C_ab B_b.
+    /// Assembly then only needs the Bernstein basis on a single reference element.
+    pub fn bezier_extraction(&self) -> Vec<Vec<f64>> {
+        let n = self.degree + 1;
+        // Both bases are polynomials of the same degree so matching them at n points is exact
+        let points: Vec<f64> = (0..n).map(|k| (k as f64 + 0.5) / n as f64).collect();
+        let mut bernstein_matrix = vec![0.0; n * n];
+        let mut derivatives = vec![0.0; n];
+        for (k, t) in points.iter().enumerate() {
+            bernstein(
+                self.degree,
+                *t,
+                &mut bernstein_matrix[k * n..(k + 1) * n],
+                &mut derivatives,
+            );
+        }
+        let inverse = invert(n, &bernstein_matrix);
+        (0..self.n_elements())
+            .map(|element| {
+                let (a, b) = self.element_bounds(element);
+                let span = self.element_span(element);
+                // Rows are the points and columns the B-splines
+                let mut spline_matrix = vec![0.0; n * n];
+                for (k, t) in points.iter().enumerate() {
+                    spline_matrix[k * n..(k + 1) * n].copy_from_slice(&self.cox_de_boor(
+                        span,
+                        a + t * (b - a),
+                        self.degree,
+                    ));
+                }
+                // C = N^T B^-T
+                let mut operator = vec![0.0; n * n];
+                for i in 0..n {
+                    for j in 0..n {
+                        operator[i * n + j] = (0..n)
+                            .map(|k| spline_matrix[k * n + i] * inverse[j * n + k])
+                            .sum();
+                    }
+                }
+                operator
+            })
+            .collect()
+    }
+
+    // Triangular Cox-de Boor recursion for the functions span - degree..=span of a degree
+    fn cox_de_boor(&self, span: usize, x: f64, degree: usize) -> Vec<f64> {
+        let u = &self.knots;
+        let mut values = vec![0.0; degree + 1];
+        let mut left = vec![0.0; degree + 1];
+        let mut right = vec![0.0; degree + 1];
+        values[0] = 1.0;
+        for j in 1..=degree {
+            left[j] = x - u[span + 1 - j];
+            right[j] = u[span + j] - x;
+            let mut saved = 0.0;
+            for r in 0..j {
+                let temp = values[r] / (right[r + 1] + left[j - r]);
+                values[r] = saved + right[r + 1] * temp;
+                saved = left[j - r] * temp;
+            }
+            values[j] = saved;
+        }
+        values
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Evaluate the Bernstein polynomials of a degree on [0, 1] and their derivatives at t
+pub fn bernstein(degree: usize, t: f64, values: &mut [f64], derivatives: &mut [f64]) {
+    assert!(
+        values.len() == degree + 1 && derivatives.len() == degree + 1,
+        "Buffers do not match the number of Bernstein polynomials"
+    );
+    // Values of degree - 1 first for the derivatives
+    let lower = bernstein_values(degree.saturating_sub(1), t);
+    values.copy_from_slice(&bernstein_values(degree, t));
+    for (b, derivative) in derivatives.iter_mut().enumerate() {
+        *derivative = 0.0;
+        if degree == 0 {
+            continue;
+        }
+        if b > 0 {
+            *derivative += degree as f64 * lower[b - 1];
+        }
+        if b < degree {
+            *derivative -= degree as f64 * lower[b];
+        }
+    }
+}
+
+// De Casteljau like recursion B_{b,p} = (1 - t) B_{b,p-1} + t B_{b-1,p-1}
+fn bernstein_values(degree: usize, t: f64) -> Vec<f64> {
+    let mut values = vec![0.0; degree + 1];
+    values[0] = 1.0;
+    for p in 1..=degree {
+        for b in (1..=p).rev() {
+            values[b] = (1.0 - t) * values[b] + t * values[b - 1];
+        }
+        values[0] *= 1.0 - t;
+    }
+    values
+}
+
+// Gauss-Jordan inversion of a small well conditioned row first matrix
+fn invert(n: usize, matrix: &[f64]) -> Vec<f64> {
+    let mut a = matrix.to_vec();
+    let mut inverse = vec![0.0; n * n];
+    for i in 0..n {
+        inverse[i * n + i] = 1.0;
+    }
+    for k in 0..n {
+        let pivot = (k..n)
+            .max_by(|i, j| a[i * n + k].abs().total_cmp(&a[j * n + k].abs()))
+            .unwrap();
+        for j in 0..n {
+            a.swap(k * n + j, pivot * n + j);
+            inverse.swap(k * n + j, pivot * n + j);
+        }
+        let diagonal = a[k * n + k];
+        for j in 0..n {
+            a[k * n + j] /= diagonal;
+            inverse[k * n + j] /= diagonal;
+        }
+        for i in (0..n).filter(|i| *i != k) {
+            let factor = a[i * n + k];
+            for j in 0..n {
+                a[i * n + j] -= factor * a[k * n + j];
+                inverse[i * n + j] -= factor * inverse[k * n + j];
+            }
+        }
+    }
+    inverse
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_knot_vector_basis() {
+        let knots = KnotVector::open_uniform(2, 2);
+        assert_eq!(knots.n_basis(), 4, "Wrong number of basis functions");
+        assert_eq!(knots.n_elements(), 2, "Wrong number of elements");
+        let mut values = vec![0.0; 3];
+        let mut derivatives = vec![0.0; 3];
+        let span = knots.basis(0.5, &mut values, &mut derivatives);
+        assert_eq!(span, 3, "Wrong span");
+        // Functions 1, 2 and 3 where the function 3 starts at the knot
+        for (v, expected) in values.iter().zip([0.5, 0.5, 0.0]) {
+            assert!((v - expected).abs() < 1e-15, "Wrong basis values");
+        }
+        // Partition of unity and derivatives against finite differences
+        let h = 1e-6;
+        let mut shifted = vec![0.0; 3];
+        for x in [0.1, 0.3, 0.7, 0.95] {
+            knots.basis(x, &mut values, &mut derivatives);
+            assert!(
+                (values.iter().sum::<f64>() - 1.0).abs() < 1e-14,
+                "Basis should be a partition of unity"
+            );
+            knots.basis(x + h, &mut shifted, &mut [0.0; 3]);
+            for a in 0..3 {
+                assert!(
+                    ((shifted[a] - values[a]) / h - derivatives[a]).abs() < 1e-4,
+                    "Wrong basis derivative"
+                );
+            }
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_bezier_extraction() {
+        let knots = KnotVector::new(
+            3,
+            vec![0.0, 0.0, 0.0, 0.0, 0.2, 0.5, 0.5, 1.0, 1.0, 1.0, 1.0],
+        );
+        assert_eq!(knots.n_elements(), 3, "Wrong number of elements");
+        let extraction = knots.bezier_extraction();
+        let mut splines = vec![0.0; 4];
+        let mut bernsteins = vec![0.0; 4];
+        let mut derivatives = vec![0.0; 4];
+        for (element, operator) in extraction.iter().enumerate() {
+            let (a, b) = knots.element_bounds(element);
+            for t in [0.1, 0.45, 0.8] {
+                knots.basis(a + t * (b - a), &mut splines, &mut derivatives);
+                bernstein(3, t, &mut bernsteins, &mut derivatives);
+                for i in 0..4 {
+                    let extracted: f64 = (0..4).map(|j| operator[i * 4 + j] * bernsteins[j]).sum();
+                    assert!(
+                        (extracted - splines[i]).abs() < 1e-12,
+                        "Extraction does not reproduce the B-splines"
+                    );
+                }
+            }
+        }
+        // First element of an open knot vector starts with the Bernstein polynomials
+        assert!(
+            (extraction[0][0] - 1.0).abs() < 1e-12,
+            "Wrong extraction of the first function"
+        );
+    }
+}
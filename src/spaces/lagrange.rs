@@ -0,0 +1,364 @@
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Lagrange finite element of arbitrary order on the reference simplex
+///
+/// The reference simplex of dimension d is the convex hull of the origin and the d unit vectors.
+/// Its barycentric coordinates are l0 = 1 - x0 - ... - x(d-1) and l(i+1) = xi. The nodes of the
+/// element are the points of barycentric coordinates a / order where a is a multi-index of d + 1
+/// integers summing to the order. The shape functions are the associated products of Silvester
+/// polynomials.
+///
+/// Nodes are ordered by the number of vertices of the sub-entity supporting them: vertex nodes
+/// come first (node i sits on vertex i), then edge nodes, face nodes and finally interior nodes.
+pub struct LagrangeElement {
+    dim: usize,
+    order: usize,
+    nodes: Vec<Vec<usize>>,
+}
+
+impl LagrangeElement {
+    /// Build the Lagrange element of the given order on the simplex of dimension dim
+    pub fn new(dim: usize, order: usize) -> Self {
+        assert!(
+            order > 0,
+            "Lagrange elements are only defined for orders >= 1"
+        );
+        let mut nodes = Vec::new();
+        let mut current = vec![0; dim + 1];
+        multi_indices(order, 0, &mut current, &mut nodes);
+        nodes.sort_by_key(|node| {
+            let support: Vec<usize> = (0..=dim).filter(|i| node[*i] > 0).collect();
+            (
+                support.len(),
+                support,
+                node.iter().rev().cloned().collect::<Vec<usize>>(),
+            )
+        });
+        LagrangeElement { dim, order, nodes }
+    }
+
+    /// Dimension of the reference simplex
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Polynomial order of the element
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Number of shape functions
+    pub fn n_dofs(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Barycentric multi-index of a node (sums to the order)
+    pub fn node_multi_index(&self, node: usize) -> &[usize] {
+        &self.nodes[node]
+    }
+
+    /// Reference coordinates of a node
+    pub fn node_coordinates(&self, node: usize) -> Vec<f64> {
+        self.nodes[node][1..]
+            .iter()
+            .map(|a| *a as f64 / self.order as f64)
+            .collect()
+    }
+
+    /// Evaluate all the shape functions at a reference point
+    pub fn values(&self, point: &[f64], values: &mut [f64]) {
+        assert!(
+            values.len() == self.n_dofs(),
+            "Values buffer does not match the number of shape functions"
+        );
+        let bary = self.barycentric(point);
+        for (node, value) in self.nodes.iter().zip(values.iter_mut()) {
+            *value = node
+                .iter()
+                .zip(bary.iter())
+                .map(|(a, l)| silvester(self.order, *a, *l).0)
+                .product();
+        }
+    }
+
+    /// Evaluate the reference gradients of all the shape functions at a reference point
+    ///
+    /// The gradients are written as a flat array of size (number of shape functions, dimension).
+    pub fn gradients(&self, point: &[f64], gradients: &mut [f64]) {
+        assert!(
+            gradients.len() == self.n_dofs() * self.dim,
+            "Gradients buffer does not match the number of shape functions"
+        );
+        let bary = self.barycentric(point);
+        for (node, grad) in self.nodes.iter().zip(gradients.chunks_mut(self.dim.max(1))) {
+            let evals: Vec<(f64, f64)> = node
+                .iter()
+                .zip(bary.iter())
+                .map(|(a, l)| silvester(self.order, *a, *l))
+                .collect();
+            // Derivative with respect to each barycentric coordinate
+            let dbary: Vec<f64> = (0..=self.dim)
+                .map(|i| {
+                    evals
+                        .iter()
+                        .enumerate()
+                        .map(|(j, (p, dp))| if i == j { *dp } else { *p })
+                        .product()
+                })
+                .collect();
+            for (m, g) in grad.iter_mut().enumerate() {
+                *g = dbary[m + 1] - dbary[0];
+            }
+        }
+    }
+
+    /// Evaluate the reference hessians of all the shape functions at a reference point
+    ///
+    /// The hessians are written as a flat array of size (number of shape functions, dimension,
+    /// dimension).
+    pub fn hessians(&self, point: &[f64], hessians: &mut [f64]) {
+        let dim = self.dim;
+        assert!(
+            hessians.len() == self.n_dofs() * dim * dim,
+            "Hessians buffer does not match the number of shape functions"
+        );
+        let bary = self.barycentric(point);
+        // Derivative of the barycentric coordinate i with respect to the reference coordinate m
+        let dl = |i: usize, m: usize| -> f64 {
+            if i == 0 {
+                -1.0
+            } else if i == m + 1 {
+                1.0
+            } else {
+                0.0
+            }
+        };
+        for (node, hessian) in self
+            .nodes
+            .iter()
+            .zip(hessians.chunks_mut((dim * dim).max(1)))
+        {
+            let evals: Vec<(f64, f64, f64)> = node
+                .iter()
+                .zip(bary.iter())
+                .map(|(a, l)| silvester_second(self.order, *a, *l))
+                .collect();
+            // Second derivatives with respect to each pair of barycentric coordinates
+            let d2bary = |i: usize, j: usize| -> f64 {
+                evals
+                    .iter()
+                    .enumerate()
+                    .map(|(m, (p, dp, d2p))| match (m == i, m == j) {
+                        (true, true) => *d2p,
+                        (true, false) | (false, true) => *dp,
+                        (false, false) => *p,
+                    })
+                    .product()
+            };
+            for a in 0..dim {
+                for b in 0..dim {
+                    let mut h = 0.0;
+                    for i in 0..=dim {
+                        for j in 0..=dim {
+                            let c = dl(i, a) * dl(j, b);
+                            if c != 0.0 {
+                                h += c * d2bary(i, j);
+                            }
+                        }
+                    }
+                    hessian[a * dim + b] = h;
+                }
+            }
+        }
+    }
+
+    fn barycentric(&self, point: &[f64]) -> Vec<f64> {
+        assert!(
+            point.len() == self.dim,
+            "Point dimension does not match the element dimension"
+        );
+        let mut bary = Vec::with_capacity(self.dim + 1);
+        bary.push(1.0 - point.iter().sum::<f64>());
+        bary.extend_from_slice(point);
+        bary
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Generate all the multi-indices of current.len() integers summing to remainder + sum(current[..pos])
+fn multi_indices(
+    remainder: usize,
+    pos: usize,
+    current: &mut Vec<usize>,
+    out: &mut Vec<Vec<usize>>,
+) {
+    if pos == current.len() - 1 {
+        current[pos] = remainder;
+        out.push(current.clone());
+        return;
+    }
+    for a in 0..=remainder {
+        current[pos] = a;
+        multi_indices(remainder - a, pos + 1, current, out);
+    }
+}
+
+// Value and derivative of the Silvester polynomial prod_{j < a} (order * l - j) / (j + 1)
+fn silvester(order: usize, a: usize, l: f64) -> (f64, f64) {
+    let (value, derivative, _) = silvester_second(order, a, l);
+    (value, derivative)
+}
+
+// Value, first and second derivatives of the Silvester polynomial
+fn silvester_second(order: usize, a: usize, l: f64) -> (f64, f64, f64) {
+    let k = order as f64;
+    let mut value = 1.0;
+    let mut derivative = 0.0;
+    let mut second = 0.0;
+    for j in 0..a {
+        let factor = (k * l - j as f64) / (j as f64 + 1.0);
+        let slope = k / (j as f64 + 1.0);
+        second = second * factor + 2.0 * derivative * slope;
+        derivative = derivative * factor + value * slope;
+        value *= factor;
+    }
+    (value, derivative, second)
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lagrange_n_dofs() {
+        assert_eq!(LagrangeElement::new(1, 3).n_dofs(), 4, "Wrong P3 interval");
+        assert_eq!(LagrangeElement::new(2, 1).n_dofs(), 3, "Wrong P1 triangle");
+        assert_eq!(LagrangeElement::new(2, 2).n_dofs(), 6, "Wrong P2 triangle");
+        assert_eq!(
+            LagrangeElement::new(3, 2).n_dofs(),
+            10,
+            "Wrong P2 tetrahedron"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_lagrange_vertex_nodes_first() {
+        let element = LagrangeElement::new(2, 3);
+        assert_eq!(element.node_coordinates(0), vec![0.0, 0.0], "Wrong node 0");
+        assert_eq!(element.node_coordinates(1), vec![1.0, 0.0], "Wrong node 1");
+        assert_eq!(element.node_coordinates(2), vec![0.0, 1.0], "Wrong node 2");
+        let last = element.node_multi_index(element.n_dofs() - 1);
+        assert_eq!(last, &[1, 1, 1], "Interior node should be last");
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_lagrange_kronecker() {
+        for (dim, order) in [(1, 4), (2, 1), (2, 3), (3, 2)] {
+            let element = LagrangeElement::new(dim, order);
+            let mut values = vec![0.0; element.n_dofs()];
+            for node in 0..element.n_dofs() {
+                element.values(&element.node_coordinates(node), &mut values);
+                for (i, v) in values.iter().enumerate() {
+                    let expected = if i == node { 1.0 } else { 0.0 };
+                    assert!(
+                        (v - expected).abs() < 1e-12,
+                        "Shape function {} is not nodal at node {}",
+                        i,
+                        node
+                    );
+                }
+            }
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_lagrange_gradients() {
+        // Compare with central finite differences
+        let element = LagrangeElement::new(2, 3);
+        let point = [0.2, 0.3];
+        let h = 1e-6;
+        let mut gradients = vec![0.0; element.n_dofs() * 2];
+        element.gradients(&point, &mut gradients);
+        let mut plus = vec![0.0; element.n_dofs()];
+        let mut minus = vec![0.0; element.n_dofs()];
+        for m in 0..2 {
+            let mut p = point;
+            p[m] += h;
+            element.values(&p, &mut plus);
+            p[m] -= 2.0 * h;
+            element.values(&p, &mut minus);
+            for i in 0..element.n_dofs() {
+                let fd = (plus[i] - minus[i]) / (2.0 * h);
+                assert!(
+                    (gradients[2 * i + m] - fd).abs() < 1e-6,
+                    "Wrong derivative {} of shape function {}",
+                    m,
+                    i
+                );
+            }
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_lagrange_hessians() {
+        // Compare with central finite differences of the gradients
+        let element = LagrangeElement::new(3, 3);
+        let point = [0.2, 0.3, 0.1];
+        let h = 1e-6;
+        let n = element.n_dofs();
+        let mut hessians = vec![0.0; n * 9];
+        element.hessians(&point, &mut hessians);
+        let mut plus = vec![0.0; n * 3];
+        let mut minus = vec![0.0; n * 3];
+        for m in 0..3 {
+            let mut p = point;
+            p[m] += h;
+            element.gradients(&p, &mut plus);
+            p[m] -= 2.0 * h;
+            element.gradients(&p, &mut minus);
+            for i in 0..n {
+                for k in 0..3 {
+                    let fd = (plus[3 * i + k] - minus[3 * i + k]) / (2.0 * h);
+                    assert!(
+                        (hessians[9 * i + 3 * k + m] - fd).abs() < 1e-5,
+                        "Wrong second derivative ({}, {}) of shape function {}",
+                        k,
+                        m,
+                        i
+                    );
+                }
+            }
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_lagrange_partition_of_unity() {
+        let element = LagrangeElement::new(3, 2);
+        let mut values = vec![0.0; element.n_dofs()];
+        let mut gradients = vec![0.0; element.n_dofs() * 3];
+        element.values(&[0.1, 0.2, 0.3], &mut values);
+        element.gradients(&[0.1, 0.2, 0.3], &mut gradients);
+        assert!(
+            (values.iter().sum::<f64>() - 1.0).abs() < 1e-14,
+            "Shape functions do not sum to one"
+        );
+        for m in 0..3 {
+            let sum: f64 = gradients.iter().skip(m).step_by(3).sum();
+            assert!(sum.abs() < 1e-13, "Gradients do not sum to zero");
+        }
+    }
+}
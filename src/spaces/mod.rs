@@ -1 +1,17 @@
+/// Numerical integration rules on reference cells
+pub mod quadrature;
 
+/// Lagrange finite elements on reference simplices
+pub mod lagrange;
+
+/// Lowest order Raviart-Thomas H(div) conforming elements
+pub mod raviart_thomas;
+
+/// Lowest order Nédélec H(curl) conforming elements
+pub mod nedelec;
+
+/// Lagrange finite elements on reference hypercubes
+pub mod tensor_product;
+
+/// B-spline bases on knot vectors and their Bézier extraction
+pub mod bspline;
@@ -0,0 +1,143 @@
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Lowest order Nédélec element of the first kind on the reference simplex
+///
+/// Shape function e is attached to the local edge (a, b) with a < b (edges are sorted
+/// lexicographically) and reads la grad(lb) - lb grad(la) where l are the barycentric coordinates.
+/// Its tangential moment along the edge going from vertex a to vertex b is one while its
+/// tangential component vanishes on the other edges. Physical shape functions are obtained with the
+/// covariant Piola transformation (see CellMapping::covariant_piola).
+pub struct NedelecElement {
+    dim: usize,
+    edges: Vec<(usize, usize)>,
+}
+
+impl NedelecElement {
+    /// Build the lowest order element on the simplex of dimension dim (2 or 3)
+    pub fn new(dim: usize) -> Self {
+        assert!(
+            dim == 2 || dim == 3,
+            "Nédélec elements are only implemented in 2 and 3 dimensions"
+        );
+        let edges = (0..=dim)
+            .flat_map(|a| ((a + 1)..=dim).map(move |b| (a, b)))
+            .collect();
+        NedelecElement { dim, edges }
+    }
+
+    /// Dimension of the reference simplex
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Number of shape functions (one per edge)
+    pub fn n_dofs(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// Local vertices of the edges in the order of the shape functions
+    pub fn edges(&self) -> &[(usize, usize)] {
+        &self.edges
+    }
+
+    /// Number of components of the curl (1 in 2 dimensions and 3 in 3 dimensions)
+    pub fn curl_dim(&self) -> usize {
+        if self.dim == 2 {
+            1
+        } else {
+            3
+        }
+    }
+
+    /// Evaluate all the shape functions at a reference point
+    ///
+    /// The values are written as a flat array of size (number of shape functions, dimension).
+    pub fn values(&self, point: &[f64], values: &mut [f64]) {
+        let dim = self.dim;
+        assert!(
+            values.len() == self.n_dofs() * dim,
+            "Values buffer does not match the number of shape functions"
+        );
+        let mut bary = vec![1.0 - point.iter().sum::<f64>()];
+        bary.extend_from_slice(point);
+        for ((a, b), value) in self.edges.iter().zip(values.chunks_mut(dim)) {
+            for (k, v) in value.iter_mut().enumerate() {
+                *v =
+                    bary[*a] * barycentric_gradient(*b, k) - bary[*b] * barycentric_gradient(*a, k);
+            }
+        }
+    }
+
+    /// Evaluate the reference curls of all the shape functions (they are constant)
+    ///
+    /// The curls are written as a flat array of size (number of shape functions, curl_dim).
+    pub fn curls(&self, curls: &mut [f64]) {
+        let curl_dim = self.curl_dim();
+        assert!(
+            curls.len() == self.n_dofs() * curl_dim,
+            "Curls buffer does not match the number of shape functions"
+        );
+        // curl(la grad(lb) - lb grad(la)) = 2 grad(la) x grad(lb)
+        for ((a, b), curl) in self.edges.iter().zip(curls.chunks_mut(curl_dim)) {
+            let ga: Vec<f64> = (0..self.dim).map(|k| barycentric_gradient(*a, k)).collect();
+            let gb: Vec<f64> = (0..self.dim).map(|k| barycentric_gradient(*b, k)).collect();
+            if self.dim == 2 {
+                curl[0] = 2.0 * (ga[0] * gb[1] - ga[1] * gb[0]);
+            } else {
+                curl[0] = 2.0 * (ga[1] * gb[2] - ga[2] * gb[1]);
+                curl[1] = 2.0 * (ga[2] * gb[0] - ga[0] * gb[2]);
+                curl[2] = 2.0 * (ga[0] * gb[1] - ga[1] * gb[0]);
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Component k of the gradient of the barycentric coordinate of vertex i
+fn barycentric_gradient(i: usize, k: usize) -> f64 {
+    if i == 0 {
+        -1.0
+    } else if k == i - 1 {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nedelec_tangential_components() {
+        let element = NedelecElement::new(3);
+        assert_eq!(element.n_dofs(), 6, "Wrong number of edges");
+        assert_eq!(element.edges()[3], (1, 2), "Wrong edge ordering");
+        let mut values = vec![0.0; 18];
+        // Midpoint of the edge (1, 2) of length sqrt(2) going along (-1, 1, 0)
+        element.values(&[0.5, 0.5, 0.0], &mut values);
+        let tangent = [-1.0, 1.0, 0.0];
+        for (e, value) in values.chunks(3).enumerate() {
+            let moment: f64 = value.iter().zip(tangent.iter()).map(|(v, t)| v * t).sum();
+            let expected = if e == 3 { 1.0 } else { 0.0 };
+            assert!(
+                (moment - expected).abs() < 1e-14,
+                "Wrong tangential moment of shape function {}",
+                e
+            );
+        }
+        let mut curls = vec![0.0; 18];
+        element.curls(&mut curls);
+        // Shape function of the edge (1, 2) is (-y, x, 0) whose curl is (0, 0, 2)
+        assert_eq!(&curls[9..12], &[0.0, 0.0, 2.0], "Wrong curl");
+    }
+}
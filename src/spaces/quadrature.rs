@@ -0,0 +1,196 @@
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::data_traits::DataContainer;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Structure holding the points and weights of a numerical integration rule on a reference cell
+///
+/// The points are held as a two dimensional array of size (number of points, dimension) in the
+/// coordinates of the reference cell.
+pub struct QuadratureRule {
+    points: DataHold<f64, [usize; 2]>,
+    weights: Vec<f64>,
+}
+
+impl QuadratureRule {
+    /// Build a rule from its points and weights
+    pub fn new(points: DataHold<f64, [usize; 2]>, weights: Vec<f64>) -> Self {
+        assert!(
+            points.dimensions()[0] == weights.len(),
+            "Tried to build a QuadratureRule with different numbers of points and weights"
+        );
+        QuadratureRule { points, weights }
+    }
+
+    /// Gauss-Legendre rule with n_points on the reference interval [0, 1]
+    ///
+    /// Integrates polynomials of degree 2 * n_points - 1 exactly.
+    pub fn gauss_legendre(n_points: usize) -> Self {
+        assert!(
+            n_points > 0,
+            "A Gauss-Legendre rule needs at least one point"
+        );
+        let mut points = Vec::with_capacity(n_points);
+        let mut weights = Vec::with_capacity(n_points);
+        for i in 0..n_points {
+            // Newton iterations on the Legendre polynomial starting from an approximation of the
+            // root on [-1, 1]
+            let mut x = (std::f64::consts::PI * (i as f64 + 0.75) / (n_points as f64 + 0.5)).cos();
+            for _ in 0..100 {
+                let (p, dp) = legendre(n_points, x);
+                let dx = p / dp;
+                x -= dx;
+                if dx.abs() < 1e-15 {
+                    break;
+                }
+            }
+            let (_, dp) = legendre(n_points, x);
+            points.push(0.5 * (1.0 - x));
+            weights.push(1.0 / ((1.0 - x * x) * dp * dp));
+        }
+        QuadratureRule::new(DataHold::new(points, [n_points, 1]), weights)
+    }
+
+    /// Rule on the reference simplex of dimension dim integrating polynomials of the given degree
+    /// exactly
+    ///
+    /// The reference simplex is the convex hull of the origin and the unit vectors. The rule is
+    /// built by collapsing a tensor product of Gauss-Legendre rules onto the simplex.
+    pub fn simplex(dim: usize, degree: usize) -> Self {
+        if dim == 0 {
+            return QuadratureRule::new(DataHold::new(vec![], [1, 0]), vec![1.0]);
+        }
+        // The collapse adds (dim - 1) to the degree in the first direction
+        let line = QuadratureRule::gauss_legendre((degree + dim).div_ceil(2));
+        let sub = QuadratureRule::simplex(dim - 1, degree);
+        let mut points = Vec::with_capacity(line.n_points() * sub.n_points() * dim);
+        let mut weights = Vec::with_capacity(line.n_points() * sub.n_points());
+        for (u, wu) in line.points.iter().zip(line.weights.iter()) {
+            let scale = 1.0 - u;
+            for q in 0..sub.n_points() {
+                points.push(*u);
+                points.extend(sub.point(q).iter().map(|y| scale * y));
+                weights.push(wu * sub.weights[q] * scale.powi(dim as i32 - 1));
+            }
+        }
+        let n_points = weights.len();
+        QuadratureRule::new(DataHold::new(points, [n_points, dim]), weights)
+    }
+
+    /// Number of points in the rule
+    pub fn n_points(&self) -> usize {
+        self.weights.len()
+    }
+
+    /// Dimension of the reference cell of the rule
+    pub fn dim(&self) -> usize {
+        self.points.dimensions()[1]
+    }
+
+    /// Coordinates of a point of the rule
+    pub fn point(&self, index: usize) -> &[f64] {
+        let dim = self.dim();
+        &self.points[index * dim..(index + 1) * dim]
+    }
+
+    /// Weights of the rule
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Value and derivative of the Legendre polynomial of degree n at x
+fn legendre(n: usize, x: f64) -> (f64, f64) {
+    let mut p0 = 1.0;
+    let mut p1 = x;
+    for k in 2..=n {
+        let p2 = ((2 * k - 1) as f64 * x * p1 - (k - 1) as f64 * p0) / k as f64;
+        p0 = p1;
+        p1 = p2;
+    }
+    if n == 0 {
+        return (1.0, 0.0);
+    }
+    let dp = n as f64 * (x * p1 - p0) / (x * x - 1.0);
+    (p1, dp)
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn integrate<F: Fn(&[f64]) -> f64>(rule: &QuadratureRule, f: F) -> f64 {
+        (0..rule.n_points())
+            .map(|q| rule.weights()[q] * f(rule.point(q)))
+            .sum()
+    }
+
+    #[test]
+    fn test_gauss_legendre_exactness() {
+        for n_points in 1..8 {
+            let rule = QuadratureRule::gauss_legendre(n_points);
+            for degree in 0..(2 * n_points) {
+                let integral = integrate(&rule, |x| x[0].powi(degree as i32));
+                assert!(
+                    (integral - 1.0 / (degree as f64 + 1.0)).abs() < 1e-13,
+                    "{} point rule did not integrate x^{} exactly",
+                    n_points,
+                    degree
+                );
+            }
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_simplex_volumes() {
+        let volumes = [1.0, 1.0, 0.5, 1.0 / 6.0];
+        for (dim, volume) in volumes.iter().enumerate() {
+            let rule = QuadratureRule::simplex(dim, 2);
+            assert_eq!(rule.dim(), dim, "Wrong dimension for the simplex rule");
+            let integral: f64 = rule.weights().iter().sum();
+            assert!(
+                (integral - volume).abs() < 1e-14,
+                "Wrong volume for the simplex of dimension {}",
+                dim
+            );
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_simplex_exactness() {
+        // Integral of x^a y^b on the reference triangle is a! b! / (a + b + 2)!
+        let fact = |n: i32| -> f64 { (1..=n).map(|v| v as f64).product() };
+        let rule = QuadratureRule::simplex(2, 5);
+        for a in 0..=5 {
+            for b in 0..=(5 - a) {
+                let integral = integrate(&rule, |x| x[0].powi(a) * x[1].powi(b));
+                let exact = fact(a) * fact(b) / fact(a + b + 2);
+                assert!(
+                    (integral - exact).abs() < 1e-14,
+                    "Did not integrate x^{} y^{} exactly on the triangle",
+                    a,
+                    b
+                );
+            }
+        }
+        // Integral of x y z on the reference tetrahedron is 1 / 720
+        let rule = QuadratureRule::simplex(3, 3);
+        let integral = integrate(&rule, |x| x[0] * x[1] * x[2]);
+        assert!(
+            (integral - 1.0 / 720.0).abs() < 1e-15,
+            "Did not integrate x y z exactly on the tetrahedron"
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Lowest order Raviart-Thomas element on the reference simplex
+///
+/// Shape function i is attached to the facet opposite to vertex i and reads (d - 1)! (x - vi) so
+/// that its flux through that facet (with the outward normal) is one while its normal component
+/// vanishes on the other facets. Physical shape functions are obtained with the contravariant
+/// Piola transformation (see CellMapping::contravariant_piola).
+pub struct RaviartThomasElement {
+    dim: usize,
+}
+
+impl RaviartThomasElement {
+    /// Build the lowest order element on the simplex of dimension dim (2 or 3)
+    pub fn new(dim: usize) -> Self {
+        assert!(
+            dim == 2 || dim == 3,
+            "Raviart-Thomas elements are only implemented in 2 and 3 dimensions"
+        );
+        RaviartThomasElement { dim }
+    }
+
+    /// Dimension of the reference simplex
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Number of shape functions (one per facet)
+    pub fn n_dofs(&self) -> usize {
+        self.dim + 1
+    }
+
+    /// Evaluate all the shape functions at a reference point
+    ///
+    /// The values are written as a flat array of size (number of shape functions, dimension).
+    pub fn values(&self, point: &[f64], values: &mut [f64]) {
+        let dim = self.dim;
+        assert!(
+            values.len() == self.n_dofs() * dim,
+            "Values buffer does not match the number of shape functions"
+        );
+        let scale = factorial(dim - 1);
+        for (i, value) in values.chunks_mut(dim).enumerate() {
+            for (k, v) in value.iter_mut().enumerate() {
+                let vertex = if i > 0 && k == i - 1 { 1.0 } else { 0.0 };
+                *v = scale * (point[k] - vertex);
+            }
+        }
+    }
+
+    /// Evaluate the reference divergences of all the shape functions (they are constant)
+    pub fn divergences(&self, divergences: &mut [f64]) {
+        assert!(
+            divergences.len() == self.n_dofs(),
+            "Divergences buffer does not match the number of shape functions"
+        );
+        divergences
+            .iter_mut()
+            .for_each(|d| *d = factorial(self.dim));
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+fn factorial(n: usize) -> f64 {
+    (1..=n).product::<usize>() as f64
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raviart_thomas_normal_components() {
+        let element = RaviartThomasElement::new(2);
+        let mut values = vec![0.0; 6];
+        // Midpoint of the facet opposite to vertex 0 with its outward normal (1, 1) / sqrt(2)
+        element.values(&[0.5, 0.5], &mut values);
+        let normal = [1.0 / 2.0_f64.sqrt(); 2];
+        let flux: Vec<f64> = values
+            .chunks(2)
+            .map(|v| v[0] * normal[0] + v[1] * normal[1])
+            .collect();
+        // The flux through the facet of length sqrt(2) is one for shape function 0 only
+        assert!(
+            (flux[0] * 2.0_f64.sqrt() - 1.0).abs() < 1e-14,
+            "Wrong flux of shape function 0"
+        );
+        assert!(
+            flux[1].abs() < 1e-14 && flux[2].abs() < 1e-14,
+            "Other shape functions should have no flux"
+        );
+        let mut divergences = vec![0.0; 3];
+        element.divergences(&mut divergences);
+        // Divergence theorem on the reference triangle of area 1 / 2
+        assert_eq!(divergences, vec![2.0; 3], "Wrong divergences");
+    }
+}
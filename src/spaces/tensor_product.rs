@@ -0,0 +1,218 @@
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Lagrange finite element of arbitrary order on the reference hypercube [0, 1]^d
+///
+/// The shape functions are products of one dimensional Lagrange polynomials on order + 1
+/// equispaced nodes. Nodes are numbered lexicographically with the first direction running
+/// fastest: node (i0, i1, ...) is i0 + (order + 1) i1 + (order + 1)^2 i2 + ... The one dimensional
+/// basis is exposed so that operators can be applied by sum factorization.
+pub struct TensorProductElement {
+    dim: usize,
+    order: usize,
+    nodes: Vec<f64>,
+}
+
+impl TensorProductElement {
+    /// Build the element of the given order on the hypercube of dimension dim
+    pub fn new(dim: usize, order: usize) -> Self {
+        assert!(
+            order > 0,
+            "Lagrange elements are only defined for orders >= 1"
+        );
+        let nodes = (0..=order).map(|i| i as f64 / order as f64).collect();
+        TensorProductElement { dim, order, nodes }
+    }
+
+    /// Dimension of the reference hypercube
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Polynomial order of the element in each direction
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Number of one dimensional shape functions
+    pub fn n_dofs_1d(&self) -> usize {
+        self.order + 1
+    }
+
+    /// Number of shape functions
+    pub fn n_dofs(&self) -> usize {
+        self.n_dofs_1d().pow(self.dim as u32)
+    }
+
+    /// Multi-index of a node (position along every direction)
+    pub fn node_multi_index(&self, node: usize) -> Vec<usize> {
+        let n = self.n_dofs_1d();
+        (0..self.dim)
+            .map(|d| (node / n.pow(d as u32)) % n)
+            .collect()
+    }
+
+    /// Reference coordinates of a node
+    pub fn node_coordinates(&self, node: usize) -> Vec<f64> {
+        self.node_multi_index(node)
+            .iter()
+            .map(|i| self.nodes[*i])
+            .collect()
+    }
+
+    /// Evaluate the one dimensional shape functions at a reference coordinate
+    pub fn values_1d(&self, x: f64, values: &mut [f64]) {
+        assert!(
+            values.len() == self.n_dofs_1d(),
+            "Values buffer does not match the number of shape functions"
+        );
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = self
+                .nodes
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, xj)| (x - xj) / (self.nodes[i] - xj))
+                .product();
+        }
+    }
+
+    /// Evaluate the derivatives of the one dimensional shape functions at a reference coordinate
+    pub fn derivatives_1d(&self, x: f64, derivatives: &mut [f64]) {
+        assert!(
+            derivatives.len() == self.n_dofs_1d(),
+            "Derivatives buffer does not match the number of shape functions"
+        );
+        for (i, derivative) in derivatives.iter_mut().enumerate() {
+            let xi = self.nodes[i];
+            // Product rule over the factors of the Lagrange polynomial
+            *derivative = (0..self.nodes.len())
+                .filter(|k| *k != i)
+                .map(|k| {
+                    self.nodes
+                        .iter()
+                        .enumerate()
+                        .filter(|(j, _)| *j != i)
+                        .map(|(j, xj)| {
+                            if j == k {
+                                1.0 / (xi - xj)
+                            } else {
+                                (x - xj) / (xi - xj)
+                            }
+                        })
+                        .product::<f64>()
+                })
+                .sum();
+        }
+    }
+
+    /// Evaluate all the shape functions at a reference point
+    pub fn values(&self, point: &[f64], values: &mut [f64]) {
+        assert!(
+            values.len() == self.n_dofs(),
+            "Values buffer does not match the number of shape functions"
+        );
+        let factors = self.factors(point, |x, v| self.values_1d(x, v));
+        for (node, value) in values.iter_mut().enumerate() {
+            *value = self
+                .node_multi_index(node)
+                .iter()
+                .enumerate()
+                .map(|(d, i)| factors[d][*i])
+                .product();
+        }
+    }
+
+    /// Evaluate the reference gradients of all the shape functions at a reference point
+    ///
+    /// The gradients are written as a flat array of size (number of shape functions, dimension).
+    pub fn gradients(&self, point: &[f64], gradients: &mut [f64]) {
+        assert!(
+            gradients.len() == self.n_dofs() * self.dim,
+            "Gradients buffer does not match the number of shape functions"
+        );
+        let values = self.factors(point, |x, v| self.values_1d(x, v));
+        let derivatives = self.factors(point, |x, v| self.derivatives_1d(x, v));
+        for (node, grad) in gradients.chunks_mut(self.dim.max(1)).enumerate() {
+            let multi_index = self.node_multi_index(node);
+            for (k, g) in grad.iter_mut().enumerate() {
+                *g = multi_index
+                    .iter()
+                    .enumerate()
+                    .map(|(d, i)| {
+                        if d == k {
+                            derivatives[d][*i]
+                        } else {
+                            values[d][*i]
+                        }
+                    })
+                    .product();
+            }
+        }
+    }
+
+    // One dimensional evaluations along every direction of a point
+    fn factors<Evaluate>(&self, point: &[f64], evaluate: Evaluate) -> Vec<Vec<f64>>
+    where
+        Evaluate: Fn(f64, &mut [f64]),
+    {
+        point
+            .iter()
+            .map(|x| {
+                let mut factor = vec![0.0; self.n_dofs_1d()];
+                evaluate(*x, &mut factor);
+                factor
+            })
+            .collect()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tensor_product_nodal_basis() {
+        let element = TensorProductElement::new(2, 3);
+        assert_eq!(element.n_dofs(), 16, "Wrong number of shape functions");
+        assert_eq!(
+            element.node_multi_index(6),
+            vec![2, 1],
+            "Wrong node ordering"
+        );
+        let mut values = vec![0.0; 16];
+        for node in 0..16 {
+            element.values(&element.node_coordinates(node), &mut values);
+            for (i, v) in values.iter().enumerate() {
+                let expected = if i == node { 1.0 } else { 0.0 };
+                assert!((v - expected).abs() < 1e-13, "Basis is not nodal");
+            }
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_tensor_product_gradients() {
+        let element = TensorProductElement::new(2, 2);
+        let point = [0.3, 0.7];
+        let mut gradients = vec![0.0; 18];
+        element.gradients(&point, &mut gradients);
+        // Gradient of x * y interpolated exactly by the nodal values
+        let mut grad = [0.0; 2];
+        for node in 0..9 {
+            let x = element.node_coordinates(node);
+            for k in 0..2 {
+                grad[k] += x[0] * x[1] * gradients[2 * node + k];
+            }
+        }
+        assert!(
+            (grad[0] - 0.7).abs() < 1e-13 && (grad[1] - 0.3).abs() < 1e-13,
+            "Wrong gradient"
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use super::quantities::QuantityOfInterest;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::core::logging::Span;
+use crate::discretizations::constraints::AffineConstraints;
+use crate::discretizations::function::Function;
+use crate::solvers::config::{MethodConfig, SolverConfig};
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Adjoint problem of a linear (or linearized) forward problem giving the sensitivities of
+/// quantities of interest to the parameters of the problem
+///
+/// The forward problem A(p) u = f(p) is condensed by its constraints u = C û + g into the system Â
+/// û = Cᵀ (f - A g) it was assembled into (see AffineConstraints::distribute_local_to_global). The
+/// adjoint of a quantity J(u) solves Âᵀ z = Cᵀ ∂J/∂u and λ = C z, vanishing on the Dirichlet
+/// dofs, gives the derivative of J along any parameter from the derivative ∂R/∂p of the
+/// unconstrained residual R = A u - f: dJ/dp = ∂J/∂p - λ.∂R/∂p. One adjoint solve thus gives the
+/// gradient of a quantity with respect to any number of parameters of the operator and the loads,
+/// the Dirichlet values being held fixed.
+pub struct Adjoint<'a> {
+    matrix: SparseCSR<f64>,
+    constraints: Option<&'a AffineConstraints>,
+    solver: SolverConfig,
+}
+
+impl<'a> Adjoint<'a> {
+    /// Adjoint of the condensed matrix of a forward problem solved by a sparse LU factorization
+    pub fn new(matrix: &SparseCSR<f64>) -> Self {
+        Adjoint {
+            matrix: matrix.transpose(),
+            constraints: None,
+            solver: SolverConfig::new(MethodConfig::SparseLU),
+        }
+    }
+
+    /// Set the constraints the forward matrix was condensed with
+    pub fn with_constraints(mut self, constraints: &'a AffineConstraints) -> Self {
+        assert!(
+            constraints.n_dofs() == self.matrix.n_rows(),
+            "Constraints do not match the matrix"
+        );
+        self.constraints = Some(constraints);
+        self
+    }
+
+    /// Set the solver of the transposed system
+    pub fn with_solver(mut self, solver: SolverConfig) -> Self {
+        self.solver = solver;
+        self
+    }
+
+    /// Adjoint λ of a quantity of interest linearized at the forward solution
+    pub fn solve<Quantity>(&self, quantity: &Quantity, solution: &Function) -> Vec<f64>
+    where
+        Quantity: QuantityOfInterest + ?Sized,
+    {
+        self.solve_derivative(quantity.derivative(solution))
+    }
+
+    /// Adjoint λ of a functional given its derivative with respect to the unconstrained dofs
+    pub fn solve_derivative(&self, mut derivative: Vec<f64>) -> Vec<f64> {
+        let _span = Span::enter("adjoint solve");
+        assert!(
+            derivative.len() == self.matrix.n_rows(),
+            "Derivative does not match the matrix"
+        );
+        if let Some(constraints) = self.constraints {
+            constraints.condense(&mut derivative);
+        }
+        let solver = self
+            .solver
+            .build(&self.matrix)
+            .expect("Adjoint system is singular");
+        let mut adjoint = vec![0.0; derivative.len()];
+        assert!(
+            solver.solve(&derivative, &mut adjoint).converged(),
+            "Adjoint solve did not converge"
+        );
+        if let Some(constraints) = self.constraints {
+            constraints.distribute_homogeneous(&mut adjoint);
+        }
+        adjoint
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Derivative of a quantity along a parameter from its adjoint and the derivative of the
+/// unconstrained residual with respect to the parameter
+///
+/// The explicit derivative ∂J/∂p is to be added when the quantity depends on the parameter itself.
+pub fn sensitivity(adjoint: &[f64], residual_derivative: &[f64]) -> f64 {
+    assert!(
+        adjoint.len() == residual_derivative.len(),
+        "Residual derivative does not match the adjoint"
+    );
+    -adjoint
+        .iter()
+        .zip(residual_derivative)
+        .map(|(l, r)| l * r)
+        .sum::<f64>()
+}
+
+/// Gradient of a quantity with respect to parameters from its adjoint and the derivatives of the
+/// unconstrained residual with respect to each of them
+pub fn gradient(adjoint: &[f64], residual_derivatives: &[Vec<f64>]) -> Vec<f64> {
+    residual_derivatives
+        .iter()
+        .map(|derivative| sensitivity(adjoint, derivative))
+        .collect()
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::discretizations::operators::stiffness_matrix;
+    use crate::discretizations::sparsity::SparsityPattern;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::spaces::quadrature::QuadratureRule;
+    use crate::workflows::quantities::{BoundaryFlux, DomainIntegral, PointValue};
+
+    // Condensed system of -k Δu + ∂u/∂x = 1 + p x with u = 0 on the left and u = 1 on the right
+    fn assemble(
+        space: &FunctionSpace,
+        k: f64,
+        p: f64,
+    ) -> (SparseCSR<f64>, Vec<f64>, AffineConstraints) {
+        let mut constraints = AffineConstraints::new(space.n_dofs());
+        for dof in space.tagged_dofs(1) {
+            constraints.add_dirichlet(dof, 0.0);
+        }
+        for dof in space.tagged_dofs(2) {
+            constraints.add_dirichlet(dof, 1.0);
+        }
+        constraints.close();
+        let mut matrix =
+            SparsityPattern::from_dofmap_and_constraints(space.dof_map(), &constraints).to_csr(0.0);
+        let mut rhs = vec![0.0; space.n_dofs()];
+        let assembler = space.assembler(QuadratureRule::simplex(2, 2));
+        assembler.assemble_system(
+            &mut matrix,
+            &mut rhs,
+            &constraints,
+            |values, local, load| {
+                let n = values.n_dofs();
+                for q in 0..values.n_points() {
+                    let w = values.weight(q);
+                    for i in 0..n {
+                        let phi_i = values.shape_value(q, i);
+                        for j in 0..n {
+                            let (gi, gj) =
+                                (values.shape_gradient(q, i), values.shape_gradient(q, j));
+                            let diffusion = gi[0] * gj[0] + gi[1] * gj[1];
+                            local[i * n + j] += (k * diffusion + gj[0] * phi_i) * w;
+                        }
+                        load[i] += (1.0 + p * values.point(q)[0]) * phi_i * w;
+                    }
+                }
+            },
+        );
+        (matrix, rhs, constraints)
+    }
+
+    // Forward solution for a diffusivity and a parameter
+    fn forward<'a>(space: &'a FunctionSpace<'a>, k: f64, p: f64) -> Function<'a> {
+        let (matrix, rhs, constraints) = assemble(space, k, p);
+        let solver = SolverConfig::new(MethodConfig::SparseLU)
+            .build(&matrix)
+            .unwrap();
+        let mut solution = vec![0.0; space.n_dofs()];
+        assert!(solver.solve(&rhs, &mut solution).converged());
+        constraints.distribute(&mut solution);
+        Function::from_values(space, solution)
+    }
+
+    #[test]
+    fn test_adjoint_sensitivities() {
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![4, 4]).simplex_mesh();
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let (k, p) = (0.5, 0.3);
+        let u = forward(&space, k, p);
+        let (matrix, _, constraints) = assemble(&space, k, p);
+        let adjoint = Adjoint::new(&matrix).with_constraints(&constraints);
+        // ∂R/∂k is the unit stiffness times u and ∂R/∂p the opposite of the load of x
+        let assembler = space.assembler(QuadratureRule::simplex(2, 2));
+        let mut dr_dk = vec![0.0; space.n_dofs()];
+        stiffness_matrix(&assembler, |_| 1.0).apply(u.values(), &mut dr_dk);
+        let mut dr_dp = vec![0.0; space.n_dofs()];
+        assembler.assemble_vector(&mut dr_dp, |values, local| {
+            for q in 0..values.n_points() {
+                for i in 0..values.n_dofs() {
+                    local[i] -= values.point(q)[0] * values.shape_value(q, i) * values.weight(q);
+                }
+            }
+        });
+        let quantities: Vec<Box<dyn QuantityOfInterest>> = vec![
+            Box::new(DomainIntegral::new("energy", |_, u| u[0] * u[0])),
+            Box::new(PointValue::new("center", &[0.4, 0.6], 0)),
+            Box::new(BoundaryFlux::diffusive("left_flux", 1, 1.0)),
+        ];
+        for quantity in &quantities {
+            let lambda = adjoint.solve(quantity.as_ref(), &u);
+            let sensitivities = gradient(&lambda, &[dr_dk.clone(), dr_dp.clone()]);
+            let h = 1e-5;
+            let expected = [
+                (quantity.evaluate(&forward(&space, k + h, p))
+                    - quantity.evaluate(&forward(&space, k - h, p)))
+                    / (2.0 * h),
+                (quantity.evaluate(&forward(&space, k, p + h))
+                    - quantity.evaluate(&forward(&space, k, p - h)))
+                    / (2.0 * h),
+            ];
+            for (computed, expected) in sensitivities.iter().zip(expected) {
+                assert!(
+                    (computed - expected).abs() < 1e-6 * expected.abs().max(1.0),
+                    "Wrong sensitivity of {}: {} instead of {}",
+                    quantity.name(),
+                    computed,
+                    expected
+                );
+            }
+        }
+    }
+}
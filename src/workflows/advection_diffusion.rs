@@ -0,0 +1,351 @@
+use crate::core::logging::Span;
+use crate::discretizations::constraints::AffineConstraints;
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+use crate::discretizations::mesh::Mesh;
+use crate::discretizations::sparsity::SparsityPattern;
+use crate::solvers::config::{MethodConfig, SolverConfig};
+use crate::spaces::quadrature::QuadratureRule;
+
+// Scalar field of the physical coordinates
+type ScalarField<'a> = Box<dyn Fn(&[f64]) -> f64 + 'a>;
+
+// Vector field filling its components at the physical coordinates
+type VectorField<'a> = Box<dyn Fn(&[f64], &mut [f64]) + 'a>;
+
+//--------------------------------------------------------------------------------------------------
+// # Enums
+//--------------------------------------------------------------------------------------------------
+
+/// Stabilization of the Galerkin discretization of advection dominated transport
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Stabilization {
+    /// Plain Galerkin, which oscillates once the cell Péclet number exceeds 1
+    Galerkin,
+    /// Streamline upwind Petrov-Galerkin, testing the residual with τ b.grad v
+    Supg,
+    /// Galerkin least squares, testing the residual with τ (b.grad v + σ v)
+    Gls,
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Steady advection diffusion reaction -ε Δu + b.grad u + σ u = f on a continuous scalar space
+///
+/// Values are imposed on tagged boundary facets and the rest of the boundary has no diffusive
+/// flux. The stabilizations add the strong residual b.grad u + σ u - f tested by their weight on
+/// every cell, the stabilization parameter being τ = h / (2 |b|) (coth(Pe) - 1 / Pe) with the cell
+/// diameter h and the cell Péclet number Pe = |b| h / (2 ε). The diffusion part of the residual is
+/// neglected, which is exact for linear elements. By default the diffusivity is 1, there is no
+/// advection, reaction nor source, the stabilization is SUPG and the system is solved by the sparse
+/// LU factorization.
+pub struct AdvectionDiffusion<'a> {
+    space: &'a FunctionSpace<'a>,
+    diffusivity: f64,
+    reaction: f64,
+    velocity: Option<VectorField<'a>>,
+    source: Option<ScalarField<'a>>,
+    dirichlet: Vec<(usize, ScalarField<'a>)>,
+    stabilization: Stabilization,
+    solver: SolverConfig,
+}
+
+impl<'a> AdvectionDiffusion<'a> {
+    /// Transport problem on the space with the default parameters
+    pub fn new(space: &'a FunctionSpace<'a>) -> Self {
+        assert!(
+            space.n_components() == 1 && !space.is_discontinuous(),
+            "Advection diffusion needs a continuous scalar space"
+        );
+        AdvectionDiffusion {
+            space,
+            diffusivity: 1.0,
+            reaction: 0.0,
+            velocity: None,
+            source: None,
+            dirichlet: Vec::new(),
+            stabilization: Stabilization::Supg,
+            solver: SolverConfig::new(MethodConfig::SparseLU),
+        }
+    }
+
+    /// Set the diffusivity ε
+    pub fn with_diffusivity(mut self, diffusivity: f64) -> Self {
+        assert!(diffusivity > 0.0, "Diffusivity should be positive");
+        self.diffusivity = diffusivity;
+        self
+    }
+
+    /// Set the reaction coefficient σ
+    pub fn with_reaction(mut self, reaction: f64) -> Self {
+        assert!(reaction >= 0.0, "Reaction should be non negative");
+        self.reaction = reaction;
+        self
+    }
+
+    /// Set the advection velocity b filling its components at the physical coordinates
+    pub fn with_velocity<Velocity>(mut self, velocity: Velocity) -> Self
+    where
+        Velocity: Fn(&[f64], &mut [f64]) + 'a,
+    {
+        self.velocity = Some(Box::new(velocity));
+        self
+    }
+
+    /// Set the source f as a function of the physical coordinates
+    pub fn with_source<Source>(mut self, source: Source) -> Self
+    where
+        Source: Fn(&[f64]) -> f64 + 'a,
+    {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// Impose the value given as a function of the physical coordinates on the boundary facets
+    /// carrying the tag
+    pub fn with_dirichlet<Value>(mut self, tag: usize, value: Value) -> Self
+    where
+        Value: Fn(&[f64]) -> f64 + 'a,
+    {
+        self.dirichlet.push((tag, Box::new(value)));
+        self
+    }
+
+    /// Set the stabilization
+    pub fn with_stabilization(mut self, stabilization: Stabilization) -> Self {
+        self.stabilization = stabilization;
+        self
+    }
+
+    /// Set the solver of the nonsymmetric system
+    pub fn with_solver(mut self, solver: SolverConfig) -> Self {
+        self.solver = solver;
+        self
+    }
+
+    /// Cell Péclet numbers |b| h / (2 ε) with the velocity at the cell centroids
+    pub fn cell_peclet_numbers(&self) -> Vec<f64> {
+        let mesh = self.space.mesh();
+        let dim = mesh.geometric_dim();
+        let mut centroid = vec![0.0; dim];
+        let mut b = vec![0.0; dim];
+        (0..mesh.n_cells())
+            .map(|cell| {
+                let vertices = mesh.cell(cell);
+                centroid.iter_mut().enumerate().for_each(|(k, x)| {
+                    *x = vertices.iter().map(|v| mesh.vertex(*v)[k]).sum::<f64>()
+                        / vertices.len() as f64
+                });
+                self.velocity_at(&centroid, &mut b);
+                norm(&b) * diameter(mesh, cell) / (2.0 * self.diffusivity)
+            })
+            .collect()
+    }
+
+    /// Largest cell Péclet number, above 1 the Galerkin solution oscillates
+    pub fn max_peclet_number(&self) -> f64 {
+        self.cell_peclet_numbers().into_iter().fold(0.0, f64::max)
+    }
+
+    /// Solve for the transported quantity
+    pub fn solve(&self) -> Function<'a> {
+        let _span = Span::enter("advection diffusion");
+        let space = self.space;
+        let mesh = space.mesh();
+        let dim = mesh.geometric_dim();
+        let mut constraints = AffineConstraints::new(space.n_dofs());
+        let coordinates = space.dof_coordinates();
+        for (tag, value) in &self.dirichlet {
+            for dof in space.tagged_dofs(*tag) {
+                constraints.add_dirichlet(dof, value(&coordinates[dof * dim..(dof + 1) * dim]));
+            }
+        }
+        constraints.close();
+        let mut matrix =
+            SparsityPattern::from_dofmap_and_constraints(space.dof_map(), &constraints).to_csr(0.0);
+        let mut rhs = vec![0.0; space.n_dofs()];
+        let (epsilon, sigma) = (self.diffusivity, self.reaction);
+        let assembler = space.assembler(QuadratureRule::simplex(dim, 2 * space.element().order()));
+        let mut b = vec![0.0; dim];
+        let mut advection = Vec::new();
+        assembler.assemble_system(
+            &mut matrix,
+            &mut rhs,
+            &constraints,
+            |values, local, load| {
+                let n = values.n_dofs();
+                let h = diameter(mesh, values.cell());
+                for q in 0..values.n_points() {
+                    let w = values.weight(q);
+                    self.velocity_at(values.point(q), &mut b);
+                    let tau = self.tau(&b, h);
+                    let f = self
+                        .source
+                        .as_ref()
+                        .map_or(0.0, |source| source(values.point(q)));
+                    // Derivatives b.grad phi of the shape functions along the flow
+                    advection.clear();
+                    advection.extend((0..n).map(|i| dot(&b, values.shape_gradient(q, i))));
+                    for i in 0..n {
+                        let phi_i = values.shape_value(q, i);
+                        let weight_i = match self.stabilization {
+                            Stabilization::Galerkin => 0.0,
+                            Stabilization::Supg => tau * advection[i],
+                            Stabilization::Gls => tau * (advection[i] + sigma * phi_i),
+                        };
+                        for j in 0..n {
+                            let phi_j = values.shape_value(q, j);
+                            let gradients =
+                                dot(values.shape_gradient(q, i), values.shape_gradient(q, j));
+                            let strong = advection[j] + sigma * phi_j;
+                            local[i * n + j] +=
+                                (epsilon * gradients + strong * phi_i + weight_i * strong) * w;
+                        }
+                        load[i] += f * (phi_i + weight_i) * w;
+                    }
+                }
+            },
+        );
+        let solver = self
+            .solver
+            .build(&matrix)
+            .expect("Advection diffusion system is singular");
+        let mut solution = vec![0.0; space.n_dofs()];
+        assert!(
+            solver.solve(&rhs, &mut solution).converged(),
+            "Advection diffusion solve did not converge"
+        );
+        constraints.distribute(&mut solution);
+        Function::from_values(space, solution)
+    }
+
+    // Velocity at a point, zero without advection
+    fn velocity_at(&self, x: &[f64], b: &mut [f64]) {
+        match &self.velocity {
+            Some(velocity) => velocity(x, b),
+            None => b.iter_mut().for_each(|b| *b = 0.0),
+        }
+    }
+
+    // Stabilization parameter of a velocity on a cell of diameter h
+    fn tau(&self, b: &[f64], h: f64) -> f64 {
+        let speed = norm(b);
+        if self.stabilization == Stabilization::Galerkin || speed == 0.0 {
+            return 0.0;
+        }
+        let peclet = speed * h / (2.0 * self.diffusivity);
+        // Series of coth(Pe) - 1 / Pe near 0 where it cancels
+        let upwinding = if peclet < 1e-3 {
+            peclet / 3.0
+        } else {
+            1.0 / peclet.tanh() - 1.0 / peclet
+        };
+        h / (2.0 * speed) * upwinding
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Largest distance between two vertices of a cell
+pub(crate) fn diameter(mesh: &Mesh, cell: usize) -> f64 {
+    let vertices = mesh.cell(cell);
+    let mut diameter: f64 = 0.0;
+    for (k, a) in vertices.iter().enumerate() {
+        for b in &vertices[k + 1..] {
+            let distance: f64 = mesh
+                .vertex(*a)
+                .iter()
+                .zip(mesh.vertex(*b))
+                .map(|(x, y)| (x - y) * (x - y))
+                .sum();
+            diameter = diameter.max(distance.sqrt());
+        }
+    }
+    diameter
+}
+
+// Euclidean inner product
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+// Euclidean norm
+fn norm(a: &[f64]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::refinement::Refinement;
+    use crate::discretizations::test_meshes::unit_square;
+    use crate::spaces::lagrange::LagrangeElement;
+
+    #[test]
+    fn test_advection_diffusion() {
+        let square = unit_square();
+        let mut mesh = Refinement::uniform(&Refinement::uniform(&square).into_mesh()).into_mesh();
+        mesh.tag_boundary(1, |x| x[0] == 0.0 || x[0] == 1.0);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        // -ε u'' + u' = 1 with u(0) = u(1) = 0 has a boundary layer of width ε at x = 1
+        let epsilon = 1e-2;
+        let problem = |stabilization| {
+            AdvectionDiffusion::new(&space)
+                .with_diffusivity(epsilon)
+                .with_velocity(|_, b| {
+                    b[0] = 1.0;
+                    b[1] = 0.0;
+                })
+                .with_source(|_| 1.0)
+                .with_dirichlet(1, |_| 0.0)
+                .with_stabilization(stabilization)
+        };
+        let galerkin = problem(Stabilization::Galerkin);
+        assert!(
+            galerkin.max_peclet_number() > 10.0,
+            "The mesh should not resolve the layer"
+        );
+        let oscillation = galerkin
+            .solve()
+            .values()
+            .iter()
+            .fold(0.0, |worst: f64, u| worst.max(-u));
+        assert!(
+            oscillation > 0.5,
+            "Galerkin should oscillate upstream of the layer"
+        );
+        // Exact solution up to terms in exp(-1 / ε)
+        let exact = |x: f64| x - ((x - 1.0) / epsilon).exp();
+        let coordinates = space.dof_coordinates();
+        for stabilization in [Stabilization::Supg, Stabilization::Gls] {
+            let u = problem(stabilization).solve();
+            let mut error: f64 = 0.0;
+            for (dof, u) in u.values().iter().enumerate() {
+                let x = coordinates[dof * 2];
+                assert!(*u > -1e-10, "Stabilized solution undershoots");
+                if x < 0.7 {
+                    error = error.max((u - exact(x)).abs());
+                }
+            }
+            assert!(
+                error < 0.1,
+                "{:?} error {} away from the layer",
+                stabilization,
+                error
+            );
+        }
+    }
+}
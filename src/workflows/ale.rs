@@ -0,0 +1,346 @@
+use super::elasticity::{IsotropicMaterial, LinearElasticity};
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::logging::Span;
+use crate::discretizations::constraints::AffineConstraints;
+use crate::discretizations::forms::{dot, grad, test, trial, vector_coefficient, Expr};
+use crate::discretizations::function_space::FunctionSpace;
+use crate::discretizations::mesh::Mesh;
+use crate::discretizations::sparsity::SparsityPattern;
+use crate::solvers::config::{MethodConfig, SolverConfig};
+use crate::spaces::lagrange::LagrangeElement;
+use crate::spaces::quadrature::QuadratureRule;
+use std::sync::Arc;
+
+// Vector field filling its components at the physical coordinates
+type VectorField<'a> = Box<dyn Fn(&[f64], &mut [f64]) + 'a>;
+
+// Poisson ratio of the pseudo material of the elastic extension
+const EXTENSION_POISSON_RATIO: f64 = 0.3;
+
+//--------------------------------------------------------------------------------------------------
+// # Enums
+//--------------------------------------------------------------------------------------------------
+
+/// Extension of the boundary displacements of a moving mesh to its interior
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeshMotionMethod {
+    /// Componentwise Laplace equation stiffened by the inverse of the cell volumes, so that small
+    /// cells deform less than large ones
+    Harmonic,
+    /// Linear elasticity of a pseudo material, which also resists shear and keeps the angles of the
+    /// cells better for large rotations of the boundaries
+    Elastic,
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Motion of the vertices of a mesh extending the displacements of its boundaries, the first step
+/// of arbitrary Lagrangian-Eulerian (ALE) discretizations of moving domains
+///
+/// Displacements are imposed on the boundary facets carrying a tag as functions of the reference
+/// coordinates, the other boundaries are free to slide and stretch. The displacement of every
+/// vertex solves the extension problem of linear elements on the reference mesh, displaced_mesh
+/// builds the current mesh and mesh_velocity the velocity w of its vertices between two steps. On
+/// the moving mesh the time derivatives are taken at fixed mesh points and the transport by the
+/// velocity b of the material becomes a transport by the relative velocity b - w (see
+/// ale_convection). By default the extension is harmonic.
+pub struct MeshMotion<'a> {
+    mesh: &'a Mesh,
+    method: MeshMotionMethod,
+    displacements: Vec<(usize, VectorField<'a>)>,
+}
+
+impl<'a> MeshMotion<'a> {
+    /// Motion of a reference mesh without imposed displacements
+    pub fn new(mesh: &'a Mesh) -> Self {
+        MeshMotion {
+            mesh,
+            method: MeshMotionMethod::Harmonic,
+            displacements: Vec::new(),
+        }
+    }
+
+    /// Set the extension method
+    pub fn with_method(mut self, method: MeshMotionMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Impose the displacement given as a function of the reference coordinates on the boundary
+    /// facets carrying the tag
+    pub fn with_displacement<Displacement>(mut self, tag: usize, displacement: Displacement) -> Self
+    where
+        Displacement: Fn(&[f64], &mut [f64]) + 'a,
+    {
+        self.displacements.push((tag, Box::new(displacement)));
+        self
+    }
+
+    /// Hold the boundary facets carrying the tag in place
+    pub fn with_fixed(self, tag: usize) -> Self {
+        self.with_displacement(tag, |_, u| u.iter_mut().for_each(|u| *u = 0.0))
+    }
+
+    /// Displacements of the vertices as a (vertices, geometric dimension) array
+    pub fn solve(&self) -> DataHold<f64, [usize; 2]> {
+        let _span = Span::enter("mesh motion");
+        match self.method {
+            MeshMotionMethod::Harmonic => self.harmonic_extension(),
+            MeshMotionMethod::Elastic => self.elastic_extension(),
+        }
+    }
+
+    // Componentwise Laplace extension stiffened by the inverse cell volumes
+    fn harmonic_extension(&self) -> DataHold<f64, [usize; 2]> {
+        let mesh = self.mesh;
+        let dim = mesh.geometric_dim();
+        let space = FunctionSpace::new(mesh, LagrangeElement::new(mesh.topological_dim(), 1));
+        let assembler = space.assembler(QuadratureRule::simplex(mesh.topological_dim(), 0));
+        let coordinates = space.dof_coordinates();
+        let mut value = vec![0.0; dim];
+        let mut displacements = vec![0.0; mesh.n_vertices() * dim];
+        for component in 0..dim {
+            let mut constraints = AffineConstraints::new(space.n_dofs());
+            for (tag, displacement) in &self.displacements {
+                for dof in space.tagged_dofs(*tag) {
+                    displacement(&coordinates[dof * dim..(dof + 1) * dim], &mut value);
+                    constraints.add_dirichlet(dof, value[component]);
+                }
+            }
+            constraints.close();
+            let mut matrix =
+                SparsityPattern::from_dofmap_and_constraints(space.dof_map(), &constraints)
+                    .to_csr(0.0);
+            let mut rhs = vec![0.0; space.n_dofs()];
+            assembler.assemble_system(&mut matrix, &mut rhs, &constraints, |values, local, _| {
+                let n = values.n_dofs();
+                let volume: f64 = (0..values.n_points()).map(|q| values.weight(q)).sum();
+                for q in 0..values.n_points() {
+                    let w = values.weight(q) / volume;
+                    for i in 0..n {
+                        for j in 0..n {
+                            let gradients: f64 = values
+                                .shape_gradient(q, i)
+                                .iter()
+                                .zip(values.shape_gradient(q, j))
+                                .map(|(a, b)| a * b)
+                                .sum();
+                            local[i * n + j] += gradients * w;
+                        }
+                    }
+                }
+            });
+            let solver = SolverConfig::new(MethodConfig::SparseCholesky)
+                .build(&matrix)
+                .expect("Mesh motion needs displacements imposed on some boundary");
+            let mut solution = vec![0.0; space.n_dofs()];
+            assert!(
+                solver.solve(&rhs, &mut solution).converged(),
+                "Mesh motion solve did not converge"
+            );
+            constraints.distribute(&mut solution);
+            for cell in 0..mesh.n_cells() {
+                for (vertex, dof) in mesh.cell(cell).iter().zip(space.dof_map().cell_dofs(cell)) {
+                    displacements[vertex * dim + component] = solution[*dof];
+                }
+            }
+        }
+        DataHold::new(displacements, [mesh.n_vertices(), dim])
+    }
+
+    // Elastic extension solved by the linear elasticity workflow
+    fn elastic_extension(&self) -> DataHold<f64, [usize; 2]> {
+        let mesh = self.mesh;
+        let dim = mesh.geometric_dim();
+        let space = FunctionSpace::vector(mesh, LagrangeElement::new(dim, 1), dim);
+        let material = IsotropicMaterial::new(1.0, EXTENSION_POISSON_RATIO);
+        let mut problem = LinearElasticity::new(&space, material);
+        for (tag, displacement) in &self.displacements {
+            problem =
+                problem.with_displacement(*tag, |x: &[f64], u: &mut [f64]| displacement(x, u));
+        }
+        problem.solve().vertex_values()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Mesh whose vertices are moved by displacements given as a (vertices, geometric dimension)
+/// array, keeping the cells and the tags
+pub fn displaced_mesh(mesh: &Mesh, displacements: &DataHold<f64, [usize; 2]>) -> Mesh {
+    assert_eq!(
+        displacements.len(),
+        mesh.vertices().len(),
+        "Displacements do not match the vertices"
+    );
+    let vertices: Vec<f64> = mesh
+        .vertices()
+        .iter()
+        .zip(displacements.iter())
+        .map(|(x, u)| x + u)
+        .collect();
+    let mut displaced = Mesh::new(
+        DataHold::new(vertices, [mesh.n_vertices(), mesh.geometric_dim()]),
+        DataHold::new(
+            mesh.cells().to_vec(),
+            [mesh.n_cells(), mesh.vertices_per_cell()],
+        ),
+    );
+    for (facet, tag) in mesh.facet_tags() {
+        displaced.tag_facet(facet, tag);
+    }
+    for (vertex, tag) in mesh.vertex_tags() {
+        displaced.tag_vertex(vertex, tag);
+    }
+    for (cell, tag) in mesh.cell_tags() {
+        displaced.tag_cell(cell, tag);
+    }
+    displaced
+}
+
+/// Velocities of the vertices of a mesh moving from a previous configuration over a time step
+pub fn mesh_velocity(previous: &Mesh, current: &Mesh, time_step: f64) -> DataHold<f64, [usize; 2]> {
+    assert!(
+        previous.n_vertices() == current.n_vertices()
+            && previous.geometric_dim() == current.geometric_dim(),
+        "Configurations should have the same vertices"
+    );
+    assert!(time_step > 0.0, "Time step should be positive");
+    let velocities = current
+        .vertices()
+        .iter()
+        .zip(previous.vertices().iter())
+        .map(|(x, y)| (x - y) / time_step)
+        .collect();
+    DataHold::new(velocities, [current.n_vertices(), current.geometric_dim()])
+}
+
+/// Piecewise linear vector field of values at the vertices of a mesh, zero outside of the mesh, as
+/// a coefficient of forms
+pub fn vertex_field(
+    mesh: Arc<Mesh>,
+    values: DataHold<f64, [usize; 2]>,
+) -> impl Fn(&[f64], &mut [f64]) + Send + Sync + 'static {
+    assert_eq!(
+        values.len() % mesh.n_vertices().max(1),
+        0,
+        "Values do not match the vertices"
+    );
+    let n_components = values.len() / mesh.n_vertices().max(1);
+    move |x, field| {
+        field.iter_mut().for_each(|f| *f = 0.0);
+        if let Some((cell, reference)) = mesh.locate(x) {
+            let first = 1.0 - reference.iter().sum::<f64>();
+            let weights = std::iter::once(first).chain(reference.iter().copied());
+            for (vertex, weight) in mesh.cell(cell).iter().zip(weights) {
+                for (f, v) in field
+                    .iter_mut()
+                    .zip(&values[vertex * n_components..(vertex + 1) * n_components])
+                {
+                    *f += weight * v;
+                }
+            }
+        }
+    }
+}
+
+/// Integrand (b - w).grad(u) v of the ALE transport by a material velocity b on a mesh moving with
+/// the velocity w, both filling their components at the physical coordinates
+pub fn ale_convection<Velocity, MeshVelocity>(
+    velocity: Velocity,
+    mesh_velocity: MeshVelocity,
+) -> Expr
+where
+    Velocity: Fn(&[f64], &mut [f64]) + Send + Sync + 'static,
+    MeshVelocity: Fn(&[f64], &mut [f64]) + Send + Sync + 'static,
+{
+    let relative = vector_coefficient(move |x, b| {
+        velocity(x, b);
+        let mut w = vec![0.0; b.len()];
+        mesh_velocity(x, &mut w);
+        b.iter_mut().zip(&w).for_each(|(b, w)| *b -= w);
+    });
+    dot(relative, grad(trial())) * test()
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::forms::dx;
+
+    #[test]
+    fn test_mesh_motion() {
+        // Both extensions reproduce affine motions of the whole boundary
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![4, 4]).simplex_mesh();
+        let affine = |x: &[f64], u: &mut [f64]| {
+            u[0] = 0.1 * x[0] + 0.2 * x[1];
+            u[1] = -0.1 * x[1] + 0.05;
+        };
+        for method in [MeshMotionMethod::Harmonic, MeshMotionMethod::Elastic] {
+            let mut motion = MeshMotion::new(&mesh).with_method(method);
+            for tag in 1..=4 {
+                motion = motion.with_displacement(tag, affine);
+            }
+            let displacements = motion.solve();
+            let mut expected = [0.0; 2];
+            for v in 0..mesh.n_vertices() {
+                affine(mesh.vertex(v), &mut expected);
+                assert!(
+                    (displacements[2 * v] - expected[0]).abs() < 1e-12
+                        && (displacements[2 * v + 1] - expected[1]).abs() < 1e-12,
+                    "{:?} extension did not reproduce the affine motion",
+                    method
+                );
+            }
+        }
+        // Lid lifted above a fixed bottom with free sides
+        let displacements = MeshMotion::new(&mesh)
+            .with_fixed(3)
+            .with_displacement(4, |_, u| u.copy_from_slice(&[0.0, 0.1]))
+            .solve();
+        let moved = displaced_mesh(&mesh, &displacements);
+        assert!(
+            (0..mesh.n_vertices())
+                .all(|v| (moved.vertex(v)[1] - 1.1 * mesh.vertex(v)[1]).abs() < 1e-12),
+            "Lid motion should stretch the mesh uniformly"
+        );
+        assert_eq!(
+            moved.facet_tags().count(),
+            mesh.facet_tags().count(),
+            "Tags were not kept"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_ale_convection() {
+        // Mesh translated at the velocity of the material sees no transport
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![3, 3]).simplex_mesh();
+        let shift = DataHold::new(
+            (0..mesh.n_vertices()).flat_map(|_| [0.02, 0.01]).collect(),
+            [mesh.n_vertices(), 2],
+        );
+        let translated = Arc::new(displaced_mesh(&mesh, &shift));
+        let velocity = mesh_velocity(&mesh, &translated, 0.01);
+        let space = FunctionSpace::new(&translated, LagrangeElement::new(2, 1));
+        let assembler = space.assembler(QuadratureRule::simplex(2, 2));
+        let pattern = SparsityPattern::from_dofmap(space.dof_map());
+        let w = vertex_field(translated.clone(), velocity);
+        let mut matrix = pattern.to_csr(0.0);
+        (ale_convection(|_, b| b.copy_from_slice(&[2.0, 1.0]), w) * dx())
+            .assemble_matrix(&assembler, &mut matrix);
+        assert!(
+            matrix.values().iter().all(|v| v.abs() < 1e-12),
+            "Relative velocity should vanish"
+        );
+    }
+}
@@ -0,0 +1,351 @@
+use crate::core::logging::{info, Span};
+use crate::discretizations::estimator::{global_estimate, mark_dorfler, mark_fixed_fraction};
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+use crate::discretizations::mesh::Mesh;
+use crate::discretizations::refinement::Refinement;
+use crate::spaces::lagrange::LagrangeElement;
+
+//--------------------------------------------------------------------------------------------------
+// # Enums
+//--------------------------------------------------------------------------------------------------
+
+/// Selection of the cells to refine from their error indicators
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Marking {
+    /// Dörfler marking of the cells holding the given fraction of the squared estimate
+    Dorfler(f64),
+    /// The given fraction of the cells with the largest indicators
+    FixedFraction(f64),
+}
+
+/// Reason an adaptive loop stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdaptiveStop {
+    /// The global estimate reached the tolerance
+    Tolerance,
+    /// Refining further would exceed the dof budget
+    DofBudget,
+    /// The maximal number of iterations was reached
+    MaxIterations,
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Summary of one solve of an adaptive loop
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdaptiveIteration {
+    n_cells: usize,
+    n_dofs: usize,
+    estimate: f64,
+    n_marked: usize,
+}
+
+/// Final mesh of an adaptive loop with the history of its iterations
+pub struct AdaptiveResult {
+    mesh: Mesh,
+    iterations: Vec<AdaptiveIteration>,
+    stop: AdaptiveStop,
+}
+
+/// Adaptive mesh refinement loop solve → estimate → mark → refine
+///
+/// Every iteration builds the continuous Lagrange space of the current mesh, hands it to the solver
+/// of the problem, computes the cell indicators of the solution, and stops if their global
+/// estimate reaches the tolerance. Otherwise the marked cells are bisected with
+/// Refinement::bisect and the loop goes on unless the refined space would exceed the dof budget.
+/// By default the elements are linear, the marking is Dörfler's with θ = 0.5, there are at most 10
+/// iterations and neither a tolerance nor a dof budget.
+pub struct AdaptiveRefinement {
+    mesh: Mesh,
+    order: usize,
+    n_components: usize,
+    marking: Marking,
+    tolerance: f64,
+    max_dofs: usize,
+    max_iterations: usize,
+}
+
+impl AdaptiveIteration {
+    /// Number of cells of the mesh
+    pub fn n_cells(&self) -> usize {
+        self.n_cells
+    }
+
+    /// Number of dofs of the space
+    pub fn n_dofs(&self) -> usize {
+        self.n_dofs
+    }
+
+    /// Global estimate of the error
+    pub fn estimate(&self) -> f64 {
+        self.estimate
+    }
+
+    /// Number of cells marked for refinement, 0 on the last iteration
+    pub fn n_marked(&self) -> usize {
+        self.n_marked
+    }
+}
+
+impl AdaptiveResult {
+    /// Mesh of the last solve
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    /// Take the mesh of the last solve out of the result
+    pub fn into_mesh(self) -> Mesh {
+        self.mesh
+    }
+
+    /// Iterations of the loop
+    pub fn iterations(&self) -> &[AdaptiveIteration] {
+        &self.iterations
+    }
+
+    /// Reason the loop stopped
+    pub fn stop(&self) -> AdaptiveStop {
+        self.stop
+    }
+}
+
+impl AdaptiveRefinement {
+    /// Adaptive loop from an initial mesh with the default parameters
+    pub fn new(mesh: Mesh) -> Self {
+        AdaptiveRefinement {
+            mesh,
+            order: 1,
+            n_components: 1,
+            marking: Marking::Dorfler(0.5),
+            tolerance: 0.0,
+            max_dofs: usize::MAX,
+            max_iterations: 10,
+        }
+    }
+
+    /// Set the order of the Lagrange elements
+    pub fn with_order(mut self, order: usize) -> Self {
+        assert!(order > 0, "Continuous elements need a positive order");
+        self.order = order;
+        self
+    }
+
+    /// Solve for a vector field with the given number of components
+    pub fn with_components(mut self, n_components: usize) -> Self {
+        assert!(n_components > 0, "Solutions need components");
+        self.n_components = n_components;
+        self
+    }
+
+    /// Set the marking strategy
+    pub fn with_marking(mut self, marking: Marking) -> Self {
+        self.marking = marking;
+        self
+    }
+
+    /// Stop once the global estimate is below the tolerance
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        assert!(tolerance >= 0.0, "Tolerance should be non negative");
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Stop before the space exceeds a number of dofs
+    pub fn with_max_dofs(mut self, max_dofs: usize) -> Self {
+        self.max_dofs = max_dofs;
+        self
+    }
+
+    /// Set the maximal number of solves
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        assert!(max_iterations > 0, "Adaptive loops need an iteration");
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Run the loop with the solver of the problem on a space and the estimator of the cell
+    /// indicators of a solution, the observer receiving the iteration, the solution and its
+    /// indicators after every solve (for instance to write them out)
+    pub fn run<Solve, Estimate, Observer>(
+        self,
+        solve: Solve,
+        estimate: Estimate,
+        mut observer: Observer,
+    ) -> AdaptiveResult
+    where
+        Solve: for<'b> Fn(&'b FunctionSpace<'b>) -> Function<'b>,
+        Estimate: Fn(&Function) -> Vec<f64>,
+        Observer: FnMut(usize, &Function, &[f64]),
+    {
+        let AdaptiveRefinement {
+            mut mesh,
+            order,
+            n_components,
+            ..
+        } = self;
+        let _span = Span::enter("adaptive refinement");
+        let mut iterations = Vec::new();
+        let stop = loop {
+            let next = {
+                let space = lagrange_space(&mesh, order, n_components);
+                let solution = solve(&space);
+                let indicators = estimate(&solution);
+                assert!(
+                    indicators.len() == mesh.n_cells(),
+                    "The estimator should give one indicator per cell"
+                );
+                observer(iterations.len(), &solution, &indicators);
+                let mut iteration = AdaptiveIteration {
+                    n_cells: mesh.n_cells(),
+                    n_dofs: space.n_dofs(),
+                    estimate: global_estimate(&indicators),
+                    n_marked: 0,
+                };
+                info!(
+                    "Adaptive iteration {} on {} cells and {} dofs with estimate {:e}",
+                    iterations.len(),
+                    iteration.n_cells,
+                    iteration.n_dofs,
+                    iteration.estimate
+                );
+                let next = if iteration.estimate <= self.tolerance {
+                    Err(AdaptiveStop::Tolerance)
+                } else if iterations.len() + 1 == self.max_iterations {
+                    Err(AdaptiveStop::MaxIterations)
+                } else {
+                    let marked = match self.marking {
+                        Marking::Dorfler(theta) => mark_dorfler(&indicators, theta),
+                        Marking::FixedFraction(fraction) => {
+                            mark_fixed_fraction(&indicators, fraction)
+                        }
+                    };
+                    let refined = Refinement::bisect(&mesh, &marked).into_mesh();
+                    if lagrange_space(&refined, order, n_components).n_dofs() > self.max_dofs {
+                        Err(AdaptiveStop::DofBudget)
+                    } else {
+                        iteration.n_marked = marked.len();
+                        Ok(refined)
+                    }
+                };
+                iterations.push(iteration);
+                next
+            };
+            match next {
+                Ok(refined) => mesh = refined,
+                Err(stop) => break stop,
+            }
+        };
+        AdaptiveResult {
+            mesh,
+            iterations,
+            stop,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Continuous Lagrange space of an order with a number of components on a mesh
+fn lagrange_space(mesh: &Mesh, order: usize, n_components: usize) -> FunctionSpace<'_> {
+    let element = LagrangeElement::new(mesh.geometric_dim(), order);
+    if n_components == 1 {
+        FunctionSpace::new(mesh, element)
+    } else {
+        FunctionSpace::vector(mesh, element, n_components)
+    }
+}
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::estimator::ErrorEstimator;
+    use crate::workflows::advection_diffusion::{AdvectionDiffusion, Stabilization};
+    use crate::workflows::convergence_study::h1_seminorm_error;
+
+    // Solution with a steep front at the circle of radius 0.5 around the origin
+    fn front(x: &[f64]) -> f64 {
+        (20.0 * (0.5 - (x[0] * x[0] + x[1] * x[1]).sqrt())).atan()
+    }
+
+    // Gradient of the front
+    fn front_gradient(x: &[f64], g: &mut [f64]) {
+        let r = (x[0] * x[0] + x[1] * x[1]).sqrt();
+        let s = 20.0 * (0.5 - r);
+        let du = -20.0 / (1.0 + s * s);
+        g[0] = du * x[0] / r;
+        g[1] = du * x[1] / r;
+    }
+
+    // Source -Δu of the front
+    fn source(x: &[f64]) -> f64 {
+        let r = (x[0] * x[0] + x[1] * x[1]).sqrt();
+        let s = 20.0 * (0.5 - r);
+        let du = -20.0 / (1.0 + s * s);
+        let d2u = -800.0 * s / ((1.0 + s * s) * (1.0 + s * s));
+        -(d2u + du / r)
+    }
+
+    // Poisson problem of the front
+    fn solve<'b>(space: &'b FunctionSpace<'b>) -> Function<'b> {
+        (1..=4)
+            .fold(AdvectionDiffusion::new(space), |problem, tag| {
+                problem.with_dirichlet(tag, front)
+            })
+            .with_stabilization(Stabilization::Galerkin)
+            .with_source(source)
+            .solve()
+    }
+
+    #[test]
+    fn test_adaptive_refinement() {
+        let estimator = ErrorEstimator::new(1.0, source);
+        let mesh = CartesianGrid::new(vec![0.1, 0.1], vec![1.0, 1.0], vec![4, 4]).simplex_mesh();
+        let mut errors = Vec::new();
+        let result = AdaptiveRefinement::new(mesh)
+            .with_max_dofs(3000)
+            .with_max_iterations(30)
+            .run(
+                solve,
+                |u: &Function| estimator.estimate(u).to_vec(),
+                |_, u, _| errors.push(h1_seminorm_error(u, front_gradient)),
+            );
+        assert_eq!(result.stop(), AdaptiveStop::DofBudget, "Wrong stop");
+        let iterations = result.iterations();
+        assert_eq!(iterations.len(), errors.len(), "Observer not called");
+        let (first, last) = (&iterations[0], iterations.last().unwrap());
+        assert_eq!(last.n_cells(), result.mesh().n_cells(), "Wrong final mesh");
+        assert!(last.n_dofs() <= 3000, "Dof budget exceeded");
+        assert!(
+            last.estimate() < 0.2 * first.estimate(),
+            "Estimate did not decrease"
+        );
+        // The energy error decays like n_dofs^(-1/2) as for smooth solutions
+        let rate = (errors[0] / errors.last().unwrap()).ln()
+            / (last.n_dofs() as f64 / first.n_dofs() as f64).ln();
+        assert!(rate > 0.4, "Adaptive rate {} is not optimal", rate);
+        let result = AdaptiveRefinement::new(
+            CartesianGrid::new(vec![0.1, 0.1], vec![1.0, 1.0], vec![4, 4]).simplex_mesh(),
+        )
+        .with_tolerance(1e3)
+        .run(
+            solve,
+            |u: &Function| estimator.estimate(u).to_vec(),
+            |_, _, _| {},
+        );
+        assert_eq!(
+            result.stop(),
+            AdaptiveStop::Tolerance,
+            "Tolerance not reached"
+        );
+        assert_eq!(result.iterations().len(), 1, "Loop should stop at once");
+    }
+}
@@ -0,0 +1,474 @@
+use super::advection_diffusion::{AdvectionDiffusion, Stabilization};
+use super::elasticity::{IsotropicMaterial, LinearElasticity};
+use super::quantities::{DomainIntegral, Functional, PointValue, QuantityOfInterest};
+use super::stokes::StokesFlow;
+use crate::core::arrays::data_hold::DataHold;
+use crate::discretizations::cartesian_grid::CartesianGrid;
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+use crate::discretizations::mesh::Mesh;
+use std::f64::consts::PI;
+
+//--------------------------------------------------------------------------------------------------
+// # Traits
+//--------------------------------------------------------------------------------------------------
+
+/// Standard problem with a mesh, boundary conditions and reference values of quantities of its
+/// solution, used as a regression target
+///
+/// Every benchmark builds the configured workflow of its problem on a space of its mesh (or of a
+/// refinement of it, the tags being kept) and its quantities are evaluated on the solution of that
+/// workflow.
+pub trait Benchmark {
+    /// Name of the benchmark
+    fn name(&self) -> &str;
+
+    /// Mesh of the domain with the tags of the boundary conditions
+    fn mesh(&self) -> &Mesh;
+
+    /// Quantities of interest of the solution
+    fn quantities(&self) -> Vec<Box<dyn QuantityOfInterest>>;
+
+    /// Reference values of the quantities, in the same order
+    fn references(&self) -> Vec<f64>;
+
+    /// Relative errors of the quantities of a solution with respect to the references
+    fn errors(&self, solution: &Function) -> Vec<f64> {
+        self.quantities()
+            .iter()
+            .zip(self.references())
+            .map(|(quantity, reference)| {
+                (quantity.evaluate(solution) - reference).abs() / reference.abs()
+            })
+            .collect()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Laplace problem on the L-shaped domain (-1, 1)² \ [0, 1) × (-1, 0] with the singular solution
+/// r^(2/3) sin(2 θ / 3) imposed on the whole boundary, tagged 1
+///
+/// The gradient of the solution is singular at the reentrant corner, so uniform refinements
+/// converge at the rate 2/3 in the H1 seminorm whatever the order, which makes it the standard
+/// target of adaptive refinements. The quantity is the value at (-1/2, 1/2), 2^(-1/3).
+pub struct LShapePoisson {
+    mesh: Mesh,
+}
+
+/// Cook's membrane: a tapered panel of vertices (0, 0), (48, 44), (48, 60) and (0, 44) clamped on
+/// its left side, tagged 1, and loaded by a unit total shear force on its right side, tagged 2
+///
+/// The material is the plane strain equivalent of the plane stress panel of Young modulus 1 and
+/// Poisson ratio 1/3, whose reference vertical displacement at the middle (48, 52) of the loaded
+/// side is 23.96. The combined bending and shear make low order elements converge slowly to it.
+pub struct CooksMembrane {
+    mesh: Mesh,
+}
+
+/// Stokes flow in the unit square driven by the lid y = 1, tagged 4, moving at a unit velocity
+/// along x, the other sides (tagged 1 to 3) being walls
+///
+/// The quantity is the height of the center of the primary vortex, where the horizontal velocity
+/// vanishes on the vertical center line, 0.762 for creeping flow.
+pub struct LidDrivenCavity {
+    mesh: Mesh,
+}
+
+/// Straight fin of a unit length with a unit base temperature excess at x = 0, tagged 1, losing
+/// heat along its length and insulated at its tip, tagged 2
+///
+/// The one dimensional fin equation -θ'' + m² θ = 0 is solved for the temperature excess θ with
+/// the fin parameter m = √(h P / (k A)). Its solution is cosh(m (1 - x)) / cosh(m), so the
+/// quantities are the tip temperature 1 / cosh(m) and the heat rate m tanh(m) through the base,
+/// computed as the heat lost along the fin.
+pub struct ThermalFin {
+    mesh: Mesh,
+    fin_parameter: f64,
+}
+
+impl LShapePoisson {
+    /// Benchmark on a mesh of n × n squares split into triangles per quadrant
+    pub fn new(n: usize) -> Self {
+        assert!(n > 0, "Meshes need cells");
+        let square =
+            CartesianGrid::new(vec![-1.0, -1.0], vec![1.0, 1.0], vec![2 * n, 2 * n]).simplex_mesh();
+        let mut mesh = restricted_mesh(&square, |x| x[0] < 0.0 || x[1] > 0.0);
+        mesh.tag_boundary(1, |_| true);
+        LShapePoisson { mesh }
+    }
+
+    /// Exact solution at a point
+    pub fn exact(x: &[f64]) -> f64 {
+        let (r, theta) = polar(x);
+        r.powf(2.0 / 3.0) * (2.0 * theta / 3.0).sin()
+    }
+
+    /// Gradient of the exact solution at a point other than the corner
+    pub fn exact_gradient(x: &[f64], gradient: &mut [f64]) {
+        let (r, theta) = polar(x);
+        let (dr, dtheta) = (
+            2.0 / 3.0 * r.powf(-1.0 / 3.0) * (2.0 * theta / 3.0).sin(),
+            2.0 / 3.0 * r.powf(-1.0 / 3.0) * (2.0 * theta / 3.0).cos(),
+        );
+        gradient[0] = dr * theta.cos() - dtheta * theta.sin();
+        gradient[1] = dr * theta.sin() + dtheta * theta.cos();
+    }
+
+    /// Laplace problem on a scalar space with the exact solution imposed on the boundary
+    pub fn problem<'b>(&self, space: &'b FunctionSpace<'b>) -> AdvectionDiffusion<'b> {
+        AdvectionDiffusion::new(space)
+            .with_stabilization(Stabilization::Galerkin)
+            .with_dirichlet(1, LShapePoisson::exact)
+    }
+}
+
+impl CooksMembrane {
+    /// Benchmark on a mesh of n × n quadrilaterals split into triangles
+    pub fn new(n: usize) -> Self {
+        assert!(n > 0, "Meshes need cells");
+        let square = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![n, n]).simplex_mesh();
+        let mesh = mapped_mesh(&square, |xi, x| {
+            x[0] = 48.0 * xi[0];
+            x[1] = 44.0 * xi[0] + xi[1] * (44.0 - 28.0 * xi[0]);
+        });
+        CooksMembrane { mesh }
+    }
+
+    /// Plane strain material equivalent to the plane stress one of the panel
+    pub fn material() -> IsotropicMaterial {
+        let (e, nu) = (1.0, 1.0 / 3.0);
+        IsotropicMaterial::new(
+            e * (1.0 + 2.0 * nu) / ((1.0 + nu) * (1.0 + nu)),
+            nu / (1.0 + nu),
+        )
+    }
+
+    /// Elasticity problem on a vector space with the clamped and loaded sides
+    pub fn problem<'b>(&self, space: &'b FunctionSpace<'b>) -> LinearElasticity<'b> {
+        LinearElasticity::new(space, CooksMembrane::material())
+            .with_displacement(1, |_, u| u.iter_mut().for_each(|u| *u = 0.0))
+            .with_traction(2, |_, t| {
+                t[0] = 0.0;
+                t[1] = 1.0 / 16.0;
+            })
+    }
+}
+
+impl LidDrivenCavity {
+    /// Benchmark on a mesh of n × n squares split into triangles
+    pub fn new(n: usize) -> Self {
+        assert!(n > 0, "Meshes need cells");
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![n, n]).simplex_mesh();
+        LidDrivenCavity { mesh }
+    }
+
+    /// Stokes problem on velocity and pressure spaces with the moving lid and the walls
+    pub fn problem<'b>(
+        &self,
+        velocity: &'b FunctionSpace<'b>,
+        pressure: &'b FunctionSpace<'b>,
+    ) -> StokesFlow<'b> {
+        (1..=3)
+            .fold(StokesFlow::new(velocity, pressure), |flow, tag| {
+                flow.with_velocity(tag, |_, u| u.iter_mut().for_each(|u| *u = 0.0))
+            })
+            .with_velocity(4, |_, u| {
+                u[0] = 1.0;
+                u[1] = 0.0;
+            })
+    }
+
+    /// Height of the center of the primary vortex, NaN if the horizontal velocity does not change
+    /// sign on the upper half of the center line
+    pub fn vortex_height(velocity: &Function) -> f64 {
+        let n = 200;
+        let samples: Vec<(f64, f64)> = (0..=n)
+            .map(|k| {
+                let y = 1.0 - 0.5 * k as f64 / n as f64;
+                (y, velocity.eval(&[0.5, y]).map_or(f64::NAN, |u| u[0]))
+            })
+            .collect();
+        samples
+            .windows(2)
+            .find(|pair| pair[0].1 > 0.0 && pair[1].1 <= 0.0)
+            .map_or(f64::NAN, |pair| {
+                let ((y0, u0), (y1, u1)) = (pair[0], pair[1]);
+                y0 + (y1 - y0) * u0 / (u0 - u1)
+            })
+    }
+}
+
+impl ThermalFin {
+    /// Benchmark with a fin parameter on a mesh of n segments
+    pub fn new(fin_parameter: f64, n: usize) -> Self {
+        assert!(fin_parameter > 0.0, "Fin parameter should be positive");
+        assert!(n > 0, "Meshes need cells");
+        let mesh = CartesianGrid::new(vec![0.0], vec![1.0], vec![n]).simplex_mesh();
+        ThermalFin {
+            mesh,
+            fin_parameter,
+        }
+    }
+
+    /// Fin parameter m
+    pub fn fin_parameter(&self) -> f64 {
+        self.fin_parameter
+    }
+
+    /// Exact temperature excess at a point
+    pub fn exact(&self, x: &[f64]) -> f64 {
+        let m = self.fin_parameter;
+        (m * (1.0 - x[0])).cosh() / m.cosh()
+    }
+
+    /// Fin efficiency tanh(m) / m, the ratio of the heat rate to the one of a fin at the base
+    /// temperature
+    pub fn efficiency(&self) -> f64 {
+        self.fin_parameter.tanh() / self.fin_parameter
+    }
+
+    /// Fin problem on a scalar space with the base temperature imposed
+    pub fn problem<'b>(&self, space: &'b FunctionSpace<'b>) -> AdvectionDiffusion<'b> {
+        AdvectionDiffusion::new(space)
+            .with_stabilization(Stabilization::Galerkin)
+            .with_reaction(self.fin_parameter * self.fin_parameter)
+            .with_dirichlet(1, |_| 1.0)
+    }
+}
+
+impl Benchmark for LShapePoisson {
+    fn name(&self) -> &str {
+        "L-shape Poisson"
+    }
+
+    fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    fn quantities(&self) -> Vec<Box<dyn QuantityOfInterest>> {
+        vec![Box::new(PointValue::new("value", &[-0.5, 0.5], 0))]
+    }
+
+    fn references(&self) -> Vec<f64> {
+        vec![LShapePoisson::exact(&[-0.5, 0.5])]
+    }
+}
+
+impl Benchmark for CooksMembrane {
+    fn name(&self) -> &str {
+        "Cook's membrane"
+    }
+
+    fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    fn quantities(&self) -> Vec<Box<dyn QuantityOfInterest>> {
+        vec![Box::new(PointValue::new(
+            "tip displacement",
+            &[48.0, 52.0],
+            1,
+        ))]
+    }
+
+    fn references(&self) -> Vec<f64> {
+        vec![23.96]
+    }
+}
+
+impl Benchmark for LidDrivenCavity {
+    fn name(&self) -> &str {
+        "lid-driven cavity"
+    }
+
+    fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    fn quantities(&self) -> Vec<Box<dyn QuantityOfInterest>> {
+        vec![Box::new(Functional::new(
+            "vortex height",
+            LidDrivenCavity::vortex_height,
+        ))]
+    }
+
+    fn references(&self) -> Vec<f64> {
+        vec![0.762]
+    }
+}
+
+impl Benchmark for ThermalFin {
+    fn name(&self) -> &str {
+        "thermal fin"
+    }
+
+    fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    fn quantities(&self) -> Vec<Box<dyn QuantityOfInterest>> {
+        let m2 = self.fin_parameter * self.fin_parameter;
+        vec![
+            Box::new(PointValue::new("tip temperature", &[1.0], 0)),
+            Box::new(DomainIntegral::new("heat rate", move |_, u| m2 * u[0])),
+        ]
+    }
+
+    fn references(&self) -> Vec<f64> {
+        let m = self.fin_parameter;
+        vec![1.0 / m.cosh(), m * m.tanh()]
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Radius and angle in [0, 2π) of a point of the plane
+fn polar(x: &[f64]) -> (f64, f64) {
+    let theta = x[1].atan2(x[0]);
+    (
+        x[0].hypot(x[1]),
+        if theta < 0.0 { theta + 2.0 * PI } else { theta },
+    )
+}
+
+// Mesh of the cells whose centroid satisfies the predicate, keeping the tags of the facets
+fn restricted_mesh<Predicate>(mesh: &Mesh, predicate: Predicate) -> Mesh
+where
+    Predicate: Fn(&[f64]) -> bool,
+{
+    let dim = mesh.geometric_dim();
+    let mut numbering = vec![usize::MAX; mesh.n_vertices()];
+    let mut coordinates = Vec::new();
+    let mut cells = Vec::new();
+    for cell in 0..mesh.n_cells() {
+        let vertices = mesh.cell(cell);
+        let mut centroid = vec![0.0; dim];
+        for v in vertices {
+            centroid
+                .iter_mut()
+                .zip(mesh.vertex(*v))
+                .for_each(|(c, x)| *c += x / vertices.len() as f64);
+        }
+        if !predicate(&centroid) {
+            continue;
+        }
+        for v in vertices {
+            if numbering[*v] == usize::MAX {
+                numbering[*v] = coordinates.len() / dim;
+                coordinates.extend_from_slice(mesh.vertex(*v));
+            }
+            cells.push(numbering[*v]);
+        }
+    }
+    let n_vertices = coordinates.len() / dim;
+    let n_cells = cells.len() / mesh.vertices_per_cell();
+    let mut restricted = Mesh::new(
+        DataHold::new(coordinates, [n_vertices, dim]),
+        DataHold::new(cells, [n_cells, mesh.vertices_per_cell()]),
+    );
+    for (facet, tag) in mesh.facet_tags() {
+        if facet.iter().all(|v| numbering[*v] != usize::MAX) {
+            let mut vertices: Vec<usize> = facet.iter().map(|v| numbering[*v]).collect();
+            vertices.sort_unstable();
+            restricted.tag_facet(&vertices, tag);
+        }
+    }
+    restricted
+}
+
+// Mesh of the vertices moved by a map of their coordinates, keeping the connectivity and the tags
+// of the facets
+fn mapped_mesh<Map>(mesh: &Mesh, map: Map) -> Mesh
+where
+    Map: Fn(&[f64], &mut [f64]),
+{
+    let dim = mesh.geometric_dim();
+    let mut coordinates = vec![0.0; mesh.n_vertices() * dim];
+    for (vertex, x) in coordinates.chunks_exact_mut(dim).enumerate() {
+        map(mesh.vertex(vertex), x);
+    }
+    let mut mapped = Mesh::new(
+        DataHold::new(coordinates, [mesh.n_vertices(), dim]),
+        DataHold::new(
+            mesh.cells().iter().copied().collect(),
+            [mesh.n_cells(), mesh.vertices_per_cell()],
+        ),
+    );
+    for (facet, tag) in mesh.facet_tags() {
+        mapped.tag_facet(facet, tag);
+    }
+    mapped
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::workflows::convergence_study::{ConvergenceStudy, ErrorNorm, Sweep};
+
+    #[test]
+    fn test_scalar_benchmarks() {
+        let l_shape = LShapePoisson::new(2);
+        let report = ConvergenceStudy::new(l_shape.mesh(), LShapePoisson::exact)
+            .with_gradient(LShapePoisson::exact_gradient)
+            .with_sweep(Sweep::Refinements {
+                levels: 4,
+                order: 2,
+            })
+            .run(|space| l_shape.problem(space).solve());
+        let rate = *report.rates(ErrorNorm::H1Seminorm).unwrap().last().unwrap();
+        assert!(
+            (rate - 2.0 / 3.0).abs() < 0.15,
+            "Wrong H1 rate {} at the reentrant corner",
+            rate
+        );
+        let space = FunctionSpace::new(l_shape.mesh(), LagrangeElement::new(2, 2));
+        let errors = l_shape.errors(&l_shape.problem(&space).solve());
+        assert!(errors[0] < 1e-2, "Wrong L-shape point value");
+
+        let fin = ThermalFin::new(2.0, 16);
+        let space = FunctionSpace::new(fin.mesh(), LagrangeElement::new(1, 2));
+        let errors = fin.errors(&fin.problem(&space).solve());
+        assert!(
+            errors.iter().all(|e| *e < 1e-4),
+            "Wrong fin quantities, relative errors {:?}",
+            errors
+        );
+        assert!(
+            (fin.efficiency() * 4.0 - fin.references()[1]).abs() < 1e-12,
+            "Wrong fin efficiency"
+        );
+    }
+
+    #[test]
+    fn test_mechanics_benchmarks() {
+        let cook = CooksMembrane::new(16);
+        let space = FunctionSpace::vector(cook.mesh(), LagrangeElement::new(2, 2), 2);
+        let errors = cook.errors(&cook.problem(&space).solve());
+        assert!(
+            errors[0] < 0.01,
+            "Wrong Cook's membrane tip displacement, relative error {}",
+            errors[0]
+        );
+
+        let cavity = LidDrivenCavity::new(16);
+        let velocity = FunctionSpace::vector(cavity.mesh(), LagrangeElement::new(2, 2), 2);
+        let pressure = FunctionSpace::new(cavity.mesh(), LagrangeElement::new(2, 1));
+        let (u, _) = cavity.problem(&velocity, &pressure).solve();
+        let errors = cavity.errors(&u);
+        assert!(
+            errors[0] < 0.01,
+            "Wrong vortex height {}",
+            LidDrivenCavity::vortex_height(&u)
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use super::elasticity::LinearElasticity;
+use crate::core::logging::{info, Span};
+use crate::discretizations::function::Function;
+#[cfg(hosted)]
+use crate::discretizations::io::vtk;
+use crate::solvers::eigen::Arnoldi;
+use std::fmt::Write;
+#[cfg(hosted)]
+use std::io::Result;
+#[cfg(hosted)]
+use std::path::Path;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Linear buckling analysis (K + λ K_G) φ = 0 of an elastic body under the loads of an
+/// elasticity problem
+///
+/// The static problem is solved for the prestress, whose geometric stiffness K_G gives the
+/// generalized eigenproblem K φ = λ (-K_G) φ handed to the shift-invert Arnoldi solver. The
+/// critical load factors λ are the positive eigenvalues, the loads scaled by λ buckling the body
+/// into the mode φ, and negative eigenvalues of the reversed loads are left out. The load factors
+/// closest to the shift are searched, 0 by default giving the lowest ones. Imposed displacements
+/// are scaled with the loads. Mode shapes are normalized to a largest component of 1.
+pub struct LinearBuckling<'a> {
+    elasticity: LinearElasticity<'a>,
+    n_modes: usize,
+    shift: f64,
+    tolerance: f64,
+}
+
+/// Critical load factors with their buckling modes sorted increasingly and the prestress
+pub struct BucklingModes<'a> {
+    prestress: Function<'a>,
+    load_factors: Vec<f64>,
+    shapes: Vec<Function<'a>>,
+    converged: bool,
+}
+
+impl<'a> LinearBuckling<'a> {
+    /// Analysis of the given number of buckling modes of an elasticity problem
+    pub fn new(elasticity: LinearElasticity<'a>, n_modes: usize) -> Self {
+        assert!(n_modes > 0, "At least one mode should be wanted");
+        LinearBuckling {
+            elasticity,
+            n_modes,
+            shift: 0.0,
+            tolerance: 1e-10,
+        }
+    }
+
+    /// Set the shift the load factors of the modes are closest to
+    pub fn with_shift(mut self, shift: f64) -> Self {
+        self.shift = shift;
+        self
+    }
+
+    /// Set the tolerance of the eigensolver on the residuals relative to the eigenvalues
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Solve for the prestress and the buckling modes
+    pub fn solve(&self) -> BucklingModes<'a> {
+        let _span = Span::enter("linear buckling");
+        let space = self.elasticity.space();
+        let prestress = self.elasticity.solve();
+        let (stiffness, _, constraints) = self.elasticity.assemble();
+        let mut geometric = self.elasticity.geometric_stiffness(&prestress);
+        geometric.values_mut().iter_mut().for_each(|v| *v = -*v);
+        // Twice the wanted modes since the eigenvalues of the reversed loads are as close
+        let pairs = Arnoldi::new(2 * self.n_modes)
+            .with_shift(self.shift)
+            .with_tolerance(self.tolerance)
+            .solve(&stiffness, Some(&geometric));
+        let mut modes: Vec<(f64, Function<'a>)> = (0..pairs.len())
+            .filter(|k| {
+                let (real, imaginary) = pairs.values()[*k];
+                real > 0.0 && imaginary.abs() <= 1e-8 * real
+            })
+            .map(|k| {
+                let mut shape = pairs.vector(k).0.to_vec();
+                constraints.distribute_homogeneous(&mut shape);
+                let largest = shape
+                    .iter()
+                    .fold(0.0, |a: f64, v| if v.abs() > a.abs() { *v } else { a });
+                shape.iter_mut().for_each(|v| *v /= largest);
+                (pairs.values()[k].0, Function::from_values(space, shape))
+            })
+            .collect();
+        modes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        modes.truncate(self.n_modes);
+        let (load_factors, shapes): (Vec<f64>, Vec<Function<'a>>) = modes.into_iter().unzip();
+        info!(
+            "Linear buckling found {} modes, the critical load factor being {:e}",
+            load_factors.len(),
+            load_factors.first().copied().unwrap_or(f64::INFINITY)
+        );
+        BucklingModes {
+            prestress,
+            converged: pairs.converged() && load_factors.len() == self.n_modes,
+            load_factors,
+            shapes,
+        }
+    }
+}
+
+impl<'a> BucklingModes<'a> {
+    /// Number of modes
+    pub fn len(&self) -> usize {
+        self.load_factors.len()
+    }
+
+    /// Whether no positive load factor was found
+    pub fn is_empty(&self) -> bool {
+        self.load_factors.is_empty()
+    }
+
+    /// Displacement of the static problem giving the prestress
+    pub fn prestress(&self) -> &Function<'a> {
+        &self.prestress
+    }
+
+    /// Load factors of the modes
+    pub fn load_factors(&self) -> &[f64] {
+        &self.load_factors
+    }
+
+    /// Smallest load factor, infinite when none was found
+    pub fn critical_load_factor(&self) -> f64 {
+        self.load_factors.first().copied().unwrap_or(f64::INFINITY)
+    }
+
+    /// Shape of a mode
+    pub fn shape(&self, mode: usize) -> &Function<'a> {
+        &self.shapes[mode]
+    }
+
+    /// Whether the eigensolver met its tolerance and found all the wanted modes
+    pub fn converged(&self) -> bool {
+        self.converged
+    }
+
+    /// Comma separated table of the modes with their load factors
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("mode,load_factor\n");
+        for (mode, factor) in self.load_factors.iter().enumerate() {
+            writeln!(csv, "{},{}", mode, factor).unwrap();
+        }
+        csv
+    }
+
+    /// Write the prestress displacement and the mode shapes to a legacy VTK file as the fields
+    /// prestress, mode_0, mode_1...
+    #[cfg(hosted)]
+    pub fn save_vtk<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let names: Vec<String> = (0..self.len()).map(|k| format!("mode_{}", k)).collect();
+        let mut functions: Vec<(&str, &Function)> = vec![("prestress", &self.prestress)];
+        functions.extend(
+            names
+                .iter()
+                .zip(&self.shapes)
+                .map(|(name, shape)| (name.as_str(), shape)),
+        );
+        vtk::save_vtk(path, self.prestress.space().mesh(), &functions)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::workflows::elasticity::IsotropicMaterial;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_linear_buckling() {
+        // Clamped column of width 0.05 and height 1 under a unit compression on its top, which
+        // buckles at the Euler load π² E I / 4 L²
+        let width = 0.05;
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![width, 1.0], vec![2, 40]).simplex_mesh();
+        let space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        let elasticity = LinearElasticity::new(&space, IsotropicMaterial::new(1.0, 0.0))
+            .with_displacement(3, |_, u| u.fill(0.0))
+            .with_traction(4, |_, t| {
+                t[0] = 0.0;
+                t[1] = -1.0;
+            });
+        let modes = LinearBuckling::new(elasticity, 2).solve();
+        assert!(modes.converged(), "Buckling modes were not found");
+        let euler = PI * PI * width.powi(3) / 12.0 / 4.0 / width;
+        assert!(
+            (modes.critical_load_factor() - euler).abs() < 2e-2 * euler,
+            "Wrong critical load factor {}",
+            modes.critical_load_factor()
+        );
+        // Second mode of the cantilever at 9 times the first one
+        let ratio = modes.load_factors()[1] / modes.load_factors()[0];
+        assert!((ratio - 9.0).abs() < 0.3, "Wrong second mode {}", ratio);
+        // The first mode sways the top sideways
+        let top = modes.shape(0).eval(&[0.5 * width, 1.0]).unwrap();
+        assert!(
+            top[0].abs() > 0.9 && top[1].abs() < 0.1,
+            "First mode should sway the top"
+        );
+    }
+}
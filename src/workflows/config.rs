@@ -0,0 +1,842 @@
+use super::advection_diffusion::{AdvectionDiffusion, Stabilization};
+use super::elasticity::{IsotropicMaterial, LinearElasticity};
+use super::heat::HeatEquation;
+use super::stokes::{StokesFlow, StokesSolver};
+use crate::core::expression::Expression;
+use crate::core::logging::Span;
+use crate::core::timers::Timers;
+#[cfg(feature = "serde")]
+use crate::core::types::{Dimension, Quantity};
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+use crate::discretizations::graded_grid::GradedGrid;
+use crate::discretizations::io::exodus::load_exodus;
+use crate::discretizations::io::med::load_med;
+use crate::discretizations::io::vtk;
+use crate::discretizations::mesh::Mesh;
+use crate::discretizations::refinement::Refinement;
+use crate::solvers::config::SolverConfig;
+use crate::solvers::time_integration::TimeScheme;
+use crate::spaces::lagrange::LagrangeElement;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+//--------------------------------------------------------------------------------------------------
+// # Enums
+//--------------------------------------------------------------------------------------------------
+
+/// Mesh of a simulation
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum MeshConfig {
+    /// Exodus II (.exo, .e) or MED (.med) file whose side sets or facet families give the tags
+    File(PathBuf),
+    /// Box split into simplices whose lower and upper sides along direction k are tagged 2 k + 1
+    /// and 2 k + 2 (see CartesianGrid::simplex_mesh), possibly graded, whose lengths may be given
+    /// with units
+    Box {
+        /// Lower corner
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "lengths"))]
+        lower: Vec<f64>,
+        /// Upper corner
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "lengths"))]
+        upper: Vec<f64>,
+        /// Number of cells along every direction
+        cells: Vec<usize>,
+        /// Gradings of the cells applied in order, uniform cells by default
+        #[cfg_attr(feature = "serde", serde(default))]
+        grading: Vec<GradingConfig>,
+    },
+}
+
+/// Grading of the cells of a box along one of its directions (see GradedGrid)
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum GradingConfig {
+    /// Cells growing geometrically from the lower to the upper side of a direction
+    Geometric {
+        /// Direction of the grading
+        direction: usize,
+        /// Ratio of the sizes of consecutive cells
+        ratio: f64,
+    },
+    /// Cells stretched into a boundary layer toward the side carrying a tag
+    BoundaryLayer {
+        /// Tag of the side, 2 k + 1 or 2 k + 2 for the sides of direction k
+        tag: usize,
+        /// Height of the cells on the side
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "length"))]
+        first_height: f64,
+        /// Ratio of the heights of consecutive cells in the layer
+        growth: f64,
+    },
+}
+
+/// Problem of a simulation, solved by the workflow of the same name with constant coefficients
+///
+/// Scalar sources, initial and boundary values are numbers or expressions of the coordinates x, y,
+/// z and the time t, such as "sin(pi*x)*exp(-t)", deserialized from their source.
+/// Coefficients and times are numbers in SI units or quantities with units, such as "210 GPa" or
+/// "30 min", whose dimensions are checked when deserializing (see Quantity).
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ProblemConfig {
+    /// Transient heat equation from an initial temperature
+    Heat {
+        /// Heat capacity
+        #[cfg_attr(
+            feature = "serde",
+            serde(deserialize_with = "volumetric_heat_capacity")
+        )]
+        capacity: f64,
+        /// Thermal conductivity
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "thermal_conductivity"))]
+        conductivity: f64,
+        /// Heat source, none by default
+        source: Option<Expression>,
+        /// Initial temperature
+        initial: Expression,
+        /// Temperatures imposed on tagged facets
+        #[cfg_attr(feature = "serde", serde(default))]
+        dirichlet: Vec<TaggedScalar>,
+        /// Start time
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "time"))]
+        start: f64,
+        /// End time
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "time"))]
+        end: f64,
+        /// Time step
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "time"))]
+        time_step: f64,
+        /// Theta scheme, Crank-Nicolson by default
+        scheme: Option<TimeScheme>,
+        /// Number of steps between outputs, 1 by default
+        output_every: Option<usize>,
+        /// Whether the mesh is the (r, z) half plane of a body of revolution, false by default
+        #[cfg_attr(feature = "serde", serde(default))]
+        axisymmetric: bool,
+    },
+    /// Static linear elasticity of an isotropic material
+    Elasticity {
+        /// Young modulus
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "pressure"))]
+        young_modulus: f64,
+        /// Poisson ratio
+        poisson_ratio: f64,
+        /// Uniform body force, none by default
+        body_force: Option<Vec<f64>>,
+        /// Tractions applied on tagged facets
+        #[cfg_attr(feature = "serde", serde(default))]
+        tractions: Vec<TaggedVector>,
+        /// Displacements imposed on tagged facets
+        #[cfg_attr(feature = "serde", serde(default))]
+        displacements: Vec<TaggedVector>,
+        /// Displacement components imposed on tagged facets
+        #[cfg_attr(feature = "serde", serde(default))]
+        component_displacements: Vec<TaggedComponent>,
+        /// Solver of the system, the sparse Cholesky by default
+        solver: Option<SolverConfig>,
+        /// Whether the mesh is the (r, z) half plane of a body of revolution, false by default
+        #[cfg_attr(feature = "serde", serde(default))]
+        axisymmetric: bool,
+    },
+    /// Steady Stokes flow, on Taylor-Hood elements of the order of the simulation for the pressure
+    /// unless stabilized where both fields have the order of the simulation
+    Stokes {
+        /// Kinematic viscosity
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "diffusivity"))]
+        viscosity: f64,
+        /// Pressure stabilization of equal order elements, 0 by default
+        #[cfg_attr(feature = "serde", serde(default))]
+        stabilization: f64,
+        /// Uniform body force, none by default
+        body_force: Option<Vec<f64>>,
+        /// Velocities imposed on tagged facets
+        #[cfg_attr(feature = "serde", serde(default))]
+        velocities: Vec<TaggedVector>,
+        /// Whether to solve by the sparse LU instead of the field split
+        #[cfg_attr(feature = "serde", serde(default))]
+        direct: bool,
+    },
+    /// Steady advection diffusion reaction with a uniform velocity
+    AdvectionDiffusion {
+        /// Diffusivity
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "diffusivity"))]
+        diffusivity: f64,
+        /// Reaction coefficient, 0 by default
+        #[cfg_attr(feature = "serde", serde(default, deserialize_with = "rate"))]
+        reaction: f64,
+        /// Advection velocity
+        velocity: Vec<f64>,
+        /// Source, none by default
+        source: Option<Expression>,
+        /// Values imposed on tagged facets
+        #[cfg_attr(feature = "serde", serde(default))]
+        dirichlet: Vec<TaggedScalar>,
+        /// Stabilization, SUPG by default
+        stabilization: Option<Stabilization>,
+        /// Solver of the system, the sparse LU by default
+        solver: Option<SolverConfig>,
+    },
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Scalar value, possibly an expression of the coordinates and time, on the boundary facets
+/// carrying a tag
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct TaggedScalar {
+    tag: usize,
+    value: Expression,
+}
+
+/// Vector value on the boundary facets carrying a tag
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct TaggedVector {
+    tag: usize,
+    value: Vec<f64>,
+}
+
+/// Value of one component on the boundary facets carrying a tag
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct TaggedComponent {
+    tag: usize,
+    component: usize,
+    value: f64,
+}
+
+/// Declarative description of a whole simulation: a mesh, the order of the Lagrange elements, a
+/// problem with its boundary conditions and solver, an optional VTK output and an optional report
+/// of the time spent in the phases of the run
+///
+/// With the serde feature it deserializes from any serde format, such as TOML read with the toml
+/// crate or YAML with serde_yaml, so that parameter variations need no code. For instance in TOML
+///
+/// ```toml
+/// order = 1
+/// output = "beam.vtk"
+/// timings = "beam_timings.json"
+/// [mesh.box]
+/// lower = [0.0, 0.0]
+/// upper = [10.0, 1.0]
+/// cells = [40, 4]
+/// grading = [{ boundary_layer = { tag = 3, first_height = 0.01, growth = 1.2 } }]
+/// [problem.elasticity]
+/// young_modulus = "210 GPa"
+/// poisson_ratio = 0.3
+/// displacements = [{ tag = 1, value = [0.0, 0.0] }]
+/// tractions = [{ tag = 2, value = [0.0, -1e6] }]
+/// ```
+///
+/// By default the order is 1, the mesh is not refined and there is neither output nor timings.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct SimulationConfig {
+    mesh: MeshConfig,
+    #[cfg_attr(feature = "serde", serde(default))]
+    refinements: usize,
+    #[cfg_attr(feature = "serde", serde(default = "linear"))]
+    order: usize,
+    problem: ProblemConfig,
+    output: Option<PathBuf>,
+    timings: Option<PathBuf>,
+}
+
+impl TaggedScalar {
+    /// Value on a tag, a number or an expression
+    pub fn new<Value: Into<Expression>>(tag: usize, value: Value) -> Self {
+        TaggedScalar {
+            tag,
+            value: value.into(),
+        }
+    }
+}
+
+impl TaggedVector {
+    /// Vector on a tag
+    pub fn new(tag: usize, value: Vec<f64>) -> Self {
+        TaggedVector { tag, value }
+    }
+}
+
+impl TaggedComponent {
+    /// Value of a component on a tag
+    pub fn new(tag: usize, component: usize, value: f64) -> Self {
+        TaggedComponent {
+            tag,
+            component,
+            value,
+        }
+    }
+}
+
+impl SimulationConfig {
+    /// Simulation of a problem on a mesh with the default parameters
+    pub fn new(mesh: MeshConfig, problem: ProblemConfig) -> Self {
+        SimulationConfig {
+            mesh,
+            refinements: 0,
+            order: 1,
+            problem,
+            output: None,
+            timings: None,
+        }
+    }
+
+    /// Set the number of uniform refinements of the mesh
+    pub fn with_refinements(mut self, refinements: usize) -> Self {
+        self.refinements = refinements;
+        self
+    }
+
+    /// Set the order of the Lagrange elements
+    pub fn with_order(mut self, order: usize) -> Self {
+        assert!(order > 0, "Continuous elements need a positive order");
+        self.order = order;
+        self
+    }
+
+    /// Set the VTK output path, the prefix of the time series of transient problems
+    pub fn with_output<P: AsRef<Path>>(mut self, output: P) -> Self {
+        self.output = Some(output.as_ref().to_path_buf());
+        self
+    }
+
+    /// Write the timers of the phases of the run once it ends, as JSON if the path has the json
+    /// extension and as a table otherwise
+    pub fn with_timings<P: AsRef<Path>>(mut self, timings: P) -> Self {
+        self.timings = Some(timings.as_ref().to_path_buf());
+        self
+    }
+
+    /// Problem of the simulation
+    pub fn problem(&self) -> &ProblemConfig {
+        &self.problem
+    }
+
+    /// Read or build the mesh and refine it
+    pub fn load_mesh(&self) -> Result<Mesh> {
+        let mut mesh = match &self.mesh {
+            MeshConfig::File(path) => match path.extension().and_then(|e| e.to_str()) {
+                Some("exo" | "e" | "ex2") => load_exodus(path)?.0,
+                Some("med") => load_med(path)?.0,
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("Unknown mesh format of {}", path.display()),
+                    ))
+                }
+            },
+            MeshConfig::Box {
+                lower,
+                upper,
+                cells,
+                grading,
+            } => {
+                let mut grid = GradedGrid::new(lower.clone(), upper.clone(), cells.clone());
+                for grading in grading {
+                    grid = match *grading {
+                        GradingConfig::Geometric { direction, ratio } => {
+                            grid.with_geometric_grading(direction, ratio)
+                        }
+                        GradingConfig::BoundaryLayer {
+                            tag,
+                            first_height,
+                            growth,
+                        } => grid.with_boundary_layer(tag, first_height, growth),
+                    };
+                }
+                grid.simplex_mesh()
+            }
+        };
+        for _ in 0..self.refinements {
+            mesh = Refinement::uniform(&mesh).into_mesh();
+        }
+        Ok(mesh)
+    }
+
+    /// Build and run the workflow of the problem, write the output if any and return summary
+    /// quantities by name: the number of dofs and the largest absolute values of the fields
+    pub fn run(&self) -> Result<Vec<(String, f64)>> {
+        let (summary, timers) = Timers::collect(|| {
+            let _span = Span::enter("simulation");
+            self.simulate()
+        });
+        if let Some(path) = &self.timings {
+            timers.save(path)?;
+        }
+        summary
+    }
+
+    // Solve the problem and compute its summary quantities
+    fn simulate(&self) -> Result<Vec<(String, f64)>> {
+        let mesh = self.load_mesh()?;
+        let dim = mesh.geometric_dim();
+        let element = || LagrangeElement::new(dim, self.order);
+        let mut summary = Vec::new();
+        match &self.problem {
+            ProblemConfig::Heat {
+                capacity,
+                conductivity,
+                source,
+                initial,
+                dirichlet,
+                start,
+                end,
+                time_step,
+                scheme,
+                output_every,
+                axisymmetric,
+            } => {
+                let space = FunctionSpace::new(&mesh, element());
+                let mut problem = HeatEquation::new(&space)
+                    .with_capacity(*capacity)
+                    .with_conductivity(*conductivity)
+                    .with_scheme(scheme.unwrap_or(TimeScheme::CrankNicolson));
+                if *axisymmetric {
+                    problem = problem.with_axisymmetry();
+                }
+                if let Some(source) = source {
+                    problem = problem.with_source(move |t, x| source.eval(x, t));
+                }
+                for condition in dirichlet {
+                    let value = &condition.value;
+                    problem = problem.with_dirichlet(condition.tag, move |t, x| value.eval(x, t));
+                }
+                let mut u = Function::new(&space);
+                u.interpolate(|x| initial.eval(x, *start));
+                let n_steps = match &self.output {
+                    Some(prefix) => problem.solve_to_vtk(
+                        &mut u,
+                        *start,
+                        *end,
+                        *time_step,
+                        prefix,
+                        output_every.unwrap_or(1),
+                    )?,
+                    None => problem.solve(&mut u, *start, *end, *time_step, |_, _| {}),
+                };
+                summary.push(("n_dofs".to_string(), space.n_dofs() as f64));
+                summary.push(("n_steps".to_string(), n_steps as f64));
+                summary.push(("max_temperature".to_string(), max_abs(&u)));
+            }
+            ProblemConfig::Elasticity {
+                young_modulus,
+                poisson_ratio,
+                body_force,
+                tractions,
+                displacements,
+                component_displacements,
+                solver,
+                axisymmetric,
+            } => {
+                let space = FunctionSpace::vector(&mesh, element(), dim);
+                let material = IsotropicMaterial::new(*young_modulus, *poisson_ratio);
+                let mut problem = LinearElasticity::new(&space, material);
+                if *axisymmetric {
+                    problem = problem.with_axisymmetry();
+                }
+                if let Some(force) = body_force {
+                    let force = checked_vector(force, dim, "Body force");
+                    problem = problem.with_body_force(move |_, f| f.copy_from_slice(&force));
+                }
+                for traction in tractions {
+                    let value = checked_vector(&traction.value, dim, "Traction");
+                    problem =
+                        problem.with_traction(traction.tag, move |_, t| t.copy_from_slice(&value));
+                }
+                for displacement in displacements {
+                    let value = checked_vector(&displacement.value, dim, "Displacement");
+                    problem = problem
+                        .with_displacement(displacement.tag, move |_, u| u.copy_from_slice(&value));
+                }
+                for condition in component_displacements {
+                    problem = problem.with_component_displacement(
+                        condition.tag,
+                        condition.component,
+                        condition.value,
+                    );
+                }
+                if let Some(solver) = solver {
+                    problem = problem.with_solver(*solver);
+                }
+                let u = problem.solve();
+                if let Some(path) = &self.output {
+                    problem.save_vtk(path, &u)?;
+                }
+                let target = FunctionSpace::new(&mesh, LagrangeElement::new(dim, 1));
+                let fields = problem.recover_stress(&u, &target);
+                let (_, von_mises) = fields.last().unwrap();
+                summary.push(("n_dofs".to_string(), space.n_dofs() as f64));
+                summary.push(("max_displacement".to_string(), max_abs(&u)));
+                summary.push(("max_von_mises".to_string(), max_abs(von_mises)));
+            }
+            ProblemConfig::Stokes {
+                viscosity,
+                stabilization,
+                body_force,
+                velocities,
+                direct,
+            } => {
+                let velocity_order = if *stabilization > 0.0 {
+                    self.order
+                } else {
+                    self.order + 1
+                };
+                let velocity_space =
+                    FunctionSpace::vector(&mesh, LagrangeElement::new(dim, velocity_order), dim);
+                let pressure_space = FunctionSpace::new(&mesh, element());
+                let mut problem = StokesFlow::new(&velocity_space, &pressure_space)
+                    .with_viscosity(*viscosity)
+                    .with_stabilization(*stabilization);
+                if let Some(force) = body_force {
+                    let force = checked_vector(force, dim, "Body force");
+                    problem = problem.with_body_force(move |_, f| f.copy_from_slice(&force));
+                }
+                for velocity in velocities {
+                    let value = checked_vector(&velocity.value, dim, "Velocity");
+                    problem =
+                        problem.with_velocity(velocity.tag, move |_, u| u.copy_from_slice(&value));
+                }
+                if *direct {
+                    problem = problem.with_solver(StokesSolver::Direct);
+                }
+                let (u, p) = problem.solve();
+                if let Some(path) = &self.output {
+                    problem.save_vtk(path, &u, &p)?;
+                }
+                let n_dofs = velocity_space.n_dofs() + pressure_space.n_dofs();
+                summary.push(("n_dofs".to_string(), n_dofs as f64));
+                summary.push(("max_velocity".to_string(), max_abs(&u)));
+                summary.push(("max_pressure".to_string(), max_abs(&p)));
+            }
+            ProblemConfig::AdvectionDiffusion {
+                diffusivity,
+                reaction,
+                velocity,
+                source,
+                dirichlet,
+                stabilization,
+                solver,
+            } => {
+                let space = FunctionSpace::new(&mesh, element());
+                let velocity = checked_vector(velocity, dim, "Velocity");
+                let mut problem = AdvectionDiffusion::new(&space)
+                    .with_diffusivity(*diffusivity)
+                    .with_reaction(*reaction)
+                    .with_velocity(move |_, b| b.copy_from_slice(&velocity));
+                if let Some(source) = source {
+                    problem = problem.with_source(move |x| source.eval(x, 0.0));
+                }
+                for condition in dirichlet {
+                    let value = &condition.value;
+                    problem = problem.with_dirichlet(condition.tag, move |x| value.eval(x, 0.0));
+                }
+                if let Some(stabilization) = stabilization {
+                    problem = problem.with_stabilization(*stabilization);
+                }
+                if let Some(solver) = solver {
+                    problem = problem.with_solver(*solver);
+                }
+                let u = problem.solve();
+                if let Some(path) = &self.output {
+                    vtk::save_vtk(path, &mesh, &[("solution", &u)])?;
+                }
+                summary.push(("n_dofs".to_string(), space.n_dofs() as f64));
+                summary.push(("max_peclet".to_string(), problem.max_peclet_number()));
+                summary.push(("max_solution".to_string(), max_abs(&u)));
+            }
+        }
+        Ok(summary)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Default order of the elements when deserializing
+#[cfg(feature = "serde")]
+fn linear() -> usize {
+    1
+}
+
+// Number in SI units or quantity of a dimension with units when deserializing
+#[cfg(feature = "serde")]
+fn quantity<'de, D>(deserializer: D, dimension: Dimension) -> std::result::Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::Deserialize;
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Source {
+        Number(f64),
+        Text(String),
+    }
+    match Source::deserialize(deserializer)? {
+        Source::Number(value) => Ok(value),
+        Source::Text(source) => Quantity::parse(&source)
+            .and_then(|quantity| quantity.value_in(dimension))
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+// Deserializers of the quantities of the dimensions of the configurations
+macro_rules! quantity_deserializer {
+    ($name:ident, $dimension:ident) => {
+        #[cfg(feature = "serde")]
+        fn $name<'de, D>(deserializer: D) -> std::result::Result<f64, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            quantity(deserializer, Dimension::$dimension)
+        }
+    };
+}
+
+quantity_deserializer!(length, LENGTH);
+quantity_deserializer!(time, TIME);
+quantity_deserializer!(rate, RATE);
+quantity_deserializer!(pressure, PRESSURE);
+quantity_deserializer!(diffusivity, DIFFUSIVITY);
+quantity_deserializer!(thermal_conductivity, THERMAL_CONDUCTIVITY);
+quantity_deserializer!(volumetric_heat_capacity, VOLUMETRIC_HEAT_CAPACITY);
+
+// Lengths in SI units or with units when deserializing
+#[cfg(feature = "serde")]
+fn lengths<'de, D>(deserializer: D) -> std::result::Result<Vec<f64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::Deserialize;
+    #[derive(serde::Deserialize)]
+    struct Length(#[serde(deserialize_with = "length")] f64);
+    Ok(Vec::<Length>::deserialize(deserializer)?
+        .into_iter()
+        .map(|Length(value)| value)
+        .collect())
+}
+
+// Copy of a vector of a configuration checked against the dimension of the mesh
+fn checked_vector(values: &[f64], dim: usize, name: &str) -> Vec<f64> {
+    assert!(
+        values.len() == dim,
+        "{} should have the dimension {} of the mesh",
+        name,
+        dim
+    );
+    values.to_vec()
+}
+
+// Largest absolute value of a function
+fn max_abs(function: &Function) -> f64 {
+    function
+        .values()
+        .iter()
+        .fold(0.0, |max: f64, v| max.max(v.abs()))
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulation_config() {
+        let directory = std::env::temp_dir().join("fe2o3_test_simulation_config");
+        std::fs::create_dir_all(&directory).unwrap();
+        let mesh = MeshConfig::Box {
+            lower: vec![0.0, 0.0],
+            upper: vec![2.0, 1.0],
+            cells: vec![4, 2],
+            grading: vec![GradingConfig::BoundaryLayer {
+                tag: 1,
+                first_height: 0.2,
+                growth: 1.5,
+            }],
+        };
+        // Plate pulled on its right side and held by rollers on the left and bottom ones
+        let (young_modulus, poisson_ratio, pull) = (100.0, 0.25, 3.0);
+        let elasticity = SimulationConfig::new(
+            mesh.clone(),
+            ProblemConfig::Elasticity {
+                young_modulus,
+                poisson_ratio,
+                body_force: None,
+                tractions: vec![TaggedVector::new(2, vec![pull, 0.0])],
+                displacements: Vec::new(),
+                component_displacements: vec![
+                    TaggedComponent::new(1, 0, 0.0),
+                    TaggedComponent::new(3, 1, 0.0),
+                ],
+                solver: None,
+                axisymmetric: false,
+            },
+        )
+        .with_output(directory.join("plate.vtk"))
+        .with_timings(directory.join("plate_timings.json"));
+        let summary = elasticity.run().unwrap();
+        let value = |summary: &[(String, f64)], name: &str| {
+            summary.iter().find(|(n, _)| n == name).unwrap().1
+        };
+        // Plane strain stretch of the right side
+        let stretch = 2.0 * pull * (1.0 - poisson_ratio * poisson_ratio) / young_modulus;
+        assert!(
+            (value(&summary, "max_displacement") - stretch).abs() < 1e-12,
+            "Wrong displacement of the plate"
+        );
+        assert_eq!(value(&summary, "n_dofs"), 30.0, "Wrong number of dofs");
+        assert!(directory.join("plate.vtk").exists(), "Output not written");
+        let timings = std::fs::read_to_string(directory.join("plate_timings.json")).unwrap();
+        assert!(
+            timings.contains("[\"simulation\", \"linear elasticity\"]"),
+            "Solve not timed in {}",
+            timings
+        );
+        // Steady temperatures of a bar held at 1 on its left side after a long time
+        let heat = SimulationConfig::new(
+            mesh,
+            ProblemConfig::Heat {
+                capacity: 1.0,
+                conductivity: 1.0,
+                source: None,
+                initial: Expression::parse("x * (2 - x) * (1 + y) / 4").unwrap(),
+                dirichlet: vec![TaggedScalar::new(1, 1.0)],
+                start: 0.0,
+                end: 50.0,
+                time_step: 1.0,
+                scheme: Some(TimeScheme::ImplicitEuler),
+                output_every: Some(25),
+                axisymmetric: false,
+            },
+        )
+        .with_refinements(1)
+        .with_output(directory.join("bar"));
+        let summary = heat.run().unwrap();
+        assert_eq!(value(&summary, "n_steps"), 50.0, "Wrong number of steps");
+        assert!(
+            (value(&summary, "max_temperature") - 1.0).abs() < 1e-12,
+            "Wrong temperature"
+        );
+        assert!(
+            directory.join("bar_00002.vtk").exists(),
+            "Series not written"
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+        let missing =
+            SimulationConfig::new(MeshConfig::File("mesh.msh".into()), heat.problem().clone());
+        assert!(missing.run().is_err(), "Unknown formats should be rejected");
+    }
+    //--------------------------------------------------------------------------------------------------
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_simulation_config_deserialize() {
+        let elasticity: SimulationConfig = toml::from_str(
+            r#"
+            order = 2
+            output = "beam.vtk"
+            [mesh.box]
+            lower = [0.0, "0 m"]
+            upper = ["10 m", "100 cm"]
+            cells = [40, 4]
+            grading = [{ boundary_layer = { tag = 3, first_height = "1 cm", growth = 1.2 } }]
+            [problem.elasticity]
+            young_modulus = "210 GPa"
+            poisson_ratio = 0.3
+            displacements = [{ tag = 1, value = [0.0, 0.0] }]
+            tractions = [{ tag = 2, value = [0.0, -1e6] }]
+            "#,
+        )
+        .unwrap();
+        let mesh = MeshConfig::Box {
+            lower: vec![0.0, 0.0],
+            upper: vec![10.0, 1.0],
+            cells: vec![40, 4],
+            grading: vec![GradingConfig::BoundaryLayer {
+                tag: 3,
+                first_height: 0.01,
+                growth: 1.2,
+            }],
+        };
+        let expected = SimulationConfig::new(
+            mesh.clone(),
+            ProblemConfig::Elasticity {
+                young_modulus: 210e9,
+                poisson_ratio: 0.3,
+                body_force: None,
+                tractions: vec![TaggedVector::new(2, vec![0.0, -1e6])],
+                displacements: vec![TaggedVector::new(1, vec![0.0, 0.0])],
+                component_displacements: Vec::new(),
+                solver: None,
+                axisymmetric: false,
+            },
+        )
+        .with_order(2)
+        .with_output("beam.vtk");
+        assert_eq!(elasticity, expected, "Wrong elasticity configuration");
+        let heat: ProblemConfig = toml::from_str(
+            r#"
+            [heat]
+            capacity = "4 MJ/(m^3*K)"
+            conductivity = "45 W/(m*K)"
+            initial = "20 + x"
+            dirichlet = [{ tag = 1, value = 20.0 }]
+            start = 0.0
+            end = "1 h"
+            time_step = "30 min"
+            scheme = "implicit_euler"
+            "#,
+        )
+        .unwrap();
+        let expected = ProblemConfig::Heat {
+            capacity: 4e6,
+            conductivity: 45.0,
+            source: None,
+            initial: Expression::parse("20 + x").unwrap(),
+            dirichlet: vec![TaggedScalar::new(1, 20.0)],
+            start: 0.0,
+            end: 3600.0,
+            time_step: 1800.0,
+            scheme: Some(TimeScheme::ImplicitEuler),
+            output_every: None,
+            axisymmetric: false,
+        };
+        assert_eq!(heat, expected, "Wrong heat configuration");
+        let rejected = [
+            ("young_modulus = \"210 m\"", "Expected a quantity in"),
+            ("young_modulus = \"1 m^100 m^100\"", "Invalid units"),
+            ("young_modulus = \"stiff\"", "No number"),
+        ];
+        for (line, message) in rejected {
+            let source = format!("[elasticity]\n{}\npoisson_ratio = 0.3\n", line);
+            let error = toml::from_str::<ProblemConfig>(&source).unwrap_err();
+            assert!(
+                error.to_string().contains(message),
+                "Wrong error for {}: {}",
+                line,
+                error
+            );
+        }
+    }
+}
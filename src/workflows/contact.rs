@@ -0,0 +1,822 @@
+use super::elasticity::LinearElasticity;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::core::logging::{debug, info, Span};
+use crate::core::search::KdTree;
+use crate::discretizations::constraints::AffineConstraints;
+use crate::discretizations::facets::Facets;
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+use crate::solvers::nonlinear::{Newton, NonlinearProblem};
+use std::collections::BTreeMap;
+
+// Dofs a gap depends on with its derivatives along them
+type GapGradient = Vec<(usize, f64)>;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Enforcement of the non penetration of the contact surfaces
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContactEnforcement {
+    /// Contact pressure proportional to the penetration, which only vanishes as the penalty grows
+    Penalty,
+    /// Penalty augmented by contact pressures updated after every nonlinear solve (Uzawa) until
+    /// the penetration falls below the tolerance, at most the given number of updates being done
+    AugmentedLagrangian { tolerance: f64, max_updates: usize },
+}
+
+/// Frictionless node to surface contact of linear elastic bodies
+///
+/// The nodes of the slave surface of every contact pair are projected onto the closest facets of
+/// the master surface in the deformed configuration, found among the facets around the master
+/// vertices within the search radius by a k-d tree, and their gap is the distance to the master
+/// surface along its outward normal. Penetrating nodes receive a pressure of the penalty times
+/// the penetration, plus the multiplier of the augmented Lagrangian, times the area of the slave
+/// surface they carry, which is added to the elasticity equations solved by Newton iterations
+/// with the projections frozen in the jacobian. The bodies are meshed in a common mesh, on linear
+/// elements, and the penalty is a stiffness per unit area of the order of the Young modulus over
+/// the mesh size. By default the penalty is enforced, the search radius is twice the largest
+/// master facet and Newton iterations use their default parameters.
+pub struct FrictionlessContact<'a> {
+    elasticity: LinearElasticity<'a>,
+    pairs: Vec<(usize, usize)>,
+    penalty: f64,
+    enforcement: ContactEnforcement,
+    search_radius: Option<f64>,
+    newton: Newton,
+}
+
+/// Displacement solving a contact problem with the state of the slave nodes
+pub struct ContactSolution<'a> {
+    displacement: Function<'a>,
+    slave_vertices: Vec<usize>,
+    gaps: Vec<f64>,
+    pressures: Vec<f64>,
+    updates: usize,
+    converged: bool,
+}
+
+// Slave and master surfaces of the contact pairs in the reference configuration
+struct ContactGeometry {
+    dim: usize,
+    // Slave vertices with their tributary area and the pair they belong to
+    slaves: Vec<(usize, f64, usize)>,
+    // Vertices of the master facets of all pairs
+    facets: Vec<Vec<usize>>,
+    // Vertex of the cell of every master facet opposite to it, giving its outward side
+    opposites: Vec<usize>,
+    // Vertices of the master surface of every pair, searched by k-d trees
+    master_vertices: Vec<Vec<usize>>,
+    // Master facets around every master vertex
+    vertex_facets: BTreeMap<usize, Vec<usize>>,
+    // Scalar dof of every mesh vertex
+    vertex_dofs: Vec<usize>,
+    search_radius: f64,
+}
+
+// Projection of a slave vertex onto a master facet
+struct Pairing {
+    slave: usize,
+    facet: usize,
+    weights: Vec<f64>,
+    normal: Vec<f64>,
+    gap: f64,
+}
+
+// Elasticity equations with the contact pressures of the current multipliers
+struct ContactProblem<'p> {
+    stiffness: &'p SparseCSR<f64>,
+    load: &'p [f64],
+    constraints: &'p AffineConstraints,
+    geometry: &'p ContactGeometry,
+    space: &'p FunctionSpace<'p>,
+    multipliers: &'p [f64],
+    penalty: f64,
+}
+
+impl<'a> FrictionlessContact<'a> {
+    /// Contact of the bodies of an elasticity problem enforced with the given penalty
+    pub fn new(elasticity: LinearElasticity<'a>, penalty: f64) -> Self {
+        assert!(penalty > 0.0, "Contact penalty should be positive");
+        assert_eq!(
+            elasticity.space().element().order(),
+            1,
+            "Node to surface contact is implemented on linear elements"
+        );
+        FrictionlessContact {
+            elasticity,
+            pairs: Vec::new(),
+            penalty,
+            enforcement: ContactEnforcement::Penalty,
+            search_radius: None,
+            newton: Newton::new(),
+        }
+    }
+
+    /// Add a contact pair of the boundary facets carrying the slave tag onto the ones carrying
+    /// the master tag
+    pub fn with_pair(mut self, slave_tag: usize, master_tag: usize) -> Self {
+        assert_ne!(slave_tag, master_tag, "Contact surfaces should differ");
+        self.pairs.push((slave_tag, master_tag));
+        self
+    }
+
+    /// Set the enforcement of the non penetration
+    pub fn with_enforcement(mut self, enforcement: ContactEnforcement) -> Self {
+        if let ContactEnforcement::AugmentedLagrangian { tolerance, .. } = enforcement {
+            assert!(tolerance > 0.0, "Penetration tolerance should be positive");
+        }
+        self.enforcement = enforcement;
+        self
+    }
+
+    /// Set the distance around the slave nodes within which master vertices are searched
+    pub fn with_search_radius(mut self, radius: f64) -> Self {
+        assert!(radius > 0.0, "Search radius should be positive");
+        self.search_radius = Some(radius);
+        self
+    }
+
+    /// Set the Newton solver of the contact equations
+    pub fn with_newton(mut self, newton: Newton) -> Self {
+        self.newton = newton;
+        self
+    }
+
+    /// Solve for the displacement in contact
+    pub fn solve(&self) -> ContactSolution<'a> {
+        let _span = Span::enter("contact");
+        assert!(!self.pairs.is_empty(), "No contact pair was given");
+        let space = self.elasticity.space();
+        let geometry = ContactGeometry::new(space, &self.pairs, self.search_radius);
+        let (stiffness, load, constraints) = self.elasticity.assemble();
+        let mut multipliers = vec![0.0; geometry.slaves.len()];
+        let mut x = vec![0.0; space.n_dofs()];
+        let mut updates = 0;
+        let (converged, pairings) = loop {
+            let problem = ContactProblem {
+                stiffness: &stiffness,
+                load: &load,
+                constraints: &constraints,
+                geometry: &geometry,
+                space,
+                multipliers: &multipliers,
+                penalty: self.penalty,
+            };
+            let convergence = self.newton.solve(&problem, &mut x);
+            let pairings = geometry.pairings(space, &problem.displacement(&x));
+            if !convergence.converged() {
+                break (false, pairings);
+            }
+            // Largest gap of the nodes in contact, which the multipliers should close
+            let violation = pairings
+                .iter()
+                .filter(|p| multipliers[p.slave] - self.penalty * p.gap > 0.0)
+                .map(|p| p.gap.abs())
+                .fold(0.0, f64::max);
+            debug!(
+                "Contact solve {}: {} Newton iterations, largest gap in contact {:e}",
+                updates,
+                convergence.iterations(),
+                violation
+            );
+            match self.enforcement {
+                ContactEnforcement::Penalty => break (true, pairings),
+                ContactEnforcement::AugmentedLagrangian {
+                    tolerance,
+                    max_updates,
+                } => {
+                    if violation <= tolerance {
+                        break (true, pairings);
+                    }
+                    if updates == max_updates {
+                        break (false, pairings);
+                    }
+                }
+            }
+            let mut updated = vec![0.0; multipliers.len()];
+            for pairing in &pairings {
+                updated[pairing.slave] =
+                    (multipliers[pairing.slave] - self.penalty * pairing.gap).max(0.0);
+            }
+            multipliers = updated;
+            updates += 1;
+        };
+        let mut gaps = vec![f64::INFINITY; geometry.slaves.len()];
+        let mut pressures = vec![0.0; geometry.slaves.len()];
+        for pairing in &pairings {
+            gaps[pairing.slave] = pairing.gap;
+            pressures[pairing.slave] =
+                (multipliers[pairing.slave] - self.penalty * pairing.gap).max(0.0);
+        }
+        info!(
+            "Contact solved after {} multiplier updates with {} nodes in contact",
+            updates,
+            pressures.iter().filter(|p| **p > 0.0).count()
+        );
+        constraints.distribute(&mut x);
+        ContactSolution {
+            displacement: Function::from_values(space, x),
+            slave_vertices: geometry.slaves.iter().map(|s| s.0).collect(),
+            gaps,
+            pressures,
+            updates,
+            converged,
+        }
+    }
+}
+
+impl<'a> ContactSolution<'a> {
+    /// Displacement of the bodies
+    pub fn displacement(&self) -> &Function<'a> {
+        &self.displacement
+    }
+
+    /// Displacement given back
+    pub fn into_displacement(self) -> Function<'a> {
+        self.displacement
+    }
+
+    /// Mesh vertices of the slave surfaces
+    pub fn slave_vertices(&self) -> &[usize] {
+        &self.slave_vertices
+    }
+
+    /// Gaps of the slave vertices to the master surfaces, negative when penetrating and infinite
+    /// when no master facet is within the search radius
+    pub fn gaps(&self) -> &[f64] {
+        &self.gaps
+    }
+
+    /// Contact pressures on the slave vertices
+    pub fn pressures(&self) -> &[f64] {
+        &self.pressures
+    }
+
+    /// Number of updates of the augmented Lagrangian multipliers
+    pub fn updates(&self) -> usize {
+        self.updates
+    }
+
+    /// Whether the Newton solves converged and the penetration met the tolerance
+    pub fn converged(&self) -> bool {
+        self.converged
+    }
+}
+
+impl ContactGeometry {
+    // Surfaces of the contact pairs on the mesh of a linear vector space
+    fn new(space: &FunctionSpace, pairs: &[(usize, usize)], search_radius: Option<f64>) -> Self {
+        let mesh = space.mesh();
+        let dim = mesh.geometric_dim();
+        let mut vertex_dofs = vec![0; mesh.n_vertices()];
+        for cell in 0..mesh.n_cells() {
+            for (vertex, dof) in mesh.cell(cell).iter().zip(space.dof_map().cell_dofs(cell)) {
+                vertex_dofs[*vertex] = *dof;
+            }
+        }
+        let facets = Facets::new(mesh);
+        let mut geometry = ContactGeometry {
+            dim,
+            slaves: Vec::new(),
+            facets: Vec::new(),
+            opposites: Vec::new(),
+            master_vertices: Vec::new(),
+            vertex_facets: BTreeMap::new(),
+            vertex_dofs,
+            search_radius: 0.0,
+        };
+        let mut largest: f64 = 0.0;
+        for (pair, (slave_tag, master_tag)) in pairs.iter().enumerate() {
+            let mut areas: BTreeMap<usize, f64> = BTreeMap::new();
+            let mut master_vertices = Vec::new();
+            for facet in facets.boundary_facets() {
+                let vertices = facets.facet_vertices(facet);
+                let tag = mesh.facet_tag(vertices);
+                if tag == Some(*slave_tag) {
+                    let points: Vec<&[f64]> = vertices.iter().map(|v| mesh.vertex(*v)).collect();
+                    let share = facet_measure(&points) / vertices.len() as f64;
+                    for vertex in vertices {
+                        *areas.entry(*vertex).or_insert(0.0) += share;
+                    }
+                } else if tag == Some(*master_tag) {
+                    let (cell, local) = facets.facet_cells(facet)[0];
+                    for vertex in vertices {
+                        let x = mesh.vertex(*vertex);
+                        for other in vertices {
+                            largest = largest.max(distance(x, mesh.vertex(*other)));
+                        }
+                        geometry
+                            .vertex_facets
+                            .entry(*vertex)
+                            .or_default()
+                            .push(geometry.facets.len());
+                        master_vertices.push(*vertex);
+                    }
+                    geometry.facets.push(vertices.to_vec());
+                    geometry.opposites.push(mesh.cell(cell)[local]);
+                }
+            }
+            assert!(
+                !areas.is_empty() && !master_vertices.is_empty(),
+                "No facet carries a tag of the contact pair"
+            );
+            geometry
+                .slaves
+                .extend(areas.into_iter().map(|(vertex, area)| (vertex, area, pair)));
+            master_vertices.sort_unstable();
+            master_vertices.dedup();
+            geometry.master_vertices.push(master_vertices);
+        }
+        geometry.search_radius = search_radius.unwrap_or(2.0 * largest);
+        geometry
+    }
+
+    // Deformed position of a vertex
+    fn position(&self, space: &FunctionSpace, displacement: &[f64], vertex: usize) -> Vec<f64> {
+        let x = space.mesh().vertex(vertex);
+        (0..self.dim)
+            .map(|c| x[c] + displacement[space.dof(self.vertex_dofs[vertex], c)])
+            .collect()
+    }
+
+    // Projections of the slave vertices onto their closest master facets in the deformed
+    // configuration of a displacement
+    fn pairings(&self, space: &FunctionSpace, displacement: &[f64]) -> Vec<Pairing> {
+        let trees: Vec<KdTree> = self
+            .master_vertices
+            .iter()
+            .map(|vertices| {
+                let points: Vec<f64> = vertices
+                    .iter()
+                    .flat_map(|v| self.position(space, displacement, *v))
+                    .collect();
+                KdTree::new(self.dim, &points)
+            })
+            .collect();
+        let mut pairings = Vec::new();
+        for (slave, (vertex, _, pair)) in self.slaves.iter().enumerate() {
+            let x = self.position(space, displacement, *vertex);
+            let mut candidates: Vec<usize> = trees[*pair]
+                .within(&x, self.search_radius)
+                .into_iter()
+                .flat_map(|v| self.vertex_facets[&self.master_vertices[*pair][v]].clone())
+                .collect();
+            candidates.sort_unstable();
+            candidates.dedup();
+            let mut best: Option<(f64, Pairing)> = None;
+            for facet in candidates {
+                let points: Vec<Vec<f64>> = self.facets[facet]
+                    .iter()
+                    .map(|v| self.position(space, displacement, *v))
+                    .collect();
+                let weights = closest_point(&points, &x);
+                let projection: Vec<f64> = (0..self.dim)
+                    .map(|c| points.iter().zip(&weights).map(|(p, w)| w * p[c]).sum())
+                    .collect();
+                let length = distance(&x, &projection);
+                if length > self.search_radius || best.as_ref().is_some_and(|b| b.0 <= length) {
+                    continue;
+                }
+                let opposite = self.position(space, displacement, self.opposites[facet]);
+                let normal = outward_normal(&points, &opposite);
+                let gap = (0..self.dim)
+                    .map(|c| normal[c] * (x[c] - projection[c]))
+                    .sum();
+                best = Some((
+                    length,
+                    Pairing {
+                        slave,
+                        facet,
+                        weights,
+                        normal,
+                        gap,
+                    },
+                ));
+            }
+            pairings.extend(best.map(|b| b.1));
+        }
+        pairings
+    }
+}
+
+impl ContactProblem<'_> {
+    // Displacement of an iterate once the constraints are distributed
+    fn displacement(&self, x: &[f64]) -> Vec<f64> {
+        let mut u = x.to_vec();
+        self.constraints.distribute(&mut u);
+        u
+    }
+
+    // Dofs of a pairing with the derivatives of its gap along them
+    fn gap_gradient(&self, pairing: &Pairing) -> GapGradient {
+        let geometry = self.geometry;
+        let space = self.space;
+        let mut gradient = Vec::with_capacity((pairing.weights.len() + 1) * geometry.dim);
+        let slave = geometry.vertex_dofs[geometry.slaves[pairing.slave].0];
+        for (c, n) in pairing.normal.iter().enumerate() {
+            gradient.push((space.dof(slave, c), *n));
+            for (vertex, w) in geometry.facets[pairing.facet].iter().zip(&pairing.weights) {
+                gradient.push((space.dof(geometry.vertex_dofs[*vertex], c), -w * n));
+            }
+        }
+        gradient
+    }
+
+    // Active pairings with their gap gradient and contact force, the pressure times the area
+    fn active(&self, x: &[f64]) -> Vec<(GapGradient, f64, f64)> {
+        self.geometry
+            .pairings(self.space, &self.displacement(x))
+            .into_iter()
+            .filter_map(|pairing| {
+                let pressure = self.multipliers[pairing.slave] - self.penalty * pairing.gap;
+                let area = self.geometry.slaves[pairing.slave].1;
+                (pressure > 0.0).then(|| (self.gap_gradient(&pairing), pressure * area, area))
+            })
+            .collect()
+    }
+
+    // Dofs carrying a dof once condensed by the constraints, with their coefficients
+    fn expand(&self, dof: usize) -> Vec<(usize, f64)> {
+        match self.constraints.constraint(dof) {
+            Some((entries, _)) => entries.to_vec(),
+            None => vec![(dof, 1.0)],
+        }
+    }
+}
+
+impl NonlinearProblem for ContactProblem<'_> {
+    fn size(&self) -> usize {
+        self.load.len()
+    }
+
+    fn residual(&self, x: &[f64], r: &mut [f64]) {
+        self.stiffness.apply(x, r);
+        r.iter_mut().zip(self.load).for_each(|(r, b)| *r -= b);
+        let mut force = vec![0.0; r.len()];
+        for (gradient, magnitude, _) in self.active(x) {
+            for (dof, coefficient) in gradient {
+                force[dof] -= magnitude * coefficient;
+            }
+        }
+        self.constraints.condense(&mut force);
+        r.iter_mut().zip(&force).for_each(|(r, f)| *r += f);
+    }
+
+    fn jacobian(&self, x: &[f64]) -> SparseCSR<f64> {
+        let mut entries: BTreeMap<(usize, usize), f64> = BTreeMap::new();
+        for (gradient, _, area) in self.active(x) {
+            let gradient: Vec<(usize, f64)> = gradient
+                .iter()
+                .flat_map(|(dof, c)| self.expand(*dof).into_iter().map(move |(d, e)| (d, c * e)))
+                .collect();
+            for (i, ci) in &gradient {
+                for (j, cj) in &gradient {
+                    *entries.entry((*i, *j)).or_insert(0.0) += self.penalty * area * ci * cj;
+                }
+            }
+        }
+        let n = self.size();
+        let mut row_offsets = vec![0; n + 1];
+        for (row, _) in entries.keys() {
+            row_offsets[row + 1] += 1;
+        }
+        for row in 0..n {
+            row_offsets[row + 1] += row_offsets[row];
+        }
+        let col_indices = entries.keys().map(|(_, col)| *col).collect();
+        let values = entries.into_values().collect();
+        let contact = SparseCSR::new(n, row_offsets, col_indices, values);
+        self.stiffness.add(1.0, &contact)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Euclidean distance between two points
+fn distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - b) * (a - b))
+        .sum::<f64>()
+        .sqrt()
+}
+
+// Length of a segment or area of a triangle
+fn facet_measure(points: &[&[f64]]) -> f64 {
+    let edge = |k: usize| -> Vec<f64> {
+        points[k]
+            .iter()
+            .zip(points[0])
+            .map(|(p, o)| p - o)
+            .collect()
+    };
+    match points.len() {
+        2 => distance(points[0], points[1]),
+        3 => 0.5 * norm3(&cross(&edge(1), &edge(2))),
+        _ => panic!("Contact facets are segments or triangles"),
+    }
+}
+
+// Cross product of 3-D vectors
+fn cross(a: &[f64], b: &[f64]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+// Norm of a 3-D vector
+fn norm3(a: &[f64; 3]) -> f64 {
+    (a[0] * a[0] + a[1] * a[1] + a[2] * a[2]).sqrt()
+}
+
+// Weights of the vertices of a segment or triangle giving its closest point to x
+fn closest_point(points: &[Vec<f64>], x: &[f64]) -> Vec<f64> {
+    let segment = |a: &[f64], b: &[f64]| -> f64 {
+        let (mut along, mut length) = (0.0, 0.0);
+        for c in 0..a.len() {
+            along += (x[c] - a[c]) * (b[c] - a[c]);
+            length += (b[c] - a[c]) * (b[c] - a[c]);
+        }
+        (along / length).clamp(0.0, 1.0)
+    };
+    if points.len() == 2 {
+        let t = segment(&points[0], &points[1]);
+        return vec![1.0 - t, t];
+    }
+    // Barycentric coordinates of the projection onto the plane of the triangle
+    let e1: Vec<f64> = (0..3).map(|c| points[1][c] - points[0][c]).collect();
+    let e2: Vec<f64> = (0..3).map(|c| points[2][c] - points[0][c]).collect();
+    let d: Vec<f64> = (0..3).map(|c| x[c] - points[0][c]).collect();
+    let dot = |a: &[f64], b: &[f64]| -> f64 { a.iter().zip(b).map(|(a, b)| a * b).sum() };
+    let (a11, a12, a22) = (dot(&e1, &e1), dot(&e1, &e2), dot(&e2, &e2));
+    let (b1, b2) = (dot(&e1, &d), dot(&e2, &d));
+    let det = a11 * a22 - a12 * a12;
+    let s = (a22 * b1 - a12 * b2) / det;
+    let t = (a11 * b2 - a12 * b1) / det;
+    if s >= 0.0 && t >= 0.0 && s + t <= 1.0 {
+        return vec![1.0 - s - t, s, t];
+    }
+    // Closest point on the edges otherwise
+    let mut best = (f64::INFINITY, Vec::new());
+    for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+        let t = segment(&points[i], &points[j]);
+        let point: Vec<f64> = (0..3)
+            .map(|c| (1.0 - t) * points[i][c] + t * points[j][c])
+            .collect();
+        let length = distance(&point, x);
+        if length < best.0 {
+            let mut weights = vec![0.0; 3];
+            weights[i] = 1.0 - t;
+            weights[j] = t;
+            best = (length, weights);
+        }
+    }
+    best.1
+}
+
+// Unit normal of a segment or triangle pointing away from the opposite vertex of its cell
+fn outward_normal(points: &[Vec<f64>], opposite: &[f64]) -> Vec<f64> {
+    let mut normal = if points.len() == 2 {
+        vec![points[1][1] - points[0][1], points[0][0] - points[1][0]]
+    } else {
+        let e1: Vec<f64> = (0..3).map(|c| points[1][c] - points[0][c]).collect();
+        let e2: Vec<f64> = (0..3).map(|c| points[2][c] - points[0][c]).collect();
+        cross(&e1, &e2).to_vec()
+    };
+    let length = normal.iter().map(|n| n * n).sum::<f64>().sqrt();
+    let inward: f64 = normal
+        .iter()
+        .zip(opposite.iter().zip(&points[0]))
+        .map(|(n, (o, p))| n * (o - p))
+        .sum();
+    let sign = if inward > 0.0 { -1.0 } else { 1.0 };
+    normal.iter_mut().for_each(|n| *n *= sign / length);
+    normal
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::data_hold::DataHold;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::mesh::Mesh;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::workflows::elasticity::IsotropicMaterial;
+    use std::panic::{self, AssertUnwindSafe};
+
+    // Description of an invalid input with the construction it should make panic
+    type Invalid<'a> = (&'a str, Box<dyn Fn() + 'a>);
+
+    // Lower block of [0, 1] x [0, 0.5] and upper block of [0, 1] x [0.5, 1] meshed apart with
+    // matching interface nodes in one mesh, the sides of the upper block being tagged 5 to 8
+    fn stacked_blocks() -> Mesh {
+        let lower = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 0.5], vec![4, 2]).simplex_mesh();
+        let upper = CartesianGrid::new(vec![0.0, 0.5], vec![1.0, 1.0], vec![4, 3]).simplex_mesh();
+        let offset = lower.n_vertices();
+        let mut vertices = lower.vertices().to_vec();
+        vertices.extend(upper.vertices().iter());
+        let mut cells = lower.cells().to_vec();
+        cells.extend(upper.cells().iter().map(|v| v + offset));
+        let n_vertices = offset + upper.n_vertices();
+        let n_cells = lower.n_cells() + upper.n_cells();
+        let mut mesh = Mesh::new(
+            DataHold::new(vertices, [n_vertices, 2]),
+            DataHold::new(cells, [n_cells, 3]),
+        );
+        for (facet, tag) in lower.facet_tags() {
+            mesh.tag_facet(facet, tag);
+        }
+        for (facet, tag) in upper.facet_tags() {
+            let facet: Vec<usize> = facet.iter().map(|v| v + offset).collect();
+            mesh.tag_facet(&facet, tag + 4);
+        }
+        mesh
+    }
+
+    #[test]
+    fn test_frictionless_contact() {
+        let mesh = stacked_blocks();
+        let space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        // Without Poisson effect the column is compressed uniformly by a strain of 0.01
+        let elasticity = || {
+            LinearElasticity::new(&space, IsotropicMaterial::new(1.0, 0.0))
+                .with_displacement(3, |_, u| u.fill(0.0))
+                .with_displacement(8, |_, u| {
+                    u[0] = 0.0;
+                    u[1] = -0.01;
+                })
+        };
+        let penalty = FrictionlessContact::new(elasticity(), 1e4)
+            .with_pair(7, 4)
+            .solve();
+        assert!(penalty.converged(), "Penalty contact did not converge");
+        assert_eq!(penalty.slave_vertices().len(), 5, "Wrong slave nodes");
+        for (gap, pressure) in penalty.gaps().iter().zip(penalty.pressures()) {
+            // The pressure balances the stress of 0.01 with a penetration of 0.01 / 1e4
+            assert!((gap + 1e-6).abs() < 1e-8, "Wrong penalty penetration");
+            assert!((pressure - 0.01).abs() < 1e-4, "Wrong contact pressure");
+        }
+        let augmented = FrictionlessContact::new(elasticity(), 10.0)
+            .with_pair(7, 4)
+            .with_enforcement(ContactEnforcement::AugmentedLagrangian {
+                tolerance: 1e-10,
+                max_updates: 50,
+            })
+            .solve();
+        assert!(
+            augmented.converged() && augmented.updates() > 0,
+            "Augmented Lagrangian did not converge"
+        );
+        assert!(
+            augmented.gaps().iter().all(|g| g.abs() < 1e-9),
+            "Surfaces should close"
+        );
+        let displacement = augmented.displacement();
+        let coordinates = space.dof_coordinates();
+        for s in 0..space.dof_map().n_dofs() {
+            let expected = -0.01 * coordinates[s * 2 + 1];
+            assert!(
+                (displacement.values()[space.dof(s, 1)] - expected).abs() < 1e-8
+                    && displacement.values()[space.dof(s, 0)].abs() < 1e-8,
+                "Displacement is not the uniform compression"
+            );
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_frictionless_contact_options() {
+        let mesh = stacked_blocks();
+        let space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let elasticity = || {
+            LinearElasticity::new(&space, IsotropicMaterial::new(1.0, 0.0))
+                .with_displacement(3, |_, u| u.fill(0.0))
+                .with_displacement(8, |_, u| {
+                    u[0] = 0.0;
+                    u[1] = -0.01;
+                })
+        };
+        // The penalty solution does not depend on the search radius nor on the Newton iterations
+        let solution = FrictionlessContact::new(elasticity(), 1e4)
+            .with_pair(7, 4)
+            .with_search_radius(0.1)
+            .with_newton(Newton::new().with_max_iterations(50))
+            .solve();
+        assert!(solution.converged(), "Contact did not converge");
+        assert_eq!(
+            solution.updates(),
+            0,
+            "Penalty should not update multipliers"
+        );
+        assert_eq!(
+            solution.gaps().len(),
+            solution.slave_vertices().len(),
+            "One gap per slave vertex"
+        );
+        assert_eq!(
+            solution.pressures().len(),
+            solution.slave_vertices().len(),
+            "One pressure per slave vertex"
+        );
+        for vertex in solution.slave_vertices() {
+            assert!(
+                (mesh.vertex(*vertex)[1] - 0.5).abs() < 1e-12,
+                "Slave vertex off the interface"
+            );
+        }
+        assert!(
+            solution.pressures().iter().all(|p| (p - 0.01).abs() < 1e-4),
+            "Wrong contact pressure"
+        );
+        let values = solution.displacement().values().to_vec();
+        assert_eq!(
+            solution.into_displacement().values(),
+            &values[..],
+            "Displacement given back differs"
+        );
+        // Too few multiplier updates to close the surfaces with a low penalty
+        let augmented = FrictionlessContact::new(elasticity(), 10.0)
+            .with_pair(7, 4)
+            .with_enforcement(ContactEnforcement::AugmentedLagrangian {
+                tolerance: 1e-10,
+                max_updates: 1,
+            })
+            .solve();
+        assert!(
+            !augmented.converged(),
+            "Penetration should exceed the tolerance"
+        );
+        assert_eq!(augmented.updates(), 1, "Wrong number of multiplier updates");
+        assert!(
+            augmented.gaps().iter().all(|g| *g < -1e-10),
+            "Surfaces should still penetrate"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_frictionless_contact_invalid() {
+        let mesh = stacked_blocks();
+        let space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let quadratic = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        let contact = |space| {
+            FrictionlessContact::new(
+                LinearElasticity::new(space, IsotropicMaterial::new(1.0, 0.0)),
+                1.0,
+            )
+        };
+        let cases: [Invalid; 7] = [
+            (
+                "a zero penalty",
+                Box::new(|| {
+                    drop(FrictionlessContact::new(
+                        LinearElasticity::new(&space, IsotropicMaterial::new(1.0, 0.0)),
+                        0.0,
+                    ))
+                }),
+            ),
+            ("a quadratic space", Box::new(|| drop(contact(&quadratic)))),
+            (
+                "a pair of one surface",
+                Box::new(|| drop(contact(&space).with_pair(4, 4))),
+            ),
+            (
+                "a zero tolerance",
+                Box::new(|| {
+                    drop(contact(&space).with_enforcement(
+                        ContactEnforcement::AugmentedLagrangian {
+                            tolerance: 0.0,
+                            max_updates: 10,
+                        },
+                    ))
+                }),
+            ),
+            (
+                "a zero search radius",
+                Box::new(|| drop(contact(&space).with_search_radius(0.0))),
+            ),
+            ("no pair", Box::new(|| drop(contact(&space).solve()))),
+            (
+                "a pair of untagged surfaces",
+                Box::new(|| drop(contact(&space).with_pair(7, 42).solve())),
+            ),
+        ];
+        for (case, build) in cases {
+            assert!(
+                panic::catch_unwind(AssertUnwindSafe(build)).is_err(),
+                "Frictionless contact accepted {}",
+                case
+            );
+        }
+    }
+}
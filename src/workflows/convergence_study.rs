@@ -0,0 +1,534 @@
+use super::advection_diffusion::diameter;
+use crate::core::logging::{info, Span};
+use crate::discretizations::cell_values::CellValues;
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+use crate::discretizations::mesh::Mesh;
+use crate::discretizations::refinement::Refinement;
+use crate::spaces::lagrange::LagrangeElement;
+use crate::spaces::quadrature::QuadratureRule;
+use std::fmt::Write;
+#[cfg(hosted)]
+use std::path::Path;
+
+// Vector field filling its components at the physical coordinates
+type VectorField<'a> = Box<dyn Fn(&[f64], &mut [f64]) + 'a>;
+
+//--------------------------------------------------------------------------------------------------
+// # Enums
+//--------------------------------------------------------------------------------------------------
+
+/// Sequence of discretizations of a convergence study
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Sweep {
+    /// Uniform refinements of the mesh with elements of a fixed order, the first level being the
+    /// mesh itself
+    Refinements {
+        /// Number of levels
+        levels: usize,
+        /// Order of the elements
+        order: usize,
+    },
+    /// Elements of increasing orders on the mesh
+    Orders(Vec<usize>),
+}
+
+/// Norm of the error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorNorm {
+    /// L2 norm of the function
+    L2,
+    /// L2 norm of the gradient
+    H1Seminorm,
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Errors of a discretization against a manufactured solution
+///
+/// The sizes are the largest cell diameters for refinement sweeps and n_dofs^(-1/d) for order
+/// sweeps, so that the errors of smooth solutions behave like size^rate in both cases.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConvergenceLevel {
+    order: usize,
+    n_cells: usize,
+    n_dofs: usize,
+    size: f64,
+    l2_error: f64,
+    h1_error: Option<f64>,
+}
+
+/// Errors of the levels of a convergence study with their observed rates
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConvergenceReport {
+    levels: Vec<ConvergenceLevel>,
+}
+
+/// Convergence study of a discretization against a manufactured solution
+///
+/// The study builds the continuous Lagrange spaces of every level of the sweep, hands them to the
+/// solver of the problem and measures the errors of the returned functions in the L2 norm, and the
+/// H1 seminorm when the gradient of the solution is given, with quadratures exact for twice the
+/// order plus 2. By default the solution is scalar and the sweep has 4 refinement levels of linear
+/// elements.
+pub struct ConvergenceStudy<'a> {
+    mesh: &'a Mesh,
+    n_components: usize,
+    exact: VectorField<'a>,
+    gradient: Option<VectorField<'a>>,
+    sweep: Sweep,
+}
+
+impl ConvergenceLevel {
+    /// Order of the elements
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Number of cells of the mesh
+    pub fn n_cells(&self) -> usize {
+        self.n_cells
+    }
+
+    /// Number of dofs of the space
+    pub fn n_dofs(&self) -> usize {
+        self.n_dofs
+    }
+
+    /// Size of the discretization
+    pub fn size(&self) -> f64 {
+        self.size
+    }
+
+    /// Error in a norm, none for the H1 seminorm without the gradient of the solution
+    pub fn error(&self, norm: ErrorNorm) -> Option<f64> {
+        match norm {
+            ErrorNorm::L2 => Some(self.l2_error),
+            ErrorNorm::H1Seminorm => self.h1_error,
+        }
+    }
+}
+
+impl ConvergenceReport {
+    /// Levels of the study
+    pub fn levels(&self) -> &[ConvergenceLevel] {
+        &self.levels
+    }
+
+    /// Observed rates log(e_k-1 / e_k) / log(s_k-1 / s_k) between consecutive levels
+    pub fn rates(&self, norm: ErrorNorm) -> Option<Vec<f64>> {
+        let errors = self.errors(norm)?;
+        Some(
+            self.levels
+                .windows(2)
+                .zip(errors.windows(2))
+                .map(|(levels, errors)| {
+                    (errors[0] / errors[1]).ln() / (levels[0].size / levels[1].size).ln()
+                })
+                .collect(),
+        )
+    }
+
+    /// Rate of the least squares fit of log(e) by an affine function of log(s) over all levels
+    pub fn fitted_rate(&self, norm: ErrorNorm) -> Option<f64> {
+        let errors = self.errors(norm)?;
+        if errors.len() < 2 {
+            return None;
+        }
+        let n = errors.len() as f64;
+        let x: Vec<f64> = self.levels.iter().map(|l| l.size.ln()).collect();
+        let y: Vec<f64> = errors.iter().map(|e| e.ln()).collect();
+        let (mean_x, mean_y) = (x.iter().sum::<f64>() / n, y.iter().sum::<f64>() / n);
+        let covariance: f64 = x
+            .iter()
+            .zip(&y)
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum();
+        let variance: f64 = x.iter().map(|x| (x - mean_x) * (x - mean_x)).sum();
+        Some(covariance / variance)
+    }
+
+    /// Comma separated table with a header and one row per level, the rates of the first level
+    /// and the missing H1 values being empty
+    pub fn to_csv(&self) -> String {
+        let l2_rates = self.rates(ErrorNorm::L2).unwrap();
+        let h1_rates = self.rates(ErrorNorm::H1Seminorm);
+        let mut csv =
+            String::from("level,order,n_cells,n_dofs,size,l2_error,l2_rate,h1_error,h1_rate\n");
+        for (k, level) in self.levels.iter().enumerate() {
+            let rate = |rates: Option<&Vec<f64>>| match (k, rates) {
+                (1.., Some(rates)) => rates[k - 1].to_string(),
+                _ => String::new(),
+            };
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{}",
+                k,
+                level.order,
+                level.n_cells,
+                level.n_dofs,
+                level.size,
+                level.l2_error,
+                rate(Some(&l2_rates)),
+                level.h1_error.map(|e| e.to_string()).unwrap_or_default(),
+                rate(h1_rates.as_ref()),
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    /// JSON object with the array of the levels and the fitted rates, missing values being null
+    pub fn to_json(&self) -> String {
+        let value = |value: Option<f64>| match value {
+            Some(value) if value.is_finite() => value.to_string(),
+            _ => "null".to_string(),
+        };
+        let l2_rates = self.rates(ErrorNorm::L2).unwrap();
+        let h1_rates = self.rates(ErrorNorm::H1Seminorm);
+        let mut json = String::from("{\n  \"levels\": [\n");
+        for (k, level) in self.levels.iter().enumerate() {
+            let rate = |rates: Option<&Vec<f64>>| {
+                value(rates.and_then(|r| r.get(k.wrapping_sub(1))).copied())
+            };
+            writeln!(
+                json,
+                "    {{\"level\": {}, \"order\": {}, \"n_cells\": {}, \"n_dofs\": {}, \"size\": {}, \
+                 \"l2_error\": {}, \"l2_rate\": {}, \"h1_error\": {}, \"h1_rate\": {}}}{}",
+                k,
+                level.order,
+                level.n_cells,
+                level.n_dofs,
+                value(Some(level.size)),
+                value(Some(level.l2_error)),
+                rate(Some(&l2_rates)),
+                value(level.h1_error),
+                rate(h1_rates.as_ref()),
+                if k + 1 < self.levels.len() { "," } else { "" }
+            )
+            .unwrap();
+        }
+        write!(
+            json,
+            "  ],\n  \"fitted_rates\": {{\"l2\": {}, \"h1\": {}}}\n}}\n",
+            value(self.fitted_rate(ErrorNorm::L2)),
+            value(self.fitted_rate(ErrorNorm::H1Seminorm))
+        )
+        .unwrap();
+        json
+    }
+
+    /// Write the report as JSON if the path has the json extension and as CSV otherwise
+    #[cfg(hosted)]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let contents = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => self.to_json(),
+            _ => self.to_csv(),
+        };
+        std::fs::write(path, contents)
+    }
+
+    // Errors of the levels in a norm if they were all measured
+    fn errors(&self, norm: ErrorNorm) -> Option<Vec<f64>> {
+        self.levels.iter().map(|level| level.error(norm)).collect()
+    }
+}
+
+impl<'a> ConvergenceStudy<'a> {
+    /// Study on the mesh against the scalar solution given as a function of the physical
+    /// coordinates
+    pub fn new<Exact>(mesh: &'a Mesh, exact: Exact) -> Self
+    where
+        Exact: Fn(&[f64]) -> f64 + 'a,
+    {
+        ConvergenceStudy {
+            mesh,
+            n_components: 1,
+            exact: Box::new(move |x, u| u[0] = exact(x)),
+            gradient: None,
+            sweep: Sweep::Refinements {
+                levels: 4,
+                order: 1,
+            },
+        }
+    }
+
+    /// Study on the mesh against the vector solution filling its components at the physical
+    /// coordinates
+    pub fn vector<Exact>(mesh: &'a Mesh, n_components: usize, exact: Exact) -> Self
+    where
+        Exact: Fn(&[f64], &mut [f64]) + 'a,
+    {
+        assert!(n_components > 0, "Solutions need components");
+        ConvergenceStudy {
+            n_components,
+            exact: Box::new(exact),
+            ..ConvergenceStudy::new(mesh, |_| 0.0)
+        }
+    }
+
+    /// Set the gradient of the solution, filled as a flat array of size (components, dimension),
+    /// to measure the H1 seminorm errors
+    pub fn with_gradient<Gradient>(mut self, gradient: Gradient) -> Self
+    where
+        Gradient: Fn(&[f64], &mut [f64]) + 'a,
+    {
+        self.gradient = Some(Box::new(gradient));
+        self
+    }
+
+    /// Set the sequence of discretizations
+    pub fn with_sweep(mut self, sweep: Sweep) -> Self {
+        match &sweep {
+            Sweep::Refinements { levels, order } => {
+                assert!(*levels > 0, "Sweeps need levels");
+                assert!(*order > 0, "Continuous elements need a positive order");
+            }
+            Sweep::Orders(orders) => {
+                assert!(!orders.is_empty(), "Sweeps need levels");
+                assert!(
+                    orders.iter().all(|o| *o > 0),
+                    "Continuous elements need a positive order"
+                );
+            }
+        }
+        self.sweep = sweep;
+        self
+    }
+
+    /// Solve the problem on every level and measure the errors of the solutions
+    pub fn run<Solve>(&self, solve: Solve) -> ConvergenceReport
+    where
+        Solve: for<'b> Fn(&'b FunctionSpace<'b>) -> Function<'b>,
+    {
+        let _span = Span::enter("convergence study");
+        let mut levels = Vec::new();
+        match &self.sweep {
+            Sweep::Refinements { levels: n, order } => {
+                let mut refined: Option<Mesh> = None;
+                for level in 0..*n {
+                    let mesh = refined.as_ref().unwrap_or(self.mesh);
+                    levels.push(self.level(mesh, *order, &solve));
+                    if level + 1 < *n {
+                        let next = Refinement::uniform(mesh).into_mesh();
+                        refined = Some(next);
+                    }
+                }
+            }
+            Sweep::Orders(orders) => {
+                for order in orders {
+                    let mut level = self.level(self.mesh, *order, &solve);
+                    level.size =
+                        (level.n_dofs as f64).powf(-1.0 / self.mesh.geometric_dim() as f64);
+                    levels.push(level);
+                }
+            }
+        }
+        ConvergenceReport { levels }
+    }
+
+    // Solve and measure the errors on a mesh with elements of an order
+    fn level<Solve>(&self, mesh: &Mesh, order: usize, solve: &Solve) -> ConvergenceLevel
+    where
+        Solve: for<'b> Fn(&'b FunctionSpace<'b>) -> Function<'b>,
+    {
+        let element = LagrangeElement::new(mesh.geometric_dim(), order);
+        let space = if self.n_components == 1 {
+            FunctionSpace::new(mesh, element)
+        } else {
+            FunctionSpace::vector(mesh, element, self.n_components)
+        };
+        let solution = solve(&space);
+        assert!(
+            solution.values().len() == space.n_dofs(),
+            "The solution does not belong to the space of the level"
+        );
+        let level = ConvergenceLevel {
+            order,
+            n_cells: mesh.n_cells(),
+            n_dofs: space.n_dofs(),
+            size: (0..mesh.n_cells())
+                .map(|cell| diameter(mesh, cell))
+                .fold(0.0, f64::max),
+            l2_error: l2_error(&solution, &self.exact),
+            h1_error: self
+                .gradient
+                .as_ref()
+                .map(|gradient| h1_seminorm_error(&solution, gradient)),
+        };
+        info!(
+            "Convergence level of order {} on {} dofs with L2 error {:e}",
+            order, level.n_dofs, level.l2_error
+        );
+        level
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// L2 norm of the difference between a function and the exact field filling its components at
+/// the physical coordinates
+pub fn l2_error<Exact>(function: &Function, exact: Exact) -> f64
+where
+    Exact: Fn(&[f64], &mut [f64]),
+{
+    let space = function.space();
+    let n_components = space.n_components();
+    let mut exact_value = vec![0.0; n_components];
+    let mut value = vec![0.0; n_components];
+    integrate_squared(function, |values, q, dofs| {
+        exact(values.point(q), &mut exact_value);
+        value.iter_mut().for_each(|v| *v = 0.0);
+        for (i, dof) in dofs.iter().enumerate() {
+            let phi = values.shape_value(q, i);
+            for (c, v) in value.iter_mut().enumerate() {
+                *v += phi * function.values()[space.dof(*dof, c)];
+            }
+        }
+        value
+            .iter()
+            .zip(&exact_value)
+            .map(|(v, e)| (v - e) * (v - e))
+            .sum()
+    })
+}
+
+/// L2 norm of the difference between the gradient of a function and the exact gradient filled as
+/// a flat array of size (components, dimension) at the physical coordinates
+pub fn h1_seminorm_error<Gradient>(function: &Function, gradient: Gradient) -> f64
+where
+    Gradient: Fn(&[f64], &mut [f64]),
+{
+    let space = function.space();
+    let dim = space.element().dim();
+    let size = space.n_components() * dim;
+    let mut exact_gradient = vec![0.0; size];
+    let mut value = vec![0.0; size];
+    integrate_squared(function, |values, q, dofs| {
+        gradient(values.point(q), &mut exact_gradient);
+        value.iter_mut().for_each(|v| *v = 0.0);
+        for (i, dof) in dofs.iter().enumerate() {
+            let grad = values.shape_gradient(q, i);
+            for (c, row) in value.chunks_mut(dim).enumerate() {
+                let u = function.values()[space.dof(*dof, c)];
+                row.iter_mut().zip(grad).for_each(|(r, g)| *r += u * g);
+            }
+        }
+        value
+            .iter()
+            .zip(&exact_gradient)
+            .map(|(v, e)| (v - e) * (v - e))
+            .sum()
+    })
+}
+
+// Square root of the integral over the mesh of a squared error evaluated at the quadrature points
+// of the cells from the values and the dofs of the cell
+fn integrate_squared<Squared>(function: &Function, mut squared: Squared) -> f64
+where
+    Squared: FnMut(&CellValues, usize, &[usize]) -> f64,
+{
+    let space = function.space();
+    let element = space.element();
+    let quadrature = QuadratureRule::simplex(element.dim(), 2 * element.order() + 2);
+    let mut values = CellValues::new(element, &quadrature);
+    let mut integral = 0.0;
+    for cell in 0..space.mesh().n_cells() {
+        values.reinit(space.mesh(), cell);
+        let dofs = space.dof_map().cell_dofs(cell);
+        for q in 0..values.n_points() {
+            integral += squared(&values, q, dofs) * values.weight(q);
+        }
+    }
+    integral.sqrt()
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::workflows::advection_diffusion::{AdvectionDiffusion, Stabilization};
+    use std::f64::consts::PI;
+
+    // Poisson problem -Δu = 2 π² sin(πx) sin(πy) with homogeneous values on the unit square
+    fn poisson<'b>(space: &'b FunctionSpace<'b>) -> Function<'b> {
+        (1..=4)
+            .fold(AdvectionDiffusion::new(space), |problem, tag| {
+                problem.with_dirichlet(tag, |_| 0.0)
+            })
+            .with_stabilization(Stabilization::Galerkin)
+            .with_source(|x| 2.0 * PI * PI * (PI * x[0]).sin() * (PI * x[1]).sin())
+            .solve()
+    }
+
+    #[test]
+    fn test_convergence_study() {
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![2, 2]).simplex_mesh();
+        for order in [1, 2] {
+            let report = ConvergenceStudy::new(&mesh, |x| (PI * x[0]).sin() * (PI * x[1]).sin())
+                .with_gradient(|x, g| {
+                    g[0] = PI * (PI * x[0]).cos() * (PI * x[1]).sin();
+                    g[1] = PI * (PI * x[0]).sin() * (PI * x[1]).cos();
+                })
+                .with_sweep(Sweep::Refinements { levels: 4, order })
+                .run(poisson);
+            let l2 = report.rates(ErrorNorm::L2).unwrap();
+            let h1 = report.rates(ErrorNorm::H1Seminorm).unwrap();
+            assert!(
+                (l2.last().unwrap() - (order + 1) as f64).abs() < 0.15,
+                "Wrong L2 rate {:?}",
+                l2
+            );
+            assert!(
+                (h1.last().unwrap() - order as f64).abs() < 0.15,
+                "Wrong H1 rate {:?}",
+                h1
+            );
+        }
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_convergence_report() {
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![4, 4]).simplex_mesh();
+        let report = ConvergenceStudy::new(&mesh, |x| (PI * x[0]).sin() * (PI * x[1]).sin())
+            .with_sweep(Sweep::Orders(vec![1, 2, 3]))
+            .run(poisson);
+        let errors: Vec<f64> = report
+            .levels()
+            .iter()
+            .map(|l| l.error(ErrorNorm::L2).unwrap())
+            .collect();
+        assert!(
+            errors.windows(2).all(|e| e[1] < 0.2 * e[0]),
+            "Errors should decrease fast with the order {:?}",
+            errors
+        );
+        assert!(report.rates(ErrorNorm::H1Seminorm).is_none(), "No gradient");
+        let csv = report.to_csv();
+        assert_eq!(csv.lines().count(), 4, "Wrong number of rows");
+        assert!(
+            csv.lines().nth(1).unwrap().ends_with(",,,"),
+            "First row has no rates"
+        );
+        let json = report.to_json();
+        assert!(
+            json.contains("\"h1\": null"),
+            "Missing H1 fit should be null"
+        );
+        assert_eq!(
+            json.matches("\"level\"").count(),
+            3,
+            "Wrong number of levels"
+        );
+    }
+}
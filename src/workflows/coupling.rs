@@ -0,0 +1,559 @@
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::logging::{debug, info, Span};
+use crate::solvers::dense::Cholesky;
+use crate::solvers::krylov::{dot, norm};
+use crate::solvers::stopping_criterion::{StopReason, StoppingCriterion};
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+//--------------------------------------------------------------------------------------------------
+// # Traits
+//--------------------------------------------------------------------------------------------------
+
+/// Sub-workflow of a partitioned simulation exchanging values on the interface with another one,
+/// for instance temperatures and heat fluxes in conjugate heat transfer or displacements and
+/// tractions in fluid structure interaction
+pub trait CouplingParticipant {
+    /// Solve the time step from time to time + time_step starting from the state of the last
+    /// accepted step, given the interface values received from the other participant, and return
+    /// the interface values to send back. It is called once per coupling iteration and should not
+    /// advance the state of the participant.
+    fn solve_step(&mut self, time: f64, time_step: f64, received: &[f64]) -> Vec<f64>;
+
+    /// Keep the state of the last solve as the start of the next time step
+    fn accept_step(&mut self) {}
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Acceleration of the fixed point iterations of a coupling
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CouplingAcceleration {
+    /// Constant under relaxation by the given factor, 1 giving plain fixed point iterations
+    Constant(f64),
+    /// Aitken dynamic relaxation starting from the given factor at every time step
+    Aitken(f64),
+    /// Anderson acceleration mixing the given number of past iterations and relaxing by a factor
+    Anderson { depth: usize, relaxation: f64 },
+}
+
+/// Partitioned coupling of two participants by Gauss-Seidel iterations on the interface
+///
+/// Every time step iterates on the values x the second participant sends to the first: the first
+/// participant solves with x and sends y to the second, which answers x̃, and x is updated from the
+/// residual x̃ - x by the acceleration until the norm of the residual falls below the largest of the
+/// absolute tolerance and the relative tolerance times the norm of the first x̃ of the step. Both
+/// participants then accept the step, or when the iterations fail the last iterate is accepted and
+/// the failure reported. By default Aitken relaxation starting from 0.5 is used, the relative
+/// tolerance is 1e-8, the absolute tolerance 1e-12 and at most 50 iterations are done per step.
+pub struct Coupling<First, Second> {
+    first: First,
+    second: Second,
+    acceleration: CouplingAcceleration,
+    stopping: StoppingCriterion,
+}
+
+/// Outcome of a coupled run with one row per time step
+///
+/// Every row holds the index of the step, its end time, the number of coupling iterations and the
+/// norm of the last interface residual.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CouplingHistory {
+    reasons: Vec<StopReason>,
+    rows: Vec<Vec<f64>>,
+    interface: Vec<f64>,
+}
+
+impl<First, Second> Coupling<First, Second>
+where
+    First: CouplingParticipant,
+    Second: CouplingParticipant,
+{
+    /// Coupling of two participants with the default parameters
+    pub fn new(first: First, second: Second) -> Self {
+        Coupling {
+            first,
+            second,
+            acceleration: CouplingAcceleration::Aitken(0.5),
+            stopping: StoppingCriterion::new(1e-8, 1e-12, 50),
+        }
+    }
+
+    /// Set the acceleration of the fixed point iterations
+    pub fn with_acceleration(mut self, acceleration: CouplingAcceleration) -> Self {
+        match acceleration {
+            CouplingAcceleration::Constant(omega) | CouplingAcceleration::Aitken(omega) => {
+                assert!(omega > 0.0, "Relaxation factor should be positive")
+            }
+            CouplingAcceleration::Anderson { depth, relaxation } => {
+                assert!(depth > 0, "Anderson should mix at least one iteration");
+                assert!(relaxation > 0.0, "Relaxation factor should be positive");
+            }
+        }
+        self.acceleration = acceleration;
+        self
+    }
+
+    /// Set the tolerance on the interface residual relative to the interface values
+    pub fn with_relative_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_relative_tolerance(tolerance);
+        self
+    }
+
+    /// Set the tolerance on the norm of the interface residual
+    pub fn with_absolute_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_absolute_tolerance(tolerance);
+        self
+    }
+
+    /// Set the maximum number of coupling iterations per time step
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.stopping = self.stopping.with_max_iterations(max_iterations);
+        self
+    }
+
+    /// First participant
+    pub fn first(&self) -> &First {
+        &self.first
+    }
+
+    /// Second participant
+    pub fn second(&self) -> &Second {
+        &self.second
+    }
+
+    /// Participants given back
+    pub fn into_participants(self) -> (First, Second) {
+        (self.first, self.second)
+    }
+
+    /// Run the coupled participants with a fixed time step from start to end, starting from the
+    /// interface values the second participant sends at the start
+    pub fn run(
+        &mut self,
+        start: f64,
+        end: f64,
+        time_step: f64,
+        interface: Vec<f64>,
+    ) -> CouplingHistory {
+        let _span = Span::enter("coupling");
+        assert!(time_step > 0.0, "Time step should be positive");
+        let n_steps = ((end - start) / time_step - 1e-10).ceil().max(0.0) as usize;
+        let mut history = CouplingHistory {
+            reasons: Vec::with_capacity(n_steps),
+            rows: Vec::with_capacity(n_steps),
+            interface,
+        };
+        for step in 0..n_steps {
+            let time = start + step as f64 * time_step;
+            let dt = time_step.min(end - time);
+            let (reason, iterations, residual) = self.step(time, dt, &mut history.interface);
+            history.reasons.push(reason);
+            history
+                .rows
+                .push(vec![step as f64, time + dt, iterations as f64, residual]);
+        }
+        info!(
+            "Coupling ran {} steps, {} of which did not converge",
+            n_steps,
+            history.reasons.iter().filter(|r| !r.converged()).count()
+        );
+        history
+    }
+
+    // Iterate on a time step until the interface converges, returning the stop reason, the
+    // number of iterations and the last residual norm
+    fn step(&mut self, time: f64, dt: f64, x: &mut Vec<f64>) -> (StopReason, usize, f64) {
+        let mut iterations = 0;
+        let mut test = None;
+        // Previous residual and relaxation of Aitken
+        let mut aitken: Option<(Vec<f64>, f64)> = None;
+        // Past differences of the residuals and of the answers of Anderson, the newest last
+        let mut memory: VecDeque<(Vec<f64>, Vec<f64>)> = VecDeque::new();
+        let mut previous: Option<(Vec<f64>, Vec<f64>)> = None;
+        let (reason, residual_norm) = loop {
+            let y = self.first.solve_step(time, dt, x);
+            let answer = self.second.solve_step(time, dt, &y);
+            assert_eq!(
+                answer.len(),
+                x.len(),
+                "Second participant changed the size of the interface"
+            );
+            let residual: Vec<f64> = answer.iter().zip(x.iter()).map(|(a, x)| a - x).collect();
+            let residual_norm = norm(&residual);
+            let test = test.get_or_insert_with(|| self.stopping.start(norm(&answer)));
+            debug!(
+                "Coupling iteration {} at time {:e}: residual {:e}",
+                iterations, time, residual_norm
+            );
+            if let Some(reason) = test.check(iterations, residual_norm, None) {
+                *x = answer;
+                break (reason, residual_norm);
+            }
+            iterations += 1;
+            match self.acceleration {
+                CouplingAcceleration::Constant(omega) => relax(x, &residual, omega),
+                CouplingAcceleration::Aitken(initial) => {
+                    let omega = match &aitken {
+                        Some((last, omega)) => {
+                            let change: Vec<f64> =
+                                residual.iter().zip(last).map(|(r, l)| r - l).collect();
+                            let change_norm = dot(&change, &change);
+                            if change_norm > 0.0 {
+                                -omega * dot(last, &change) / change_norm
+                            } else {
+                                *omega
+                            }
+                        }
+                        None => initial,
+                    };
+                    relax(x, &residual, omega);
+                    aitken = Some((residual, omega));
+                }
+                CouplingAcceleration::Anderson { depth, relaxation } => {
+                    if let Some((last_residual, last_answer)) = &previous {
+                        if memory.len() == depth {
+                            memory.pop_front();
+                        }
+                        memory.push_back((
+                            residual
+                                .iter()
+                                .zip(last_residual)
+                                .map(|(r, l)| r - l)
+                                .collect(),
+                            answer.iter().zip(last_answer).map(|(a, l)| a - l).collect(),
+                        ));
+                    }
+                    let mixing = anderson_mixing(&mut memory, &residual);
+                    relax(x, &residual, relaxation);
+                    // x + β r - (ΔX + β ΔR) γ with ΔX = ΔX̃ - ΔR
+                    for ((dr, da), gamma) in memory.iter().zip(&mixing) {
+                        for i in 0..x.len() {
+                            x[i] -= gamma * (da[i] + (relaxation - 1.0) * dr[i]);
+                        }
+                    }
+                    previous = Some((residual, answer));
+                }
+            }
+        };
+        self.first.accept_step();
+        self.second.accept_step();
+        (reason, iterations, residual_norm)
+    }
+}
+
+impl CouplingHistory {
+    /// Reasons the iterations of every time step stopped
+    pub fn reasons(&self) -> &[StopReason] {
+        &self.reasons
+    }
+
+    /// Whether the iterations of every time step converged
+    pub fn converged(&self) -> bool {
+        self.reasons.iter().all(|r| r.converged())
+    }
+
+    /// Total number of coupling iterations over the time steps
+    pub fn iterations(&self) -> usize {
+        self.rows.iter().map(|row| row[2] as usize).sum()
+    }
+
+    /// Interface values sent by the second participant at the end of the run
+    pub fn interface(&self) -> &[f64] {
+        &self.interface
+    }
+
+    /// Names of the columns of the history
+    pub fn columns(&self) -> Vec<String> {
+        ["step", "time", "iterations", "residual"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect()
+    }
+
+    /// Rows of the history, one per time step
+    pub fn rows(&self) -> &[Vec<f64>] {
+        &self.rows
+    }
+
+    /// Comma separated table with a header and one row per time step
+    pub fn to_csv(&self) -> String {
+        let mut csv = self.columns().join(",");
+        csv.push('\n');
+        for row in &self.rows {
+            let values: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+            writeln!(csv, "{}", values.join(",")).unwrap();
+        }
+        csv
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Move the iterate along the residual by the relaxation factor
+fn relax(x: &mut [f64], residual: &[f64], omega: f64) {
+    x.iter_mut()
+        .zip(residual)
+        .for_each(|(x, r)| *x += omega * r);
+}
+
+// Coefficients of the past residual differences best approximating the residual in the least
+// squares sense, the oldest differences being dropped until the normal equations are definite
+fn anderson_mixing(memory: &mut VecDeque<(Vec<f64>, Vec<f64>)>, residual: &[f64]) -> Vec<f64> {
+    while !memory.is_empty() {
+        let m = memory.len();
+        let mut normal = vec![0.0; m * m];
+        for i in 0..m {
+            for j in 0..m {
+                normal[i * m + j] = dot(&memory[i].0, &memory[j].0);
+            }
+        }
+        let projection: Vec<f64> = memory.iter().map(|(dr, _)| dot(dr, residual)).collect();
+        if let Some(factor) = Cholesky::new(&DataHold::new(normal, [m, m])) {
+            if factor.condition_estimate() < 1e12 {
+                let mut gamma = vec![0.0; m];
+                factor.solve(&projection, &mut gamma);
+                return gamma;
+            }
+        }
+        memory.pop_front();
+    }
+    Vec::new()
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    // Description of an invalid input with the construction it should make panic
+    type Invalid<'a> = (&'a str, Box<dyn Fn() + 'a>);
+
+    // Implicit Euler step of u' = gain x - u sending u, the state being accepted separately
+    struct Decay {
+        gain: f64,
+        state: f64,
+        next: f64,
+        accepted: usize,
+    }
+
+    impl Decay {
+        fn new(gain: f64) -> Self {
+            Decay {
+                gain,
+                state: 1.0,
+                next: 1.0,
+                accepted: 0,
+            }
+        }
+    }
+
+    impl CouplingParticipant for Decay {
+        fn solve_step(&mut self, _time: f64, time_step: f64, received: &[f64]) -> Vec<f64> {
+            self.next = (self.state + time_step * self.gain * received[0]) / (1.0 + time_step);
+            vec![self.next]
+        }
+
+        fn accept_step(&mut self) {
+            self.state = self.next;
+            self.accepted += 1;
+        }
+    }
+
+    // Participant answering twice the interface values it receives
+    struct Doubling;
+
+    impl CouplingParticipant for Doubling {
+        fn solve_step(&mut self, _time: f64, _time_step: f64, received: &[f64]) -> Vec<f64> {
+            received.iter().chain(received).map(|r| 2.0 * r).collect()
+        }
+    }
+
+    // Monolithic implicit Euler of u' = v - u, v' = -8 u - v with u(0) = v(0) = 1
+    fn monolithic(time_steps: &[f64]) -> (f64, f64) {
+        let (mut u, mut v) = (1.0, 1.0);
+        for dt in time_steps {
+            // ((1 + dt) u - dt v, 8 dt u + (1 + dt) v) = (u_n, v_n)
+            let next_u = ((1.0 + dt) * u + dt * v) / ((1.0 + dt) * (1.0 + dt) + 8.0 * dt * dt);
+            v = (v - 8.0 * dt * next_u) / (1.0 + dt);
+            u = next_u;
+        }
+        (u, v)
+    }
+
+    #[test]
+    fn test_coupling_accelerations() {
+        let (u, v) = monolithic(&[1.0; 3]);
+        for acceleration in [
+            CouplingAcceleration::Aitken(0.5),
+            CouplingAcceleration::Anderson {
+                depth: 3,
+                relaxation: 0.5,
+            },
+        ] {
+            let mut coupling = Coupling::new(Decay::new(1.0), Decay::new(-8.0))
+                .with_acceleration(acceleration)
+                .with_relative_tolerance(1e-12);
+            let history = coupling.run(0.0, 3.0, 1.0, vec![1.0]);
+            assert!(history.converged(), "{:?} did not converge", acceleration);
+            assert_eq!(history.rows().len(), 3, "Wrong number of time steps");
+            assert_eq!(coupling.first().accepted, 3, "Steps were not accepted");
+            assert!(
+                (coupling.first().state - u).abs() < 1e-10
+                    && (coupling.second().state - v).abs() < 1e-10
+                    && (history.interface()[0] - v).abs() < 1e-10,
+                "{:?} did not reach the monolithic solution",
+                acceleration
+            );
+        }
+        // The fixed point map has slope -2, plain iterations diverge
+        let mut coupling = Coupling::new(Decay::new(1.0), Decay::new(-8.0))
+            .with_acceleration(CouplingAcceleration::Constant(1.0))
+            .with_max_iterations(20);
+        let history = coupling.run(0.0, 1.0, 1.0, vec![1.0]);
+        assert_eq!(
+            history.reasons(),
+            &[StopReason::MaxIterations],
+            "Plain iterations should not converge"
+        );
+        assert!(
+            history
+                .to_csv()
+                .starts_with("step,time,iterations,residual\n"),
+            "Wrong header"
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_coupling_relaxation() {
+        // Under-relaxation makes the plain fixed point converge, more slowly than a secant mixing,
+        // the last step being shortened to end at 2.5
+        let (u, v) = monolithic(&[1.0, 1.0, 0.5]);
+        let mut iterations = Vec::new();
+        for acceleration in [
+            CouplingAcceleration::Constant(1.0 / 3.0),
+            CouplingAcceleration::Constant(0.25),
+            CouplingAcceleration::Anderson {
+                depth: 1,
+                relaxation: 1.0,
+            },
+        ] {
+            let mut coupling = Coupling::new(Decay::new(1.0), Decay::new(-8.0))
+                .with_acceleration(acceleration)
+                .with_relative_tolerance(1e-12);
+            let history = coupling.run(0.0, 2.5, 1.0, vec![1.0]);
+            assert!(history.converged(), "{:?} did not converge", acceleration);
+            let times: Vec<f64> = history.rows().iter().map(|row| row[1]).collect();
+            assert_eq!(times, [1.0, 2.0, 2.5], "Wrong times of the steps");
+            assert_eq!(
+                history.iterations(),
+                history.rows().iter().map(|row| row[2] as usize).sum(),
+                "Wrong total of iterations"
+            );
+            assert_eq!(
+                history.to_csv().lines().count(),
+                4,
+                "One line per step after the header"
+            );
+            let (first, second) = coupling.into_participants();
+            assert!(
+                (first.state - u).abs() < 1e-10 && (second.state - v).abs() < 1e-10,
+                "{:?} did not reach the monolithic solution",
+                acceleration
+            );
+            assert_eq!(second.accepted, 3, "Steps were not accepted");
+            iterations.push(history.iterations());
+        }
+        assert!(
+            iterations[2] < iterations[0] && iterations[0] < iterations[1],
+            "Wrong iterations {:?}",
+            iterations
+        );
+        // A loose absolute tolerance stops first
+        let loose = Coupling::new(Decay::new(1.0), Decay::new(-8.0))
+            .with_acceleration(CouplingAcceleration::Constant(0.25))
+            .with_relative_tolerance(0.0)
+            .with_absolute_tolerance(1e-2)
+            .run(0.0, 2.5, 1.0, vec![1.0]);
+        assert!(loose.converged(), "Loose coupling did not converge");
+        assert!(
+            loose.iterations() < iterations[1],
+            "A loose tolerance should take fewer iterations"
+        );
+        assert_eq!(
+            loose.columns(),
+            ["step", "time", "iterations", "residual"],
+            "Wrong columns"
+        );
+        assert!(
+            loose.rows().iter().all(|row| row[3] <= 1e-2),
+            "Residuals should meet the tolerance"
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_coupling_invalid() {
+        let coupling = || Coupling::new(Decay::new(1.0), Decay::new(-8.0));
+        let cases: [Invalid; 6] = [
+            (
+                "a zero relaxation",
+                Box::new(|| {
+                    coupling().with_acceleration(CouplingAcceleration::Constant(0.0));
+                }),
+            ),
+            (
+                "a negative Aitken relaxation",
+                Box::new(|| {
+                    coupling().with_acceleration(CouplingAcceleration::Aitken(-1.0));
+                }),
+            ),
+            (
+                "an Anderson mixing of no iteration",
+                Box::new(|| {
+                    coupling().with_acceleration(CouplingAcceleration::Anderson {
+                        depth: 0,
+                        relaxation: 1.0,
+                    });
+                }),
+            ),
+            (
+                "a zero Anderson relaxation",
+                Box::new(|| {
+                    coupling().with_acceleration(CouplingAcceleration::Anderson {
+                        depth: 2,
+                        relaxation: 0.0,
+                    });
+                }),
+            ),
+            (
+                "a zero time step",
+                Box::new(|| {
+                    coupling().run(0.0, 1.0, 0.0, vec![1.0]);
+                }),
+            ),
+            (
+                "a participant changing the size of the interface",
+                Box::new(|| {
+                    Coupling::new(Doubling, Doubling).run(0.0, 1.0, 1.0, vec![1.0]);
+                }),
+            ),
+        ];
+        for (case, build) in cases {
+            assert!(
+                panic::catch_unwind(AssertUnwindSafe(build)).is_err(),
+                "Coupling accepted {}",
+                case
+            );
+        }
+    }
+}
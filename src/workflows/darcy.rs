@@ -0,0 +1,624 @@
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::logging::{info, Span};
+use crate::discretizations::cell_mapping::CellMapping;
+use crate::discretizations::conforming_values::HDivValues;
+use crate::discretizations::constraints::AffineConstraints;
+use crate::discretizations::dof_map::DofMap;
+use crate::discretizations::facets::Facets;
+#[cfg(hosted)]
+use crate::discretizations::function::Function;
+#[cfg(hosted)]
+use crate::discretizations::function_space::FunctionSpace;
+#[cfg(hosted)]
+use crate::discretizations::io::vtk;
+use crate::discretizations::mesh::Mesh;
+use crate::discretizations::sparsity::SparsityPattern;
+use crate::solvers::sparse_direct::SparseLU;
+#[cfg(hosted)]
+use crate::spaces::lagrange::LagrangeElement;
+use crate::spaces::quadrature::QuadratureRule;
+use crate::spaces::raviart_thomas::RaviartThomasElement;
+#[cfg(hosted)]
+use std::io::Result;
+#[cfg(hosted)]
+use std::path::Path;
+
+// Scalar field of the physical coordinates
+type Field<'a> = Box<dyn Fn(&[f64]) -> f64 + 'a>;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Steady Darcy flow u = -(k / μ) grad(p), div(u) = f in a porous medium on lowest order
+/// Raviart-Thomas velocities and piecewise constant pressures (RT0-P0)
+///
+/// The mixed form ∫ (μ / k) u·v - ∫ p div(v) = -∫ p_D v·n, -∫ q div(u) = -∫ f q is solved by the
+/// sparse LU with one velocity dof per facet, the flux through the facet along the outward normal
+/// of its first cell in Facets, and one pressure dof per cell. Pressures are imposed weakly on
+/// tagged boundary facets and normal velocities u·n strongly on others, the rest of the boundary
+/// being impermeable, so that some pressure has to be imposed. The permeability k is constant on
+/// the cells of every tag with a default value elsewhere, 1 by default as the viscosity μ, and
+/// there is no source by default.
+pub struct DarcyFlow<'a> {
+    mesh: &'a Mesh,
+    permeability: f64,
+    permeabilities: Vec<(usize, f64)>,
+    viscosity: f64,
+    source: Option<Field<'a>>,
+    pressures: Vec<(usize, Field<'a>)>,
+    normal_velocities: Vec<(usize, Field<'a>)>,
+}
+
+/// Facet fluxes and cell pressures of a Darcy flow
+pub struct DarcySolution<'a> {
+    mesh: &'a Mesh,
+    facets: Facets,
+    fluxes: Vec<f64>,
+    pressures: Vec<f64>,
+    permeabilities: Vec<f64>,
+}
+
+impl<'a> DarcyFlow<'a> {
+    /// Darcy flow on a simplicial mesh with the default parameters
+    pub fn new(mesh: &'a Mesh) -> Self {
+        let dim = mesh.geometric_dim();
+        assert!(
+            (2..=3).contains(&dim) && mesh.topological_dim() == dim,
+            "Darcy flow needs a 2D or 3D mesh of full dimension"
+        );
+        DarcyFlow {
+            mesh,
+            permeability: 1.0,
+            permeabilities: Vec::new(),
+            viscosity: 1.0,
+            source: None,
+            pressures: Vec::new(),
+            normal_velocities: Vec::new(),
+        }
+    }
+
+    /// Set the permeability of the cells without a tag of their own
+    pub fn with_default_permeability(mut self, permeability: f64) -> Self {
+        assert!(permeability > 0.0, "Permeability should be positive");
+        self.permeability = permeability;
+        self
+    }
+
+    /// Set the permeability of the cells carrying the tag
+    pub fn with_permeability(mut self, tag: usize, permeability: f64) -> Self {
+        assert!(permeability > 0.0, "Permeability should be positive");
+        self.permeabilities.push((tag, permeability));
+        self
+    }
+
+    /// Set the dynamic viscosity μ of the fluid
+    pub fn with_viscosity(mut self, viscosity: f64) -> Self {
+        assert!(viscosity > 0.0, "Viscosity should be positive");
+        self.viscosity = viscosity;
+        self
+    }
+
+    /// Set the volumetric source f as a function of the physical coordinates
+    pub fn with_source<Source>(mut self, source: Source) -> Self
+    where
+        Source: Fn(&[f64]) -> f64 + 'a,
+    {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// Impose the pressure given as a function of the physical coordinates on the boundary facets
+    /// carrying the tag
+    pub fn with_pressure<Pressure>(mut self, tag: usize, pressure: Pressure) -> Self
+    where
+        Pressure: Fn(&[f64]) -> f64 + 'a,
+    {
+        self.pressures.push((tag, Box::new(pressure)));
+        self
+    }
+
+    /// Impose the outward normal velocity given as a function of the physical coordinates on the
+    /// boundary facets carrying the tag, negative for an inflow
+    pub fn with_normal_velocity<Velocity>(mut self, tag: usize, velocity: Velocity) -> Self
+    where
+        Velocity: Fn(&[f64]) -> f64 + 'a,
+    {
+        self.normal_velocities.push((tag, Box::new(velocity)));
+        self
+    }
+
+    /// Permeability of every cell from its tag
+    pub fn cell_permeabilities(&self) -> Vec<f64> {
+        (0..self.mesh.n_cells())
+            .map(|cell| {
+                let tag = self.mesh.cell_tag(cell);
+                self.permeabilities
+                    .iter()
+                    .rev()
+                    .find(|(t, _)| Some(*t) == tag)
+                    .map_or(self.permeability, |(_, k)| *k)
+            })
+            .collect()
+    }
+
+    /// Solve for the fluxes and the pressures
+    pub fn solve(&self) -> DarcySolution<'a> {
+        let _span = Span::enter("darcy flow");
+        assert!(
+            !self.pressures.is_empty(),
+            "Darcy flow needs a pressure imposed on some boundary"
+        );
+        let mesh = self.mesh;
+        let dim = mesh.geometric_dim();
+        let facets = Facets::new(mesh);
+        let n_facets = facets.n_facets();
+        let n_dofs = n_facets + mesh.n_cells();
+        // Facet velocities followed by the cell pressure
+        let facet_dofs = DofMap::facets(mesh, &facets);
+        let cell_dofs: Vec<usize> = (0..mesh.n_cells())
+            .flat_map(|cell| {
+                let dofs = facet_dofs.cell_dofs(cell);
+                dofs.iter().copied().chain(std::iter::once(n_facets + cell))
+            })
+            .collect();
+        let dof_map = DofMap::new(DataHold::new(cell_dofs, [mesh.n_cells(), dim + 2]), n_dofs);
+        let mut rhs = vec![0.0; n_dofs];
+        let mut constraints = AffineConstraints::new(n_dofs);
+        for facet in facets.boundary_facets() {
+            let vertices = facets.facet_vertices(facet);
+            let tag = mesh.facet_tag(vertices);
+            if let Some((_, pressure)) = self.pressures.iter().find(|(t, _)| Some(*t) == tag) {
+                // The shape function of the facet has a unit outward flux
+                rhs[facet] -= facet_mean(mesh, vertices, pressure);
+                continue;
+            }
+            let flux = match self.normal_velocities.iter().find(|(t, _)| Some(*t) == tag) {
+                Some((_, velocity)) => {
+                    facet_mean(mesh, vertices, velocity) * facet_measure(mesh, vertices)
+                }
+                None => 0.0,
+            };
+            constraints.add_dirichlet(facet, flux);
+        }
+        constraints.close();
+        let mut matrix =
+            SparsityPattern::from_dofmap_and_constraints(&dof_map, &constraints).to_csr(0.0);
+        let permeabilities = self.cell_permeabilities();
+        let element = RaviartThomasElement::new(dim);
+        let mut values = HDivValues::new(&element, &QuadratureRule::simplex(dim, 2));
+        let n = dim + 2;
+        let mut local_matrix = vec![0.0; n * n];
+        let mut local_vector = vec![0.0; n];
+        for (cell, permeability) in permeabilities.iter().enumerate() {
+            values.reinit(mesh, &facets, cell);
+            local_matrix.iter_mut().for_each(|v| *v = 0.0);
+            local_vector.iter_mut().for_each(|v| *v = 0.0);
+            let resistance = self.viscosity / permeability;
+            for q in 0..values.n_points() {
+                let w = values.weight(q);
+                for i in 0..=dim {
+                    let vi = values.shape_value(q, i);
+                    for j in 0..=dim {
+                        let vj = values.shape_value(q, j);
+                        let dot: f64 = vi.iter().zip(vj).map(|(a, b)| a * b).sum();
+                        local_matrix[i * n + j] += resistance * dot * w;
+                    }
+                }
+                if let Some(source) = &self.source {
+                    local_vector[dim + 1] -= source(values.point(q)) * w;
+                }
+            }
+            let volume: f64 = (0..values.n_points()).map(|q| values.weight(q)).sum();
+            for i in 0..=dim {
+                let divergence = values.shape_divergence(i) * volume;
+                local_matrix[i * n + dim + 1] -= divergence;
+                local_matrix[(dim + 1) * n + i] -= divergence;
+            }
+            constraints.distribute_local_to_global(
+                dof_map.cell_dofs(cell),
+                Some(&local_matrix),
+                Some(&local_vector),
+                Some(&mut matrix),
+                Some(&mut rhs),
+            );
+        }
+        let lu = SparseLU::new(&matrix).expect("Darcy system is singular");
+        let mut solution = vec![0.0; n_dofs];
+        lu.solve(&rhs, &mut solution);
+        constraints.distribute(&mut solution);
+        let pressures = solution.split_off(n_facets);
+        info!(
+            "Darcy flow solved on {} facets and {} cells",
+            n_facets,
+            mesh.n_cells()
+        );
+        DarcySolution {
+            mesh,
+            facets,
+            fluxes: solution,
+            pressures,
+            permeabilities,
+        }
+    }
+}
+
+impl<'a> DarcySolution<'a> {
+    /// Fluxes through the facets of Facets along the outward normals of their first cells
+    pub fn fluxes(&self) -> &[f64] {
+        &self.fluxes
+    }
+
+    /// Pressures of the cells
+    pub fn pressures(&self) -> &[f64] {
+        &self.pressures
+    }
+
+    /// Permeabilities of the cells
+    pub fn permeabilities(&self) -> &[f64] {
+        &self.permeabilities
+    }
+
+    /// Total flux through the facets carrying a tag, along the outward normal on the boundary and
+    /// of the first cell of every facet inside the mesh
+    pub fn flux(&self, tag: usize) -> f64 {
+        (0..self.facets.n_facets())
+            .filter(|f| self.mesh.facet_tag(self.facets.facet_vertices(*f)) == Some(tag))
+            .map(|f| self.fluxes[f])
+            .sum()
+    }
+
+    /// Velocity at a physical point, None outside of the mesh
+    pub fn velocity(&self, point: &[f64]) -> Option<Vec<f64>> {
+        let (cell, reference) = self.mesh.locate(point)?;
+        let mut velocity = vec![0.0; self.mesh.geometric_dim()];
+        self.velocity_in_cell(cell, &reference, &mut velocity);
+        Some(velocity)
+    }
+
+    /// Velocity in a cell at reference coordinates
+    pub fn velocity_in_cell(&self, cell: usize, reference: &[f64], velocity: &mut [f64]) {
+        let mesh = self.mesh;
+        let dim = mesh.geometric_dim();
+        let element = RaviartThomasElement::new(dim);
+        let mut shape_values = vec![0.0; element.n_dofs() * dim];
+        element.values(reference, &mut shape_values);
+        let mapping = CellMapping::new(mesh, cell);
+        let mut value = vec![0.0; dim];
+        velocity.iter_mut().for_each(|v| *v = 0.0);
+        let vertices = mesh.cell(cell);
+        for (local, shape) in shape_values.chunks(dim).enumerate() {
+            let facet: Vec<usize> = vertices
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != local)
+                .map(|(_, v)| *v)
+                .collect();
+            let facet = self
+                .facets
+                .facet_index(&facet)
+                .expect("Facets do not belong to the mesh");
+            // Fluxes are oriented by the first cell of the facet
+            let sign = if self.facets.facet_cells(facet)[0].0 == cell {
+                1.0
+            } else {
+                -1.0
+            };
+            mapping.contravariant_piola(shape, &mut value);
+            for (v, s) in velocity.iter_mut().zip(&value) {
+                *v += sign * self.fluxes[facet] * s;
+            }
+        }
+    }
+
+    /// Write the pressure, the velocity and the permeability as discontinuous linear fields to a
+    /// legacy VTK file
+    #[cfg(hosted)]
+    pub fn save_vtk<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mesh = self.mesh;
+        let dim = mesh.geometric_dim();
+        let scalar = FunctionSpace::discontinuous(mesh, LagrangeElement::new(dim, 1));
+        let vector = FunctionSpace::discontinuous_vector(mesh, LagrangeElement::new(dim, 1), dim);
+        let mut pressure = Function::new(&scalar);
+        let mut permeability = Function::new(&scalar);
+        let mut velocity = Function::new(&vector);
+        let mut reference = vec![0.0; dim];
+        let mut value = vec![0.0; dim];
+        for cell in 0..mesh.n_cells() {
+            // The linear velocity of the cell is exact at its vertices
+            for (local, dof) in scalar.dof_map().cell_dofs(cell).iter().enumerate() {
+                reference.iter_mut().for_each(|r| *r = 0.0);
+                if local > 0 {
+                    reference[local - 1] = 1.0;
+                }
+                self.velocity_in_cell(cell, &reference, &mut value);
+                for (c, v) in value.iter().enumerate() {
+                    velocity.values_mut()[vector.dof(*dof, c)] = *v;
+                }
+                pressure.values_mut()[*dof] = self.pressures[cell];
+                permeability.values_mut()[*dof] = self.permeabilities[cell];
+            }
+        }
+        vtk::save_vtk(
+            path,
+            mesh,
+            &[
+                ("pressure", &pressure),
+                ("velocity", &velocity),
+                ("permeability", &permeability),
+            ],
+        )
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Mean of a field over a facet by the quadrature of degree 2
+fn facet_mean(mesh: &Mesh, vertices: &[usize], field: &Field) -> f64 {
+    let dim = mesh.geometric_dim();
+    let quadrature = QuadratureRule::simplex(dim - 1, 2);
+    let origin = mesh.vertex(vertices[0]);
+    let mut point = vec![0.0; dim];
+    let mut sum = 0.0;
+    for q in 0..quadrature.n_points() {
+        point.copy_from_slice(origin);
+        for (k, xi) in quadrature.point(q).iter().enumerate() {
+            let vertex = mesh.vertex(vertices[k + 1]);
+            for c in 0..dim {
+                point[c] += xi * (vertex[c] - origin[c]);
+            }
+        }
+        sum += quadrature.weights()[q] * field(&point);
+    }
+    sum / quadrature.weights().iter().sum::<f64>()
+}
+
+// Length or area of a facet from the Gram determinant of its edges
+fn facet_measure(mesh: &Mesh, vertices: &[usize]) -> f64 {
+    let origin = mesh.vertex(vertices[0]);
+    let edges: Vec<Vec<f64>> = vertices[1..]
+        .iter()
+        .map(|v| {
+            mesh.vertex(*v)
+                .iter()
+                .zip(origin)
+                .map(|(x, o)| x - o)
+                .collect()
+        })
+        .collect();
+    let dot = |a: &[f64], b: &[f64]| -> f64 { a.iter().zip(b).map(|(x, y)| x * y).sum() };
+    match edges.len() {
+        1 => dot(&edges[0], &edges[0]).sqrt(),
+        _ => {
+            let (aa, bb, ab) = (
+                dot(&edges[0], &edges[0]),
+                dot(&edges[1], &edges[1]),
+                dot(&edges[0], &edges[1]),
+            );
+            0.5 * (aa * bb - ab * ab).sqrt()
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::test_meshes::unit_square_grid;
+    use std::panic::{self, AssertUnwindSafe};
+
+    // Description of an invalid input with the construction it should make panic
+    type Invalid<'a> = (&'a str, Box<dyn Fn() + 'a>);
+
+    #[test]
+    fn test_darcy_flow() {
+        // Two layers in series with a unit pressure drop: the velocity (q, 0) is uniform with
+        // q = 1 / (0.5 / 1 + 0.5 / 0.25) and the pressure is piecewise linear, both reproduced
+        // exactly with cell pressures at the means
+        let mut mesh = unit_square_grid(4);
+        mesh.tag_cells(1, |x| x[0] > 0.5);
+        let flow = DarcyFlow::new(&mesh)
+            .with_permeability(1, 0.25)
+            .with_pressure(1, |_| 1.0)
+            .with_pressure(2, |_| 0.0);
+        let solution = flow.solve();
+        let q = 0.4;
+        assert!(
+            (solution.flux(2) - q).abs() < 1e-12 && (solution.flux(1) + q).abs() < 1e-12,
+            "Wrong fluxes through the sides {} and {}",
+            solution.flux(1),
+            solution.flux(2)
+        );
+        assert!(
+            solution.flux(3).abs() < 1e-12,
+            "The bottom should be impermeable"
+        );
+        let velocity = solution.velocity(&[0.7, 0.3]).unwrap();
+        assert!(
+            (velocity[0] - q).abs() < 1e-12 && velocity[1].abs() < 1e-12,
+            "Wrong velocity {:?}",
+            velocity
+        );
+        let exact = |x: f64| {
+            if x < 0.5 {
+                1.0 - q * x
+            } else {
+                1.0 - 0.5 * q - 4.0 * q * (x - 0.5)
+            }
+        };
+        for (cell, pressure) in solution.pressures().iter().enumerate() {
+            let centroid = mesh
+                .cell(cell)
+                .iter()
+                .map(|v| mesh.vertex(*v)[0])
+                .sum::<f64>()
+                / 3.0;
+            assert!(
+                (pressure - exact(centroid)).abs() < 1e-12,
+                "Wrong pressure {} in cell {}",
+                pressure,
+                cell
+            );
+        }
+        // A source of 1 with impermeable sides leaves by the open sides
+        let solution = DarcyFlow::new(&mesh)
+            .with_source(|_| 1.0)
+            .with_pressure(1, |_| 0.0)
+            .with_pressure(2, |_| 0.0)
+            .solve();
+        assert!(
+            (solution.flux(1) + solution.flux(2) - 1.0).abs() < 1e-12,
+            "Produced fluid should leave the domain"
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_darcy_flow_options() {
+        // An inflow of 0.5 through the left side drives the uniform velocity (0.5, 0), so that the
+        // pressure 1 - x has the gradient -μ u / k
+        let mut mesh = unit_square_grid(4);
+        mesh.tag_cells(1, |x| x[1] > 0.5);
+        let flow = DarcyFlow::new(&mesh)
+            .with_default_permeability(2.0)
+            .with_permeability(1, 0.5)
+            .with_permeability(1, 2.0)
+            .with_viscosity(4.0)
+            .with_normal_velocity(1, |_| -0.5)
+            .with_pressure(2, |_| 0.0);
+        let permeabilities = flow.cell_permeabilities();
+        assert_eq!(
+            permeabilities.len(),
+            mesh.n_cells(),
+            "One permeability per cell"
+        );
+        assert!(
+            permeabilities.iter().all(|k| *k == 2.0),
+            "The last permeability of a tag should apply"
+        );
+        let solution = flow.solve();
+        assert_eq!(
+            solution.permeabilities(),
+            &permeabilities[..],
+            "Wrong permeabilities of the solution"
+        );
+        assert_eq!(
+            solution.fluxes().len(),
+            Facets::new(&mesh).n_facets(),
+            "One flux per facet"
+        );
+        assert!(
+            (solution.flux(1) + 0.5).abs() < 1e-12 && (solution.flux(2) - 0.5).abs() < 1e-12,
+            "Wrong fluxes through the sides {} and {}",
+            solution.flux(1),
+            solution.flux(2)
+        );
+        for (cell, pressure) in solution.pressures().iter().enumerate() {
+            let centroid = mesh
+                .cell(cell)
+                .iter()
+                .map(|v| mesh.vertex(*v)[0])
+                .sum::<f64>()
+                / 3.0;
+            assert!(
+                (pressure - (1.0 - centroid)).abs() < 1e-12,
+                "Wrong pressure {} in cell {}",
+                pressure,
+                cell
+            );
+        }
+        let mut velocity = vec![0.0; 2];
+        solution.velocity_in_cell(3, &[0.2, 0.3], &mut velocity);
+        assert!(
+            (velocity[0] - 0.5).abs() < 1e-12 && velocity[1].abs() < 1e-12,
+            "Wrong velocity {:?}",
+            velocity
+        );
+        assert!(
+            solution.velocity(&[1.5, 0.5]).is_none(),
+            "No velocity outside of the mesh"
+        );
+        let directory = std::env::temp_dir().join("fe2o3_test_darcy_flow");
+        std::fs::create_dir_all(&directory).unwrap();
+        solution.save_vtk(directory.join("darcy.vtk")).unwrap();
+        let vtk = std::fs::read_to_string(directory.join("darcy.vtk")).unwrap();
+        assert!(
+            ["pressure", "velocity", "permeability"]
+                .iter()
+                .all(|field| vtk.contains(field)),
+            "Missing fields"
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_darcy_flow_3d() {
+        let mesh = CartesianGrid::new(vec![0.0; 3], vec![1.0; 3], vec![2; 3]).simplex_mesh();
+        let solution = DarcyFlow::new(&mesh)
+            .with_pressure(1, |_| 1.0)
+            .with_pressure(2, |_| 0.0)
+            .solve();
+        assert!(
+            (solution.flux(2) - 1.0).abs() < 1e-12 && (solution.flux(1) + 1.0).abs() < 1e-12,
+            "Wrong fluxes through the sides {} and {}",
+            solution.flux(1),
+            solution.flux(2)
+        );
+        let velocity = solution.velocity(&[0.3, 0.6, 0.2]).unwrap();
+        assert!(
+            (velocity[0] - 1.0).abs() < 1e-12 && velocity[1..].iter().all(|v| v.abs() < 1e-12),
+            "Wrong velocity {:?}",
+            velocity
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_darcy_flow_invalid() {
+        let mesh = unit_square_grid(4);
+        let surface = Mesh::new(
+            DataHold::new(vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0], [3, 3]),
+            DataHold::new(vec![0, 1, 2], [1, 3]),
+        );
+        let cases: [Invalid; 5] = [
+            (
+                "a surface mesh",
+                Box::new(|| drop(DarcyFlow::new(&surface))),
+            ),
+            (
+                "a zero permeability",
+                Box::new(|| drop(DarcyFlow::new(&mesh).with_default_permeability(0.0))),
+            ),
+            (
+                "a negative tag permeability",
+                Box::new(|| drop(DarcyFlow::new(&mesh).with_permeability(1, -1.0))),
+            ),
+            (
+                "a zero viscosity",
+                Box::new(|| drop(DarcyFlow::new(&mesh).with_viscosity(0.0))),
+            ),
+            (
+                "no imposed pressure",
+                Box::new(|| {
+                    DarcyFlow::new(&mesh)
+                        .with_normal_velocity(1, |_| -1.0)
+                        .solve();
+                }),
+            ),
+        ];
+        for (case, build) in cases {
+            assert!(
+                panic::catch_unwind(AssertUnwindSafe(build)).is_err(),
+                "Darcy flow accepted {}",
+                case
+            );
+        }
+    }
+}
@@ -0,0 +1,789 @@
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::core::logging::Span;
+use crate::discretizations::cell_values::CoordinateSystem;
+use crate::discretizations::constraints::AffineConstraints;
+use crate::discretizations::facet_values::FacetValues;
+use crate::discretizations::facets::Facets;
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+#[cfg(hosted)]
+use crate::discretizations::io::vtk;
+use crate::discretizations::mixed::{MixedAssembler, MixedSpace};
+use crate::discretizations::recovery::recover_gradient;
+use crate::discretizations::sparsity::SparsityPattern;
+use crate::solvers::config::{MethodConfig, SolverConfig};
+use crate::spaces::lagrange::LagrangeElement;
+use crate::spaces::quadrature::QuadratureRule;
+#[cfg(hosted)]
+use std::io::Result;
+#[cfg(hosted)]
+use std::path::Path;
+
+// Vector field filling its components at the physical coordinates
+type VectorField<'a> = Box<dyn Fn(&[f64], &mut [f64]) + 'a>;
+
+// Names of the directions in the names of the tensor components
+pub(crate) const AXES: [char; 3] = ['x', 'y', 'z'];
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Isotropic linear elastic material given by its Young modulus E and Poisson ratio ν
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IsotropicMaterial {
+    young_modulus: f64,
+    poisson_ratio: f64,
+}
+
+/// Static linear elasticity -div(σ(u)) = f with Hooke's law σ = λ tr(ε) I + 2 μ ε on a continuous
+/// vector space of the dimension of the mesh
+///
+/// Two dimensional problems are in plane strain, or axisymmetric on the (r, z) half plane of a body
+/// of revolution where the displacement is (u_r, u_z) and the hoop strain u_r / r enters the strain
+/// energy. Displacements, possibly of a single component
+/// (for rollers and symmetry planes), are imposed on tagged boundary facets through constraints,
+/// tractions σ n = t on others and the rest of the boundary is free. The displacement conditions
+/// have to prevent rigid motions. By default the system is solved by the sparse Cholesky
+/// factorization.
+pub struct LinearElasticity<'a> {
+    space: &'a FunctionSpace<'a>,
+    material: IsotropicMaterial,
+    body_force: Option<VectorField<'a>>,
+    tractions: Vec<(usize, VectorField<'a>)>,
+    displacements: Vec<(usize, VectorField<'a>)>,
+    component_displacements: Vec<(usize, usize, f64)>,
+    coordinate_system: CoordinateSystem,
+    solver: SolverConfig,
+}
+
+impl IsotropicMaterial {
+    /// Material of a positive Young modulus and a Poisson ratio in (-1, 1/2)
+    pub fn new(young_modulus: f64, poisson_ratio: f64) -> Self {
+        assert!(young_modulus > 0.0, "Young modulus should be positive");
+        assert!(
+            poisson_ratio > -1.0 && poisson_ratio < 0.5,
+            "Poisson ratio should be in (-1, 1/2)"
+        );
+        IsotropicMaterial {
+            young_modulus,
+            poisson_ratio,
+        }
+    }
+
+    /// Young modulus E
+    pub fn young_modulus(&self) -> f64 {
+        self.young_modulus
+    }
+
+    /// Poisson ratio ν
+    pub fn poisson_ratio(&self) -> f64 {
+        self.poisson_ratio
+    }
+
+    /// Lamé parameters λ = E ν / ((1 + ν) (1 - 2 ν)) and μ = E / (2 (1 + ν))
+    pub fn lame_parameters(&self) -> (f64, f64) {
+        let (e, nu) = (self.young_modulus, self.poisson_ratio);
+        (
+            e * nu / ((1.0 + nu) * (1.0 - 2.0 * nu)),
+            e / (2.0 * (1.0 + nu)),
+        )
+    }
+}
+
+impl<'a> LinearElasticity<'a> {
+    /// Elasticity problem on a vector space with a material and no loads
+    pub fn new(space: &'a FunctionSpace<'a>, material: IsotropicMaterial) -> Self {
+        let dim = space.mesh().geometric_dim();
+        assert!(
+            (2..=3).contains(&dim) && space.n_components() == dim && !space.is_discontinuous(),
+            "Elasticity needs a continuous vector space of the dimension of a 2D or 3D mesh"
+        );
+        LinearElasticity {
+            space,
+            material,
+            body_force: None,
+            tractions: Vec::new(),
+            displacements: Vec::new(),
+            component_displacements: Vec::new(),
+            coordinate_system: CoordinateSystem::Cartesian,
+            solver: SolverConfig::new(MethodConfig::SparseCholesky),
+        }
+    }
+
+    /// Set the body force per unit volume filling its components at the physical coordinates
+    pub fn with_body_force<Force>(mut self, force: Force) -> Self
+    where
+        Force: Fn(&[f64], &mut [f64]) + 'a,
+    {
+        self.body_force = Some(Box::new(force));
+        self
+    }
+
+    /// Apply the traction filling its components at the physical coordinates on the boundary
+    /// facets carrying the tag
+    pub fn with_traction<Traction>(mut self, tag: usize, traction: Traction) -> Self
+    where
+        Traction: Fn(&[f64], &mut [f64]) + 'a,
+    {
+        self.tractions.push((tag, Box::new(traction)));
+        self
+    }
+
+    /// Impose the displacement filling its components at the physical coordinates on the
+    /// boundary facets carrying the tag
+    pub fn with_displacement<Displacement>(mut self, tag: usize, displacement: Displacement) -> Self
+    where
+        Displacement: Fn(&[f64], &mut [f64]) + 'a,
+    {
+        self.displacements.push((tag, Box::new(displacement)));
+        self
+    }
+
+    /// Impose a value to one component of the displacement on the boundary facets carrying the
+    /// tag, the other components being free
+    pub fn with_component_displacement(mut self, tag: usize, component: usize, value: f64) -> Self {
+        assert!(
+            component < self.space.n_components(),
+            "Component out of bounds"
+        );
+        self.component_displacements.push((tag, component, value));
+        self
+    }
+
+    /// Solve the axisymmetric problem of the body of revolution of the 2-D mesh around its y axis
+    pub fn with_axisymmetry(mut self) -> Self {
+        assert!(
+            self.space.mesh().geometric_dim() == 2,
+            "Axisymmetric problems are solved on a 2-D mesh"
+        );
+        self.coordinate_system = CoordinateSystem::Axisymmetric;
+        self
+    }
+
+    /// Set the solver of the symmetric positive definite system
+    pub fn with_solver(mut self, solver: SolverConfig) -> Self {
+        self.solver = solver;
+        self
+    }
+
+    /// Material of the problem
+    pub fn material(&self) -> &IsotropicMaterial {
+        &self.material
+    }
+
+    /// Vector space of the displacement
+    pub fn space(&self) -> &'a FunctionSpace<'a> {
+        self.space
+    }
+
+    /// Coordinate system of the problem, cartesian or axisymmetric
+    pub fn coordinate_system(&self) -> CoordinateSystem {
+        self.coordinate_system
+    }
+
+    /// Solve for the displacement
+    pub fn solve(&self) -> Function<'a> {
+        let _span = Span::enter("linear elasticity");
+        let (matrix, rhs, constraints) = self.assemble();
+        let solver = self
+            .solver
+            .build(&matrix)
+            .expect("Elasticity system is singular, rigid motions should be constrained");
+        let mut displacement = vec![0.0; self.space.n_dofs()];
+        assert!(
+            solver.solve(&rhs, &mut displacement).converged(),
+            "Elasticity solve did not converge"
+        );
+        constraints.distribute(&mut displacement);
+        Function::from_values(self.space, displacement)
+    }
+
+    /// Stiffness matrix and load vector condensed by the displacement constraints, whose solution
+    /// gives the displacement once the constraints are distributed
+    pub fn assemble(&self) -> (SparseCSR<f64>, Vec<f64>, AffineConstraints) {
+        self.assemble_degraded(|_, _| 1.0)
+    }
+
+    // Assemble with the stiffness scaled at every cell and quadrature point of the rule of
+    // quadrature, for instance by the degradation of a damage
+    pub(crate) fn assemble_degraded<Degradation>(
+        &self,
+        mut degradation: Degradation,
+    ) -> (SparseCSR<f64>, Vec<f64>, AffineConstraints)
+    where
+        Degradation: FnMut(usize, usize) -> f64,
+    {
+        let space = self.space;
+        let mesh = space.mesh();
+        let dim = mesh.geometric_dim();
+        let constraints = self.constraints();
+        let mixed = MixedSpace::new(vec![space]);
+        let mut matrix =
+            SparsityPattern::from_dofmap_and_constraints(mixed.dof_map(), &constraints).to_csr(0.0);
+        let mut rhs = vec![0.0; space.n_dofs()];
+        let (lambda, mu) = self.material.lame_parameters();
+        let mut force = vec![0.0; dim];
+        let mut assembler = MixedAssembler::new(&mixed, self.quadrature());
+        assembler.set_coordinate_system(self.coordinate_system);
+        let axisymmetric = self.coordinate_system == CoordinateSystem::Axisymmetric;
+        assembler.assemble_system(
+            &mut matrix,
+            &mut rhs,
+            &constraints,
+            |values, local, load| {
+                let values = &values[0];
+                let n = values.n_dofs() * dim;
+                for q in 0..values.n_points() {
+                    let w = values.weight(q);
+                    let stiffness_w = degradation(values.cell(), q) * w;
+                    let r = values.point(q)[0];
+                    for a in 0..values.n_dofs() {
+                        let ga = values.shape_gradient(q, a);
+                        for b in 0..values.n_dofs() {
+                            let gb = values.shape_gradient(q, b);
+                            let dot: f64 = ga.iter().zip(gb).map(|(x, y)| x * y).sum();
+                            for i in 0..dim {
+                                for j in 0..dim {
+                                    let mut entry = lambda * ga[i] * gb[j] + mu * ga[j] * gb[i];
+                                    if i == j {
+                                        entry += mu * dot;
+                                    }
+                                    // Hoop strains of the radial components in the trace and
+                                    // the strain energy
+                                    if axisymmetric {
+                                        let hoop = |c: usize, s: usize| {
+                                            if c == 0 {
+                                                values.shape_value(q, s) / r
+                                            } else {
+                                                0.0
+                                            }
+                                        };
+                                        let (ha, hb) = (hoop(i, a), hoop(j, b));
+                                        entry += lambda * (ga[i] * hb + ha * gb[j] + ha * hb)
+                                            + 2.0 * mu * ha * hb;
+                                    }
+                                    local[(a * dim + i) * n + b * dim + j] += entry * stiffness_w;
+                                }
+                            }
+                        }
+                    }
+                    if let Some(body_force) = &self.body_force {
+                        body_force(values.point(q), &mut force);
+                        for a in 0..values.n_dofs() {
+                            for (i, f) in force.iter().enumerate() {
+                                load[a * dim + i] += f * values.shape_value(q, a) * w;
+                            }
+                        }
+                    }
+                }
+            },
+        );
+        let mut traction_load = self.traction_load();
+        constraints.set_zero(&mut traction_load);
+        rhs.iter_mut()
+            .zip(&traction_load)
+            .for_each(|(r, t)| *r += t);
+        (matrix, rhs, constraints)
+    }
+
+    /// Consistent mass matrix of a density condensed by the displacement constraints, with the
+    /// pattern of the stiffness matrix and vanishing constrained rows so that the constrained dofs
+    /// carry no vibration mode
+    pub fn mass_matrix(&self, density: f64) -> SparseCSR<f64> {
+        assert!(density > 0.0, "Density should be positive");
+        let space = self.space;
+        let dim = space.mesh().geometric_dim();
+        let constraints = self.constraints();
+        let mixed = MixedSpace::new(vec![space]);
+        let mut matrix =
+            SparsityPattern::from_dofmap_and_constraints(mixed.dof_map(), &constraints).to_csr(0.0);
+        let mut rhs = vec![0.0; space.n_dofs()];
+        let order = space.element().order();
+        let mut assembler = MixedAssembler::new(&mixed, QuadratureRule::simplex(dim, 2 * order));
+        assembler.set_coordinate_system(self.coordinate_system);
+        assembler.assemble_system(&mut matrix, &mut rhs, &constraints, |values, local, _| {
+            let values = &values[0];
+            let n = values.n_dofs() * dim;
+            for q in 0..values.n_points() {
+                let w = density * values.weight(q);
+                for a in 0..values.n_dofs() {
+                    for b in 0..values.n_dofs() {
+                        let entry = values.shape_value(q, a) * values.shape_value(q, b) * w;
+                        for i in 0..dim {
+                            local[(a * dim + i) * n + b * dim + i] += entry;
+                        }
+                    }
+                }
+            }
+        });
+        for dof in 0..space.n_dofs() {
+            if constraints.is_constrained(dof) {
+                *matrix.get_mut(dof, dof).unwrap() = 0.0;
+            }
+        }
+        matrix
+    }
+
+    /// Geometric stiffness matrix ∫ ∇v : (∇u σ₀) of the stress σ₀ of a displacement, with the
+    /// pattern of the stiffness matrix and vanishing constrained rows, so that (K + λ K_G) φ = 0
+    /// gives the buckling modes of the load scaled by λ
+    pub fn geometric_stiffness(&self, displacement: &Function) -> SparseCSR<f64> {
+        assert!(
+            self.coordinate_system == CoordinateSystem::Cartesian,
+            "Geometric stiffness is implemented for cartesian problems"
+        );
+        let space = self.space;
+        let dim = space.mesh().geometric_dim();
+        let constraints = self.constraints();
+        let mixed = MixedSpace::new(vec![space]);
+        let mut matrix =
+            SparsityPattern::from_dofmap_and_constraints(mixed.dof_map(), &constraints).to_csr(0.0);
+        let mut rhs = vec![0.0; space.n_dofs()];
+        let order = space.element().order();
+        let assembler = MixedAssembler::new(&mixed, QuadratureRule::simplex(dim, 2 * order));
+        let (lambda, mu) = self.material.lame_parameters();
+        let u = displacement.values();
+        let mut gradient = vec![0.0; dim * dim];
+        let mut stress = vec![0.0; dim * dim];
+        assembler.assemble_system(&mut matrix, &mut rhs, &constraints, |values, local, _| {
+            let values = &values[0];
+            let n = values.n_dofs() * dim;
+            let dofs = space.dof_map().cell_dofs(values.cell());
+            for q in 0..values.n_points() {
+                // Prestress of Hooke's law at the quadrature point
+                gradient.iter_mut().for_each(|g| *g = 0.0);
+                for (a, dof) in dofs.iter().enumerate() {
+                    let ga = values.shape_gradient(q, a);
+                    for i in 0..dim {
+                        for j in 0..dim {
+                            gradient[i * dim + j] += u[space.dof(*dof, i)] * ga[j];
+                        }
+                    }
+                }
+                let trace: f64 = (0..dim).map(|i| gradient[i * dim + i]).sum();
+                for i in 0..dim {
+                    for j in 0..dim {
+                        stress[i * dim + j] = mu * (gradient[i * dim + j] + gradient[j * dim + i]);
+                    }
+                    stress[i * dim + i] += lambda * trace;
+                }
+                let w = values.weight(q);
+                for a in 0..values.n_dofs() {
+                    let ga = values.shape_gradient(q, a);
+                    for b in 0..values.n_dofs() {
+                        let gb = values.shape_gradient(q, b);
+                        let mut entry = 0.0;
+                        for k in 0..dim {
+                            for l in 0..dim {
+                                entry += ga[k] * stress[k * dim + l] * gb[l];
+                            }
+                        }
+                        for i in 0..dim {
+                            local[(a * dim + i) * n + b * dim + i] += entry * w;
+                        }
+                    }
+                }
+            }
+        });
+        for dof in 0..space.n_dofs() {
+            if constraints.is_constrained(dof) {
+                *matrix.get_mut(dof, dof).unwrap() = 0.0;
+            }
+        }
+        matrix
+    }
+
+    /// Load vector ∫ (3λ + 2μ) ε_th div(v) of the free isotropic thermal strain ε_th I given by
+    /// a scalar function on the mesh, for instance α (T - T_ref) of a temperature T, with the
+    /// constrained entries zeroed so that it adds to the load of assemble
+    ///
+    /// The strain is isotropic in three dimensions, the out of plane component being held by the
+    /// plane strain or taken by the hoop strain of axisymmetric problems.
+    pub fn thermal_load(&self, strain: &Function) -> Vec<f64> {
+        let space = self.space;
+        let mesh = space.mesh();
+        assert!(
+            std::ptr::eq(strain.space().mesh(), mesh) && strain.space().n_components() == 1,
+            "Thermal strain should be a scalar function on the mesh of the problem"
+        );
+        let dim = mesh.geometric_dim();
+        let order = space.element().order();
+        let quadrature = QuadratureRule::simplex(dim, 2 * order);
+        let mixed = MixedSpace::new(vec![space]);
+        let mut assembler = MixedAssembler::new(&mixed, QuadratureRule::simplex(dim, 2 * order));
+        assembler.set_coordinate_system(self.coordinate_system);
+        let axisymmetric = self.coordinate_system == CoordinateSystem::Axisymmetric;
+        let (lambda, mu) = self.material.lame_parameters();
+        let mut value = [0.0];
+        let mut load = vec![0.0; space.n_dofs()];
+        assembler.assemble_vector(&mut load, |values, local| {
+            let values = &values[0];
+            for q in 0..values.n_points() {
+                strain.eval_in_cell(values.cell(), quadrature.point(q), &mut value);
+                let stress = (3.0 * lambda + 2.0 * mu) * value[0] * values.weight(q);
+                for a in 0..values.n_dofs() {
+                    let ga = values.shape_gradient(q, a);
+                    for (i, g) in ga.iter().enumerate() {
+                        local[a * dim + i] += stress * g;
+                    }
+                    // Hoop strain of the radial component in the divergence
+                    if axisymmetric {
+                        local[a * dim] += stress * values.shape_value(q, a) / values.point(q)[0];
+                    }
+                }
+            }
+        });
+        self.constraints().set_zero(&mut load);
+        load
+    }
+
+    /// Strain and stress components and von Mises stress of a displacement recovered at the nodes
+    /// of a continuous scalar space of the same mesh
+    ///
+    /// The fields are named strain_ij and stress_ij for the upper triangle of the tensors (i <= j
+    /// among x, y and z), followed in axisymmetric problems by strain_hoop and stress_hoop and then
+    /// by von_mises, which includes the out of plane stress of plane strain and axisymmetry in two
+    /// dimensions.
+    pub fn recover_stress<'b>(
+        &self,
+        displacement: &Function,
+        target: &'b FunctionSpace<'b>,
+    ) -> Vec<(String, Function<'b>)> {
+        let mesh = target.mesh();
+        let dim = mesh.geometric_dim();
+        assert!(
+            std::ptr::eq(displacement.space(), self.space),
+            "Displacement does not live on the space of the problem"
+        );
+        assert!(
+            std::ptr::eq(mesh, self.space.mesh())
+                && target.n_components() == 1
+                && !target.is_discontinuous(),
+            "Stresses are recovered on a continuous scalar space of the mesh"
+        );
+        // Same element and mesh so the scalar dofs of the gradient space are the ones of the target
+        let gradient_space = FunctionSpace::vector(
+            mesh,
+            LagrangeElement::new(dim, target.element().order()),
+            dim * dim,
+        );
+        let gradient = recover_gradient(displacement, &gradient_space);
+        let hoop_strains = self.hoop_strains(displacement, target, gradient.values());
+        let pairs: Vec<(usize, usize)> = (0..dim)
+            .flat_map(|i| (i..dim).map(move |j| (i, j)))
+            .collect();
+        let n = target.dof_map().n_dofs();
+        let mut strains = vec![vec![0.0; n]; pairs.len()];
+        let mut stresses = vec![vec![0.0; n]; pairs.len()];
+        let mut hoop_stresses = vec![0.0; n];
+        let mut von_mises = vec![0.0; n];
+        let (lambda, mu) = self.material.lame_parameters();
+        for (s, g) in gradient.values().chunks(dim * dim).enumerate() {
+            let strain = |i: usize, j: usize| 0.5 * (g[i * dim + j] + g[j * dim + i]);
+            let hoop = hoop_strains.as_ref().map_or(0.0, |h| h[s]);
+            let trace = (0..dim).map(|i| strain(i, i)).sum::<f64>() + hoop;
+            let stress = |i: usize, j: usize| {
+                2.0 * mu * strain(i, j) + if i == j { lambda * trace } else { 0.0 }
+            };
+            for (k, (i, j)) in pairs.iter().enumerate() {
+                strains[k][s] = strain(*i, *j);
+                stresses[k][s] = stress(*i, *j);
+            }
+            hoop_stresses[s] = lambda * trace + 2.0 * mu * hoop;
+            // Full 3D stress tensor, the out of plane components of plane strain or axisymmetry
+            // being known
+            let full = |i: usize, j: usize| match (i < dim, j < dim) {
+                (true, true) => stress(i, j),
+                _ if i == j => hoop_stresses[s],
+                _ => 0.0,
+            };
+            let deviatoric = (full(0, 0) - full(1, 1)).powi(2)
+                + (full(1, 1) - full(2, 2)).powi(2)
+                + (full(2, 2) - full(0, 0)).powi(2);
+            let shear = full(0, 1).powi(2) + full(1, 2).powi(2) + full(0, 2).powi(2);
+            von_mises[s] = (0.5 * deviatoric + 3.0 * shear).sqrt();
+        }
+        let name = |kind: &str, (i, j): (usize, usize)| format!("{}_{}{}", kind, AXES[i], AXES[j]);
+        let mut fields = Vec::with_capacity(2 * pairs.len() + 1);
+        for (pair, values) in pairs.iter().zip(strains) {
+            fields.push((name("strain", *pair), Function::from_values(target, values)));
+        }
+        for (pair, values) in pairs.iter().zip(stresses) {
+            fields.push((name("stress", *pair), Function::from_values(target, values)));
+        }
+        if let Some(hoop_strains) = hoop_strains {
+            fields.push((
+                "strain_hoop".to_string(),
+                Function::from_values(target, hoop_strains),
+            ));
+            fields.push((
+                "stress_hoop".to_string(),
+                Function::from_values(target, hoop_stresses),
+            ));
+        }
+        fields.push((
+            "von_mises".to_string(),
+            Function::from_values(target, von_mises),
+        ));
+        fields
+    }
+
+    /// Write a displacement and its strains and stresses recovered on linear elements to a legacy
+    /// VTK file
+    #[cfg(hosted)]
+    pub fn save_vtk<P: AsRef<Path>>(&self, path: P, displacement: &Function) -> Result<()> {
+        let mesh = self.space.mesh();
+        let target = FunctionSpace::new(mesh, LagrangeElement::new(mesh.geometric_dim(), 1));
+        let fields = self.recover_stress(displacement, &target);
+        let mut functions = vec![("displacement", displacement)];
+        functions.extend(fields.iter().map(|(name, f)| (name.as_str(), f)));
+        vtk::save_vtk(path, mesh, &functions)
+    }
+
+    // Hoop strains u_r / r at the nodes of the target of axisymmetric problems, du_r / dr on the
+    // axis, from the recovered gradients of the displacement
+    fn hoop_strains(
+        &self,
+        displacement: &Function,
+        target: &FunctionSpace,
+        gradient: &[f64],
+    ) -> Option<Vec<f64>> {
+        if self.coordinate_system != CoordinateSystem::Axisymmetric {
+            return None;
+        }
+        let coordinates = target.dof_coordinates();
+        let hoop = coordinates
+            .chunks(2)
+            .zip(gradient.chunks(4))
+            .map(|(x, g)| match displacement.eval(x) {
+                Some(u) if x[0] > 1e-12 => u[0] / x[0],
+                _ => g[0],
+            })
+            .collect();
+        Some(hoop)
+    }
+
+    // Cell quadrature rule of the stiffness
+    pub(crate) fn quadrature(&self) -> QuadratureRule {
+        let order = self.space.element().order();
+        QuadratureRule::simplex(self.space.mesh().geometric_dim(), 2 * order)
+    }
+
+    // Closed constraints of the imposed displacements
+    fn constraints(&self) -> AffineConstraints {
+        let space = self.space;
+        let dim = space.mesh().geometric_dim();
+        let mut constraints = AffineConstraints::new(space.n_dofs());
+        let coordinates = space.dof_coordinates();
+        let mut value = vec![0.0; dim];
+        for (tag, displacement) in &self.displacements {
+            for s in space.tagged_dofs(*tag) {
+                displacement(&coordinates[s * dim..(s + 1) * dim], &mut value);
+                for (c, v) in value.iter().enumerate() {
+                    constraints.add_dirichlet(space.dof(s, c), *v);
+                }
+            }
+        }
+        for (tag, component, value) in &self.component_displacements {
+            for s in space.tagged_dofs(*tag) {
+                constraints.add_dirichlet(space.dof(s, *component), *value);
+            }
+        }
+        constraints.close();
+        constraints
+    }
+
+    // Load vector of the tractions on the tagged facets
+    fn traction_load(&self) -> Vec<f64> {
+        let space = self.space;
+        let mesh = space.mesh();
+        let dim = mesh.geometric_dim();
+        let mut load = vec![0.0; space.n_dofs()];
+        if self.tractions.is_empty() {
+            return load;
+        }
+        let facets = Facets::new(mesh);
+        let quadrature = QuadratureRule::simplex(dim - 1, 2 * space.element().order());
+        let mut values = FacetValues::new(space.element(), &quadrature);
+        values.set_coordinate_system(self.coordinate_system);
+        let mut traction_value = vec![0.0; dim];
+        for facet in facets.boundary_facets() {
+            let vertices = facets.facet_vertices(facet);
+            let Some(tag) = mesh.facet_tag(vertices) else {
+                continue;
+            };
+            for (_, traction) in self.tractions.iter().filter(|(t, _)| *t == tag) {
+                let cell = facets.facet_cells(facet)[0].0;
+                values.reinit(mesh, vertices, cell);
+                let cell_dofs = space.dof_map().cell_dofs(cell);
+                for q in 0..values.n_points() {
+                    traction(values.point(q), &mut traction_value);
+                    for (a, dof) in cell_dofs.iter().enumerate() {
+                        let scale = values.shape_value(q, a) * values.weight(q);
+                        for (c, t) in traction_value.iter().enumerate() {
+                            load[space.dof(*dof, c)] += scale * t;
+                        }
+                    }
+                }
+            }
+        }
+        load
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::data_hold::DataHold;
+    use crate::discretizations::mesh::Mesh;
+    use crate::discretizations::refinement::Refinement;
+    use crate::discretizations::test_meshes::unit_square;
+
+    #[test]
+    fn test_linear_elasticity() {
+        // Unit square in two triangles and unit cube in the six tetrahedra of Kuhn
+        let square = unit_square();
+        let corners: Vec<f64> = (0..8)
+            .flat_map(|v| (0..3).map(move |d| ((v >> d) & 1) as f64))
+            .collect();
+        let mut tetrahedra = Vec::new();
+        for axes in [
+            [0, 1, 2],
+            [0, 2, 1],
+            [1, 0, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [2, 1, 0],
+        ] {
+            let mut vertex = 0;
+            tetrahedra.push(vertex);
+            for axis in axes {
+                vertex |= 1 << axis;
+                tetrahedra.push(vertex);
+            }
+        }
+        let cube = Mesh::new(
+            DataHold::new(corners, [8, 3]),
+            DataHold::new(tetrahedra, [6, 4]),
+        );
+        let material = IsotropicMaterial::new(200.0, 0.3);
+        let (e, nu) = (material.young_modulus(), material.poisson_ratio());
+        let pull = 2.0;
+        for coarse in [square, cube] {
+            // Uniaxial tension of a block on rollers, which linear elements reproduce exactly
+            let mut mesh = Refinement::uniform(&coarse).into_mesh();
+            let dim = mesh.geometric_dim();
+            for d in 0..dim {
+                mesh.tag_boundary(d, |x| x[d] == 0.0);
+            }
+            mesh.tag_boundary(3, |x| x[0] == 1.0);
+            let space = FunctionSpace::vector(&mesh, LagrangeElement::new(dim, 1), dim);
+            let mut problem = LinearElasticity::new(&space, material).with_traction(3, |_, t| {
+                t.iter_mut().for_each(|t| *t = 0.0);
+                t[0] = pull;
+            });
+            for d in 0..dim {
+                problem = problem.with_component_displacement(d, d, 0.0);
+            }
+            let u = problem.solve();
+            // Plane strain keeps the thickness, stretching the block more
+            let (axial, lateral) = if dim == 2 {
+                (pull * (1.0 - nu * nu) / e, -pull * nu * (1.0 + nu) / e)
+            } else {
+                (pull / e, -pull * nu / e)
+            };
+            let corner = u.eval(&vec![1.0; dim]).unwrap();
+            assert!((corner[0] - axial).abs() < 1e-12, "Wrong axial stretch");
+            for c in corner.iter().skip(1) {
+                assert!((c - lateral).abs() < 1e-12, "Wrong lateral contraction");
+            }
+            let target = FunctionSpace::new(&mesh, LagrangeElement::new(dim, 1));
+            let fields = problem.recover_stress(&u, &target);
+            assert_eq!(fields.len(), dim * (dim + 1) + 1, "Wrong number of fields");
+            let field = |name: &str| {
+                let (_, f) = fields.iter().find(|(n, _)| n == name).unwrap();
+                f.values().to_vec()
+            };
+            let von_mises = if dim == 2 {
+                pull * (1.0 - nu + nu * nu).sqrt()
+            } else {
+                pull
+            };
+            for ((xx, xy), vm) in field("stress_xx")
+                .iter()
+                .zip(field("stress_xy"))
+                .zip(field("von_mises"))
+            {
+                assert!((xx - pull).abs() < 1e-10, "Wrong axial stress {}", xx);
+                assert!(xy.abs() < 1e-10, "Shear stress {} in tension", xy);
+                assert!(
+                    (vm - von_mises).abs() < 1e-10,
+                    "Wrong von Mises stress {}",
+                    vm
+                );
+            }
+            for strain in field("strain_yy") {
+                assert!((strain - lateral).abs() < 1e-12, "Wrong lateral strain");
+            }
+        }
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_axisymmetric_elasticity() {
+        // Tube 1 < r < 2 of height 1 between rigid plates expanded by the radial displacement
+        // u_r = e r under the radial stress 2 (λ + μ) e on both walls, which is uniform
+        let coarse = Mesh::new(
+            DataHold::new(vec![1.0, 0.0, 2.0, 0.0, 1.0, 1.0, 2.0, 1.0], [4, 2]),
+            DataHold::new(vec![0, 1, 2, 3, 2, 1], [2, 3]),
+        );
+        let mut mesh = Refinement::uniform(&coarse).into_mesh();
+        mesh.tag_boundary(1, |x| x[1] == 0.0 || x[1] == 1.0);
+        mesh.tag_boundary(2, |x| x[0] == 1.0);
+        mesh.tag_boundary(3, |x| x[0] == 2.0);
+        let space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let material = IsotropicMaterial::new(200.0, 0.3);
+        let (lambda, mu) = material.lame_parameters();
+        let e = 1e-3;
+        let radial = 2.0 * (lambda + mu) * e;
+        let problem = LinearElasticity::new(&space, material)
+            .with_axisymmetry()
+            .with_component_displacement(1, 1, 0.0)
+            .with_traction(2, move |_, t| t.copy_from_slice(&[-radial, 0.0]))
+            .with_traction(3, move |_, t| t.copy_from_slice(&[radial, 0.0]));
+        let u = problem.solve();
+        for x in [[1.0, 0.5], [1.5, 0.25], [2.0, 1.0]] {
+            let value = u.eval(&x).unwrap();
+            assert!(
+                (value[0] - e * x[0]).abs() < 1e-12 && value[1].abs() < 1e-12,
+                "Wrong radial expansion {:?} at {:?}",
+                value,
+                x
+            );
+        }
+        let target = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let fields = problem.recover_stress(&u, &target);
+        assert_eq!(fields.len(), 9, "Hoop fields are missing");
+        let field = |name: &str| {
+            let (_, f) = fields.iter().find(|(n, _)| n == name).unwrap();
+            f.values().to_vec()
+        };
+        for ((hoop, stress), axial) in field("strain_hoop")
+            .iter()
+            .zip(field("stress_hoop"))
+            .zip(field("stress_yy"))
+        {
+            assert!((hoop - e).abs() < 1e-12, "Wrong hoop strain {}", hoop);
+            assert!((stress - radial).abs() < 1e-9, "Wrong hoop stress");
+            assert!(
+                (axial - 2.0 * lambda * e).abs() < 1e-9,
+                "Wrong axial stress"
+            );
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use super::heat::set_identity_rows;
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::core::logging::{debug, info, Span};
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+#[cfg(hosted)]
+use crate::discretizations::io::vtk;
+#[cfg(hosted)]
+use crate::discretizations::recovery::recover_gradient;
+use crate::discretizations::sparsity::SparsityPattern;
+use crate::solvers::sparse_direct::SparseLU;
+#[cfg(hosted)]
+use crate::spaces::lagrange::LagrangeElement;
+use crate::spaces::quadrature::QuadratureRule;
+#[cfg(hosted)]
+use std::io::Result;
+#[cfg(hosted)]
+use std::path::Path;
+
+// Scalar field of the physical coordinates
+type Field<'a> = Box<dyn Fn(&[f64]) -> f64 + 'a>;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Electrostatic potential -div(ε grad φ) = ρ in dielectrics on a continuous scalar space
+///
+/// Potentials are imposed on tagged boundary facets, the electrodes, and the rest of the boundary
+/// carries no surface charge. The system is solved by the sparse LU and the charge held by an
+/// electrode is the reaction ∫ ε grad φ·n of its dofs, n being the outward normal of the domain.
+/// The permittivity ε is constant on the cells of every tag with a default value elsewhere, 1 by
+/// default, and there is no free charge density ρ by default.
+pub struct Electrostatics<'a> {
+    space: &'a FunctionSpace<'a>,
+    permittivity: f64,
+    permittivities: Vec<(usize, f64)>,
+    charge_density: Option<Field<'a>>,
+    potentials: Vec<(usize, Field<'a>)>,
+}
+
+/// Potential of an electrostatic problem with the charges of its electrodes
+pub struct ElectrostaticSolution<'a> {
+    potential: Function<'a>,
+    reactions: Vec<f64>,
+    energy: f64,
+}
+
+impl<'a> Electrostatics<'a> {
+    /// Electrostatic problem on the space with the default parameters
+    pub fn new(space: &'a FunctionSpace<'a>) -> Self {
+        assert!(
+            space.n_components() == 1 && !space.is_discontinuous(),
+            "Electrostatics needs a continuous scalar space"
+        );
+        Electrostatics {
+            space,
+            permittivity: 1.0,
+            permittivities: Vec::new(),
+            charge_density: None,
+            potentials: Vec::new(),
+        }
+    }
+
+    /// Set the permittivity of the cells without a tag of their own
+    pub fn with_default_permittivity(mut self, permittivity: f64) -> Self {
+        assert!(permittivity > 0.0, "Permittivity should be positive");
+        self.permittivity = permittivity;
+        self
+    }
+
+    /// Set the permittivity of the cells carrying the tag
+    pub fn with_permittivity(mut self, tag: usize, permittivity: f64) -> Self {
+        assert!(permittivity > 0.0, "Permittivity should be positive");
+        self.permittivities.push((tag, permittivity));
+        self
+    }
+
+    /// Set the free charge density ρ as a function of the physical coordinates
+    pub fn with_charge_density<Density>(mut self, density: Density) -> Self
+    where
+        Density: Fn(&[f64]) -> f64 + 'a,
+    {
+        self.charge_density = Some(Box::new(density));
+        self
+    }
+
+    /// Impose the potential given as a function of the physical coordinates on the boundary facets
+    /// carrying the tag
+    pub fn with_potential<Potential>(mut self, tag: usize, potential: Potential) -> Self
+    where
+        Potential: Fn(&[f64]) -> f64 + 'a,
+    {
+        self.potentials.push((tag, Box::new(potential)));
+        self
+    }
+
+    /// Permittivity of every cell from its tag
+    pub fn cell_permittivities(&self) -> Vec<f64> {
+        let mesh = self.space.mesh();
+        (0..mesh.n_cells())
+            .map(|cell| {
+                let tag = mesh.cell_tag(cell);
+                self.permittivities
+                    .iter()
+                    .rev()
+                    .find(|(t, _)| Some(*t) == tag)
+                    .map_or(self.permittivity, |(_, e)| *e)
+            })
+            .collect()
+    }
+
+    /// Solve for the potential
+    pub fn solve(&self) -> ElectrostaticSolution<'a> {
+        let _span = Span::enter("electrostatics");
+        assert!(
+            !self.potentials.is_empty(),
+            "Electrostatics needs a potential imposed on some electrode"
+        );
+        let (stiffness, load) = self.assemble();
+        let coordinates = self.space.dof_coordinates();
+        let dim = self.space.mesh().geometric_dim();
+        let mut imposed = Vec::new();
+        for (tag, potential) in &self.potentials {
+            for dof in self.space.tagged_dofs(*tag) {
+                imposed.push((dof, potential(&coordinates[dof * dim..(dof + 1) * dim])));
+            }
+        }
+        let solution = self.solve_with(&stiffness, &load, &imposed);
+        info!(
+            "Electrostatics solved on {} dofs with {} electrodes",
+            self.space.n_dofs(),
+            self.potentials.len()
+        );
+        solution
+    }
+
+    /// Capacitance matrix C_ij = Q_i of the charges of the electrodes carrying the tags when
+    /// electrode j is at a unit potential and the others are grounded
+    ///
+    /// The potentials and the charge density of the problem are left out. The matrix is symmetric
+    /// with positive diagonal entries and nonpositive off diagonal ones.
+    pub fn capacitance_matrix(&self, electrodes: &[usize]) -> DataHold<f64, [usize; 2]> {
+        let _span = Span::enter("capacitance extraction");
+        let n = electrodes.len();
+        assert!(n > 0, "Capacitance needs at least one electrode");
+        let (stiffness, _) = self.assemble();
+        let load = vec![0.0; self.space.n_dofs()];
+        let dofs: Vec<Vec<usize>> = electrodes
+            .iter()
+            .map(|tag| self.space.tagged_dofs(*tag))
+            .collect();
+        let mut capacitance = vec![0.0; n * n];
+        for j in 0..n {
+            let imposed: Vec<(usize, f64)> = dofs
+                .iter()
+                .enumerate()
+                .flat_map(|(k, dofs)| {
+                    let value = if k == j { 1.0 } else { 0.0 };
+                    dofs.iter().map(move |dof| (*dof, value))
+                })
+                .collect();
+            let solution = self.solve_with(&stiffness, &load, &imposed);
+            for (i, dofs) in dofs.iter().enumerate() {
+                capacitance[i * n + j] = dofs.iter().map(|dof| solution.reactions[*dof]).sum();
+            }
+            debug!("Electrode {} at a unit potential", electrodes[j]);
+        }
+        info!("Capacitance extracted between {} electrodes", n);
+        DataHold::new(capacitance, [n, n])
+    }
+
+    // Stiffness of the permittivities and load of the charge density
+    fn assemble(&self) -> (SparseCSR<f64>, Vec<f64>) {
+        let element = self.space.element();
+        let assembler = self
+            .space
+            .assembler(QuadratureRule::simplex(element.dim(), 2 * element.order()));
+        let permittivities = self.cell_permittivities();
+        let mut stiffness = SparsityPattern::from_dofmap(self.space.dof_map()).to_csr(0.0);
+        assembler.assemble_matrix(&mut stiffness, |values, local| {
+            let n = values.n_dofs();
+            let permittivity = permittivities[values.cell()];
+            for q in 0..values.n_points() {
+                let scale = permittivity * values.weight(q);
+                for i in 0..n {
+                    let gi = values.shape_gradient(q, i);
+                    for j in 0..n {
+                        let gj = values.shape_gradient(q, j);
+                        let dot: f64 = gi.iter().zip(gj).map(|(a, b)| a * b).sum();
+                        local[i * n + j] += scale * dot;
+                    }
+                }
+            }
+        });
+        let mut load = vec![0.0; self.space.n_dofs()];
+        if let Some(density) = &self.charge_density {
+            assembler.assemble_vector(&mut load, |values, local| {
+                for q in 0..values.n_points() {
+                    let rho = density(values.point(q)) * values.weight(q);
+                    for i in 0..values.n_dofs() {
+                        local[i] += rho * values.shape_value(q, i);
+                    }
+                }
+            });
+        }
+        (stiffness, load)
+    }
+
+    // Solve with potentials imposed on dofs and compute the reactions K φ - F
+    fn solve_with(
+        &self,
+        stiffness: &SparseCSR<f64>,
+        load: &[f64],
+        imposed: &[(usize, f64)],
+    ) -> ElectrostaticSolution<'a> {
+        let mut system = stiffness.clone();
+        let dofs: Vec<usize> = imposed.iter().map(|(dof, _)| *dof).collect();
+        set_identity_rows(&mut system, &dofs);
+        let mut rhs = load.to_vec();
+        for (dof, value) in imposed {
+            rhs[*dof] = *value;
+        }
+        let lu = SparseLU::new(&system).expect("Electrostatic system is singular");
+        let mut potential = vec![0.0; load.len()];
+        lu.solve(&rhs, &mut potential);
+        let mut reactions = vec![0.0; load.len()];
+        stiffness.apply(&potential, &mut reactions);
+        let energy = 0.5
+            * reactions
+                .iter()
+                .zip(&potential)
+                .map(|(r, p)| r * p)
+                .sum::<f64>();
+        reactions.iter_mut().zip(load).for_each(|(r, f)| *r -= f);
+        ElectrostaticSolution {
+            potential: Function::from_values(self.space, potential),
+            reactions,
+            energy,
+        }
+    }
+}
+
+impl<'a> ElectrostaticSolution<'a> {
+    /// Electric potential φ
+    pub fn potential(&self) -> &Function<'a> {
+        &self.potential
+    }
+
+    /// Charge held by the electrode on the boundary facets carrying the tag
+    pub fn charge(&self, tag: usize) -> f64 {
+        let space = self.potential.space();
+        space
+            .tagged_dofs(tag)
+            .iter()
+            .map(|dof| self.reactions[*dof])
+            .sum()
+    }
+
+    /// Electrostatic energy ½ ∫ ε |grad φ|²
+    pub fn energy(&self) -> f64 {
+        self.energy
+    }
+
+    /// Electric field -grad φ at a physical point, None outside of the mesh
+    pub fn electric_field(&self, point: &[f64]) -> Option<Vec<f64>> {
+        let mesh = self.potential.space().mesh();
+        let (cell, reference) = mesh.locate(point)?;
+        let mut field = vec![0.0; mesh.geometric_dim()];
+        self.potential
+            .gradient_in_cell(cell, &reference, &mut field);
+        field.iter_mut().for_each(|e| *e = -*e);
+        Some(field)
+    }
+
+    /// Write the potential and the electric field recovered at the nodes to a legacy VTK file
+    #[cfg(hosted)]
+    pub fn save_vtk<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let space = self.potential.space();
+        let mesh = space.mesh();
+        let element = LagrangeElement::new(space.element().dim(), space.element().order());
+        let vector = FunctionSpace::vector(mesh, element, mesh.geometric_dim());
+        let mut field = recover_gradient(&self.potential, &vector);
+        field.values_mut().iter_mut().for_each(|e| *e = -*e);
+        vtk::save_vtk(
+            path,
+            mesh,
+            &[("potential", &self.potential), ("electric_field", &field)],
+        )
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+
+    #[test]
+    fn test_electrostatics() {
+        // Parallel plates at y = 0 and y = 1 with two dielectric layers in series, whose
+        // capacitance is 1 / (0.5 / 1 + 0.5 / 4) and whose linear potentials are exact
+        let mut mesh =
+            CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![4, 4]).simplex_mesh();
+        mesh.tag_cells(1, |x| x[1] > 0.5);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let problem = Electrostatics::new(&space)
+            .with_permittivity(1, 4.0)
+            .with_potential(3, |_| 1.0)
+            .with_potential(4, |_| 0.0);
+        let solution = problem.solve();
+        let capacitance = 1.6;
+        assert!(
+            (solution.charge(3) - capacitance).abs() < 1e-12
+                && (solution.charge(4) + capacitance).abs() < 1e-12,
+            "Wrong charges {} and {}",
+            solution.charge(3),
+            solution.charge(4)
+        );
+        assert!(
+            (solution.energy() - 0.5 * capacitance).abs() < 1e-12,
+            "Wrong energy {}",
+            solution.energy()
+        );
+        let field = solution.electric_field(&[0.3, 0.8]).unwrap();
+        assert!(
+            field[0].abs() < 1e-12 && (field[1] - capacitance / 4.0).abs() < 1e-12,
+            "Wrong field {:?}",
+            field
+        );
+        let matrix = problem.capacitance_matrix(&[3, 4]);
+        let expected = [capacitance, -capacitance, -capacitance, capacitance];
+        for (c, e) in matrix.iter().zip(expected) {
+            assert!(
+                (c - e).abs() < 1e-12,
+                "Wrong capacitance matrix {:?}",
+                matrix.as_ref()
+            );
+        }
+        // A uniform charge density in a grounded box is balanced by the charges of the walls
+        let solution = Electrostatics::new(&space)
+            .with_charge_density(|_| 2.0)
+            .with_potential(3, |_| 0.0)
+            .with_potential(4, |_| 0.0)
+            .solve();
+        assert!(
+            (solution.charge(3) + solution.charge(4) + 2.0).abs() < 1e-12,
+            "Walls should hold the opposite of the free charge"
+        );
+    }
+}
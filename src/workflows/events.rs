@@ -0,0 +1,269 @@
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::discretizations::function::Function;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+//--------------------------------------------------------------------------------------------------
+// # Enums
+//--------------------------------------------------------------------------------------------------
+
+/// Decision of an observer on the rest of a workflow run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    /// Go on with the next step
+    Continue,
+    /// Stop after the current event, the run returning what it has done so far
+    Stop,
+}
+
+/// Event of a workflow run as recorded by an EventLog
+#[derive(Clone, Debug, PartialEq)]
+pub enum WorkflowEvent {
+    /// A step is about to start
+    StepStart { step: usize, time: f64 },
+    /// The system of a step is assembled, with the norm of its right hand side
+    Assembled { step: usize, rhs_norm: f64 },
+    /// The solution of a step is known
+    Solved { step: usize, time: f64 },
+    /// The solution of a step was written to a file
+    Output {
+        step: usize,
+        time: f64,
+        path: PathBuf,
+    },
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Traits
+//--------------------------------------------------------------------------------------------------
+
+/// Observer of the events of the steps of a workflow run (time steps, load steps...), for custom
+/// monitoring, live plotting or early termination
+///
+/// All the hooks do nothing by default. The hooks take the observer by reference, so stateful
+/// observers keep their state in cells and share it with their clones (see EventLog). Step 0 is
+/// the initial state, which is only reported as solved.
+pub trait WorkflowObserver {
+    /// Called before a step at the time it will reach
+    fn on_step_start(&self, _step: usize, _time: f64) -> Control {
+        Control::Continue
+    }
+
+    /// Called with the system matrix and right hand side of a step before it is solved
+    fn on_assembled(&self, _step: usize, _matrix: &SparseCSR<f64>, _rhs: &[f64]) {}
+
+    /// Called with the solution of a step
+    fn on_solved(&self, _step: usize, _time: f64, _solution: &Function) -> Control {
+        Control::Continue
+    }
+
+    /// Called after the solution of a step was written to a file
+    fn on_output(&self, _step: usize, _time: f64, _path: &Path) {}
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Observer recording the events of the runs it observes
+///
+/// Clones share the same events so that one can be given to a workflow and the other queried
+/// after the run.
+#[derive(Clone, Debug, Default)]
+pub struct EventLog {
+    events: Rc<RefCell<Vec<WorkflowEvent>>>,
+}
+
+/// Observer stopping a run once a predicate of the step, the time and the solution holds
+pub struct StopWhen<Predicate> {
+    predicate: Predicate,
+}
+
+// Observers of a workflow notified one after the other, the run stopping if any of them asks to
+pub(crate) struct Observers<'a> {
+    observers: &'a [Box<dyn WorkflowObserver + 'a>],
+}
+
+impl EventLog {
+    /// Empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of events
+    pub fn len(&self) -> usize {
+        self.events.borrow().len()
+    }
+
+    /// Whether nothing was recorded
+    pub fn is_empty(&self) -> bool {
+        self.events.borrow().is_empty()
+    }
+
+    /// Copy of the events in the order they happened
+    pub fn events(&self) -> Vec<WorkflowEvent> {
+        self.events.borrow().clone()
+    }
+
+    /// Forget the events
+    pub fn clear(&self) {
+        self.events.borrow_mut().clear();
+    }
+}
+
+impl<Predicate> StopWhen<Predicate>
+where
+    Predicate: Fn(usize, f64, &Function) -> bool,
+{
+    /// Observer stopping at the first solved step satisfying the predicate
+    pub fn new(predicate: Predicate) -> Self {
+        StopWhen { predicate }
+    }
+}
+
+impl<'a> Observers<'a> {
+    // Observers of a workflow
+    pub(crate) fn new(observers: &'a [Box<dyn WorkflowObserver + 'a>]) -> Self {
+        Observers { observers }
+    }
+
+    // Notify the start of a step
+    pub(crate) fn step_start(&self, step: usize, time: f64) -> Control {
+        self.fold(|observer| observer.on_step_start(step, time))
+    }
+
+    // Notify an assembled system
+    pub(crate) fn assembled(&self, step: usize, matrix: &SparseCSR<f64>, rhs: &[f64]) {
+        for observer in self.observers {
+            observer.on_assembled(step, matrix, rhs);
+        }
+    }
+
+    // Notify a solution
+    pub(crate) fn solved(&self, step: usize, time: f64, solution: &Function) -> Control {
+        self.fold(|observer| observer.on_solved(step, time, solution))
+    }
+
+    // Notify a written file
+    #[cfg(hosted)]
+    pub(crate) fn output(&self, step: usize, time: f64, path: &Path) {
+        for observer in self.observers {
+            observer.on_output(step, time, path);
+        }
+    }
+
+    // Call a hook of every observer, stopping if any of them does
+    fn fold<Hook>(&self, hook: Hook) -> Control
+    where
+        Hook: Fn(&dyn WorkflowObserver) -> Control,
+    {
+        self.observers
+            .iter()
+            .fold(Control::Continue, |control, observer| {
+                match (hook(observer.as_ref()), control) {
+                    (Control::Continue, Control::Continue) => Control::Continue,
+                    _ => Control::Stop,
+                }
+            })
+    }
+}
+
+impl WorkflowObserver for EventLog {
+    fn on_step_start(&self, step: usize, time: f64) -> Control {
+        self.events
+            .borrow_mut()
+            .push(WorkflowEvent::StepStart { step, time });
+        Control::Continue
+    }
+
+    fn on_assembled(&self, step: usize, _matrix: &SparseCSR<f64>, rhs: &[f64]) {
+        let rhs_norm = rhs.iter().map(|r| r * r).sum::<f64>().sqrt();
+        self.events
+            .borrow_mut()
+            .push(WorkflowEvent::Assembled { step, rhs_norm });
+    }
+
+    fn on_solved(&self, step: usize, time: f64, _solution: &Function) -> Control {
+        self.events
+            .borrow_mut()
+            .push(WorkflowEvent::Solved { step, time });
+        Control::Continue
+    }
+
+    fn on_output(&self, step: usize, time: f64, path: &Path) {
+        self.events.borrow_mut().push(WorkflowEvent::Output {
+            step,
+            time,
+            path: path.to_path_buf(),
+        });
+    }
+}
+
+impl<Predicate> WorkflowObserver for StopWhen<Predicate>
+where
+    Predicate: Fn(usize, f64, &Function) -> bool,
+{
+    fn on_solved(&self, step: usize, time: f64, solution: &Function) -> Control {
+        if (self.predicate)(step, time, solution) {
+            Control::Stop
+        } else {
+            Control::Continue
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::workflows::heat::HeatEquation;
+
+    #[test]
+    fn test_workflow_observers() {
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![4, 4]).simplex_mesh();
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let log = EventLog::new();
+        let problem = HeatEquation::new(&space)
+            .with_source(|_, _| 1.0)
+            .with_dirichlet(1, |_, _| 0.0)
+            .with_observer(log.clone())
+            .with_observer(StopWhen::new(|step, _, _| step == 3));
+        let mut u = Function::new(&space);
+        let directory = std::env::temp_dir().join("fe2o3_test_workflow_observers");
+        std::fs::create_dir_all(&directory).unwrap();
+        let n_steps = problem
+            .solve_to_vtk(&mut u, 0.0, 1.0, 0.1, directory.join("heat"), 2)
+            .unwrap();
+        assert_eq!(n_steps, 3, "The observer should stop after the third step");
+        let events = log.events();
+        assert_eq!(events.len(), 12, "Wrong number of events {:?}", events);
+        assert_eq!(
+            events[0],
+            WorkflowEvent::Solved { step: 0, time: 0.0 },
+            "The initial state should be reported first"
+        );
+        assert!(
+            matches!(events[2], WorkflowEvent::StepStart { step: 1, .. })
+                && matches!(events[3], WorkflowEvent::Assembled { step: 1, rhs_norm } if rhs_norm > 0.0)
+                && matches!(events[4], WorkflowEvent::Solved { step: 1, .. }),
+            "Wrong order of the events of a step {:?}",
+            events
+        );
+        assert_eq!(
+            events[1],
+            WorkflowEvent::Output {
+                step: 0,
+                time: 0.0,
+                path: directory.join("heat_00000.vtk")
+            },
+            "Wrong output event"
+        );
+    }
+}
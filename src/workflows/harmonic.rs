@@ -0,0 +1,329 @@
+use super::elasticity::LinearElasticity;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::core::logging::{debug, info, Span};
+use crate::discretizations::function::Function;
+use crate::solvers::sparse_direct::SparseLU;
+use std::f64::consts::PI;
+use std::fmt::Write;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Steady state response of an elastic body to loads oscillating at a frequency
+///
+/// The loads of a linear elasticity problem are the amplitudes of the excitation F e^{iωt} and the
+/// complex amplitude U of the displacement solves (K - ω² M + iω C) U = F with the consistent mass
+/// M of a density and the Rayleigh damping C = α M + β K, as a real system of twice the size for
+/// its real and imaginary parts factorized by the sparse LU at every frequency. Imposed
+/// displacements are fixed supports and have to vanish. By default there is no damping, which
+/// makes the system singular at the natural frequencies.
+pub struct HarmonicResponse<'a> {
+    elasticity: LinearElasticity<'a>,
+    density: f64,
+    rayleigh: (f64, f64),
+    probes: Vec<(Vec<f64>, usize)>,
+}
+
+/// Complex amplitude of the displacement at a frequency
+pub struct HarmonicSolution<'a> {
+    frequency: f64,
+    real: Function<'a>,
+    imaginary: Function<'a>,
+}
+
+/// Amplitudes and phases of the displacement at the probes over a frequency sweep
+///
+/// Every row holds the frequency followed by the amplitude and the phase of every probe, whose
+/// columns are named amplitude_k and phase_k.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrequencyResponse {
+    n_probes: usize,
+    rows: Vec<Vec<f64>>,
+}
+
+// Matrices of the body condensed by its supports
+struct Matrices {
+    stiffness: SparseCSR<f64>,
+    mass: SparseCSR<f64>,
+    damping: SparseCSR<f64>,
+    load: Vec<f64>,
+}
+
+impl<'a> HarmonicResponse<'a> {
+    /// Response of the body of an elasticity problem of a density
+    pub fn new(elasticity: LinearElasticity<'a>, density: f64) -> Self {
+        assert!(density > 0.0, "Density should be positive");
+        HarmonicResponse {
+            elasticity,
+            density,
+            rayleigh: (0.0, 0.0),
+            probes: Vec::new(),
+        }
+    }
+
+    /// Set the Rayleigh damping C = α M + β K
+    pub fn with_rayleigh_damping(mut self, alpha: f64, beta: f64) -> Self {
+        assert!(
+            alpha >= 0.0 && beta >= 0.0,
+            "Rayleigh coefficients should not be negative"
+        );
+        self.rayleigh = (alpha, beta);
+        self
+    }
+
+    /// Report the response of a component of the displacement at a point
+    pub fn with_probe(mut self, point: &[f64], component: usize) -> Self {
+        let space = self.elasticity.space();
+        assert_eq!(
+            point.len(),
+            space.mesh().geometric_dim(),
+            "Probe point does not match the mesh"
+        );
+        assert!(component < space.n_components(), "Component out of bounds");
+        self.probes.push((point.to_vec(), component));
+        self
+    }
+
+    /// Complex amplitude of the displacement at a frequency in Hz
+    pub fn solve(&self, frequency: f64) -> HarmonicSolution<'a> {
+        let _span = Span::enter("harmonic response");
+        self.solve_with(&self.matrices(), frequency)
+    }
+
+    /// Response at the probes over frequencies in Hz
+    pub fn sweep(&self, frequencies: &[f64]) -> FrequencyResponse {
+        let _span = Span::enter("frequency sweep");
+        let matrices = self.matrices();
+        let mut response = FrequencyResponse {
+            n_probes: self.probes.len(),
+            rows: Vec::with_capacity(frequencies.len()),
+        };
+        for frequency in frequencies {
+            let solution = self.solve_with(&matrices, *frequency);
+            let mut row = vec![*frequency];
+            for (point, component) in &self.probes {
+                let (amplitude, phase) = solution
+                    .probe(point, *component)
+                    .expect("Probe point is outside of the mesh");
+                row.push(amplitude);
+                row.push(phase);
+            }
+            debug!("Harmonic response solved at {:e} Hz", frequency);
+            response.rows.push(row);
+        }
+        info!(
+            "Frequency sweep solved at {} frequencies for {} probes",
+            frequencies.len(),
+            self.probes.len()
+        );
+        response
+    }
+
+    // Stiffness, mass, damping and load of the body
+    fn matrices(&self) -> Matrices {
+        let (stiffness, load, constraints) = self.elasticity.assemble();
+        for dof in 0..constraints.n_dofs() {
+            if let Some((_, inhomogeneity)) = constraints.constraint(dof) {
+                assert!(
+                    inhomogeneity == 0.0,
+                    "Imposed displacements of a harmonic response should vanish"
+                );
+            }
+        }
+        let mass = self.elasticity.mass_matrix(self.density);
+        let (alpha, beta) = self.rayleigh;
+        let mut damping = mass.clone();
+        damping.values_mut().iter_mut().for_each(|v| *v *= alpha);
+        let damping = damping.add(beta, &stiffness);
+        Matrices {
+            stiffness,
+            mass,
+            damping,
+            load,
+        }
+    }
+
+    // Solve the real form of the complex system at a frequency
+    fn solve_with(&self, matrices: &Matrices, frequency: f64) -> HarmonicSolution<'a> {
+        let space = self.elasticity.space();
+        let omega = 2.0 * PI * frequency;
+        let real = matrices.stiffness.add(-omega * omega, &matrices.mass);
+        let mut imaginary = matrices.damping.clone();
+        imaginary.values_mut().iter_mut().for_each(|v| *v *= omega);
+        let system = complex_system(&real, &imaginary);
+        let lu = SparseLU::new(&system)
+            .expect("Harmonic system is singular, the frequency is undamped and natural");
+        let n = space.n_dofs();
+        let mut rhs = matrices.load.clone();
+        rhs.resize(2 * n, 0.0);
+        let mut x = vec![0.0; 2 * n];
+        lu.solve(&rhs, &mut x);
+        let imaginary = x.split_off(n);
+        HarmonicSolution {
+            frequency,
+            real: Function::from_values(space, x),
+            imaginary: Function::from_values(space, imaginary),
+        }
+    }
+}
+
+impl<'a> HarmonicSolution<'a> {
+    /// Frequency in Hz
+    pub fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    /// Real part of the amplitude, the displacement in phase with the loads
+    pub fn real(&self) -> &Function<'a> {
+        &self.real
+    }
+
+    /// Imaginary part of the amplitude
+    pub fn imaginary(&self) -> &Function<'a> {
+        &self.imaginary
+    }
+
+    /// Amplitude and phase in radians of a component of the displacement at a point, None outside
+    /// of the mesh
+    pub fn probe(&self, point: &[f64], component: usize) -> Option<(f64, f64)> {
+        let real = self.real.eval(point)?[component];
+        let imaginary = self.imaginary.eval(point)?[component];
+        Some((real.hypot(imaginary), imaginary.atan2(real)))
+    }
+}
+
+impl FrequencyResponse {
+    /// Frequencies of the sweep
+    pub fn frequencies(&self) -> Vec<f64> {
+        self.rows.iter().map(|row| row[0]).collect()
+    }
+
+    /// Amplitudes of a probe over the sweep
+    pub fn amplitudes(&self, probe: usize) -> Vec<f64> {
+        self.rows.iter().map(|row| row[1 + 2 * probe]).collect()
+    }
+
+    /// Phases of a probe over the sweep in radians
+    pub fn phases(&self, probe: usize) -> Vec<f64> {
+        self.rows.iter().map(|row| row[2 + 2 * probe]).collect()
+    }
+
+    /// Names of the columns of the table
+    pub fn columns(&self) -> Vec<String> {
+        let mut columns = vec!["frequency".to_string()];
+        for k in 0..self.n_probes {
+            columns.push(format!("amplitude_{}", k));
+            columns.push(format!("phase_{}", k));
+        }
+        columns
+    }
+
+    /// Rows of the table, one per frequency
+    pub fn rows(&self) -> &[Vec<f64>] {
+        &self.rows
+    }
+
+    /// Comma separated table with a header and one row per frequency
+    pub fn to_csv(&self) -> String {
+        let mut csv = self.columns().join(",");
+        csv.push('\n');
+        for row in &self.rows {
+            let values: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+            writeln!(csv, "{}", values.join(",")).unwrap();
+        }
+        csv
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Real matrix [[A, -B], [B, A]] of the complex matrix A + iB
+pub(crate) fn complex_system(real: &SparseCSR<f64>, imaginary: &SparseCSR<f64>) -> SparseCSR<f64> {
+    let n = real.n_rows();
+    let mut offsets = vec![0];
+    let (mut indices, mut values) = (Vec::new(), Vec::new());
+    for half in 0..2 {
+        for row in 0..n {
+            let blocks = if half == 0 {
+                [(real, 1.0), (imaginary, -1.0)]
+            } else {
+                [(imaginary, 1.0), (real, 1.0)]
+            };
+            for (k, (matrix, sign)) in blocks.iter().enumerate() {
+                let (cols, row_values) = matrix.row(row);
+                indices.extend(cols.iter().map(|col| col + k * n));
+                values.extend(row_values.iter().map(|v| sign * v));
+            }
+            offsets.push(indices.len());
+        }
+    }
+    SparseCSR::new(2 * n, offsets, indices, values)
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::workflows::elasticity::IsotropicMaterial;
+
+    #[test]
+    fn test_harmonic_response() {
+        // Square on rollers loaded by its first mode f = (sin(π x), 0) of natural frequency 1 Hz,
+        // responding as one damped oscillator U = f / (ρ (ω_n² - ω²) + iω α ρ)
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![8, 8]).simplex_mesh();
+        let space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        let elasticity = LinearElasticity::new(&space, IsotropicMaterial::new(2.0, 0.0))
+            .with_component_displacement(1, 0, 0.0)
+            .with_component_displacement(2, 0, 0.0)
+            .with_component_displacement(3, 1, 0.0)
+            .with_component_displacement(4, 1, 0.0)
+            .with_body_force(|x, f| {
+                f[0] = (PI * x[0]).sin();
+                f[1] = 0.0;
+            });
+        let (density, alpha) = (0.5, 0.5);
+        let response = HarmonicResponse::new(elasticity, density)
+            .with_rayleigh_damping(alpha, 0.0)
+            .with_probe(&[0.5, 0.5], 0);
+        let frequencies = [0.5, 1.0, 1.5];
+        let sweep = response.sweep(&frequencies);
+        for ((frequency, amplitude), phase) in frequencies
+            .iter()
+            .zip(sweep.amplitudes(0))
+            .zip(sweep.phases(0))
+        {
+            let omega = 2.0 * PI * frequency;
+            let (a, b) = (
+                density * (4.0 * PI * PI - omega * omega),
+                omega * alpha * density,
+            );
+            let expected = 1.0 / a.hypot(b);
+            assert!(
+                (amplitude - expected).abs() < 1e-2 * expected,
+                "Wrong amplitude {} at {} Hz",
+                amplitude,
+                frequency
+            );
+            assert!(
+                (phase + b.atan2(a)).abs() < 1e-2,
+                "Wrong phase at {} Hz",
+                frequency
+            );
+        }
+        assert!(
+            sweep
+                .to_csv()
+                .starts_with("frequency,amplitude_0,phase_0\n"),
+            "Wrong header"
+        );
+    }
+}
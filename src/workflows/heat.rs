@@ -0,0 +1,370 @@
+use super::events::{Control, Observers, WorkflowObserver};
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::core::logging::{debug, info, ProgressBar, Span};
+use crate::discretizations::assembler::Assembler;
+use crate::discretizations::cell_values::CoordinateSystem;
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+#[cfg(hosted)]
+use crate::discretizations::io::vtk::VtkSeries;
+use crate::discretizations::operators::{mass_matrix, stiffness_matrix};
+use crate::solvers::sparse_direct::SparseLU;
+use crate::solvers::time_integration::TimeScheme;
+use crate::spaces::quadrature::QuadratureRule;
+#[cfg(hosted)]
+use std::io::Result;
+#[cfg(hosted)]
+use std::path::Path;
+
+// Scalar field of the time and the physical coordinates
+type Field<'a> = Box<dyn Fn(f64, &[f64]) -> f64 + 'a>;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Transient heat equation c du/dt - div(k grad u) = f on a continuous scalar space
+///
+/// Time dependent temperatures are imposed on the tagged boundary facets, the rest of the boundary
+/// being insulated. Steps of the theta method solve the system (c M + θ dt K) u_n+1 =
+/// (c M - (1 - θ) dt K) u_n + dt (θ F_n+1 + (1 - θ) F_n) with the mass M and stiffness K matrices
+/// assembled and the system factorized by the sparse LU once. By default the heat capacity c and
+/// the conductivity k are 1, there is no source, the scheme is Crank-Nicolson and no progress bar
+/// is shown. Axisymmetric problems are solved on the (r, z) half plane of the body of revolution.
+/// Workflow observers are notified of the events of every step and may stop the integration.
+pub struct HeatEquation<'a> {
+    space: &'a FunctionSpace<'a>,
+    capacity: f64,
+    conductivity: f64,
+    source: Option<Field<'a>>,
+    dirichlet: Vec<(usize, Field<'a>)>,
+    scheme: TimeScheme,
+    coordinate_system: CoordinateSystem,
+    progress: bool,
+    observers: Vec<Box<dyn WorkflowObserver + 'a>>,
+}
+
+impl<'a> HeatEquation<'a> {
+    /// Heat equation on the space with the default parameters
+    pub fn new(space: &'a FunctionSpace<'a>) -> Self {
+        assert!(
+            space.n_components() == 1 && !space.is_discontinuous(),
+            "The heat equation needs a continuous scalar space"
+        );
+        HeatEquation {
+            space,
+            capacity: 1.0,
+            conductivity: 1.0,
+            source: None,
+            dirichlet: Vec::new(),
+            scheme: TimeScheme::CrankNicolson,
+            coordinate_system: CoordinateSystem::Cartesian,
+            progress: false,
+            observers: Vec::new(),
+        }
+    }
+
+    /// Set the heat capacity c (density times specific heat)
+    pub fn with_capacity(mut self, capacity: f64) -> Self {
+        assert!(capacity > 0.0, "Heat capacity should be positive");
+        self.capacity = capacity;
+        self
+    }
+
+    /// Set the thermal conductivity k
+    pub fn with_conductivity(mut self, conductivity: f64) -> Self {
+        assert!(conductivity > 0.0, "Conductivity should be positive");
+        self.conductivity = conductivity;
+        self
+    }
+
+    /// Set the heat source f as a function of the time and the physical coordinates
+    pub fn with_source<Source>(mut self, source: Source) -> Self
+    where
+        Source: Fn(f64, &[f64]) -> f64 + 'a,
+    {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// Impose the temperature given as a function of the time and the physical coordinates on the
+    /// boundary facets carrying the tag
+    pub fn with_dirichlet<Value>(mut self, tag: usize, value: Value) -> Self
+    where
+        Value: Fn(f64, &[f64]) -> f64 + 'a,
+    {
+        self.dirichlet.push((tag, Box::new(value)));
+        self
+    }
+
+    /// Set the time scheme, which has to be a theta method
+    pub fn with_scheme(mut self, scheme: TimeScheme) -> Self {
+        assert!(
+            !matches!(scheme, TimeScheme::Bdf(_)),
+            "The heat equation is integrated by theta methods"
+        );
+        self.scheme = scheme;
+        self
+    }
+
+    /// Solve in the axisymmetric coordinates (r, z) of a body of revolution
+    pub fn with_axisymmetry(mut self) -> Self {
+        assert!(
+            self.space.mesh().geometric_dim() == 2,
+            "Axisymmetric problems are solved on a 2-D mesh"
+        );
+        self.coordinate_system = CoordinateSystem::Axisymmetric;
+        self
+    }
+
+    /// Notify an observer of the events of the steps
+    pub fn with_observer<Observer: WorkflowObserver + 'a>(mut self, observer: Observer) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Show a terminal progress bar of the time steps
+    pub fn with_progress(mut self) -> Self {
+        self.progress = true;
+        self
+    }
+
+    /// Integrate from the start to the end time, u holding the initial temperature and then the
+    /// one of every step, which is also passed to the observer along with the start time
+    ///
+    /// The time step is reduced if needed so that a whole number of steps ends on the final time.
+    /// The number of steps done is returned, fewer if a workflow observer stopped the integration.
+    pub fn solve<Observer>(
+        &self,
+        u: &mut Function<'a>,
+        start: f64,
+        end: f64,
+        time_step: f64,
+        mut observer: Observer,
+    ) -> usize
+    where
+        Observer: FnMut(f64, &Function),
+    {
+        assert!(
+            std::ptr::eq(u.space(), self.space),
+            "Temperature does not live on the space of the problem"
+        );
+        assert!(time_step > 0.0, "Time step should be positive");
+        let theta = match self.scheme {
+            TimeScheme::ImplicitEuler => 1.0,
+            TimeScheme::CrankNicolson => 0.5,
+            TimeScheme::Theta(theta) => theta,
+            TimeScheme::Bdf(_) => unreachable!(),
+        };
+        let _span = Span::enter("heat equation");
+        let n_steps = ((end - start) / time_step - 1e-10).ceil().max(1.0) as usize;
+        let dt = (end - start) / n_steps as f64;
+        info!(
+            "Heat equation on {} dofs in {} steps of {}",
+            self.space.n_dofs(),
+            n_steps,
+            dt
+        );
+        let mut progress = if self.progress {
+            ProgressBar::new("time steps", n_steps)
+        } else {
+            ProgressBar::hidden("time steps", n_steps)
+        };
+        let element = self.space.element();
+        let mut assembler = self
+            .space
+            .assembler(QuadratureRule::simplex(element.dim(), 2 * element.order()));
+        assembler.set_coordinate_system(self.coordinate_system);
+        let mass = mass_matrix(&assembler);
+        let stiffness = stiffness_matrix(&assembler, |_| self.conductivity);
+        let mut system = stiffness.add(self.capacity / (theta * dt), &mass);
+        system
+            .values_mut()
+            .iter_mut()
+            .for_each(|v| *v *= theta * dt);
+        let explicit = stiffness.add(-self.capacity / ((1.0 - theta) * dt), &mass);
+        let coordinates = self.space.dof_coordinates();
+        let dim = self.space.mesh().geometric_dim();
+        let dirichlet: Vec<(Vec<usize>, &Field)> = self
+            .dirichlet
+            .iter()
+            .map(|(tag, value)| (self.space.tagged_dofs(*tag), value))
+            .collect();
+        for (dofs, _) in &dirichlet {
+            set_identity_rows(&mut system, dofs);
+        }
+        let lu = SparseLU::new(&system).expect("Heat equation system is singular");
+        let n = self.space.n_dofs();
+        let mut load = self.load(&assembler, start);
+        let mut rhs = vec![0.0; n];
+        let mut next = vec![0.0; n];
+        let observers = Observers::new(&self.observers);
+        let control = observers.solved(0, start, u);
+        observer(start, u);
+        if control == Control::Stop {
+            return 0;
+        }
+        for step in 0..n_steps {
+            let time = start + (step + 1) as f64 * dt;
+            if observers.step_start(step + 1, time) == Control::Stop {
+                return step;
+            }
+            // Explicit part -(1 - θ) dt (K u_n - F_n) + c M u_n, the mass alone if θ = 1
+            if theta < 1.0 {
+                explicit.apply(u.values(), &mut rhs);
+                let scale = -(1.0 - theta) * dt;
+                rhs.iter_mut()
+                    .zip(&load)
+                    .for_each(|(r, f)| *r = scale * (*r - f));
+            } else {
+                mass.apply(u.values(), &mut rhs);
+                rhs.iter_mut().for_each(|r| *r *= self.capacity);
+            }
+            load = self.load(&assembler, time);
+            rhs.iter_mut()
+                .zip(&load)
+                .for_each(|(r, f)| *r += theta * dt * f);
+            for (dofs, value) in &dirichlet {
+                for dof in dofs {
+                    rhs[*dof] = value(time, &coordinates[dof * dim..(dof + 1) * dim]);
+                }
+            }
+            observers.assembled(step + 1, &system, &rhs);
+            lu.solve(&rhs, &mut next);
+            u.values_mut().copy_from_slice(&next);
+            debug!("Step {} at time {}", step + 1, time);
+            progress.inc();
+            let control = observers.solved(step + 1, time, u);
+            observer(time, u);
+            if control == Control::Stop {
+                return step + 1;
+            }
+        }
+        n_steps
+    }
+
+    /// Integrate like solve and write the initial temperature and the one of every given number of
+    /// steps to a VTK time series (see VtkSeries) starting with the prefix path
+    #[cfg(hosted)]
+    pub fn solve_to_vtk<P: AsRef<Path>>(
+        &self,
+        u: &mut Function<'a>,
+        start: f64,
+        end: f64,
+        time_step: f64,
+        prefix: P,
+        every: usize,
+    ) -> Result<usize> {
+        assert!(every > 0, "Output needs a positive number of steps");
+        let mesh = self.space.mesh();
+        let mut series = VtkSeries::new(prefix);
+        let mut status = Ok(());
+        let mut step = 0;
+        let n_steps = self.solve(u, start, end, time_step, |time, u| {
+            if step % every == 0 && status.is_ok() {
+                status = series.write(time, mesh, &[("temperature", u)]);
+                if status.is_ok() {
+                    let path = series.step_path(series.times().len() - 1);
+                    Observers::new(&self.observers).output(step, time, &path);
+                }
+            }
+            step += 1;
+        });
+        status.map(|_| n_steps)
+    }
+
+    // Load vector F of the source at a time
+    fn load(&self, assembler: &Assembler, time: f64) -> Vec<f64> {
+        let mut load = vec![0.0; self.space.n_dofs()];
+        if let Some(source) = &self.source {
+            assembler.assemble_vector(&mut load, |values, local| {
+                for q in 0..values.n_points() {
+                    let f = source(time, values.point(q)) * values.weight(q);
+                    for i in 0..values.n_dofs() {
+                        local[i] += f * values.shape_value(q, i);
+                    }
+                }
+            });
+        }
+        load
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Replace the rows of the dofs by the ones of the identity, the diagonal being in the pattern
+pub(crate) fn set_identity_rows(matrix: &mut SparseCSR<f64>, dofs: &[usize]) {
+    for dof in dofs {
+        let (start, end) = (matrix.row_offsets()[*dof], matrix.row_offsets()[dof + 1]);
+        for position in start..end {
+            let diagonal = matrix.col_indices()[position] == *dof;
+            matrix.values_mut()[position] = if diagonal { 1.0 } else { 0.0 };
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::refinement::Refinement;
+    use crate::discretizations::test_meshes::unit_square;
+    use crate::spaces::lagrange::LagrangeElement;
+
+    #[test]
+    fn test_heat_equation() {
+        let square = unit_square();
+        let mut mesh = Refinement::uniform(&square).into_mesh();
+        mesh.tag_boundary(1, |_| true);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        // u = (1 + t) |x|^2 is quadratic in space and linear in time, which Crank-Nicolson on P2
+        // elements reproduces exactly
+        let exact = |t: f64, x: &[f64]| (1.0 + t) * (x[0] * x[0] + x[1] * x[1]);
+        let problem = HeatEquation::new(&space)
+            .with_capacity(2.0)
+            .with_conductivity(0.5)
+            .with_source(|t, x| 2.0 * (x[0] * x[0] + x[1] * x[1]) - 2.0 * (1.0 + t))
+            .with_dirichlet(1, exact);
+        let mut u = Function::new(&space);
+        u.interpolate(|x| exact(0.0, x));
+        let directory = std::env::temp_dir().join("fe2o3_test_heat_equation");
+        std::fs::create_dir_all(&directory).unwrap();
+        let n_steps = problem
+            .solve_to_vtk(&mut u, 0.0, 1.0, 0.3, directory.join("heat"), 2)
+            .unwrap();
+        assert_eq!(n_steps, 4, "The time step should be reduced to 0.25");
+        assert!(
+            directory.join("heat_00002.vtk").exists() && !directory.join("heat_00003.vtk").exists(),
+            "Every other step should be written"
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+        let mut expected = Function::new(&space);
+        expected.interpolate(|x| exact(1.0, x));
+        for (u, e) in u.values().iter().zip(expected.values()) {
+            assert!(
+                (u - e).abs() < 1e-10,
+                "Wrong temperature {} instead of {}",
+                u,
+                e
+            );
+        }
+        // Backward differences are exact as well for solutions linear in time
+        let mut v = Function::new(&space);
+        v.interpolate(|x| exact(0.0, x));
+        problem
+            .with_scheme(TimeScheme::ImplicitEuler)
+            .solve(&mut v, 0.0, 1.0, 0.25, |_, _| {});
+        let error = v
+            .values()
+            .iter()
+            .zip(expected.values())
+            .map(|(v, e)| (v - e).abs())
+            .fold(0.0, f64::max);
+        assert!(error < 1e-10, "Implicit Euler error {}", error);
+    }
+}
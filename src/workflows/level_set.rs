@@ -0,0 +1,424 @@
+use super::advection_diffusion::AdvectionDiffusion;
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::logging::Span;
+use crate::discretizations::cut_cell::CutCells;
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+use crate::discretizations::mesh::Mesh;
+use std::f64::consts::PI;
+
+// Diffusivity of the transport of the level set, negligible next to the advection
+const TRANSPORT_DIFFUSIVITY: f64 = 1e-10;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Level set of an interface, the zero level of a scalar function negative inside and positive
+/// outside, on a continuous scalar space
+///
+/// Level sets are initialized as signed distances to analytic shapes (see sphere_distance and
+/// box_distance) or to closed surface meshes, the sign of the latter being given by their winding
+/// numbers. They are transported by a velocity with the advection workflow, one backward Euler
+/// step solving (phi - phi_old) / dt + b.grad phi = 0 with SUPG, which spoils the distance
+/// property away from the interface. Reinitialization restores it by recomputing the distances to
+/// the interface of the linear interpolant of the vertex values while keeping the signs. The
+/// inside of the interface is the geometry of CutCells for immersed discretizations.
+pub struct LevelSet<'a> {
+    function: Function<'a>,
+}
+
+impl<'a> LevelSet<'a> {
+    /// Interpolate a level set given as a function of the physical coordinates
+    pub fn new<Distance>(space: &'a FunctionSpace<'a>, distance: Distance) -> Self
+    where
+        Distance: Fn(&[f64]) -> f64,
+    {
+        assert!(
+            space.n_components() == 1 && !space.is_discontinuous(),
+            "Level sets need a continuous scalar space"
+        );
+        let mut function = Function::new(space);
+        function.interpolate(distance);
+        LevelSet { function }
+    }
+
+    /// Signed distance to a closed surface mesh of codimension one (segments in 2-D and triangles
+    /// in 3-D), negative inside
+    ///
+    /// Distances are taken to the cells of the surface overlapping the ball around the dof reaching
+    /// the nearest surface vertex and the winding number of the whole surface gives the sign.
+    pub fn from_surface(space: &'a FunctionSpace<'a>, surface: &Mesh) -> Self {
+        let dim = space.mesh().geometric_dim();
+        assert!(
+            surface.geometric_dim() == dim && surface.topological_dim() + 1 == dim && dim >= 2,
+            "Surface should be a mesh of codimension one of the space of the level set"
+        );
+        let mut level_set = LevelSet::new(space, |_| 0.0);
+        let coordinates = space.dof_coordinates();
+        for (value, x) in level_set
+            .function
+            .values_mut()
+            .iter_mut()
+            .zip(coordinates.chunks(dim))
+        {
+            let distance = surface_distance(surface, x);
+            *value = if winding_number(surface, x).abs() > 0.5 {
+                -distance
+            } else {
+                distance
+            };
+        }
+        level_set
+    }
+
+    /// Level set as a function
+    pub fn function(&self) -> &Function<'a> {
+        &self.function
+    }
+
+    /// Values of the level set at the dofs
+    pub fn values(&self) -> &[f64] {
+        self.function.values()
+    }
+
+    /// Cells of the mesh classified against the inside of the interface
+    pub fn cut_cells(&self) -> CutCells {
+        let space = self.function.space();
+        CutCells::from_vertex_values(space.mesh(), self.function.vertex_values().to_vec())
+    }
+
+    /// Replace the values by the signed distances to the current interface of the linear
+    /// interpolant of the vertex values, nothing being done without interface
+    pub fn reinitialize(&mut self) {
+        let _span = Span::enter("level set reinitialization");
+        let space = self.function.space();
+        let mesh = space.mesh();
+        let dim = mesh.geometric_dim();
+        let Some(interface) = interface_mesh(mesh, &self.function.vertex_values()) else {
+            return;
+        };
+        let coordinates = space.dof_coordinates();
+        for (value, x) in self
+            .function
+            .values_mut()
+            .iter_mut()
+            .zip(coordinates.chunks(dim))
+        {
+            let distance = surface_distance(&interface, x);
+            *value = if *value < 0.0 { -distance } else { distance };
+        }
+    }
+
+    /// Transport the level set by a velocity over a time step
+    pub fn advect<Velocity>(&mut self, velocity: Velocity, time_step: f64)
+    where
+        Velocity: Fn(&[f64], &mut [f64]),
+    {
+        assert!(time_step > 0.0, "Time step should be positive");
+        let _span = Span::enter("level set advection");
+        let space = self.function.space();
+        let previous = &self.function;
+        let transported = AdvectionDiffusion::new(space)
+            .with_diffusivity(TRANSPORT_DIFFUSIVITY)
+            .with_reaction(1.0 / time_step)
+            .with_velocity(velocity)
+            .with_source(|x| previous.eval(x).map_or(0.0, |v| v[0]) / time_step)
+            .solve()
+            .into_values();
+        self.function = Function::from_values(space, transported);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Signed distance to the sphere (circle in 2-D) of a center and radius
+pub fn sphere_distance(center: Vec<f64>, radius: f64) -> impl Fn(&[f64]) -> f64 {
+    assert!(radius > 0.0, "Sphere radius should be positive");
+    move |x| norm_between(x, &center) - radius
+}
+
+/// Signed distance to the axis aligned box [lower, upper]
+pub fn box_distance(lower: Vec<f64>, upper: Vec<f64>) -> impl Fn(&[f64]) -> f64 {
+    assert!(
+        lower.len() == upper.len() && lower.iter().zip(&upper).all(|(l, u)| l < u),
+        "Box bounds should have the same dimension with lower bounds below the upper ones"
+    );
+    move |x| {
+        // Offsets of the point from the faces along every axis, positive outside of the slab
+        let offsets: Vec<f64> = (0..lower.len())
+            .map(|d| (lower[d] - x[d]).max(x[d] - upper[d]))
+            .collect();
+        let outside = offsets
+            .iter()
+            .map(|o| o.max(0.0).powi(2))
+            .sum::<f64>()
+            .sqrt();
+        outside
+            + offsets
+                .iter()
+                .fold(f64::NEG_INFINITY, |m, o| m.max(*o))
+                .min(0.0)
+    }
+}
+
+// Mesh of the zero level of the linear interpolant of vertex values, none without interface
+fn interface_mesh(mesh: &Mesh, vertex_values: &DataHold<f64, [usize; 2]>) -> Option<Mesh> {
+    let dim = mesh.geometric_dim();
+    let mut vertices = Vec::new();
+    let mut cells = Vec::new();
+    for cell in 0..mesh.n_cells() {
+        let cell_vertices = mesh.cell(cell);
+        let (inside, outside): (Vec<usize>, Vec<usize>) =
+            cell_vertices.iter().partition(|v| vertex_values[**v] < 0.0);
+        if inside.is_empty() || outside.is_empty() {
+            continue;
+        }
+        // Crossings of the edges ordered around the interface for quadrilaterals
+        let pairs: Vec<(usize, usize)> = if inside.len() == 2 && outside.len() == 2 {
+            vec![
+                (inside[0], outside[0]),
+                (inside[0], outside[1]),
+                (inside[1], outside[1]),
+                (inside[1], outside[0]),
+            ]
+        } else {
+            inside
+                .iter()
+                .flat_map(|a| outside.iter().map(move |b| (*a, *b)))
+                .collect()
+        };
+        let first = vertices.len() / dim;
+        for (a, b) in &pairs {
+            let (pa, pb) = (vertex_values[*a], vertex_values[*b]);
+            let t = pa / (pa - pb);
+            let (xa, xb) = (mesh.vertex(*a), mesh.vertex(*b));
+            vertices.extend(xa.iter().zip(xb).map(|(x, y)| x + t * (y - x)));
+        }
+        match pairs.len() {
+            4 => {
+                cells.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3])
+            }
+            n => cells.extend(first..first + n),
+        }
+    }
+    if cells.is_empty() {
+        return None;
+    }
+    let n_vertices = vertices.len() / dim;
+    Some(Mesh::new(
+        DataHold::new(vertices, [n_vertices, dim]),
+        DataHold::new(cells.clone(), [cells.len() / dim, dim]),
+    ))
+}
+
+// Distance from a point to a surface mesh, among the cells whose box overlaps the ball reaching
+// the nearest vertex
+fn surface_distance(surface: &Mesh, x: &[f64]) -> f64 {
+    let (_, bound) = surface
+        .nearest_vertex(x)
+        .expect("Surface mesh has no vertices");
+    let lower: Vec<f64> = x.iter().map(|x| x - bound).collect();
+    let upper: Vec<f64> = x.iter().map(|x| x + bound).collect();
+    surface
+        .cell_tree()
+        .overlapping(&lower, &upper)
+        .into_iter()
+        .map(|cell| {
+            let vertices: Vec<&[f64]> = surface
+                .cell(cell)
+                .iter()
+                .map(|v| surface.vertex(*v))
+                .collect();
+            match vertices.as_slice() {
+                [a, b] => segment_distance(x, a, b),
+                [a, b, c] => triangle_distance(x, a, b, c),
+                _ => unreachable!("Surface cells are segments or triangles"),
+            }
+        })
+        .fold(bound, f64::min)
+}
+
+// Distance from a point to the segment [a, b]
+fn segment_distance(x: &[f64], a: &[f64], b: &[f64]) -> f64 {
+    let ab: Vec<f64> = b.iter().zip(a).map(|(b, a)| b - a).collect();
+    let ax: Vec<f64> = x.iter().zip(a).map(|(x, a)| x - a).collect();
+    let length = dot(&ab, &ab);
+    let t = if length > 0.0 {
+        (dot(&ax, &ab) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let closest: Vec<f64> = a.iter().zip(&ab).map(|(a, d)| a + t * d).collect();
+    norm_between(x, &closest)
+}
+
+// Distance from a point to the triangle (a, b, c) in 3-D, by the closest point in the Voronoi
+// regions of its vertices, edges and face
+fn triangle_distance(x: &[f64], a: &[f64], b: &[f64], c: &[f64]) -> f64 {
+    let sub = |p: &[f64], q: &[f64]| -> Vec<f64> { p.iter().zip(q).map(|(p, q)| p - q).collect() };
+    let (ab, ac, ax) = (sub(b, a), sub(c, a), sub(x, a));
+    let (d1, d2) = (dot(&ab, &ax), dot(&ac, &ax));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return norm_between(x, a);
+    }
+    let bx = sub(x, b);
+    let (d3, d4) = (dot(&ab, &bx), dot(&ac, &bx));
+    if d3 >= 0.0 && d4 <= d3 {
+        return norm_between(x, b);
+    }
+    let cx = sub(x, c);
+    let (d5, d6) = (dot(&ab, &cx), dot(&ac, &cx));
+    if d6 >= 0.0 && d5 <= d6 {
+        return norm_between(x, c);
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return segment_distance(x, a, b);
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return segment_distance(x, a, c);
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return segment_distance(x, b, c);
+    }
+    let (v, w) = (vb / (va + vb + vc), vc / (va + vb + vc));
+    let closest: Vec<f64> = (0..3).map(|d| a[d] + v * ab[d] + w * ac[d]).collect();
+    norm_between(x, &closest)
+}
+
+// Winding number of a closed surface around a point, by the angles (2-D) or solid angles (3-D)
+// its cells subtend
+fn winding_number(surface: &Mesh, x: &[f64]) -> f64 {
+    let mut total = 0.0;
+    for cell in 0..surface.n_cells() {
+        let r: Vec<Vec<f64>> = surface
+            .cell(cell)
+            .iter()
+            .map(|v| {
+                surface
+                    .vertex(*v)
+                    .iter()
+                    .zip(x)
+                    .map(|(p, x)| p - x)
+                    .collect()
+            })
+            .collect();
+        total += match r.as_slice() {
+            [a, b] => (a[0] * b[1] - a[1] * b[0]).atan2(dot(a, b)) / (2.0 * PI),
+            [a, b, c] => {
+                let triple = a[0] * (b[1] * c[2] - b[2] * c[1])
+                    - a[1] * (b[0] * c[2] - b[2] * c[0])
+                    + a[2] * (b[0] * c[1] - b[1] * c[0]);
+                let (na, nb, nc) = (dot(a, a).sqrt(), dot(b, b).sqrt(), dot(c, c).sqrt());
+                let denominator = na * nb * nc + dot(a, b) * nc + dot(a, c) * nb + dot(b, c) * na;
+                2.0 * triple.atan2(denominator) / (4.0 * PI)
+            }
+            _ => unreachable!("Surface cells are segments or triangles"),
+        };
+    }
+    total
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+fn norm_between(a: &[f64], b: &[f64]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - b) * (a - b))
+        .sum::<f64>()
+        .sqrt()
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::cut_cell::CellLocation;
+    use crate::spaces::lagrange::LagrangeElement;
+
+    #[test]
+    fn test_level_set_distances() {
+        let mesh =
+            CartesianGrid::new(vec![-1.0, -1.0], vec![1.0, 1.0], vec![16, 16]).simplex_mesh();
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let circle = sphere_distance(vec![0.1, 0.0], 0.5);
+        // Polygon of the circle oriented clockwise, which the winding number does not mind
+        let n = 256;
+        let polygon: Vec<f64> = (0..n)
+            .flat_map(|k| {
+                let angle = -2.0 * PI * k as f64 / n as f64;
+                [0.1 + 0.5 * angle.cos(), 0.5 * angle.sin()]
+            })
+            .collect();
+        let segments: Vec<usize> = (0..n).flat_map(|k| [k, (k + 1) % n]).collect();
+        let surface = Mesh::new(
+            DataHold::new(polygon, [n, 2]),
+            DataHold::new(segments, [n, 2]),
+        );
+        let from_surface = LevelSet::from_surface(&space, &surface);
+        // Level set with the right interface but three times the distance
+        let mut scaled = LevelSet::new(&space, |x| 3.0 * circle(x));
+        scaled.reinitialize();
+        let coordinates = space.dof_coordinates();
+        for (dof, x) in coordinates.chunks(2).enumerate() {
+            let exact = circle(x);
+            assert!(
+                (from_surface.values()[dof] - exact).abs() < 1e-3,
+                "Wrong distance to the surface at {:?}",
+                x
+            );
+            assert!(
+                (scaled.values()[dof] - exact).abs() < 2e-2,
+                "Reinitialization did not recover the distance at {:?}",
+                x
+            );
+        }
+        let cut = scaled.cut_cells();
+        assert!(
+            !cut.cells(CellLocation::Cut).is_empty(),
+            "Interface should cut cells"
+        );
+        let square = box_distance(vec![0.0, 0.0], vec![1.0, 2.0]);
+        assert!(
+            (square(&[0.5, 1.5]) + 0.5).abs() < 1e-15,
+            "Wrong inside distance"
+        );
+        assert!(
+            (square(&[4.0, 6.0]) - 5.0).abs() < 1e-15,
+            "Wrong corner distance"
+        );
+    }
+
+    //--------------------------------------------------------------------------------------------------
+    #[test]
+    fn test_level_set_advection() {
+        // Circle translated by (0.2, 0.1) in ten steps
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![32, 32]).simplex_mesh();
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let mut level_set = LevelSet::new(&space, sphere_distance(vec![0.4, 0.4], 0.2));
+        for _ in 0..10 {
+            level_set.advect(|_, b| b.copy_from_slice(&[2.0, 1.0]), 0.01);
+        }
+        level_set.reinitialize();
+        let moved = sphere_distance(vec![0.6, 0.5], 0.2);
+        let coordinates = space.dof_coordinates();
+        let error = coordinates
+            .chunks(2)
+            .zip(level_set.values())
+            .filter(|(x, _)| moved(x).abs() < 0.1)
+            .map(|(x, value)| (value - moved(x)).abs())
+            .fold(0.0, f64::max);
+        assert!(error < 0.02, "Interface moved wrongly, error {}", error);
+    }
+}
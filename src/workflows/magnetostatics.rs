@@ -0,0 +1,580 @@
+use super::harmonic::complex_system;
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::logging::{info, Span};
+use crate::discretizations::conforming_values::HCurlValues;
+use crate::discretizations::constraints::AffineConstraints;
+use crate::discretizations::dof_map::DofMap;
+use crate::discretizations::facets::Facets;
+#[cfg(hosted)]
+use crate::discretizations::function::Function;
+#[cfg(hosted)]
+use crate::discretizations::function_space::FunctionSpace;
+#[cfg(hosted)]
+use crate::discretizations::io::vtk;
+use crate::discretizations::mesh::Mesh;
+use crate::discretizations::sparsity::SparsityPattern;
+use crate::solvers::sparse_direct::SparseLU;
+#[cfg(hosted)]
+use crate::spaces::lagrange::LagrangeElement;
+use crate::spaces::nedelec::NedelecElement;
+use crate::spaces::quadrature::QuadratureRule;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+#[cfg(hosted)]
+use std::io::Result;
+#[cfg(hosted)]
+use std::path::Path;
+
+// Vector field of the physical coordinates
+type VectorField<'a> = Box<dyn Fn(&[f64], &mut [f64]) + 'a>;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Magnetostatics and time harmonic eddy currents curl(ν curl A) + iω σ A = J for the magnetic
+/// vector potential A on lowest order Nédélec elements
+///
+/// The flux density is B = curl A, a scalar in 2D, and the reluctivity ν = 1 / μ comes from the
+/// permeability μ. Tangential potentials n × A are imposed on tagged boundary facets through the
+/// tangential moments of their edges, the rest of the boundary having no tangential field
+/// n × H = 0. The currents J are amplitudes at a frequency f in Hz, ω = 2π f, inducing eddy
+/// currents -iω σ A in the cells of a positive conductivity σ, and the complex system is solved as
+/// a real system of twice the size by the sparse LU. The kernel of the curl is removed by the mass
+/// term δ ν ∫ A·v, which selects the divergence free potential of divergence free currents. The
+/// permeability is 1 and the conductivity 0 on the cells without a tag of their own, the frequency
+/// is 0 (magnetostatics), δ is 1e-8 and there are no currents by default.
+pub struct Magnetostatics<'a> {
+    mesh: &'a Mesh,
+    permeability: f64,
+    permeabilities: Vec<(usize, f64)>,
+    conductivity: f64,
+    conductivities: Vec<(usize, f64)>,
+    frequency: f64,
+    regularization: f64,
+    current: Option<VectorField<'a>>,
+    potentials: Vec<(usize, VectorField<'a>)>,
+}
+
+/// Complex amplitude of the vector potential on the edges of the mesh
+pub struct MagneticSolution<'a> {
+    mesh: &'a Mesh,
+    dof_map: DofMap,
+    real: Vec<f64>,
+    imaginary: Vec<f64>,
+    reluctivities: Vec<f64>,
+    energy: f64,
+    losses: f64,
+}
+
+impl<'a> Magnetostatics<'a> {
+    /// Magnetic problem on a simplicial mesh with the default parameters
+    pub fn new(mesh: &'a Mesh) -> Self {
+        let dim = mesh.geometric_dim();
+        assert!(
+            (2..=3).contains(&dim) && mesh.topological_dim() == dim,
+            "Magnetostatics needs a 2D or 3D mesh of full dimension"
+        );
+        Magnetostatics {
+            mesh,
+            permeability: 1.0,
+            permeabilities: Vec::new(),
+            conductivity: 0.0,
+            conductivities: Vec::new(),
+            frequency: 0.0,
+            regularization: 1e-8,
+            current: None,
+            potentials: Vec::new(),
+        }
+    }
+
+    /// Set the permeability of the cells without a tag of their own
+    pub fn with_default_permeability(mut self, permeability: f64) -> Self {
+        assert!(permeability > 0.0, "Permeability should be positive");
+        self.permeability = permeability;
+        self
+    }
+
+    /// Set the permeability of the cells carrying the tag
+    pub fn with_permeability(mut self, tag: usize, permeability: f64) -> Self {
+        assert!(permeability > 0.0, "Permeability should be positive");
+        self.permeabilities.push((tag, permeability));
+        self
+    }
+
+    /// Set the conductivity of the cells without a tag of their own
+    pub fn with_default_conductivity(mut self, conductivity: f64) -> Self {
+        assert!(conductivity >= 0.0, "Conductivity should not be negative");
+        self.conductivity = conductivity;
+        self
+    }
+
+    /// Set the conductivity of the cells carrying the tag
+    pub fn with_conductivity(mut self, tag: usize, conductivity: f64) -> Self {
+        assert!(conductivity >= 0.0, "Conductivity should not be negative");
+        self.conductivities.push((tag, conductivity));
+        self
+    }
+
+    /// Set the frequency in Hz of the currents, 0 for magnetostatics
+    pub fn with_frequency(mut self, frequency: f64) -> Self {
+        assert!(frequency >= 0.0, "Frequency should not be negative");
+        self.frequency = frequency;
+        self
+    }
+
+    /// Set the relative weight δ of the mass term fixing the gauge
+    pub fn with_regularization(mut self, regularization: f64) -> Self {
+        assert!(regularization > 0.0, "Regularization should be positive");
+        self.regularization = regularization;
+        self
+    }
+
+    /// Set the source current density J as a function of the physical coordinates
+    pub fn with_current<Current>(mut self, current: Current) -> Self
+    where
+        Current: Fn(&[f64], &mut [f64]) + 'a,
+    {
+        self.current = Some(Box::new(current));
+        self
+    }
+
+    /// Impose the tangential part of the potential given as a function of the physical coordinates
+    /// on the boundary facets carrying the tag
+    pub fn with_potential<Potential>(mut self, tag: usize, potential: Potential) -> Self
+    where
+        Potential: Fn(&[f64], &mut [f64]) + 'a,
+    {
+        self.potentials.push((tag, Box::new(potential)));
+        self
+    }
+
+    /// Permeability of every cell from its tag
+    pub fn cell_permeabilities(&self) -> Vec<f64> {
+        cell_values(self.mesh, self.permeability, &self.permeabilities)
+    }
+
+    /// Conductivity of every cell from its tag
+    pub fn cell_conductivities(&self) -> Vec<f64> {
+        cell_values(self.mesh, self.conductivity, &self.conductivities)
+    }
+
+    /// Solve for the vector potential
+    pub fn solve(&self) -> MagneticSolution<'a> {
+        let _span = Span::enter("magnetostatics");
+        let mesh = self.mesh;
+        let dim = mesh.geometric_dim();
+        let element = NedelecElement::new(dim);
+        let dof_map = DofMap::edges(mesh, element.edges());
+        let n_dofs = dof_map.n_dofs();
+        let constraints = self.constraints(&element, &dof_map);
+        let reluctivities: Vec<f64> = self
+            .cell_permeabilities()
+            .iter()
+            .map(|mu| 1.0 / mu)
+            .collect();
+        let conductivities = self.cell_conductivities();
+        let omega = 2.0 * PI * self.frequency;
+        let eddy = omega > 0.0 && conductivities.iter().any(|sigma| *sigma > 0.0);
+        let mut real =
+            SparsityPattern::from_dofmap_and_constraints(&dof_map, &constraints).to_csr(0.0);
+        let mut imaginary = real.clone();
+        let mut rhs = vec![0.0; n_dofs];
+        let mut imaginary_rhs = vec![0.0; n_dofs];
+        let mut values = HCurlValues::new(&element, &QuadratureRule::simplex(dim, 2));
+        let n = element.n_dofs();
+        let mut local_real = vec![0.0; n * n];
+        let mut local_imaginary = vec![0.0; n * n];
+        let mut local_vector = vec![0.0; n];
+        let mut current = vec![0.0; dim];
+        for cell in 0..mesh.n_cells() {
+            values.reinit(mesh, cell);
+            local_real.iter_mut().for_each(|v| *v = 0.0);
+            local_imaginary.iter_mut().for_each(|v| *v = 0.0);
+            local_vector.iter_mut().for_each(|v| *v = 0.0);
+            let nu = reluctivities[cell];
+            let (mass_real, mass_imaginary) =
+                (self.regularization * nu, omega * conductivities[cell]);
+            let mut volume = 0.0;
+            for q in 0..values.n_points() {
+                let w = values.weight(q);
+                volume += w;
+                if let Some(source) = &self.current {
+                    source(values.point(q), &mut current);
+                }
+                for i in 0..n {
+                    let vi = values.shape_value(q, i);
+                    if self.current.is_some() {
+                        local_vector[i] += dot(&current, vi) * w;
+                    }
+                    for j in 0..n {
+                        let mass = dot(vi, values.shape_value(q, j)) * w;
+                        local_real[i * n + j] += mass_real * mass;
+                        local_imaginary[i * n + j] += mass_imaginary * mass;
+                    }
+                }
+            }
+            for i in 0..n {
+                for j in 0..n {
+                    local_real[i * n + j] +=
+                        nu * dot(values.shape_curl(i), values.shape_curl(j)) * volume;
+                }
+            }
+            let dofs = dof_map.cell_dofs(cell);
+            constraints.distribute_local_to_global(
+                dofs,
+                Some(&local_real),
+                Some(&local_vector),
+                Some(&mut real),
+                Some(&mut rhs),
+            );
+            if eddy {
+                constraints.distribute_local_to_global(
+                    dofs,
+                    Some(&local_imaginary),
+                    None,
+                    Some(&mut imaginary),
+                    Some(&mut imaginary_rhs),
+                );
+            }
+        }
+        let (mut solution, mut imaginary_solution) = if eddy {
+            let lu = SparseLU::new(&complex_system(&real, &imaginary))
+                .expect("Eddy current system is singular");
+            rhs.extend_from_slice(&imaginary_rhs);
+            let mut x = vec![0.0; 2 * n_dofs];
+            lu.solve(&rhs, &mut x);
+            let imaginary = x.split_off(n_dofs);
+            (x, imaginary)
+        } else {
+            let lu = SparseLU::new(&real).expect("Magnetostatic system is singular");
+            let mut x = vec![0.0; n_dofs];
+            lu.solve(&rhs, &mut x);
+            (x, vec![0.0; n_dofs])
+        };
+        constraints.distribute(&mut solution);
+        constraints.distribute_homogeneous(&mut imaginary_solution);
+        let mut solution = MagneticSolution {
+            mesh,
+            dof_map,
+            real: solution,
+            imaginary: imaginary_solution,
+            reluctivities,
+            energy: 0.0,
+            losses: 0.0,
+        };
+        solution.integrate(&conductivities, omega);
+        info!(
+            "Magnetic problem solved on {} edges at {:e} Hz",
+            n_dofs, self.frequency
+        );
+        solution
+    }
+
+    // Tangential moments of the imposed potentials on the edges of the tagged boundary facets
+    fn constraints(&self, element: &NedelecElement, dof_map: &DofMap) -> AffineConstraints {
+        let mesh = self.mesh;
+        let mut edge_dofs = HashMap::new();
+        for cell in 0..mesh.n_cells() {
+            let vertices = mesh.cell(cell);
+            for ((a, b), dof) in element.edges().iter().zip(dof_map.cell_dofs(cell)) {
+                let (va, vb) = (vertices[*a], vertices[*b]);
+                edge_dofs.insert((va.min(vb), va.max(vb)), *dof);
+            }
+        }
+        let mut constraints = AffineConstraints::new(dof_map.n_dofs());
+        let facets = Facets::new(mesh);
+        for facet in facets.boundary_facets() {
+            let vertices = facets.facet_vertices(facet);
+            let tag = mesh.facet_tag(vertices);
+            if let Some((_, potential)) = self.potentials.iter().find(|(t, _)| Some(*t) == tag) {
+                for (k, va) in vertices.iter().enumerate() {
+                    for vb in &vertices[k + 1..] {
+                        let edge = (*va.min(vb), *va.max(vb));
+                        let moment = tangential_moment(mesh, edge, potential);
+                        constraints.add_dirichlet(edge_dofs[&edge], moment);
+                    }
+                }
+            }
+        }
+        constraints.close();
+        constraints
+    }
+}
+
+impl<'a> MagneticSolution<'a> {
+    /// Real part of the tangential moments of the potential on the edges, oriented from their
+    /// lower to their higher vertex
+    pub fn real(&self) -> &[f64] {
+        &self.real
+    }
+
+    /// Imaginary part of the tangential moments of the potential on the edges
+    pub fn imaginary(&self) -> &[f64] {
+        &self.imaginary
+    }
+
+    /// Real and imaginary parts of the vector potential at a physical point, None outside of the
+    /// mesh
+    pub fn potential(&self, point: &[f64]) -> Option<(Vec<f64>, Vec<f64>)> {
+        let (cell, reference) = self.mesh.locate(point)?;
+        let values = self.values_at(cell, &reference);
+        Some((
+            self.potential_at(&values, &self.real),
+            self.potential_at(&values, &self.imaginary),
+        ))
+    }
+
+    /// Real and imaginary parts of the flux density B = curl A at a physical point, None outside
+    /// of the mesh
+    pub fn flux_density(&self, point: &[f64]) -> Option<(Vec<f64>, Vec<f64>)> {
+        let (cell, reference) = self.mesh.locate(point)?;
+        let values = self.values_at(cell, &reference);
+        Some((
+            self.curl_at(&values, &self.real),
+            self.curl_at(&values, &self.imaginary),
+        ))
+    }
+
+    /// Magnetic energy ½ ∫ ν |B|² of the amplitude, twice the time average of harmonic fields
+    pub fn magnetic_energy(&self) -> f64 {
+        self.energy
+    }
+
+    /// Time averaged Joule losses ½ ∫ σ ω² |A|² of the eddy currents
+    pub fn joule_losses(&self) -> f64 {
+        self.losses
+    }
+
+    /// Write the real and imaginary parts of the potential and of the flux density with the
+    /// permeability as discontinuous linear fields to a legacy VTK file
+    #[cfg(hosted)]
+    pub fn save_vtk<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mesh = self.mesh;
+        let dim = mesh.geometric_dim();
+        let curl_dim = if dim == 2 { 1 } else { 3 };
+        let element = || LagrangeElement::new(dim, 1);
+        let scalar = FunctionSpace::discontinuous(mesh, element());
+        let vector = FunctionSpace::discontinuous_vector(mesh, element(), dim);
+        let curl = FunctionSpace::discontinuous_vector(mesh, element(), curl_dim);
+        let mut potentials = [Function::new(&vector), Function::new(&vector)];
+        let mut densities = [Function::new(&curl), Function::new(&curl)];
+        let mut permeability = Function::new(&scalar);
+        let mut reference = vec![0.0; dim];
+        for cell in 0..mesh.n_cells() {
+            // The linear potential of the cell is exact at its vertices
+            for (local, dof) in scalar.dof_map().cell_dofs(cell).iter().enumerate() {
+                reference.iter_mut().for_each(|r| *r = 0.0);
+                if local > 0 {
+                    reference[local - 1] = 1.0;
+                }
+                let values = self.values_at(cell, &reference);
+                for (part, edges) in [&self.real, &self.imaginary].iter().enumerate() {
+                    for (c, v) in self.potential_at(&values, edges).iter().enumerate() {
+                        potentials[part].values_mut()[vector.dof(*dof, c)] = *v;
+                    }
+                    for (c, v) in self.curl_at(&values, edges).iter().enumerate() {
+                        densities[part].values_mut()[curl.dof(*dof, c)] = *v;
+                    }
+                }
+                permeability.values_mut()[*dof] = 1.0 / self.reluctivities[cell];
+            }
+        }
+        vtk::save_vtk(
+            path,
+            mesh,
+            &[
+                ("potential_real", &potentials[0]),
+                ("potential_imaginary", &potentials[1]),
+                ("flux_density_real", &densities[0]),
+                ("flux_density_imaginary", &densities[1]),
+                ("permeability", &permeability),
+            ],
+        )
+    }
+
+    // Energy and losses summed over the cells
+    fn integrate(&mut self, conductivities: &[f64], omega: f64) {
+        let dim = self.mesh.geometric_dim();
+        let mut values =
+            HCurlValues::new(&NedelecElement::new(dim), &QuadratureRule::simplex(dim, 2));
+        let (mut energy, mut losses) = (0.0, 0.0);
+        for (cell, sigma) in conductivities.iter().enumerate() {
+            values.reinit(self.mesh, cell);
+            let volume: f64 = (0..values.n_points()).map(|q| values.weight(q)).sum();
+            for edges in [&self.real, &self.imaginary] {
+                let b = self.curl_at(&values, edges);
+                energy += 0.5 * self.reluctivities[cell] * dot(&b, &b) * volume;
+                if *sigma > 0.0 {
+                    for q in 0..values.n_points() {
+                        let a = self.value_at(&values, q, edges);
+                        losses += 0.5 * sigma * omega * omega * dot(&a, &a) * values.weight(q);
+                    }
+                }
+            }
+        }
+        self.energy = energy;
+        self.losses = losses;
+    }
+
+    // Shape functions of a cell at a single reference point
+    fn values_at(&self, cell: usize, reference: &[f64]) -> HCurlValues {
+        let dim = self.mesh.geometric_dim();
+        let rule = QuadratureRule::new(DataHold::new(reference.to_vec(), [1, dim]), vec![1.0]);
+        let mut values = HCurlValues::new(&NedelecElement::new(dim), &rule);
+        values.reinit(self.mesh, cell);
+        values
+    }
+
+    // Potential at the only point of the values
+    fn potential_at(&self, values: &HCurlValues, edges: &[f64]) -> Vec<f64> {
+        self.value_at(values, 0, edges)
+    }
+
+    // Potential at a quadrature point of the values
+    fn value_at(&self, values: &HCurlValues, q: usize, edges: &[f64]) -> Vec<f64> {
+        let mut potential = vec![0.0; values.dim()];
+        for (i, dof) in self.dof_map.cell_dofs(values.cell()).iter().enumerate() {
+            for (a, v) in potential.iter_mut().zip(values.shape_value(q, i)) {
+                *a += edges[*dof] * v;
+            }
+        }
+        potential
+    }
+
+    // Curl of the potential, constant on the cell
+    fn curl_at(&self, values: &HCurlValues, edges: &[f64]) -> Vec<f64> {
+        let mut curl = vec![0.0; values.curl_dim()];
+        for (i, dof) in self.dof_map.cell_dofs(values.cell()).iter().enumerate() {
+            for (c, v) in curl.iter_mut().zip(values.shape_curl(i)) {
+                *c += edges[*dof] * v;
+            }
+        }
+        curl
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Value of every cell from its tag
+fn cell_values(mesh: &Mesh, default: f64, tagged: &[(usize, f64)]) -> Vec<f64> {
+    (0..mesh.n_cells())
+        .map(|cell| {
+            let tag = mesh.cell_tag(cell);
+            tagged
+                .iter()
+                .rev()
+                .find(|(t, _)| Some(*t) == tag)
+                .map_or(default, |(_, v)| *v)
+        })
+        .collect()
+}
+
+// Integral of A·t along an edge from its first to its second vertex
+fn tangential_moment(mesh: &Mesh, edge: (usize, usize), potential: &VectorField) -> f64 {
+    let dim = mesh.geometric_dim();
+    let (start, end) = (mesh.vertex(edge.0), mesh.vertex(edge.1));
+    let tangent: Vec<f64> = end.iter().zip(start).map(|(e, s)| e - s).collect();
+    let quadrature = QuadratureRule::simplex(1, 2);
+    let mut point = vec![0.0; dim];
+    let mut value = vec![0.0; dim];
+    let mut moment = 0.0;
+    for q in 0..quadrature.n_points() {
+        let s = quadrature.point(q)[0];
+        for (p, (x, t)) in point.iter_mut().zip(start.iter().zip(&tangent)) {
+            *p = x + s * t;
+        }
+        potential(&point, &mut value);
+        moment += quadrature.weights()[q] * dot(&value, &tangent);
+    }
+    moment
+}
+
+// Euclidean dot product
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+
+    #[test]
+    fn test_magnetostatics() {
+        // The rotating potential A = (-y, x, 0) imposed on the boundary gives the uniform flux
+        // density B = 2 ez reproduced exactly by the edge elements in 2D and 3D
+        for dim in 2..=3 {
+            let mut mesh =
+                CartesianGrid::new(vec![0.0; dim], vec![1.0; dim], vec![3; dim]).simplex_mesh();
+            mesh.tag_boundary(1, |_| true);
+            let solution = Magnetostatics::new(&mesh)
+                .with_default_permeability(0.5)
+                .with_potential(1, |x, a| {
+                    a.fill(0.0);
+                    a[0] = -x[1];
+                    a[1] = x[0];
+                })
+                .solve();
+            let point = vec![0.4; dim];
+            let (potential, _) = solution.potential(&point).unwrap();
+            assert!(
+                (potential[0] + 0.4).abs() < 1e-6 && (potential[1] - 0.4).abs() < 1e-6,
+                "Wrong potential {:?} in {}D",
+                potential,
+                dim
+            );
+            let (density, imaginary) = solution.flux_density(&point).unwrap();
+            assert!(
+                (density[density.len() - 1] - 2.0).abs() < 1e-6
+                    && imaginary.iter().all(|b| *b == 0.0),
+                "Wrong flux density {:?} in {}D",
+                density,
+                dim
+            );
+            assert!(
+                (solution.magnetic_energy() - 4.0).abs() < 1e-5,
+                "Wrong magnetic energy {}",
+                solution.magnetic_energy()
+            );
+        }
+    }
+
+    #[test]
+    fn test_eddy_currents() {
+        // A uniform current in a uniform conductor is balanced by the eddy currents alone, so that
+        // A = J / (iω σ) and the losses ½ σ ω² |A|² = 1 / 2σ do not depend on the frequency
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![4, 4]).simplex_mesh();
+        let sigma = 2.0;
+        let frequency = 0.5;
+        let solution = Magnetostatics::new(&mesh)
+            .with_default_conductivity(sigma)
+            .with_frequency(frequency)
+            .with_current(|_, j| {
+                j[0] = 1.0;
+                j[1] = 0.0;
+            })
+            .solve();
+        let omega = 2.0 * PI * frequency;
+        let (real, imaginary) = solution.potential(&[0.3, 0.6]).unwrap();
+        assert!(
+            real.iter().all(|a| a.abs() < 1e-6)
+                && (imaginary[0] + 1.0 / (omega * sigma)).abs() < 1e-6
+                && imaginary[1].abs() < 1e-6,
+            "Wrong potential {:?} + i {:?}",
+            real,
+            imaginary
+        );
+        assert!(
+            (solution.joule_losses() - 0.5 / sigma).abs() < 1e-6,
+            "Wrong losses {}",
+            solution.joule_losses()
+        );
+    }
+}
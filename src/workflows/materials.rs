@@ -0,0 +1,557 @@
+use super::elasticity::IsotropicMaterial;
+
+// Tensor indices of the Voigt components of symmetric tensors in 3D and in 2D
+const VOIGT_3D: [(usize, usize); 6] = [(0, 0), (1, 1), (2, 2), (1, 2), (0, 2), (0, 1)];
+const VOIGT_2D: [(usize, usize); 3] = [(0, 0), (1, 1), (0, 1)];
+
+//--------------------------------------------------------------------------------------------------
+// # Traits
+//--------------------------------------------------------------------------------------------------
+
+/// Constitutive model giving the flux of a gradient at a quadrature point, possibly depending on
+/// state variables stored per point in a QuadratureField
+///
+/// Mechanical models map the strain (ε_xx, ε_yy, ε_zz, 2 ε_yz, 2 ε_xz, 2 ε_xy) in Voigt notation
+/// to the stress (σ_xx, σ_yy, σ_zz, σ_yz, σ_xz, σ_xy), two dimensional models keeping the
+/// components xx, yy and xy of plane strain. Thermal models map the temperature gradient to the
+/// heat flux. The tangent is the derivative of the flux with respect to the gradient, row major.
+pub trait ConstitutiveModel {
+    /// Number of components of the gradient and the flux in a dimension
+    fn n_components(&self, dim: usize) -> usize;
+
+    /// Number of state variables of a quadrature point in a dimension, none by default
+    fn n_state(&self, _dim: usize) -> usize {
+        0
+    }
+
+    /// Initialize the state variables of a quadrature point, to zero by default
+    fn initial_state(&self, _dim: usize, state: &mut [f64]) {
+        state.iter_mut().for_each(|s| *s = 0.0);
+    }
+
+    /// Flux and tangent of a gradient given the state at the start of a step, filling the state
+    /// at its end
+    fn evaluate(
+        &self,
+        dim: usize,
+        gradient: &[f64],
+        state: &[f64],
+        flux: &mut [f64],
+        tangent: &mut [f64],
+        new_state: &mut [f64],
+    );
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Orthotropic linear elastic material whose axes of symmetry are the coordinate axes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrthotropicMaterial {
+    young_moduli: [f64; 3],
+    poisson_ratios: [f64; 3],
+    shear_moduli: [f64; 3],
+}
+
+/// Fourier conduction q = -K ∇T with a constant conductivity tensor K
+#[derive(Clone, Debug, PartialEq)]
+pub struct FourierConduction {
+    conductivity: Vec<f64>,
+    dim: usize,
+}
+
+/// Small strain J2 plasticity with linear isotropic hardening
+///
+/// The von Mises yield function √(3/2) |s| - (σ_y + H α) of the deviatoric stress s and the
+/// equivalent plastic strain α is enforced by the radial return, with the consistent tangent of
+/// the return. The state of a point is the plastic strain tensor (xx, yy, zz, yz, xz, xy) followed
+/// by α, kept in 3D for plane strain since the plastic strain has out of plane components.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct J2Plasticity {
+    elastic: IsotropicMaterial,
+    yield_stress: f64,
+    hardening: f64,
+}
+
+impl OrthotropicMaterial {
+    /// Material of Young moduli (E_x, E_y, E_z), Poisson ratios (ν_xy, ν_xz, ν_yz) and shear
+    /// moduli (G_yz, G_xz, G_xy)
+    pub fn new(young_moduli: [f64; 3], poisson_ratios: [f64; 3], shear_moduli: [f64; 3]) -> Self {
+        assert!(
+            young_moduli.iter().chain(&shear_moduli).all(|m| *m > 0.0),
+            "Moduli should be positive"
+        );
+        let material = OrthotropicMaterial {
+            young_moduli,
+            poisson_ratios,
+            shear_moduli,
+        };
+        let compliance = material.normal_compliance();
+        assert!(
+            compliance[0] * compliance[4] > compliance[1] * compliance[1]
+                && determinant(&compliance) > 0.0,
+            "Poisson ratios do not give a positive definite stiffness"
+        );
+        material
+    }
+
+    /// Stiffness of the Voigt strain of a dimension, row major
+    pub fn stiffness(&self, dim: usize) -> Vec<f64> {
+        let normal = inverse(&self.normal_compliance());
+        let mut stiffness = vec![0.0; 36];
+        for i in 0..3 {
+            for j in 0..3 {
+                stiffness[i * 6 + j] = normal[i * 3 + j];
+            }
+            stiffness[(i + 3) * 6 + i + 3] = self.shear_moduli[i];
+        }
+        restrict(&stiffness, dim)
+    }
+
+    // Compliance of the normal strains
+    fn normal_compliance(&self) -> [f64; 9] {
+        let [ex, ey, ez] = self.young_moduli;
+        let [nu_xy, nu_xz, nu_yz] = self.poisson_ratios;
+        [
+            1.0 / ex,
+            -nu_xy / ex,
+            -nu_xz / ex,
+            -nu_xy / ex,
+            1.0 / ey,
+            -nu_yz / ey,
+            -nu_xz / ex,
+            -nu_yz / ey,
+            1.0 / ez,
+        ]
+    }
+}
+
+impl FourierConduction {
+    /// Isotropic conduction of a positive conductivity in a dimension
+    pub fn isotropic(dim: usize, conductivity: f64) -> Self {
+        assert!(conductivity > 0.0, "Conductivity should be positive");
+        let mut tensor = vec![0.0; dim * dim];
+        (0..dim).for_each(|i| tensor[i * dim + i] = conductivity);
+        FourierConduction::anisotropic(dim, tensor)
+    }
+
+    /// Anisotropic conduction of a symmetric conductivity tensor, row major
+    pub fn anisotropic(dim: usize, conductivity: Vec<f64>) -> Self {
+        assert!(
+            conductivity.len() == dim * dim,
+            "Conductivity should be a square tensor of the dimension"
+        );
+        assert!(
+            (0..dim)
+                .all(|i| (0..dim).all(|j| conductivity[i * dim + j] == conductivity[j * dim + i])),
+            "Conductivity should be symmetric"
+        );
+        FourierConduction { conductivity, dim }
+    }
+
+    /// Conductivity tensor, row major
+    pub fn conductivity(&self) -> &[f64] {
+        &self.conductivity
+    }
+}
+
+impl J2Plasticity {
+    /// Elastoplastic material of an elastic law, a positive initial yield stress σ_y and a non
+    /// negative hardening modulus H
+    pub fn new(elastic: IsotropicMaterial, yield_stress: f64, hardening: f64) -> Self {
+        assert!(yield_stress > 0.0, "Yield stress should be positive");
+        assert!(hardening >= 0.0, "Hardening modulus should be non negative");
+        J2Plasticity {
+            elastic,
+            yield_stress,
+            hardening,
+        }
+    }
+
+    /// Elastic law
+    pub fn elastic(&self) -> &IsotropicMaterial {
+        &self.elastic
+    }
+
+    /// Yield stress σ_y + H α at an equivalent plastic strain α
+    pub fn yield_stress(&self, alpha: f64) -> f64 {
+        self.yield_stress + self.hardening * alpha
+    }
+}
+
+impl ConstitutiveModel for IsotropicMaterial {
+    fn n_components(&self, dim: usize) -> usize {
+        voigt(dim).len()
+    }
+
+    fn evaluate(
+        &self,
+        dim: usize,
+        gradient: &[f64],
+        _state: &[f64],
+        flux: &mut [f64],
+        tangent: &mut [f64],
+        _new_state: &mut [f64],
+    ) {
+        let (lambda, mu) = self.lame_parameters();
+        let tensor = |i, j, k, l| isotropic(lambda + 2.0 * mu / 3.0, mu, i, j, k, l);
+        linear_response(dim, tensor, gradient, flux, tangent);
+    }
+}
+
+impl ConstitutiveModel for OrthotropicMaterial {
+    fn n_components(&self, dim: usize) -> usize {
+        voigt(dim).len()
+    }
+
+    fn evaluate(
+        &self,
+        dim: usize,
+        gradient: &[f64],
+        _state: &[f64],
+        flux: &mut [f64],
+        tangent: &mut [f64],
+        _new_state: &mut [f64],
+    ) {
+        let stiffness = self.stiffness(dim);
+        let n = voigt(dim).len();
+        tangent.copy_from_slice(&stiffness);
+        for (i, f) in flux.iter_mut().enumerate() {
+            *f = (0..n).map(|j| stiffness[i * n + j] * gradient[j]).sum();
+        }
+    }
+}
+
+impl ConstitutiveModel for FourierConduction {
+    fn n_components(&self, dim: usize) -> usize {
+        dim
+    }
+
+    fn evaluate(
+        &self,
+        dim: usize,
+        gradient: &[f64],
+        _state: &[f64],
+        flux: &mut [f64],
+        tangent: &mut [f64],
+        _new_state: &mut [f64],
+    ) {
+        assert!(dim == self.dim, "Conductivity of another dimension");
+        for (i, f) in flux.iter_mut().enumerate() {
+            *f = -(0..dim)
+                .map(|j| self.conductivity[i * dim + j] * gradient[j])
+                .sum::<f64>();
+        }
+        tangent
+            .iter_mut()
+            .zip(&self.conductivity)
+            .for_each(|(t, k)| *t = -k);
+    }
+}
+
+impl ConstitutiveModel for J2Plasticity {
+    fn n_components(&self, dim: usize) -> usize {
+        voigt(dim).len()
+    }
+
+    fn n_state(&self, _dim: usize) -> usize {
+        7
+    }
+
+    fn evaluate(
+        &self,
+        dim: usize,
+        gradient: &[f64],
+        state: &[f64],
+        flux: &mut [f64],
+        tangent: &mut [f64],
+        new_state: &mut [f64],
+    ) {
+        // Consistent tangent κ 1 ⊗ 1 + 2 μ θ I_dev - 2 μ θ̄ n ⊗ n of Simo and Hughes
+        let (lambda, mu) = self.elastic.lame_parameters();
+        let bulk = lambda + 2.0 * mu / 3.0;
+        // Elastic strain and trial stress as full 3D tensors
+        let mut strain = [[0.0; 3]; 3];
+        for (k, (i, j)) in voigt(dim).iter().enumerate() {
+            let value = if i == j {
+                gradient[k]
+            } else {
+                0.5 * gradient[k]
+            };
+            strain[*i][*j] = value;
+            strain[*j][*i] = value;
+        }
+        for (k, (i, j)) in VOIGT_3D.iter().enumerate() {
+            strain[*i][*j] -= state[k];
+            if i != j {
+                strain[*j][*i] -= state[k];
+            }
+        }
+        let trace = strain[0][0] + strain[1][1] + strain[2][2];
+        let mut deviator = [[0.0; 3]; 3];
+        for i in 0..3 {
+            for j in 0..3 {
+                deviator[i][j] = 2.0 * mu * (strain[i][j] - if i == j { trace / 3.0 } else { 0.0 });
+            }
+        }
+        let norm = deviator.iter().flatten().map(|s| s * s).sum::<f64>().sqrt();
+        let alpha = state[6];
+        let trial = (1.5f64).sqrt() * norm - self.yield_stress(alpha);
+        new_state[..7].copy_from_slice(&state[..7]);
+        let (theta, theta_bar, direction) = if trial > 0.0 {
+            let increment = trial / (3.0 * mu + self.hardening);
+            let mut direction = deviator;
+            direction.iter_mut().flatten().for_each(|n| *n /= norm);
+            // Return of the stress and plastic flow along the deviatoric direction
+            let flow = (1.5f64).sqrt() * increment;
+            for (k, (i, j)) in VOIGT_3D.iter().enumerate() {
+                new_state[k] += flow * direction[*i][*j];
+            }
+            new_state[6] = alpha + increment;
+            deviator
+                .iter_mut()
+                .flatten()
+                .zip(direction.iter().flatten())
+                .for_each(|(s, n)| *s -= 2.0 * mu * flow * n);
+            let theta = 1.0 - 2.0 * mu * flow / norm;
+            let theta_bar = 3.0 * mu / (3.0 * mu + self.hardening) - (1.0 - theta);
+            (theta, theta_bar, direction)
+        } else {
+            (1.0, 0.0, [[0.0; 3]; 3])
+        };
+        let pairs = voigt(dim);
+        for (a, (i, j)) in pairs.iter().enumerate() {
+            flux[a] = deviator[*i][*j] + if i == j { bulk * trace } else { 0.0 };
+            for (b, (k, l)) in pairs.iter().enumerate() {
+                tangent[a * pairs.len() + b] = isotropic(bulk, mu * theta, *i, *j, *k, *l)
+                    - 2.0 * mu * theta_bar * direction[*i][*j] * direction[*k][*l];
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Tensor indices of the Voigt components of a dimension
+fn voigt(dim: usize) -> &'static [(usize, usize)] {
+    match dim {
+        2 => &VOIGT_2D,
+        3 => &VOIGT_3D,
+        _ => panic!("Mechanical models are 2D or 3D"),
+    }
+}
+
+// Kronecker delta
+fn delta(i: usize, j: usize) -> f64 {
+    if i == j {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+// Component of the isotropic stiffness κ 1 ⊗ 1 + 2 μ I_dev
+fn isotropic(bulk: f64, mu: f64, i: usize, j: usize, k: usize, l: usize) -> f64 {
+    bulk * delta(i, j) * delta(k, l) + mu * (delta(i, k) * delta(j, l) + delta(i, l) * delta(j, k))
+        - 2.0 * mu / 3.0 * delta(i, j) * delta(k, l)
+}
+
+// Stress and tangent of a linear law given by the components of its stiffness tensor
+fn linear_response<Tensor>(
+    dim: usize,
+    tensor: Tensor,
+    gradient: &[f64],
+    flux: &mut [f64],
+    tangent: &mut [f64],
+) where
+    Tensor: Fn(usize, usize, usize, usize) -> f64,
+{
+    let pairs = voigt(dim);
+    let n = pairs.len();
+    for (a, (i, j)) in pairs.iter().enumerate() {
+        for (b, (k, l)) in pairs.iter().enumerate() {
+            tangent[a * n + b] = tensor(*i, *j, *k, *l);
+        }
+        flux[a] = (0..n).map(|b| tangent[a * n + b] * gradient[b]).sum();
+    }
+}
+
+// Voigt stiffness of a dimension from the 3D one, plane strain keeping the xx, yy and xy rows
+fn restrict(stiffness: &[f64], dim: usize) -> Vec<f64> {
+    let rows: Vec<usize> = voigt(dim)
+        .iter()
+        .map(|pair| VOIGT_3D.iter().position(|p| p == pair).unwrap())
+        .collect();
+    rows.iter()
+        .flat_map(|i| rows.iter().map(move |j| stiffness[i * 6 + j]))
+        .collect()
+}
+
+// Determinant of a 3x3 matrix, row major
+fn determinant(m: &[f64; 9]) -> f64 {
+    m[0] * (m[4] * m[8] - m[5] * m[7]) - m[1] * (m[3] * m[8] - m[5] * m[6])
+        + m[2] * (m[3] * m[7] - m[4] * m[6])
+}
+
+// Inverse of a 3x3 matrix, row major
+fn inverse(m: &[f64; 9]) -> [f64; 9] {
+    let det = determinant(m);
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        m[r0 * 3 + c0] * m[r1 * 3 + c1] - m[r0 * 3 + c1] * m[r1 * 3 + c0]
+    };
+    let mut inverse = [0.0; 9];
+    for i in 0..3 {
+        for j in 0..3 {
+            inverse[j * 3 + i] = cofactor(i, j) / det;
+        }
+    }
+    inverse
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::quadrature_field::QuadratureField;
+    use crate::spaces::quadrature::QuadratureRule;
+
+    #[test]
+    fn test_elastic_materials() {
+        let isotropic = IsotropicMaterial::new(200.0, 0.3);
+        let (lambda, mu) = isotropic.lame_parameters();
+        let shear = 200.0 / 2.6;
+        let orthotropic = OrthotropicMaterial::new([200.0; 3], [0.3; 3], [shear; 3]);
+        let strain = [1e-3, -2e-3, 4e-3];
+        let (mut stress, mut tangent) = ([0.0; 3], [0.0; 9]);
+        isotropic.evaluate(2, &strain, &[], &mut stress, &mut tangent, &mut []);
+        let plane_strain = [
+            lambda + 2.0 * mu,
+            lambda,
+            0.0,
+            lambda,
+            lambda + 2.0 * mu,
+            0.0,
+            0.0,
+            0.0,
+            mu,
+        ];
+        for (t, p) in tangent.iter().zip(&plane_strain) {
+            assert!((t - p).abs() < 1e-10, "Wrong isotropic tangent");
+        }
+        assert!(
+            (stress[2] - mu * strain[2]).abs() < 1e-12,
+            "Wrong shear stress"
+        );
+        let mut other = [0.0; 3];
+        orthotropic.evaluate(2, &strain, &[], &mut other, &mut tangent, &mut []);
+        for (s, o) in stress.iter().zip(&other) {
+            assert!(
+                (s - o).abs() < 1e-10,
+                "Orthotropic material is not isotropic"
+            );
+        }
+        // Stiffer fibers along x
+        let fibers = OrthotropicMaterial::new([1000.0, 100.0, 100.0], [0.3, 0.3, 0.4], [50.0; 3]);
+        let stiffness = fibers.stiffness(3);
+        assert!(
+            stiffness[0] > 5.0 * stiffness[7],
+            "Fibers should be stiffer"
+        );
+        let conduction = FourierConduction::anisotropic(2, vec![2.0, 1.0, 1.0, 3.0]);
+        let (mut flux, mut tangent) = ([0.0; 2], [0.0; 4]);
+        conduction.evaluate(2, &[1.0, -1.0], &[], &mut flux, &mut tangent, &mut []);
+        assert_eq!(flux, [-1.0, 2.0], "Wrong heat flux");
+        assert_eq!(
+            tangent,
+            [-2.0, -1.0, -1.0, -3.0],
+            "Wrong conduction tangent"
+        );
+        assert_eq!(conduction.n_state(2), 0, "Conduction has no state");
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_j2_plasticity() {
+        let (yield_stress, hardening) = (1.0, 10.0);
+        let model = J2Plasticity::new(IsotropicMaterial::new(200.0, 0.3), yield_stress, hardening);
+        let mu = model.elastic().lame_parameters().1;
+        // Monotonic simple shear of one point in 3D
+        let point = CartesianGrid::new(vec![0.0; 3], vec![1.0; 3], vec![1; 3]).simplex_mesh();
+        let mut states = QuadratureField::new(&point, &QuadratureRule::simplex(3, 1), 7);
+        states.fill_with(|state| model.initial_state(3, state));
+        let (mut stress, mut tangent) = ([0.0; 6], [0.0; 36]);
+        let steps = 30;
+        for step in 1..=steps {
+            let gamma = 0.03 * step as f64 / steps as f64;
+            let strain = [0.0, 0.0, 0.0, 0.0, 0.0, gamma];
+            let (state, new_state) = states.point_mut(0, 0);
+            model.evaluate(3, &strain, state, &mut stress, &mut tangent, new_state);
+            states.commit();
+            // τ = γ μ elastically and γ = τ / μ + (3 τ - √3 σ_y) / H once yielded
+            let elastic = mu * gamma;
+            let expected = if 3f64.sqrt() * elastic <= yield_stress {
+                elastic
+            } else {
+                (gamma + 3f64.sqrt() * yield_stress / hardening) / (1.0 / mu + 3.0 / hardening)
+            };
+            assert!(
+                (stress[5] - expected).abs() < 1e-12,
+                "Wrong shear stress {} at step {}",
+                stress[5],
+                step
+            );
+        }
+        let alpha = states.committed(0, 0)[6];
+        assert!(
+            (3f64.sqrt() * stress[5] - model.yield_stress(alpha)).abs() < 1e-12,
+            "Stress is not on the yield surface"
+        );
+        // Consistent tangent of a plastic plane strain step against finite differences
+        let state = states.committed(0, 0).to_vec();
+        let strain = [0.02, -0.01, 0.04];
+        let (mut stress, mut tangent, mut new_state) = ([0.0; 3], [0.0; 9], [0.0; 7]);
+        model.evaluate(
+            2,
+            &strain,
+            &state,
+            &mut stress,
+            &mut tangent,
+            &mut new_state,
+        );
+        assert!(new_state[6] > alpha, "Step should be plastic");
+        let h = 1e-7;
+        for j in 0..3 {
+            let mut shifted = strain;
+            shifted[j] += h;
+            let mut perturbed = [0.0; 3];
+            model.evaluate(
+                2,
+                &shifted,
+                &state,
+                &mut perturbed,
+                &mut [0.0; 9],
+                &mut [0.0; 7],
+            );
+            for i in 0..3 {
+                let derivative = (perturbed[i] - stress[i]) / h;
+                assert!(
+                    (derivative - tangent[i * 3 + j]).abs() < 1e-4 * mu,
+                    "Inconsistent tangent ({}, {}): {} and {}",
+                    i,
+                    j,
+                    tangent[i * 3 + j],
+                    derivative
+                );
+            }
+        }
+    }
+}
@@ -1 +1,105 @@
+/// Transient heat equation solved by the theta method with time series output
+pub mod heat;
 
+/// Static linear elasticity with isotropic materials and stress recovery
+pub mod elasticity;
+
+/// Steady Stokes flow on stable or stabilized mixed elements with block solvers
+pub mod stokes;
+
+/// Darcy flow in heterogeneous porous media on Raviart-Thomas velocities with surface fluxes
+pub mod darcy;
+
+/// Electrostatic potentials in dielectrics with electrode charges and capacitance matrices
+pub mod electrostatics;
+
+/// Magnetostatics and time harmonic eddy currents on Nédélec edge elements
+pub mod magnetostatics;
+
+/// Steady advection diffusion with streamline stabilizations and Péclet diagnostics
+pub mod advection_diffusion;
+
+/// Level set representation of interfaces with signed distances, reinitialization and transport
+pub mod level_set;
+
+/// Arbitrary Lagrangian-Eulerian mesh motion, mesh velocities and transport on moving meshes
+pub mod ale;
+
+/// Partitioned coupling of workflows exchanging interface fields by accelerated fixed points
+pub mod coupling;
+
+/// Adapter of coupled workflows to co-simulations with external codes through preCICE
+#[cfg(feature = "precice")]
+pub mod precice;
+
+/// Frictionless contact of elastic bodies by penalty or augmented Lagrangian enforcement
+pub mod contact;
+
+/// Vibration modes and natural frequencies of elastic bodies by shift-invert Lanczos
+pub mod modal;
+
+/// Critical load factors and buckling modes of prestressed elastic bodies
+pub mod buckling;
+
+/// Forced harmonic response of damped elastic bodies over frequency sweeps
+pub mod harmonic;
+
+/// Thermoelasticity of conducting bodies coupling temperatures and displacements one or two ways
+pub mod thermoelasticity;
+
+/// Brittle fracture by staggered phase field damage with history fields and crack paths
+pub mod phase_field;
+
+/// Transient wave equation by implicit dynamics schemes or explicit central differences
+pub mod wave;
+
+/// Constitutive models of elastic, plastic and conducting materials with quadrature point states
+pub mod materials;
+
+/// Observers of the events of workflow runs for monitoring and early termination
+pub mod events;
+
+/// Detection of the steady states of transient workflows stopping their time stepping
+pub mod steady_state;
+
+/// Declarative simulation descriptions building and running the workflows
+#[cfg(hosted)]
+pub mod config;
+
+/// Convergence studies against manufactured solutions with observed rates and reports
+pub mod convergence_study;
+/// Verification of workflows against manufactured solutions with derived forcings and asserted orders
+pub mod verification;
+
+/// Benchmark problems with their meshes, boundary conditions and reference values
+pub mod benchmarks;
+
+/// Batches of workflow runs over parameter sets collected into summary tables
+pub mod parameter_sweep;
+
+/// Monte Carlo propagation of random parameters to statistics of quantities and fields
+pub mod uq;
+
+/// Gaussian random fields of covariance kernels by Karhunen-Loève expansions or circulant embedding
+pub mod random_field;
+
+/// Adaptive mesh refinement loops driven by a posteriori error estimators
+pub mod amr;
+
+/// Quantities of interest evaluated after solves and logged to tables
+pub mod quantities;
+
+/// Monitor points, lines and surfaces sampled every step and streamed to CSV files
+pub mod probes;
+
+/// Adjoint solves giving the sensitivities of quantities of interest to problem parameters
+pub mod adjoint;
+
+/// Bound constrained minimization of design objectives by gradient descent or L-BFGS
+pub mod optimize;
+
+/// Reduced order models by proper orthogonal decomposition and Galerkin projection
+pub mod rom;
+
+/// Post-processing pipelines of derived fields and integral quantities
+pub mod postprocess;
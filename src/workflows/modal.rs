@@ -0,0 +1,230 @@
+use super::elasticity::LinearElasticity;
+use crate::core::logging::{info, Span};
+use crate::discretizations::function::Function;
+#[cfg(hosted)]
+use crate::discretizations::io::vtk;
+use crate::solvers::eigen::Lanczos;
+use crate::solvers::krylov::dot;
+use std::f64::consts::PI;
+use std::fmt::Write;
+#[cfg(hosted)]
+use std::io::Result;
+#[cfg(hosted)]
+use std::path::Path;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Free vibration modes K φ = ω² M φ of an elastic body
+///
+/// The stiffness of a linear elasticity problem, whose imposed displacements become fixed
+/// supports and whose loads are ignored, and its consistent mass matrix for a density are handed
+/// to the shift-invert Lanczos solver. The modes closest to the shift are found, by default the
+/// lowest ones with a shift of 0, which has to be moved below 0 when rigid motions are left free.
+/// Mode shapes are normalized to a unit modal mass φᵀ M φ = 1.
+pub struct ModalAnalysis<'a> {
+    elasticity: LinearElasticity<'a>,
+    density: f64,
+    n_modes: usize,
+    shift: f64,
+    tolerance: f64,
+}
+
+/// Vibration modes sorted by increasing eigenvalue
+pub struct Modes<'a> {
+    eigenvalues: Vec<f64>,
+    shapes: Vec<Function<'a>>,
+    converged: bool,
+}
+
+impl<'a> ModalAnalysis<'a> {
+    /// Analysis of the given number of modes of an elasticity problem with a density
+    pub fn new(elasticity: LinearElasticity<'a>, density: f64, n_modes: usize) -> Self {
+        assert!(density > 0.0, "Density should be positive");
+        assert!(n_modes > 0, "At least one mode should be wanted");
+        ModalAnalysis {
+            elasticity,
+            density,
+            n_modes,
+            shift: 0.0,
+            tolerance: 1e-10,
+        }
+    }
+
+    /// Set the shift the squared angular frequencies of the modes are closest to
+    pub fn with_shift(mut self, shift: f64) -> Self {
+        self.shift = shift;
+        self
+    }
+
+    /// Set the tolerance of the eigensolver on the residuals relative to the eigenvalues
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Solve for the modes
+    pub fn solve(&self) -> Modes<'a> {
+        let _span = Span::enter("modal analysis");
+        let space = self.elasticity.space();
+        let (stiffness, _, constraints) = self.elasticity.assemble();
+        let mass = self.elasticity.mass_matrix(self.density);
+        let pairs = Lanczos::new(self.n_modes)
+            .with_shift(self.shift)
+            .with_tolerance(self.tolerance)
+            .solve(&stiffness, Some(&mass));
+        let mut modes: Vec<(f64, Function<'a>)> = (0..pairs.len())
+            .map(|k| {
+                let mut shape = pairs.vector(k).to_vec();
+                let mut mass_shape = vec![0.0; shape.len()];
+                mass.apply(&shape, &mut mass_shape);
+                let scale = dot(&shape, &mass_shape).sqrt();
+                shape.iter_mut().for_each(|v| *v /= scale);
+                constraints.distribute_homogeneous(&mut shape);
+                (pairs.values()[k], Function::from_values(space, shape))
+            })
+            .collect();
+        modes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let (eigenvalues, shapes) = modes.into_iter().unzip();
+        let modes = Modes {
+            eigenvalues,
+            shapes,
+            converged: pairs.converged(),
+        };
+        info!(
+            "Modal analysis found {} modes from {:e} Hz to {:e} Hz",
+            modes.len(),
+            modes.frequency(0),
+            modes.frequency(modes.len() - 1)
+        );
+        modes
+    }
+}
+
+impl<'a> Modes<'a> {
+    /// Number of modes
+    pub fn len(&self) -> usize {
+        self.eigenvalues.len()
+    }
+
+    /// Whether no mode was found
+    pub fn is_empty(&self) -> bool {
+        self.eigenvalues.is_empty()
+    }
+
+    /// Eigenvalues ω² of the modes
+    pub fn eigenvalues(&self) -> &[f64] {
+        &self.eigenvalues
+    }
+
+    /// Angular frequency ω of a mode, negative eigenvalues of unstable problems giving 0
+    pub fn angular_frequency(&self, mode: usize) -> f64 {
+        self.eigenvalues[mode].max(0.0).sqrt()
+    }
+
+    /// Frequency ω / 2π of a mode
+    pub fn frequency(&self, mode: usize) -> f64 {
+        self.angular_frequency(mode) / (2.0 * PI)
+    }
+
+    /// Mass normalized shape of a mode
+    pub fn shape(&self, mode: usize) -> &Function<'a> {
+        &self.shapes[mode]
+    }
+
+    /// Whether the eigensolver met its tolerance
+    pub fn converged(&self) -> bool {
+        self.converged
+    }
+
+    /// Comma separated table of the modes with their eigenvalues and frequencies
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("mode,eigenvalue,angular_frequency,frequency\n");
+        for mode in 0..self.len() {
+            writeln!(
+                csv,
+                "{},{},{},{}",
+                mode,
+                self.eigenvalues[mode],
+                self.angular_frequency(mode),
+                self.frequency(mode)
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    /// Write the mode shapes to a legacy VTK file as the fields mode_0, mode_1...
+    #[cfg(hosted)]
+    pub fn save_vtk<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let names: Vec<String> = (0..self.len()).map(|k| format!("mode_{}", k)).collect();
+        let functions: Vec<(&str, &Function)> = names
+            .iter()
+            .zip(&self.shapes)
+            .map(|(name, shape)| (name.as_str(), shape))
+            .collect();
+        vtk::save_vtk(path, self.shapes[0].space().mesh(), &functions)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::workflows::elasticity::IsotropicMaterial;
+
+    #[test]
+    fn test_modal_analysis() {
+        // Square on rollers without Poisson effect: the lowest modes are the pressure waves
+        // u = (sin(π x), 0) and u = (0, sin(π y)) and the shear wave of the stream function
+        // sin(π x) sin(π y), all with ω² = E π² / ρ, followed by the pressure wave of the potential
+        // cos(π x) cos(π y) with twice this value
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![8, 8]).simplex_mesh();
+        let space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        let elasticity = || {
+            LinearElasticity::new(&space, IsotropicMaterial::new(2.0, 0.0))
+                .with_component_displacement(1, 0, 0.0)
+                .with_component_displacement(2, 0, 0.0)
+                .with_component_displacement(3, 1, 0.0)
+                .with_component_displacement(4, 1, 0.0)
+        };
+        let modes = ModalAnalysis::new(elasticity(), 0.5, 4).solve();
+        assert!(modes.converged(), "Eigensolver did not converge");
+        assert_eq!(modes.len(), 4, "Wrong number of modes");
+        for (value, factor) in modes.eigenvalues().iter().zip([1.0, 1.0, 1.0, 2.0]) {
+            let expected = factor * 4.0 * PI * PI;
+            assert!(
+                (value - expected).abs() < 1e-3 * expected,
+                "Wrong eigenvalue {}",
+                value
+            );
+        }
+        assert!(
+            (modes.frequency(0) - 1.0).abs() < 1e-3,
+            "Frequency of the first mode should be 1 Hz"
+        );
+        let mass = elasticity().mass_matrix(0.5);
+        for mode in 0..4 {
+            let shape = modes.shape(mode).values();
+            let mut mass_shape = vec![0.0; shape.len()];
+            mass.apply(shape, &mut mass_shape);
+            assert!(
+                (dot(shape, &mass_shape) - 1.0).abs() < 1e-10,
+                "Mode is not mass normalized"
+            );
+        }
+        assert!(
+            modes
+                .to_csv()
+                .starts_with("mode,eigenvalue,angular_frequency,frequency\n0,"),
+            "Wrong table"
+        );
+    }
+}
@@ -0,0 +1,618 @@
+use crate::core::logging::{debug, info, Span};
+use crate::solvers::krylov::{dot, norm};
+use crate::solvers::stopping_criterion::{StopReason, StoppingCriterion};
+use std::collections::VecDeque;
+use std::fmt::Write;
+#[cfg(hosted)]
+use std::path::Path;
+
+//--------------------------------------------------------------------------------------------------
+// # Traits
+//--------------------------------------------------------------------------------------------------
+
+/// Objective of design parameters to minimize with its gradient, usually a quantity of interest of
+/// the solution of a problem depending on the parameters differentiated by an adjoint solve (see
+/// workflows::adjoint)
+pub trait DesignProblem {
+    /// Number of design parameters
+    fn n_parameters(&self) -> usize;
+
+    /// Value of the objective at some parameters, filling its gradient with respect to them
+    fn objective(&self, parameters: &[f64], gradient: &mut [f64]) -> f64;
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Descent direction of an optimizer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OptimizationMethod {
+    /// Steepest descent along the opposite of the gradient
+    GradientDescent,
+    /// Limited memory BFGS keeping the given number of past updates to approximate the inverse
+    /// hessian
+    Lbfgs(usize),
+}
+
+/// Minimizer of the objective of a design problem within bounds on the parameters
+///
+/// Every iteration projects onto the bounds a step along the descent direction restricted to the
+/// parameters which are free to move, the parameters on a bound the gradient pushes against being
+/// held fixed, and backtracks it until the Armijo condition holds. Iterations stop when the norm
+/// of the projected gradient falls below the largest of the absolute tolerance and the relative
+/// tolerance times its initial norm, or without convergence when no step decreases the
+/// objective. By default L-BFGS with 10 updates is used, the relative tolerance is 1e-8, the
+/// absolute tolerance 1e-12, at most 100 iterations are done, the parameters are unbounded and
+/// the first step has length 1.
+pub struct Optimizer {
+    method: OptimizationMethod,
+    stopping: StoppingCriterion,
+    bounds: Option<(Vec<f64>, Vec<f64>)>,
+    initial_step: f64,
+}
+
+/// Outcome of an optimization with the history of its iterations
+///
+/// Every iteration gets a row with its index, the objective, the norm of the projected gradient
+/// and the length of the step leading to it, followed by the parameters p0, p1...
+#[derive(Clone, Debug, PartialEq)]
+pub struct Optimization {
+    reason: StopReason,
+    n_parameters: usize,
+    rows: Vec<Vec<f64>>,
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Optimizer {
+            method: OptimizationMethod::Lbfgs(10),
+            stopping: StoppingCriterion::new(1e-8, 1e-12, 100),
+            bounds: None,
+            initial_step: 1.0,
+        }
+    }
+}
+
+impl Optimizer {
+    /// Optimizer with the default parameters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the descent direction
+    pub fn with_method(mut self, method: OptimizationMethod) -> Self {
+        if let OptimizationMethod::Lbfgs(memory) = method {
+            assert!(memory > 0, "L-BFGS needs to keep at least one update");
+        }
+        self.method = method;
+        self
+    }
+
+    /// Set the tolerance on the projected gradient relative to the initial one
+    pub fn with_relative_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_relative_tolerance(tolerance);
+        self
+    }
+
+    /// Set the tolerance on the norm of the projected gradient
+    pub fn with_absolute_tolerance(mut self, tolerance: f64) -> Self {
+        self.stopping = self.stopping.with_absolute_tolerance(tolerance);
+        self
+    }
+
+    /// Set the maximum number of iterations
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.stopping = self.stopping.with_max_iterations(max_iterations);
+        self
+    }
+
+    /// Replace the whole stopping criterion, the residual being the projected gradient and the
+    /// reference norm its initial norm
+    pub fn with_stopping_criterion(mut self, stopping: StoppingCriterion) -> Self {
+        self.stopping = stopping;
+        self
+    }
+
+    /// Set lower and upper bounds on the parameters, which may be infinite
+    pub fn with_bounds(mut self, lower: &[f64], upper: &[f64]) -> Self {
+        assert!(
+            lower.len() == upper.len() && lower.iter().zip(upper).all(|(l, u)| l <= u),
+            "Lower bounds should not exceed upper bounds"
+        );
+        self.bounds = Some((lower.to_vec(), upper.to_vec()));
+        self
+    }
+
+    /// Set the length of the first step, before any curvature information is available
+    pub fn with_initial_step(mut self, step: f64) -> Self {
+        assert!(step > 0.0, "Steps should be positive");
+        self.initial_step = step;
+        self
+    }
+
+    /// Minimize the objective starting from the parameters given, which end at the best design
+    /// found
+    pub fn minimize<Problem>(&self, problem: &Problem, parameters: &mut [f64]) -> Optimization
+    where
+        Problem: DesignProblem,
+    {
+        let _span = Span::enter("optimization");
+        let n = problem.n_parameters();
+        assert_eq!(
+            parameters.len(),
+            n,
+            "Parameters do not match the design problem"
+        );
+        if let Some((lower, _)) = &self.bounds {
+            assert_eq!(lower.len(), n, "Bounds do not match the design problem");
+        }
+        self.project(parameters);
+        let mut gradient = vec![0.0; n];
+        let mut objective = problem.objective(parameters, &mut gradient);
+        let mut gradient_norm = self.projected_gradient_norm(parameters, &gradient);
+        let mut test = self.stopping.start(gradient_norm);
+        let mut history = Optimization {
+            reason: StopReason::MaxIterations,
+            n_parameters: n,
+            rows: Vec::new(),
+        };
+        // Past steps s and gradient changes y of L-BFGS, the newest last
+        let mut memory: VecDeque<(Vec<f64>, Vec<f64>)> = VecDeque::new();
+        let mut direction = vec![0.0; n];
+        let mut trial = vec![0.0; n];
+        let mut trial_gradient = vec![0.0; n];
+        let mut step = 0.0;
+        let mut length = self.initial_step;
+        let mut iterations = 0;
+        let mut update = None;
+        history.reason = loop {
+            history.push(iterations, objective, gradient_norm, step, parameters);
+            debug!(
+                "Optimization iteration {}: objective {:e}, projected gradient {:e}",
+                iterations, objective, gradient_norm
+            );
+            if let Some(reason) = test.check(iterations, gradient_norm, update) {
+                break reason;
+            }
+            iterations += 1;
+            let free: Vec<bool> = (0..n)
+                .map(|i| self.is_free(parameters, &gradient, i))
+                .collect();
+            self.direction(&gradient, &free, &memory, &mut direction);
+            if dot(&gradient, &direction) >= 0.0 {
+                // Curvature information went stale: restart along the steepest descent
+                memory.clear();
+                self.direction(&gradient, &free, &memory, &mut direction);
+            }
+            // Start from a full quasi Newton step, or from the last steepest descent length
+            let direction_norm = norm(&direction);
+            let mut alpha = if memory.is_empty() {
+                length / direction_norm
+            } else {
+                1.0
+            };
+            let mut accepted = false;
+            for _ in 0..40 {
+                for i in 0..n {
+                    trial[i] = parameters[i] + alpha * direction[i];
+                }
+                self.project(&mut trial);
+                let decrease: f64 = (0..n)
+                    .map(|i| gradient[i] * (trial[i] - parameters[i]))
+                    .sum();
+                if decrease >= 0.0 {
+                    break;
+                }
+                let trial_objective = problem.objective(&trial, &mut trial_gradient);
+                if trial_objective <= objective + 1e-4 * decrease {
+                    objective = trial_objective;
+                    accepted = true;
+                    break;
+                }
+                alpha /= 2.0;
+            }
+            if !accepted {
+                break StopReason::Breakdown;
+            }
+            let s: Vec<f64> = (0..n).map(|i| trial[i] - parameters[i]).collect();
+            let y: Vec<f64> = (0..n).map(|i| trial_gradient[i] - gradient[i]).collect();
+            step = norm(&s);
+            length = 2.0 * alpha * direction_norm;
+            if let OptimizationMethod::Lbfgs(size) = self.method {
+                // Only updates of positive curvature keep the inverse hessian positive definite
+                if dot(&s, &y) > 1e-12 * step * norm(&y) {
+                    if memory.len() == size {
+                        memory.pop_front();
+                    }
+                    memory.push_back((s, y));
+                }
+            }
+            parameters.copy_from_slice(&trial);
+            gradient.copy_from_slice(&trial_gradient);
+            gradient_norm = self.projected_gradient_norm(parameters, &gradient);
+            update = Some((step, norm(parameters)));
+        };
+        info!(
+            "Optimization stopped after {} iterations ({:?}) at objective {:e}",
+            iterations, history.reason, objective
+        );
+        history
+    }
+
+    // Clamp parameters to the bounds
+    fn project(&self, parameters: &mut [f64]) {
+        if let Some((lower, upper)) = &self.bounds {
+            for (p, (l, u)) in parameters.iter_mut().zip(lower.iter().zip(upper)) {
+                *p = p.clamp(*l, *u);
+            }
+        }
+    }
+
+    // Whether a parameter may move along the opposite of the gradient without leaving the bounds
+    fn is_free(&self, parameters: &[f64], gradient: &[f64], i: usize) -> bool {
+        match &self.bounds {
+            Some((lower, upper)) => {
+                !(parameters[i] <= lower[i] && gradient[i] > 0.0
+                    || parameters[i] >= upper[i] && gradient[i] < 0.0)
+            }
+            None => true,
+        }
+    }
+
+    // Norm of the projected gradient P(p - g) - p, vanishing at the constrained minimizers
+    fn projected_gradient_norm(&self, parameters: &[f64], gradient: &[f64]) -> f64 {
+        let mut projected: Vec<f64> = parameters
+            .iter()
+            .zip(gradient)
+            .map(|(p, g)| p - g)
+            .collect();
+        self.project(&mut projected);
+        projected
+            .iter()
+            .zip(parameters)
+            .map(|(q, p)| (q - p) * (q - p))
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    // Descent direction on the free parameters by the two loop recursion of L-BFGS, the steepest
+    // descent when no update is kept
+    fn direction(
+        &self,
+        gradient: &[f64],
+        free: &[bool],
+        memory: &VecDeque<(Vec<f64>, Vec<f64>)>,
+        direction: &mut [f64],
+    ) {
+        let restrict = |v: &mut [f64]| {
+            v.iter_mut()
+                .zip(free)
+                .filter(|(_, free)| !**free)
+                .for_each(|(v, _)| *v = 0.0)
+        };
+        direction.copy_from_slice(gradient);
+        restrict(direction);
+        let mut alphas = Vec::with_capacity(memory.len());
+        for (s, y) in memory.iter().rev() {
+            let alpha = dot(s, direction) / dot(s, y);
+            direction
+                .iter_mut()
+                .zip(y)
+                .for_each(|(d, y)| *d -= alpha * y);
+            alphas.push(alpha);
+        }
+        if let Some((s, y)) = memory.back() {
+            let scale = dot(s, y) / dot(y, y);
+            direction.iter_mut().for_each(|d| *d *= scale);
+        }
+        for ((s, y), alpha) in memory.iter().zip(alphas.iter().rev()) {
+            let beta = dot(y, direction) / dot(s, y);
+            direction
+                .iter_mut()
+                .zip(s)
+                .for_each(|(d, s)| *d += (alpha - beta) * s);
+        }
+        restrict(direction);
+        direction.iter_mut().for_each(|d| *d = -*d);
+    }
+}
+
+impl Optimization {
+    /// Reason the optimization stopped
+    pub fn reason(&self) -> StopReason {
+        self.reason
+    }
+
+    /// Whether the projected gradient met the tolerances
+    pub fn converged(&self) -> bool {
+        self.reason.converged()
+    }
+
+    /// Number of iterations done
+    pub fn iterations(&self) -> usize {
+        self.rows.len() - 1
+    }
+
+    /// Objective at the final parameters
+    pub fn objective(&self) -> f64 {
+        self.rows.last().unwrap()[1]
+    }
+
+    /// Names of the columns of the history
+    pub fn columns(&self) -> Vec<String> {
+        let mut columns: Vec<String> = ["iteration", "objective", "gradient_norm", "step"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        columns.extend((0..self.n_parameters).map(|k| format!("p{}", k)));
+        columns
+    }
+
+    /// Rows of the history, one per iteration starting with the initial parameters
+    pub fn rows(&self) -> &[Vec<f64>] {
+        &self.rows
+    }
+
+    /// Comma separated table with a header and one row per iteration
+    pub fn to_csv(&self) -> String {
+        let mut csv = self.columns().join(",");
+        csv.push('\n');
+        for row in &self.rows {
+            let values: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+            writeln!(csv, "{}", values.join(",")).unwrap();
+        }
+        csv
+    }
+
+    /// JSON array with one object per iteration, non finite values being null
+    pub fn to_json(&self) -> String {
+        let columns = self.columns();
+        let mut json = String::from("[\n");
+        for (k, row) in self.rows.iter().enumerate() {
+            let fields: Vec<String> = columns
+                .iter()
+                .zip(row)
+                .map(|(c, v)| {
+                    if v.is_finite() {
+                        format!("\"{}\": {}", c, v)
+                    } else {
+                        format!("\"{}\": null", c)
+                    }
+                })
+                .collect();
+            let separator = if k + 1 < self.rows.len() { "," } else { "" };
+            writeln!(json, "  {{{}}}{}", fields.join(", "), separator).unwrap();
+        }
+        json.push_str("]\n");
+        json
+    }
+
+    /// Write the history as JSON if the path has the json extension and as CSV otherwise
+    #[cfg(hosted)]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let contents = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => self.to_json(),
+            _ => self.to_csv(),
+        };
+        std::fs::write(path, contents)
+    }
+
+    // Log an iteration
+    fn push(&mut self, iteration: usize, objective: f64, gradient_norm: f64, step: f64, p: &[f64]) {
+        let mut row = vec![iteration as f64, objective, gradient_norm, step];
+        row.extend_from_slice(p);
+        self.rows.push(row);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::sparse_csr::SparseCSR;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::constraints::AffineConstraints;
+    use crate::discretizations::function::Function;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::discretizations::operators::stiffness_matrix;
+    use crate::discretizations::sparsity::SparsityPattern;
+    use crate::solvers::config::{MethodConfig, SolverConfig};
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::spaces::quadrature::QuadratureRule;
+    use crate::workflows::adjoint::{gradient, Adjoint};
+
+    // Identification of the diffusivity k and source strength s of -k Δu = s from observations of
+    // u, with u = 0 on the left and right sides
+    struct Identification<'a> {
+        space: &'a FunctionSpace<'a>,
+        stiffness: SparseCSR<f64>,
+        load: Vec<f64>,
+        constraints: AffineConstraints,
+        observations: Vec<f64>,
+    }
+
+    impl<'a> Identification<'a> {
+        fn new(space: &'a FunctionSpace<'a>, k: f64, s: f64) -> Self {
+            let assembler = space.assembler(QuadratureRule::simplex(2, 2));
+            let mut load = vec![0.0; space.n_dofs()];
+            assembler.assemble_vector(&mut load, |values, local| {
+                for q in 0..values.n_points() {
+                    for i in 0..values.n_dofs() {
+                        local[i] += values.shape_value(q, i) * values.weight(q);
+                    }
+                }
+            });
+            let mut constraints = AffineConstraints::new(space.n_dofs());
+            for dof in space.tagged_dofs(1).into_iter().chain(space.tagged_dofs(2)) {
+                constraints.add_dirichlet(dof, 0.0);
+            }
+            constraints.close();
+            let mut problem = Identification {
+                space,
+                stiffness: stiffness_matrix(&assembler, |_| 1.0),
+                load,
+                constraints,
+                observations: Vec::new(),
+            };
+            problem.observations = problem.forward(k, s).0.into_values();
+            problem
+        }
+
+        // Solution and condensed matrix of the forward problem
+        fn forward(&self, k: f64, s: f64) -> (Function<'a>, SparseCSR<f64>) {
+            let mut matrix = SparsityPattern::from_dofmap_and_constraints(
+                self.space.dof_map(),
+                &self.constraints,
+            )
+            .to_csr(0.0);
+            let mut rhs = vec![0.0; self.space.n_dofs()];
+            let assembler = self.space.assembler(QuadratureRule::simplex(2, 2));
+            assembler.assemble_system(
+                &mut matrix,
+                &mut rhs,
+                &self.constraints,
+                |v, local, load| {
+                    let n = v.n_dofs();
+                    for q in 0..v.n_points() {
+                        for i in 0..n {
+                            for j in 0..n {
+                                let (gi, gj) = (v.shape_gradient(q, i), v.shape_gradient(q, j));
+                                local[i * n + j] +=
+                                    k * (gi[0] * gj[0] + gi[1] * gj[1]) * v.weight(q);
+                            }
+                            load[i] += s * v.shape_value(q, i) * v.weight(q);
+                        }
+                    }
+                },
+            );
+            let mut solution = vec![0.0; self.space.n_dofs()];
+            let solver = SolverConfig::new(MethodConfig::SparseLU)
+                .build(&matrix)
+                .unwrap();
+            assert!(solver.solve(&rhs, &mut solution).converged());
+            drop(solver);
+            self.constraints.distribute(&mut solution);
+            (Function::from_values(self.space, solution), matrix)
+        }
+    }
+
+    impl<'a> DesignProblem for Identification<'a> {
+        fn n_parameters(&self) -> usize {
+            2
+        }
+
+        // Misfit 1/2 |u - u_obs|² over the dofs
+        fn objective(&self, parameters: &[f64], g: &mut [f64]) -> f64 {
+            let (u, matrix) = self.forward(parameters[0], parameters[1]);
+            let misfit: Vec<f64> = u
+                .values()
+                .iter()
+                .zip(&self.observations)
+                .map(|(u, o)| u - o)
+                .collect();
+            let adjoint = Adjoint::new(&matrix)
+                .with_constraints(&self.constraints)
+                .solve_derivative(misfit.clone());
+            let mut dr_dk = vec![0.0; self.space.n_dofs()];
+            self.stiffness.apply(u.values(), &mut dr_dk);
+            let dr_ds: Vec<f64> = self.load.iter().map(|f| -f).collect();
+            g.copy_from_slice(&gradient(&adjoint, &[dr_dk, dr_ds]));
+            0.5 * dot(&misfit, &misfit)
+        }
+    }
+
+    #[test]
+    fn test_optimize_identification() {
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![4, 4]).simplex_mesh();
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let problem = Identification::new(&space, 0.5, 2.0);
+        // Only the ratio s / k is observable: fixing k by its bounds identifies s
+        let mut parameters = vec![1.0, 1.0];
+        let optimization = Optimizer::new()
+            .with_bounds(&[0.5, 0.0], &[0.5, 10.0])
+            .minimize(&problem, &mut parameters);
+        assert!(optimization.converged(), "{:?}", optimization.reason());
+        assert!((parameters[0] - 0.5).abs() < 1e-14, "Bounds were violated");
+        assert!(
+            (parameters[1] - 2.0).abs() < 1e-6,
+            "Wrong source {}",
+            parameters[1]
+        );
+        let mut parameters = vec![1.0, 1.0];
+        let optimization = Optimizer::new()
+            .with_bounds(&[0.1, 0.0], &[10.0, 3.0])
+            .minimize(&problem, &mut parameters);
+        assert!(optimization.converged(), "{:?}", optimization.reason());
+        assert!(
+            (parameters[1] / parameters[0] - 4.0).abs() < 1e-6,
+            "Wrong ratio {:?}",
+            parameters
+        );
+        assert!(optimization.objective() < 1e-12, "Wrong objective");
+        let csv = optimization.to_csv();
+        assert!(
+            csv.starts_with("iteration,objective,gradient_norm,step,p0,p1\n0,"),
+            "Wrong history {}",
+            csv
+        );
+        assert_eq!(
+            csv.lines().count(),
+            optimization.iterations() + 2,
+            "One row per iteration"
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_optimize_bounds() {
+        // Rosenbrock function with its minimum (1, 1) cut off by the upper bound of x
+        struct Rosenbrock;
+        impl DesignProblem for Rosenbrock {
+            fn n_parameters(&self) -> usize {
+                2
+            }
+
+            fn objective(&self, p: &[f64], g: &mut [f64]) -> f64 {
+                let (x, y) = (p[0], p[1]);
+                g[0] = -2.0 * (1.0 - x) - 400.0 * x * (y - x * x);
+                g[1] = 200.0 * (y - x * x);
+                (1.0 - x).powi(2) + 100.0 * (y - x * x).powi(2)
+            }
+        }
+        let mut parameters = vec![-1.2, 1.0];
+        let optimization = Optimizer::new()
+            .with_max_iterations(200)
+            .minimize(&Rosenbrock, &mut parameters);
+        assert!(optimization.converged(), "{:?}", optimization.reason());
+        assert!(
+            (parameters[0] - 1.0).abs() < 1e-6 && (parameters[1] - 1.0).abs() < 1e-6,
+            "Wrong minimum {:?}",
+            parameters
+        );
+        // Constrained minimum at x = 0.5 and y = x² where the gradient pushes against the bound
+        for method in [
+            OptimizationMethod::Lbfgs(5),
+            OptimizationMethod::GradientDescent,
+        ] {
+            let mut parameters = vec![-1.2, 1.0];
+            let optimization = Optimizer::new()
+                .with_method(method)
+                .with_bounds(&[-2.0, -2.0], &[0.5, 2.0])
+                .with_relative_tolerance(1e-6)
+                .with_max_iterations(20000)
+                .minimize(&Rosenbrock, &mut parameters);
+            assert!(optimization.converged(), "{:?}", optimization.reason());
+            assert!(
+                (parameters[0] - 0.5).abs() < 1e-12 && (parameters[1] - 0.25).abs() < 1e-5,
+                "Wrong constrained minimum {:?} with {:?}",
+                parameters,
+                method
+            );
+        }
+    }
+}
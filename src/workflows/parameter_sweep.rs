@@ -0,0 +1,333 @@
+use std::fmt::Write;
+#[cfg(hosted)]
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::core::logging::{info, Span};
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Named values of one run of a parameter sweep
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameters<'s> {
+    names: &'s [String],
+    values: &'s [f64],
+}
+
+/// Batch of runs of a workflow over parameter sets
+///
+/// The sets are either the cartesian product of value lists, the last parameter varying fastest,
+/// or an explicit list. Runs are independent and spread over threads taking the next pending set
+/// in turn, the table keeping the order of the sets. By default the runs are sequential.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParameterSweep {
+    names: Vec<String>,
+    sets: Vec<Vec<f64>>,
+    n_threads: usize,
+}
+
+/// Table of the parameters and summary quantities of the runs of a sweep
+#[derive(Clone, Debug, PartialEq)]
+pub struct SweepTable {
+    columns: Vec<String>,
+    n_parameters: usize,
+    rows: Vec<Vec<f64>>,
+}
+
+impl<'s> Parameters<'s> {
+    // Values of named parameters, in the same order
+    pub(crate) fn new(names: &'s [String], values: &'s [f64]) -> Self {
+        Parameters { names, values }
+    }
+
+    /// Value of a parameter
+    pub fn get(&self, name: &str) -> f64 {
+        let index = self
+            .names
+            .iter()
+            .position(|n| n == name)
+            .unwrap_or_else(|| panic!("Unknown parameter {}", name));
+        self.values[index]
+    }
+
+    /// Names and values of the parameters
+    pub fn iter(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.names
+            .iter()
+            .map(|n| n.as_str())
+            .zip(self.values.iter().copied())
+    }
+}
+
+impl ParameterSweep {
+    /// Sweep over the cartesian product of the values of the parameters
+    pub fn product(parameters: Vec<(&str, Vec<f64>)>) -> Self {
+        let mut sets = vec![Vec::new()];
+        for (_, values) in &parameters {
+            sets = sets
+                .iter()
+                .flat_map(|set| {
+                    values.iter().map(move |v| {
+                        let mut set = set.clone();
+                        set.push(*v);
+                        set
+                    })
+                })
+                .collect();
+        }
+        let names = parameters.into_iter().map(|(n, _)| n.to_string()).collect();
+        ParameterSweep::list(names, sets)
+    }
+
+    /// Sweep over a list of sets of values of the named parameters
+    pub fn list(names: Vec<String>, sets: Vec<Vec<f64>>) -> Self {
+        assert!(
+            sets.iter().all(|set| set.len() == names.len()),
+            "Every set should give a value to every parameter"
+        );
+        ParameterSweep {
+            names,
+            sets,
+            n_threads: 1,
+        }
+    }
+
+    /// Set the number of threads running the sets concurrently
+    pub fn with_n_threads(mut self, n_threads: usize) -> Self {
+        assert!(n_threads > 0, "Sweeps need at least one thread");
+        self.n_threads = n_threads;
+        self
+    }
+
+    /// Names of the parameters
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Number of parameter sets
+    pub fn n_sets(&self) -> usize {
+        self.sets.len()
+    }
+
+    /// Run the workflow on every parameter set and collect its named summary quantities, which
+    /// should be the same for every run
+    pub fn run<Run>(&self, run: Run) -> SweepTable
+    where
+        Run: Fn(&Parameters) -> Vec<(String, f64)> + Sync,
+    {
+        let _span = Span::enter("parameter sweep");
+        let n_sets = self.sets.len();
+        let parameters = |k: usize| {
+            info!("Parameter set {} of {}: {:?}", k + 1, n_sets, self.sets[k]);
+            Parameters {
+                names: &self.names,
+                values: &self.sets[k],
+            }
+        };
+        let results: Vec<Vec<(String, f64)>> = if self.n_threads == 1 {
+            (0..n_sets).map(|k| run(&parameters(k))).collect()
+        } else {
+            let next = AtomicUsize::new(0);
+            let results = Mutex::new(vec![Vec::new(); n_sets]);
+            thread::scope(|scope| {
+                for _ in 0..self.n_threads.min(n_sets) {
+                    scope.spawn(|| loop {
+                        let k = next.fetch_add(1, Ordering::Relaxed);
+                        if k >= n_sets {
+                            break;
+                        }
+                        let summary = run(&parameters(k));
+                        results.lock().unwrap()[k] = summary;
+                    });
+                }
+            });
+            results.into_inner().unwrap()
+        };
+        let mut columns = self.names.clone();
+        if let Some(first) = results.first() {
+            columns.extend(first.iter().map(|(n, _)| n.clone()));
+        }
+        let rows = results
+            .into_iter()
+            .zip(&self.sets)
+            .map(|(summary, set)| {
+                assert!(
+                    summary.len() + set.len() == columns.len()
+                        && summary
+                            .iter()
+                            .zip(&columns[set.len()..])
+                            .all(|((n, _), c)| n == c),
+                    "Every run should return the same summary quantities"
+                );
+                set.iter()
+                    .copied()
+                    .chain(summary.into_iter().map(|(_, v)| v))
+                    .collect()
+            })
+            .collect();
+        SweepTable {
+            columns,
+            n_parameters: self.names.len(),
+            rows,
+        }
+    }
+}
+
+impl SweepTable {
+    /// Names of the parameters then of the summary quantities
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Number of parameter columns
+    pub fn n_parameters(&self) -> usize {
+        self.n_parameters
+    }
+
+    /// Values of the runs in the order of the columns
+    pub fn rows(&self) -> &[Vec<f64>] {
+        &self.rows
+    }
+
+    /// Values of a column over the runs
+    pub fn column(&self, name: &str) -> Vec<f64> {
+        let index = self
+            .columns
+            .iter()
+            .position(|c| c == name)
+            .unwrap_or_else(|| panic!("Unknown column {}", name));
+        self.rows.iter().map(|row| row[index]).collect()
+    }
+
+    /// Comma separated table with a header and one row per run
+    pub fn to_csv(&self) -> String {
+        let mut csv = self.columns.join(",");
+        csv.push('\n');
+        for row in &self.rows {
+            let values: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+            writeln!(csv, "{}", values.join(",")).unwrap();
+        }
+        csv
+    }
+
+    /// JSON array with one object per run, non finite values being null
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("[\n");
+        for (k, row) in self.rows.iter().enumerate() {
+            let fields: Vec<String> = self
+                .columns
+                .iter()
+                .zip(row)
+                .map(|(c, v)| match v.is_finite() {
+                    true => format!("\"{}\": {}", c, v),
+                    false => format!("\"{}\": null", c),
+                })
+                .collect();
+            let separator = if k + 1 < self.rows.len() { "," } else { "" };
+            writeln!(json, "  {{{}}}{}", fields.join(", "), separator).unwrap();
+        }
+        json.push_str("]\n");
+        json
+    }
+
+    /// Write the table as JSON if the path has the json extension and as CSV otherwise
+    #[cfg(hosted)]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let contents = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => self.to_json(),
+            _ => self.to_csv(),
+        };
+        std::fs::write(path, contents)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflows::config::{
+        MeshConfig, ProblemConfig, SimulationConfig, TaggedComponent, TaggedVector,
+    };
+
+    #[test]
+    fn test_parameter_sweep() {
+        let sweep =
+            ParameterSweep::product(vec![("a", vec![1.0, 2.0]), ("b", vec![3.0, 4.0, 5.0])]);
+        assert_eq!(sweep.n_sets(), 6, "Wrong number of sets");
+        for n_threads in [1, 4] {
+            let table = sweep
+                .clone()
+                .with_n_threads(n_threads)
+                .run(|p| vec![("product".to_string(), p.get("a") * p.get("b"))]);
+            assert_eq!(table.columns(), ["a", "b", "product"], "Wrong columns");
+            assert_eq!(
+                table.column("a"),
+                [1.0, 1.0, 1.0, 2.0, 2.0, 2.0],
+                "Wrong order"
+            );
+            assert_eq!(
+                table.column("product"),
+                [3.0, 4.0, 5.0, 6.0, 8.0, 10.0],
+                "Wrong summaries with {} threads",
+                n_threads
+            );
+        }
+        let table = ParameterSweep::list(vec!["x".to_string()], vec![vec![1.0], vec![f64::NAN]])
+            .run(|p| vec![("y".to_string(), 2.0 * p.get("x"))]);
+        assert_eq!(table.to_csv(), "x,y\n1,2\nNaN,NaN\n", "Wrong CSV");
+        assert_eq!(
+            table.to_json(),
+            "[\n  {\"x\": 1, \"y\": 2},\n  {\"x\": null, \"y\": null}\n]\n",
+            "Wrong JSON"
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_parameter_sweep_simulations() {
+        // Plates of varying stiffness pulled on their right side with varying forces
+        let sweep = ParameterSweep::product(vec![
+            ("young_modulus", vec![100.0, 200.0]),
+            ("pull", vec![1.0, 2.0]),
+        ])
+        .with_n_threads(2);
+        let table = sweep.run(|p| {
+            let problem = ProblemConfig::Elasticity {
+                young_modulus: p.get("young_modulus"),
+                poisson_ratio: 0.0,
+                body_force: None,
+                tractions: vec![TaggedVector::new(2, vec![p.get("pull"), 0.0])],
+                displacements: Vec::new(),
+                component_displacements: vec![
+                    TaggedComponent::new(1, 0, 0.0),
+                    TaggedComponent::new(3, 1, 0.0),
+                ],
+                solver: None,
+                axisymmetric: false,
+            };
+            let mesh = MeshConfig::Box {
+                lower: vec![0.0, 0.0],
+                upper: vec![1.0, 1.0],
+                cells: vec![2, 2],
+                grading: Vec::new(),
+            };
+            SimulationConfig::new(mesh, problem).run().unwrap()
+        });
+        let stretch = table.column("max_displacement");
+        for (k, row) in table.rows().iter().enumerate() {
+            assert!(
+                (stretch[k] - row[1] / row[0]).abs() < 1e-12,
+                "Wrong stretch of run {}",
+                k
+            );
+        }
+    }
+}
@@ -0,0 +1,712 @@
+use super::elasticity::LinearElasticity;
+use super::events::{Control, Observers, WorkflowObserver};
+use crate::core::logging::{debug, info, Span};
+use crate::discretizations::cell_values::{CellValues, CoordinateSystem};
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+#[cfg(hosted)]
+use crate::discretizations::io::vtk::VtkSeries;
+use crate::discretizations::quadrature_field::QuadratureField;
+use crate::discretizations::sparsity::SparsityPattern;
+use crate::solvers::sparse_direct::SparseCholesky;
+use crate::spaces::quadrature::QuadratureRule;
+use std::fmt::Write;
+#[cfg(hosted)]
+use std::io::Result;
+#[cfg(hosted)]
+use std::path::Path;
+
+// Predicate on the physical coordinates
+type Region<'a> = Box<dyn Fn(&[f64]) -> bool + 'a>;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Quasi-static brittle fracture by the AT2 phase field model, solved by staggered iterations of
+/// the elasticity and the damage
+///
+/// The damage d lives on a continuous scalar space of the mesh of the elasticity problem, whose
+/// loads and imposed displacements are scaled by a sequence of load factors. At every load step
+/// the displacement is solved with the stiffness degraded by (1 - d)² + k_res and the damage from
+/// (G_c / l + 2 H) d - G_c l Δd = 2 H with natural boundary conditions, until the damage changes by
+/// less than the tolerance. Irreversibility is enforced by the history field H, the largest tensile
+/// energy ψ⁺ met at every quadrature point over the load steps, with the volumetric-deviatoric
+/// split ψ⁺ = K/2 <tr ε>₊² + μ dev ε : dev ε of the hybrid formulation which keeps the elasticity
+/// isotropic. Initial cracks are regions where the history starts at 1000 G_c / 2l, damaging them
+/// fully. By default k_res is 1e-6, the tolerance 1e-6 and at most 100 staggered iterations are
+/// done per step. Problems are cartesian. Workflow observers are notified of the load steps, as
+/// steps from 1 at the times of their load factors with the damage as solution, and may stop the
+/// simulation.
+pub struct PhaseFieldFracture<'a> {
+    elasticity: LinearElasticity<'a>,
+    space: &'a FunctionSpace<'a>,
+    toughness: f64,
+    length: f64,
+    residual_stiffness: f64,
+    cracks: Vec<Region<'a>>,
+    tolerance: f64,
+    max_iterations: usize,
+    observers: Vec<Box<dyn WorkflowObserver + 'a>>,
+}
+
+/// Outcome of the load steps of a fracture simulation
+///
+/// Every row holds the index of the step, its load factor, the number of staggered iterations, the
+/// largest damage, the elastic energy ∫ ((1 - d)² + k_res) ψ and the fracture energy
+/// G_c / 2 ∫ (d² / l + l |grad d|²).
+#[derive(Clone, Debug, PartialEq)]
+pub struct FractureHistory {
+    converged: Vec<bool>,
+    rows: Vec<Vec<f64>>,
+}
+
+impl<'a> PhaseFieldFracture<'a> {
+    /// Fracture of the body of an elasticity problem with its damage on a scalar space of the same
+    /// mesh, the critical energy release rate G_c and the regularization length l
+    pub fn new(
+        elasticity: LinearElasticity<'a>,
+        space: &'a FunctionSpace<'a>,
+        toughness: f64,
+        length: f64,
+    ) -> Self {
+        assert!(
+            std::ptr::eq(space.mesh(), elasticity.space().mesh())
+                && space.n_components() == 1
+                && !space.is_discontinuous(),
+            "Damage needs a continuous scalar space of the mesh of the elasticity problem"
+        );
+        assert!(
+            space.element().order() <= elasticity.space().element().order(),
+            "Damage elements should not be of a higher order than the displacement"
+        );
+        assert!(
+            elasticity.coordinate_system() == CoordinateSystem::Cartesian,
+            "Phase field fracture is implemented for cartesian problems"
+        );
+        assert!(
+            toughness > 0.0 && length > 0.0,
+            "Toughness and length should be positive"
+        );
+        PhaseFieldFracture {
+            elasticity,
+            space,
+            toughness,
+            length,
+            residual_stiffness: 1e-6,
+            cracks: Vec::new(),
+            tolerance: 1e-6,
+            max_iterations: 100,
+            observers: Vec::new(),
+        }
+    }
+
+    /// Set the residual stiffness k_res keeping fully damaged regions solvable
+    pub fn with_residual_stiffness(mut self, residual_stiffness: f64) -> Self {
+        assert!(
+            residual_stiffness > 0.0,
+            "Residual stiffness should be positive"
+        );
+        self.residual_stiffness = residual_stiffness;
+        self
+    }
+
+    /// Add an initial crack as the region of the points satisfying the predicate
+    pub fn with_initial_crack<Crack>(mut self, crack: Crack) -> Self
+    where
+        Crack: Fn(&[f64]) -> bool + 'a,
+    {
+        self.cracks.push(Box::new(crack));
+        self
+    }
+
+    /// Set the tolerance on the largest change of the damage between staggered iterations
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Set the maximum number of staggered iterations per load step
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        assert!(max_iterations > 0, "At least one iteration should be done");
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Notify an observer of the events of the load steps
+    pub fn with_observer<Observer: WorkflowObserver + 'a>(mut self, observer: Observer) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Apply the load factors one after the other, d holding the initial damage and then the one
+    /// of every step, which is passed to the observer with the load factor and the displacement
+    pub fn solve<Observer>(
+        &self,
+        damage: &mut Function<'a>,
+        load_factors: &[f64],
+        mut observer: Observer,
+    ) -> FractureHistory
+    where
+        Observer: FnMut(f64, &Function, &Function),
+    {
+        assert!(
+            std::ptr::eq(damage.space(), self.space),
+            "Damage does not live on the space of the problem"
+        );
+        let _span = Span::enter("phase field fracture");
+        let mesh = self.space.mesh();
+        let quadrature = self.elasticity.quadrature();
+        let mut history = QuadratureField::new(mesh, &quadrature, 1);
+        self.initialize_history(&mut history);
+        let mut fracture = FractureHistory {
+            converged: Vec::with_capacity(load_factors.len()),
+            rows: Vec::with_capacity(load_factors.len()),
+        };
+        let mut displacement = Function::new(self.elasticity.space());
+        let observers = Observers::new(&self.observers);
+        if observers.solved(0, 0.0, damage) == Control::Stop {
+            return fracture;
+        }
+        for (step, factor) in load_factors.iter().enumerate() {
+            if observers.step_start(step + 1, *factor) == Control::Stop {
+                break;
+            }
+            let mut iterations = 0;
+            let mut converged = false;
+            while iterations < self.max_iterations && !converged {
+                iterations += 1;
+                displacement = self.solve_displacement(damage, *factor);
+                self.update_history(&displacement, &mut history);
+                let next = self.solve_damage(&history);
+                let change = next
+                    .iter()
+                    .zip(damage.values())
+                    .map(|(a, b)| (a - b).abs())
+                    .fold(0.0, f64::max);
+                damage.values_mut().copy_from_slice(&next);
+                debug!(
+                    "Staggered iteration {} of step {}: damage change {:e}",
+                    iterations, step, change
+                );
+                converged = change <= self.tolerance;
+            }
+            history.commit();
+            let (elastic, fracture_energy) = self.energies(&displacement, damage);
+            let max_damage = damage.values().iter().copied().fold(0.0, f64::max);
+            info!(
+                "Load step {} at factor {:e}: {} staggered iterations, largest damage {:.3}",
+                step, factor, iterations, max_damage
+            );
+            fracture.converged.push(converged);
+            fracture.rows.push(vec![
+                step as f64,
+                *factor,
+                iterations as f64,
+                max_damage,
+                elastic,
+                fracture_energy,
+            ]);
+            let control = observers.solved(step + 1, *factor, damage);
+            observer(*factor, &displacement, damage);
+            if control == Control::Stop {
+                break;
+            }
+        }
+        fracture
+    }
+
+    /// Solve like solve and write the displacement and the damage of every load step to a VTK
+    /// time series (see VtkSeries) starting with the prefix path, the crack path being the damaged
+    /// band, with the load factors as times
+    #[cfg(hosted)]
+    pub fn solve_to_vtk<P: AsRef<Path>>(
+        &self,
+        damage: &mut Function<'a>,
+        load_factors: &[f64],
+        prefix: P,
+    ) -> Result<FractureHistory> {
+        let mesh = self.space.mesh();
+        let mut series = VtkSeries::new(prefix);
+        let mut status = Ok(());
+        let history = self.solve(damage, load_factors, |factor, displacement, damage| {
+            if status.is_ok() {
+                status = series.write(
+                    factor,
+                    mesh,
+                    &[("displacement", displacement), ("damage", damage)],
+                );
+                if status.is_ok() {
+                    let step = series.times().len();
+                    let path = series.step_path(step - 1);
+                    Observers::new(&self.observers).output(step, factor, &path);
+                }
+            }
+        });
+        status.map(|_| history)
+    }
+
+    // History of the initial cracks
+    fn initialize_history(&self, history: &mut QuadratureField) {
+        if self.cracks.is_empty() {
+            return;
+        }
+        let mesh = self.space.mesh();
+        let quadrature = self.elasticity.quadrature();
+        let mut values = CellValues::new(self.space.element(), &quadrature);
+        let initial = 1e3 * self.toughness / (2.0 * self.length);
+        for cell in 0..mesh.n_cells() {
+            values.reinit(mesh, cell);
+            for q in 0..values.n_points() {
+                if self.cracks.iter().any(|crack| crack(values.point(q))) {
+                    history.point_mut(cell, q).1[0] = initial;
+                }
+            }
+        }
+        history.commit();
+    }
+
+    // Displacement under the load factor with the stiffness degraded by the damage
+    fn solve_displacement(&self, damage: &Function, factor: f64) -> Function<'a> {
+        let values = damage_at_points(damage, &self.elasticity.quadrature());
+        let n_points = self.elasticity.quadrature().n_points();
+        let (matrix, rhs, constraints) = self.elasticity.assemble_degraded(|cell, q| {
+            (1.0 - values[cell * n_points + q]).powi(2) + self.residual_stiffness
+        });
+        let cholesky = SparseCholesky::new(&matrix)
+            .expect("Elasticity system is singular, rigid motions should be constrained");
+        let mut displacement = vec![0.0; rhs.len()];
+        cholesky.solve(&rhs, &mut displacement);
+        // The displacement of the unit loads scales with the load factor
+        constraints.distribute(&mut displacement);
+        displacement.iter_mut().for_each(|u| *u *= factor);
+        Function::from_values(self.elasticity.space(), displacement)
+    }
+
+    // Trial history of the largest tensile energy from the committed one
+    fn update_history(&self, displacement: &Function, history: &mut QuadratureField) {
+        let mesh = self.space.mesh();
+        let quadrature = self.elasticity.quadrature();
+        let (lambda, mu) = self.elasticity.material().lame_parameters();
+        let mut values = CellValues::new(self.elasticity.space().element(), &quadrature);
+        for cell in 0..mesh.n_cells() {
+            values.reinit(mesh, cell);
+            for q in 0..values.n_points() {
+                let (_, tensile) = strain_energies(displacement, &values, q, lambda, mu);
+                let (committed, trial) = history.point_mut(cell, q);
+                trial[0] = committed[0].max(tensile);
+            }
+        }
+    }
+
+    // Damage of the trial history
+    fn solve_damage(&self, history: &QuadratureField) -> Vec<f64> {
+        let space = self.space;
+        let assembler = space.assembler(self.elasticity.quadrature());
+        let mut matrix = SparsityPattern::from_dofmap(space.dof_map()).to_csr(0.0);
+        let mut rhs = vec![0.0; space.n_dofs()];
+        let (toughness, length) = (self.toughness, self.length);
+        assembler.assemble_matrix(&mut matrix, |values, local| {
+            let n = values.n_dofs();
+            for q in 0..values.n_points() {
+                let w = values.weight(q);
+                let reaction = toughness / length + 2.0 * history.trial(values.cell(), q)[0];
+                for i in 0..n {
+                    let gi = values.shape_gradient(q, i);
+                    for j in 0..n {
+                        let gj = values.shape_gradient(q, j);
+                        let dot: f64 = gi.iter().zip(gj).map(|(a, b)| a * b).sum();
+                        local[i * n + j] +=
+                            (reaction * values.shape_value(q, i) * values.shape_value(q, j)
+                                + toughness * length * dot)
+                                * w;
+                    }
+                }
+            }
+        });
+        assembler.assemble_vector(&mut rhs, |values, local| {
+            for q in 0..values.n_points() {
+                let driving = 2.0 * history.trial(values.cell(), q)[0] * values.weight(q);
+                for i in 0..values.n_dofs() {
+                    local[i] += driving * values.shape_value(q, i);
+                }
+            }
+        });
+        let cholesky = SparseCholesky::new(&matrix).expect("Damage system is singular");
+        let mut damage = vec![0.0; space.n_dofs()];
+        cholesky.solve(&rhs, &mut damage);
+        damage
+    }
+
+    // Degraded elastic energy and fracture energy
+    fn energies(&self, displacement: &Function, damage: &Function) -> (f64, f64) {
+        let mesh = self.space.mesh();
+        let quadrature = self.elasticity.quadrature();
+        let (lambda, mu) = self.elasticity.material().lame_parameters();
+        let mut elastic_values = CellValues::new(self.elasticity.space().element(), &quadrature);
+        let mut damage_values = CellValues::new(self.space.element(), &quadrature);
+        let dim = mesh.geometric_dim();
+        let mut gradient = vec![0.0; dim];
+        let (mut elastic, mut fracture) = (0.0, 0.0);
+        for cell in 0..mesh.n_cells() {
+            elastic_values.reinit(mesh, cell);
+            damage_values.reinit(mesh, cell);
+            let dofs = self.space.dof_map().cell_dofs(cell);
+            for q in 0..quadrature.n_points() {
+                let w = damage_values.weight(q);
+                let mut d = 0.0;
+                gradient.iter_mut().for_each(|g| *g = 0.0);
+                for (i, dof) in dofs.iter().enumerate() {
+                    let value = damage.values()[*dof];
+                    d += value * damage_values.shape_value(q, i);
+                    for (g, s) in gradient.iter_mut().zip(damage_values.shape_gradient(q, i)) {
+                        *g += value * s;
+                    }
+                }
+                let (energy, _) = strain_energies(displacement, &elastic_values, q, lambda, mu);
+                elastic += ((1.0 - d).powi(2) + self.residual_stiffness) * energy * w;
+                let slope: f64 = gradient.iter().map(|g| g * g).sum();
+                fracture += 0.5 * self.toughness * (d * d / self.length + self.length * slope) * w;
+            }
+        }
+        (elastic, fracture)
+    }
+}
+
+impl FractureHistory {
+    /// Whether the staggered iterations of every load step converged
+    pub fn converged(&self) -> bool {
+        self.converged.iter().all(|c| *c)
+    }
+
+    /// Largest damage of every load step
+    pub fn max_damages(&self) -> Vec<f64> {
+        self.rows.iter().map(|row| row[3]).collect()
+    }
+
+    /// Names of the columns of the history
+    pub fn columns(&self) -> Vec<String> {
+        [
+            "step",
+            "load_factor",
+            "iterations",
+            "max_damage",
+            "elastic_energy",
+            "fracture_energy",
+        ]
+        .iter()
+        .map(|c| c.to_string())
+        .collect()
+    }
+
+    /// Rows of the history, one per load step
+    pub fn rows(&self) -> &[Vec<f64>] {
+        &self.rows
+    }
+
+    /// Comma separated table with a header and one row per load step
+    pub fn to_csv(&self) -> String {
+        let mut csv = self.columns().join(",");
+        csv.push('\n');
+        for row in &self.rows {
+            let values: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+            writeln!(csv, "{}", values.join(",")).unwrap();
+        }
+        csv
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Cells whose damage at the centroid exceeds a threshold, tracing the crack path for output or
+/// for marking the cells to refine
+pub fn cracked_cells(damage: &Function, threshold: f64) -> Vec<usize> {
+    let mesh = damage.space().mesh();
+    let dim = mesh.topological_dim();
+    let centroid = vec![1.0 / (dim + 1) as f64; dim];
+    let mut value = [0.0];
+    (0..mesh.n_cells())
+        .filter(|cell| {
+            damage.eval_in_cell(*cell, &centroid, &mut value);
+            value[0] > threshold
+        })
+        .collect()
+}
+
+// Damage at the quadrature points of every cell, cell by cell
+fn damage_at_points(damage: &Function, quadrature: &QuadratureRule) -> Vec<f64> {
+    let space = damage.space();
+    let mesh = space.mesh();
+    let mut values = CellValues::new(space.element(), quadrature);
+    let mut points = Vec::with_capacity(mesh.n_cells() * quadrature.n_points());
+    for cell in 0..mesh.n_cells() {
+        values.reinit(mesh, cell);
+        let dofs = space.dof_map().cell_dofs(cell);
+        for q in 0..values.n_points() {
+            points.push(
+                dofs.iter()
+                    .enumerate()
+                    .map(|(i, dof)| damage.values()[*dof] * values.shape_value(q, i))
+                    .sum(),
+            );
+        }
+    }
+    points
+}
+
+// Strain energy ψ and its tensile part ψ⁺ of a displacement at a quadrature point
+fn strain_energies(
+    displacement: &Function,
+    values: &CellValues,
+    q: usize,
+    lambda: f64,
+    mu: f64,
+) -> (f64, f64) {
+    let space = displacement.space();
+    let dim = values.geometric_dim();
+    let u = displacement.values();
+    let mut gradient = [0.0; 9];
+    for (a, dof) in space.dof_map().cell_dofs(values.cell()).iter().enumerate() {
+        let ga = values.shape_gradient(q, a);
+        for i in 0..dim {
+            for j in 0..dim {
+                gradient[i * dim + j] += u[space.dof(*dof, i)] * ga[j];
+            }
+        }
+    }
+    let strain = |i: usize, j: usize| 0.5 * (gradient[i * dim + j] + gradient[j * dim + i]);
+    let trace: f64 = (0..dim).map(|i| strain(i, i)).sum();
+    let squared: f64 = (0..dim)
+        .flat_map(|i| (0..dim).map(move |j| (i, j)))
+        .map(|(i, j)| strain(i, j).powi(2))
+        .sum();
+    // Out of plane strains vanish in plane strain so the 3D invariants are the in-plane ones
+    let bulk = lambda + 2.0 * mu / 3.0;
+    let deviatoric = squared - trace * trace / 3.0;
+    (
+        0.5 * lambda * trace * trace + mu * squared,
+        0.5 * bulk * trace.max(0.0).powi(2) + mu * deviatoric,
+    )
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::test_meshes::unit_square_grid;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::workflows::elasticity::IsotropicMaterial;
+    use crate::workflows::events::{EventLog, StopWhen, WorkflowEvent};
+    use std::panic::{self, AssertUnwindSafe};
+
+    // Description of an invalid input with the construction it should make panic
+    type Invalid<'a> = (&'a str, Box<dyn Fn() + 'a>);
+
+    // Uniaxial strain of a unit square without Poisson effect, stretched by 1 along x
+    fn stretched<'a>(space: &'a FunctionSpace<'a>) -> LinearElasticity<'a> {
+        LinearElasticity::new(space, IsotropicMaterial::new(1.0, 0.0))
+            .with_component_displacement(1, 0, 0.0)
+            .with_component_displacement(2, 0, 1.0)
+            .with_component_displacement(3, 1, 0.0)
+            .with_component_displacement(4, 1, 0.0)
+    }
+
+    #[test]
+    fn test_phase_field_fracture() {
+        // Uniaxial strain ε of a square without Poisson effect: the damage stays uniform with
+        // d = E ε² / (G_c / l + E ε²), and keeps its largest value when the load is lowered
+        let mesh = unit_square_grid(4);
+        let displacement_space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let (toughness, length) = (1.0, 0.1);
+        let fracture =
+            PhaseFieldFracture::new(stretched(&displacement_space), &space, toughness, length)
+                .with_residual_stiffness(1e-8);
+        let mut damage = Function::new(&space);
+        let directory = std::env::temp_dir().join("fe2o3_test_phase_field_fracture");
+        std::fs::create_dir_all(&directory).unwrap();
+        let history = fracture
+            .solve_to_vtk(&mut damage, &[1.0, 2.0, 1.0], directory.join("fracture"))
+            .unwrap();
+        assert!(
+            directory.join("fracture_00002.vtk").exists(),
+            "Every load step should be written"
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert!(history.converged(), "Staggered iterations did not converge");
+        let uniform = |strain: f64| strain * strain / (toughness / length + strain * strain);
+        for (d, expected) in
+            history
+                .max_damages()
+                .iter()
+                .zip([uniform(1.0), uniform(2.0), uniform(2.0)])
+        {
+            assert!(
+                (d - expected).abs() < 1e-10,
+                "Wrong damage {} instead of {}",
+                d,
+                expected
+            );
+        }
+        assert!(
+            damage
+                .values()
+                .iter()
+                .all(|d| (d - uniform(2.0)).abs() < 1e-10),
+            "Damage should stay uniform"
+        );
+        let row = &history.rows()[1];
+        let d = uniform(2.0);
+        assert!(
+            (row[4] - (1.0 - d).powi(2) * 2.0).abs() < 1e-6
+                && (row[5] - 0.5 * toughness * d * d / length).abs() < 1e-10,
+            "Wrong energies {:?}",
+            row
+        );
+        assert_eq!(
+            cracked_cells(&damage, 0.2).len(),
+            mesh.n_cells(),
+            "Whole square is damaged"
+        );
+        assert!(
+            history
+                .to_csv()
+                .starts_with("step,load_factor,iterations,max_damage,"),
+            "Wrong header"
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_phase_field_options() {
+        let mesh = unit_square_grid(4);
+        let displacement_space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        // An initial crack along the left side is damaged under a vanishing load
+        let cracked = PhaseFieldFracture::new(stretched(&displacement_space), &space, 1.0, 0.1)
+            .with_initial_crack(|x| x[0] < 0.2);
+        let mut damage = Function::new(&space);
+        let history = cracked.solve(&mut damage, &[1e-3], |_, _, _| {});
+        assert!(history.converged(), "Staggered iterations did not converge");
+        assert!(history.max_damages()[0] > 0.9, "Crack is not damaged");
+        let cells = cracked_cells(&damage, 0.5);
+        assert!(
+            !cells.is_empty() && cells.len() < mesh.n_cells(),
+            "Damage should stay around the crack"
+        );
+        // Too few staggered iterations to converge, the observers stopping after the second step
+        let log = EventLog::new();
+        let limited = PhaseFieldFracture::new(stretched(&displacement_space), &space, 1.0, 0.1)
+            .with_tolerance(1e-14)
+            .with_max_iterations(1)
+            .with_observer(log.clone())
+            .with_observer(StopWhen::new(|step, _, _| step == 2));
+        let mut damage = Function::new(&space);
+        let mut factors = Vec::new();
+        let history = limited.solve(&mut damage, &[1.0, 2.0, 3.0], |factor, _, _| {
+            factors.push(factor)
+        });
+        assert!(!history.converged(), "One iteration should not converge");
+        assert_eq!(
+            factors,
+            vec![1.0, 2.0],
+            "Observer should see the solved steps"
+        );
+        assert_eq!(history.rows().len(), 2, "Wrong number of steps");
+        for row in history.rows() {
+            assert_eq!(row.len(), history.columns().len(), "Wrong row size");
+            assert_eq!(row[2], 1.0, "Wrong number of iterations");
+        }
+        let solved = log
+            .events()
+            .iter()
+            .filter(|e| matches!(e, WorkflowEvent::Solved { .. }))
+            .count();
+        assert_eq!(solved, 3, "Initial damage and both steps should be solved");
+        assert_eq!(history.to_csv().lines().count(), 3, "Wrong number of lines");
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_phase_field_invalid() {
+        let mesh = unit_square_grid(4);
+        let displacement_space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let quadratic = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        let other = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let fracture = || PhaseFieldFracture::new(stretched(&displacement_space), &space, 1.0, 0.1);
+        let cases: [Invalid; 7] = [
+            (
+                "a zero toughness",
+                Box::new(|| {
+                    drop(PhaseFieldFracture::new(
+                        stretched(&displacement_space),
+                        &space,
+                        0.0,
+                        0.1,
+                    ))
+                }),
+            ),
+            (
+                "a vector damage space",
+                Box::new(|| {
+                    drop(PhaseFieldFracture::new(
+                        stretched(&displacement_space),
+                        &displacement_space,
+                        1.0,
+                        0.1,
+                    ))
+                }),
+            ),
+            (
+                "a higher order damage space",
+                Box::new(|| {
+                    drop(PhaseFieldFracture::new(
+                        stretched(&displacement_space),
+                        &quadratic,
+                        1.0,
+                        0.1,
+                    ))
+                }),
+            ),
+            (
+                "an axisymmetric elasticity",
+                Box::new(|| {
+                    drop(PhaseFieldFracture::new(
+                        stretched(&displacement_space).with_axisymmetry(),
+                        &space,
+                        1.0,
+                        0.1,
+                    ))
+                }),
+            ),
+            (
+                "a zero residual stiffness",
+                Box::new(|| drop(fracture().with_residual_stiffness(0.0))),
+            ),
+            (
+                "no iteration",
+                Box::new(|| drop(fracture().with_max_iterations(0))),
+            ),
+            (
+                "a damage of another space",
+                Box::new(|| {
+                    let mut damage = Function::new(&other);
+                    fracture().solve(&mut damage, &[1.0], |_, _, _| {});
+                }),
+            ),
+        ];
+        for (case, build) in cases {
+            assert!(
+                panic::catch_unwind(AssertUnwindSafe(build)).is_err(),
+                "Phase field fracture accepted {}",
+                case
+            );
+        }
+    }
+}
@@ -0,0 +1,534 @@
+use super::elasticity::{IsotropicMaterial, LinearElasticity, AXES};
+use crate::discretizations::cell_values::CellValues;
+use crate::discretizations::facet_values::FacetValues;
+use crate::discretizations::facets::Facets;
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+#[cfg(hosted)]
+use crate::discretizations::io::vtk;
+use crate::discretizations::recovery::recover_gradient;
+use crate::spaces::lagrange::LagrangeElement;
+use crate::spaces::quadrature::QuadratureRule;
+#[cfg(hosted)]
+use std::fmt::Write;
+#[cfg(hosted)]
+use std::path::Path;
+
+//--------------------------------------------------------------------------------------------------
+// # Enums
+//--------------------------------------------------------------------------------------------------
+
+// Stage of a post-processing pipeline on a named field
+enum Operation {
+    Stress(String, IsotropicMaterial),
+    Vorticity(String),
+    Flux(String, usize, Option<f64>),
+    VolumeIntegral(String),
+    SurfaceIntegral(String, usize),
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Pipeline of derived fields and integral quantities computed from named solution fields
+///
+/// Derived fields are recovered on a continuous scalar target space of the mesh of the solutions,
+/// one function per component: the strains, stresses and von Mises stress of a displacement (see
+/// LinearElasticity::recover_stress) and the vorticity of a velocity, scalar in 2D and named
+/// vorticity_x, vorticity_y and vorticity_z in 3D. Quantities are integrals over the mesh or over
+/// the facets carrying a tag, oriented by the normal pointing out of the first cell of each facet
+/// (out of the domain on the boundary). Integrals of several components get one quantity per axis.
+/// The stages run in the order they were added.
+pub struct Postprocess<'a> {
+    target: &'a FunctionSpace<'a>,
+    fields: Vec<(String, &'a Function<'a>)>,
+    operations: Vec<Operation>,
+}
+
+/// Derived fields and quantities of a post-processing pipeline
+pub struct PostprocessResult<'a> {
+    fields: Vec<(String, Function<'a>)>,
+    quantities: Vec<(String, f64)>,
+}
+
+impl<'a> Postprocess<'a> {
+    /// Pipeline recovering the derived fields on the target space
+    pub fn new(target: &'a FunctionSpace<'a>) -> Self {
+        assert!(
+            target.n_components() == 1 && !target.is_discontinuous(),
+            "Derived fields are recovered on a continuous scalar space"
+        );
+        Postprocess {
+            target,
+            fields: Vec::new(),
+            operations: Vec::new(),
+        }
+    }
+
+    /// Add a solution field under a name
+    pub fn with_field(mut self, name: &str, field: &'a Function<'a>) -> Self {
+        assert!(
+            std::ptr::eq(field.space().mesh(), self.target.mesh()),
+            "Fields should live on the mesh of the target space"
+        );
+        self.fields.push((name.to_string(), field));
+        self
+    }
+
+    /// Recover the strains and stresses of a displacement field of an isotropic material
+    pub fn with_stress(mut self, displacement: &str, material: IsotropicMaterial) -> Self {
+        self.operations
+            .push(Operation::Stress(displacement.to_string(), material));
+        self
+    }
+
+    /// Recover the vorticity curl(v) of a velocity field
+    pub fn with_vorticity(mut self, velocity: &str) -> Self {
+        self.operations
+            .push(Operation::Vorticity(velocity.to_string()));
+        self
+    }
+
+    /// Integrate the normal flux v.n of a vector field through the facets carrying a tag, named
+    /// flux_{field}_{tag}
+    pub fn with_flux(mut self, field: &str, tag: usize) -> Self {
+        self.operations
+            .push(Operation::Flux(field.to_string(), tag, None));
+        self
+    }
+
+    /// Integrate the diffusive flux -k grad(u).n of a scalar field through the facets carrying a
+    /// tag, named flux_{field}_{tag}
+    pub fn with_diffusive_flux(mut self, field: &str, tag: usize, diffusivity: f64) -> Self {
+        self.operations
+            .push(Operation::Flux(field.to_string(), tag, Some(diffusivity)));
+        self
+    }
+
+    /// Integrate a field over the mesh, named integral_{field}
+    pub fn with_volume_integral(mut self, field: &str) -> Self {
+        self.operations
+            .push(Operation::VolumeIntegral(field.to_string()));
+        self
+    }
+
+    /// Integrate a field over the facets carrying a tag, named integral_{field}_{tag}
+    pub fn with_surface_integral(mut self, field: &str, tag: usize) -> Self {
+        self.operations
+            .push(Operation::SurfaceIntegral(field.to_string(), tag));
+        self
+    }
+
+    /// Run the stages of the pipeline
+    pub fn run(&self) -> PostprocessResult<'a> {
+        let mut result = PostprocessResult {
+            fields: Vec::new(),
+            quantities: Vec::new(),
+        };
+        for operation in &self.operations {
+            match operation {
+                Operation::Stress(name, material) => {
+                    let displacement = self.field(name);
+                    let elasticity = LinearElasticity::new(displacement.space(), *material);
+                    result
+                        .fields
+                        .extend(elasticity.recover_stress(displacement, self.target));
+                }
+                Operation::Vorticity(name) => {
+                    result.fields.extend(self.vorticity(self.field(name)));
+                }
+                Operation::Flux(name, tag, diffusivity) => {
+                    let flux = flux(self.field(name), *tag, *diffusivity);
+                    result
+                        .quantities
+                        .push((format!("flux_{}_{}", name, tag), flux));
+                }
+                Operation::VolumeIntegral(name) => {
+                    let integrals = volume_integral(self.field(name));
+                    push_components(
+                        &mut result.quantities,
+                        &format!("integral_{}", name),
+                        &integrals,
+                    );
+                }
+                Operation::SurfaceIntegral(name, tag) => {
+                    let integrals = surface_integral(self.field(name), *tag);
+                    let name = format!("integral_{}_{}", name, tag);
+                    push_components(&mut result.quantities, &name, &integrals);
+                }
+            }
+        }
+        result
+    }
+
+    /// Write the solution fields and the derived fields of a result to a VTK file
+    #[cfg(hosted)]
+    pub fn save_vtk<P: AsRef<Path>>(
+        &self,
+        path: P,
+        result: &PostprocessResult,
+    ) -> std::io::Result<()> {
+        let mut functions: Vec<(&str, &Function)> =
+            self.fields.iter().map(|(n, f)| (n.as_str(), *f)).collect();
+        functions.extend(result.fields.iter().map(|(n, f)| (n.as_str(), f)));
+        vtk::save_vtk(path, self.target.mesh(), &functions)
+    }
+
+    // Solution field of a name
+    fn field(&self, name: &str) -> &'a Function<'a> {
+        self.fields
+            .iter()
+            .find(|(n, _)| n == name)
+            .unwrap_or_else(|| panic!("Unknown field {}", name))
+            .1
+    }
+
+    // Vorticity of a velocity recovered on the target
+    fn vorticity(&self, velocity: &Function) -> Vec<(String, Function<'a>)> {
+        let mesh = self.target.mesh();
+        let dim = mesh.geometric_dim();
+        assert!(
+            (2..=3).contains(&dim) && velocity.space().n_components() == dim,
+            "Vorticity needs a velocity of the dimension of a 2D or 3D mesh"
+        );
+        // Same element and mesh so the scalar dofs of the gradient space are the ones of the target
+        let gradient_space = FunctionSpace::vector(
+            mesh,
+            LagrangeElement::new(dim, self.target.element().order()),
+            dim * dim,
+        );
+        let gradient = recover_gradient(velocity, &gradient_space);
+        let n = self.target.dof_map().n_dofs();
+        let curl: Vec<(usize, usize)> = match dim {
+            2 => vec![(1, 0)],
+            _ => vec![(2, 1), (0, 2), (1, 0)],
+        };
+        let mut vorticity = vec![vec![0.0; n]; curl.len()];
+        for (s, g) in gradient.values().chunks(dim * dim).enumerate() {
+            for (k, (i, j)) in curl.iter().enumerate() {
+                vorticity[k][s] = g[i * dim + j] - g[j * dim + i];
+            }
+        }
+        let name = |k: usize| match dim {
+            2 => "vorticity".to_string(),
+            _ => format!("vorticity_{}", AXES[k]),
+        };
+        vorticity
+            .into_iter()
+            .enumerate()
+            .map(|(k, values)| (name(k), Function::from_values(self.target, values)))
+            .collect()
+    }
+}
+
+impl<'a> PostprocessResult<'a> {
+    /// Derived fields by name
+    pub fn fields(&self) -> &[(String, Function<'a>)] {
+        &self.fields
+    }
+
+    /// Derived field of a name
+    pub fn field(&self, name: &str) -> Option<&Function<'a>> {
+        self.fields.iter().find(|(n, _)| n == name).map(|(_, f)| f)
+    }
+
+    /// Quantities by name
+    pub fn quantities(&self) -> &[(String, f64)] {
+        &self.quantities
+    }
+
+    /// Quantity of a name
+    pub fn quantity(&self, name: &str) -> Option<f64> {
+        self.quantities
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, q)| *q)
+    }
+
+    /// Write the quantities as a JSON object if the path has the json extension and as a two
+    /// column CSV table otherwise
+    #[cfg(hosted)]
+    pub fn save_quantities<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let mut contents = String::new();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => {
+                let entries: Vec<String> = self
+                    .quantities
+                    .iter()
+                    .map(|(n, q)| {
+                        if q.is_finite() {
+                            format!("  \"{}\": {}", n, q)
+                        } else {
+                            format!("  \"{}\": null", n)
+                        }
+                    })
+                    .collect();
+                writeln!(contents, "{{\n{}\n}}", entries.join(",\n")).unwrap();
+            }
+            _ => {
+                contents.push_str("quantity,value\n");
+                for (name, quantity) in &self.quantities {
+                    writeln!(contents, "{},{}", name, quantity).unwrap();
+                }
+            }
+        }
+        std::fs::write(path, contents)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Add quantities with the axis of every component in their names if there are several
+pub(crate) fn push_components(quantities: &mut Vec<(String, f64)>, name: &str, values: &[f64]) {
+    match values {
+        [value] => quantities.push((name.to_string(), *value)),
+        _ => {
+            for (c, value) in values.iter().enumerate() {
+                let axis = AXES.get(c).map_or(c.to_string(), |a| a.to_string());
+                quantities.push((format!("{}_{}", name, axis), *value));
+            }
+        }
+    }
+}
+
+// Integrals of the components of a function over the mesh
+fn volume_integral(function: &Function) -> Vec<f64> {
+    let space = function.space();
+    let element = space.element();
+    let quadrature = QuadratureRule::simplex(element.dim(), element.order() + 1);
+    let mut values = CellValues::new(element, &quadrature);
+    let mut integrals = vec![0.0; space.n_components()];
+    for cell in 0..space.mesh().n_cells() {
+        values.reinit(space.mesh(), cell);
+        let dofs = space.dof_map().cell_dofs(cell);
+        for q in 0..values.n_points() {
+            for (i, dof) in dofs.iter().enumerate() {
+                let scale = values.shape_value(q, i) * values.weight(q);
+                for (c, integral) in integrals.iter_mut().enumerate() {
+                    *integral += scale * function.values()[space.dof(*dof, c)];
+                }
+            }
+        }
+    }
+    integrals
+}
+
+// Accumulate a quantity computed at the quadrature points of the facets carrying a tag from the
+// facet values and the dofs of the first cell of each facet
+fn facet_sum<Integrand>(space: &FunctionSpace, tag: usize, degree: usize, mut integrand: Integrand)
+where
+    Integrand: FnMut(&FacetValues, usize, &[usize]),
+{
+    let mesh = space.mesh();
+    let facets = Facets::new(mesh);
+    let quadrature = QuadratureRule::simplex(mesh.topological_dim() - 1, degree);
+    let mut values = FacetValues::new(space.element(), &quadrature);
+    for facet in 0..facets.n_facets() {
+        let vertices = facets.facet_vertices(facet);
+        if mesh.facet_tag(vertices) != Some(tag) {
+            continue;
+        }
+        let cell = facets.facet_cells(facet)[0].0;
+        values.reinit(mesh, vertices, cell);
+        let dofs = space.dof_map().cell_dofs(cell);
+        for q in 0..values.n_points() {
+            integrand(&values, q, dofs);
+        }
+    }
+}
+
+// Integrals of the components of a function over the facets carrying a tag
+pub(crate) fn surface_integral(function: &Function, tag: usize) -> Vec<f64> {
+    let space = function.space();
+    let mut integrals = vec![0.0; space.n_components()];
+    facet_sum(
+        space,
+        tag,
+        space.element().order() + 1,
+        |values, q, dofs| {
+            for (i, dof) in dofs.iter().enumerate() {
+                let scale = values.shape_value(q, i) * values.weight(q);
+                for (c, integral) in integrals.iter_mut().enumerate() {
+                    *integral += scale * function.values()[space.dof(*dof, c)];
+                }
+            }
+        },
+    );
+    integrals
+}
+
+// Normal flux of a vector field, or diffusive flux of a scalar field, through the facets carrying
+// a tag
+pub(crate) fn flux(function: &Function, tag: usize, diffusivity: Option<f64>) -> f64 {
+    flux_coefficients(function.space(), tag, diffusivity)
+        .iter()
+        .zip(function.values())
+        .map(|(c, u)| c * u)
+        .sum()
+}
+
+// Coefficients of the dofs of a space in the flux through the facets carrying a tag, which is
+// linear in the field
+pub(crate) fn flux_coefficients(
+    space: &FunctionSpace,
+    tag: usize,
+    diffusivity: Option<f64>,
+) -> Vec<f64> {
+    let dim = space.mesh().geometric_dim();
+    let order = space.element().order();
+    let mut coefficients = vec![0.0; space.n_dofs()];
+    match diffusivity {
+        None => {
+            assert!(
+                space.n_components() == dim,
+                "Normal fluxes need a vector field of the dimension of the mesh"
+            );
+            facet_sum(space, tag, order + 1, |values, q, dofs| {
+                for (i, dof) in dofs.iter().enumerate() {
+                    let scale = values.shape_value(q, i) * values.weight(q);
+                    for (c, n) in values.normal().iter().enumerate() {
+                        coefficients[space.dof(*dof, c)] += scale * n;
+                    }
+                }
+            });
+        }
+        Some(diffusivity) => {
+            assert!(
+                space.n_components() == 1,
+                "Diffusive fluxes need a scalar field"
+            );
+            facet_sum(space, tag, order, |values, q, dofs| {
+                for (i, dof) in dofs.iter().enumerate() {
+                    let gradient = values.shape_gradient(q, i);
+                    let normal: f64 = gradient
+                        .iter()
+                        .zip(values.normal())
+                        .map(|(g, n)| g * n)
+                        .sum();
+                    coefficients[*dof] -= diffusivity * normal * values.weight(q);
+                }
+            });
+        }
+    }
+    coefficients
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+
+    #[test]
+    fn test_postprocess_quantities() {
+        // Unit square whose right side is tagged 2
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![3, 3]).simplex_mesh();
+        let scalars = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let vectors = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let mut u = Function::new(&scalars);
+        u.interpolate(|x| x[0] + 2.0 * x[1]);
+        // Rigid rotation of vorticity 2
+        let mut v = Function::new(&vectors);
+        v.interpolate_vector(|x, v| {
+            v[0] = -x[1];
+            v[1] = x[0];
+        });
+        let pipeline = Postprocess::new(&scalars)
+            .with_field("u", &u)
+            .with_field("v", &v)
+            .with_volume_integral("u")
+            .with_surface_integral("u", 2)
+            .with_diffusive_flux("u", 2, 3.0)
+            .with_volume_integral("v")
+            .with_flux("v", 2)
+            .with_vorticity("v");
+        let result = pipeline.run();
+        let expected = [
+            ("integral_u", 1.5),
+            ("integral_u_2", 2.0),
+            ("flux_u_2", -3.0),
+            ("integral_v_x", -0.5),
+            ("integral_v_y", 0.5),
+            ("flux_v_2", -0.5),
+        ];
+        assert_eq!(
+            result.quantities().len(),
+            expected.len(),
+            "Wrong quantities"
+        );
+        for (name, value) in expected {
+            assert!(
+                (result.quantity(name).unwrap() - value).abs() < 1e-12,
+                "Wrong {}",
+                name
+            );
+        }
+        let vorticity = result.field("vorticity").unwrap();
+        assert!(
+            vorticity.values().iter().all(|w| (w - 2.0).abs() < 1e-12),
+            "Wrong vorticity"
+        );
+        let directory = std::env::temp_dir().join("fe2o3_test_postprocess");
+        std::fs::create_dir_all(&directory).unwrap();
+        pipeline
+            .save_vtk(directory.join("fields.vtk"), &result)
+            .unwrap();
+        result
+            .save_quantities(directory.join("quantities.csv"))
+            .unwrap();
+        let vtk = std::fs::read_to_string(directory.join("fields.vtk")).unwrap();
+        assert!(
+            vtk.contains("vorticity") && vtk.contains(" u "),
+            "Missing fields"
+        );
+        let csv = std::fs::read_to_string(directory.join("quantities.csv")).unwrap();
+        assert!(
+            csv.starts_with("quantity,value\nintegral_u,"),
+            "Wrong table"
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_postprocess_fields() {
+        let mesh = CartesianGrid::new(vec![0.0; 3], vec![1.0; 3], vec![2; 3]).simplex_mesh();
+        let scalars = FunctionSpace::new(&mesh, LagrangeElement::new(3, 1));
+        let vectors = FunctionSpace::vector(&mesh, LagrangeElement::new(3, 1), 3);
+        // Uniaxial stretch plus a rigid rotation around z
+        let mut u = Function::new(&vectors);
+        u.interpolate_vector(|x, u| {
+            u[0] = 0.01 * x[0] - 0.1 * x[1];
+            u[1] = 0.1 * x[0];
+            u[2] = 0.0;
+        });
+        let material = IsotropicMaterial::new(100.0, 0.25);
+        let result = Postprocess::new(&scalars)
+            .with_field("displacement", &u)
+            .with_stress("displacement", material)
+            .with_vorticity("displacement")
+            .run();
+        let (lambda, mu) = material.lame_parameters();
+        let field = |name: &str| result.field(name).unwrap().values().to_vec();
+        for (name, value) in [
+            ("stress_xx", (lambda + 2.0 * mu) * 0.01),
+            ("stress_yy", lambda * 0.01),
+            ("stress_xy", 0.0),
+            ("vorticity_x", 0.0),
+            ("vorticity_z", 0.2),
+        ] {
+            assert!(
+                field(name).iter().all(|s| (s - value).abs() < 1e-12),
+                "Wrong {}",
+                name
+            );
+        }
+    }
+}
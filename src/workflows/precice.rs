@@ -0,0 +1,337 @@
+use super::coupling::CouplingParticipant;
+use crate::core::logging::{debug, info, Span};
+use crate::discretizations::mesh::Mesh;
+use std::collections::BTreeSet;
+use std::ffi::CString;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Participant of a preCICE co-simulation exchanging fields on the vertices of tagged surfaces
+/// with external codes such as OpenFOAM or CalculiX
+///
+/// Every interface is a mesh of the preCICE configuration made of the vertices of the facets
+/// carrying a tag, in increasing order, whose data values are stored vertex after vertex with the
+/// dimension of the data given by the configuration. A Fe2O3 workflow takes part through the
+/// CouplingParticipant trait: its steps are repeated with new values until preCICE accepts them,
+/// which replaces the checkpoints of implicit coupling schemes. preCICE must be installed with its
+/// C bindings (version 3) and linkable, and is finalized when the adapter is dropped.
+pub struct PreciceAdapter {
+    interfaces: Vec<PreciceInterface>,
+    initialized: bool,
+}
+
+/// Surface of a mesh registered as a preCICE mesh
+#[derive(Clone, Debug)]
+pub struct PreciceInterface {
+    name: CString,
+    vertices: Vec<usize>,
+    ids: Vec<i32>,
+}
+
+impl PreciceAdapter {
+    /// Create the participant of the given name from a preCICE configuration file, for the process
+    /// of the given index among the processes of the solver
+    pub fn new(participant: &str, config_file: &str, rank: usize, size: usize) -> Self {
+        let participant = CString::new(participant).expect("Participant is not a valid C string");
+        let config_file = CString::new(config_file).expect("Path is not a valid C string");
+        unsafe {
+            ffi::precicec_createParticipant(
+                participant.as_ptr(),
+                config_file.as_ptr(),
+                rank as i32,
+                size as i32,
+            );
+        }
+        PreciceAdapter {
+            interfaces: Vec::new(),
+            initialized: false,
+        }
+    }
+
+    /// Register the vertices of the facets carrying a tag as the preCICE mesh of the given name,
+    /// returning the index of the interface
+    pub fn add_interface(&mut self, name: &str, mesh: &Mesh, tag: usize) -> usize {
+        assert!(
+            !self.initialized,
+            "Interfaces are set before initialization"
+        );
+        let name = CString::new(name).expect("Mesh name is not a valid C string");
+        let dim = mesh.geometric_dim();
+        let mesh_dim = unsafe { ffi::precicec_getMeshDimensions(name.as_ptr()) } as usize;
+        assert_eq!(
+            mesh_dim, dim,
+            "Dimension of the preCICE mesh does not match"
+        );
+        let vertices = tagged_vertices(mesh, tag);
+        assert!(!vertices.is_empty(), "No facet carries the interface tag");
+        let coordinates: Vec<f64> = vertices
+            .iter()
+            .flat_map(|v| mesh.vertex(*v).iter().copied())
+            .collect();
+        let mut ids = vec![0; vertices.len()];
+        unsafe {
+            ffi::precicec_setMeshVertices(
+                name.as_ptr(),
+                vertices.len() as i32,
+                coordinates.as_ptr(),
+                ids.as_mut_ptr(),
+            );
+        }
+        self.interfaces.push(PreciceInterface {
+            name,
+            vertices,
+            ids,
+        });
+        self.interfaces.len() - 1
+    }
+
+    /// Interface of the given index
+    pub fn interface(&self, interface: usize) -> &PreciceInterface {
+        &self.interfaces[interface]
+    }
+
+    /// Whether initial data should be written before initialization
+    pub fn requires_initial_data(&self) -> bool {
+        unsafe { ffi::precicec_requiresInitialData() != 0 }
+    }
+
+    /// Initialize the coupling once the interfaces are set and the initial data written
+    pub fn initialize(&mut self) {
+        assert!(!self.initialized, "preCICE was already initialized");
+        unsafe { ffi::precicec_initialize() };
+        self.initialized = true;
+    }
+
+    /// Whether the coupled simulation goes on
+    pub fn is_coupling_ongoing(&self) -> bool {
+        unsafe { ffi::precicec_isCouplingOngoing() != 0 }
+    }
+
+    /// Largest time step allowed by preCICE before the next exchange
+    pub fn max_time_step(&self) -> f64 {
+        unsafe { ffi::precicec_getMaxTimeStepSize() }
+    }
+
+    /// Values of some data on the vertices of an interface at a time relative to the start of the
+    /// current step
+    pub fn read(&self, interface: usize, data: &str, relative_time: f64) -> Vec<f64> {
+        let interface = &self.interfaces[interface];
+        let data = CString::new(data).expect("Data name is not a valid C string");
+        let mut values = vec![0.0; interface.n_vertices() * interface.data_dim(&data)];
+        unsafe {
+            ffi::precicec_readData(
+                interface.name.as_ptr(),
+                data.as_ptr(),
+                interface.ids.len() as i32,
+                interface.ids.as_ptr(),
+                relative_time,
+                values.as_mut_ptr(),
+            );
+        }
+        values
+    }
+
+    /// Write values of some data on the vertices of an interface
+    pub fn write(&mut self, interface: usize, data: &str, values: &[f64]) {
+        let interface = &self.interfaces[interface];
+        let data = CString::new(data).expect("Data name is not a valid C string");
+        assert_eq!(
+            values.len(),
+            interface.n_vertices() * interface.data_dim(&data),
+            "Values do not match the interface"
+        );
+        unsafe {
+            ffi::precicec_writeData(
+                interface.name.as_ptr(),
+                data.as_ptr(),
+                interface.ids.len() as i32,
+                interface.ids.as_ptr(),
+                values.as_ptr(),
+            );
+        }
+    }
+
+    /// Advance the coupling by a time step, exchanging the data written
+    pub fn advance(&mut self, time_step: f64) {
+        assert!(self.initialized, "preCICE was not initialized");
+        unsafe { ffi::precicec_advance(time_step) };
+    }
+
+    /// Whether the state at the start of the step should be kept before solving it
+    pub fn requires_writing_checkpoint(&self) -> bool {
+        unsafe { ffi::precicec_requiresWritingCheckpoint() != 0 }
+    }
+
+    /// Whether the step should be solved again from the checkpoint
+    pub fn requires_reading_checkpoint(&self) -> bool {
+        unsafe { ffi::precicec_requiresReadingCheckpoint() != 0 }
+    }
+
+    /// Couple a workflow through an interface until the co-simulation ends, the workflow receiving
+    /// the read data and sending back the written data every step with the smallest of its time
+    /// step and the one allowed by preCICE. Returns the final time and the number of accepted steps.
+    pub fn couple<Participant>(
+        &mut self,
+        participant: &mut Participant,
+        interface: usize,
+        read_data: &str,
+        write_data: &str,
+        time_step: f64,
+    ) -> (f64, usize)
+    where
+        Participant: CouplingParticipant,
+    {
+        let _span = Span::enter("precice");
+        assert!(time_step > 0.0, "Time step should be positive");
+        if !self.initialized {
+            self.initialize();
+        }
+        let mut time = 0.0;
+        let mut steps = 0;
+        let mut iterations = 0;
+        while self.is_coupling_ongoing() {
+            let dt = time_step.min(self.max_time_step());
+            // The participant keeps the state of its last accepted step, which is the checkpoint
+            self.requires_writing_checkpoint();
+            let received = self.read(interface, read_data, dt);
+            let sent = participant.solve_step(time, dt, &received);
+            self.write(interface, write_data, &sent);
+            self.advance(dt);
+            iterations += 1;
+            // Steps are only accepted once preCICE does not ask to repeat them
+            if !self.requires_reading_checkpoint() {
+                participant.accept_step();
+                time += dt;
+                steps += 1;
+                debug!(
+                    "preCICE step {} accepted at time {:e} after {} iterations",
+                    steps, time, iterations
+                );
+                iterations = 0;
+            }
+        }
+        info!(
+            "preCICE coupling ended at time {:e} after {} steps",
+            time, steps
+        );
+        (time, steps)
+    }
+}
+
+impl Drop for PreciceAdapter {
+    fn drop(&mut self) {
+        unsafe { ffi::precicec_finalize() };
+    }
+}
+
+impl PreciceInterface {
+    /// Name of the preCICE mesh
+    pub fn name(&self) -> &str {
+        self.name.to_str().unwrap()
+    }
+
+    /// Number of vertices of the interface
+    pub fn n_vertices(&self) -> usize {
+        self.vertices.len()
+    }
+
+    /// Vertices of the Fe2O3 mesh on the interface, in the order of the data values
+    pub fn vertices(&self) -> &[usize] {
+        &self.vertices
+    }
+
+    // Number of components of some data on the interface
+    fn data_dim(&self, data: &CString) -> usize {
+        unsafe { ffi::precicec_getDataDimensions(self.name.as_ptr(), data.as_ptr()) as usize }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Vertices of the facets of a mesh carrying a tag, in increasing order
+pub fn tagged_vertices(mesh: &Mesh, tag: usize) -> Vec<usize> {
+    let vertices: BTreeSet<usize> = mesh
+        .facet_tags()
+        .filter(|(_, t)| *t == tag)
+        .flat_map(|(facet, _)| facet.iter().copied())
+        .collect();
+    vertices.into_iter().collect()
+}
+
+// C bindings of preCICE version 3
+mod ffi {
+    use std::ffi::c_char;
+
+    #[link(name = "precice")]
+    extern "C" {
+        pub fn precicec_createParticipant(
+            participant_name: *const c_char,
+            configuration_file_name: *const c_char,
+            solver_process_index: i32,
+            solver_process_size: i32,
+        );
+        pub fn precicec_initialize();
+        pub fn precicec_advance(computed_time_step_size: f64);
+        pub fn precicec_finalize();
+        pub fn precicec_getMeshDimensions(mesh_name: *const c_char) -> i32;
+        pub fn precicec_getDataDimensions(
+            mesh_name: *const c_char,
+            data_name: *const c_char,
+        ) -> i32;
+        pub fn precicec_isCouplingOngoing() -> i32;
+        pub fn precicec_getMaxTimeStepSize() -> f64;
+        pub fn precicec_requiresInitialData() -> i32;
+        pub fn precicec_requiresWritingCheckpoint() -> i32;
+        pub fn precicec_requiresReadingCheckpoint() -> i32;
+        pub fn precicec_setMeshVertices(
+            mesh_name: *const c_char,
+            size: i32,
+            coordinates: *const f64,
+            ids: *mut i32,
+        );
+        pub fn precicec_writeData(
+            mesh_name: *const c_char,
+            data_name: *const c_char,
+            size: i32,
+            value_indices: *const i32,
+            values: *const f64,
+        );
+        pub fn precicec_readData(
+            mesh_name: *const c_char,
+            data_name: *const c_char,
+            size: i32,
+            value_indices: *const i32,
+            relative_read_time: f64,
+            values: *mut f64,
+        );
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+
+    #[test]
+    fn test_tagged_vertices() {
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![3, 2]).simplex_mesh();
+        let vertices = tagged_vertices(&mesh, 2);
+        assert_eq!(vertices.len(), 3, "Right side has three vertices");
+        assert!(
+            vertices.windows(2).all(|w| w[0] < w[1]),
+            "Vertices should be sorted"
+        );
+        assert!(
+            vertices.iter().all(|v| mesh.vertex(*v)[0] == 1.0),
+            "Vertex off the tagged side"
+        );
+    }
+}
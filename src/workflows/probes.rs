@@ -0,0 +1,326 @@
+use super::events::{Control, WorkflowObserver};
+use super::postprocess::{push_components, surface_integral};
+use crate::core::arrays::data_hold::DataHold;
+use crate::discretizations::function::Function;
+use std::cell::RefCell;
+use std::fmt::Write as _;
+#[cfg(hosted)]
+use std::fs::File;
+#[cfg(hosted)]
+use std::io::{BufWriter, Result, Write};
+#[cfg(hosted)]
+use std::path::Path;
+use std::rc::Rc;
+
+//--------------------------------------------------------------------------------------------------
+// # Enums
+//--------------------------------------------------------------------------------------------------
+
+// Location sampled by a probe
+enum Location {
+    Point(Vec<f64>),
+    Line(Vec<f64>, Vec<f64>, usize),
+    Surface(usize),
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Monitor points, lines and tagged surfaces sampled at every record and logged to a table, for
+/// instance every step of a transient run as a workflow observer
+///
+/// Every record gets a row with its time followed by the values of the components of the solution
+/// at the points, at the n equally spaced points of the lines (from start to end) and their
+/// integrals over the facets carrying the tags, in the order the probes were added. Columns are
+/// named after the probes, with the index of the point along a line and the axis of the component
+/// for vector solutions. Values at points outside of the mesh are NaN. Rows can be streamed to a
+/// CSV file as they are recorded, which is flushed after every row so that the histories can be
+/// followed during long runs. Clones share the same table.
+#[derive(Clone)]
+pub struct Probe {
+    probes: Rc<Vec<(String, Location)>>,
+    log: Rc<RefCell<ProbeLog>>,
+}
+
+// Table of the records and its optional CSV stream
+#[derive(Default)]
+struct ProbeLog {
+    columns: Vec<String>,
+    rows: Vec<Vec<f64>>,
+    #[cfg(hosted)]
+    stream: Option<BufWriter<File>>,
+    #[cfg(hosted)]
+    error: Option<std::io::Error>,
+}
+
+impl Probe {
+    /// Probe without locations
+    pub fn new() -> Self {
+        Probe {
+            probes: Rc::new(Vec::new()),
+            log: Rc::new(RefCell::new(ProbeLog::default())),
+        }
+    }
+
+    /// Sample the solution at a point
+    pub fn with_point(self, name: &str, point: &[f64]) -> Self {
+        self.with(name, Location::Point(point.to_vec()))
+    }
+
+    /// Sample the solution at n equally spaced points from the start to the end of a line
+    pub fn with_line(self, name: &str, start: &[f64], end: &[f64], n_points: usize) -> Self {
+        assert!(
+            start.len() == end.len(),
+            "Line ends of different dimensions"
+        );
+        assert!(n_points >= 2, "Lines need at least two points");
+        self.with(name, Location::Line(start.to_vec(), end.to_vec(), n_points))
+    }
+
+    /// Integrate the solution over the facets carrying a tag
+    pub fn with_surface(self, name: &str, tag: usize) -> Self {
+        self.with(name, Location::Surface(tag))
+    }
+
+    /// Stream the rows to a CSV file, created or truncated
+    #[cfg(hosted)]
+    pub fn with_csv<P: AsRef<Path>>(self, path: P) -> Result<Self> {
+        self.log.borrow_mut().stream = Some(BufWriter::new(File::create(path)?));
+        Ok(self)
+    }
+
+    /// Sample the solution at a time, returning the row of the record
+    pub fn record(&self, time: f64, solution: &Function) -> Vec<f64> {
+        let samples = self.sample(solution);
+        let mut log = self.log.borrow_mut();
+        if log.columns.is_empty() {
+            log.columns = std::iter::once("time".to_string())
+                .chain(samples.iter().map(|(name, _)| name.clone()))
+                .collect();
+            #[cfg(hosted)]
+            {
+                let header = log.columns.join(",");
+                log.stream_line(&header);
+            }
+        }
+        assert_eq!(
+            samples.len() + 1,
+            log.columns.len(),
+            "Records do not match the columns of the probe"
+        );
+        let row: Vec<f64> = std::iter::once(time)
+            .chain(samples.iter().map(|(_, value)| *value))
+            .collect();
+        #[cfg(hosted)]
+        {
+            let line = csv_row(&row);
+            log.stream_line(&line);
+        }
+        log.rows.push(row.clone());
+        row
+    }
+
+    /// Names of the columns, empty before the first record
+    pub fn columns(&self) -> Vec<String> {
+        self.log.borrow().columns.clone()
+    }
+
+    /// Rows of the records
+    pub fn rows(&self) -> Vec<Vec<f64>> {
+        self.log.borrow().rows.clone()
+    }
+
+    /// History of a column over the records
+    pub fn column(&self, name: &str) -> Vec<f64> {
+        let log = self.log.borrow();
+        let index = log
+            .columns
+            .iter()
+            .position(|column| column == name)
+            .unwrap_or_else(|| panic!("No column {}", name));
+        log.rows.iter().map(|row| row[index]).collect()
+    }
+
+    /// Table as CSV with a header line
+    pub fn to_csv(&self) -> String {
+        let log = self.log.borrow();
+        let mut csv = log.columns.join(",");
+        csv.push('\n');
+        for row in &log.rows {
+            writeln!(csv, "{}", csv_row(row)).unwrap();
+        }
+        csv
+    }
+
+    /// First error met while streaming the rows, the stream being stopped at it
+    #[cfg(hosted)]
+    pub fn status(&self) -> Result<()> {
+        match self.log.borrow_mut().error.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    // Add a location, before the probe is shared
+    fn with(mut self, name: &str, location: Location) -> Self {
+        Rc::get_mut(&mut self.probes)
+            .expect("Locations should be added before the probe is cloned")
+            .push((name.to_string(), location));
+        self
+    }
+
+    // Named values of the components of the solution at the locations
+    fn sample(&self, solution: &Function) -> Vec<(String, f64)> {
+        let dim = solution.space().mesh().geometric_dim();
+        let n_components = solution.space().n_components();
+        let mut samples = Vec::new();
+        for (name, location) in self.probes.iter() {
+            match location {
+                Location::Point(point) => {
+                    assert!(point.len() == dim, "Probe point of a wrong dimension");
+                    let value = solution
+                        .eval(point)
+                        .unwrap_or_else(|| vec![f64::NAN; n_components]);
+                    push_components(&mut samples, name, &value);
+                }
+                Location::Line(start, end, n_points) => {
+                    assert!(start.len() == dim, "Probe line of a wrong dimension");
+                    let coordinates: Vec<f64> = (0..*n_points)
+                        .flat_map(|k| {
+                            let s = k as f64 / (*n_points - 1) as f64;
+                            start.iter().zip(end).map(move |(a, b)| a + s * (b - a))
+                        })
+                        .collect();
+                    let points = DataHold::new(coordinates, [*n_points, dim]);
+                    for (k, value) in solution.eval_at(&points).into_iter().enumerate() {
+                        let value = value.unwrap_or_else(|| vec![f64::NAN; n_components]);
+                        push_components(&mut samples, &format!("{}_{}", name, k), &value);
+                    }
+                }
+                Location::Surface(tag) => {
+                    push_components(&mut samples, name, &surface_integral(solution, *tag));
+                }
+            }
+        }
+        samples
+    }
+}
+
+impl Default for Probe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(hosted)]
+impl ProbeLog {
+    // Write and flush a line to the stream, keeping the first error
+    fn stream_line(&mut self, line: &str) {
+        if let Some(stream) = &mut self.stream {
+            if let Err(error) = writeln!(stream, "{}", line).and_then(|_| stream.flush()) {
+                self.error = Some(error);
+                self.stream = None;
+            }
+        }
+    }
+}
+
+impl WorkflowObserver for Probe {
+    fn on_solved(&self, _step: usize, time: f64, solution: &Function) -> Control {
+        self.record(time, solution);
+        Control::Continue
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Values of a row separated by commas
+fn csv_row(row: &[f64]) -> String {
+    row.iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::workflows::heat::HeatEquation;
+
+    #[test]
+    fn test_probe() {
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![4, 4]).simplex_mesh();
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        let directory = std::env::temp_dir().join("fe2o3_test_probe");
+        std::fs::create_dir_all(&directory).unwrap();
+        let probe = Probe::new()
+            .with_point("center", &[0.5, 0.5])
+            .with_point("outside", &[2.0, 0.0])
+            .with_line("diagonal", &[0.0, 0.0], &[1.0, 1.0], 3)
+            .with_surface("right", 2)
+            .with_csv(directory.join("probe.csv"))
+            .unwrap();
+        // u = (1 + t) x is reproduced exactly with the source x and the temperature on x = 0, 1
+        let problem = HeatEquation::new(&space)
+            .with_source(|_, x| x[0])
+            .with_dirichlet(1, |_, _| 0.0)
+            .with_dirichlet(2, |t, _| 1.0 + t)
+            .with_observer(probe.clone());
+        let mut u = Function::new(&space);
+        u.interpolate(|x| x[0]);
+        problem.solve(&mut u, 0.0, 1.0, 0.25, |_, _| {});
+        assert!(probe.status().is_ok(), "Streaming failed");
+        assert_eq!(
+            probe.columns(),
+            [
+                "time",
+                "center",
+                "outside",
+                "diagonal_0",
+                "diagonal_1",
+                "diagonal_2",
+                "right"
+            ],
+            "Wrong columns"
+        );
+        let rows = probe.rows();
+        assert_eq!(rows.len(), 5, "Every step should be recorded");
+        let last = &rows[4];
+        assert!(
+            (last[1] - 1.0).abs() < 1e-10 && last[2].is_nan(),
+            "Wrong point values {:?}",
+            last
+        );
+        assert!(
+            last[3].abs() < 1e-10 && (last[4] - 1.0).abs() < 1e-10 && (last[5] - 2.0).abs() < 1e-10,
+            "Wrong line values {:?}",
+            last
+        );
+        assert!(
+            (last[6] - 2.0).abs() < 1e-10,
+            "Wrong surface integral {}",
+            last[6]
+        );
+        assert_eq!(
+            probe.column("time"),
+            [0.0, 0.25, 0.5, 0.75, 1.0],
+            "Wrong times"
+        );
+        let streamed = std::fs::read_to_string(directory.join("probe.csv")).unwrap();
+        assert_eq!(
+            streamed,
+            probe.to_csv(),
+            "Streamed rows differ from the table"
+        );
+    }
+}
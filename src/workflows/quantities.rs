@@ -0,0 +1,674 @@
+use super::postprocess::{flux, flux_coefficients};
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::discretizations::cell_values::CellValues;
+use crate::discretizations::function::Function;
+use crate::spaces::quadrature::QuadratureRule;
+use std::fmt::Write;
+#[cfg(hosted)]
+use std::path::Path;
+
+// Integrand of the physical coordinates and the components of a solution
+type Integrand<'a> = Box<dyn Fn(&[f64], &[f64]) -> f64 + 'a>;
+
+//--------------------------------------------------------------------------------------------------
+// # Traits
+//--------------------------------------------------------------------------------------------------
+
+/// Scalar quantity of interest of a solution, such as a flux, an integral or a point value
+pub trait QuantityOfInterest {
+    /// Name of the quantity
+    fn name(&self) -> &str;
+
+    /// Value of the quantity for a solution
+    fn evaluate(&self, solution: &Function) -> f64;
+
+    /// Derivative of the quantity with respect to every dof of a solution, the right hand side of
+    /// its adjoint problem
+    ///
+    /// The default differentiates evaluate by central differences, costing two evaluations per
+    /// dof: quantities used in adjoint computations should provide the exact derivative.
+    fn derivative(&self, solution: &Function) -> Vec<f64> {
+        let mut perturbed = Function::from_values(solution.space(), solution.values().to_vec());
+        (0..solution.values().len())
+            .map(|dof| {
+                let value = solution.values()[dof];
+                let h = 1e-6 * value.abs().max(1.0);
+                perturbed.values_mut()[dof] = value + h;
+                let forward = self.evaluate(&perturbed);
+                perturbed.values_mut()[dof] = value - h;
+                let backward = self.evaluate(&perturbed);
+                perturbed.values_mut()[dof] = value;
+                (forward - backward) / (2.0 * h)
+            })
+            .collect()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Integral over the mesh of an integrand of the physical coordinates and the components of the
+/// solution
+pub struct DomainIntegral<'a> {
+    name: String,
+    integrand: Integrand<'a>,
+}
+
+/// Flux through the facets carrying a tag, oriented out of the domain on the boundary: the normal
+/// flux v.n of a vector solution or the diffusive flux -k grad(u).n of a scalar one
+pub struct BoundaryFlux {
+    name: String,
+    tag: usize,
+    diffusivity: Option<f64>,
+}
+
+/// Value of a component of the solution at a point, NaN outside of the mesh
+pub struct PointValue {
+    name: String,
+    point: Vec<f64>,
+    component: usize,
+}
+
+/// Reaction at a Dirichlet boundary: the sum of the residual K u - f of the unconstrained system
+/// over the dofs of a component on the facets carrying a tag
+///
+/// It is the force (or heat flux) the boundary condition applies to the domain, and for scalar
+/// diffusion the opposite of the outward diffusive flux.
+pub struct Reaction<'a> {
+    name: String,
+    tag: usize,
+    component: usize,
+    matrix: &'a SparseCSR<f64>,
+    load: &'a [f64],
+}
+
+/// Quantity computed by any function of the solution, for instance a maximal stress
+pub struct Functional<'a> {
+    name: String,
+    functional: Box<dyn Fn(&Function) -> f64 + 'a>,
+}
+
+/// Quantities of interest evaluated after every solve or time step and logged to a table
+///
+/// Every record gets a row with its time (or load step, iteration...) followed by the values of
+/// the quantities in the order they were added.
+pub struct QuantityLog<'a> {
+    quantities: Vec<Box<dyn QuantityOfInterest + 'a>>,
+    rows: Vec<Vec<f64>>,
+}
+
+impl<'a> DomainIntegral<'a> {
+    /// Integral of an integrand of the coordinates and the components of the solution
+    pub fn new<Integrand>(name: &str, integrand: Integrand) -> Self
+    where
+        Integrand: Fn(&[f64], &[f64]) -> f64 + 'a,
+    {
+        DomainIntegral {
+            name: name.to_string(),
+            integrand: Box::new(integrand),
+        }
+    }
+}
+
+impl BoundaryFlux {
+    /// Normal flux of a vector solution of the dimension of the mesh
+    pub fn normal(name: &str, tag: usize) -> Self {
+        BoundaryFlux {
+            name: name.to_string(),
+            tag,
+            diffusivity: None,
+        }
+    }
+
+    /// Diffusive flux of a scalar solution with a diffusivity
+    pub fn diffusive(name: &str, tag: usize, diffusivity: f64) -> Self {
+        BoundaryFlux {
+            name: name.to_string(),
+            tag,
+            diffusivity: Some(diffusivity),
+        }
+    }
+}
+
+impl PointValue {
+    /// Value of a component of the solution at a point
+    pub fn new(name: &str, point: &[f64], component: usize) -> Self {
+        PointValue {
+            name: name.to_string(),
+            point: point.to_vec(),
+            component,
+        }
+    }
+}
+
+impl<'a> Reaction<'a> {
+    /// Reaction of a component on a tag given the unconstrained matrix and load of the problem
+    pub fn new(
+        name: &str,
+        tag: usize,
+        component: usize,
+        matrix: &'a SparseCSR<f64>,
+        load: &'a [f64],
+    ) -> Self {
+        assert!(
+            matrix.n_rows() == load.len(),
+            "Load does not match the matrix"
+        );
+        Reaction {
+            name: name.to_string(),
+            tag,
+            component,
+            matrix,
+            load,
+        }
+    }
+}
+
+impl<'a> Functional<'a> {
+    /// Quantity of a function of the solution
+    pub fn new<F: Fn(&Function) -> f64 + 'a>(name: &str, functional: F) -> Self {
+        Functional {
+            name: name.to_string(),
+            functional: Box::new(functional),
+        }
+    }
+}
+
+impl<'a> QuantityLog<'a> {
+    /// Empty log
+    pub fn new() -> Self {
+        QuantityLog {
+            quantities: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Add a quantity to evaluate
+    pub fn with<Quantity: QuantityOfInterest + 'a>(mut self, quantity: Quantity) -> Self {
+        assert!(
+            self.rows.is_empty(),
+            "Quantities are added before recording"
+        );
+        self.quantities.push(Box::new(quantity));
+        self
+    }
+
+    /// Names of the quantities
+    pub fn names(&self) -> Vec<&str> {
+        self.quantities.iter().map(|q| q.name()).collect()
+    }
+
+    /// Evaluate the quantities for the solution at a time and log them, returning their values
+    pub fn record(&mut self, time: f64, solution: &Function) -> &[f64] {
+        let mut row = vec![time];
+        row.extend(self.quantities.iter().map(|q| q.evaluate(solution)));
+        self.rows.push(row);
+        &self.rows.last().unwrap()[1..]
+    }
+
+    /// Logged rows of the time followed by the quantities
+    pub fn rows(&self) -> &[Vec<f64>] {
+        &self.rows
+    }
+
+    /// Values of a quantity over the records
+    pub fn column(&self, name: &str) -> Vec<f64> {
+        let index = self
+            .quantities
+            .iter()
+            .position(|q| q.name() == name)
+            .unwrap_or_else(|| panic!("Unknown quantity {}", name));
+        self.rows.iter().map(|row| row[index + 1]).collect()
+    }
+
+    /// Comma separated table with a header and one row per record
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("time");
+        for name in self.names() {
+            write!(csv, ",{}", name).unwrap();
+        }
+        csv.push('\n');
+        for row in &self.rows {
+            let values: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+            writeln!(csv, "{}", values.join(",")).unwrap();
+        }
+        csv
+    }
+
+    /// JSON array with one object per record, non finite values being null
+    pub fn to_json(&self) -> String {
+        let mut columns = vec!["time"];
+        columns.extend(self.names());
+        let mut json = String::from("[\n");
+        for (k, row) in self.rows.iter().enumerate() {
+            let fields: Vec<String> = columns
+                .iter()
+                .zip(row)
+                .map(|(c, v)| {
+                    if v.is_finite() {
+                        format!("\"{}\": {}", c, v)
+                    } else {
+                        format!("\"{}\": null", c)
+                    }
+                })
+                .collect();
+            let separator = if k + 1 < self.rows.len() { "," } else { "" };
+            writeln!(json, "  {{{}}}{}", fields.join(", "), separator).unwrap();
+        }
+        json.push_str("]\n");
+        json
+    }
+
+    /// Write the log as JSON if the path has the json extension and as CSV otherwise
+    #[cfg(hosted)]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let contents = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => self.to_json(),
+            _ => self.to_csv(),
+        };
+        std::fs::write(path, contents)
+    }
+}
+
+impl<'a> Default for QuantityLog<'a> {
+    fn default() -> Self {
+        QuantityLog::new()
+    }
+}
+
+impl<'a> DomainIntegral<'a> {
+    // Visit the quadrature points of the cells with their values, dofs and solution components
+    fn for_each_point<Visit>(&self, solution: &Function, mut visit: Visit)
+    where
+        Visit: FnMut(&CellValues, usize, &[usize], &[f64]),
+    {
+        let space = solution.space();
+        let element = space.element();
+        let quadrature = QuadratureRule::simplex(element.dim(), 2 * element.order() + 1);
+        let mut values = CellValues::new(element, &quadrature);
+        let mut u = vec![0.0; space.n_components()];
+        for cell in 0..space.mesh().n_cells() {
+            values.reinit(space.mesh(), cell);
+            let dofs = space.dof_map().cell_dofs(cell);
+            for q in 0..values.n_points() {
+                u.iter_mut().for_each(|u| *u = 0.0);
+                for (i, dof) in dofs.iter().enumerate() {
+                    for (c, u) in u.iter_mut().enumerate() {
+                        *u += values.shape_value(q, i) * solution.values()[space.dof(*dof, c)];
+                    }
+                }
+                visit(&values, q, dofs, &u);
+            }
+        }
+    }
+}
+
+impl<'a> QuantityOfInterest for DomainIntegral<'a> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn evaluate(&self, solution: &Function) -> f64 {
+        let mut integral = 0.0;
+        self.for_each_point(solution, |values, q, _, u| {
+            integral += (self.integrand)(values.point(q), u) * values.weight(q);
+        });
+        integral
+    }
+
+    // The integrand is differentiated in the components by central differences at every point
+    fn derivative(&self, solution: &Function) -> Vec<f64> {
+        let space = solution.space();
+        let mut derivative = vec![0.0; space.n_dofs()];
+        let mut perturbed = vec![0.0; space.n_components()];
+        self.for_each_point(solution, |values, q, dofs, u| {
+            let x = values.point(q);
+            perturbed.copy_from_slice(u);
+            for c in 0..u.len() {
+                let h = 1e-6 * u[c].abs().max(1.0);
+                perturbed[c] = u[c] + h;
+                let forward = (self.integrand)(x, &perturbed);
+                perturbed[c] = u[c] - h;
+                let backward = (self.integrand)(x, &perturbed);
+                perturbed[c] = u[c];
+                let slope = (forward - backward) / (2.0 * h) * values.weight(q);
+                for (i, dof) in dofs.iter().enumerate() {
+                    derivative[space.dof(*dof, c)] += slope * values.shape_value(q, i);
+                }
+            }
+        });
+        derivative
+    }
+}
+
+impl QuantityOfInterest for BoundaryFlux {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn evaluate(&self, solution: &Function) -> f64 {
+        flux(solution, self.tag, self.diffusivity)
+    }
+
+    fn derivative(&self, solution: &Function) -> Vec<f64> {
+        flux_coefficients(solution.space(), self.tag, self.diffusivity)
+    }
+}
+
+impl QuantityOfInterest for PointValue {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn evaluate(&self, solution: &Function) -> f64 {
+        solution
+            .eval(&self.point)
+            .map_or(f64::NAN, |value| value[self.component])
+    }
+
+    // The shape functions of the cell containing the point, zero outside of the mesh
+    fn derivative(&self, solution: &Function) -> Vec<f64> {
+        let space = solution.space();
+        let mut derivative = vec![0.0; space.n_dofs()];
+        if let Some((cell, reference)) = space.mesh().locate(&self.point) {
+            let mut shape_values = vec![0.0; space.element().n_dofs()];
+            space.element().values(&reference, &mut shape_values);
+            for (phi, dof) in shape_values.iter().zip(space.dof_map().cell_dofs(cell)) {
+                derivative[space.dof(*dof, self.component)] += phi;
+            }
+        }
+        derivative
+    }
+}
+
+impl<'a> QuantityOfInterest for Reaction<'a> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn evaluate(&self, solution: &Function) -> f64 {
+        let space = solution.space();
+        assert!(
+            self.matrix.n_rows() == space.n_dofs(),
+            "The system does not match the space of the solution"
+        );
+        let mut residual = vec![0.0; space.n_dofs()];
+        self.matrix.apply(solution.values(), &mut residual);
+        space
+            .tagged_dofs(self.tag)
+            .iter()
+            .map(|s| {
+                let dof = space.dof(*s, self.component);
+                residual[dof] - self.load[dof]
+            })
+            .sum()
+    }
+
+    // The sum of the rows of the matrix of the tagged dofs
+    fn derivative(&self, solution: &Function) -> Vec<f64> {
+        let space = solution.space();
+        let mut derivative = vec![0.0; space.n_dofs()];
+        for s in space.tagged_dofs(self.tag) {
+            let (columns, values) = self.matrix.row(space.dof(s, self.component));
+            for (j, value) in columns.iter().zip(values) {
+                derivative[*j] += value;
+            }
+        }
+        derivative
+    }
+}
+
+impl<'a> QuantityOfInterest for Functional<'a> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn evaluate(&self, solution: &Function) -> f64 {
+        (self.functional)(solution)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::discretizations::operators::stiffness_matrix;
+    use crate::discretizations::test_meshes::unit_square_grid;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::workflows::advection_diffusion::{AdvectionDiffusion, Stabilization};
+    use std::panic::{self, AssertUnwindSafe};
+
+    // Description of an invalid input with the construction it should make panic
+    type Invalid<'a> = (&'a str, Box<dyn Fn() + 'a>);
+
+    // Quantity differentiated by the central differences of the default derivative
+    struct Differenced<'q>(&'q dyn QuantityOfInterest);
+
+    impl QuantityOfInterest for Differenced<'_> {
+        fn name(&self) -> &str {
+            self.0.name()
+        }
+
+        fn evaluate(&self, solution: &Function) -> f64 {
+            self.0.evaluate(solution)
+        }
+    }
+
+    #[test]
+    fn test_quantities_of_interest() {
+        // -Δu = 2 with u = 0 on the left and right sides, solved exactly by u = x (1 - x)
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![3, 3]).simplex_mesh();
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        let u = AdvectionDiffusion::new(&space)
+            .with_stabilization(Stabilization::Galerkin)
+            .with_source(|_| 2.0)
+            .with_dirichlet(1, |_| 0.0)
+            .with_dirichlet(2, |_| 0.0)
+            .solve();
+        let assembler = space.assembler(QuadratureRule::simplex(2, 4));
+        let matrix = stiffness_matrix(&assembler, |_| 1.0);
+        let mut load = vec![0.0; space.n_dofs()];
+        assembler.assemble_vector(&mut load, |values, local| {
+            for q in 0..values.n_points() {
+                for i in 0..values.n_dofs() {
+                    local[i] += 2.0 * values.shape_value(q, i) * values.weight(q);
+                }
+            }
+        });
+        let mut log = QuantityLog::new()
+            .with(DomainIntegral::new("mean", |_, u| u[0]))
+            .with(DomainIntegral::new("energy", |x, u| x[1] * u[0] * u[0]))
+            .with(BoundaryFlux::diffusive("left_flux", 1, 1.0))
+            .with(Reaction::new("left_reaction", 1, 0, &matrix, &load))
+            .with(PointValue::new("center", &[0.5, 0.3], 0))
+            .with(PointValue::new("outside", &[2.0, 0.0], 0))
+            .with(Functional::new("max", |u| {
+                u.values().iter().cloned().fold(f64::MIN, f64::max)
+            }));
+        let values = log.record(0.0, &u).to_vec();
+        // Mean 1/6, energy ∫ y x² (1 - x)² = 1/60, outflux 1 through the left side
+        let expected = [1.0 / 6.0, 1.0 / 60.0, 1.0, -1.0, 0.25];
+        for (k, (value, expected)) in values.iter().zip(expected).enumerate() {
+            assert!(
+                (value - expected).abs() < 1e-10,
+                "Wrong {}: {} instead of {}",
+                log.names()[k],
+                value,
+                expected
+            );
+        }
+        assert!(values[5].is_nan(), "Points outside give NaN");
+        assert!((values[6] - 0.25).abs() < 1e-12, "Wrong maximum");
+        log.record(1.0, &u);
+        assert_eq!(log.column("left_flux").len(), 2, "Wrong number of records");
+        let csv = log.to_csv();
+        assert!(
+            csv.starts_with("time,mean,energy,left_flux,left_reaction,center,outside,max\n0,"),
+            "Wrong CSV header {}",
+            csv
+        );
+        assert!(log.to_json().contains("\"outside\": null"), "Wrong JSON");
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_quantity_derivatives() {
+        // The exact derivatives match the central differences of the evaluations
+        let mesh = unit_square_grid(3);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        let vector_space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let mut u = Function::new(&space);
+        u.interpolate(|x| x[0] * x[1] + x[0] * x[0]);
+        let mut v = Function::new(&vector_space);
+        v.interpolate_vector(|x, value| value.copy_from_slice(&[1.0 + x[0], x[0] * x[1]]));
+        let assembler = space.assembler(QuadratureRule::simplex(2, 4));
+        let matrix = stiffness_matrix(&assembler, |_| 1.0);
+        let load = vec![1.0; space.n_dofs()];
+        let scalar: [Box<dyn QuantityOfInterest>; 5] = [
+            Box::new(DomainIntegral::new("square", |x, u| x[0] * u[0] * u[0])),
+            Box::new(BoundaryFlux::diffusive("flux", 2, 3.0)),
+            Box::new(PointValue::new("point", &[0.4, 0.7], 0)),
+            Box::new(PointValue::new("outside", &[0.4, 1.7], 0)),
+            Box::new(Reaction::new("reaction", 4, 0, &matrix, &load)),
+        ];
+        let vector: [Box<dyn QuantityOfInterest>; 3] = [
+            Box::new(DomainIntegral::new("kinetic", |_, v| {
+                v[0] * v[0] + v[1] * v[1]
+            })),
+            Box::new(BoundaryFlux::normal("outflow", 2)),
+            Box::new(PointValue::new("v_y", &[0.4, 0.7], 1)),
+        ];
+        for (quantity, solution) in scalar
+            .iter()
+            .map(|q| (q, &u))
+            .chain(vector.iter().map(|q| (q, &v)))
+        {
+            let exact = quantity.derivative(solution);
+            let differenced = Differenced(quantity.as_ref()).derivative(solution);
+            assert_eq!(
+                exact.len(),
+                solution.values().len(),
+                "Wrong derivative size"
+            );
+            let error = exact
+                .iter()
+                .zip(&differenced)
+                .map(|(e, d)| (e - d).abs())
+                .fold(0.0, f64::max);
+            assert!(
+                error < 1e-6,
+                "Wrong derivative of {}: {}",
+                quantity.name(),
+                error
+            );
+        }
+        // Outflow ∫ v.n of v = (1 + x, x y) through the side x = 1
+        let outflow = vector[1].evaluate(&v);
+        assert!((outflow - 2.0).abs() < 1e-12, "Wrong outflow {}", outflow);
+        assert!(
+            scalar[3].derivative(&u).iter().all(|d| *d == 0.0),
+            "Points outside should not depend on the solution"
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_quantity_log_save() {
+        let mesh = unit_square_grid(2);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let mut u = Function::new(&space);
+        let mut log = QuantityLog::default()
+            .with(Functional::new("min", |u| {
+                u.values().iter().cloned().fold(f64::MAX, f64::min)
+            }))
+            .with(Functional::new("max", |u| {
+                u.values().iter().cloned().fold(f64::MIN, f64::max)
+            }));
+        assert_eq!(log.names(), ["min", "max"], "Wrong names");
+        for step in 0..3 {
+            u.interpolate(|x| step as f64 + x[0]);
+            log.record(0.5 * step as f64, &u);
+        }
+        assert_eq!(
+            log.rows(),
+            &[
+                vec![0.0, 0.0, 1.0],
+                vec![0.5, 1.0, 2.0],
+                vec![1.0, 2.0, 3.0]
+            ],
+            "Wrong rows"
+        );
+        assert_eq!(log.column("max"), [1.0, 2.0, 3.0], "Wrong column");
+        let directory = std::env::temp_dir().join("fe2o3_test_quantity_log_save");
+        std::fs::create_dir_all(&directory).unwrap();
+        log.save(directory.join("quantities.csv")).unwrap();
+        log.save(directory.join("quantities.json")).unwrap();
+        let csv = std::fs::read_to_string(directory.join("quantities.csv")).unwrap();
+        let json = std::fs::read_to_string(directory.join("quantities.json")).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(csv, log.to_csv(), "CSV files should hold the CSV table");
+        assert_eq!(
+            csv.lines().last(),
+            Some("1,2,3"),
+            "Wrong last CSV row {}",
+            csv
+        );
+        assert_eq!(json, log.to_json(), "JSON files should hold the JSON array");
+        assert!(
+            json.contains("  {\"time\": 0.5, \"min\": 1, \"max\": 2},\n"),
+            "Wrong JSON {}",
+            json
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_quantities_invalid() {
+        let mesh = unit_square_grid(2);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let quadratic = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        let matrix = stiffness_matrix(&space.assembler(QuadratureRule::simplex(2, 2)), |_| 1.0);
+        let load = vec![0.0; space.n_dofs()];
+        let u = Function::new(&space);
+        let log = || QuantityLog::new().with(Functional::new("zero", |_| 0.0));
+        let cases: [Invalid; 4] = [
+            (
+                "a load of another size",
+                Box::new(|| drop(Reaction::new("reaction", 1, 0, &matrix, &load[1..]))),
+            ),
+            (
+                "a reaction of a solution of another space",
+                Box::new(|| {
+                    Reaction::new("reaction", 1, 0, &matrix, &load)
+                        .evaluate(&Function::new(&quadratic));
+                }),
+            ),
+            (
+                "a quantity added after recording",
+                Box::new(|| {
+                    let mut log = log();
+                    log.record(0.0, &u);
+                    drop(log.with(Functional::new("one", |_| 1.0)));
+                }),
+            ),
+            (
+                "the column of an unknown quantity",
+                Box::new(|| drop(log().column("one"))),
+            ),
+        ];
+        for (case, build) in cases {
+            assert!(
+                panic::catch_unwind(AssertUnwindSafe(build)).is_err(),
+                "Quantities accepted {}",
+                case
+            );
+        }
+    }
+}
@@ -0,0 +1,631 @@
+use super::uq::Generator;
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::logging::{info, Span};
+use crate::discretizations::cartesian_grid::CartesianGrid;
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+use crate::discretizations::operators::{lumped_mass_matrix, Lumping};
+use crate::solvers::dense::SymmetricEigen;
+use crate::spaces::quadrature::QuadratureRule;
+
+//--------------------------------------------------------------------------------------------------
+// # Enums
+//--------------------------------------------------------------------------------------------------
+
+/// Stationary isotropic covariance kernel C(r) of the distance r between two points
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum CovarianceKernel {
+    /// σ² exp(-r / ℓ), whose samples are continuous but rough
+    Exponential {
+        /// Pointwise variance σ²
+        variance: f64,
+        /// Correlation length ℓ
+        length: f64,
+    },
+    /// σ² exp(-r² / 2ℓ²), whose samples are smooth
+    SquaredExponential {
+        /// Pointwise variance σ²
+        variance: f64,
+        /// Correlation length ℓ
+        length: f64,
+    },
+    /// Matérn kernel of smoothness 3/2, σ² (1 + √3 r / ℓ) exp(-√3 r / ℓ)
+    Matern32 {
+        /// Pointwise variance σ²
+        variance: f64,
+        /// Correlation length ℓ
+        length: f64,
+    },
+    /// Matérn kernel of smoothness 5/2, σ² (1 + √5 r / ℓ + 5 r² / 3ℓ²) exp(-√5 r / ℓ)
+    Matern52 {
+        /// Pointwise variance σ²
+        variance: f64,
+        /// Correlation length ℓ
+        length: f64,
+    },
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Gaussian random field of a mean and a covariance kernel, or its exponential for log-normal
+/// coefficients that stay positive
+///
+/// Fields are sampled by a truncated Karhunen-Loève expansion on a function space of any mesh or
+/// exactly by circulant embedding on the vertices of a Cartesian grid. Sample k only depends on
+/// the seed and on k as in MonteCarlo. By default the mean is 0, the field is Gaussian and the
+/// seed is 0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GaussianRandomField {
+    kernel: CovarianceKernel,
+    mean: f64,
+    log_normal: bool,
+    seed: u64,
+}
+
+/// Truncated Karhunen-Loève expansion g = Σ √λ_k ξ_k φ_k of a random field on a continuous scalar
+/// space with independent standard normal ξ_k
+///
+/// The covariance operator is discretized at the nodes with the lumped mass D, giving the
+/// symmetric eigenproblem D^½ C D^½ ψ = λ ψ with φ = D^-½ ψ orthonormal in the lumped L² product,
+/// solved densely so that the space should have at most a few thousand dofs. The modes with the
+/// largest eigenvalues are kept.
+pub struct KarhunenLoeve<'a> {
+    field: GaussianRandomField,
+    space: &'a FunctionSpace<'a>,
+    eigenvalues: Vec<f64>,
+    modes: Vec<Vec<f64>>,
+    total_variance: f64,
+}
+
+/// Exact sampling of a random field on the vertices of a Cartesian grid by circulant embedding
+///
+/// The covariance of the vertices is embedded in a periodic one on a grid of twice the size,
+/// rounded up to powers of two, whose eigenvalues come from a fast Fourier transform. The
+/// embedding is doubled until it is positive semidefinite, up to 8 times the grid, after which
+/// negative eigenvalues are dropped and the samples are only approximate. Every transform of
+/// complex normal numbers gives two independent samples, its real and imaginary parts. Samples
+/// are interpolated multilinearly onto the nodes of function spaces on unstructured meshes
+/// covered by the grid.
+pub struct CirculantEmbedding {
+    field: GaussianRandomField,
+    lower: Vec<f64>,
+    spacing: Vec<f64>,
+    n_points: Vec<usize>,
+    sizes: Vec<usize>,
+    roots: Vec<f64>,
+    exact: bool,
+}
+
+impl CovarianceKernel {
+    /// Covariance of two points at a distance
+    pub fn covariance(&self, distance: f64) -> f64 {
+        match *self {
+            CovarianceKernel::Exponential { variance, length } => {
+                variance * (-distance / length).exp()
+            }
+            CovarianceKernel::SquaredExponential { variance, length } => {
+                variance * (-0.5 * (distance / length).powi(2)).exp()
+            }
+            CovarianceKernel::Matern32 { variance, length } => {
+                let r = 3.0f64.sqrt() * distance / length;
+                variance * (1.0 + r) * (-r).exp()
+            }
+            CovarianceKernel::Matern52 { variance, length } => {
+                let r = 5.0f64.sqrt() * distance / length;
+                variance * (1.0 + r + r * r / 3.0) * (-r).exp()
+            }
+        }
+    }
+
+    /// Pointwise variance σ²
+    pub fn variance(&self) -> f64 {
+        self.covariance(0.0)
+    }
+}
+
+impl GaussianRandomField {
+    /// Centered Gaussian field of a covariance kernel
+    pub fn new(kernel: CovarianceKernel) -> Self {
+        let (CovarianceKernel::Exponential { variance, length }
+        | CovarianceKernel::SquaredExponential { variance, length }
+        | CovarianceKernel::Matern32 { variance, length }
+        | CovarianceKernel::Matern52 { variance, length }) = kernel;
+        assert!(
+            variance > 0.0 && length > 0.0,
+            "Variance and correlation length should be positive"
+        );
+        GaussianRandomField {
+            kernel,
+            mean: 0.0,
+            log_normal: false,
+            seed: 0,
+        }
+    }
+
+    /// Set the mean of the field, the one of its logarithm for a log-normal field
+    pub fn with_mean(mut self, mean: f64) -> Self {
+        self.mean = mean;
+        self
+    }
+
+    /// Sample the exponential exp(mean + g) of the Gaussian field
+    pub fn with_log_normal(mut self) -> Self {
+        self.log_normal = true;
+        self
+    }
+
+    /// Set the seed of the random numbers
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Covariance kernel of the Gaussian field
+    pub fn kernel(&self) -> CovarianceKernel {
+        self.kernel
+    }
+
+    /// Karhunen-Loève expansion of the field on a continuous scalar space truncated to a number of
+    /// modes
+    pub fn karhunen_loeve<'a>(
+        &self,
+        space: &'a FunctionSpace<'a>,
+        n_modes: usize,
+    ) -> KarhunenLoeve<'a> {
+        let _span = Span::enter("karhunen-loeve expansion");
+        assert!(
+            space.n_components() == 1 && !space.is_discontinuous(),
+            "Karhunen-Loève expansions need a continuous scalar space"
+        );
+        let n = space.n_dofs();
+        assert!(
+            n_modes > 0 && n_modes <= n,
+            "Number of modes should lie between 1 and the number of dofs"
+        );
+        let element = space.element();
+        let assembler =
+            space.assembler(QuadratureRule::simplex(element.dim(), 2 * element.order()));
+        let mass = lumped_mass_matrix(&assembler, Lumping::Hrz);
+        let roots: Vec<f64> = mass.values().iter().map(|m| m.sqrt()).collect();
+        let coordinates = space.dof_coordinates();
+        let dim = space.mesh().geometric_dim();
+        let point = |i: usize| &coordinates[i * dim..(i + 1) * dim];
+        let mut matrix = vec![0.0; n * n];
+        for i in 0..n {
+            for j in 0..=i {
+                let distance = distance(point(i), point(j));
+                let value = roots[i] * self.kernel.covariance(distance) * roots[j];
+                matrix[i * n + j] = value;
+                matrix[j * n + i] = value;
+            }
+        }
+        let eigen = SymmetricEigen::new(&DataHold::new(matrix, [n, n]));
+        let eigenvalues: Vec<f64> = eigen.values()[..n_modes]
+            .iter()
+            .map(|l| l.max(0.0))
+            .collect();
+        let modes: Vec<Vec<f64>> = (0..n_modes)
+            .map(|k| {
+                eigen
+                    .vector(k)
+                    .iter()
+                    .zip(&roots)
+                    .map(|(v, r)| v / r)
+                    .collect()
+            })
+            .collect();
+        let total_variance = self.kernel.variance() * mass.values().iter().sum::<f64>();
+        let expansion = KarhunenLoeve {
+            field: *self,
+            space,
+            eigenvalues,
+            modes,
+            total_variance,
+        };
+        info!(
+            "Karhunen-Loève expansion of {} modes capturing {:.1}% of the variance",
+            n_modes,
+            100.0 * expansion.captured_variance()
+        );
+        expansion
+    }
+
+    /// Circulant embedding of the field on the vertices of a grid
+    pub fn circulant_embedding(&self, grid: &CartesianGrid) -> CirculantEmbedding {
+        let _span = Span::enter("circulant embedding");
+        let dim = grid.dim();
+        let n_points: Vec<usize> = grid.cells_per_dim().iter().map(|n| n + 1).collect();
+        let spacing: Vec<f64> = (0..dim).map(|k| grid.cell_size(k)).collect();
+        let mut sizes: Vec<usize> = n_points
+            .iter()
+            .map(|n| (2 * (n - 1)).next_power_of_two())
+            .collect();
+        let mut doublings = 0;
+        loop {
+            let total: usize = sizes.iter().product();
+            // First row of the periodic covariance at the wrapped distances
+            let mut re: Vec<f64> = (0..total)
+                .map(|index| {
+                    let squared: f64 = multi_index(index, &sizes)
+                        .iter()
+                        .zip(&sizes)
+                        .zip(&spacing)
+                        .map(|((j, m), h)| (h * (*j).min(m - j) as f64).powi(2))
+                        .sum();
+                    self.kernel.covariance(squared.sqrt())
+                })
+                .collect();
+            let mut im = vec![0.0; total];
+            fft(&mut re, &mut im, &sizes);
+            let largest = re.iter().fold(0.0, |a: f64, l| a.max(*l));
+            let exact = re.iter().all(|l| *l >= -1e-10 * largest);
+            if exact || doublings == 2 {
+                info!(
+                    "Circulant embedding of {} vertices on {} points{}",
+                    n_points.iter().product::<usize>(),
+                    total,
+                    if exact {
+                        ""
+                    } else {
+                        ", dropping negative eigenvalues"
+                    }
+                );
+                return CirculantEmbedding {
+                    field: *self,
+                    lower: grid.lower().to_vec(),
+                    spacing,
+                    n_points,
+                    sizes,
+                    roots: re
+                        .iter()
+                        .map(|l| (l.max(0.0) / total as f64).sqrt())
+                        .collect(),
+                    exact,
+                };
+            }
+            sizes.iter_mut().for_each(|m| *m *= 2);
+            doublings += 1;
+        }
+    }
+
+    // Value of the field from a value of the Gaussian fluctuation
+    fn transform(&self, fluctuation: f64) -> f64 {
+        if self.log_normal {
+            (self.mean + fluctuation).exp()
+        } else {
+            self.mean + fluctuation
+        }
+    }
+}
+
+impl<'a> KarhunenLoeve<'a> {
+    /// Number of modes of the expansion
+    pub fn n_modes(&self) -> usize {
+        self.eigenvalues.len()
+    }
+
+    /// Eigenvalues λ_k of the modes sorted decreasingly
+    pub fn eigenvalues(&self) -> &[f64] {
+        &self.eigenvalues
+    }
+
+    /// Mode φ_k of the expansion
+    pub fn mode(&self, k: usize) -> Function<'a> {
+        Function::from_values(self.space, self.modes[k].clone())
+    }
+
+    /// Fraction Σ λ_k / ∫ σ² of the variance of the field captured by the modes
+    pub fn captured_variance(&self) -> f64 {
+        self.eigenvalues.iter().sum::<f64>() / self.total_variance
+    }
+
+    /// Sample of the field
+    pub fn sample(&self, k: usize) -> Function<'a> {
+        let mut generator = Generator::new(self.field.seed, k as u64);
+        let xi: Vec<f64> = (0..self.n_modes()).map(|_| generator.normal()).collect();
+        self.sample_with(&xi)
+    }
+
+    /// Field of given standard normal coordinates ξ_k of the modes, for instance random parameters
+    /// of a MonteCarlo study
+    pub fn sample_with(&self, xi: &[f64]) -> Function<'a> {
+        assert_eq!(
+            xi.len(),
+            self.n_modes(),
+            "Needs one coordinate per mode of the expansion"
+        );
+        let mut values = vec![0.0; self.space.n_dofs()];
+        for ((lambda, mode), x) in self.eigenvalues.iter().zip(&self.modes).zip(xi) {
+            let scale = lambda.sqrt() * x;
+            values
+                .iter_mut()
+                .zip(mode)
+                .for_each(|(v, m)| *v += scale * m);
+        }
+        values
+            .iter_mut()
+            .for_each(|v| *v = self.field.transform(*v));
+        Function::from_values(self.space, values)
+    }
+}
+
+impl CirculantEmbedding {
+    /// Number of vertices of the grid along every direction
+    pub fn n_points(&self) -> &[usize] {
+        &self.n_points
+    }
+
+    /// Whether the embedding is positive semidefinite, making the samples exact
+    pub fn is_exact(&self) -> bool {
+        self.exact
+    }
+
+    /// Sample of the field at the vertices of the grid, numbered lexicographically with the first
+    /// direction running fastest
+    pub fn sample_grid(&self, k: usize) -> Vec<f64> {
+        let total = self.roots.len();
+        let mut generator = Generator::new(self.field.seed, (k / 2) as u64);
+        let mut re = vec![0.0; total];
+        let mut im = vec![0.0; total];
+        for ((r, i), root) in re.iter_mut().zip(im.iter_mut()).zip(&self.roots) {
+            *r = root * generator.normal();
+            *i = root * generator.normal();
+        }
+        fft(&mut re, &mut im, &self.sizes);
+        let part = if k & 1 == 0 { re } else { im };
+        (0..self.n_points.iter().product())
+            .map(|vertex| {
+                let index = multi_index(vertex, &self.n_points);
+                let flat = index
+                    .iter()
+                    .zip(&self.sizes)
+                    .rev()
+                    .fold(0, |flat, (j, m)| flat * m + j);
+                self.field.transform(part[flat])
+            })
+            .collect()
+    }
+
+    /// Sample of the field interpolated multilinearly at the nodes of a scalar space, whose mesh
+    /// should lie in the grid
+    pub fn sample<'a>(&self, k: usize, space: &'a FunctionSpace<'a>) -> Function<'a> {
+        assert!(
+            space.n_components() == 1,
+            "Random fields are sampled on scalar spaces"
+        );
+        let dim = self.n_points.len();
+        assert_eq!(
+            space.mesh().geometric_dim(),
+            dim,
+            "Space does not match the dimension of the grid"
+        );
+        let grid = self.sample_grid(k);
+        let coordinates = space.dof_coordinates();
+        let values = coordinates
+            .chunks(dim)
+            .map(|point| {
+                // Cell of the grid holding the point and local coordinates in it
+                let mut cell = vec![0; dim];
+                let mut local = vec![0.0; dim];
+                for k in 0..dim {
+                    let position = (point[k] - self.lower[k]) / self.spacing[k];
+                    let last = self.n_points[k] - 2;
+                    cell[k] = (position.floor().max(0.0) as usize).min(last);
+                    local[k] = (position - cell[k] as f64).clamp(0.0, 1.0);
+                }
+                (0..1usize << dim)
+                    .map(|corner| {
+                        let mut weight = 1.0;
+                        let mut flat = 0;
+                        for k in (0..dim).rev() {
+                            let upper = (corner >> k) & 1;
+                            weight *= if upper == 1 { local[k] } else { 1.0 - local[k] };
+                            flat = flat * self.n_points[k] + cell[k] + upper;
+                        }
+                        weight * grid[flat]
+                    })
+                    .sum()
+            })
+            .collect();
+        Function::from_values(space, values)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Euclidean distance of two points
+fn distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+// Position of a lexicographic index along every direction, the first one running fastest
+fn multi_index(index: usize, sizes: &[usize]) -> Vec<usize> {
+    let mut rest = index;
+    sizes
+        .iter()
+        .map(|n| {
+            let j = rest % n;
+            rest /= n;
+            j
+        })
+        .collect()
+}
+
+// Unnormalized discrete Fourier transform along every direction of a lexicographic array of
+// complex numbers, whose sizes are powers of two
+fn fft(re: &mut [f64], im: &mut [f64], sizes: &[usize]) {
+    let mut stride = 1;
+    for n in sizes {
+        let (mut line_re, mut line_im) = (vec![0.0; *n], vec![0.0; *n]);
+        // Lines start at the indices whose position along the direction is 0
+        let starts = (0..re.len() / (n * stride))
+            .flat_map(|outer| (0..stride).map(move |inner| outer * n * stride + inner));
+        for start in starts {
+            for j in 0..*n {
+                line_re[j] = re[start + j * stride];
+                line_im[j] = im[start + j * stride];
+            }
+            fft_line(&mut line_re, &mut line_im);
+            for j in 0..*n {
+                re[start + j * stride] = line_re[j];
+                im[start + j * stride] = line_im[j];
+            }
+        }
+        stride *= n;
+    }
+}
+
+// Iterative radix-2 transform of a line in place
+fn fft_line(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut length = 2;
+    while length <= n {
+        let angle = -2.0 * std::f64::consts::PI / length as f64;
+        for start in (0..n).step_by(length) {
+            for k in 0..length / 2 {
+                let (c, s) = ((angle * k as f64).cos(), (angle * k as f64).sin());
+                let (a, b) = (start + k, start + k + length / 2);
+                let (xr, xi) = (re[b] * c - im[b] * s, re[b] * s + im[b] * c);
+                re[b] = re[a] - xr;
+                im[b] = im[a] - xi;
+                re[a] += xr;
+                im[a] += xi;
+            }
+        }
+        length <<= 1;
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spaces::lagrange::LagrangeElement;
+
+    #[test]
+    fn test_karhunen_loeve() {
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![6, 6]).simplex_mesh();
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let kernel = CovarianceKernel::Exponential {
+            variance: 2.0,
+            length: 0.3,
+        };
+        let field = GaussianRandomField::new(kernel).with_seed(3);
+        // All the modes capture the whole variance and recover the covariance at the nodes
+        let full = field.karhunen_loeve(&space, space.n_dofs());
+        assert!(
+            (full.captured_variance() - 1.0).abs() < 1e-10,
+            "Wrong captured variance {}",
+            full.captured_variance()
+        );
+        let node = 24;
+        let variance: f64 = (0..full.n_modes())
+            .map(|k| full.eigenvalues()[k] * full.mode(k).values()[node].powi(2))
+            .sum();
+        assert!(
+            (variance - 2.0).abs() < 1e-8,
+            "Wrong pointwise variance {}",
+            variance
+        );
+        let truncated = field.karhunen_loeve(&space, 10);
+        assert!(
+            truncated.captured_variance() < 1.0
+                && truncated.eigenvalues().windows(2).all(|l| l[0] >= l[1]),
+            "Truncated modes should be the largest ones"
+        );
+        // Sample variance at a node
+        let n_samples = 2000;
+        let samples: Vec<f64> = (0..n_samples)
+            .map(|k| full.sample(k).values()[node])
+            .collect();
+        let mean = samples.iter().sum::<f64>() / n_samples as f64;
+        let sample_variance =
+            samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n_samples - 1) as f64;
+        assert!(
+            mean.abs() < 0.15 && (sample_variance - 2.0).abs() < 0.2,
+            "Wrong sample statistics {} and {}",
+            mean,
+            sample_variance
+        );
+        let positive = field.with_log_normal().karhunen_loeve(&space, 5).sample(0);
+        assert!(
+            positive.values().iter().all(|v| *v > 0.0),
+            "Log-normal samples should be positive"
+        );
+    }
+
+    #[test]
+    fn test_circulant_embedding() {
+        // The sample covariance of vertices 4 cells apart matches the kernel
+        let grid = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![16, 16]);
+        let kernel = CovarianceKernel::Exponential {
+            variance: 1.0,
+            length: 0.3,
+        };
+        let embedding = GaussianRandomField::new(kernel)
+            .with_mean(1.0)
+            .circulant_embedding(&grid);
+        assert!(embedding.is_exact(), "Embedding should be exact");
+        let n_samples = 2000;
+        let (a, b) = (17 * 8 + 4, 17 * 8 + 8);
+        let (mut mean, mut covariance, mut variance) = (0.0, 0.0, 0.0);
+        for k in 0..n_samples {
+            let sample = embedding.sample_grid(k);
+            let (x, y) = (sample[a] - 1.0, sample[b] - 1.0);
+            mean += x / n_samples as f64;
+            covariance += x * y / n_samples as f64;
+            variance += x * x / n_samples as f64;
+        }
+        assert!(
+            mean.abs() < 0.1 && (variance - 1.0).abs() < 0.1,
+            "Wrong sample statistics {} and {}",
+            mean,
+            variance
+        );
+        let expected = kernel.covariance(0.25);
+        assert!(
+            (covariance - expected).abs() < 0.1,
+            "Wrong covariance {} instead of {}",
+            covariance,
+            expected
+        );
+        // Interpolation onto a space is exact at the vertices of the grid
+        let mesh = grid.simplex_mesh();
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let sample = embedding.sample(5, &space);
+        let value = sample.eval(&[0.25, 0.5]).unwrap()[0];
+        assert!(
+            (value - embedding.sample_grid(5)[a]).abs() < 1e-12,
+            "Wrong interpolated value"
+        );
+    }
+}
@@ -0,0 +1,618 @@
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::core::logging::{info, Span};
+use crate::solvers::dense::LU;
+use crate::solvers::eigen::symmetric_eigen;
+use crate::solvers::krylov::dot;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Solution snapshots of transient or parametric runs of a full order model
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshots {
+    size: usize,
+    snapshots: Vec<Vec<f64>>,
+}
+
+/// Proper orthogonal decomposition of snapshots by the method of snapshots
+///
+/// The eigenvalues λ of the correlation matrix of the snapshots in an inner product (euclidean or
+/// given by a mass matrix) measure the energy of the modes. The basis keeps the leading modes
+/// until they capture the energy fraction asked for, at most the maximum number of modes. By
+/// default the energy fraction is 1 - 1e-10 and the number of modes is not limited.
+pub struct Pod<'a> {
+    energy: f64,
+    max_modes: Option<usize>,
+    inner_product: Option<&'a SparseCSR<f64>>,
+}
+
+/// Orthonormal basis of the leading modes of snapshots with the energies of all the modes
+#[derive(Clone, Debug, PartialEq)]
+pub struct PodBasis {
+    modes: Vec<Vec<f64>>,
+    // Modes multiplied by the matrix of the inner product
+    duals: Vec<Vec<f64>>,
+    eigenvalues: Vec<f64>,
+}
+
+/// Galerkin projection onto a POD basis of a full order system with an affine dependence on the
+/// parameters, A(μ) = Σ θ_q(μ) A_q and b(μ) = Σ φ_q(μ) b_q
+///
+/// The terms are projected once so that solving for new coefficients only combines and solves
+/// square systems of the size of the basis. The full order matrices should be the ones the
+/// snapshots solve, for instance condensed by homogeneous constraints.
+pub struct ReducedModel {
+    basis: PodBasis,
+    matrices: Vec<Vec<f64>>,
+    vectors: Vec<Vec<f64>>,
+}
+
+impl Snapshots {
+    /// Empty collection of snapshots of a given size
+    pub fn new(size: usize) -> Self {
+        Snapshots {
+            size,
+            snapshots: Vec::new(),
+        }
+    }
+
+    /// Add a snapshot
+    pub fn push(&mut self, snapshot: &[f64]) {
+        assert!(
+            snapshot.len() == self.size,
+            "Snapshot does not match the size of the collection"
+        );
+        self.snapshots.push(snapshot.to_vec());
+    }
+
+    /// Size of the snapshots
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of snapshots
+    pub fn n_snapshots(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Snapshot of an index
+    pub fn snapshot(&self, index: usize) -> &[f64] {
+        &self.snapshots[index]
+    }
+}
+
+impl<'a> Default for Pod<'a> {
+    fn default() -> Self {
+        Pod {
+            energy: 1.0 - 1e-10,
+            max_modes: None,
+            inner_product: None,
+        }
+    }
+}
+
+impl<'a> Pod<'a> {
+    /// Decomposition with the default parameters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the fraction of the energy of the snapshots the modes should capture
+    pub fn with_energy(mut self, energy: f64) -> Self {
+        assert!(
+            energy > 0.0 && energy <= 1.0,
+            "Energy fractions lie in (0, 1]"
+        );
+        self.energy = energy;
+        self
+    }
+
+    /// Set the maximum number of modes
+    pub fn with_max_modes(mut self, max_modes: usize) -> Self {
+        assert!(max_modes > 0, "Bases need at least one mode");
+        self.max_modes = Some(max_modes);
+        self
+    }
+
+    /// Set the symmetric positive definite matrix of the inner product, such as a mass matrix
+    pub fn with_inner_product(mut self, matrix: &'a SparseCSR<f64>) -> Self {
+        self.inner_product = Some(matrix);
+        self
+    }
+
+    /// Basis of the leading modes of snapshots
+    pub fn compute(&self, snapshots: &Snapshots) -> PodBasis {
+        let _span = Span::enter("proper orthogonal decomposition");
+        let n = snapshots.n_snapshots();
+        assert!(n > 0, "Decompositions need snapshots");
+        let weight = |v: &[f64]| match self.inner_product {
+            Some(matrix) => {
+                let mut weighted = vec![0.0; v.len()];
+                matrix.apply(v, &mut weighted);
+                weighted
+            }
+            None => v.to_vec(),
+        };
+        let weighted: Vec<Vec<f64>> = snapshots.snapshots.iter().map(|s| weight(s)).collect();
+        let mut correlation = vec![0.0; n * n];
+        for i in 0..n {
+            for j in 0..=i {
+                let value = dot(&snapshots.snapshots[i], &weighted[j]);
+                correlation[i * n + j] = value;
+                correlation[j * n + i] = value;
+            }
+        }
+        let (values, vectors) = symmetric_eigen(n, correlation);
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|a, b| values[*b].total_cmp(&values[*a]));
+        let eigenvalues: Vec<f64> = order.iter().map(|k| values[*k].max(0.0)).collect();
+        let total: f64 = eigenvalues.iter().sum();
+        // Leading modes capturing the energy, skipping the ones lost in round off
+        let mut n_modes = 0;
+        let mut captured = 0.0;
+        while n_modes < n
+            && captured < self.energy * total
+            && eigenvalues[n_modes] > 1e-12 * eigenvalues[0]
+            && self.max_modes.is_none_or(|max| n_modes < max)
+        {
+            captured += eigenvalues[n_modes];
+            n_modes += 1;
+        }
+        let mut modes: Vec<Vec<f64>> = Vec::with_capacity(n_modes);
+        let mut duals: Vec<Vec<f64>> = Vec::with_capacity(n_modes);
+        for k in &order[..n_modes] {
+            let mut mode = vec![0.0; snapshots.size];
+            for (i, snapshot) in snapshots.snapshots.iter().enumerate() {
+                let c = vectors[i * n + k];
+                mode.iter_mut().zip(snapshot).for_each(|(m, s)| *m += c * s);
+            }
+            // Orthonormalize again against the previous modes to undo round off
+            for (previous, dual) in modes.iter().zip(&duals) {
+                let c = dot(&mode, dual);
+                mode.iter_mut().zip(previous).for_each(|(m, p)| *m -= c * p);
+            }
+            let dual = weight(&mode);
+            let length = dot(&mode, &dual).sqrt();
+            modes.push(mode.iter().map(|m| m / length).collect());
+            duals.push(dual.iter().map(|d| d / length).collect());
+        }
+        info!(
+            "Proper orthogonal decomposition kept {} of {} modes",
+            n_modes, n
+        );
+        PodBasis {
+            modes,
+            duals,
+            eigenvalues,
+        }
+    }
+}
+
+impl PodBasis {
+    /// Number of modes of the basis
+    pub fn n_modes(&self) -> usize {
+        self.modes.len()
+    }
+
+    /// Size of the modes
+    pub fn size(&self) -> usize {
+        self.modes.first().map_or(0, |m| m.len())
+    }
+
+    /// Mode of an index, the most energetic first
+    pub fn mode(&self, index: usize) -> &[f64] {
+        &self.modes[index]
+    }
+
+    /// Energies of all the modes of the snapshots in decreasing order
+    pub fn eigenvalues(&self) -> &[f64] {
+        &self.eigenvalues
+    }
+
+    /// Fraction of the energy of the snapshots captured by the basis
+    pub fn captured_energy(&self) -> f64 {
+        let total: f64 = self.eigenvalues.iter().sum();
+        self.eigenvalues[..self.n_modes()].iter().sum::<f64>() / total
+    }
+
+    /// Coordinates in the basis of the projection of a full vector
+    pub fn project(&self, vector: &[f64]) -> Vec<f64> {
+        assert!(
+            vector.len() == self.size(),
+            "Vector does not match the basis"
+        );
+        self.duals.iter().map(|dual| dot(dual, vector)).collect()
+    }
+
+    /// Full vector of coordinates in the basis
+    pub fn reconstruct(&self, coordinates: &[f64]) -> Vec<f64> {
+        assert!(
+            coordinates.len() == self.n_modes(),
+            "Coordinates do not match the basis"
+        );
+        let mut vector = vec![0.0; self.size()];
+        for (mode, c) in self.modes.iter().zip(coordinates) {
+            vector.iter_mut().zip(mode).for_each(|(v, m)| *v += c * m);
+        }
+        vector
+    }
+}
+
+impl ReducedModel {
+    /// Model without terms on a basis
+    pub fn new(basis: PodBasis) -> Self {
+        ReducedModel {
+            basis,
+            matrices: Vec::new(),
+            vectors: Vec::new(),
+        }
+    }
+
+    /// Add a term of the matrix
+    pub fn with_matrix(mut self, matrix: &SparseCSR<f64>) -> Self {
+        let r = self.basis.n_modes();
+        let mut applied = vec![0.0; self.basis.size()];
+        let mut reduced = vec![0.0; r * r];
+        for j in 0..r {
+            matrix.apply(&self.basis.modes[j], &mut applied);
+            for i in 0..r {
+                reduced[i * r + j] = dot(&self.basis.modes[i], &applied);
+            }
+        }
+        self.matrices.push(reduced);
+        self
+    }
+
+    /// Add a term of the right hand side
+    pub fn with_vector(mut self, vector: &[f64]) -> Self {
+        let reduced = self
+            .basis
+            .modes
+            .iter()
+            .map(|mode| dot(mode, vector))
+            .collect();
+        self.vectors.push(reduced);
+        self
+    }
+
+    /// Basis of the model
+    pub fn basis(&self) -> &PodBasis {
+        &self.basis
+    }
+
+    /// Reduced matrix and right hand side for coefficients of the matrix and vector terms
+    pub fn reduced_system(
+        &self,
+        matrix_coefficients: &[f64],
+        vector_coefficients: &[f64],
+    ) -> (DataHold<f64, [usize; 2]>, Vec<f64>) {
+        assert!(
+            matrix_coefficients.len() == self.matrices.len()
+                && vector_coefficients.len() == self.vectors.len(),
+            "Coefficients do not match the terms of the model"
+        );
+        let r = self.basis.n_modes();
+        let mut matrix = vec![0.0; r * r];
+        for (term, c) in self.matrices.iter().zip(matrix_coefficients) {
+            matrix.iter_mut().zip(term).for_each(|(m, t)| *m += c * t);
+        }
+        let mut rhs = vec![0.0; r];
+        for (term, c) in self.vectors.iter().zip(vector_coefficients) {
+            rhs.iter_mut().zip(term).for_each(|(b, t)| *b += c * t);
+        }
+        (DataHold::new(matrix, [r, r]), rhs)
+    }
+
+    /// Coordinates in the basis of the solution for coefficients of the terms
+    pub fn solve_reduced(
+        &self,
+        matrix_coefficients: &[f64],
+        vector_coefficients: &[f64],
+    ) -> Vec<f64> {
+        let (matrix, rhs) = self.reduced_system(matrix_coefficients, vector_coefficients);
+        let lu = LU::new(&matrix).expect("Reduced system is singular");
+        let mut coordinates = vec![0.0; rhs.len()];
+        lu.solve(&rhs, &mut coordinates);
+        coordinates
+    }
+
+    /// Full solution for coefficients of the terms
+    pub fn solve(&self, matrix_coefficients: &[f64], vector_coefficients: &[f64]) -> Vec<f64> {
+        self.basis
+            .reconstruct(&self.solve_reduced(matrix_coefficients, vector_coefficients))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::arrays::data_traits::DataContainer;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::constraints::AffineConstraints;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::discretizations::operators::mass_matrix;
+    use crate::discretizations::sparsity::SparsityPattern;
+    use crate::solvers::config::{MethodConfig, SolverConfig};
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::spaces::quadrature::QuadratureRule;
+    use std::panic::{self, AssertUnwindSafe};
+
+    // Description of an invalid input with the construction it should make panic
+    type Invalid<'a> = (&'a str, Box<dyn Fn() + 'a>);
+
+    // Diagonal matrix of the values
+    fn diagonal(values: &[f64]) -> SparseCSR<f64> {
+        let n = values.len();
+        SparseCSR::new(n, (0..=n).collect(), (0..n).collect(), values.to_vec())
+    }
+
+    // Snapshots e_1 and 3 e_2 of R^3
+    fn axis_snapshots() -> Snapshots {
+        let mut snapshots = Snapshots::new(3);
+        snapshots.push(&[1.0, 0.0, 0.0]);
+        snapshots.push(&[0.0, 3.0, 0.0]);
+        snapshots
+    }
+
+    #[test]
+    fn test_reduced_model() {
+        // Thermal block -div(k grad u) = 1 with k = μ1 on the left half and μ2 on the right half
+        // and u = 0 on the boundary
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![8, 8]).simplex_mesh();
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let mut constraints = AffineConstraints::new(space.n_dofs());
+        for tag in 1..=4 {
+            for dof in space.tagged_dofs(tag) {
+                constraints.add_dirichlet(dof, 0.0);
+            }
+        }
+        constraints.close();
+        let assembler = space.assembler(QuadratureRule::simplex(2, 2));
+        let pattern = SparsityPattern::from_dofmap_and_constraints(space.dof_map(), &constraints);
+        // Condensed terms of the halves, the constrained diagonal being split between them
+        let mut terms = Vec::new();
+        let mut load = vec![0.0; space.n_dofs()];
+        for half in 0..2 {
+            let mut matrix = pattern.to_csr(0.0);
+            let mut rhs = vec![0.0; space.n_dofs()];
+            assembler.assemble_system(&mut matrix, &mut rhs, &constraints, |v, local, b| {
+                let n = v.n_dofs();
+                let left = v.point(0)[0] < 0.5;
+                for q in 0..v.n_points() {
+                    for i in 0..n {
+                        if left == (half == 0) {
+                            for j in 0..n {
+                                let (gi, gj) = (v.shape_gradient(q, i), v.shape_gradient(q, j));
+                                local[i * n + j] += (gi[0] * gj[0] + gi[1] * gj[1]) * v.weight(q);
+                            }
+                        }
+                        b[i] += 0.5 * v.shape_value(q, i) * v.weight(q);
+                    }
+                }
+            });
+            load.iter_mut().zip(&rhs).for_each(|(l, r)| *l += r);
+            terms.push(matrix);
+        }
+        let full_solve = |mu: [f64; 2]| {
+            let matrix = terms[0].add(mu[1] / mu[0], &terms[1]);
+            let solver = SolverConfig::new(MethodConfig::SparseLU)
+                .build(&matrix)
+                .unwrap();
+            let mut solution = vec![0.0; space.n_dofs()];
+            let rhs: Vec<f64> = load.iter().map(|b| b / mu[0]).collect();
+            assert!(solver.solve(&rhs, &mut solution).converged());
+            solution
+        };
+        let mut snapshots = Snapshots::new(space.n_dofs());
+        for mu in [0.1, 0.3, 1.0, 3.0, 10.0] {
+            snapshots.push(&full_solve([1.0, mu]));
+        }
+        let mass = mass_matrix(&assembler);
+        let basis = Pod::new().with_inner_product(&mass).compute(&snapshots);
+        assert!(basis.n_modes() <= 5, "Too many modes");
+        let dual = basis.project(basis.mode(0));
+        assert!((dual[0] - 1.0).abs() < 1e-12, "Modes are not normalized");
+        assert!(
+            dual[1..].iter().all(|d| d.abs() < 1e-10),
+            "Modes are not orthogonal"
+        );
+        assert!(basis.captured_energy() > 1.0 - 1e-10, "Wrong energy");
+        let model = ReducedModel::new(basis)
+            .with_matrix(&terms[0])
+            .with_matrix(&terms[1])
+            .with_vector(&load);
+        for mu in [[0.7, 2.3], [2.0, 0.5]] {
+            let reduced = model.solve(&mu, &[1.0]);
+            let full = full_solve(mu);
+            let error = reduced
+                .iter()
+                .zip(&full)
+                .map(|(r, f)| (r - f).abs())
+                .fold(0.0, f64::max);
+            let scale = full.iter().cloned().fold(0.0, f64::max);
+            assert!(
+                error < 1e-3 * scale,
+                "Wrong reduced solution for {:?}: error {}",
+                mu,
+                error
+            );
+        }
+        let max_modes = Pod::new().with_max_modes(2).compute(&snapshots);
+        assert_eq!(max_modes.n_modes(), 2, "Wrong number of modes");
+        assert!(
+            max_modes.eigenvalues().windows(2).all(|w| w[0] >= w[1]),
+            "Energies are not sorted"
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_pod_basis() {
+        // The snapshots e_1, 3 e_2 and their sum span a plane: two modes capture all the energy,
+        // the third eigenvalue vanishing, and the weights diag(4, 1, 1) of the inner product
+        // change the energies to 9 and 4 and scale the modes to unit weighted norms
+        let mut snapshots = axis_snapshots();
+        snapshots.push(&[1.0, 3.0, 0.0]);
+        assert_eq!(snapshots.size(), 3, "Wrong snapshot size");
+        assert_eq!(snapshots.n_snapshots(), 3, "Wrong number of snapshots");
+        assert_eq!(snapshots.snapshot(2), &[1.0, 3.0, 0.0], "Wrong snapshot");
+        let basis = Pod::new().compute(&snapshots);
+        assert_eq!(basis.n_modes(), 2, "The snapshots span a plane");
+        assert_eq!(basis.size(), 3, "Wrong mode size");
+        assert_eq!(basis.eigenvalues().len(), 3, "Every snapshot has an energy");
+        assert!(
+            basis.eigenvalues()[2].abs() < 1e-10,
+            "The third energy should vanish"
+        );
+        assert!(
+            (basis.captured_energy() - 1.0).abs() < 1e-12,
+            "Wrong captured energy"
+        );
+        let reconstructed = basis.reconstruct(&basis.project(&[2.0, -1.0, 0.0]));
+        assert!(
+            reconstructed
+                .iter()
+                .zip([2.0, -1.0, 0.0])
+                .all(|(r, e)| (r - e).abs() < 1e-12),
+            "Vectors of the plane should be reproduced: {:?}",
+            reconstructed
+        );
+        let dominant = Pod::new().with_energy(0.9).compute(&snapshots);
+        assert_eq!(dominant.n_modes(), 1, "One mode holds 90 % of the energy");
+        assert!(
+            dominant.captured_energy() > 0.9 && dominant.captured_energy() < 1.0,
+            "Wrong captured energy {}",
+            dominant.captured_energy()
+        );
+        let mass = diagonal(&[4.0, 1.0, 1.0]);
+        let weighted = Pod::new()
+            .with_inner_product(&mass)
+            .compute(&axis_snapshots());
+        assert!(
+            weighted
+                .eigenvalues()
+                .iter()
+                .zip([9.0, 4.0])
+                .all(|(l, e)| (l - e).abs() < 1e-12),
+            "Wrong weighted energies {:?}",
+            weighted.eigenvalues()
+        );
+        assert!(
+            (weighted.mode(0)[1].abs() - 1.0).abs() < 1e-12
+                && (weighted.mode(1)[0].abs() - 0.5).abs() < 1e-12,
+            "Modes should have unit weighted norms"
+        );
+        let coordinates = weighted.project(&[1.0, 0.0, 0.0]);
+        assert!(
+            (coordinates[1].abs() - 2.0).abs() < 1e-12 && coordinates[0].abs() < 1e-12,
+            "Wrong weighted projection {:?}",
+            coordinates
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_reduced_system() {
+        // On the modes e_2 and e_1, 2 diag(1, 2, 3) + I reduces to diag(5, 3) and solves
+        // (1, 1, 1) by (1 / 3, 1 / 5, 0)
+        let model = ReducedModel::new(Pod::new().compute(&axis_snapshots()))
+            .with_matrix(&diagonal(&[1.0, 2.0, 3.0]))
+            .with_matrix(&diagonal(&[1.0, 1.0, 1.0]))
+            .with_vector(&[1.0, 1.0, 1.0]);
+        assert_eq!(model.basis().n_modes(), 2, "Wrong basis");
+        let (matrix, rhs) = model.reduced_system(&[2.0, 1.0], &[1.0]);
+        assert_eq!(matrix.dimensions(), &[2, 2], "Wrong reduced size");
+        assert_eq!(
+            matrix.as_ref(),
+            &[5.0, 0.0, 0.0, 3.0],
+            "Wrong reduced matrix"
+        );
+        assert!(
+            rhs.iter().all(|b| (b.abs() - 1.0).abs() < 1e-12),
+            "Wrong reduced right hand side {:?}",
+            rhs
+        );
+        let coordinates = model.solve_reduced(&[2.0, 1.0], &[1.0]);
+        assert!(
+            (coordinates[0].abs() - 0.2).abs() < 1e-12
+                && (coordinates[1].abs() - 1.0 / 3.0).abs() < 1e-12,
+            "Wrong reduced solution {:?}",
+            coordinates
+        );
+        let solution = model.solve(&[2.0, 1.0], &[1.0]);
+        assert!(
+            solution
+                .iter()
+                .zip([1.0 / 3.0, 0.2, 0.0])
+                .all(|(u, e)| (u - e).abs() < 1e-12),
+            "Wrong full solution {:?}",
+            solution
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_rom_invalid() {
+        let basis = Pod::new().compute(&axis_snapshots());
+        let model = ReducedModel::new(basis.clone())
+            .with_matrix(&diagonal(&[1.0, 1.0, 1.0]))
+            .with_vector(&[1.0, 1.0, 1.0]);
+        let cases: [Invalid; 9] = [
+            (
+                "a snapshot of another size",
+                Box::new(|| axis_snapshots().push(&[1.0, 0.0])),
+            ),
+            (
+                "a zero energy fraction",
+                Box::new(|| {
+                    Pod::new().with_energy(0.0);
+                }),
+            ),
+            (
+                "an energy fraction above 1",
+                Box::new(|| {
+                    Pod::new().with_energy(1.5);
+                }),
+            ),
+            (
+                "no mode",
+                Box::new(|| {
+                    Pod::new().with_max_modes(0);
+                }),
+            ),
+            (
+                "no snapshot",
+                Box::new(|| drop(Pod::new().compute(&Snapshots::new(3)))),
+            ),
+            (
+                "the projection of a vector of another size",
+                Box::new(|| drop(basis.project(&[1.0, 0.0]))),
+            ),
+            (
+                "coordinates of another size",
+                Box::new(|| drop(basis.reconstruct(&[1.0]))),
+            ),
+            (
+                "coefficients of missing terms",
+                Box::new(|| drop(model.reduced_system(&[1.0, 1.0], &[1.0]))),
+            ),
+            (
+                "a singular reduced system",
+                Box::new(|| drop(model.solve(&[0.0], &[1.0]))),
+            ),
+        ];
+        for (case, build) in cases {
+            assert!(
+                panic::catch_unwind(AssertUnwindSafe(build)).is_err(),
+                "Reduced order modelling accepted {}",
+                case
+            );
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use super::events::{Control, WorkflowObserver};
+use super::quantities::QuantityOfInterest;
+use crate::core::logging::info;
+use crate::discretizations::function::Function;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Workflow observer stopping transient runs once they reach a steady state
+///
+/// The change of a step is the largest relative rate of change of the monitored values,
+/// |a_n - a_n-1| / (max(|a_n|, 1e-12) (t_n - t_n-1)), over the Euclidean norm of the selected
+/// components of the solution and every quantity of interest. The steady state is reached, and
+/// the run stopped, once the changes of a window of consecutive steps are all below the tolerance.
+/// By default the tolerance is 1e-6 per unit time, the window 3 steps and all the components of
+/// the solution are monitored along with the quantities. Clones share the same detection so that
+/// one can be given to a workflow and the other queried after the run.
+#[derive(Clone)]
+pub struct SteadyStateDetector {
+    tolerance: f64,
+    window: usize,
+    components: Option<Vec<usize>>,
+    quantities: Rc<Vec<Box<dyn QuantityOfInterest>>>,
+    state: Rc<RefCell<Detection>>,
+}
+
+// Values of the last step and changes of the steps seen so far
+#[derive(Default)]
+struct Detection {
+    previous: Option<(f64, Vec<f64>)>,
+    changes: Vec<(f64, f64)>,
+    steady_time: Option<f64>,
+}
+
+impl SteadyStateDetector {
+    /// Detector with the default parameters
+    pub fn new() -> Self {
+        SteadyStateDetector {
+            tolerance: 1e-6,
+            window: 3,
+            components: None,
+            quantities: Rc::new(Vec::new()),
+            state: Rc::new(RefCell::new(Detection::default())),
+        }
+    }
+
+    /// Set the tolerance on the relative rate of change
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        assert!(tolerance > 0.0, "Tolerance should be positive");
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Set the number of consecutive steps whose changes have to be below the tolerance
+    pub fn with_window(mut self, window: usize) -> Self {
+        assert!(window > 0, "Window should hold at least one step");
+        self.window = window;
+        self
+    }
+
+    /// Monitor only some components of the solution, none to only monitor the quantities
+    pub fn with_components(mut self, components: &[usize]) -> Self {
+        self.components = Some(components.to_vec());
+        self
+    }
+
+    /// Monitor a quantity of interest of the solution
+    pub fn with_quantity<Quantity: QuantityOfInterest + 'static>(
+        mut self,
+        quantity: Quantity,
+    ) -> Self {
+        Rc::get_mut(&mut self.quantities)
+            .expect("Quantities should be added before the detector is cloned")
+            .push(Box::new(quantity));
+        self
+    }
+
+    /// Time at which the steady state was reached, if it was
+    pub fn steady_time(&self) -> Option<f64> {
+        self.state.borrow().steady_time
+    }
+
+    /// Times and changes of the steps seen so far, from the first one after the initial state
+    pub fn changes(&self) -> Vec<(f64, f64)> {
+        self.state.borrow().changes.clone()
+    }
+
+    /// Forget the steps seen, to detect the steady state of another run
+    pub fn reset(&self) {
+        *self.state.borrow_mut() = Detection::default();
+    }
+
+    // Monitored values of a solution: the norm of the selected components and the quantities
+    fn monitored(&self, solution: &Function) -> Vec<f64> {
+        let n_components = solution.space().n_components();
+        let squared: f64 = match &self.components {
+            None => solution.values().iter().map(|u| u * u).sum(),
+            Some(components) => components
+                .iter()
+                .map(|c| {
+                    assert!(*c < n_components, "Monitored component out of range");
+                    solution.component(*c).iter().map(|u| u * u).sum::<f64>()
+                })
+                .sum(),
+        };
+        let mut values = Vec::with_capacity(self.quantities.len() + 1);
+        if self.components.as_ref().is_none_or(|c| !c.is_empty()) {
+            values.push(squared.sqrt());
+        }
+        values.extend(self.quantities.iter().map(|q| q.evaluate(solution)));
+        values
+    }
+}
+
+impl Default for SteadyStateDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WorkflowObserver for SteadyStateDetector {
+    fn on_solved(&self, step: usize, time: f64, solution: &Function) -> Control {
+        let values = self.monitored(solution);
+        assert!(
+            !values.is_empty(),
+            "Steady state detection needs monitored components or quantities"
+        );
+        let mut state = self.state.borrow_mut();
+        if step == 0 {
+            *state = Detection::default();
+        }
+        let previous = state.previous.replace((time, values));
+        let Some((previous_time, previous_values)) = previous else {
+            return Control::Continue;
+        };
+        let dt = time - previous_time;
+        let (_, values) = state.previous.as_ref().unwrap();
+        let change = values
+            .iter()
+            .zip(&previous_values)
+            .map(|(a, b)| (a - b).abs() / (a.abs().max(1e-12) * dt))
+            .fold(0.0, f64::max);
+        state.changes.push((time, change));
+        let n = state.changes.len();
+        if n >= self.window
+            && state.changes[n - self.window..]
+                .iter()
+                .all(|(_, change)| *change <= self.tolerance)
+        {
+            info!(
+                "Steady state reached at time {} with change {:e}",
+                time, change
+            );
+            state.steady_time = Some(time);
+            return Control::Stop;
+        }
+        Control::Continue
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::discretizations::function_space::FunctionSpace;
+    use crate::solvers::time_integration::TimeScheme;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::workflows::advection_diffusion::{AdvectionDiffusion, Stabilization};
+    use crate::workflows::heat::HeatEquation;
+    use crate::workflows::quantities::PointValue;
+
+    #[test]
+    fn test_steady_state_detector() {
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![4, 4]).simplex_mesh();
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let steady = (1..=4)
+            .fold(AdvectionDiffusion::new(&space), |problem, tag| {
+                problem.with_dirichlet(tag, |_| 0.0)
+            })
+            .with_stabilization(Stabilization::Galerkin)
+            .with_source(|_| 1.0)
+            .solve();
+        for detector in [
+            SteadyStateDetector::new(),
+            SteadyStateDetector::new()
+                .with_components(&[])
+                .with_quantity(PointValue::new("center", &[0.5, 0.5], 0)),
+        ] {
+            let problem = (1..=4)
+                .fold(HeatEquation::new(&space), |problem, tag| {
+                    problem.with_dirichlet(tag, |_, _| 0.0)
+                })
+                .with_source(|_, _| 1.0)
+                .with_scheme(TimeScheme::ImplicitEuler)
+                .with_observer(detector.clone());
+            let mut u = Function::new(&space);
+            let n_steps = problem.solve(&mut u, 0.0, 100.0, 0.1, |_, _| {});
+            let time = detector.steady_time().expect("Steady state not detected");
+            assert!(
+                n_steps < 1000 && (time - 0.1 * n_steps as f64).abs() < 1e-9,
+                "The run should stop at the steady state"
+            );
+            let error = u
+                .values()
+                .iter()
+                .zip(steady.values())
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f64::max);
+            assert!(error < 1e-6, "Wrong steady state, error {:e}", error);
+            assert_eq!(detector.changes().len(), n_steps, "Wrong number of changes");
+        }
+    }
+}
@@ -0,0 +1,552 @@
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::core::logging::Span;
+use crate::discretizations::constraints::AffineConstraints;
+use crate::discretizations::facets::Facets;
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+#[cfg(hosted)]
+use crate::discretizations::io::vtk;
+use crate::discretizations::mixed::{MixedAssembler, MixedSpace};
+use crate::discretizations::operators::mass_matrix;
+use crate::discretizations::sparsity::SparsityPattern;
+use crate::solvers::field_split::FieldSplit;
+use crate::solvers::krylov::Gmres;
+use crate::solvers::sparse_direct::{SparseCholesky, SparseLU};
+use crate::spaces::quadrature::QuadratureRule;
+#[cfg(hosted)]
+use std::io::Result;
+use std::ops::Range;
+#[cfg(hosted)]
+use std::path::Path;
+
+// Vector field filling its components at the physical coordinates
+type VectorField<'a> = Box<dyn Fn(&[f64], &mut [f64]) + 'a>;
+
+//--------------------------------------------------------------------------------------------------
+// # Enums
+//--------------------------------------------------------------------------------------------------
+
+/// Solver of the saddle point system of the Stokes equations
+pub enum StokesSolver {
+    /// Sparse LU factorization of the whole system
+    Direct,
+    /// Field split through the pressure Schur complement, the velocity block being factorized by
+    /// the sparse Cholesky and the Schur complement solved by the GMRES solver preconditioned by
+    /// the pressure mass matrix scaled by the viscosity
+    FieldSplit(Gmres),
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Steady Stokes flow -ν Δu + grad p = f, div u = 0 on a continuous velocity space of the dimension
+/// of the mesh and a continuous scalar pressure space
+///
+/// Taylor-Hood elements (the velocity one order above the pressure) are stable. Equal order
+/// elements need the Brezzi-Pitkäranta stabilization δ h² (grad p, grad q) added to the mass
+/// conservation, h being the cell size. Velocities are imposed on tagged boundary facets and the
+/// rest of the boundary is a free outflow, ν du/dn - p n = 0. When the velocity is imposed on the
+/// whole boundary the pressure is only known up to a constant and the solution with a zero mean
+/// pressure is returned. By default the viscosity is 1, there is no body force nor stabilization
+/// and the solver is the field split with the default GMRES.
+pub struct StokesFlow<'a> {
+    velocity: &'a FunctionSpace<'a>,
+    pressure: &'a FunctionSpace<'a>,
+    viscosity: f64,
+    stabilization: f64,
+    body_force: Option<VectorField<'a>>,
+    velocities: Vec<(usize, VectorField<'a>)>,
+    solver: StokesSolver,
+}
+
+impl<'a> StokesFlow<'a> {
+    /// Stokes problem on velocity and pressure spaces of the same mesh
+    pub fn new(velocity: &'a FunctionSpace<'a>, pressure: &'a FunctionSpace<'a>) -> Self {
+        let dim = velocity.mesh().geometric_dim();
+        assert!(
+            velocity.n_components() == dim && !velocity.is_discontinuous(),
+            "Stokes flow needs a continuous velocity space of the dimension of the mesh"
+        );
+        assert!(
+            pressure.n_components() == 1
+                && !pressure.is_discontinuous()
+                && std::ptr::eq(pressure.mesh(), velocity.mesh()),
+            "Stokes flow needs a continuous scalar pressure space on the velocity mesh"
+        );
+        StokesFlow {
+            velocity,
+            pressure,
+            viscosity: 1.0,
+            stabilization: 0.0,
+            body_force: None,
+            velocities: Vec::new(),
+            solver: StokesSolver::FieldSplit(Gmres::new()),
+        }
+    }
+
+    /// Set the kinematic viscosity ν
+    pub fn with_viscosity(mut self, viscosity: f64) -> Self {
+        assert!(viscosity > 0.0, "Viscosity should be positive");
+        self.viscosity = viscosity;
+        self
+    }
+
+    /// Set the parameter δ of the pressure stabilization of equal order elements
+    pub fn with_stabilization(mut self, stabilization: f64) -> Self {
+        assert!(stabilization >= 0.0, "Stabilization should be non negative");
+        self.stabilization = stabilization;
+        self
+    }
+
+    /// Set the body force filling its components at the physical coordinates
+    pub fn with_body_force<Force>(mut self, force: Force) -> Self
+    where
+        Force: Fn(&[f64], &mut [f64]) + 'a,
+    {
+        self.body_force = Some(Box::new(force));
+        self
+    }
+
+    /// Impose the velocity filling its components at the physical coordinates on the boundary
+    /// facets carrying the tag
+    pub fn with_velocity<Velocity>(mut self, tag: usize, velocity: Velocity) -> Self
+    where
+        Velocity: Fn(&[f64], &mut [f64]) + 'a,
+    {
+        self.velocities.push((tag, Box::new(velocity)));
+        self
+    }
+
+    /// Set the solver of the saddle point system
+    pub fn with_solver(mut self, solver: StokesSolver) -> Self {
+        self.solver = solver;
+        self
+    }
+
+    /// Solve for the velocity and the pressure
+    pub fn solve(&self) -> (Function<'a>, Function<'a>) {
+        let _span = Span::enter("Stokes problem");
+        let (velocity, pressure) = (self.velocity, self.pressure);
+        assert!(
+            velocity.element().order() > pressure.element().order() || self.stabilization > 0.0,
+            "Equal order Stokes elements need a pressure stabilization"
+        );
+        let mesh = velocity.mesh();
+        let dim = mesh.geometric_dim();
+        let mixed = MixedSpace::new(vec![velocity, pressure]);
+        let closed = self.is_enclosed();
+        let constraints = self.constraints(&mixed, closed);
+        let mut matrix =
+            SparsityPattern::from_dofmap_and_constraints(mixed.dof_map(), &constraints).to_csr(0.0);
+        let mut rhs = vec![0.0; mixed.n_dofs()];
+        let (nu, delta) = (self.viscosity, self.stabilization);
+        let n = mixed.dof_map().dofs_per_cell();
+        let mut force = vec![0.0; dim];
+        let order = 2 * velocity.element().order();
+        let assembler = MixedAssembler::new(&mixed, QuadratureRule::simplex(dim, order));
+        assembler.assemble_system(
+            &mut matrix,
+            &mut rhs,
+            &constraints,
+            |values, local, load| {
+                let (u, p) = (&values[0], &values[1]);
+                let size = (0..u.n_points()).map(|q| u.weight(q)).sum::<f64>();
+                let h2 = size.powf(2.0 / dim as f64);
+                for q in 0..u.n_points() {
+                    let w = u.weight(q);
+                    for a in 0..u.n_dofs() {
+                        let ga = u.shape_gradient(q, a);
+                        // ν (grad u, grad v)
+                        for b in 0..u.n_dofs() {
+                            let gb = u.shape_gradient(q, b);
+                            let dot: f64 = ga.iter().zip(gb).map(|(x, y)| x * y).sum();
+                            for i in 0..dim {
+                                local[(a * dim + i) * n + b * dim + i] += nu * dot * w;
+                            }
+                        }
+                        // -(p, div v) and its transpose -(q, div u)
+                        for b in 0..p.n_dofs() {
+                            let col = mixed.local_index(1, b, 0);
+                            let phi = p.shape_value(q, b);
+                            for (i, g) in ga.iter().enumerate() {
+                                local[(a * dim + i) * n + col] -= phi * g * w;
+                                local[col * n + a * dim + i] -= phi * g * w;
+                            }
+                        }
+                    }
+                    if delta > 0.0 {
+                        for a in 0..p.n_dofs() {
+                            let ga = p.shape_gradient(q, a);
+                            for b in 0..p.n_dofs() {
+                                let gb = p.shape_gradient(q, b);
+                                let dot: f64 = ga.iter().zip(gb).map(|(x, y)| x * y).sum();
+                                local[mixed.local_index(1, a, 0) * n
+                                    + mixed.local_index(1, b, 0)] -= delta * h2 * dot * w;
+                            }
+                        }
+                    }
+                    if let Some(body_force) = &self.body_force {
+                        body_force(u.point(q), &mut force);
+                        for a in 0..u.n_dofs() {
+                            for (i, f) in force.iter().enumerate() {
+                                load[a * dim + i] += f * u.shape_value(q, a) * w;
+                            }
+                        }
+                    }
+                }
+            },
+        );
+        let mut solution = vec![0.0; mixed.n_dofs()];
+        match &self.solver {
+            StokesSolver::Direct => {
+                let lu = SparseLU::new(&matrix).expect("Stokes system is singular");
+                lu.solve(&rhs, &mut solution);
+            }
+            StokesSolver::FieldSplit(gmres) => {
+                let (u_range, p_range) = (mixed.block_range(0), mixed.block_range(1));
+                let a = block(&matrix, u_range.clone(), u_range.clone());
+                let b = block(&matrix, u_range.clone(), p_range.clone());
+                let c = block(&matrix, p_range.clone(), u_range);
+                let d = block(&matrix, p_range.clone(), p_range);
+                let a_solver = SparseCholesky::new(&a).expect("Velocity block is singular");
+                // The Schur complement -div A^-1 grad behaves like -M / ν on the pressures
+                let mut schur =
+                    mass_matrix(&pressure.assembler(QuadratureRule::simplex(dim, order)));
+                schur.values_mut().iter_mut().for_each(|v| *v *= -1.0 / nu);
+                let schur_solver = SparseLU::new(&schur).expect("Pressure mass matrix is singular");
+                let split = FieldSplit::new(&a, &b, &c, Some(&d), &a_solver)
+                    .with_schur_preconditioner(&schur_solver)
+                    .with_schur_solver(gmres.clone());
+                assert!(
+                    split.solve(&rhs, &mut solution).converged(),
+                    "Schur complement solve of the Stokes system did not converge"
+                );
+            }
+        }
+        constraints.distribute(&mut solution);
+        let mut fields = mixed.split(&solution).into_iter();
+        let u = fields.next().unwrap();
+        let mut p = fields.next().unwrap();
+        if closed {
+            let mean = self.mean_pressure(&p);
+            p.values_mut().iter_mut().for_each(|p| *p -= mean);
+        }
+        (u, p)
+    }
+
+    /// Write a velocity and a pressure to a legacy VTK file
+    #[cfg(hosted)]
+    pub fn save_vtk<P: AsRef<Path>>(
+        &self,
+        path: P,
+        velocity: &Function,
+        pressure: &Function,
+    ) -> Result<()> {
+        vtk::save_vtk(
+            path,
+            self.velocity.mesh(),
+            &[("velocity", velocity), ("pressure", pressure)],
+        )
+    }
+
+    // Whether the velocity is imposed on every boundary facet
+    fn is_enclosed(&self) -> bool {
+        let mesh = self.velocity.mesh();
+        let facets = Facets::new(mesh);
+        facets.boundary_facets().iter().all(|facet| {
+            mesh.facet_tag(facets.facet_vertices(*facet))
+                .is_some_and(|tag| self.velocities.iter().any(|(t, _)| *t == tag))
+        })
+    }
+
+    // Closed constraints of the imposed velocities, pinning the first pressure if it is only
+    // known up to a constant
+    fn constraints(&self, mixed: &MixedSpace, pin_pressure: bool) -> AffineConstraints {
+        let space = self.velocity;
+        let dim = space.mesh().geometric_dim();
+        let mut constraints = AffineConstraints::new(mixed.n_dofs());
+        let coordinates = space.dof_coordinates();
+        let mut value = vec![0.0; dim];
+        for (tag, velocity) in &self.velocities {
+            for s in space.tagged_dofs(*tag) {
+                velocity(&coordinates[s * dim..(s + 1) * dim], &mut value);
+                for (c, v) in value.iter().enumerate() {
+                    constraints.add_dirichlet(space.dof(s, c), *v);
+                }
+            }
+        }
+        if pin_pressure {
+            constraints.add_dirichlet(mixed.block_range(1).start, 0.0);
+        }
+        constraints.close();
+        constraints
+    }
+
+    // Mean value of a pressure over the domain
+    fn mean_pressure(&self, pressure: &Function) -> f64 {
+        let element = self.pressure.element();
+        let assembler = self
+            .pressure
+            .assembler(QuadratureRule::simplex(element.dim(), element.order()));
+        let mut integrals = vec![0.0; self.pressure.n_dofs()];
+        assembler.assemble_vector(&mut integrals, |values, local| {
+            for q in 0..values.n_points() {
+                for i in 0..values.n_dofs() {
+                    local[i] += values.shape_value(q, i) * values.weight(q);
+                }
+            }
+        });
+        let total: f64 = pressure
+            .values()
+            .iter()
+            .zip(&integrals)
+            .map(|(p, w)| p * w)
+            .sum();
+        total / integrals.iter().sum::<f64>()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Block of a matrix on ranges of rows and columns, renumbered from 0
+fn block(matrix: &SparseCSR<f64>, rows: Range<usize>, cols: Range<usize>) -> SparseCSR<f64> {
+    let mut offsets = vec![0];
+    let (mut indices, mut values) = (Vec::new(), Vec::new());
+    for row in rows {
+        let (row_cols, row_values) = matrix.row(row);
+        for (col, value) in row_cols.iter().zip(row_values) {
+            if cols.contains(col) {
+                indices.push(col - cols.start);
+                values.push(*value);
+            }
+        }
+        offsets.push(indices.len());
+    }
+    SparseCSR::new(cols.len(), offsets, indices, values)
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::refinement::Refinement;
+    use crate::discretizations::test_meshes::{unit_square, unit_square_grid};
+    use crate::spaces::lagrange::LagrangeElement;
+    use std::panic::{self, AssertUnwindSafe};
+
+    // Description of an invalid input with the construction it should make panic
+    type Invalid<'a> = (&'a str, Box<dyn Fn() + 'a>);
+
+    // Poiseuille profile u = (y (1 - y), 0) entering at x = 0 between walls at y = 0 and y = 1
+    fn poiseuille(x: &[f64], u: &mut [f64]) {
+        u[0] = x[1] * (1.0 - x[1]);
+        u[1] = 0.0;
+    }
+
+    #[test]
+    fn test_stokes_flow() {
+        let square = unit_square();
+        let mut mesh = Refinement::uniform(&square).into_mesh();
+        mesh.tag_boundary(1, |_| true);
+        // u = (x^2, -2 x y) and p = x + y - 1 of zero mean are reproduced by Taylor-Hood P2-P1
+        let nu = 0.5;
+        let exact = |x: &[f64], u: &mut [f64]| {
+            u[0] = x[0] * x[0];
+            u[1] = -2.0 * x[0] * x[1];
+        };
+        let velocity = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        let pressure = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let mut expected_u = Function::new(&velocity);
+        expected_u.interpolate_vector(exact);
+        let mut expected_p = Function::new(&pressure);
+        expected_p.interpolate(|x| x[0] + x[1] - 1.0);
+        for solver in [
+            StokesSolver::Direct,
+            StokesSolver::FieldSplit(Gmres::new().with_relative_tolerance(1e-12)),
+        ] {
+            let problem = StokesFlow::new(&velocity, &pressure)
+                .with_viscosity(nu)
+                .with_body_force(|_, f| {
+                    f[0] = 1.0 - 2.0 * nu;
+                    f[1] = 1.0;
+                })
+                .with_velocity(1, exact)
+                .with_solver(solver);
+            let (u, p) = problem.solve();
+            for (u, e) in u.values().iter().zip(expected_u.values()) {
+                assert!(
+                    (u - e).abs() < 1e-9,
+                    "Wrong velocity {} instead of {}",
+                    u,
+                    e
+                );
+            }
+            for (p, e) in p.values().iter().zip(expected_p.values()) {
+                assert!(
+                    (p - e).abs() < 1e-9,
+                    "Wrong pressure {} instead of {}",
+                    p,
+                    e
+                );
+            }
+        }
+        // Stabilized equal order elements converge without being exact
+        let fine = Refinement::uniform(&mesh).into_mesh();
+        let velocity = FunctionSpace::vector(&fine, LagrangeElement::new(2, 1), 2);
+        let pressure = FunctionSpace::new(&fine, LagrangeElement::new(2, 1));
+        let (u, p) = StokesFlow::new(&velocity, &pressure)
+            .with_viscosity(nu)
+            .with_stabilization(0.5)
+            .with_body_force(|_, f| {
+                f[0] = 1.0 - 2.0 * nu;
+                f[1] = 1.0;
+            })
+            .with_velocity(1, exact)
+            .solve();
+        let mut expected_u = Function::new(&velocity);
+        expected_u.interpolate_vector(exact);
+        let mut expected_p = Function::new(&pressure);
+        expected_p.interpolate(|x| x[0] + x[1] - 1.0);
+        let error = |a: &Function, b: &Function| {
+            a.values()
+                .iter()
+                .zip(b.values())
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f64::max)
+        };
+        assert!(error(&u, &expected_u) < 1e-2, "Stabilized velocity too far");
+        assert!(error(&p, &expected_p) < 0.2, "Stabilized pressure too far");
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_stokes_outflow() {
+        // The free outflow at x = 1 fixes the pressure p = 2 ν (1 - x) of the Poiseuille flow
+        let mesh = unit_square_grid(2);
+        let nu = 0.25;
+        let velocity = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        let pressure = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let problem = StokesFlow::new(&velocity, &pressure)
+            .with_viscosity(nu)
+            .with_velocity(1, poiseuille)
+            .with_velocity(3, |_, u| u.fill(0.0))
+            .with_velocity(4, |_, u| u.fill(0.0))
+            .with_solver(StokesSolver::Direct);
+        let (u, p) = problem.solve();
+        let mut expected_u = Function::new(&velocity);
+        expected_u.interpolate_vector(poiseuille);
+        let mut expected_p = Function::new(&pressure);
+        expected_p.interpolate(|x| 2.0 * nu * (1.0 - x[0]));
+        for (u, e) in u.values().iter().zip(expected_u.values()) {
+            assert!(
+                (u - e).abs() < 1e-9,
+                "Wrong velocity {} instead of {}",
+                u,
+                e
+            );
+        }
+        for (p, e) in p.values().iter().zip(expected_p.values()) {
+            assert!(
+                (p - e).abs() < 1e-9,
+                "Wrong pressure {} instead of {}",
+                p,
+                e
+            );
+        }
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_stokes_save_vtk() {
+        let mesh = unit_square_grid(2);
+        let velocity = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        let pressure = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let problem = StokesFlow::new(&velocity, &pressure)
+            .with_velocity(1, poiseuille)
+            .with_velocity(3, |_, u| u.fill(0.0))
+            .with_velocity(4, |_, u| u.fill(0.0));
+        let (u, p) = problem.solve();
+        let directory = std::env::temp_dir().join("fe2o3_test_stokes");
+        std::fs::create_dir_all(&directory).unwrap();
+        problem
+            .save_vtk(directory.join("stokes.vtk"), &u, &p)
+            .unwrap();
+        let vtk = std::fs::read_to_string(directory.join("stokes.vtk")).unwrap();
+        assert!(
+            vtk.contains("velocity") && vtk.contains("pressure"),
+            "Missing fields"
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_stokes_invalid() {
+        let mesh = unit_square_grid(2);
+        let other_mesh = unit_square_grid(2);
+        let velocity = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        let pressure = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let scalar = FunctionSpace::new(&mesh, LagrangeElement::new(2, 2));
+        let broken = FunctionSpace::discontinuous(&mesh, LagrangeElement::new(2, 1));
+        let other = FunctionSpace::new(&other_mesh, LagrangeElement::new(2, 1));
+        let equal = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let problem = || StokesFlow::new(&velocity, &pressure);
+        let cases: [Invalid; 8] = [
+            (
+                "a scalar velocity space",
+                Box::new(|| drop(StokesFlow::new(&scalar, &pressure))),
+            ),
+            (
+                "a vector pressure space",
+                Box::new(|| drop(StokesFlow::new(&velocity, &velocity))),
+            ),
+            (
+                "a discontinuous pressure space",
+                Box::new(|| drop(StokesFlow::new(&velocity, &broken))),
+            ),
+            (
+                "a pressure space on another mesh",
+                Box::new(|| drop(StokesFlow::new(&velocity, &other))),
+            ),
+            (
+                "a zero viscosity",
+                Box::new(|| drop(problem().with_viscosity(0.0))),
+            ),
+            (
+                "a negative stabilization",
+                Box::new(|| drop(problem().with_stabilization(-1.0))),
+            ),
+            (
+                "equal order elements without stabilization",
+                Box::new(|| drop(StokesFlow::new(&equal, &pressure).solve())),
+            ),
+            (
+                "a Schur complement solve out of iterations",
+                Box::new(|| {
+                    let gmres = Gmres::new()
+                        .with_relative_tolerance(1e-14)
+                        .with_max_iterations(1);
+                    drop(
+                        problem()
+                            .with_velocity(1, poiseuille)
+                            .with_solver(StokesSolver::FieldSplit(gmres))
+                            .solve(),
+                    )
+                }),
+            ),
+        ];
+        for (case, build) in cases {
+            assert!(
+                panic::catch_unwind(AssertUnwindSafe(build)).is_err(),
+                "Stokes flow accepted {}",
+                case
+            );
+        }
+    }
+}
@@ -0,0 +1,744 @@
+use super::coupling::{Coupling, CouplingAcceleration, CouplingHistory, CouplingParticipant};
+use super::elasticity::LinearElasticity;
+use super::heat::set_identity_rows;
+use crate::core::arrays::data_hold::DataHold;
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::core::logging::{info, Span};
+use crate::discretizations::assembler::Assembler;
+use crate::discretizations::cell_values::CoordinateSystem;
+use crate::discretizations::constraints::AffineConstraints;
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+#[cfg(hosted)]
+use crate::discretizations::io::vtk;
+use crate::discretizations::operators::{mass_matrix, stiffness_matrix};
+use crate::solvers::sparse_direct::{SparseCholesky, SparseLU};
+#[cfg(hosted)]
+use crate::spaces::lagrange::LagrangeElement;
+use crate::spaces::quadrature::QuadratureRule;
+#[cfg(hosted)]
+use std::io::Result;
+#[cfg(hosted)]
+use std::path::Path;
+
+// Scalar field of the time and the physical coordinates
+type Field<'a> = Box<dyn Fn(f64, &[f64]) -> f64 + 'a>;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Quasi-static thermoelasticity of a body conducting heat c dT/dt - div(k grad T) = f and
+/// deforming elastically with the thermal strain α (T - T_ref) I
+///
+/// The temperature lives on a continuous scalar space of the mesh of the elasticity problem, whose
+/// loads, supports and coordinate system are kept, and is integrated by implicit Euler steps. Both
+/// fields are participants of a Coupling: the conduction sends the temperature of the step to the
+/// elasticity, which answers the thermoelastic dissipation T_ref (3λ + 2μ) α tr(ε) whose rate is a
+/// heat sink of the conduction. In the default one-way coupling the dissipation is left out, the
+/// elasticity following the temperature without feedback, and every step takes one iteration.
+/// Temperatures are imposed on tagged boundary facets and the rest of the boundary is insulated.
+/// By default c and k are 1, there is no source, the reference temperature is 0 and the coupling
+/// iterations are accelerated by Aitken relaxation to a relative tolerance of 1e-8.
+pub struct Thermoelasticity<'a> {
+    elasticity: LinearElasticity<'a>,
+    space: &'a FunctionSpace<'a>,
+    expansion: f64,
+    reference_temperature: f64,
+    capacity: f64,
+    conductivity: f64,
+    source: Option<Field<'a>>,
+    dirichlet: Vec<(usize, Field<'a>)>,
+    two_way: bool,
+    acceleration: CouplingAcceleration,
+    tolerance: f64,
+    max_iterations: usize,
+}
+
+/// Temperature and displacement at the end of a thermoelastic run with the history of the coupling
+pub struct ThermoelasticSolution<'a> {
+    temperature: Function<'a>,
+    displacement: Function<'a>,
+    history: CouplingHistory,
+}
+
+// Conduction participant stepping the temperature, the received dissipation entering by its
+// change over the step
+struct Conduction<'p, 'a> {
+    problem: &'p Thermoelasticity<'a>,
+    assembler: Assembler<'a>,
+    mass: SparseCSR<f64>,
+    lu: SparseLU,
+    dirichlet: Vec<(Vec<usize>, &'p Field<'a>)>,
+    coordinates: DataHold<f64, [usize; 2]>,
+    time_step: f64,
+    temperature: Vec<f64>,
+    next: Vec<f64>,
+    dissipation: Vec<f64>,
+    received: Vec<f64>,
+}
+
+// Elasticity participant answering the dissipation of the displacement under a temperature
+struct Deformation<'p, 'a> {
+    problem: &'p Thermoelasticity<'a>,
+    assembler: Assembler<'a>,
+    cholesky: SparseCholesky,
+    load: Vec<f64>,
+    constraints: AffineConstraints,
+    displacement: Function<'a>,
+}
+
+impl<'a> Thermoelasticity<'a> {
+    /// Thermoelastic body of an elasticity problem with its temperature on a scalar space of the
+    /// same mesh and an expansion coefficient α
+    pub fn new(
+        elasticity: LinearElasticity<'a>,
+        space: &'a FunctionSpace<'a>,
+        expansion: f64,
+    ) -> Self {
+        assert!(
+            std::ptr::eq(space.mesh(), elasticity.space().mesh())
+                && space.n_components() == 1
+                && !space.is_discontinuous(),
+            "Temperature needs a continuous scalar space of the mesh of the elasticity problem"
+        );
+        Thermoelasticity {
+            elasticity,
+            space,
+            expansion,
+            reference_temperature: 0.0,
+            capacity: 1.0,
+            conductivity: 1.0,
+            source: None,
+            dirichlet: Vec::new(),
+            two_way: false,
+            acceleration: CouplingAcceleration::Aitken(0.5),
+            tolerance: 1e-8,
+            max_iterations: 50,
+        }
+    }
+
+    /// Set the reference temperature T_ref free of thermal strain, absolute in two-way coupling
+    pub fn with_reference_temperature(mut self, temperature: f64) -> Self {
+        self.reference_temperature = temperature;
+        self
+    }
+
+    /// Set the heat capacity c (density times specific heat)
+    pub fn with_capacity(mut self, capacity: f64) -> Self {
+        assert!(capacity > 0.0, "Heat capacity should be positive");
+        self.capacity = capacity;
+        self
+    }
+
+    /// Set the thermal conductivity k
+    pub fn with_conductivity(mut self, conductivity: f64) -> Self {
+        assert!(conductivity > 0.0, "Conductivity should be positive");
+        self.conductivity = conductivity;
+        self
+    }
+
+    /// Set the heat source f as a function of the time and the physical coordinates
+    pub fn with_source<Source>(mut self, source: Source) -> Self
+    where
+        Source: Fn(f64, &[f64]) -> f64 + 'a,
+    {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// Impose the temperature given as a function of the time and the physical coordinates on the
+    /// boundary facets carrying the tag
+    pub fn with_dirichlet<Value>(mut self, tag: usize, value: Value) -> Self
+    where
+        Value: Fn(f64, &[f64]) -> f64 + 'a,
+    {
+        self.dirichlet.push((tag, Box::new(value)));
+        self
+    }
+
+    /// Feed the thermoelastic dissipation back to the conduction
+    pub fn with_two_way_coupling(mut self) -> Self {
+        self.two_way = true;
+        self
+    }
+
+    /// Set the acceleration of the coupling iterations of two-way coupling
+    pub fn with_acceleration(mut self, acceleration: CouplingAcceleration) -> Self {
+        self.acceleration = acceleration;
+        self
+    }
+
+    /// Set the tolerance of the coupling iterations on the dissipation relative to its norm
+    pub fn with_coupling_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Set the maximum number of coupling iterations per time step
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Elasticity problem of the body
+    pub fn elasticity(&self) -> &LinearElasticity<'a> {
+        &self.elasticity
+    }
+
+    /// Scalar space of the temperature
+    pub fn space(&self) -> &'a FunctionSpace<'a> {
+        self.space
+    }
+
+    /// Integrate from the start to the end time from an initial temperature, the displacement
+    /// following the temperature from the start
+    ///
+    /// The time step is reduced if needed so that a whole number of steps ends on the final time.
+    pub fn solve(
+        &self,
+        temperature: &Function<'a>,
+        start: f64,
+        end: f64,
+        time_step: f64,
+    ) -> ThermoelasticSolution<'a> {
+        let _span = Span::enter("thermoelasticity");
+        assert!(
+            std::ptr::eq(temperature.space(), self.space),
+            "Temperature does not live on the space of the problem"
+        );
+        assert!(time_step > 0.0, "Time step should be positive");
+        let n_steps = ((end - start) / time_step - 1e-10).ceil().max(1.0) as usize;
+        let dt = (end - start) / n_steps as f64;
+        let mut deformation = Deformation::new(self);
+        let dissipation = deformation.solve_step(start, dt, temperature.values());
+        let conduction = Conduction::new(self, temperature.values().to_vec(), &dissipation, dt);
+        let mut coupling = Coupling::new(conduction, deformation)
+            .with_acceleration(self.acceleration)
+            .with_relative_tolerance(self.tolerance)
+            .with_max_iterations(self.max_iterations);
+        let history = coupling.run(start, end, dt, dissipation);
+        let (conduction, deformation) = coupling.into_participants();
+        info!(
+            "Thermoelasticity ran {} steps with {} coupling iterations",
+            n_steps,
+            history.iterations()
+        );
+        ThermoelasticSolution {
+            temperature: Function::from_values(self.space, conduction.temperature),
+            displacement: deformation.displacement,
+            history,
+        }
+    }
+
+    // Thermal strain α (T - T_ref) of temperature values
+    fn thermal_strain(&self, temperature: &[f64]) -> Function<'a> {
+        let strain = temperature
+            .iter()
+            .map(|t| self.expansion * (t - self.reference_temperature))
+            .collect();
+        Function::from_values(self.space, strain)
+    }
+}
+
+impl<'a> ThermoelasticSolution<'a> {
+    /// Temperature at the end time
+    pub fn temperature(&self) -> &Function<'a> {
+        &self.temperature
+    }
+
+    /// Displacement at the end time
+    pub fn displacement(&self) -> &Function<'a> {
+        &self.displacement
+    }
+
+    /// History of the coupling iterations of every time step
+    pub fn history(&self) -> &CouplingHistory {
+        &self.history
+    }
+
+    /// Strains and stresses of the displacement like LinearElasticity::recover_stress, the normal
+    /// stresses being reduced by the thermal stress (3λ + 2μ) α (T - T_ref)
+    pub fn recover_stress<'b>(
+        &self,
+        problem: &Thermoelasticity,
+        target: &'b FunctionSpace<'b>,
+    ) -> Vec<(String, Function<'b>)> {
+        let elasticity = &problem.elasticity;
+        let mut fields = elasticity.recover_stress(&self.displacement, target);
+        let (lambda, mu) = elasticity.material().lame_parameters();
+        let dim = target.mesh().geometric_dim();
+        let coordinates = target.dof_coordinates();
+        let thermal: Vec<f64> = coordinates
+            .chunks(dim)
+            .map(|x| {
+                let temperature = self
+                    .temperature
+                    .eval(x)
+                    .map_or(problem.reference_temperature, |t| t[0]);
+                (3.0 * lambda + 2.0 * mu)
+                    * problem.expansion
+                    * (temperature - problem.reference_temperature)
+            })
+            .collect();
+        let normal: Vec<String> = super::elasticity::AXES[..dim]
+            .iter()
+            .map(|a| format!("stress_{}{}", a, a))
+            .chain(std::iter::once("stress_hoop".to_string()))
+            .collect();
+        // A hydrostatic shift leaves the von Mises stress unchanged
+        for (_, field) in fields.iter_mut().filter(|(name, _)| normal.contains(name)) {
+            field
+                .values_mut()
+                .iter_mut()
+                .zip(&thermal)
+                .for_each(|(s, t)| *s -= t);
+        }
+        fields
+    }
+
+    /// Write the temperature, the displacement and its strains and stresses recovered on linear
+    /// elements to a legacy VTK file
+    #[cfg(hosted)]
+    pub fn save_vtk<P: AsRef<Path>>(&self, path: P, problem: &Thermoelasticity) -> Result<()> {
+        let mesh = self.displacement.space().mesh();
+        let target = FunctionSpace::new(mesh, LagrangeElement::new(mesh.geometric_dim(), 1));
+        let fields = self.recover_stress(problem, &target);
+        let mut functions = vec![
+            ("temperature", &self.temperature),
+            ("displacement", &self.displacement),
+        ];
+        functions.extend(fields.iter().map(|(name, f)| (name.as_str(), f)));
+        vtk::save_vtk(path, mesh, &functions)
+    }
+}
+
+impl<'p, 'a> Conduction<'p, 'a> {
+    // Implicit Euler system of the conduction factorized with the imposed temperatures
+    fn new(
+        problem: &'p Thermoelasticity<'a>,
+        temperature: Vec<f64>,
+        dissipation: &[f64],
+        time_step: f64,
+    ) -> Self {
+        let space = problem.space;
+        let order = space.element().order();
+        let mut assembler =
+            space.assembler(QuadratureRule::simplex(space.element().dim(), 2 * order));
+        assembler.set_coordinate_system(problem.elasticity.coordinate_system());
+        let mass = mass_matrix(&assembler);
+        let stiffness = stiffness_matrix(&assembler, |_| problem.conductivity);
+        let mut system = stiffness.add(problem.capacity / time_step, &mass);
+        system.values_mut().iter_mut().for_each(|v| *v *= time_step);
+        let dirichlet: Vec<(Vec<usize>, &Field)> = problem
+            .dirichlet
+            .iter()
+            .map(|(tag, value)| (space.tagged_dofs(*tag), value))
+            .collect();
+        for (dofs, _) in &dirichlet {
+            set_identity_rows(&mut system, dofs);
+        }
+        let lu = SparseLU::new(&system).expect("Conduction system is singular");
+        Conduction {
+            problem,
+            assembler,
+            mass,
+            lu,
+            dirichlet,
+            coordinates: space.dof_coordinates(),
+            time_step,
+            next: temperature.clone(),
+            temperature,
+            dissipation: dissipation.to_vec(),
+            received: dissipation.to_vec(),
+        }
+    }
+}
+
+impl<'p, 'a> CouplingParticipant for Conduction<'p, 'a> {
+    fn solve_step(&mut self, time: f64, _time_step: f64, received: &[f64]) -> Vec<f64> {
+        let problem = self.problem;
+        let (dt, end) = (self.time_step, time + self.time_step);
+        let mut rhs = vec![0.0; self.temperature.len()];
+        self.mass.apply(&self.temperature, &mut rhs);
+        rhs.iter_mut().for_each(|r| *r *= problem.capacity);
+        if let Some(source) = &problem.source {
+            self.assembler.assemble_vector(&mut rhs, |values, local| {
+                for q in 0..values.n_points() {
+                    let f = dt * source(end, values.point(q)) * values.weight(q);
+                    for i in 0..values.n_dofs() {
+                        local[i] += f * values.shape_value(q, i);
+                    }
+                }
+            });
+        }
+        // Heat sink of the rate of the dissipation over the step
+        for ((r, d), start) in rhs.iter_mut().zip(received).zip(&self.dissipation) {
+            *r -= d - start;
+        }
+        let dim = problem.space.mesh().geometric_dim();
+        for (dofs, value) in &self.dirichlet {
+            for dof in dofs {
+                rhs[*dof] = value(end, &self.coordinates[dof * dim..(dof + 1) * dim]);
+            }
+        }
+        self.lu.solve(&rhs, &mut self.next);
+        self.received = received.to_vec();
+        self.next.clone()
+    }
+
+    fn accept_step(&mut self) {
+        self.temperature.copy_from_slice(&self.next);
+        self.dissipation.copy_from_slice(&self.received);
+    }
+}
+
+impl<'p, 'a> Deformation<'p, 'a> {
+    // Condensed stiffness of the body factorized once
+    fn new(problem: &'p Thermoelasticity<'a>) -> Self {
+        let (stiffness, load, constraints) = problem.elasticity.assemble();
+        let cholesky = SparseCholesky::new(&stiffness)
+            .expect("Elasticity system is singular, rigid motions should be constrained");
+        let space = problem.space;
+        let order = space.element().order() + problem.elasticity.space().element().order();
+        let mut assembler = space.assembler(QuadratureRule::simplex(space.element().dim(), order));
+        assembler.set_coordinate_system(problem.elasticity.coordinate_system());
+        Deformation {
+            problem,
+            assembler,
+            cholesky,
+            load,
+            constraints,
+            displacement: Function::new(problem.elasticity.space()),
+        }
+    }
+
+    // Load vector of the dissipation T_ref (3λ + 2μ) α tr(ε) of the displacement on the
+    // temperature space
+    fn dissipation(&self) -> Vec<f64> {
+        let problem = self.problem;
+        let mut dissipation = vec![0.0; problem.space.n_dofs()];
+        if !problem.two_way {
+            return dissipation;
+        }
+        let elasticity = &problem.elasticity;
+        let dim = problem.space.mesh().geometric_dim();
+        let (lambda, mu) = elasticity.material().lame_parameters();
+        let scale = problem.reference_temperature * (3.0 * lambda + 2.0 * mu) * problem.expansion;
+        let axisymmetric = elasticity.coordinate_system() == CoordinateSystem::Axisymmetric;
+        let quadrature = self.assembler.quadrature();
+        let mut gradient = vec![0.0; dim * dim];
+        let mut value = vec![0.0; dim];
+        self.assembler
+            .assemble_vector(&mut dissipation, |values, local| {
+                for q in 0..values.n_points() {
+                    let reference = quadrature.point(q);
+                    self.displacement
+                        .gradient_in_cell(values.cell(), reference, &mut gradient);
+                    let mut trace: f64 = (0..dim).map(|i| gradient[i * dim + i]).sum();
+                    if axisymmetric {
+                        self.displacement
+                            .eval_in_cell(values.cell(), reference, &mut value);
+                        trace += value[0] / values.point(q)[0];
+                    }
+                    let d = scale * trace * values.weight(q);
+                    for i in 0..values.n_dofs() {
+                        local[i] += d * values.shape_value(q, i);
+                    }
+                }
+            });
+        dissipation
+    }
+}
+
+impl<'p, 'a> CouplingParticipant for Deformation<'p, 'a> {
+    fn solve_step(&mut self, _time: f64, _time_step: f64, received: &[f64]) -> Vec<f64> {
+        let elasticity = &self.problem.elasticity;
+        let mut rhs = elasticity.thermal_load(&self.problem.thermal_strain(received));
+        rhs.iter_mut().zip(&self.load).for_each(|(r, f)| *r += f);
+        let displacement = self.displacement.values_mut();
+        self.cholesky.solve(&rhs, displacement);
+        self.constraints.distribute(displacement);
+        self.dissipation()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::test_meshes::unit_square_grid;
+    use crate::spaces::lagrange::LagrangeElement;
+    use crate::workflows::elasticity::IsotropicMaterial;
+    use std::panic::{self, AssertUnwindSafe};
+
+    // Description of an invalid input with the construction it should make panic
+    type Invalid<'a> = (&'a str, Box<dyn Fn() + 'a>);
+
+    // Square on rollers along its lower sides with a unit heat source, the modulus of 1 and the
+    // Poisson ratio of 0.25 giving λ = μ = 0.4
+    fn heated_square<'a>(
+        displacement_space: &'a FunctionSpace<'a>,
+        space: &'a FunctionSpace<'a>,
+    ) -> Thermoelasticity<'a> {
+        let elasticity =
+            LinearElasticity::new(displacement_space, IsotropicMaterial::new(1.0, 0.25))
+                .with_component_displacement(1, 0, 0.0)
+                .with_component_displacement(3, 1, 0.0);
+        Thermoelasticity::new(elasticity, space, 0.1)
+            .with_reference_temperature(1.0)
+            .with_capacity(0.1)
+            .with_source(|_, _| 1.0)
+    }
+
+    #[test]
+    fn test_thermoelasticity() {
+        // Square on rollers heated uniformly without conduction gradients: the plane strain
+        // expansion is (1 + ν) α (T - T_ref) in the plane, and the dissipation of two-way coupling
+        // raises the heat capacity to c + T_ref (3λ + 2μ)² α² / (λ + μ)
+        let mesh = unit_square_grid(4);
+        let displacement_space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let (lambda, mu) = IsotropicMaterial::new(1.0, 0.25).lame_parameters();
+        let (alpha, reference, capacity) = (0.1, 1.0, 0.1);
+        let problem = || heated_square(&displacement_space, &space);
+        let mut initial = Function::new(&space);
+        initial.interpolate(|_| reference);
+        let coupling =
+            reference * (3.0 * lambda + 2.0 * mu).powi(2) * alpha * alpha / (lambda + mu);
+        for (problem, effective) in [
+            (problem(), capacity),
+            (problem().with_two_way_coupling(), capacity + coupling),
+        ] {
+            let solution = problem.solve(&initial, 0.0, 1.0, 0.25);
+            assert!(solution.history().converged(), "Coupling did not converge");
+            let rise = 1.0 / effective;
+            for t in solution.temperature().values() {
+                assert!(
+                    (t - reference - rise).abs() < 1e-8,
+                    "Wrong temperature {} of capacity {}",
+                    t,
+                    effective
+                );
+            }
+            let expansion = 1.25 * alpha * rise;
+            let u = solution.displacement().eval(&[1.0, 0.5]).unwrap();
+            assert!(
+                (u[0] - expansion).abs() < 1e-8 && (u[1] - 0.5 * expansion).abs() < 1e-8,
+                "Wrong thermal expansion {:?}",
+                u
+            );
+            let target = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+            let fields = solution.recover_stress(&problem, &target);
+            let (_, stress) = fields.iter().find(|(name, _)| name == "stress_xx").unwrap();
+            assert!(
+                stress.values().iter().all(|s| s.abs() < 1e-8),
+                "Free expansion should be stress free in the plane"
+            );
+        }
+        // One-way coupling takes a single iteration per step
+        let history = problem().solve(&initial, 0.0, 1.0, 0.25).history().clone();
+        assert_eq!(history.iterations(), 0, "One-way steps should not iterate");
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_thermoelasticity_accelerations() {
+        // Every acceleration of two-way coupling reaches the temperature rise 1 / 0.15 of the
+        // effective capacity in fewer iterations than plain fixed points, and a looser tolerance
+        // stops the iterations earlier
+        let mesh = unit_square_grid(4);
+        let displacement_space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let mut initial = Function::new(&space);
+        initial.interpolate(|_| 1.0);
+        let iterations = |acceleration, tolerance| {
+            let solution = heated_square(&displacement_space, &space)
+                .with_two_way_coupling()
+                .with_acceleration(acceleration)
+                .with_coupling_tolerance(tolerance)
+                .solve(&initial, 0.0, 1.0, 0.25);
+            assert!(
+                solution.history().converged(),
+                "Coupling did not converge with {:?}",
+                acceleration
+            );
+            let error = solution
+                .temperature()
+                .values()
+                .iter()
+                .map(|t| (t - 1.0 - 1.0 / 0.15).abs())
+                .fold(0.0, f64::max);
+            assert!(
+                error < 1e3 * tolerance,
+                "Wrong temperature with {:?}, error {}",
+                acceleration,
+                error
+            );
+            solution.history().iterations()
+        };
+        let plain = iterations(CouplingAcceleration::Constant(1.0), 1e-10);
+        for acceleration in [
+            CouplingAcceleration::Constant(0.5),
+            CouplingAcceleration::Aitken(0.5),
+            CouplingAcceleration::Anderson {
+                depth: 2,
+                relaxation: 0.5,
+            },
+        ] {
+            assert!(
+                iterations(acceleration, 1e-10) < plain,
+                "{:?} should take fewer iterations than plain fixed points",
+                acceleration
+            );
+        }
+        assert!(
+            iterations(CouplingAcceleration::Constant(1.0), 1e-4) < plain,
+            "A looser tolerance should take fewer iterations"
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_thermoelasticity_dirichlet() {
+        // With a negligible capacity and no source, the temperature interpolates the imposed t x
+        // of the sides x = 0 and x = 1 at every step
+        let mesh = unit_square_grid(4);
+        let displacement_space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let elasticity =
+            LinearElasticity::new(&displacement_space, IsotropicMaterial::new(1.0, 0.25))
+                .with_component_displacement(1, 0, 0.0)
+                .with_component_displacement(3, 1, 0.0);
+        let solution = Thermoelasticity::new(elasticity, &space, 0.1)
+            .with_capacity(1e-12)
+            .with_conductivity(2.0)
+            .with_dirichlet(1, |_, _| 0.0)
+            .with_dirichlet(2, |t, x| t * x[0])
+            .solve(&Function::new(&space), 0.0, 2.0, 0.5);
+        let coordinates = space.dof_coordinates();
+        for (t, x) in solution
+            .temperature()
+            .values()
+            .iter()
+            .zip(coordinates.chunks(2))
+        {
+            assert!(
+                (t - 2.0 * x[0]).abs() < 1e-8,
+                "Wrong temperature {} at {:?}",
+                t,
+                x
+            );
+        }
+        assert_eq!(
+            solution.history().reasons().len(),
+            4,
+            "Wrong number of steps"
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_thermoelasticity_unconverged() {
+        // A single coupling iteration per step cannot meet the tolerance of two-way coupling, the
+        // last iterate being kept
+        let mesh = unit_square_grid(4);
+        let displacement_space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let mut initial = Function::new(&space);
+        initial.interpolate(|_| 1.0);
+        let solution = heated_square(&displacement_space, &space)
+            .with_two_way_coupling()
+            .with_acceleration(CouplingAcceleration::Constant(1.0))
+            .with_max_iterations(1)
+            .solve(&initial, 0.0, 1.0, 0.25);
+        let history = solution.history();
+        assert!(!history.converged(), "Coupling should not converge");
+        assert!(
+            history.reasons().iter().all(|r| !r.converged()),
+            "No step should converge"
+        );
+        assert_eq!(history.iterations(), 4, "Every step should iterate once");
+        assert!(
+            solution
+                .temperature()
+                .values()
+                .iter()
+                .all(|t| t.is_finite() && *t > 1.0),
+            "The last iterate should still heat the body"
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_thermoelasticity_save_vtk() {
+        let mesh = unit_square_grid(2);
+        let displacement_space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 2), 2);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let problem = heated_square(&displacement_space, &space);
+        let solution = problem.solve(&Function::new(&space), 0.0, 1.0, 0.5);
+        let directory = std::env::temp_dir().join("fe2o3_test_thermoelasticity");
+        std::fs::create_dir_all(&directory).unwrap();
+        solution
+            .save_vtk(directory.join("thermoelasticity.vtk"), &problem)
+            .unwrap();
+        let vtk = std::fs::read_to_string(directory.join("thermoelasticity.vtk")).unwrap();
+        assert!(
+            ["temperature", "displacement", "stress_xx", "von_mises"]
+                .iter()
+                .all(|field| vtk.contains(field)),
+            "Missing fields"
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_thermoelasticity_invalid() {
+        let mesh = unit_square_grid(2);
+        let displacement_space = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let other = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let elasticity =
+            || LinearElasticity::new(&displacement_space, IsotropicMaterial::new(1.0, 0.25));
+        let problem = || heated_square(&displacement_space, &space);
+        let cases: [Invalid; 5] = [
+            (
+                "a vector temperature space",
+                Box::new(|| {
+                    drop(Thermoelasticity::new(
+                        elasticity(),
+                        &displacement_space,
+                        0.1,
+                    ))
+                }),
+            ),
+            (
+                "a zero capacity",
+                Box::new(|| drop(problem().with_capacity(0.0))),
+            ),
+            (
+                "a negative conductivity",
+                Box::new(|| drop(problem().with_conductivity(-1.0))),
+            ),
+            (
+                "a temperature of another space",
+                Box::new(|| drop(problem().solve(&Function::new(&other), 0.0, 1.0, 0.5))),
+            ),
+            (
+                "a zero time step",
+                Box::new(|| drop(problem().solve(&Function::new(&space), 0.0, 1.0, 0.0))),
+            ),
+        ];
+        for (case, build) in cases {
+            assert!(
+                panic::catch_unwind(AssertUnwindSafe(build)).is_err(),
+                "Thermoelasticity accepted {}",
+                case
+            );
+        }
+    }
+}
@@ -0,0 +1,720 @@
+use super::parameter_sweep::Parameters;
+use crate::core::logging::{info, Span};
+use std::fmt::Write;
+#[cfg(hosted)]
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+// Quantities and field of the run of a sample
+type Outputs = (Vec<(String, f64)>, Vec<f64>);
+
+//--------------------------------------------------------------------------------------------------
+// # Enums
+//--------------------------------------------------------------------------------------------------
+
+/// Probability distribution of a random input parameter
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Distribution {
+    /// Uniform between two bounds
+    Uniform {
+        /// Lower bound
+        lower: f64,
+        /// Upper bound
+        upper: f64,
+    },
+    /// Normal of a mean and a standard deviation
+    Normal {
+        /// Mean
+        mean: f64,
+        /// Standard deviation
+        std_dev: f64,
+    },
+    /// Log-normal whose logarithm is normal of a mean and a standard deviation, for positive
+    /// coefficients
+    LogNormal {
+        /// Mean of the logarithm
+        mu: f64,
+        /// Standard deviation of the logarithm
+        sigma: f64,
+    },
+    /// Triangular between two bounds peaking at a mode
+    Triangular {
+        /// Lower bound
+        lower: f64,
+        /// Most likely value
+        mode: f64,
+        /// Upper bound
+        upper: f64,
+    },
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Named random input parameter
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct RandomParameter {
+    name: String,
+    distribution: Distribution,
+}
+
+/// Monte Carlo propagation of the uncertainty of random input parameters through a workflow
+///
+/// Every sample draws the parameters independently from their distributions, or by Latin
+/// hypercube sampling which stratifies each of them, and runs the workflow. Sample k only depends
+/// on the seed and on k so that the results do not depend on the number of threads, which take
+/// the next pending sample in turn. By default 100 samples are drawn sequentially from the seed 0.
+///
+/// With the serde feature the study deserializes from any serde format, for instance in TOML
+///
+/// ```toml
+/// n_samples = 1000
+/// seed = 7
+/// [[parameters]]
+/// name = "conductivity"
+/// distribution = { log_normal = { mu = 0.0, sigma = 0.2 } }
+/// [[parameters]]
+/// name = "source"
+/// distribution = { uniform = { lower = 0.5, upper = 1.5 } }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct MonteCarlo {
+    parameters: Vec<RandomParameter>,
+    #[cfg_attr(feature = "serde", serde(default = "default_samples"))]
+    n_samples: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    seed: u64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    latin_hypercube: bool,
+    #[cfg_attr(feature = "serde", serde(default = "sequential"))]
+    n_threads: usize,
+}
+
+/// Samples of the parameters, quantities of interest and field of the runs of a Monte Carlo study
+/// with their statistics
+#[derive(Clone, Debug, PartialEq)]
+pub struct MonteCarloResults {
+    columns: Vec<String>,
+    n_parameters: usize,
+    rows: Vec<Vec<f64>>,
+    fields: Vec<Vec<f64>>,
+}
+
+/// Sample statistics of a quantity
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Statistics {
+    /// Sample mean
+    pub mean: f64,
+    /// Unbiased sample variance, zero for a single sample
+    pub variance: f64,
+    /// Smallest sample
+    pub min: f64,
+    /// Largest sample
+    pub max: f64,
+}
+
+// Splitmix64 generator of the uniform numbers of a sample
+pub(crate) struct Generator {
+    state: u64,
+}
+
+impl Distribution {
+    /// Value of the inverse cumulative distribution function at a probability in (0, 1)
+    pub fn quantile(&self, p: f64) -> f64 {
+        assert!(p > 0.0 && p < 1.0, "Probabilities should lie in (0, 1)");
+        match *self {
+            Distribution::Uniform { lower, upper } => lower + p * (upper - lower),
+            Distribution::Normal { mean, std_dev } => mean + std_dev * standard_normal_quantile(p),
+            Distribution::LogNormal { mu, sigma } => {
+                (mu + sigma * standard_normal_quantile(p)).exp()
+            }
+            Distribution::Triangular { lower, mode, upper } => {
+                let split = (mode - lower) / (upper - lower);
+                if p < split {
+                    lower + (p * (upper - lower) * (mode - lower)).sqrt()
+                } else {
+                    upper - ((1.0 - p) * (upper - lower) * (upper - mode)).sqrt()
+                }
+            }
+        }
+    }
+
+    /// Mean of the distribution
+    pub fn mean(&self) -> f64 {
+        match *self {
+            Distribution::Uniform { lower, upper } => 0.5 * (lower + upper),
+            Distribution::Normal { mean, .. } => mean,
+            Distribution::LogNormal { mu, sigma } => (mu + 0.5 * sigma * sigma).exp(),
+            Distribution::Triangular { lower, mode, upper } => (lower + mode + upper) / 3.0,
+        }
+    }
+}
+
+impl RandomParameter {
+    /// Parameter of a distribution
+    pub fn new(name: &str, distribution: Distribution) -> Self {
+        RandomParameter {
+            name: name.to_string(),
+            distribution,
+        }
+    }
+}
+
+impl MonteCarlo {
+    /// Study of random parameters with the default number of samples and seed
+    pub fn new(parameters: Vec<RandomParameter>) -> Self {
+        MonteCarlo {
+            parameters,
+            n_samples: 100,
+            seed: 0,
+            latin_hypercube: false,
+            n_threads: 1,
+        }
+    }
+
+    /// Set the number of samples
+    pub fn with_n_samples(mut self, n_samples: usize) -> Self {
+        assert!(n_samples > 0, "Studies need at least one sample");
+        self.n_samples = n_samples;
+        self
+    }
+
+    /// Set the seed of the random numbers
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Draw the samples by Latin hypercube sampling
+    pub fn with_latin_hypercube(mut self) -> Self {
+        self.latin_hypercube = true;
+        self
+    }
+
+    /// Set the number of threads running the samples concurrently
+    pub fn with_n_threads(mut self, n_threads: usize) -> Self {
+        assert!(n_threads > 0, "Studies need at least one thread");
+        self.n_threads = n_threads;
+        self
+    }
+
+    /// Names of the parameters
+    pub fn names(&self) -> Vec<String> {
+        self.parameters.iter().map(|p| p.name.clone()).collect()
+    }
+
+    /// Values of the parameters of every sample
+    pub fn samples(&self) -> Vec<Vec<f64>> {
+        let n = self.n_samples;
+        // Random stratum of every sample for every parameter
+        let strata: Vec<Vec<usize>> = if self.latin_hypercube {
+            (0..self.parameters.len())
+                .map(|j| {
+                    let mut generator = Generator::new(self.seed, u64::MAX - j as u64);
+                    let mut permutation: Vec<usize> = (0..n).collect();
+                    for i in (1..n).rev() {
+                        permutation.swap(i, generator.below(i + 1));
+                    }
+                    permutation
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        (0..n)
+            .map(|k| {
+                let mut generator = Generator::new(self.seed, k as u64);
+                self.parameters
+                    .iter()
+                    .enumerate()
+                    .map(|(j, parameter)| {
+                        let mut u = generator.uniform();
+                        if self.latin_hypercube {
+                            u = (strata[j][k] as f64 + u) / n as f64;
+                        }
+                        parameter.distribution.quantile(u)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Run the workflow on every sample and collect its named quantities of interest, which
+    /// should be the same for every run
+    pub fn run<Run>(&self, run: Run) -> MonteCarloResults
+    where
+        Run: Fn(&Parameters) -> Vec<(String, f64)> + Sync,
+    {
+        self.run_with_fields(|p| (run(p), Vec::new()))
+    }
+
+    /// Run the workflow on every sample and collect its named quantities of interest and a field,
+    /// such as the values of a solution, which should have the same size for every run
+    pub fn run_with_fields<Run>(&self, run: Run) -> MonteCarloResults
+    where
+        Run: Fn(&Parameters) -> (Vec<(String, f64)>, Vec<f64>) + Sync,
+    {
+        let _span = Span::enter("Monte Carlo");
+        let names = self.names();
+        let samples = self.samples();
+        let n = self.n_samples;
+        let sample = |k: usize| {
+            info!("Sample {} of {}: {:?}", k + 1, n, samples[k]);
+            run(&Parameters::new(&names, &samples[k]))
+        };
+        let outputs: Vec<Outputs> = if self.n_threads == 1 {
+            (0..n).map(sample).collect()
+        } else {
+            let next = AtomicUsize::new(0);
+            let outputs = Mutex::new(vec![(Vec::new(), Vec::new()); n]);
+            thread::scope(|scope| {
+                for _ in 0..self.n_threads.min(n) {
+                    scope.spawn(|| loop {
+                        let k = next.fetch_add(1, Ordering::Relaxed);
+                        if k >= n {
+                            break;
+                        }
+                        let output = sample(k);
+                        outputs.lock().unwrap()[k] = output;
+                    });
+                }
+            });
+            outputs.into_inner().unwrap()
+        };
+        let mut columns = names.clone();
+        columns.extend(outputs[0].0.iter().map(|(n, _)| n.clone()));
+        let mut rows = Vec::with_capacity(n);
+        let mut fields = Vec::with_capacity(n);
+        for ((quantities, field), set) in outputs.into_iter().zip(samples) {
+            assert!(
+                quantities.len() + set.len() == columns.len()
+                    && quantities
+                        .iter()
+                        .zip(&columns[set.len()..])
+                        .all(|((n, _), c)| n == c),
+                "Every run should return the same quantities"
+            );
+            assert!(
+                fields
+                    .first()
+                    .is_none_or(|f: &Vec<f64>| f.len() == field.len()),
+                "Every run should return fields of the same size"
+            );
+            rows.push(
+                set.into_iter()
+                    .chain(quantities.into_iter().map(|(_, v)| v))
+                    .collect(),
+            );
+            fields.push(field);
+        }
+        MonteCarloResults {
+            columns,
+            n_parameters: names.len(),
+            rows,
+            fields,
+        }
+    }
+}
+
+impl MonteCarloResults {
+    /// Names of the parameters then of the quantities of interest
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Number of leading parameter columns
+    pub fn n_parameters(&self) -> usize {
+        self.n_parameters
+    }
+
+    /// Number of samples
+    pub fn n_samples(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Rows of the parameters and quantities of every sample
+    pub fn rows(&self) -> &[Vec<f64>] {
+        &self.rows
+    }
+
+    /// Samples of a parameter or quantity
+    pub fn column(&self, name: &str) -> Vec<f64> {
+        let index = self
+            .columns
+            .iter()
+            .position(|c| c == name)
+            .unwrap_or_else(|| panic!("Unknown column {}", name));
+        self.rows.iter().map(|row| row[index]).collect()
+    }
+
+    /// Statistics of a parameter or quantity
+    pub fn statistics(&self, name: &str) -> Statistics {
+        statistics(&self.column(name))
+    }
+
+    /// Quantile of a parameter or quantity at a probability, interpolated between the sorted
+    /// samples
+    pub fn quantile(&self, name: &str, p: f64) -> f64 {
+        let mut samples = self.column(name);
+        samples.sort_by(f64::total_cmp);
+        sorted_quantile(&samples, p)
+    }
+
+    /// Field of every sample
+    pub fn fields(&self) -> &[Vec<f64>] {
+        &self.fields
+    }
+
+    /// Mean of the fields value by value
+    pub fn field_mean(&self) -> Vec<f64> {
+        self.field_map(|values| statistics(values).mean)
+    }
+
+    /// Unbiased variance of the fields value by value
+    pub fn field_variance(&self) -> Vec<f64> {
+        self.field_map(|values| statistics(values).variance)
+    }
+
+    /// Quantile of the fields value by value at a probability
+    pub fn field_quantile(&self, p: f64) -> Vec<f64> {
+        self.field_map(|values| {
+            values.sort_by(f64::total_cmp);
+            sorted_quantile(values, p)
+        })
+    }
+
+    /// Comma separated table of the mean, standard deviation, extremes and 5, 50 and 95%
+    /// quantiles of every parameter and quantity
+    pub fn summary_csv(&self) -> String {
+        let mut csv = String::from("name,mean,std_dev,min,p05,median,p95,max\n");
+        for name in &self.columns {
+            let s = self.statistics(name);
+            let q: Vec<f64> = [0.05, 0.5, 0.95]
+                .iter()
+                .map(|p| self.quantile(name, *p))
+                .collect();
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{}",
+                name,
+                s.mean,
+                s.variance.sqrt(),
+                s.min,
+                q[0],
+                q[1],
+                q[2],
+                s.max
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    /// Comma separated table with a header and one row per sample
+    pub fn to_csv(&self) -> String {
+        let mut csv = self.columns.join(",");
+        csv.push('\n');
+        for row in &self.rows {
+            let values: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+            writeln!(csv, "{}", values.join(",")).unwrap();
+        }
+        csv
+    }
+
+    /// JSON array with one object per sample, non finite values being null
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("[\n");
+        for (k, row) in self.rows.iter().enumerate() {
+            let fields: Vec<String> = self
+                .columns
+                .iter()
+                .zip(row)
+                .map(|(c, v)| {
+                    if v.is_finite() {
+                        format!("\"{}\": {}", c, v)
+                    } else {
+                        format!("\"{}\": null", c)
+                    }
+                })
+                .collect();
+            let separator = if k + 1 < self.rows.len() { "," } else { "" };
+            writeln!(json, "  {{{}}}{}", fields.join(", "), separator).unwrap();
+        }
+        json.push_str("]\n");
+        json
+    }
+
+    /// Write the samples as JSON if the path has the json extension and as CSV otherwise
+    #[cfg(hosted)]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let contents = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => self.to_json(),
+            _ => self.to_csv(),
+        };
+        std::fs::write(path, contents)
+    }
+
+    // Reduce the samples of every value of the fields
+    fn field_map<Reduce: Fn(&mut Vec<f64>) -> f64>(&self, reduce: Reduce) -> Vec<f64> {
+        let size = self.fields.first().map_or(0, |f| f.len());
+        (0..size)
+            .map(|i| reduce(&mut self.fields.iter().map(|f| f[i]).collect()))
+            .collect()
+    }
+}
+
+impl Generator {
+    // Stream of a sample of a study
+    pub(crate) fn new(seed: u64, stream: u64) -> Self {
+        let mut generator = Generator {
+            state: seed ^ stream.wrapping_mul(0xd1b5_4a32_d192_ed03),
+        };
+        generator.next();
+        generator
+    }
+
+    // Next 64 random bits
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform number in (0, 1)
+    pub(crate) fn uniform(&mut self) -> f64 {
+        ((self.next() >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    }
+
+    // Standard normal number by inversion
+    pub(crate) fn normal(&mut self) -> f64 {
+        standard_normal_quantile(self.uniform())
+    }
+
+    // Uniform integer below a bound
+    fn below(&mut self, bound: usize) -> usize {
+        ((self.uniform() * bound as f64) as usize).min(bound - 1)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Default number of samples when deserializing
+#[cfg(feature = "serde")]
+fn default_samples() -> usize {
+    100
+}
+
+// Default number of threads when deserializing
+#[cfg(feature = "serde")]
+fn sequential() -> usize {
+    1
+}
+
+// Statistics of samples
+fn statistics(samples: &[f64]) -> Statistics {
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+    let variance = if samples.len() > 1 {
+        samples.iter().map(|s| (s - mean) * (s - mean)).sum::<f64>() / (n - 1.0)
+    } else {
+        0.0
+    };
+    Statistics {
+        mean,
+        variance,
+        min: samples.iter().cloned().fold(f64::INFINITY, f64::min),
+        max: samples.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+    }
+}
+
+// Quantile of sorted samples interpolated linearly between the order statistics
+fn sorted_quantile(sorted: &[f64], p: f64) -> f64 {
+    assert!(
+        (0.0..=1.0).contains(&p),
+        "Probabilities should lie in [0, 1]"
+    );
+    let position = p * (sorted.len() - 1) as f64;
+    let below = position.floor() as usize;
+    let above = (below + 1).min(sorted.len() - 1);
+    sorted[below] + (position - below as f64) * (sorted[above] - sorted[below])
+}
+
+// Inverse of the standard normal cumulative distribution function by the rational approximations
+// of Acklam, with a relative error below 1.2e-9
+fn standard_normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e1,
+        2.209460984245205e2,
+        -2.759285104469687e2,
+        1.38357751867269e2,
+        -3.066479806614716e1,
+        2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1,
+        1.615858368580409e2,
+        -1.556989798598866e2,
+        6.680131188771972e1,
+        -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3,
+        -3.223964580411365e-1,
+        -2.400758277161838,
+        -2.549732539343734,
+        4.374664141464968,
+        2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-3,
+        3.224671290700398e-1,
+        2.445134137142996,
+        3.754408661907416,
+    ];
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    let low = 0.02425;
+    if p < low {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - low {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distributions() {
+        let normal = Distribution::Normal {
+            mean: 2.0,
+            std_dev: 0.5,
+        };
+        assert!((normal.quantile(0.975) - (2.0 + 0.5 * 1.959963984540054)).abs() < 1e-8);
+        assert!((normal.quantile(0.01) - (2.0 - 0.5 * 2.326347874040841)).abs() < 1e-8);
+        assert!((normal.quantile(0.5) - 2.0).abs() < 1e-14, "Wrong median");
+        let log_normal = Distribution::LogNormal {
+            mu: 0.0,
+            sigma: 1.0,
+        };
+        assert!(
+            (log_normal.quantile(0.5) - 1.0).abs() < 1e-14,
+            "Wrong median"
+        );
+        let triangular = Distribution::Triangular {
+            lower: 0.0,
+            mode: 1.0,
+            upper: 4.0,
+        };
+        assert!(
+            (triangular.quantile(0.25) - 1.0).abs() < 1e-14,
+            "Wrong mode"
+        );
+        assert!((triangular.mean() - 5.0 / 3.0).abs() < 1e-14, "Wrong mean");
+        assert_eq!(
+            sorted_quantile(&[1.0, 2.0, 4.0], 0.75),
+            3.0,
+            "Wrong quantile"
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_monte_carlo() {
+        let study = MonteCarlo::new(vec![
+            RandomParameter::new(
+                "a",
+                Distribution::Uniform {
+                    lower: 0.0,
+                    upper: 1.0,
+                },
+            ),
+            RandomParameter::new(
+                "b",
+                Distribution::Normal {
+                    mean: 2.0,
+                    std_dev: 0.5,
+                },
+            ),
+        ])
+        .with_n_samples(4000)
+        .with_seed(3);
+        let run = |p: &Parameters| {
+            let (a, b) = (p.get("a"), p.get("b"));
+            (vec![("sum".to_string(), a + b)], vec![a, b * b])
+        };
+        let results = study.run_with_fields(run);
+        assert_eq!(results.columns(), ["a", "b", "sum"], "Wrong columns");
+        let sum = results.statistics("sum");
+        assert!((sum.mean - 2.5).abs() < 0.03, "Wrong mean {}", sum.mean);
+        let variance = 1.0 / 12.0 + 0.25;
+        assert!((sum.variance - variance).abs() < 0.03, "Wrong variance");
+        assert!(
+            (results.quantile("b", 0.5) - 2.0).abs() < 0.03,
+            "Wrong median"
+        );
+        // E[b²] = 4 + 0.25
+        let field = results.field_mean();
+        assert!(
+            (field[1] - 4.25).abs() < 0.06,
+            "Wrong field mean {}",
+            field[1]
+        );
+        assert!(results.field_quantile(0.0)[0] >= 0.0, "Wrong field minimum");
+        assert_eq!(
+            study.clone().with_n_threads(4).run_with_fields(run),
+            results,
+            "Samples depend on the threads"
+        );
+        // Stratification integrates the linear quantity almost exactly
+        let stratified = study
+            .with_n_samples(200)
+            .with_latin_hypercube()
+            .run(|p| vec![("value".to_string(), p.get("a"))]);
+        let a = stratified.column("a");
+        assert!(
+            (stratified.statistics("a").mean - 0.5).abs() < 1e-3,
+            "Wrong mean"
+        );
+        let mut strata: Vec<usize> = a.iter().map(|a| (a * 200.0) as usize).collect();
+        strata.sort();
+        assert_eq!(
+            strata,
+            (0..200).collect::<Vec<_>>(),
+            "Strata are not covered"
+        );
+        assert!(
+            stratified
+                .summary_csv()
+                .starts_with("name,mean,std_dev,min,p05,median,p95,max\na,"),
+            "Wrong summary"
+        );
+    }
+}
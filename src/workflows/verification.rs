@@ -0,0 +1,394 @@
+use super::convergence_study::{ConvergenceReport, ConvergenceStudy, ErrorNorm, Sweep};
+use crate::core::expression::Expression;
+use crate::core::logging::{info, Span};
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+use crate::discretizations::mesh::Mesh;
+use std::io::Result;
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Manufactured solution given by an expression of every component, whose forcing terms are
+/// derived symbolically for the operators of the workflows
+///
+/// Solutions may depend on the time, in which case the studies compare with the solution at a
+/// given time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ManufacturedSolution {
+    dim: usize,
+    components: Vec<Expression>,
+}
+
+/// Comparison of an observed convergence rate with the expected one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateCheck {
+    /// Norm of the errors
+    pub norm: ErrorNorm,
+    /// Expected rate
+    pub expected: f64,
+    /// Rate observed between the last two levels
+    pub observed: f64,
+    /// Whether the observed rate lies within the tolerance of the expected one
+    pub passed: bool,
+}
+
+/// Convergence report of a verification with the checks of its rates
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerificationReport {
+    report: ConvergenceReport,
+    checks: Vec<RateCheck>,
+}
+
+/// Verification of a solver against a manufactured solution by a convergence study asserting the
+/// orders of accuracy
+///
+/// The solver receives the spaces of the levels and has to impose the forcing terms and boundary
+/// values of the solution. The rate observed between the last two levels is checked for every
+/// expected rate, which are p + 1 in the L2 norm and p in the H1 seminorm for refinements of
+/// elements of order p unless given, within a tolerance of 0.15 by default. The sweep defaults to
+/// the one of ConvergenceStudy.
+pub struct Verification<'a> {
+    mesh: &'a Mesh,
+    solution: &'a ManufacturedSolution,
+    time: f64,
+    sweep: Sweep,
+    expected: Vec<(ErrorNorm, f64)>,
+    tolerance: f64,
+}
+
+impl ManufacturedSolution {
+    /// Solution of expressions of its components on a domain of a dimension
+    pub fn new(dim: usize, components: Vec<Expression>) -> Self {
+        assert!((1..=3).contains(&dim), "Dimension should be 1, 2 or 3");
+        assert!(!components.is_empty(), "Solutions need components");
+        ManufacturedSolution { dim, components }
+    }
+
+    /// Solution of the sources of the expressions of its components
+    pub fn parse(dim: usize, sources: &[&str]) -> Result<Self> {
+        let components = sources
+            .iter()
+            .map(|source| Expression::parse(source))
+            .collect::<Result<Vec<Expression>>>()?;
+        Ok(ManufacturedSolution::new(dim, components))
+    }
+
+    /// Dimension of the domain
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Number of components
+    pub fn n_components(&self) -> usize {
+        self.components.len()
+    }
+
+    /// Expression of a component
+    pub fn component(&self, component: usize) -> &Expression {
+        &self.components[component]
+    }
+
+    /// Values of the components at a point and a time
+    pub fn value(&self, x: &[f64], t: f64, u: &mut [f64]) {
+        for (u, component) in u.iter_mut().zip(&self.components) {
+            *u = component.eval(x, t);
+        }
+    }
+
+    /// Gradient of the components as a flat array of size (components, dimension)
+    pub fn gradient(&self) -> Vec<Expression> {
+        self.components
+            .iter()
+            .flat_map(|u| (0..self.dim).map(move |k| u.derivative(k)))
+            .collect()
+    }
+
+    /// Source f = -k Δu of the Poisson problem of a scalar solution for a conductivity k
+    pub fn poisson_source(&self, conductivity: f64) -> Expression {
+        -(conductivity * self.scalar().laplacian(self.dim))
+    }
+
+    /// Source f = c du/dt - k Δu of the heat equation of a scalar solution
+    pub fn heat_source(&self, capacity: f64, conductivity: f64) -> Expression {
+        let u = self.scalar();
+        capacity * u.time_derivative() - conductivity * u.laplacian(self.dim)
+    }
+
+    /// Source f = b·grad(u) - k Δu of the steady advection diffusion of a scalar solution by a
+    /// uniform velocity b
+    pub fn advection_diffusion_source(&self, diffusivity: f64, velocity: &[f64]) -> Expression {
+        assert_eq!(
+            velocity.len(),
+            self.dim,
+            "Velocity does not match the dimension"
+        );
+        let u = self.scalar();
+        velocity
+            .iter()
+            .enumerate()
+            .fold(-(diffusivity * u.laplacian(self.dim)), |f, (k, b)| {
+                f + *b * u.derivative(k)
+            })
+    }
+
+    /// Body force f = -μ Δu - (λ + μ) grad(div u) of the linear elasticity of a displacement
+    /// with the Lamé parameters
+    pub fn elasticity_body_force(&self, lambda: f64, mu: f64) -> Vec<Expression> {
+        assert_eq!(
+            self.n_components(),
+            self.dim,
+            "Displacements need one component per dimension"
+        );
+        let divergence = (0..self.dim).fold(Expression::constant(0.0), |div, k| {
+            div + self.components[k].derivative(k)
+        });
+        (0..self.dim)
+            .map(|i| {
+                -(mu * self.components[i].laplacian(self.dim))
+                    - (lambda + mu) * divergence.derivative(i)
+            })
+            .collect()
+    }
+
+    /// Convergence study against the solution and its gradient at a time
+    pub fn study<'a>(&self, mesh: &'a Mesh, time: f64) -> ConvergenceStudy<'a> {
+        assert_eq!(
+            mesh.geometric_dim(),
+            self.dim,
+            "Mesh does not match the dimension of the solution"
+        );
+        let solution = self.clone();
+        let gradient = self.gradient();
+        ConvergenceStudy::vector(mesh, self.n_components(), move |x, u| {
+            solution.value(x, time, u)
+        })
+        .with_gradient(move |x, g| {
+            for (g, derivative) in g.iter_mut().zip(&gradient) {
+                *g = derivative.eval(x, time);
+            }
+        })
+    }
+
+    // Only component of a scalar solution
+    fn scalar(&self) -> &Expression {
+        assert_eq!(
+            self.n_components(),
+            1,
+            "The operator needs a scalar solution"
+        );
+        &self.components[0]
+    }
+}
+
+impl VerificationReport {
+    /// Errors and rates of the levels
+    pub fn report(&self) -> &ConvergenceReport {
+        &self.report
+    }
+
+    /// Checks of the expected rates
+    pub fn checks(&self) -> &[RateCheck] {
+        &self.checks
+    }
+
+    /// Whether all the rates are within the tolerance
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    /// Panic with the failed checks unless all the rates are within the tolerance
+    pub fn assert_passed(&self) {
+        let failures: Vec<String> = self
+            .checks
+            .iter()
+            .filter(|check| !check.passed)
+            .map(|check| {
+                format!(
+                    "{:?} rate {} instead of {}",
+                    check.norm, check.observed, check.expected
+                )
+            })
+            .collect();
+        assert!(
+            failures.is_empty(),
+            "Verification failed: {}",
+            failures.join(", ")
+        );
+    }
+}
+
+impl<'a> Verification<'a> {
+    /// Verification on a mesh against a manufactured solution with the default parameters
+    pub fn new(mesh: &'a Mesh, solution: &'a ManufacturedSolution) -> Self {
+        Verification {
+            mesh,
+            solution,
+            time: 0.0,
+            sweep: Sweep::Refinements {
+                levels: 4,
+                order: 1,
+            },
+            expected: Vec::new(),
+            tolerance: 0.15,
+        }
+    }
+
+    /// Set the time of the solution compared with, the final time of transient solves
+    pub fn with_time(mut self, time: f64) -> Self {
+        self.time = time;
+        self
+    }
+
+    /// Set the sequence of discretizations
+    pub fn with_sweep(mut self, sweep: Sweep) -> Self {
+        self.sweep = sweep;
+        self
+    }
+
+    /// Expect a rate in a norm, replacing the default expectations
+    pub fn with_expected_rate(mut self, norm: ErrorNorm, rate: f64) -> Self {
+        self.expected.retain(|(n, _)| *n != norm);
+        self.expected.push((norm, rate));
+        self
+    }
+
+    /// Set the largest difference between the observed and the expected rates
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        assert!(tolerance > 0.0, "Tolerance should be positive");
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Run the convergence study of the solver and check its rates
+    pub fn run<Solve>(&self, solve: Solve) -> VerificationReport
+    where
+        Solve: for<'b> Fn(&'b FunctionSpace<'b>) -> Function<'b>,
+    {
+        let _span = Span::enter("verification");
+        let expected = match (&self.sweep, self.expected.is_empty()) {
+            (Sweep::Refinements { order, .. }, true) => vec![
+                (ErrorNorm::L2, (order + 1) as f64),
+                (ErrorNorm::H1Seminorm, *order as f64),
+            ],
+            _ => self.expected.clone(),
+        };
+        assert!(!expected.is_empty(), "Sweeps of orders need expected rates");
+        let report = self
+            .solution
+            .study(self.mesh, self.time)
+            .with_sweep(self.sweep.clone())
+            .run(solve);
+        let checks: Vec<RateCheck> = expected
+            .iter()
+            .map(|(norm, rate)| {
+                let observed = report
+                    .rates(*norm)
+                    .and_then(|rates| rates.last().copied())
+                    .expect("Verification needs at least two levels");
+                RateCheck {
+                    norm: *norm,
+                    expected: *rate,
+                    observed,
+                    passed: (observed - rate).abs() <= self.tolerance,
+                }
+            })
+            .collect();
+        info!(
+            "Verification {} with rates {:?}",
+            if checks.iter().all(|c| c.passed) {
+                "passed"
+            } else {
+                "failed"
+            },
+            checks.iter().map(|c| c.observed).collect::<Vec<f64>>()
+        );
+        VerificationReport { report, checks }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::cartesian_grid::CartesianGrid;
+    use crate::workflows::advection_diffusion::{AdvectionDiffusion, Stabilization};
+    use crate::workflows::elasticity::{IsotropicMaterial, LinearElasticity};
+
+    // Poisson solve with the boundary values and the source of a manufactured solution
+    fn poisson<'b>(
+        space: &'b FunctionSpace<'b>,
+        solution: &ManufacturedSolution,
+        source: &Expression,
+    ) -> Function<'b> {
+        let source = source.clone();
+        (1..=4)
+            .fold(AdvectionDiffusion::new(space), |problem, tag| {
+                let exact = solution.component(0).clone();
+                problem.with_dirichlet(tag, move |x| exact.eval(x, 0.0))
+            })
+            .with_stabilization(Stabilization::Galerkin)
+            .with_source(move |x| source.eval(x, 0.0))
+            .solve()
+    }
+
+    // Elasticity solve with the boundary displacements and the body force of a manufactured
+    // solution
+    fn elasticity<'b>(
+        space: &'b FunctionSpace<'b>,
+        solution: &ManufacturedSolution,
+        material: IsotropicMaterial,
+    ) -> Function<'b> {
+        let (lambda, mu) = material.lame_parameters();
+        let force = solution.elasticity_body_force(lambda, mu);
+        (1..=4)
+            .fold(LinearElasticity::new(space, material), |problem, tag| {
+                let solution = solution.clone();
+                problem.with_displacement(tag, move |x, u| solution.value(x, 0.0, u))
+            })
+            .with_body_force(move |x, f| {
+                for (f, component) in f.iter_mut().zip(&force) {
+                    *f = component.eval(x, 0.0);
+                }
+            })
+            .solve()
+    }
+
+    #[test]
+    fn test_poisson_verification() {
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![2, 2]).simplex_mesh();
+        let solution = ManufacturedSolution::parse(2, &["exp(x) * sin(2*y) + x^2 * y"]).unwrap();
+        let source = solution.poisson_source(1.0);
+        for order in [1, 2] {
+            Verification::new(&mesh, &solution)
+                .with_sweep(Sweep::Refinements { levels: 4, order })
+                .run(|space| poisson(space, &solution, &source))
+                .assert_passed();
+        }
+        let report = Verification::new(&mesh, &solution)
+            .with_expected_rate(ErrorNorm::L2, 3.0)
+            .run(|space| poisson(space, &solution, &source));
+        assert!(
+            !report.passed() && report.checks().len() == 1,
+            "Linear elements should not converge at third order"
+        );
+    }
+
+    #[test]
+    fn test_elasticity_verification() {
+        let mesh = CartesianGrid::new(vec![0.0, 0.0], vec![1.0, 1.0], vec![2, 2]).simplex_mesh();
+        let solution = ManufacturedSolution::parse(2, &["sin(x) * y^2", "cos(y) + x * y"]).unwrap();
+        let material = IsotropicMaterial::new(2.0, 0.3);
+        Verification::new(&mesh, &solution)
+            .with_sweep(Sweep::Refinements {
+                levels: 4,
+                order: 2,
+            })
+            .run(|space| elasticity(space, &solution, material))
+            .assert_passed();
+    }
+}
@@ -0,0 +1,524 @@
+use crate::core::arrays::sparse_csr::SparseCSR;
+use crate::core::logging::{debug, info, ProgressBar, Span};
+use crate::discretizations::assembler::Assembler;
+use crate::discretizations::function::Function;
+use crate::discretizations::function_space::FunctionSpace;
+use crate::discretizations::operators::{mass_matrix, stiffness_matrix};
+use crate::solvers::krylov::{dot, norm};
+use crate::solvers::sparse_direct::SparseCholesky;
+use crate::solvers::time_integration::{
+    DynamicState, DynamicsIntegrator, DynamicsScheme, SecondOrderSystem,
+};
+use crate::spaces::quadrature::QuadratureRule;
+
+// Scalar field of the time and the physical coordinates
+type Field<'a> = Box<dyn Fn(f64, &[f64]) -> f64 + 'a>;
+
+// Number of power iterations estimating the highest frequency
+const POWER_ITERATIONS: usize = 200;
+
+//--------------------------------------------------------------------------------------------------
+// # Enums
+//--------------------------------------------------------------------------------------------------
+
+/// Time schemes of the wave equation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WaveScheme {
+    /// Implicit scheme of the dynamics integrator, factorized once
+    Implicit(DynamicsScheme),
+    /// Explicit central differences in their velocity Verlet form, stable for time steps below
+    /// the critical one
+    CentralDifference,
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Structs
+//--------------------------------------------------------------------------------------------------
+
+/// Transient scalar wave equation d²u/dt² - c² Δu = f on a continuous scalar space
+///
+/// Tagged boundary facets are fixed (u = 0) and the rest of the boundary is free. The mass matrix
+/// is either the consistent one or the one lumped on every cell by scaling its diagonal to the
+/// cell measure, which stays positive for any order and makes central differences fully explicit.
+/// By default the wave speed c is 1, there is no source, the mass is consistent and the scheme is
+/// the average acceleration Newmark method.
+pub struct WaveEquation<'a> {
+    space: &'a FunctionSpace<'a>,
+    speed: f64,
+    source: Option<Field<'a>>,
+    fixed: Vec<usize>,
+    lumped: bool,
+    scheme: WaveScheme,
+    progress: bool,
+}
+
+// Mass matrix of the wave equation with its diagonal when lumped
+struct Mass {
+    matrix: SparseCSR<f64>,
+    lumped: Option<Vec<f64>>,
+}
+
+impl<'a> WaveEquation<'a> {
+    /// Wave equation on the space with the default parameters
+    pub fn new(space: &'a FunctionSpace<'a>) -> Self {
+        assert!(
+            space.n_components() == 1 && !space.is_discontinuous(),
+            "The wave equation needs a continuous scalar space"
+        );
+        WaveEquation {
+            space,
+            speed: 1.0,
+            source: None,
+            fixed: Vec::new(),
+            lumped: false,
+            scheme: WaveScheme::Implicit(DynamicsScheme::Newmark(0.25, 0.5)),
+            progress: false,
+        }
+    }
+
+    /// Set the wave speed c
+    pub fn with_wave_speed(mut self, speed: f64) -> Self {
+        assert!(speed > 0.0, "Wave speed should be positive");
+        self.speed = speed;
+        self
+    }
+
+    /// Set the source f as a function of the time and the physical coordinates
+    pub fn with_source<Source>(mut self, source: Source) -> Self
+    where
+        Source: Fn(f64, &[f64]) -> f64 + 'a,
+    {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// Fix the boundary facets carrying the tag
+    pub fn with_fixed_boundary(mut self, tag: usize) -> Self {
+        self.fixed.push(tag);
+        self
+    }
+
+    /// Use the lumped mass matrix
+    pub fn with_lumped_mass(mut self) -> Self {
+        self.lumped = true;
+        self
+    }
+
+    /// Set the time scheme
+    pub fn with_scheme(mut self, scheme: WaveScheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    /// Show a terminal progress bar of the time steps
+    pub fn with_progress(mut self) -> Self {
+        self.progress = true;
+        self
+    }
+
+    /// Critical time step 2 / ω_max of central differences, the highest angular frequency ω_max
+    /// of the discrete system being estimated by power iterations
+    ///
+    /// The estimate of ω_max converges from below, so time steps should keep a safety margin.
+    pub fn critical_time_step(&self) -> f64 {
+        let assembler = self.assembler();
+        let (mass, stiffness) = self.matrices(&assembler);
+        let n = self.space.n_dofs();
+        let cholesky = mass.factorization();
+        // Deterministic start rich in all the modes
+        let mut x: Vec<f64> = (0..n).map(|i| ((i * 7919) % 257) as f64 - 128.0).collect();
+        let (mut kx, mut mx) = (vec![0.0; n], vec![0.0; n]);
+        let mut omega2 = 0.0;
+        for _ in 0..POWER_ITERATIONS {
+            stiffness.apply(&x, &mut kx);
+            mass.matrix.apply(&x, &mut mx);
+            omega2 = dot(&x, &kx) / dot(&x, &mx);
+            mass.solve(cholesky.as_ref(), &kx, &mut x);
+            let scale = norm(&x);
+            x.iter_mut().for_each(|x| *x /= scale);
+        }
+        2.0 / omega2.sqrt()
+    }
+
+    /// Integrate from the start to the end time, u and v holding the initial displacement and
+    /// velocity and then the ones of every step, which are also passed to the observer along with
+    /// the start time
+    ///
+    /// The time step is reduced if needed so that a whole number of steps ends on the final time,
+    /// which is returned. The displacement and velocity of the fixed boundaries are set to 0.
+    pub fn solve<Observer>(
+        &self,
+        u: &mut Function<'a>,
+        v: &mut Function<'a>,
+        start: f64,
+        end: f64,
+        time_step: f64,
+        mut observer: Observer,
+    ) -> usize
+    where
+        Observer: FnMut(f64, &Function, &Function),
+    {
+        assert!(
+            std::ptr::eq(u.space(), self.space) && std::ptr::eq(v.space(), self.space),
+            "Displacement and velocity do not live on the space of the problem"
+        );
+        assert!(time_step > 0.0, "Time step should be positive");
+        let _span = Span::enter("wave equation");
+        let n_steps = ((end - start) / time_step - 1e-10).ceil().max(1.0) as usize;
+        info!(
+            "Wave equation on {} dofs in {} steps",
+            self.space.n_dofs(),
+            n_steps
+        );
+        let mut progress = if self.progress {
+            ProgressBar::new("time steps", n_steps)
+        } else {
+            ProgressBar::hidden("time steps", n_steps)
+        };
+        let mut observer = |time: f64, u: &Function, v: &Function| {
+            if time > start {
+                debug!("Step {} at time {}", progress.done() + 1, time);
+                progress.inc();
+            }
+            observer(time, u, v)
+        };
+        let assembler = self.assembler();
+        let (mass, stiffness) = self.matrices(&assembler);
+        let fixed = self.fixed_dofs();
+        for dof in &fixed {
+            u.values_mut()[*dof] = 0.0;
+            v.values_mut()[*dof] = 0.0;
+        }
+        let load = |time: f64, f: &mut [f64]| {
+            self.load(&assembler, time, f);
+            fixed.iter().for_each(|dof| f[*dof] = 0.0);
+        };
+        observer(start, u, v);
+        match self.scheme {
+            WaveScheme::Implicit(scheme) => {
+                let system = SecondOrderSystem::new(&mass.matrix, &stiffness);
+                let mut state = DynamicState::new(u.values().to_vec(), v.values().to_vec());
+                DynamicsIntegrator::new(scheme, time_step).integrate(
+                    &system,
+                    load,
+                    start,
+                    end,
+                    &mut state,
+                    |time, state| {
+                        u.values_mut().copy_from_slice(state.displacement());
+                        v.values_mut().copy_from_slice(state.velocity());
+                        observer(time, u, v);
+                    },
+                )
+            }
+            WaveScheme::CentralDifference => {
+                let dt = (end - start) / n_steps as f64;
+                let cholesky = mass.factorization();
+                let n = self.space.n_dofs();
+                let (mut f, mut balance, mut a) = (vec![0.0; n], vec![0.0; n], vec![0.0; n]);
+                // Acceleration M^-1 (f - K u) at a time
+                let mut accelerate = |time: f64, u: &[f64], a: &mut [f64]| {
+                    load(time, &mut f);
+                    stiffness.apply(u, &mut balance);
+                    balance.iter_mut().zip(&f).for_each(|(b, f)| *b = f - *b);
+                    mass.solve(cholesky.as_ref(), &balance, a);
+                };
+                accelerate(start, u.values(), &mut a);
+                for step in 0..n_steps {
+                    let time = start + (step + 1) as f64 * dt;
+                    let velocity = v.values_mut();
+                    velocity
+                        .iter_mut()
+                        .zip(&a)
+                        .for_each(|(v, a)| *v += 0.5 * dt * a);
+                    u.values_mut()
+                        .iter_mut()
+                        .zip(velocity.iter())
+                        .for_each(|(u, v)| *u += dt * v);
+                    accelerate(time, u.values(), &mut a);
+                    v.values_mut()
+                        .iter_mut()
+                        .zip(&a)
+                        .for_each(|(v, a)| *v += 0.5 * dt * a);
+                    observer(time, u, v);
+                }
+                n_steps
+            }
+        }
+    }
+
+    // Assembler exact for the mass matrix
+    fn assembler(&self) -> Assembler<'_> {
+        let element = self.space.element();
+        self.space
+            .assembler(QuadratureRule::simplex(element.dim(), 2 * element.order()))
+    }
+
+    // Dofs of the fixed boundaries
+    fn fixed_dofs(&self) -> Vec<usize> {
+        let mut dofs: Vec<usize> = self
+            .fixed
+            .iter()
+            .flat_map(|tag| self.space.tagged_dofs(*tag))
+            .collect();
+        dofs.sort_unstable();
+        dofs.dedup();
+        dofs
+    }
+
+    // Mass matrix and stiffness c² K with the rows and columns of the fixed dofs replaced by the
+    // ones of the identity in the mass and removed from the stiffness, so that they stay at rest
+    fn matrices(&self, assembler: &Assembler) -> (Mass, SparseCSR<f64>) {
+        let n = self.space.n_dofs();
+        let mut is_fixed = vec![false; n];
+        self.fixed_dofs()
+            .into_iter()
+            .for_each(|dof| is_fixed[dof] = true);
+        let mut stiffness = stiffness_matrix(assembler, |_| self.speed * self.speed);
+        fix_dofs(&mut stiffness, &is_fixed, 0.0);
+        let mass = if self.lumped {
+            let mut diagonal = vec![0.0; n];
+            assembler.assemble_vector(&mut diagonal, |values, local| {
+                let mut measure = 0.0;
+                for q in 0..values.n_points() {
+                    measure += values.weight(q);
+                    for i in 0..values.n_dofs() {
+                        local[i] += values.shape_value(q, i).powi(2) * values.weight(q);
+                    }
+                }
+                let total: f64 = (0..values.n_dofs()).map(|i| local[i]).sum();
+                (0..values.n_dofs()).for_each(|i| local[i] *= measure / total);
+            });
+            diagonal
+                .iter_mut()
+                .zip(&is_fixed)
+                .filter(|(_, fixed)| **fixed)
+                .for_each(|(d, _)| *d = 1.0);
+            let matrix = SparseCSR::new(n, (0..=n).collect(), (0..n).collect(), diagonal.clone());
+            Mass {
+                matrix,
+                lumped: Some(diagonal),
+            }
+        } else {
+            let mut matrix = mass_matrix(assembler);
+            fix_dofs(&mut matrix, &is_fixed, 1.0);
+            Mass {
+                matrix,
+                lumped: None,
+            }
+        };
+        (mass, stiffness)
+    }
+
+    // Load vector of the source at a time
+    fn load(&self, assembler: &Assembler, time: f64, load: &mut [f64]) {
+        load.iter_mut().for_each(|f| *f = 0.0);
+        if let Some(source) = &self.source {
+            assembler.assemble_vector(load, |values, local| {
+                for q in 0..values.n_points() {
+                    let f = source(time, values.point(q)) * values.weight(q);
+                    for i in 0..values.n_dofs() {
+                        local[i] += f * values.shape_value(q, i);
+                    }
+                }
+            });
+        }
+    }
+}
+
+impl Mass {
+    // Cholesky factorization of a consistent mass
+    fn factorization(&self) -> Option<SparseCholesky> {
+        match self.lumped {
+            Some(_) => None,
+            None => Some(SparseCholesky::new(&self.matrix).expect("Mass matrix is singular")),
+        }
+    }
+
+    // Solve M x = b with the lumped diagonal or the factorization
+    fn solve(&self, cholesky: Option<&SparseCholesky>, b: &[f64], x: &mut [f64]) {
+        match (&self.lumped, cholesky) {
+            (Some(diagonal), _) => x
+                .iter_mut()
+                .zip(b.iter().zip(diagonal))
+                .for_each(|(x, (b, d))| *x = b / d),
+            (None, Some(cholesky)) => cholesky.solve(b, x),
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Functions
+//--------------------------------------------------------------------------------------------------
+
+// Zero the rows and columns of the fixed dofs and set their diagonal, which is in the pattern
+fn fix_dofs(matrix: &mut SparseCSR<f64>, is_fixed: &[bool], diagonal: f64) {
+    for (row, fixed_row) in is_fixed.iter().enumerate() {
+        let (start, end) = (matrix.row_offsets()[row], matrix.row_offsets()[row + 1]);
+        for position in start..end {
+            let col = matrix.col_indices()[position];
+            if col == row && *fixed_row {
+                matrix.values_mut()[position] = diagonal;
+            } else if *fixed_row || is_fixed[col] {
+                matrix.values_mut()[position] = 0.0;
+            }
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// # Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discretizations::refinement::Refinement;
+    use crate::discretizations::test_meshes::{unit_square, unit_square_grid};
+    use crate::spaces::lagrange::LagrangeElement;
+    use std::panic::{self, AssertUnwindSafe};
+
+    // Description of an invalid input with the construction it should make panic
+    type Invalid<'a> = (&'a str, Box<dyn Fn() + 'a>);
+
+    #[test]
+    fn test_wave_equation() {
+        let mut mesh = unit_square();
+        for _ in 0..3 {
+            mesh = Refinement::uniform(&mesh).into_mesh();
+        }
+        mesh.tag_boundary(1, |x| x[0] == 0.0 || x[0] == 1.0);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        // Standing wave sin(π x) cos(π c t) of a string fixed at both ends, back inverted at t = 1/c
+        let c = 2.0;
+        let string = || {
+            WaveEquation::new(&space)
+                .with_wave_speed(c)
+                .with_fixed_boundary(1)
+        };
+        let mut expected = Function::new(&space);
+        expected.interpolate(|x| -(std::f64::consts::PI * x[0]).sin());
+        let implicit = string();
+        let explicit = string()
+            .with_lumped_mass()
+            .with_scheme(WaveScheme::CentralDifference);
+        // Lumping lowers the highest frequency and relaxes the stability limit
+        let critical = explicit.critical_time_step();
+        assert!(
+            critical > implicit.critical_time_step(),
+            "Lumped mass should allow larger explicit steps"
+        );
+        for (problem, time_step, tolerance) in
+            [(&implicit, 0.01, 2e-2), (&explicit, 0.5 * critical, 1e-3)]
+        {
+            let mut u = Function::new(&space);
+            u.interpolate(|x| (std::f64::consts::PI * x[0]).sin());
+            let mut v = Function::new(&space);
+            let mut n_observed = 0;
+            let n_steps = problem.solve(&mut u, &mut v, 0.0, 1.0 / c, time_step, |_, _, _| {
+                n_observed += 1;
+            });
+            assert_eq!(n_observed, n_steps + 1, "Every step should be observed");
+            let error = u
+                .values()
+                .iter()
+                .zip(expected.values())
+                .map(|(u, e)| (u - e).abs())
+                .fold(0.0, f64::max);
+            assert!(error < tolerance, "Standing wave error {}", error);
+        }
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_wave_source() {
+        let mesh = unit_square_grid(2);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        // A uniform source accelerates a free body at rest as u = t² / 2, reproduced by every
+        // scheme, the step of 0.3 being reduced to 0.25
+        for problem in [
+            WaveEquation::new(&space).with_progress(),
+            WaveEquation::new(&space).with_lumped_mass(),
+            WaveEquation::new(&space).with_scheme(WaveScheme::CentralDifference),
+            WaveEquation::new(&space)
+                .with_lumped_mass()
+                .with_scheme(WaveScheme::CentralDifference),
+        ] {
+            let problem = problem.with_wave_speed(3.0).with_source(|_, _| 1.0);
+            let (mut u, mut v) = (Function::new(&space), Function::new(&space));
+            let mut times = Vec::new();
+            let n_steps =
+                problem.solve(&mut u, &mut v, 0.0, 1.0, 0.3, |time, _, _| times.push(time));
+            assert_eq!(n_steps, 4, "Wrong number of steps");
+            assert!(
+                (times[1] - 0.25).abs() < 1e-12 && (times[4] - 1.0).abs() < 1e-12,
+                "Wrong times {:?}",
+                times
+            );
+            assert!(
+                u.values().iter().all(|u| (u - 0.5).abs() < 1e-8)
+                    && v.values().iter().all(|v| (v - 1.0).abs() < 1e-8),
+                "Wrong uniform motion"
+            );
+        }
+        // Fixed boundaries are put and kept at rest
+        let problem = WaveEquation::new(&space).with_fixed_boundary(1);
+        let fixed = space.tagged_dofs(1);
+        let (mut u, mut v) = (Function::new(&space), Function::new(&space));
+        u.interpolate(|_| 1.0);
+        v.interpolate(|_| 1.0);
+        problem.solve(&mut u, &mut v, 0.0, 0.5, 0.1, |_, u, v| {
+            assert!(
+                fixed
+                    .iter()
+                    .all(|dof| u.values()[*dof] == 0.0 && v.values()[*dof] == 0.0),
+                "A fixed boundary moved"
+            );
+        });
+        assert!(
+            u.values().iter().any(|u| *u != 0.0),
+            "The free part should keep moving"
+        );
+    }
+
+    //----------------------------------------------------------------------------------------------
+    #[test]
+    fn test_wave_invalid() {
+        let mesh = unit_square_grid(2);
+        let space = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let other = FunctionSpace::new(&mesh, LagrangeElement::new(2, 1));
+        let vector = FunctionSpace::vector(&mesh, LagrangeElement::new(2, 1), 2);
+        let broken = FunctionSpace::discontinuous(&mesh, LagrangeElement::new(2, 1));
+        let solve = |u_space, time_step| {
+            let (mut u, mut v) = (Function::new(u_space), Function::new(&space));
+            WaveEquation::new(&space).solve(&mut u, &mut v, 0.0, 1.0, time_step, |_, _, _| {});
+        };
+        let cases: [Invalid; 5] = [
+            (
+                "a vector space",
+                Box::new(|| drop(WaveEquation::new(&vector))),
+            ),
+            (
+                "a discontinuous space",
+                Box::new(|| drop(WaveEquation::new(&broken))),
+            ),
+            (
+                "a zero wave speed",
+                Box::new(|| drop(WaveEquation::new(&space).with_wave_speed(0.0))),
+            ),
+            ("a zero time step", Box::new(|| solve(&space, 0.0))),
+            (
+                "a displacement on another space",
+                Box::new(|| solve(&other, 0.1)),
+            ),
+        ];
+        for (case, build) in cases {
+            assert!(
+                panic::catch_unwind(AssertUnwindSafe(build)).is_err(),
+                "Wave equation accepted {}",
+                case
+            );
+        }
+    }
+}
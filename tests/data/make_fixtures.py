@@ -0,0 +1,457 @@
+"""Write the MED and Exodus II fixtures of the readers of fe2o3.
+
+The files are encoded from the HDF5, MED, NetCDF and Exodus II specifications, independently of
+the encoders of the unit tests, with the layout the reference libraries use by default:
+
+- plate.med follows MED 4.1 on HDF5 with the default (earliest) file format: version 0
+  superblock, version 1 object headers, groups indexed by symbol tables, contiguous datasets,
+  32 bit integers and the node coordinates and connectivities stored without interlacing.
+- plate.exo follows the Exodus II API in the 64-bit offset NetCDF format: coordinates split by
+  direction, one TRI3 block, a side set, a node set, QA records and a nodal variable over two
+  time steps.
+
+Both hold the rectangle [0, 2] x [0, 1] cut in five triangles around the node (1, 0.5).
+
+Run with `python3 tests/data/make_fixtures.py` from the root of the repository.
+"""
+
+import os
+import struct
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+
+# Nodes, triangles (1 based), bottom and right edges of the plate
+NODES = [(0.0, 0.0), (2.0, 0.0), (2.0, 1.0), (0.0, 1.0), (1.0, 0.5), (1.0, 0.0)]
+TRIANGLES = [(1, 6, 5), (6, 2, 5), (2, 3, 5), (3, 4, 5), (4, 1, 5)]
+BOTTOM = [(1, 6), (6, 2)]
+RIGHT = [(2, 3)]
+
+# --------------------------------------------------------------------------------------------------
+# HDF5
+# --------------------------------------------------------------------------------------------------
+
+UNDEFINED = 0xFFFFFFFFFFFFFFFF
+GROUP_LEAF_K = 4
+GROUP_INTERNAL_K = 16
+
+
+def pad8(data):
+    return data + b"\0" * (-len(data) % 8)
+
+
+class Dataset:
+    def __init__(self, kind, values, attributes=()):
+        self.kind = kind
+        self.values = values
+        self.attributes = list(attributes)
+
+
+class Group:
+    def __init__(self, children=(), attributes=()):
+        self.children = dict(children)
+        self.attributes = list(attributes)
+
+
+def int32(value):
+    return ("int32", [value])
+
+
+def float64(value):
+    return ("float64", [value])
+
+
+def string(value, size):
+    return ("string", value, size)
+
+
+def datatype(kind, size=None):
+    if kind == "int32":
+        # Fixed point, little endian, signed, 32 bits at offset 0
+        return struct.pack("<B3sIHH", 0x10, b"\x08\0\0", 4, 0, 32)
+    if kind == "int8":
+        return struct.pack("<B3sIHH", 0x10, b"\x08\0\0", 1, 0, 8)
+    if kind == "float64":
+        # IEEE double: sign at bit 63, 11 exponent bits at 52 biased by 1023, 52 mantissa bits
+        return struct.pack("<B3sIHHBBBBI", 0x11, b"\x20\x3f\0", 8, 0, 64, 52, 11, 0, 52, 1023)
+    if kind == "string":
+        # Null terminated ASCII string
+        return struct.pack("<B3sI", 0x13, b"\0\0\0", size)
+    raise ValueError(kind)
+
+
+def dataspace(dimensions):
+    # Version 1 with the maximum dimensions equal to the current ones
+    flags = 1 if dimensions else 0
+    data = struct.pack("<BBBB4x", 1, len(dimensions), flags, 0)
+    data += b"".join(struct.pack("<Q", d) for d in dimensions)
+    if dimensions:
+        data += b"".join(struct.pack("<Q", d) for d in dimensions)
+    return data
+
+
+def raw(kind, values, size=None):
+    if kind == "int32":
+        return struct.pack("<%di" % len(values), *values)
+    if kind == "int8":
+        return struct.pack("<%db" % len(values), *values)
+    if kind == "float64":
+        return struct.pack("<%dd" % len(values), *values)
+    text = values.encode()
+    return text + b"\0" * (size - len(text))
+
+
+def attribute_message(name, value):
+    if value[0] == "string":
+        _, text, size = value
+        kind_data, space, data = datatype("string", size), dataspace([]), raw("string", text, size)
+    else:
+        kind, values = value
+        kind_data, space, data = datatype(kind), dataspace([]), raw(kind, values)
+    name_data = name.encode() + b"\0"
+    header = struct.pack("<BBHHH", 1, 0, len(name_data), len(kind_data), len(space))
+    return header + pad8(name_data) + pad8(kind_data) + pad8(space) + data
+
+
+class Hdf5Writer:
+    def __init__(self):
+        # Superblock (96 bytes) first, patched at the end
+        self.bytes = bytearray(96)
+
+    def allocate(self, data):
+        address = len(self.bytes)
+        self.bytes += pad8(bytes(data))
+        return address
+
+    def object_header(self, messages, reserve=0):
+        body = b""
+        for kind, data in messages:
+            data = pad8(data)
+            body += struct.pack("<HHB3x", kind, len(data), 0) + data
+        if reserve:
+            # Null message keeping room for more messages as the library does
+            body += struct.pack("<HHB3x", 0, reserve, 0) + b"\0" * reserve
+        prefix = struct.pack("<BBHII4x", 1, 0, len(messages) + (1 if reserve else 0), 1, len(body))
+        return self.allocate(prefix + body)
+
+    def write_dataset(self, dataset):
+        kind, values = dataset.kind, dataset.values
+        address = self.allocate(raw(kind, values))
+        size = len(raw(kind, values))
+        layout = struct.pack("<BBQQ", 3, 1, address, size)
+        messages = [
+            (0x0001, dataspace([len(values)])),
+            (0x0003, datatype(kind)),
+            # Fill value: late allocation, written if set, undefined
+            (0x0005, struct.pack("<BBBB", 2, 2, 2, 0)),
+            (0x0008, layout),
+        ]
+        messages += [(0x000C, attribute_message(n, v)) for n, v in dataset.attributes]
+        return self.object_header(messages)
+
+    def write_group(self, group):
+        entries = []
+        for name in sorted(group.children):
+            child = group.children[name]
+            if isinstance(child, Group):
+                header, tree, heap = self.write_group(child)
+                entries.append((name, header, 1, tree, heap))
+            else:
+                entries.append((name, self.write_dataset(child), 0, 0, 0))
+        assert len(entries) <= 2 * GROUP_LEAF_K, "One symbol table node per group"
+        # Local heap of the names, the empty name first
+        names = b"\0" * 8
+        offsets = []
+        for name, *_ in entries:
+            offsets.append(len(names))
+            names += pad8(name.encode() + b"\0")
+        heap_data = self.allocate(names)
+        heap = self.allocate(struct.pack("<4sB3xQQQ", b"HEAP", 0, len(names), UNDEFINED, heap_data))
+        node = struct.pack("<4sBBH", b"SNOD", 1, 0, len(entries))
+        for (name, header, cache, tree, child_heap), offset in zip(entries, offsets):
+            node += struct.pack("<QQII", offset, header, cache, 0)
+            node += struct.pack("<QQ", tree, child_heap) if cache == 1 else b"\0" * 16
+        node += b"\0" * 40 * (2 * GROUP_LEAF_K - len(entries))
+        symbols = self.allocate(node)
+        tree = struct.pack("<4sBBHQQ", b"TREE", 0, 0, 1, UNDEFINED, UNDEFINED)
+        tree += struct.pack("<QQQ", 0, symbols, offsets[-1] if offsets else 0)
+        # Room for the 2K children of the node
+        tree += b"\0" * (8 * (4 * GROUP_INTERNAL_K - 1) - 24)
+        tree_address = self.allocate(tree)
+        messages = [(0x0011, struct.pack("<QQ", tree_address, heap))]
+        messages += [(0x000C, attribute_message(n, v)) for n, v in group.attributes]
+        header = self.object_header(messages, reserve=24)
+        return header, tree_address, heap
+
+    def finish(self, root):
+        header, tree, heap = self.write_group(root)
+        superblock = b"\x89HDF\r\n\x1a\n"
+        superblock += struct.pack("<BBBBBBBB", 0, 0, 0, 0, 0, 8, 8, 0)
+        superblock += struct.pack("<HHI", GROUP_LEAF_K, GROUP_INTERNAL_K, 0)
+        superblock += struct.pack("<QQQQ", 0, UNDEFINED, len(self.bytes), UNDEFINED)
+        superblock += struct.pack("<QQII", 0, header, 1, 0) + struct.pack("<QQ", tree, heap)
+        assert len(superblock) == 96
+        self.bytes[:96] = superblock
+        return bytes(self.bytes)
+
+
+def med_names(groups):
+    characters = b"".join(g.encode().ljust(80, b"\0") for g in groups)
+    return Dataset("int8", list(characters), [("NBR", int32(len(groups)))])
+
+
+def med_family(number, groups):
+    return Group(
+        {"GRO": Group({"NOM": med_names(groups)}, [("NBR", int32(len(groups)))])},
+        [("NUM", int32(number))],
+    )
+
+
+def med_entities(connectivity, families, geometry):
+    n = len(connectivity)
+    # Without interlacing: the first nodes of every element, then the second ones...
+    nodes = [element[k] for k in range(len(connectivity[0])) for element in connectivity]
+    attributes = [("NBR", int32(n)), ("CGT", int32(1))]
+    return Group(
+        {
+            "NOD": Dataset("int32", nodes, attributes),
+            "FAM": Dataset("int32", families, attributes),
+            "NUM": Dataset("int32", list(range(1, n + 1)), attributes),
+        },
+        [("CGT", int32(1)), ("CGS", int32(1)), ("GEO", int32(geometry)), ("PFL", string("MED_NO_PROFILE_INTERNAL", 65))],
+    )
+
+
+def med_file():
+    n = len(NODES)
+    coordinates = [x for x, _ in NODES] + [y for _, y in NODES]
+    node_attributes = [("NBR", int32(n)), ("CGT", int32(1))]
+    nodes = Group(
+        {
+            "COO": Dataset("float64", coordinates, node_attributes),
+            "FAM": Dataset("int32", [0, 0, 1, 0, 0, 0], node_attributes),
+            "NUM": Dataset("int32", list(range(1, n + 1)), node_attributes),
+        },
+        [("CGT", int32(1)), ("CGS", int32(1)), ("PFL", string("MED_NO_PROFILE_INTERNAL", 65))],
+    )
+    elements = Group(
+        {
+            "TR3": med_entities(TRIANGLES, [-3, -3, 0, 0, 0], 203),
+            "SE2": med_entities(BOTTOM + RIGHT, [-1, -1, -2], 102),
+        },
+        [("CGT", int32(1))],
+    )
+    step = Group(
+        {"NOE": nodes, "MAI": elements},
+        [
+            ("NDT", int32(-1)),
+            ("NOR", int32(-1)),
+            ("PDT", float64(-1.0)),
+            ("CGT", int32(1)),
+            ("NXT", int32(-1)),
+            ("NXI", int32(-1)),
+            ("PVT", int32(-1)),
+            ("PVI", int32(-1)),
+        ],
+    )
+    mesh = Group(
+        {"-0000000000000000001-0000000000000000001": step},
+        [
+            ("DIM", int32(2)),
+            ("ESP", int32(2)),
+            ("REP", int32(0)),
+            ("TYP", int32(0)),
+            ("SRT", int32(0)),
+            ("NXT", int32(-1)),
+            ("NXI", int32(-1)),
+            ("NOM", string("X".ljust(16) + "Y".ljust(16), 33)),
+            ("UNI", string("".ljust(32), 33)),
+            ("DES", string("Plate with a lower left group", 201)),
+            ("UNT", string("", 17)),
+        ],
+    )
+    families = Group(
+        {
+            "FAMILLE_ZERO": Group({}, [("NUM", int32(0))]),
+            "ELEME": Group(
+                {
+                    "FAM_-1_bottom": med_family(-1, ["bottom", "walls"]),
+                    "FAM_-2_right": med_family(-2, ["walls"]),
+                    "FAM_-3_lower_left": med_family(-3, ["lower_left"]),
+                }
+            ),
+            "NOEUD": Group({"FAM_1_corner": med_family(1, ["corner"])}),
+        }
+    )
+    root = Group(
+        {
+            "INFOS_GENERALES": Group({}, [("MAJ", int32(4)), ("MIN", int32(1)), ("REL", int32(0))]),
+            "ENS_MAA": Group({"plate": mesh}),
+            "FAS": Group({"plate": families}),
+        }
+    )
+    return Hdf5Writer().finish(root)
+
+
+# --------------------------------------------------------------------------------------------------
+# NetCDF
+# --------------------------------------------------------------------------------------------------
+
+NC_BYTE, NC_CHAR, NC_SHORT, NC_INT, NC_FLOAT, NC_DOUBLE = 1, 2, 3, 4, 5, 6
+SIZES = {NC_BYTE: 1, NC_CHAR: 1, NC_SHORT: 2, NC_INT: 4, NC_FLOAT: 4, NC_DOUBLE: 8}
+FORMATS = {NC_BYTE: "b", NC_SHORT: "h", NC_INT: "i", NC_FLOAT: "f", NC_DOUBLE: "d"}
+
+
+def pad4(data):
+    return data + b"\0" * (-len(data) % 4)
+
+
+def nc_name(name):
+    return struct.pack(">I", len(name)) + pad4(name.encode())
+
+
+def nc_values(kind, values):
+    if kind == NC_CHAR:
+        return values if isinstance(values, bytes) else values.encode()
+    return struct.pack(">%d%s" % (len(values), FORMATS[kind]), *values)
+
+
+def nc_attributes(attributes):
+    if not attributes:
+        return struct.pack(">II", 0, 0)
+    data = struct.pack(">II", 12, len(attributes))
+    for name, kind, values in attributes:
+        encoded = nc_values(kind, values)
+        data += nc_name(name) + struct.pack(">II", kind, len(encoded) // SIZES[kind]) + pad4(encoded)
+    return data
+
+
+def chars(strings, length):
+    return b"".join(s.encode().ljust(length, b"\0") for s in strings)
+
+
+def exodus_file():
+    n_nodes, n_elements = len(NODES), len(TRIANGLES)
+    dimensions = [
+        ("len_string", 33),
+        ("len_line", 81),
+        ("four", 4),
+        ("len_name", 33),
+        ("time_step", 0),
+        ("num_dim", 2),
+        ("num_nodes", n_nodes),
+        ("num_elem", n_elements),
+        ("num_el_blk", 1),
+        ("num_node_sets", 1),
+        ("num_side_sets", 1),
+        ("num_qa_rec", 1),
+        ("num_side_ss1", 2),
+        ("num_node_ns1", 1),
+        ("num_el_in_blk1", n_elements),
+        ("num_nod_per_el1", 3),
+        ("num_nod_var", 1),
+    ]
+    index = {name: i for i, (name, _) in enumerate(dimensions)}
+    temperature = [[x + 0.5 * y for x, y in NODES], [2.0 * x + y for x, y in NODES]]
+    # Name, dimensions, attributes, type and values (per record for record variables)
+    variables = [
+        ("time_whole", ["time_step"], [], NC_DOUBLE, [[0.0], [0.5]]),
+        ("eb_status", ["num_el_blk"], [], NC_INT, [1]),
+        ("eb_prop1", ["num_el_blk"], [("name", NC_CHAR, "ID")], NC_INT, [10]),
+        ("ns_status", ["num_node_sets"], [], NC_INT, [1]),
+        ("ns_prop1", ["num_node_sets"], [("name", NC_CHAR, "ID")], NC_INT, [5]),
+        ("ss_status", ["num_side_sets"], [], NC_INT, [1]),
+        ("ss_prop1", ["num_side_sets"], [("name", NC_CHAR, "ID")], NC_INT, [3]),
+        ("coordx", ["num_nodes"], [], NC_DOUBLE, [x for x, _ in NODES]),
+        ("coordy", ["num_nodes"], [], NC_DOUBLE, [y for _, y in NODES]),
+        ("eb_names", ["num_el_blk", "len_name"], [], NC_CHAR, chars(["plate"], 33)),
+        ("ns_names", ["num_node_sets", "len_name"], [], NC_CHAR, chars(["corner"], 33)),
+        ("ss_names", ["num_side_sets", "len_name"], [], NC_CHAR, chars(["bottom"], 33)),
+        ("coor_names", ["num_dim", "len_name"], [], NC_CHAR, chars(["x", "y"], 33)),
+        (
+            "qa_records",
+            ["num_qa_rec", "four", "len_string"],
+            [],
+            NC_CHAR,
+            chars(["make_fixtures", "1.0", "10/14/26", "12:00:00"], 33),
+        ),
+        ("node_ns1", ["num_node_ns1"], [], NC_INT, [3]),
+        ("elem_ss1", ["num_side_ss1"], [], NC_INT, [1, 2]),
+        # Sides of TRI3 elements: 1 = (n1, n2), 2 = (n2, n3), 3 = (n3, n1)
+        ("side_ss1", ["num_side_ss1"], [], NC_INT, [1, 1]),
+        (
+            "connect1",
+            ["num_el_in_blk1", "num_nod_per_el1"],
+            [("elem_type", NC_CHAR, "TRI3")],
+            NC_INT,
+            [n for triangle in TRIANGLES for n in triangle],
+        ),
+        ("name_nod_var", ["num_nod_var", "len_name"], [], NC_CHAR, chars(["temperature"], 33)),
+        ("vals_nod_var1", ["time_step", "num_nodes"], [], NC_DOUBLE, temperature),
+    ]
+    attributes = [
+        ("api_version", NC_FLOAT, [8.11]),
+        ("version", NC_FLOAT, [8.11]),
+        ("floating_point_word_size", NC_INT, [8]),
+        ("file_size", NC_INT, [1]),
+        ("maximum_name_length", NC_INT, [32]),
+        ("int64_status", NC_INT, [0]),
+        ("title", NC_CHAR, "Plate cut in five triangles"),
+    ]
+
+    def is_record(variable):
+        return variable[1][0] == "time_step"
+
+    def encoded(variable, record=None):
+        _, _, _, kind, values = variable
+        return nc_values(kind, values[record] if record is not None else values)
+
+    vsizes = []
+    for variable in variables:
+        data = encoded(variable, 0) if is_record(variable) else encoded(variable)
+        vsizes.append(len(pad4(data)))
+
+    def header(begins):
+        data = b"CDF\x02" + struct.pack(">I", 2)
+        data += struct.pack(">II", 10, len(dimensions))
+        for name, length in dimensions:
+            data += nc_name(name) + struct.pack(">I", length)
+        data += nc_attributes(attributes)
+        data += struct.pack(">II", 11, len(variables))
+        for variable, vsize, begin in zip(variables, vsizes, begins):
+            name, dims, variable_attributes, kind, _ = variable
+            data += nc_name(name) + struct.pack(">I", len(dims))
+            data += b"".join(struct.pack(">I", index[d]) for d in dims)
+            data += nc_attributes(variable_attributes)
+            data += struct.pack(">IIQ", kind, vsize, begin)
+        return data
+
+    # Fixed size variables after the header, then the records
+    size = len(header([0] * len(variables)))
+    begins, position = [], size
+    for variable, vsize in zip(variables, vsizes):
+        if not is_record(variable):
+            begins.append(position)
+            position += vsize
+        else:
+            begins.append(None)
+    record_start, offset = position, 0
+    for k, (variable, vsize) in enumerate(zip(variables, vsizes)):
+        if is_record(variable):
+            begins[k] = record_start + offset
+            offset += vsize
+    data = bytearray(header(begins))
+    for variable in variables:
+        if not is_record(variable):
+            data += pad4(encoded(variable))
+    for record in range(2):
+        for variable in variables:
+            if is_record(variable):
+                data += pad4(encoded(variable, record))
+    return bytes(data)
+
+
+if __name__ == "__main__":
+    with open(os.path.join(HERE, "plate.med"), "wb") as f:
+        f.write(med_file())
+    with open(os.path.join(HERE, "plate.exo"), "wb") as f:
+        f.write(exodus_file())